@@ -23,6 +23,7 @@ use std::io::BufReader;
 use std::sync::Arc;
 use std::time::Instant;
 
+use ballista::prelude::verify_table_statement;
 use datafusion::common::Result;
 use datafusion::prelude::SessionContext;
 use rustyline::error::ReadlineError;
@@ -172,6 +173,7 @@ async fn exec_and_print(
     sql: String,
 ) -> Result<()> {
     let now = Instant::now();
+    let sql = verify_table_statement(&sql).unwrap_or(sql);
     let df = ctx.sql(&sql).await?;
     let schema = Arc::new(df.schema().as_arrow().clone());
     let results = df.collect().await?;
@@ -18,16 +18,33 @@
 use ballista_core::extension::SessionConfigHelperExt;
 pub use ballista_core::extension::{SessionConfigExt, SessionStateExt};
 use ballista_core::{
-    serde::protobuf::{scheduler_grpc_client::SchedulerGrpcClient, CreateSessionParams},
-    utils::create_grpc_client_connection,
+    cluster_catalog::ClusterTable,
+    error::leader_url_from_status,
+    execution_plans::DistributedQueryExec,
+    security::GrpcSecurityConfig,
+    serde::protobuf::{
+        scheduler_grpc_client::SchedulerGrpcClient, CancelSlotReservationParams,
+        CreateSessionParams, GetJobPlanParams, JobPlanStage, ListClusterTablesParams,
+        ListSlotReservationsParams, ReserveSlotsParams, SampleStageParams,
+        SlotReservation, SlotReservationUsage,
+    },
+    utils::{create_grpc_client_connection, GrpcChannel},
 };
 use datafusion::{
+    arrow::{ipc::reader::StreamReader, record_batch::RecordBatch},
+    common::TableReference,
+    datasource::ViewTable,
     error::DataFusionError,
     execution::SessionState,
-    prelude::{SessionConfig, SessionContext},
+    prelude::{DataFrame, SessionConfig, SessionContext},
 };
+use datafusion_proto::protobuf::LogicalPlanNode;
+use std::sync::Arc;
+use std::time::Duration;
 use url::Url;
 
+use crate::job::JobHandle;
+
 const DEFAULT_SCHEDULER_PORT: u16 = 50050;
 
 /// Module provides [SessionContextExt] which adds `standalone*` and `remote*`
@@ -175,6 +192,323 @@ impl SessionContextExt for SessionContext {
     }
 }
 
+/// Module provides [DataFrameExt] which allows mixing DataFrame and SQL
+/// workflows against a Ballista cluster.
+///
+///```no_run
+/// use ballista::prelude::{DataFrameExt, SessionContextExt};
+/// use datafusion::prelude::{col, lit, SessionContext};
+///
+/// # #[tokio::main]
+/// # async fn main() -> datafusion::error::Result<()> {
+/// let ctx: SessionContext = SessionContext::remote("df://localhost:50050").await?;
+/// ctx.read_parquet("data.parquet", Default::default())
+///     .await?
+///     .filter(col("id").gt(lit(4)))?
+///     .into_temp_view("filtered")?;
+///
+/// let df = ctx.sql("select count(*) from filtered").await?;
+/// # Ok(())
+/// # }
+///```
+pub trait DataFrameExt {
+    /// Registers the logical plan of this DataFrame as a view named `name` in the
+    /// session it was created from, replacing any existing table with the same name.
+    ///
+    /// The view is inlined into the plans of subsequent queries referencing it, so it
+    /// is executed on the cluster together with them.
+    ///
+    /// Note that this is different from [DataFrame::into_view], which returns the
+    /// DataFrame as a table provider without registering it.
+    fn into_temp_view(self, name: &str) -> datafusion::error::Result<()>;
+}
+
+impl DataFrameExt for DataFrame {
+    fn into_temp_view(self, name: &str) -> datafusion::error::Result<()> {
+        let (state, plan) = self.into_parts();
+        let table_ref = TableReference::from(name);
+        let view = ViewTable::try_new(plan, None)?;
+
+        let catalog_options = &state.config_options().catalog;
+        let table_ref = table_ref.resolve(
+            &catalog_options.default_catalog,
+            &catalog_options.default_schema,
+        );
+        let schema = state
+            .catalog_list()
+            .catalog(&table_ref.catalog)
+            .and_then(|catalog| catalog.schema(&table_ref.schema))
+            .ok_or_else(|| {
+                DataFusionError::Plan(format!(
+                    "failed to resolve schema of view {table_ref}"
+                ))
+            })?;
+        schema.deregister_table(&table_ref.table)?;
+        schema.register_table(table_ref.table.to_string(), Arc::new(view))?;
+
+        Ok(())
+    }
+}
+
+/// Client of a Ballista scheduler, to submit jobs and inspect the jobs it ran
+#[derive(Clone)]
+pub struct SchedulerClient {
+    scheduler: SchedulerGrpcClient<GrpcChannel>,
+    security: Arc<GrpcSecurityConfig>,
+}
+
+impl SchedulerClient {
+    /// Connects to the scheduler at `url`, with the gRPC settings of `config`
+    pub async fn connect(
+        url: &str,
+        config: &SessionConfig,
+    ) -> datafusion::error::Result<Self> {
+        let scheduler_url = Extension::parse_url(url)?;
+        let security = config.ballista_grpc_security();
+        let connection = create_grpc_client_connection(scheduler_url, &security)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+
+        let limit = config.ballista_grpc_client_max_message_size();
+        let scheduler = SchedulerGrpcClient::new(connection)
+            .max_encoding_message_size(limit)
+            .max_decoding_message_size(limit);
+
+        Ok(Self {
+            scheduler,
+            security,
+        })
+    }
+
+    /// Submits the query of a data frame of a remote session of the scheduler as a
+    /// job, without waiting for it to complete. The job runs until it completes or is
+    /// cancelled, even once the returned handle is dropped:
+    ///
+    ///```no_run
+    /// # use ballista::prelude::{SchedulerClient, SessionContextExt};
+    /// # use datafusion::prelude::{SessionConfig, SessionContext};
+    /// # #[tokio::main]
+    /// # async fn main() -> datafusion::error::Result<()> {
+    /// let ctx = SessionContext::remote("df://localhost:50050").await?;
+    /// let mut scheduler =
+    ///     SchedulerClient::connect("df://localhost:50050", &SessionConfig::new()).await?;
+    /// let job = scheduler.submit_job(ctx.sql("SELECT 1").await?).await?;
+    /// println!("Submitted job {}", job.job_id());
+    ///
+    /// // later, possibly in another process
+    /// let mut job = scheduler.job("job_id");
+    /// println!("{:?}", job.progress().await?);
+    /// let results = job.fetch_results().await?;
+    /// # Ok(())
+    /// # }
+    ///```
+    pub async fn submit_job(
+        &mut self,
+        data_frame: DataFrame,
+    ) -> datafusion::error::Result<JobHandle> {
+        let (state, plan) = data_frame.into_parts();
+        let plan = state.create_physical_plan(&plan).await?;
+        let Some(query) = plan
+            .as_any()
+            .downcast_ref::<DistributedQueryExec<LogicalPlanNode>>()
+        else {
+            return Err(DataFusionError::Plan(format!(
+                "The query can't be submitted as a job, as it runs {} rather than on the cluster",
+                plan.name()
+            )));
+        };
+        let job_id = query.submit(state.task_ctx()).await?;
+
+        Ok(self.job(job_id))
+    }
+
+    /// Returns the handle of a job of the scheduler, given its ID
+    pub fn job(&self, job_id: impl Into<String>) -> JobHandle {
+        JobHandle::new(job_id.into(), self.scheduler.clone(), self.security.clone())
+    }
+
+    /// Returns the distributed plan a completed job ran, i.e. its stages ordered by
+    /// stage ID along with the stages they read from.
+    ///
+    /// The plan of each stage is the one its last attempt ran, with the runtime
+    /// predicates and the shuffle reads resolved by the scheduler while the job ran. It
+    /// is an encoded `PhysicalPlanNode`, which the physical codec of a [BallistaCodec]
+    /// decodes, e.g. to compare the plans of a query across releases.
+    ///
+    /// [BallistaCodec]: ballista_core::serde::BallistaCodec
+    pub async fn job_plan(
+        &mut self,
+        job_id: &str,
+    ) -> datafusion::error::Result<Vec<JobPlanStage>> {
+        let result = self
+            .scheduler
+            .get_job_plan(GetJobPlanParams {
+                job_id: job_id.to_owned(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        Ok(result.stages)
+    }
+
+    /// Executes a stage of a job in isolation and returns at most `limit` rows of its
+    /// output, 100 if `limit` is 0, to find which stage of a long pipeline produces
+    /// unexpected values. Only the task of `partition` runs if set, otherwise the tasks
+    /// of the stage run in order until enough rows are read.
+    ///
+    /// The scheduler executes the plan of the stage without its shuffle write, reading
+    /// its inputs from the executors holding the outputs of the stages it depends on,
+    /// so the stage can be sampled once these completed and until the job's shuffle
+    /// data is cleaned up. Only the stages which scan, filter and project their inputs
+    /// can be sampled, not those aggregating, joining or sorting them. The stages are
+    /// listed by [Self::job_plan].
+    pub async fn sample_stage(
+        &mut self,
+        job_id: &str,
+        stage_id: u32,
+        partition: Option<u32>,
+        limit: u64,
+    ) -> datafusion::error::Result<Vec<RecordBatch>> {
+        let result = self
+            .scheduler
+            .sample_stage(SampleStageParams {
+                job_id: job_id.to_owned(),
+                stage_id,
+                partition_id: partition,
+                limit,
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        if result.data.is_empty() {
+            return Ok(vec![]);
+        }
+        let reader = StreamReader::try_new(result.data.as_slice(), None)?;
+        Ok(reader.collect::<Result<_, _>>()?)
+    }
+
+    /// Returns the tables of the catalog of the cluster, which the scheduler shares
+    /// between the sessions when `cluster-catalog` is enabled.
+    ///
+    /// Registering them in a [SessionContext] lets its queries reference the tables by
+    /// name, the scheduler resolving them when it plans the jobs:
+    ///
+    ///```no_run
+    /// # use std::sync::Arc;
+    /// # use ballista::prelude::{SchedulerClient, SessionContextExt};
+    /// # use datafusion::prelude::{SessionConfig, SessionContext};
+    /// # #[tokio::main]
+    /// # async fn main() -> datafusion::error::Result<()> {
+    /// let ctx = SessionContext::remote("df://localhost:50050").await?;
+    /// let mut scheduler =
+    ///     SchedulerClient::connect("df://localhost:50050", &SessionConfig::new()).await?;
+    /// for table in scheduler.cluster_tables().await? {
+    ///     ctx.register_table(table.name().to_owned(), Arc::new(table))?;
+    /// }
+    /// # Ok(())
+    /// # }
+    ///```
+    pub async fn cluster_tables(
+        &mut self,
+    ) -> datafusion::error::Result<Vec<ClusterTable>> {
+        let result = self
+            .scheduler
+            .list_cluster_tables(ListClusterTablesParams {})
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        result
+            .tables
+            .into_iter()
+            .map(ClusterTable::try_from_proto)
+            .collect()
+    }
+
+    /// Reserves `slots` executor slots for `ttl`, for the jobs of the sessions setting
+    /// `ballista.job.slot_reservation` to `reservation_id`, replacing the reservation
+    /// with the same ID. The reserved slots are withheld from the other jobs while the
+    /// jobs of the reservation don't use them, so that time-critical jobs start without
+    /// waiting for the tasks of the others to finish:
+    ///
+    ///```no_run
+    /// # use std::time::Duration;
+    /// # use ballista::prelude::{SchedulerClient, SessionConfigExt};
+    /// # use datafusion::prelude::SessionConfig;
+    /// # #[tokio::main]
+    /// # async fn main() -> datafusion::error::Result<()> {
+    /// let mut scheduler =
+    ///     SchedulerClient::connect("df://localhost:50050", &SessionConfig::new()).await?;
+    /// scheduler
+    ///     .reserve_slots("daily-report", 16, Duration::from_secs(3600))
+    ///     .await?;
+    ///
+    /// // the jobs of the sessions of this config run in the reserved slots
+    /// let session_config = SessionConfig::new_with_ballista()
+    ///     .with_ballista_job_slot_reservation("daily-report");
+    /// # Ok(())
+    /// # }
+    ///```
+    pub async fn reserve_slots(
+        &mut self,
+        reservation_id: &str,
+        slots: u32,
+        ttl: Duration,
+    ) -> datafusion::error::Result<SlotReservation> {
+        let result = self
+            .scheduler
+            .reserve_slots(ReserveSlotsParams {
+                reservation_id: reservation_id.to_owned(),
+                slots,
+                ttl_seconds: ttl.as_secs().max(1),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        result.reservation.ok_or_else(|| {
+            DataFusionError::Internal(format!(
+                "The scheduler returned no reservation for {reservation_id}"
+            ))
+        })
+    }
+
+    /// Cancels a reservation of executor slots before it expires, returning whether it
+    /// existed. The running jobs of the reservation carry on with the free slots
+    pub async fn cancel_slot_reservation(
+        &mut self,
+        reservation_id: &str,
+    ) -> datafusion::error::Result<bool> {
+        let result = self
+            .scheduler
+            .cancel_slot_reservation(CancelSlotReservationParams {
+                reservation_id: reservation_id.to_owned(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        Ok(result.cancelled)
+    }
+
+    /// Returns the reservations of executor slots not expired yet, with the running
+    /// jobs of each and the reserved slots their tasks take
+    pub async fn slot_reservations(
+        &mut self,
+    ) -> datafusion::error::Result<Vec<SlotReservationUsage>> {
+        let result = self
+            .scheduler
+            .list_slot_reservations(ListSlotReservationsParams {})
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        Ok(result.reservations)
+    }
+}
+
 struct Extension {}
 
 impl Extension {
@@ -215,7 +549,13 @@ impl Extension {
         let scheduler_url = format!("http://localhost:{}", addr.port());
 
         let mut scheduler = loop {
-            match SchedulerGrpcClient::connect(scheduler_url.clone()).await {
+            match create_grpc_client_connection(
+                scheduler_url.clone(),
+                &config.ballista_grpc_security(),
+            )
+            .await
+            .map(SchedulerGrpcClient::new)
+            {
                 Err(_) => {
                     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                     log::info!("Attempting to connect to in-proc scheduler...");
@@ -263,24 +603,40 @@ impl Extension {
         config: &SessionConfig,
         scheduler_url: String,
     ) -> datafusion::error::Result<String> {
-        let connection = create_grpc_client_connection(scheduler_url.clone())
-            .await
-            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let params = CreateSessionParams {
+            settings: config.to_key_value_pairs(),
+        };
+        let mut scheduler = Self::connect_scheduler(config, scheduler_url).await?;
+        let session = match scheduler.create_session(params.clone()).await {
+            Ok(session) => session,
+            // read replicas direct the sessions to the leader scheduler
+            Err(status) => match leader_url_from_status(&status) {
+                Some(leader_url) => Self::connect_scheduler(config, leader_url)
+                    .await?
+                    .create_session(params)
+                    .await
+                    .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?,
+                None => return Err(DataFusionError::Execution(format!("{status:?}"))),
+            },
+        };
 
-        let limit = config.ballista_grpc_client_max_message_size();
-        let mut scheduler = SchedulerGrpcClient::new(connection)
-            .max_encoding_message_size(limit)
-            .max_decoding_message_size(limit);
+        Ok(session.into_inner().session_id)
+    }
 
-        let remote_session_id = scheduler
-            .create_session(CreateSessionParams {
-                settings: config.to_key_value_pairs(),
-            })
-            .await
-            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
-            .into_inner()
-            .session_id;
+    async fn connect_scheduler(
+        config: &SessionConfig,
+        scheduler_url: String,
+    ) -> datafusion::error::Result<SchedulerGrpcClient<GrpcChannel>> {
+        let connection = create_grpc_client_connection(
+            scheduler_url,
+            &config.ballista_grpc_security(),
+        )
+        .await
+        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
 
-        Ok(remote_session_id)
+        let limit = config.ballista_grpc_client_max_message_size();
+        Ok(SchedulerGrpcClient::new(connection)
+            .max_encoding_message_size(limit)
+            .max_decoding_message_size(limit))
     }
 }
@@ -0,0 +1,183 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Jobs submitted to a scheduler without waiting for their results, which are tracked
+//! by their ID, possibly from another process than the one which submitted them.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ballista_core::execution_plans::fetch_job_output;
+use ballista_core::security::GrpcSecurityConfig;
+use ballista_core::serde::protobuf::{
+    job_status, scheduler_grpc_client::SchedulerGrpcClient, CancelJobParams,
+    GetJobMetricsParams, GetJobStatusParams, JobStatus, ReleaseJobResultsParams,
+};
+use ballista_core::utils::GrpcChannel;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use futures::Stream;
+
+/// Interval at which [JobHandle::wait] polls the status of the job
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Handle of a job submitted with [SchedulerClient::submit_job], or of any job of the
+/// scheduler, given its ID with [SchedulerClient::job]
+///
+/// [SchedulerClient::submit_job]: crate::extension::SchedulerClient::submit_job
+/// [SchedulerClient::job]: crate::extension::SchedulerClient::job
+#[derive(Clone)]
+pub struct JobHandle {
+    job_id: String,
+    scheduler: SchedulerGrpcClient<GrpcChannel>,
+    security: Arc<GrpcSecurityConfig>,
+}
+
+/// Progress of a job, in stages and tasks completed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JobProgress {
+    pub completed_stages: usize,
+    pub stages: usize,
+    pub completed_tasks: usize,
+    /// Tasks of the stages which are resolved, i.e. whose number of tasks is known.
+    /// The number grows as the stages reading the outputs of other stages are resolved
+    pub tasks: usize,
+}
+
+impl JobHandle {
+    pub(crate) fn new(
+        job_id: String,
+        scheduler: SchedulerGrpcClient<GrpcChannel>,
+        security: Arc<GrpcSecurityConfig>,
+    ) -> Self {
+        Self {
+            job_id,
+            scheduler,
+            security,
+        }
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    /// Returns the current status of the job, None if the scheduler doesn't know it,
+    /// e.g. while the job is being submitted or once it was purged
+    pub async fn status(&mut self) -> Result<Option<JobStatus>> {
+        let result = self
+            .scheduler
+            .get_job_status(GetJobStatusParams {
+                job_id: self.job_id.clone(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        Ok(result.status)
+    }
+
+    /// Returns the stages and tasks of the job completed so far
+    pub async fn progress(&mut self) -> Result<JobProgress> {
+        let result = self
+            .scheduler
+            .get_job_metrics(GetJobMetricsParams {
+                job_id: self.job_id.clone(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        let mut progress = JobProgress {
+            stages: result.stages.len(),
+            ..Default::default()
+        };
+        for stage in &result.stages {
+            if stage.status == "Successful" {
+                progress.completed_stages += 1;
+            }
+            progress.completed_tasks += stage.completed_tasks as usize;
+            progress.tasks += stage.partitions as usize;
+        }
+        Ok(progress)
+    }
+
+    /// Cancels the job, unless it already completed
+    pub async fn cancel(&mut self) -> Result<()> {
+        self.scheduler
+            .cancel_job(CancelJobParams {
+                job_id: self.job_id.clone(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+
+        Ok(())
+    }
+
+    /// Waits for the job to complete, returning its final status, whether it
+    /// succeeded or failed
+    pub async fn wait(&mut self) -> Result<JobStatus> {
+        loop {
+            if let Some(status) = self.status().await? {
+                if matches!(
+                    status.status,
+                    Some(
+                        job_status::Status::Successful(_) | job_status::Status::Failed(_)
+                    )
+                ) {
+                    return Ok(status);
+                }
+            }
+            tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Waits for the job to complete, then streams its results from the executors
+    /// holding them. The results can be fetched until they are released with
+    /// [Self::release_results], or until their time to live expires, see
+    /// `ballista.job.result_ttl_seconds`
+    pub async fn fetch_results(
+        &mut self,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<RecordBatch>> + Send>>> {
+        let status = self.wait().await?;
+        match &status.status {
+            Some(job_status::Status::Successful(successful)) => {
+                Ok(fetch_job_output(successful, self.security.clone()))
+            }
+            Some(job_status::Status::Failed(failed)) => Err(DataFusionError::Execution(
+                format!("Job {} failed: {}", self.job_id, failed.error),
+            )),
+            _ => unreachable!("the job completed"),
+        }
+    }
+
+    /// Acknowledges the results of the successful job were fetched, for the executors
+    /// to delete them without waiting for their time to live to expire. Returns the
+    /// size in bytes of the released results, None if they were already deleted
+    pub async fn release_results(&mut self) -> Result<Option<u64>> {
+        let result = self
+            .scheduler
+            .release_job_results(ReleaseJobResultsParams {
+                job_id: self.job_id.clone(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+
+        Ok(result.released.then_some(result.released_bytes))
+    }
+}
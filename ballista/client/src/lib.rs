@@ -18,4 +18,7 @@
 #![doc = include_str!("../README.md")]
 
 pub mod extension;
+pub mod job;
 pub mod prelude;
+#[cfg(feature = "standalone")]
+pub mod testing;
@@ -22,5 +22,11 @@
 //     error::{BallistaError, Result},
 // };
 
-pub use crate::extension::{SessionConfigExt, SessionContextExt};
+pub use crate::extension::{
+    DataFrameExt, SchedulerClient, SessionConfigExt, SessionContextExt,
+};
+pub use crate::job::{JobHandle, JobProgress};
+pub use ballista_core::cluster_catalog::ClusterTable;
+pub use ballista_core::federation::{FlightTableProvider, RemoteTable};
+pub use ballista_core::verify_table::{verify_table_statement, VerifyTable};
 //pub use futures::StreamExt;
@@ -0,0 +1,225 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! In-process Ballista clusters for integration tests.
+//!
+//! [TestCluster] starts a scheduler and any number of executors inside the current
+//! tokio runtime, listening on ephemeral ports of `localhost`, so distributed
+//! behavior such as shuffles between executors can be tested with `cargo test`
+//! without Docker.
+//!
+//!```no_run
+//! use ballista::testing::TestCluster;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> datafusion::error::Result<()> {
+//! let cluster = TestCluster::new(2).await?;
+//! let ctx = cluster.context().await?;
+//! ctx.sql("SELECT 1").await?.show().await?;
+//! # Ok(())
+//! # }
+//!```
+
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use ballista_core::config::BallistaConfig;
+use ballista_core::extension::SessionConfigExt;
+use ballista_core::registry::BallistaFunctionRegistry;
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::BallistaCodec;
+use ballista_core::utils::{
+    create_grpc_client_connection, default_config_producer, default_session_builder,
+    GrpcChannel,
+};
+use ballista_core::{ConfigProducer, RuntimeProducer};
+use ballista_scheduler::SessionBuilder;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
+use datafusion::execution::{SessionState, SessionStateBuilder};
+use datafusion::prelude::{SessionConfig, SessionContext};
+use tempfile::TempDir;
+
+use crate::extension::SessionContextExt;
+
+/// A scheduler and its executors running in-process.
+///
+/// Each executor writes its shuffle files to its own temporary work directory,
+/// which is removed when the cluster is dropped. The servers themselves run on
+/// the tokio runtime which created the cluster and stop with it.
+pub struct TestCluster {
+    scheduler_addr: SocketAddr,
+    work_dirs: Vec<TempDir>,
+}
+
+impl TestCluster {
+    /// Starts a cluster of `num_executors` executors with the default configuration
+    pub async fn new(num_executors: usize) -> Result<Self> {
+        let config_producer: ConfigProducer = Arc::new(default_config_producer);
+        Self::new_with_builders(
+            num_executors,
+            config_producer,
+            Arc::new(default_session_builder),
+            None,
+        )
+        .await
+    }
+
+    /// Starts a cluster of `num_executors` executors whose sessions are based on
+    /// `session_state`, including its configuration, runtime and registered functions
+    pub async fn new_with_state(
+        num_executors: usize,
+        session_state: &SessionState,
+    ) -> Result<Self> {
+        let session_config = session_state
+            .config()
+            .clone()
+            .with_option_extension(BallistaConfig::default());
+        let config_producer: ConfigProducer = Arc::new(move || session_config.clone());
+
+        let state = session_state.clone();
+        let session_builder: SessionBuilder = Arc::new(move |c: SessionConfig| {
+            Ok(SessionStateBuilder::new_from_existing(state.clone())
+                .with_config(c)
+                .build())
+        });
+
+        Self::new_with_builders(
+            num_executors,
+            config_producer,
+            session_builder,
+            Some(session_state),
+        )
+        .await
+    }
+
+    async fn new_with_builders(
+        num_executors: usize,
+        config_producer: ConfigProducer,
+        session_builder: SessionBuilder,
+        session_state: Option<&SessionState>,
+    ) -> Result<Self> {
+        let config = config_producer();
+        let codec = BallistaCodec::new(
+            config.ballista_logical_extension_codec(),
+            config.ballista_physical_extension_codec(),
+        );
+
+        let scheduler_addr =
+            ballista_scheduler::standalone::new_standalone_scheduler_with_builder(
+                session_builder,
+                config_producer.clone(),
+                codec.clone(),
+            )
+            .await
+            .map_err(|e| DataFusionError::Configuration(e.to_string()))?;
+        let scheduler =
+            connect_to_scheduler(format!("http://localhost:{}", scheduler_addr.port()))
+                .await?;
+
+        let mut work_dirs = Vec::with_capacity(num_executors);
+        for _ in 0..num_executors {
+            let work_dir = TempDir::new()?;
+            let (runtime_producer, function_registry): (RuntimeProducer, _) =
+                match session_state {
+                    Some(session_state) => {
+                        let runtime = session_state.runtime_env().clone();
+                        (Arc::new(move |_| Ok(runtime.clone())), session_state.into())
+                    }
+                    None => (
+                        spill_to(work_dir.path()),
+                        BallistaFunctionRegistry::default(),
+                    ),
+                };
+
+            ballista_executor::new_standalone_executor_with_work_dir(
+                scheduler.clone(),
+                config.ballista_standalone_parallelism(),
+                config_producer.clone(),
+                runtime_producer,
+                codec.clone(),
+                function_registry,
+                &work_dir.path().to_string_lossy(),
+            )
+            .await
+            .map_err(|e| DataFusionError::Configuration(e.to_string()))?;
+            work_dirs.push(work_dir);
+        }
+
+        Ok(Self {
+            scheduler_addr,
+            work_dirs,
+        })
+    }
+
+    /// Url of the scheduler, to be passed to [SessionContextExt::remote]
+    pub fn scheduler_url(&self) -> String {
+        format!("df://localhost:{}", self.scheduler_addr.port())
+    }
+
+    /// Work directories of the executors, where they write their shuffle files
+    pub fn work_dirs(&self) -> Vec<&Path> {
+        self.work_dirs.iter().map(|dir| dir.path()).collect()
+    }
+
+    /// Creates a context running its queries on this cluster
+    pub async fn context(&self) -> Result<SessionContext> {
+        SessionContext::remote(&self.scheduler_url()).await
+    }
+
+    /// Creates a context running its queries on this cluster, based on `state`
+    pub async fn context_with_state(
+        &self,
+        state: SessionState,
+    ) -> Result<SessionContext> {
+        SessionContext::remote_with_state(&self.scheduler_url(), state).await
+    }
+}
+
+/// Runtime of an executor which spills to its work directory
+fn spill_to(work_dir: &Path) -> RuntimeProducer {
+    let work_dir = work_dir.to_path_buf();
+    Arc::new(move |_: &SessionConfig| {
+        let config = RuntimeConfig::new().with_temp_file_path(work_dir.clone());
+        Ok(Arc::new(RuntimeEnv::new(config)?))
+    })
+}
+
+async fn connect_to_scheduler(
+    scheduler_url: String,
+) -> Result<SchedulerGrpcClient<GrpcChannel>> {
+    let mut retry = 50;
+    loop {
+        match create_grpc_client_connection(scheduler_url.clone(), &Default::default())
+            .await
+            .map(SchedulerGrpcClient::new)
+        {
+            Err(_) if retry > 0 => {
+                retry -= 1;
+                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                log::debug!("Re-attempting to connect to test scheduler...");
+            }
+            Err(e) => {
+                return Err(DataFusionError::Configuration(format!(
+                    "failed to connect to test scheduler: {e}"
+                )))
+            }
+            Ok(scheduler) => return Ok(scheduler),
+        }
+    }
+}
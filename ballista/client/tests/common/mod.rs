@@ -23,6 +23,7 @@ use ballista::prelude::{SessionConfigExt, SessionContextExt};
 use ballista_core::serde::{
     protobuf::scheduler_grpc_client::SchedulerGrpcClient, BallistaCodec,
 };
+use ballista_core::utils::{create_grpc_client_connection, GrpcChannel};
 use ballista_core::{ConfigProducer, RuntimeProducer};
 use ballista_scheduler::SessionBuilder;
 use datafusion::execution::SessionState;
@@ -200,12 +201,13 @@ pub async fn setup_test_cluster_with_builders(
     (host, addr.port())
 }
 
-async fn connect_to_scheduler(
-    scheduler_url: String,
-) -> SchedulerGrpcClient<tonic::transport::Channel> {
+async fn connect_to_scheduler(scheduler_url: String) -> SchedulerGrpcClient<GrpcChannel> {
     let mut retry = 50;
     loop {
-        match SchedulerGrpcClient::connect(scheduler_url.clone()).await {
+        match create_grpc_client_connection(scheduler_url.clone(), &Default::default())
+            .await
+            .map(SchedulerGrpcClient::new)
+        {
             Err(_) if retry > 0 => {
                 retry -= 1;
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
@@ -20,10 +20,12 @@ mod common;
 mod supported {
 
     use crate::common::{remote_context, standalone_context};
+    use ballista::prelude::DataFrameExt;
     use ballista_core::config::BallistaConfig;
     use datafusion::prelude::*;
     use datafusion::{assert_batches_eq, prelude::SessionContext};
     use rstest::*;
+    use std::future::Future;
 
     #[rstest::fixture]
     fn test_data() -> String {
@@ -125,7 +127,7 @@ mod supported {
             "| name                                  | value    |",
             "+---------------------------------------+----------+",
             "| ballista.grpc_client_max_message_size | 16777216 |",
-            "| ballista.job.name                     |          |",
+            "| ballista.job.best_effort              | false    |",
             "+---------------------------------------+----------+",
         ];
 
@@ -363,4 +365,91 @@ mod supported {
 
         Ok(())
     }
+
+    // the plan of the view is nested in that of the query, whose serialization recurses
+    // deeper than the stacks of the test threads allow in debug builds, so the test runs
+    // on threads with the stack size of the scheduler
+    #[rstest]
+    #[case::standalone(standalone_context())]
+    #[case::remote(remote_context())]
+    fn should_execute_sql_on_dataframe_view(
+        #[case] ctx: impl Future<Output = SessionContext> + Send + 'static,
+        test_data: String,
+    ) -> datafusion::error::Result<()> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_stack_size(32 * 1024 * 1024)
+            .build()?;
+        runtime
+            .block_on(runtime.spawn(execute_sql_on_dataframe_view(ctx, test_data)))
+            .expect("test panicked")
+    }
+
+    async fn execute_sql_on_dataframe_view(
+        ctx: impl Future<Output = SessionContext>,
+        test_data: String,
+    ) -> datafusion::error::Result<()> {
+        let ctx = ctx.await;
+        ctx.read_parquet(
+            &format!("{test_data}/alltypes_plain.parquet"),
+            Default::default(),
+        )
+        .await?
+        .filter(col("id").gt(lit(4)))?
+        .select_columns(&["id", "string_col"])?
+        .into_temp_view("filtered")?;
+
+        let result = ctx
+            .sql("select string_col, count(*) as cnt from filtered group by string_col order by string_col")
+            .await?
+            .collect()
+            .await?;
+        let expected = [
+            "+------------+-----+",
+            "| string_col | cnt |",
+            "+------------+-----+",
+            "| 30         | 1   |",
+            "| 31         | 2   |",
+            "+------------+-----+",
+        ];
+
+        assert_batches_eq!(expected, &result);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::standalone(standalone_context())]
+    #[case::remote(remote_context())]
+    #[tokio::test]
+    async fn should_execute_sql_random(
+        #[future(awt)]
+        #[case]
+        ctx: SessionContext,
+        test_data: String,
+    ) -> datafusion::error::Result<()> {
+        ctx.register_parquet(
+            "test",
+            &format!("{test_data}/alltypes_plain.parquet"),
+            Default::default(),
+        )
+        .await?;
+
+        let result = ctx
+            .sql("select count(distinct r) as cnt, min(r) >= 0 and max(r) < 1 as in_range from (select random() as r from test)")
+            .await?
+            .collect()
+            .await?;
+        let expected = [
+            "+-----+----------+",
+            "| cnt | in_range |",
+            "+-----+----------+",
+            "| 8   | true     |",
+            "+-----+----------+",
+        ];
+
+        assert_batches_eq!(expected, &result);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,463 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+mod common;
+
+#[cfg(test)]
+#[cfg(feature = "standalone")]
+mod test_cluster {
+    use ballista::prelude::{SchedulerClient, SessionConfigExt};
+    use ballista::testing::TestCluster;
+    use ballista_core::config::ExplainFormat;
+    use ballista_core::serde::protobuf::execute_query_params::{
+        OptionalSessionId, Query,
+    };
+    use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+    use ballista_core::serde::protobuf::{
+        execute_query_result, job_status, CreateSessionParams, ExecuteQueryParams,
+        GetJobStatusParams,
+    };
+    use ballista_core::serde::BallistaCodec;
+    use ballista_core::utils::{create_grpc_client_connection, GrpcChannel};
+    use datafusion::arrow::array::StringArray;
+    use datafusion::error::Result;
+    use datafusion::execution::SessionStateBuilder;
+    use datafusion::prelude::SessionConfig;
+    use datafusion::{assert_batches_eq, assert_batches_sorted_eq};
+    use datafusion_proto::physical_plan::AsExecutionPlan;
+    use datafusion_proto::protobuf::PhysicalPlanNode;
+    use futures::TryStreamExt;
+
+    #[tokio::test]
+    async fn should_shuffle_between_executors() -> Result<()> {
+        let test_data = crate::common::example_test_data();
+        let cluster = TestCluster::new(2).await?;
+        let ctx = cluster.context().await?;
+        ctx.register_parquet(
+            "test",
+            &format!("{test_data}/alltypes_plain.parquet"),
+            Default::default(),
+        )
+        .await?;
+
+        let result = ctx
+            .sql(
+                "select bool_col, count(*) from test group by bool_col order by bool_col",
+            )
+            .await?
+            .collect()
+            .await?;
+        let expected = [
+            "+----------+----------+",
+            "| bool_col | count(*) |",
+            "+----------+----------+",
+            "| false    | 4        |",
+            "| true     | 4        |",
+            "+----------+----------+",
+        ];
+        assert_batches_eq!(expected, &result);
+
+        let work_dirs = cluster.work_dirs();
+        assert_eq!(work_dirs.len(), 2);
+        assert!(work_dirs
+            .iter()
+            .any(|dir| std::fs::read_dir(dir).unwrap().next().is_some()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn should_explain_analyze_stages_of_job() -> Result<()> {
+        let test_data = crate::common::example_test_data();
+        // Several partitions, so that the aggregation is split across two stages
+        let state = SessionStateBuilder::new()
+            .with_default_features()
+            .with_config(SessionConfig::new_with_ballista().with_target_partitions(2))
+            .build();
+        let cluster = TestCluster::new_with_state(2, &state).await?;
+        let ctx = cluster.context_with_state(state).await?;
+        ctx.register_parquet(
+            "test",
+            &format!("{test_data}/alltypes_plain.parquet"),
+            Default::default(),
+        )
+        .await?;
+
+        let result = ctx
+            .sql("explain analyze select bool_col, count(*) from test group by bool_col")
+            .await?
+            .collect()
+            .await?;
+        assert_eq!(result.len(), 1);
+        let column = |index| {
+            result[0]
+                .column(index)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .iter()
+                .map(|value| value.unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let (plan_types, plans) = (column(0), column(1));
+        assert_eq!(plan_types, vec!["Stage 1", "Stage 2", "Job"]);
+
+        // The final stage reads the shuffle files of the first one, whose tasks
+        // reported their metrics
+        assert!(plans[0].starts_with("input_stages=[]"));
+        assert!(plans[0].contains("ParquetExec"));
+        assert!(plans[0].contains("Tasks: elapsed"));
+        assert!(plans[1].starts_with("input_stages=[1]"));
+        assert!(plans[1].contains("ShuffleReaderExec"));
+        assert!(plans[2].contains("output_rows=2"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn should_explain_distributed_plan_as_json() -> Result<()> {
+        let test_data = crate::common::example_test_data();
+        let state = SessionStateBuilder::new()
+            .with_default_features()
+            .with_config(
+                SessionConfig::new_with_ballista()
+                    .with_target_partitions(2)
+                    .with_ballista_explain_format(ExplainFormat::Json),
+            )
+            .build();
+        let cluster = TestCluster::new_with_state(2, &state).await?;
+        let ctx = cluster.context_with_state(state).await?;
+        ctx.register_parquet(
+            "test",
+            &format!("{test_data}/alltypes_plain.parquet"),
+            Default::default(),
+        )
+        .await?;
+
+        let result = ctx
+            .sql("explain select bool_col, count(*) from test group by bool_col")
+            .await?
+            .collect()
+            .await?;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].num_rows(), 2);
+        let plans = result[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert!(plans.value(0).contains("Aggregate"));
+
+        // the physical plan is the distributed plan, as JSON
+        let plan: serde_json::Value = serde_json::from_str(plans.value(1)).unwrap();
+        let stages = plan["stages"].as_array().unwrap();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0]["output_partitioning"]["scheme"], "hash");
+        assert_eq!(stages[0]["plan"]["operator"], "AggregateExec");
+        assert_eq!(stages[1]["input_stages"], serde_json::json!([1]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn should_run_recursive_query_iteratively() -> Result<()> {
+        let test_data = crate::common::example_test_data();
+        let state = SessionStateBuilder::new()
+            .with_default_features()
+            .with_config(SessionConfig::new_with_ballista().with_target_partitions(2))
+            .build();
+        let cluster = TestCluster::new_with_state(2, &state).await?;
+        let ctx = cluster.context_with_state(state).await?;
+        ctx.register_parquet(
+            "test",
+            &format!("{test_data}/alltypes_plain.parquet"),
+            Default::default(),
+        )
+        .await?;
+
+        // Each iteration joins the rows found by the previous one with the table
+        let result = ctx
+            .sql(
+                "with recursive chain as ( \
+                    select id, 0 as depth from test where id = 0 \
+                    union all \
+                    select test.id, chain.depth + 1 from chain join test on test.id = chain.id + 1 \
+                ) \
+                select count(*), max(depth) from chain",
+            )
+            .await?
+            .collect()
+            .await?;
+        let expected = [
+            "+----------+------------------+",
+            "| count(*) | max(chain.depth) |",
+            "+----------+------------------+",
+            "| 8        | 7                |",
+            "+----------+------------------+",
+        ];
+        assert_batches_eq!(expected, &result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn should_aggregate_grouping_sets() -> Result<()> {
+        let test_data = crate::common::example_test_data();
+        for pre_aggregate in [false, true] {
+            let state = SessionStateBuilder::new()
+                .with_default_features()
+                .with_config(
+                    SessionConfig::new_with_ballista()
+                        .with_target_partitions(2)
+                        .with_ballista_pre_aggregate_grouping_sets(pre_aggregate),
+                )
+                .build();
+            let cluster = TestCluster::new_with_state(2, &state).await?;
+            let ctx = cluster.context_with_state(state).await?;
+            ctx.register_parquet(
+                "test",
+                &format!("{test_data}/alltypes_plain.parquet"),
+                Default::default(),
+            )
+            .await?;
+
+            let result = ctx
+                .sql(
+                    "select bool_col, id % 4, sum(id), count(*), min(id), max(id) \
+                    from test group by rollup(bool_col, id % 4)",
+                )
+                .await?
+                .collect()
+                .await?;
+            let expected = [
+                "+----------+--------------------+--------------+----------+--------------+--------------+",
+                "| bool_col | test.id % Int64(4) | sum(test.id) | count(*) | min(test.id) | max(test.id) |",
+                "+----------+--------------------+--------------+----------+--------------+--------------+",
+                "|          |                    | 28           | 8        | 0            | 7            |",
+                "| false    |                    | 16           | 4        | 1            | 7            |",
+                "| false    | 1                  | 6            | 2        | 1            | 5            |",
+                "| false    | 3                  | 10           | 2        | 3            | 7            |",
+                "| true     |                    | 12           | 4        | 0            | 6            |",
+                "| true     | 0                  | 4            | 2        | 0            | 4            |",
+                "| true     | 2                  | 8            | 2        | 2            | 6            |",
+                "+----------+--------------------+--------------+----------+--------------+--------------+",
+            ];
+            assert_batches_sorted_eq!(expected, &result);
+
+            let result = ctx
+                .sql(
+                    "select bool_col, tinyint_col, sum(id), count(*) \
+                    from test group by cube(bool_col, tinyint_col)",
+                )
+                .await?
+                .collect()
+                .await?;
+            let expected = [
+                "+----------+-------------+--------------+----------+",
+                "| bool_col | tinyint_col | sum(test.id) | count(*) |",
+                "+----------+-------------+--------------+----------+",
+                "|          |             | 28           | 8        |",
+                "|          | 0           | 12           | 4        |",
+                "|          | 1           | 16           | 4        |",
+                "| false    |             | 16           | 4        |",
+                "| false    | 1           | 16           | 4        |",
+                "| true     |             | 12           | 4        |",
+                "| true     | 0           | 12           | 4        |",
+                "+----------+-------------+--------------+----------+",
+            ];
+            assert_batches_sorted_eq!(expected, &result);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn should_use_session_state_of_cluster() -> Result<()> {
+        let state = SessionStateBuilder::new()
+            .with_default_features()
+            .with_config(SessionConfig::new_with_ballista().with_target_partitions(3))
+            .build();
+        let cluster = TestCluster::new_with_state(1, &state).await?;
+        let ctx = cluster.context_with_state(state).await?;
+
+        let result = ctx
+            .sql("select count(*) from (values (1), (2), (3)) as t(a)")
+            .await?
+            .collect()
+            .await?;
+        let expected = [
+            "+----------+",
+            "| count(*) |",
+            "+----------+",
+            "| 3        |",
+            "+----------+",
+        ];
+        assert_batches_eq!(expected, &result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn should_track_submitted_job_by_id() -> Result<()> {
+        let test_data = crate::common::example_test_data();
+        let cluster = TestCluster::new(2).await?;
+        let ctx = cluster.context().await?;
+        ctx.register_parquet(
+            "test",
+            &format!("{test_data}/alltypes_plain.parquet"),
+            Default::default(),
+        )
+        .await?;
+        let config = SessionConfig::new_with_ballista();
+        let mut client =
+            SchedulerClient::connect(&cluster.scheduler_url(), &config).await?;
+
+        let job = client
+            .submit_job(
+                ctx.sql("select bool_col, count(*) from test group by bool_col")
+                    .await?,
+            )
+            .await?;
+
+        // the job is tracked by its ID alone, e.g. from another process
+        let mut job = client.job(job.job_id().to_owned());
+        let results = job.fetch_results().await?.try_collect::<Vec<_>>().await?;
+        let expected = [
+            "+----------+----------+",
+            "| bool_col | count(*) |",
+            "+----------+----------+",
+            "| false    | 4        |",
+            "| true     | 4        |",
+            "+----------+----------+",
+        ];
+        assert_batches_sorted_eq!(expected, &results);
+
+        let status = job.status().await?.unwrap();
+        assert!(matches!(
+            status.status,
+            Some(job_status::Status::Successful(_))
+        ));
+        let progress = job.progress().await?;
+        assert!(progress.stages > 0);
+        assert_eq!(progress.stages, progress.completed_stages);
+        assert_eq!(progress.tasks, progress.completed_tasks);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn should_fetch_plan_of_completed_job() -> Result<()> {
+        let cluster = TestCluster::new(1).await?;
+        let config = SessionConfig::new_with_ballista();
+        let mut client =
+            SchedulerClient::connect(&cluster.scheduler_url(), &config).await?;
+
+        let connection = create_grpc_client_connection(
+            cluster.scheduler_url().replace("df://", "http://"),
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        let session_id = scheduler
+            .create_session(CreateSessionParams::default())
+            .await
+            .unwrap()
+            .into_inner()
+            .session_id;
+
+        let test_data = crate::common::example_test_data();
+        run_sql(
+            &mut scheduler,
+            &session_id,
+            &format!(
+                "create external table test stored as parquet location '{test_data}/alltypes_plain.parquet'"
+            ),
+        )
+        .await;
+        let job_id = run_sql(
+            &mut scheduler,
+            &session_id,
+            "select bool_col, count(*) from test group by bool_col",
+        )
+        .await;
+
+        let stages = client.job_plan(&job_id).await?;
+        assert!(stages.len() > 1);
+        let final_stage = stages.last().unwrap();
+        assert!(final_stage.output_links.is_empty());
+        assert!(!final_stage.input_stages.is_empty());
+
+        // The plan of every stage decodes with the codec of the client
+        let ctx = cluster.context().await?;
+        let codec: BallistaCodec = BallistaCodec::default();
+        for stage in &stages {
+            PhysicalPlanNode::try_decode(&stage.plan)?.try_into_physical_plan(
+                &ctx,
+                ctx.runtime_env().as_ref(),
+                codec.physical_extension_codec(),
+            )?;
+        }
+
+        assert!(client.job_plan("unknown").await.is_err());
+
+        Ok(())
+    }
+
+    /// Runs `sql` in the session of the scheduler, returning the ID of its job once it
+    /// succeeded
+    async fn run_sql(
+        scheduler: &mut SchedulerGrpcClient<GrpcChannel>,
+        session_id: &str,
+        sql: &str,
+    ) -> String {
+        #[allow(deprecated)]
+        let result = scheduler
+            .execute_query(ExecuteQueryParams {
+                query: Some(Query::Sql(sql.to_owned())),
+                optional_session_id: Some(OptionalSessionId::SessionId(
+                    session_id.to_owned(),
+                )),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let Some(execute_query_result::Result::Success(success)) = result.result else {
+            panic!("query submission failed: {result:?}");
+        };
+
+        loop {
+            let status = scheduler
+                .get_job_status(GetJobStatusParams {
+                    job_id: success.job_id.clone(),
+                })
+                .await
+                .unwrap()
+                .into_inner()
+                .status
+                .and_then(|status| status.status);
+            match status {
+                Some(job_status::Status::Successful(_)) => break success.job_id,
+                Some(job_status::Status::Failed(failed)) => {
+                    panic!("job failed: {}", failed.error)
+                }
+                _ => tokio::time::sleep(std::time::Duration::from_millis(100)).await,
+            }
+        }
+    }
+}
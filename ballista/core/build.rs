@@ -44,6 +44,11 @@ fn main() -> Result<(), String> {
             .extern_path(".datafusion_common", "::datafusion_proto_common")
             .extern_path(".datafusion", "::datafusion_proto::protobuf")
             .protoc_arg("--experimental_allow_proto3_optional")
+            // the plans of stages are shared by their tasks rather than copied
+            .bytes([
+                ".ballista.protobuf.TaskDefinition.plan",
+                ".ballista.protobuf.MultiTaskDefinition.plan",
+            ])
             .compile_protos(&["proto/ballista.proto"], &["proto"])
             .map_err(|e| format!("protobuf compilation failed: {e}"))?;
         let generated_source_path = out.join("ballista.protobuf.rs");
@@ -17,22 +17,24 @@
 
 //! Client API for sending requests to executors.
 
-use std::collections::HashMap;
 use std::sync::Arc;
 
 use std::{
     convert::{TryFrom, TryInto},
+    result,
     task::{Context, Poll},
 };
 
-use crate::error::{BallistaError, Result};
+use crate::compression::CompressionCodec;
+use crate::config::ShuffleCompression;
+use crate::error::{error_code_from_status, BallistaError, Result};
 use crate::serde::scheduler::{Action, PartitionId};
 
 use arrow_flight;
-use arrow_flight::utils::flight_data_to_arrow_batch;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::error::FlightError;
 use arrow_flight::Ticket;
 use arrow_flight::{flight_service_client::FlightServiceClient, FlightData};
-use datafusion::arrow::array::ArrayRef;
 use datafusion::arrow::{
     datatypes::{Schema, SchemaRef},
     error::ArrowError,
@@ -40,18 +42,18 @@ use datafusion::arrow::{
 };
 use datafusion::error::DataFusionError;
 
+use crate::security::GrpcSecurityConfig;
 use crate::serde::protobuf;
-use crate::utils::create_grpc_client_connection;
+use crate::utils::{create_grpc_client_connection, GrpcChannel};
 use datafusion::physical_plan::{RecordBatchStream, SendableRecordBatchStream};
-use futures::{Stream, StreamExt};
+use futures::{Stream, StreamExt, TryStreamExt};
 use log::{debug, warn};
 use prost::Message;
-use tonic::{Code, Streaming};
 
 /// Client for interacting with Ballista executors.
 #[derive(Clone)]
 pub struct BallistaClient {
-    flight_client: FlightServiceClient<tonic::transport::channel::Channel>,
+    flight_client: FlightServiceClient<GrpcChannel>,
 }
 
 //TODO make this configurable
@@ -61,24 +63,30 @@ const IO_RETRY_WAIT_TIME_MS: u64 = 3000;
 impl BallistaClient {
     /// Create a new BallistaClient to connect to the executor listening on the specified
     /// host and port
-    pub async fn try_new(host: &str, port: u16) -> Result<Self> {
+    pub async fn try_new(
+        host: &str,
+        port: u16,
+        security: &GrpcSecurityConfig,
+    ) -> Result<Self> {
         let addr = format!("http://{host}:{port}");
         debug!("BallistaClient connecting to {}", addr);
-        let connection =
-            create_grpc_client_connection(addr.clone())
-                .await
-                .map_err(|e| {
-                    BallistaError::GrpcConnectionError(format!(
-                    "Error connecting to Ballista scheduler or executor at {addr}: {e:?}"
+        let connection = create_grpc_client_connection(addr.clone(), security)
+            .await
+            .map_err(|e| {
+                BallistaError::ExecutorUnreachable(format!(
+                    "Error connecting to Ballista executor at {addr}: {e:?}"
                 ))
-                })?;
+            })?;
         let flight_client = FlightServiceClient::new(connection);
         debug!("BallistaClient connected OK");
 
         Ok(Self { flight_client })
     }
 
-    /// Fetch a partition from an executor
+    /// Fetch a partition from an executor, presenting the token of its job. The
+    /// partition is sent compressed with `transfer_compression` if any, which the
+    /// executor must have registered, or else with the `compression` of its file
+    #[allow(clippy::too_many_arguments)]
     pub async fn fetch_partition(
         &mut self,
         executor_id: &str,
@@ -86,6 +94,10 @@ impl BallistaClient {
         path: &str,
         host: &str,
         port: u16,
+        compression: ShuffleCompression,
+        fetch_token: &str,
+        transfer_compression: Option<Arc<dyn CompressionCodec>>,
+        ipc_metadata_version: u32,
     ) -> Result<SendableRecordBatchStream> {
         let action = Action::FetchPartition {
             job_id: partition_id.job_id.clone(),
@@ -94,8 +106,19 @@ impl BallistaClient {
             path: path.to_owned(),
             host: host.to_owned(),
             port,
+            compression,
+            fetch_token: fetch_token.to_owned(),
+            transfer_compression: transfer_compression
+                .as_ref()
+                .map(|codec| codec.name().to_owned())
+                .unwrap_or_default(),
+            ipc_metadata_version,
         };
-        self.execute_action(&action)
+        // the bodies of the Flight messages are compressed with the codecs which Arrow
+        // IPC doesn't support
+        let body_compression =
+            transfer_compression.filter(|codec| codec.ipc_compression().is_none());
+        self.execute_action_with_body_compression(&action, body_compression)
             .await
             .map_err(|error| match error {
                 // map grpc connection error to partition fetch error.
@@ -113,6 +136,17 @@ impl BallistaClient {
     pub async fn execute_action(
         &mut self,
         action: &Action,
+    ) -> Result<SendableRecordBatchStream> {
+        self.execute_action_with_body_compression(action, None)
+            .await
+    }
+
+    /// Execute an action and retrieve the results, whose Flight message bodies are
+    /// compressed with `body_compression` if any
+    async fn execute_action_with_body_compression(
+        &mut self,
+        action: &Action,
+        body_compression: Option<Arc<dyn CompressionCodec>>,
     ) -> Result<SendableRecordBatchStream> {
         let serialized_action: protobuf::Action = action.to_owned().try_into()?;
 
@@ -143,7 +177,9 @@ impl BallistaClient {
                 Err(ref err) => {
                     // IO related error like connection timeout, reset... will warp with Code::Unknown
                     // This means IO related error will retry.
-                    if i == IO_RETRIES_TIMES - 1 || err.code() != Code::Unknown {
+                    if i == IO_RETRIES_TIMES - 1
+                        || !error_code_from_status(err).is_retryable()
+                    {
                         return BallistaError::GrpcActionError(format!(
                             "{:?}",
                             result.unwrap_err()
@@ -163,8 +199,15 @@ impl BallistaClient {
                         Some(flight_data) => {
                             let schema = Arc::new(Schema::try_from(&flight_data)?);
 
-                            // all the remaining stream messages should be dictionary and record batches
-                            Ok(Box::pin(FlightDataStream::new(stream, schema)))
+                            // all the remaining stream messages should be dictionary and record batches,
+                            // the decoder needs to see the schema message as well
+                            let stream = futures::stream::once(async { Ok(flight_data) })
+                                .chain(stream);
+                            Ok(Box::pin(FlightDataStream::new(
+                                stream,
+                                schema,
+                                body_compression.clone(),
+                            )))
                         }
                         None => Err(BallistaError::GrpcActionError(
                             "Did not receive schema batch from flight server".to_string(),
@@ -172,7 +215,9 @@ impl BallistaClient {
                     };
                 }
                 Err(e) => {
-                    if i == IO_RETRIES_TIMES - 1 || e.code() != Code::Unknown {
+                    if i == IO_RETRIES_TIMES - 1
+                        || !error_code_from_status(&e).is_retryable()
+                    {
                         return BallistaError::GrpcActionError(format!(
                             "{:?}",
                             e.to_string()
@@ -187,22 +232,62 @@ impl BallistaClient {
     }
 }
 
+/// Decodes the flight data of a partition, including the dictionary batches of
+/// dictionary encoded (possibly nested) columns
 struct FlightDataStream {
-    stream: Streaming<FlightData>,
+    stream: FlightRecordBatchStream,
     schema: SchemaRef,
-    dictionaries_by_id: HashMap<i64, ArrayRef>,
 }
 
 impl FlightDataStream {
-    pub fn new(stream: Streaming<FlightData>, schema: SchemaRef) -> Self {
+    /// Decodes `stream`, decompressing the bodies of its messages with
+    /// `body_compression` if any
+    pub fn new(
+        stream: impl Stream<Item = result::Result<FlightData, tonic::Status>> + Send + 'static,
+        schema: SchemaRef,
+        body_compression: Option<Arc<dyn CompressionCodec>>,
+    ) -> Self {
+        let stream = stream
+            .map_err(FlightError::Tonic)
+            .and_then(move |mut data| {
+                let result = match &body_compression {
+                    Some(codec) if !data.data_body.is_empty() => codec
+                        .decompress(&data.data_body)
+                        .map(|body| {
+                            data.data_body = body.into();
+                            data
+                        })
+                        .map_err(|e| FlightError::ExternalError(Box::new(e))),
+                    _ => Ok(data),
+                };
+                futures::future::ready(result)
+            });
         Self {
-            stream,
+            stream: FlightRecordBatchStream::new_from_flight_data(stream),
             schema,
-            dictionaries_by_id: HashMap::new(),
         }
     }
 }
 
+/// Compresses the bodies of the messages of `stream` with `codec`, the way
+/// [BallistaClient::fetch_partition] expects codecs which Arrow IPC doesn't support to
+/// compress them
+pub fn compress_flight_data(
+    stream: impl Stream<Item = result::Result<FlightData, tonic::Status>> + Send + 'static,
+    codec: Arc<dyn CompressionCodec>,
+) -> impl Stream<Item = result::Result<FlightData, tonic::Status>> + Send + 'static {
+    stream.map(move |data| {
+        let mut data = data?;
+        if !data.data_body.is_empty() {
+            data.data_body = codec
+                .compress(&data.data_body)
+                .map_err(|e| e.to_grpc_status())?
+                .into();
+        }
+        Ok(data)
+    })
+}
+
 impl Stream for FlightDataStream {
     type Item = datafusion::error::Result<RecordBatch>;
 
@@ -210,21 +295,15 @@ impl Stream for FlightDataStream {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        self.stream.poll_next_unpin(cx).map(|x| match x {
-            Some(flight_data_chunk_result) => {
-                let converted_chunk = flight_data_chunk_result
-                    .map_err(|e| ArrowError::from_external_error(Box::new(e)).into())
-                    .and_then(|flight_data_chunk| {
-                        flight_data_to_arrow_batch(
-                            &flight_data_chunk,
-                            self.schema.clone(),
-                            &self.dictionaries_by_id,
-                        )
-                        .map_err(|e| DataFusionError::ArrowError(e, None))
-                    });
-                Some(converted_chunk)
-            }
-            None => None,
+        self.stream.poll_next_unpin(cx).map(|x| {
+            x.map(|batch| {
+                batch.map_err(|e| {
+                    DataFusionError::ArrowError(
+                        ArrowError::from_external_error(Box::new(e)),
+                        None,
+                    )
+                })
+            })
         })
     }
 }
@@ -234,3 +313,72 @@ impl RecordBatchStream for FlightDataStream {
         self.schema.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::CompressionCodecRegistry;
+    use crate::test_utils::type_conformance_batches;
+    use arrow_flight::encode::{DictionaryHandling, FlightDataEncoderBuilder};
+    use datafusion::arrow::ipc::writer::IpcWriteOptions;
+    use datafusion::physical_plan::common;
+
+    #[tokio::test]
+    async fn test_flight_transfer_type_conformance() -> Result<()> {
+        let compressions = [
+            ShuffleCompression::Lz4,
+            ShuffleCompression::Zstd,
+            ShuffleCompression::Uncompressed,
+        ];
+        for (batch, compression) in
+            type_conformance_batches().into_iter().flat_map(|batch| {
+                compressions.map(|compression| (batch.clone(), compression))
+            })
+        {
+            let schema = batch.schema();
+
+            // encode the same way as the executor's flight service
+            let write_options = IpcWriteOptions::default()
+                .try_with_compression(compression.ipc_compression())?;
+            let flight_data = FlightDataEncoderBuilder::new()
+                .with_schema(schema.clone())
+                .with_options(write_options)
+                .with_dictionary_handling(DictionaryHandling::Resend)
+                .build(futures::stream::iter(vec![
+                    Ok::<_, FlightError>(batch.clone()),
+                    Ok(batch.clone()),
+                ]))
+                .map_err(|e| tonic::Status::internal(e.to_string()));
+
+            let stream = FlightDataStream::new(flight_data, schema.clone(), None);
+            let result = common::collect(Box::pin(stream)).await?;
+
+            assert_eq!(vec![batch.clone(), batch], result, "{schema} {compression}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flight_transfer_body_compression() -> Result<()> {
+        let codec = CompressionCodecRegistry::default().codec("snappy")?;
+        for batch in type_conformance_batches() {
+            let schema = batch.schema();
+
+            let flight_data = FlightDataEncoderBuilder::new()
+                .with_schema(schema.clone())
+                .with_dictionary_handling(DictionaryHandling::Resend)
+                .build(futures::stream::iter(vec![Ok::<_, FlightError>(
+                    batch.clone(),
+                )]))
+                .map_err(|e| tonic::Status::internal(e.to_string()));
+            let flight_data = compress_flight_data(flight_data, codec.clone());
+
+            let stream =
+                FlightDataStream::new(flight_data, schema.clone(), Some(codec.clone()));
+            let result = common::collect(Box::pin(stream)).await?;
+
+            assert_eq!(vec![batch], result, "{schema}");
+        }
+        Ok(())
+    }
+}
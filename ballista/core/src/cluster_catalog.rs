@@ -0,0 +1,113 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Tables of the catalog of the cluster, which the scheduler keeps for all the
+//! sessions, when enabled.
+//!
+//! Clients register the tables of the catalog as [ClusterTable]s, which their plans
+//! reference by name rather than carrying the definition of the table, e.g. all the
+//! options of a listing table. The scheduler resolves the references to the tables of
+//! its catalog before planning the jobs.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::catalog::{Session, TableProvider};
+use datafusion::common::{DataFusionError, Result};
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::ExecutionPlan;
+
+use crate::serde::protobuf::ClusterTableNode;
+use crate::serde::{decode_schema, encode_arrow_schema};
+
+/// A table of the catalog of the cluster, referenced by name
+#[derive(Debug, Clone)]
+pub struct ClusterTable {
+    name: String,
+    schema: SchemaRef,
+    table_type: TableType,
+}
+
+impl ClusterTable {
+    pub fn new(
+        name: impl Into<String>,
+        schema: SchemaRef,
+        table_type: TableType,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+            table_type,
+        }
+    }
+
+    /// Name of the table in the catalog of the cluster
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn try_from_proto(node: ClusterTableNode) -> Result<Self> {
+        let table_type = if node.view {
+            TableType::View
+        } else {
+            TableType::Base
+        };
+        Ok(Self::new(
+            node.name,
+            Arc::new(decode_schema(&node.arrow_schema, &None)?),
+            table_type,
+        ))
+    }
+
+    pub fn try_to_proto(&self) -> Result<ClusterTableNode> {
+        Ok(ClusterTableNode {
+            name: self.name.clone(),
+            arrow_schema: encode_arrow_schema(&self.schema)?,
+            view: self.table_type == TableType::View,
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for ClusterTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.table_type
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        _projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Err(DataFusionError::Plan(format!(
+            "Table {} of the cluster catalog can only be scanned by jobs of the cluster",
+            self.name
+        )))
+    }
+}
@@ -0,0 +1,269 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Codecs compressing the payloads Ballista writes to disk or sends over the wire, by
+//! name: the shuffle files, the shuffle partitions fetched over Flight and the plans
+//! dispatched with tasks.
+//!
+//! Shuffle files are Arrow IPC files, which only the codecs Arrow IPC supports natively
+//! can compress, see [CompressionCodec::ipc_compression]. Other codecs compress the
+//! bytes of the Flight messages and of the plans.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::config::ShuffleCompression;
+use crate::error::{BallistaError, Result};
+
+/// Codec compressing byte payloads, registered by name in a [CompressionCodecRegistry].
+/// The scheduler, the executors and the clients must register the same codecs
+pub trait CompressionCodec: Debug + Send + Sync {
+    /// Name of the codec, which the settings refer to it by
+    fn name(&self) -> &str;
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// The Arrow IPC compression equivalent to the codec, if Arrow IPC supports it
+    /// natively, which Arrow IPC payloads are compressed with rather than with the codec
+    fn ipc_compression(&self) -> Option<ShuffleCompression> {
+        None
+    }
+}
+
+/// Leaves payloads as they are
+#[derive(Debug, Default)]
+pub struct NoCompression;
+
+impl CompressionCodec for NoCompression {
+    fn name(&self) -> &str {
+        "none"
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+
+    fn ipc_compression(&self) -> Option<ShuffleCompression> {
+        Some(ShuffleCompression::Uncompressed)
+    }
+}
+
+/// LZ4, fast with a moderate ratio
+#[derive(Debug, Default)]
+pub struct Lz4Codec;
+
+impl CompressionCodec for Lz4Codec {
+    fn name(&self) -> &str {
+        "lz4"
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(lz4_flex::compress_prepend_size(data))
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        lz4_flex::decompress_size_prepended(data)
+            .map_err(|e| BallistaError::General(format!("Invalid LZ4 payload: {e}")))
+    }
+
+    fn ipc_compression(&self) -> Option<ShuffleCompression> {
+        Some(ShuffleCompression::Lz4)
+    }
+}
+
+/// Zstandard, compressing better than LZ4 at a higher CPU cost
+#[derive(Debug)]
+pub struct ZstdCodec {
+    level: i32,
+}
+
+impl ZstdCodec {
+    pub fn new(level: i32) -> Self {
+        Self { level }
+    }
+}
+
+impl Default for ZstdCodec {
+    fn default() -> Self {
+        Self::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+impl CompressionCodec for ZstdCodec {
+    fn name(&self) -> &str {
+        "zstd"
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        zstd::bulk::compress(data, self.level).map_err(BallistaError::IoError)
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        zstd::stream::decode_all(data).map_err(BallistaError::IoError)
+    }
+
+    fn ipc_compression(&self) -> Option<ShuffleCompression> {
+        Some(ShuffleCompression::Zstd)
+    }
+}
+
+/// Snappy, about as fast as LZ4. Arrow IPC doesn't support it, so shuffle files can't
+/// be compressed with it
+#[derive(Debug, Default)]
+pub struct SnappyCodec;
+
+impl CompressionCodec for SnappyCodec {
+    fn name(&self) -> &str {
+        "snappy"
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        snap::raw::Encoder::new()
+            .compress_vec(data)
+            .map_err(|e| BallistaError::General(format!("Snappy compression: {e}")))
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        snap::raw::Decoder::new()
+            .decompress_vec(data)
+            .map_err(|e| BallistaError::General(format!("Invalid Snappy payload: {e}")))
+    }
+}
+
+/// The compression codecs by name, including the built-in `none`, `lz4`, `zstd` and
+/// `snappy` codecs
+#[derive(Debug, Clone)]
+pub struct CompressionCodecRegistry {
+    codecs: HashMap<String, Arc<dyn CompressionCodec>>,
+}
+
+impl Default for CompressionCodecRegistry {
+    fn default() -> Self {
+        let codecs: [Arc<dyn CompressionCodec>; 4] = [
+            Arc::new(NoCompression),
+            Arc::new(Lz4Codec),
+            Arc::new(ZstdCodec::default()),
+            Arc::new(SnappyCodec),
+        ];
+        codecs
+            .into_iter()
+            .fold(Self::empty(), |registry, codec| registry.with_codec(codec))
+    }
+}
+
+impl CompressionCodecRegistry {
+    fn empty() -> Self {
+        Self {
+            codecs: HashMap::new(),
+        }
+    }
+
+    /// Register a codec, replacing the codec with the same name if any
+    pub fn with_codec(mut self, codec: Arc<dyn CompressionCodec>) -> Self {
+        self.codecs.insert(codec.name().to_owned(), codec);
+        self
+    }
+
+    pub fn codec(&self, name: &str) -> Result<Arc<dyn CompressionCodec>> {
+        self.codecs.get(name).cloned().ok_or_else(|| {
+            BallistaError::General(format!(
+                "Unknown compression codec {name}, expected one of {}",
+                self.names().join(", ")
+            ))
+        })
+    }
+
+    /// Names of the registered codecs, in alphabetical order
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.codecs.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Decompress a payload compressed with the codec named `name`, if any
+    pub fn decompress<'a>(&self, name: &str, data: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        if name.is_empty() {
+            return Ok(Cow::Borrowed(data));
+        }
+        Ok(Cow::Owned(self.codec(name)?.decompress(data)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reverses the bytes, to tell apart payloads it compressed
+    #[derive(Debug)]
+    struct ReverseCodec;
+
+    impl CompressionCodec for ReverseCodec {
+        fn name(&self) -> &str {
+            "reverse"
+        }
+
+        fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+            Ok(data.iter().rev().copied().collect())
+        }
+
+        fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+            self.compress(data)
+        }
+    }
+
+    #[test]
+    fn test_compression_codecs() -> Result<()> {
+        let registry = CompressionCodecRegistry::default();
+        assert_eq!(vec!["lz4", "none", "snappy", "zstd"], registry.names());
+
+        let data = "ballista ".repeat(1000).into_bytes();
+        for name in registry.names() {
+            let codec = registry.codec(name)?;
+            let compressed = codec.compress(&data)?;
+            if name != "none" {
+                assert!(compressed.len() < data.len(), "{name}");
+            }
+            assert_eq!(data, registry.decompress(name, &compressed)?.as_ref());
+        }
+        assert_eq!(None, registry.codec("snappy")?.ipc_compression());
+        assert!(registry.codec("reverse").is_err());
+        assert!(registry.decompress("lz4", b"not lz4").is_err());
+        // payloads without codec are not compressed
+        assert!(matches!(
+            registry.decompress("", b"data")?,
+            Cow::Borrowed(b"data")
+        ));
+
+        let registry = registry.with_codec(Arc::new(ReverseCodec));
+        let compressed = registry.codec("reverse")?.compress(b"abc")?;
+        assert_eq!(b"cba".to_vec(), compressed);
+        assert_eq!(
+            b"abc",
+            registry.decompress("reverse", &compressed)?.as_ref()
+        );
+
+        Ok(())
+    }
+}
@@ -24,9 +24,16 @@ use std::collections::HashMap;
 use std::result;
 
 use crate::error::{BallistaError, Result};
+use crate::historical_statistics::TableSize;
+use crate::serde::protobuf::KeyValuePair;
 
 use datafusion::{
-    arrow::datatypes::DataType, common::config_err, config::ConfigExtension,
+    arrow::datatypes::DataType,
+    arrow::error::ArrowError,
+    arrow::ipc::writer::IpcWriteOptions,
+    arrow::ipc::{CompressionType, MetadataVersion},
+    common::config_err,
+    config::ConfigExtension,
 };
 
 pub const BALLISTA_JOB_NAME: &str = "ballista.job.name";
@@ -34,6 +41,107 @@ pub const BALLISTA_STANDALONE_PARALLELISM: &str = "ballista.standalone.paralleli
 /// max message size for gRPC clients
 pub const BALLISTA_GRPC_CLIENT_MAX_MESSAGE_SIZE: &str =
     "ballista.grpc_client_max_message_size";
+/// number of shuffle output partitions above which the sort-based shuffle is used
+pub const BALLISTA_SHUFFLE_SORT_BASED_PARTITION_THRESHOLD: &str =
+    "ballista.shuffle.sort_based.partition_threshold";
+/// max size of a final stage output partition to be inlined in the task status
+pub const BALLISTA_INLINE_RESULT_MAX_BYTES: &str = "ballista.job.inline_result_max_bytes";
+/// stages executed a second time on another executor to cross-check their output
+pub const BALLISTA_VERIFY_STAGES: &str = "ballista.job.verify_stages";
+/// whether the output partitions of the final stage are checksummed by the executors
+pub const BALLISTA_CHECKSUM_RESULTS: &str = "ballista.job.checksum_results";
+/// whether jobs produce the same results, in the same order, on every run
+pub const BALLISTA_DETERMINISTIC: &str = "ballista.job.deterministic";
+/// max number of result rows returned to the client, beyond which results are truncated
+pub const BALLISTA_MAX_RESULT_ROWS: &str = "ballista.job.max_result_rows";
+/// whether jobs complete with partial results when partitions permanently fail
+pub const BALLISTA_BEST_EFFORT: &str = "ballista.job.best_effort";
+/// format of the distributed plan shown by EXPLAIN
+pub const BALLISTA_EXPLAIN_FORMAT: &str = "ballista.job.explain_format";
+/// priority of jobs, the tasks of higher priority jobs being scheduled first
+pub const BALLISTA_JOB_PRIORITY: &str = "ballista.job.priority";
+/// URL the scheduler calls back once jobs finish, fail or are cancelled
+pub const BALLISTA_JOB_WEBHOOK_URL: &str = "ballista.job.webhook_url";
+/// maximum number of tasks of a job running at the same time on an executor
+pub const BALLISTA_JOB_MAX_TASKS_PER_EXECUTOR: &str =
+    "ballista.job.max_tasks_per_executor";
+/// object store URL under which each job gets a staging prefix for its temporary data
+pub const BALLISTA_JOB_STAGING_URL: &str = "ballista.job.staging_url";
+/// named resource profile of the scheduler whose settings the jobs are submitted with
+pub const BALLISTA_JOB_RESOURCE_PROFILE: &str = "ballista.job.resource_profile";
+/// reservation of executor slots on the scheduler the jobs run with
+pub const BALLISTA_JOB_SLOT_RESERVATION: &str = "ballista.job.slot_reservation";
+/// seconds the executors keep the results of the jobs unless the client releases them
+pub const BALLISTA_JOB_RESULT_TTL: &str = "ballista.job.result_ttl_seconds";
+/// seconds a task of a stage may run before it is failed and retried
+pub const BALLISTA_STAGE_TIMEOUT: &str = "ballista.stage.timeout";
+/// whether tables are created with the merged schema of their files
+pub const BALLISTA_SCAN_MERGE_SCHEMAS: &str = "ballista.scan.merge_schemas";
+/// whether the files of file scans are leased to tasks as they go, for idle tasks to
+/// scan the files slow tasks have not started yet
+pub const BALLISTA_SCAN_SPLITTABLE: &str = "ballista.scan.splittable";
+/// types of the partition columns of the tables, overriding their declared types
+pub const BALLISTA_SCAN_PARTITION_COLUMN_TYPES: &str =
+    "ballista.scan.partition_column_types";
+/// whether joins are executed as sort-merge joins over sorted shuffles
+pub const BALLISTA_JOIN_PREFER_SORT_MERGE: &str = "ballista.join.prefer_sort_merge";
+/// max estimated size of the build side of a hash join for it to be broadcast
+pub const BALLISTA_JOIN_BROADCAST_THRESHOLD_BYTES: &str =
+    "ballista.join.broadcast_threshold_bytes";
+/// whether grouping set aggregations are pre-aggregated by all their grouping columns
+pub const BALLISTA_OPTIMIZER_PRE_AGGREGATE_GROUPING_SETS: &str =
+    "ballista.optimizer.pre_aggregate_grouping_sets";
+/// factor correcting the expected compression ratios of the exchanged columns
+pub const BALLISTA_OPTIMIZER_COMPRESSION_RATIO_SCALE: &str =
+    "ballista.optimizer.compression_ratio_scale";
+/// network bandwidth between the executors the exchanges are costed with
+pub const BALLISTA_OPTIMIZER_NETWORK_BYTES_PER_SECOND: &str =
+    "ballista.optimizer.network_bytes_per_second";
+/// whether scans without statistics get the sizes of their tables recorded from earlier jobs
+pub const BALLISTA_OPTIMIZER_HISTORICAL_STATISTICS: &str =
+    "ballista.optimizer.historical_statistics";
+/// sizes of the tables the scans without statistics are planned with
+pub const BALLISTA_OPTIMIZER_TABLE_STATISTICS: &str =
+    "ballista.optimizer.table_statistics";
+/// codec compressing shuffle files and, by default, the shuffle partitions fetched over Flight
+pub const BALLISTA_SHUFFLE_COMPRESSION: &str = "ballista.shuffle.compression";
+/// codec compressing the shuffle partitions fetched over Flight, if not the one of their files
+pub const BALLISTA_SHUFFLE_TRANSFER_COMPRESSION: &str =
+    "ballista.shuffle.transfer_compression";
+/// object store which map tasks upload their shuffle files to
+pub const BALLISTA_SHUFFLE_OBJECT_STORE_URL: &str = "ballista.shuffle.object_store_url";
+/// whether small shuffle partitions are coalesced once the sizes of their map outputs are known
+pub const BALLISTA_SHUFFLE_COALESCE_PARTITIONS: &str =
+    "ballista.shuffle.coalesce_partitions";
+/// size in bytes shuffle partitions are coalesced up to
+pub const BALLISTA_SHUFFLE_TARGET_PARTITION_BYTES: &str =
+    "ballista.shuffle.target_partition_bytes";
+/// max number of shuffle partitions a task fetches at the same time
+pub const BALLISTA_SHUFFLE_MAX_CONCURRENT_FETCHES: &str =
+    "ballista.shuffle.max_concurrent_fetches";
+/// number of times a failed shuffle partition fetch is retried
+pub const BALLISTA_SHUFFLE_FETCH_RETRIES: &str = "ballista.shuffle.fetch_retries";
+/// milliseconds before the first retry of a failed shuffle partition fetch, doubled on each retry
+pub const BALLISTA_SHUFFLE_FETCH_RETRY_BACKOFF_MS: &str =
+    "ballista.shuffle.fetch_retry_backoff_ms";
+/// max number of fetched shuffle batches buffered until the task consumes them
+pub const BALLISTA_SHUFFLE_MAX_BUFFERED_BATCHES: &str =
+    "ballista.shuffle.max_buffered_batches";
+
+/// log filter directives of the executors, e.g. `info,ballista=debug`
+pub const EXECUTOR_LOG_LEVEL: &str = "executor.log_level";
+/// max number of plans dispatched by reference, and of stages planned from logical
+/// plans, which executors cache
+pub const EXECUTOR_TASK_PLAN_CACHE_SIZE: &str = "executor.task_plan_cache_size";
+/// max number of log lines executors capture per task
+pub const EXECUTOR_TASK_LOG_MAX_LINES: &str = "executor.task_log_max_lines";
+/// operational settings of the executors which the scheduler can push to them at
+/// runtime, rather than being set when they start
+pub const EXECUTOR_SETTINGS: &[&str] = &[
+    EXECUTOR_LOG_LEVEL,
+    EXECUTOR_TASK_PLAN_CACHE_SIZE,
+    EXECUTOR_TASK_LOG_MAX_LINES,
+];
 
 pub type ParseResult<T> = result::Result<T, String>;
 use std::sync::LazyLock;
@@ -50,6 +158,146 @@ static CONFIG_ENTRIES: LazyLock<HashMap<String, ConfigEntry>> = LazyLock::new(||
                          "Configuration for max message size in gRPC clients".to_string(),
                          DataType::UInt64,
                          Some((16 * 1024 * 1024).to_string())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_SORT_BASED_PARTITION_THRESHOLD.to_string(),
+                         "Number of shuffle output partitions above which map tasks write a single sorted and indexed file instead of one file per output partition. Set to 0 to disable the sort-based shuffle".to_string(),
+                         DataType::UInt64,
+                         Some(1000.to_string())),
+        ConfigEntry::new(BALLISTA_INLINE_RESULT_MAX_BYTES.to_string(),
+                         "Maximum size in bytes of an output partition of the final stage for it to be delivered inline with the task status, saving the client a Flight request per partition. Set to 0 to always fetch results from the executors".to_string(),
+                         DataType::UInt64,
+                         Some(0.to_string())),
+        ConfigEntry::new(BALLISTA_VERIFY_STAGES.to_string(),
+                         "Comma-separated ids of the stages whose tasks are executed a second time on another executor, with the checksums of both outputs compared to detect non-determinism or hardware corruption. Set to * to verify all stages. Only meant for debugging, as verified stages cost twice as much".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_CHECKSUM_RESULTS.to_string(),
+                         "Whether the executors checksum the output partitions of the final stage, reporting the checksums with the statistics of the partitions so that the results of jobs are compared without reading them. Set by the scheduler on the jobs of shadow executions".to_string(),
+                         DataType::Boolean,
+                         Some(false.to_string())),
+        ConfigEntry::new(BALLISTA_DETERMINISTIC.to_string(),
+                         "Whether two runs of the same query over the same data produce byte-identical results: sorts break ties on all columns, results without a defined order are sorted on all columns, and shuffle partitions are read in a fixed order. Meant for regression testing, as the extra sorting and ordered reads are slower".to_string(),
+                         DataType::Boolean,
+                         Some(false.to_string())),
+        ConfigEntry::new(BALLISTA_MAX_RESULT_ROWS.to_string(),
+                         "Maximum number of result rows returned to the client. Results with more rows are truncated, and flagged as such in the job status. The scheduler can enforce a lower limit. Set to 0 for no limit".to_string(),
+                         DataType::UInt64,
+                         Some(0.to_string())),
+        ConfigEntry::new(BALLISTA_BEST_EFFORT.to_string(),
+                         "Whether jobs complete with partial results when tasks permanently fail, after their retries, instead of failing. The partitions of the failed tasks are skipped, and listed in the job status. Meant for exploratory queries, as results are silently incomplete to clients ignoring the skipped partitions. Fetch failures are still retried by rerunning the stages".to_string(),
+                         DataType::Boolean,
+                         Some(false.to_string())),
+        ConfigEntry::new(BALLISTA_EXPLAIN_FORMAT.to_string(),
+                         "Format of the distributed plan of the query shown by EXPLAIN: text, or json for a machine-readable representation of its stages, operators, partitioning and estimated statistics".to_string(),
+                         DataType::Utf8,
+                         Some(ExplainFormat::Text.to_string())),
+        ConfigEntry::new(BALLISTA_JOB_PRIORITY.to_string(),
+                         "Priority of the jobs, higher values being more important. Under the priority scheduling policy of the scheduler, queued tasks of higher priority jobs are bound to free executor slots before those of lower priority jobs, and under the fair-share policy jobs get shares of the slots proportional to their priority plus one. Running tasks are never preempted".to_string(),
+                         DataType::UInt32,
+                         Some(0.to_string())),
+        ConfigEntry::new(BALLISTA_JOB_WEBHOOK_URL.to_string(),
+                         "URL the scheduler posts a signed JSON notification to once the jobs finish, fail or are cancelled, in addition to the webhooks of the scheduler. It must start with one of the prefixes allowed by the scheduler. Empty for no webhook".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_JOB_MAX_TASKS_PER_EXECUTOR.to_string(),
+                         "Maximum number of tasks of a job running at the same time on an executor, so that a single job doesn't take all the slots, memory and disk bandwidth of a node. The scheduler binds the other tasks of the job to other executors or waits for tasks of the job to complete, and executors hold back the tasks of the job beyond the limit. Set to 0 for no limit".to_string(),
+                         DataType::UInt64,
+                         Some(0.to_string())),
+        ConfigEntry::new(BALLISTA_JOB_STAGING_URL.to_string(),
+                         "URL of an object store, e.g. s3://bucket/staging, under which each job gets its own staging prefix, the URL followed by the job ID, for the temporary data it writes to object stores. The scheduler recursively deletes the staging prefix of the jobs, along with the shuffle files they uploaded to ballista.shuffle.object_store_url, once they complete or expire. The object store must be registered in the runtime of the scheduler. Empty for no staging area".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_JOB_RESOURCE_PROFILE.to_string(),
+                         "Name of a resource profile configured on the scheduler, e.g. small, medium or large, bundling the settings the jobs of the session are submitted with, such as their target partitions, their priority and the maximum number of their tasks per executor. The settings of the profile take precedence over those of the session. Empty for no profile".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_JOB_SLOT_RESERVATION.to_string(),
+                         "ID of a reservation of executor slots made on the scheduler ahead of the jobs, e.g. for a latency critical scheduled report. Until the reservation expires, the scheduler holds back as many free slots as it reserves from the other jobs, so that the jobs running with it don't queue behind them. Empty for no reservation".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_JOB_RESULT_TTL.to_string(),
+                         "Seconds the executors keep the results of the successful jobs, their output partitions, after the jobs complete, unless the client releases them earlier once it fetched them. Set to 0 to keep them for the finished job data clean up interval of the scheduler".to_string(),
+                         DataType::UInt64,
+                         Some(0.to_string())),
+        ConfigEntry::new(BALLISTA_STAGE_TIMEOUT.to_string(),
+                         "Seconds a task of any stage of a job may run, so that a stage hung on e.g. stuck object store reads is retried or failed well before the job would be. Executors interrupt the tasks running for longer and report them as failed, to be retried up to the maximum task failures of the scheduler before the stage fails, and the scheduler fails the tasks its executors don't report in time. Set to 0 for no timeout".to_string(),
+                         DataType::UInt64,
+                         Some(0.to_string())),
+        ConfigEntry::new(BALLISTA_SCAN_MERGE_SCHEMAS.to_string(),
+                         "Whether Parquet tables created by CREATE EXTERNAL TABLE get the merged schema of their files, where columns missing from some files are read as nulls and columns with different types are read as the type they all widen to. Otherwise the files must have compatible schemas".to_string(),
+                         DataType::Boolean,
+                         Some(false.to_string())),
+        ConfigEntry::new(BALLISTA_SCAN_SPLITTABLE.to_string(),
+                         "Whether the file scans of stages reading no other input are split among their tasks as they go: each task asks the scheduler for the next file to scan, starting with the files of its own partition, and once they are all scanned takes over the files other tasks have not started yet, so that tasks finishing early relieve the slow ones. Scans whose partitions are sorted or limited are not split".to_string(),
+                         DataType::Boolean,
+                         Some(false.to_string())),
+        ConfigEntry::new(BALLISTA_SCAN_PARTITION_COLUMN_TYPES.to_string(),
+                         "Comma-separated column:type pairs setting the types of the partition columns of the tables created by CREATE EXTERNAL TABLE, overriding their declared types, e.g. 'year:Int32,month:Int32'. A * column applies to all other partition columns, e.g. '*:Utf8' forces all partition values to be strings".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_JOIN_PREFER_SORT_MERGE.to_string(),
+                         "Whether equi-joins are executed as sort-merge joins rather than hash joins, with map tasks sorting their shuffle output and reduce tasks merging the sorted outputs. Slower than hash joins, but no join side has to fit in the memory of an executor".to_string(),
+                         DataType::Boolean,
+                         Some(false.to_string())),
+        ConfigEntry::new(BALLISTA_JOIN_BROADCAST_THRESHOLD_BYTES.to_string(),
+                         "Maximum estimated size in bytes of a side of a hash join for it to be broadcast to all the tasks of the join, each of them joining its partition of the other side with the whole broadcast side, rather than both sides being shuffled. Supersedes datafusion.optimizer.hash_join_single_partition_threshold. Set to 0 to never broadcast".to_string(),
+                         DataType::UInt64,
+                         Some((10 * 1024 * 1024).to_string())),
+        ConfigEntry::new(BALLISTA_OPTIMIZER_PRE_AGGREGATE_GROUPING_SETS.to_string(),
+                         "Whether ROLLUP, CUBE and GROUPING SETS aggregations of sum, min, max and count are planned as an aggregation by all their grouping columns, followed by the grouping set aggregation of its output, so that map tasks shuffle a single partial aggregate per group rather than one per grouping set".to_string(),
+                         DataType::Boolean,
+                         Some(false.to_string())),
+        ConfigEntry::new(BALLISTA_OPTIMIZER_COMPRESSION_RATIO_SCALE.to_string(),
+                         "Factor correcting the compression ratios the planner expects of each type of column when estimating the bytes broadcast and shuffled over the network, e.g. 1.5 for data compressing 1.5 times worse than expected. Empty for the factor the scheduler calibrates from the shuffles of the completed jobs".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_OPTIMIZER_NETWORK_BYTES_PER_SECOND.to_string(),
+                         "Network bandwidth between the executors in bytes per second, scaling ballista.join.broadcast_threshold_bytes, which is meant for a 1 Gbit/s network, and keeping the partial aggregations reducing their input less over slower networks. 0 for the bandwidth the scheduler measures from the network throughput of the executors, or 1 Gbit/s until it has measured it".to_string(),
+                         DataType::UInt64,
+                         Some(0.to_string())),
+        ConfigEntry::new(BALLISTA_OPTIMIZER_HISTORICAL_STATISTICS.to_string(),
+                         "Whether the scans of the tables DataFusion has no statistics of, e.g. Parquet tables without datafusion.execution.collect_statistics, are planned with the rows and bytes of the tables recorded by the scheduler from the completed jobs which scanned them whole, so that the joins and exchanges of the later jobs are planned for their actual sizes".to_string(),
+                         DataType::Boolean,
+                         Some(true.to_string())),
+        ConfigEntry::new(BALLISTA_OPTIMIZER_TABLE_STATISTICS.to_string(),
+                         "Comma-separated location=rows:bytes sizes of the tables the scans without statistics are planned with, the location being the object store URL and directory of the files of a table, e.g. 's3://bucket/orders=1000000:64000000'. Empty for the sizes the scheduler recorded of the tables the job scans".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_COMPRESSION.to_string(),
+                         "Codec compressing the shuffle files written by map tasks, and by default the shuffle partitions sent to the tasks reading them: lz4, zstd or uncompressed (none), the codecs Arrow IPC supports. Zstd compresses better than LZ4, at a higher CPU cost".to_string(),
+                         DataType::Utf8,
+                         Some(ShuffleCompression::Lz4.to_string())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_TRANSFER_COMPRESSION.to_string(),
+                         "Codec compressing the shuffle partitions sent over Flight to the tasks reading them, rather than the codec of their shuffle files: lz4, zstd, snappy, none or a codec registered with SessionConfigExt::with_ballista_compression_codec on the executors. Empty to send the partitions compressed like their shuffle files".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_OBJECT_STORE_URL.to_string(),
+                         "URL of an object store, e.g. s3://bucket/shuffle, which map tasks upload their shuffle files to, so that they outlive the executor which wrote them and the stages reading them don't have to be re-run when an executor is lost. The object store must be registered in the runtime of the executors. Empty to keep shuffle files on the disk of the executors".to_string(),
+                         DataType::Utf8,
+                         Some(String::new())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_COALESCE_PARTITIONS.to_string(),
+                         "Whether adjacent shuffle partitions are coalesced into a single task once the stage writing them has completed, up to ballista.shuffle.target_partition_bytes per task, so that small shuffles don't run one tiny task per partition".to_string(),
+                         DataType::Boolean,
+                         Some(false.to_string())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_TARGET_PARTITION_BYTES.to_string(),
+                         "Size in bytes, as reported by the map tasks, that shuffle partitions are coalesced up to when ballista.shuffle.coalesce_partitions is set".to_string(),
+                         DataType::UInt64,
+                         Some((64 * 1024 * 1024).to_string())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_MAX_CONCURRENT_FETCHES.to_string(),
+                         "Maximum number of shuffle partitions a task fetches from other executors at the same time".to_string(),
+                         DataType::UInt64,
+                         Some(50.to_string())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_FETCH_RETRIES.to_string(),
+                         "Number of times the fetch of a shuffle partition from another executor or an object store is retried when it fails with a transient error, before falling back to another location of the partition if any. Errors occurring after the first batches of a partition were read are not retried".to_string(),
+                         DataType::UInt64,
+                         Some(3.to_string())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_FETCH_RETRY_BACKOFF_MS.to_string(),
+                         "Milliseconds waited before the first retry of a failed shuffle partition fetch, doubled on every following retry".to_string(),
+                         DataType::UInt64,
+                         Some(100.to_string())),
+        ConfigEntry::new(BALLISTA_SHUFFLE_MAX_BUFFERED_BATCHES.to_string(),
+                         "Maximum number of record batches of the fetched shuffle partitions buffered until the task consumes them. Fetches pause while the buffer is full, bounding the memory used by fast producers feeding slow operators".to_string(),
+                         DataType::UInt64,
+                         Some(16.to_string())),
     ];
     entries
         .into_iter()
@@ -167,6 +415,220 @@ impl BallistaConfig {
         self.get_usize_setting(BALLISTA_STANDALONE_PARALLELISM)
     }
 
+    pub fn shuffle_sort_based_partition_threshold(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_SORT_BASED_PARTITION_THRESHOLD)
+    }
+
+    pub fn inline_result_max_bytes(&self) -> usize {
+        self.get_usize_setting(BALLISTA_INLINE_RESULT_MAX_BYTES)
+    }
+
+    /// Whether the stage is executed twice to verify its output
+    pub fn verify_stage(&self, stage_id: usize) -> bool {
+        self.get_string_setting(BALLISTA_VERIFY_STAGES)
+            .split(',')
+            .map(str::trim)
+            .any(|stage| stage == "*" || stage.parse::<usize>().ok() == Some(stage_id))
+    }
+
+    pub fn checksum_results(&self) -> bool {
+        self.get_bool_setting(BALLISTA_CHECKSUM_RESULTS)
+    }
+
+    pub fn deterministic(&self) -> bool {
+        self.get_bool_setting(BALLISTA_DETERMINISTIC)
+    }
+
+    pub fn max_result_rows(&self) -> usize {
+        self.get_usize_setting(BALLISTA_MAX_RESULT_ROWS)
+    }
+
+    pub fn best_effort(&self) -> bool {
+        self.get_bool_setting(BALLISTA_BEST_EFFORT)
+    }
+
+    pub fn job_priority(&self) -> u32 {
+        self.get_usize_setting(BALLISTA_JOB_PRIORITY) as u32
+    }
+
+    pub fn job_webhook_url(&self) -> Option<String> {
+        let url = self.get_string_setting(BALLISTA_JOB_WEBHOOK_URL);
+        (!url.is_empty()).then_some(url)
+    }
+
+    pub fn job_max_tasks_per_executor(&self) -> usize {
+        self.get_usize_setting(BALLISTA_JOB_MAX_TASKS_PER_EXECUTOR)
+    }
+
+    /// Object store under which the jobs get their staging prefix, if any
+    pub fn job_staging_url(&self) -> Option<String> {
+        let url = self.get_string_setting(BALLISTA_JOB_STAGING_URL);
+        (!url.is_empty()).then_some(url)
+    }
+
+    /// Resource profile of the scheduler the jobs are submitted with, if any
+    pub fn job_resource_profile(&self) -> Option<String> {
+        let profile = self.get_string_setting(BALLISTA_JOB_RESOURCE_PROFILE);
+        (!profile.is_empty()).then_some(profile)
+    }
+
+    /// Reservation of executor slots the jobs run with, if any
+    pub fn job_slot_reservation(&self) -> Option<String> {
+        let reservation = self.get_string_setting(BALLISTA_JOB_SLOT_RESERVATION);
+        (!reservation.is_empty()).then_some(reservation)
+    }
+
+    pub fn job_result_ttl_seconds(&self) -> usize {
+        self.get_usize_setting(BALLISTA_JOB_RESULT_TTL)
+    }
+
+    pub fn stage_timeout_seconds(&self) -> usize {
+        self.get_usize_setting(BALLISTA_STAGE_TIMEOUT)
+    }
+
+    pub fn merge_schemas(&self) -> bool {
+        self.get_bool_setting(BALLISTA_SCAN_MERGE_SCHEMAS)
+    }
+
+    pub fn splittable_scans(&self) -> bool {
+        self.get_bool_setting(BALLISTA_SCAN_SPLITTABLE)
+    }
+
+    pub fn prefer_sort_merge_join(&self) -> bool {
+        self.get_bool_setting(BALLISTA_JOIN_PREFER_SORT_MERGE)
+    }
+
+    pub fn pre_aggregate_grouping_sets(&self) -> bool {
+        self.get_bool_setting(BALLISTA_OPTIMIZER_PRE_AGGREGATE_GROUPING_SETS)
+    }
+
+    pub fn broadcast_join_threshold(&self) -> usize {
+        self.get_usize_setting(BALLISTA_JOIN_BROADCAST_THRESHOLD_BYTES)
+    }
+
+    /// Factor correcting the expected compression ratios, if set
+    pub fn compression_ratio_scale(&self) -> ParseResult<Option<f64>> {
+        let scale = self.get_string_setting(BALLISTA_OPTIMIZER_COMPRESSION_RATIO_SCALE);
+        if scale.is_empty() {
+            return Ok(None);
+        }
+        match scale.parse::<f64>() {
+            Ok(scale) if scale > 0.0 => Ok(Some(scale)),
+            _ => Err(format!(
+                "Invalid compression ratio scale {scale}, expected a positive number"
+            )),
+        }
+    }
+
+    pub fn network_bytes_per_second(&self) -> u64 {
+        self.get_usize_setting(BALLISTA_OPTIMIZER_NETWORK_BYTES_PER_SECOND) as u64
+    }
+
+    pub fn historical_statistics(&self) -> bool {
+        self.get_bool_setting(BALLISTA_OPTIMIZER_HISTORICAL_STATISTICS)
+    }
+
+    /// Sizes of the tables the scans without statistics are planned with, by location
+    pub fn table_statistics(&self) -> ParseResult<Vec<(String, TableSize)>> {
+        let statistics = self.get_string_setting(BALLISTA_OPTIMIZER_TABLE_STATISTICS);
+        split_list(&statistics)
+            .into_iter()
+            .map(|entry| {
+                let invalid = || {
+                    format!(
+                        "Invalid table statistics {entry}, expected location=rows:bytes"
+                    )
+                };
+                let (location, size) = entry.rsplit_once('=').ok_or_else(invalid)?;
+                let (rows, bytes) = size.split_once(':').ok_or_else(invalid)?;
+                let size = TableSize {
+                    rows: rows.trim().parse().map_err(|_| invalid())?,
+                    bytes: bytes.trim().parse().map_err(|_| invalid())?,
+                };
+                Ok((location.trim().to_owned(), size))
+            })
+            .collect()
+    }
+
+    pub fn explain_format(&self) -> ParseResult<ExplainFormat> {
+        let format = self.get_string_setting(BALLISTA_EXPLAIN_FORMAT);
+        format.parse().map_err(|_| {
+            format!("Invalid explain format {format}, expected text or json")
+        })
+    }
+
+    pub fn shuffle_compression(&self) -> ParseResult<ShuffleCompression> {
+        let compression = self.get_string_setting(BALLISTA_SHUFFLE_COMPRESSION);
+        compression.parse().map_err(|_| {
+            format!(
+                "Invalid shuffle compression {compression}, expected lz4, zstd or uncompressed"
+            )
+        })
+    }
+
+    /// Codec of the compression codec registry the shuffle partitions are sent with,
+    /// if not the codec of their shuffle files
+    pub fn shuffle_transfer_compression(&self) -> Option<String> {
+        let codec = self.get_string_setting(BALLISTA_SHUFFLE_TRANSFER_COMPRESSION);
+        (!codec.is_empty()).then_some(codec)
+    }
+
+    /// Object store which map tasks upload their shuffle files to, if any
+    pub fn shuffle_object_store_url(&self) -> Option<String> {
+        let url = self.get_string_setting(BALLISTA_SHUFFLE_OBJECT_STORE_URL);
+        (!url.is_empty()).then_some(url)
+    }
+
+    pub fn coalesce_partitions(&self) -> bool {
+        self.get_bool_setting(BALLISTA_SHUFFLE_COALESCE_PARTITIONS)
+    }
+
+    pub fn target_partition_bytes(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_TARGET_PARTITION_BYTES)
+    }
+
+    pub fn shuffle_max_concurrent_fetches(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_MAX_CONCURRENT_FETCHES)
+    }
+
+    pub fn shuffle_fetch_retries(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_FETCH_RETRIES)
+    }
+
+    pub fn shuffle_fetch_retry_backoff_ms(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_FETCH_RETRY_BACKOFF_MS)
+    }
+
+    pub fn shuffle_max_buffered_batches(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_MAX_BUFFERED_BATCHES)
+    }
+
+    /// Type set for a partition column, by its own entry or else by the `*` entry
+    pub fn partition_column_type(&self, column: &str) -> ParseResult<Option<DataType>> {
+        let types = self.get_string_setting(BALLISTA_SCAN_PARTITION_COLUMN_TYPES);
+        let mut default_type = None;
+        for entry in split_list(&types) {
+            let (name, data_type) = entry.split_once(':').ok_or_else(|| {
+                format!("Invalid partition column type {entry}, expected column:type")
+            })?;
+            let name = name.trim();
+            if name != column && name != "*" {
+                continue;
+            }
+
+            let data_type = data_type
+                .trim()
+                .parse::<DataType>()
+                .map_err(|e| format!("Invalid type of partition column {name}: {e}"))?;
+            if name == column {
+                return Ok(Some(data_type));
+            }
+            default_type = Some(data_type);
+        }
+
+        Ok(default_type)
+    }
+
     fn get_usize_setting(&self, key: &str) -> usize {
         if let Some(v) = self.settings.get(key) {
             // infallible because we validate all configs in the constructor
@@ -179,7 +641,6 @@ impl BallistaConfig {
         }
     }
 
-    #[allow(dead_code)]
     fn get_bool_setting(&self, key: &str) -> bool {
         if let Some(v) = self.settings.get(key) {
             // infallible because we validate all configs in the constructor
@@ -191,7 +652,6 @@ impl BallistaConfig {
             v.parse::<bool>().unwrap()
         }
     }
-    #[allow(dead_code)]
     fn get_string_setting(&self, key: &str) -> String {
         if let Some(v) = self.settings.get(key) {
             // infallible because we validate all configs in the constructor
@@ -296,6 +756,189 @@ impl parse_arg::ParseArgFromStr for LogRotationPolicy {
     }
 }
 
+/// Arrow IPC metadata version the shuffle partitions are written in. Executors of
+/// other versions may write or read other versions, see
+/// [ShuffleFormats](crate::serde::scheduler::ShuffleFormats)
+pub const SHUFFLE_IPC_METADATA_VERSION: u32 = MetadataVersion::V5.0 as u32;
+
+/// Codec compressing the Arrow IPC shuffle files, and the shuffle partitions fetched
+/// over Flight
+#[derive(Clone, ValueEnum, Copy, Debug, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShuffleCompression {
+    #[default]
+    Lz4,
+    Zstd,
+    #[value(alias = "none")]
+    Uncompressed,
+}
+
+impl ShuffleCompression {
+    /// Compression of the Arrow IPC batches
+    pub fn ipc_compression(&self) -> Option<CompressionType> {
+        match self {
+            ShuffleCompression::Lz4 => Some(CompressionType::LZ4_FRAME),
+            ShuffleCompression::Zstd => Some(CompressionType::ZSTD),
+            ShuffleCompression::Uncompressed => None,
+        }
+    }
+
+    /// Options of the Arrow IPC writers of shuffle partitions compressed with the
+    /// codec, in Arrow IPC metadata version `ipc_metadata_version`, or in
+    /// [SHUFFLE_IPC_METADATA_VERSION] if 0. Versions before V5 don't support
+    /// compression, so their batches are left uncompressed
+    pub fn ipc_write_options(
+        &self,
+        ipc_metadata_version: u32,
+    ) -> result::Result<IpcWriteOptions, ArrowError> {
+        let version = match ipc_metadata_version {
+            0 => SHUFFLE_IPC_METADATA_VERSION,
+            version => version,
+        };
+        let version = i16::try_from(version).map(MetadataVersion).map_err(|_| {
+            ArrowError::InvalidArgumentError(format!(
+                "Invalid Arrow IPC metadata version {version}"
+            ))
+        })?;
+        let compression = if version < MetadataVersion::V5 {
+            None
+        } else {
+            self.ipc_compression()
+        };
+        IpcWriteOptions::try_new(64, false, version)?.try_with_compression(compression)
+    }
+}
+
+impl std::str::FromStr for ShuffleCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ValueEnum::from_str(s, true)
+    }
+}
+
+impl fmt::Display for ShuffleCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShuffleCompression::Lz4 => write!(f, "lz4"),
+            ShuffleCompression::Zstd => write!(f, "zstd"),
+            ShuffleCompression::Uncompressed => write!(f, "uncompressed"),
+        }
+    }
+}
+
+/// Format of the distributed plan shown by `EXPLAIN`
+#[derive(Clone, ValueEnum, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExplainFormat {
+    /// The plan of each stage, as indented text
+    #[default]
+    Text,
+    /// A JSON document of the stages, operators, partitioning and estimated statistics
+    Json,
+}
+
+impl std::str::FromStr for ExplainFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ValueEnum::from_str(s, true)
+    }
+}
+
+impl fmt::Display for ExplainFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplainFormat::Text => write!(f, "text"),
+            ExplainFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Value shown in place of the values of secret settings
+pub const REDACTED_VALUE: &str = "<redacted>";
+
+/// Patterns of the keys of secret settings, matched case-insensitively anywhere in the
+/// key once its `-` and `.` separators are read as `_`, e.g. `aws.secret_access_key`,
+/// the `x-api-key` header, a fetch token or the `authorization` header of a Flight table
+const SECRET_KEY_PATTERNS: &[&str] = &[
+    "secret",
+    "token",
+    "password",
+    "passwd",
+    "credential",
+    "private_key",
+    "api_key",
+    "apikey",
+    "authorization",
+];
+
+/// Whether the value of a setting, option or header is a secret, which is redacted
+/// wherever the settings are shown: logs, status APIs, the UI and the event log
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase().replace(['-', '.'], "_");
+    SECRET_KEY_PATTERNS
+        .iter()
+        .any(|pattern| key.contains(pattern))
+}
+
+/// The value of a setting as it is shown, redacted if its key names a secret
+pub fn redact_value<'a>(key: &str, value: &'a str) -> &'a str {
+    if is_secret_key(key) {
+        REDACTED_VALUE
+    } else {
+        value
+    }
+}
+
+/// The settings as they are shown, with the values of the secret ones redacted
+pub fn redact_key_value_pairs(settings: &[KeyValuePair]) -> Vec<KeyValuePair> {
+    settings
+        .iter()
+        .map(|KeyValuePair { key, value }| KeyValuePair {
+            key: key.clone(),
+            value: redact_value(key, value).to_owned(),
+        })
+        .collect()
+}
+
+/// A map of settings, options or headers as it is shown, with the values of the secret
+/// ones redacted
+pub fn redact_settings<'a, C>(
+    settings: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> C
+where
+    C: FromIterator<(String, String)>,
+{
+    settings
+        .into_iter()
+        .map(|(key, value)| (key.clone(), redact_value(key, value).to_owned()))
+        .collect()
+}
+
+/// Split a comma-separated list, ignoring the commas between parentheses
+fn split_list(list: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let (mut depth, mut start) = (0i32, 0);
+    for (i, c) in list.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&list[start..]);
+
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -304,6 +947,206 @@ mod tests {
     fn default_config() -> Result<()> {
         let config = BallistaConfig::default();
         assert_eq!(16777216, config.default_grpc_client_max_message_size());
+        assert_eq!(1000, config.shuffle_sort_based_partition_threshold());
+        assert_eq!(0, config.inline_result_max_bytes());
+        assert!(!config.verify_stage(1));
+        assert!(!config.checksum_results());
+        assert!(!config.deterministic());
+        assert_eq!(0, config.max_result_rows());
+        assert!(!config.best_effort());
+        assert_eq!(None, config.job_webhook_url());
+        assert_eq!(None, config.job_resource_profile());
+        assert_eq!(None, config.job_slot_reservation());
+        assert_eq!(0, config.job_result_ttl_seconds());
+        assert_eq!(0, config.job_max_tasks_per_executor());
+        assert_eq!(0, config.stage_timeout_seconds());
+        assert!(!config.merge_schemas());
+        assert!(!config.splittable_scans());
+        assert!(!config.prefer_sort_merge_join());
+        assert!(!config.pre_aggregate_grouping_sets());
+        assert_eq!(Ok(None), config.partition_column_type("year"));
+        assert_eq!(Ok(ShuffleCompression::Lz4), config.shuffle_compression());
+        assert_eq!(None, config.shuffle_transfer_compression());
+        assert_eq!(50, config.shuffle_max_concurrent_fetches());
+        assert_eq!(3, config.shuffle_fetch_retries());
+        assert_eq!(Ok(ExplainFormat::Text), config.explain_format());
+        Ok(())
+    }
+
+    #[test]
+    fn shuffle_compression() -> Result<()> {
+        let settings = HashMap::from([(
+            BALLISTA_SHUFFLE_COMPRESSION.to_string(),
+            "ZSTD".to_string(),
+        )]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert_eq!(Ok(ShuffleCompression::Zstd), config.shuffle_compression());
+
+        let settings = HashMap::from([(
+            BALLISTA_SHUFFLE_COMPRESSION.to_string(),
+            "gzip".to_string(),
+        )]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert!(config.shuffle_compression().is_err());
+
+        let settings = HashMap::from([(
+            BALLISTA_SHUFFLE_COMPRESSION.to_string(),
+            "none".to_string(),
+        )]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert_eq!(
+            Ok(ShuffleCompression::Uncompressed),
+            config.shuffle_compression()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shuffle_ipc_write_options() -> Result<()> {
+        use datafusion::arrow::array::{ArrayRef, Int32Array, RecordBatch};
+        use datafusion::arrow::ipc::reader::StreamReader;
+        use datafusion::arrow::ipc::writer::StreamWriter;
+        use std::sync::Arc;
+
+        let batch = RecordBatch::try_from_iter([(
+            "a",
+            Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef,
+        )])?;
+        // batches written for readers of V4 are left uncompressed
+        for version in [
+            0,
+            SHUFFLE_IPC_METADATA_VERSION,
+            MetadataVersion::V4.0 as u32,
+        ] {
+            let options = ShuffleCompression::Zstd.ipc_write_options(version)?;
+            let mut data = vec![];
+            let mut writer =
+                StreamWriter::try_new_with_options(&mut data, &batch.schema(), options)?;
+            writer.write(&batch)?;
+            writer.finish()?;
+            drop(writer);
+
+            let batches = StreamReader::try_new(data.as_slice(), None)?
+                .collect::<result::Result<Vec<_>, _>>()?;
+            assert_eq!(vec![batch.clone()], batches);
+        }
+        assert!(ShuffleCompression::Lz4.ipc_write_options(u32::MAX).is_err());
         Ok(())
     }
+
+    #[test]
+    fn verify_stages() -> Result<()> {
+        let settings =
+            HashMap::from([(BALLISTA_VERIFY_STAGES.to_string(), "1, 3".to_string())]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert!(config.verify_stage(1));
+        assert!(!config.verify_stage(2));
+        assert!(config.verify_stage(3));
+
+        let settings =
+            HashMap::from([(BALLISTA_VERIFY_STAGES.to_string(), "*".to_string())]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert!(config.verify_stage(2));
+        Ok(())
+    }
+
+    #[test]
+    fn partition_column_types() -> Result<()> {
+        let settings = HashMap::from([(
+            BALLISTA_SCAN_PARTITION_COLUMN_TYPES.to_string(),
+            "year:Int32, price:Decimal128(10, 2), *:Utf8".to_string(),
+        )]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert_eq!(
+            Ok(Some(DataType::Int32)),
+            config.partition_column_type("year")
+        );
+        assert_eq!(
+            Ok(Some(DataType::Decimal128(10, 2))),
+            config.partition_column_type("price")
+        );
+        assert_eq!(
+            Ok(Some(DataType::Utf8)),
+            config.partition_column_type("month")
+        );
+
+        let settings = HashMap::from([(
+            BALLISTA_SCAN_PARTITION_COLUMN_TYPES.to_string(),
+            "year:Integer".to_string(),
+        )]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert!(config.partition_column_type("year").is_err());
+        assert_eq!(Ok(None), config.partition_column_type("month"));
+        Ok(())
+    }
+
+    #[test]
+    fn table_statistics() -> Result<()> {
+        assert_eq!(Ok(vec![]), BallistaConfig::default().table_statistics());
+
+        let settings = HashMap::from([(
+            BALLISTA_OPTIMIZER_TABLE_STATISTICS.to_string(),
+            "s3://bucket/orders=1000:64000, file:///data/t=5:40".to_string(),
+        )]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert_eq!(
+            Ok(vec![
+                ("s3://bucket/orders".to_owned(), TableSize::new(1000, 64000)),
+                ("file:///data/t".to_owned(), TableSize::new(5, 40)),
+            ]),
+            config.table_statistics()
+        );
+
+        let settings = HashMap::from([(
+            BALLISTA_OPTIMIZER_TABLE_STATISTICS.to_string(),
+            "s3://bucket/orders=1000".to_string(),
+        )]);
+        let config = BallistaConfig::with_settings(settings)?;
+        assert!(config.table_statistics().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn redact_secrets() {
+        for key in [
+            "aws.secret_access_key",
+            "ballista.fetch_token",
+            "datafusion.catalog.PASSWORD",
+            "Authorization",
+            "gcp.service_account_credentials",
+            "x-api-key",
+            "gcs.private-key",
+            "tls.private.key",
+            "openai.api.key",
+        ] {
+            assert!(is_secret_key(key), "{key}");
+            assert_eq!(REDACTED_VALUE, redact_value(key, "hunter2"));
+        }
+        assert!(!is_secret_key(BALLISTA_JOB_NAME));
+        assert_eq!(
+            "4",
+            redact_value("datafusion.execution.target_partitions", "4")
+        );
+
+        let pairs = redact_key_value_pairs(&[
+            KeyValuePair {
+                key: BALLISTA_JOB_NAME.to_owned(),
+                value: "report".to_owned(),
+            },
+            KeyValuePair {
+                key: "aws.secret_access_key".to_owned(),
+                value: "hunter2".to_owned(),
+            },
+        ]);
+        assert_eq!("report", pairs[0].value);
+        assert_eq!(REDACTED_VALUE, pairs[1].value);
+
+        let headers = HashMap::from([
+            ("authorization".to_owned(), "Bearer abc".to_owned()),
+            ("x-tenant".to_owned(), "acme".to_owned()),
+        ]);
+        let redacted: HashMap<String, String> = redact_settings(&headers);
+        assert_eq!(REDACTED_VALUE, redacted["authorization"]);
+        assert_eq!("acme", redacted["x-tenant"]);
+    }
 }
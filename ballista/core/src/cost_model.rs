@@ -0,0 +1,501 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cost of the exchanges of the distributed plans, i.e. of the data the tasks send
+//! each other over the network.
+//!
+//! DataFusion estimates the sizes of the inputs of joins and aggregations in memory,
+//! while the shuffled and broadcast partitions cross the network compressed, by a
+//! ratio which depends on the types of their columns and on the codec. The cost
+//! model weighs the expected compression ratio of each type of column by its width
+//! in memory to estimate the bytes sent over the network, and scales the broadcast
+//! threshold and the partial aggregations by the bandwidth of the network.
+//!
+//! Both the compression ratios and the bandwidth are calibrated by the scheduler, the
+//! former from the bytes shuffled by the completed jobs, the latter from the network
+//! throughput the executors report with their heartbeats, unless the sessions set
+//! them with `ballista.optimizer.compression_ratio_scale` and
+//! `ballista.optimizer.network_bytes_per_second`.
+
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::{DataType, Schema};
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::error::Result;
+use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+use datafusion::physical_plan::joins::{HashJoinExec, PartitionMode};
+use datafusion::physical_plan::repartition::RepartitionExec;
+use datafusion::physical_plan::{ExecutionPlan, Partitioning};
+use datafusion::prelude::SessionConfig;
+use log::debug;
+
+use crate::config::ShuffleCompression;
+use crate::extension::SessionConfigExt;
+
+/// Network bandwidth the broadcast threshold and the partial aggregations are tuned
+/// for, 1 Gbit/s
+pub const REFERENCE_NETWORK_BYTES_PER_SECOND: u64 = 125_000_000;
+
+/// Bounds of the factor scaling the broadcast threshold by the network bandwidth
+const MIN_BANDWIDTH_SCALE: f64 = 0.25;
+const MAX_BANDWIDTH_SCALE: f64 = 4.0;
+
+/// Bounds of the calibrated factor correcting the expected compression ratios
+pub const MIN_COMPRESSION_RATIO_SCALE: f64 = 0.25;
+pub const MAX_COMPRESSION_RATIO_SCALE: f64 = 4.0;
+
+/// Class of columns compressing alike
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnClass {
+    Boolean,
+    Integer,
+    Float,
+    Decimal,
+    Temporal,
+    String,
+    Binary,
+    Other,
+}
+
+impl ColumnClass {
+    pub fn of(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::Boolean => ColumnClass::Boolean,
+            DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64 => ColumnClass::Integer,
+            DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+                ColumnClass::Float
+            }
+            DataType::Decimal128(_, _) | DataType::Decimal256(_, _) => {
+                ColumnClass::Decimal
+            }
+            DataType::Date32
+            | DataType::Date64
+            | DataType::Time32(_)
+            | DataType::Time64(_)
+            | DataType::Timestamp(_, _)
+            | DataType::Duration(_)
+            | DataType::Interval(_) => ColumnClass::Temporal,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {
+                ColumnClass::String
+            }
+            DataType::Binary
+            | DataType::LargeBinary
+            | DataType::BinaryView
+            | DataType::FixedSizeBinary(_) => ColumnClass::Binary,
+            DataType::Dictionary(_, value_type) => Self::of(value_type),
+            _ => ColumnClass::Other,
+        }
+    }
+
+    /// Expected size of the column compressed with `codec` over its size in memory.
+    /// Strings and low-cardinality integers compress well, floats and binary data,
+    /// often already compressed, hardly do
+    pub fn compression_ratio(&self, codec: ShuffleCompression) -> f64 {
+        match (codec, self) {
+            (ShuffleCompression::Uncompressed, _) => 1.0,
+            (ShuffleCompression::Lz4, ColumnClass::Boolean) => 0.5,
+            (ShuffleCompression::Lz4, ColumnClass::Integer) => 0.45,
+            (ShuffleCompression::Lz4, ColumnClass::Float) => 0.8,
+            (ShuffleCompression::Lz4, ColumnClass::Decimal) => 0.5,
+            (ShuffleCompression::Lz4, ColumnClass::Temporal) => 0.4,
+            (ShuffleCompression::Lz4, ColumnClass::String) => 0.35,
+            (ShuffleCompression::Lz4, ColumnClass::Binary) => 0.85,
+            (ShuffleCompression::Lz4, ColumnClass::Other) => 0.6,
+            (ShuffleCompression::Zstd, ColumnClass::Boolean) => 0.35,
+            (ShuffleCompression::Zstd, ColumnClass::Integer) => 0.3,
+            (ShuffleCompression::Zstd, ColumnClass::Float) => 0.7,
+            (ShuffleCompression::Zstd, ColumnClass::Decimal) => 0.35,
+            (ShuffleCompression::Zstd, ColumnClass::Temporal) => 0.25,
+            (ShuffleCompression::Zstd, ColumnClass::String) => 0.22,
+            (ShuffleCompression::Zstd, ColumnClass::Binary) => 0.75,
+            (ShuffleCompression::Zstd, ColumnClass::Other) => 0.45,
+        }
+    }
+}
+
+/// Expected width in memory of a value of a column, in bytes, weighing its
+/// compression ratio in that of the rows
+fn value_width(data_type: &DataType) -> f64 {
+    match data_type {
+        DataType::Boolean => 0.125,
+        DataType::Utf8 | DataType::Binary => 36.0,
+        DataType::LargeUtf8 | DataType::LargeBinary => 40.0,
+        DataType::Utf8View | DataType::BinaryView => 48.0,
+        DataType::FixedSizeBinary(size) => *size as f64,
+        DataType::Dictionary(key_type, _) => value_width(key_type),
+        data_type => data_type
+            .primitive_width()
+            .map(|width| width as f64)
+            .unwrap_or(16.0),
+    }
+}
+
+/// Expected width in memory of the rows of `schema`, in bytes
+pub fn row_width(schema: &Schema) -> f64 {
+    schema
+        .fields()
+        .iter()
+        .map(|field| value_width(field.data_type()))
+        .sum()
+}
+
+/// Cost model of the exchanges of a job
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeCostModel {
+    codec: ShuffleCompression,
+    compression_ratio_scale: f64,
+    network_bytes_per_second: u64,
+}
+
+impl ExchangeCostModel {
+    /// The cost model of exchanges compressed with `codec`, with the expected
+    /// compression ratios and over a network of [REFERENCE_NETWORK_BYTES_PER_SECOND]
+    pub fn new(codec: ShuffleCompression) -> Self {
+        Self {
+            codec,
+            compression_ratio_scale: 1.0,
+            network_bytes_per_second: REFERENCE_NETWORK_BYTES_PER_SECOND,
+        }
+    }
+
+    /// The cost model of the exchanges of the jobs of a session, compressed with the
+    /// transfer codec of the session if Arrow IPC supports it, or else with the codec
+    /// of its shuffle files
+    pub fn from_config(config: &SessionConfig) -> Self {
+        let codec = config
+            .ballista_shuffle_transfer_compression()
+            .and_then(|codec| codec.parse().ok())
+            .or_else(|| config.ballista_shuffle_compression().ok())
+            .unwrap_or_default();
+        let mut model = Self::new(codec);
+        if let Ok(Some(scale)) = config.ballista_compression_ratio_scale() {
+            model = model.with_compression_ratio_scale(scale);
+        }
+        match config.ballista_network_bytes_per_second() {
+            0 => model,
+            bandwidth => model.with_network_bytes_per_second(bandwidth),
+        }
+    }
+
+    /// Correct the expected compression ratios by `scale`, the ratio of the
+    /// compression ratios observed to those expected
+    pub fn with_compression_ratio_scale(mut self, scale: f64) -> Self {
+        self.compression_ratio_scale =
+            scale.clamp(MIN_COMPRESSION_RATIO_SCALE, MAX_COMPRESSION_RATIO_SCALE);
+        self
+    }
+
+    pub fn with_network_bytes_per_second(mut self, bandwidth: u64) -> Self {
+        self.network_bytes_per_second = bandwidth.max(1);
+        self
+    }
+
+    pub fn codec(&self) -> ShuffleCompression {
+        self.codec
+    }
+
+    pub fn network_bytes_per_second(&self) -> u64 {
+        self.network_bytes_per_second
+    }
+
+    /// Expected size of rows of `schema` compressed with the codec of the model over
+    /// their size in memory, without the correction of the calibration
+    pub fn expected_compression_ratio(&self, schema: &Schema) -> f64 {
+        let mut compressed = 0.0;
+        let mut total = 0.0;
+        for field in schema.fields() {
+            let width = value_width(field.data_type());
+            let class = ColumnClass::of(field.data_type());
+            compressed += width * class.compression_ratio(self.codec);
+            total += width;
+        }
+        if total > 0.0 {
+            compressed / total
+        } else {
+            1.0
+        }
+    }
+
+    /// Expected size of rows of `schema` sent over the network over their size in
+    /// memory, at most 1
+    pub fn compression_ratio(&self, schema: &Schema) -> f64 {
+        (self.expected_compression_ratio(schema) * self.compression_ratio_scale).min(1.0)
+    }
+
+    /// Lowest compression ratio the model expects of any row
+    pub fn min_compression_ratio(&self) -> f64 {
+        [
+            ColumnClass::Boolean,
+            ColumnClass::Integer,
+            ColumnClass::Float,
+            ColumnClass::Decimal,
+            ColumnClass::Temporal,
+            ColumnClass::String,
+            ColumnClass::Binary,
+            ColumnClass::Other,
+        ]
+        .iter()
+        .map(|class| {
+            (class.compression_ratio(self.codec) * self.compression_ratio_scale).min(1.0)
+        })
+        .fold(1.0, f64::min)
+    }
+
+    /// Expected bytes sent over the network for rows of `schema` of `memory_bytes`
+    pub fn network_bytes(&self, schema: &Schema, memory_bytes: usize) -> usize {
+        (memory_bytes as f64 * self.compression_ratio(schema)) as usize
+    }
+
+    /// Factor of the network bandwidth over [REFERENCE_NETWORK_BYTES_PER_SECOND]
+    fn bandwidth_scale(&self) -> f64 {
+        self.network_bytes_per_second as f64 / REFERENCE_NETWORK_BYTES_PER_SECOND as f64
+    }
+
+    /// Max bytes of the build side of a hash join sent over the network for it to
+    /// be broadcast, from `threshold_bytes` over the reference network. Each task of
+    /// the join fetches the whole broadcast side, so that faster networks broadcast
+    /// larger build sides, and slower ones smaller ones
+    pub fn broadcast_threshold(&self, threshold_bytes: usize) -> usize {
+        let scale = self
+            .bandwidth_scale()
+            .clamp(MIN_BANDWIDTH_SCALE, MAX_BANDWIDTH_SCALE);
+        (threshold_bytes as f64 * scale) as usize
+    }
+
+    /// Ratio of the groups over the rows of the input of a partial aggregation above
+    /// which the aggregation is skipped, from `threshold` over the reference network.
+    /// Over slower networks, partial aggregations reducing their input less are kept,
+    /// since each row they save is costlier to shuffle
+    pub fn skip_partial_aggregation_ratio(&self, threshold: f64) -> f64 {
+        let scale = self.bandwidth_scale().min(1.0);
+        1.0 - (1.0 - threshold) * scale
+    }
+}
+
+/// Shuffle both sides of the hash joins collecting a build side expected to send
+/// more than `threshold_bytes` over the network, rather than broadcasting it.
+///
+/// DataFusion picks the joins collecting their build side by its estimated size in
+/// memory, which the planner sets up to cover the build sides which compress enough
+/// to be broadcast. Joins whose output is sorted, or whose build side has no
+/// estimated size, are left as they are.
+pub fn shuffle_oversized_build_sides(
+    plan: Arc<dyn ExecutionPlan>,
+    cost_model: &ExchangeCostModel,
+    threshold_bytes: usize,
+    partitions: usize,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    plan.transform_up(|plan| {
+        let Some(join) = plan.as_any().downcast_ref::<HashJoinExec>() else {
+            return Ok(Transformed::no(plan));
+        };
+        if *join.partition_mode() != PartitionMode::CollectLeft
+            || join.properties().output_ordering().is_some()
+        {
+            return Ok(Transformed::no(plan));
+        }
+        let left_stats = join.left().statistics()?;
+        let Some(memory_bytes) = left_stats.total_byte_size.get_value() else {
+            return Ok(Transformed::no(plan));
+        };
+        let network_bytes =
+            cost_model.network_bytes(&join.left().schema(), *memory_bytes);
+        if network_bytes <= threshold_bytes {
+            return Ok(Transformed::no(plan));
+        }
+
+        debug!(
+            "Shuffling the build side of a hash join of {memory_bytes} bytes, \
+            {network_bytes} bytes over the network, rather than broadcasting it"
+        );
+        let left = match join
+            .left()
+            .as_any()
+            .downcast_ref::<CoalescePartitionsExec>()
+        {
+            Some(coalesce) => coalesce.input().clone(),
+            None => join.left().clone(),
+        };
+        let (left_keys, right_keys) = join.on().iter().cloned().unzip();
+        let left =
+            RepartitionExec::try_new(left, Partitioning::Hash(left_keys, partitions))?;
+        let right = RepartitionExec::try_new(
+            join.right().clone(),
+            Partitioning::Hash(right_keys, partitions),
+        )?;
+        Ok(Transformed::yes(Arc::new(HashJoinExec::try_new(
+            Arc::new(left),
+            Arc::new(right),
+            join.on().to_vec(),
+            join.filter().cloned(),
+            join.join_type(),
+            join.projection.clone(),
+            PartitionMode::Partitioned,
+            join.null_equals_null(),
+        )?)))
+    })
+    .map(|transformed| transformed.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::StringArray;
+    use datafusion::arrow::datatypes::Field;
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::common::JoinType;
+    use datafusion::physical_plan::expressions::col;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use datafusion::physical_plan::ExecutionPlanProperties;
+
+    fn schema(types: &[DataType]) -> Schema {
+        Schema::new(
+            types
+                .iter()
+                .enumerate()
+                .map(|(i, data_type)| {
+                    Field::new(format!("c{i}"), data_type.clone(), true)
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let strings = schema(&[DataType::Utf8]);
+        let floats = schema(&[DataType::Float64]);
+        let lz4 = ExchangeCostModel::new(ShuffleCompression::Lz4);
+        assert_eq!(0.35, lz4.compression_ratio(&strings));
+        assert_eq!(0.8, lz4.compression_ratio(&floats));
+        assert!(
+            ExchangeCostModel::new(ShuffleCompression::Zstd).compression_ratio(&strings)
+                < lz4.compression_ratio(&strings)
+        );
+        assert_eq!(
+            1.0,
+            ExchangeCostModel::new(ShuffleCompression::Uncompressed)
+                .compression_ratio(&strings)
+        );
+
+        // the wide string column weighs more than the integer column
+        let mixed = schema(&[DataType::Utf8, DataType::Int32]);
+        let ratio = lz4.compression_ratio(&mixed);
+        assert!(ratio > 0.35 && ratio < 0.4, "{ratio}");
+        assert_eq!(350, lz4.network_bytes(&strings, 1000));
+
+        // calibrated compression ratios, never over 1
+        let calibrated = lz4.clone().with_compression_ratio_scale(2.0);
+        assert_eq!(0.7, calibrated.compression_ratio(&strings));
+        assert_eq!(1.0, calibrated.compression_ratio(&floats));
+        assert_eq!(0.35, lz4.min_compression_ratio());
+    }
+
+    #[test]
+    fn test_bandwidth() {
+        let model = ExchangeCostModel::new(ShuffleCompression::Lz4);
+        assert_eq!(1000, model.broadcast_threshold(1000));
+        assert_eq!(0.8, model.skip_partial_aggregation_ratio(0.8));
+
+        let fast = model
+            .clone()
+            .with_network_bytes_per_second(2 * REFERENCE_NETWORK_BYTES_PER_SECOND);
+        assert_eq!(2000, fast.broadcast_threshold(1000));
+        assert_eq!(0.8, fast.skip_partial_aggregation_ratio(0.8));
+
+        let slow = model
+            .clone()
+            .with_network_bytes_per_second(REFERENCE_NETWORK_BYTES_PER_SECOND / 2);
+        assert_eq!(500, slow.broadcast_threshold(1000));
+        assert!((slow.skip_partial_aggregation_ratio(0.8) - 0.9).abs() < 1e-9);
+
+        let crawling = model.with_network_bytes_per_second(1);
+        assert_eq!(250, crawling.broadcast_threshold(1000));
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config = SessionConfig::new_with_ballista()
+            .with_ballista_shuffle_compression(ShuffleCompression::Zstd);
+        assert_eq!(
+            ExchangeCostModel::new(ShuffleCompression::Zstd),
+            ExchangeCostModel::from_config(&config)
+        );
+
+        let config = config
+            .with_ballista_shuffle_transfer_compression("none")
+            .with_ballista_compression_ratio_scale(1.5)
+            .with_ballista_network_bytes_per_second(1_000_000);
+        assert_eq!(
+            ExchangeCostModel::new(ShuffleCompression::Uncompressed)
+                .with_compression_ratio_scale(1.5)
+                .with_network_bytes_per_second(1_000_000),
+            ExchangeCostModel::from_config(&config)
+        );
+    }
+
+    #[test]
+    fn test_shuffle_oversized_build_sides() -> Result<()> {
+        let schema = Arc::new(schema(&[DataType::Utf8]));
+        let names: StringArray = (0..100).map(|i| Some(format!("name {i}"))).collect();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(names)])?;
+        let input = || -> Result<Arc<dyn ExecutionPlan>> {
+            Ok(Arc::new(MemoryExec::try_new(
+                &[vec![batch.clone()]],
+                schema.clone(),
+                None,
+            )?))
+        };
+        let on = vec![(col("c0", &schema)?, col("c0", &schema)?)];
+        let join: Arc<dyn ExecutionPlan> = Arc::new(HashJoinExec::try_new(
+            Arc::new(CoalescePartitionsExec::new(input()?)),
+            input()?,
+            on,
+            None,
+            &JoinType::Inner,
+            None,
+            PartitionMode::CollectLeft,
+            false,
+        )?);
+        let memory_bytes = *join.children()[0]
+            .statistics()?
+            .total_byte_size
+            .get_value()
+            .unwrap();
+
+        // strings are expected to compress to 35% of their size with LZ4
+        let model = ExchangeCostModel::new(ShuffleCompression::Lz4);
+        let kept =
+            shuffle_oversized_build_sides(join.clone(), &model, memory_bytes / 2, 4)?;
+        assert!(Arc::ptr_eq(&join, &kept));
+
+        let shuffled = shuffle_oversized_build_sides(join, &model, memory_bytes / 4, 4)?;
+        let shuffled = shuffled.as_any().downcast_ref::<HashJoinExec>().unwrap();
+        assert_eq!(PartitionMode::Partitioned, *shuffled.partition_mode());
+        for side in [shuffled.left(), shuffled.right()] {
+            assert!(side.as_any().is::<RepartitionExec>());
+            assert_eq!(4, side.output_partitioning().partition_count());
+        }
+        Ok(())
+    }
+}
@@ -21,16 +21,130 @@ use std::{
     error::Error,
     fmt::{Display, Formatter},
     io, result,
+    str::FromStr,
 };
 
 use crate::serde::protobuf::failed_task::FailedReason;
-use crate::serde::protobuf::{ExecutionError, FailedTask, FetchPartitionError, IoError};
+use crate::serde::protobuf::{
+    ExecutionError, ExecutorLost, FailedTask, FetchPartitionError, IoError, TaskKilled,
+};
 use datafusion::error::DataFusionError;
 use datafusion::{arrow::error::ArrowError, sql::sqlparser::parser};
 use futures::future::Aborted;
 
 pub type Result<T> = result::Result<T, BallistaError>;
 
+/// gRPC metadata key carrying the [ErrorCode] of a failed request
+pub const BALLISTA_ERROR_CODE_METADATA_KEY: &str = "x-ballista-error-code";
+
+/// gRPC metadata key carrying the address of the leader scheduler, with which read
+/// replicas reject the writes they receive
+pub const LEADER_METADATA_KEY: &str = "x-ballista-leader";
+
+/// Stable error codes carried through protobuf messages, gRPC status metadata,
+/// client errors and logs.
+///
+/// Codes are grouped by range: `1xxx` cluster connectivity, `2xxx` task execution
+/// and shuffle, `3xxx` query planning, `9xxx` internal errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    SchedulerUnreachable,
+    ExecutorUnreachable,
+    GrpcTransport,
+    ExecutionFailed,
+    Cancelled,
+    IoFailure,
+    ShuffleFetchFailed,
+    ExecutorLost,
+    ResultLost,
+    TaskKilled,
+    TaskTimedOut,
+    SqlParsing,
+    PlanningFailed,
+    NotImplemented,
+    Internal,
+    General,
+}
+
+impl ErrorCode {
+    /// The stable string representation, e.g. `BAL-2004`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::SchedulerUnreachable => "BAL-1001",
+            ErrorCode::ExecutorUnreachable => "BAL-1002",
+            ErrorCode::GrpcTransport => "BAL-1003",
+            ErrorCode::ExecutionFailed => "BAL-2001",
+            ErrorCode::Cancelled => "BAL-2002",
+            ErrorCode::IoFailure => "BAL-2003",
+            ErrorCode::ShuffleFetchFailed => "BAL-2004",
+            ErrorCode::ExecutorLost => "BAL-2005",
+            ErrorCode::ResultLost => "BAL-2006",
+            ErrorCode::TaskKilled => "BAL-2007",
+            ErrorCode::TaskTimedOut => "BAL-2008",
+            ErrorCode::SqlParsing => "BAL-3001",
+            ErrorCode::PlanningFailed => "BAL-3002",
+            ErrorCode::NotImplemented => "BAL-3003",
+            ErrorCode::Internal => "BAL-9001",
+            ErrorCode::General => "BAL-9002",
+        }
+    }
+
+    /// Whether an operation failing with this code may succeed if retried.
+    /// Permanent errors should be surfaced to the user right away.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::SchedulerUnreachable
+                | ErrorCode::ExecutorUnreachable
+                | ErrorCode::GrpcTransport
+                | ErrorCode::IoFailure
+                | ErrorCode::ShuffleFetchFailed
+                | ErrorCode::ExecutorLost
+                | ErrorCode::ResultLost
+                | ErrorCode::TaskTimedOut
+        )
+    }
+
+    fn all() -> &'static [ErrorCode] {
+        &[
+            ErrorCode::SchedulerUnreachable,
+            ErrorCode::ExecutorUnreachable,
+            ErrorCode::GrpcTransport,
+            ErrorCode::ExecutionFailed,
+            ErrorCode::Cancelled,
+            ErrorCode::IoFailure,
+            ErrorCode::ShuffleFetchFailed,
+            ErrorCode::ExecutorLost,
+            ErrorCode::ResultLost,
+            ErrorCode::TaskKilled,
+            ErrorCode::TaskTimedOut,
+            ErrorCode::SqlParsing,
+            ErrorCode::PlanningFailed,
+            ErrorCode::NotImplemented,
+            ErrorCode::Internal,
+            ErrorCode::General,
+        ]
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = BallistaError;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        ErrorCode::all()
+            .iter()
+            .find(|code| code.as_str() == s)
+            .copied()
+            .ok_or_else(|| BallistaError::General(format!("Unknown error code {s}")))
+    }
+}
+
 /// Ballista error
 #[derive(Debug)]
 pub enum BallistaError {
@@ -54,6 +168,10 @@ pub enum BallistaError {
     // (executor_id, map_stage_id, map_partition_id, message)
     FetchFailed(String, usize, usize, String),
     Cancelled,
+    TimedOut(String),
+    ExecutorUnreachable(String),
+    ExecutorLost(String),
+    TaskKilled(String),
 }
 
 #[allow(clippy::from_over_into)]
@@ -220,12 +338,122 @@ impl Display for BallistaError {
                 )
             }
             BallistaError::Cancelled => write!(f, "Task cancelled"),
+            BallistaError::TimedOut(desc) => write!(f, "Task timed out: {desc}"),
+            BallistaError::ExecutorUnreachable(desc) => {
+                write!(f, "Executor unreachable: {desc}")
+            }
+            BallistaError::ExecutorLost(desc) => write!(f, "Executor lost: {desc}"),
+            BallistaError::TaskKilled(desc) => write!(f, "Task killed: {desc}"),
         }
     }
 }
 
+impl BallistaError {
+    /// Returns the stable [ErrorCode] classifying this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            BallistaError::NotImplemented(_) => ErrorCode::NotImplemented,
+            BallistaError::General(_) => ErrorCode::General,
+            BallistaError::Internal(_) => ErrorCode::Internal,
+            BallistaError::ArrowError(_) => ErrorCode::ExecutionFailed,
+            BallistaError::DataFusionError(DataFusionError::IoError(_)) => {
+                ErrorCode::IoFailure
+            }
+            BallistaError::DataFusionError(DataFusionError::Plan(_))
+            | BallistaError::DataFusionError(DataFusionError::SchemaError(_, _)) => {
+                ErrorCode::PlanningFailed
+            }
+            BallistaError::DataFusionError(DataFusionError::SQL(_, _)) => {
+                ErrorCode::SqlParsing
+            }
+            BallistaError::DataFusionError(DataFusionError::NotImplemented(_)) => {
+                ErrorCode::NotImplemented
+            }
+            BallistaError::DataFusionError(_) => ErrorCode::ExecutionFailed,
+            BallistaError::SqlError(_) => ErrorCode::SqlParsing,
+            BallistaError::IoError(_) => ErrorCode::IoFailure,
+            BallistaError::TonicError(_) => ErrorCode::GrpcTransport,
+            BallistaError::GrpcError(status) => error_code_from_status(status),
+            BallistaError::GrpcConnectionError(_) => ErrorCode::SchedulerUnreachable,
+            BallistaError::TokioError(_) => ErrorCode::Internal,
+            BallistaError::GrpcActionError(_) => ErrorCode::GrpcTransport,
+            BallistaError::FetchFailed(_, _, _, _) => ErrorCode::ShuffleFetchFailed,
+            BallistaError::Cancelled => ErrorCode::Cancelled,
+            BallistaError::TimedOut(_) => ErrorCode::TaskTimedOut,
+            BallistaError::ExecutorUnreachable(_) => ErrorCode::ExecutorUnreachable,
+            BallistaError::ExecutorLost(_) => ErrorCode::ExecutorLost,
+            BallistaError::TaskKilled(_) => ErrorCode::TaskKilled,
+        }
+    }
+
+    /// Whether the operation which failed with this error may be retried
+    pub fn is_retryable(&self) -> bool {
+        self.code().is_retryable()
+    }
+
+    /// Converts this error to a gRPC status carrying the error code in its metadata
+    pub fn to_grpc_status(&self) -> tonic::Status {
+        self.to_grpc_status_with_message(&self.to_string())
+    }
+
+    /// Converts this error to a gRPC status carrying the error code in its metadata,
+    /// whose message is `message`, e.g. describing the request which failed with it
+    pub fn to_grpc_status_with_message(&self, message: &str) -> tonic::Status {
+        let code = self.code();
+        let message = format!("[{code}] {message}");
+        let mut status = match code {
+            ErrorCode::Cancelled | ErrorCode::TaskKilled => {
+                tonic::Status::cancelled(message)
+            }
+            ErrorCode::SqlParsing | ErrorCode::PlanningFailed => {
+                tonic::Status::invalid_argument(message)
+            }
+            ErrorCode::NotImplemented => tonic::Status::unimplemented(message),
+            ErrorCode::SchedulerUnreachable
+            | ErrorCode::ExecutorUnreachable
+            | ErrorCode::GrpcTransport => tonic::Status::unavailable(message),
+            _ => tonic::Status::internal(message),
+        };
+        status.metadata_mut().insert(
+            BALLISTA_ERROR_CODE_METADATA_KEY,
+            tonic::metadata::MetadataValue::from_static(code.as_str()),
+        );
+        status
+    }
+}
+
+/// Extracts the [ErrorCode] of a gRPC status, falling back to a classification
+/// based on the gRPC code if the status was not produced by Ballista
+pub fn error_code_from_status(status: &tonic::Status) -> ErrorCode {
+    if let Some(code) = status
+        .metadata()
+        .get(BALLISTA_ERROR_CODE_METADATA_KEY)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| ErrorCode::from_str(v).ok())
+    {
+        return code;
+    }
+    match status.code() {
+        tonic::Code::Unknown | tonic::Code::Unavailable => ErrorCode::GrpcTransport,
+        tonic::Code::Cancelled => ErrorCode::Cancelled,
+        tonic::Code::Unimplemented => ErrorCode::NotImplemented,
+        tonic::Code::InvalidArgument => ErrorCode::PlanningFailed,
+        _ => ErrorCode::Internal,
+    }
+}
+
+/// The URL of the leader scheduler a request was rejected in favour of, if any
+pub fn leader_url_from_status(status: &tonic::Status) -> Option<String> {
+    status
+        .metadata()
+        .get(LEADER_METADATA_KEY)
+        .and_then(|v| v.to_str().ok())
+        .map(|leader| format!("http://{leader}"))
+}
+
 impl From<BallistaError> for FailedTask {
     fn from(e: BallistaError) -> Self {
+        let error_code = e.code().to_string();
         match e {
             BallistaError::FetchFailed(
                 executor_id,
@@ -238,6 +466,7 @@ impl From<BallistaError> for FailedTask {
                     // fetch partition error is considered to be non-retryable
                     retryable: false,
                     count_to_failures: false,
+                    error_code,
                     failed_reason: Some(FailedReason::FetchPartitionError(
                         FetchPartitionError {
                             executor_id,
@@ -253,6 +482,7 @@ impl From<BallistaError> for FailedTask {
                     // IO error is considered to be temporary and retryable
                     retryable: true,
                     count_to_failures: true,
+                    error_code,
                     failed_reason: Some(FailedReason::IoError(IoError {})),
                 }
             }
@@ -262,13 +492,53 @@ impl From<BallistaError> for FailedTask {
                     // IO error is considered to be temporary and retryable
                     retryable: true,
                     count_to_failures: true,
+                    error_code,
                     failed_reason: Some(FailedReason::IoError(IoError {})),
                 }
             }
+            BallistaError::TimedOut(desc) => {
+                FailedTask {
+                    error: format!("Task timed out: {desc}"),
+                    // the task may complete on another attempt, e.g. on another executor
+                    retryable: true,
+                    count_to_failures: true,
+                    error_code,
+                    failed_reason: Some(FailedReason::TaskKilled(TaskKilled {})),
+                }
+            }
+            BallistaError::ExecutorUnreachable(desc) => {
+                FailedTask {
+                    error: format!("Task failed due to an unreachable executor: {desc}"),
+                    // the executor may be reachable again, or the task run on another one
+                    retryable: true,
+                    count_to_failures: true,
+                    error_code,
+                    failed_reason: Some(FailedReason::IoError(IoError {})),
+                }
+            }
+            BallistaError::ExecutorLost(desc) => {
+                FailedTask {
+                    error: format!("Task failed due to a lost executor: {desc}"),
+                    // the task is run again on another executor, which is no failure of
+                    // the task itself
+                    retryable: true,
+                    count_to_failures: false,
+                    error_code,
+                    failed_reason: Some(FailedReason::ExecutorLost(ExecutorLost {})),
+                }
+            }
+            BallistaError::TaskKilled(desc) => FailedTask {
+                error: format!("Task killed: {desc}"),
+                retryable: false,
+                count_to_failures: false,
+                error_code,
+                failed_reason: Some(FailedReason::TaskKilled(TaskKilled {})),
+            },
             other => FailedTask {
                 error: format!("Task failed due to runtime execution error: {other:?}"),
                 retryable: false,
                 count_to_failures: false,
+                error_code,
                 failed_reason: Some(FailedReason::ExecutionError(ExecutionError {})),
             },
         }
@@ -276,3 +546,95 @@ impl From<BallistaError> for FailedTask {
 }
 
 impl Error for BallistaError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_code_round_trip() {
+        for code in ErrorCode::all() {
+            assert_eq!(*code, ErrorCode::from_str(code.as_str()).unwrap());
+        }
+        assert!(ErrorCode::from_str("BAL-0000").is_err());
+    }
+
+    #[test]
+    fn error_code_through_grpc_status() {
+        let error = BallistaError::FetchFailed(
+            "executor_1".to_string(),
+            1,
+            2,
+            "connection reset".to_string(),
+        );
+        assert!(error.is_retryable());
+
+        let status = error.to_grpc_status();
+        assert_eq!(
+            ErrorCode::ShuffleFetchFailed,
+            BallistaError::from(status).code()
+        );
+
+        let status = tonic::Status::internal("no metadata");
+        assert_eq!(ErrorCode::Internal, error_code_from_status(&status));
+        assert!(!BallistaError::Cancelled.is_retryable());
+    }
+
+    #[test]
+    fn leader_through_grpc_status() {
+        let mut status = tonic::Status::failed_precondition("read replica");
+        assert_eq!(None, leader_url_from_status(&status));
+        status.metadata_mut().insert(
+            LEADER_METADATA_KEY,
+            tonic::metadata::MetadataValue::from_static("scheduler-1:50050"),
+        );
+        assert_eq!(
+            Some("http://scheduler-1:50050".to_owned()),
+            leader_url_from_status(&status)
+        );
+    }
+
+    #[test]
+    fn failed_task_carries_error_code() {
+        let failed_task: FailedTask =
+            BallistaError::IoError(io::Error::other("disk full")).into();
+        assert_eq!("BAL-2003", failed_task.error_code);
+        assert!(failed_task.retryable);
+
+        let failed_task: FailedTask =
+            BallistaError::ExecutorLost("executor_1".to_string()).into();
+        assert_eq!("BAL-2005", failed_task.error_code);
+        assert!(failed_task.retryable);
+        assert!(!failed_task.count_to_failures);
+        assert!(matches!(
+            failed_task.failed_reason,
+            Some(FailedReason::ExecutorLost(_))
+        ));
+
+        let failed_task: FailedTask =
+            BallistaError::TaskKilled("task 1".to_string()).into();
+        assert_eq!("BAL-2007", failed_task.error_code);
+        assert!(!failed_task.retryable);
+        assert!(matches!(
+            failed_task.failed_reason,
+            Some(FailedReason::TaskKilled(_))
+        ));
+    }
+
+    #[test]
+    fn executor_errors_through_grpc_status() {
+        let error = BallistaError::ExecutorUnreachable("executor_1".to_string());
+        assert!(error.is_retryable());
+        let status = error.to_grpc_status_with_message("Failed to launch tasks");
+        assert_eq!(tonic::Code::Unavailable, status.code());
+        assert_eq!("[BAL-1002] Failed to launch tasks", status.message());
+        assert_eq!(
+            ErrorCode::ExecutorUnreachable,
+            BallistaError::from(status).code()
+        );
+
+        let status = BallistaError::TaskKilled("task 1".to_string()).to_grpc_status();
+        assert_eq!(tonic::Code::Cancelled, status.code());
+        assert_eq!(ErrorCode::TaskKilled, error_code_from_status(&status));
+    }
+}
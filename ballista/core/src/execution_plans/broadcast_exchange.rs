@@ -0,0 +1,322 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Broadcast of the build side of joins collecting their left input, i.e. of hash
+//! joins in `CollectLeft` mode, to all the tasks of the join stage.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_plan::metrics::{
+    ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    collect, DisplayAs, DisplayFormatType, ExecutionPlan, ExecutionPlanProperties,
+    Partitioning, PlanProperties, SendableRecordBatchStream, Statistics,
+};
+use futures::TryStreamExt;
+use log::debug;
+use tokio::sync::OnceCell;
+
+/// BroadcastExchangeExec reads all the partitions of the shuffle written by the stage
+/// of the build side of a join as a single partition, like a [CoalescePartitionsExec]
+/// would, so that every task of the join stage joins its partition of the probe side
+/// with the whole build side.
+///
+/// Executors with a [BroadcastCache] in the config of their tasks fetch the build side
+/// once, and share it among the tasks of the join stage they execute.
+///
+/// [CoalescePartitionsExec]: datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec
+#[derive(Debug, Clone)]
+pub struct BroadcastExchangeExec {
+    job_id: String,
+    /// The stage writing the build side
+    stage_id: usize,
+    /// The shuffle of the build side, an `UnresolvedShuffleExec` until the stage of the
+    /// build side has completed, a `ShuffleReaderExec` afterwards
+    input: Arc<dyn ExecutionPlan>,
+    properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl BroadcastExchangeExec {
+    pub fn new(job_id: String, stage_id: usize, input: Arc<dyn ExecutionPlan>) -> Self {
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(input.schema()),
+            Partitioning::UnknownPartitioning(1),
+            input.execution_mode(),
+        );
+        Self {
+            job_id,
+            stage_id,
+            input,
+            properties,
+            metrics: ExecutionPlanMetricsSet::new(),
+        }
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    pub fn stage_id(&self) -> usize {
+        self.stage_id
+    }
+
+    pub fn input(&self) -> &Arc<dyn ExecutionPlan> {
+        &self.input
+    }
+}
+
+impl DisplayAs for BroadcastExchangeExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "BroadcastExchangeExec: stage_id={}", self.stage_id)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for BroadcastExchangeExec {
+    fn name(&self) -> &str {
+        "BroadcastExchangeExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(BroadcastExchangeExec::new(
+            self.job_id.clone(),
+            self.stage_id,
+            children[0].clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        if partition != 0 {
+            return Err(DataFusionError::Internal(format!(
+                "BroadcastExchangeExec invalid partition {partition}"
+            )));
+        }
+
+        let output_rows = MetricBuilder::new(&self.metrics).output_rows(partition);
+        let input = self.input.clone();
+        let cache = context.session_config().get_extension::<BroadcastCache>();
+        let key = (self.job_id.clone(), self.stage_id);
+        let build_side = async move {
+            match cache {
+                Some(cache) => cache.get_or_fetch(key, input, context).await,
+                None => collect(input, context).await.map(Arc::new),
+            }
+        };
+
+        let stream = futures::stream::once(build_side)
+            .map_ok(move |batches| {
+                output_rows.add(batches.iter().map(|batch| batch.num_rows()).sum());
+                futures::stream::iter(batches.as_ref().clone().into_iter().map(Ok))
+            })
+            .try_flatten();
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+/// Default maximum number of build sides kept by a [BroadcastCache]
+pub const DEFAULT_MAX_BROADCAST_BUILD_SIDES: usize = 16;
+
+/// Key of a broadcast build side: the job and the stage writing the build side
+type BroadcastKey = (String, usize);
+
+type BuildSide = Arc<OnceCell<Arc<Vec<RecordBatch>>>>;
+
+/// Build sides of broadcast joins fetched by an executor, shared by the tasks of the
+/// join stages it executes. Concurrent tasks of a stage wait for the first one to fetch
+/// the build side. An executor puts its cache in the [SessionConfig] of its tasks as an
+/// extension, and drops the build sides of a job once the job is done. The cache is
+/// bounded, the build sides fetched first being dropped first, as executors polling
+/// the scheduler for tasks aren't told when jobs are done.
+///
+/// [SessionConfig]: datafusion::prelude::SessionConfig
+#[derive(Debug)]
+pub struct BroadcastCache {
+    max_build_sides: usize,
+    build_sides: Mutex<VecDeque<(BroadcastKey, BuildSide)>>,
+}
+
+impl Default for BroadcastCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BROADCAST_BUILD_SIDES)
+    }
+}
+
+impl BroadcastCache {
+    pub fn new(max_build_sides: usize) -> Self {
+        Self {
+            max_build_sides,
+            build_sides: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// The build side read by `input`, fetched unless another task already did
+    async fn get_or_fetch(
+        &self,
+        key: BroadcastKey,
+        input: Arc<dyn ExecutionPlan>,
+        context: Arc<TaskContext>,
+    ) -> Result<Arc<Vec<RecordBatch>>> {
+        let build_side = {
+            let mut build_sides = self.build_sides.lock().unwrap();
+            match build_sides
+                .iter()
+                .find(|(cached_key, _)| *cached_key == key)
+            {
+                Some((_, build_side)) => build_side.clone(),
+                None => {
+                    let build_side = BuildSide::default();
+                    if build_sides.len() >= self.max_build_sides {
+                        build_sides.pop_front();
+                    }
+                    if self.max_build_sides > 0 {
+                        build_sides.push_back((key.clone(), build_side.clone()));
+                    }
+                    build_side
+                }
+            }
+        };
+
+        build_side
+            .get_or_try_init(|| async {
+                let (job_id, stage_id) = key;
+                debug!(
+                    "Fetching broadcast build side of stage {stage_id} of job {job_id}"
+                );
+                collect(input, context).await.map(Arc::new)
+            })
+            .await
+            .cloned()
+    }
+
+    /// Number of the build sides of a job which are cached
+    pub fn job_build_sides(&self, job_id: &str) -> usize {
+        self.build_sides
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((cached_job_id, _), _)| cached_job_id == job_id)
+            .count()
+    }
+
+    /// Drop the build sides of a job
+    pub fn remove_job(&self, job_id: &str) {
+        self.build_sides
+            .lock()
+            .unwrap()
+            .retain(|((cached_job_id, _), _)| cached_job_id != job_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::common;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use datafusion::prelude::SessionConfig;
+
+    #[tokio::test]
+    async fn broadcast_all_partitions() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = |values: Vec<i32>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))])
+                .unwrap()
+        };
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![batch(vec![1, 2])], vec![batch(vec![3])]],
+            schema.clone(),
+            None,
+        )?);
+
+        let broadcast = BroadcastExchangeExec::new("job".to_owned(), 1, input);
+        assert_eq!(
+            1,
+            broadcast
+                .properties()
+                .output_partitioning()
+                .partition_count()
+        );
+
+        let cache = Arc::new(BroadcastCache::default());
+        let config = SessionConfig::new().with_extension(cache.clone());
+        let context = Arc::new(TaskContext::default().with_session_config(config));
+        let batches = common::collect(broadcast.execute(0, context.clone())?).await?;
+        assert_eq!(vec![batch(vec![1, 2]), batch(vec![3])], batches);
+        assert_eq!(Some(3), broadcast.metrics().unwrap().output_rows());
+        assert_eq!(1, cache.job_build_sides("job"));
+
+        // other tasks of the stage read the cached build side rather than the input
+        let other_task = BroadcastExchangeExec::new(
+            "job".to_owned(),
+            1,
+            Arc::new(MemoryExec::try_new(&[vec![]], schema.clone(), None)?),
+        );
+        let batches = common::collect(other_task.execute(0, context)?).await?;
+        assert_eq!(vec![batch(vec![1, 2]), batch(vec![3])], batches);
+
+        cache.remove_job("job");
+        assert_eq!(0, cache.job_build_sides("job"));
+        Ok(())
+    }
+}
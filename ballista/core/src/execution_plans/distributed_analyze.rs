@@ -0,0 +1,295 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! `EXPLAIN ANALYZE` of a query running on a Ballista cluster
+
+use crate::execution_plans::distributed_query::submit_query;
+use crate::execution_plans::DistributedQueryExec;
+use crate::extension::SessionConfigExt;
+use crate::serde::protobuf::{GetJobMetricsParams, StageMetrics, TaskMetrics};
+use datafusion::arrow::array::StringBuilder;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning,
+    PlanProperties, SendableRecordBatchStream,
+};
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// DistributedAnalyzeExec runs a query on a Ballista cluster, like
+/// [DistributedQueryExec], discarding its results. It then returns the plan of each
+/// stage of the job, annotated with the metrics the tasks of the stage reported to the
+/// scheduler, in the `plan_type` and `plan` columns of `EXPLAIN ANALYZE`.
+///
+/// The plan of each stage is followed by the elapsed times of its tasks, pointing out
+/// the slowest one, and with `EXPLAIN ANALYZE VERBOSE`, by the metrics of each task.
+#[derive(Debug, Clone)]
+pub struct DistributedAnalyzeExec<T: 'static + AsLogicalPlan> {
+    /// The query to analyze
+    input: DistributedQueryExec<T>,
+    /// Whether to show the metrics of each task
+    verbose: bool,
+    schema: SchemaRef,
+    properties: PlanProperties,
+}
+
+impl<T: 'static + AsLogicalPlan> DistributedAnalyzeExec<T> {
+    pub fn new(input: DistributedQueryExec<T>, verbose: bool, schema: SchemaRef) -> Self {
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(schema.clone()),
+            Partitioning::UnknownPartitioning(1),
+            ExecutionMode::Bounded,
+        );
+        Self {
+            input,
+            verbose,
+            schema,
+            properties,
+        }
+    }
+}
+
+impl<T: 'static + AsLogicalPlan> DisplayAs for DistributedAnalyzeExec<T> {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "DistributedAnalyzeExec: scheduler_url={}, verbose={}",
+                    self.input.scheduler_url(),
+                    self.verbose
+                )
+            }
+        }
+    }
+}
+
+impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedAnalyzeExec<T> {
+    fn name(&self) -> &str {
+        "DistributedAnalyzeExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        assert_eq!(0, partition);
+
+        let query = self.input.query_params(&context)?;
+        let scheduler_url = self.input.scheduler_url().to_owned();
+        let session_id = self.input.session_id().to_owned();
+        let max_message_size = self.input.config().default_grpc_client_max_message_size();
+        let security = context.session_config().ballista_grpc_security();
+        let verbose = self.verbose;
+        let schema = self.schema.clone();
+
+        let batch = async move {
+            let start = Instant::now();
+            let mut results = submit_query(
+                scheduler_url,
+                Some(session_id),
+                query,
+                max_message_size,
+                security,
+            )
+            .await?;
+            let mut output_rows = 0;
+            while let Some(batch) = results.next().await {
+                output_rows += batch?.num_rows();
+            }
+            let elapsed = start.elapsed();
+
+            let job_id = results.job_id().to_owned();
+            let job_metrics = results
+                .scheduler()
+                .get_job_metrics(GetJobMetricsParams {
+                    job_id: job_id.clone(),
+                })
+                .await
+                .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+                .into_inner();
+
+            let mut plan_types = StringBuilder::new();
+            let mut plans = StringBuilder::new();
+            for stage in &job_metrics.stages {
+                plan_types.append_value(format!("Stage {}", stage.stage_id));
+                plans.append_value(format_stage(stage, verbose));
+            }
+            plan_types.append_value("Job");
+            plans.append_value(format!(
+                "job_id={job_id}, stages={}, output_rows={output_rows}, elapsed={}ms",
+                job_metrics.stages.len(),
+                elapsed.as_millis()
+            ));
+
+            RecordBatch::try_new(
+                schema,
+                vec![Arc::new(plan_types.finish()), Arc::new(plans.finish())],
+            )
+            .map_err(DataFusionError::from)
+        };
+
+        let stream = futures::stream::once(batch);
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+}
+
+/// The plan of a stage annotated with its metrics, followed by the elapsed times of its
+/// tasks and, if `verbose`, the metrics of each task
+fn format_stage(stage: &StageMetrics, verbose: bool) -> String {
+    let mut text = String::new();
+    let input_stages = stage
+        .input_stages
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let _ = writeln!(
+        text,
+        "input_stages=[{input_stages}], partitions={}, completed_tasks={}",
+        stage.partitions, stage.completed_tasks
+    );
+    text.push_str(&stage.plan);
+
+    let mut elapsed = stage
+        .tasks
+        .iter()
+        .map(|task| (task_elapsed_ms(task), task))
+        .collect::<Vec<_>>();
+    elapsed.sort_by_key(|(elapsed, _)| *elapsed);
+    if let Some((max, slowest)) = elapsed.last() {
+        let min = elapsed[0].0;
+        let median = elapsed[elapsed.len() / 2].0;
+        let _ = write!(
+            text,
+            "Tasks: elapsed min={min}ms, median={median}ms, max={max}ms (partition {} on executor {})",
+            slowest.partition_id, slowest.executor_id
+        );
+    }
+    if verbose {
+        for task in &stage.tasks {
+            let metrics = task
+                .metrics
+                .iter()
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = write!(
+                text,
+                "\n  partition={}, task_id={}, executor={}, elapsed={}ms, metrics=[{metrics}]",
+                task.partition_id,
+                task.task_id,
+                task.executor_id,
+                task_elapsed_ms(task)
+            );
+        }
+    }
+    text
+}
+
+fn task_elapsed_ms(task: &TaskMetrics) -> u64 {
+    task.end_exec_time.saturating_sub(task.start_exec_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn task(partition_id: u32, start: u64, end: u64) -> TaskMetrics {
+        TaskMetrics {
+            partition_id,
+            task_id: partition_id + 10,
+            executor_id: format!("executor-{partition_id}"),
+            start_exec_time: start,
+            end_exec_time: end,
+            metrics: HashMap::from([
+                ("output_rows".to_owned(), 100),
+                ("spill_count".to_owned(), 1),
+            ]),
+        }
+    }
+
+    #[test]
+    fn format_stage_points_out_slowest_task() {
+        let stage = StageMetrics {
+            stage_id: 2,
+            status: "Successful".to_owned(),
+            partitions: 3,
+            completed_tasks: 3,
+            metrics: HashMap::new(),
+            plan: "ShuffleWriterExec: None, metrics=[output_rows=300]\n".to_owned(),
+            tasks: vec![task(0, 100, 110), task(1, 100, 150), task(2, 100, 120)],
+            input_stages: vec![1],
+        };
+
+        assert_eq!(
+            format_stage(&stage, false),
+            "input_stages=[1], partitions=3, completed_tasks=3\n\
+            ShuffleWriterExec: None, metrics=[output_rows=300]\n\
+            Tasks: elapsed min=10ms, median=20ms, max=50ms (partition 1 on executor executor-1)"
+        );
+
+        let verbose = format_stage(&stage, true);
+        assert!(verbose.ends_with(
+            "\n  partition=2, task_id=12, executor=executor-2, elapsed=20ms, \
+            metrics=[output_rows=100, spill_count=1]"
+        ));
+    }
+}
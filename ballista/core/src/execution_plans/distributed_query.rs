@@ -16,16 +16,24 @@
 // under the License.
 
 use crate::client::BallistaClient;
-use crate::config::BallistaConfig;
-use crate::serde::protobuf::execute_query_params::OptionalSessionId;
+use crate::compression::{CompressionCodec, NoCompression};
+use crate::config::{BallistaConfig, SHUFFLE_IPC_METADATA_VERSION};
+use crate::error::{leader_url_from_status, ErrorCode};
+use crate::extension::SessionConfigExt;
+use crate::recursive_query::encode_recursive_queries;
+use crate::security::GrpcSecurityConfig;
+use crate::serde::protobuf::execute_query_params::{OptionalPriority, OptionalSessionId};
 use crate::serde::protobuf::{
     execute_query_params::Query, execute_query_result, job_status,
-    scheduler_grpc_client::SchedulerGrpcClient, ExecuteQueryParams, GetJobStatusParams,
-    GetJobStatusResult, KeyValuePair, PartitionLocation,
+    scheduler_grpc_client::SchedulerGrpcClient, CancelJobParams, ExecuteQueryParams,
+    GetJobStatusParams, GetJobStatusResult, KeyValuePair, PartitionLocation,
+    SuccessfulJob,
 };
-use crate::utils::create_grpc_client_connection;
+use crate::serde::scheduler::ShuffleFormats;
+use crate::utils::{create_grpc_client_connection, GrpcChannel};
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::ipc::reader::StreamReader;
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::error::{DataFusionError, Result};
 use datafusion::execution::context::TaskContext;
@@ -36,21 +44,26 @@ use datafusion::physical_plan::{
     DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning,
     PlanProperties, SendableRecordBatchStream, Statistics,
 };
+use datafusion::prelude::SessionConfig;
 use datafusion_proto::logical_plan::{
     AsLogicalPlan, DefaultLogicalExtensionCodec, LogicalExtensionCodec,
 };
-use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
-use log::{error, info};
+use futures::{future, Stream, StreamExt, TryFutureExt, TryStreamExt};
+use log::{error, info, warn};
 use std::any::Any;
+use std::collections::{HashSet, VecDeque};
 use std::fmt::Debug;
+use std::io::Cursor;
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::result;
 use std::sync::Arc;
 use std::time::Duration;
 
 /// This operator sends a logical plan to a Ballista scheduler for execution and
-/// polls the scheduler until the query is complete and then fetches the resulting
-/// batches directly from the executors that hold the results from the final
-/// query stage.
+/// polls the scheduler until the query is complete, fetching the resulting batches
+/// directly from the executors that hold the results from the final query stage as
+/// its partitions complete.
 #[derive(Debug, Clone)]
 pub struct DistributedQueryExec<T: 'static + AsLogicalPlan> {
     /// Ballista scheduler URL
@@ -126,6 +139,65 @@ impl<T: 'static + AsLogicalPlan> DistributedQueryExec<T> {
         }
     }
 
+    /// The request submitting the logical plan to the scheduler
+    pub(crate) fn query_params(
+        &self,
+        context: &TaskContext,
+    ) -> Result<ExecuteQueryParams> {
+        let mut buf: Vec<u8> = vec![];
+        // recursive queries can only be serialized as extension nodes
+        let plan = encode_recursive_queries(self.plan.clone())?;
+        let plan_message = T::try_from_logical_plan(&plan, self.extension_codec.as_ref())
+            .map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to serialize logical plan: {e:?}"
+                ))
+            })?;
+        plan_message.try_encode(&mut buf).map_err(|e| {
+            DataFusionError::Execution(format!("failed to encode logical plan: {e:?}"))
+        })?;
+
+        Ok(ExecuteQueryParams {
+            query: Some(Query::LogicalPlan(buf)),
+            settings: session_settings(context.session_config()),
+            optional_session_id: Some(OptionalSessionId::SessionId(
+                self.session_id.clone(),
+            )),
+            optional_priority: Some(OptionalPriority::Priority(
+                context.session_config().ballista_job_priority(),
+            )),
+        })
+    }
+
+    pub(crate) fn scheduler_url(&self) -> &str {
+        &self.scheduler_url
+    }
+
+    /// Submits the query to the scheduler without waiting for it to complete, returning
+    /// the ID of its job. Unlike the stream returned by [ExecutionPlan::execute], which
+    /// cancels the job when it is dropped, the job runs until it completes or is
+    /// cancelled through its ID
+    pub async fn submit(&self, context: Arc<TaskContext>) -> Result<String> {
+        let query = self.query_params(&context)?;
+        let results = submit_query(
+            self.scheduler_url.clone(),
+            Some(self.session_id.clone()),
+            query,
+            self.config.default_grpc_client_max_message_size(),
+            context.session_config().ballista_grpc_security(),
+        )
+        .await?;
+        Ok(results.detach())
+    }
+
+    pub(crate) fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub(crate) fn config(&self) -> &BallistaConfig {
+        &self.config
+    }
+
     fn compute_properties(schema: SchemaRef) -> PlanProperties {
         PlanProperties::new(
             EquivalenceProperties::new(schema),
@@ -198,45 +270,14 @@ impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedQueryExec<T> {
     ) -> Result<SendableRecordBatchStream> {
         assert_eq!(0, partition);
 
-        let mut buf: Vec<u8> = vec![];
-        let plan_message = T::try_from_logical_plan(
-            &self.plan,
-            self.extension_codec.as_ref(),
-        )
-        .map_err(|e| {
-            DataFusionError::Internal(format!("failed to serialize logical plan: {e:?}"))
-        })?;
-        plan_message.try_encode(&mut buf).map_err(|e| {
-            DataFusionError::Execution(format!("failed to encode logical plan: {e:?}"))
-        })?;
-
-        let settings = context
-            .session_config()
-            .options()
-            .entries()
-            .iter()
-            .map(
-                |datafusion::config::ConfigEntry { key, value, .. }| KeyValuePair {
-                    key: key.to_owned(),
-                    value: value.clone().unwrap_or_else(|| String::from("")),
-                },
-            )
-            .collect();
-
-        let query = ExecuteQueryParams {
-            query: Some(Query::LogicalPlan(buf)),
-            settings,
-            optional_session_id: Some(OptionalSessionId::SessionId(
-                self.session_id.clone(),
-            )),
-        };
-
+        let query = self.query_params(&context)?;
         let stream = futures::stream::once(
             execute_query(
                 self.scheduler_url.clone(),
-                self.session_id.clone(),
+                Some(self.session_id.clone()),
                 query,
                 self.config.default_grpc_client_max_message_size(),
+                context.session_config().ballista_grpc_security(),
             )
             .map_err(|e| ArrowError::ExternalError(Box::new(e))),
         )
@@ -254,27 +295,68 @@ impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedQueryExec<T> {
     }
 }
 
-async fn execute_query(
+/// Collects the session settings which are sent to the scheduler along with a query
+pub(crate) fn session_settings(config: &SessionConfig) -> Vec<KeyValuePair> {
+    config
+        .options()
+        .entries()
+        .iter()
+        .map(
+            |datafusion::config::ConfigEntry { key, value, .. }| KeyValuePair {
+                key: key.to_owned(),
+                value: value.clone().unwrap_or_else(|| String::from("")),
+            },
+        )
+        .collect()
+}
+
+/// Submits a query to the scheduler and streams the batches of its final stage as its
+/// partitions complete. Dropping the stream before the query completes cancels it. If
+/// `session_id` is given, the scheduler must run the query in that session.
+pub(crate) async fn execute_query(
     scheduler_url: String,
-    session_id: String,
+    session_id: Option<String>,
     query: ExecuteQueryParams,
     max_message_size: usize,
+    security: Arc<GrpcSecurityConfig>,
 ) -> Result<impl Stream<Item = Result<RecordBatch>> + Send> {
-    info!("Connecting to Ballista scheduler at {}", scheduler_url);
-    // TODO reuse the scheduler to avoid connecting to the Ballista scheduler again and again
-    let connection = create_grpc_client_connection(scheduler_url)
-        .await
-        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+    let results =
+        submit_query(scheduler_url, session_id, query, max_message_size, security)
+            .await?;
 
-    let mut scheduler = SchedulerGrpcClient::new(connection)
-        .max_encoding_message_size(max_message_size)
-        .max_decoding_message_size(max_message_size);
+    Ok(futures::stream::unfold(results, |mut results| async move {
+        results.next().await.map(|batch| (batch, results))
+    }))
+}
 
-    let query_result = scheduler
-        .execute_query(query)
-        .await
-        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
-        .into_inner();
+/// Submits a query to the scheduler, returning the results of the job running it
+pub(crate) async fn submit_query(
+    scheduler_url: String,
+    session_id: Option<String>,
+    query: ExecuteQueryParams,
+    max_message_size: usize,
+    security: Arc<GrpcSecurityConfig>,
+) -> Result<JobResults> {
+    // TODO reuse the scheduler to avoid connecting to the Ballista scheduler again and again
+    let mut scheduler =
+        connect_scheduler(scheduler_url, max_message_size, &security).await?;
+
+    let query_result = match scheduler.execute_query(query.clone()).await {
+        Ok(result) => result,
+        // read replicas direct the queries to the leader scheduler
+        Err(status) => match leader_url_from_status(&status) {
+            Some(leader_url) => {
+                scheduler =
+                    connect_scheduler(leader_url, max_message_size, &security).await?;
+                scheduler
+                    .execute_query(query)
+                    .await
+                    .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            }
+            None => return Err(DataFusionError::Execution(format!("{status:?}"))),
+        },
+    }
+    .into_inner();
 
     let query_result = match query_result.result.unwrap() {
         execute_query_result::Result::Success(success_result) => success_result,
@@ -285,16 +367,124 @@ async fn execute_query(
         }
     };
 
-    assert_eq!(
-        session_id, query_result.session_id,
-        "Session id inconsistent between Client and Server side in DistributedQueryExec."
-    );
+    if let Some(session_id) = session_id {
+        assert_eq!(
+            session_id, query_result.session_id,
+            "Session id inconsistent between Client and Server side in DistributedQueryExec."
+        );
+    }
 
     let job_id = query_result.job_id;
-    let mut prev_status: Option<job_status::Status> = None;
+    Ok(JobResults {
+        cancellation: JobCancellation {
+            scheduler: Some(scheduler.clone()),
+            job_id: job_id.clone(),
+        },
+        scheduler,
+        job_id,
+        security,
+        prev_status: None,
+        fetched_partitions: HashSet::new(),
+        pending_partitions: VecDeque::new(),
+        current: None,
+        done: false,
+    })
+}
+
+async fn connect_scheduler(
+    scheduler_url: String,
+    max_message_size: usize,
+    security: &GrpcSecurityConfig,
+) -> Result<SchedulerGrpcClient<GrpcChannel>> {
+    info!("Connecting to Ballista scheduler at {}", scheduler_url);
+    let connection = create_grpc_client_connection(scheduler_url, security)
+        .await
+        .map_err(|e| {
+            DataFusionError::Execution(format!(
+                "[{}] {e:?}",
+                ErrorCode::SchedulerUnreachable
+            ))
+        })?;
+
+    Ok(SchedulerGrpcClient::new(connection)
+        .max_encoding_message_size(max_message_size)
+        .max_decoding_message_size(max_message_size))
+}
+
+type BatchStream = Pin<Box<dyn Stream<Item = Result<RecordBatch>> + Send>>;
+
+/// Batches of the output partitions of a job, which are streamed from the executors as
+/// they complete, rather than once the job completes
+pub(crate) struct JobResults {
+    scheduler: SchedulerGrpcClient<GrpcChannel>,
+    job_id: String,
+    security: Arc<GrpcSecurityConfig>,
+    prev_status: Option<job_status::Status>,
+    /// Output partitions found in the status of the job so far, which are not read
+    /// again once the job completes
+    fetched_partitions: HashSet<(Option<u32>, u32)>,
+    pending_partitions: VecDeque<PartitionLocation>,
+    current: Option<BatchStream>,
+    /// Set once the results are read to the end, or failed
+    done: bool,
+    cancellation: JobCancellation,
+}
+
+impl JobResults {
+    pub(crate) fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    /// Client of the scheduler running the job
+    pub(crate) fn scheduler(&self) -> SchedulerGrpcClient<GrpcChannel> {
+        self.scheduler.clone()
+    }
+
+    /// Stop tracking the job, which is no longer cancelled when the results are
+    /// dropped, returning its ID
+    fn detach(mut self) -> String {
+        self.cancellation.scheduler = None;
+        std::mem::take(&mut self.job_id)
+    }
 
-    loop {
-        let GetJobStatusResult { status } = scheduler
+    pub(crate) async fn next(&mut self) -> Option<Result<RecordBatch>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            if let Some(current) = &mut self.current {
+                match current.next().await {
+                    Some(Err(e)) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                    Some(batch) => return Some(batch),
+                    None => self.current = None,
+                }
+                continue;
+            }
+            if let Some(location) = self.pending_partitions.pop_front() {
+                self.current = Some(self.fetch(location));
+                continue;
+            }
+            if self.cancellation.scheduler.is_none() {
+                // The job completed and all its output partitions were read
+                self.done = true;
+                return None;
+            }
+            if let Err(e) = self.poll_status().await {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+
+    /// Get the status of the job, queueing the output partitions it completed since it
+    /// was last polled, and waiting before the next poll if there are none
+    async fn poll_status(&mut self) -> Result<()> {
+        let job_id = &self.job_id;
+        let GetJobStatusResult { status } = self
+            .scheduler
             .get_job_status(GetJobStatusParams {
                 job_id: job_id.clone(),
             })
@@ -303,51 +493,167 @@ async fn execute_query(
             .into_inner();
         let status = status.and_then(|s| s.status);
         let wait_future = tokio::time::sleep(Duration::from_millis(100));
-        let has_status_change = prev_status != status;
-        match status {
+        let has_status_change = self.prev_status.as_ref().map(std::mem::discriminant)
+            != status.as_ref().map(std::mem::discriminant);
+        match &status {
             None => {
                 if has_status_change {
                     info!("Job {} still in initialization ...", job_id);
                 }
-                wait_future.await;
-                prev_status = status;
             }
             Some(job_status::Status::Queued(_)) => {
                 if has_status_change {
                     info!("Job {} still queued...", job_id);
                 }
-                wait_future.await;
-                prev_status = status;
             }
-            Some(job_status::Status::Running(_)) => {
+            Some(job_status::Status::Running(running)) => {
                 if has_status_change {
                     info!("Job {} is running...", job_id);
                 }
-                wait_future.await;
-                prev_status = status;
+                self.queue_partitions(running.partition_location.clone());
             }
             Some(job_status::Status::Failed(err)) => {
-                let msg = format!("Job {} failed: {}", job_id, err.error);
+                let msg = if err.error_code.is_empty() {
+                    format!("Job {} failed: {}", job_id, err.error)
+                } else {
+                    format!("Job {} failed: [{}] {}", job_id, err.error_code, err.error)
+                };
                 error!("{}", msg);
-                break Err(DataFusionError::Execution(msg));
+                return Err(DataFusionError::Execution(msg));
             }
             Some(job_status::Status::Successful(successful)) => {
-                let streams = successful.partition_location.into_iter().map(|p| {
-                    let f = fetch_partition(p)
-                        .map_err(|e| ArrowError::ExternalError(Box::new(e)));
-
-                    futures::stream::once(f).try_flatten()
-                });
+                self.cancellation.scheduler = None;
+                for skipped in &successful.skipped_partitions {
+                    warn!(
+                        "Results of job {job_id} are missing partition {} of stage {}: {}",
+                        skipped.partition_id, skipped.stage_id, skipped.error
+                    );
+                }
+                if let Some(max_rows) = successful.truncated_at {
+                    warn!("Results of job {job_id} truncated at {max_rows} rows");
+                    // Jobs with a result row limit don't stream their output partitions
+                    // while running, so that they can be truncated here
+                    self.current =
+                        Some(fetch_job_output(successful, self.security.clone()));
+                } else {
+                    self.queue_partitions(successful.partition_location.clone());
+                }
+                return Ok(());
+            }
+        };
+        self.prev_status = status;
+        if self.pending_partitions.is_empty() {
+            wait_future.await;
+        }
+        Ok(())
+    }
 
-                break Ok(futures::stream::iter(streams).flatten());
+    fn queue_partitions(&mut self, locations: Vec<PartitionLocation>) {
+        for location in locations {
+            let key = (
+                location.partition_id.as_ref().map(|p| p.partition_id),
+                location.map_partition_id,
+            );
+            if self.fetched_partitions.insert(key) {
+                self.pending_partitions.push_back(location);
             }
+        }
+    }
+
+    fn fetch(&self, location: PartitionLocation) -> BatchStream {
+        Box::pin(
+            futures::stream::once(fetch_partition(location, self.security.clone()))
+                .try_flatten(),
+        )
+    }
+}
+
+/// Cancels a job if the stream of its results is dropped before the job completes, so
+/// that its remaining tasks don't run for nothing
+struct JobCancellation {
+    /// Scheduler to send the cancellation to, unset once the job completed
+    scheduler: Option<SchedulerGrpcClient<GrpcChannel>>,
+    job_id: String,
+}
+
+impl Drop for JobCancellation {
+    fn drop(&mut self) {
+        let Some(mut scheduler) = self.scheduler.take() else {
+            return;
         };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let job_id = std::mem::take(&mut self.job_id);
+        runtime.spawn(async move {
+            info!("Results of job {job_id} were dropped, cancelling it");
+            if let Err(e) = scheduler
+                .cancel_job(CancelJobParams {
+                    job_id: job_id.clone(),
+                })
+                .await
+            {
+                warn!("Failed to cancel job {job_id}: {e}");
+            }
+        });
     }
 }
 
-async fn fetch_partition(
+/// Fetch the batches of the output partitions of a successful job in order, from the
+/// executors holding them. Truncated results are cut at their row limit
+pub fn fetch_job_output(
+    successful: &SuccessfulJob,
+    security: Arc<GrpcSecurityConfig>,
+) -> Pin<Box<dyn Stream<Item = Result<RecordBatch>> + Send>> {
+    let streams = successful
+        .partition_location
+        .clone()
+        .into_iter()
+        .map(move |p| {
+            futures::stream::once(fetch_partition(p, security.clone())).try_flatten()
+        });
+    Box::pin(limit_rows(
+        futures::stream::iter(streams).flatten(),
+        successful.truncated_at.map(|max_rows| max_rows as usize),
+    ))
+}
+
+/// Stream at most `fetch` rows of a stream of batches. Truncated results hold one row
+/// more than the limit, so that they can be told apart
+fn limit_rows<E>(
+    stream: impl Stream<Item = result::Result<RecordBatch, E>>,
+    fetch: Option<usize>,
+) -> impl Stream<Item = result::Result<RecordBatch, E>> {
+    stream.scan(fetch.unwrap_or(usize::MAX), |remaining, batch| {
+        let batch = match batch {
+            Ok(_) if *remaining == 0 => return future::ready(None),
+            Ok(batch) => {
+                let batch = batch.slice(0, batch.num_rows().min(*remaining));
+                *remaining -= batch.num_rows();
+                Ok(batch)
+            }
+            Err(e) => Err(e),
+        };
+        future::ready(Some(batch))
+    })
+}
+
+/// Fetch the data of an output partition of a job, from the executor which produced it
+/// with the token of the job set on the location, or from the partition location itself
+/// if the data was delivered inline
+pub async fn fetch_partition(
     location: PartitionLocation,
+    security: Arc<GrpcSecurityConfig>,
 ) -> Result<SendableRecordBatchStream> {
+    if !location.inline_data.is_empty() {
+        return read_inline_partition(location.inline_data);
+    }
+
+    let compression = location.compression().into();
+    // partitions in a format this client doesn't read are re-encoded by the executor
+    let transfer_compression = (!ShuffleFormats::supported()
+        .reads(compression, location.ipc_metadata_version))
+    .then(|| Arc::new(NoCompression) as Arc<dyn CompressionCodec>);
     let metadata = location.executor_meta.ok_or_else(|| {
         DataFusionError::Internal("Received empty executor metadata".to_owned())
     })?;
@@ -356,7 +662,7 @@ async fn fetch_partition(
     })?;
     let host = metadata.host.as_str();
     let port = metadata.port as u16;
-    let mut ballista_client = BallistaClient::try_new(host, port)
+    let mut ballista_client = BallistaClient::try_new(host, port, &security)
         .await
         .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
     ballista_client
@@ -366,7 +672,22 @@ async fn fetch_partition(
             &location.path,
             host,
             port,
+            compression,
+            &location.fetch_token,
+            transfer_compression,
+            SHUFFLE_IPC_METADATA_VERSION,
         )
         .await
         .map_err(|e| DataFusionError::External(Box::new(e)))
 }
+
+/// Decode a partition which was delivered inline in Arrow IPC stream format
+fn read_inline_partition(data: Vec<u8>) -> Result<SendableRecordBatchStream> {
+    let reader = StreamReader::try_new(Cursor::new(data), None)?;
+    let schema = reader.schema();
+    let stream = futures::stream::iter(
+        reader.map(|batch| batch.map_err(|e| DataFusionError::ArrowError(e, None))),
+    );
+
+    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+}
@@ -0,0 +1,311 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Scan of a flight served by an Arrow Flight (SQL) endpoint
+
+use crate::config::redact_settings;
+use crate::utils::{create_grpc_client_connection, GrpcChannel};
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_client::FlightServiceClient;
+use arrow_flight::{FlightDescriptor, FlightInfo, Ticket};
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning,
+    PlanProperties, SendableRecordBatchStream, Statistics,
+};
+use futures::{StreamExt, TryStreamExt};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue};
+
+/// Location scheme meaning a ticket is redeemed at the endpoint which issued it
+const REUSE_CONNECTION_SCHEME: &str = "arrow-flight-reuse-connection:";
+
+/// An endpoint of a flight, read by one partition of a [FlightScanExec]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlightPartition {
+    pub ticket: Vec<u8>,
+    /// Locations the ticket can be redeemed at. If empty, it is redeemed
+    /// at the endpoint which returned the flight info.
+    pub locations: Vec<String>,
+}
+
+/// FlightScanExec reads the endpoints of a flight in parallel, one partition per endpoint,
+/// by redeeming their tickets at the locations returned by the Flight service.
+#[derive(Clone)]
+pub struct FlightScanExec {
+    /// Flight endpoint the flight info was retrieved from
+    endpoint: String,
+    /// gRPC headers sent with each request
+    headers: HashMap<String, String>,
+    partitions: Vec<FlightPartition>,
+    /// Schema of the flight
+    schema: SchemaRef,
+    /// Columns of the flight returned by this scan
+    projection: Vec<usize>,
+    properties: PlanProperties,
+}
+
+impl FlightScanExec {
+    pub fn try_new(
+        endpoint: String,
+        headers: HashMap<String, String>,
+        partitions: Vec<FlightPartition>,
+        schema: SchemaRef,
+        projection: Vec<usize>,
+    ) -> Result<Self> {
+        let projected_schema = Arc::new(schema.project(&projection)?);
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(projected_schema),
+            Partitioning::UnknownPartitioning(partitions.len()),
+            ExecutionMode::Bounded,
+        );
+        Ok(Self {
+            endpoint,
+            headers,
+            partitions,
+            schema,
+            projection,
+            properties,
+        })
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub fn partitions(&self) -> &[FlightPartition] {
+        &self.partitions
+    }
+
+    /// Schema of the flight, before projection
+    pub fn flight_schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    pub fn projection(&self) -> &[usize] {
+        &self.projection
+    }
+}
+
+impl std::fmt::Debug for FlightScanExec {
+    // Keep the credentials of the headers out of logs
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headers: BTreeMap<String, String> = redact_settings(&self.headers);
+        f.debug_struct("FlightScanExec")
+            .field("endpoint", &self.endpoint)
+            .field("headers", &headers)
+            .field("partitions", &self.partitions)
+            .field("schema", &self.schema)
+            .field("projection", &self.projection)
+            .field("properties", &self.properties)
+            .finish()
+    }
+}
+
+impl DisplayAs for FlightScanExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "FlightScanExec: endpoint={}, partitions={}",
+                    self.endpoint,
+                    self.partitions.len()
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for FlightScanExec {
+    fn name(&self) -> &str {
+        "FlightScanExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.is_empty() {
+            Ok(self)
+        } else {
+            Err(DataFusionError::Internal(
+                "FlightScanExec does not support children".to_owned(),
+            ))
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        _context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let flight_partition = self.partitions.get(partition).ok_or_else(|| {
+            DataFusionError::Internal(format!(
+                "FlightScanExec invalid partition {partition}"
+            ))
+        })?;
+
+        let url = flight_partition
+            .locations
+            .iter()
+            .find_map(|location| location_url(location))
+            .unwrap_or_else(|| self.endpoint.clone());
+        let ticket = Ticket::new(flight_partition.ticket.clone());
+        let headers = self.headers.clone();
+        let projection = self.projection.clone();
+
+        let stream = futures::stream::once(async move {
+            let mut client = flight_client(url).await?;
+            let flight_data = client
+                .do_get(flight_request(ticket, &headers)?)
+                .await
+                .map_err(|e| DataFusionError::External(Box::new(e)))?
+                .into_inner();
+
+            Ok::<_, DataFusionError>(
+                FlightRecordBatchStream::new_from_flight_data(
+                    flight_data.map_err(FlightError::Tonic),
+                )
+                .map_err(|e| DataFusionError::External(Box::new(e))),
+            )
+        })
+        .try_flatten()
+        .map(move |batch| -> Result<RecordBatch> { Ok(batch?.project(&projection)?) });
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+/// Retrieve the flight info of a flight from a Flight endpoint
+pub(crate) async fn get_flight_info(
+    endpoint: &str,
+    descriptor: FlightDescriptor,
+    headers: &HashMap<String, String>,
+) -> Result<FlightInfo> {
+    let mut client = flight_client(endpoint.to_owned()).await?;
+    Ok(client
+        .get_flight_info(flight_request(descriptor, headers)?)
+        .await
+        .map_err(|e| DataFusionError::External(Box::new(e)))?
+        .into_inner())
+}
+
+async fn flight_client(url: String) -> Result<FlightServiceClient<GrpcChannel>> {
+    // Flight services outside of the cluster aren't sent the auth token of the cluster
+    let connection = create_grpc_client_connection(url.clone(), &Default::default())
+        .await
+        .map_err(|e| {
+            DataFusionError::Execution(format!(
+                "Failed to connect to Flight endpoint {url}: {e:?}"
+            ))
+        })?;
+    Ok(FlightServiceClient::new(connection))
+}
+
+fn flight_request<T>(
+    message: T,
+    headers: &HashMap<String, String>,
+) -> Result<tonic::Request<T>> {
+    let mut request = tonic::Request::new(message);
+    for (key, value) in headers {
+        let key = AsciiMetadataKey::from_bytes(key.as_bytes())
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let value = AsciiMetadataValue::try_from(value.as_str())
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        request.metadata_mut().insert(key, value);
+    }
+    Ok(request)
+}
+
+/// Converts a Flight location to the url of a gRPC endpoint, or None if the
+/// ticket should be redeemed at the endpoint which issued it
+fn location_url(location: &str) -> Option<String> {
+    if location.starts_with(REUSE_CONNECTION_SCHEME) {
+        None
+    } else if let Some(address) = location.strip_prefix("grpc+tls://") {
+        Some(format!("https://{address}"))
+    } else if let Some(address) = location
+        .strip_prefix("grpc+tcp://")
+        .or_else(|| location.strip_prefix("grpc://"))
+    {
+        Some(format!("http://{address}"))
+    } else {
+        Some(location.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_url() {
+        assert_eq!(None, location_url("arrow-flight-reuse-connection://?"));
+        assert_eq!(
+            Some("https://dremio:32010".to_owned()),
+            location_url("grpc+tls://dremio:32010")
+        );
+        assert_eq!(
+            Some("http://localhost:50051".to_owned()),
+            location_url("grpc+tcp://localhost:50051")
+        );
+        assert_eq!(
+            Some("http://localhost:50051".to_owned()),
+            location_url("grpc://localhost:50051")
+        );
+        assert_eq!(
+            Some("http://localhost:50051".to_owned()),
+            location_url("http://localhost:50051")
+        );
+    }
+}
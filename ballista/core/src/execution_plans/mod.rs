@@ -18,12 +18,47 @@
 //! This module contains execution plans that are needed to distribute DataFusion's execution plans into
 //! several Ballista executors.
 
+mod broadcast_exchange;
+mod distributed_analyze;
 mod distributed_query;
+mod flight_scan;
+mod range_partitioning;
+mod remote_query;
+mod scan_options;
+mod shuffle_fetch_limiter;
+mod shuffle_io;
 mod shuffle_reader;
+mod shuffle_storage;
 mod shuffle_writer;
+mod sort_shuffle;
+mod sorted_runs;
+mod splittable_scan;
 mod unresolved_shuffle;
+mod verify_files;
 
-pub use distributed_query::DistributedQueryExec;
+pub use broadcast_exchange::{BroadcastCache, BroadcastExchangeExec};
+pub use distributed_analyze::DistributedAnalyzeExec;
+pub use distributed_query::{fetch_job_output, fetch_partition, DistributedQueryExec};
+pub use flight_scan::{FlightPartition, FlightScanExec};
+pub use range_partitioning::RangePartitioning;
+pub use remote_query::RemoteQueryExec;
+pub use scan_options::{with_scan_options, ScanOptionsExec};
+pub use shuffle_fetch_limiter::{
+    ShuffleFetchLimiter, ShuffleFetchPermit, ShuffleFetchUsage,
+};
+pub use shuffle_io::{shuffle_io_uring_enabled, ShuffleFileRange, ShuffleFileWriter};
 pub use shuffle_reader::ShuffleReaderExec;
+pub use shuffle_storage::is_object_store_path;
 pub use shuffle_writer::ShuffleWriterExec;
+pub use sort_shuffle::{
+    open_shuffle_file, read_shuffle_index, shuffle_index_path, ShuffleFileReader,
+};
+pub use splittable_scan::{ScanFileLeaser, ScanFileLeases, SplittableScanExec};
 pub use unresolved_shuffle::UnresolvedShuffleExec;
+pub use verify_files::{
+    verify_files_schema, VerifyFilesExec, VERIFY_STATUS_MISMATCH, VERIFY_STATUS_OK,
+    VERIFY_STATUS_UNREADABLE, VERIFY_STATUS_UNVERIFIED,
+};
+
+pub(crate) use flight_scan::get_flight_info;
+pub(crate) use splittable_scan::{scan_config, scan_file_groups, with_scan_config};
@@ -0,0 +1,230 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Range partitioning of the output of a stage, which DataFusion has no
+//! [Partitioning](datafusion::physical_plan::Partitioning) for.
+//!
+//! All the rows of an output partition sort before those of the next partition, so
+//! that sorting each partition on its own sorts the output of the stage globally.
+
+use datafusion::arrow::array::{ArrayRef, UInt32Array};
+use datafusion::arrow::compute::{cast, take_record_batch};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::row::{Row, RowConverter, SortField};
+use datafusion::common::{DataFusionError, Result, ScalarValue};
+use datafusion::physical_expr::PhysicalSortExpr;
+
+/// Partitioning of rows by ranges of their sort key
+#[derive(Debug, Clone)]
+pub struct RangePartitioning {
+    sort_exprs: Vec<PhysicalSortExpr>,
+    /// Sort keys from which rows go to the next partition, in the order of the sort,
+    /// one fewer than the partitions
+    boundaries: Vec<Vec<ScalarValue>>,
+}
+
+impl RangePartitioning {
+    pub fn try_new(
+        sort_exprs: Vec<PhysicalSortExpr>,
+        boundaries: Vec<Vec<ScalarValue>>,
+    ) -> Result<Self> {
+        if sort_exprs.is_empty() {
+            return Err(DataFusionError::Plan(
+                "Range partitioning requires a sort key".to_owned(),
+            ));
+        }
+        if let Some(boundary) = boundaries
+            .iter()
+            .find(|boundary| boundary.len() != sort_exprs.len())
+        {
+            return Err(DataFusionError::Plan(format!(
+                "Range partitioning boundary {boundary:?} doesn't match the {} sort expressions",
+                sort_exprs.len()
+            )));
+        }
+        Ok(Self {
+            sort_exprs,
+            boundaries,
+        })
+    }
+
+    pub fn sort_exprs(&self) -> &[PhysicalSortExpr] {
+        &self.sort_exprs
+    }
+
+    pub fn boundaries(&self) -> &[Vec<ScalarValue>] {
+        &self.boundaries
+    }
+
+    pub fn partition_count(&self) -> usize {
+        self.boundaries.len() + 1
+    }
+
+    /// Split the rows of the batch by output partition, calling `f` with the rows of
+    /// each partition, unless there are none
+    pub fn partition<F>(&self, batch: &RecordBatch, mut f: F) -> Result<()>
+    where
+        F: FnMut(usize, RecordBatch) -> Result<()>,
+    {
+        for (partition, indices) in self.partition_indices(batch)?.into_iter().enumerate()
+        {
+            if indices.is_empty() {
+                continue;
+            }
+            let indices = UInt32Array::from(indices);
+            f(partition, take_record_batch(batch, &indices)?)?;
+        }
+        Ok(())
+    }
+
+    /// Indices of the rows of the batch going to each output partition
+    fn partition_indices(&self, batch: &RecordBatch) -> Result<Vec<Vec<u32>>> {
+        let mut indices = vec![vec![]; self.partition_count()];
+        if self.boundaries.is_empty() {
+            indices[0] = (0..batch.num_rows() as u32).collect();
+            return Ok(indices);
+        }
+
+        let keys = self
+            .sort_exprs
+            .iter()
+            .map(|sort_expr| sort_expr.expr.evaluate(batch)?.into_array(batch.num_rows()))
+            .collect::<Result<Vec<ArrayRef>>>()?;
+        let converter = RowConverter::new(
+            keys.iter()
+                .zip(&self.sort_exprs)
+                .map(|(key, sort_expr)| {
+                    SortField::new_with_options(
+                        key.data_type().clone(),
+                        sort_expr.options,
+                    )
+                })
+                .collect(),
+        )?;
+        let boundary_keys = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let values = self.boundaries.iter().map(|boundary| boundary[i].clone());
+                Ok(cast(&ScalarValue::iter_to_array(values)?, key.data_type())?)
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?;
+        let boundary_rows = converter.convert_columns(&boundary_keys)?;
+        let boundaries: Vec<Row> = boundary_rows.iter().collect();
+
+        let rows = converter.convert_columns(&keys)?;
+        for (i, row) in rows.iter().enumerate() {
+            let partition = boundaries.partition_point(|boundary| *boundary <= row);
+            indices[partition].push(i as u32);
+        }
+        Ok(indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{AsArray, Int32Array};
+    use datafusion::arrow::compute::SortOptions;
+    use datafusion::arrow::datatypes::{DataType, Field, Int32Type, Schema};
+    use datafusion::physical_expr::expressions::Column;
+
+    use super::*;
+
+    #[test]
+    fn partition_by_range() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int32Array::from(vec![
+                Some(7),
+                Some(1),
+                None,
+                Some(10),
+                Some(4),
+                Some(20),
+            ]))],
+        )?;
+        let partition_values = |sort_expr: PhysicalSortExpr| -> Result<Vec<Vec<i32>>> {
+            let partitioning = RangePartitioning::try_new(
+                vec![sort_expr],
+                vec![
+                    vec![ScalarValue::Int32(Some(5))],
+                    vec![ScalarValue::Int32(Some(10))],
+                ],
+            )?;
+            let mut values = vec![vec![]; partitioning.partition_count()];
+            partitioning.partition(&batch, |partition, batch| {
+                values[partition]
+                    .extend(batch.column(0).as_primitive::<Int32Type>().iter().flatten());
+                Ok(())
+            })?;
+            Ok(values)
+        };
+
+        // nulls sort first, and rows equal to a boundary go to the next partition
+        let ascending = PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        };
+        assert_eq!(
+            vec![vec![1, 4], vec![7], vec![10, 20]],
+            partition_values(ascending)?
+        );
+
+        let descending = PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions {
+                descending: true,
+                nulls_first: false,
+            },
+        };
+        let partitioning = RangePartitioning::try_new(
+            vec![descending],
+            vec![
+                vec![ScalarValue::Int32(Some(10))],
+                vec![ScalarValue::Int32(Some(5))],
+            ],
+        )?;
+        let mut values = vec![vec![]; partitioning.partition_count()];
+        partitioning.partition(&batch, |partition, batch| {
+            values[partition].push(batch.column(0).as_primitive::<Int32Type>().len());
+            Ok(())
+        })?;
+        // 20 | 10, 7 | 4, 1, null
+        assert_eq!(vec![vec![1], vec![2], vec![3]], values);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_boundaries() {
+        let sort_expr = PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        };
+        assert!(RangePartitioning::try_new(vec![], vec![]).is_err());
+        assert!(RangePartitioning::try_new(
+            vec![sort_expr],
+            vec![vec![
+                ScalarValue::Int32(Some(1)),
+                ScalarValue::Int32(Some(2))
+            ]]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,169 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Execution of a subplan by another Ballista cluster
+
+use crate::execution_plans::distributed_query::{execute_query, session_settings};
+use crate::extension::SessionConfigExt;
+use crate::serde::protobuf::execute_query_params::{OptionalPriority, Query};
+use crate::serde::protobuf::ExecuteQueryParams;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::error::ArrowError;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning,
+    PlanProperties, SendableRecordBatchStream, Statistics,
+};
+use futures::{TryFutureExt, TryStreamExt};
+use std::any::Any;
+use std::sync::Arc;
+
+/// RemoteQueryExec submits an encoded logical plan to the scheduler of another
+/// Ballista cluster and streams the results of its final stage, much like a shuffle
+/// read from a stage which ran elsewhere.
+///
+/// The plan runs in a new session on the remote cluster, configured with the
+/// settings of the task executing this operator. The executors of the remote
+/// cluster must be reachable from the executor running this operator.
+#[derive(Debug, Clone)]
+pub struct RemoteQueryExec {
+    /// Scheduler of the remote cluster
+    scheduler_url: String,
+    /// Encoded `LogicalPlanNode` to execute on the remote cluster
+    logical_plan: Vec<u8>,
+    schema: SchemaRef,
+    properties: PlanProperties,
+}
+
+impl RemoteQueryExec {
+    pub fn new(scheduler_url: String, logical_plan: Vec<u8>, schema: SchemaRef) -> Self {
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(schema.clone()),
+            Partitioning::UnknownPartitioning(1),
+            ExecutionMode::Bounded,
+        );
+        Self {
+            scheduler_url,
+            logical_plan,
+            schema,
+            properties,
+        }
+    }
+
+    pub fn scheduler_url(&self) -> &str {
+        &self.scheduler_url
+    }
+
+    pub fn logical_plan(&self) -> &[u8] {
+        &self.logical_plan
+    }
+}
+
+impl DisplayAs for RemoteQueryExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "RemoteQueryExec: scheduler_url={}", self.scheduler_url)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for RemoteQueryExec {
+    fn name(&self) -> &str {
+        "RemoteQueryExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.is_empty() {
+            Ok(self)
+        } else {
+            Err(DataFusionError::Internal(
+                "RemoteQueryExec does not support children".to_owned(),
+            ))
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        if partition != 0 {
+            return Err(DataFusionError::Internal(format!(
+                "RemoteQueryExec invalid partition {partition}"
+            )));
+        }
+
+        let config = context.session_config();
+        let query = ExecuteQueryParams {
+            query: Some(Query::LogicalPlan(self.logical_plan.clone())),
+            settings: session_settings(config),
+            optional_session_id: None,
+            optional_priority: Some(OptionalPriority::Priority(
+                config.ballista_job_priority(),
+            )),
+        };
+
+        let stream = futures::stream::once(
+            execute_query(
+                self.scheduler_url.clone(),
+                None,
+                query,
+                config.ballista_grpc_client_max_message_size(),
+                config.ballista_grpc_security(),
+            )
+            .map_err(|e| ArrowError::ExternalError(Box::new(e))),
+        )
+        .try_flatten();
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            stream,
+        )))
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema))
+    }
+}
@@ -0,0 +1,220 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::GetExt;
+use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+use datafusion::datasource::physical_plan::{CsvExec, FileScanConfig, NdJsonExec};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+    SendableRecordBatchStream, Statistics,
+};
+
+/// ScanOptionsExec carries the options of a CSV or JSON scan which DataFusion does not
+/// serialize, currently the compression of the files.
+///
+/// It only exists while a plan is serialized: [with_scan_options] wraps the scans
+/// before encoding, and decoding it yields the scan with its options restored.
+#[derive(Debug)]
+pub struct ScanOptionsExec {
+    input: Arc<dyn ExecutionPlan>,
+    compression: FileCompressionType,
+}
+
+impl ScanOptionsExec {
+    /// Create a new ScanOptionsExec wrapping a CSV or JSON scan
+    pub fn new(input: Arc<dyn ExecutionPlan>, compression: FileCompressionType) -> Self {
+        Self { input, compression }
+    }
+
+    /// Compression of the files of the scan
+    pub fn compression(&self) -> FileCompressionType {
+        self.compression
+    }
+
+    /// Restore the options of a scan decoded by DataFusion
+    pub fn restore(
+        scan: Arc<dyn ExecutionPlan>,
+        compression: FileCompressionType,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if let Some(exec) = scan.as_any().downcast_ref::<CsvExec>() {
+            let mut exec = exec.clone();
+            exec.file_compression_type = compression;
+            Ok(Arc::new(exec))
+        } else if let Some(exec) = scan.as_any().downcast_ref::<NdJsonExec>() {
+            Ok(Arc::new(NdJsonExec::new(
+                exec.base_config().clone(),
+                compression,
+            )))
+        } else {
+            Err(DataFusionError::Internal(format!(
+                "ScanOptionsExec does not support scan {}",
+                scan.name()
+            )))
+        }
+    }
+}
+
+/// Wrap the compressed CSV and JSON scans of a plan into [ScanOptionsExec]s, so that
+/// their compression survives serialization
+pub fn with_scan_options(plan: Arc<dyn ExecutionPlan>) -> Result<Arc<dyn ExecutionPlan>> {
+    plan.transform_up(|plan| {
+        let compression = if let Some(exec) = plan.as_any().downcast_ref::<CsvExec>() {
+            exec.file_compression_type
+        } else if let Some(exec) = plan.as_any().downcast_ref::<NdJsonExec>() {
+            // NdJsonExec does not expose its compression, which DataFusion matches
+            // with the extension of the files when listing them
+            files_compression(exec.base_config())
+        } else {
+            return Ok(Transformed::no(plan));
+        };
+
+        if compression.is_compressed() {
+            Ok(Transformed::yes(Arc::new(ScanOptionsExec::new(
+                plan,
+                compression,
+            ))))
+        } else {
+            Ok(Transformed::no(plan))
+        }
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// Compression whose extension all the files of a scan end with
+pub(super) fn files_compression(config: &FileScanConfig) -> FileCompressionType {
+    let mut files = config.file_groups.iter().flatten().peekable();
+    if files.peek().is_none() {
+        return FileCompressionType::UNCOMPRESSED;
+    }
+
+    [
+        FileCompressionType::GZIP,
+        FileCompressionType::BZIP2,
+        FileCompressionType::XZ,
+        FileCompressionType::ZSTD,
+    ]
+    .into_iter()
+    .find(|compression| {
+        let extension = compression.get_ext();
+        files
+            .clone()
+            .all(|file| file.object_meta.location.as_ref().ends_with(&extension))
+    })
+    .unwrap_or(FileCompressionType::UNCOMPRESSED)
+}
+
+impl DisplayAs for ScanOptionsExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "ScanOptionsExec: compression={}",
+                    self.compression.get_variant()
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for ScanOptionsExec {
+    fn name(&self) -> &str {
+        "ScanOptionsExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [input] => Ok(Arc::new(Self::new(input.clone(), self.compression))),
+            _ => Err(DataFusionError::Internal(
+                "ScanOptionsExec wrong number of children".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.input.execute(partition, context)
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::execution_plans::scan_options::{with_scan_options, ScanOptionsExec};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+    use datafusion::datasource::listing::PartitionedFile;
+    use datafusion::datasource::physical_plan::{FileScanConfig, NdJsonExec};
+    use datafusion::execution::object_store::ObjectStoreUrl;
+    use datafusion::physical_plan::ExecutionPlan;
+    use std::sync::Arc;
+
+    fn json_scan(files: &[&str]) -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let mut config = FileScanConfig::new(ObjectStoreUrl::local_filesystem(), schema);
+        for file in files {
+            config = config.with_file(PartitionedFile::new(file.to_string(), 10));
+        }
+        Arc::new(NdJsonExec::new(config, FileCompressionType::UNCOMPRESSED))
+    }
+
+    #[test]
+    fn test_wrap_compressed_scans() {
+        let plan = with_scan_options(json_scan(&["a.json.gz", "b.json.gz"])).unwrap();
+        let exec = plan.as_any().downcast_ref::<ScanOptionsExec>().unwrap();
+        assert_eq!(exec.compression(), FileCompressionType::GZIP);
+
+        let plan = with_scan_options(json_scan(&["a.json.gz", "b.json"])).unwrap();
+        assert!(plan.as_any().is::<NdJsonExec>());
+    }
+}
@@ -0,0 +1,246 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Limit of the shuffle partitions an executor fetches at the same time from the other
+//! executors and from object stores, across all its tasks, as a reduce task reading
+//! the outputs of hundreds of map tasks would otherwise open as many connections at
+//! once. The fetches waiting for the limit are let through a task at a time in turn,
+//! so that a task with many fetches doesn't hold up the other tasks.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+/// Limit of the concurrent shuffle fetches of an executor. An executor puts its
+/// limiter in the [SessionConfig] of its tasks as an extension, the tasks of a
+/// session without one only being limited by `ballista.shuffle.max_concurrent_fetches`.
+///
+/// [SessionConfig]: datafusion::prelude::SessionConfig
+#[derive(Debug)]
+pub struct ShuffleFetchLimiter {
+    max_fetches: usize,
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    in_flight: usize,
+    waiting: usize,
+    /// Fetches waiting for a permit, by task
+    queues: HashMap<String, VecDeque<oneshot::Sender<ShuffleFetchPermit>>>,
+    /// Tasks with waiting fetches, in the order they are let through
+    turns: VecDeque<String>,
+}
+
+/// Shuffle fetches of an executor at some point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShuffleFetchUsage {
+    /// Fetches holding a permit
+    pub in_flight: usize,
+    /// Fetches waiting for a permit
+    pub waiting: usize,
+}
+
+/// Permit of a shuffle fetch, letting a waiting fetch through once dropped
+#[derive(Debug)]
+pub struct ShuffleFetchPermit {
+    limiter: Option<Arc<ShuffleFetchLimiter>>,
+}
+
+impl ShuffleFetchLimiter {
+    /// A limit of `max_fetches` concurrent fetches, at least 1
+    pub fn new(max_fetches: usize) -> Self {
+        Self {
+            max_fetches: max_fetches.max(1),
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    pub fn max_fetches(&self) -> usize {
+        self.max_fetches
+    }
+
+    /// Wait for a permit of a fetch of `task`, given once the fetches in flight are
+    /// under the limit and the fetches of the tasks waiting before it got theirs
+    pub async fn acquire(self: &Arc<Self>, task: &str) -> ShuffleFetchPermit {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            if state.in_flight < self.max_fetches && state.turns.is_empty() {
+                state.in_flight += 1;
+                return ShuffleFetchPermit {
+                    limiter: Some(self.clone()),
+                };
+            }
+            let (sender, receiver) = oneshot::channel();
+            // a task gets a turn once it has a fetch waiting
+            let first = state.queues.get(task).is_none_or(|queue| queue.is_empty());
+            if first {
+                state.turns.push_back(task.to_owned());
+            }
+            state
+                .queues
+                .entry(task.to_owned())
+                .or_default()
+                .push_back(sender);
+            state.waiting += 1;
+            receiver
+        };
+        // the senders are only dropped with the limiter, which outlives its waiters
+        receiver
+            .await
+            .unwrap_or(ShuffleFetchPermit { limiter: None })
+    }
+
+    pub fn usage(&self) -> ShuffleFetchUsage {
+        let state = self.state.lock().unwrap();
+        ShuffleFetchUsage {
+            in_flight: state.in_flight,
+            waiting: state.waiting,
+        }
+    }
+
+    /// Hand the permit of a completed fetch over to the first fetch of the next task
+    /// in turn, skipping the fetches which stopped waiting
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(task) = state.turns.pop_front() {
+            let Some(queue) = state.queues.get_mut(&task) else {
+                continue;
+            };
+            let sender = queue.pop_front();
+            if queue.is_empty() {
+                state.queues.remove(&task);
+            } else {
+                state.turns.push_back(task);
+            }
+            let Some(sender) = sender else {
+                continue;
+            };
+            state.waiting -= 1;
+            let permit = ShuffleFetchPermit {
+                limiter: Some(self.clone()),
+            };
+            match sender.send(permit) {
+                // the fetch in flight is now the one of the waiter
+                Ok(()) => return,
+                Err(mut permit) => permit.limiter = None,
+            }
+        }
+        state.in_flight -= 1;
+    }
+}
+
+impl Drop for ShuffleFetchPermit {
+    fn drop(&mut self) {
+        if let Some(limiter) = self.limiter.take() {
+            limiter.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn fetches_of_tasks_are_let_through_in_turn() {
+        let limiter = Arc::new(ShuffleFetchLimiter::new(1));
+        let first = limiter.acquire("a").now_or_never().unwrap();
+
+        let mut a2 = Box::pin(limiter.acquire("a"));
+        let mut a3 = Box::pin(limiter.acquire("a"));
+        let mut b1 = Box::pin(limiter.acquire("b"));
+        assert!((&mut a2).now_or_never().is_none());
+        assert!((&mut a3).now_or_never().is_none());
+        assert!((&mut b1).now_or_never().is_none());
+        assert_eq!(
+            ShuffleFetchUsage {
+                in_flight: 1,
+                waiting: 3
+            },
+            limiter.usage()
+        );
+
+        drop(first);
+        let a2 = (&mut a2).now_or_never().unwrap();
+        assert!((&mut a3).now_or_never().is_none());
+        drop(a2);
+        // the fetch of task b waited less but is let through before the next of a
+        let b1 = (&mut b1).now_or_never().unwrap();
+        assert!((&mut a3).now_or_never().is_none());
+        drop(b1);
+        let a3 = (&mut a3).now_or_never().unwrap();
+        assert_eq!(
+            ShuffleFetchUsage {
+                in_flight: 1,
+                waiting: 0
+            },
+            limiter.usage()
+        );
+        drop(a3);
+        assert_eq!(ShuffleFetchUsage::default(), limiter.usage());
+    }
+
+    #[tokio::test]
+    async fn tasks_queue_behind_a_full_limiter() {
+        let limiter = Arc::new(ShuffleFetchLimiter::new(1));
+        let first = limiter.acquire("a").now_or_never().unwrap();
+        let mut b1 = Box::pin(limiter.acquire("b"));
+        let mut b2 = Box::pin(limiter.acquire("b"));
+        assert!((&mut b1).now_or_never().is_none());
+        assert!((&mut b2).now_or_never().is_none());
+        // a fetch of the task holding the permit waits for its turn too
+        let mut a2 = Box::pin(limiter.acquire("a"));
+        assert!((&mut a2).now_or_never().is_none());
+        assert_eq!(
+            ShuffleFetchUsage {
+                in_flight: 1,
+                waiting: 3
+            },
+            limiter.usage()
+        );
+
+        drop(first);
+        let b1 = (&mut b1).now_or_never().unwrap();
+        assert!((&mut a2).now_or_never().is_none());
+        drop(b1);
+        let a2 = (&mut a2).now_or_never().unwrap();
+        drop(a2);
+        let b2 = (&mut b2).now_or_never().unwrap();
+        drop(b2);
+        assert_eq!(ShuffleFetchUsage::default(), limiter.usage());
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiters_are_skipped() {
+        let limiter = Arc::new(ShuffleFetchLimiter::new(1));
+        let first = limiter.acquire("a").now_or_never().unwrap();
+        let mut cancelled = Box::pin(limiter.acquire("b"));
+        let mut waiting = Box::pin(limiter.acquire("c"));
+        assert!((&mut cancelled).now_or_never().is_none());
+        assert!((&mut waiting).now_or_never().is_none());
+        drop(cancelled);
+
+        drop(first);
+        let permit = (&mut waiting).now_or_never().unwrap();
+        assert_eq!(1, limiter.usage().in_flight);
+        drop(permit);
+        assert_eq!(ShuffleFetchUsage::default(), limiter.usage());
+    }
+}
@@ -0,0 +1,552 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! IO of shuffle files.
+//!
+//! Map tasks write shuffle files with a [ShuffleFileWriter], and the tasks and Flight
+//! services reading them read them through a [ShuffleFileRange]. When built with the
+//! `io-uring` feature, both submit their IO to an io_uring on Linux, keeping several
+//! writes or reads ahead in flight rather than blocking on each of them. They fall
+//! back on buffered file IO on other platforms, without the feature, or when the
+//! kernel doesn't support io_uring, which is detected once per process.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Take, Write};
+use std::path::Path;
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+use log::debug;
+
+/// Size of the buffer of a [ShuffleFileWriter], written at once
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Writer of a shuffle file
+pub struct ShuffleFileWriter {
+    inner: WriterImpl,
+    /// Number of bytes written so far
+    position: u64,
+}
+
+enum WriterImpl {
+    Buffered(BufWriter<File>),
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring(Box<uring::UringWriter>),
+}
+
+impl ShuffleFileWriter {
+    /// Create the file at `path`, truncating it if it exists
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::create(path)?;
+
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if uring::available() {
+            match uring::ring() {
+                Ok(ring) => {
+                    return Ok(Self {
+                        inner: WriterImpl::Uring(Box::new(uring::UringWriter::new(
+                            file, ring,
+                        ))),
+                        position: 0,
+                    })
+                }
+                Err(e) => debug!("Falling back on buffered shuffle file writes: {e}"),
+            }
+        }
+
+        Ok(Self {
+            inner: WriterImpl::Buffered(BufWriter::with_capacity(
+                WRITE_BUFFER_SIZE,
+                file,
+            )),
+            position: 0,
+        })
+    }
+
+    /// Number of bytes written so far, i.e. the size of the file once flushed
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Flush the writes and sync the file to disk
+    pub fn sync_all(&mut self) -> io::Result<()> {
+        self.flush()?;
+        match &self.inner {
+            WriterImpl::Buffered(writer) => writer.get_ref().sync_all(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            WriterImpl::Uring(writer) => writer.get_ref().sync_all(),
+        }
+    }
+}
+
+impl Write for ShuffleFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = match &mut self.inner {
+            WriterImpl::Buffered(writer) => writer.write(buf)?,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            WriterImpl::Uring(writer) => writer.write(buf)?,
+        };
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            WriterImpl::Buffered(writer) => writer.flush(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            WriterImpl::Uring(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Reader of a range of bytes of a shuffle file, e.g. of an output partition of a
+/// sort-based shuffle file
+pub struct ShuffleFileRange {
+    inner: ReaderImpl,
+}
+
+enum ReaderImpl {
+    Buffered(Take<File>),
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring(Box<uring::UringReader>),
+}
+
+impl ShuffleFileRange {
+    /// Read `length` bytes of `file` from `offset`, or up to the end of the file if
+    /// it is shorter
+    pub fn new(mut file: File, offset: u64, length: u64) -> io::Result<Self> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if uring::available() {
+            match uring::ring() {
+                Ok(ring) => {
+                    return Ok(Self {
+                        inner: ReaderImpl::Uring(Box::new(uring::UringReader::new(
+                            file,
+                            ring,
+                            offset,
+                            offset.saturating_add(length),
+                        ))),
+                    })
+                }
+                Err(e) => debug!("Falling back on buffered shuffle file reads: {e}"),
+            }
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Self {
+            inner: ReaderImpl::Buffered(file.take(length)),
+        })
+    }
+}
+
+impl Read for ShuffleFileRange {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            ReaderImpl::Buffered(reader) => reader.read(buf),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            ReaderImpl::Uring(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// Whether shuffle files are written and read with io_uring
+pub fn shuffle_io_uring_enabled() -> bool {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    {
+        uring::available()
+    }
+    #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+    {
+        false
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring {
+    use std::collections::{HashMap, VecDeque};
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::os::unix::fs::FileExt;
+    use std::os::unix::io::AsRawFd;
+    use std::sync::OnceLock;
+
+    use io_uring::{opcode, squeue, types, IoUring};
+    use log::{info, warn};
+
+    use super::WRITE_BUFFER_SIZE;
+
+    /// Number of writes or reads of a file in flight
+    const QUEUE_DEPTH: usize = 4;
+
+    /// Size of the reads of a [UringReader]
+    const READ_CHUNK_SIZE: usize = 256 * 1024;
+
+    /// Whether the kernel supports io_uring, probed once
+    pub(super) fn available() -> bool {
+        static AVAILABLE: OnceLock<bool> = OnceLock::new();
+        *AVAILABLE.get_or_init(|| match IoUring::new(QUEUE_DEPTH as u32) {
+            Ok(_) => {
+                info!("Using io_uring for shuffle file IO");
+                true
+            }
+            Err(e) => {
+                warn!("io_uring is not available, using buffered shuffle file IO: {e}");
+                false
+            }
+        })
+    }
+
+    pub(super) fn ring() -> io::Result<IoUring> {
+        IoUring::new(QUEUE_DEPTH as u32)
+    }
+
+    /// Submit an operation. The submission queue has room for it as long as at most
+    /// [QUEUE_DEPTH] operations are in flight.
+    ///
+    /// # Safety
+    ///
+    /// The buffer of the operation must stay valid until it completes.
+    unsafe fn submit(ring: &mut IoUring, entry: &squeue::Entry) -> io::Result<()> {
+        ring.submission()
+            .push(entry)
+            .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+        ring.submit()?;
+        Ok(())
+    }
+
+    /// Wait for an operation to complete, returning the user data and result of all
+    /// the completed operations
+    fn wait(ring: &mut IoUring) -> io::Result<Vec<(u64, i32)>> {
+        loop {
+            match ring.submit_and_wait(1) {
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(ring
+            .completion()
+            .map(|entry| (entry.user_data(), entry.result()))
+            .collect())
+    }
+
+    /// Writer buffering the writes to a file, and writing each full buffer with an
+    /// io_uring write
+    pub(super) struct UringWriter {
+        file: File,
+        ring: IoUring,
+        buffer: Vec<u8>,
+        /// Offset in the file of the next buffer written
+        offset: u64,
+        /// Buffers being written, with their offset, by the user data of their write
+        in_flight: HashMap<u64, (u64, Vec<u8>)>,
+        next_id: u64,
+    }
+
+    impl UringWriter {
+        pub(super) fn new(file: File, ring: IoUring) -> Self {
+            Self {
+                file,
+                ring,
+                buffer: Vec::with_capacity(WRITE_BUFFER_SIZE),
+                offset: 0,
+                in_flight: HashMap::new(),
+                next_id: 0,
+            }
+        }
+
+        pub(super) fn get_ref(&self) -> &File {
+            &self.file
+        }
+
+        fn submit_buffer(&mut self) -> io::Result<()> {
+            if self.buffer.is_empty() {
+                return Ok(());
+            }
+            while self.in_flight.len() >= QUEUE_DEPTH {
+                self.reap()?;
+            }
+
+            let buffer = std::mem::replace(
+                &mut self.buffer,
+                Vec::with_capacity(WRITE_BUFFER_SIZE),
+            );
+            let id = self.next_id;
+            self.next_id += 1;
+            let entry = opcode::Write::new(
+                types::Fd(self.file.as_raw_fd()),
+                buffer.as_ptr(),
+                buffer.len() as u32,
+            )
+            .offset(self.offset)
+            .build()
+            .user_data(id);
+            // the buffer is kept in flight until the write completes
+            unsafe { submit(&mut self.ring, &entry)? };
+            let offset = self.offset;
+            self.offset += buffer.len() as u64;
+            self.in_flight.insert(id, (offset, buffer));
+            Ok(())
+        }
+
+        /// Wait for writes to complete
+        fn reap(&mut self) -> io::Result<()> {
+            let completions = wait(&mut self.ring)?;
+            self.complete(completions)
+        }
+
+        /// Release the buffers of completed writes, returning the first error of the
+        /// writes
+        fn complete(&mut self, completions: Vec<(u64, i32)>) -> io::Result<()> {
+            let mut result = Ok(());
+            for (id, written) in completions {
+                let Some((offset, buffer)) = self.in_flight.remove(&id) else {
+                    continue;
+                };
+                let completed = if written < 0 {
+                    Err(io::Error::from_raw_os_error(-written))
+                } else if (written as usize) < buffer.len() {
+                    // short writes are rare, and are completed synchronously
+                    let written = written as usize;
+                    self.file
+                        .write_all_at(&buffer[written..], offset + written as u64)
+                } else {
+                    Ok(())
+                };
+                if result.is_ok() {
+                    result = completed;
+                }
+            }
+            result
+        }
+    }
+
+    impl Write for UringWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.buffer.len() == WRITE_BUFFER_SIZE {
+                self.submit_buffer()?;
+            }
+            let len = buf.len().min(WRITE_BUFFER_SIZE - self.buffer.len());
+            self.buffer.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.submit_buffer()?;
+            while !self.in_flight.is_empty() {
+                self.reap()?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for UringWriter {
+        fn drop(&mut self) {
+            let _ = self.submit_buffer();
+            while !self.in_flight.is_empty() {
+                match wait(&mut self.ring) {
+                    Ok(completions) => {
+                        let _ = self.complete(completions);
+                    }
+                    Err(_) => {
+                        // the kernel may still be writing the buffers
+                        std::mem::forget(std::mem::take(&mut self.in_flight));
+                    }
+                }
+            }
+        }
+    }
+
+    struct PendingRead {
+        id: u64,
+        offset: u64,
+        buffer: Vec<u8>,
+        result: Option<i32>,
+    }
+
+    /// Reader of a range of bytes of a file, keeping reads of the next chunks of the
+    /// range in flight
+    pub(super) struct UringReader {
+        file: File,
+        ring: IoUring,
+        /// Offset of the next chunk to read
+        next_offset: u64,
+        end: u64,
+        /// Reads in flight, in the order of their offsets
+        pending: VecDeque<PendingRead>,
+        next_id: u64,
+        chunk: Vec<u8>,
+        position: usize,
+    }
+
+    impl UringReader {
+        pub(super) fn new(file: File, ring: IoUring, offset: u64, end: u64) -> Self {
+            Self {
+                file,
+                ring,
+                next_offset: offset,
+                end,
+                pending: VecDeque::new(),
+                next_id: 0,
+                chunk: vec![],
+                position: 0,
+            }
+        }
+
+        fn submit_reads(&mut self) -> io::Result<()> {
+            while self.pending.len() < QUEUE_DEPTH && self.next_offset < self.end {
+                let len = (READ_CHUNK_SIZE as u64).min(self.end - self.next_offset);
+                let mut buffer = vec![0u8; len as usize];
+                let id = self.next_id;
+                self.next_id += 1;
+                let entry = opcode::Read::new(
+                    types::Fd(self.file.as_raw_fd()),
+                    buffer.as_mut_ptr(),
+                    len as u32,
+                )
+                .offset(self.next_offset)
+                .build()
+                .user_data(id);
+                // the buffer is kept pending until the read completes
+                unsafe { submit(&mut self.ring, &entry)? };
+                self.pending.push_back(PendingRead {
+                    id,
+                    offset: self.next_offset,
+                    buffer,
+                    result: None,
+                });
+                self.next_offset += len;
+            }
+            Ok(())
+        }
+
+        fn complete(&mut self, completions: Vec<(u64, i32)>) {
+            for (id, result) in completions {
+                if let Some(read) = self.pending.iter_mut().find(|read| read.id == id) {
+                    read.result = Some(result);
+                }
+            }
+        }
+
+        /// Move on to the next chunk of the range, returning whether there is one
+        fn next_chunk(&mut self) -> io::Result<bool> {
+            self.submit_reads()?;
+            loop {
+                match self.pending.front() {
+                    None => return Ok(false),
+                    Some(read) if read.result.is_some() => break,
+                    Some(_) => {
+                        let completions = wait(&mut self.ring)?;
+                        self.complete(completions);
+                    }
+                }
+            }
+
+            let PendingRead {
+                offset,
+                mut buffer,
+                result,
+                ..
+            } = self.pending.pop_front().unwrap();
+            let result = result.unwrap();
+            if result < 0 {
+                return Err(io::Error::from_raw_os_error(-result));
+            }
+            // short reads are rare, and are completed synchronously
+            let mut len = result as usize;
+            while len < buffer.len() {
+                let read = self.file.read_at(&mut buffer[len..], offset + len as u64)?;
+                if read == 0 {
+                    // the file is shorter than the range
+                    self.end = offset + len as u64;
+                    break;
+                }
+                len += read;
+            }
+            buffer.truncate(len);
+            self.chunk = buffer;
+            self.position = 0;
+            Ok(true)
+        }
+    }
+
+    impl Read for UringReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.position == self.chunk.len() {
+                if !self.next_chunk()? {
+                    return Ok(0);
+                }
+                if self.chunk.is_empty() {
+                    return Ok(0);
+                }
+            }
+            let len = buf.len().min(self.chunk.len() - self.position);
+            buf[..len].copy_from_slice(&self.chunk[self.position..self.position + len]);
+            self.position += len;
+            Ok(len)
+        }
+    }
+
+    impl Drop for UringReader {
+        fn drop(&mut self) {
+            while self.pending.iter().any(|read| read.result.is_none()) {
+                match wait(&mut self.ring) {
+                    Ok(completions) => self.complete(completions),
+                    Err(_) => {
+                        // the kernel may still be reading into the buffers
+                        std::mem::forget(std::mem::take(&mut self.pending));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn write_and_read_ranges() -> io::Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("data.arrow");
+        // larger than the buffers and chunks of the writers and readers
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut writer = ShuffleFileWriter::create(&path)?;
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk)?;
+        }
+        assert_eq!(data.len() as u64, writer.position());
+        writer.sync_all()?;
+        drop(writer);
+        assert_eq!(data, std::fs::read(&path)?);
+
+        let read_range = |offset: u64, length: u64| -> io::Result<Vec<u8>> {
+            let mut buf = vec![];
+            ShuffleFileRange::new(File::open(&path)?, offset, length)?
+                .read_to_end(&mut buf)?;
+            Ok(buf)
+        };
+        assert_eq!(data, read_range(0, u64::MAX)?);
+        assert_eq!(data[1000..300_000], read_range(1000, 299_000)?);
+        // ranges past the end of the file are cut short
+        assert_eq!(data[999_000..], read_range(999_000, 10_000)?);
+        assert!(read_range(2_000_000, 10)?.is_empty());
+        Ok(())
+    }
+}
@@ -16,39 +16,57 @@
 // under the License.
 
 use async_trait::async_trait;
-use datafusion::arrow::ipc::reader::StreamReader;
 use datafusion::common::stats::Precision;
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::fs::File;
-use std::io::BufReader;
 use std::pin::Pin;
 use std::result;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use crate::client::BallistaClient;
-use crate::serde::scheduler::{PartitionLocation, PartitionStats};
+use crate::compression::{CompressionCodec, NoCompression};
+use crate::config::SHUFFLE_IPC_METADATA_VERSION;
+use crate::execution_plans::shuffle_fetch_limiter::{
+    ShuffleFetchLimiter, ShuffleFetchPermit,
+};
+use crate::execution_plans::shuffle_storage::{
+    is_object_store_path, read_object_store_partition,
+};
+use crate::execution_plans::sort_shuffle::{open_shuffle_file, ShuffleFileReader};
+use crate::execution_plans::sorted_runs::merge_sorted_runs;
+use crate::extension::{SessionConfigExt, SessionConfigHelperExt};
+use crate::security::{FetchToken, GrpcSecurityConfig};
+use crate::serde::scheduler::{PartitionLocation, PartitionStats, ShuffleFormats};
 
+use datafusion::arrow::compute::filter_record_batch;
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::cast::as_boolean_array;
 use datafusion::common::runtime::SpawnedTask;
 
-use datafusion::error::Result;
-use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricsSet};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_expr::{EquivalenceProperties, PhysicalSortExpr};
+use datafusion::physical_plan::metrics::{
+    self, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
 use datafusion::physical_plan::{
     ColumnStatistics, DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning,
-    PlanProperties, RecordBatchStream, SendableRecordBatchStream, Statistics,
+    PhysicalExpr, PlanProperties, RecordBatchStream, SendableRecordBatchStream,
+    Statistics,
 };
 use futures::{Stream, StreamExt, TryStreamExt};
 
 use crate::error::BallistaError;
 use datafusion::execution::context::TaskContext;
+use datafusion::execution::runtime_env::RuntimeEnv;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::prelude::SessionConfig;
 use itertools::Itertools;
-use log::{error, info};
+use log::{error, info, warn};
 use rand::prelude::SliceRandom;
 use rand::thread_rng;
 use tokio::sync::{mpsc, Semaphore};
@@ -63,6 +81,15 @@ pub struct ShuffleReaderExec {
     pub(crate) schema: SchemaRef,
     /// Each partition of a shuffle can read data from multiple locations
     pub partition: Vec<Vec<PartitionLocation>>,
+    /// Predicates attached after planning, e.g. from dynamic pruning results. Rows not
+    /// matching all of them are dropped while reading
+    pub runtime_predicates: Vec<Arc<dyn PhysicalExpr>>,
+    /// If not empty, each location of a partition holds rows sorted on these
+    /// expressions, which are merged into a sorted partition
+    pub sort_order: Vec<PhysicalSortExpr>,
+    /// Number of partitions of the shuffle, more than the partitions read when
+    /// adjacent partitions of the shuffle were coalesced
+    pub shuffle_partition_count: usize,
     /// Execution metrics
     metrics: ExecutionPlanMetricsSet,
     properties: PlanProperties,
@@ -85,11 +112,47 @@ impl ShuffleReaderExec {
         Ok(Self {
             stage_id,
             schema,
+            shuffle_partition_count: partition.len(),
             partition,
+            runtime_predicates: vec![],
+            sort_order: vec![],
             metrics: ExecutionPlanMetricsSet::new(),
             properties,
         })
     }
+
+    /// Attach predicates to be applied to the batches read by this reader
+    pub fn with_runtime_predicates(
+        mut self,
+        predicates: impl IntoIterator<Item = Arc<dyn PhysicalExpr>>,
+    ) -> Self {
+        self.runtime_predicates.extend(predicates);
+        self
+    }
+
+    /// Merge the locations of each partition, which hold rows sorted on `sort_order`,
+    /// into sorted partitions
+    pub fn with_sort_order(mut self, sort_order: Vec<PhysicalSortExpr>) -> Self {
+        if !sort_order.is_empty() {
+            let eq_properties = EquivalenceProperties::new_with_orderings(
+                self.schema.clone(),
+                std::slice::from_ref(&sort_order),
+            );
+            self.properties = self.properties.clone().with_eq_properties(eq_properties);
+        }
+        self.sort_order = sort_order;
+        self
+    }
+
+    /// Set the number of partitions of the shuffle, whose adjacent partitions were
+    /// coalesced into the partitions read
+    pub fn with_shuffle_partition_count(
+        mut self,
+        shuffle_partition_count: usize,
+    ) -> Self {
+        self.shuffle_partition_count = shuffle_partition_count;
+        self
+    }
 }
 
 impl DisplayAs for ShuffleReaderExec {
@@ -100,7 +163,21 @@ impl DisplayAs for ShuffleReaderExec {
     ) -> std::fmt::Result {
         match t {
             DisplayFormatType::Default | DisplayFormatType::Verbose => {
-                write!(f, "ShuffleReaderExec: partitions={}", self.partition.len())
+                write!(f, "ShuffleReaderExec: partitions={}", self.partition.len())?;
+                if self.shuffle_partition_count != self.partition.len() {
+                    write!(f, ", shuffle_partitions={}", self.shuffle_partition_count)?;
+                }
+                if !self.runtime_predicates.is_empty() {
+                    write!(
+                        f,
+                        ", runtime_predicates=[{}]",
+                        self.runtime_predicates.iter().join(", ")
+                    )?;
+                }
+                if !self.sort_order.is_empty() {
+                    write!(f, ", sort_order=[{}]", self.sort_order.iter().join(", "))?;
+                }
+                Ok(())
             }
         }
     }
@@ -130,11 +207,16 @@ impl ExecutionPlan for ShuffleReaderExec {
         self: Arc<Self>,
         _children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(ShuffleReaderExec::try_new(
-            self.stage_id,
-            self.partition.clone(),
-            self.schema.clone(),
-        )?))
+        Ok(Arc::new(
+            ShuffleReaderExec::try_new(
+                self.stage_id,
+                self.partition.clone(),
+                self.schema.clone(),
+            )?
+            .with_runtime_predicates(self.runtime_predicates.clone())
+            .with_sort_order(self.sort_order.clone())
+            .with_shuffle_partition_count(self.shuffle_partition_count),
+        ))
     }
 
     fn execute(
@@ -145,32 +227,73 @@ impl ExecutionPlan for ShuffleReaderExec {
         let task_id = context.task_id().unwrap_or_else(|| partition.to_string());
         info!("ShuffleReaderExec::execute({})", task_id);
 
-        // TODO make the maximum size configurable, or make it depends on global memory control
-        let max_request_num = 50usize;
+        let session_config = context.session_config();
+        let transfer_compression = session_config
+            .ballista_shuffle_transfer_compression()
+            .map(|name| session_config.ballista_compression_codecs().codec(&name))
+            .transpose()
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let fetcher = PartitionFetcher {
+            runtime: context.runtime_env(),
+            security: session_config.ballista_grpc_security(),
+            fetch_token: session_config.ballista_fetch_token(),
+            transfer_compression,
+            options: FetchOptions::new(session_config),
+            limiter: session_config.get_extension::<ShuffleFetchLimiter>(),
+            task_id: Arc::from(task_id.as_str()),
+        };
         let mut partition_locations = HashMap::new();
-        for p in &self.partition[partition] {
+        for replicas in partition_replicas(&self.partition[partition]) {
             partition_locations
-                .entry(p.executor_meta.id.clone())
+                .entry(replicas[0].executor_meta.id.clone())
                 .or_insert_with(Vec::new)
-                .push(p.clone());
+                .push(replicas);
         }
         // Sort partitions for evenly send fetching partition requests to avoid hot executors within one task
-        let mut partition_locations: Vec<PartitionLocation> = partition_locations
+        let mut partition_locations: Vec<Vec<PartitionLocation>> = partition_locations
             .into_values()
             .flat_map(|ps| ps.into_iter().enumerate())
             .sorted_by(|(p1_idx, _), (p2_idx, _)| Ord::cmp(p1_idx, p2_idx))
             .map(|(_, p)| p)
             .collect();
-        // Shuffle partitions for evenly send fetching partition requests to avoid hot executors within multiple tasks
-        partition_locations.shuffle(&mut thread_rng());
+        let batches: FetchedBatches = if !self.sort_order.is_empty() {
+            // The sorted runs are merged in a fixed order, so that equal rows come out
+            // in the same order on every run
+            partition_locations.sort_by_key(|p| p[0].map_partition_id);
+            let runs = fetch_partitions_in_order(partition_locations, fetcher);
+            let schema = self.schema.clone();
+            let sort_order = self.sort_order.clone();
+            let batch_size = session_config.batch_size();
+            let merged = async move {
+                let runs: Vec<_> = runs.try_collect().await?;
+                merge_sorted_runs(runs, schema, sort_order, batch_size)
+                    .map_err(|e| ArrowError::ExternalError(Box::new(e)))
+            };
+            Box::pin(futures::stream::once(merged).try_flatten())
+        } else if session_config.ballista_deterministic() {
+            // Read the map outputs in a fixed order, so that the order of rows, and
+            // the results of order sensitive computations, are the same on every run
+            partition_locations.sort_by_key(|p| p[0].map_partition_id);
+            Box::pin(
+                fetch_partitions_in_order(partition_locations, fetcher).try_flatten(),
+            )
+        } else {
+            // Shuffle partitions for evenly send fetching partition requests to avoid hot executors within multiple tasks
+            partition_locations.shuffle(&mut thread_rng());
+            Box::pin(send_fetch_partitions(partition_locations, fetcher))
+        };
 
-        let response_receiver =
-            send_fetch_partitions(partition_locations, max_request_num);
+        let predicates = self.runtime_predicates.clone();
+        let filtered_rows =
+            MetricBuilder::new(&self.metrics).counter("runtime_filtered_rows", partition);
+        let stream = batches.map(move |batch| {
+            batch.and_then(|batch| {
+                apply_runtime_predicates(batch, &predicates, &filtered_rows)
+            })
+        });
 
-        let result = RecordBatchStreamAdapter::new(
-            Arc::new(self.schema.as_ref().clone()),
-            response_receiver.try_flatten(),
-        );
+        let result =
+            RecordBatchStreamAdapter::new(Arc::new(self.schema.as_ref().clone()), stream);
         Ok(Box::pin(result))
     }
 
@@ -189,6 +312,21 @@ impl ExecutionPlan for ShuffleReaderExec {
     }
 }
 
+/// Drops the rows of the batch not matching all of the runtime predicates
+fn apply_runtime_predicates(
+    mut batch: RecordBatch,
+    predicates: &[Arc<dyn PhysicalExpr>],
+    filtered_rows: &metrics::Count,
+) -> Result<RecordBatch> {
+    for predicate in predicates {
+        let num_rows = batch.num_rows();
+        let mask = predicate.evaluate(&batch)?.into_array(num_rows)?;
+        batch = filter_record_batch(&batch, as_boolean_array(&mask)?)?;
+        filtered_rows.add(num_rows - batch.num_rows());
+    }
+    Ok(batch)
+}
+
 fn stats_for_partitions(
     num_fields: usize,
     partition_stats: impl Iterator<Item = PartitionStats>,
@@ -214,11 +352,11 @@ fn stats_for_partitions(
 }
 
 struct LocalShuffleStream {
-    reader: StreamReader<BufReader<File>>,
+    reader: ShuffleFileReader,
 }
 
 impl LocalShuffleStream {
-    pub fn new(reader: StreamReader<BufReader<File>>) -> Self {
+    pub fn new(reader: ShuffleFileReader) -> Self {
         LocalShuffleStream { reader }
     }
 }
@@ -243,20 +381,19 @@ impl RecordBatchStream for LocalShuffleStream {
     }
 }
 
-/// Adapter for a tokio ReceiverStream that implements the SendableRecordBatchStream interface
+/// Adapter for a tokio ReceiverStream of the batches of the fetched partitions, which
+/// aborts the fetches once dropped
 struct AbortableReceiverStream {
-    inner: ReceiverStream<result::Result<SendableRecordBatchStream, BallistaError>>,
+    inner: ReceiverStream<Result<RecordBatch>>,
 
     #[allow(dead_code)]
     drop_helper: Vec<SpawnedTask<()>>,
 }
 
 impl AbortableReceiverStream {
-    /// Construct a new SendableRecordBatchReceiverStream which will send batches of the specified schema from inner
+    /// Construct a new AbortableReceiverStream which will send the batches received from rx
     pub fn create(
-        rx: tokio::sync::mpsc::Receiver<
-            result::Result<SendableRecordBatchStream, BallistaError>,
-        >,
+        rx: tokio::sync::mpsc::Receiver<Result<RecordBatch>>,
         spawned_tasks: Vec<SpawnedTask<()>>,
     ) -> AbortableReceiverStream {
         let inner = ReceiverStream::new(rx);
@@ -268,28 +405,198 @@ impl AbortableReceiverStream {
 }
 
 impl Stream for AbortableReceiverStream {
-    type Item = result::Result<SendableRecordBatchStream, ArrowError>;
+    type Item = Result<RecordBatch>;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.inner
-            .poll_next_unpin(cx)
-            .map_err(|e| ArrowError::ExternalError(Box::new(e)))
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// Batches of the fetched partitions of a shuffle
+type FetchedBatches = Pin<Box<dyn Stream<Item = Result<RecordBatch>> + Send>>;
+
+/// Options of the fetches of the shuffle partitions of a task, set per query
+#[derive(Debug, Clone, Copy)]
+struct FetchOptions {
+    /// Maximum number of partitions fetched at the same time
+    max_concurrent_fetches: usize,
+    /// Number of times a fetch failing with a transient error is retried
+    max_retries: usize,
+    /// Delay before the first retry of a fetch, doubled on every following retry
+    retry_backoff: Duration,
+    /// Maximum number of fetched batches buffered until the task consumes them
+    max_buffered_batches: usize,
+}
+
+impl FetchOptions {
+    fn new(config: &SessionConfig) -> Self {
+        Self {
+            max_concurrent_fetches: config
+                .ballista_shuffle_max_concurrent_fetches()
+                .max(1),
+            max_retries: config.ballista_shuffle_fetch_retries(),
+            retry_backoff: Duration::from_millis(
+                config.ballista_shuffle_fetch_retry_backoff_ms() as u64,
+            ),
+            max_buffered_batches: config.ballista_shuffle_max_buffered_batches().max(1),
+        }
     }
 }
 
+/// Fetches the shuffle partitions of a task
+#[derive(Clone)]
+struct PartitionFetcher {
+    runtime: Arc<RuntimeEnv>,
+    security: Arc<GrpcSecurityConfig>,
+    fetch_token: Arc<FetchToken>,
+    transfer_compression: Option<Arc<dyn CompressionCodec>>,
+    options: FetchOptions,
+    /// Limit of the concurrent remote fetches of all the tasks of the executor
+    limiter: Option<Arc<ShuffleFetchLimiter>>,
+    /// Task the fetches are limited for, along with the other tasks of the executor
+    task_id: Arc<str>,
+}
+
+impl PartitionFetcher {
+    /// Fetches a partition from the first of its locations, the other ones being
+    /// replicas of the partition. Remote fetches failing with a transient error are
+    /// retried with an exponential backoff, before falling back to the next replica.
+    /// Remote fetches wait for a permit of the limiter of the executor, held until the
+    /// partition is read.
+    async fn fetch(
+        &self,
+        replicas: &[PartitionLocation],
+    ) -> result::Result<SendableRecordBatchStream, BallistaError> {
+        let mut first_error = None;
+        let mut permit = None;
+        for location in replicas {
+            let reader = partition_reader(
+                location,
+                &self.runtime,
+                &self.security,
+                &self.fetch_token,
+                &self.transfer_compression,
+            );
+            // a missing local file doesn't show up by retrying
+            let max_retries = match reader {
+                PartitionReaderEnum::Local => 0,
+                _ => self.options.max_retries,
+            };
+            if let Some(limiter) = &self.limiter {
+                if permit.is_none() && !matches!(reader, PartitionReaderEnum::Local) {
+                    permit = Some(limiter.acquire(&self.task_id).await);
+                }
+            }
+            let mut backoff = self.options.retry_backoff;
+            for attempt in 0..=max_retries {
+                if attempt > 0 {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                match reader.fetch_partition(location).await {
+                    Ok(stream) => return Ok(holding_permit(stream, permit)),
+                    Err(e @ BallistaError::FetchFailed(..)) => {
+                        warn!(
+                            "Fetch attempt {} of partition {:?} from executor {} failed: {}",
+                            attempt + 1,
+                            location.partition_id,
+                            location.executor_meta.id,
+                            e
+                        );
+                        first_error.get_or_insert(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        // the error of the first location is reported, whose map task is re-run if the
+        // scheduler resubmits the map stage
+        Err(first_error.unwrap_or_else(|| {
+            BallistaError::Internal("No location to fetch the partition from".to_owned())
+        }))
+    }
+}
+
+/// The batches of a fetched partition, releasing the permit of the fetch once they
+/// are read
+fn holding_permit(
+    stream: SendableRecordBatchStream,
+    permit: Option<ShuffleFetchPermit>,
+) -> SendableRecordBatchStream {
+    let Some(permit) = permit else {
+        return stream;
+    };
+    let schema = stream.schema();
+    Box::pin(RecordBatchStreamAdapter::new(
+        schema,
+        stream.map(move |batch| {
+            let _permit = &permit;
+            batch
+        }),
+    ))
+}
+
+/// Groups the locations of the same map output, the first one of each group being
+/// fetched and the others being replicas to fall back to
+fn partition_replicas(locations: &[PartitionLocation]) -> Vec<Vec<PartitionLocation>> {
+    let mut replicas: Vec<Vec<PartitionLocation>> = vec![];
+    let mut groups: HashMap<(&str, usize, usize, usize), usize> = HashMap::new();
+    for location in locations {
+        // a reader may read the outputs of several jobs, e.g. of the iterations of a
+        // recursive query
+        let key = (
+            location.partition_id.job_id.as_str(),
+            location.partition_id.stage_id,
+            location.map_partition_id,
+            location.partition_id.partition_id,
+        );
+        match groups.get(&key) {
+            Some(&group) => replicas[group].push(location.clone()),
+            None => {
+                groups.insert(key, replicas.len());
+                replicas.push(vec![location.clone()]);
+            }
+        }
+    }
+    replicas
+}
+
+/// Fetch partitions concurrently, but stream them in the order of `partition_locations`
+fn fetch_partitions_in_order(
+    partition_locations: Vec<Vec<PartitionLocation>>,
+    fetcher: PartitionFetcher,
+) -> impl Stream<Item = result::Result<SendableRecordBatchStream, ArrowError>> {
+    let max_concurrent_fetches = fetcher.options.max_concurrent_fetches;
+    futures::stream::iter(partition_locations)
+        .map(move |replicas| {
+            let fetcher = fetcher.clone();
+            async move {
+                fetcher
+                    .fetch(&replicas)
+                    .await
+                    .map_err(|e| ArrowError::ExternalError(Box::new(e)))
+            }
+        })
+        .buffered(max_concurrent_fetches)
+}
+
+/// Fetch partitions concurrently, streaming their batches as they arrive. The fetches
+/// pause while the task doesn't consume the buffered batches
 fn send_fetch_partitions(
-    partition_locations: Vec<PartitionLocation>,
-    max_request_num: usize,
+    partition_locations: Vec<Vec<PartitionLocation>>,
+    fetcher: PartitionFetcher,
 ) -> AbortableReceiverStream {
-    let (response_sender, response_receiver) = mpsc::channel(max_request_num);
-    let semaphore = Arc::new(Semaphore::new(max_request_num));
+    let options = fetcher.options;
+    let (response_sender, response_receiver) =
+        mpsc::channel(options.max_buffered_batches);
+    let semaphore = Arc::new(Semaphore::new(options.max_concurrent_fetches));
     let mut spawned_tasks: Vec<SpawnedTask<()>> = vec![];
     let (local_locations, remote_locations): (Vec<_>, Vec<_>) = partition_locations
         .into_iter()
-        .partition(check_is_local_location);
+        .partition(|replicas| check_is_local_location(&replicas[0]));
 
     info!(
         "local shuffle file counts:{}, remote shuffle file count:{}.",
@@ -299,26 +606,24 @@ fn send_fetch_partitions(
 
     // keep local shuffle files reading in serial order for memory control.
     let response_sender_c = response_sender.clone();
+    let fetcher_c = fetcher.clone();
     spawned_tasks.push(SpawnedTask::spawn(async move {
-        for p in local_locations {
-            let r = PartitionReaderEnum::Local.fetch_partition(&p).await;
-            if let Err(e) = response_sender_c.send(r).await {
-                error!("Fail to send response event to the channel due to {}", e);
-            }
+        for replicas in local_locations {
+            let r = fetcher_c.fetch(&replicas).await;
+            send_partition_batches(r, &response_sender_c).await;
         }
     }));
 
-    for p in remote_locations.into_iter() {
+    for replicas in remote_locations.into_iter() {
+        let fetcher = fetcher.clone();
         let semaphore = semaphore.clone();
         let response_sender = response_sender.clone();
         spawned_tasks.push(SpawnedTask::spawn(async move {
             // Block if exceeds max request number.
             let permit = semaphore.acquire_owned().await.unwrap();
-            let r = PartitionReaderEnum::FlightRemote.fetch_partition(&p).await;
-            // Block if the channel buffer is full.
-            if let Err(e) = response_sender.send(r).await {
-                error!("Fail to send response event to the channel due to {}", e);
-            }
+            let r = fetcher.fetch(&replicas).await;
+            // Block while the channel buffer is full.
+            send_partition_batches(r, &response_sender).await;
             // Increase semaphore by dropping existing permits.
             drop(permit);
         }));
@@ -327,8 +632,65 @@ fn send_fetch_partitions(
     AbortableReceiverStream::create(response_receiver, spawned_tasks)
 }
 
+/// Sends the batches of a fetched partition, or the error fetching it, to the channel
+async fn send_partition_batches(
+    partition: result::Result<SendableRecordBatchStream, BallistaError>,
+    response_sender: &mpsc::Sender<Result<RecordBatch>>,
+) {
+    let mut stream = match partition {
+        Ok(stream) => stream,
+        Err(e) => {
+            let e = DataFusionError::from(ArrowError::ExternalError(Box::new(e)));
+            if let Err(e) = response_sender.send(Err(e)).await {
+                error!("Fail to send response event to the channel due to {}", e);
+            }
+            return;
+        }
+    };
+    while let Some(batch) = stream.next().await {
+        let failed = batch.is_err();
+        if response_sender.send(batch).await.is_err() {
+            // the task stopped reading its input
+            return;
+        }
+        if failed {
+            return;
+        }
+    }
+}
+
 fn check_is_local_location(location: &PartitionLocation) -> bool {
-    std::path::Path::new(location.path.as_str()).exists()
+    !is_object_store_path(&location.path)
+        && std::path::Path::new(location.path.as_str()).exists()
+}
+
+/// Reads a partition from the object store holding it, from the local disk if it was
+/// written by this executor, or else from the executor which wrote it with the token of
+/// the job, as well as the partitions in a format this executor doesn't read
+fn partition_reader(
+    location: &PartitionLocation,
+    runtime: &Arc<RuntimeEnv>,
+    security: &Arc<GrpcSecurityConfig>,
+    fetch_token: &Arc<FetchToken>,
+    transfer_compression: &Option<Arc<dyn CompressionCodec>>,
+) -> PartitionReaderEnum {
+    let readable = ShuffleFormats::supported()
+        .reads(location.compression, location.ipc_metadata_version);
+    if is_object_store_path(&location.path) {
+        PartitionReaderEnum::ObjectStoreRemote(runtime.clone())
+    } else if readable && check_is_local_location(location) {
+        PartitionReaderEnum::Local
+    } else {
+        // the executor which wrote a partition this one doesn't read re-encodes it
+        let transfer_compression = transfer_compression.clone().or_else(|| {
+            (!readable).then(|| Arc::new(NoCompression) as Arc<dyn CompressionCodec>)
+        });
+        PartitionReaderEnum::FlightRemote(
+            security.clone(),
+            fetch_token.clone(),
+            transfer_compression,
+        )
+    }
 }
 
 /// Partition reader Trait, different partition reader can have
@@ -344,9 +706,12 @@ trait PartitionReader: Send + Sync + Clone {
 #[derive(Clone)]
 enum PartitionReaderEnum {
     Local,
-    FlightRemote,
-    #[allow(dead_code)]
-    ObjectStoreRemote,
+    FlightRemote(
+        Arc<GrpcSecurityConfig>,
+        Arc<FetchToken>,
+        Option<Arc<dyn CompressionCodec>>,
+    ),
+    ObjectStoreRemote(Arc<RuntimeEnv>),
 }
 
 #[async_trait]
@@ -357,10 +722,22 @@ impl PartitionReader for PartitionReaderEnum {
         location: &PartitionLocation,
     ) -> result::Result<SendableRecordBatchStream, BallistaError> {
         match self {
-            PartitionReaderEnum::FlightRemote => fetch_partition_remote(location).await,
+            PartitionReaderEnum::FlightRemote(
+                security,
+                fetch_token,
+                transfer_compression,
+            ) => {
+                fetch_partition_remote(
+                    location,
+                    security,
+                    fetch_token,
+                    transfer_compression.clone(),
+                )
+                .await
+            }
             PartitionReaderEnum::Local => fetch_partition_local(location).await,
-            PartitionReaderEnum::ObjectStoreRemote => {
-                fetch_partition_object_store(location).await
+            PartitionReaderEnum::ObjectStoreRemote(runtime) => {
+                fetch_partition_object_store(location, runtime).await
             }
         }
     }
@@ -368,6 +745,9 @@ impl PartitionReader for PartitionReaderEnum {
 
 async fn fetch_partition_remote(
     location: &PartitionLocation,
+    security: &GrpcSecurityConfig,
+    fetch_token: &FetchToken,
+    transfer_compression: Option<Arc<dyn CompressionCodec>>,
 ) -> result::Result<SendableRecordBatchStream, BallistaError> {
     let metadata = &location.executor_meta;
     let partition_id = &location.partition_id;
@@ -375,22 +755,31 @@ async fn fetch_partition_remote(
     // And we should also avoid to keep alive too many connections for long time.
     let host = metadata.host.as_str();
     let port = metadata.port;
-    let mut ballista_client =
-        BallistaClient::try_new(host, port)
-            .await
-            .map_err(|error| match error {
-                // map grpc connection error to partition fetch error.
-                BallistaError::GrpcConnectionError(msg) => BallistaError::FetchFailed(
-                    metadata.id.clone(),
-                    partition_id.stage_id,
-                    partition_id.partition_id,
-                    msg,
-                ),
-                other => other,
-            })?;
+    let mut ballista_client = BallistaClient::try_new(host, port, security)
+        .await
+        .map_err(|error| match error {
+            // map executor connection error to partition fetch error.
+            BallistaError::ExecutorUnreachable(msg) => BallistaError::FetchFailed(
+                metadata.id.clone(),
+                partition_id.stage_id,
+                partition_id.partition_id,
+                msg,
+            ),
+            other => other,
+        })?;
 
     ballista_client
-        .fetch_partition(&metadata.id, partition_id, &location.path, host, port)
+        .fetch_partition(
+            &metadata.id,
+            partition_id,
+            &location.path,
+            host,
+            port,
+            location.compression,
+            fetch_token.as_str(),
+            transfer_compression,
+            SHUFFLE_IPC_METADATA_VERSION,
+        )
         .await
 }
 
@@ -401,42 +790,48 @@ async fn fetch_partition_local(
     let metadata = &location.executor_meta;
     let partition_id = &location.partition_id;
 
-    let reader = fetch_partition_local_inner(path).map_err(|e| {
-        // return BallistaError::FetchFailed may let scheduler retry this task.
-        BallistaError::FetchFailed(
-            metadata.id.clone(),
-            partition_id.stage_id,
-            partition_id.partition_id,
-            e.to_string(),
-        )
-    })?;
+    let reader =
+        fetch_partition_local_inner(path, partition_id.partition_id).map_err(|e| {
+            // return BallistaError::FetchFailed may let scheduler retry this task.
+            BallistaError::FetchFailed(
+                metadata.id.clone(),
+                partition_id.stage_id,
+                partition_id.partition_id,
+                e.to_string(),
+            )
+        })?;
     Ok(Box::pin(LocalShuffleStream::new(reader)))
 }
 
 fn fetch_partition_local_inner(
     path: &str,
-) -> result::Result<StreamReader<BufReader<File>>, BallistaError> {
-    let file = File::open(path).map_err(|e| {
-        BallistaError::General(format!("Failed to open partition file at {path}: {e:?}"))
-    })?;
-    let file = BufReader::new(file);
-    let reader = StreamReader::try_new(file, None).map_err(|e| {
-        BallistaError::General(format!("Failed to new arrow FileReader at {path}: {e:?}"))
-    })?;
-    Ok(reader)
+    partition_id: usize,
+) -> result::Result<ShuffleFileReader, BallistaError> {
+    open_shuffle_file(path, partition_id)
 }
 
 async fn fetch_partition_object_store(
-    _location: &PartitionLocation,
+    location: &PartitionLocation,
+    runtime: &RuntimeEnv,
 ) -> result::Result<SendableRecordBatchStream, BallistaError> {
-    Err(BallistaError::NotImplemented(
-        "Should not use ObjectStorePartitionReader".to_string(),
-    ))
+    let partition_id = &location.partition_id;
+    read_object_store_partition(runtime, &location.path, partition_id.partition_id)
+        .await
+        .map_err(|e| {
+            // return BallistaError::FetchFailed may let scheduler retry this task.
+            BallistaError::FetchFailed(
+                location.executor_meta.id.clone(),
+                partition_id.stage_id,
+                partition_id.partition_id,
+                e.to_string(),
+            )
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ShuffleCompression;
     use crate::execution_plans::ShuffleWriterExec;
     use crate::serde::scheduler::{ExecutorMetadata, ExecutorSpecification, PartitionId};
     use crate::utils;
@@ -444,12 +839,15 @@ mod tests {
     use datafusion::arrow::datatypes::{DataType, Field, Schema};
     use datafusion::arrow::ipc::writer::StreamWriter;
     use datafusion::arrow::record_batch::RecordBatch;
-    use datafusion::common::DataFusionError;
-    use datafusion::physical_expr::expressions::Column;
+    use datafusion::common::{DataFusionError, ScalarValue};
+    use datafusion::logical_expr::Operator;
+    use datafusion::physical_expr::expressions::{BinaryExpr, Column, Literal};
     use datafusion::physical_plan::common;
     use datafusion::physical_plan::memory::MemoryExec;
     use datafusion::prelude::SessionContext;
-    use tempfile::{tempdir, TempDir};
+    use std::fs::File;
+    use std::path::Path;
+    use tempfile::TempDir;
 
     #[tokio::test]
     async fn test_stats_for_partitions_empty() {
@@ -471,11 +869,13 @@ mod tests {
                 num_rows: Some(10),
                 num_bytes: Some(84),
                 num_batches: Some(1),
+                checksum: None,
             },
             PartitionStats {
                 num_rows: Some(4),
                 num_bytes: Some(65),
                 num_batches: None,
+                checksum: None,
             },
         ];
 
@@ -497,11 +897,13 @@ mod tests {
                 num_rows: Some(10),
                 num_bytes: Some(84),
                 num_batches: Some(1),
+                checksum: None,
             },
             PartitionStats {
                 num_rows: None,
                 num_bytes: None,
                 num_batches: None,
+                checksum: None,
             },
         ];
 
@@ -543,9 +945,16 @@ mod tests {
                     port: 7070,
                     grpc_port: 8080,
                     specification: ExecutorSpecification { task_slots: 1 },
+                    version: crate::build_version(),
+                    functions: None,
+                    shuffle_formats: None,
+                    pool: String::new(),
                 },
                 partition_stats: Default::default(),
                 path: "test_path".to_string(),
+                inline_data: None,
+                compression: ShuffleCompression::default(),
+                ipc_metadata_version: 0,
             })
         }
 
@@ -579,6 +988,41 @@ mod tests {
         test_send_fetch_partitions(4, 10).await;
     }
 
+    #[tokio::test]
+    async fn test_fetch_partition_replica() -> result::Result<(), BallistaError> {
+        let work_dir = TempDir::new().unwrap();
+        let file_path = write_test_partition_file(work_dir.path());
+        let mut replicas = get_test_partition_locations(2, file_path.clone());
+        for replica in &mut replicas {
+            replica.partition_id.partition_id = 0;
+        }
+        // the first location is on an unreachable executor
+        replicas[0].path = "missing_path".to_owned();
+        replicas[0].executor_meta.port = 1;
+        assert_eq!(partition_replicas(&replicas).len(), 1);
+        // the same map output of another job is no replica
+        let mut other_job = replicas[1].clone();
+        other_job.partition_id.job_id = "other_job".to_owned();
+        assert_eq!(
+            partition_replicas(&[replicas[1].clone(), other_job]).len(),
+            2
+        );
+
+        let mut stream = test_fetcher(1, 1).fetch(&replicas).await?;
+        let batches = utils::collect_stream(&mut stream).await?;
+        assert_eq!(batches.len(), 1);
+
+        let err = test_fetcher(1, 1)
+            .fetch(&replicas[..1])
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, BallistaError::FetchFailed(ref executor_id, ..) if executor_id == "exec0")
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_read_local_shuffle() {
         let session_ctx = SessionContext::new();
@@ -607,7 +1051,7 @@ mod tests {
 
         // from to input partitions test the first one with two batches
         let file_path = path.value(0);
-        let reader = fetch_partition_local_inner(file_path).unwrap();
+        let reader = fetch_partition_local_inner(file_path, 0).unwrap();
 
         let mut stream: Pin<Box<dyn RecordBatchStream + Send>> =
             async { Box::pin(LocalShuffleStream::new(reader)) }.await;
@@ -623,36 +1067,93 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_apply_runtime_predicates() -> Result<()> {
+        let predicate: Arc<dyn PhysicalExpr> = Arc::new(BinaryExpr::new(
+            Arc::new(Column::new("number", 0)),
+            Operator::Gt,
+            Arc::new(Literal::new(ScalarValue::UInt32(Some(1)))),
+        ));
+        let metrics = ExecutionPlanMetricsSet::new();
+        let filtered_rows =
+            MetricBuilder::new(&metrics).counter("runtime_filtered_rows", 0);
+
+        let batch = apply_runtime_predicates(
+            create_test_batch(),
+            std::slice::from_ref(&predicate),
+            &filtered_rows,
+        )?;
+        assert_eq!(2, batch.num_rows());
+        assert_eq!(1, filtered_rows.value());
+
+        let reader = ShuffleReaderExec::try_new(1, vec![], create_test_schema())?
+            .with_runtime_predicates(vec![predicate]);
+        let display = datafusion::physical_plan::displayable(&reader)
+            .one_line()
+            .to_string();
+        assert_eq!(
+            "ShuffleReaderExec: partitions=0, runtime_predicates=[number@0 > 1]\n",
+            display
+        );
+        Ok(())
+    }
+
     async fn test_send_fetch_partitions(max_request_num: usize, partition_num: usize) {
         let schema = get_test_partition_schema();
-        let data_array = Int32Array::from(vec![1]);
-        let batch =
-            RecordBatch::try_new(Arc::new(schema.clone()), vec![Arc::new(data_array)])
-                .unwrap();
-        let tmp_dir = tempdir().unwrap();
-        let file_path = tmp_dir.path().join("shuffle_data");
-        let file = File::create(&file_path).unwrap();
-        let mut writer = StreamWriter::try_new(file, &schema).unwrap();
-        writer.write(&batch).unwrap();
-        writer.finish().unwrap();
-
+        let work_dir = TempDir::new().unwrap();
         let partition_locations = get_test_partition_locations(
             partition_num,
-            file_path.to_str().unwrap().to_string(),
+            write_test_partition_file(work_dir.path()),
         );
 
-        let response_receiver =
-            send_fetch_partitions(partition_locations, max_request_num);
-
-        let stream = RecordBatchStreamAdapter::new(
-            Arc::new(schema),
-            response_receiver.try_flatten(),
+        // a single buffered batch makes the fetches wait for each other
+        let response_receiver = send_fetch_partitions(
+            partition_replicas(&partition_locations),
+            test_fetcher(max_request_num, 1),
         );
 
+        let stream = RecordBatchStreamAdapter::new(Arc::new(schema), response_receiver);
+
         let result = common::collect(Box::pin(stream)).await.unwrap();
         assert_eq!(partition_num, result.len());
     }
 
+    fn test_fetcher(
+        max_concurrent_fetches: usize,
+        max_buffered_batches: usize,
+    ) -> PartitionFetcher {
+        PartitionFetcher {
+            runtime: Arc::new(RuntimeEnv::default()),
+            security: Arc::default(),
+            fetch_token: Arc::default(),
+            transfer_compression: None,
+            options: FetchOptions {
+                max_concurrent_fetches,
+                max_retries: 1,
+                retry_backoff: Duration::from_millis(1),
+                max_buffered_batches,
+            },
+            limiter: None,
+            task_id: Arc::from("test"),
+        }
+    }
+
+    /// Writes a shuffle file holding a single batch of one row in `dir`, returning its
+    /// path
+    fn write_test_partition_file(dir: &Path) -> String {
+        let schema = get_test_partition_schema();
+        let data_array = Int32Array::from(vec![1]);
+        let batch =
+            RecordBatch::try_new(Arc::new(schema.clone()), vec![Arc::new(data_array)])
+                .unwrap();
+        let file_path = dir.join("shuffle_data");
+        let file = File::create(&file_path).unwrap();
+        let mut writer = StreamWriter::try_new(file, &schema).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        file_path.to_str().unwrap().to_string()
+    }
+
     fn get_test_partition_locations(n: usize, path: String) -> Vec<PartitionLocation> {
         (0..n)
             .map(|partition_id| PartitionLocation {
@@ -668,9 +1169,16 @@ mod tests {
                     port: 50051,
                     grpc_port: 50052,
                     specification: ExecutorSpecification { task_slots: 12 },
+                    version: crate::build_version(),
+                    functions: None,
+                    shuffle_formats: None,
+                    pool: String::new(),
                 },
                 partition_stats: Default::default(),
                 path: path.clone(),
+                inline_data: None,
+                compression: ShuffleCompression::default(),
+                ipc_metadata_version: 0,
             })
             .collect()
     }
@@ -0,0 +1,226 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Shuffle files kept in an object store.
+//!
+//! Map tasks write their shuffle files to the work directory of their executor as
+//! usual, then upload them to the object store under the same path relative to the
+//! work directory, and report the URIs of the uploaded files as the paths of their
+//! output partitions. Sort-based shuffle files are uploaded along with their index.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path as LocalPath;
+
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use log::debug;
+use object_store::path::Path;
+use object_store::{ObjectStore, WriteMultipart};
+use url::Url;
+
+use crate::execution_plans::sort_shuffle::shuffle_index_path;
+use crate::serde::protobuf::ShuffleWritePartition;
+use crate::staging::resolve_object;
+
+/// Size of the parts shuffle files are uploaded in
+const UPLOAD_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Whether a shuffle partition path is the URI of an object in an object store,
+/// rather than the path of a file on the disk of the executor which wrote it
+pub fn is_object_store_path(path: &str) -> bool {
+    // a scheme of one letter is the drive of a Windows path
+    Url::parse(path).is_ok_and(|url| url.scheme().len() > 1 && url.scheme() != "file")
+}
+
+/// Uploads the shuffle files of the given partitions, written under `work_dir`, to the
+/// object store at `store_url`, pointing the partitions at the uploaded files. The
+/// local files are removed once uploaded.
+pub(crate) async fn upload_shuffle_files(
+    runtime: &RuntimeEnv,
+    store_url: &str,
+    work_dir: &str,
+    partitions: &mut [ShuffleWritePartition],
+) -> Result<()> {
+    let local_paths: BTreeSet<String> =
+        partitions.iter().map(|p| p.path.clone()).collect();
+
+    for local_path in local_paths {
+        let relative_path = LocalPath::new(&local_path)
+            .strip_prefix(work_dir)
+            .map_err(|_| {
+                DataFusionError::Internal(format!(
+                    "Shuffle file {local_path} is not in work dir {work_dir}"
+                ))
+            })?
+            .to_string_lossy()
+            .to_string();
+        let uri = format!("{}/{relative_path}", store_url.trim_end_matches('/'));
+        let (store, path) = resolve_object(runtime, &uri)?;
+
+        upload_file(store.as_ref(), &local_path, &path).await?;
+        let local_index_path = shuffle_index_path(&local_path);
+        if LocalPath::new(&local_index_path).exists() {
+            let index_path = Path::from(shuffle_index_path(path.as_ref()));
+            upload_file(store.as_ref(), &local_index_path, &index_path).await?;
+            std::fs::remove_file(&local_index_path)?;
+        }
+        std::fs::remove_file(&local_path)?;
+        debug!("Uploaded shuffle file {local_path} to {uri}");
+
+        partitions
+            .iter_mut()
+            .filter(|p| p.path == local_path)
+            .for_each(|p| p.path = uri.clone());
+    }
+
+    Ok(())
+}
+
+async fn upload_file(
+    store: &dyn ObjectStore,
+    local_path: &str,
+    path: &Path,
+) -> Result<()> {
+    let mut file = File::open(local_path)?;
+    let mut upload = WriteMultipart::new(store.put_multipart(path).await?);
+    let mut chunk = vec![0; UPLOAD_CHUNK_SIZE];
+    loop {
+        let len = file.read(&mut chunk)?;
+        if len == 0 {
+            break;
+        }
+        upload.wait_for_capacity(2).await?;
+        upload.write(&chunk[..len]);
+    }
+    upload.finish().await?;
+    Ok(())
+}
+
+/// Reads an output partition of a shuffle file uploaded to an object store. Works for
+/// both hash-based shuffle files (the whole object) and sort-based shuffle files (the
+/// partition's byte range, looked up through the uploaded index).
+pub(crate) async fn read_object_store_partition(
+    runtime: &RuntimeEnv,
+    uri: &str,
+    partition_id: usize,
+) -> Result<SendableRecordBatchStream> {
+    let (store, path) = resolve_object(runtime, uri)?;
+    let index_path = Path::from(shuffle_index_path(path.as_ref()));
+
+    let index_range = partition_id * 8..partition_id * 8 + 16;
+    let data = match store.get_range(&index_path, index_range).await {
+        Ok(offsets) => {
+            let start = u64::from_le_bytes(offsets[0..8].try_into().unwrap());
+            let end = u64::from_le_bytes(offsets[8..16].try_into().unwrap());
+            store.get_range(&path, start as usize..end as usize).await?
+        }
+        Err(object_store::Error::NotFound { .. }) => {
+            store.get(&path).await?.bytes().await?
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let reader = StreamReader::try_new(Cursor::new(data), None)?;
+    let schema = reader.schema();
+    let stream = futures::stream::iter(
+        reader.map(|batch| batch.map_err(|e| DataFusionError::ArrowError(e, None))),
+    );
+    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShuffleCompression;
+    use crate::execution_plans::sort_shuffle::write_sorted_shuffle;
+    use datafusion::arrow::array::UInt32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::physical_plan::common;
+    use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricBuilder};
+    use object_store::memory::InMemory;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_object_store_path() {
+        assert!(is_object_store_path(
+            "s3://bucket/shuffle/job/1/0/data.arrow"
+        ));
+        assert!(is_object_store_path("memory:///job/1/0/data.arrow"));
+        assert!(!is_object_store_path("/tmp/job/1/0/data.arrow"));
+        assert!(!is_object_store_path("file:///tmp/job/1/0/data.arrow"));
+        assert!(!is_object_store_path("C:\\work\\job\\1\\0\\data.arrow"));
+    }
+
+    #[tokio::test]
+    async fn sorted_shuffle_object_store_round_trip() -> Result<()> {
+        let schema =
+            Arc::new(Schema::new(vec![Field::new("a", DataType::UInt32, false)]));
+        let batch = |values: Vec<u32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(UInt32Array::from(values))],
+            )
+            .unwrap()
+        };
+
+        let work_dir = TempDir::new()?;
+        let work_dir_str = work_dir.path().to_str().unwrap();
+        let data_path = work_dir.path().join("job").join("data-0.arrow");
+        std::fs::create_dir_all(data_path.parent().unwrap())?;
+        let metrics = ExecutionPlanMetricsSet::new();
+        let write_time = MetricBuilder::new(&metrics).subset_time("write_time", 0);
+        let mut partitions = write_sorted_shuffle(
+            &data_path,
+            &schema,
+            vec![vec![batch(vec![1, 2])], vec![], vec![batch(vec![3])]]
+                .into_iter()
+                .map(|batches| batches.into_iter().map(Ok)),
+            ShuffleCompression::Lz4,
+            &write_time,
+        )
+        .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        let runtime = RuntimeEnv::default();
+        let store_url = Url::parse("memory://shuffle").unwrap();
+        runtime.register_object_store(&store_url, Arc::new(InMemory::new()));
+
+        upload_shuffle_files(
+            &runtime,
+            "memory://shuffle/",
+            work_dir_str,
+            &mut partitions,
+        )
+        .await?;
+        assert!(!data_path.exists());
+        for partition in &partitions {
+            assert_eq!("memory://shuffle/job/data-0.arrow", partition.path);
+        }
+
+        let first = read_object_store_partition(&runtime, &partitions[0].path, 0).await?;
+        assert_eq!(vec![batch(vec![1, 2])], common::collect(first).await?);
+        let last = read_object_store_partition(&runtime, &partitions[1].path, 2).await?;
+        assert_eq!(vec![batch(vec![3])], common::collect(last).await?);
+        Ok(())
+    }
+}
@@ -20,22 +20,25 @@
 //! partition is re-partitioned and streamed to disk in Arrow IPC format. Future stages of the query
 //! will use the ShuffleReaderExec to read these results.
 
-use datafusion::arrow::ipc::writer::IpcWriteOptions;
-use datafusion::arrow::ipc::CompressionType;
-
 use datafusion::arrow::ipc::writer::StreamWriter;
 use std::any::Any;
-use std::fs;
-use std::fs::File;
 use std::future::Future;
 use std::iter::Iterator;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 
+use crate::config::{ShuffleCompression, SHUFFLE_IPC_METADATA_VERSION};
+use crate::execution_plans::range_partitioning::RangePartitioning;
+use crate::execution_plans::shuffle_io::ShuffleFileWriter;
+use crate::execution_plans::shuffle_storage::upload_shuffle_files;
+use crate::execution_plans::sort_shuffle::SortedShuffleBuffer;
+use crate::extension::SessionConfigExt;
 use crate::utils;
 
-use crate::serde::protobuf::ShuffleWritePartition;
+use crate::serde::protobuf::{self, ShuffleWritePartition};
 use crate::serde::scheduler::PartitionStats;
 use datafusion::arrow::array::{
     ArrayBuilder, ArrayRef, StringBuilder, StructBuilder, UInt32Builder, UInt64Builder,
@@ -53,13 +56,16 @@ use datafusion::physical_plan::{
     DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PlanProperties,
     SendableRecordBatchStream, Statistics,
 };
+use futures::future::Either;
 use futures::{StreamExt, TryFutureExt, TryStreamExt};
 
 use datafusion::arrow::error::ArrowError;
 use datafusion::execution::context::TaskContext;
+use datafusion::execution::memory_pool::MemoryConsumer;
 use datafusion::physical_plan::repartition::BatchPartitioner;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
-use log::{debug, info};
+use log::{debug, info, warn};
+use tokio_util::sync::CancellationToken;
 
 /// ShuffleWriterExec represents a section of a query plan that has consistent partitioning and
 /// can be executed as one unit with each partition being executed in parallel. The output of each
@@ -78,6 +84,16 @@ pub struct ShuffleWriterExec {
     /// Optional shuffle output partitioning.
     /// If it's none, it means there's no need to do repartitioning.
     shuffle_output_partitioning: Option<Partitioning>,
+    /// Range partitioning of the output, if any, which the shuffle output partitioning
+    /// then only gives the number of partitions of
+    range_partitioning: Option<RangePartitioning>,
+    /// Codec compressing the shuffle files
+    compression: ShuffleCompression,
+    /// Object store the shuffle files are uploaded to, if any
+    object_store_url: Option<String>,
+    /// Whether this is the final stage of its job, whose small outputs are shipped
+    /// inline with the task status
+    final_stage: bool,
     /// Execution metrics
     metrics: ExecutionPlanMetricsSet,
     properties: PlanProperties,
@@ -86,7 +102,9 @@ pub struct ShuffleWriterExec {
 pub struct WriteTracker {
     pub num_batches: usize,
     pub num_rows: usize,
-    pub writer: StreamWriter<File>,
+    /// Checksum of the written rows, if computed
+    pub checksum: Option<u64>,
+    pub writer: StreamWriter<ShuffleFileWriter>,
     pub path: PathBuf,
 }
 
@@ -96,7 +114,15 @@ struct ShuffleWriteMetrics {
     write_time: metrics::Time,
     repart_time: metrics::Time,
     input_rows: metrics::Count,
+    /// Bytes in memory of the batches written, which the bytes of the shuffle files
+    /// are compressed from
+    input_bytes: metrics::Count,
     output_rows: metrics::Count,
+    /// Bytes of the shuffle files written
+    output_bytes: metrics::Count,
+    /// Runs spilled by the sort-based shuffle, and their bytes on disk
+    spill_count: metrics::Count,
+    spilled_bytes: metrics::Count,
 }
 
 impl ShuffleWriteMetrics {
@@ -107,13 +133,25 @@ impl ShuffleWriteMetrics {
 
         let input_rows = MetricBuilder::new(metrics).counter("input_rows", partition);
 
+        let input_bytes = MetricBuilder::new(metrics).counter("input_bytes", partition);
+
         let output_rows = MetricBuilder::new(metrics).output_rows(partition);
 
+        let output_bytes = MetricBuilder::new(metrics).counter("output_bytes", partition);
+
+        let spill_count = MetricBuilder::new(metrics).spill_count(partition);
+
+        let spilled_bytes = MetricBuilder::new(metrics).spilled_bytes(partition);
+
         Self {
             write_time,
             repart_time,
             input_rows,
+            input_bytes,
             output_rows,
+            output_bytes,
+            spill_count,
+            spilled_bytes,
         }
     }
 }
@@ -143,11 +181,55 @@ impl ShuffleWriterExec {
             plan,
             work_dir,
             shuffle_output_partitioning,
+            range_partitioning: None,
+            compression: ShuffleCompression::default(),
+            object_store_url: None,
+            final_stage: false,
             metrics: ExecutionPlanMetricsSet::new(),
             properties,
         })
     }
 
+    /// Partition the output by ranges of its sort key, so that a global sort can sort
+    /// each output partition on its own
+    pub fn with_range_partitioning(
+        mut self,
+        range_partitioning: Option<RangePartitioning>,
+    ) -> Self {
+        if let Some(range_partitioning) = &range_partitioning {
+            let partitioning =
+                Partitioning::UnknownPartitioning(range_partitioning.partition_count());
+            self.properties = self
+                .properties
+                .clone()
+                .with_partitioning(partitioning.clone());
+            self.shuffle_output_partitioning = Some(partitioning);
+        }
+        self.range_partitioning = range_partitioning;
+        self
+    }
+
+    /// Compress the shuffle files with the given codec
+    pub fn with_compression(mut self, compression: ShuffleCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Upload the shuffle files to the object store at the given URL once written, so
+    /// that they outlive the executor
+    pub fn with_object_store_url(mut self, object_store_url: Option<String>) -> Self {
+        self.object_store_url = object_store_url;
+        self
+    }
+
+    /// Mark this stage as the final stage of its job, whose small unpartitioned
+    /// outputs are shipped inline with the task status, see
+    /// `ballista.job.inline_result_max_bytes`
+    pub fn with_final_stage(mut self, final_stage: bool) -> Self {
+        self.final_stage = final_stage;
+        self
+    }
+
     /// Get the Job ID for this query stage
     pub fn job_id(&self) -> &str {
         &self.job_id
@@ -171,6 +253,26 @@ impl ShuffleWriterExec {
         self.shuffle_output_partitioning.as_ref()
     }
 
+    /// Get the range partitioning of the output, if any
+    pub fn range_partitioning(&self) -> Option<&RangePartitioning> {
+        self.range_partitioning.as_ref()
+    }
+
+    /// Get the codec compressing the shuffle files
+    pub fn compression(&self) -> ShuffleCompression {
+        self.compression
+    }
+
+    /// Get the object store the shuffle files are uploaded to, if any
+    pub fn object_store_url(&self) -> Option<&str> {
+        self.object_store_url.as_deref()
+    }
+
+    /// Whether this is the final stage of its job
+    pub fn is_final_stage(&self) -> bool {
+        self.final_stage
+    }
+
     pub fn execute_shuffle_write(
         &self,
         input_partition: usize,
@@ -182,13 +284,42 @@ impl ShuffleWriterExec {
 
         let write_metrics = ShuffleWriteMetrics::new(input_partition, &self.metrics);
         let output_partitioning = self.shuffle_output_partitioning.clone();
+        let range_partitioning = self.range_partitioning.clone();
+        let compression = self.compression;
         let plan = self.plan.clone();
-
-        async move {
+        let sort_based_threshold = context
+            .session_config()
+            .ballista_shuffle_sort_based_partition_threshold();
+        let inline_result_max_bytes = if self.final_stage {
+            context.session_config().ballista_inline_result_max_bytes()
+        } else {
+            0
+        };
+        // the output of verified stages is checksummed, to be compared with the output
+        // of the same task executed on another executor, and so are the results of
+        // jobs compared with those of other jobs
+        let compute_checksums = context
+            .session_config()
+            .ballista_verify_stage(self.stage_id)
+            || (self.final_stage && context.session_config().ballista_checksum_results());
+        let work_dir = self.work_dir.clone();
+        let object_store_url = self.object_store_url.clone();
+        let runtime = context.runtime_env();
+        // the executor cancels the token of the task to interrupt it
+        let cancellation = context
+            .session_config()
+            .get_extension::<CancellationToken>()
+            .unwrap_or_default();
+        let stage_dir = path.clone();
+        let partitioned = output_partitioning.is_some();
+
+        let interrupted = cancellation.clone();
+        let write = async move {
             let now = Instant::now();
-            let mut stream = plan.execute(input_partition, context)?;
+            let mut stream =
+                cancellable(plan.execute(input_partition, context)?, interrupted);
 
-            match output_partitioning {
+            let mut part_locs = match output_partitioning {
                 None => {
                     let timer = write_metrics.write_time.timer();
                     path.push(format!("{input_partition}"));
@@ -197,10 +328,26 @@ impl ShuffleWriterExec {
                     let path = path.to_str().unwrap();
                     debug!("Writing results to {}", path);
 
+                    let checksum = Arc::new(AtomicU64::new(0));
+                    if compute_checksums {
+                        let checksum = checksum.clone();
+                        let schema = stream.schema();
+                        let checksummed = stream.and_then(move |batch| {
+                            let result = utils::batch_checksum(&batch).map(|c| {
+                                checksum.fetch_add(c, Ordering::Relaxed);
+                                batch
+                            });
+                            futures::future::ready(result)
+                        });
+                        stream =
+                            Box::pin(RecordBatchStreamAdapter::new(schema, checksummed));
+                    }
+
                     // stream results to disk
                     let stats = utils::write_stream_to_disk(
                         &mut stream,
                         path,
+                        compression,
                         &write_metrics.write_time,
                     )
                     .await
@@ -209,6 +356,9 @@ impl ShuffleWriterExec {
                     write_metrics
                         .input_rows
                         .add(stats.num_rows.unwrap_or(0) as usize);
+                    write_metrics
+                        .input_bytes
+                        .add(stats.num_bytes.unwrap_or(0) as usize);
                     write_metrics
                         .output_rows
                         .add(stats.num_rows.unwrap_or(0) as usize);
@@ -221,16 +371,119 @@ impl ShuffleWriterExec {
                         stats
                     );
 
-                    Ok(vec![ShuffleWritePartition {
+                    // small final results of a job are shipped inline so they don't
+                    // need to be fetched
+                    let file_size = std::fs::metadata(path)?.len();
+                    let inline_data = if inline_result_max_bytes > 0
+                        && file_size <= inline_result_max_bytes as u64
+                    {
+                        std::fs::read(path)?
+                    } else {
+                        vec![]
+                    };
+
+                    Ok::<_, DataFusionError>(vec![ShuffleWritePartition {
                         partition_id: input_partition as u64,
                         path: path.to_owned(),
                         num_batches: stats.num_batches.unwrap_or(0),
                         num_rows: stats.num_rows.unwrap_or(0),
                         num_bytes: stats.num_bytes.unwrap_or(0),
+                        inline_data,
+                        checksum: compute_checksums
+                            .then(|| checksum.load(Ordering::Relaxed)),
+                        compression: protobuf::ShuffleCompression::from(compression)
+                            as i32,
+                        ipc_metadata_version: SHUFFLE_IPC_METADATA_VERSION,
                     }])
                 }
 
-                Some(Partitioning::Hash(exprs, num_output_partitions)) => {
+                Some(partitioning)
+                    if sort_based_threshold > 0
+                        && partitioning.partition_count() > sort_based_threshold =>
+                {
+                    let num_output_partitions = partitioning.partition_count();
+                    // with a very high number of output partitions, keeping a file open
+                    // per output partition is too expensive, so we buffer the output
+                    // grouped by partition id and write one sorted and indexed file
+                    std::fs::create_dir_all(&path)?;
+                    path.push(format!("data-{input_partition}.arrow"));
+                    let reservation = MemoryConsumer::new(format!(
+                        "ShuffleWriterExec[{input_partition}]"
+                    ))
+                    .with_can_spill(true)
+                    .register(&runtime.memory_pool);
+                    let mut buffered = SortedShuffleBuffer::new(
+                        num_output_partitions,
+                        stream.schema(),
+                        compression,
+                        reservation,
+                        path.clone(),
+                        write_metrics.spill_count.clone(),
+                        write_metrics.spilled_bytes.clone(),
+                    );
+                    let mut checksums = vec![0u64; num_output_partitions];
+
+                    let mut partitioner = OutputPartitioner::try_new(
+                        partitioning,
+                        range_partitioning,
+                        write_metrics.repart_time.clone(),
+                    )?;
+
+                    while let Some(result) = stream.next().await {
+                        let input_batch = result?;
+
+                        write_metrics.input_rows.add(input_batch.num_rows());
+                        write_metrics
+                            .input_bytes
+                            .add(input_batch.get_array_memory_size());
+
+                        partitioner.partition(
+                            input_batch,
+                            |output_partition, output_batch| {
+                                write_metrics.output_rows.add(output_batch.num_rows());
+                                if compute_checksums {
+                                    checksums[output_partition] =
+                                        checksums[output_partition].wrapping_add(
+                                            utils::batch_checksum(&output_batch)?,
+                                        );
+                                }
+                                buffered
+                                    .push(
+                                        output_partition,
+                                        output_batch,
+                                        &write_metrics.write_time,
+                                    )
+                                    .map_err(|e| {
+                                        DataFusionError::Execution(format!("{e:?}"))
+                                    })
+                            },
+                        )?;
+                    }
+
+                    debug!("Writing sorted shuffle results to {:?}", path);
+                    let mut part_locs = buffered
+                        .finish(&path, &write_metrics.write_time)
+                        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+                    if compute_checksums {
+                        for part_loc in &mut part_locs {
+                            part_loc.checksum =
+                                Some(checksums[part_loc.partition_id as usize]);
+                        }
+                    }
+
+                    info!(
+                        "Executed partition {} in {} seconds. Wrote {} sorted shuffle partitions to {:?}",
+                        input_partition,
+                        now.elapsed().as_secs(),
+                        part_locs.len(),
+                        path
+                    );
+
+                    Ok(part_locs)
+                }
+
+                Some(partitioning) => {
+                    let num_output_partitions = partitioning.partition_count();
                     // we won't necessary produce output for every possible partition, so we
                     // create writers on demand
                     let mut writers: Vec<Option<WriteTracker>> = vec![];
@@ -238,8 +491,9 @@ impl ShuffleWriterExec {
                         writers.push(None);
                     }
 
-                    let mut partitioner = BatchPartitioner::try_new(
-                        Partitioning::Hash(exprs, num_output_partitions),
+                    let mut partitioner = OutputPartitioner::try_new(
+                        partitioning,
+                        range_partitioning,
                         write_metrics.repart_time.clone(),
                     )?;
 
@@ -247,16 +501,28 @@ impl ShuffleWriterExec {
                         let input_batch = result?;
 
                         write_metrics.input_rows.add(input_batch.num_rows());
+                        write_metrics
+                            .input_bytes
+                            .add(input_batch.get_array_memory_size());
 
                         partitioner.partition(
                             input_batch,
                             |output_partition, output_batch| {
-                                // partition func in datafusion make sure not write empty output_batch.
+                                // the partitioners make sure not to write empty output batches
                                 let timer = write_metrics.write_time.timer();
+                                let checksum = if compute_checksums {
+                                    Some(utils::batch_checksum(&output_batch)?)
+                                } else {
+                                    None
+                                };
                                 match &mut writers[output_partition] {
                                     Some(w) => {
                                         w.num_batches += 1;
                                         w.num_rows += output_batch.num_rows();
+                                        w.checksum = w
+                                            .checksum
+                                            .zip(checksum)
+                                            .map(|(a, b)| a.wrapping_add(b));
                                         w.writer.write(&output_batch)?;
                                     }
                                     None => {
@@ -269,12 +535,11 @@ impl ShuffleWriterExec {
                                         ));
                                         debug!("Writing results to {:?}", path);
 
-                                        let options = IpcWriteOptions::default()
-                                            .try_with_compression(Some(
-                                                CompressionType::LZ4_FRAME,
-                                            ))?;
+                                        let options = compression.ipc_write_options(
+                                            SHUFFLE_IPC_METADATA_VERSION,
+                                        )?;
 
-                                        let file = File::create(path.clone())?;
+                                        let file = ShuffleFileWriter::create(&path)?;
                                         let mut writer =
                                             StreamWriter::try_new_with_options(
                                                 file,
@@ -286,6 +551,7 @@ impl ShuffleWriterExec {
                                         writers[output_partition] = Some(WriteTracker {
                                             num_batches: 1,
                                             num_rows: output_batch.num_rows(),
+                                            checksum,
                                             writer,
                                             path,
                                         });
@@ -302,8 +568,8 @@ impl ShuffleWriterExec {
 
                     for (i, w) in writers.iter_mut().enumerate() {
                         if let Some(w) = w {
-                            let num_bytes = fs::metadata(&w.path)?.len();
                             w.writer.finish()?;
+                            let num_bytes = w.writer.get_ref().position();
                             debug!(
                                 "Finished writing shuffle partition {} at {:?}. Batches: {}. Rows: {}. Bytes: {}.",
                                 i,
@@ -319,17 +585,159 @@ impl ShuffleWriterExec {
                                 num_batches: w.num_batches as u64,
                                 num_rows: w.num_rows as u64,
                                 num_bytes,
+                                inline_data: vec![],
+                                checksum: w.checksum,
+                                compression: protobuf::ShuffleCompression::from(
+                                    compression,
+                                ) as i32,
+                                ipc_metadata_version: SHUFFLE_IPC_METADATA_VERSION,
                             });
                         }
                     }
                     Ok(part_locs)
                 }
+            }?;
+            write_metrics
+                .output_bytes
+                .add(part_locs.iter().map(|loc| loc.num_bytes as usize).sum());
+
+            if let Some(object_store_url) = object_store_url {
+                let _timer = write_metrics.write_time.timer();
+                upload_shuffle_files(
+                    &runtime,
+                    &object_store_url,
+                    &work_dir,
+                    &mut part_locs,
+                )
+                .await?;
+            }
+
+            Ok::<_, DataFusionError>(part_locs)
+        };
+
+        async move {
+            let result = write.await;
+            if result.is_err() && cancellation.is_cancelled() {
+                remove_task_output(&stage_dir, input_partition, partitioned);
+            }
+            result
+        }
+    }
+}
+
+/// Splits the input batches of a task by output partition
+enum OutputPartitioner {
+    Batch(BatchPartitioner),
+    Range(RangePartitioning, metrics::Time),
+}
+
+impl OutputPartitioner {
+    fn try_new(
+        partitioning: Partitioning,
+        range_partitioning: Option<RangePartitioning>,
+        repart_time: metrics::Time,
+    ) -> Result<Self> {
+        match (partitioning, range_partitioning) {
+            (Partitioning::UnknownPartitioning(n), Some(range_partitioning))
+                if n == range_partitioning.partition_count() =>
+            {
+                Ok(Self::Range(range_partitioning, repart_time))
+            }
+            (partitioning @ Partitioning::Hash(..), None)
+            | (partitioning @ Partitioning::RoundRobinBatch(_), None) => Ok(Self::Batch(
+                BatchPartitioner::try_new(partitioning, repart_time)?,
+            )),
+            _ => Err(DataFusionError::Execution(
+                "Invalid shuffle partitioning scheme".to_owned(),
+            )),
+        }
+    }
 
-                _ => Err(DataFusionError::Execution(
-                    "Invalid shuffle partitioning scheme".to_owned(),
+    fn partition<F>(&mut self, batch: RecordBatch, mut f: F) -> Result<()>
+    where
+        F: FnMut(usize, RecordBatch) -> Result<()>,
+    {
+        match self {
+            Self::Batch(partitioner) => partitioner.partition(batch, f),
+            Self::Range(range_partitioning, repart_time) => {
+                let timer = repart_time.timer();
+                let mut output_batches = vec![];
+                range_partitioning.partition(&batch, |partition, output_batch| {
+                    output_batches.push((partition, output_batch));
+                    Ok(())
+                })?;
+                timer.done();
+                output_batches
+                    .into_iter()
+                    .try_for_each(|(partition, output_batch)| f(partition, output_batch))
+            }
+        }
+    }
+}
+
+/// Interrupt the input of a task once its [CancellationToken] is cancelled, even while
+/// it waits for its next batch
+fn cancellable(
+    stream: SendableRecordBatchStream,
+    cancellation: Arc<CancellationToken>,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+    let batches = futures::stream::unfold(Some(stream), move |stream| {
+        let cancellation = cancellation.clone();
+        async move {
+            let mut stream = stream?;
+            let cancelled = pin!(cancellation.cancelled());
+            match futures::future::select(cancelled, stream.next()).await {
+                Either::Left(_) => Some((
+                    Err(DataFusionError::Execution("Task cancelled".to_owned())),
+                    None,
                 )),
+                Either::Right((batch, _)) => batch.map(|batch| (batch, Some(stream))),
             }
         }
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, batches))
+}
+
+/// Remove the shuffle files partially written by a cancelled task from the dir of its
+/// stage, so that they don't linger until the data of the job is cleaned up
+fn remove_task_output(stage_dir: &Path, input_partition: usize, partitioned: bool) {
+    let result = if partitioned {
+        // `data-{input_partition}.arrow` in the dir of each output partition, or a
+        // single sorted file with its index in the dir of the stage
+        let file_name = format!("data-{input_partition}.arrow");
+        std::fs::read_dir(stage_dir).and_then(|entries| {
+            for entry in entries {
+                let path = entry?.path();
+                let file = if path.is_dir() {
+                    path.join(&file_name)
+                } else {
+                    path
+                };
+                if file.file_name().is_some_and(|name| {
+                    name.to_string_lossy().starts_with(file_name.as_str())
+                }) && file.exists()
+                {
+                    std::fs::remove_file(&file)?;
+                }
+            }
+            Ok(())
+        })
+    } else {
+        let dir = stage_dir.join(format!("{input_partition}"));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)
+        } else {
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => info!(
+            "Removed the output of cancelled partition {input_partition} from {stage_dir:?}"
+        ),
+        Err(e) => warn!(
+            "Failed to remove the output of cancelled partition {input_partition} from {stage_dir:?}: {e}"
+        ),
     }
 }
 
@@ -341,11 +749,24 @@ impl DisplayAs for ShuffleWriterExec {
     ) -> std::fmt::Result {
         match t {
             DisplayFormatType::Default | DisplayFormatType::Verbose => {
-                write!(
-                    f,
-                    "ShuffleWriterExec: {:?}",
-                    self.shuffle_output_partitioning
-                )
+                match &self.range_partitioning {
+                    Some(range_partitioning) => write!(
+                        f,
+                        "ShuffleWriterExec: Some(Range([{}], {}))",
+                        range_partitioning
+                            .sort_exprs()
+                            .iter()
+                            .map(|sort_expr| sort_expr.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        range_partitioning.partition_count()
+                    ),
+                    None => write!(
+                        f,
+                        "ShuffleWriterExec: {:?}",
+                        self.shuffle_output_partitioning
+                    ),
+                }
             }
         }
     }
@@ -376,13 +797,19 @@ impl ExecutionPlan for ShuffleWriterExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(ShuffleWriterExec::try_new(
-            self.job_id.clone(),
-            self.stage_id,
-            children[0].clone(),
-            self.work_dir.clone(),
-            self.shuffle_output_partitioning.clone(),
-        )?))
+        Ok(Arc::new(
+            ShuffleWriterExec::try_new(
+                self.job_id.clone(),
+                self.stage_id,
+                children[0].clone(),
+                self.work_dir.clone(),
+                self.shuffle_output_partitioning.clone(),
+            )?
+            .with_range_partitioning(self.range_partitioning.clone())
+            .with_compression(self.compression)
+            .with_object_store_url(self.object_store_url.clone())
+            .with_final_stage(self.final_stage),
+        ))
     }
 
     fn execute(
@@ -470,11 +897,14 @@ fn result_schema() -> SchemaRef {
 mod tests {
     use super::*;
     use datafusion::arrow::array::{StringArray, StructArray, UInt32Array, UInt64Array};
+    use datafusion::arrow::compute::SortOptions;
+    use datafusion::common::ScalarValue;
+    use datafusion::physical_expr::PhysicalSortExpr;
     use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
     use datafusion::physical_plan::expressions::Column;
 
     use datafusion::physical_plan::memory::MemoryExec;
-    use datafusion::prelude::SessionContext;
+    use datafusion::prelude::{SessionConfig, SessionContext};
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -574,6 +1004,227 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_inline_result() -> Result<()> {
+        for (max_bytes, final_stage, expect_inline) in [
+            (0, true, false),
+            (1024 * 1024, true, true),
+            (1024 * 1024, false, false),
+        ] {
+            let session_ctx = SessionContext::new_with_config(
+                SessionConfig::new_with_ballista()
+                    .with_ballista_inline_result_max_bytes(max_bytes),
+            );
+            let work_dir = TempDir::new()?;
+            let query_stage = ShuffleWriterExec::try_new(
+                "jobOne".to_owned(),
+                1,
+                create_input_plan()?,
+                work_dir.path().to_str().unwrap().to_owned(),
+                None,
+            )?
+            .with_final_stage(final_stage);
+            let partitions = query_stage
+                .execute_shuffle_write(0, session_ctx.task_ctx())
+                .await?;
+            assert_eq!(1, partitions.len());
+
+            let inline_data = &partitions[0].inline_data;
+            assert_eq!(expect_inline, !inline_data.is_empty());
+            if expect_inline {
+                assert_eq!(std::fs::read(&partitions[0].path)?, *inline_data);
+                let reader = datafusion::arrow::ipc::reader::StreamReader::try_new(
+                    std::io::Cursor::new(inline_data),
+                    None,
+                )?;
+                let num_rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+                assert_eq!(partitions[0].num_rows as usize, num_rows);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    // number of rows in each partition is a function of the hash output, so don't test here
+    #[cfg(not(feature = "force_hash_collisions"))]
+    async fn test_sort_based() -> Result<()> {
+        let session_ctx = SessionContext::new_with_config(
+            SessionConfig::new_with_ballista()
+                .with_ballista_shuffle_sort_based_partition_threshold(1),
+        );
+        let task_ctx = session_ctx.task_ctx();
+
+        let input_plan = create_input_plan()?;
+        let work_dir = TempDir::new()?;
+        let query_stage = ShuffleWriterExec::try_new(
+            "jobOne".to_owned(),
+            1,
+            input_plan,
+            work_dir.into_path().to_str().unwrap().to_owned(),
+            Some(Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 2)),
+        )?;
+        let mut stream = query_stage.execute(0, task_ctx)?;
+        let batches = utils::collect_stream(&mut stream)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        assert_eq!(1, batches.len());
+        let batch = &batches[0];
+        assert_eq!(2, batch.num_rows());
+        let path = batch.columns()[1]
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+
+        // both output partitions live in the same file
+        assert_eq!(path.value(0), path.value(1));
+        assert!(
+            path.value(0).ends_with("/jobOne/1/data-0.arrow")
+                || path.value(0).ends_with("\\jobOne\\1\\data-0.arrow")
+        );
+
+        for partition in 0..2 {
+            let rows: usize = crate::execution_plans::open_shuffle_file(
+                path.value(partition),
+                partition,
+            )
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .map(|b| b.map(|b| b.num_rows()))
+            .sum::<std::result::Result<usize, _>>()?;
+            assert_eq!(2, rows);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_and_range_partitioned() -> Result<()> {
+        let range_partitioning = RangePartitioning::try_new(
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("a", 0)),
+                options: SortOptions::default(),
+            }],
+            vec![vec![ScalarValue::UInt32(Some(2))]],
+        )?;
+        let round_robin = ShuffleWriterExec::try_new(
+            "jobOne".to_owned(),
+            1,
+            create_input_plan()?,
+            TempDir::new()?.into_path().to_str().unwrap().to_owned(),
+            Some(Partitioning::RoundRobinBatch(2)),
+        )?;
+        let range = ShuffleWriterExec::try_new(
+            "jobOne".to_owned(),
+            1,
+            create_input_plan()?,
+            TempDir::new()?.into_path().to_str().unwrap().to_owned(),
+            None,
+        )?
+        .with_range_partitioning(Some(range_partitioning));
+        assert_eq!(
+            2,
+            range.properties().output_partitioning().partition_count()
+        );
+
+        // each input batch has a row with a = 1 and a row with a = 3
+        for (query_stage, expected_values) in
+            [(round_robin, [[1, 3], [1, 3]]), (range, [[1, 1], [3, 3]])]
+        {
+            let session_ctx = SessionContext::new();
+            let partitions = query_stage
+                .execute_shuffle_write(0, session_ctx.task_ctx())
+                .await?;
+            assert_eq!(2, partitions.len());
+            for (partition, expected) in partitions.iter().zip(expected_values) {
+                let mut values = vec![];
+                let reader = crate::execution_plans::open_shuffle_file(
+                    &partition.path,
+                    partition.partition_id as usize,
+                )
+                .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+                for batch in reader {
+                    let batch = batch?;
+                    let a = batch.columns()[0]
+                        .as_any()
+                        .downcast_ref::<UInt32Array>()
+                        .unwrap();
+                    values.extend(a.iter().flatten());
+                }
+                assert_eq!(expected.to_vec(), values);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_type_conformance_round_trip() -> Result<()> {
+        // hash-based and sort-based shuffle, with each compression codec
+        for (input, threshold, compression) in itertools::iproduct!(
+            crate::test_utils::type_conformance_batches(),
+            [0, 1],
+            [
+                ShuffleCompression::Lz4,
+                ShuffleCompression::Zstd,
+                ShuffleCompression::Uncompressed
+            ]
+        ) {
+            let session_ctx = SessionContext::new_with_config(
+                SessionConfig::new_with_ballista()
+                    .with_ballista_shuffle_sort_based_partition_threshold(threshold),
+            );
+            let input_plan = Arc::new(MemoryExec::try_new(
+                &[vec![input.clone()]],
+                input.schema(),
+                None,
+            )?);
+            let work_dir = TempDir::new()?;
+            let query_stage = ShuffleWriterExec::try_new(
+                "jobOne".to_owned(),
+                1,
+                input_plan,
+                work_dir.path().to_str().unwrap().to_owned(),
+                Some(Partitioning::Hash(vec![Arc::new(Column::new("id", 0))], 2)),
+            )?
+            .with_compression(compression);
+            let mut stream = query_stage.execute(0, session_ctx.task_ctx())?;
+            let batches = utils::collect_stream(&mut stream)
+                .await
+                .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+
+            let mut output = vec![];
+            for batch in &batches {
+                let partitions = batch.columns()[0]
+                    .as_any()
+                    .downcast_ref::<UInt32Array>()
+                    .unwrap();
+                let paths = batch.columns()[1]
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .unwrap();
+                for i in 0..batch.num_rows() {
+                    let reader = crate::execution_plans::open_shuffle_file(
+                        paths.value(i),
+                        partitions.value(i) as usize,
+                    )
+                    .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+                    for batch in reader {
+                        output.push(batch?);
+                    }
+                }
+            }
+
+            assert_eq!(
+                crate::test_utils::sorted_rows(std::slice::from_ref(&input)),
+                crate::test_utils::sorted_rows(&output),
+                "round trip of {} failed with threshold {threshold} and {compression}",
+                input.schema()
+            );
+        }
+
+        Ok(())
+    }
+
     fn create_input_plan() -> Result<Arc<dyn ExecutionPlan>> {
         let schema = Arc::new(Schema::new(vec![
             Field::new("a", DataType::UInt32, true),
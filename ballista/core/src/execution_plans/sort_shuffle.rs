@@ -0,0 +1,449 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sort-based shuffle file layout.
+//!
+//! Instead of one file per output partition, a map task writes all of its output
+//! partitions into a single data file ordered by output partition id. Each output
+//! partition is a self-contained Arrow IPC stream. A companion index file holds
+//! `num_partitions + 1` little-endian `u64` byte offsets, so the bytes of output
+//! partition `i` live in `[offsets[i], offsets[i + 1])` of the data file.
+//!
+//! The output partitions are buffered in memory until the map task completes, within
+//! the memory pool of the executor. Once the pool is exhausted, the buffered partitions
+//! are spilled to a sorted run, itself laid out as a shuffle file, and the runs are
+//! merged into the data file of the task.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::{iter, mem};
+
+use datafusion::arrow::datatypes::{Schema, SchemaRef};
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::execution::memory_pool::MemoryReservation;
+use datafusion::physical_plan::metrics;
+
+use crate::config::{ShuffleCompression, SHUFFLE_IPC_METADATA_VERSION};
+use crate::error::{BallistaError, Result};
+use crate::execution_plans::shuffle_io::{ShuffleFileRange, ShuffleFileWriter};
+use crate::serde::protobuf::{self, ShuffleWritePartition};
+
+/// Suffix appended to a sort-based shuffle data file to get its index file
+pub const SHUFFLE_INDEX_FILE_SUFFIX: &str = ".index";
+
+/// Reader over one output partition of a shuffle file
+pub type ShuffleFileReader = StreamReader<BufReader<ShuffleFileRange>>;
+
+/// Returns the index file path belonging to the given data file
+pub fn shuffle_index_path(data_path: &str) -> String {
+    format!("{data_path}{SHUFFLE_INDEX_FILE_SUFFIX}")
+}
+
+/// Writes all output partitions of a map task into a single data file, ordered by
+/// output partition id, together with its offset index.
+///
+/// The i-th item of `partitions` yields the batches of output partition `i`. Only
+/// non-empty partitions are reported in the result.
+pub(crate) fn write_sorted_shuffle<I>(
+    data_path: &Path,
+    schema: &Schema,
+    partitions: impl IntoIterator<Item = I>,
+    compression: ShuffleCompression,
+    disk_write_metric: &metrics::Time,
+) -> Result<Vec<ShuffleWritePartition>>
+where
+    I: IntoIterator<Item = Result<RecordBatch>>,
+{
+    let _timer = disk_write_metric.timer();
+    let path_str = data_path.to_string_lossy().to_string();
+
+    let mut file = ShuffleFileWriter::create(data_path)?;
+    let mut offsets = vec![0u64];
+    let mut part_locs = vec![];
+
+    for (partition_id, batches) in partitions.into_iter().enumerate() {
+        let start = file.position();
+        let mut num_batches = 0;
+        let mut num_rows = 0;
+        {
+            // the stream of a partition is only started once it has a batch
+            let mut writer = None;
+            for batch in batches {
+                let batch = batch?;
+                if writer.is_none() {
+                    let options =
+                        compression.ipc_write_options(SHUFFLE_IPC_METADATA_VERSION)?;
+                    writer = Some(StreamWriter::try_new_with_options(
+                        &mut file, schema, options,
+                    )?);
+                }
+                if let Some(writer) = writer.as_mut() {
+                    writer.write(&batch)?;
+                }
+                num_batches += 1;
+                num_rows += batch.num_rows();
+            }
+            if let Some(mut writer) = writer {
+                writer.finish()?;
+            }
+        }
+
+        let end = file.position();
+        if num_batches > 0 {
+            part_locs.push(ShuffleWritePartition {
+                partition_id: partition_id as u64,
+                path: path_str.clone(),
+                num_batches: num_batches as u64,
+                num_rows: num_rows as u64,
+                num_bytes: end - start,
+                inline_data: vec![],
+                checksum: None,
+                compression: protobuf::ShuffleCompression::from(compression) as i32,
+                ipc_metadata_version: SHUFFLE_IPC_METADATA_VERSION,
+            });
+        }
+        offsets.push(end);
+    }
+    file.sync_all()?;
+
+    let mut index = File::create(shuffle_index_path(&path_str))?;
+    for offset in offsets {
+        index.write_all(&offset.to_le_bytes())?;
+    }
+    index.sync_all()?;
+
+    Ok(part_locs)
+}
+
+/// Output partitions of a map task buffered until they are written to a sort-based
+/// shuffle file, which are spilled to sorted runs when the memory pool can't hold them
+pub(crate) struct SortedShuffleBuffer {
+    schema: SchemaRef,
+    compression: ShuffleCompression,
+    /// Batches buffered in memory, by output partition
+    partitions: Vec<Vec<RecordBatch>>,
+    /// Reservation of the memory of the buffered batches
+    reservation: MemoryReservation,
+    /// Prefix of the paths of the spilled runs
+    spill_prefix: PathBuf,
+    /// Paths of the spilled runs, in the order they were spilled
+    spills: Vec<PathBuf>,
+    spill_count: metrics::Count,
+    spilled_bytes: metrics::Count,
+}
+
+impl SortedShuffleBuffer {
+    /// Create a buffer of `num_partitions` output partitions spilling its runs to the
+    /// paths starting with `spill_prefix`
+    pub(crate) fn new(
+        num_partitions: usize,
+        schema: SchemaRef,
+        compression: ShuffleCompression,
+        reservation: MemoryReservation,
+        spill_prefix: PathBuf,
+        spill_count: metrics::Count,
+        spilled_bytes: metrics::Count,
+    ) -> Self {
+        Self {
+            schema,
+            compression,
+            partitions: vec![vec![]; num_partitions],
+            reservation,
+            spill_prefix,
+            spills: vec![],
+            spill_count,
+            spilled_bytes,
+        }
+    }
+
+    /// Buffer a batch of output partition `partition`, spilling the buffered batches
+    /// first if the memory pool can't hold it
+    pub(crate) fn push(
+        &mut self,
+        partition: usize,
+        batch: RecordBatch,
+        disk_write_metric: &metrics::Time,
+    ) -> Result<()> {
+        let size = batch.get_array_memory_size();
+        if self.reservation.try_grow(size).is_err() {
+            self.spill(disk_write_metric)?;
+            // fails if the pool can't even hold a single batch
+            self.reservation.try_grow(size)?;
+        }
+        self.partitions[partition].push(batch);
+        Ok(())
+    }
+
+    fn spill(&mut self, disk_write_metric: &metrics::Time) -> Result<()> {
+        if self.partitions.iter().all(|batches| batches.is_empty()) {
+            return Ok(());
+        }
+        let mut path = self.spill_prefix.clone().into_os_string();
+        path.push(format!(".spill-{}", self.spills.len()));
+        let path = PathBuf::from(path);
+
+        let num_partitions = self.partitions.len();
+        let partitions = mem::replace(&mut self.partitions, vec![vec![]; num_partitions]);
+        let part_locs = write_sorted_shuffle(
+            &path,
+            &self.schema,
+            partitions
+                .into_iter()
+                .map(|batches| batches.into_iter().map(Ok)),
+            self.compression,
+            disk_write_metric,
+        )?;
+        self.spills.push(path);
+        self.reservation.free();
+        self.spill_count.add(1);
+        self.spilled_bytes.add(
+            part_locs
+                .iter()
+                .map(|part_loc| part_loc.num_bytes as usize)
+                .sum(),
+        );
+        Ok(())
+    }
+
+    /// Write the buffered partitions to the shuffle file at `data_path`, following
+    /// the batches spilled before them, and remove the spilled runs
+    pub(crate) fn finish(
+        mut self,
+        data_path: &Path,
+        disk_write_metric: &metrics::Time,
+    ) -> Result<Vec<ShuffleWritePartition>> {
+        let partitions = mem::take(&mut self.partitions);
+        let spills = mem::take(&mut self.spills);
+        // the runs are read one at a time, as the partitions are written in order
+        let inputs = partitions
+            .into_iter()
+            .enumerate()
+            .map(|(partition_id, batches)| {
+                spills
+                    .iter()
+                    .flat_map(move |spill| spilled_batches(spill, partition_id))
+                    .chain(batches.into_iter().map(Ok))
+            });
+        let part_locs = write_sorted_shuffle(
+            data_path,
+            &self.schema,
+            inputs,
+            self.compression,
+            disk_write_metric,
+        );
+
+        for spill in &spills {
+            std::fs::remove_file(spill)?;
+            std::fs::remove_file(shuffle_index_path(&spill.to_string_lossy()))?;
+        }
+        part_locs
+    }
+}
+
+/// Batches of output partition `partition_id` in the run spilled to `spill`
+fn spilled_batches(
+    spill: &Path,
+    partition_id: usize,
+) -> Box<dyn Iterator<Item = Result<RecordBatch>>> {
+    let spill = spill.to_string_lossy();
+    let reader = match read_shuffle_index(&spill, partition_id) {
+        // the streams of the partitions missing from a run are empty
+        Ok(Some((_, length))) if length > 0 => open_shuffle_file(&spill, partition_id),
+        Ok(_) => return Box::new(iter::empty()),
+        Err(e) => Err(e),
+    };
+    match reader {
+        Ok(reader) => Box::new(reader.map(|batch| batch.map_err(BallistaError::from))),
+        Err(e) => Box::new(iter::once(Err(e))),
+    }
+}
+
+/// Looks up the byte range `(offset, length)` of an output partition in a
+/// sort-based shuffle file. Returns `None` if the data file has no index, i.e.
+/// it was written by the hash-based shuffle writer.
+pub fn read_shuffle_index(
+    data_path: &str,
+    partition_id: usize,
+) -> Result<Option<(u64, u64)>> {
+    let index_path = shuffle_index_path(data_path);
+    if !Path::new(&index_path).exists() {
+        return Ok(None);
+    }
+
+    let mut index = File::open(&index_path)?;
+    index.seek(SeekFrom::Start(partition_id as u64 * 8))?;
+    let mut buf = [0u8; 16];
+    index.read_exact(&mut buf).map_err(|e| {
+        BallistaError::General(format!(
+            "Failed to read shuffle index {index_path} for partition {partition_id}: {e:?}"
+        ))
+    })?;
+    let start = u64::from_le_bytes(buf[0..8].try_into().unwrap());
+    let end = u64::from_le_bytes(buf[8..16].try_into().unwrap());
+    Ok(Some((start, end - start)))
+}
+
+/// Opens a reader over the given output partition of a shuffle file. Works for both
+/// hash-based shuffle files (the whole file) and sort-based shuffle files (the
+/// partition's sub-range, looked up through the index).
+pub fn open_shuffle_file(path: &str, partition_id: usize) -> Result<ShuffleFileReader> {
+    let file = File::open(path).map_err(|e| {
+        BallistaError::General(format!("Failed to open partition file at {path}: {e:?}"))
+    })?;
+    let (offset, length) = match read_shuffle_index(path, partition_id)? {
+        Some(range) => range,
+        None => (0, file.metadata()?.len()),
+    };
+    let file = ShuffleFileRange::new(file, offset, length)?;
+    StreamReader::try_new(BufReader::new(file), None).map_err(|e| {
+        BallistaError::General(format!("Failed to new arrow FileReader at {path}: {e:?}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::UInt32Array;
+    use datafusion::arrow::datatypes::{DataType, Field};
+    use datafusion::execution::memory_pool::{
+        GreedyMemoryPool, MemoryConsumer, MemoryPool,
+    };
+    use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricBuilder};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn sorted_shuffle_round_trip() -> Result<()> {
+        let schema =
+            Arc::new(Schema::new(vec![Field::new("a", DataType::UInt32, false)]));
+        let batch = |values: Vec<u32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(UInt32Array::from(values))],
+            )
+            .unwrap()
+        };
+        let partitions = vec![
+            vec![batch(vec![1, 2]), batch(vec![3])],
+            vec![],
+            vec![batch(vec![4, 5, 6])],
+        ];
+
+        let work_dir = TempDir::new()?;
+        let data_path = work_dir.path().join("data-0.arrow");
+        let metrics = ExecutionPlanMetricsSet::new();
+        let write_time = MetricBuilder::new(&metrics).subset_time("write_time", 0);
+        let locs = write_sorted_shuffle(
+            &data_path,
+            &schema,
+            partitions
+                .into_iter()
+                .map(|batches| batches.into_iter().map(Ok)),
+            ShuffleCompression::Zstd,
+            &write_time,
+        )?;
+
+        assert_eq!(2, locs.len());
+        assert_eq!(0, locs[0].partition_id);
+        assert_eq!(3, locs[0].num_rows);
+        assert_eq!(2, locs[1].partition_id);
+        assert_eq!(3, locs[1].num_rows);
+
+        let path = data_path.to_str().unwrap();
+        let first =
+            open_shuffle_file(path, 0)?.collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(vec![batch(vec![1, 2]), batch(vec![3])], first);
+
+        let last =
+            open_shuffle_file(path, 2)?.collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(vec![batch(vec![4, 5, 6])], last);
+
+        assert_eq!(Some((locs[0].num_bytes, 0)), read_shuffle_index(path, 1)?);
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_shuffle_buffer_spills_runs() -> Result<()> {
+        let schema =
+            Arc::new(Schema::new(vec![Field::new("a", DataType::UInt32, false)]));
+        let batch = |values: Vec<u32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(UInt32Array::from(values))],
+            )
+            .unwrap()
+        };
+        // room for about two batches at a time
+        let batch_size = batch((0..1024).collect()).get_array_memory_size();
+        let pool: Arc<dyn MemoryPool> =
+            Arc::new(GreedyMemoryPool::new(batch_size * 5 / 2));
+        let reservation = MemoryConsumer::new("test").register(&pool);
+
+        let work_dir = TempDir::new()?;
+        let data_path = work_dir.path().join("data-0.arrow");
+        let metrics = ExecutionPlanMetricsSet::new();
+        let write_time = MetricBuilder::new(&metrics).subset_time("write_time", 0);
+        let spill_count = MetricBuilder::new(&metrics).spill_count(0);
+        let mut buffer = SortedShuffleBuffer::new(
+            3,
+            schema.clone(),
+            ShuffleCompression::Zstd,
+            reservation,
+            data_path.clone(),
+            spill_count.clone(),
+            MetricBuilder::new(&metrics).spilled_bytes(0),
+        );
+        for i in 0..8u32 {
+            let partition = if i % 2 == 0 { 0 } else { 2 };
+            buffer.push(
+                partition,
+                batch((i * 1024..(i + 1) * 1024).collect()),
+                &write_time,
+            )?;
+        }
+        assert!(pool.reserved() <= batch_size * 5 / 2);
+        let locs = buffer.finish(&data_path, &write_time)?;
+
+        assert!(spill_count.value() > 0);
+        assert_eq!(0, pool.reserved());
+        assert_eq!(2, locs.len());
+        assert_eq!(4 * 1024, locs[0].num_rows);
+        assert_eq!(4 * 1024, locs[1].num_rows);
+
+        // the spilled runs are merged in order and removed
+        let path = data_path.to_str().unwrap();
+        let first =
+            open_shuffle_file(path, 0)?.collect::<std::result::Result<Vec<_>, _>>()?;
+        let expected = (0..8u32)
+            .step_by(2)
+            .map(|i| batch((i * 1024..(i + 1) * 1024).collect()))
+            .collect::<Vec<_>>();
+        assert_eq!(expected, first);
+        let mut files = std::fs::read_dir(work_dir.path())?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+        files.sort();
+        assert_eq!(
+            vec!["data-0.arrow".to_owned(), "data-0.arrow.index".to_owned()],
+            files
+        );
+        Ok(())
+    }
+}
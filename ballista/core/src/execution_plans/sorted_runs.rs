@@ -0,0 +1,276 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Merge of sorted shuffle outputs.
+//!
+//! With sorted shuffles, every map task sorts its input before partitioning it, so each
+//! map output of a shuffle partition is a sorted run. The reader of the partition merges
+//! the runs, holding a single batch of each of them in memory, rather than sorting the
+//! whole partition.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use datafusion::arrow::array::Array;
+use datafusion::arrow::compute::interleave;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::row::{OwnedRow, RowConverter, Rows, SortField};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_expr::PhysicalSortExpr;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::StreamExt;
+
+/// Merge streams of rows sorted on `ordering` into a single stream sorted on it. Rows
+/// comparing equal come out in the order of their streams.
+pub(crate) fn merge_sorted_runs(
+    runs: Vec<SendableRecordBatchStream>,
+    schema: SchemaRef,
+    ordering: Vec<PhysicalSortExpr>,
+    batch_size: usize,
+) -> Result<SendableRecordBatchStream> {
+    let sort_fields = ordering
+        .iter()
+        .map(|sort| {
+            Ok(SortField::new_with_options(
+                sort.expr.data_type(&schema)?,
+                sort.options,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let merge = SortedRunsMerge {
+        schema: schema.clone(),
+        converter: RowConverter::new(sort_fields)?,
+        ordering,
+        batch_size: batch_size.max(1),
+        runs: runs
+            .into_iter()
+            .map(|stream| SortedRun {
+                stream,
+                current: None,
+                row: 0,
+                batch_index: 0,
+            })
+            .collect(),
+        batches: vec![],
+        heap: BinaryHeap::new(),
+        started: false,
+    };
+
+    let stream = futures::stream::try_unfold(merge, |mut merge| async move {
+        let batch = merge.next_batch().await?;
+        Ok::<_, DataFusionError>(batch.map(|batch| (batch, merge)))
+    });
+    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+}
+
+struct SortedRun {
+    stream: SendableRecordBatchStream,
+    /// Batch being merged, with its sort keys, or None once the run is exhausted
+    current: Option<(RecordBatch, Rows)>,
+    /// Next row of the batch to merge
+    row: usize,
+    /// Index of the batch in [SortedRunsMerge::batches]
+    batch_index: usize,
+}
+
+struct SortedRunsMerge {
+    schema: SchemaRef,
+    converter: RowConverter,
+    ordering: Vec<PhysicalSortExpr>,
+    batch_size: usize,
+    runs: Vec<SortedRun>,
+    /// Batches the rows of the next output batch are taken from
+    batches: Vec<RecordBatch>,
+    /// Next row of each run which is not exhausted, smallest first
+    heap: BinaryHeap<Reverse<(OwnedRow, usize)>>,
+    started: bool,
+}
+
+impl SortedRunsMerge {
+    async fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        if !self.started {
+            self.started = true;
+            for run in 0..self.runs.len() {
+                self.load_batch(run).await?;
+                self.push_row(run);
+            }
+        }
+
+        let mut indices = Vec::with_capacity(self.batch_size);
+        while indices.len() < self.batch_size {
+            let Some(Reverse((_, run))) = self.heap.pop() else {
+                break;
+            };
+            let sorted_run = &mut self.runs[run];
+            indices.push((sorted_run.batch_index, sorted_run.row));
+            sorted_run.row += 1;
+            let exhausted_batch = sorted_run
+                .current
+                .as_ref()
+                .is_some_and(|(batch, _)| sorted_run.row == batch.num_rows());
+            if exhausted_batch {
+                self.load_batch(run).await?;
+            }
+            self.push_row(run);
+        }
+        if indices.is_empty() {
+            return Ok(None);
+        }
+
+        let columns = (0..self.schema.fields().len())
+            .map(|column| {
+                let arrays: Vec<&dyn Array> = self
+                    .batches
+                    .iter()
+                    .map(|batch| batch.column(column).as_ref())
+                    .collect();
+                interleave(&arrays, &indices)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+
+        // Only the batches still being merged are needed for the next output batch
+        self.batches.clear();
+        for run in &mut self.runs {
+            if let Some((batch, _)) = &run.current {
+                run.batch_index = self.batches.len();
+                self.batches.push(batch.clone());
+            }
+        }
+
+        Ok(Some(batch))
+    }
+
+    /// Load the next non-empty batch of a run
+    async fn load_batch(&mut self, run: usize) -> Result<()> {
+        let sorted_run = &mut self.runs[run];
+        sorted_run.current = None;
+        while let Some(batch) = sorted_run.stream.next().await {
+            let batch = batch?;
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            let keys = self
+                .ordering
+                .iter()
+                .map(|sort| sort.expr.evaluate(&batch)?.into_array(batch.num_rows()))
+                .collect::<Result<Vec<_>>>()?;
+            let rows = self.converter.convert_columns(&keys)?;
+
+            sorted_run.row = 0;
+            sorted_run.batch_index = self.batches.len();
+            self.batches.push(batch.clone());
+            sorted_run.current = Some((batch, rows));
+            break;
+        }
+        Ok(())
+    }
+
+    fn push_row(&mut self, run: usize) {
+        let sorted_run = &self.runs[run];
+        if let Some((_, rows)) = &sorted_run.current {
+            self.heap
+                .push(Reverse((rows.row(sorted_run.row).owned(), run)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use datafusion::arrow::compute::SortOptions;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::assert_batches_eq;
+    use datafusion::physical_expr::expressions::Column;
+    use datafusion::physical_expr::PhysicalSortExpr;
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use datafusion::physical_plan::{common, SendableRecordBatchStream};
+
+    use crate::execution_plans::sorted_runs::merge_sorted_runs;
+
+    fn run(batches: Vec<(Vec<i32>, Vec<&str>)>) -> SendableRecordBatchStream {
+        let batches = batches
+            .into_iter()
+            .map(|(a, b)| {
+                RecordBatch::try_from_iter(vec![
+                    ("a", Arc::new(Int32Array::from(a)) as _),
+                    ("b", Arc::new(StringArray::from(b)) as _),
+                ])
+            })
+            .collect::<Vec<_>>();
+        Box::pin(RecordBatchStreamAdapter::new(
+            schema(),
+            futures::stream::iter(
+                batches.into_iter().map(|batch| batch.map_err(Into::into)),
+            ),
+        ))
+    }
+
+    fn schema() -> Arc<Schema> {
+        Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, false),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn test_merge_sorted_runs() {
+        let runs = vec![
+            run(vec![
+                (vec![1, 4], vec!["x", "x"]),
+                (vec![], vec![]),
+                (vec![6], vec!["x"]),
+            ]),
+            run(vec![]),
+            run(vec![(vec![2, 4, 5], vec!["y", "y", "y"])]),
+            run(vec![(vec![3], vec!["z"]), (vec![7], vec!["z"])]),
+        ];
+        let ordering = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+
+        let merged = merge_sorted_runs(runs, schema(), ordering, 3).unwrap();
+        let batches = common::collect(merged).await.unwrap();
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![3, 3, 2]
+        );
+        assert_batches_eq!(
+            [
+                "+---+---+",
+                "| a | b |",
+                "+---+---+",
+                "| 1 | x |",
+                "| 2 | y |",
+                "| 3 | z |",
+                "| 4 | x |",
+                "| 4 | y |",
+                "| 5 | y |",
+                "| 6 | x |",
+                "| 7 | z |",
+                "+---+---+",
+            ],
+            &batches
+        );
+    }
+}
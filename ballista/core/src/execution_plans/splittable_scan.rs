@@ -0,0 +1,439 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! File scans whose files are leased one at a time to the tasks of their stage, so
+//! that the files a slow task has not started scanning yet can be scanned by the tasks
+//! which are done with theirs.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::datasource::listing::PartitionedFile;
+use datafusion::datasource::physical_plan::{
+    ArrowExec, AvroExec, CsvExec, FileScanConfig, NdJsonExec, ParquetExec,
+};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+    SendableRecordBatchStream, Statistics,
+};
+use futures::TryStreamExt;
+
+use super::scan_options::files_compression;
+use super::ScanOptionsExec;
+
+/// Leases the files of the splittable scans of the stages of jobs to their tasks,
+/// usually by asking the scheduler
+#[async_trait]
+pub trait ScanFileLeaser: Send + Sync + Debug {
+    /// The index, among the files of all the partitions of the scan of stage
+    /// `stage_id`, of the file a task of partition `partition_id` scans after the
+    /// `position` files it already scanned, or None once there are no files left.
+    /// Attempts of a task are leased the same files as the previous attempts.
+    async fn lease_file(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        partition_id: usize,
+        position: usize,
+    ) -> Result<Option<usize>>;
+}
+
+/// The [ScanFileLeaser] of the tasks of an executor, which the executor puts in the
+/// [SessionConfig] of its tasks as an extension
+///
+/// [SessionConfig]: datafusion::prelude::SessionConfig
+#[derive(Debug, Clone)]
+pub struct ScanFileLeases(pub Arc<dyn ScanFileLeaser>);
+
+/// SplittableScanExec scans the files leased to its task by a [ScanFileLeaser] rather
+/// than the files of the partition of its input, a Parquet, CSV, JSON, Avro or Arrow
+/// scan. Each partition starts with its own files, and once they are all leased, is
+/// leased the files other partitions have not started scanning yet.
+///
+/// Tasks executed without [ScanFileLeases] in their config scan the files of their
+/// partition, as their input would.
+#[derive(Debug)]
+pub struct SplittableScanExec {
+    job_id: String,
+    stage_id: usize,
+    input: Arc<dyn ExecutionPlan>,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl SplittableScanExec {
+    /// Create a new SplittableScanExec wrapping a file scan, which fails if the scan
+    /// isn't splittable
+    pub fn try_new(
+        job_id: String,
+        stage_id: usize,
+        input: Arc<dyn ExecutionPlan>,
+    ) -> Result<Self> {
+        if scan_config(input.as_ref()).is_none() {
+            return Err(DataFusionError::Internal(format!(
+                "SplittableScanExec does not support scan {}",
+                input.name()
+            )));
+        }
+        Ok(Self {
+            job_id,
+            stage_id,
+            input,
+            metrics: ExecutionPlanMetricsSet::new(),
+        })
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    pub fn stage_id(&self) -> usize {
+        self.stage_id
+    }
+
+    /// Number of files of each partition of the scan
+    pub fn partition_files(&self) -> Vec<usize> {
+        self.base_config()
+            .file_groups
+            .iter()
+            .map(|group| group.len())
+            .collect()
+    }
+
+    fn base_config(&self) -> &FileScanConfig {
+        scan_config(self.input.as_ref()).expect("SplittableScanExec of a file scan")
+    }
+
+    /// Whether a scan can be split, i.e. is a file scan whose partitions have no
+    /// ordering or limit to preserve
+    pub fn is_splittable(plan: &dyn ExecutionPlan) -> bool {
+        scan_config(plan).is_some_and(|config| {
+            config.output_ordering.is_empty() && config.limit.is_none()
+        })
+    }
+}
+
+/// Config of a Parquet, CSV, JSON, Avro or Arrow scan
+pub(crate) fn scan_config(plan: &dyn ExecutionPlan) -> Option<&FileScanConfig> {
+    let any = plan.as_any();
+    if let Some(exec) = any.downcast_ref::<ParquetExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = any.downcast_ref::<CsvExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = any.downcast_ref::<NdJsonExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = any.downcast_ref::<AvroExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = any.downcast_ref::<ArrowExec>() {
+        Some(exec.base_config())
+    } else if any.is::<ScanOptionsExec>() {
+        // scans are wrapped into a ScanOptionsExec while their plan is serialized
+        scan_config(plan.children()[0].as_ref())
+    } else {
+        None
+    }
+}
+
+/// The scan of `file` alone, with the options of the scan `input`
+pub(super) fn scan_file(
+    input: &dyn ExecutionPlan,
+    file: PartitionedFile,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    scan_file_groups(input, vec![vec![file]])
+}
+
+/// The scan of `file_groups`, with the options of the scan `input`
+pub(crate) fn scan_file_groups(
+    input: &dyn ExecutionPlan,
+    file_groups: Vec<Vec<PartitionedFile>>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    with_scan_config(input, |config| config.file_groups = file_groups)
+}
+
+/// The scan `input` with its config changed by `update`, keeping its other options
+pub(crate) fn with_scan_config(
+    input: &dyn ExecutionPlan,
+    update: impl FnOnce(&mut FileScanConfig),
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let updated = |config: &FileScanConfig| {
+        let mut config = config.clone();
+        update(&mut config);
+        config
+    };
+    let any = input.as_any();
+    if let Some(exec) = any.downcast_ref::<ParquetExec>() {
+        let mut builder = ParquetExec::builder(updated(exec.base_config()))
+            .with_table_parquet_options(exec.table_parquet_options().clone());
+        if let Some(predicate) = exec.predicate() {
+            builder = builder.with_predicate(predicate.clone());
+        }
+        Ok(builder.build_arc())
+    } else if let Some(exec) = any.downcast_ref::<CsvExec>() {
+        Ok(Arc::new(
+            CsvExec::builder(updated(exec.base_config()))
+                .with_has_header(exec.has_header())
+                .with_delimeter(exec.delimiter())
+                .with_quote(exec.quote())
+                .with_terminator(exec.terminator())
+                .with_escape(exec.escape())
+                .with_comment(exec.comment())
+                .with_newlines_in_values(exec.newlines_in_values())
+                .with_file_compression_type(exec.file_compression_type)
+                .build(),
+        ))
+    } else if let Some(exec) = any.downcast_ref::<NdJsonExec>() {
+        // NdJsonExec does not expose its compression, which matches the extension of
+        // the file
+        let config = updated(exec.base_config());
+        let compression = files_compression(&config);
+        Ok(Arc::new(NdJsonExec::new(config, compression)))
+    } else if let Some(exec) = any.downcast_ref::<AvroExec>() {
+        Ok(Arc::new(AvroExec::new(updated(exec.base_config()))))
+    } else if let Some(exec) = any.downcast_ref::<ArrowExec>() {
+        Ok(Arc::new(ArrowExec::new(updated(exec.base_config()))))
+    } else {
+        Err(DataFusionError::Internal(format!(
+            "Unsupported file scan {}",
+            input.name()
+        )))
+    }
+}
+
+/// The files leased to a task of a [SplittableScanExec]
+struct LeasedFiles {
+    leases: Arc<ScanFileLeases>,
+    files: Vec<PartitionedFile>,
+    input: Arc<dyn ExecutionPlan>,
+    job_id: String,
+    stage_id: usize,
+    partition: usize,
+    context: Arc<TaskContext>,
+    scanned_files: Count,
+}
+
+impl LeasedFiles {
+    /// Scan of the file leased after the `position` files already scanned, along with
+    /// the position of the next file
+    async fn scan_next(
+        &self,
+        position: usize,
+    ) -> Result<Option<(SendableRecordBatchStream, usize)>> {
+        let Some(file) = self
+            .leases
+            .0
+            .lease_file(&self.job_id, self.stage_id, self.partition, position)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let file = self.files.get(file).cloned().ok_or_else(|| {
+            DataFusionError::Internal(format!(
+                "File {file} leased to partition {} of stage {} is out of the {} files \
+                 of the scan",
+                self.partition,
+                self.stage_id,
+                self.files.len()
+            ))
+        })?;
+        self.scanned_files.add(1);
+        let stream =
+            scan_file(self.input.as_ref(), file)?.execute(0, self.context.clone())?;
+        Ok(Some((stream, position + 1)))
+    }
+}
+
+impl DisplayAs for SplittableScanExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "SplittableScanExec: stage_id={}", self.stage_id)
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for SplittableScanExec {
+    fn name(&self) -> &str {
+        "SplittableScanExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [input] => Ok(Arc::new(Self::try_new(
+                self.job_id.clone(),
+                self.stage_id,
+                input.clone(),
+            )?)),
+            _ => Err(DataFusionError::Internal(
+                "SplittableScanExec wrong number of children".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let Some(leases) = context.session_config().get_extension::<ScanFileLeases>()
+        else {
+            return self.input.execute(partition, context);
+        };
+
+        let leased_files = Arc::new(LeasedFiles {
+            leases,
+            files: self.base_config().file_groups.concat(),
+            input: self.input.clone(),
+            job_id: self.job_id.clone(),
+            stage_id: self.stage_id,
+            partition,
+            context,
+            scanned_files: MetricBuilder::new(&self.metrics)
+                .counter("scanned_files", partition),
+        });
+        let stream = futures::stream::try_unfold(0, move |position| {
+            let leased_files = leased_files.clone();
+            async move { leased_files.scan_next(position).await }
+        })
+        .try_flatten();
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::execution::object_store::ObjectStoreUrl;
+    use datafusion::physical_plan::common;
+    use datafusion::prelude::SessionConfig;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Leases the files of the scan in the given order, to whichever partition asks
+    #[derive(Debug)]
+    struct OrderedLeaser {
+        files: Mutex<Vec<usize>>,
+        leases: Mutex<HashMap<(usize, usize), Option<usize>>>,
+    }
+
+    #[async_trait]
+    impl ScanFileLeaser for OrderedLeaser {
+        async fn lease_file(
+            &self,
+            _job_id: &str,
+            _stage_id: usize,
+            partition_id: usize,
+            position: usize,
+        ) -> Result<Option<usize>> {
+            let mut leases = self.leases.lock().unwrap();
+            Ok(*leases
+                .entry((partition_id, position))
+                .or_insert_with(|| self.files.lock().unwrap().pop()))
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_leased_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut files = vec![];
+        for (name, rows) in [("a.csv", "1\n2\n"), ("b.csv", "3\n"), ("c.csv", "4\n")] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, rows)?;
+            files.push(PartitionedFile::new(
+                path.to_str().unwrap().to_owned(),
+                rows.len() as u64,
+            ));
+        }
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let config = FileScanConfig::new(ObjectStoreUrl::local_filesystem(), schema)
+            .with_file_groups(vec![files[..2].to_vec(), files[2..].to_vec()]);
+        let scan = Arc::new(
+            CsvExec::builder(config)
+                .with_has_header(false)
+                .with_delimeter(b',')
+                .with_quote(b'"')
+                .build(),
+        );
+        assert!(SplittableScanExec::is_splittable(scan.as_ref()));
+
+        let exec = SplittableScanExec::try_new("job".to_owned(), 1, scan)?;
+        assert_eq!(vec![2, 1], exec.partition_files());
+
+        // the first partition scans all the files, while the second one is slow to start
+        let leaser = Arc::new(OrderedLeaser {
+            files: Mutex::new(vec![2, 1, 0]),
+            leases: Mutex::default(),
+        });
+        let config =
+            SessionConfig::new().with_extension(Arc::new(ScanFileLeases(leaser)));
+        let context = Arc::new(TaskContext::default().with_session_config(config));
+        let batches = common::collect(exec.execute(0, context.clone())?).await?;
+        assert_eq!(
+            4,
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>()
+        );
+        let batches = common::collect(exec.execute(1, context)?).await?;
+        assert!(batches.is_empty());
+
+        // without leases, each partition scans its own files
+        let context = Arc::new(TaskContext::default());
+        let batches = common::collect(exec.execute(1, context)?).await?;
+        assert_eq!(
+            1,
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>()
+        );
+        Ok(())
+    }
+}
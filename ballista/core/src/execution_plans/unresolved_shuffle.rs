@@ -21,10 +21,12 @@ use std::sync::Arc;
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::error::{DataFusionError, Result};
 use datafusion::execution::context::TaskContext;
+use datafusion::physical_expr::{EquivalenceProperties, PhysicalSortExpr};
 use datafusion::physical_plan::{
     DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PlanProperties,
     SendableRecordBatchStream, Statistics,
 };
+use itertools::Itertools;
 
 /// UnresolvedShuffleExec represents a dependency on the results of a ShuffleWriterExec node which hasn't computed yet.
 ///
@@ -41,6 +43,10 @@ pub struct UnresolvedShuffleExec {
     // The partition count this node will have once it is replaced with a ShuffleReaderExec
     pub output_partition_count: usize,
 
+    // If not empty, the partitions of the ShuffleReaderExec replacing this node are
+    // merged from locations sorted on these expressions
+    pub sort_order: Vec<PhysicalSortExpr>,
+
     properties: PlanProperties,
 }
 
@@ -52,7 +58,7 @@ impl UnresolvedShuffleExec {
         output_partition_count: usize,
     ) -> Self {
         let properties = PlanProperties::new(
-            datafusion::physical_expr::EquivalenceProperties::new(schema.clone()),
+            EquivalenceProperties::new(schema.clone()),
             // TODO the output partition is known and should be populated here!
             // see https://github.com/apache/arrow-datafusion/issues/758
             Partitioning::UnknownPartitioning(output_partition_count),
@@ -62,9 +68,23 @@ impl UnresolvedShuffleExec {
             stage_id,
             schema,
             output_partition_count,
+            sort_order: vec![],
             properties,
         }
     }
+
+    /// Mark the shuffle as sorted on `sort_order`
+    pub fn with_sort_order(mut self, sort_order: Vec<PhysicalSortExpr>) -> Self {
+        if !sort_order.is_empty() {
+            let eq_properties = EquivalenceProperties::new_with_orderings(
+                self.schema.clone(),
+                std::slice::from_ref(&sort_order),
+            );
+            self.properties = self.properties.clone().with_eq_properties(eq_properties);
+        }
+        self.sort_order = sort_order;
+        self
+    }
 }
 
 impl DisplayAs for UnresolvedShuffleExec {
@@ -75,7 +95,11 @@ impl DisplayAs for UnresolvedShuffleExec {
     ) -> std::fmt::Result {
         match t {
             DisplayFormatType::Default | DisplayFormatType::Verbose => {
-                write!(f, "UnresolvedShuffleExec")
+                write!(f, "UnresolvedShuffleExec")?;
+                if !self.sort_order.is_empty() {
+                    write!(f, ": sort_order=[{}]", self.sort_order.iter().join(", "))?;
+                }
+                Ok(())
             }
         }
     }
@@ -0,0 +1,365 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Verification of the files of a table, which reads each file to count its rows and
+//! checksum its content, and compares them with the metadata of the file.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, StringBuilder, UInt64Builder};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::stats::Precision;
+use datafusion::datasource::listing::PartitionedFile;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, ExecutionPlanProperties,
+    Partitioning, PlanProperties, SendableRecordBatchStream, Statistics,
+};
+use futures::TryStreamExt;
+
+use super::splittable_scan::{scan_config, scan_file};
+use crate::utils::batch_checksum;
+
+/// The rows and checksum of the file match its metadata
+pub const VERIFY_STATUS_OK: &str = "ok";
+/// The metadata of the file has no row count to compare with
+pub const VERIFY_STATUS_UNVERIFIED: &str = "unverified";
+/// The rows of the file differ from its metadata
+pub const VERIFY_STATUS_MISMATCH: &str = "mismatch";
+/// The file could not be read
+pub const VERIFY_STATUS_UNREADABLE: &str = "unreadable";
+
+/// Schema of the output of [VerifyFilesExec], a row per file
+pub fn verify_files_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("file", DataType::Utf8, false),
+        Field::new("size", DataType::UInt64, false),
+        Field::new("expected_rows", DataType::UInt64, true),
+        Field::new("rows", DataType::UInt64, true),
+        Field::new("checksum", DataType::UInt64, true),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("error", DataType::Utf8, true),
+    ]))
+}
+
+/// VerifyFilesExec reads each file of its input, a Parquet, CSV, JSON, Avro or Arrow
+/// scan, and outputs a row per file with its number of rows and the checksum of its
+/// content, see [batch_checksum], along with the number of rows its metadata expects.
+/// Each partition verifies the files of the same partition of its input, and a file
+/// which can't be read is reported rather than failing the partition.
+#[derive(Debug)]
+pub struct VerifyFilesExec {
+    input: Arc<dyn ExecutionPlan>,
+    properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl VerifyFilesExec {
+    /// Create a new VerifyFilesExec verifying the files of a file scan, which fails
+    /// if the input isn't a file scan
+    pub fn try_new(input: Arc<dyn ExecutionPlan>) -> Result<Self> {
+        if scan_config(input.as_ref()).is_none() {
+            return Err(DataFusionError::Plan(format!(
+                "Only the files of Parquet, CSV, JSON, Avro or Arrow scans can be \
+                 verified, not of {}",
+                input.name()
+            )));
+        }
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(verify_files_schema()),
+            Partitioning::UnknownPartitioning(
+                input.output_partitioning().partition_count(),
+            ),
+            ExecutionMode::Bounded,
+        );
+        Ok(Self {
+            input,
+            properties,
+            metrics: ExecutionPlanMetricsSet::new(),
+        })
+    }
+}
+
+/// Outcome of the verification of a file
+struct FileVerification {
+    file: String,
+    size: u64,
+    expected_rows: Option<u64>,
+    rows: Option<u64>,
+    checksum: Option<u64>,
+    status: &'static str,
+    error: Option<String>,
+}
+
+async fn verify_file(
+    input: &dyn ExecutionPlan,
+    file: PartitionedFile,
+    context: Arc<TaskContext>,
+) -> FileVerification {
+    let path = file.object_meta.location.to_string();
+    let size = file.object_meta.size as u64;
+    let expected_rows = match file.statistics.as_ref().map(|stats| stats.num_rows) {
+        Some(Precision::Exact(rows)) => Some(rows as u64),
+        _ => None,
+    };
+
+    let counted = async {
+        let stream = scan_file(input, file)?.execute(0, context)?;
+        stream
+            .try_fold((0u64, 0u64), |(rows, checksum), batch| async move {
+                Ok((
+                    rows + batch.num_rows() as u64,
+                    checksum.wrapping_add(batch_checksum(&batch)?),
+                ))
+            })
+            .await
+    };
+    match counted.await {
+        Ok((rows, checksum)) => FileVerification {
+            file: path,
+            size,
+            expected_rows,
+            rows: Some(rows),
+            checksum: Some(checksum),
+            status: match expected_rows {
+                None => VERIFY_STATUS_UNVERIFIED,
+                Some(expected) if expected == rows => VERIFY_STATUS_OK,
+                Some(_) => VERIFY_STATUS_MISMATCH,
+            },
+            error: None,
+        },
+        Err(e) => FileVerification {
+            file: path,
+            size,
+            expected_rows,
+            rows: None,
+            checksum: None,
+            status: VERIFY_STATUS_UNREADABLE,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+fn verifications_batch(verifications: &[FileVerification]) -> Result<RecordBatch> {
+    let mut file = StringBuilder::new();
+    let mut size = UInt64Builder::new();
+    let mut expected_rows = UInt64Builder::new();
+    let mut rows = UInt64Builder::new();
+    let mut checksum = UInt64Builder::new();
+    let mut status = StringBuilder::new();
+    let mut error = StringBuilder::new();
+    for verification in verifications {
+        file.append_value(&verification.file);
+        size.append_value(verification.size);
+        expected_rows.append_option(verification.expected_rows);
+        rows.append_option(verification.rows);
+        checksum.append_option(verification.checksum);
+        status.append_value(verification.status);
+        error.append_option(verification.error.as_deref());
+    }
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(file.finish()),
+        Arc::new(size.finish()),
+        Arc::new(expected_rows.finish()),
+        Arc::new(rows.finish()),
+        Arc::new(checksum.finish()),
+        Arc::new(status.finish()),
+        Arc::new(error.finish()),
+    ];
+    Ok(RecordBatch::try_new(verify_files_schema(), columns)?)
+}
+
+impl DisplayAs for VerifyFilesExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(f, "VerifyFilesExec")
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for VerifyFilesExec {
+    fn name(&self) -> &str {
+        "VerifyFilesExec"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        vec![&self.input]
+    }
+
+    fn benefits_from_input_partitioning(&self) -> Vec<bool> {
+        // splitting the files of the scan into ranges would verify them in pieces
+        vec![false]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [input] => Ok(Arc::new(Self::try_new(input.clone())?)),
+            _ => Err(DataFusionError::Internal(
+                "VerifyFilesExec wrong number of children".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let files = scan_config(self.input.as_ref())
+            .and_then(|config| config.file_groups.get(partition))
+            .cloned()
+            .unwrap_or_default();
+        let input = self.input.clone();
+        let verified_files: Count =
+            MetricBuilder::new(&self.metrics).counter("verified_files", partition);
+        let invalid_files: Count =
+            MetricBuilder::new(&self.metrics).counter("invalid_files", partition);
+
+        let stream = futures::stream::once(async move {
+            let mut verifications = Vec::with_capacity(files.len());
+            for file in files {
+                let verification =
+                    verify_file(input.as_ref(), file, context.clone()).await;
+                verified_files.add(1);
+                if verification.status != VERIFY_STATUS_OK
+                    && verification.status != VERIFY_STATUS_UNVERIFIED
+                {
+                    invalid_files.add(1);
+                }
+                verifications.push(verification);
+            }
+            verifications_batch(&verifications)
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, AsArray};
+    use datafusion::arrow::datatypes::UInt64Type;
+    use datafusion::common::ColumnStatistics;
+    use datafusion::datasource::physical_plan::{CsvExec, FileScanConfig};
+    use datafusion::execution::object_store::ObjectStoreUrl;
+    use datafusion::physical_plan::common;
+
+    #[tokio::test]
+    async fn verify_rows_of_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let mut files = vec![];
+        for (name, rows, expected) in [
+            ("a.csv", "1\n2\n", Some(2)),
+            ("b.csv", "2\n1\n", Some(3)),
+            ("c.csv", "3\n", None),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, rows)?;
+            let mut file = PartitionedFile::new(
+                path.to_str().unwrap().to_owned(),
+                rows.len() as u64,
+            );
+            file.statistics = expected.map(|expected| Statistics {
+                num_rows: Precision::Exact(expected),
+                total_byte_size: Precision::Absent,
+                column_statistics: vec![ColumnStatistics::new_unknown()],
+            });
+            files.push(file);
+        }
+        let mut missing = files[2].clone();
+        missing.object_meta.location = object_store::path::Path::from(
+            dir.path().join("missing.csv").to_str().unwrap(),
+        );
+        files.push(missing);
+
+        let config = FileScanConfig::new(ObjectStoreUrl::local_filesystem(), schema)
+            .with_file_groups(vec![files[..2].to_vec(), files[2..].to_vec()]);
+        let scan = Arc::new(
+            CsvExec::builder(config)
+                .with_has_header(false)
+                .with_delimeter(b',')
+                .with_quote(b'"')
+                .build(),
+        );
+        let exec = VerifyFilesExec::try_new(scan)?;
+        assert_eq!(2, exec.properties().output_partitioning().partition_count());
+
+        let context = Arc::new(TaskContext::default());
+        let batches = common::collect(exec.execute(0, context.clone())?).await?;
+        let batch = &batches[0];
+        let rows = batch.column(3).as_primitive::<UInt64Type>();
+        let checksums = batch.column(4).as_primitive::<UInt64Type>();
+        let statuses = batch.column(5).as_string::<i32>();
+        assert_eq!((2, 2), (rows.value(0), rows.value(1)));
+        // the files have the same rows in another order
+        assert_eq!(checksums.value(0), checksums.value(1));
+        assert_eq!(
+            (VERIFY_STATUS_OK, VERIFY_STATUS_MISMATCH),
+            (statuses.value(0), statuses.value(1))
+        );
+
+        let batches = common::collect(exec.execute(1, context)?).await?;
+        let batch = &batches[0];
+        let rows = batch.column(3).as_primitive::<UInt64Type>();
+        let statuses = batch.column(5).as_string::<i32>();
+        assert_eq!(1, rows.value(0));
+        assert_eq!(
+            (VERIFY_STATUS_UNVERIFIED, VERIFY_STATUS_UNREADABLE),
+            (statuses.value(0), statuses.value(1))
+        );
+        assert!(rows.is_null(1));
+        assert!(batch.column(6).is_valid(1));
+        Ok(())
+    }
+}
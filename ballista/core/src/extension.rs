@@ -15,13 +15,37 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::compression::{CompressionCodec, CompressionCodecRegistry};
 use crate::config::{
-    BallistaConfig, BALLISTA_GRPC_CLIENT_MAX_MESSAGE_SIZE, BALLISTA_JOB_NAME,
-    BALLISTA_STANDALONE_PARALLELISM,
+    redact_value, BallistaConfig, ExplainFormat, ParseResult, ShuffleCompression,
+    BALLISTA_BEST_EFFORT, BALLISTA_CHECKSUM_RESULTS, BALLISTA_DETERMINISTIC,
+    BALLISTA_EXPLAIN_FORMAT, BALLISTA_GRPC_CLIENT_MAX_MESSAGE_SIZE,
+    BALLISTA_INLINE_RESULT_MAX_BYTES, BALLISTA_JOB_MAX_TASKS_PER_EXECUTOR,
+    BALLISTA_JOB_NAME, BALLISTA_JOB_PRIORITY, BALLISTA_JOB_RESOURCE_PROFILE,
+    BALLISTA_JOB_RESULT_TTL, BALLISTA_JOB_SLOT_RESERVATION, BALLISTA_JOB_STAGING_URL,
+    BALLISTA_JOB_WEBHOOK_URL, BALLISTA_JOIN_BROADCAST_THRESHOLD_BYTES,
+    BALLISTA_JOIN_PREFER_SORT_MERGE, BALLISTA_MAX_RESULT_ROWS,
+    BALLISTA_OPTIMIZER_COMPRESSION_RATIO_SCALE, BALLISTA_OPTIMIZER_HISTORICAL_STATISTICS,
+    BALLISTA_OPTIMIZER_NETWORK_BYTES_PER_SECOND,
+    BALLISTA_OPTIMIZER_PRE_AGGREGATE_GROUPING_SETS, BALLISTA_SCAN_MERGE_SCHEMAS,
+    BALLISTA_SCAN_PARTITION_COLUMN_TYPES, BALLISTA_SCAN_SPLITTABLE,
+    BALLISTA_SHUFFLE_COALESCE_PARTITIONS, BALLISTA_SHUFFLE_COMPRESSION,
+    BALLISTA_SHUFFLE_FETCH_RETRIES, BALLISTA_SHUFFLE_FETCH_RETRY_BACKOFF_MS,
+    BALLISTA_SHUFFLE_MAX_BUFFERED_BATCHES, BALLISTA_SHUFFLE_MAX_CONCURRENT_FETCHES,
+    BALLISTA_SHUFFLE_OBJECT_STORE_URL, BALLISTA_SHUFFLE_SORT_BASED_PARTITION_THRESHOLD,
+    BALLISTA_SHUFFLE_TARGET_PARTITION_BYTES, BALLISTA_SHUFFLE_TRANSFER_COMPRESSION,
+    BALLISTA_STAGE_TIMEOUT, BALLISTA_STANDALONE_PARALLELISM, BALLISTA_VERIFY_STAGES,
 };
+use crate::error::{BallistaError, Result};
+use crate::historical_statistics::TableSize;
+use crate::security::{FetchToken, GrpcSecurityConfig};
 use crate::serde::protobuf::KeyValuePair;
 use crate::serde::{BallistaLogicalExtensionCodec, BallistaPhysicalExtensionCodec};
+use crate::table_factory::with_ballista_table_factories;
+use crate::table_functions::with_ballista_table_functions;
 use crate::utils::BallistaQueryPlanner;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::config::ConfigOptions;
 use datafusion::execution::context::{QueryPlanner, SessionConfig, SessionState};
 use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
 use datafusion::execution::session_state::SessionStateBuilder;
@@ -70,6 +94,27 @@ pub trait SessionConfigExt {
         codec: Arc<dyn PhysicalExtensionCodec>,
     ) -> SessionConfig;
 
+    /// Registers a user [LogicalExtensionCodec] for the extension nodes
+    /// and table providers ballista's codec does not know,
+    /// e.g. the providers of table formats
+    ///
+    /// User codecs are tried in the order of their registration,
+    /// and are ignored if ballista's codec is overridden
+    fn with_ballista_user_logical_extension_codec(
+        self,
+        codec: Arc<dyn LogicalExtensionCodec>,
+    ) -> SessionConfig;
+
+    /// Registers a user [PhysicalExtensionCodec] for the execution plans
+    /// ballista's codec does not know
+    ///
+    /// User codecs are tried in the order of their registration,
+    /// and are ignored if ballista's codec is overridden
+    fn with_ballista_user_physical_extension_codec(
+        self,
+        codec: Arc<dyn PhysicalExtensionCodec>,
+    ) -> SessionConfig;
+
     /// returns [LogicalExtensionCodec] if set
     /// or default ballista codec if not
     fn ballista_logical_extension_codec(&self) -> Arc<dyn LogicalExtensionCodec>;
@@ -104,6 +149,281 @@ pub trait SessionConfigExt {
 
     /// Sets ballista job name
     fn with_ballista_job_name(self, job_name: &str) -> Self;
+
+    /// retrieves the number of shuffle output partitions above which
+    /// the sort-based shuffle is used (0 disables it)
+    fn ballista_shuffle_sort_based_partition_threshold(&self) -> usize;
+
+    /// sets the number of shuffle output partitions above which
+    /// the sort-based shuffle is used (0 disables it)
+    fn with_ballista_shuffle_sort_based_partition_threshold(
+        self,
+        threshold: usize,
+    ) -> Self;
+
+    /// retrieves the maximum size of a final stage output partition
+    /// to be delivered inline with the task status (0 disables it)
+    fn ballista_inline_result_max_bytes(&self) -> usize;
+
+    /// sets the maximum size of a final stage output partition
+    /// to be delivered inline with the task status (0 disables it)
+    fn with_ballista_inline_result_max_bytes(self, max_bytes: usize) -> Self;
+
+    /// whether the stage is executed a second time on another
+    /// executor to verify its output
+    fn ballista_verify_stage(&self, stage_id: usize) -> bool;
+
+    /// sets the comma-separated ids of the stages to verify,
+    /// or * to verify all stages
+    fn with_ballista_verify_stages(self, stages: &str) -> Self;
+
+    /// whether the executors checksum the output partitions
+    /// of the final stage
+    fn ballista_checksum_results(&self) -> bool;
+
+    /// sets whether the executors checksum the output partitions
+    /// of the final stage
+    fn with_ballista_checksum_results(self, checksum: bool) -> Self;
+
+    /// whether jobs produce byte-identical results on every run
+    fn ballista_deterministic(&self) -> bool;
+
+    /// sets whether jobs produce byte-identical results on every run
+    fn with_ballista_deterministic(self, deterministic: bool) -> Self;
+
+    /// retrieves the maximum number of result rows returned to
+    /// the client (0 means no limit)
+    fn ballista_max_result_rows(&self) -> usize;
+
+    /// sets the maximum number of result rows returned to
+    /// the client (0 means no limit)
+    fn with_ballista_max_result_rows(self, max_rows: usize) -> Self;
+
+    /// whether jobs complete with partial results when partitions
+    /// permanently fail
+    fn ballista_best_effort(&self) -> bool;
+
+    /// sets whether jobs complete with partial results when partitions
+    /// permanently fail
+    fn with_ballista_best_effort(self, best_effort: bool) -> Self;
+
+    /// retrieves the priority of jobs, higher values being more important
+    fn ballista_job_priority(&self) -> u32;
+
+    /// sets the priority of jobs, higher values being more important
+    fn with_ballista_job_priority(self, priority: u32) -> Self;
+
+    /// retrieves the URL the scheduler calls back once jobs complete
+    fn ballista_job_webhook_url(&self) -> Option<String>;
+
+    /// sets the URL the scheduler calls back once jobs finish, fail or are cancelled
+    fn with_ballista_job_webhook_url(self, url: &str) -> Self;
+
+    /// retrieves the maximum number of tasks of a job running at the same time on an
+    /// executor (0 means no limit)
+    fn ballista_job_max_tasks_per_executor(&self) -> usize;
+
+    /// sets the maximum number of tasks of a job running at the same time on an
+    /// executor (0 means no limit)
+    fn with_ballista_job_max_tasks_per_executor(self, max_tasks: usize) -> Self;
+
+    /// retrieves the object store under which the jobs get their staging prefix
+    fn ballista_job_staging_url(&self) -> Option<String>;
+
+    /// sets the object store under which the jobs get their staging prefix,
+    /// e.g. s3://bucket/staging
+    fn with_ballista_job_staging_url(self, url: &str) -> Self;
+
+    /// retrieves the resource profile of the scheduler the jobs are submitted with
+    fn ballista_job_resource_profile(&self) -> Option<String>;
+
+    /// sets the resource profile of the scheduler, e.g. small, medium or large, the
+    /// jobs are submitted with
+    fn with_ballista_job_resource_profile(self, profile: &str) -> Self;
+
+    /// retrieves the reservation of executor slots the jobs run with
+    fn ballista_job_slot_reservation(&self) -> Option<String>;
+
+    /// sets the ID of the reservation of executor slots, made on the scheduler ahead of
+    /// the jobs, they run with
+    fn with_ballista_job_slot_reservation(self, reservation_id: &str) -> Self;
+
+    /// retrieves the seconds the executors keep the results of the jobs (0 means the
+    /// finished job data clean up interval of the scheduler)
+    fn ballista_job_result_ttl_seconds(&self) -> usize;
+
+    /// sets the seconds the executors keep the results of the jobs unless released
+    /// earlier by the client (0 means the finished job data clean up interval of the
+    /// scheduler)
+    fn with_ballista_job_result_ttl_seconds(self, ttl_seconds: usize) -> Self;
+
+    /// retrieves the seconds a task of a stage may run (0 means no timeout)
+    fn ballista_stage_timeout_seconds(&self) -> usize;
+
+    /// sets the seconds a task of a stage may run (0 means no timeout)
+    fn with_ballista_stage_timeout_seconds(self, timeout_seconds: usize) -> Self;
+
+    /// whether tables are created with the merged schema of their files
+    fn ballista_merge_schemas(&self) -> bool;
+
+    /// sets whether tables are created with the merged schema of their files
+    fn with_ballista_merge_schemas(self, merge_schemas: bool) -> Self;
+
+    /// whether the files of file scans are leased to tasks as they go
+    fn ballista_splittable_scans(&self) -> bool;
+
+    /// sets whether the files of file scans are leased to tasks as they go
+    fn with_ballista_splittable_scans(self, splittable: bool) -> Self;
+
+    /// retrieves the type set for a partition column of the tables,
+    /// overriding its declared type
+    fn ballista_partition_column_type(
+        &self,
+        column: &str,
+    ) -> ParseResult<Option<DataType>>;
+
+    /// sets the comma-separated column:type pairs of the types of the
+    /// partition columns of the tables, * applying to all other columns
+    fn with_ballista_partition_column_types(self, types: &str) -> Self;
+
+    /// whether equi-joins are executed as sort-merge joins
+    fn ballista_prefer_sort_merge_join(&self) -> bool;
+
+    /// sets whether equi-joins are executed as sort-merge joins,
+    /// also setting `datafusion.optimizer.prefer_hash_join` accordingly
+    fn with_ballista_prefer_sort_merge_join(self, prefer: bool) -> Self;
+
+    /// whether grouping set aggregations are pre-aggregated by all their
+    /// grouping columns
+    fn ballista_pre_aggregate_grouping_sets(&self) -> bool;
+
+    /// sets whether grouping set aggregations are pre-aggregated by all their
+    /// grouping columns
+    fn with_ballista_pre_aggregate_grouping_sets(self, pre_aggregate: bool) -> Self;
+
+    /// retrieves the max estimated size in bytes of the build side of a
+    /// hash join for it to be broadcast
+    fn ballista_broadcast_join_threshold(&self) -> usize;
+
+    /// sets the max estimated size in bytes of the build side of a hash join
+    /// for it to be broadcast, 0 disabling broadcast joins, also setting the
+    /// thresholds of `datafusion.optimizer.hash_join_single_partition_threshold`
+    /// accordingly
+    fn with_ballista_broadcast_join_threshold(self, threshold_bytes: usize) -> Self;
+
+    /// retrieves the factor correcting the expected compression ratios of the
+    /// exchanged columns, if set
+    fn ballista_compression_ratio_scale(&self) -> ParseResult<Option<f64>>;
+
+    /// sets the factor correcting the expected compression ratios of the exchanged
+    /// columns, rather than the one calibrated by the scheduler
+    fn with_ballista_compression_ratio_scale(self, scale: f64) -> Self;
+
+    /// retrieves the network bandwidth in bytes per second the exchanges are costed
+    /// with, 0 for the one measured by the scheduler
+    fn ballista_network_bytes_per_second(&self) -> u64;
+
+    /// sets the network bandwidth in bytes per second the exchanges are costed with,
+    /// rather than the one measured by the scheduler
+    fn with_ballista_network_bytes_per_second(self, bandwidth: u64) -> Self;
+
+    /// whether the scans without statistics are planned with the sizes of their
+    /// tables recorded from the completed jobs
+    fn ballista_historical_statistics(&self) -> bool;
+
+    /// sets whether the scans without statistics are planned with the sizes of their
+    /// tables recorded from the completed jobs
+    fn with_ballista_historical_statistics(self, enabled: bool) -> Self;
+
+    /// retrieves the sizes of the tables the scans without statistics are planned
+    /// with, by location
+    fn ballista_table_statistics(&self) -> ParseResult<Vec<(String, TableSize)>>;
+
+    /// retrieves the codec compressing shuffle files
+    fn ballista_shuffle_compression(&self) -> ParseResult<ShuffleCompression>;
+
+    /// sets the codec compressing shuffle files
+    fn with_ballista_shuffle_compression(self, compression: ShuffleCompression) -> Self;
+
+    /// retrieves the codec compressing the shuffle partitions fetched over Flight, if
+    /// not the codec of their shuffle files
+    fn ballista_shuffle_transfer_compression(&self) -> Option<String>;
+
+    /// sets the codec of the [CompressionCodecRegistry] compressing the shuffle
+    /// partitions fetched over Flight, e.g. snappy
+    fn with_ballista_shuffle_transfer_compression(self, codec: &str) -> Self;
+
+    /// retrieves the compression codecs payloads are compressed with by name
+    fn ballista_compression_codecs(&self) -> Arc<CompressionCodecRegistry>;
+
+    /// registers a compression codec, which settings can then refer to by name. The
+    /// executors and the scheduler must register it too
+    fn with_ballista_compression_codec(self, codec: Arc<dyn CompressionCodec>) -> Self;
+
+    /// retrieves the format of the distributed plan shown by EXPLAIN
+    fn ballista_explain_format(&self) -> ParseResult<ExplainFormat>;
+
+    /// sets the format of the distributed plan shown by EXPLAIN
+    fn with_ballista_explain_format(self, format: ExplainFormat) -> Self;
+
+    /// retrieves the object store which map tasks upload their shuffle files to
+    fn ballista_shuffle_object_store_url(&self) -> Option<String>;
+
+    /// sets the object store which map tasks upload their shuffle files to,
+    /// e.g. s3://bucket/shuffle
+    fn with_ballista_shuffle_object_store_url(self, url: &str) -> Self;
+
+    /// whether small shuffle partitions are coalesced at runtime
+    fn ballista_coalesce_partitions(&self) -> bool;
+
+    /// sets whether small shuffle partitions are coalesced at runtime
+    fn with_ballista_coalesce_partitions(self, coalesce: bool) -> Self;
+
+    /// retrieves the size in bytes shuffle partitions are coalesced up to
+    fn ballista_target_partition_bytes(&self) -> usize;
+
+    /// sets the size in bytes shuffle partitions are coalesced up to
+    fn with_ballista_target_partition_bytes(self, target_bytes: usize) -> Self;
+
+    /// retrieves the maximum number of shuffle partitions a task
+    /// fetches at the same time
+    fn ballista_shuffle_max_concurrent_fetches(&self) -> usize;
+
+    /// sets the maximum number of shuffle partitions a task
+    /// fetches at the same time
+    fn with_ballista_shuffle_max_concurrent_fetches(self, max_fetches: usize) -> Self;
+
+    /// retrieves the number of times a failed shuffle partition fetch
+    /// is retried
+    fn ballista_shuffle_fetch_retries(&self) -> usize;
+
+    /// sets the number of times a failed shuffle partition fetch
+    /// is retried
+    fn with_ballista_shuffle_fetch_retries(self, retries: usize) -> Self;
+
+    /// retrieves the milliseconds before the first retry of a failed
+    /// shuffle partition fetch, doubled on each retry
+    fn ballista_shuffle_fetch_retry_backoff_ms(&self) -> usize;
+
+    /// sets the milliseconds before the first retry of a failed
+    /// shuffle partition fetch, doubled on each retry
+    fn with_ballista_shuffle_fetch_retry_backoff_ms(self, backoff_ms: usize) -> Self;
+
+    /// retrieves the maximum number of fetched shuffle batches
+    /// buffered until the task consumes them
+    fn ballista_shuffle_max_buffered_batches(&self) -> usize;
+
+    /// sets the maximum number of fetched shuffle batches
+    /// buffered until the task consumes them
+    fn with_ballista_shuffle_max_buffered_batches(self, max_batches: usize) -> Self;
+
+    /// retrieves the TLS and auth token configuration of the connections to the
+    /// scheduler and the executors
+    fn ballista_grpc_security(&self) -> Arc<GrpcSecurityConfig>;
+
+    /// sets the TLS and auth token configuration of the connections to the scheduler
+    /// and the executors. Unlike settings, it is never sent to the scheduler
+    fn with_ballista_grpc_security(self, security: GrpcSecurityConfig) -> Self;
 }
 
 /// [SessionConfigHelperExt] is set of [SessionConfig] extension methods
@@ -115,8 +435,28 @@ pub trait SessionConfigHelperExt {
     fn update_from_key_value_pair(self, key_value_pairs: &[KeyValuePair]) -> Self;
     /// updates mut [SessionConfig] from proto
     fn update_from_key_value_pair_mut(&mut self, key_value_pairs: &[KeyValuePair]);
+    /// converts the settings of [SessionConfig] which every task of the session is
+    /// executed with, whatever the format of its plan, to proto
+    fn to_task_key_value_pairs(&self) -> Vec<KeyValuePair>;
+    /// checks that [SessionConfig] has the values of the settings which every task of
+    /// the session is executed with, so that a task never runs with those of its executor
+    fn check_task_key_value_pairs(&self, key_value_pairs: &[KeyValuePair]) -> Result<()>;
+    /// retrieves the token of the job of a task, which it presents to fetch the
+    /// shuffle partitions of the job from the other executors
+    fn ballista_fetch_token(&self) -> Arc<FetchToken>;
+    /// sets the token of the job of a task
+    fn with_ballista_fetch_token(self, token: FetchToken) -> Self;
 }
 
+/// Settings which executors apply to every task of a session: the settings changing
+/// the results of functions, e.g. of `date_trunc` on timestamps with a time zone, and
+/// the codec the tasks fetch shuffle partitions with. DataFusion has no locale setting,
+/// the formatting of values doesn't depend on the executor
+const TASK_SESSION_KEYS: &[&str] = &[
+    "datafusion.execution.time_zone",
+    BALLISTA_SHUFFLE_TRANSFER_COMPRESSION,
+];
+
 impl SessionStateExt for SessionState {
     fn new_ballista_state(
         scheduler_url: String,
@@ -135,13 +475,14 @@ impl SessionStateExt for SessionState {
 
         let runtime_config = RuntimeConfig::default();
         let runtime_env = RuntimeEnv::new(runtime_config)?;
-        let session_state = SessionStateBuilder::new()
+        let builder = SessionStateBuilder::new()
             .with_default_features()
             .with_config(session_config)
             .with_runtime_env(Arc::new(runtime_env))
             .with_query_planner(Arc::new(planner))
-            .with_session_id(session_id)
-            .build();
+            .with_session_id(session_id);
+        let session_state =
+            with_ballista_table_functions(with_ballista_table_factories(builder)).build();
 
         Ok(session_state)
     }
@@ -185,7 +526,7 @@ impl SessionStateExt for SessionState {
             }
         };
 
-        Ok(builder.build())
+        Ok(with_ballista_table_functions(with_ballista_table_factories(builder)).build())
     }
 }
 
@@ -211,15 +552,56 @@ impl SessionConfigExt for SessionConfig {
         self.with_extension(Arc::new(extension))
     }
 
+    fn with_ballista_user_logical_extension_codec(
+        self,
+        codec: Arc<dyn LogicalExtensionCodec>,
+    ) -> SessionConfig {
+        let mut extension = self
+            .get_extension::<BallistaConfigExtensionUserCodecs>()
+            .map(|c| c.as_ref().clone())
+            .unwrap_or_default();
+        extension.logical.push(codec);
+        self.with_extension(Arc::new(extension))
+    }
+    fn with_ballista_user_physical_extension_codec(
+        self,
+        codec: Arc<dyn PhysicalExtensionCodec>,
+    ) -> SessionConfig {
+        let mut extension = self
+            .get_extension::<BallistaConfigExtensionUserCodecs>()
+            .map(|c| c.as_ref().clone())
+            .unwrap_or_default();
+        extension.physical.push(codec);
+        self.with_extension(Arc::new(extension))
+    }
+
     fn ballista_logical_extension_codec(&self) -> Arc<dyn LogicalExtensionCodec> {
         self.get_extension::<BallistaConfigExtensionLogicalCodec>()
             .map(|c| c.codec())
-            .unwrap_or_else(|| Arc::new(BallistaLogicalExtensionCodec::default()))
+            .unwrap_or_else(|| {
+                let user_codecs = self
+                    .get_extension::<BallistaConfigExtensionUserCodecs>()
+                    .map(|c| c.logical.clone())
+                    .unwrap_or_default();
+                Arc::new(user_codecs.into_iter().fold(
+                    BallistaLogicalExtensionCodec::default(),
+                    |codec, user_codec| codec.with_extension_codec(user_codec),
+                ))
+            })
     }
     fn ballista_physical_extension_codec(&self) -> Arc<dyn PhysicalExtensionCodec> {
         self.get_extension::<BallistaConfigExtensionPhysicalCodec>()
             .map(|c| c.codec())
-            .unwrap_or_else(|| Arc::new(BallistaPhysicalExtensionCodec::default()))
+            .unwrap_or_else(|| {
+                let user_codecs = self
+                    .get_extension::<BallistaConfigExtensionUserCodecs>()
+                    .map(|c| c.physical.clone())
+                    .unwrap_or_default();
+                Arc::new(user_codecs.into_iter().fold(
+                    BallistaPhysicalExtensionCodec::default(),
+                    |codec, user_codec| codec.with_extension_codec(user_codec),
+                ))
+            })
     }
 
     fn with_ballista_query_planner(
@@ -281,126 +663,808 @@ impl SessionConfigExt for SessionConfig {
                 .set_usize(BALLISTA_STANDALONE_PARALLELISM, parallelism)
         }
     }
-}
 
-impl SessionConfigHelperExt for SessionConfig {
-    fn to_key_value_pairs(&self) -> Vec<KeyValuePair> {
+    fn ballista_shuffle_sort_based_partition_threshold(&self) -> usize {
         self.options()
-            .entries()
-            .iter()
-            .filter(|v| v.value.is_some())
-            .map(
-                // TODO MM make `value` optional value
-                |datafusion::config::ConfigEntry { key, value, .. }| {
-                    log::trace!(
-                        "sending configuration key: `{}`, value`{:?}`",
-                        key,
-                        value
-                    );
-                    KeyValuePair {
-                        key: key.to_owned(),
-                        value: value.clone().unwrap(),
-                    }
-                },
-            )
-            .collect()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.shuffle_sort_based_partition_threshold())
+            .unwrap_or_else(|| {
+                BallistaConfig::default().shuffle_sort_based_partition_threshold()
+            })
     }
 
-    fn update_from_key_value_pair(self, key_value_pairs: &[KeyValuePair]) -> Self {
-        let mut s = self;
-        for KeyValuePair { key, value } in key_value_pairs {
-            log::trace!(
-                "setting up configuration key: `{}`, value: `{}`",
-                key,
-                value
-            );
-            if let Err(e) = s.options_mut().set(key, value) {
-                log::warn!(
-                    "could not set configuration key: `{}`, value: `{}`, reason: {}",
-                    key,
-                    value,
-                    e.to_string()
-                )
-            }
+    fn with_ballista_shuffle_sort_based_partition_threshold(
+        self,
+        threshold: usize,
+    ) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_SHUFFLE_SORT_BASED_PARTITION_THRESHOLD, threshold)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_SHUFFLE_SORT_BASED_PARTITION_THRESHOLD, threshold)
         }
-        s
     }
 
-    fn update_from_key_value_pair_mut(&mut self, key_value_pairs: &[KeyValuePair]) {
-        for KeyValuePair { key, value } in key_value_pairs {
-            log::trace!(
-                "setting up configuration key : `{}`, value: `{}`",
-                key,
-                value
-            );
-            if let Err(e) = self.options_mut().set(key, value) {
-                log::warn!(
-                    "could not set configuration key: `{}`, value: `{}`, reason: {}",
-                    key,
-                    value,
-                    e.to_string()
-                )
-            }
+    fn ballista_inline_result_max_bytes(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.inline_result_max_bytes())
+            .unwrap_or_else(|| BallistaConfig::default().inline_result_max_bytes())
+    }
+
+    fn with_ballista_inline_result_max_bytes(self, max_bytes: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_INLINE_RESULT_MAX_BYTES, max_bytes)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_INLINE_RESULT_MAX_BYTES, max_bytes)
         }
     }
-}
 
-/// Wrapper for [SessionConfig] extension
-/// holding [LogicalExtensionCodec] if overridden
-struct BallistaConfigExtensionLogicalCodec {
-    codec: Arc<dyn LogicalExtensionCodec>,
-}
+    fn ballista_verify_stage(&self, stage_id: usize) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.verify_stage(stage_id))
+            .unwrap_or_else(|| BallistaConfig::default().verify_stage(stage_id))
+    }
 
-impl BallistaConfigExtensionLogicalCodec {
-    fn new(codec: Arc<dyn LogicalExtensionCodec>) -> Self {
-        Self { codec }
+    fn with_ballista_verify_stages(self, stages: &str) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_VERIFY_STAGES, stages)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_VERIFY_STAGES, stages)
+        }
     }
-    fn codec(&self) -> Arc<dyn LogicalExtensionCodec> {
-        self.codec.clone()
+
+    fn ballista_checksum_results(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.checksum_results())
+            .unwrap_or_else(|| BallistaConfig::default().checksum_results())
     }
-}
 
-/// Wrapper for [SessionConfig] extension
-/// holding [PhysicalExtensionCodec] if overridden
-struct BallistaConfigExtensionPhysicalCodec {
-    codec: Arc<dyn PhysicalExtensionCodec>,
-}
+    fn with_ballista_checksum_results(self, checksum: bool) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(BALLISTA_CHECKSUM_RESULTS, checksum)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(BALLISTA_CHECKSUM_RESULTS, checksum)
+        }
+    }
 
-impl BallistaConfigExtensionPhysicalCodec {
-    fn new(codec: Arc<dyn PhysicalExtensionCodec>) -> Self {
-        Self { codec }
+    fn ballista_deterministic(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.deterministic())
+            .unwrap_or_else(|| BallistaConfig::default().deterministic())
     }
-    fn codec(&self) -> Arc<dyn PhysicalExtensionCodec> {
-        self.codec.clone()
+
+    fn with_ballista_deterministic(self, deterministic: bool) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(BALLISTA_DETERMINISTIC, deterministic)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(BALLISTA_DETERMINISTIC, deterministic)
+        }
     }
-}
 
-/// Wrapper for [SessionConfig] extension
-/// holding overridden [QueryPlanner]
-struct BallistaQueryPlannerExtension {
-    planner: Arc<dyn QueryPlanner + Send + Sync + 'static>,
-}
+    fn ballista_max_result_rows(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.max_result_rows())
+            .unwrap_or_else(|| BallistaConfig::default().max_result_rows())
+    }
 
-impl BallistaQueryPlannerExtension {
-    fn new(planner: Arc<dyn QueryPlanner + Send + Sync + 'static>) -> Self {
-        Self { planner }
+    fn with_ballista_max_result_rows(self, max_rows: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_MAX_RESULT_ROWS, max_rows)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_MAX_RESULT_ROWS, max_rows)
+        }
     }
-    fn planner(&self) -> Arc<dyn QueryPlanner + Send + Sync + 'static> {
-        self.planner.clone()
+
+    fn ballista_best_effort(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.best_effort())
+            .unwrap_or_else(|| BallistaConfig::default().best_effort())
     }
-}
 
-#[cfg(test)]
-mod test {
-    use datafusion::{
-        execution::{SessionState, SessionStateBuilder},
-        prelude::SessionConfig,
-    };
+    fn with_ballista_best_effort(self, best_effort: bool) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(BALLISTA_BEST_EFFORT, best_effort)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(BALLISTA_BEST_EFFORT, best_effort)
+        }
+    }
 
-    use crate::{
-        config::BALLISTA_JOB_NAME,
-        extension::{SessionConfigExt, SessionConfigHelperExt, SessionStateExt},
-    };
+    fn ballista_job_priority(&self) -> u32 {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.job_priority())
+            .unwrap_or_else(|| BallistaConfig::default().job_priority())
+    }
+
+    fn with_ballista_job_priority(self, priority: u32) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_JOB_PRIORITY, priority as usize)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_JOB_PRIORITY, priority as usize)
+        }
+    }
+
+    fn ballista_job_webhook_url(&self) -> Option<String> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.job_webhook_url())
+            .unwrap_or_else(|| BallistaConfig::default().job_webhook_url())
+    }
+
+    fn with_ballista_job_webhook_url(self, url: &str) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_JOB_WEBHOOK_URL, url)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_JOB_WEBHOOK_URL, url)
+        }
+    }
+
+    fn ballista_job_max_tasks_per_executor(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.job_max_tasks_per_executor())
+            .unwrap_or_else(|| BallistaConfig::default().job_max_tasks_per_executor())
+    }
+
+    fn with_ballista_job_max_tasks_per_executor(self, max_tasks: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_JOB_MAX_TASKS_PER_EXECUTOR, max_tasks)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_JOB_MAX_TASKS_PER_EXECUTOR, max_tasks)
+        }
+    }
+
+    fn ballista_job_staging_url(&self) -> Option<String> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.job_staging_url())
+            .unwrap_or_else(|| BallistaConfig::default().job_staging_url())
+    }
+
+    fn with_ballista_job_staging_url(self, url: &str) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_JOB_STAGING_URL, url)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_JOB_STAGING_URL, url)
+        }
+    }
+
+    fn ballista_job_resource_profile(&self) -> Option<String> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.job_resource_profile())
+            .unwrap_or_else(|| BallistaConfig::default().job_resource_profile())
+    }
+
+    fn with_ballista_job_resource_profile(self, profile: &str) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_JOB_RESOURCE_PROFILE, profile)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_JOB_RESOURCE_PROFILE, profile)
+        }
+    }
+
+    fn ballista_job_slot_reservation(&self) -> Option<String> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.job_slot_reservation())
+            .unwrap_or_else(|| BallistaConfig::default().job_slot_reservation())
+    }
+
+    fn with_ballista_job_slot_reservation(self, reservation_id: &str) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_JOB_SLOT_RESERVATION, reservation_id)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_JOB_SLOT_RESERVATION, reservation_id)
+        }
+    }
+
+    fn ballista_job_result_ttl_seconds(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.job_result_ttl_seconds())
+            .unwrap_or_else(|| BallistaConfig::default().job_result_ttl_seconds())
+    }
+
+    fn with_ballista_job_result_ttl_seconds(self, ttl_seconds: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_JOB_RESULT_TTL, ttl_seconds)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_JOB_RESULT_TTL, ttl_seconds)
+        }
+    }
+
+    fn ballista_stage_timeout_seconds(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.stage_timeout_seconds())
+            .unwrap_or_else(|| BallistaConfig::default().stage_timeout_seconds())
+    }
+
+    fn with_ballista_stage_timeout_seconds(self, timeout_seconds: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_STAGE_TIMEOUT, timeout_seconds)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_STAGE_TIMEOUT, timeout_seconds)
+        }
+    }
+
+    fn ballista_merge_schemas(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.merge_schemas())
+            .unwrap_or_else(|| BallistaConfig::default().merge_schemas())
+    }
+
+    fn with_ballista_merge_schemas(self, merge_schemas: bool) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(BALLISTA_SCAN_MERGE_SCHEMAS, merge_schemas)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(BALLISTA_SCAN_MERGE_SCHEMAS, merge_schemas)
+        }
+    }
+
+    fn ballista_splittable_scans(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.splittable_scans())
+            .unwrap_or_else(|| BallistaConfig::default().splittable_scans())
+    }
+
+    fn with_ballista_splittable_scans(self, splittable: bool) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(BALLISTA_SCAN_SPLITTABLE, splittable)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(BALLISTA_SCAN_SPLITTABLE, splittable)
+        }
+    }
+
+    fn ballista_partition_column_type(
+        &self,
+        column: &str,
+    ) -> ParseResult<Option<DataType>> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.partition_column_type(column))
+            .unwrap_or_else(|| BallistaConfig::default().partition_column_type(column))
+    }
+
+    fn with_ballista_partition_column_types(self, types: &str) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_SCAN_PARTITION_COLUMN_TYPES, types)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_SCAN_PARTITION_COLUMN_TYPES, types)
+        }
+    }
+
+    fn ballista_prefer_sort_merge_join(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.prefer_sort_merge_join())
+            .unwrap_or_else(|| BallistaConfig::default().prefer_sort_merge_join())
+    }
+
+    fn with_ballista_prefer_sort_merge_join(self, prefer: bool) -> Self {
+        let mut config = if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(BALLISTA_JOIN_PREFER_SORT_MERGE, prefer)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(BALLISTA_JOIN_PREFER_SORT_MERGE, prefer)
+        };
+        config.options_mut().optimizer.prefer_hash_join = !prefer;
+        config
+    }
+
+    fn ballista_pre_aggregate_grouping_sets(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.pre_aggregate_grouping_sets())
+            .unwrap_or_else(|| BallistaConfig::default().pre_aggregate_grouping_sets())
+    }
+
+    fn with_ballista_pre_aggregate_grouping_sets(self, pre_aggregate: bool) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(
+                BALLISTA_OPTIMIZER_PRE_AGGREGATE_GROUPING_SETS,
+                pre_aggregate,
+            )
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(
+                    BALLISTA_OPTIMIZER_PRE_AGGREGATE_GROUPING_SETS,
+                    pre_aggregate,
+                )
+        }
+    }
+
+    fn ballista_broadcast_join_threshold(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.broadcast_join_threshold())
+            .unwrap_or_else(|| BallistaConfig::default().broadcast_join_threshold())
+    }
+
+    fn with_ballista_broadcast_join_threshold(self, threshold_bytes: usize) -> Self {
+        let mut config = if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_JOIN_BROADCAST_THRESHOLD_BYTES, threshold_bytes)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_JOIN_BROADCAST_THRESHOLD_BYTES, threshold_bytes)
+        };
+        set_broadcast_join_threshold(config.options_mut(), threshold_bytes);
+        config
+    }
+
+    fn ballista_compression_ratio_scale(&self) -> ParseResult<Option<f64>> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.compression_ratio_scale())
+            .unwrap_or_else(|| BallistaConfig::default().compression_ratio_scale())
+    }
+
+    fn with_ballista_compression_ratio_scale(self, scale: f64) -> Self {
+        let scale = scale.to_string();
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_OPTIMIZER_COMPRESSION_RATIO_SCALE, &scale)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_OPTIMIZER_COMPRESSION_RATIO_SCALE, &scale)
+        }
+    }
+
+    fn ballista_network_bytes_per_second(&self) -> u64 {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.network_bytes_per_second())
+            .unwrap_or_else(|| BallistaConfig::default().network_bytes_per_second())
+    }
+
+    fn with_ballista_network_bytes_per_second(self, bandwidth: u64) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_u64(BALLISTA_OPTIMIZER_NETWORK_BYTES_PER_SECOND, bandwidth)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_u64(BALLISTA_OPTIMIZER_NETWORK_BYTES_PER_SECOND, bandwidth)
+        }
+    }
+
+    fn ballista_historical_statistics(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.historical_statistics())
+            .unwrap_or_else(|| BallistaConfig::default().historical_statistics())
+    }
+
+    fn with_ballista_historical_statistics(self, enabled: bool) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(BALLISTA_OPTIMIZER_HISTORICAL_STATISTICS, enabled)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(BALLISTA_OPTIMIZER_HISTORICAL_STATISTICS, enabled)
+        }
+    }
+
+    fn ballista_table_statistics(&self) -> ParseResult<Vec<(String, TableSize)>> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.table_statistics())
+            .unwrap_or_else(|| BallistaConfig::default().table_statistics())
+    }
+
+    fn ballista_shuffle_compression(&self) -> ParseResult<ShuffleCompression> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.shuffle_compression())
+            .unwrap_or_else(|| BallistaConfig::default().shuffle_compression())
+    }
+
+    fn with_ballista_shuffle_compression(self, compression: ShuffleCompression) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_SHUFFLE_COMPRESSION, &compression.to_string())
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_SHUFFLE_COMPRESSION, &compression.to_string())
+        }
+    }
+
+    fn ballista_shuffle_transfer_compression(&self) -> Option<String> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.shuffle_transfer_compression())
+            .unwrap_or_else(|| BallistaConfig::default().shuffle_transfer_compression())
+    }
+
+    fn with_ballista_shuffle_transfer_compression(self, codec: &str) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_SHUFFLE_TRANSFER_COMPRESSION, codec)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_SHUFFLE_TRANSFER_COMPRESSION, codec)
+        }
+    }
+
+    fn ballista_compression_codecs(&self) -> Arc<CompressionCodecRegistry> {
+        self.get_extension::<CompressionCodecRegistry>()
+            .unwrap_or_default()
+    }
+
+    fn with_ballista_compression_codec(self, codec: Arc<dyn CompressionCodec>) -> Self {
+        let codecs = self.ballista_compression_codecs().as_ref().clone();
+        self.with_extension(Arc::new(codecs.with_codec(codec)))
+    }
+
+    fn ballista_explain_format(&self) -> ParseResult<ExplainFormat> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.explain_format())
+            .unwrap_or_else(|| BallistaConfig::default().explain_format())
+    }
+
+    fn with_ballista_explain_format(self, format: ExplainFormat) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_EXPLAIN_FORMAT, &format.to_string())
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_EXPLAIN_FORMAT, &format.to_string())
+        }
+    }
+
+    fn ballista_shuffle_object_store_url(&self) -> Option<String> {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.shuffle_object_store_url())
+            .unwrap_or_else(|| BallistaConfig::default().shuffle_object_store_url())
+    }
+
+    fn with_ballista_shuffle_object_store_url(self, url: &str) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_str(BALLISTA_SHUFFLE_OBJECT_STORE_URL, url)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_str(BALLISTA_SHUFFLE_OBJECT_STORE_URL, url)
+        }
+    }
+
+    fn ballista_coalesce_partitions(&self) -> bool {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.coalesce_partitions())
+            .unwrap_or_else(|| BallistaConfig::default().coalesce_partitions())
+    }
+
+    fn with_ballista_coalesce_partitions(self, coalesce: bool) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_bool(BALLISTA_SHUFFLE_COALESCE_PARTITIONS, coalesce)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_bool(BALLISTA_SHUFFLE_COALESCE_PARTITIONS, coalesce)
+        }
+    }
+
+    fn ballista_target_partition_bytes(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.target_partition_bytes())
+            .unwrap_or_else(|| BallistaConfig::default().target_partition_bytes())
+    }
+
+    fn with_ballista_target_partition_bytes(self, target_bytes: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_SHUFFLE_TARGET_PARTITION_BYTES, target_bytes)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_SHUFFLE_TARGET_PARTITION_BYTES, target_bytes)
+        }
+    }
+
+    fn ballista_shuffle_max_concurrent_fetches(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.shuffle_max_concurrent_fetches())
+            .unwrap_or_else(|| BallistaConfig::default().shuffle_max_concurrent_fetches())
+    }
+
+    fn with_ballista_shuffle_max_concurrent_fetches(self, max_fetches: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_SHUFFLE_MAX_CONCURRENT_FETCHES, max_fetches)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_SHUFFLE_MAX_CONCURRENT_FETCHES, max_fetches)
+        }
+    }
+
+    fn ballista_shuffle_fetch_retries(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.shuffle_fetch_retries())
+            .unwrap_or_else(|| BallistaConfig::default().shuffle_fetch_retries())
+    }
+
+    fn with_ballista_shuffle_fetch_retries(self, retries: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_SHUFFLE_FETCH_RETRIES, retries)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_SHUFFLE_FETCH_RETRIES, retries)
+        }
+    }
+
+    fn ballista_shuffle_fetch_retry_backoff_ms(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.shuffle_fetch_retry_backoff_ms())
+            .unwrap_or_else(|| BallistaConfig::default().shuffle_fetch_retry_backoff_ms())
+    }
+
+    fn with_ballista_shuffle_fetch_retry_backoff_ms(self, backoff_ms: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_SHUFFLE_FETCH_RETRY_BACKOFF_MS, backoff_ms)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_SHUFFLE_FETCH_RETRY_BACKOFF_MS, backoff_ms)
+        }
+    }
+
+    fn ballista_shuffle_max_buffered_batches(&self) -> usize {
+        self.options()
+            .extensions
+            .get::<BallistaConfig>()
+            .map(|c| c.shuffle_max_buffered_batches())
+            .unwrap_or_else(|| BallistaConfig::default().shuffle_max_buffered_batches())
+    }
+
+    fn with_ballista_shuffle_max_buffered_batches(self, max_batches: usize) -> Self {
+        if self.options().extensions.get::<BallistaConfig>().is_some() {
+            self.set_usize(BALLISTA_SHUFFLE_MAX_BUFFERED_BATCHES, max_batches)
+        } else {
+            self.with_option_extension(BallistaConfig::default())
+                .set_usize(BALLISTA_SHUFFLE_MAX_BUFFERED_BATCHES, max_batches)
+        }
+    }
+
+    fn ballista_grpc_security(&self) -> Arc<GrpcSecurityConfig> {
+        self.get_extension::<GrpcSecurityConfig>()
+            .unwrap_or_default()
+    }
+
+    fn with_ballista_grpc_security(self, security: GrpcSecurityConfig) -> Self {
+        self.with_extension(Arc::new(security))
+    }
+}
+
+impl SessionConfigHelperExt for SessionConfig {
+    fn to_key_value_pairs(&self) -> Vec<KeyValuePair> {
+        self.options()
+            .entries()
+            .iter()
+            .filter(|v| v.value.is_some())
+            .map(
+                // TODO MM make `value` optional value
+                |datafusion::config::ConfigEntry { key, value, .. }| {
+                    log::trace!(
+                        "sending configuration key: `{}`, value`{:?}`",
+                        key,
+                        value.as_deref().map(|value| redact_value(key, value))
+                    );
+                    KeyValuePair {
+                        key: key.to_owned(),
+                        value: value.clone().unwrap(),
+                    }
+                },
+            )
+            .collect()
+    }
+
+    fn update_from_key_value_pair(self, key_value_pairs: &[KeyValuePair]) -> Self {
+        let mut s = self;
+        for KeyValuePair { key, value } in key_value_pairs {
+            log::trace!(
+                "setting up configuration key: `{}`, value: `{}`",
+                key,
+                redact_value(key, value)
+            );
+            if let Err(e) = s.options_mut().set(key, value) {
+                log::warn!(
+                    "could not set configuration key: `{}`, value: `{}`, reason: {}",
+                    key,
+                    redact_value(key, value),
+                    e.to_string()
+                )
+            }
+        }
+        s
+    }
+
+    fn update_from_key_value_pair_mut(&mut self, key_value_pairs: &[KeyValuePair]) {
+        for KeyValuePair { key, value } in key_value_pairs {
+            log::trace!(
+                "setting up configuration key : `{}`, value: `{}`",
+                key,
+                redact_value(key, value)
+            );
+            if let Err(e) = self.options_mut().set(key, value) {
+                log::warn!(
+                    "could not set configuration key: `{}`, value: `{}`, reason: {}",
+                    key,
+                    redact_value(key, value),
+                    e.to_string()
+                )
+            }
+        }
+    }
+
+    fn to_task_key_value_pairs(&self) -> Vec<KeyValuePair> {
+        self.to_key_value_pairs()
+            .into_iter()
+            .filter(|pair| TASK_SESSION_KEYS.contains(&pair.key.as_str()))
+            .collect()
+    }
+
+    fn check_task_key_value_pairs(&self, key_value_pairs: &[KeyValuePair]) -> Result<()> {
+        let entries = self.options().entries();
+        for KeyValuePair { key, value } in key_value_pairs {
+            if !TASK_SESSION_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            let actual = entries
+                .iter()
+                .find(|entry| &entry.key == key)
+                .and_then(|entry| entry.value.as_ref());
+            if actual != Some(value) {
+                return Err(BallistaError::General(format!(
+                    "Task expects configuration key `{key}` to be `{}` but the executor has `{}`",
+                    redact_value(key, value),
+                    actual.map(|actual| redact_value(key, actual)).unwrap_or("unset")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn ballista_fetch_token(&self) -> Arc<FetchToken> {
+        self.get_extension::<FetchToken>().unwrap_or_default()
+    }
+
+    fn with_ballista_fetch_token(self, token: FetchToken) -> Self {
+        self.with_extension(Arc::new(token))
+    }
+}
+
+/// Wrapper for [SessionConfig] extension
+/// holding [LogicalExtensionCodec] if overridden
+struct BallistaConfigExtensionLogicalCodec {
+    codec: Arc<dyn LogicalExtensionCodec>,
+}
+
+impl BallistaConfigExtensionLogicalCodec {
+    fn new(codec: Arc<dyn LogicalExtensionCodec>) -> Self {
+        Self { codec }
+    }
+    fn codec(&self) -> Arc<dyn LogicalExtensionCodec> {
+        self.codec.clone()
+    }
+}
+
+/// Wrapper for [SessionConfig] extension
+/// holding [PhysicalExtensionCodec] if overridden
+struct BallistaConfigExtensionPhysicalCodec {
+    codec: Arc<dyn PhysicalExtensionCodec>,
+}
+
+impl BallistaConfigExtensionPhysicalCodec {
+    fn new(codec: Arc<dyn PhysicalExtensionCodec>) -> Self {
+        Self { codec }
+    }
+    fn codec(&self) -> Arc<dyn PhysicalExtensionCodec> {
+        self.codec.clone()
+    }
+}
+
+/// Wrapper for [SessionConfig] extension
+/// holding the user codecs registered on ballista's codecs
+#[derive(Clone, Default)]
+struct BallistaConfigExtensionUserCodecs {
+    logical: Vec<Arc<dyn LogicalExtensionCodec>>,
+    physical: Vec<Arc<dyn PhysicalExtensionCodec>>,
+}
+
+/// Wrapper for [SessionConfig] extension
+/// holding overridden [QueryPlanner]
+struct BallistaQueryPlannerExtension {
+    planner: Arc<dyn QueryPlanner + Send + Sync + 'static>,
+}
+
+impl BallistaQueryPlannerExtension {
+    fn new(planner: Arc<dyn QueryPlanner + Send + Sync + 'static>) -> Self {
+        Self { planner }
+    }
+    fn planner(&self) -> Arc<dyn QueryPlanner + Send + Sync + 'static> {
+        self.planner.clone()
+    }
+}
+
+/// Make DataFusion collect the left side of hash joins, which the distributed planner
+/// then broadcasts, when its estimated size is below `threshold_bytes`. Without a
+/// byte size estimate, DataFusion falls back on the number of rows, which is only
+/// disabled along with broadcast joins.
+pub(crate) fn set_broadcast_join_threshold(
+    options: &mut ConfigOptions,
+    threshold_bytes: usize,
+) {
+    options.optimizer.hash_join_single_partition_threshold = threshold_bytes;
+    if threshold_bytes == 0 {
+        options.optimizer.hash_join_single_partition_threshold_rows = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::{
+        execution::{SessionState, SessionStateBuilder},
+        prelude::SessionConfig,
+    };
+
+    use crate::{
+        config::BALLISTA_JOB_NAME,
+        extension::{SessionConfigExt, SessionConfigHelperExt, SessionStateExt},
+    };
 
     // Ballista disables round robin repatriations
     #[tokio::test]
@@ -435,4 +1499,31 @@ mod test {
             .iter()
             .any(|p| p.key == "datafusion.catalog.information_schema"))
     }
+
+    #[test]
+    fn should_propagate_time_zone_to_tasks() {
+        let mut config = SessionConfig::new_with_ballista();
+        config.options_mut().execution.time_zone = Some("+05:00".to_owned());
+        let pairs = config.to_task_key_value_pairs();
+        // along with the codec of the shuffle transfers
+        assert_eq!(2, pairs.len());
+        let time_zone = pairs
+            .iter()
+            .find(|pair| pair.key == "datafusion.execution.time_zone")
+            .unwrap();
+        assert_eq!("+05:00", time_zone.value);
+
+        let executor_config = SessionConfig::new_with_ballista();
+        assert!(executor_config.check_task_key_value_pairs(&pairs).is_err());
+
+        let task_config = executor_config.update_from_key_value_pair(&pairs);
+        assert_eq!(
+            Some("+05:00"),
+            task_config.options().execution.time_zone.as_deref()
+        );
+        task_config.check_task_key_value_pairs(&pairs).unwrap();
+        task_config
+            .check_task_key_value_pairs(&config.to_key_value_pairs())
+            .unwrap();
+    }
 }
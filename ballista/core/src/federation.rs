@@ -0,0 +1,314 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Federation of tables living in other Ballista clusters or Flight services.
+//!
+//! A [RemoteTable] is registered like any other table. When a query scans it,
+//! the scan (including pushed down projections, filters and limits) is cut out of
+//! the plan and submitted to the scheduler of the cluster holding the table,
+//! and its results are read back by a [RemoteQueryExec].
+//!
+//! A [FlightTableProvider] exposes a flight of an Arrow Flight or Flight SQL
+//! endpoint, e.g. the result of a query against Dremio or another DataFusion
+//! service. Its endpoints are read in parallel by a [FlightScanExec].
+
+use crate::config::redact_settings;
+use crate::execution_plans::{
+    get_flight_info, FlightPartition, FlightScanExec, RemoteQueryExec,
+};
+use crate::extension::SessionConfigExt;
+use arrow_flight::sql::{CommandStatementQuery, ProstMessageExt};
+use arrow_flight::FlightDescriptor;
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::catalog::{Session, TableProvider};
+use datafusion::common::{DataFusionError, Result};
+use datafusion::datasource::provider_as_source;
+use datafusion::logical_expr::expr_rewriter::unnormalize_cols;
+use datafusion::logical_expr::utils::conjunction;
+use datafusion::logical_expr::{
+    col, Expr, LogicalPlan, LogicalPlanBuilder, TableProviderFilterPushDown, TableType,
+};
+use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion_proto::logical_plan::{AsLogicalPlan, LogicalExtensionCodec};
+use datafusion_proto::protobuf::LogicalPlanNode;
+use prost::Message;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Name of the table scanned by subplans submitted to a remote cluster
+const REMOTE_SCAN_NAME: &str = "remote_table";
+
+/// A table living in another Ballista cluster.
+///
+/// `inner` describes the table as the remote cluster sees it, e.g. a
+/// [ListingTable](datafusion::datasource::listing::ListingTable) over storage
+/// local to that cluster. It is never scanned by this cluster: it is serialized
+/// into the subplan submitted to `scheduler_url`, so it must be encodable by the
+/// session's [LogicalExtensionCodec].
+pub struct RemoteTable {
+    scheduler_url: String,
+    inner: Arc<dyn TableProvider>,
+}
+
+impl RemoteTable {
+    pub fn new(scheduler_url: impl Into<String>, inner: Arc<dyn TableProvider>) -> Self {
+        Self {
+            scheduler_url: scheduler_url.into(),
+            inner,
+        }
+    }
+
+    /// Scheduler of the cluster holding the table
+    pub fn scheduler_url(&self) -> &str {
+        &self.scheduler_url
+    }
+
+    /// The table as seen by the remote cluster
+    pub fn inner(&self) -> Arc<dyn TableProvider> {
+        self.inner.clone()
+    }
+
+    /// Builds the subplan executed by the remote cluster
+    fn remote_plan(
+        &self,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<LogicalPlan> {
+        let mut builder = LogicalPlanBuilder::scan(
+            REMOTE_SCAN_NAME,
+            provider_as_source(self.inner.clone()),
+            None,
+        )?;
+
+        // filters are qualified with the name of the table in the local plan
+        if let Some(predicate) = conjunction(unnormalize_cols(filters.to_vec())) {
+            builder = builder.filter(predicate)?;
+        }
+
+        if let Some(projection) = projection {
+            let schema = self.inner.schema();
+            builder = builder
+                .project(projection.iter().map(|i| col(schema.field(*i).name())))?;
+        }
+
+        if let Some(limit) = limit {
+            builder = builder.limit(0, Some(limit))?;
+        }
+
+        builder.build()
+    }
+}
+
+#[async_trait]
+impl TableProvider for RemoteTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let plan = self.remote_plan(projection, filters, limit)?;
+        let schema: SchemaRef = plan.schema().as_ref().clone().into();
+        let codec = state.config().ballista_logical_extension_codec();
+
+        Ok(Arc::new(RemoteQueryExec::new(
+            self.scheduler_url.clone(),
+            encode_logical_plan(&plan, codec.as_ref())?,
+            schema,
+        )))
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        // filters are applied by the subplan
+        Ok(vec![TableProviderFilterPushDown::Exact; filters.len()])
+    }
+}
+
+pub(crate) fn encode_logical_plan(
+    plan: &LogicalPlan,
+    codec: &dyn LogicalExtensionCodec,
+) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    LogicalPlanNode::try_from_logical_plan(plan, codec)?
+        .try_encode(&mut buf)
+        .map_err(|e| {
+            DataFusionError::Internal(format!("failed to encode logical plan: {e:?}"))
+        })?;
+    Ok(buf)
+}
+
+/// A flight of an Arrow Flight or Flight SQL endpoint, exposed as a table.
+///
+/// The flight info is requested when a scan is planned, and each of its endpoints
+/// is read by a separate partition, so the executors must be able to reach the
+/// locations of the endpoints.
+#[derive(Clone)]
+pub struct FlightTableProvider {
+    endpoint: String,
+    descriptor: FlightDescriptor,
+    /// gRPC headers sent with each request, e.g. for authentication
+    headers: HashMap<String, String>,
+    schema: SchemaRef,
+}
+
+impl FlightTableProvider {
+    /// Creates a provider for the flight identified by `descriptor`,
+    /// retrieving its schema from `endpoint`
+    pub async fn try_new(
+        endpoint: impl Into<String>,
+        descriptor: FlightDescriptor,
+        headers: HashMap<String, String>,
+    ) -> Result<Self> {
+        let endpoint = endpoint.into();
+        let info = get_flight_info(&endpoint, descriptor.clone(), &headers).await?;
+        let schema = Arc::new(info.try_decode_schema()?);
+        Ok(Self::new(endpoint, descriptor, headers, schema))
+    }
+
+    /// Creates a provider for the result of a SQL query against a Flight SQL endpoint
+    pub async fn try_new_sql(
+        endpoint: impl Into<String>,
+        query: impl Into<String>,
+        headers: HashMap<String, String>,
+    ) -> Result<Self> {
+        let command = CommandStatementQuery {
+            query: query.into(),
+            transaction_id: None,
+        };
+        let descriptor = FlightDescriptor::new_cmd(command.as_any().encode_to_vec());
+        Self::try_new(endpoint, descriptor, headers).await
+    }
+
+    /// Creates a provider for a flight with a known schema
+    pub fn new(
+        endpoint: impl Into<String>,
+        descriptor: FlightDescriptor,
+        headers: HashMap<String, String>,
+        schema: SchemaRef,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            descriptor,
+            headers,
+            schema,
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn descriptor(&self) -> &FlightDescriptor {
+        &self.descriptor
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+}
+
+impl std::fmt::Debug for FlightTableProvider {
+    // Keep the credentials of the headers out of logs
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headers: BTreeMap<String, String> = redact_settings(&self.headers);
+        f.debug_struct("FlightTableProvider")
+            .field("endpoint", &self.endpoint)
+            .field("descriptor", &self.descriptor)
+            .field("headers", &headers)
+            .field("schema", &self.schema)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl TableProvider for FlightTableProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let projection = projection
+            .cloned()
+            .unwrap_or_else(|| (0..self.schema.fields().len()).collect());
+
+        let info =
+            get_flight_info(&self.endpoint, self.descriptor.clone(), &self.headers)
+                .await?;
+        let partitions: Vec<_> = info
+            .endpoint
+            .into_iter()
+            .filter_map(|endpoint| {
+                endpoint.ticket.map(|ticket| FlightPartition {
+                    ticket: ticket.ticket.to_vec(),
+                    locations: endpoint
+                        .location
+                        .into_iter()
+                        .map(|location| location.uri)
+                        .collect(),
+                })
+            })
+            .collect();
+
+        if partitions.is_empty() {
+            return Ok(Arc::new(EmptyExec::new(Arc::new(
+                self.schema.project(&projection)?,
+            ))));
+        }
+
+        Ok(Arc::new(FlightScanExec::try_new(
+            self.endpoint.clone(),
+            self.headers.clone(),
+            partitions,
+            self.schema.clone(),
+            projection,
+        )?))
+    }
+}
@@ -0,0 +1,308 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Pre-aggregation of grouping sets (`ROLLUP`, `CUBE` and `GROUPING SETS`).
+//!
+//! DataFusion aggregates each row of the input of a grouping set aggregation into every
+//! grouping set in the map tasks, so that they shuffle the partial aggregates of every
+//! grouping set. When the aggregate functions can be re-aggregated, the aggregation is
+//! planned as an aggregation of the input by all the grouping columns instead, whose
+//! map tasks shuffle a single partial aggregate per group, followed by the grouping set
+//! aggregation of its much smaller output.
+
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{Column, Result};
+use datafusion::functions_aggregate::sum::sum_udaf;
+use datafusion::logical_expr::expr::AggregateFunction;
+use datafusion::logical_expr::{
+    cast, Aggregate, Expr, GroupingSet, LogicalPlan, Projection,
+};
+use datafusion::physical_plan::aggregates::{AggregateExec, AggregateMode};
+use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+use datafusion::physical_plan::repartition::RepartitionExec;
+use datafusion::physical_plan::{ExecutionPlan, Partitioning};
+use log::debug;
+use std::sync::Arc;
+
+/// Prefix of the columns of the pre-aggregates of the aggregate functions
+const PRE_AGGREGATE_PREFIX: &str = "__grouping_sets_pre_aggregate";
+
+/// Plan the grouping set aggregations of a plan as an aggregation by all their grouping
+/// columns followed by the grouping set aggregation of its output, when their
+/// aggregate functions are `sum`, `min`, `max` or `count`, without `DISTINCT`, filter
+/// or ordering. Other aggregations are left as they are.
+pub fn pre_aggregate_grouping_sets(plan: LogicalPlan) -> Result<LogicalPlan> {
+    plan.transform_up(|plan| match &plan {
+        LogicalPlan::Aggregate(aggregate) => match pre_aggregate(aggregate)? {
+            Some(rewritten) => Ok(Transformed::yes(rewritten)),
+            None => Ok(Transformed::no(plan)),
+        },
+        _ => Ok(Transformed::no(plan)),
+    })
+    .map(|transformed| transformed.data)
+}
+
+fn pre_aggregate(aggregate: &Aggregate) -> Result<Option<LogicalPlan>> {
+    let [Expr::GroupingSet(grouping_set)] = &aggregate.group_expr[..] else {
+        return Ok(None);
+    };
+    let Some(functions) = aggregate
+        .aggr_expr
+        .iter()
+        .map(re_aggregatable)
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Ok(None);
+    };
+
+    // aggregation by all the grouping columns
+    let group_exprs = grouping_set
+        .distinct_expr()
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    let pre_aggr_exprs = functions
+        .iter()
+        .enumerate()
+        .map(|(index, function)| {
+            Expr::AggregateFunction(AggregateFunction::new_udf(
+                function.func.clone(),
+                function.args.clone(),
+                false,
+                None,
+                None,
+                function.null_treatment,
+            ))
+            .alias(format!("{PRE_AGGREGATE_PREFIX}_{index}"))
+        })
+        .collect();
+    let pre_aggregate = LogicalPlan::Aggregate(Aggregate::try_new(
+        aggregate.input.clone(),
+        group_exprs.clone(),
+        pre_aggr_exprs,
+    )?);
+
+    // grouping set aggregation of the output of the pre-aggregation, by its columns
+    let columns = pre_aggregate
+        .schema()
+        .iter()
+        .map(Column::from)
+        .collect::<Vec<_>>();
+    let group_column = |expr: &Expr| {
+        let index = group_exprs
+            .iter()
+            .position(|e| e == expr)
+            .unwrap_or_default();
+        Expr::Column(columns[index].clone())
+    };
+    let group_columns =
+        |exprs: &[Expr]| exprs.iter().map(group_column).collect::<Vec<_>>();
+    let grouping_set = match grouping_set {
+        GroupingSet::Rollup(exprs) => GroupingSet::Rollup(group_columns(exprs)),
+        GroupingSet::Cube(exprs) => GroupingSet::Cube(group_columns(exprs)),
+        GroupingSet::GroupingSets(sets) => {
+            GroupingSet::GroupingSets(sets.iter().map(|set| group_columns(set)).collect())
+        }
+    };
+    let num_group_exprs = group_exprs.len();
+    let num_output_group_exprs = aggregate.group_expr_len()?;
+    let aggr_exprs = functions
+        .iter()
+        .enumerate()
+        .map(|(index, function)| {
+            // the counts of the groups are summed
+            let func = match function.func.name() {
+                "count" => sum_udaf(),
+                _ => function.func.clone(),
+            };
+            Expr::AggregateFunction(AggregateFunction::new_udf(
+                func,
+                vec![Expr::Column(columns[num_group_exprs + index].clone())],
+                false,
+                None,
+                None,
+                None,
+            ))
+            .alias(
+                aggregate
+                    .schema
+                    .field(num_output_group_exprs + index)
+                    .name(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let grouping_set_aggregate = LogicalPlan::Aggregate(Aggregate::try_new(
+        Arc::new(pre_aggregate),
+        vec![Expr::GroupingSet(grouping_set)],
+        aggr_exprs,
+    )?);
+
+    // re-aggregates whose type differs from that of the aggregate functions, e.g. sums
+    // of decimals, are cast back to it
+    let exprs = grouping_set_aggregate
+        .schema()
+        .iter()
+        .zip(aggregate.schema.fields())
+        .map(|((qualifier, field), original)| {
+            let column = Expr::Column(Column::from((qualifier, field)));
+            if field.data_type() == original.data_type() {
+                column
+            } else {
+                cast(column, original.data_type().clone()).alias(original.name())
+            }
+        })
+        .collect();
+    let plan = LogicalPlan::Projection(Projection::try_new(
+        exprs,
+        Arc::new(grouping_set_aggregate),
+    )?);
+
+    if !plan
+        .schema()
+        .equivalent_names_and_types(aggregate.schema.as_ref())
+    {
+        debug!("Grouping set aggregation left as it is, as its pre-aggregation changes its schema");
+        return Ok(None);
+    }
+    Ok(Some(plan))
+}
+
+/// Shuffle the partial aggregates of grouping sets by the grouping columns before their
+/// final aggregation, when it isn't the case already. DataFusion takes the partial
+/// aggregation of grouping sets of an input partitioned by the grouping columns, e.g.
+/// the output of their pre-aggregation, to be partitioned by them too, while the
+/// grouping sets missing a column spread each of their groups across the partitions.
+pub fn shuffle_grouping_sets(
+    plan: Arc<dyn ExecutionPlan>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    plan.transform_up(|plan| {
+        let Some(aggregate) = plan
+            .as_any()
+            .downcast_ref::<AggregateExec>()
+            .filter(|aggregate| *aggregate.mode() == AggregateMode::FinalPartitioned)
+        else {
+            return Ok(Transformed::no(plan));
+        };
+        let partitioning = Partitioning::Hash(
+            aggregate.group_expr().input_exprs(),
+            aggregate
+                .properties()
+                .output_partitioning()
+                .partition_count(),
+        );
+        match shuffle_partial_aggregate(aggregate.input(), partitioning)? {
+            Some(input) => Ok(Transformed::yes(plan.with_new_children(vec![input])?)),
+            None => Ok(Transformed::no(plan)),
+        }
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// Input of a final aggregation with its partial aggregation of grouping sets
+/// shuffled, if it isn't shuffled already
+fn shuffle_partial_aggregate(
+    input: &Arc<dyn ExecutionPlan>,
+    partitioning: Partitioning,
+) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+    if let Some(aggregate) = input.as_any().downcast_ref::<AggregateExec>() {
+        if *aggregate.mode() != AggregateMode::Partial
+            || aggregate.group_expr().is_single()
+        {
+            return Ok(None);
+        }
+        let repartition = RepartitionExec::try_new(input.clone(), partitioning)?;
+        return Ok(Some(Arc::new(repartition)));
+    }
+    if input.as_any().is::<CoalesceBatchesExec>() {
+        let child = input.children()[0];
+        if let Some(child) = shuffle_partial_aggregate(child, partitioning)? {
+            return Ok(Some(input.clone().with_new_children(vec![child])?));
+        }
+    }
+    Ok(None)
+}
+
+/// The aggregate function of an aggregate expression, if its results over the groups of
+/// a grouping set can be re-aggregated into its result over a coarser grouping set
+fn re_aggregatable(expr: &Expr) -> Option<&AggregateFunction> {
+    let function = match expr {
+        Expr::AggregateFunction(function) => function,
+        Expr::Alias(alias) => return re_aggregatable(&alias.expr),
+        _ => return None,
+    };
+    let re_aggregatable = matches!(function.func.name(), "sum" | "min" | "max" | "count")
+        && !function.distinct
+        && function.filter.is_none()
+        && function.order_by.is_none();
+    re_aggregatable.then_some(function)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::functions_aggregate::expr_fn::{avg, count, max, sum};
+    use datafusion::logical_expr::{col, rollup, LogicalPlanBuilder};
+
+    fn scan() -> LogicalPlanBuilder {
+        let schema = Schema::new(vec![
+            Field::new("a", DataType::Utf8, false),
+            Field::new("b", DataType::Utf8, false),
+            Field::new("c", DataType::Decimal128(10, 2), false),
+        ]);
+        datafusion::logical_expr::table_scan(Some("t"), &schema, None).unwrap()
+    }
+
+    #[test]
+    fn pre_aggregates_rollup() -> Result<()> {
+        let plan = scan()
+            .aggregate(
+                vec![rollup(vec![col("a"), col("b")])],
+                vec![sum(col("c")), count(col("c")), max(col("c"))],
+            )?
+            .build()?;
+
+        let rewritten = pre_aggregate_grouping_sets(plan.clone())?;
+        assert_eq!(
+            format!("{}", rewritten.display_indent()),
+            "Projection: t.a, t.b, CAST(sum(t.c) AS Decimal128(20, 2)) AS sum(t.c), count(t.c), max(t.c)\
+            \n  Aggregate: groupBy=[[ROLLUP (t.a, t.b)]], aggr=[[sum(__grouping_sets_pre_aggregate_0) AS sum(t.c), sum(__grouping_sets_pre_aggregate_1) AS count(t.c), max(__grouping_sets_pre_aggregate_2) AS max(t.c)]]\
+            \n    Aggregate: groupBy=[[t.a, t.b]], aggr=[[sum(t.c) AS __grouping_sets_pre_aggregate_0, count(t.c) AS __grouping_sets_pre_aggregate_1, max(t.c) AS __grouping_sets_pre_aggregate_2]]\
+            \n      TableScan: t"
+        );
+        assert!(rewritten
+            .schema()
+            .equivalent_names_and_types(plan.schema().as_ref()));
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_other_aggregations() -> Result<()> {
+        // avg can't be re-aggregated
+        let plan = scan()
+            .aggregate(vec![rollup(vec![col("a"), col("b")])], vec![avg(col("c"))])?
+            .build()?;
+        assert_eq!(pre_aggregate_grouping_sets(plan.clone())?, plan);
+
+        // no grouping sets
+        let plan = scan()
+            .aggregate(vec![col("a"), col("b")], vec![sum(col("c"))])?
+            .build()?;
+        assert_eq!(pre_aggregate_grouping_sets(plan.clone())?, plan);
+        Ok(())
+    }
+}
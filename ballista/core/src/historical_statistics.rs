@@ -0,0 +1,253 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sizes of the tables recorded from the completed jobs, which the planner falls back
+//! to for the scans it has no statistics of.
+//!
+//! DataFusion only knows the rows and bytes of the scans of the tables whose
+//! statistics it collects, e.g. from the footers of Parquet files with
+//! `datafusion.execution.collect_statistics`, so that the joins of the other tables
+//! are planned without knowing which side is the smaller one, or whether it is small
+//! enough to be broadcast. The scheduler records the rows output by the scans of whole
+//! tables in the successful jobs, and plans the later jobs scanning the same tables
+//! with them, unless `ballista.optimizer.historical_statistics` is disabled.
+//!
+//! Tables are identified by their location, the object store URL and the longest
+//! common directory of their files.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use datafusion::common::stats::Precision;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::Statistics;
+use datafusion::config::ConfigOptions;
+use datafusion::datasource::listing::ListingTableUrl;
+use datafusion::datasource::physical_plan::{FileScanConfig, ParquetExec};
+use datafusion::error::Result;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::ExecutionPlan;
+use object_store::path::Path;
+
+use crate::cost_model::row_width;
+use crate::execution_plans::{scan_config, with_scan_config};
+
+/// Rows of a table and their size in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableSize {
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+impl TableSize {
+    pub fn new(rows: u64, bytes: u64) -> Self {
+        Self { rows, bytes }
+    }
+}
+
+/// Location of the files of a file scan: the URL of their object store followed by
+/// their longest common directory
+pub fn scan_location(config: &FileScanConfig) -> Option<String> {
+    let mut files = config.file_groups.iter().flatten();
+    let mut directory: Vec<_> = files.next()?.object_meta.location.parts().collect();
+    directory.pop();
+    for file in files {
+        let parts: Vec<_> = file.object_meta.location.parts().collect();
+        let common = directory
+            .iter()
+            .zip(&parts[..parts.len() - 1])
+            .take_while(|(left, right)| left == right)
+            .count();
+        directory.truncate(common);
+    }
+    Some(format!(
+        "{}{}",
+        config.object_store_url.as_str(),
+        Path::from_iter(directory)
+    ))
+}
+
+/// Location of the files of a table at `url`, the directory of the file if the table is
+/// a single file
+pub fn table_location(url: &ListingTableUrl) -> String {
+    let prefix = if url.is_collection() {
+        url.prefix().clone()
+    } else {
+        let mut parts: Vec<_> = url.prefix().parts().collect();
+        parts.pop();
+        Path::from_iter(parts)
+    };
+    format!("{}{}", url.object_store().as_str(), prefix)
+}
+
+/// Location and size of the table scanned whole by `plan`, a file scan which output
+/// `rows`. None if `plan` is not a file scan, or may skip some rows of its table: when
+/// limited, when its predicate prunes the row groups of Parquet files, or when the
+/// partitions of its table are pruned
+pub fn scanned_table_size(
+    plan: &dyn ExecutionPlan,
+    rows: u64,
+) -> Option<(String, TableSize)> {
+    let config = scan_config(plan)?;
+    if config.limit.is_some() || !config.table_partition_cols.is_empty() {
+        return None;
+    }
+    if plan
+        .as_any()
+        .downcast_ref::<ParquetExec>()
+        .is_some_and(|exec| exec.predicate().is_some())
+    {
+        return None;
+    }
+
+    let bytes = (rows as f64 * row_width(&config.file_schema)) as u64;
+    Some((scan_location(config)?, TableSize::new(rows, bytes)))
+}
+
+/// Physical optimizer rule setting the statistics of the file scans without any to the
+/// sizes of their tables, which the planner runs ahead of the rules of DataFusion
+#[derive(Debug, Default)]
+pub struct HistoricalStatisticsRule {
+    /// Sizes of the tables, by location
+    tables: HashMap<String, TableSize>,
+}
+
+impl HistoricalStatisticsRule {
+    pub fn new(tables: impl IntoIterator<Item = (String, TableSize)>) -> Self {
+        Self {
+            tables: tables.into_iter().collect(),
+        }
+    }
+}
+
+impl PhysicalOptimizerRule for HistoricalStatisticsRule {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        plan.transform_up(|plan| {
+            let Some(config) = scan_config(plan.as_ref()) else {
+                return Ok(Transformed::no(plan));
+            };
+            if config.statistics.num_rows.get_value().is_some() {
+                return Ok(Transformed::no(plan));
+            }
+            let Some(size) =
+                scan_location(config).and_then(|location| self.tables.get(&location))
+            else {
+                return Ok(Transformed::no(plan));
+            };
+
+            let statistics = Statistics {
+                num_rows: Precision::Inexact(size.rows as usize),
+                total_byte_size: Precision::Inexact(size.bytes as usize),
+                column_statistics: Statistics::unknown_column(&config.file_schema),
+            };
+            with_scan_config(plan.as_ref(), |config| config.statistics = statistics)
+                .map(Transformed::yes)
+        })
+        .map(|transformed| transformed.data)
+    }
+
+    fn name(&self) -> &str {
+        "historical_statistics"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::listing::PartitionedFile;
+    use datafusion::execution::object_store::ObjectStoreUrl;
+    use datafusion::physical_plan::limit::GlobalLimitExec;
+
+    fn scan(files: &[&str]) -> FileScanConfig {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("price", DataType::Float64, true),
+        ]));
+        FileScanConfig::new(ObjectStoreUrl::parse("s3://bucket").unwrap(), schema)
+            .with_file_group(
+                files
+                    .iter()
+                    .map(|file| PartitionedFile::new(*file, 100))
+                    .collect(),
+            )
+    }
+
+    #[test]
+    fn test_locations() -> Result<()> {
+        let config = scan(&["orders/2024/a.parquet", "orders/2025/b.parquet"]);
+        assert_eq!(
+            Some("s3://bucket/orders".to_owned()),
+            scan_location(&config)
+        );
+        let config = scan(&["orders/a.parquet"]);
+        assert_eq!(
+            Some("s3://bucket/orders".to_owned()),
+            scan_location(&config)
+        );
+        assert_eq!(None, scan_location(&scan(&[])));
+
+        let url = ListingTableUrl::parse("s3://bucket/orders/")?;
+        assert_eq!("s3://bucket/orders", table_location(&url));
+        let url = ListingTableUrl::parse("s3://bucket/orders/a.parquet")?;
+        assert_eq!("s3://bucket/orders", table_location(&url));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scanned_table_size() {
+        let plan = ParquetExec::builder(scan(&["orders/a.parquet"])).build();
+        assert_eq!(
+            Some(("s3://bucket/orders".to_owned(), TableSize::new(10, 160))),
+            scanned_table_size(&plan, 10)
+        );
+
+        // the rows of limited scans are not those of their tables
+        let plan =
+            ParquetExec::builder(scan(&["orders/a.parquet"]).with_limit(Some(5))).build();
+        assert_eq!(None, scanned_table_size(&plan, 5));
+    }
+
+    #[test]
+    fn test_historical_statistics() -> Result<()> {
+        let rule = HistoricalStatisticsRule::new([(
+            "s3://bucket/orders".to_owned(),
+            TableSize::new(1000, 16000),
+        )]);
+        let orders = ParquetExec::builder(scan(&["orders/a.parquet"])).build_arc();
+        let plan: Arc<dyn ExecutionPlan> =
+            Arc::new(GlobalLimitExec::new(orders, 0, Some(10)));
+        let plan = rule.optimize(plan, &ConfigOptions::default())?;
+        let statistics = plan.children()[0].statistics()?;
+        assert_eq!(Precision::Inexact(1000), statistics.num_rows);
+        assert_eq!(Precision::Inexact(16000), statistics.total_byte_size);
+
+        // the scans of other tables keep their statistics
+        let items = ParquetExec::builder(scan(&["items/a.parquet"])).build_arc();
+        let plan = rule.optimize(items, &ConfigOptions::default())?;
+        assert_eq!(Precision::Absent, plan.statistics()?.num_rows);
+        Ok(())
+    }
+}
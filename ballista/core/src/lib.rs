@@ -22,19 +22,46 @@ use std::sync::Arc;
 use datafusion::{execution::runtime_env::RuntimeEnv, prelude::SessionConfig};
 pub const BALLISTA_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Version identifying both the Ballista and DataFusion build of a process. Stages
+/// of a job should only run on executors sharing the same build version, since
+/// plans and shuffle data are not guaranteed to be compatible across versions.
+pub fn build_version() -> String {
+    format!(
+        "{BALLISTA_VERSION}+datafusion.{}",
+        datafusion::DATAFUSION_VERSION
+    )
+}
+
 pub fn print_version() {
     println!("Ballista version: {BALLISTA_VERSION}")
 }
 
 pub mod client;
+pub mod cluster_catalog;
+pub mod compression;
 pub mod config;
+#[cfg(feature = "consistent-hash")]
 pub mod consistent_hash;
+pub mod cost_model;
 pub mod error;
 pub mod event_loop;
 pub mod execution_plans;
 pub mod extension;
+pub mod federation;
+pub mod grouping_sets;
+pub mod historical_statistics;
+pub mod planner;
+pub mod recursive_query;
 pub mod registry;
+pub mod schema_evolution;
+pub mod security;
+pub mod staging;
+pub mod table_factory;
+pub mod table_functions;
+#[cfg(test)]
+pub(crate) mod test_utils;
 pub mod utils;
+pub mod verify_table;
 
 #[macro_use]
 pub mod serde;
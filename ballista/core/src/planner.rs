@@ -0,0 +1,954 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Distributed query execution
+
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::ShuffleCompression;
+use crate::cost_model::{shuffle_oversized_build_sides, ExchangeCostModel};
+use crate::error::{BallistaError, Result};
+use crate::extension::{set_broadcast_join_threshold, SessionConfigExt};
+use crate::grouping_sets::{pre_aggregate_grouping_sets, shuffle_grouping_sets};
+use crate::historical_statistics::HistoricalStatisticsRule;
+use crate::utils::StableHasher;
+use crate::{
+    execution_plans::{
+        scan_config, BroadcastExchangeExec, ShuffleReaderExec, ShuffleWriterExec,
+        SplittableScanExec, UnresolvedShuffleExec, VerifyFilesExec,
+    },
+    serde::scheduler::PartitionLocation,
+};
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
+use datafusion::execution::{SessionState, SessionStateBuilder};
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::physical_plan::joins::{HashJoinExec, PartitionMode};
+use datafusion::physical_plan::repartition::RepartitionExec;
+use datafusion::physical_plan::sorts::sort::SortExec;
+use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
+use datafusion::physical_plan::{
+    with_new_children_if_necessary, ExecutionPlan, ExecutionPlanProperties, Partitioning,
+    PhysicalExpr,
+};
+use datafusion::prelude::SessionConfig;
+
+use log::{debug, info};
+
+type PartialQueryStageResult = (Arc<dyn ExecutionPlan>, Vec<Arc<ShuffleWriterExec>>);
+
+pub struct DistributedPlanner {
+    next_stage_id: usize,
+    sorted_shuffles: bool,
+    shuffle_compression: ShuffleCompression,
+    shuffle_object_store_url: Option<String>,
+    splittable_scans: bool,
+}
+
+impl DistributedPlanner {
+    pub fn new() -> Self {
+        Self {
+            next_stage_id: 0,
+            sorted_shuffles: false,
+            shuffle_compression: ShuffleCompression::default(),
+            shuffle_object_store_url: None,
+            splittable_scans: false,
+        }
+    }
+
+    /// Sort the output of map tasks whose shuffle is sorted by the next stage, which
+    /// then merges the sorted outputs instead, as done for sort-merge joins
+    pub fn with_sorted_shuffles(mut self, sorted_shuffles: bool) -> Self {
+        self.sorted_shuffles = sorted_shuffles;
+        self
+    }
+
+    /// Codec the map tasks of every stage compress their shuffle files with
+    pub fn with_shuffle_compression(mut self, compression: ShuffleCompression) -> Self {
+        self.shuffle_compression = compression;
+        self
+    }
+
+    /// Object store the map tasks of every stage but the final one upload their shuffle
+    /// files to, so that the stages reading them survive the loss of an executor
+    pub fn with_shuffle_object_store_url(mut self, url: Option<String>) -> Self {
+        self.shuffle_object_store_url = url;
+        self
+    }
+
+    /// Lease the files of the scans of the stages reading no other input to their tasks
+    /// as they go, see [SplittableScanExec]
+    pub fn with_splittable_scans(mut self, splittable_scans: bool) -> Self {
+        self.splittable_scans = splittable_scans;
+        self
+    }
+
+    /// Planner of the stages of the jobs of a session, configured by its settings
+    pub fn for_session(config: &SessionConfig) -> Result<Self> {
+        Ok(Self::new()
+            .with_sorted_shuffles(config.ballista_prefer_sort_merge_join())
+            .with_shuffle_compression(
+                config
+                    .ballista_shuffle_compression()
+                    .map_err(BallistaError::General)?,
+            )
+            .with_shuffle_object_store_url(config.ballista_shuffle_object_store_url())
+            .with_splittable_scans(config.ballista_splittable_scans()))
+    }
+}
+
+impl Default for DistributedPlanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DistributedPlanner {
+    /// Returns a vector of ExecutionPlans, where the root node is a [ShuffleWriterExec].
+    /// Plans that depend on the input of other plans will have leaf nodes of type [UnresolvedShuffleExec].
+    /// A [ShuffleWriterExec] is created whenever the partitioning changes.
+    pub fn plan_query_stages<'a>(
+        &'a mut self,
+        job_id: &'a str,
+        execution_plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<Vec<Arc<ShuffleWriterExec>>> {
+        info!("planning query stages for job {}", job_id);
+        let (new_plan, mut stages) =
+            self.plan_query_stages_internal(job_id, execution_plan)?;
+        // the output of the final stage is fetched by the client from the executors
+        if let Some(url) = &self.shuffle_object_store_url {
+            for stage in stages.iter_mut() {
+                *stage = Arc::new(
+                    stage
+                        .as_ref()
+                        .clone()
+                        .with_object_store_url(Some(url.clone())),
+                );
+            }
+        }
+        let final_stage = create_shuffle_writer(
+            job_id,
+            self.next_stage_id(),
+            new_plan,
+            None,
+            self.shuffle_compression,
+        )?;
+        stages.push(Arc::new(
+            final_stage.as_ref().clone().with_final_stage(true),
+        ));
+        if self.splittable_scans {
+            stages = stages
+                .into_iter()
+                .map(split_stage_scan)
+                .collect::<Result<_>>()?;
+        }
+        Ok(stages)
+    }
+
+    /// Returns a potentially modified version of the input execution_plan along with the resulting query stages.
+    /// This function is needed because the input execution_plan might need to be modified, but it might not hold a
+    /// complete query stage (its parent might also belong to the same stage)
+    fn plan_query_stages_internal<'a>(
+        &'a mut self,
+        job_id: &'a str,
+        execution_plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<PartialQueryStageResult> {
+        // recurse down and replace children
+        if execution_plan.children().is_empty() {
+            return Ok((execution_plan, vec![]));
+        }
+
+        let mut stages = vec![];
+        let mut children = vec![];
+        for child in execution_plan.children() {
+            let (new_child, mut child_stages) =
+                self.plan_query_stages_internal(job_id, child.clone())?;
+            children.push(new_child);
+            stages.append(&mut child_stages);
+        }
+
+        if let Some(_coalesce) = execution_plan
+            .as_any()
+            .downcast_ref::<CoalescePartitionsExec>()
+        {
+            let shuffle_writer = create_shuffle_writer(
+                job_id,
+                self.next_stage_id(),
+                children[0].clone(),
+                None,
+                self.shuffle_compression,
+            )?;
+            let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
+            stages.push(shuffle_writer);
+            Ok((
+                with_new_children_if_necessary(execution_plan, vec![unresolved_shuffle])?,
+                stages,
+            ))
+        } else if let Some(_sort_preserving_merge) = execution_plan
+            .as_any()
+            .downcast_ref::<SortPreservingMergeExec>(
+        ) {
+            let shuffle_writer = create_shuffle_writer(
+                job_id,
+                self.next_stage_id(),
+                children[0].clone(),
+                None,
+                self.shuffle_compression,
+            )?;
+            let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
+            stages.push(shuffle_writer);
+            Ok((
+                with_new_children_if_necessary(execution_plan, vec![unresolved_shuffle])?,
+                stages,
+            ))
+        } else if execution_plan
+            .as_any()
+            .downcast_ref::<HashJoinExec>()
+            .is_some_and(|join| *join.partition_mode() == PartitionMode::CollectLeft)
+        {
+            children[0] = broadcast_build_side(job_id, children[0].clone());
+            Ok((
+                with_new_children_if_necessary(execution_plan, children)?,
+                stages,
+            ))
+        } else if let Some(sort) = execution_plan
+            .as_any()
+            .downcast_ref::<SortExec>()
+            .filter(|sort| {
+                self.sorted_shuffles
+                    && sort.preserve_partitioning()
+                    && sort.fetch().is_none()
+            })
+        {
+            match sort_shuffle_output(job_id, sort, &children[0], &mut stages)? {
+                Some(sorted_shuffle) => Ok((sorted_shuffle, stages)),
+                None => Ok((
+                    with_new_children_if_necessary(execution_plan, children)?,
+                    stages,
+                )),
+            }
+        } else if let Some(repart) =
+            execution_plan.as_any().downcast_ref::<RepartitionExec>()
+        {
+            match repart.properties().output_partitioning() {
+                Partitioning::Hash(_, _) => {
+                    let shuffle_writer = create_shuffle_writer(
+                        job_id,
+                        self.next_stage_id(),
+                        children[0].clone(),
+                        Some(repart.partitioning().to_owned()),
+                        self.shuffle_compression,
+                    )?;
+                    let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
+                    stages.push(shuffle_writer);
+                    Ok((unresolved_shuffle, stages))
+                }
+                _ => {
+                    // remove any non-hash repartition from the distributed plan
+                    Ok((children[0].clone(), stages))
+                }
+            }
+        } else {
+            Ok((
+                with_new_children_if_necessary(execution_plan, children)?,
+                stages,
+            ))
+        }
+    }
+
+    /// Generate a new stage ID
+    fn next_stage_id(&mut self) -> usize {
+        self.next_stage_id += 1;
+        self.next_stage_id
+    }
+}
+
+/// Creates the physical plan of a job from its optimized logical plan. Executors
+/// planning the stages of jobs dispatched as logical plans create the same physical plan
+/// as the scheduler, as long as they are configured the same way.
+pub async fn create_job_physical_plan(
+    mut state: SessionState,
+    plan: &LogicalPlan,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    if state.config().ballista_prefer_sort_merge_join() {
+        // the option may have been set without its setter, e.g. by a SET statement
+        state.config_mut().options_mut().optimizer.prefer_hash_join = false;
+    }
+    let cost_model = ExchangeCostModel::from_config(state.config());
+    let broadcast_join_threshold = cost_model
+        .broadcast_threshold(state.config().ballista_broadcast_join_threshold());
+    // DataFusion collects the build sides by their estimated size in memory, up to the
+    // size of those compressing enough to be broadcast. The others are shuffled below
+    set_broadcast_join_threshold(
+        state.config_mut().options_mut(),
+        (broadcast_join_threshold as f64 / cost_model.min_compression_ratio()) as usize,
+    );
+    let execution = &mut state.config_mut().options_mut().execution;
+    execution.skip_partial_aggregation_probe_ratio_threshold = cost_model
+        .skip_partial_aggregation_ratio(
+            execution.skip_partial_aggregation_probe_ratio_threshold,
+        );
+    let plan = if state.config().ballista_pre_aggregate_grouping_sets() {
+        &pre_aggregate_grouping_sets(plan.clone())?
+    } else {
+        plan
+    };
+    let state = if state.config().ballista_historical_statistics() {
+        with_historical_statistics(state)?
+    } else {
+        state
+    };
+    let plan = state
+        .query_planner()
+        .create_physical_plan(plan, &state)
+        .await?;
+    let plan = shuffle_grouping_sets(plan)?;
+    let plan = if broadcast_join_threshold > 0 {
+        shuffle_oversized_build_sides(
+            plan,
+            &cost_model,
+            broadcast_join_threshold,
+            state.config().target_partitions(),
+        )?
+    } else {
+        plan
+    };
+    debug!(
+        "Physical plan: {}",
+        DisplayableExecutionPlan::new(plan.as_ref()).indent(false)
+    );
+
+    let plan = plan.transform_down(&|node: Arc<dyn ExecutionPlan>| {
+        if node.output_partitioning().partition_count() == 0 {
+            let empty: Arc<dyn ExecutionPlan> = Arc::new(EmptyExec::new(node.schema()));
+            Ok(Transformed::yes(empty))
+        } else {
+            Ok(Transformed::no(node))
+        }
+    })?;
+    debug!(
+        "Transformed physical plan: {}",
+        DisplayableExecutionPlan::new(plan.data.as_ref()).indent(false)
+    );
+    Ok(plan.data)
+}
+
+/// The session state whose file scans without statistics get the sizes of their tables
+/// set by `ballista.optimizer.table_statistics`, before DataFusion plans the joins
+fn with_historical_statistics(state: SessionState) -> Result<SessionState> {
+    let tables = state
+        .config()
+        .ballista_table_statistics()
+        .map_err(BallistaError::General)?;
+    if tables.is_empty() {
+        return Ok(state);
+    }
+
+    let mut rules: Vec<Arc<dyn PhysicalOptimizerRule + Send + Sync>> =
+        vec![Arc::new(HistoricalStatisticsRule::new(tables))];
+    rules.extend(state.physical_optimizers().iter().cloned());
+    Ok(SessionStateBuilder::new_from_existing(state)
+        .with_physical_optimizer_rules(rules)
+        .build())
+}
+
+/// Plans the stage `stage_id` of a job from the physical plan of the job, resolving the
+/// shuffles it reads with `input_locations`, the locations of the partitions of its
+/// input stages by stage id and partition id. Fails if the stage doesn't exist, or
+/// doesn't read from the same stages as `input_locations`, which happens when the plan
+/// differs from the one the scheduler planned the job with.
+pub fn plan_job_stage(
+    job_id: &str,
+    stage_id: usize,
+    plan: Arc<dyn ExecutionPlan>,
+    config: &SessionConfig,
+    input_locations: &HashMap<usize, HashMap<usize, Vec<PartitionLocation>>>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let stage = plan_unresolved_job_stage(job_id, stage_id, plan, config)?;
+    resolve_job_stage(job_id, stage_id, stage, config, input_locations)
+}
+
+/// Plans the stage `stage_id` of a job from the physical plan of the job, leaving the
+/// shuffles it reads unresolved. Fails if the stage doesn't exist.
+pub fn plan_unresolved_job_stage(
+    job_id: &str,
+    stage_id: usize,
+    plan: Arc<dyn ExecutionPlan>,
+    config: &SessionConfig,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    DistributedPlanner::for_session(config)?
+        .plan_query_stages(job_id, plan)?
+        .into_iter()
+        .find(|stage| stage.stage_id() == stage_id)
+        .map(|stage| stage as Arc<dyn ExecutionPlan>)
+        .ok_or_else(|| {
+            BallistaError::Internal(format!(
+                "Stage {stage_id} is missing from the stages planned for job {job_id}"
+            ))
+        })
+}
+
+/// Resolves the shuffles read by the unresolved stage `stage_id` of a job, as planned
+/// by [plan_unresolved_job_stage], with `input_locations`. See [plan_job_stage].
+pub fn resolve_job_stage(
+    job_id: &str,
+    stage_id: usize,
+    stage: Arc<dyn ExecutionPlan>,
+    config: &SessionConfig,
+    input_locations: &HashMap<usize, HashMap<usize, Vec<PartitionLocation>>>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let mut input_stages: Vec<usize> = find_unresolved_shuffles(&stage)?
+        .iter()
+        .map(|shuffle| shuffle.stage_id)
+        .collect();
+    input_stages.sort_unstable();
+    input_stages.dedup();
+    let mut expected_input_stages: Vec<usize> = input_locations.keys().copied().collect();
+    expected_input_stages.sort_unstable();
+    if input_stages != expected_input_stages {
+        return Err(BallistaError::Internal(format!(
+            "Stage {stage_id} of job {job_id} is planned to read from stages {input_stages:?} \
+            instead of stages {expected_input_stages:?}"
+        )));
+    }
+
+    let stage = remove_unresolved_shuffles(stage, input_locations)?;
+    coalesce_shuffle_partitions(stage, config)
+}
+
+/// Fingerprint of the operators and partitioning of a resolved stage and of the files
+/// it scans, for executors planning the stages of jobs dispatched as logical plans to
+/// check that they planned them the same way as the scheduler. The fingerprint is
+/// stable across builds, as the scheduler and the executors may not share a toolchain.
+pub fn stage_plan_fingerprint(stage: &Arc<dyn ExecutionPlan>) -> Result<u64> {
+    let mut hasher = StableHasher::default();
+    stage.apply(|plan| {
+        hasher.write_str(plan.name());
+        for field in plan.schema().fields() {
+            hasher.write_str(field.name());
+            hasher.write_str(&field.data_type().to_string());
+        }
+        hasher.write_u64(plan.output_partitioning().partition_count() as u64);
+        let Some(config) = scan_config(plan.as_ref()) else {
+            return Ok(TreeNodeRecursion::Continue);
+        };
+        hasher.write_u64(config.file_groups.len() as u64);
+        for files in &config.file_groups {
+            hasher.write_u64(files.len() as u64);
+            for file in files {
+                hasher.write_str(file.object_meta.location.as_ref());
+                match &file.range {
+                    Some(range) => {
+                        hasher.write_u64(1);
+                        hasher.write_u64(range.start as u64);
+                        hasher.write_u64(range.end as u64);
+                    }
+                    None => hasher.write_u64(0),
+                }
+            }
+        }
+        Ok(TreeNodeRecursion::Jump)
+    })?;
+    Ok(hasher.finish())
+}
+
+/// Default maximum number of stages kept by a [StagePlanCache]
+pub const DEFAULT_MAX_CACHED_STAGES: usize = 32;
+
+/// Cache of the unresolved stages an executor plans from the logical plans of jobs, by
+/// job ID and stage ID, so that the tasks of a stage don't plan the whole job again. An
+/// executor puts its cache in the [SessionConfig] of its tasks as an extension, and
+/// drops the stages of a job once the job is done. The cache is bounded, the stages
+/// planned first being dropped first.
+#[derive(Debug)]
+pub struct StagePlanCache {
+    max_stages: AtomicUsize,
+    stages: Mutex<VecDeque<CachedStage>>,
+}
+
+/// An unresolved stage, by job ID and stage ID
+type CachedStage = ((String, usize), Arc<dyn ExecutionPlan>);
+
+impl Default for StagePlanCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CACHED_STAGES)
+    }
+}
+
+impl StagePlanCache {
+    pub fn new(max_stages: usize) -> Self {
+        Self {
+            max_stages: AtomicUsize::new(max_stages),
+            stages: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Change the maximum number of cached stages, dropping the stages planned first
+    /// beyond it
+    pub fn set_max_stages(&self, max_stages: usize) {
+        self.max_stages.store(max_stages, Ordering::Relaxed);
+        let mut stages = self.stages.lock().unwrap();
+        while stages.len() > max_stages {
+            stages.pop_front();
+        }
+    }
+
+    /// Get the cached unresolved stage `stage_id` of a job
+    pub fn get(&self, job_id: &str, stage_id: usize) -> Option<Arc<dyn ExecutionPlan>> {
+        self.stages
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|((cached_job_id, cached_stage_id), _)| {
+                cached_job_id == job_id && *cached_stage_id == stage_id
+            })
+            .map(|(_, stage)| stage.clone())
+    }
+
+    /// Cache the unresolved stage `stage_id` of a job
+    pub fn insert(&self, job_id: &str, stage_id: usize, stage: Arc<dyn ExecutionPlan>) {
+        let max_stages = self.max_stages.load(Ordering::Relaxed);
+        if max_stages == 0 || self.get(job_id, stage_id).is_some() {
+            return;
+        }
+        let mut stages = self.stages.lock().unwrap();
+        if stages.len() >= max_stages {
+            stages.pop_front();
+        }
+        stages.push_back(((job_id.to_owned(), stage_id), stage));
+    }
+
+    /// Drop the stages of a job
+    pub fn remove_job(&self, job_id: &str) {
+        self.stages
+            .lock()
+            .unwrap()
+            .retain(|((cached_job_id, _), _)| cached_job_id != job_id);
+    }
+}
+
+/// Move a sort of the partitions of a hash shuffle to the map tasks writing the
+/// shuffle, returning the shuffle read as sorted to replace the sort with. Each map
+/// output is then a sorted run, which the shuffle reader merges with the other runs of
+/// the partition, so that no task has to sort a whole partition.
+fn sort_shuffle_output(
+    job_id: &str,
+    sort: &SortExec,
+    input: &Arc<dyn ExecutionPlan>,
+    stages: &mut [Arc<ShuffleWriterExec>],
+) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+    // batches coming out of the merge are already coalesced
+    let shuffle = match input.as_any().downcast_ref::<CoalesceBatchesExec>() {
+        Some(coalesce) => coalesce.input(),
+        None => input,
+    };
+    let Some(unresolved_shuffle) =
+        shuffle.as_any().downcast_ref::<UnresolvedShuffleExec>()
+    else {
+        return Ok(None);
+    };
+    let Some(stage) = stages
+        .iter_mut()
+        .find(|stage| stage.stage_id() == unresolved_shuffle.stage_id)
+    else {
+        return Ok(None);
+    };
+    let partitioning = match stage.shuffle_output_partitioning() {
+        Some(partitioning @ Partitioning::Hash(_, _)) => partitioning.clone(),
+        _ => return Ok(None),
+    };
+
+    let sorted_input = SortExec::new(sort.expr().to_vec(), stage.children()[0].clone())
+        .with_preserve_partitioning(true);
+    *stage = create_shuffle_writer(
+        job_id,
+        stage.stage_id(),
+        Arc::new(sorted_input),
+        Some(partitioning),
+        stage.compression(),
+    )?;
+
+    Ok(Some(Arc::new(
+        unresolved_shuffle
+            .clone()
+            .with_sort_order(sort.expr().to_vec()),
+    )))
+}
+
+/// Broadcast the build side of a hash join collecting its left input, if the build
+/// side is read from the shuffle of another stage, replacing the merge of the
+/// partitions of the shuffle with a [BroadcastExchangeExec]
+fn broadcast_build_side(
+    job_id: &str,
+    build_side: Arc<dyn ExecutionPlan>,
+) -> Arc<dyn ExecutionPlan> {
+    let Some(coalesce) = build_side.as_any().downcast_ref::<CoalescePartitionsExec>()
+    else {
+        return build_side;
+    };
+    let Some(shuffle) = coalesce
+        .input()
+        .as_any()
+        .downcast_ref::<UnresolvedShuffleExec>()
+    else {
+        return build_side;
+    };
+    Arc::new(BroadcastExchangeExec::new(
+        job_id.to_owned(),
+        shuffle.stage_id,
+        coalesce.input().clone(),
+    ))
+}
+
+fn create_unresolved_shuffle(
+    shuffle_writer: &ShuffleWriterExec,
+) -> Arc<UnresolvedShuffleExec> {
+    Arc::new(UnresolvedShuffleExec::new(
+        shuffle_writer.stage_id(),
+        shuffle_writer.schema(),
+        shuffle_writer
+            .properties()
+            .output_partitioning()
+            .partition_count(),
+    ))
+}
+
+/// Returns the unresolved shuffles in the execution plan
+pub fn find_unresolved_shuffles(
+    plan: &Arc<dyn ExecutionPlan>,
+) -> Result<Vec<UnresolvedShuffleExec>> {
+    if let Some(unresolved_shuffle) =
+        plan.as_any().downcast_ref::<UnresolvedShuffleExec>()
+    {
+        Ok(vec![unresolved_shuffle.clone()])
+    } else {
+        Ok(plan
+            .children()
+            .into_iter()
+            .map(find_unresolved_shuffles)
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
+
+pub fn remove_unresolved_shuffles(
+    stage: Arc<dyn ExecutionPlan>,
+    partition_locations: &HashMap<usize, HashMap<usize, Vec<PartitionLocation>>>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let mut new_children: Vec<Arc<dyn ExecutionPlan>> = vec![];
+    for child in stage.children() {
+        if let Some(unresolved_shuffle) =
+            child.as_any().downcast_ref::<UnresolvedShuffleExec>()
+        {
+            let mut relevant_locations = vec![];
+            let p = partition_locations
+                .get(&unresolved_shuffle.stage_id)
+                .ok_or_else(|| {
+                    BallistaError::General(
+                        "Missing partition location. Could not remove unresolved shuffles"
+                            .to_owned(),
+                    )
+                })?
+                .clone();
+
+            for i in 0..unresolved_shuffle.output_partition_count {
+                if let Some(x) = p.get(&i) {
+                    relevant_locations.push(x.to_owned());
+                } else {
+                    relevant_locations.push(vec![]);
+                }
+            }
+            debug!(
+                "Creating shuffle reader: {}",
+                relevant_locations
+                    .iter()
+                    .map(|c| c
+                        .iter()
+                        .filter(|l| !l.path.is_empty())
+                        .map(|l| l.path.clone())
+                        .collect::<Vec<_>>()
+                        .join(", "))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+            new_children.push(Arc::new(
+                ShuffleReaderExec::try_new(
+                    unresolved_shuffle.stage_id,
+                    relevant_locations,
+                    unresolved_shuffle.schema().clone(),
+                )?
+                .with_sort_order(unresolved_shuffle.sort_order.clone()),
+            ))
+        } else {
+            new_children.push(remove_unresolved_shuffles(
+                child.clone(),
+                partition_locations,
+            )?);
+        }
+    }
+    Ok(with_new_children_if_necessary(stage, new_children)?)
+}
+
+/// Coalesce adjacent partitions of the shuffles read by a resolved stage, up to
+/// `ballista.shuffle.target_partition_bytes` per partition as reported by the map tasks,
+/// so that small shuffles aren't read by one tiny task per partition. The shuffles
+/// read by the stage are coalesced alike, which keeps the partitions of a partitioned
+/// join aligned, and the stage is left as it is if they have different numbers of
+/// partitions. Shuffles merged into a single partition, e.g. below a
+/// [CoalescePartitionsExec], don't set the partitions of the stage and are left as
+/// they are. Does nothing unless `ballista.shuffle.coalesce_partitions` is set.
+///
+/// The result only depends on the partition locations and the session, so that
+/// executors planning the stage themselves coalesce it the same way as the scheduler.
+pub fn coalesce_shuffle_partitions(
+    stage: Arc<dyn ExecutionPlan>,
+    config: &SessionConfig,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    if !config.ballista_coalesce_partitions() {
+        return Ok(stage);
+    }
+
+    let mut readers = vec![];
+    stage.apply(|plan| {
+        if is_single_partition_merge(plan) {
+            return Ok(TreeNodeRecursion::Jump);
+        }
+        if let Some(reader) = plan.as_any().downcast_ref::<ShuffleReaderExec>() {
+            readers.push(reader.clone());
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    let Some(partition_count) = readers.first().map(|reader| reader.partition.len())
+    else {
+        return Ok(stage);
+    };
+    if readers.iter().any(|reader| {
+        reader.partition.len() != partition_count
+            || reader.shuffle_partition_count != partition_count
+    }) {
+        return Ok(stage);
+    }
+
+    let mut partition_bytes = vec![0; partition_count];
+    for reader in &readers {
+        for (bytes, locations) in partition_bytes.iter_mut().zip(&reader.partition) {
+            for location in locations {
+                match location.partition_stats.num_bytes {
+                    Some(num_bytes) => *bytes += num_bytes,
+                    // sizes not reported, nothing to coalesce on
+                    None => return Ok(stage),
+                }
+            }
+        }
+    }
+    let ranges = coalesced_partition_ranges(
+        &partition_bytes,
+        config.ballista_target_partition_bytes() as u64,
+    );
+    if ranges.len() == partition_count {
+        return Ok(stage);
+    }
+    debug!(
+        "Coalescing the {partition_count} shuffle partitions read by the stage into {}",
+        ranges.len()
+    );
+
+    let stage = stage.transform_down(|plan| {
+        if is_single_partition_merge(&plan) {
+            return Ok(Transformed::new(plan, false, TreeNodeRecursion::Jump));
+        }
+        let Some(reader) = plan.as_any().downcast_ref::<ShuffleReaderExec>() else {
+            return Ok(Transformed::no(plan));
+        };
+        let partition = ranges
+            .iter()
+            .map(|range| reader.partition[range.clone()].concat())
+            .collect();
+        let coalesced_reader =
+            ShuffleReaderExec::try_new(reader.stage_id, partition, reader.schema())?
+                .with_runtime_predicates(reader.runtime_predicates.clone())
+                .with_sort_order(reader.sort_order.clone())
+                .with_shuffle_partition_count(partition_count);
+        Ok(Transformed::yes(Arc::new(coalesced_reader)))
+    })?;
+    Ok(stage.data)
+}
+
+/// Whether the plan merges all the partitions of its input into a single one
+fn is_single_partition_merge(plan: &Arc<dyn ExecutionPlan>) -> bool {
+    plan.as_any().is::<CoalescePartitionsExec>()
+        || plan.as_any().is::<SortPreservingMergeExec>()
+        || plan.as_any().is::<BroadcastExchangeExec>()
+}
+
+/// Ranges of adjacent partitions of up to `target_bytes` in total. A partition larger
+/// than `target_bytes` makes a range of its own.
+fn coalesced_partition_ranges(
+    partition_bytes: &[u64],
+    target_bytes: u64,
+) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut range_bytes = 0;
+    for (partition, bytes) in partition_bytes.iter().enumerate() {
+        if partition > start && range_bytes + bytes > target_bytes {
+            ranges.push(start..partition);
+            start = partition;
+            range_bytes = 0;
+        }
+        range_bytes += bytes;
+    }
+    if start < partition_bytes.len() {
+        ranges.push(start..partition_bytes.len());
+    }
+    ranges
+}
+
+/// Attach runtime predicates to the ShuffleReaderExecs reading from `input_stage_id`.
+/// The predicates are lost when the stage is rolled back to be unresolved.
+pub fn attach_runtime_predicates(
+    stage: Arc<dyn ExecutionPlan>,
+    input_stage_id: usize,
+    predicates: &[Arc<dyn PhysicalExpr>],
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let mut new_children: Vec<Arc<dyn ExecutionPlan>> = vec![];
+    for child in stage.children() {
+        match child.as_any().downcast_ref::<ShuffleReaderExec>() {
+            Some(shuffle_reader) if shuffle_reader.stage_id == input_stage_id => {
+                new_children.push(Arc::new(
+                    shuffle_reader
+                        .clone()
+                        .with_runtime_predicates(predicates.to_vec()),
+                ));
+            }
+            _ => new_children.push(attach_runtime_predicates(
+                child.clone(),
+                input_stage_id,
+                predicates,
+            )?),
+        }
+    }
+    Ok(with_new_children_if_necessary(stage, new_children)?)
+}
+
+/// Rollback the ShuffleReaderExec to UnresolvedShuffleExec.
+/// Used when the input stages are finished but some partitions are missing due to executor lost.
+/// The entire stage need to be rolled back and rescheduled.
+pub fn rollback_resolved_shuffles(
+    stage: Arc<dyn ExecutionPlan>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let mut new_children: Vec<Arc<dyn ExecutionPlan>> = vec![];
+    for child in stage.children() {
+        if let Some(shuffle_reader) = child.as_any().downcast_ref::<ShuffleReaderExec>() {
+            let output_partition_count = shuffle_reader.shuffle_partition_count;
+            let stage_id = shuffle_reader.stage_id;
+
+            let unresolved_shuffle = Arc::new(
+                UnresolvedShuffleExec::new(
+                    stage_id,
+                    shuffle_reader.schema(),
+                    output_partition_count,
+                )
+                .with_sort_order(shuffle_reader.sort_order.clone()),
+            );
+            new_children.push(unresolved_shuffle);
+        } else {
+            new_children.push(rollback_resolved_shuffles(child.clone())?);
+        }
+    }
+    Ok(with_new_children_if_necessary(stage, new_children)?)
+}
+
+/// Wraps the scan of a stage into a [SplittableScanExec], if the scan is the only leaf
+/// of the stage and its partitions can be split
+fn split_stage_scan(stage: Arc<ShuffleWriterExec>) -> Result<Arc<ShuffleWriterExec>> {
+    let input = stage.children()[0].clone();
+    let mut leaves = vec![];
+    let mut verifies_files = false;
+    input.apply(|node| {
+        // the files verified by a VerifyFilesExec are read by the partition they
+        // were listed in
+        if node.as_any().is::<VerifyFilesExec>() {
+            verifies_files = true;
+            return Ok(TreeNodeRecursion::Stop);
+        }
+        if node.children().is_empty() {
+            leaves.push(node.clone());
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    if verifies_files
+        || !matches!(leaves.as_slice(), [scan] if SplittableScanExec::is_splittable(scan.as_ref()))
+    {
+        return Ok(stage);
+    }
+
+    let input = input
+        .transform_up(|node| {
+            if node.children().is_empty() {
+                let scan = SplittableScanExec::try_new(
+                    stage.job_id().to_owned(),
+                    stage.stage_id(),
+                    node,
+                )?;
+                Ok(Transformed::yes(Arc::new(scan) as Arc<dyn ExecutionPlan>))
+            } else {
+                Ok(Transformed::no(node))
+            }
+        })?
+        .data;
+    stage
+        .clone()
+        .with_new_children(vec![input])?
+        .as_any()
+        .downcast_ref::<ShuffleWriterExec>()
+        .map(|stage| Arc::new(stage.clone()))
+        .ok_or_else(|| {
+            BallistaError::Internal(format!(
+                "Stage {} is not a ShuffleWriterExec",
+                stage.stage_id()
+            ))
+        })
+}
+
+fn create_shuffle_writer(
+    job_id: &str,
+    stage_id: usize,
+    plan: Arc<dyn ExecutionPlan>,
+    partitioning: Option<Partitioning>,
+    compression: ShuffleCompression,
+) -> Result<Arc<ShuffleWriterExec>> {
+    Ok(Arc::new(
+        ShuffleWriterExec::try_new(
+            job_id.to_owned(),
+            stage_id,
+            plan,
+            "".to_owned(), // executor will decide on the work_dir path
+            partitioning,
+        )?
+        .with_compression(compression),
+    ))
+}
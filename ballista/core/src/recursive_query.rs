@@ -0,0 +1,156 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Recursive queries (`WITH RECURSIVE`) submitted to a Ballista cluster.
+//!
+//! DataFusion can't serialize a [RecursiveQuery], so clients submit it as a
+//! [RecursiveQueryNode] extension node, which the scheduler turns back into a
+//! [RecursiveQuery]. The scheduler then runs the recursive term as repeated
+//! distributed stages until it reaches a fixpoint, rather than running the query in a
+//! single task.
+
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{DFSchemaRef, DataFusionError, Result};
+use datafusion::logical_expr::{
+    Expr, Extension, LogicalPlan, RecursiveQuery, UserDefinedLogicalNodeCore,
+};
+use std::fmt;
+use std::sync::Arc;
+
+/// A [RecursiveQuery] as an extension node, whose inputs are its static term and its
+/// recursive term
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecursiveQueryNode {
+    name: String,
+    static_term: Arc<LogicalPlan>,
+    recursive_term: Arc<LogicalPlan>,
+    is_distinct: bool,
+}
+
+impl RecursiveQueryNode {
+    pub fn new(
+        name: impl Into<String>,
+        static_term: Arc<LogicalPlan>,
+        recursive_term: Arc<LogicalPlan>,
+        is_distinct: bool,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            static_term,
+            recursive_term,
+            is_distinct,
+        }
+    }
+
+    /// Name of the query, which is also the name of its work table
+    pub fn query_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the results of the recursive term are deduplicated (`UNION`) or not
+    /// (`UNION ALL`)
+    pub fn is_distinct(&self) -> bool {
+        self.is_distinct
+    }
+}
+
+impl UserDefinedLogicalNodeCore for RecursiveQueryNode {
+    fn name(&self) -> &str {
+        "RecursiveQuery"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.static_term, &self.recursive_term]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.static_term.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RecursiveQuery: name={}, is_distinct={}",
+            self.name, self.is_distinct
+        )
+    }
+
+    fn with_exprs_and_inputs(
+        &self,
+        _exprs: Vec<Expr>,
+        inputs: Vec<LogicalPlan>,
+    ) -> Result<Self> {
+        let [static_term, recursive_term]: [LogicalPlan; 2] =
+            inputs.try_into().map_err(|inputs: Vec<LogicalPlan>| {
+                DataFusionError::Internal(format!(
+                    "RecursiveQuery expects 2 inputs, got {}",
+                    inputs.len()
+                ))
+            })?;
+        Ok(Self::new(
+            self.name.clone(),
+            Arc::new(static_term),
+            Arc::new(recursive_term),
+            self.is_distinct,
+        ))
+    }
+}
+
+/// Replace the recursive queries of a plan with [RecursiveQueryNode]s, so that the
+/// plan can be serialized
+pub fn encode_recursive_queries(plan: LogicalPlan) -> Result<LogicalPlan> {
+    plan.transform_up(|plan| match plan {
+        LogicalPlan::RecursiveQuery(query) => {
+            Ok(Transformed::yes(LogicalPlan::Extension(Extension {
+                node: Arc::new(RecursiveQueryNode::new(
+                    query.name,
+                    query.static_term,
+                    query.recursive_term,
+                    query.is_distinct,
+                )),
+            })))
+        }
+        _ => Ok(Transformed::no(plan)),
+    })
+    .map(|transformed| transformed.data)
+}
+
+/// Replace the [RecursiveQueryNode]s of a deserialized plan with the recursive
+/// queries they stand for
+pub fn decode_recursive_queries(plan: LogicalPlan) -> Result<LogicalPlan> {
+    plan.transform_up(|plan| {
+        let LogicalPlan::Extension(extension) = &plan else {
+            return Ok(Transformed::no(plan));
+        };
+        match extension.node.as_any().downcast_ref::<RecursiveQueryNode>() {
+            Some(node) => Ok(Transformed::yes(LogicalPlan::RecursiveQuery(
+                RecursiveQuery {
+                    name: node.name.clone(),
+                    static_term: node.static_term.clone(),
+                    recursive_term: node.recursive_term.clone(),
+                    is_distinct: node.is_distinct,
+                },
+            ))),
+            None => Ok(Transformed::no(plan)),
+        }
+    })
+    .map(|transformed| transformed.data)
+}
@@ -15,15 +15,24 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use datafusion::common::DataFusionError;
+use crate::utils::StableHasher;
+use datafusion::arrow::array::Float64Array;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{not_impl_err, DataFusionError};
 use datafusion::execution::{FunctionRegistry, SessionState};
 use datafusion::functions::all_default_functions;
 use datafusion::functions_aggregate::all_default_aggregate_functions;
 use datafusion::functions_window::all_default_window_functions;
 use datafusion::logical_expr::planner::ExprPlanner;
-use datafusion::logical_expr::{AggregateUDF, ScalarUDF, WindowUDF};
+use datafusion::logical_expr::{
+    AggregateUDF, ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+    WindowUDF,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::any::Any;
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
 pub struct BallistaFunctionRegistry {
@@ -57,6 +66,68 @@ impl Default for BallistaFunctionRegistry {
     }
 }
 
+impl BallistaFunctionRegistry {
+    /// The functions of this registry, with `random()` drawing its values from a
+    /// generator seeded with `seed`, which is the same for all the attempts of a task
+    pub fn with_random_seed(&self, seed: u64) -> Self {
+        let mut scalar_functions = self.scalar_functions.clone();
+        let random = ScalarUDF::from(SeededRandomFunc::new(seed));
+        scalar_functions.insert(random.name().to_string(), Arc::new(random));
+
+        Self {
+            scalar_functions,
+            aggregate_functions: self.aggregate_functions.clone(),
+            window_functions: self.window_functions.clone(),
+        }
+    }
+
+    /// Whether the `random()` function of this registry is referenced outside of it,
+    /// e.g. by a plan decoded with it
+    pub fn is_random_referenced(&self) -> bool {
+        self.scalar_functions
+            .get(SeededRandomFunc::NAME)
+            .is_some_and(|random| Arc::strong_count(random) > 1)
+    }
+
+    /// Whether a scalar, aggregate or window function is registered under `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.scalar_functions.contains_key(name)
+            || self.aggregate_functions.contains_key(name)
+            || self.window_functions.contains_key(name)
+    }
+
+    /// Sorted names of the functions of this registry which aren't built into
+    /// DataFusion, which executors advertise to the scheduler. The aliases of built-in
+    /// functions are left out
+    pub fn user_defined_function_names(&self) -> Vec<String> {
+        let builtins = Self::default();
+        let scalar_functions = self
+            .scalar_functions
+            .iter()
+            .filter(|(_, f)| !builtins.scalar_functions.contains_key(f.name()))
+            .map(|(name, _)| name);
+        let aggregate_functions = self
+            .aggregate_functions
+            .iter()
+            .filter(|(_, f)| !builtins.aggregate_functions.contains_key(f.name()))
+            .map(|(name, _)| name);
+        let window_functions = self
+            .window_functions
+            .iter()
+            .filter(|(_, f)| !builtins.window_functions.contains_key(f.name()))
+            .map(|(name, _)| name);
+
+        let mut names: Vec<String> = scalar_functions
+            .chain(aggregate_functions)
+            .chain(window_functions)
+            .cloned()
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
 impl FunctionRegistry for BallistaFunctionRegistry {
     fn expr_planners(&self) -> Vec<Arc<dyn ExprPlanner>> {
         vec![]
@@ -110,3 +181,137 @@ impl From<&SessionState> for BallistaFunctionRegistry {
         }
     }
 }
+
+/// Seed of the `random()` function of the task executing a partition of a stage. All the
+/// attempts of the task, including speculative and verification attempts, share the
+/// seed, so that a retried task writes the same rows to its shuffle files
+pub fn task_random_seed(job_id: &str, stage_id: usize, partition_id: usize) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write_bytes(job_id.as_bytes());
+    hasher.write_u64(stage_id as u64);
+    hasher.write_u64(partition_id as u64);
+    hasher.finish()
+}
+
+/// `random()` drawing its values from a generator with a fixed seed, rather than from
+/// the generator of the thread evaluating it
+#[derive(Debug)]
+pub struct SeededRandomFunc {
+    signature: Signature,
+    rng: Mutex<StdRng>,
+}
+
+impl SeededRandomFunc {
+    const NAME: &'static str = "random";
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            signature: Signature::exact(vec![], Volatility::Volatile),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl ScalarUDFImpl for SeededRandomFunc {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(
+        &self,
+        _arg_types: &[DataType],
+    ) -> datafusion::common::Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn invoke(
+        &self,
+        _args: &[ColumnarValue],
+    ) -> datafusion::common::Result<ColumnarValue> {
+        not_impl_err!("{} function does not accept arguments", self.name())
+    }
+
+    fn invoke_no_args(
+        &self,
+        num_rows: usize,
+    ) -> datafusion::common::Result<ColumnarValue> {
+        let mut values = vec![0.0; num_rows];
+        self.rng.lock().unwrap().fill(&mut values[..]);
+        Ok(ColumnarValue::Array(Arc::new(Float64Array::from(values))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, AsArray};
+    use datafusion::arrow::datatypes::Float64Type;
+    use datafusion::logical_expr::create_udf;
+
+    fn draw(registry: &BallistaFunctionRegistry, num_rows: usize) -> Vec<f64> {
+        let random = registry.udf("random").unwrap();
+        let ColumnarValue::Array(values) = random.invoke_no_args(num_rows).unwrap()
+        else {
+            panic!("random() should return an array");
+        };
+        assert_eq!(num_rows, values.len());
+        values.as_primitive::<Float64Type>().values().to_vec()
+    }
+
+    #[test]
+    fn seeded_random() {
+        let registry = BallistaFunctionRegistry::default();
+        let seed = task_random_seed("job", 1, 2);
+        assert_eq!(seed, task_random_seed("job", 1, 2));
+        assert_ne!(seed, task_random_seed("job", 1, 3));
+
+        // Attempts of the same task draw the same values
+        let values = draw(&registry.with_random_seed(seed), 10);
+        assert_eq!(values, draw(&registry.with_random_seed(seed), 10));
+        assert!(values.iter().all(|value| (0.0..1.0).contains(value)));
+        assert_ne!(
+            values,
+            draw(
+                &registry.with_random_seed(task_random_seed("job", 1, 3)),
+                10
+            )
+        );
+
+        let registry = registry.with_random_seed(seed);
+        assert!(!registry.is_random_referenced());
+        let _random = registry.udf("random").unwrap();
+        assert!(registry.is_random_referenced());
+    }
+
+    #[test]
+    fn user_defined_function_names() {
+        let mut registry = BallistaFunctionRegistry::default();
+        assert!(registry.user_defined_function_names().is_empty());
+
+        // Aliases of built-in functions aren't user defined
+        let abs = registry.udf("abs").unwrap();
+        registry.scalar_functions.insert("absolute".to_owned(), abs);
+        let my_udf = create_udf(
+            "my_udf",
+            vec![],
+            Arc::new(DataType::Float64),
+            Volatility::Immutable,
+            Arc::new(|_| not_impl_err!("my_udf")),
+        );
+        registry
+            .scalar_functions
+            .insert("my_udf".to_owned(), Arc::new(my_udf));
+        assert_eq!(vec!["my_udf"], registry.user_defined_function_names());
+        assert!(registry.contains("my_udf"));
+        assert!(registry.contains("sum"));
+        assert!(!registry.contains("other_udf"));
+    }
+}
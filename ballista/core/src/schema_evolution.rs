@@ -0,0 +1,187 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Evolution of the schemas of the files of a table.
+//!
+//! Directories written over time often hold Parquet files with different schemas,
+//! with columns added by later writers or widened from `Int32` to `Int64`. DataFusion
+//! fails to infer the schema of such a table as soon as a column has different types
+//! in different files. With `ballista.scan.merge_schemas` set, Parquet tables created
+//! by `CREATE EXTERNAL TABLE` get the [merged schema](merge_schemas) of their files
+//! instead, see [table_factory](crate::table_factory). That schema is serialized with
+//! the scans of the table, and every executor adapts the files it reads to it the same
+//! way: columns missing from a file are read as nulls, and narrower types are cast to
+//! the wider type.
+
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::common::{DataFusionError, Result};
+use datafusion::datasource::listing::{ListingOptions, ListingTableUrl};
+use datafusion::execution::session_state::SessionState;
+use futures::TryStreamExt;
+
+/// Merge the schemas of the files of a table into the schema of the table.
+///
+/// Columns are ordered as they first appear in `schemas`. Columns missing from some
+/// of the schemas are nullable, and columns with different types take the type all
+/// of them [widen to](widens_to) losslessly, failing if there is no such type.
+pub fn merge_schemas<'a>(
+    schemas: impl IntoIterator<Item = &'a Schema>,
+) -> Result<Schema> {
+    let mut fields: Vec<Field> = vec![];
+    // number of schemas each merged field appears in
+    let mut appearances: Vec<usize> = vec![];
+    let mut num_schemas = 0;
+
+    for schema in schemas {
+        num_schemas += 1;
+        for field in schema.fields() {
+            let Some(i) = fields.iter().position(|f| f.name() == field.name()) else {
+                fields.push(field.as_ref().clone());
+                appearances.push(1);
+                continue;
+            };
+
+            let merged = &fields[i];
+            let data_type = common_type(merged.data_type(), field.data_type())
+                .ok_or_else(|| {
+                    DataFusionError::Plan(format!(
+                        "Column {} has incompatible types {} and {} across files",
+                        field.name(),
+                        merged.data_type(),
+                        field.data_type()
+                    ))
+                })?;
+            let nullable = merged.is_nullable() || field.is_nullable();
+            fields[i] = merged
+                .clone()
+                .with_data_type(data_type)
+                .with_nullable(nullable);
+            appearances[i] += 1;
+        }
+    }
+
+    let fields = fields
+        .into_iter()
+        .zip(appearances)
+        .map(|(field, appearances)| {
+            let nullable = field.is_nullable() || appearances < num_schemas;
+            field.with_nullable(nullable)
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Schema::new(fields))
+}
+
+/// Type which both types widen to losslessly, if one of them does
+fn common_type(left: &DataType, right: &DataType) -> Option<DataType> {
+    if left == right || widens_to(right, left) || *right == DataType::Null {
+        Some(left.clone())
+    } else if widens_to(left, right) || *left == DataType::Null {
+        Some(right.clone())
+    } else {
+        None
+    }
+}
+
+/// Whether every value of type `from` is represented exactly by type `to`
+fn widens_to(from: &DataType, to: &DataType) -> bool {
+    use DataType::*;
+
+    match (from, to) {
+        (Int8, Int16 | Int32 | Int64)
+        | (Int16, Int32 | Int64)
+        | (Int32, Int64)
+        | (UInt8, UInt16 | UInt32 | UInt64 | Int16 | Int32 | Int64)
+        | (UInt16, UInt32 | UInt64 | Int32 | Int64)
+        | (UInt32, UInt64 | Int64)
+        | (Int8 | Int16 | UInt8 | UInt16, Float32 | Float64)
+        | (Int32 | UInt32, Float64)
+        | (Float16, Float32 | Float64)
+        | (Float32, Float64)
+        | (Utf8, LargeUtf8 | Utf8View)
+        | (Binary, LargeBinary | BinaryView)
+        | (Date32, Date64) => true,
+        // the wider decimal must have at least as many digits on both sides of the point
+        (Decimal128(p1, s1), Decimal128(p2, s2)) => {
+            s2 >= s1 && i16::from(*p2) - i16::from(*s2) >= i16::from(*p1) - i16::from(*s1)
+        }
+        _ => false,
+    }
+}
+
+/// Infer the schema of a table by [merging](merge_schemas) the schemas of all its
+/// files, or None if the table has no files
+pub async fn infer_merged_schema(
+    state: &SessionState,
+    table_path: &ListingTableUrl,
+    options: &ListingOptions,
+) -> Result<Option<Schema>> {
+    let store = state.runtime_env().object_store(table_path)?;
+    let mut files: Vec<_> = table_path
+        .list_all_files(state, store.as_ref(), &options.file_extension)
+        .await?
+        .try_collect()
+        .await?;
+    if files.is_empty() {
+        return Ok(None);
+    }
+    // columns are ordered as they first appear, so the files are listed in a fixed order
+    files.sort_by(|a, b| a.location.cmp(&b.location));
+
+    let mut schemas = Vec::with_capacity(files.len());
+    for file in files {
+        schemas.push(options.format.infer_schema(state, &store, &[file]).await?);
+    }
+
+    merge_schemas(schemas.iter().map(|schema| schema.as_ref())).map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schema_evolution::merge_schemas;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+
+    #[test]
+    fn test_merge_schemas() {
+        let first = Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, false),
+        ]);
+        let second = Schema::new(vec![
+            Field::new("c", DataType::Float32, false),
+            Field::new("a", DataType::Int64, false),
+        ]);
+
+        let merged = merge_schemas([&first, &second]).unwrap();
+        assert_eq!(
+            merged,
+            Schema::new(vec![
+                Field::new("a", DataType::Int64, false),
+                Field::new("b", DataType::Utf8, true),
+                Field::new("c", DataType::Float32, true),
+            ])
+        );
+
+        // columns keep their wider type
+        let narrower = Schema::new(vec![Field::new("a", DataType::Int16, false)]);
+        let merged = merge_schemas([&merged, &narrower]).unwrap();
+        assert_eq!(merged.field(0).data_type(), &DataType::Int64);
+
+        let incompatible = Schema::new(vec![Field::new("b", DataType::Int64, false)]);
+        assert!(merge_schemas([&first, &incompatible]).is_err());
+    }
+}
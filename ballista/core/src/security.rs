@@ -0,0 +1,350 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! TLS and token authentication of the gRPC connections between the clients, the
+//! scheduler and the executors of a cluster, and tokens scoping the fetches of
+//! shuffle partitions to their job.
+
+use std::fmt;
+
+use rand::Rng;
+use tonic::metadata::{AsciiMetadataValue, MetadataValue};
+use tonic::service::Interceptor;
+use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+use tonic::{Request, Status};
+
+use crate::error::{BallistaError, Result};
+
+/// Metadata key of the token authenticating the requests between the processes of a
+/// cluster. It's distinct from the `authorization` header, which carries the
+/// credentials of the principal of a request
+pub const AUTH_TOKEN_METADATA_KEY: &str = "x-ballista-auth-token";
+
+/// TLS and token authentication of the gRPC servers and clients of a process.
+///
+/// Servers accept TLS connections only once given a certificate and its key, and then
+/// require clients to present a certificate signed by the CA if `client_auth` is set.
+/// Clients connect with TLS once given the CA certificate the certificates of servers
+/// are verified against, presenting the certificate of the process if any. Servers with
+/// an auth token reject the requests which don't carry it, and clients attach it to
+/// their requests.
+#[derive(Clone, Default)]
+pub struct GrpcSecurityConfig {
+    /// PEM certificate chain of the process, presented to its peers
+    tls_cert: Option<Vec<u8>>,
+    /// PEM private key of the certificate of the process
+    tls_key: Option<Vec<u8>>,
+    /// PEM certificate of the CA the certificates of the peers are verified against
+    tls_ca: Option<Vec<u8>>,
+    /// Whether servers require clients to present a certificate signed by the CA
+    client_auth: bool,
+    /// Token shared by the processes of the cluster
+    auth_token: Option<AsciiMetadataValue>,
+}
+
+impl GrpcSecurityConfig {
+    /// Presents the PEM certificate `cert` with its private key `key` to the peers
+    pub fn with_tls_identity(mut self, cert: Vec<u8>, key: Vec<u8>) -> Self {
+        self.tls_cert = Some(cert);
+        self.tls_key = Some(key);
+        self
+    }
+
+    /// Verifies the certificates of the peers against the PEM certificate `ca`
+    pub fn with_tls_ca(mut self, ca: Vec<u8>) -> Self {
+        self.tls_ca = Some(ca);
+        self
+    }
+
+    /// Reads the PEM certificate, its private key and the CA certificate of the
+    /// process from files. The certificate and the key must be set together
+    pub fn with_tls_files(
+        self,
+        cert_file: Option<&str>,
+        key_file: Option<&str>,
+        ca_file: Option<&str>,
+    ) -> Result<Self> {
+        let read = |path: &str| {
+            std::fs::read(path).map_err(|e| {
+                BallistaError::General(format!("Could not read TLS file {path}: {e}"))
+            })
+        };
+        let config = match (cert_file, key_file) {
+            (Some(cert_file), Some(key_file)) => {
+                self.with_tls_identity(read(cert_file)?, read(key_file)?)
+            }
+            (None, None) => self,
+            _ => {
+                return Err(BallistaError::General(
+                    "The TLS certificate and key must be set together".to_owned(),
+                ))
+            }
+        };
+        match ca_file {
+            Some(ca_file) => Ok(config.with_tls_ca(read(ca_file)?)),
+            None => Ok(config),
+        }
+    }
+
+    /// Requires the clients of the servers to present a certificate signed by the CA
+    pub fn with_client_auth(mut self, client_auth: bool) -> Self {
+        self.client_auth = client_auth;
+        self
+    }
+
+    /// Authenticates the requests between the processes of the cluster with `token`
+    pub fn with_auth_token(mut self, token: &str) -> Result<Self> {
+        let token = MetadataValue::try_from(token).map_err(|_| {
+            BallistaError::General("The auth token must be printable ASCII".to_owned())
+        })?;
+        self.auth_token = Some(token);
+        Ok(self)
+    }
+
+    pub fn tls_cert(&self) -> Option<&[u8]> {
+        self.tls_cert.as_deref()
+    }
+
+    pub fn tls_key(&self) -> Option<&[u8]> {
+        self.tls_key.as_deref()
+    }
+
+    pub fn tls_ca(&self) -> Option<&[u8]> {
+        self.tls_ca.as_deref()
+    }
+
+    pub fn client_auth(&self) -> bool {
+        self.client_auth
+    }
+
+    /// Whether the servers of the process accept TLS connections only
+    pub fn is_server_tls(&self) -> bool {
+        self.tls_cert.is_some() && self.tls_key.is_some()
+    }
+
+    /// Whether the clients of the process connect with TLS
+    pub fn is_client_tls(&self) -> bool {
+        self.tls_ca.is_some()
+    }
+
+    /// The TLS configuration of the servers of the process, if they use TLS
+    pub fn server_tls_config(&self) -> Option<ServerTlsConfig> {
+        let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) else {
+            return None;
+        };
+        let config = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+        match &self.tls_ca {
+            Some(ca) if self.client_auth => {
+                Some(config.client_ca_root(Certificate::from_pem(ca)))
+            }
+            _ => Some(config),
+        }
+    }
+
+    /// The TLS configuration of the clients of the process, if they use TLS
+    pub fn client_tls_config(&self) -> Option<ClientTlsConfig> {
+        let ca = self.tls_ca.as_ref()?;
+        let config = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(ca));
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => {
+                Some(config.identity(Identity::from_pem(cert, key)))
+            }
+            _ => Some(config),
+        }
+    }
+
+    /// Interceptor attaching the auth token, if any, to the requests of clients
+    pub fn auth_token_interceptor(&self) -> AuthTokenInterceptor {
+        AuthTokenInterceptor {
+            token: self.auth_token.clone(),
+        }
+    }
+
+    /// Interceptor rejecting the requests to servers which don't carry the auth token,
+    /// if any
+    pub fn auth_token_validator(&self) -> AuthTokenValidator {
+        AuthTokenValidator {
+            token: self.auth_token.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for GrpcSecurityConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrpcSecurityConfig")
+            .field("server_tls", &self.is_server_tls())
+            .field("client_tls", &self.is_client_tls())
+            .field("client_auth", &self.client_auth)
+            .field("auth_token", &self.auth_token.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// Attaches the auth token of the cluster, if any, to the requests of a gRPC client
+#[derive(Clone)]
+pub struct AuthTokenInterceptor {
+    token: Option<AsciiMetadataValue>,
+}
+
+impl Interceptor for AuthTokenInterceptor {
+    fn call(
+        &mut self,
+        mut request: Request<()>,
+    ) -> std::result::Result<Request<()>, Status> {
+        if let Some(token) = &self.token {
+            request
+                .metadata_mut()
+                .insert(AUTH_TOKEN_METADATA_KEY, token.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Rejects the requests to a gRPC server which don't carry the auth token of the
+/// cluster, if any
+#[derive(Clone)]
+pub struct AuthTokenValidator {
+    token: Option<AsciiMetadataValue>,
+}
+
+impl Interceptor for AuthTokenValidator {
+    fn call(&mut self, request: Request<()>) -> std::result::Result<Request<()>, Status> {
+        let Some(expected) = &self.token else {
+            return Ok(request);
+        };
+        match request.metadata().get(AUTH_TOKEN_METADATA_KEY) {
+            Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+                Ok(request)
+            }
+            Some(_) => Err(Status::unauthenticated("Invalid auth token")),
+            None => Err(Status::unauthenticated("Missing auth token")),
+        }
+    }
+}
+
+/// Token of a job, issued by the scheduler to the tasks and the clients of the job.
+/// Executors only serve the shuffle partitions of a job to the fetches presenting its
+/// token, so that knowing the path of a partition is not enough to read it
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct FetchToken(String);
+
+impl FetchToken {
+    /// A new random token
+    pub fn generate() -> Self {
+        Self(format!("{:032x}", rand::thread_rng().gen::<u128>()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether `token` is this token, compared in constant time
+    pub fn matches(&self, token: &str) -> bool {
+        constant_time_eq(self.0.as_bytes(), token.as_bytes())
+    }
+}
+
+impl From<String> for FetchToken {
+    fn from(token: String) -> Self {
+        Self(token)
+    }
+}
+
+impl fmt::Debug for FetchToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FetchToken(***)")
+    }
+}
+
+/// Compares two byte strings in a time which doesn't depend on where they differ
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_token() -> Result<()> {
+        let config = GrpcSecurityConfig::default().with_auth_token("s3cr3t")?;
+        let request = config
+            .auth_token_interceptor()
+            .call(Request::new(()))
+            .unwrap();
+        assert!(config.auth_token_validator().call(request).is_ok());
+
+        let status = config
+            .auth_token_validator()
+            .call(Request::new(()))
+            .unwrap_err();
+        assert_eq!(tonic::Code::Unauthenticated, status.code());
+
+        let other = GrpcSecurityConfig::default().with_auth_token("other")?;
+        let request = other
+            .auth_token_interceptor()
+            .call(Request::new(()))
+            .unwrap();
+        assert!(config.auth_token_validator().call(request).is_err());
+
+        // Without a token, requests are neither attached one nor checked
+        let request = GrpcSecurityConfig::default()
+            .auth_token_interceptor()
+            .call(Request::new(()))
+            .unwrap();
+        assert!(request.metadata().get(AUTH_TOKEN_METADATA_KEY).is_none());
+        assert!(GrpcSecurityConfig::default()
+            .auth_token_validator()
+            .call(request)
+            .is_ok());
+
+        assert!(GrpcSecurityConfig::default().with_auth_token("\n").is_err());
+        assert!(!format!("{config:?}").contains("s3cr3t"));
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_token() {
+        let token = FetchToken::generate();
+        assert_eq!(32, token.as_str().len());
+        assert_ne!(token, FetchToken::generate());
+        assert!(token.matches(token.as_str()));
+        assert!(!token.matches(""));
+        assert!(!FetchToken::default().matches(token.as_str()));
+        assert!(!format!("{token:?}").contains(token.as_str()));
+    }
+
+    #[test]
+    fn tls_files() {
+        let config = GrpcSecurityConfig::default()
+            .with_tls_files(Some("cert.pem"), None, None)
+            .unwrap_err();
+        assert!(config.to_string().contains("must be set together"));
+
+        let config = GrpcSecurityConfig::default()
+            .with_tls_files(None, None, None)
+            .unwrap();
+        assert!(!config.is_server_tls());
+        assert!(!config.is_client_tls());
+        assert!(config.server_tls_config().is_none());
+        assert!(config.client_tls_config().is_none());
+
+        let config = config.with_tls_identity(b"cert".to_vec(), b"key".to_vec());
+        assert!(config.is_server_tls());
+        assert!(!config.is_client_tls());
+    }
+}
@@ -4,7 +4,10 @@
 /// /////////////////////////////////////////////////////////////////////////////////////////////////
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BallistaPhysicalPlanNode {
-    #[prost(oneof = "ballista_physical_plan_node::PhysicalPlanType", tags = "1, 2, 3")]
+    #[prost(
+        oneof = "ballista_physical_plan_node::PhysicalPlanType",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12"
+    )]
     pub physical_plan_type: ::core::option::Option<
         ballista_physical_plan_node::PhysicalPlanType,
     >,
@@ -19,6 +22,24 @@ pub mod ballista_physical_plan_node {
         ShuffleReader(super::ShuffleReaderExecNode),
         #[prost(message, tag = "3")]
         UnresolvedShuffle(super::UnresolvedShuffleExecNode),
+        #[prost(message, tag = "4")]
+        RemoteQuery(super::RemoteQueryExecNode),
+        #[prost(message, tag = "5")]
+        FlightScan(super::FlightScanExecNode),
+        #[prost(message, tag = "6")]
+        ScanOptions(super::ScanOptionsExecNode),
+        #[prost(message, tag = "7")]
+        Extension(super::ExtensionCodecNode),
+        #[prost(message, tag = "8")]
+        NdjsonScan(super::NdJsonScanExecNode),
+        #[prost(message, tag = "9")]
+        MemoryScan(super::MemoryScanExecNode),
+        #[prost(message, tag = "10")]
+        BroadcastExchange(super::BroadcastExchangeExecNode),
+        #[prost(message, tag = "11")]
+        SplittableScan(super::SplittableScanExecNode),
+        #[prost(message, tag = "12")]
+        VerifyFiles(super::VerifyFilesExecNode),
     }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -35,6 +56,38 @@ pub struct ShuffleWriterExecNode {
     pub output_partitioning: ::core::option::Option<
         ::datafusion_proto::protobuf::PhysicalHashRepartition,
     >,
+    /// Codec compressing the shuffle files written by the stage
+    #[prost(enumeration = "ShuffleCompression", tag = "5")]
+    pub compression: i32,
+    /// URL of the object store the shuffle files are uploaded to, empty to keep them
+    /// on the disk of the executor
+    #[prost(string, tag = "6")]
+    pub object_store_url: ::prost::alloc::string::String,
+    /// If not 0, the output is partitioned round-robin into this number of partitions
+    #[prost(uint64, tag = "7")]
+    pub round_robin_partition_count: u64,
+    /// If set, the output is partitioned by ranges of its sort key
+    #[prost(message, optional, tag = "8")]
+    pub range_partitioning: ::core::option::Option<RangePartitioning>,
+    /// Whether the stage is the final stage of its job, whose small outputs are
+    /// delivered inline with the task status
+    #[prost(bool, tag = "9")]
+    pub final_stage: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RangePartitioning {
+    #[prost(message, repeated, tag = "1")]
+    pub sort_expr: ::prost::alloc::vec::Vec<
+        ::datafusion_proto::protobuf::PhysicalSortExprNode,
+    >,
+    /// Sort keys from which rows go to the next partition, one fewer than the partitions
+    #[prost(message, repeated, tag = "2")]
+    pub boundaries: ::prost::alloc::vec::Vec<RangeBoundary>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RangeBoundary {
+    #[prost(message, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<::datafusion_proto_common::ScalarValue>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct UnresolvedShuffleExecNode {
@@ -44,6 +97,14 @@ pub struct UnresolvedShuffleExecNode {
     pub schema: ::core::option::Option<::datafusion_proto_common::Schema>,
     #[prost(uint32, tag = "4")]
     pub output_partition_count: u32,
+    /// Arrow IPC encoded schema, supporting types the protobuf schema does not
+    #[prost(bytes = "vec", tag = "5")]
+    pub arrow_schema: ::prost::alloc::vec::Vec<u8>,
+    /// If not empty, the shuffle is sorted on these expressions
+    #[prost(message, repeated, tag = "6")]
+    pub sort_order: ::prost::alloc::vec::Vec<
+        ::datafusion_proto::protobuf::PhysicalSortExprNode,
+    >,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ShuffleReaderExecNode {
@@ -54,6 +115,230 @@ pub struct ShuffleReaderExecNode {
     /// The stage to read from
     #[prost(uint32, tag = "3")]
     pub stage_id: u32,
+    /// Predicates attached after planning, applied to batches while reading
+    #[prost(message, repeated, tag = "4")]
+    pub runtime_predicates: ::prost::alloc::vec::Vec<
+        ::datafusion_proto::protobuf::PhysicalExprNode,
+    >,
+    /// Arrow IPC encoded schema, supporting types the protobuf schema does not
+    #[prost(bytes = "vec", tag = "5")]
+    pub arrow_schema: ::prost::alloc::vec::Vec<u8>,
+    /// If not empty, the rows of each location are sorted on these expressions, and
+    /// the locations of a partition are merged into a sorted partition
+    #[prost(message, repeated, tag = "6")]
+    pub sort_order: ::prost::alloc::vec::Vec<
+        ::datafusion_proto::protobuf::PhysicalSortExprNode,
+    >,
+    /// Number of partitions of the shuffle, more than the partitions read when adjacent
+    /// partitions were coalesced. 0 if unknown, i.e. as many as the partitions read
+    #[prost(uint32, tag = "7")]
+    pub shuffle_partition_count: u32,
+}
+/// Subplan executed by a remote Ballista cluster
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoteQueryExecNode {
+    /// Scheduler of the remote cluster
+    #[prost(string, tag = "1")]
+    pub scheduler_url: ::prost::alloc::string::String,
+    /// Encoded datafusion.LogicalPlanNode to submit to the remote scheduler
+    #[prost(bytes = "vec", tag = "2")]
+    pub logical_plan: ::prost::alloc::vec::Vec<u8>,
+    /// Arrow IPC encoded schema of the subplan output
+    #[prost(bytes = "vec", tag = "3")]
+    pub arrow_schema: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FlightScanExecNode {
+    /// Flight endpoint the flight info was retrieved from
+    #[prost(string, tag = "1")]
+    pub endpoint: ::prost::alloc::string::String,
+    /// gRPC headers sent with each request, e.g. for authentication
+    #[prost(message, repeated, tag = "2")]
+    pub headers: ::prost::alloc::vec::Vec<KeyValuePair>,
+    #[prost(message, repeated, tag = "3")]
+    pub partition: ::prost::alloc::vec::Vec<FlightScanPartition>,
+    /// Arrow IPC encoded schema of the flight
+    #[prost(bytes = "vec", tag = "4")]
+    pub arrow_schema: ::prost::alloc::vec::Vec<u8>,
+    /// Columns of the flight returned by the scan
+    #[prost(uint32, repeated, tag = "5")]
+    pub projection: ::prost::alloc::vec::Vec<u32>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FlightScanPartition {
+    #[prost(bytes = "vec", tag = "1")]
+    pub ticket: ::prost::alloc::vec::Vec<u8>,
+    /// Locations the ticket can be redeemed at. If empty, it is redeemed at the endpoint
+    #[prost(string, repeated, tag = "2")]
+    pub location: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Options of the CSV or JSON scan of its input which DataFusion does not serialize
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScanOptionsExecNode {
+    /// Compression of the files of the scan, as a datafusion_common.CompressionTypeVariant
+    #[prost(string, tag = "1")]
+    pub compression: ::prost::alloc::string::String,
+}
+/// A JSON scan, which DataFusion does not serialize. Its compression is carried by
+/// the ScanOptionsExecNode wrapping it
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NdJsonScanExecNode {
+    #[prost(message, optional, tag = "1")]
+    pub base_conf: ::core::option::Option<
+        ::datafusion_proto::protobuf::FileScanExecConf,
+    >,
+}
+/// In-memory batches, e.g. of the tables served by the scheduler, which DataFusion does
+/// not serialize. The batches are sent along with the plan
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MemoryScanExecNode {
+    /// Arrow IPC encoded schema of the batches, once projected
+    #[prost(bytes = "vec", tag = "1")]
+    pub arrow_schema: ::prost::alloc::vec::Vec<u8>,
+    /// Arrow IPC stream of the projected batches of each partition
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub partition: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// Reads all the partitions of the shuffle of the build side of a join as a single
+/// partition, shared by the tasks of the join stage on an executor
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BroadcastExchangeExecNode {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// The stage writing the build side
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+}
+/// Scans the files leased to its task by the scheduler rather than the files of the
+/// partition of its input, so that the files of slow tasks can be scanned by idle ones
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SplittableScanExecNode {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+}
+/// Counts the rows and checksums the content of each file scanned by its input, and
+/// compares them with the metadata of the file
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct VerifyFilesExecNode {}
+/// Table providers encoded by the BallistaLogicalExtensionCodec
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BallistaTableProviderNode {
+    #[prost(
+        oneof = "ballista_table_provider_node::TableProviderType",
+        tags = "1, 2, 3, 4, 5, 6, 7"
+    )]
+    pub table_provider_type: ::core::option::Option<
+        ballista_table_provider_node::TableProviderType,
+    >,
+}
+/// Nested message and enum types in `BallistaTableProviderNode`.
+pub mod ballista_table_provider_node {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum TableProviderType {
+        #[prost(message, tag = "1")]
+        Remote(super::RemoteTableNode),
+        #[prost(message, tag = "2")]
+        Flight(super::FlightTableNode),
+        #[prost(message, tag = "3")]
+        Extension(super::ExtensionCodecNode),
+        #[prost(message, tag = "4")]
+        CteWorkTable(super::CteWorkTableNode),
+        #[prost(message, tag = "5")]
+        ClusterTable(super::ClusterTableNode),
+        #[prost(message, tag = "6")]
+        VerifyTable(super::VerifyTableNode),
+        #[prost(message, tag = "7")]
+        GenerateSeries(super::GenerateSeriesNode),
+    }
+}
+/// Logical extension nodes encoded by the BallistaLogicalExtensionCodec
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BallistaLogicalExtensionNode {
+    #[prost(oneof = "ballista_logical_extension_node::ExtensionType", tags = "1, 2")]
+    pub extension_type: ::core::option::Option<
+        ballista_logical_extension_node::ExtensionType,
+    >,
+}
+/// Nested message and enum types in `BallistaLogicalExtensionNode`.
+pub mod ballista_logical_extension_node {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum ExtensionType {
+        #[prost(message, tag = "1")]
+        Extension(super::ExtensionCodecNode),
+        #[prost(message, tag = "2")]
+        RecursiveQuery(super::RecursiveQueryNode),
+    }
+}
+/// Recursive query, whose inputs are its static term and its recursive term
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecursiveQueryNode {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub is_distinct: bool,
+}
+/// Work table of a recursive query, named after the query
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CteWorkTableNode {}
+/// Table of the catalog of the cluster, referenced by name
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClusterTableNode {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// Arrow IPC encoded schema of the table
+    #[prost(bytes = "vec", tag = "2")]
+    pub arrow_schema: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bool, tag = "3")]
+    pub view: bool,
+}
+/// Verification of the files of a table
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VerifyTableNode {
+    /// Encoded datafusion.LogicalPlanNode scanning the verified table
+    #[prost(bytes = "vec", tag = "1")]
+    pub plan: ::prost::alloc::vec::Vec<u8>,
+}
+/// Series of integers of the generate_series table function
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct GenerateSeriesNode {
+    #[prost(int64, tag = "1")]
+    pub start: i64,
+    #[prost(int64, tag = "2")]
+    pub stop: i64,
+    #[prost(int64, tag = "3")]
+    pub step: i64,
+}
+/// Node encoded by one of the user extension codecs registered on the Ballista codecs
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExtensionCodecNode {
+    /// Position of the codec in the list of registered codecs
+    #[prost(uint32, tag = "1")]
+    pub codec_position: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub blob: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoteTableNode {
+    #[prost(string, tag = "1")]
+    pub scheduler_url: ::prost::alloc::string::String,
+    /// Encoded datafusion.LogicalPlanNode scanning the table as seen by the remote cluster
+    #[prost(bytes = "vec", tag = "2")]
+    pub plan: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FlightTableNode {
+    #[prost(string, tag = "1")]
+    pub endpoint: ::prost::alloc::string::String,
+    /// Encoded arrow.flight.protocol.FlightDescriptor
+    #[prost(bytes = "vec", tag = "2")]
+    pub descriptor: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, repeated, tag = "3")]
+    pub headers: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// Arrow IPC encoded schema of the flight
+    #[prost(bytes = "vec", tag = "4")]
+    pub arrow_schema: ::prost::alloc::vec::Vec<u8>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ShuffleReaderPartition {
@@ -92,6 +377,13 @@ pub struct ExecutionGraph {
     pub end_time: u64,
     #[prost(uint64, tag = "13")]
     pub queued_at: u64,
+    #[prost(string, tag = "14")]
+    pub fetch_token: ::prost::alloc::string::String,
+    #[prost(bool, tag = "15")]
+    pub planned_from_cache: bool,
+    /// Identity which submitted the job, if any
+    #[prost(string, optional, tag = "16")]
+    pub owner: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct StageAttempts {
@@ -214,6 +506,9 @@ pub struct TaskInfo {
     /// Scheduler side finish time
     #[prost(uint64, tag = "7")]
     pub finish_time: u64,
+    /// Executor the task was scheduled on
+    #[prost(string, tag = "11")]
+    pub executor_id: ::prost::alloc::string::String,
     #[prost(oneof = "task_info::Status", tags = "8, 9, 10")]
     pub status: ::core::option::Option<task_info::Status>,
 }
@@ -302,6 +597,21 @@ pub struct FetchPartition {
     pub host: ::prost::alloc::string::String,
     #[prost(uint32, tag = "6")]
     pub port: u32,
+    /// Codec compressing the partition when it is sent
+    #[prost(enumeration = "ShuffleCompression", tag = "7")]
+    pub compression: i32,
+    /// Token of the job of the partition, which the executor holding it requires
+    #[prost(string, tag = "8")]
+    pub fetch_token: ::prost::alloc::string::String,
+    /// Codec of the CompressionCodecRegistry the batches are sent with, overriding
+    /// `compression`. Codecs Arrow IPC doesn't support natively compress the bodies of
+    /// the uncompressed Flight messages. Empty to send the batches with `compression`
+    #[prost(string, tag = "9")]
+    pub transfer_compression: ::prost::alloc::string::String,
+    /// Arrow IPC metadata version the batches are sent in, 0 for the version of the
+    /// executor holding the partition
+    #[prost(uint32, tag = "10")]
+    pub ipc_metadata_version: u32,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PartitionLocation {
@@ -317,6 +627,20 @@ pub struct PartitionLocation {
     pub partition_stats: ::core::option::Option<PartitionStats>,
     #[prost(string, tag = "5")]
     pub path: ::prost::alloc::string::String,
+    /// Partition data in Arrow IPC stream format, when small enough to be delivered inline
+    #[prost(bytes = "vec", tag = "6")]
+    pub inline_data: ::prost::alloc::vec::Vec<u8>,
+    /// Codec the partition was compressed with when written
+    #[prost(enumeration = "ShuffleCompression", tag = "7")]
+    pub compression: i32,
+    /// Token of the job, set on the output partitions of the job returned to clients so
+    /// that they can fetch them
+    #[prost(string, tag = "8")]
+    pub fetch_token: ::prost::alloc::string::String,
+    /// Arrow IPC metadata version the partition was written in, 0 if it was written by
+    /// an executor which doesn't record it, i.e. in V5
+    #[prost(uint32, tag = "9")]
+    pub ipc_metadata_version: u32,
 }
 /// Unique identifier for a materialized partition of data
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -336,6 +660,9 @@ pub struct TaskId {
     pub task_attempt_num: u32,
     #[prost(uint32, tag = "3")]
     pub partition_id: u32,
+    /// Seed of random() in the task, the same for all the attempts of the task
+    #[prost(uint64, tag = "4")]
+    pub random_seed: u64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PartitionStats {
@@ -347,6 +674,9 @@ pub struct PartitionStats {
     pub num_bytes: i64,
     #[prost(message, repeated, tag = "4")]
     pub column_stats: ::prost::alloc::vec::Vec<ColumnStats>,
+    /// Order-insensitive checksum of the rows of the partition, when it was checksummed
+    #[prost(uint64, optional, tag = "5")]
+    pub checksum: ::core::option::Option<u64>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ColumnStats {
@@ -434,6 +764,35 @@ pub struct ExecutorMetadata {
     pub grpc_port: u32,
     #[prost(message, optional, tag = "5")]
     pub specification: ::core::option::Option<ExecutorSpecification>,
+    /// Ballista and DataFusion version the executor was built with
+    #[prost(string, tag = "6")]
+    pub version: ::prost::alloc::string::String,
+    /// Functions registered on the executor on top of the built-in functions of DataFusion,
+    /// unset if the executor doesn't advertise them
+    #[prost(message, optional, tag = "7")]
+    pub functions: ::core::option::Option<ExecutorFunctions>,
+    /// Formats of the shuffle partitions the executor reads, unset if the executor
+    /// doesn't advertise them
+    #[prost(message, optional, tag = "8")]
+    pub shuffle_formats: ::core::option::Option<ShuffleFormats>,
+    /// Pool of executors the executor joined, empty for the shared pool
+    #[prost(string, tag = "9")]
+    pub pool: ::prost::alloc::string::String,
+}
+/// Names of scalar, aggregate and window functions
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecutorFunctions {
+    #[prost(string, repeated, tag = "1")]
+    pub names: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Arrow IPC options of the shuffle partitions an executor reads
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ShuffleFormats {
+    #[prost(enumeration = "ShuffleCompression", repeated, tag = "1")]
+    pub compressions: ::prost::alloc::vec::Vec<i32>,
+    /// Latest Arrow IPC metadata version the executor reads
+    #[prost(uint32, tag = "2")]
+    pub max_ipc_metadata_version: u32,
 }
 /// Used for scheduler-executor
 /// communication
@@ -449,6 +808,24 @@ pub struct ExecutorRegistration {
     pub grpc_port: u32,
     #[prost(message, optional, tag = "5")]
     pub specification: ::core::option::Option<ExecutorSpecification>,
+    /// Ballista and DataFusion version the executor was built with
+    #[prost(string, tag = "6")]
+    pub version: ::prost::alloc::string::String,
+    /// Functions registered on the executor on top of the built-in functions of DataFusion,
+    /// unset if the executor doesn't advertise them
+    #[prost(message, optional, tag = "7")]
+    pub functions: ::core::option::Option<ExecutorFunctions>,
+    /// Token the executor presents to register with the scheduler, empty if it has none.
+    /// Executors present it with every request to the scheduler
+    #[prost(string, tag = "8")]
+    pub registration_token: ::prost::alloc::string::String,
+    /// Formats of the shuffle partitions the executor reads, unset if the executor
+    /// doesn't advertise them
+    #[prost(message, optional, tag = "9")]
+    pub shuffle_formats: ::core::option::Option<ShuffleFormats>,
+    /// Pool of executors the executor joins, empty for the shared pool
+    #[prost(string, tag = "10")]
+    pub pool: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorHeartbeat {
@@ -462,21 +839,86 @@ pub struct ExecutorHeartbeat {
     #[prost(message, optional, tag = "4")]
     pub status: ::core::option::Option<ExecutorStatus>,
 }
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorMetric {
     /// TODO add more metrics
-    #[prost(oneof = "executor_metric::Metric", tags = "1")]
+    #[prost(oneof = "executor_metric::Metric", tags = "1, 2, 3, 4, 5")]
     pub metric: ::core::option::Option<executor_metric::Metric>,
 }
 /// Nested message and enum types in `ExecutorMetric`.
 pub mod executor_metric {
     /// TODO add more metrics
-    #[derive(Clone, Copy, PartialEq, ::prost::Oneof)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum Metric {
         #[prost(uint64, tag = "1")]
         AvailableMemory(u64),
+        #[prost(message, tag = "2")]
+        ShuffleDiskUsage(super::ShuffleDiskUsage),
+        #[prost(message, tag = "3")]
+        ResourceUsage(super::ResourceUsage),
+        #[prost(message, tag = "4")]
+        DataCacheUsage(super::DataCacheUsage),
+        #[prost(message, tag = "5")]
+        TaskCounts(super::TaskCounts),
     }
 }
+/// Tasks of an executor at the time of its heartbeat
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct TaskCounts {
+    #[prost(uint32, tag = "1")]
+    pub running_tasks: u32,
+    /// Tasks launched on the executor which wait for a task permit of their job
+    #[prost(uint32, tag = "2")]
+    pub queued_tasks: u32,
+}
+/// Utilization of the host of an executor, averaged since its previous heartbeat
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ResourceUsage {
+    /// Fraction of the CPU time of the host which was busy, between 0 and 1
+    #[prost(double, tag = "1")]
+    pub cpu_usage: f64,
+    #[prost(uint64, tag = "2")]
+    pub used_memory_bytes: u64,
+    #[prost(uint64, tag = "3")]
+    pub total_memory_bytes: u64,
+    /// Disk of the file system holding the work dir of the executor
+    #[prost(uint64, tag = "4")]
+    pub used_disk_bytes: u64,
+    #[prost(uint64, tag = "5")]
+    pub total_disk_bytes: u64,
+    /// Bytes per second received and sent by the network interfaces of the host
+    #[prost(uint64, tag = "6")]
+    pub network_received_bytes_per_second: u64,
+    #[prost(uint64, tag = "7")]
+    pub network_sent_bytes_per_second: u64,
+}
+/// Disk used by the shuffle data in the work dir of an executor
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ShuffleDiskUsage {
+    #[prost(uint64, tag = "1")]
+    pub used_bytes: u64,
+    /// 0 if the disk usage of the executor isn't bounded
+    #[prost(uint64, tag = "2")]
+    pub max_bytes: u64,
+}
+/// Data cache of an executor, holding the byte ranges of the files its tasks scanned.
+/// Hits and misses are counted since the executor started
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DataCacheUsage {
+    #[prost(uint64, tag = "1")]
+    pub memory_hits: u64,
+    #[prost(uint64, tag = "2")]
+    pub disk_hits: u64,
+    #[prost(uint64, tag = "3")]
+    pub misses: u64,
+    #[prost(uint64, tag = "4")]
+    pub memory_bytes: u64,
+    #[prost(uint64, tag = "5")]
+    pub disk_bytes: u64,
+    /// Paths of the files with byte ranges in the cache, the most recently read first
+    #[prost(string, repeated, tag = "6")]
+    pub cached_files: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorStatus {
     #[prost(oneof = "executor_status::Status", tags = "1, 2, 3, 4")]
@@ -556,6 +998,9 @@ pub struct FailedTask {
     /// Whether this task failure should be counted to the maximum number of times the task is allowed to retry
     #[prost(bool, tag = "3")]
     pub count_to_failures: bool,
+    /// Stable Ballista error code, e.g. BAL-2004
+    #[prost(string, tag = "10")]
+    pub error_code: ::prost::alloc::string::String,
     #[prost(oneof = "failed_task::FailedReason", tags = "4, 5, 6, 7, 8, 9")]
     pub failed_reason: ::core::option::Option<failed_task::FailedReason>,
 }
@@ -618,6 +1063,18 @@ pub struct ShuffleWritePartition {
     pub num_rows: u64,
     #[prost(uint64, tag = "5")]
     pub num_bytes: u64,
+    /// Partition data in Arrow IPC stream format, when small enough to be delivered inline
+    #[prost(bytes = "vec", tag = "6")]
+    pub inline_data: ::prost::alloc::vec::Vec<u8>,
+    /// Order-insensitive checksum of the rows of the partition, when its stage is verified
+    #[prost(uint64, optional, tag = "7")]
+    pub checksum: ::core::option::Option<u64>,
+    /// Codec the partition was compressed with when written
+    #[prost(enumeration = "ShuffleCompression", tag = "8")]
+    pub compression: i32,
+    /// Arrow IPC metadata version the partition was written in
+    #[prost(uint32, tag = "9")]
+    pub ipc_metadata_version: u32,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct TaskStatus {
@@ -678,14 +1135,38 @@ pub struct TaskDefinition {
     pub stage_attempt_num: u32,
     #[prost(uint32, tag = "6")]
     pub partition_id: u32,
-    #[prost(bytes = "vec", tag = "7")]
-    pub plan: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "bytes", tag = "7")]
+    pub plan: ::prost::bytes::Bytes,
     #[prost(string, tag = "9")]
     pub session_id: ::prost::alloc::string::String,
     #[prost(uint64, tag = "10")]
     pub launch_time: u64,
     #[prost(message, repeated, tag = "11")]
     pub props: ::prost::alloc::vec::Vec<KeyValuePair>,
+    #[prost(enumeration = "TaskPlanFormat", tag = "12")]
+    pub plan_format: i32,
+    /// Shuffle partitions read by the stage, for plans in the LOGICAL_PLAN format
+    #[prost(message, repeated, tag = "13")]
+    pub inputs: ::prost::alloc::vec::Vec<StageInput>,
+    /// Hash of the plan, if it's dispatched by reference, leaving `plan` empty for
+    /// executors to fetch it with GetTaskPlan unless they already hold it
+    #[prost(string, tag = "14")]
+    pub plan_hash: ::prost::alloc::string::String,
+    /// Seed of random() in the task, the same for all the attempts of the task
+    #[prost(uint64, tag = "15")]
+    pub random_seed: u64,
+    /// Token of the job issued by the scheduler. Executors only serve the shuffle
+    /// partitions of the job to the fetches presenting it
+    #[prost(string, tag = "16")]
+    pub fetch_token: ::prost::alloc::string::String,
+    /// Codec of the CompressionCodecRegistry the plan, including plans dispatched by
+    /// reference, is compressed with. Empty for uncompressed plans
+    #[prost(string, tag = "17")]
+    pub plan_compression: ::prost::alloc::string::String,
+    /// Fingerprint of the stage as planned by the scheduler, for plans in the
+    /// LOGICAL_PLAN format, which executors check the stage they plan against
+    #[prost(uint64, tag = "18")]
+    pub plan_fingerprint: u64,
 }
 /// A set of tasks in the same stage
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -698,14 +1179,46 @@ pub struct MultiTaskDefinition {
     pub stage_id: u32,
     #[prost(uint32, tag = "4")]
     pub stage_attempt_num: u32,
-    #[prost(bytes = "vec", tag = "5")]
-    pub plan: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "bytes", tag = "5")]
+    pub plan: ::prost::bytes::Bytes,
     #[prost(string, tag = "7")]
     pub session_id: ::prost::alloc::string::String,
     #[prost(uint64, tag = "8")]
     pub launch_time: u64,
     #[prost(message, repeated, tag = "9")]
     pub props: ::prost::alloc::vec::Vec<KeyValuePair>,
+    #[prost(enumeration = "TaskPlanFormat", tag = "10")]
+    pub plan_format: i32,
+    /// Shuffle partitions read by the stage, for plans in the LOGICAL_PLAN format
+    #[prost(message, repeated, tag = "11")]
+    pub inputs: ::prost::alloc::vec::Vec<StageInput>,
+    /// Hash of the plan, if it's dispatched by reference, leaving `plan` empty for
+    /// executors to fetch it with GetTaskPlan unless they already hold it
+    #[prost(string, tag = "12")]
+    pub plan_hash: ::prost::alloc::string::String,
+    /// Token of the job issued by the scheduler, see TaskDefinition
+    #[prost(string, tag = "13")]
+    pub fetch_token: ::prost::alloc::string::String,
+    /// Codec the plan is compressed with, see TaskDefinition
+    #[prost(string, tag = "14")]
+    pub plan_compression: ::prost::alloc::string::String,
+    /// Fingerprint of the stage as planned by the scheduler, see TaskDefinition
+    #[prost(uint64, tag = "15")]
+    pub plan_fingerprint: u64,
+}
+/// The shuffle partitions a stage reads from one of its input stages
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StageInput {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    #[prost(message, repeated, tag = "2")]
+    pub partition: ::prost::alloc::vec::Vec<ShuffleReaderPartition>,
+    /// Predicates the stage filters the rows it reads from the input stage with, known
+    /// only once other stages completed
+    #[prost(message, repeated, tag = "3")]
+    pub runtime_predicates: ::prost::alloc::vec::Vec<
+        ::datafusion_proto::protobuf::PhysicalExprNode,
+    >,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SessionSettings {
@@ -723,6 +1236,9 @@ pub struct JobSessionConfig {
 pub struct PollWorkResult {
     #[prost(message, repeated, tag = "1")]
     pub tasks: ::prost::alloc::vec::Vec<TaskDefinition>,
+    /// Operational settings pushed to the executor
+    #[prost(message, optional, tag = "2")]
+    pub settings: ::core::option::Option<ExecutorSettings>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RegisterExecutorParams {
@@ -745,11 +1261,24 @@ pub struct HeartBeatParams {
     #[prost(message, optional, tag = "4")]
     pub metadata: ::core::option::Option<ExecutorRegistration>,
 }
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct HeartBeatResult {
     /// TODO it's from Spark for BlockManager
     #[prost(bool, tag = "1")]
     pub reregister: bool,
+    /// Operational settings pushed to the executor
+    #[prost(message, optional, tag = "2")]
+    pub settings: ::core::option::Option<ExecutorSettings>,
+}
+/// Operational settings of executors, e.g. their log level, which the scheduler pushes
+/// to them at runtime
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecutorSettings {
+    /// Executors apply each version of the settings once
+    #[prost(uint64, tag = "1")]
+    pub version: u64,
+    #[prost(message, repeated, tag = "2")]
+    pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct StopExecutorParams {
@@ -771,6 +1300,9 @@ pub struct ExecutorStoppedParams {
     /// stop reason
     #[prost(string, tag = "2")]
     pub reason: ::prost::alloc::string::String,
+    /// Registration token of the executor
+    #[prost(string, tag = "3")]
+    pub registration_token: ::prost::alloc::string::String,
 }
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct ExecutorStoppedResult {}
@@ -781,6 +1313,9 @@ pub struct UpdateTaskStatusParams {
     /// All tasks must be reported until they reach the failed or completed state
     #[prost(message, repeated, tag = "2")]
     pub task_status: ::prost::alloc::vec::Vec<TaskStatus>,
+    /// Registration token of the executor
+    #[prost(string, tag = "3")]
+    pub registration_token: ::prost::alloc::string::String,
 }
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct UpdateTaskStatusResult {
@@ -791,12 +1326,17 @@ pub struct UpdateTaskStatusResult {
 pub struct ExecuteQueryParams {
     #[prost(message, repeated, tag = "4")]
     pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
-    #[prost(oneof = "execute_query_params::Query", tags = "1, 2")]
+    #[prost(oneof = "execute_query_params::Query", tags = "1, 2, 6")]
     pub query: ::core::option::Option<execute_query_params::Query>,
     #[prost(oneof = "execute_query_params::OptionalSessionId", tags = "3")]
     pub optional_session_id: ::core::option::Option<
         execute_query_params::OptionalSessionId,
     >,
+    /// Priority of the job, overriding the ballista.job.priority setting
+    #[prost(oneof = "execute_query_params::OptionalPriority", tags = "5")]
+    pub optional_priority: ::core::option::Option<
+        execute_query_params::OptionalPriority,
+    >,
 }
 /// Nested message and enum types in `ExecuteQueryParams`.
 pub mod execute_query_params {
@@ -807,12 +1347,22 @@ pub mod execute_query_params {
         /// I'd suggest to remove this, if SQL needed use `flight-sql`
         #[prost(string, tag = "2")]
         Sql(::prost::alloc::string::String),
+        /// Substrait plan, converted to a logical plan by schedulers built with the
+        /// substrait feature
+        #[prost(bytes, tag = "6")]
+        SubstraitPlan(::prost::alloc::vec::Vec<u8>),
     }
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum OptionalSessionId {
         #[prost(string, tag = "3")]
         SessionId(::prost::alloc::string::String),
     }
+    /// Priority of the job, overriding the ballista.job.priority setting
+    #[derive(Clone, Copy, PartialEq, ::prost::Oneof)]
+    pub enum OptionalPriority {
+        #[prost(uint32, tag = "5")]
+        Priority(u32),
+    }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CreateSessionParams {
@@ -905,6 +1455,30 @@ pub struct SuccessfulJob {
     pub started_at: u64,
     #[prost(uint64, tag = "4")]
     pub ended_at: u64,
+    /// Set when the results exceed the maximum number of rows returned to the client,
+    /// which then only reads this many rows
+    #[prost(uint64, optional, tag = "5")]
+    pub truncated_at: ::core::option::Option<u64>,
+    /// Partitions skipped after their tasks permanently failed, for best-effort jobs
+    #[prost(message, repeated, tag = "6")]
+    pub skipped_partitions: ::prost::alloc::vec::Vec<SkippedPartition>,
+    /// Set when the distributed plan of the job was reused from the query cache of the
+    /// scheduler rather than created
+    #[prost(bool, tag = "7")]
+    pub planned_from_cache: bool,
+    /// Set when the results are those of this earlier identical job, served from the
+    /// query cache of the scheduler without running the job
+    #[prost(string, tag = "8")]
+    pub cached_results_job_id: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SkippedPartition {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub partition_id: u32,
+    #[prost(string, tag = "3")]
+    pub error: ::prost::alloc::string::String,
 }
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct QueuedJob {
@@ -920,6 +1494,10 @@ pub struct RunningJob {
     pub started_at: u64,
     #[prost(string, tag = "3")]
     pub scheduler: ::prost::alloc::string::String,
+    /// Output partitions of the final stage completed so far, which clients stream
+    /// before the job completes
+    #[prost(message, repeated, tag = "4")]
+    pub partition_location: ::prost::alloc::vec::Vec<PartitionLocation>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FailedJob {
@@ -931,6 +1509,9 @@ pub struct FailedJob {
     pub started_at: u64,
     #[prost(uint64, tag = "4")]
     pub ended_at: u64,
+    /// Stable Ballista error code, e.g. BAL-2001
+    #[prost(string, tag = "5")]
+    pub error_code: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct JobStatus {
@@ -961,6 +1542,44 @@ pub struct GetJobStatusResult {
     pub status: ::core::option::Option<JobStatus>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribeJobEventsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+}
+/// Event of a job, pushed to the clients subscribed to it
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobEvent {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// Milliseconds since the epoch
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(oneof = "job_event::Event", tags = "3, 4")]
+    pub event: ::core::option::Option<job_event::Event>,
+}
+/// Nested message and enum types in `JobEvent`.
+pub mod job_event {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        /// The status of the job changed, e.g. from queued to running, or it ended
+        #[prost(message, tag = "3")]
+        Status(super::JobStatus),
+        /// A stage of the job completed
+        #[prost(message, tag = "4")]
+        StageCompleted(super::StageCompletedEvent),
+    }
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct StageCompletedEvent {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    /// Stages of the job completed so far, including this one
+    #[prost(uint32, tag = "2")]
+    pub completed_stages: u32,
+    #[prost(uint32, tag = "3")]
+    pub total_stages: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FilePartitionMetadata {
     #[prost(string, repeated, tag = "1")]
     pub filename: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
@@ -983,37 +1602,416 @@ pub struct CleanJobDataParams {
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct CleanJobDataResult {}
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct LaunchTaskParams {
-    /// Allow to launch a task set to an executor at once
-    #[prost(message, repeated, tag = "1")]
-    pub tasks: ::prost::alloc::vec::Vec<TaskDefinition>,
-    #[prost(string, tag = "2")]
-    pub scheduler_id: ::prost::alloc::string::String,
-}
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct LaunchMultiTaskParams {
-    /// Allow to launch a task set to an executor at once
-    #[prost(message, repeated, tag = "1")]
-    pub multi_tasks: ::prost::alloc::vec::Vec<MultiTaskDefinition>,
-    #[prost(string, tag = "2")]
-    pub scheduler_id: ::prost::alloc::string::String,
-}
-#[derive(Clone, Copy, PartialEq, ::prost::Message)]
-pub struct LaunchTaskResult {
-    /// TODO when part of the task set are scheduled successfully
-    #[prost(bool, tag = "1")]
-    pub success: bool,
+pub struct ReleaseJobResultsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
 }
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
-pub struct LaunchMultiTaskResult {
-    /// TODO when part of the task set are scheduled successfully
+pub struct ReleaseJobResultsResult {
+    /// Whether the results of the job were still retained by the executors
     #[prost(bool, tag = "1")]
-    pub success: bool,
+    pub released: bool,
+    /// Size of the released results in bytes, as reported by the executors
+    #[prost(uint64, tag = "2")]
+    pub released_bytes: u64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct CancelTasksParams {
-    #[prost(message, repeated, tag = "1")]
-    pub task_infos: ::prost::alloc::vec::Vec<RunningTaskInfo>,
+pub struct PurgeJobsParams {
+    /// Completed jobs to purge
+    #[prost(string, repeated, tag = "1")]
+    pub job_id: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Also purge all the completed jobs ended before this timestamp (milliseconds), if set
+    #[prost(uint64, optional, tag = "2")]
+    pub ended_before: ::core::option::Option<u64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PurgeJobsResult {
+    /// Jobs which have been purged, unknown and not yet completed jobs are skipped
+    #[prost(string, repeated, tag = "1")]
+    pub job_id: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTaskPlanParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// Hash of the plan dispatched by reference with the tasks of the job
+    #[prost(string, tag = "2")]
+    pub plan_hash: ::prost::alloc::string::String,
+    /// Offset of the chunk of the encoded plan to fetch
+    #[prost(uint64, tag = "3")]
+    pub offset: u64,
+    /// Maximum size of the chunk, the rest of the plan if 0
+    #[prost(uint64, tag = "4")]
+    pub max_bytes: u64,
+    /// Executor fetching the plan, with its registration token
+    #[prost(string, tag = "5")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub registration_token: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTaskPlanResult {
+    /// Chunk of the encoded plan starting at the requested offset
+    #[prost(bytes = "vec", tag = "1")]
+    pub plan: ::prost::alloc::vec::Vec<u8>,
+    /// Size of the whole encoded plan
+    #[prost(uint64, tag = "2")]
+    pub total_bytes: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LeaseScanFileParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "3")]
+    pub partition_id: u32,
+    /// Number of the files the task scanned before this one
+    #[prost(uint64, tag = "4")]
+    pub position: u64,
+    /// Executor running the task, with its registration token
+    #[prost(string, tag = "5")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub registration_token: ::prost::alloc::string::String,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct LeaseScanFileResult {
+    /// Index of the file among the files of all the partitions of the scan, none once
+    /// there are no files left to scan
+    #[prost(uint64, optional, tag = "1")]
+    pub file: ::core::option::Option<u64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJobPlanParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobPlanStage {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub stage_attempt_num: u32,
+    /// Number of tasks of the stage, 0 if the stage was never resolved
+    #[prost(uint32, tag = "3")]
+    pub partitions: u32,
+    /// Stages whose output the stage reads
+    #[prost(uint32, repeated, tag = "4")]
+    pub input_stages: ::prost::alloc::vec::Vec<u32>,
+    /// Stages reading the output of the stage, empty for the final stage
+    #[prost(uint32, repeated, tag = "5")]
+    pub output_links: ::prost::alloc::vec::Vec<u32>,
+    /// PhysicalPlanNode of the stage as last run, with the runtime predicates and shuffle
+    /// reads resolved while the job ran
+    #[prost(bytes = "vec", tag = "6")]
+    pub plan: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJobPlanResult {
+    /// Stages of the job, ordered by stage ID
+    #[prost(message, repeated, tag = "1")]
+    pub stages: ::prost::alloc::vec::Vec<JobPlanStage>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SampleStageParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+    /// Partition of the stage to execute, all of them in order until enough rows are read
+    /// if unset
+    #[prost(uint32, optional, tag = "3")]
+    pub partition_id: ::core::option::Option<u32>,
+    /// Maximum number of rows of the sample, 100 if 0, capped by the scheduler
+    #[prost(uint64, tag = "4")]
+    pub limit: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SampleStageResult {
+    /// Rows of the output of the stage, in Arrow IPC stream format, empty if there are
+    /// none
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub num_rows: u64,
+    /// Partitions of the stage the rows were read from
+    #[prost(uint32, repeated, tag = "3")]
+    pub partitions: ::prost::alloc::vec::Vec<u32>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJobMetricsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StageMetrics {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    /// Unresolved, Resolved, Running, Successful or Failed
+    #[prost(string, tag = "2")]
+    pub status: ::prost::alloc::string::String,
+    /// Number of tasks of the stage, 0 if the stage is not resolved yet
+    #[prost(uint32, tag = "3")]
+    pub partitions: u32,
+    /// Number of tasks of the stage which completed successfully
+    #[prost(uint32, tag = "4")]
+    pub completed_tasks: u32,
+    /// Metrics of the completed tasks of the stage summed by name across its operators,
+    /// e.g. elapsed_compute in nanoseconds. output_rows is the number of rows the stage
+    /// wrote and output_bytes the number of bytes of its shuffle files
+    #[prost(map = "string, uint64", tag = "5")]
+    pub metrics: ::std::collections::HashMap<::prost::alloc::string::String, u64>,
+    /// Plan of the stage, each operator annotated with its metrics combined across the
+    /// completed tasks
+    #[prost(string, tag = "6")]
+    pub plan: ::prost::alloc::string::String,
+    /// Completed tasks of the stage, ordered by partition
+    #[prost(message, repeated, tag = "7")]
+    pub tasks: ::prost::alloc::vec::Vec<TaskMetrics>,
+    /// IDs of the stages whose outputs the stage reads
+    #[prost(uint32, repeated, tag = "8")]
+    pub input_stages: ::prost::alloc::vec::Vec<u32>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskMetrics {
+    #[prost(uint32, tag = "1")]
+    pub partition_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub task_id: u32,
+    #[prost(string, tag = "3")]
+    pub executor_id: ::prost::alloc::string::String,
+    /// The time the executor started and finished to run the task, in milliseconds
+    #[prost(uint64, tag = "4")]
+    pub start_exec_time: u64,
+    #[prost(uint64, tag = "5")]
+    pub end_exec_time: u64,
+    /// Metrics of the task summed by name across the operators of the stage
+    #[prost(map = "string, uint64", tag = "6")]
+    pub metrics: ::std::collections::HashMap<::prost::alloc::string::String, u64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJobMetricsResult {
+    /// Stages of the job, ordered by stage ID
+    #[prost(message, repeated, tag = "1")]
+    pub stages: ::prost::alloc::vec::Vec<StageMetrics>,
+    /// Metrics of the stages of the job summed by name
+    #[prost(map = "string, uint64", tag = "2")]
+    pub metrics: ::std::collections::HashMap<::prost::alloc::string::String, u64>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetResultsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// Index of the first row of the results to return
+    #[prost(uint64, tag = "2")]
+    pub offset: u64,
+    /// Maximum number of rows to return, 0 for the maximum number of rows of a page
+    #[prost(uint64, tag = "3")]
+    pub max_rows: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetResultsResult {
+    /// Rows of the page in Arrow IPC stream format, empty when no rows remain
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub num_rows: u64,
+    /// Whether the page holds the last rows of the results
+    #[prost(bool, tag = "3")]
+    pub end_of_results: bool,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ListClusterTablesParams {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListClusterTablesResult {
+    /// Tables of the catalog of the cluster, ordered by name
+    #[prost(message, repeated, tag = "1")]
+    pub tables: ::prost::alloc::vec::Vec<ClusterTableNode>,
+}
+/// Executor slots held back from the other jobs for the jobs running with a reservation,
+/// until it expires
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SlotReservation {
+    #[prost(string, tag = "1")]
+    pub reservation_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub slots: u32,
+    /// Timestamp in milliseconds at which the reservation expires
+    #[prost(uint64, tag = "3")]
+    pub expires_at: u64,
+    /// Principal which made the reservation, empty if made anonymously
+    #[prost(string, tag = "4")]
+    pub owner: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "5")]
+    pub created_at: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReserveSlotsParams {
+    /// ID the jobs set with ballista.job.slot_reservation to run with the reservation, the
+    /// reservation with the same ID being replaced
+    #[prost(string, tag = "1")]
+    pub reservation_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub slots: u32,
+    /// Seconds until the reservation expires
+    #[prost(uint64, tag = "3")]
+    pub ttl_seconds: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReserveSlotsResult {
+    #[prost(message, optional, tag = "1")]
+    pub reservation: ::core::option::Option<SlotReservation>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelSlotReservationParams {
+    #[prost(string, tag = "1")]
+    pub reservation_id: ::prost::alloc::string::String,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CancelSlotReservationResult {
+    /// Whether the reservation existed
+    #[prost(bool, tag = "1")]
+    pub cancelled: bool,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ListSlotReservationsParams {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SlotReservationUsage {
+    #[prost(message, optional, tag = "1")]
+    pub reservation: ::core::option::Option<SlotReservation>,
+    /// Running jobs of the reservation
+    #[prost(string, repeated, tag = "2")]
+    pub job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Slots of the reservation taken by the running tasks of its jobs
+    #[prost(uint32, tag = "3")]
+    pub used_slots: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListSlotReservationsResult {
+    /// Reservations not expired yet, ordered by ID
+    #[prost(message, repeated, tag = "1")]
+    pub reservations: ::prost::alloc::vec::Vec<SlotReservationUsage>,
+}
+/// Size of a table as of the latest scan of the whole table by a successful job, which
+/// the planner falls back to for the scans of the table it has no statistics of
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TableStatistics {
+    /// Object store URL and directory of the files of the table
+    #[prost(string, tag = "1")]
+    pub location: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub rows: u64,
+    /// Size of the rows in memory, estimated from the types of the columns
+    #[prost(uint64, tag = "3")]
+    pub bytes: u64,
+    /// Job which scanned the table, and the time in milliseconds it succeeded at
+    #[prost(string, tag = "4")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "5")]
+    pub recorded_at: u64,
+}
+/// Calibration of the cost model of the exchanges from the completed jobs, which the
+/// jobs are planned with unless their sessions set it
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ExchangeCalibrationState {
+    /// Ratio of the compression ratios the shuffles achieved to those expected, smoothed
+    #[prost(double, optional, tag = "1")]
+    pub compression_ratio_scale: ::core::option::Option<f64>,
+    /// Peak network bandwidth of the executors, 0 until measured
+    #[prost(uint64, tag = "2")]
+    pub network_bytes_per_second: u64,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct GetClusterUtilizationParams {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetClusterUtilizationResult {
+    #[prost(message, optional, tag = "1")]
+    pub utilization: ::core::option::Option<ClusterUtilization>,
+}
+/// Demand for and utilization of the resources of the cluster, as of the latest
+/// heartbeats of the alive executors
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClusterUtilization {
+    #[prost(uint32, tag = "1")]
+    pub running_jobs: u32,
+    #[prost(uint32, tag = "2")]
+    pub queued_jobs: u32,
+    /// Tasks of the running jobs which are ready to run, but not bound to an executor yet
+    #[prost(uint64, tag = "3")]
+    pub pending_tasks: u64,
+    #[prost(uint32, tag = "4")]
+    pub task_slots: u32,
+    #[prost(uint32, tag = "5")]
+    pub running_tasks: u32,
+    #[prost(uint32, tag = "6")]
+    pub queued_tasks: u32,
+    /// Average CPU usage of the executors reporting it, between 0 and 1
+    #[prost(double, tag = "7")]
+    pub cpu_usage: f64,
+    #[prost(uint64, tag = "8")]
+    pub used_memory_bytes: u64,
+    #[prost(uint64, tag = "9")]
+    pub total_memory_bytes: u64,
+    #[prost(uint64, tag = "10")]
+    pub shuffle_disk_used_bytes: u64,
+    #[prost(message, repeated, tag = "11")]
+    pub executors: ::prost::alloc::vec::Vec<ExecutorUtilization>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecutorUtilization {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub task_slots: u32,
+    #[prost(uint32, tag = "3")]
+    pub running_tasks: u32,
+    #[prost(uint32, tag = "4")]
+    pub queued_tasks: u32,
+    #[prost(double, tag = "5")]
+    pub cpu_usage: f64,
+    #[prost(uint64, tag = "6")]
+    pub used_memory_bytes: u64,
+    #[prost(uint64, tag = "7")]
+    pub total_memory_bytes: u64,
+    #[prost(uint64, tag = "8")]
+    pub shuffle_disk_used_bytes: u64,
+    /// 0 if the disk usage of the executor isn't bounded
+    #[prost(uint64, tag = "9")]
+    pub shuffle_disk_max_bytes: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LaunchTaskParams {
+    /// Allow to launch a task set to an executor at once
+    #[prost(message, repeated, tag = "1")]
+    pub tasks: ::prost::alloc::vec::Vec<TaskDefinition>,
+    #[prost(string, tag = "2")]
+    pub scheduler_id: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LaunchMultiTaskParams {
+    /// Allow to launch a task set to an executor at once
+    #[prost(message, repeated, tag = "1")]
+    pub multi_tasks: ::prost::alloc::vec::Vec<MultiTaskDefinition>,
+    #[prost(string, tag = "2")]
+    pub scheduler_id: ::prost::alloc::string::String,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct LaunchTaskResult {
+    /// TODO when part of the task set are scheduled successfully
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct LaunchMultiTaskResult {
+    /// TODO when part of the task set are scheduled successfully
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelTasksParams {
+    #[prost(message, repeated, tag = "1")]
+    pub task_infos: ::prost::alloc::vec::Vec<RunningTaskInfo>,
 }
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct CancelTasksResult {
@@ -1028,6 +2026,55 @@ pub struct RemoveJobDataParams {
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
 pub struct RemoveJobDataResult {}
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoveTaskOutputsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub paths: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RemoveTaskOutputsResult {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTaskLogsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub task_id: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTaskLogsResult {
+    /// Whether the executor holds any logs for the requested task
+    #[prost(bool, tag = "1")]
+    pub found: bool,
+    #[prost(string, repeated, tag = "2")]
+    pub lines: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetProfileParams {
+    /// Duration of the profile. When scoped to a task, the maximum time to wait for the task
+    #[prost(uint32, tag = "1")]
+    pub seconds: u32,
+    /// Sampling frequency in Hz
+    #[prost(uint32, tag = "2")]
+    pub frequency: u32,
+    #[prost(enumeration = "ProfileFormat", tag = "3")]
+    pub format: i32,
+    /// Only profile while the given task is running
+    #[prost(string, tag = "4")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, optional, tag = "5")]
+    pub task_id: ::core::option::Option<u32>,
+    /// Heap profiles are a snapshot of the sampled allocations which are still live,
+    /// so they ignore the duration, frequency and task
+    #[prost(enumeration = "ProfileType", tag = "6")]
+    pub profile_type: i32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetProfileResult {
+    #[prost(bytes = "vec", tag = "1")]
+    pub profile: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RunningTaskInfo {
     #[prost(uint32, tag = "1")]
     pub task_id: u32,
@@ -1038,6 +2085,120 @@ pub struct RunningTaskInfo {
     #[prost(uint32, tag = "4")]
     pub partition_id: u32,
 }
+/// Codec compressing shuffle files, and the shuffle partitions fetched over Flight
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ShuffleCompression {
+    /// Zero, so that the partitions of older versions, which always compressed shuffle
+    /// files with LZ4, are read as such
+    Lz4Frame = 0,
+    Zstd = 1,
+    Uncompressed = 2,
+}
+impl ShuffleCompression {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Lz4Frame => "LZ4_FRAME",
+            Self::Zstd => "ZSTD",
+            Self::Uncompressed => "UNCOMPRESSED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "LZ4_FRAME" => Some(Self::Lz4Frame),
+            "ZSTD" => Some(Self::Zstd),
+            "UNCOMPRESSED" => Some(Self::Uncompressed),
+            _ => None,
+        }
+    }
+}
+/// Format of the plans dispatched with tasks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum TaskPlanFormat {
+    /// The physical plan of the stage of the tasks
+    PhysicalPlan = 0,
+    /// The optimized logical plan of the whole job, which executors plan physically and
+    /// split into stages the same way as the scheduler, running the stage of the tasks
+    LogicalPlan = 1,
+}
+impl TaskPlanFormat {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::PhysicalPlan => "PHYSICAL_PLAN",
+            Self::LogicalPlan => "LOGICAL_PLAN",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "PHYSICAL_PLAN" => Some(Self::PhysicalPlan),
+            "LOGICAL_PLAN" => Some(Self::LogicalPlan),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ProfileFormat {
+    Flamegraph = 0,
+    Pprof = 1,
+}
+impl ProfileFormat {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Flamegraph => "FLAMEGRAPH",
+            Self::Pprof => "PPROF",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "FLAMEGRAPH" => Some(Self::Flamegraph),
+            "PPROF" => Some(Self::Pprof),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ProfileType {
+    Cpu = 0,
+    Heap = 1,
+}
+impl ProfileType {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Cpu => "CPU",
+            Self::Heap => "HEAP",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CPU" => Some(Self::Cpu),
+            "HEAP" => Some(Self::Heap),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod scheduler_grpc_client {
     #![allow(
@@ -1447,104 +2608,42 @@ pub mod scheduler_grpc_client {
                 );
             self.inner.unary(req, path, codec).await
         }
-    }
-}
-/// Generated client implementations.
-pub mod executor_grpc_client {
-    #![allow(
-        unused_variables,
-        dead_code,
-        missing_docs,
-        clippy::wildcard_imports,
-        clippy::let_unit_value,
-    )]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
-    #[derive(Debug, Clone)]
-    pub struct ExecutorGrpcClient<T> {
-        inner: tonic::client::Grpc<T>,
-    }
-    impl ExecutorGrpcClient<tonic::transport::Channel> {
-        /// Attempt to create a new client by connecting to a given endpoint.
-        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
-        where
-            D: TryInto<tonic::transport::Endpoint>,
-            D::Error: Into<StdError>,
-        {
-            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
-            Ok(Self::new(conn))
-        }
-    }
-    impl<T> ExecutorGrpcClient<T>
-    where
-        T: tonic::client::GrpcService<tonic::body::BoxBody>,
-        T::Error: Into<StdError>,
-        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
-        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
-    {
-        pub fn new(inner: T) -> Self {
-            let inner = tonic::client::Grpc::new(inner);
-            Self { inner }
-        }
-        pub fn with_origin(inner: T, origin: Uri) -> Self {
-            let inner = tonic::client::Grpc::with_origin(inner, origin);
-            Self { inner }
-        }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> ExecutorGrpcClient<InterceptedService<T, F>>
-        where
-            F: tonic::service::Interceptor,
-            T::ResponseBody: Default,
-            T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
-                >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + std::marker::Send + std::marker::Sync,
-        {
-            ExecutorGrpcClient::new(InterceptedService::new(inner, interceptor))
-        }
-        /// Compress requests with the given encoding.
-        ///
-        /// This requires the server to support it otherwise it might respond with an
-        /// error.
-        #[must_use]
-        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.send_compressed(encoding);
-            self
-        }
-        /// Enable decompressing responses.
-        #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.accept_compressed(encoding);
-            self
-        }
-        /// Limits the maximum size of a decoded message.
-        ///
-        /// Default: `4MB`
-        #[must_use]
-        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_decoding_message_size(limit);
-            self
-        }
-        /// Limits the maximum size of an encoded message.
-        ///
-        /// Default: `usize::MAX`
-        #[must_use]
-        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_encoding_message_size(limit);
-            self
+        /// Acknowledge the results of a successful job were fetched, for the executors to delete them
+        pub async fn release_job_results(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ReleaseJobResultsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ReleaseJobResultsResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/ReleaseJobResults",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "ReleaseJobResults",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
         }
-        pub async fn launch_task(
+        /// Purge completed jobs from the scheduler state, archiving them if an archive is configured
+        pub async fn purge_jobs(
             &mut self,
-            request: impl tonic::IntoRequest<super::LaunchTaskParams>,
+            request: impl tonic::IntoRequest<super::PurgeJobsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::LaunchTaskResult>,
+            tonic::Response<super::PurgeJobsResult>,
             tonic::Status,
         > {
             self.inner
@@ -1557,18 +2656,19 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/LaunchTask",
+                "/ballista.protobuf.SchedulerGrpc/PurgeJobs",
             );
             let mut req = request.into_request();
             req.extensions_mut()
-                .insert(GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchTask"));
+                .insert(GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "PurgeJobs"));
             self.inner.unary(req, path, codec).await
         }
-        pub async fn launch_multi_task(
+        /// Used by executors to fetch the plans dispatched by reference with their tasks
+        pub async fn get_task_plan(
             &mut self,
-            request: impl tonic::IntoRequest<super::LaunchMultiTaskParams>,
+            request: impl tonic::IntoRequest<super::GetTaskPlanParams>,
         ) -> std::result::Result<
-            tonic::Response<super::LaunchMultiTaskResult>,
+            tonic::Response<super::GetTaskPlanResult>,
             tonic::Status,
         > {
             self.inner
@@ -1581,20 +2681,21 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/LaunchMultiTask",
+                "/ballista.protobuf.SchedulerGrpc/GetTaskPlan",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchMultiTask"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "GetTaskPlan"),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn stop_executor(
+        /// Get the distributed plan a completed job ran, e.g. to compare the plans of queries
+        pub async fn get_job_plan(
             &mut self,
-            request: impl tonic::IntoRequest<super::StopExecutorParams>,
+            request: impl tonic::IntoRequest<super::GetJobPlanParams>,
         ) -> std::result::Result<
-            tonic::Response<super::StopExecutorResult>,
+            tonic::Response<super::GetJobPlanResult>,
             tonic::Status,
         > {
             self.inner
@@ -1607,20 +2708,23 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/StopExecutor",
+                "/ballista.protobuf.SchedulerGrpc/GetJobPlan",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "StopExecutor"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "GetJobPlan"),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn cancel_tasks(
+        /// Execute a stage of a job in isolation, or a single task of it, and return a sample
+        /// of its output, to find which stage of a pipeline produces unexpected values. Only
+        /// the stages which scan, filter and project their inputs can be sampled
+        pub async fn sample_stage(
             &mut self,
-            request: impl tonic::IntoRequest<super::CancelTasksParams>,
+            request: impl tonic::IntoRequest<super::SampleStageParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CancelTasksResult>,
+            tonic::Response<super::SampleStageResult>,
             tonic::Status,
         > {
             self.inner
@@ -1633,20 +2737,21 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/CancelTasks",
+                "/ballista.protobuf.SchedulerGrpc/SampleStage",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "CancelTasks"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "SampleStage"),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn remove_job_data(
+        /// Get the metrics of the completed tasks of a job, aggregated per stage and for the job
+        pub async fn get_job_metrics(
             &mut self,
-            request: impl tonic::IntoRequest<super::RemoveJobDataParams>,
+            request: impl tonic::IntoRequest<super::GetJobMetricsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::RemoveJobDataResult>,
+            tonic::Response<super::GetJobMetricsResult>,
             tonic::Status,
         > {
             self.inner
@@ -1659,150 +2764,324 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/RemoveJobData",
+                "/ballista.protobuf.SchedulerGrpc/GetJobMetrics",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "RemoveJobData"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "GetJobMetrics"),
                 );
             self.inner.unary(req, path, codec).await
         }
-    }
-}
-/// Generated server implementations.
-pub mod scheduler_grpc_server {
-    #![allow(
-        unused_variables,
-        dead_code,
-        missing_docs,
-        clippy::wildcard_imports,
-        clippy::let_unit_value,
-    )]
-    use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with SchedulerGrpcServer.
-    #[async_trait]
-    pub trait SchedulerGrpc: std::marker::Send + std::marker::Sync + 'static {
-        /// Executors must poll the scheduler for heartbeat and to receive tasks
-        async fn poll_work(
-            &self,
-            request: tonic::Request<super::PollWorkParams>,
-        ) -> std::result::Result<tonic::Response<super::PollWorkResult>, tonic::Status>;
-        async fn register_executor(
-            &self,
-            request: tonic::Request<super::RegisterExecutorParams>,
-        ) -> std::result::Result<
-            tonic::Response<super::RegisterExecutorResult>,
-            tonic::Status,
-        >;
-        /// Push-based task scheduler will only leverage this interface
-        /// rather than the PollWork interface to report executor states
-        async fn heart_beat_from_executor(
-            &self,
-            request: tonic::Request<super::HeartBeatParams>,
-        ) -> std::result::Result<tonic::Response<super::HeartBeatResult>, tonic::Status>;
-        async fn update_task_status(
-            &self,
-            request: tonic::Request<super::UpdateTaskStatusParams>,
+        /// Get a page of the results of a successful job, proxied from the executors holding
+        /// them, for clients which can't fetch them over Flight
+        pub async fn get_results(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetResultsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::UpdateTaskStatusResult>,
+            tonic::Response<super::GetResultsResult>,
             tonic::Status,
-        >;
-        async fn create_session(
-            &self,
-            request: tonic::Request<super::CreateSessionParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/GetResults",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "GetResults"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// List the tables of the catalog of the cluster, shared by all the sessions
+        pub async fn list_cluster_tables(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListClusterTablesParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CreateSessionResult>,
+            tonic::Response<super::ListClusterTablesResult>,
             tonic::Status,
-        >;
-        async fn update_session(
-            &self,
-            request: tonic::Request<super::UpdateSessionParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/ListClusterTables",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "ListClusterTables",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Get the demand for and utilization of the resources of the cluster, e.g. to drive
+        /// an autoscaler
+        pub async fn get_cluster_utilization(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetClusterUtilizationParams>,
         ) -> std::result::Result<
-            tonic::Response<super::UpdateSessionResult>,
+            tonic::Response<super::GetClusterUtilizationResult>,
             tonic::Status,
-        >;
-        async fn remove_session(
-            &self,
-            request: tonic::Request<super::RemoveSessionParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/GetClusterUtilization",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "GetClusterUtilization",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Reserve executor slots ahead of the jobs running with the reservation, e.g. for a
+        /// latency critical scheduled report not to queue behind ad-hoc jobs
+        pub async fn reserve_slots(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ReserveSlotsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::RemoveSessionResult>,
+            tonic::Response<super::ReserveSlotsResult>,
             tonic::Status,
-        >;
-        async fn execute_query(
-            &self,
-            request: tonic::Request<super::ExecuteQueryParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/ReserveSlots",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "ReserveSlots"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn cancel_slot_reservation(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CancelSlotReservationParams>,
         ) -> std::result::Result<
-            tonic::Response<super::ExecuteQueryResult>,
+            tonic::Response<super::CancelSlotReservationResult>,
             tonic::Status,
-        >;
-        async fn get_job_status(
-            &self,
-            request: tonic::Request<super::GetJobStatusParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/CancelSlotReservation",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "CancelSlotReservation",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn list_slot_reservations(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListSlotReservationsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::GetJobStatusResult>,
+            tonic::Response<super::ListSlotReservationsResult>,
             tonic::Status,
-        >;
-        /// Used by Executor to tell Scheduler it is stopped.
-        async fn executor_stopped(
-            &self,
-            request: tonic::Request<super::ExecutorStoppedParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/ListSlotReservations",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "ListSlotReservations",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Used by executors to lease the next file to scan to the tasks of stages with
+        /// splittable scans
+        pub async fn lease_scan_file(
+            &mut self,
+            request: impl tonic::IntoRequest<super::LeaseScanFileParams>,
         ) -> std::result::Result<
-            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Response<super::LeaseScanFileResult>,
             tonic::Status,
-        >;
-        async fn cancel_job(
-            &self,
-            request: tonic::Request<super::CancelJobParams>,
-        ) -> std::result::Result<tonic::Response<super::CancelJobResult>, tonic::Status>;
-        async fn clean_job_data(
-            &self,
-            request: tonic::Request<super::CleanJobDataParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/LeaseScanFile",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "LeaseScanFile"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Streams the status changes and stage completions of a job until it ends, starting
+        /// with its current status, instead of clients polling GetJobStatus
+        pub async fn subscribe_job_events(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SubscribeJobEventsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CleanJobDataResult>,
+            tonic::Response<tonic::codec::Streaming<super::JobEvent>>,
             tonic::Status,
-        >;
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/SubscribeJobEvents",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "SubscribeJobEvents",
+                    ),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
     }
-    #[derive(Debug)]
-    pub struct SchedulerGrpcServer<T> {
-        inner: Arc<T>,
-        accept_compression_encodings: EnabledCompressionEncodings,
-        send_compression_encodings: EnabledCompressionEncodings,
-        max_decoding_message_size: Option<usize>,
-        max_encoding_message_size: Option<usize>,
+}
+/// Generated client implementations.
+pub mod executor_grpc_client {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    #[derive(Debug, Clone)]
+    pub struct ExecutorGrpcClient<T> {
+        inner: tonic::client::Grpc<T>,
     }
-    impl<T> SchedulerGrpcServer<T> {
+    impl ExecutorGrpcClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> ExecutorGrpcClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
+    {
         pub fn new(inner: T) -> Self {
-            Self::from_arc(Arc::new(inner))
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
         }
-        pub fn from_arc(inner: Arc<T>) -> Self {
-            Self {
-                inner,
-                accept_compression_encodings: Default::default(),
-                send_compression_encodings: Default::default(),
-                max_decoding_message_size: None,
-                max_encoding_message_size: None,
-            }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
         }
         pub fn with_interceptor<F>(
             inner: T,
             interceptor: F,
-        ) -> InterceptedService<Self, F>
+        ) -> ExecutorGrpcClient<InterceptedService<T, F>>
         where
             F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + std::marker::Send + std::marker::Sync,
         {
-            InterceptedService::new(Self::new(inner), interceptor)
+            ExecutorGrpcClient::new(InterceptedService::new(inner, interceptor))
         }
-        /// Enable decompressing requests with the given encoding.
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
         #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.accept_compression_encodings.enable(encoding);
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
             self
         }
-        /// Compress responses with the given encoding, if the client supports it.
+        /// Enable decompressing responses.
         #[must_use]
-        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.send_compression_encodings.enable(encoding);
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
             self
         }
         /// Limits the maximum size of a decoded message.
@@ -1810,7 +3089,7 @@ pub mod scheduler_grpc_server {
         /// Default: `4MB`
         #[must_use]
         pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
-            self.max_decoding_message_size = Some(limit);
+            self.inner = self.inner.max_decoding_message_size(limit);
             self
         }
         /// Limits the maximum size of an encoded message.
@@ -1818,46 +3097,1164 @@ pub mod scheduler_grpc_server {
         /// Default: `usize::MAX`
         #[must_use]
         pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
-            self.max_encoding_message_size = Some(limit);
+            self.inner = self.inner.max_encoding_message_size(limit);
             self
         }
-    }
-    impl<T, B> tonic::codegen::Service<http::Request<B>> for SchedulerGrpcServer<T>
-    where
-        T: SchedulerGrpc,
-        B: Body + std::marker::Send + 'static,
-        B::Error: Into<StdError> + std::marker::Send + 'static,
-    {
-        type Response = http::Response<tonic::body::BoxBody>;
-        type Error = std::convert::Infallible;
-        type Future = BoxFuture<Self::Response, Self::Error>;
-        fn poll_ready(
+        pub async fn launch_task(
             &mut self,
-            _cx: &mut Context<'_>,
-        ) -> Poll<std::result::Result<(), Self::Error>> {
-            Poll::Ready(Ok(()))
+            request: impl tonic::IntoRequest<super::LaunchTaskParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::LaunchTaskResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/LaunchTask",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchTask"));
+            self.inner.unary(req, path, codec).await
         }
-        fn call(&mut self, req: http::Request<B>) -> Self::Future {
-            match req.uri().path() {
-                "/ballista.protobuf.SchedulerGrpc/PollWork" => {
+        pub async fn launch_multi_task(
+            &mut self,
+            request: impl tonic::IntoRequest<super::LaunchMultiTaskParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::LaunchMultiTaskResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/LaunchMultiTask",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchMultiTask"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn stop_executor(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StopExecutorParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::StopExecutorResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/StopExecutor",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "StopExecutor"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn cancel_tasks(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CancelTasksParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelTasksResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/CancelTasks",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "CancelTasks"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn remove_job_data(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemoveJobDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveJobDataResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/RemoveJobData",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "RemoveJobData"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn remove_task_outputs(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemoveTaskOutputsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveTaskOutputsResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/RemoveTaskOutputs",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.ExecutorGrpc",
+                        "RemoveTaskOutputs",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_task_logs(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetTaskLogsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetTaskLogsResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/GetTaskLogs",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "GetTaskLogs"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_profile(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetProfileParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetProfileResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/GetProfile",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "GetProfile"));
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod scheduler_grpc_server {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with SchedulerGrpcServer.
+    #[async_trait]
+    pub trait SchedulerGrpc: std::marker::Send + std::marker::Sync + 'static {
+        /// Executors must poll the scheduler for heartbeat and to receive tasks
+        async fn poll_work(
+            &self,
+            request: tonic::Request<super::PollWorkParams>,
+        ) -> std::result::Result<tonic::Response<super::PollWorkResult>, tonic::Status>;
+        async fn register_executor(
+            &self,
+            request: tonic::Request<super::RegisterExecutorParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RegisterExecutorResult>,
+            tonic::Status,
+        >;
+        /// Push-based task scheduler will only leverage this interface
+        /// rather than the PollWork interface to report executor states
+        async fn heart_beat_from_executor(
+            &self,
+            request: tonic::Request<super::HeartBeatParams>,
+        ) -> std::result::Result<tonic::Response<super::HeartBeatResult>, tonic::Status>;
+        async fn update_task_status(
+            &self,
+            request: tonic::Request<super::UpdateTaskStatusParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateTaskStatusResult>,
+            tonic::Status,
+        >;
+        async fn create_session(
+            &self,
+            request: tonic::Request<super::CreateSessionParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CreateSessionResult>,
+            tonic::Status,
+        >;
+        async fn update_session(
+            &self,
+            request: tonic::Request<super::UpdateSessionParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateSessionResult>,
+            tonic::Status,
+        >;
+        async fn remove_session(
+            &self,
+            request: tonic::Request<super::RemoveSessionParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveSessionResult>,
+            tonic::Status,
+        >;
+        async fn execute_query(
+            &self,
+            request: tonic::Request<super::ExecuteQueryParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExecuteQueryResult>,
+            tonic::Status,
+        >;
+        async fn get_job_status(
+            &self,
+            request: tonic::Request<super::GetJobStatusParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetJobStatusResult>,
+            tonic::Status,
+        >;
+        /// Used by Executor to tell Scheduler it is stopped.
+        async fn executor_stopped(
+            &self,
+            request: tonic::Request<super::ExecutorStoppedParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Status,
+        >;
+        async fn cancel_job(
+            &self,
+            request: tonic::Request<super::CancelJobParams>,
+        ) -> std::result::Result<tonic::Response<super::CancelJobResult>, tonic::Status>;
+        async fn clean_job_data(
+            &self,
+            request: tonic::Request<super::CleanJobDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CleanJobDataResult>,
+            tonic::Status,
+        >;
+        /// Acknowledge the results of a successful job were fetched, for the executors to delete them
+        async fn release_job_results(
+            &self,
+            request: tonic::Request<super::ReleaseJobResultsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ReleaseJobResultsResult>,
+            tonic::Status,
+        >;
+        /// Purge completed jobs from the scheduler state, archiving them if an archive is configured
+        async fn purge_jobs(
+            &self,
+            request: tonic::Request<super::PurgeJobsParams>,
+        ) -> std::result::Result<tonic::Response<super::PurgeJobsResult>, tonic::Status>;
+        /// Used by executors to fetch the plans dispatched by reference with their tasks
+        async fn get_task_plan(
+            &self,
+            request: tonic::Request<super::GetTaskPlanParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetTaskPlanResult>,
+            tonic::Status,
+        >;
+        /// Get the distributed plan a completed job ran, e.g. to compare the plans of queries
+        async fn get_job_plan(
+            &self,
+            request: tonic::Request<super::GetJobPlanParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetJobPlanResult>,
+            tonic::Status,
+        >;
+        /// Execute a stage of a job in isolation, or a single task of it, and return a sample
+        /// of its output, to find which stage of a pipeline produces unexpected values. Only
+        /// the stages which scan, filter and project their inputs can be sampled
+        async fn sample_stage(
+            &self,
+            request: tonic::Request<super::SampleStageParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::SampleStageResult>,
+            tonic::Status,
+        >;
+        /// Get the metrics of the completed tasks of a job, aggregated per stage and for the job
+        async fn get_job_metrics(
+            &self,
+            request: tonic::Request<super::GetJobMetricsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetJobMetricsResult>,
+            tonic::Status,
+        >;
+        /// Get a page of the results of a successful job, proxied from the executors holding
+        /// them, for clients which can't fetch them over Flight
+        async fn get_results(
+            &self,
+            request: tonic::Request<super::GetResultsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetResultsResult>,
+            tonic::Status,
+        >;
+        /// List the tables of the catalog of the cluster, shared by all the sessions
+        async fn list_cluster_tables(
+            &self,
+            request: tonic::Request<super::ListClusterTablesParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListClusterTablesResult>,
+            tonic::Status,
+        >;
+        /// Get the demand for and utilization of the resources of the cluster, e.g. to drive
+        /// an autoscaler
+        async fn get_cluster_utilization(
+            &self,
+            request: tonic::Request<super::GetClusterUtilizationParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetClusterUtilizationResult>,
+            tonic::Status,
+        >;
+        /// Reserve executor slots ahead of the jobs running with the reservation, e.g. for a
+        /// latency critical scheduled report not to queue behind ad-hoc jobs
+        async fn reserve_slots(
+            &self,
+            request: tonic::Request<super::ReserveSlotsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ReserveSlotsResult>,
+            tonic::Status,
+        >;
+        async fn cancel_slot_reservation(
+            &self,
+            request: tonic::Request<super::CancelSlotReservationParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelSlotReservationResult>,
+            tonic::Status,
+        >;
+        async fn list_slot_reservations(
+            &self,
+            request: tonic::Request<super::ListSlotReservationsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListSlotReservationsResult>,
+            tonic::Status,
+        >;
+        /// Used by executors to lease the next file to scan to the tasks of stages with
+        /// splittable scans
+        async fn lease_scan_file(
+            &self,
+            request: tonic::Request<super::LeaseScanFileParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::LeaseScanFileResult>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the SubscribeJobEvents method.
+        type SubscribeJobEventsStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::JobEvent, tonic::Status>,
+            >
+            + std::marker::Send
+            + 'static;
+        /// Streams the status changes and stage completions of a job until it ends, starting
+        /// with its current status, instead of clients polling GetJobStatus
+        async fn subscribe_job_events(
+            &self,
+            request: tonic::Request<super::SubscribeJobEventsParams>,
+        ) -> std::result::Result<
+            tonic::Response<Self::SubscribeJobEventsStream>,
+            tonic::Status,
+        >;
+    }
+    #[derive(Debug)]
+    pub struct SchedulerGrpcServer<T> {
+        inner: Arc<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    impl<T> SchedulerGrpcServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for SchedulerGrpcServer<T>
+    where
+        T: SchedulerGrpc,
+        B: Body + std::marker::Send + 'static,
+        B::Error: Into<StdError> + std::marker::Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            match req.uri().path() {
+                "/ballista.protobuf.SchedulerGrpc/PollWork" => {
+                    #[allow(non_camel_case_types)]
+                    struct PollWorkSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::PollWorkParams>
+                    for PollWorkSvc<T> {
+                        type Response = super::PollWorkResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PollWorkParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::poll_work(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = PollWorkSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/RegisterExecutor" => {
+                    #[allow(non_camel_case_types)]
+                    struct RegisterExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::RegisterExecutorParams>
+                    for RegisterExecutorSvc<T> {
+                        type Response = super::RegisterExecutorResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RegisterExecutorParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::register_executor(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RegisterExecutorSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/HeartBeatFromExecutor" => {
+                    #[allow(non_camel_case_types)]
+                    struct HeartBeatFromExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::HeartBeatParams>
+                    for HeartBeatFromExecutorSvc<T> {
+                        type Response = super::HeartBeatResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HeartBeatParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::heart_beat_from_executor(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = HeartBeatFromExecutorSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/UpdateTaskStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateTaskStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::UpdateTaskStatusParams>
+                    for UpdateTaskStatusSvc<T> {
+                        type Response = super::UpdateTaskStatusResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateTaskStatusParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::update_task_status(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdateTaskStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/CreateSession" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::CreateSessionParams>
+                    for CreateSessionSvc<T> {
+                        type Response = super::CreateSessionResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateSessionParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::create_session(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CreateSessionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/UpdateSession" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::UpdateSessionParams>
+                    for UpdateSessionSvc<T> {
+                        type Response = super::UpdateSessionResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateSessionParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::update_session(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdateSessionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/RemoveSession" => {
+                    #[allow(non_camel_case_types)]
+                    struct RemoveSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::RemoveSessionParams>
+                    for RemoveSessionSvc<T> {
+                        type Response = super::RemoveSessionResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RemoveSessionParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::remove_session(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RemoveSessionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ExecuteQuery" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExecuteQuerySvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::ExecuteQueryParams>
+                    for ExecuteQuerySvc<T> {
+                        type Response = super::ExecuteQueryResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExecuteQueryParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::execute_query(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ExecuteQuerySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetJobStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetJobStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::GetJobStatusParams>
+                    for GetJobStatusSvc<T> {
+                        type Response = super::GetJobStatusResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetJobStatusParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::get_job_status(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetJobStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExecutorStoppedSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::ExecutorStoppedParams>
+                    for ExecutorStoppedSvc<T> {
+                        type Response = super::ExecutorStoppedResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExecutorStoppedParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::executor_stopped(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ExecutorStoppedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/CancelJob" => {
+                    #[allow(non_camel_case_types)]
+                    struct CancelJobSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::CancelJobParams>
+                    for CancelJobSvc<T> {
+                        type Response = super::CancelJobResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CancelJobParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::cancel_job(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CancelJobSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/CleanJobData" => {
+                    #[allow(non_camel_case_types)]
+                    struct CleanJobDataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::CleanJobDataParams>
+                    for CleanJobDataSvc<T> {
+                        type Response = super::CleanJobDataResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CleanJobDataParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::clean_job_data(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CleanJobDataSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ReleaseJobResults" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReleaseJobResultsSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::ReleaseJobResultsParams>
+                    for ReleaseJobResultsSvc<T> {
+                        type Response = super::ReleaseJobResultsResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ReleaseJobResultsParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::release_job_results(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ReleaseJobResultsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/PurgeJobs" => {
+                    #[allow(non_camel_case_types)]
+                    struct PurgeJobsSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::PurgeJobsParams>
+                    for PurgeJobsSvc<T> {
+                        type Response = super::PurgeJobsResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PurgeJobsParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::purge_jobs(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = PurgeJobsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetTaskPlan" => {
                     #[allow(non_camel_case_types)]
-                    struct PollWorkSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetTaskPlanSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::PollWorkParams>
-                    for PollWorkSvc<T> {
-                        type Response = super::PollWorkResult;
+                    > tonic::server::UnaryService<super::GetTaskPlanParams>
+                    for GetTaskPlanSvc<T> {
+                        type Response = super::GetTaskPlanResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::PollWorkParams>,
+                            request: tonic::Request<super::GetTaskPlanParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::poll_work(&inner, request).await
+                                <T as SchedulerGrpc>::get_task_plan(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -1868,7 +4265,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = PollWorkSvc(inner);
+                        let method = GetTaskPlanSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1884,26 +4281,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/RegisterExecutor" => {
+                "/ballista.protobuf.SchedulerGrpc/GetJobPlan" => {
                     #[allow(non_camel_case_types)]
-                    struct RegisterExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetJobPlanSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::RegisterExecutorParams>
-                    for RegisterExecutorSvc<T> {
-                        type Response = super::RegisterExecutorResult;
+                    > tonic::server::UnaryService<super::GetJobPlanParams>
+                    for GetJobPlanSvc<T> {
+                        type Response = super::GetJobPlanResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RegisterExecutorParams>,
+                            request: tonic::Request<super::GetJobPlanParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::register_executor(&inner, request)
-                                    .await
+                                <T as SchedulerGrpc>::get_job_plan(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -1914,7 +4310,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = RegisterExecutorSvc(inner);
+                        let method = GetJobPlanSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1930,29 +4326,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/HeartBeatFromExecutor" => {
+                "/ballista.protobuf.SchedulerGrpc/SampleStage" => {
                     #[allow(non_camel_case_types)]
-                    struct HeartBeatFromExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct SampleStageSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::HeartBeatParams>
-                    for HeartBeatFromExecutorSvc<T> {
-                        type Response = super::HeartBeatResult;
+                    > tonic::server::UnaryService<super::SampleStageParams>
+                    for SampleStageSvc<T> {
+                        type Response = super::SampleStageResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::HeartBeatParams>,
+                            request: tonic::Request<super::SampleStageParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::heart_beat_from_executor(
-                                        &inner,
-                                        request,
-                                    )
-                                    .await
+                                <T as SchedulerGrpc>::sample_stage(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -1963,7 +4355,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = HeartBeatFromExecutorSvc(inner);
+                        let method = SampleStageSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1979,26 +4371,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/UpdateTaskStatus" => {
+                "/ballista.protobuf.SchedulerGrpc/GetJobMetrics" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateTaskStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetJobMetricsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::UpdateTaskStatusParams>
-                    for UpdateTaskStatusSvc<T> {
-                        type Response = super::UpdateTaskStatusResult;
+                    > tonic::server::UnaryService<super::GetJobMetricsParams>
+                    for GetJobMetricsSvc<T> {
+                        type Response = super::GetJobMetricsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpdateTaskStatusParams>,
+                            request: tonic::Request<super::GetJobMetricsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::update_task_status(&inner, request)
-                                    .await
+                                <T as SchedulerGrpc>::get_job_metrics(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -2009,7 +4400,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = UpdateTaskStatusSvc(inner);
+                        let method = GetJobMetricsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2025,25 +4416,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/CreateSession" => {
+                "/ballista.protobuf.SchedulerGrpc/GetResults" => {
                     #[allow(non_camel_case_types)]
-                    struct CreateSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetResultsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::CreateSessionParams>
-                    for CreateSessionSvc<T> {
-                        type Response = super::CreateSessionResult;
+                    > tonic::server::UnaryService<super::GetResultsParams>
+                    for GetResultsSvc<T> {
+                        type Response = super::GetResultsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CreateSessionParams>,
+                            request: tonic::Request<super::GetResultsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::create_session(&inner, request).await
+                                <T as SchedulerGrpc>::get_results(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -2054,7 +4445,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = CreateSessionSvc(inner);
+                        let method = GetResultsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2070,25 +4461,26 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/UpdateSession" => {
+                "/ballista.protobuf.SchedulerGrpc/ListClusterTables" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct ListClusterTablesSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::UpdateSessionParams>
-                    for UpdateSessionSvc<T> {
-                        type Response = super::UpdateSessionResult;
+                    > tonic::server::UnaryService<super::ListClusterTablesParams>
+                    for ListClusterTablesSvc<T> {
+                        type Response = super::ListClusterTablesResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpdateSessionParams>,
+                            request: tonic::Request<super::ListClusterTablesParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::update_session(&inner, request).await
+                                <T as SchedulerGrpc>::list_cluster_tables(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2099,7 +4491,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = UpdateSessionSvc(inner);
+                        let method = ListClusterTablesSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2115,25 +4507,29 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/RemoveSession" => {
+                "/ballista.protobuf.SchedulerGrpc/GetClusterUtilization" => {
                     #[allow(non_camel_case_types)]
-                    struct RemoveSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetClusterUtilizationSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::RemoveSessionParams>
-                    for RemoveSessionSvc<T> {
-                        type Response = super::RemoveSessionResult;
+                    > tonic::server::UnaryService<super::GetClusterUtilizationParams>
+                    for GetClusterUtilizationSvc<T> {
+                        type Response = super::GetClusterUtilizationResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RemoveSessionParams>,
+                            request: tonic::Request<super::GetClusterUtilizationParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::remove_session(&inner, request).await
+                                <T as SchedulerGrpc>::get_cluster_utilization(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2144,7 +4540,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = RemoveSessionSvc(inner);
+                        let method = GetClusterUtilizationSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2160,25 +4556,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/ExecuteQuery" => {
+                "/ballista.protobuf.SchedulerGrpc/ReserveSlots" => {
                     #[allow(non_camel_case_types)]
-                    struct ExecuteQuerySvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct ReserveSlotsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::ExecuteQueryParams>
-                    for ExecuteQuerySvc<T> {
-                        type Response = super::ExecuteQueryResult;
+                    > tonic::server::UnaryService<super::ReserveSlotsParams>
+                    for ReserveSlotsSvc<T> {
+                        type Response = super::ReserveSlotsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ExecuteQueryParams>,
+                            request: tonic::Request<super::ReserveSlotsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::execute_query(&inner, request).await
+                                <T as SchedulerGrpc>::reserve_slots(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -2189,7 +4585,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = ExecuteQuerySvc(inner);
+                        let method = ReserveSlotsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2205,25 +4601,29 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/GetJobStatus" => {
+                "/ballista.protobuf.SchedulerGrpc/CancelSlotReservation" => {
                     #[allow(non_camel_case_types)]
-                    struct GetJobStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct CancelSlotReservationSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::GetJobStatusParams>
-                    for GetJobStatusSvc<T> {
-                        type Response = super::GetJobStatusResult;
+                    > tonic::server::UnaryService<super::CancelSlotReservationParams>
+                    for CancelSlotReservationSvc<T> {
+                        type Response = super::CancelSlotReservationResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::GetJobStatusParams>,
+                            request: tonic::Request<super::CancelSlotReservationParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::get_job_status(&inner, request).await
+                                <T as SchedulerGrpc>::cancel_slot_reservation(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2234,7 +4634,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = GetJobStatusSvc(inner);
+                        let method = CancelSlotReservationSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2250,25 +4650,28 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped" => {
+                "/ballista.protobuf.SchedulerGrpc/ListSlotReservations" => {
                     #[allow(non_camel_case_types)]
-                    struct ExecutorStoppedSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct ListSlotReservationsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::ExecutorStoppedParams>
-                    for ExecutorStoppedSvc<T> {
-                        type Response = super::ExecutorStoppedResult;
+                    > tonic::server::UnaryService<super::ListSlotReservationsParams>
+                    for ListSlotReservationsSvc<T> {
+                        type Response = super::ListSlotReservationsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ExecutorStoppedParams>,
+                            request: tonic::Request<super::ListSlotReservationsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::executor_stopped(&inner, request)
+                                <T as SchedulerGrpc>::list_slot_reservations(
+                                        &inner,
+                                        request,
+                                    )
                                     .await
                             };
                             Box::pin(fut)
@@ -2280,7 +4683,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = ExecutorStoppedSvc(inner);
+                        let method = ListSlotReservationsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2296,25 +4699,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/CancelJob" => {
+                "/ballista.protobuf.SchedulerGrpc/LeaseScanFile" => {
                     #[allow(non_camel_case_types)]
-                    struct CancelJobSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct LeaseScanFileSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::CancelJobParams>
-                    for CancelJobSvc<T> {
-                        type Response = super::CancelJobResult;
+                    > tonic::server::UnaryService<super::LeaseScanFileParams>
+                    for LeaseScanFileSvc<T> {
+                        type Response = super::LeaseScanFileResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CancelJobParams>,
+                            request: tonic::Request<super::LeaseScanFileParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::cancel_job(&inner, request).await
+                                <T as SchedulerGrpc>::lease_scan_file(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -2325,7 +4728,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = CancelJobSvc(inner);
+                        let method = LeaseScanFileSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2341,25 +4744,28 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/CleanJobData" => {
+                "/ballista.protobuf.SchedulerGrpc/SubscribeJobEvents" => {
                     #[allow(non_camel_case_types)]
-                    struct CleanJobDataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct SubscribeJobEventsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::CleanJobDataParams>
-                    for CleanJobDataSvc<T> {
-                        type Response = super::CleanJobDataResult;
+                    > tonic::server::ServerStreamingService<
+                        super::SubscribeJobEventsParams,
+                    > for SubscribeJobEventsSvc<T> {
+                        type Response = super::JobEvent;
+                        type ResponseStream = T::SubscribeJobEventsStream;
                         type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
+                            tonic::Response<Self::ResponseStream>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CleanJobDataParams>,
+                            request: tonic::Request<super::SubscribeJobEventsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::clean_job_data(&inner, request).await
+                                <T as SchedulerGrpc>::subscribe_job_events(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2370,7 +4776,7 @@ pub mod scheduler_grpc_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = CleanJobDataSvc(inner);
+                        let method = SubscribeJobEventsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2381,7 +4787,7 @@ pub mod scheduler_grpc_server {
                                 max_decoding_message_size,
                                 max_encoding_message_size,
                             );
-                        let res = grpc.unary(method, req).await;
+                        let res = grpc.server_streaming(method, req).await;
                         Ok(res)
                     };
                     Box::pin(fut)
@@ -2472,6 +4878,27 @@ pub mod executor_grpc_server {
             tonic::Response<super::RemoveJobDataResult>,
             tonic::Status,
         >;
+        async fn remove_task_outputs(
+            &self,
+            request: tonic::Request<super::RemoveTaskOutputsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveTaskOutputsResult>,
+            tonic::Status,
+        >;
+        async fn get_task_logs(
+            &self,
+            request: tonic::Request<super::GetTaskLogsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetTaskLogsResult>,
+            tonic::Status,
+        >;
+        async fn get_profile(
+            &self,
+            request: tonic::Request<super::GetProfileParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetProfileResult>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
     pub struct ExecutorGrpcServer<T> {
@@ -2775,6 +5202,142 @@ pub mod executor_grpc_server {
                     };
                     Box::pin(fut)
                 }
+                "/ballista.protobuf.ExecutorGrpc/RemoveTaskOutputs" => {
+                    #[allow(non_camel_case_types)]
+                    struct RemoveTaskOutputsSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::RemoveTaskOutputsParams>
+                    for RemoveTaskOutputsSvc<T> {
+                        type Response = super::RemoveTaskOutputsResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RemoveTaskOutputsParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ExecutorGrpc>::remove_task_outputs(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RemoveTaskOutputsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.ExecutorGrpc/GetTaskLogs" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetTaskLogsSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::GetTaskLogsParams>
+                    for GetTaskLogsSvc<T> {
+                        type Response = super::GetTaskLogsResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetTaskLogsParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ExecutorGrpc>::get_task_logs(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetTaskLogsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.ExecutorGrpc/GetProfile" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetProfileSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::GetProfileParams>
+                    for GetProfileSvc<T> {
+                        type Response = super::GetProfileResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetProfileParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ExecutorGrpc>::get_profile(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetProfileSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         let mut response = http::Response::new(empty_body());
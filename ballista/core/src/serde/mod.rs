@@ -21,14 +21,32 @@
 use crate::{error::BallistaError, serde::scheduler::Action as BallistaAction};
 
 use arrow_flight::sql::ProstMessageExt;
-use datafusion::common::{DataFusionError, Result};
+use arrow_flight::{FlightDescriptor, IpcMessage, SchemaAsIpc};
+use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::{IpcWriteOptions, StreamWriter};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::parsers::CompressionTypeVariant;
+use datafusion::common::{DataFusionError, Result, ScalarValue};
+use datafusion::datasource::cte_worktable::CteWorkTable;
+use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+use datafusion::datasource::physical_plan::NdJsonExec;
+use datafusion::datasource::{provider_as_source, source_as_provider};
 use datafusion::execution::FunctionRegistry;
+use datafusion::logical_expr::{Extension, LogicalPlan, LogicalPlanBuilder};
+use datafusion::physical_plan::memory::MemoryExec;
 use datafusion::physical_plan::{ExecutionPlan, Partitioning};
 use datafusion_proto::logical_plan::file_formats::{
     ArrowLogicalExtensionCodec, AvroLogicalExtensionCodec, CsvLogicalExtensionCodec,
     JsonLogicalExtensionCodec, ParquetLogicalExtensionCodec,
 };
-use datafusion_proto::physical_plan::from_proto::parse_protobuf_hash_partitioning;
+use datafusion_proto::physical_plan::from_proto::{
+    parse_physical_expr, parse_physical_sort_exprs, parse_protobuf_file_scan_config,
+    parse_protobuf_hash_partitioning,
+};
+use datafusion_proto::physical_plan::to_proto::{
+    serialize_file_scan_config, serialize_physical_expr, serialize_physical_sort_exprs,
+};
 use datafusion_proto::protobuf::proto_error;
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
 use datafusion_proto::{
@@ -38,16 +56,26 @@ use datafusion_proto::{
 };
 
 use prost::Message;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::{convert::TryInto, io::Cursor};
 
+use crate::cluster_catalog::ClusterTable;
 use crate::execution_plans::{
-    ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec,
+    BroadcastExchangeExec, FlightPartition, FlightScanExec, RangePartitioning,
+    RemoteQueryExec, ScanOptionsExec, ShuffleReaderExec, ShuffleWriterExec,
+    SplittableScanExec, UnresolvedShuffleExec, VerifyFilesExec,
 };
+use crate::federation::{encode_logical_plan, FlightTableProvider, RemoteTable};
+use crate::recursive_query::RecursiveQueryNode;
+use crate::serde::protobuf::ballista_logical_extension_node::ExtensionType;
 use crate::serde::protobuf::ballista_physical_plan_node::PhysicalPlanType;
+use crate::serde::protobuf::ballista_table_provider_node::TableProviderType;
 use crate::serde::scheduler::PartitionLocation;
+use crate::table_functions::GenerateSeries;
+use crate::verify_table::VerifyTable;
 pub use generated::ballista as protobuf;
 
 pub mod generated;
@@ -109,6 +137,25 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> BallistaCodec<T,
         }
     }
 
+    /// Creates a codec encoding the extension nodes and table providers which Ballista
+    /// does not know with the given user codecs, see
+    /// [BallistaLogicalExtensionCodec::with_extension_codec] and
+    /// [BallistaPhysicalExtensionCodec::with_extension_codec]
+    pub fn new_with_extension_codecs(
+        logical_extension_codecs: Vec<Arc<dyn LogicalExtensionCodec>>,
+        physical_extension_codecs: Vec<Arc<dyn PhysicalExtensionCodec>>,
+    ) -> Self {
+        let logical = logical_extension_codecs.into_iter().fold(
+            BallistaLogicalExtensionCodec::default(),
+            |codec, extension| codec.with_extension_codec(extension),
+        );
+        let physical = physical_extension_codecs.into_iter().fold(
+            BallistaPhysicalExtensionCodec::default(),
+            |codec, extension| codec.with_extension_codec(extension),
+        );
+        Self::new(Arc::new(logical), Arc::new(physical))
+    }
+
     pub fn logical_extension_codec(&self) -> &dyn LogicalExtensionCodec {
         self.logical_extension_codec.as_ref()
     }
@@ -122,9 +169,20 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> BallistaCodec<T,
 pub struct BallistaLogicalExtensionCodec {
     default_codec: Arc<dyn LogicalExtensionCodec>,
     file_format_codecs: Vec<Arc<dyn LogicalExtensionCodec>>,
+    extension_codecs: Vec<Arc<dyn LogicalExtensionCodec>>,
 }
 
 impl BallistaLogicalExtensionCodec {
+    /// Registers a user codec for the extension nodes and table providers which
+    /// Ballista does not know, e.g. the providers of table formats.
+    ///
+    /// User codecs are tried in the order of their registration, and the scheduler and
+    /// the executors must register the same codecs in the same order.
+    pub fn with_extension_codec(mut self, codec: Arc<dyn LogicalExtensionCodec>) -> Self {
+        self.extension_codecs.push(codec);
+        self
+    }
+
     /// looks for a codec which can operate on this node
     /// returns a position of codec in the list and result.
     ///
@@ -165,6 +223,7 @@ impl Default for BallistaLogicalExtensionCodec {
                 Arc::new(ArrowLogicalExtensionCodec {}),
                 Arc::new(AvroLogicalExtensionCodec {}),
             ],
+            extension_codecs: vec![],
         }
     }
 }
@@ -176,7 +235,32 @@ impl LogicalExtensionCodec for BallistaLogicalExtensionCodec {
         inputs: &[datafusion::logical_expr::LogicalPlan],
         ctx: &datafusion::prelude::SessionContext,
     ) -> Result<datafusion::logical_expr::Extension> {
-        self.default_codec.try_decode(buf, inputs, ctx)
+        let extension = protobuf::BallistaLogicalExtensionNode::decode(buf)
+            .ok()
+            .and_then(|node| node.extension_type);
+        match extension {
+            Some(ExtensionType::RecursiveQuery(query)) => {
+                let [static_term, recursive_term] = inputs else {
+                    return Err(DataFusionError::Internal(format!(
+                        "RecursiveQuery expects 2 inputs, got {}",
+                        inputs.len()
+                    )));
+                };
+                Ok(Extension {
+                    node: Arc::new(RecursiveQueryNode::new(
+                        query.name,
+                        Arc::new(static_term.clone()),
+                        Arc::new(recursive_term.clone()),
+                        query.is_distinct,
+                    )),
+                })
+            }
+            Some(ExtensionType::Extension(extension)) => {
+                extension_codec(&self.extension_codecs, extension.codec_position)?
+                    .try_decode(&extension.blob, inputs, ctx)
+            }
+            None => self.default_codec.try_decode(buf, inputs, ctx),
+        }
     }
 
     fn try_encode(
@@ -184,7 +268,26 @@ impl LogicalExtensionCodec for BallistaLogicalExtensionCodec {
         node: &datafusion::logical_expr::Extension,
         buf: &mut Vec<u8>,
     ) -> Result<()> {
-        self.default_codec.try_encode(node, buf)
+        let extension_type = if let Some(query) =
+            node.node.as_any().downcast_ref::<RecursiveQueryNode>()
+        {
+            ExtensionType::RecursiveQuery(protobuf::RecursiveQueryNode {
+                name: query.query_name().to_owned(),
+                is_distinct: query.is_distinct(),
+            })
+        } else {
+            match encode_with_extension_codecs(&self.extension_codecs, |codec, blob| {
+                codec.try_encode(node, blob)
+            }) {
+                Some(extension) => ExtensionType::Extension(extension),
+                None => return self.default_codec.try_encode(node, buf),
+            }
+        };
+        protobuf::BallistaLogicalExtensionNode {
+            extension_type: Some(extension_type),
+        }
+        .encode(buf)
+        .map_err(|e| DataFusionError::Internal(e.to_string()))
     }
 
     fn try_decode_table_provider(
@@ -194,6 +297,70 @@ impl LogicalExtensionCodec for BallistaLogicalExtensionCodec {
         schema: datafusion::arrow::datatypes::SchemaRef,
         ctx: &datafusion::prelude::SessionContext,
     ) -> Result<Arc<dyn datafusion::catalog::TableProvider>> {
+        let provider = protobuf::BallistaTableProviderNode::decode(buf)
+            .ok()
+            .and_then(|node| node.table_provider_type);
+        match provider {
+            Some(TableProviderType::Remote(remote)) => {
+                let inner = LogicalPlanNode::try_decode(&remote.plan)?
+                    .try_into_logical_plan(ctx, self)?;
+                let LogicalPlan::TableScan(scan) = inner else {
+                    return Err(DataFusionError::Internal(format!(
+                        "Remote table is not a table scan: {inner:?}"
+                    )));
+                };
+                let inner = source_as_provider(&scan.source)?;
+                return Ok(Arc::new(RemoteTable::new(remote.scheduler_url, inner)));
+            }
+            Some(TableProviderType::VerifyTable(verify)) => {
+                let inner = LogicalPlanNode::try_decode(&verify.plan)?
+                    .try_into_logical_plan(ctx, self)?;
+                let LogicalPlan::TableScan(scan) = inner else {
+                    return Err(DataFusionError::Internal(format!(
+                        "Verified table is not a table scan: {inner:?}"
+                    )));
+                };
+                let inner = source_as_provider(&scan.source)?;
+                return Ok(Arc::new(VerifyTable::new(inner)));
+            }
+            Some(TableProviderType::Flight(flight)) => {
+                let descriptor = FlightDescriptor::decode(flight.descriptor.as_slice())
+                    .map_err(|e| DataFusionError::Internal(e.to_string()))?;
+                return Ok(Arc::new(FlightTableProvider::new(
+                    flight.endpoint,
+                    descriptor,
+                    from_key_value_pairs(flight.headers),
+                    Arc::new(decode_schema(&flight.arrow_schema, &None)?),
+                )));
+            }
+            Some(TableProviderType::Extension(extension)) => {
+                return extension_codec(
+                    &self.extension_codecs,
+                    extension.codec_position,
+                )?
+                .try_decode_table_provider(
+                    &extension.blob,
+                    table_ref,
+                    schema,
+                    ctx,
+                );
+            }
+            Some(TableProviderType::CteWorkTable(_)) => {
+                return Ok(Arc::new(CteWorkTable::new(table_ref.table(), schema)));
+            }
+            Some(TableProviderType::ClusterTable(table)) => {
+                return Ok(Arc::new(ClusterTable::try_from_proto(table)?));
+            }
+            Some(TableProviderType::GenerateSeries(series)) => {
+                return Ok(Arc::new(GenerateSeries::try_new(
+                    series.start,
+                    series.stop,
+                    series.step,
+                )?));
+            }
+            None => {}
+        }
+
         self.default_codec
             .try_decode_table_provider(buf, table_ref, schema, ctx)
     }
@@ -204,6 +371,113 @@ impl LogicalExtensionCodec for BallistaLogicalExtensionCodec {
         node: Arc<dyn datafusion::catalog::TableProvider>,
         buf: &mut Vec<u8>,
     ) -> Result<()> {
+        if let Some(remote) = node.as_any().downcast_ref::<RemoteTable>() {
+            // the inner table is encoded as a scan, so it is decoded
+            // with the same codecs as any other table
+            let inner = LogicalPlanBuilder::scan(
+                table_ref.clone(),
+                provider_as_source(remote.inner()),
+                None,
+            )?
+            .build()?;
+            let proto = protobuf::BallistaTableProviderNode {
+                table_provider_type: Some(TableProviderType::Remote(
+                    protobuf::RemoteTableNode {
+                        scheduler_url: remote.scheduler_url().to_owned(),
+                        plan: encode_logical_plan(&inner, self)?,
+                    },
+                )),
+            };
+            return proto
+                .encode(buf)
+                .map_err(|e| DataFusionError::Internal(e.to_string()));
+        }
+
+        if let Some(verify) = node.as_any().downcast_ref::<VerifyTable>() {
+            let inner = LogicalPlanBuilder::scan(
+                table_ref.clone(),
+                provider_as_source(verify.inner()),
+                None,
+            )?
+            .build()?;
+            let proto = protobuf::BallistaTableProviderNode {
+                table_provider_type: Some(TableProviderType::VerifyTable(
+                    protobuf::VerifyTableNode {
+                        plan: encode_logical_plan(&inner, self)?,
+                    },
+                )),
+            };
+            return proto
+                .encode(buf)
+                .map_err(|e| DataFusionError::Internal(e.to_string()));
+        }
+
+        if let Some(flight) = node.as_any().downcast_ref::<FlightTableProvider>() {
+            let proto = protobuf::BallistaTableProviderNode {
+                table_provider_type: Some(TableProviderType::Flight(
+                    protobuf::FlightTableNode {
+                        endpoint: flight.endpoint().to_owned(),
+                        descriptor: flight.descriptor().encode_to_vec(),
+                        headers: to_key_value_pairs(flight.headers()),
+                        arrow_schema: encode_arrow_schema(node.schema().as_ref())?,
+                    },
+                )),
+            };
+            return proto
+                .encode(buf)
+                .map_err(|e| DataFusionError::Internal(e.to_string()));
+        }
+
+        if node.as_any().is::<CteWorkTable>() {
+            let proto = protobuf::BallistaTableProviderNode {
+                table_provider_type: Some(TableProviderType::CteWorkTable(
+                    protobuf::CteWorkTableNode {},
+                )),
+            };
+            return proto
+                .encode(buf)
+                .map_err(|e| DataFusionError::Internal(e.to_string()));
+        }
+
+        if let Some(table) = node.as_any().downcast_ref::<ClusterTable>() {
+            let proto = protobuf::BallistaTableProviderNode {
+                table_provider_type: Some(TableProviderType::ClusterTable(
+                    table.try_to_proto()?,
+                )),
+            };
+            return proto
+                .encode(buf)
+                .map_err(|e| DataFusionError::Internal(e.to_string()));
+        }
+
+        if let Some(series) = node.as_any().downcast_ref::<GenerateSeries>() {
+            let proto = protobuf::BallistaTableProviderNode {
+                table_provider_type: Some(TableProviderType::GenerateSeries(
+                    protobuf::GenerateSeriesNode {
+                        start: series.start(),
+                        stop: series.stop(),
+                        step: series.step(),
+                    },
+                )),
+            };
+            return proto
+                .encode(buf)
+                .map_err(|e| DataFusionError::Internal(e.to_string()));
+        }
+
+        if let Some(extension) =
+            encode_with_extension_codecs(&self.extension_codecs, |codec, blob| {
+                codec.try_encode_table_provider(table_ref, node.clone(), blob)
+            })
+        {
+            let proto = protobuf::BallistaTableProviderNode {
+                table_provider_type: Some(TableProviderType::Extension(extension)),
+            };
+            return proto
+                .encode(buf)
+                .map_err(|e| DataFusionError::Internal(e.to_string()));
+        }
+
         self.default_codec
             .try_encode_table_provider(table_ref, node, buf)
     }
@@ -245,8 +519,97 @@ impl LogicalExtensionCodec for BallistaLogicalExtensionCodec {
     }
 }
 
+/// Encodes the schema in Arrow IPC format. Unlike the protobuf schema it supports all
+/// Arrow types, e.g. Decimal256.
+pub(crate) fn encode_arrow_schema(schema: &Schema) -> Result<Vec<u8>, DataFusionError> {
+    let IpcMessage(bytes) =
+        SchemaAsIpc::new(schema, &IpcWriteOptions::default()).try_into()?;
+    Ok(bytes.to_vec())
+}
+
+/// Decodes the Arrow IPC encoded schema if present, falling back to the protobuf
+/// schema for plans encoded without it.
+pub(crate) fn decode_schema(
+    arrow_schema: &[u8],
+    schema: &Option<datafusion_proto_common::Schema>,
+) -> Result<Schema, DataFusionError> {
+    if arrow_schema.is_empty() {
+        Ok(convert_required!(schema)?)
+    } else {
+        Ok(Schema::try_from(IpcMessage(arrow_schema.to_vec().into()))?)
+    }
+}
+
+fn project_batch(
+    batch: &RecordBatch,
+    projection: &Option<Vec<usize>>,
+) -> Result<RecordBatch, DataFusionError> {
+    match projection {
+        Some(projection) => Ok(batch.project(projection)?),
+        None => Ok(batch.clone()),
+    }
+}
+
+fn to_key_value_pairs(map: &HashMap<String, String>) -> Vec<protobuf::KeyValuePair> {
+    map.iter()
+        .map(|(key, value)| protobuf::KeyValuePair {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect()
+}
+
+fn from_key_value_pairs(pairs: Vec<protobuf::KeyValuePair>) -> HashMap<String, String> {
+    pairs.into_iter().map(|kv| (kv.key, kv.value)).collect()
+}
+
+/// Encodes a node with the first user extension codec able to encode it, recording
+/// the position of the codec so that the node is decoded by the same codec
+fn encode_with_extension_codecs<C: ?Sized>(
+    codecs: &[Arc<C>],
+    mut encode: impl FnMut(&C, &mut Vec<u8>) -> Result<()>,
+) -> Option<protobuf::ExtensionCodecNode> {
+    codecs.iter().enumerate().find_map(|(position, codec)| {
+        let mut blob = vec![];
+        encode(codec.as_ref(), &mut blob)
+            .ok()
+            .map(|_| protobuf::ExtensionCodecNode {
+                codec_position: position as u32,
+                blob,
+            })
+    })
+}
+
+/// User extension codec at the given position
+fn extension_codec<C: ?Sized>(codecs: &[Arc<C>], position: u32) -> Result<&C> {
+    codecs
+        .get(position as usize)
+        .map(|codec| codec.as_ref())
+        .ok_or_else(|| {
+            DataFusionError::Internal(format!(
+                "Can't find extension codec {position}, the same extension codecs must be registered by the scheduler and the executors"
+            ))
+        })
+}
+
 #[derive(Debug, Default)]
-pub struct BallistaPhysicalExtensionCodec {}
+pub struct BallistaPhysicalExtensionCodec {
+    extension_codecs: Vec<Arc<dyn PhysicalExtensionCodec>>,
+}
+
+impl BallistaPhysicalExtensionCodec {
+    /// Registers a user codec for the execution plans which Ballista does not know.
+    ///
+    /// User codecs are tried in the order of their registration, and the scheduler and
+    /// the executors must register the same codecs in the same order.
+    pub fn with_extension_codec(
+        mut self,
+        codec: Arc<dyn PhysicalExtensionCodec>,
+    ) -> Self {
+        self.extension_codecs.push(codec);
+        self
+    }
+}
 
 impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
     fn try_decode(
@@ -276,24 +639,67 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                 let default_codec =
                     datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec {};
 
-                let shuffle_output_partitioning = parse_protobuf_hash_partitioning(
-                    shuffle_writer.output_partitioning.as_ref(),
-                    registry,
-                    input.schema().as_ref(),
-                    &default_codec,
-                )?;
+                let shuffle_output_partitioning =
+                    if shuffle_writer.round_robin_partition_count > 0 {
+                        Some(Partitioning::RoundRobinBatch(
+                            shuffle_writer.round_robin_partition_count as usize,
+                        ))
+                    } else {
+                        parse_protobuf_hash_partitioning(
+                            shuffle_writer.output_partitioning.as_ref(),
+                            registry,
+                            input.schema().as_ref(),
+                            &default_codec,
+                        )?
+                    };
+                let range_partitioning = shuffle_writer
+                    .range_partitioning
+                    .as_ref()
+                    .map(|range_partitioning| {
+                        let sort_exprs = parse_physical_sort_exprs(
+                            &range_partitioning.sort_expr,
+                            registry,
+                            input.schema().as_ref(),
+                            &default_codec,
+                        )?;
+                        let boundaries = range_partitioning
+                            .boundaries
+                            .iter()
+                            .map(|boundary| {
+                                boundary
+                                    .values
+                                    .iter()
+                                    .map(|value| Ok(ScalarValue::try_from(value)?))
+                                    .collect::<Result<Vec<_>>>()
+                            })
+                            .collect::<Result<Vec<_>>>()?;
+                        RangePartitioning::try_new(sort_exprs, boundaries)
+                    })
+                    .transpose()?;
 
-                Ok(Arc::new(ShuffleWriterExec::try_new(
-                    shuffle_writer.job_id.clone(),
-                    shuffle_writer.stage_id as usize,
-                    input,
-                    "".to_string(), // this is intentional but hacky - the executor will fill this in
-                    shuffle_output_partitioning,
-                )?))
+                Ok(Arc::new(
+                    ShuffleWriterExec::try_new(
+                        shuffle_writer.job_id.clone(),
+                        shuffle_writer.stage_id as usize,
+                        input,
+                        "".to_string(), // this is intentional but hacky - the executor will fill this in
+                        shuffle_output_partitioning,
+                    )?
+                    .with_range_partitioning(range_partitioning)
+                    .with_compression(shuffle_writer.compression().into())
+                    .with_object_store_url(
+                        (!shuffle_writer.object_store_url.is_empty())
+                            .then(|| shuffle_writer.object_store_url.clone()),
+                    )
+                    .with_final_stage(shuffle_writer.final_stage),
+                ))
             }
             PhysicalPlanType::ShuffleReader(shuffle_reader) => {
                 let stage_id = shuffle_reader.stage_id as usize;
-                let schema = Arc::new(convert_required!(shuffle_reader.schema)?);
+                let schema = Arc::new(decode_schema(
+                    &shuffle_reader.arrow_schema,
+                    &shuffle_reader.schema,
+                )?);
                 let partition_location: Vec<Vec<PartitionLocation>> = shuffle_reader
                     .partition
                     .iter()
@@ -310,18 +716,135 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                             .collect::<Result<Vec<_>, _>>()
                     })
                     .collect::<Result<Vec<_>, DataFusionError>>()?;
-                let shuffle_reader =
-                    ShuffleReaderExec::try_new(stage_id, partition_location, schema)?;
-                Ok(Arc::new(shuffle_reader))
+                let default_codec =
+                    datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec {};
+                let runtime_predicates = shuffle_reader
+                    .runtime_predicates
+                    .iter()
+                    .map(|expr| {
+                        parse_physical_expr(
+                            expr,
+                            registry,
+                            schema.as_ref(),
+                            &default_codec,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let sort_order = parse_physical_sort_exprs(
+                    &shuffle_reader.sort_order,
+                    registry,
+                    schema.as_ref(),
+                    &default_codec,
+                )?;
+                let mut reader =
+                    ShuffleReaderExec::try_new(stage_id, partition_location, schema)?
+                        .with_runtime_predicates(runtime_predicates)
+                        .with_sort_order(sort_order);
+                if shuffle_reader.shuffle_partition_count > 0 {
+                    reader = reader.with_shuffle_partition_count(
+                        shuffle_reader.shuffle_partition_count as usize,
+                    );
+                }
+                Ok(Arc::new(reader))
             }
             PhysicalPlanType::UnresolvedShuffle(unresolved_shuffle) => {
-                let schema = Arc::new(convert_required!(unresolved_shuffle.schema)?);
-                Ok(Arc::new(UnresolvedShuffleExec::new(
-                    unresolved_shuffle.stage_id as usize,
+                let schema = Arc::new(decode_schema(
+                    &unresolved_shuffle.arrow_schema,
+                    &unresolved_shuffle.schema,
+                )?);
+                let default_codec =
+                    datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec {};
+                let sort_order = parse_physical_sort_exprs(
+                    &unresolved_shuffle.sort_order,
+                    registry,
+                    schema.as_ref(),
+                    &default_codec,
+                )?;
+                Ok(Arc::new(
+                    UnresolvedShuffleExec::new(
+                        unresolved_shuffle.stage_id as usize,
+                        schema,
+                        unresolved_shuffle.output_partition_count as usize,
+                    )
+                    .with_sort_order(sort_order),
+                ))
+            }
+            PhysicalPlanType::RemoteQuery(remote_query) => {
+                let schema = Arc::new(decode_schema(&remote_query.arrow_schema, &None)?);
+                Ok(Arc::new(RemoteQueryExec::new(
+                    remote_query.scheduler_url.clone(),
+                    remote_query.logical_plan.clone(),
+                    schema,
+                )))
+            }
+            PhysicalPlanType::FlightScan(flight_scan) => {
+                let schema = Arc::new(decode_schema(&flight_scan.arrow_schema, &None)?);
+                let partitions = flight_scan
+                    .partition
+                    .iter()
+                    .map(|p| FlightPartition {
+                        ticket: p.ticket.clone(),
+                        locations: p.location.clone(),
+                    })
+                    .collect();
+                Ok(Arc::new(FlightScanExec::try_new(
+                    flight_scan.endpoint.clone(),
+                    from_key_value_pairs(flight_scan.headers.clone()),
+                    partitions,
                     schema,
-                    unresolved_shuffle.output_partition_count as usize,
+                    flight_scan.projection.iter().map(|i| *i as usize).collect(),
+                )?))
+            }
+            PhysicalPlanType::ScanOptions(scan_options) => {
+                let compression: CompressionTypeVariant =
+                    scan_options.compression.parse()?;
+                ScanOptionsExec::restore(inputs[0].clone(), compression.into())
+            }
+            PhysicalPlanType::NdjsonScan(ndjson_scan) => {
+                let base_conf = ndjson_scan.base_conf.as_ref().ok_or_else(|| {
+                    DataFusionError::Internal(
+                        "NdJsonScanExecNode is missing its file scan config".to_owned(),
+                    )
+                })?;
+                Ok(Arc::new(NdJsonExec::new(
+                    parse_protobuf_file_scan_config(base_conf, registry, self)?,
+                    // restored by the ScanOptionsExec wrapping compressed scans
+                    FileCompressionType::UNCOMPRESSED,
                 )))
             }
+            PhysicalPlanType::MemoryScan(memory_scan) => {
+                let schema = Arc::new(decode_schema(&memory_scan.arrow_schema, &None)?);
+                let partitions = memory_scan
+                    .partition
+                    .iter()
+                    .map(|partition| {
+                        StreamReader::try_new(Cursor::new(partition), None)?
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Arc::new(MemoryExec::try_new(&partitions, schema, None)?))
+            }
+            PhysicalPlanType::BroadcastExchange(broadcast_exchange) => {
+                Ok(Arc::new(BroadcastExchangeExec::new(
+                    broadcast_exchange.job_id.clone(),
+                    broadcast_exchange.stage_id as usize,
+                    inputs[0].clone(),
+                )))
+            }
+            PhysicalPlanType::SplittableScan(splittable_scan) => {
+                Ok(Arc::new(SplittableScanExec::try_new(
+                    splittable_scan.job_id.clone(),
+                    splittable_scan.stage_id as usize,
+                    inputs[0].clone(),
+                )?))
+            }
+            PhysicalPlanType::VerifyFiles(_) => {
+                Ok(Arc::new(VerifyFilesExec::try_new(inputs[0].clone())?))
+            }
+            PhysicalPlanType::Extension(extension) => {
+                extension_codec(&self.extension_codecs, extension.codec_position)?
+                    .try_decode(&extension.blob, inputs, registry)
+            }
         }
     }
 
@@ -333,10 +856,11 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
         if let Some(exec) = node.as_any().downcast_ref::<ShuffleWriterExec>() {
             // note that we use shuffle_output_partitioning() rather than output_partitioning()
             // to get the true output partitioning
+            let default_codec =
+                datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec {};
+            let mut round_robin_partition_count = 0;
             let output_partitioning = match exec.shuffle_output_partitioning() {
                 Some(Partitioning::Hash(exprs, partition_count)) => {
-                    let default_codec =
-                        datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec {};
                     Some(datafusion_proto::protobuf::PhysicalHashRepartition {
                         hash_expr: exprs
                             .iter()
@@ -345,6 +869,16 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                         partition_count: *partition_count as u64,
                     })
                 }
+                Some(Partitioning::RoundRobinBatch(partition_count)) => {
+                    round_robin_partition_count = *partition_count as u64;
+                    None
+                }
+                // the partition count of the range partitioning
+                Some(Partitioning::UnknownPartitioning(_))
+                    if exec.range_partitioning().is_some() =>
+                {
+                    None
+                }
                 None => None,
                 other => {
                     return Err(DataFusionError::Internal(format!(
@@ -353,6 +887,30 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                 }
             };
 
+            let range_partitioning = exec
+                .range_partitioning()
+                .map(|range_partitioning| {
+                    Ok::<_, DataFusionError>(protobuf::RangePartitioning {
+                        sort_expr: serialize_physical_sort_exprs(
+                            range_partitioning.sort_exprs().to_vec(),
+                            &default_codec,
+                        )?,
+                        boundaries: range_partitioning
+                            .boundaries()
+                            .iter()
+                            .map(|boundary| {
+                                Ok(protobuf::RangeBoundary {
+                                    values: boundary
+                                        .iter()
+                                        .map(datafusion_proto_common::ScalarValue::try_from)
+                                        .collect::<Result<Vec<_>, _>>()?,
+                                })
+                            })
+                            .collect::<Result<Vec<_>>>()?,
+                    })
+                })
+                .transpose()?;
+
             let proto = protobuf::BallistaPhysicalPlanNode {
                 physical_plan_type: Some(PhysicalPlanType::ShuffleWriter(
                     protobuf::ShuffleWriterExecNode {
@@ -360,6 +918,16 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                         stage_id: exec.stage_id() as u32,
                         input: None,
                         output_partitioning,
+                        compression: protobuf::ShuffleCompression::from(
+                            exec.compression(),
+                        ) as i32,
+                        object_store_url: exec
+                            .object_store_url()
+                            .unwrap_or_default()
+                            .to_owned(),
+                        round_robin_partition_count,
+                        range_partitioning,
+                        final_stage: exec.is_final_stage(),
                     },
                 )),
             };
@@ -388,12 +956,25 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                         .collect::<Result<Vec<_>, _>>()?,
                 });
             }
+            let default_codec =
+                datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec {};
+            let runtime_predicates = exec
+                .runtime_predicates
+                .iter()
+                .map(|expr| serialize_physical_expr(expr, &default_codec))
+                .collect::<Result<Vec<_>, DataFusionError>>()?;
+            let sort_order =
+                serialize_physical_sort_exprs(exec.sort_order.clone(), &default_codec)?;
             let proto = protobuf::BallistaPhysicalPlanNode {
                 physical_plan_type: Some(PhysicalPlanType::ShuffleReader(
                     protobuf::ShuffleReaderExecNode {
                         stage_id,
                         partition,
-                        schema: Some(exec.schema().as_ref().try_into()?),
+                        schema: exec.schema().as_ref().try_into().ok(),
+                        arrow_schema: encode_arrow_schema(exec.schema().as_ref())?,
+                        runtime_predicates,
+                        sort_order,
+                        shuffle_partition_count: exec.shuffle_partition_count as u32,
                     },
                 )),
             };
@@ -405,12 +986,18 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
 
             Ok(())
         } else if let Some(exec) = node.as_any().downcast_ref::<UnresolvedShuffleExec>() {
+            let default_codec =
+                datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec {};
+            let sort_order =
+                serialize_physical_sort_exprs(exec.sort_order.clone(), &default_codec)?;
             let proto = protobuf::BallistaPhysicalPlanNode {
                 physical_plan_type: Some(PhysicalPlanType::UnresolvedShuffle(
                     protobuf::UnresolvedShuffleExecNode {
                         stage_id: exec.stage_id as u32,
-                        schema: Some(exec.schema().as_ref().try_into()?),
+                        schema: exec.schema().as_ref().try_into().ok(),
+                        arrow_schema: encode_arrow_schema(exec.schema().as_ref())?,
                         output_partition_count: exec.output_partition_count as u32,
+                        sort_order,
                     },
                 )),
             };
@@ -420,6 +1007,170 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                 ))
             })?;
 
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<RemoteQueryExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::RemoteQuery(
+                    protobuf::RemoteQueryExecNode {
+                        scheduler_url: exec.scheduler_url().to_owned(),
+                        logical_plan: exec.logical_plan().to_vec(),
+                        arrow_schema: encode_arrow_schema(exec.schema().as_ref())?,
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode remote query execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<FlightScanExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::FlightScan(
+                    protobuf::FlightScanExecNode {
+                        endpoint: exec.endpoint().to_owned(),
+                        headers: to_key_value_pairs(exec.headers()),
+                        partition: exec
+                            .partitions()
+                            .iter()
+                            .map(|p| protobuf::FlightScanPartition {
+                                ticket: p.ticket.clone(),
+                                location: p.locations.clone(),
+                            })
+                            .collect(),
+                        arrow_schema: encode_arrow_schema(exec.flight_schema().as_ref())?,
+                        projection: exec.projection().iter().map(|i| *i as u32).collect(),
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode flight scan execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<ScanOptionsExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::ScanOptions(
+                    protobuf::ScanOptionsExecNode {
+                        compression: exec.compression().get_variant().to_string(),
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode scan options execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<NdJsonExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::NdjsonScan(
+                    protobuf::NdJsonScanExecNode {
+                        base_conf: Some(serialize_file_scan_config(
+                            exec.base_config(),
+                            self,
+                        )?),
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode json scan execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<MemoryExec>() {
+            let schema = exec.schema();
+            let partition = exec
+                .partitions()
+                .iter()
+                .map(|batches| {
+                    let mut writer = StreamWriter::try_new(vec![], &schema)?;
+                    for batch in batches {
+                        writer.write(&project_batch(batch, exec.projection())?)?;
+                    }
+                    Ok(writer.into_inner()?)
+                })
+                .collect::<Result<Vec<_>, DataFusionError>>()?;
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::MemoryScan(
+                    protobuf::MemoryScanExecNode {
+                        arrow_schema: encode_arrow_schema(&schema)?,
+                        partition,
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode memory scan execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<BroadcastExchangeExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::BroadcastExchange(
+                    protobuf::BroadcastExchangeExecNode {
+                        job_id: exec.job_id().to_owned(),
+                        stage_id: exec.stage_id() as u32,
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode broadcast exchange execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<SplittableScanExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::SplittableScan(
+                    protobuf::SplittableScanExecNode {
+                        job_id: exec.job_id().to_owned(),
+                        stage_id: exec.stage_id() as u32,
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode splittable scan execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if node.as_any().is::<VerifyFilesExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::VerifyFiles(
+                    protobuf::VerifyFilesExecNode {},
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode verify files execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(extension) =
+            encode_with_extension_codecs(&self.extension_codecs, |codec, blob| {
+                codec.try_encode(node.clone(), blob)
+            })
+        {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::Extension(extension)),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode extension execution plan: {e:?}"
+                ))
+            })?;
+
             Ok(())
         } else {
             Err(DataFusionError::Internal(format!(
@@ -494,4 +1245,852 @@ mod test {
         assert_eq!(o.to_string(), d.to_string())
         //logical_plan.
     }
+
+    #[test]
+    fn shuffle_reader_runtime_predicates_roundtrip() {
+        use crate::execution_plans::ShuffleReaderExec;
+        use crate::serde::BallistaPhysicalExtensionCodec;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::common::ScalarValue;
+        use datafusion::logical_expr::Operator;
+        use datafusion::physical_expr::expressions::{BinaryExpr, Column, Literal};
+        use datafusion::physical_plan::PhysicalExpr;
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let predicate: Arc<dyn PhysicalExpr> = Arc::new(BinaryExpr::new(
+            Arc::new(Column::new("a", 0)),
+            Operator::Lt,
+            Arc::new(Literal::new(ScalarValue::Int32(Some(10)))),
+        ));
+        let reader = ShuffleReaderExec::try_new(1, vec![], schema)
+            .unwrap()
+            .with_runtime_predicates(vec![predicate]);
+
+        let codec = BallistaPhysicalExtensionCodec::default();
+        let mut buf: Vec<u8> = vec![];
+        codec.try_encode(Arc::new(reader), &mut buf).unwrap();
+
+        let ctx = SessionContext::new();
+        let decoded = codec.try_decode(&buf, &[], &ctx).unwrap();
+        let decoded = decoded
+            .as_any()
+            .downcast_ref::<ShuffleReaderExec>()
+            .unwrap();
+        assert_eq!(1, decoded.runtime_predicates.len());
+        assert_eq!("a@0 < 10", decoded.runtime_predicates[0].to_string());
+    }
+
+    #[test]
+    fn shuffle_writer_partitioning_roundtrip() {
+        use crate::execution_plans::{RangePartitioning, ShuffleWriterExec};
+        use crate::serde::BallistaPhysicalExtensionCodec;
+        use datafusion::arrow::compute::SortOptions;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::common::ScalarValue;
+        use datafusion::physical_expr::expressions::Column;
+        use datafusion::physical_expr::PhysicalSortExpr;
+        use datafusion::physical_plan::empty::EmptyExec;
+        use datafusion::physical_plan::{ExecutionPlan, Partitioning};
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let input: Arc<dyn ExecutionPlan> = Arc::new(EmptyExec::new(schema));
+        let range_partitioning = RangePartitioning::try_new(
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("a", 0)),
+                options: SortOptions {
+                    descending: true,
+                    nulls_first: false,
+                },
+            }],
+            vec![
+                vec![ScalarValue::Int32(Some(100))],
+                vec![ScalarValue::Int32(Some(10))],
+            ],
+        )
+        .unwrap();
+        let round_robin = ShuffleWriterExec::try_new(
+            "job".to_owned(),
+            1,
+            input.clone(),
+            "".to_owned(),
+            Some(Partitioning::RoundRobinBatch(4)),
+        )
+        .unwrap();
+        let range = ShuffleWriterExec::try_new(
+            "job".to_owned(),
+            1,
+            input.clone(),
+            "".to_owned(),
+            None,
+        )
+        .unwrap()
+        .with_range_partitioning(Some(range_partitioning))
+        .with_final_stage(true);
+
+        let codec = BallistaPhysicalExtensionCodec::default();
+        let ctx = SessionContext::new();
+        let roundtrip = |writer: ShuffleWriterExec| {
+            let mut buf: Vec<u8> = vec![];
+            codec.try_encode(Arc::new(writer), &mut buf).unwrap();
+            codec
+                .try_decode(&buf, std::slice::from_ref(&input), &ctx)
+                .unwrap()
+        };
+
+        let decoded = roundtrip(round_robin);
+        let decoded = decoded
+            .as_any()
+            .downcast_ref::<ShuffleWriterExec>()
+            .unwrap();
+        assert!(matches!(
+            decoded.shuffle_output_partitioning(),
+            Some(Partitioning::RoundRobinBatch(4))
+        ));
+        assert!(decoded.range_partitioning().is_none());
+        assert!(!decoded.is_final_stage());
+
+        let decoded = roundtrip(range);
+        let decoded = decoded
+            .as_any()
+            .downcast_ref::<ShuffleWriterExec>()
+            .unwrap();
+        assert!(decoded.is_final_stage());
+        let range_partitioning = decoded.range_partitioning().unwrap();
+        assert_eq!(3, range_partitioning.partition_count());
+        assert_eq!(
+            "a@0 DESC NULLS LAST",
+            range_partitioning.sort_exprs()[0].to_string()
+        );
+        assert_eq!(
+            vec![
+                vec![ScalarValue::Int32(Some(100))],
+                vec![ScalarValue::Int32(Some(10))],
+            ],
+            range_partitioning.boundaries()
+        );
+        assert!(matches!(
+            decoded.shuffle_output_partitioning(),
+            Some(Partitioning::UnknownPartitioning(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn memory_scan_roundtrip() {
+        use crate::serde::BallistaPhysicalExtensionCodec;
+        use datafusion::arrow::array::{Int32Array, StringArray};
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::arrow::record_batch::RecordBatch;
+        use datafusion::physical_plan::common;
+        use datafusion::physical_plan::memory::MemoryExec;
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["x", "y"])),
+            ],
+        )
+        .unwrap();
+        let scan =
+            MemoryExec::try_new(&[vec![batch.clone()], vec![]], schema, Some(vec![1]))
+                .unwrap();
+
+        let codec = BallistaPhysicalExtensionCodec::default();
+        let mut buf: Vec<u8> = vec![];
+        codec.try_encode(Arc::new(scan), &mut buf).unwrap();
+        let ctx = SessionContext::new();
+        let decoded = codec.try_decode(&buf, &[], &ctx).unwrap();
+
+        // the batches are sent projected
+        assert_eq!(
+            2,
+            decoded.properties().output_partitioning().partition_count()
+        );
+        let partition = decoded.execute(0, ctx.task_ctx()).unwrap();
+        let batches = common::collect(partition).await.unwrap();
+        assert_eq!(vec![batch.project(&[1]).unwrap()], batches);
+    }
+
+    #[test]
+    fn shuffle_sort_order_roundtrip() {
+        use crate::execution_plans::{ShuffleReaderExec, UnresolvedShuffleExec};
+        use crate::serde::BallistaPhysicalExtensionCodec;
+        use datafusion::arrow::compute::SortOptions;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::physical_expr::expressions::Column;
+        use datafusion::physical_expr::PhysicalSortExpr;
+        use datafusion::physical_plan::ExecutionPlan;
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let sort_order = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions {
+                descending: true,
+                nulls_first: false,
+            },
+        }];
+        let plans: Vec<Arc<dyn ExecutionPlan>> = vec![
+            Arc::new(
+                ShuffleReaderExec::try_new(1, vec![], schema.clone())
+                    .unwrap()
+                    .with_sort_order(sort_order.clone()),
+            ),
+            Arc::new(
+                UnresolvedShuffleExec::new(1, schema.clone(), 2)
+                    .with_sort_order(sort_order.clone()),
+            ),
+        ];
+
+        let codec = BallistaPhysicalExtensionCodec::default();
+        let ctx = SessionContext::new();
+        for plan in plans {
+            let mut buf: Vec<u8> = vec![];
+            codec.try_encode(plan, &mut buf).unwrap();
+            let decoded = codec.try_decode(&buf, &[], &ctx).unwrap();
+            assert_eq!(
+                Some(sort_order.as_slice()),
+                decoded.properties().output_ordering()
+            );
+        }
+    }
+
+    #[test]
+    fn broadcast_exchange_roundtrip() {
+        use crate::execution_plans::{BroadcastExchangeExec, UnresolvedShuffleExec};
+        use crate::serde::BallistaPhysicalExtensionCodec;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::physical_plan::ExecutionPlan;
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let shuffle: Arc<dyn ExecutionPlan> =
+            Arc::new(UnresolvedShuffleExec::new(2, schema.clone(), 4));
+        let broadcast = BroadcastExchangeExec::new("job".to_owned(), 2, shuffle.clone());
+
+        let codec = BallistaPhysicalExtensionCodec::default();
+        let mut buf: Vec<u8> = vec![];
+        codec.try_encode(Arc::new(broadcast), &mut buf).unwrap();
+        let ctx = SessionContext::new();
+        let decoded = codec.try_decode(&buf, &[shuffle], &ctx).unwrap();
+        let decoded = decoded
+            .as_any()
+            .downcast_ref::<BroadcastExchangeExec>()
+            .unwrap();
+        assert_eq!("job", decoded.job_id());
+        assert_eq!(2, decoded.stage_id());
+        assert_eq!(
+            1,
+            decoded.properties().output_partitioning().partition_count()
+        );
+        assert_eq!(schema, decoded.schema());
+    }
+
+    #[test]
+    fn shuffle_exec_type_conformance_schema_roundtrip() {
+        use crate::execution_plans::{ShuffleReaderExec, UnresolvedShuffleExec};
+        use crate::serde::BallistaPhysicalExtensionCodec;
+        use datafusion::physical_plan::ExecutionPlan;
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+
+        let codec = BallistaPhysicalExtensionCodec::default();
+        let ctx = SessionContext::new();
+
+        for batch in crate::test_utils::type_conformance_batches() {
+            let schema = batch.schema();
+            let plans: Vec<Arc<dyn ExecutionPlan>> = vec![
+                Arc::new(ShuffleReaderExec::try_new(1, vec![], schema.clone()).unwrap()),
+                Arc::new(UnresolvedShuffleExec::new(1, schema.clone(), 2)),
+            ];
+            for plan in plans {
+                let mut buf: Vec<u8> = vec![];
+                codec.try_encode(plan.clone(), &mut buf).unwrap();
+                let decoded = codec.try_decode(&buf, &[], &ctx).unwrap();
+                assert_eq!(schema, decoded.schema(), "{}: {schema}", plan.name());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn remote_table_roundtrip() {
+        use crate::execution_plans::RemoteQueryExec;
+        use crate::federation::RemoteTable;
+        use crate::serde::{
+            BallistaLogicalExtensionCodec, BallistaPhysicalExtensionCodec,
+        };
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::common::tree_node::TreeNode;
+        use datafusion::datasource::file_format::parquet::ParquetFormat;
+        use datafusion::datasource::listing::{
+            ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+        };
+        use datafusion::datasource::source_as_provider;
+        use datafusion::prelude::{col, lit};
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let config =
+            ListingTableConfig::new(ListingTableUrl::parse("file:///remote/t/").unwrap())
+                .with_listing_options(ListingOptions::new(Arc::new(
+                    ParquetFormat::default(),
+                )))
+                .with_schema(schema.clone());
+        let inner = Arc::new(ListingTable::try_new(config).unwrap());
+        let remote = Arc::new(RemoteTable::new("http://remote:50050", inner));
+
+        let ctx = SessionContext::new();
+        ctx.register_table("t", remote).unwrap();
+        let df = ctx
+            .table("t")
+            .await
+            .unwrap()
+            .filter(col("a").lt(lit(10)))
+            .unwrap()
+            .select_columns(&["b"])
+            .unwrap()
+            .limit(0, Some(5))
+            .unwrap();
+
+        // the logical plan referencing the remote table roundtrips
+        let codec = BallistaLogicalExtensionCodec::default();
+        let plan = df.logical_plan().clone();
+        let mut buf: Vec<u8> = vec![];
+        LogicalPlanNode::try_from_logical_plan(&plan, &codec)
+            .unwrap()
+            .try_encode(&mut buf)
+            .unwrap();
+        let decoded = LogicalPlanNode::try_decode(&buf)
+            .unwrap()
+            .try_into_logical_plan(&ctx, &codec)
+            .unwrap();
+        let mut scans = vec![];
+        decoded
+            .apply(|node| {
+                if let LogicalPlan::TableScan(scan) = node {
+                    scans.push(source_as_provider(&scan.source).unwrap());
+                }
+                Ok(datafusion::common::tree_node::TreeNodeRecursion::Continue)
+            })
+            .unwrap();
+        let decoded = scans[0].as_any().downcast_ref::<RemoteTable>().unwrap();
+        assert_eq!("http://remote:50050", decoded.scheduler_url());
+        assert_eq!(schema, decoded.inner().schema());
+
+        // the scan is planned as a subplan of the remote cluster
+        let exec = df.create_physical_plan().await.unwrap();
+        let mut remote_query = None;
+        exec.apply(|node| {
+            if node.as_any().is::<RemoteQueryExec>() {
+                remote_query = Some(node.clone());
+            }
+            Ok(datafusion::common::tree_node::TreeNodeRecursion::Continue)
+        })
+        .unwrap();
+        let remote_query = remote_query.unwrap();
+
+        let physical_codec = BallistaPhysicalExtensionCodec::default();
+        let mut buf: Vec<u8> = vec![];
+        physical_codec
+            .try_encode(remote_query.clone(), &mut buf)
+            .unwrap();
+        let decoded = physical_codec.try_decode(&buf, &[], &ctx).unwrap();
+        assert_eq!(remote_query.schema(), decoded.schema());
+
+        let decoded = decoded.as_any().downcast_ref::<RemoteQueryExec>().unwrap();
+        assert_eq!("http://remote:50050", decoded.scheduler_url());
+        let subplan = LogicalPlanNode::try_decode(decoded.logical_plan())
+            .unwrap()
+            .try_into_logical_plan(&ctx, &codec)
+            .unwrap();
+        let subplan = subplan.display_indent().to_string();
+        assert!(
+            subplan.contains("Filter: remote_table.a < Int32(10)"),
+            "{subplan}"
+        );
+        assert!(subplan.contains("remote_table"), "{subplan}");
+    }
+
+    #[test]
+    fn flight_table_roundtrip() {
+        use crate::execution_plans::{FlightPartition, FlightScanExec};
+        use crate::federation::FlightTableProvider;
+        use crate::serde::{
+            BallistaLogicalExtensionCodec, BallistaPhysicalExtensionCodec,
+        };
+        use arrow_flight::FlightDescriptor;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::datasource::{provider_as_source, source_as_provider};
+        use datafusion::logical_expr::LogicalPlanBuilder;
+        use datafusion::physical_plan::ExecutionPlan;
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+        use std::collections::HashMap;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let headers =
+            HashMap::from([("authorization".to_owned(), "Bearer token".to_owned())]);
+        let descriptor = FlightDescriptor::new_path(vec!["sales".to_owned()]);
+        let provider = FlightTableProvider::new(
+            "http://dremio:32010",
+            descriptor.clone(),
+            headers.clone(),
+            schema.clone(),
+        );
+
+        let ctx = SessionContext::new();
+        let codec = BallistaLogicalExtensionCodec::default();
+        let plan =
+            LogicalPlanBuilder::scan("t", provider_as_source(Arc::new(provider)), None)
+                .unwrap()
+                .build()
+                .unwrap();
+        let mut buf: Vec<u8> = vec![];
+        LogicalPlanNode::try_from_logical_plan(&plan, &codec)
+            .unwrap()
+            .try_encode(&mut buf)
+            .unwrap();
+        let LogicalPlan::TableScan(scan) = LogicalPlanNode::try_decode(&buf)
+            .unwrap()
+            .try_into_logical_plan(&ctx, &codec)
+            .unwrap()
+        else {
+            panic!("expected a table scan");
+        };
+        let provider = source_as_provider(&scan.source).unwrap();
+        let decoded = provider
+            .as_any()
+            .downcast_ref::<FlightTableProvider>()
+            .unwrap();
+        assert_eq!("http://dremio:32010", decoded.endpoint());
+        assert_eq!(&descriptor, decoded.descriptor());
+        assert_eq!(&headers, decoded.headers());
+        assert_eq!(schema, provider.schema());
+
+        let partitions = vec![
+            FlightPartition {
+                ticket: b"ticket-1".to_vec(),
+                locations: vec![],
+            },
+            FlightPartition {
+                ticket: b"ticket-2".to_vec(),
+                locations: vec!["grpc+tls://dremio-2:32010".to_owned()],
+            },
+        ];
+        let scan = FlightScanExec::try_new(
+            "http://dremio:32010".to_owned(),
+            headers.clone(),
+            partitions.clone(),
+            schema,
+            vec![1],
+        )
+        .unwrap();
+
+        let physical_codec = BallistaPhysicalExtensionCodec::default();
+        let mut buf: Vec<u8> = vec![];
+        physical_codec
+            .try_encode(Arc::new(scan.clone()), &mut buf)
+            .unwrap();
+        let decoded = physical_codec.try_decode(&buf, &[], &ctx).unwrap();
+        let decoded = decoded.as_any().downcast_ref::<FlightScanExec>().unwrap();
+        assert_eq!(scan.endpoint(), decoded.endpoint());
+        assert_eq!(&headers, decoded.headers());
+        assert_eq!(partitions, decoded.partitions());
+        assert_eq!(&[1], decoded.projection());
+        assert_eq!(scan.schema(), decoded.schema());
+        assert_eq!(
+            2,
+            decoded.properties().output_partitioning().partition_count()
+        );
+    }
+
+    #[test]
+    fn generate_series_roundtrip() {
+        use crate::serde::BallistaLogicalExtensionCodec;
+        use crate::table_functions::GenerateSeries;
+        use datafusion::datasource::{provider_as_source, source_as_provider};
+        use datafusion::logical_expr::LogicalPlanBuilder;
+
+        let series = GenerateSeries::try_new(10, 1, -3).unwrap();
+        let ctx = SessionContext::new();
+        let codec = BallistaLogicalExtensionCodec::default();
+        let plan =
+            LogicalPlanBuilder::scan("t", provider_as_source(Arc::new(series)), None)
+                .unwrap()
+                .build()
+                .unwrap();
+        let mut buf: Vec<u8> = vec![];
+        LogicalPlanNode::try_from_logical_plan(&plan, &codec)
+            .unwrap()
+            .try_encode(&mut buf)
+            .unwrap();
+        let LogicalPlan::TableScan(scan) = LogicalPlanNode::try_decode(&buf)
+            .unwrap()
+            .try_into_logical_plan(&ctx, &codec)
+            .unwrap()
+        else {
+            panic!("expected a table scan");
+        };
+        let provider = source_as_provider(&scan.source).unwrap();
+        let decoded = provider.as_any().downcast_ref::<GenerateSeries>().unwrap();
+        assert_eq!(&series, decoded);
+    }
+
+    #[test]
+    fn extension_codecs_roundtrip() {
+        use crate::serde::BallistaCodec;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+        use datafusion::catalog::TableProvider;
+        use datafusion::common::{not_impl_err, Result};
+        use datafusion::datasource::empty::EmptyTable;
+        use datafusion::datasource::{provider_as_source, source_as_provider};
+        use datafusion::execution::FunctionRegistry;
+        use datafusion::logical_expr::{Extension, LogicalPlanBuilder};
+        use datafusion::physical_plan::empty::EmptyExec;
+        use datafusion::physical_plan::ExecutionPlan;
+        use datafusion::sql::TableReference;
+        use datafusion_proto::logical_plan::LogicalExtensionCodec;
+        use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+        use datafusion_proto::protobuf::PhysicalPlanNode;
+
+        /// Codec which encodes nothing
+        #[derive(Debug)]
+        struct RejectingCodec;
+
+        impl LogicalExtensionCodec for RejectingCodec {
+            fn try_decode(
+                &self,
+                _buf: &[u8],
+                _inputs: &[LogicalPlan],
+                _ctx: &SessionContext,
+            ) -> Result<Extension> {
+                not_impl_err!("RejectingCodec")
+            }
+
+            fn try_encode(&self, _node: &Extension, _buf: &mut Vec<u8>) -> Result<()> {
+                not_impl_err!("RejectingCodec")
+            }
+
+            fn try_decode_table_provider(
+                &self,
+                _buf: &[u8],
+                _table_ref: &TableReference,
+                _schema: SchemaRef,
+                _ctx: &SessionContext,
+            ) -> Result<Arc<dyn TableProvider>> {
+                not_impl_err!("RejectingCodec")
+            }
+
+            fn try_encode_table_provider(
+                &self,
+                _table_ref: &TableReference,
+                _node: Arc<dyn TableProvider>,
+                _buf: &mut Vec<u8>,
+            ) -> Result<()> {
+                not_impl_err!("RejectingCodec")
+            }
+        }
+
+        impl PhysicalExtensionCodec for RejectingCodec {
+            fn try_decode(
+                &self,
+                _buf: &[u8],
+                _inputs: &[Arc<dyn ExecutionPlan>],
+                _registry: &dyn FunctionRegistry,
+            ) -> Result<Arc<dyn ExecutionPlan>> {
+                not_impl_err!("RejectingCodec")
+            }
+
+            fn try_encode(
+                &self,
+                _node: Arc<dyn ExecutionPlan>,
+                _buf: &mut Vec<u8>,
+            ) -> Result<()> {
+                not_impl_err!("RejectingCodec")
+            }
+        }
+
+        /// Codec of empty tables and plans, standing for third-party ones
+        #[derive(Debug)]
+        struct EmptyCodec;
+
+        impl LogicalExtensionCodec for EmptyCodec {
+            fn try_decode(
+                &self,
+                _buf: &[u8],
+                _inputs: &[LogicalPlan],
+                _ctx: &SessionContext,
+            ) -> Result<Extension> {
+                not_impl_err!("EmptyCodec")
+            }
+
+            fn try_encode(&self, _node: &Extension, _buf: &mut Vec<u8>) -> Result<()> {
+                not_impl_err!("EmptyCodec")
+            }
+
+            fn try_decode_table_provider(
+                &self,
+                buf: &[u8],
+                _table_ref: &TableReference,
+                schema: SchemaRef,
+                _ctx: &SessionContext,
+            ) -> Result<Arc<dyn TableProvider>> {
+                assert_eq!(b"empty", buf);
+                Ok(Arc::new(EmptyTable::new(schema)))
+            }
+
+            fn try_encode_table_provider(
+                &self,
+                _table_ref: &TableReference,
+                node: Arc<dyn TableProvider>,
+                buf: &mut Vec<u8>,
+            ) -> Result<()> {
+                if !node.as_any().is::<EmptyTable>() {
+                    return not_impl_err!("EmptyCodec");
+                }
+                buf.extend_from_slice(b"empty");
+                Ok(())
+            }
+        }
+
+        impl PhysicalExtensionCodec for EmptyCodec {
+            fn try_decode(
+                &self,
+                buf: &[u8],
+                _inputs: &[Arc<dyn ExecutionPlan>],
+                _registry: &dyn FunctionRegistry,
+            ) -> Result<Arc<dyn ExecutionPlan>> {
+                assert_eq!(b"empty", buf);
+                Ok(Arc::new(EmptyExec::new(Arc::new(Schema::empty()))))
+            }
+
+            fn try_encode(
+                &self,
+                node: Arc<dyn ExecutionPlan>,
+                buf: &mut Vec<u8>,
+            ) -> Result<()> {
+                if !node.as_any().is::<EmptyExec>() {
+                    return not_impl_err!("EmptyCodec");
+                }
+                buf.extend_from_slice(b"empty");
+                Ok(())
+            }
+        }
+
+        let codec =
+            BallistaCodec::<LogicalPlanNode, PhysicalPlanNode>::new_with_extension_codecs(
+                vec![Arc::new(RejectingCodec), Arc::new(EmptyCodec)],
+                vec![Arc::new(RejectingCodec), Arc::new(EmptyCodec)],
+            );
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let table = Arc::new(EmptyTable::new(schema.clone()));
+        let plan = LogicalPlanBuilder::scan("t", provider_as_source(table), None)
+            .unwrap()
+            .build()
+            .unwrap();
+        let ctx = SessionContext::new();
+        let mut buf: Vec<u8> = vec![];
+        LogicalPlanNode::try_from_logical_plan(&plan, codec.logical_extension_codec())
+            .unwrap()
+            .try_encode(&mut buf)
+            .unwrap();
+        let LogicalPlan::TableScan(scan) = LogicalPlanNode::try_decode(&buf)
+            .unwrap()
+            .try_into_logical_plan(&ctx, codec.logical_extension_codec())
+            .unwrap()
+        else {
+            panic!("expected a table scan");
+        };
+        let provider = source_as_provider(&scan.source).unwrap();
+        assert!(provider.as_any().is::<EmptyTable>());
+        assert_eq!(schema, provider.schema());
+
+        let mut buf: Vec<u8> = vec![];
+        codec
+            .physical_extension_codec()
+            .try_encode(Arc::new(EmptyExec::new(schema)), &mut buf)
+            .unwrap();
+        let decoded = codec
+            .physical_extension_codec()
+            .try_decode(&buf, &[], &ctx)
+            .unwrap();
+        assert!(decoded.as_any().is::<EmptyExec>());
+
+        // Without the user codecs, the table can't be encoded
+        let mut buf: Vec<u8> = vec![];
+        assert!(LogicalPlanNode::try_from_logical_plan(
+            &plan,
+            &crate::serde::BallistaLogicalExtensionCodec::default()
+        )
+        .and_then(|node| node.try_encode(&mut buf))
+        .is_err());
+    }
+
+    #[test]
+    fn decode_schema_falls_back_to_protobuf_schema() {
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+
+        let schema = Schema::new(vec![Field::new("a", DataType::Int32, false)]);
+        let proto = Some((&schema).try_into().unwrap());
+        assert_eq!(schema, super::decode_schema(&[], &proto).unwrap());
+
+        let arrow_schema = super::encode_arrow_schema(&schema).unwrap();
+        assert_eq!(schema, super::decode_schema(&arrow_schema, &None).unwrap());
+    }
+
+    #[tokio::test]
+    async fn csv_json_table_options_roundtrip() {
+        use crate::serde::BallistaLogicalExtensionCodec;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::common::config::{CsvOptions, JsonOptions};
+        use datafusion::common::parsers::CompressionTypeVariant;
+        use datafusion::datasource::file_format::csv::CsvFormat;
+        use datafusion::datasource::file_format::json::JsonFormat;
+        use datafusion::datasource::file_format::FileFormat;
+        use datafusion::datasource::listing::{
+            ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+        };
+        use datafusion::datasource::{provider_as_source, source_as_provider};
+        use datafusion::logical_expr::LogicalPlanBuilder;
+
+        let roundtrip = |format: Arc<dyn FileFormat>| {
+            let schema =
+                Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+            let config = ListingTableConfig::new(
+                ListingTableUrl::parse("file:///data/t/").unwrap(),
+            )
+            .with_listing_options(ListingOptions::new(format))
+            .with_schema(schema);
+            let table = Arc::new(ListingTable::try_new(config).unwrap());
+            let plan = LogicalPlanBuilder::scan("t", provider_as_source(table), None)
+                .unwrap()
+                .build()
+                .unwrap();
+
+            let ctx = SessionContext::new();
+            let codec = BallistaLogicalExtensionCodec::default();
+            let mut buf: Vec<u8> = vec![];
+            LogicalPlanNode::try_from_logical_plan(&plan, &codec)
+                .unwrap()
+                .try_encode(&mut buf)
+                .unwrap();
+            let decoded = LogicalPlanNode::try_decode(&buf)
+                .unwrap()
+                .try_into_logical_plan(&ctx, &codec)
+                .unwrap();
+            let LogicalPlan::TableScan(scan) = decoded else {
+                panic!("Expected a table scan, got {decoded}");
+            };
+            let table = source_as_provider(&scan.source).unwrap();
+            let table = table.as_any().downcast_ref::<ListingTable>().unwrap();
+            table.options().format.clone()
+        };
+
+        let csv_options = CsvOptions {
+            has_header: Some(true),
+            delimiter: b'|',
+            quote: b'\'',
+            escape: Some(b'\\'),
+            double_quote: Some(true),
+            compression: CompressionTypeVariant::GZIP,
+            schema_infer_max_rec: 10,
+            null_value: Some("NA".to_owned()),
+            ..Default::default()
+        };
+        let format = roundtrip(Arc::new(
+            CsvFormat::default().with_options(csv_options.clone()),
+        ));
+        let format = format.as_any().downcast_ref::<CsvFormat>().unwrap();
+        assert_eq!(&csv_options, format.options());
+
+        let json_options = JsonOptions {
+            compression: CompressionTypeVariant::ZSTD,
+            schema_infer_max_rec: 10,
+        };
+        let format = roundtrip(Arc::new(
+            JsonFormat::default().with_options(json_options.clone()),
+        ));
+        let format = format.as_any().downcast_ref::<JsonFormat>().unwrap();
+        assert_eq!(&json_options, format.options());
+    }
+
+    #[test]
+    fn csv_json_scan_options_roundtrip() {
+        use crate::execution_plans::{with_scan_options, ScanOptionsExec};
+        use crate::serde::BallistaPhysicalExtensionCodec;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+        use datafusion::datasource::listing::PartitionedFile;
+        use datafusion::datasource::physical_plan::{
+            CsvExec, FileScanConfig, NdJsonExec,
+        };
+        use datafusion::execution::object_store::ObjectStoreUrl;
+        use datafusion::physical_plan::ExecutionPlan;
+        use datafusion_proto::physical_plan::AsExecutionPlan;
+        use datafusion_proto::protobuf::PhysicalPlanNode;
+
+        let roundtrip = |plan: Arc<dyn ExecutionPlan>| {
+            let ctx = SessionContext::new();
+            let codec = BallistaPhysicalExtensionCodec::default();
+            let plan = with_scan_options(plan).unwrap();
+            let mut buf: Vec<u8> = vec![];
+            PhysicalPlanNode::try_from_physical_plan(plan, &codec)
+                .unwrap()
+                .try_encode(&mut buf)
+                .unwrap();
+            PhysicalPlanNode::try_decode(&buf)
+                .unwrap()
+                .try_into_physical_plan(&ctx, &ctx.runtime_env(), &codec)
+                .unwrap()
+        };
+        let config = |file: &str| {
+            let schema =
+                Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+            FileScanConfig::new(ObjectStoreUrl::local_filesystem(), schema)
+                .with_file(PartitionedFile::new(file.to_owned(), 100))
+        };
+
+        let csv = CsvExec::builder(config("/data/t/1.csv.bz2"))
+            .with_has_header(true)
+            .with_delimeter(b'|')
+            .with_quote(b'\'')
+            .with_escape(Some(b'\\'))
+            .with_file_compression_type(FileCompressionType::BZIP2)
+            .build();
+        let decoded = roundtrip(Arc::new(csv));
+        let decoded = decoded.as_any().downcast_ref::<CsvExec>().unwrap();
+        assert_eq!(FileCompressionType::BZIP2, decoded.file_compression_type);
+        assert!(decoded.has_header());
+        assert_eq!(b'|', decoded.delimiter());
+        assert_eq!(b'\'', decoded.quote());
+        assert_eq!(Some(b'\\'), decoded.escape());
+
+        let json =
+            NdJsonExec::new(config("/data/t/1.json.gz"), FileCompressionType::GZIP);
+        let decoded = roundtrip(Arc::new(json));
+        assert!(decoded.as_any().is::<NdJsonExec>());
+        // the decoded scan is still seen as compressed when encoded again
+        let wrapped = with_scan_options(decoded).unwrap();
+        let wrapped = wrapped.as_any().downcast_ref::<ScanOptionsExec>().unwrap();
+        assert_eq!(FileCompressionType::GZIP, wrapped.compression());
+
+        // uncompressed scans are serialized by DataFusion alone
+        let plan = with_scan_options(Arc::new(
+            CsvExec::builder(config("/data/t/1.csv")).build(),
+        ))
+        .unwrap();
+        assert!(plan.as_any().is::<CsvExec>());
+    }
 }
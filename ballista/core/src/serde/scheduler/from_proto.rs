@@ -18,25 +18,33 @@
 use chrono::{TimeZone, Utc};
 use datafusion::common::tree_node::{Transformed, TransformedResult, TreeNode};
 
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::execution::SessionStateBuilder;
 use datafusion::logical_expr::{AggregateUDF, ScalarUDF, WindowUDF};
 use datafusion::physical_plan::metrics::{
     Count, Gauge, MetricValue, MetricsSet, Time, Timestamp,
 };
 use datafusion::physical_plan::{ExecutionPlan, Metric};
-use datafusion::prelude::SessionConfig;
+use datafusion::prelude::{SessionConfig, SessionContext};
 use datafusion_proto::logical_plan::AsLogicalPlan;
-use datafusion_proto::physical_plan::AsExecutionPlan;
+use datafusion_proto::physical_plan::from_proto::parse_physical_expr;
+use datafusion_proto::physical_plan::{AsExecutionPlan, DefaultPhysicalExtensionCodec};
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::config::ShuffleCompression;
 use crate::error::BallistaError;
-use crate::extension::SessionConfigHelperExt;
+use crate::extension::{SessionConfigExt, SessionConfigHelperExt};
+use crate::planner::{
+    attach_runtime_predicates, create_job_physical_plan, find_unresolved_shuffles,
+    plan_unresolved_job_stage, resolve_job_stage, stage_plan_fingerprint, StagePlanCache,
+};
 use crate::serde::scheduler::{
     Action, BallistaFunctionRegistry, ExecutorData, ExecutorMetadata,
     ExecutorSpecification, PartitionId, PartitionLocation, PartitionStats,
-    TaskDefinition,
+    ShuffleFormats, TaskDefinition,
 };
 
 use crate::serde::{protobuf, BallistaCodec};
@@ -50,12 +58,16 @@ impl TryInto<Action> for protobuf::Action {
         match self.action_type {
             Some(protobuf::action::ActionType::FetchPartition(fetch)) => {
                 Ok(Action::FetchPartition {
+                    compression: fetch.compression().into(),
                     job_id: fetch.job_id,
                     stage_id: fetch.stage_id as usize,
                     partition_id: fetch.partition_id as usize,
                     path: fetch.path,
                     host: fetch.host,
                     port: fetch.port as u16,
+                    fetch_token: fetch.fetch_token,
+                    transfer_compression: fetch.transfer_compression,
+                    ipc_metadata_version: fetch.ipc_metadata_version,
                 })
             }
             _ => Err(BallistaError::General(
@@ -84,6 +96,7 @@ impl Into<PartitionStats> for protobuf::PartitionStats {
             foo(self.num_batches),
             foo(self.num_bytes),
         )
+        .with_checksum(self.checksum)
     }
 }
 
@@ -100,6 +113,7 @@ impl TryInto<PartitionLocation> for protobuf::PartitionLocation {
 
     fn try_into(self) -> Result<PartitionLocation, Self::Error> {
         Ok(PartitionLocation {
+            compression: self.compression().into(),
             map_partition_id: self.map_partition_id as usize,
             partition_id: self
                 .partition_id
@@ -126,10 +140,24 @@ impl TryInto<PartitionLocation> for protobuf::PartitionLocation {
                 })?
                 .into(),
             path: self.path,
+            inline_data: (!self.inline_data.is_empty()).then_some(self.inline_data),
+            ipc_metadata_version: self.ipc_metadata_version,
         })
     }
 }
 
+impl From<protobuf::ShuffleCompression> for ShuffleCompression {
+    fn from(compression: protobuf::ShuffleCompression) -> Self {
+        match compression {
+            protobuf::ShuffleCompression::Lz4Frame => ShuffleCompression::Lz4,
+            protobuf::ShuffleCompression::Zstd => ShuffleCompression::Zstd,
+            protobuf::ShuffleCompression::Uncompressed => {
+                ShuffleCompression::Uncompressed
+            }
+        }
+    }
+}
+
 impl TryInto<MetricValue> for protobuf::OperatorMetric {
     type Error = BallistaError;
 
@@ -234,6 +262,22 @@ impl Into<ExecutorMetadata> for protobuf::ExecutorMetadata {
             port: self.port as u16,
             grpc_port: self.grpc_port as u16,
             specification: self.specification.unwrap().into(),
+            version: self.version,
+            functions: self.functions.map(|functions| functions.names),
+            shuffle_formats: self.shuffle_formats.map(Into::into),
+            pool: self.pool,
+        }
+    }
+}
+
+impl From<protobuf::ShuffleFormats> for ShuffleFormats {
+    fn from(formats: protobuf::ShuffleFormats) -> Self {
+        ShuffleFormats {
+            compressions: formats
+                .compressions()
+                .map(ShuffleCompression::from)
+                .collect(),
+            max_ipc_metadata_version: formats.max_ipc_metadata_version,
         }
     }
 }
@@ -283,7 +327,10 @@ impl Into<ExecutorData> for protobuf::ExecutorData {
     }
 }
 
-pub fn get_task_definition<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
+pub async fn get_task_definition<
+    T: 'static + AsLogicalPlan,
+    U: 'static + AsExecutionPlan,
+>(
     task: protobuf::TaskDefinition,
     produce_runtime: RuntimeProducer,
     session_config: SessionConfig,
@@ -292,7 +339,10 @@ pub fn get_task_definition<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     window_functions: HashMap<String, Arc<WindowUDF>>,
     codec: BallistaCodec<T, U>,
 ) -> Result<TaskDefinition, BallistaError> {
-    let session_config = session_config.update_from_key_value_pair(&task.props);
+    let session_config = session_config
+        .update_from_key_value_pair(&task.props)
+        .with_ballista_fetch_token(task.fetch_token.clone().into());
+    session_config.check_task_key_value_pairs(&task.props)?;
 
     let mut task_scalar_functions = HashMap::new();
     let mut task_aggregate_functions = HashMap::new();
@@ -307,23 +357,32 @@ pub fn get_task_definition<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     for agg_func in window_functions {
         task_window_functions.insert(agg_func.0, agg_func.1);
     }
-    let function_registry = Arc::new(BallistaFunctionRegistry {
-        scalar_functions: task_scalar_functions,
-        aggregate_functions: task_aggregate_functions,
-        window_functions: task_window_functions,
-    });
+    let function_registry = Arc::new(
+        BallistaFunctionRegistry {
+            scalar_functions: task_scalar_functions,
+            aggregate_functions: task_aggregate_functions,
+            window_functions: task_window_functions,
+        }
+        .with_random_seed(task.random_seed),
+    );
     let runtime = produce_runtime(&session_config)?;
-    let encoded_plan = task.plan.as_slice();
-    let plan: Arc<dyn ExecutionPlan> = U::try_decode(encoded_plan).and_then(|proto| {
-        proto.try_into_physical_plan(
-            function_registry.as_ref(),
-            runtime.as_ref(),
-            codec.physical_extension_codec(),
-        )
-    })?;
+    let stage_id = task.stage_id as usize;
+    let plan = decode_task_plan(
+        task.plan_format(),
+        &task.plan,
+        &task.plan_compression,
+        &task.inputs,
+        task.plan_fingerprint,
+        &task.job_id,
+        stage_id,
+        &session_config,
+        &function_registry,
+        runtime,
+        &codec,
+    )
+    .await?;
 
     let job_id = task.job_id;
-    let stage_id = task.stage_id as usize;
     let partition_id = task.partition_id as usize;
     let task_attempt_num = task.task_attempt_num as usize;
     let stage_attempt_num = task.stage_attempt_num as usize;
@@ -346,7 +405,7 @@ pub fn get_task_definition<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     })
 }
 
-pub fn get_task_definition_vec<
+pub async fn get_task_definition_vec<
     T: 'static + AsLogicalPlan,
     U: 'static + AsExecutionPlan,
 >(
@@ -358,7 +417,10 @@ pub fn get_task_definition_vec<
     window_functions: HashMap<String, Arc<WindowUDF>>,
     codec: BallistaCodec<T, U>,
 ) -> Result<Vec<TaskDefinition>, BallistaError> {
-    let session_config = session_config.update_from_key_value_pair(&multi_task.props);
+    let session_config = session_config
+        .update_from_key_value_pair(&multi_task.props)
+        .with_ballista_fetch_token(multi_task.fetch_token.clone().into());
+    session_config.check_task_key_value_pairs(&multi_task.props)?;
 
     let mut task_scalar_functions = HashMap::new();
     let mut task_aggregate_functions = HashMap::new();
@@ -373,48 +435,276 @@ pub fn get_task_definition_vec<
     for agg_func in window_functions {
         task_window_functions.insert(agg_func.0, agg_func.1);
     }
-    let function_registry = Arc::new(BallistaFunctionRegistry {
+    let functions = BallistaFunctionRegistry {
         scalar_functions: task_scalar_functions,
         aggregate_functions: task_aggregate_functions,
         window_functions: task_window_functions,
-    });
+    };
 
     let runtime = runtime_producer(&session_config)?;
-
-    let encoded_plan = multi_task.plan.as_slice();
-    let plan: Arc<dyn ExecutionPlan> = U::try_decode(encoded_plan).and_then(|proto| {
-        proto.try_into_physical_plan(
-            function_registry.as_ref(),
-            runtime.as_ref(),
-            codec.physical_extension_codec(),
-        )
-    })?;
+    let stage_id = multi_task.stage_id as usize;
+    // The tasks share the plan, unless it calls random(), which draws different values
+    // in each task
+    let mut task_plans: Vec<(Arc<dyn ExecutionPlan>, Arc<BallistaFunctionRegistry>)> =
+        Vec::with_capacity(multi_task.task_ids.len());
+    for task_id in multi_task.task_ids.iter() {
+        let (plan, function_registry) = match task_plans.first() {
+            Some((plan, function_registry))
+                if !function_registry.is_random_referenced() =>
+            {
+                (
+                    reset_metrics_for_execution_plan(Arc::clone(plan))?,
+                    Arc::clone(function_registry),
+                )
+            }
+            _ => {
+                let function_registry =
+                    Arc::new(functions.with_random_seed(task_id.random_seed));
+                let plan = decode_task_plan(
+                    multi_task.plan_format(),
+                    &multi_task.plan,
+                    &multi_task.plan_compression,
+                    &multi_task.inputs,
+                    multi_task.plan_fingerprint,
+                    &multi_task.job_id,
+                    stage_id,
+                    &session_config,
+                    &function_registry,
+                    runtime.clone(),
+                    &codec,
+                )
+                .await?;
+                (plan, function_registry)
+            }
+        };
+        task_plans.push((plan, function_registry));
+    }
 
     let job_id = multi_task.job_id;
-    let stage_id = multi_task.stage_id as usize;
     let stage_attempt_num = multi_task.stage_attempt_num as usize;
     let launch_time = multi_task.launch_time;
-    let task_ids = multi_task.task_ids;
     let session_id = multi_task.session_id;
 
-    task_ids
+    Ok(multi_task
+        .task_ids
         .iter()
-        .map(|task_id| {
-            Ok(TaskDefinition {
-                task_id: task_id.task_id as usize,
-                task_attempt_num: task_id.task_attempt_num as usize,
-                job_id: job_id.clone(),
+        .zip(task_plans)
+        .map(|(task_id, (plan, function_registry))| TaskDefinition {
+            task_id: task_id.task_id as usize,
+            task_attempt_num: task_id.task_attempt_num as usize,
+            job_id: job_id.clone(),
+            stage_id,
+            stage_attempt_num,
+            partition_id: task_id.partition_id as usize,
+            plan,
+            launch_time,
+            session_id: session_id.clone(),
+            session_config: session_config.clone(),
+            function_registry,
+        })
+        .collect())
+}
+
+/// Decodes the plan dispatched with the tasks of a stage. Logical plans of jobs are
+/// planned physically and split into stages the way the scheduler does, the stage of the
+/// tasks reading the partitions of its input stages listed in `inputs`, and checked
+/// against `plan_fingerprint`, the fingerprint of the stage planned by the scheduler,
+/// unless 0. The unresolved stage is kept in the [StagePlanCache] of the session, if
+/// any, for the other tasks of the stage. Plans are decompressed first with the codec
+/// named `plan_compression` of the session, if any.
+#[allow(clippy::too_many_arguments)]
+pub async fn decode_task_plan<
+    T: 'static + AsLogicalPlan,
+    U: 'static + AsExecutionPlan,
+>(
+    plan_format: protobuf::TaskPlanFormat,
+    encoded_plan: &[u8],
+    plan_compression: &str,
+    inputs: &[protobuf::StageInput],
+    plan_fingerprint: u64,
+    job_id: &str,
+    stage_id: usize,
+    session_config: &SessionConfig,
+    function_registry: &BallistaFunctionRegistry,
+    runtime: Arc<RuntimeEnv>,
+    codec: &BallistaCodec<T, U>,
+) -> Result<Arc<dyn ExecutionPlan>, BallistaError> {
+    match plan_format {
+        protobuf::TaskPlanFormat::PhysicalPlan => {
+            let encoded_plan = session_config
+                .ballista_compression_codecs()
+                .decompress(plan_compression, encoded_plan)?;
+            Ok(U::try_decode(encoded_plan.as_ref()).and_then(|proto| {
+                proto.try_into_physical_plan(
+                    function_registry,
+                    runtime.as_ref(),
+                    codec.physical_extension_codec(),
+                )
+            })?)
+        }
+        protobuf::TaskPlanFormat::LogicalPlan => {
+            let stage_plan_cache = session_config.get_extension::<StagePlanCache>();
+            let stage = match stage_plan_cache
+                .as_ref()
+                .and_then(|cache| cache.get(job_id, stage_id))
+            {
+                Some(stage) => stage,
+                None => {
+                    let encoded_plan = session_config
+                        .ballista_compression_codecs()
+                        .decompress(plan_compression, encoded_plan)?;
+                    let stage = plan_logical_task_stage(
+                        encoded_plan.as_ref(),
+                        job_id,
+                        stage_id,
+                        session_config,
+                        function_registry,
+                        runtime,
+                        codec,
+                    )
+                    .await?;
+                    // plans calling random() draw different values in each task
+                    if let Some(cache) = &stage_plan_cache {
+                        if !function_registry.is_random_referenced() {
+                            cache.insert(job_id, stage_id, stage.clone());
+                        }
+                    }
+                    stage
+                }
+            };
+            resolve_logical_task_stage(
+                stage,
+                inputs,
+                plan_fingerprint,
+                job_id,
                 stage_id,
-                stage_attempt_num,
-                partition_id: task_id.partition_id as usize,
-                plan: reset_metrics_for_execution_plan(plan.clone())?,
-                launch_time,
-                session_id: session_id.clone(),
-                session_config: session_config.clone(),
-                function_registry: function_registry.clone(),
+                session_config,
+                function_registry,
+            )
+        }
+    }
+}
+
+/// Resolves the unresolved stage `stage_id` of a job dispatched as a logical plan with
+/// `inputs`, checking it against `plan_fingerprint` unless 0
+fn resolve_logical_task_stage(
+    stage: Arc<dyn ExecutionPlan>,
+    inputs: &[protobuf::StageInput],
+    plan_fingerprint: u64,
+    job_id: &str,
+    stage_id: usize,
+    session_config: &SessionConfig,
+    function_registry: &BallistaFunctionRegistry,
+) -> Result<Arc<dyn ExecutionPlan>, BallistaError> {
+    // the locations are grouped by the partition they belong to rather than by the
+    // partition of the reader, which coalesced partitions hold several of
+    let mut input_locations = HashMap::new();
+    for input in inputs {
+        let mut partition_locations: HashMap<usize, Vec<PartitionLocation>> =
+            HashMap::new();
+        for partition in &input.partition {
+            for location in &partition.location {
+                let location: PartitionLocation = location.clone().try_into()?;
+                partition_locations
+                    .entry(location.partition_id.partition_id)
+                    .or_default()
+                    .push(location);
+            }
+        }
+        input_locations.insert(input.stage_id as usize, partition_locations);
+    }
+    let unresolved_shuffles = find_unresolved_shuffles(&stage)?;
+    let mut resolved_stage =
+        resolve_job_stage(job_id, stage_id, stage, session_config, &input_locations)?;
+    // the tasks of a stage don't share the metrics of its cached plan
+    resolved_stage = reset_metrics_for_execution_plan(resolved_stage)?;
+
+    if plan_fingerprint != 0 {
+        let fingerprint = stage_plan_fingerprint(&resolved_stage)?;
+        if fingerprint != plan_fingerprint {
+            return Err(BallistaError::Internal(format!(
+                "Stage {stage_id} of job {job_id} is planned with fingerprint \
+                {fingerprint} instead of {plan_fingerprint}, the plan of the scheduler"
+            )));
+        }
+    }
+
+    for input in inputs
+        .iter()
+        .filter(|input| !input.runtime_predicates.is_empty())
+    {
+        let Some(shuffle) = unresolved_shuffles
+            .iter()
+            .find(|shuffle| shuffle.stage_id == input.stage_id as usize)
+        else {
+            continue;
+        };
+        let predicates = input
+            .runtime_predicates
+            .iter()
+            .map(|predicate| {
+                parse_physical_expr(
+                    predicate,
+                    function_registry,
+                    shuffle.schema().as_ref(),
+                    &DefaultPhysicalExtensionCodec {},
+                )
             })
-        })
-        .collect()
+            .collect::<Result<Vec<_>, _>>()?;
+        resolved_stage = attach_runtime_predicates(
+            resolved_stage,
+            input.stage_id as usize,
+            &predicates,
+        )?;
+    }
+    Ok(resolved_stage)
+}
+
+/// Plans the unresolved stage `stage_id` from the encoded logical plan of its job
+async fn plan_logical_task_stage<
+    T: 'static + AsLogicalPlan,
+    U: 'static + AsExecutionPlan,
+>(
+    encoded_plan: &[u8],
+    job_id: &str,
+    stage_id: usize,
+    session_config: &SessionConfig,
+    function_registry: &BallistaFunctionRegistry,
+    runtime: Arc<RuntimeEnv>,
+    codec: &BallistaCodec<T, U>,
+) -> Result<Arc<dyn ExecutionPlan>, BallistaError> {
+    let state = SessionStateBuilder::new()
+        .with_default_features()
+        .with_config(session_config.clone())
+        .with_runtime_env(runtime)
+        .with_scalar_functions(
+            function_registry
+                .scalar_functions
+                .values()
+                .cloned()
+                .collect(),
+        )
+        .with_aggregate_functions(
+            function_registry
+                .aggregate_functions
+                .values()
+                .cloned()
+                .collect(),
+        )
+        .with_window_functions(
+            function_registry
+                .window_functions
+                .values()
+                .cloned()
+                .collect(),
+        )
+        .build();
+    let ctx = SessionContext::new_with_state(state.clone());
+    let plan = T::try_decode(encoded_plan).and_then(|proto| {
+        proto.try_into_logical_plan(&ctx, codec.logical_extension_codec())
+    })?;
+    let plan = create_job_physical_plan(state, &plan).await?;
+    plan_unresolved_job_stage(job_id, stage_id, plan, session_config)
 }
 
 fn reset_metrics_for_execution_plan(
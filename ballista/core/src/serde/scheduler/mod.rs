@@ -15,6 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::config::{ShuffleCompression, SHUFFLE_IPC_METADATA_VERSION};
 use crate::error::BallistaError;
 use crate::registry::BallistaFunctionRegistry;
 use datafusion::arrow::array::{
@@ -42,6 +43,20 @@ pub enum Action {
         path: String,
         host: String,
         port: u16,
+        /// Codec compressing the partition when it is sent
+        compression: ShuffleCompression,
+        /// Token of the job of the partition, see [FetchToken]
+        ///
+        /// [FetchToken]: crate::security::FetchToken
+        fetch_token: String,
+        /// Codec of the [CompressionCodecRegistry] the partition is sent with, rather
+        /// than `compression`, empty if none
+        ///
+        /// [CompressionCodecRegistry]: crate::compression::CompressionCodecRegistry
+        transfer_compression: String,
+        /// Arrow IPC metadata version the partition is sent in, 0 for the version of
+        /// the executor holding it
+        ipc_metadata_version: u32,
     },
 }
 
@@ -70,6 +85,14 @@ pub struct PartitionLocation {
     pub executor_meta: ExecutorMetadata,
     pub partition_stats: PartitionStats,
     pub path: String,
+    /// Partition data in Arrow IPC stream format, if it was small enough to be inlined
+    /// in the task status instead of being fetched from the executor
+    pub inline_data: Option<Vec<u8>>,
+    /// Codec the partition was compressed with when written
+    pub compression: ShuffleCompression,
+    /// Arrow IPC metadata version the partition was written in, 0 if the executor
+    /// which wrote it didn't record it, i.e. [SHUFFLE_IPC_METADATA_VERSION]
+    pub ipc_metadata_version: u32,
 }
 
 /// Meta-data for an executor, used when fetching shuffle partitions from other executors
@@ -80,6 +103,57 @@ pub struct ExecutorMetadata {
     pub port: u16,
     pub grpc_port: u16,
     pub specification: ExecutorSpecification,
+    /// Ballista and DataFusion version the executor was built with
+    pub version: String,
+    /// Names of the functions registered on the executor on top of the built-in
+    /// functions of DataFusion, none if the executor doesn't advertise them
+    pub functions: Option<Vec<String>>,
+    /// Formats of the shuffle partitions the executor reads, none if the executor
+    /// doesn't advertise them
+    pub shuffle_formats: Option<ShuffleFormats>,
+    /// Pool of executors the executor joined, empty for the shared pool, which runs
+    /// the jobs of the tenants without a pool of their own
+    pub pool: String,
+}
+
+/// Arrow IPC options of the shuffle partitions an executor reads. Executors of
+/// different versions of Ballista may not read the partitions of each other, e.g. as
+/// they were built without a codec, in which case the partitions are fetched from the
+/// executors which wrote them, re-encoded in a format the reader supports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShuffleFormats {
+    pub compressions: Vec<ShuffleCompression>,
+    /// Latest Arrow IPC metadata version the executor reads
+    pub max_ipc_metadata_version: u32,
+}
+
+impl ShuffleFormats {
+    /// The formats this build of Ballista reads
+    pub fn supported() -> Self {
+        Self {
+            compressions: vec![
+                ShuffleCompression::Lz4,
+                ShuffleCompression::Zstd,
+                ShuffleCompression::Uncompressed,
+            ],
+            max_ipc_metadata_version: SHUFFLE_IPC_METADATA_VERSION,
+        }
+    }
+
+    /// Whether a partition written with `compression` in Arrow IPC metadata version
+    /// `ipc_metadata_version`, 0 if unknown, can be read
+    pub fn reads(
+        &self,
+        compression: ShuffleCompression,
+        ipc_metadata_version: u32,
+    ) -> bool {
+        let ipc_metadata_version = match ipc_metadata_version {
+            0 => SHUFFLE_IPC_METADATA_VERSION,
+            version => version,
+        };
+        self.compressions.contains(&compression)
+            && ipc_metadata_version <= self.max_ipc_metadata_version
+    }
 }
 
 /// Specification of an executor, indicting executor resources, like total task slots
@@ -107,6 +181,8 @@ pub struct PartitionStats {
     pub(crate) num_rows: Option<u64>,
     pub(crate) num_batches: Option<u64>,
     pub(crate) num_bytes: Option<u64>,
+    /// Order-insensitive checksum of the rows, when the partition was checksummed
+    pub(crate) checksum: Option<u64>,
 }
 
 impl fmt::Display for PartitionStats {
@@ -129,9 +205,20 @@ impl PartitionStats {
             num_rows,
             num_batches,
             num_bytes,
+            checksum: None,
         }
     }
 
+    pub fn with_checksum(mut self, checksum: Option<u64>) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Bytes of the partition, if known
+    pub fn num_bytes(&self) -> Option<u64> {
+        self.num_bytes
+    }
+
     pub fn arrow_struct_repr(self) -> Field {
         Field::new(
             "partition_stats",
@@ -201,6 +288,7 @@ impl PartitionStats {
             num_rows: Some(num_rows.value(0).to_owned()),
             num_batches: Some(num_batches.value(0).to_owned()),
             num_bytes: Some(num_bytes.value(0).to_owned()),
+            checksum: None,
         }
     }
 }
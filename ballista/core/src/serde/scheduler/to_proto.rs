@@ -19,6 +19,7 @@ use datafusion::error::DataFusionError;
 use datafusion::physical_plan::metrics::{MetricValue, MetricsSet};
 use std::convert::TryInto;
 
+use crate::config::ShuffleCompression;
 use crate::error::BallistaError;
 
 use crate::serde::protobuf;
@@ -26,7 +27,7 @@ use datafusion_proto::protobuf as datafusion_protobuf;
 
 use crate::serde::scheduler::{
     Action, ExecutorData, ExecutorMetadata, ExecutorSpecification, PartitionId,
-    PartitionLocation, PartitionStats,
+    PartitionLocation, PartitionStats, ShuffleFormats,
 };
 use datafusion::physical_plan::Partitioning;
 use protobuf::{action::ActionType, operator_metric, NamedCount, NamedGauge, NamedTime};
@@ -43,6 +44,10 @@ impl TryInto<protobuf::Action> for Action {
                 path,
                 host,
                 port,
+                compression,
+                fetch_token,
+                transfer_compression,
+                ipc_metadata_version,
             } => Ok(protobuf::Action {
                 action_type: Some(ActionType::FetchPartition(protobuf::FetchPartition {
                     job_id,
@@ -51,6 +56,10 @@ impl TryInto<protobuf::Action> for Action {
                     path,
                     host,
                     port: port as u32,
+                    compression: protobuf::ShuffleCompression::from(compression) as i32,
+                    fetch_token,
+                    transfer_compression,
+                    ipc_metadata_version,
                 })),
                 settings: vec![],
             }),
@@ -79,10 +88,26 @@ impl TryInto<protobuf::PartitionLocation> for PartitionLocation {
             executor_meta: Some(self.executor_meta.into()),
             partition_stats: Some(self.partition_stats.into()),
             path: self.path,
+            inline_data: self.inline_data.unwrap_or_default(),
+            compression: protobuf::ShuffleCompression::from(self.compression) as i32,
+            fetch_token: String::new(),
+            ipc_metadata_version: self.ipc_metadata_version,
         })
     }
 }
 
+impl From<ShuffleCompression> for protobuf::ShuffleCompression {
+    fn from(compression: ShuffleCompression) -> Self {
+        match compression {
+            ShuffleCompression::Lz4 => protobuf::ShuffleCompression::Lz4Frame,
+            ShuffleCompression::Zstd => protobuf::ShuffleCompression::Zstd,
+            ShuffleCompression::Uncompressed => {
+                protobuf::ShuffleCompression::Uncompressed
+            }
+        }
+    }
+}
+
 #[allow(clippy::from_over_into)]
 impl Into<protobuf::PartitionStats> for PartitionStats {
     fn into(self) -> protobuf::PartitionStats {
@@ -92,6 +117,7 @@ impl Into<protobuf::PartitionStats> for PartitionStats {
             num_batches: self.num_batches.map(|n| n as i64).unwrap_or(none_value),
             num_bytes: self.num_bytes.map(|n| n as i64).unwrap_or(none_value),
             column_stats: vec![],
+            checksum: self.checksum,
         }
     }
 }
@@ -202,6 +228,25 @@ impl Into<protobuf::ExecutorMetadata> for ExecutorMetadata {
             port: self.port as u32,
             grpc_port: self.grpc_port as u32,
             specification: Some(self.specification.into()),
+            version: self.version,
+            functions: self
+                .functions
+                .map(|names| protobuf::ExecutorFunctions { names }),
+            shuffle_formats: self.shuffle_formats.map(Into::into),
+            pool: self.pool,
+        }
+    }
+}
+
+impl From<ShuffleFormats> for protobuf::ShuffleFormats {
+    fn from(formats: ShuffleFormats) -> Self {
+        protobuf::ShuffleFormats {
+            compressions: formats
+                .compressions
+                .into_iter()
+                .map(|compression| protobuf::ShuffleCompression::from(compression) as i32)
+                .collect(),
+            max_ipc_metadata_version: formats.max_ipc_metadata_version,
         }
     }
 }
@@ -0,0 +1,114 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Staging prefixes of the jobs in object stores, under which they write their
+//! temporary data, i.e. the URL of `ballista.job.staging_url` or of
+//! `ballista.shuffle.object_store_url` followed by the job ID. The scheduler deletes
+//! them once the jobs complete, so that no temporary data is left in the buckets.
+
+use std::sync::Arc;
+
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::object_store::ObjectStoreUrl;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::prelude::SessionConfig;
+use futures::{StreamExt, TryStreamExt};
+use object_store::path::Path;
+use object_store::ObjectStore;
+use url::{Position, Url};
+
+use crate::extension::SessionConfigExt;
+
+/// The staging prefix of a job under the object store URL `staging_url`
+pub fn job_staging_prefix(staging_url: &str, job_id: &str) -> String {
+    format!("{}/{job_id}", staging_url.trim_end_matches('/'))
+}
+
+/// The object store URLs the jobs of a session write temporary data under, their
+/// staging URL and the URL shuffle files are uploaded to
+pub fn job_staging_urls(config: &SessionConfig) -> Vec<String> {
+    let mut urls: Vec<String> = config
+        .ballista_job_staging_url()
+        .into_iter()
+        .chain(config.ballista_shuffle_object_store_url())
+        .map(|url| url.trim_end_matches('/').to_owned())
+        .collect();
+    urls.dedup();
+    urls
+}
+
+/// Recursively deletes the objects under a prefix, using the object store registered
+/// in the runtime for it. Returns the number of objects deleted
+pub async fn remove_staging_prefix(runtime: &RuntimeEnv, prefix: &str) -> Result<usize> {
+    let (store, path) = resolve_object(runtime, prefix)?;
+    let locations = store.list(Some(&path)).map_ok(|meta| meta.location).boxed();
+    let deleted = store
+        .delete_stream(locations)
+        .try_fold(0, |deleted, _| async move { Ok(deleted + 1) })
+        .await?;
+    Ok(deleted)
+}
+
+/// The object store registered in the runtime for a URI, and the path of the object
+pub(crate) fn resolve_object(
+    runtime: &RuntimeEnv,
+    uri: &str,
+) -> Result<(Arc<dyn ObjectStore>, Path)> {
+    let url = Url::parse(uri).map_err(|e| {
+        DataFusionError::Configuration(format!("Invalid object URI {uri}: {e}"))
+    })?;
+    let store =
+        runtime.object_store(ObjectStoreUrl::parse(&url[..Position::BeforePath])?)?;
+    let path = Path::from_url_path(url.path())?;
+    Ok((store, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn test_remove_staging_prefix() -> Result<()> {
+        let runtime = RuntimeEnv::default();
+        let store = Arc::new(InMemory::new());
+        runtime.register_object_store(
+            &Url::parse("memory://staging").unwrap(),
+            store.clone(),
+        );
+        for path in ["job1/a", "job1/1/0/data.arrow", "job10/a", "job2/a"] {
+            store.put(&Path::from(path), "data".into()).await?;
+        }
+
+        let prefix = job_staging_prefix("memory://staging/", "job1");
+        assert_eq!("memory://staging/job1", prefix);
+        assert_eq!(2, remove_staging_prefix(&runtime, &prefix).await?);
+        let left: Vec<String> = store
+            .list(None)
+            .map_ok(|meta| meta.location.to_string())
+            .try_collect()
+            .await?;
+        assert_eq!(vec!["job10/a", "job2/a"], left);
+
+        let config = SessionConfig::new_with_ballista()
+            .with_ballista_job_staging_url("s3://bucket/staging/")
+            .with_ballista_shuffle_object_store_url("s3://bucket/staging");
+        assert_eq!(vec!["s3://bucket/staging"], job_staging_urls(&config));
+        assert!(job_staging_urls(&SessionConfig::new_with_ballista()).is_empty());
+        Ok(())
+    }
+}
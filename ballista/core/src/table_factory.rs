@@ -0,0 +1,298 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Creation of the tables of `CREATE EXTERNAL TABLE` statements.
+//!
+//! Tables are created by DataFusion's [ListingTableFactory], with the schema settings
+//! of the session applied on top of it. The resulting table definition, including the
+//! types of the partition columns, is serialized with the scans of the table, so the
+//! scheduler and the executors never infer the schema on their own.
+
+use crate::extension::SessionConfigExt;
+use crate::schema_evolution::infer_merged_schema;
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::catalog::{Session, TableProvider, TableProviderFactory};
+use datafusion::common::{DFSchema, DataFusionError, Result};
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
+use datafusion::datasource::listing_table_factory::ListingTableFactory;
+use datafusion::execution::session_state::{SessionState, SessionStateBuilder};
+use datafusion::logical_expr::CreateExternalTable;
+use std::sync::Arc;
+
+/// File types of the tables created by a [BallistaListingTableFactory]
+const LISTING_FILE_TYPES: [&str; 6] =
+    ["PARQUET", "CSV", "JSON", "NDJSON", "AVRO", "ARROW"];
+
+/// Factory of the tables of the file formats, which behaves like DataFusion's
+/// [ListingTableFactory] except that:
+///
+/// * Parquet tables without a declared schema get the
+///   [merged schema](infer_merged_schema) of their files when
+///   `ballista.scan.merge_schemas` is set
+/// * partition columns get the types set by `ballista.scan.partition_column_types`,
+///   rather than their declared types or `Utf8`
+#[derive(Debug, Default)]
+pub struct BallistaListingTableFactory {
+    inner: ListingTableFactory,
+}
+
+#[async_trait]
+impl TableProviderFactory for BallistaListingTableFactory {
+    async fn create(
+        &self,
+        state: &dyn Session,
+        cmd: &CreateExternalTable,
+    ) -> Result<Arc<dyn TableProvider>> {
+        let Some(session_state) = state.as_any().downcast_ref::<SessionState>() else {
+            return self.inner.create(state, cmd).await;
+        };
+
+        let merged;
+        let cmd = match merge_schemas(session_state, cmd).await? {
+            Some(cmd) => {
+                merged = cmd;
+                &merged
+            }
+            None => cmd,
+        };
+        let table = self.inner.create(state, cmd).await?;
+
+        with_partition_column_types(session_state, cmd, table)
+    }
+}
+
+/// Declare the merged schema of the files of a Parquet table, if enabled and the
+/// schema of the table is not declared
+async fn merge_schemas(
+    state: &SessionState,
+    cmd: &CreateExternalTable,
+) -> Result<Option<CreateExternalTable>> {
+    if !state.config().ballista_merge_schemas()
+        || !cmd.file_type.eq_ignore_ascii_case("PARQUET")
+        || !cmd.schema.fields().is_empty()
+    {
+        return Ok(None);
+    }
+
+    let table_path = ListingTableUrl::parse(&cmd.location)?;
+    let format =
+        ParquetFormat::default().with_options(state.default_table_options().parquet);
+    let options = ListingOptions::new(Arc::new(format));
+    let Some(schema) = infer_merged_schema(state, &table_path, &options).await? else {
+        return Ok(None);
+    };
+
+    // Partition columns are not stored in the files, but a declared schema must end
+    // with them
+    let mut fields = schema.fields().to_vec();
+    for column in &cmd.table_partition_cols {
+        if schema.field_with_name(column).is_err() {
+            fields.push(Arc::new(Field::new(column, DataType::Utf8, true)));
+        }
+    }
+
+    Ok(Some(CreateExternalTable {
+        schema: Arc::new(DFSchema::try_from(Schema::new(fields))?),
+        ..cmd.clone()
+    }))
+}
+
+/// Recreate a listing table with the types of its partition columns set by
+/// `ballista.scan.partition_column_types`, if they differ from its own
+fn with_partition_column_types(
+    state: &SessionState,
+    cmd: &CreateExternalTable,
+    table: Arc<dyn TableProvider>,
+) -> Result<Arc<dyn TableProvider>> {
+    let Some(listing_table) = table.as_any().downcast_ref::<ListingTable>() else {
+        return Ok(table);
+    };
+
+    let mut options = listing_table.options().clone();
+    let mut retyped = false;
+    for (column, data_type) in options.table_partition_cols.iter_mut() {
+        let column_type = state
+            .config()
+            .ballista_partition_column_type(column)
+            .map_err(DataFusionError::Configuration)?;
+        if let Some(column_type) = column_type.filter(|t| t != &*data_type) {
+            *data_type = column_type;
+            retyped = true;
+        }
+    }
+    if !retyped {
+        return Ok(table);
+    }
+
+    // the schema of a listing table ends with its partition columns
+    let schema = listing_table.schema();
+    let num_file_fields = schema.fields().len() - options.table_partition_cols.len();
+    let file_schema = Schema::new(schema.fields()[..num_file_fields].to_vec());
+    let config =
+        ListingTableConfig::new_with_multi_paths(listing_table.table_paths().clone())
+            .with_listing_options(options)
+            .with_schema(Arc::new(file_schema));
+    let table = ListingTable::try_new(config)?
+        .with_cache(state.runtime_env().cache_manager.get_file_statistic_cache())
+        .with_definition(cmd.definition.clone())
+        .with_constraints(cmd.constraints.clone())
+        .with_column_defaults(cmd.column_defaults.clone());
+
+    Ok(Arc::new(table))
+}
+
+/// Create the tables of the file formats with a [BallistaListingTableFactory]
+pub(crate) fn with_ballista_table_factories(
+    mut builder: SessionStateBuilder,
+) -> SessionStateBuilder {
+    if let Some(factories) = builder.table_factories() {
+        for file_type in LISTING_FILE_TYPES {
+            factories.insert(
+                file_type.to_owned(),
+                Arc::new(BallistaListingTableFactory::default()),
+            );
+        }
+    }
+    builder
+}
+
+#[cfg(test)]
+mod test {
+    use crate::extension::SessionConfigExt;
+    use crate::table_factory::with_ballista_table_factories;
+    use datafusion::arrow::array::{Float64Array, Int32Array, Int64Array, StringArray};
+    use datafusion::arrow::datatypes::DataType;
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::assert_batches_sorted_eq;
+    use datafusion::execution::session_state::SessionStateBuilder;
+    use datafusion::parquet::arrow::ArrowWriter;
+    use datafusion::prelude::{SessionConfig, SessionContext};
+    use std::fs::{self, File};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn context(config: SessionConfig) -> SessionContext {
+        let builder = SessionStateBuilder::new()
+            .with_default_features()
+            .with_config(config);
+        SessionContext::new_with_state(with_ballista_table_factories(builder).build())
+    }
+
+    fn write_parquet(path: &Path, batch: RecordBatch) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = File::create(path).unwrap();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_table_with_merged_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        write_parquet(
+            &dir.path().join("1.parquet"),
+            RecordBatch::try_from_iter(vec![
+                ("a", Arc::new(Int32Array::from(vec![1, 2])) as _),
+                ("b", Arc::new(StringArray::from(vec!["x", "y"])) as _),
+            ])
+            .unwrap(),
+        );
+        write_parquet(
+            &dir.path().join("2.parquet"),
+            RecordBatch::try_from_iter(vec![
+                ("a", Arc::new(Int64Array::from(vec![3])) as _),
+                ("c", Arc::new(Float64Array::from(vec![0.5])) as _),
+            ])
+            .unwrap(),
+        );
+        let create_table = format!(
+            "CREATE EXTERNAL TABLE t STORED AS PARQUET LOCATION '{}/'",
+            dir.path().display()
+        );
+
+        let config = SessionConfig::new_with_ballista();
+        assert!(context(config.clone()).sql(&create_table).await.is_err());
+
+        let ctx = context(config.with_ballista_merge_schemas(true));
+        ctx.sql(&create_table).await.unwrap();
+        let batches = ctx
+            .sql("SELECT a, b, c FROM t")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].schema().field(0).data_type(), &DataType::Int64);
+        assert_batches_sorted_eq!(
+            [
+                "+---+---+-----+",
+                "| a | b | c   |",
+                "+---+---+-----+",
+                "| 1 | x |     |",
+                "| 2 | y |     |",
+                "| 3 |   | 0.5 |",
+                "+---+---+-----+",
+            ],
+            &batches
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_table_with_partition_column_types() {
+        let dir = tempfile::tempdir().unwrap();
+        write_parquet(
+            &dir.path().join("year=2024").join("1.parquet"),
+            RecordBatch::try_from_iter(vec![(
+                "a",
+                Arc::new(Int32Array::from(vec![1])) as _,
+            )])
+            .unwrap(),
+        );
+        let create_table = format!(
+            "CREATE EXTERNAL TABLE t STORED AS PARQUET PARTITIONED BY (year) LOCATION '{}/'",
+            dir.path().display()
+        );
+        let year_type = |ctx: SessionContext| {
+            let create_table = create_table.clone();
+            async move {
+                ctx.sql(&create_table).await.unwrap();
+                let batches = ctx
+                    .sql("SELECT a, year FROM t WHERE year = 2024")
+                    .await
+                    .unwrap()
+                    .collect()
+                    .await
+                    .unwrap();
+                assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+                batches[0].schema().field(1).data_type().clone()
+            }
+        };
+
+        let config = SessionConfig::new_with_ballista();
+        // partition values are dictionary encoded strings by default
+        assert_eq!(
+            year_type(context(config.clone())).await,
+            DataType::Dictionary(Box::new(DataType::UInt16), Box::new(DataType::Utf8))
+        );
+        let config = config.with_ballista_partition_column_types("year:Int32");
+        assert_eq!(year_type(context(config)).await, DataType::Int32);
+    }
+}
@@ -0,0 +1,430 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Table functions registered on the sessions of the clients and of the scheduler, so
+//! that the SQL submitted to the scheduler calls the same functions as the SQL planned
+//! by the clients.
+//!
+//! * `read_parquet('path')`, `read_csv('path')` and `read_json('path')` scan the files
+//!   of a path, a single file or a directory, with the schema inferred from the files
+//!   and the options of the format the session was created with. Their scans are
+//!   listing tables, which the executors read as the tables of the catalog.
+//! * `generate_series(start, stop[, step])` returns the integers from `start` to
+//!   `stop` included in a `value` column, encoded as a [GenerateSeries] table.
+//! * `verify_table('name')`, see [crate::verify_table].
+
+use std::any::Any;
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::array::Int64Array;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::{Session, TableProvider};
+use datafusion::common::{plan_err, DataFusionError, Result, ScalarValue};
+use datafusion::datasource::file_format::csv::CsvFormat;
+use datafusion::datasource::file_format::json::JsonFormat;
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::file_format::FileFormat;
+use datafusion::datasource::function::{TableFunction, TableFunctionImpl};
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
+use datafusion::execution::context::SessionState;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::execution::SessionStateBuilder;
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionConfig;
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::verify_table::with_verify_table_function;
+
+/// Name of the table function scanning Parquet files
+pub const READ_PARQUET_FUNCTION: &str = "read_parquet";
+
+/// Name of the table function scanning CSV files
+pub const READ_CSV_FUNCTION: &str = "read_csv";
+
+/// Name of the table function scanning newline-delimited JSON files
+pub const READ_JSON_FUNCTION: &str = "read_json";
+
+/// Name of the table function generating a series of integers
+pub const GENERATE_SERIES_FUNCTION: &str = "generate_series";
+
+/// Format of the files scanned by a [ReadFilesFunction]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadFormat {
+    Parquet,
+    Csv,
+    Json,
+}
+
+impl ReadFormat {
+    pub fn function_name(&self) -> &'static str {
+        match self {
+            ReadFormat::Parquet => READ_PARQUET_FUNCTION,
+            ReadFormat::Csv => READ_CSV_FUNCTION,
+            ReadFormat::Json => READ_JSON_FUNCTION,
+        }
+    }
+
+    /// The format with the options of the session for it
+    fn file_format(&self, state: &SessionState) -> Arc<dyn FileFormat> {
+        let options = state.table_options();
+        match self {
+            ReadFormat::Parquet => {
+                Arc::new(ParquetFormat::default().with_options(options.parquet.clone()))
+            }
+            ReadFormat::Csv => {
+                Arc::new(CsvFormat::default().with_options(options.csv.clone()))
+            }
+            ReadFormat::Json => {
+                Arc::new(JsonFormat::default().with_options(options.json.clone()))
+            }
+        }
+    }
+}
+
+/// A `read_*` table function, whose argument is the path of the files to scan,
+/// resolved against the object stores of the session
+pub struct ReadFilesFunction {
+    format: ReadFormat,
+    config: SessionConfig,
+    runtime_env: Arc<RuntimeEnv>,
+}
+
+impl ReadFilesFunction {
+    pub fn new(
+        format: ReadFormat,
+        config: SessionConfig,
+        runtime_env: Arc<RuntimeEnv>,
+    ) -> Self {
+        Self {
+            format,
+            config,
+            runtime_env,
+        }
+    }
+}
+
+impl TableFunctionImpl for ReadFilesFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let name = self.format.function_name();
+        let [Expr::Literal(ScalarValue::Utf8(Some(path)))] = args else {
+            return plan_err!("{name} expects the path of the files to read");
+        };
+        let state = SessionStateBuilder::new()
+            .with_config(self.config.clone())
+            .with_runtime_env(self.runtime_env.clone())
+            .build();
+        let table_path = ListingTableUrl::parse(path)?;
+        let format = self.format.file_format(&state);
+        // a file is read whatever its extension, the files of a directory only with
+        // the extension of the format
+        let extension = if table_path.is_collection() {
+            format.get_ext()
+        } else {
+            String::new()
+        };
+        let options = ListingOptions::new(format).with_file_extension(extension);
+        let config = block_on_planning(
+            ListingTableConfig::new(table_path)
+                .with_listing_options(options)
+                .infer_schema(&state),
+        )??;
+        let table = ListingTable::try_new(config)?
+            .with_cache(state.runtime_env().cache_manager.get_file_statistic_cache());
+        Ok(Arc::new(table))
+    }
+}
+
+/// Run an asynchronous step of the planning of a table function, as table functions
+/// are planned synchronously. It runs on the runtime the planning happens in if it is
+/// multi-threaded, or on a runtime of its own otherwise
+fn block_on_planning<F>(future: F) -> Result<F::Output>
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    let handle = Handle::try_current()
+        .ok()
+        .filter(|handle| handle.runtime_flavor() == RuntimeFlavor::MultiThread);
+    std::thread::scope(|scope| {
+        scope
+            .spawn(move || -> Result<F::Output> {
+                match handle {
+                    Some(handle) => Ok(handle.block_on(future)),
+                    None => Ok(tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()?
+                        .block_on(future)),
+                }
+            })
+            .join()
+            .map_err(|_| {
+                DataFusionError::Execution(
+                    "Planning of a table function panicked".to_owned(),
+                )
+            })?
+    })
+}
+
+/// The integers from `start` to `stop` included, `step` apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerateSeries {
+    start: i64,
+    stop: i64,
+    step: i64,
+}
+
+impl GenerateSeries {
+    pub fn try_new(start: i64, stop: i64, step: i64) -> Result<Self> {
+        if step == 0 {
+            return plan_err!("The step of {GENERATE_SERIES_FUNCTION} can't be 0");
+        }
+        Ok(Self { start, stop, step })
+    }
+
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    pub fn stop(&self) -> i64 {
+        self.stop
+    }
+
+    pub fn step(&self) -> i64 {
+        self.step
+    }
+
+    /// Number of integers of the series, none if `stop` is before `start` in the
+    /// direction of `step`, saturating at `u64::MAX`
+    pub fn len(&self) -> u64 {
+        let (start, stop, step) =
+            (self.start as i128, self.stop as i128, self.step as i128);
+        if (step > 0 && start > stop) || (step < 0 && start < stop) {
+            return 0;
+        }
+        ((stop - start) / step + 1).min(u64::MAX as i128) as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn value(&self, index: u64) -> i64 {
+        (self.start as i128 + index as i128 * self.step as i128) as i64
+    }
+}
+
+#[async_trait]
+impl TableProvider for GenerateSeries {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![Field::new(
+            "value",
+            DataType::Int64,
+            false,
+        )]))
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let schema = self.schema();
+        let num_rows = limit.map_or(self.len(), |limit| self.len().min(limit as u64));
+        let batch_size = state.config().batch_size().max(1) as u64;
+        let batches = (0..num_rows)
+            .step_by(batch_size as usize)
+            .map(|offset| {
+                let values: Int64Array = (offset..num_rows.min(offset + batch_size))
+                    .map(|index| self.value(index))
+                    .collect();
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(values)])
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Arc::new(MemoryExec::try_new(
+            &[batches],
+            schema,
+            projection.cloned(),
+        )?))
+    }
+}
+
+/// The `generate_series` table function, whose arguments are the integers `start`,
+/// `stop` and optionally `step`, 1 by default
+pub struct GenerateSeriesFunction;
+
+impl TableFunctionImpl for GenerateSeriesFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let args = args.iter().map(int64_arg).collect::<Option<Vec<_>>>();
+        let (start, stop, step) = match args.as_deref() {
+            Some([start, stop]) => (*start, *stop, 1),
+            Some([start, stop, step]) => (*start, *stop, *step),
+            _ => {
+                return plan_err!(
+                    "{GENERATE_SERIES_FUNCTION} expects the integers start, stop and \
+                     optionally step"
+                )
+            }
+        };
+        Ok(Arc::new(GenerateSeries::try_new(start, stop, step)?))
+    }
+}
+
+fn int64_arg(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::Literal(value) => match value.cast_to(&DataType::Int64).ok()? {
+            ScalarValue::Int64(value) => value,
+            _ => None,
+        },
+        Expr::Negative(expr) => int64_arg(expr)?.checked_neg(),
+        _ => None,
+    }
+}
+
+/// Register the table functions of Ballista, the `read_*` functions being bound to the
+/// config and the object stores of the session. The functions of the same names
+/// registered before are kept, but for `verify_table`
+pub fn with_ballista_table_functions(
+    mut builder: SessionStateBuilder,
+) -> SessionStateBuilder {
+    let config = builder.config().clone().unwrap_or_default();
+    let runtime_env = builder
+        .runtime_env()
+        .get_or_insert_with(|| Arc::new(RuntimeEnv::default()))
+        .clone();
+    let mut functions: Vec<(&str, Arc<dyn TableFunctionImpl>)> =
+        vec![(GENERATE_SERIES_FUNCTION, Arc::new(GenerateSeriesFunction))];
+    for format in [ReadFormat::Parquet, ReadFormat::Csv, ReadFormat::Json] {
+        let function =
+            ReadFilesFunction::new(format, config.clone(), runtime_env.clone());
+        functions.push((format.function_name(), Arc::new(function)));
+    }
+
+    let table_functions = builder
+        .table_functions()
+        .get_or_insert_with(Default::default);
+    for (name, function) in functions {
+        table_functions
+            .entry(name.to_owned())
+            .or_insert_with(|| Arc::new(TableFunction::new(name.to_owned(), function)));
+    }
+    with_verify_table_function(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::AsArray;
+    use datafusion::arrow::datatypes::Int64Type;
+    use datafusion::prelude::SessionContext;
+
+    fn context() -> SessionContext {
+        let builder = SessionStateBuilder::new().with_default_features();
+        SessionContext::new_with_state(with_ballista_table_functions(builder).build())
+    }
+
+    async fn int64_values(ctx: &SessionContext, sql: &str) -> Result<Vec<i64>> {
+        let batches = ctx.sql(sql).await?.collect().await?;
+        Ok(batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect())
+    }
+
+    #[test]
+    fn generate_series_len() -> Result<()> {
+        assert_eq!(5, GenerateSeries::try_new(1, 5, 1)?.len());
+        assert_eq!(3, GenerateSeries::try_new(1, 6, 2)?.len());
+        assert_eq!(3, GenerateSeries::try_new(5, 1, -2)?.len());
+        assert!(GenerateSeries::try_new(5, 1, 1)?.is_empty());
+        assert_eq!(
+            u64::MAX,
+            GenerateSeries::try_new(i64::MIN, i64::MAX, 1)?.len()
+        );
+        assert!(GenerateSeries::try_new(1, 5, 0).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn call_generate_series() -> Result<()> {
+        let ctx = context();
+        assert_eq!(
+            vec![1, 2, 3],
+            int64_values(&ctx, "SELECT * FROM generate_series(1, 3)").await?
+        );
+        assert_eq!(
+            vec![10, 7, 4],
+            int64_values(&ctx, "SELECT value FROM generate_series(10, 2, -3)").await?
+        );
+        assert_eq!(
+            vec![0, 1],
+            int64_values(&ctx, "SELECT * FROM generate_series(0, 100) LIMIT 2").await?
+        );
+        assert!(ctx
+            .sql("SELECT * FROM generate_series('a', 2)")
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn call_read_csv() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.csv"), "a\n1\n2\n")?;
+        std::fs::write(dir.path().join("b.csv"), "a\n3\n")?;
+        std::fs::write(dir.path().join("c.txt"), "a\n4\n")?;
+        let path = dir.path().to_str().unwrap();
+
+        let ctx = context();
+        let values = int64_values(
+            &ctx,
+            &format!("SELECT a FROM read_csv('{path}/') ORDER BY a"),
+        )
+        .await?;
+        assert_eq!(vec![1, 2, 3], values);
+        // a file is read whatever its extension
+        let values =
+            int64_values(&ctx, &format!("SELECT a FROM read_csv('{path}/c.txt')"))
+                .await?;
+        assert_eq!(vec![4], values);
+
+        assert!(ctx.sql("SELECT * FROM read_parquet(1)").await.is_err());
+        Ok(())
+    }
+}
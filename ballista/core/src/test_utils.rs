@@ -0,0 +1,185 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Test data shared by the type conformance tests of shuffle, serde and flight
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    Array, ArrayRef, Decimal256Array, DictionaryArray, Int32Array, Int32Builder,
+    Int64Array, IntervalMonthDayNanoArray, ListArray, ListBuilder, MapBuilder,
+    StringArray, StringBuilder, StringDictionaryBuilder, StructArray,
+    TimestampMicrosecondArray, TimestampNanosecondArray,
+};
+use datafusion::arrow::buffer::OffsetBuffer;
+use datafusion::arrow::datatypes::{
+    i256, DataType, Field, Int32Type, IntervalMonthDayNano,
+};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::pretty::pretty_format_batches;
+
+/// All batches the type conformance tests run against
+pub(crate) fn type_conformance_batches() -> Vec<RecordBatch> {
+    vec![nested_types_batch(), exotic_types_batch()]
+}
+
+/// A batch with an `id` column followed by deeply nested columns:
+/// struct of list, list of struct, map, and list of dictionary
+pub(crate) fn nested_types_batch() -> RecordBatch {
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+
+    let mut tags = ListBuilder::new(StringBuilder::new());
+    tags.append_value([Some("x"), Some("y")]);
+    tags.append_null();
+    tags.append_value([None::<&str>]);
+    let tags = tags.finish();
+    let info: ArrayRef = Arc::new(StructArray::from(vec![
+        (
+            Arc::new(Field::new("a", DataType::Int32, true)),
+            Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])) as ArrayRef,
+        ),
+        (
+            Arc::new(Field::new("tags", tags.data_type().clone(), true)),
+            Arc::new(tags) as ArrayRef,
+        ),
+    ]));
+
+    let point = StructArray::from(vec![
+        (
+            Arc::new(Field::new("x", DataType::Int64, true)),
+            Arc::new(Int64Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+        ),
+        (
+            Arc::new(Field::new("y", DataType::Utf8, true)),
+            Arc::new(StringArray::from(vec!["a", "b", "c", "d"])) as ArrayRef,
+        ),
+    ]);
+    let points: ArrayRef = Arc::new(ListArray::new(
+        Arc::new(Field::new("item", point.data_type().clone(), true)),
+        OffsetBuffer::new(vec![0, 2, 2, 4].into()),
+        Arc::new(point),
+        None,
+    ));
+
+    let mut attributes = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+    attributes.keys().append_value("k1");
+    attributes.values().append_value(1);
+    attributes.keys().append_value("k2");
+    attributes.values().append_null();
+    attributes.append(true).unwrap();
+    attributes.append(false).unwrap();
+    attributes.keys().append_value("k3");
+    attributes.values().append_value(3);
+    attributes.append(true).unwrap();
+    let attributes: ArrayRef = Arc::new(attributes.finish());
+
+    let mut colors = ListBuilder::new(StringDictionaryBuilder::<Int32Type>::new());
+    colors.append_value([Some("red"), Some("green"), Some("red")]);
+    colors.append_value([None::<&str>]);
+    colors.append_value([Some("blue")]);
+    let colors: ArrayRef = Arc::new(colors.finish());
+
+    RecordBatch::try_from_iter(vec![
+        ("id", id),
+        ("info", info),
+        ("points", points),
+        ("attributes", attributes),
+        ("colors", colors),
+    ])
+    .unwrap()
+}
+
+/// A batch with an `id` column followed by columns of less common types: Decimal256,
+/// Interval(MonthDayNano), timestamps with non-UTC timezones and a dictionary of struct
+pub(crate) fn exotic_types_batch() -> RecordBatch {
+    let id: ArrayRef = Arc::new(Int32Array::from(vec![1, 2, 3]));
+
+    let decimal: ArrayRef = Arc::new(
+        Decimal256Array::from(vec![
+            Some(i256::from_i128(123_456_789_012_345_678_901_234_567_890)),
+            None,
+            Some(i256::from_i128(-1)),
+        ])
+        .with_precision_and_scale(50, 10)
+        .unwrap(),
+    );
+
+    let interval: ArrayRef = Arc::new(IntervalMonthDayNanoArray::from(vec![
+        Some(IntervalMonthDayNano::new(1, 2, 3_000_000_000)),
+        Some(IntervalMonthDayNano::new(-14, 0, 0)),
+        None,
+    ]));
+
+    let ts_named: ArrayRef = Arc::new(
+        TimestampNanosecondArray::from(vec![
+            Some(1_600_000_000_000_000_000),
+            None,
+            Some(0),
+        ])
+        .with_timezone("America/New_York"),
+    );
+    let ts_offset: ArrayRef = Arc::new(
+        TimestampMicrosecondArray::from(vec![Some(1), Some(2), Some(3)])
+            .with_timezone("+05:30"),
+    );
+
+    let dictionary_values = StructArray::from(vec![
+        (
+            Arc::new(Field::new("code", DataType::Int32, true)),
+            Arc::new(Int32Array::from(vec![10, 20])) as ArrayRef,
+        ),
+        (
+            Arc::new(Field::new("label", DataType::Utf8, true)),
+            Arc::new(StringArray::from(vec!["ten", "twenty"])) as ArrayRef,
+        ),
+    ]);
+    let dictionary_of_struct: ArrayRef = Arc::new(
+        DictionaryArray::<Int32Type>::try_new(
+            Int32Array::from(vec![Some(1), Some(0), None]),
+            Arc::new(dictionary_values),
+        )
+        .unwrap(),
+    );
+
+    RecordBatch::try_from_iter(vec![
+        ("id", id),
+        ("decimal", decimal),
+        ("interval", interval),
+        ("ts_named", ts_named),
+        ("ts_offset", ts_offset),
+        ("dictionary_of_struct", dictionary_of_struct),
+    ])
+    .unwrap()
+}
+
+/// The formatted rows of the batches, sorted so that batches holding the same rows in
+/// any order and split compare equal.
+///
+/// Batches are compared row by row rather than concatenated, as the buffers of
+/// `Interval(MonthDayNano)` columns read from compressed IPC streams are not aligned
+/// for `concat`.
+pub(crate) fn sorted_rows(batches: &[RecordBatch]) -> Vec<String> {
+    let formatted = pretty_format_batches(batches).unwrap().to_string();
+    let mut rows = formatted
+        .lines()
+        .skip(3)
+        .filter(|line| line.starts_with('|'))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    rows.sort();
+    rows
+}
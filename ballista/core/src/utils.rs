@@ -15,22 +15,24 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::config::BallistaConfig;
+use crate::config::{BallistaConfig, ShuffleCompression, SHUFFLE_IPC_METADATA_VERSION};
 use crate::error::{BallistaError, Result};
 use crate::execution_plans::{
-    DistributedQueryExec, ShuffleWriterExec, UnresolvedShuffleExec,
+    DistributedAnalyzeExec, DistributedQueryExec, ShuffleFileWriter, ShuffleWriterExec,
+    UnresolvedShuffleExec,
 };
 
 use crate::extension::SessionConfigExt;
+use crate::security::{AuthTokenInterceptor, GrpcSecurityConfig};
 use crate::serde::scheduler::PartitionStats;
 use crate::serde::BallistaLogicalExtensionCodec;
+use crate::table_functions::with_ballista_table_functions;
 
 use async_trait::async_trait;
 use datafusion::arrow::datatypes::Schema;
-use datafusion::arrow::ipc::writer::IpcWriteOptions;
 use datafusion::arrow::ipc::writer::StreamWriter;
-use datafusion::arrow::ipc::CompressionType;
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::row::{RowConverter, SortField};
 use datafusion::common::tree_node::{TreeNode, TreeNodeVisitor};
 use datafusion::datasource::physical_plan::{CsvExec, ParquetExec};
 use datafusion::error::DataFusionError;
@@ -52,6 +54,8 @@ use datafusion::physical_planner::{DefaultPhysicalPlanner, PhysicalPlanner};
 use datafusion_proto::logical_plan::{AsLogicalPlan, LogicalExtensionCodec};
 use futures::StreamExt;
 use log::error;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::io::{BufWriter, Write};
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -59,17 +63,18 @@ use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fs::File, pin::Pin};
 use tonic::codegen::StdError;
-use tonic::transport::{Channel, Error, Server};
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::{Channel, Endpoint, Error, Server};
 
 /// Default session builder using the provided configuration
 pub fn default_session_builder(
     config: SessionConfig,
 ) -> datafusion::common::Result<SessionState> {
-    Ok(SessionStateBuilder::new()
+    let builder = SessionStateBuilder::new()
         .with_default_features()
         .with_config(config)
-        .with_runtime_env(Arc::new(RuntimeEnv::new(RuntimeConfig::default())?))
-        .build())
+        .with_runtime_env(Arc::new(RuntimeEnv::new(RuntimeConfig::default())?));
+    Ok(with_ballista_table_functions(builder).build())
 }
 
 pub fn default_config_producer() -> SessionConfig {
@@ -80,9 +85,10 @@ pub fn default_config_producer() -> SessionConfig {
 pub async fn write_stream_to_disk(
     stream: &mut Pin<Box<dyn RecordBatchStream + Send>>,
     path: &str,
+    compression: ShuffleCompression,
     disk_write_metric: &metrics::Time,
 ) -> Result<PartitionStats> {
-    let file = File::create(path).map_err(|e| {
+    let file = ShuffleFileWriter::create(path).map_err(|e| {
         error!("Failed to create partition file at {}: {:?}", path, e);
         BallistaError::IoError(e)
     })?;
@@ -91,8 +97,7 @@ pub async fn write_stream_to_disk(
     let mut num_batches = 0;
     let mut num_bytes = 0;
 
-    let options = IpcWriteOptions::default()
-        .try_with_compression(Some(CompressionType::LZ4_FRAME))?;
+    let options = compression.ipc_write_options(SHUFFLE_IPC_METADATA_VERSION)?;
 
     let mut writer =
         StreamWriter::try_new_with_options(file, stream.schema().as_ref(), options)?;
@@ -119,6 +124,65 @@ pub async fn write_stream_to_disk(
     ))
 }
 
+/// Order-insensitive checksum of the rows of a batch. The checksums of batches add up,
+/// with wrapping, to the checksum of all their rows, however the rows are ordered and
+/// split into batches.
+pub fn batch_checksum(batch: &RecordBatch) -> datafusion::error::Result<u64> {
+    let fields = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| SortField::new(field.data_type().clone()))
+        .collect();
+    let rows = RowConverter::new(fields)?.convert_columns(batch.columns())?;
+
+    Ok(rows.iter().fold(0u64, |checksum, row| {
+        let mut hasher = DefaultHasher::new();
+        row.as_ref().hash(&mut hasher);
+        checksum.wrapping_add(hasher.finish())
+    }))
+}
+
+/// FNV-1a hasher. Unlike the hasher of the standard library, its hashes are stable
+/// across builds and platforms, so that processes built with different toolchains can
+/// compare them.
+#[derive(Debug, Clone, Copy)]
+pub struct StableHasher {
+    hash: u64,
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self {
+            hash: 0xcbf29ce484222325,
+        }
+    }
+}
+
+impl StableHasher {
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    /// Hash a string prefixed with its length, so that consecutive strings hash
+    /// differently from their concatenation
+    pub fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write_bytes(value.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
 pub async fn collect_stream(
     stream: &mut Pin<Box<dyn RecordBatchStream + Send>>,
 ) -> Result<Vec<RecordBatch>> {
@@ -324,6 +388,23 @@ impl<T: 'static + AsLogicalPlan> QueryPlanner for BallistaQueryPlanner<T> {
                     log::debug!("create_physical_plan - handling empty exec");
                     Ok(Arc::new(EmptyExec::new(Arc::new(Schema::empty()))))
                 }
+                LogicalPlan::Analyze(analyze) => {
+                    log::debug!("create_physical_plan - handling explain analyze");
+
+                    let input = DistributedQueryExec::with_repr(
+                        self.scheduler_url.clone(),
+                        self.config.clone(),
+                        analyze.input.as_ref().clone(),
+                        self.extension_codec.clone(),
+                        self.plan_repr,
+                        session_state.session_id().to_string(),
+                    );
+                    Ok(Arc::new(DistributedAnalyzeExec::new(
+                        input,
+                        analyze.verbose,
+                        analyze.schema.as_ref().clone().into(),
+                    )))
+                }
                 _ => {
                     log::debug!("create_physical_plan - handling general statement");
 
@@ -341,14 +422,29 @@ impl<T: 'static + AsLogicalPlan> QueryPlanner for BallistaQueryPlanner<T> {
     }
 }
 
+/// Channel of the gRPC clients of Ballista, attaching the auth token of the cluster, if
+/// any, to their requests
+pub type GrpcChannel = InterceptedService<Channel, AuthTokenInterceptor>;
+
+/// Connects to a scheduler or an executor, with TLS if `security` says so, in which case
+/// `http` urls are connected to as `https` urls
 pub async fn create_grpc_client_connection<D>(
     dst: D,
-) -> std::result::Result<Channel, Error>
+    security: &GrpcSecurityConfig,
+) -> std::result::Result<GrpcChannel, Error>
 where
     D: std::convert::TryInto<tonic::transport::Endpoint>,
     D::Error: Into<StdError>,
 {
-    let endpoint = tonic::transport::Endpoint::new(dst)?
+    let mut endpoint = Endpoint::new(dst)?;
+    if let Some(tls) = security.client_tls_config() {
+        let uri = endpoint.uri().to_string();
+        if let Some(address) = uri.strip_prefix("http://") {
+            endpoint = Endpoint::from_shared(format!("https://{address}"))?;
+        }
+        endpoint = endpoint.tls_config(tls)?;
+    }
+    let endpoint = endpoint
         .connect_timeout(Duration::from_secs(20))
         .timeout(Duration::from_secs(20))
         // Disable Nagle's Algorithm since we don't want packets to wait
@@ -357,17 +453,30 @@ where
         .http2_keep_alive_interval(Duration::from_secs(300))
         .keep_alive_timeout(Duration::from_secs(20))
         .keep_alive_while_idle(true);
-    endpoint.connect().await
+    let channel = endpoint.connect().await?;
+    Ok(InterceptedService::new(
+        channel,
+        security.auth_token_interceptor(),
+    ))
 }
 
-pub fn create_grpc_server() -> Server {
-    Server::builder()
+/// Builds a gRPC server, accepting TLS connections only if `security` says so. The auth
+/// token is checked by wrapping the services of the server with the
+/// [GrpcSecurityConfig::auth_token_validator] of `security`
+pub fn create_grpc_server(
+    security: &GrpcSecurityConfig,
+) -> std::result::Result<Server, Error> {
+    let server = Server::builder()
         .timeout(Duration::from_secs(20))
         // Disable Nagle's Algorithm since we don't want packets to wait
         .tcp_nodelay(true)
         .tcp_keepalive(Option::Some(Duration::from_secs(3600)))
         .http2_keepalive_interval(Option::Some(Duration::from_secs(300)))
-        .http2_keepalive_timeout(Option::Some(Duration::from_secs(20)))
+        .http2_keepalive_timeout(Option::Some(Duration::from_secs(20)));
+    match security.server_tls_config() {
+        Some(tls) => server.tls_config(tls),
+        None => Ok(server),
+    }
 }
 
 pub fn collect_plan_metrics(plan: &dyn ExecutionPlan) -> Vec<MetricsSet> {
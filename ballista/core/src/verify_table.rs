@@ -0,0 +1,315 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Verification of the files of a table, e.g. after a migration or a compaction.
+//!
+//! `SELECT * FROM verify_table('name')`, or `VERIFY TABLE name` where the statement
+//! is rewritten by [verify_table_statement], runs a job reading every file of the
+//! table, which reports a row per file with its number of rows and the checksum of
+//! its content, compared with the number of rows in the metadata of the file, such
+//! as the footer of a Parquet file. See [VerifyFilesExec] for the columns.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::catalog::{CatalogProviderList, Session, TableProvider};
+use datafusion::catalog_common::MemoryCatalogProviderList;
+use datafusion::common::{plan_err, DataFusionError, Result, ScalarValue};
+use datafusion::datasource::function::{TableFunction, TableFunctionImpl};
+use datafusion::datasource::listing::ListingTable;
+use datafusion::execution::context::SessionState;
+use datafusion::execution::SessionStateBuilder;
+use datafusion::logical_expr::{Expr, TableType};
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::sql::TableReference;
+use futures::FutureExt;
+
+use crate::execution_plans::{
+    scan_config, scan_file_groups, verify_files_schema, VerifyFilesExec,
+};
+
+/// Name of the table function verifying the files of a table
+pub const VERIFY_TABLE_FUNCTION: &str = "verify_table";
+
+/// The report of the verification of the files of a table, a row per file
+pub struct VerifyTable {
+    inner: Arc<dyn TableProvider>,
+}
+
+impl VerifyTable {
+    pub fn new(inner: Arc<dyn TableProvider>) -> Self {
+        Self { inner }
+    }
+
+    /// The table whose files are verified
+    pub fn inner(&self) -> Arc<dyn TableProvider> {
+        self.inner.clone()
+    }
+}
+
+#[async_trait]
+impl TableProvider for VerifyTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        verify_files_schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let mut scan = self.inner.scan(state, None, &[], None).await?;
+        if let Some(table) = self.inner.as_any().downcast_ref::<ListingTable>() {
+            scan = with_file_statistics(state, table, scan).await?;
+        }
+        let verify: Arc<dyn ExecutionPlan> = Arc::new(VerifyFilesExec::try_new(scan)?);
+
+        let Some(projection) = projection else {
+            return Ok(verify);
+        };
+        let schema = verify.schema();
+        let exprs: Vec<(Arc<dyn PhysicalExpr>, String)> = projection
+            .iter()
+            .map(|index| {
+                let name = schema.field(*index).name().to_owned();
+                (Arc::new(Column::new(&name, *index)) as _, name)
+            })
+            .collect();
+        Ok(Arc::new(ProjectionExec::try_new(exprs, verify)?))
+    }
+}
+
+/// Fill the statistics the files of a listing table scan were listed without, as
+/// the number of rows they hold is the expectation the files are verified against
+async fn with_file_statistics(
+    state: &dyn Session,
+    table: &ListingTable,
+    scan: Arc<dyn ExecutionPlan>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let (Some(state), Some(config)) = (
+        state.as_any().downcast_ref::<SessionState>(),
+        scan_config(scan.as_ref()),
+    ) else {
+        return Ok(scan);
+    };
+    if config
+        .file_groups
+        .iter()
+        .flatten()
+        .all(|file| file.statistics.is_some())
+    {
+        return Ok(scan);
+    }
+
+    let store = state.runtime_env().object_store(&config.object_store_url)?;
+    let format = &table.options().format;
+    let mut file_groups = config.file_groups.clone();
+    for file in file_groups.iter_mut().flatten() {
+        if file.statistics.is_some() {
+            continue;
+        }
+        // a file whose metadata can't be read is reported once it is verified
+        if let Ok(statistics) = format
+            .infer_stats(state, &store, config.file_schema.clone(), &file.object_meta)
+            .await
+        {
+            file.statistics = Some(statistics);
+        }
+    }
+    scan_file_groups(scan.as_ref(), file_groups)
+}
+
+/// The `verify_table` table function, whose argument is the name of the table to
+/// verify, resolved against the catalogs of the session
+pub struct VerifyTableFunction {
+    catalogs: Arc<dyn CatalogProviderList>,
+    default_catalog: String,
+    default_schema: String,
+}
+
+impl VerifyTableFunction {
+    pub fn new(
+        catalogs: Arc<dyn CatalogProviderList>,
+        default_catalog: impl Into<String>,
+        default_schema: impl Into<String>,
+    ) -> Self {
+        Self {
+            catalogs,
+            default_catalog: default_catalog.into(),
+            default_schema: default_schema.into(),
+        }
+    }
+}
+
+impl TableFunctionImpl for VerifyTableFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let [Expr::Literal(ScalarValue::Utf8(Some(name)))] = args else {
+            return plan_err!("{VERIFY_TABLE_FUNCTION} expects the name of a table");
+        };
+        let table_ref = TableReference::from(name.as_str())
+            .resolve(&self.default_catalog, &self.default_schema);
+        let schema = self
+            .catalogs
+            .catalog(&table_ref.catalog)
+            .and_then(|catalog| catalog.schema(&table_ref.schema))
+            .ok_or_else(|| {
+                DataFusionError::Plan(format!("Schema of table {name} not found"))
+            })?;
+        // table functions are planned synchronously, which the table lookups of the
+        // in-memory schemas are
+        let table = schema
+            .table(&table_ref.table)
+            .now_or_never()
+            .ok_or_else(|| {
+                DataFusionError::Plan(format!(
+                    "Table {name} can't be looked up by {VERIFY_TABLE_FUNCTION}"
+                ))
+            })??
+            .ok_or_else(|| DataFusionError::Plan(format!("Table {name} not found")))?;
+        Ok(Arc::new(VerifyTable::new(table)))
+    }
+}
+
+/// Register the `verify_table` table function, bound to the catalogs of the session
+pub(crate) fn with_verify_table_function(
+    mut builder: SessionStateBuilder,
+) -> SessionStateBuilder {
+    let catalogs = builder
+        .catalog_list()
+        .get_or_insert_with(|| Arc::new(MemoryCatalogProviderList::new()))
+        .clone();
+    let (default_catalog, default_schema) = match builder.config() {
+        Some(config) => {
+            let catalog = &config.options().catalog;
+            (
+                catalog.default_catalog.clone(),
+                catalog.default_schema.clone(),
+            )
+        }
+        None => ("datafusion".to_owned(), "public".to_owned()),
+    };
+    let function = VerifyTableFunction::new(catalogs, default_catalog, default_schema);
+    builder
+        .table_functions()
+        .get_or_insert_with(Default::default)
+        .insert(
+            VERIFY_TABLE_FUNCTION.to_owned(),
+            Arc::new(TableFunction::new(
+                VERIFY_TABLE_FUNCTION.to_owned(),
+                Arc::new(function),
+            )),
+        );
+    builder
+}
+
+/// Rewrite a `VERIFY TABLE name` statement into the query of its report, none if
+/// `sql` is another statement
+pub fn verify_table_statement(sql: &str) -> Option<String> {
+    let sql = sql.trim().trim_end_matches(';');
+    let name = strip_keyword(strip_keyword(sql, "VERIFY")?, "TABLE")?.trim();
+    (!name.is_empty()).then(|| {
+        format!(
+            "SELECT * FROM {VERIFY_TABLE_FUNCTION}('{}')",
+            name.replace('\'', "''")
+        )
+    })
+}
+
+fn strip_keyword<'a>(sql: &'a str, keyword: &str) -> Option<&'a str> {
+    let (word, rest) = sql.trim_start().split_once(char::is_whitespace)?;
+    word.eq_ignore_ascii_case(keyword).then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution_plans::VERIFY_STATUS_UNVERIFIED;
+    use datafusion::arrow::array::AsArray;
+    use datafusion::arrow::datatypes::UInt64Type;
+    use datafusion::prelude::{CsvReadOptions, SessionContext};
+
+    #[test]
+    fn rewrite_verify_table_statements() {
+        assert_eq!(
+            Some("SELECT * FROM verify_table('s.t')".to_owned()),
+            verify_table_statement(" verify  TABLE s.t;")
+        );
+        assert_eq!(
+            Some("SELECT * FROM verify_table('\"it''s\"')".to_owned()),
+            verify_table_statement("VERIFY TABLE \"it's\"")
+        );
+        assert_eq!(None, verify_table_statement("VERIFY TABLE"));
+        assert_eq!(None, verify_table_statement("SELECT * FROM verify"));
+    }
+
+    #[tokio::test]
+    async fn verify_files_of_table() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.csv"), "a\n1\n2\n")?;
+        std::fs::write(dir.path().join("b.csv"), "a\n3\n")?;
+
+        let builder = SessionStateBuilder::new().with_default_features();
+        let ctx =
+            SessionContext::new_with_state(with_verify_table_function(builder).build());
+        ctx.register_csv("t", dir.path().to_str().unwrap(), CsvReadOptions::new())
+            .await?;
+
+        let batches = ctx
+            .sql("SELECT rows, status FROM verify_table('t') ORDER BY rows")
+            .await?
+            .collect()
+            .await?;
+        let rows: Vec<u64> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(vec![1, 2], rows);
+        // CSV files have no row count in their metadata
+        for batch in &batches {
+            for status in batch.column(1).as_string::<i32>().iter() {
+                assert_eq!(Some(VERIFY_STATUS_UNVERIFIED), status);
+            }
+        }
+
+        assert!(ctx
+            .sql("SELECT * FROM verify_table('missing')")
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -21,17 +21,34 @@ use anyhow::Result;
 use ballista_core::config::LogRotationPolicy;
 use ballista_core::print_version;
 use ballista_executor::config::prelude::*;
+use ballista_executor::executor::LogFilterReloader;
 use ballista_executor::executor_process::{
     start_executor_process, ExecutorProcessConfig,
 };
+use ballista_executor::task_logs::{TaskLogLayer, TaskLogStore};
 use std::env;
 use std::sync::Arc;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
-#[cfg(feature = "mimalloc")]
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// Samples an allocation every 512 KiB on average, which keeps the overhead of
+/// heap profiling low enough to leave it enabled
+#[cfg(feature = "jemalloc")]
+#[allow(non_upper_case_globals)]
+#[export_name = "malloc_conf"]
+pub static malloc_conf: &[u8] = b"prof:true,prof_active:true,lg_prof_sample:19\0";
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // parse command-line arguments
@@ -44,7 +61,7 @@ async fn main() -> Result<()> {
         std::process::exit(0);
     }
 
-    let config: ExecutorProcessConfig = opt.try_into()?;
+    let mut config: ExecutorProcessConfig = opt.try_into()?;
 
     let rust_log = env::var(EnvFilter::DEFAULT_ENV);
     let log_filter =
@@ -56,6 +73,13 @@ async fn main() -> Result<()> {
         .with_thread_ids(config.print_thread_info)
         .with_env_filter(log_filter);
 
+    // Captures the logs of each task, to be served through the GetTaskLogs rpc
+    let task_log_layer = (config.task_log_max_lines > 0).then(|| {
+        let store = Arc::new(TaskLogStore::new(config.task_log_max_lines));
+        config.task_log_store = Some(store.clone());
+        TaskLogLayer::new(store)
+    });
+
     // File layer
     if let Some(log_dir) = &config.log_dir {
         let log_file = match config.log_rotation_policy {
@@ -73,10 +97,36 @@ async fn main() -> Result<()> {
             }
         };
 
-        tracing.with_writer(log_file).init();
+        let tracing = tracing.with_writer(log_file).with_filter_reloading();
+        config.log_filter_reloader = Some(log_filter_reloader(tracing.reload_handle()));
+        tracing.finish().with(task_log_layer).init();
     } else {
-        tracing.init();
+        let tracing = tracing.with_filter_reloading();
+        config.log_filter_reloader = Some(log_filter_reloader(tracing.reload_handle()));
+        tracing.finish().with(task_log_layer).init();
     }
 
     start_executor_process(Arc::new(config)).await
 }
+
+/// Replaces the log filter of the subscriber with the directives pushed by the scheduler
+fn log_filter_reloader<S: 'static>(
+    handle: reload::Handle<EnvFilter, S>,
+) -> LogFilterReloader {
+    Arc::new(move |directives| {
+        let filter = EnvFilter::try_new(directives).map_err(|e| e.to_string())?;
+        handle.reload(filter).map_err(|e| e.to_string())?;
+        // The records of the `log` crate are forwarded to the subscriber only up to the
+        // max level set when it was installed
+        let max_level = match LevelFilter::current() {
+            LevelFilter::OFF => log::LevelFilter::Off,
+            LevelFilter::ERROR => log::LevelFilter::Error,
+            LevelFilter::WARN => log::LevelFilter::Warn,
+            LevelFilter::INFO => log::LevelFilter::Info,
+            LevelFilter::DEBUG => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        };
+        log::set_max_level(max_level);
+        Ok(())
+    })
+}
@@ -15,9 +15,14 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::path::Path;
+use std::time::Duration;
+
 use ballista_core::error::BallistaError;
+use ballista_core::security::GrpcSecurityConfig;
 
 use crate::executor_process::ExecutorProcessConfig;
+use crate::object_store_retry::ObjectStoreRetryConfig;
 
 // Ideally we would use the include_config macro from configure_me, but then we cannot use
 // #[allow(clippy::all)] to silence clippy warnings from the generated code
@@ -35,27 +40,85 @@ impl TryFrom<Config> for ExecutorProcessConfig {
             opt.bind_port
         );
 
+        if opt.job_data_max_disk_bytes > 0 && opt.job_data_clean_up_interval_seconds == 0
+        {
+            return Err(BallistaError::General(
+                "job_data_max_disk_bytes requires job_data_clean_up_interval_seconds"
+                    .to_string(),
+            ));
+        }
+
+        if let (Some(data_cache_dir), Some(work_dir)) =
+            (&opt.data_cache_dir, &opt.work_dir)
+        {
+            // the janitor removes the directories of the work dir
+            if Path::new(data_cache_dir).starts_with(work_dir) {
+                return Err(BallistaError::General(
+                    "data_cache_dir must not be in the work dir".to_string(),
+                ));
+            }
+        }
+
+        let mut grpc_security = GrpcSecurityConfig::default()
+            .with_tls_files(
+                opt.grpc_tls_cert_file.as_deref(),
+                opt.grpc_tls_key_file.as_deref(),
+                opt.grpc_tls_ca_file.as_deref(),
+            )?
+            .with_client_auth(opt.grpc_tls_client_auth);
+        if let Some(auth_token) = &opt.grpc_auth_token {
+            grpc_security = grpc_security.with_auth_token(auth_token)?;
+        }
+
         Ok(ExecutorProcessConfig {
             special_mod_log_level: opt.log_level_setting,
             external_host: opt.external_host,
             bind_host: opt.bind_host,
             port: opt.bind_port,
             grpc_port: opt.bind_grpc_port,
+            metrics_port: opt.metrics_port,
             scheduler_host: opt.scheduler_host,
             scheduler_port: opt.scheduler_port,
             scheduler_connect_timeout_seconds: opt.scheduler_connect_timeout_seconds,
             concurrent_tasks: opt.concurrent_tasks,
+            memory_pool_size: opt.memory_pool_size,
+            task_memory_limit: opt.task_memory_limit,
             task_scheduling_policy: opt.task_scheduling_policy,
             work_dir: opt.work_dir,
             log_dir: opt.log_dir,
             log_file_name_prefix,
             log_rotation_policy: opt.log_rotation_policy,
             print_thread_info: opt.print_thread_info,
+            task_log_max_lines: opt.task_log_max_lines,
+            task_log_store: None,
+            log_filter_reloader: None,
             job_data_ttl_seconds: opt.job_data_ttl_seconds,
             job_data_clean_up_interval_seconds: opt.job_data_clean_up_interval_seconds,
+            job_data_max_disk_bytes: opt.job_data_max_disk_bytes,
+            task_spill_dirs: opt.task_spill_dirs,
+            task_spill_encryption: opt.task_spill_encryption,
+            data_cache_memory_bytes: opt.data_cache_memory_bytes,
+            data_cache_disk_bytes: opt.data_cache_disk_bytes,
+            data_cache_dir: opt.data_cache_dir,
+            data_cache_metadata_ttl_seconds: opt.data_cache_metadata_ttl_seconds,
+            metadata_cache_memory_bytes: opt.metadata_cache_memory_bytes,
+            max_concurrent_shuffle_fetches: opt.max_concurrent_shuffle_fetches as usize,
+            object_store_retry: ObjectStoreRetryConfig {
+                max_retries: opt.object_store_max_retries,
+                backoff: Duration::from_millis(opt.object_store_retry_backoff_ms),
+                circuit_breaker_threshold: opt.object_store_circuit_breaker_threshold,
+                circuit_breaker_cooldown: Duration::from_secs(
+                    opt.object_store_circuit_breaker_cooldown_seconds,
+                ),
+                degraded_concurrency: opt.object_store_degraded_concurrency as usize,
+            },
             grpc_max_decoding_message_size: opt.grpc_server_max_decoding_message_size,
             grpc_max_encoding_message_size: opt.grpc_server_max_encoding_message_size,
             executor_heartbeat_interval_seconds: opt.executor_heartbeat_interval_seconds,
+            drain_timeout_seconds: opt.drain_timeout_seconds,
+            grpc_security,
+            registration_token: opt.registration_token,
+            executor_pool: opt.executor_pool,
             override_execution_engine: None,
             override_function_registry: None,
             override_config_producer: None,
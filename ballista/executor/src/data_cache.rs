@@ -0,0 +1,663 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cache of the byte ranges the tasks of the executor read from object stores, so that
+//! repeated scans of the same files, e.g. by dashboards, don't pay the latency of the
+//! object store every time.
+//!
+//! Ranges are cached in memory, and moved to the disk once evicted from memory, both
+//! tiers evicting the least recently read ranges first. They are keyed by the path and
+//! the version of their file, its ETag or else its modification time and size, which
+//! is checked again with the object store once older than the metadata TTL. Parquet
+//! scans read the same ranges of a file every time, which are matched exactly.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ballista_core::serde::protobuf::{executor_metric, DataCacheUsage, ExecutorMetric};
+use ballista_core::RuntimeProducer;
+use bytes::Bytes;
+use dashmap::DashMap;
+use datafusion::error::Result as DataFusionResult;
+use datafusion::execution::object_store::ObjectStoreRegistry;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use futures::stream::BoxStream;
+use log::warn;
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOpts, PutOptions, PutPayload, PutResult,
+};
+use parking_lot::Mutex;
+use url::Url;
+
+/// Maximum number of cached files reported with the heartbeats
+const MAX_REPORTED_CACHED_FILES: usize = 1000;
+
+/// A byte range of a version of a file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    location: String,
+    version: String,
+    range: Range<usize>,
+}
+
+#[derive(Debug)]
+struct LruEntry<V> {
+    value: V,
+    size: u64,
+    tick: u64,
+}
+
+/// Entries up to a total size, evicting the least recently used entries first
+#[derive(Debug)]
+pub(crate) struct Lru<K, V> {
+    capacity: u64,
+    size: u64,
+    tick: u64,
+    entries: HashMap<K, LruEntry<V>>,
+    // Keys of the entries by the tick they were last used at
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> Lru<K, V> {
+    pub(crate) fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.tick);
+        self.tick += 1;
+        entry.tick = self.tick;
+        self.order.insert(self.tick, key.clone());
+        Some(entry.value.clone())
+    }
+
+    pub(crate) fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Insert an entry, returning the entries evicted to make room for it, or the
+    /// entry itself if it's larger than the capacity
+    pub(crate) fn insert(&mut self, key: K, value: V, size: u64) -> Vec<(K, V)> {
+        if size > self.capacity {
+            return vec![(key, value)];
+        }
+        let mut evicted: Vec<(K, V)> = self
+            .remove(&key)
+            .map(|value| (key.clone(), value))
+            .into_iter()
+            .collect();
+        while self.size + size > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.size -= entry.size;
+                evicted.push((oldest, entry.value));
+            }
+        }
+
+        self.tick += 1;
+        self.size += size;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            LruEntry {
+                value,
+                size,
+                tick: self.tick,
+            },
+        );
+        evicted
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        self.size -= entry.size;
+        Some(entry.value)
+    }
+
+    /// The total size of the entries
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Keys of the entries, the most recently used first
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &K> {
+        self.order.values().rev()
+    }
+}
+
+/// The version of a file, its ETag or else its modification time and size
+pub(crate) fn file_version(meta: &ObjectMeta) -> String {
+    meta.e_tag.clone().unwrap_or_else(|| {
+        format!("{}-{}", meta.last_modified.timestamp_millis(), meta.size)
+    })
+}
+
+/// The byte ranges read from object stores, in memory and on disk
+#[derive(Debug)]
+pub struct DataCache {
+    memory: Mutex<Lru<CacheKey, Bytes>>,
+    disk: Mutex<Lru<CacheKey, PathBuf>>,
+    disk_dir: PathBuf,
+    next_file_id: AtomicU64,
+    /// Version of each file and when it was checked, keyed by path
+    versions: DashMap<String, (String, Instant)>,
+    metadata_ttl: Duration,
+    memory_hits: AtomicU64,
+    disk_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl DataCache {
+    /// Create a cache of up to `memory_bytes` in memory and `disk_bytes` in files
+    /// under `disk_dir`, which is emptied. The versions of the files are checked again
+    /// once older than `metadata_ttl`
+    pub fn try_new(
+        memory_bytes: u64,
+        disk_bytes: u64,
+        disk_dir: PathBuf,
+        metadata_ttl: Duration,
+    ) -> std::io::Result<Self> {
+        if disk_bytes > 0 {
+            match std::fs::remove_dir_all(&disk_dir) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            std::fs::create_dir_all(&disk_dir)?;
+        }
+        Ok(Self {
+            memory: Mutex::new(Lru::new(memory_bytes)),
+            disk: Mutex::new(Lru::new(disk_bytes)),
+            disk_dir,
+            next_file_id: AtomicU64::new(0),
+            versions: DashMap::new(),
+            metadata_ttl,
+            memory_hits: AtomicU64::new(0),
+            disk_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    async fn get(&self, key: &CacheKey) -> Option<Bytes> {
+        if let Some(data) = self.memory.lock().get(key) {
+            self.memory_hits.fetch_add(1, Ordering::Relaxed);
+            return Some(data);
+        }
+        let file = self.disk.lock().get(key);
+        if let Some(file) = file {
+            // the file may have been evicted meanwhile
+            if let Ok(data) = tokio::fs::read(file).await {
+                self.disk_hits.fetch_add(1, Ordering::Relaxed);
+                let data = Bytes::from(data);
+                self.insert(key.clone(), data.clone()).await;
+                return Some(data);
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// Cache a range in memory, moving the ranges evicted from memory to the disk
+    async fn insert(&self, key: CacheKey, data: Bytes) {
+        let size = data.len() as u64;
+        let evicted = self.memory.lock().insert(key, data, size);
+        for (key, data) in evicted {
+            let size = data.len() as u64;
+            {
+                let disk = self.disk.lock();
+                if disk.contains(&key) || size > disk.capacity {
+                    continue;
+                }
+            }
+            let file = self.disk_dir.join(format!(
+                "{:020}",
+                self.next_file_id.fetch_add(1, Ordering::Relaxed)
+            ));
+            if let Err(e) = tokio::fs::write(&file, &data).await {
+                warn!("Fail to write data cache file {file:?}: {e}");
+                continue;
+            }
+            let evicted = self.disk.lock().insert(key, file, size);
+            for (_, file) in evicted {
+                let _ = tokio::fs::remove_file(file).await;
+            }
+        }
+    }
+
+    /// The version of a file, from the object store if the version known is older
+    /// than the metadata TTL
+    async fn version(
+        &self,
+        store: &dyn ObjectStore,
+        location: &Path,
+    ) -> object_store::Result<String> {
+        if let Some(version) = self.versions.get(location.as_ref()) {
+            if version.1.elapsed() < self.metadata_ttl {
+                return Ok(version.0.clone());
+            }
+        }
+        let meta = store.head(location).await?;
+        Ok(self.record_meta(&meta))
+    }
+
+    /// Record the version of a file from its metadata
+    fn record_meta(&self, meta: &ObjectMeta) -> String {
+        let version = file_version(meta);
+        self.versions.insert(
+            meta.location.as_ref().to_owned(),
+            (version.clone(), Instant::now()),
+        );
+        version
+    }
+
+    /// Forget the version of a file written through the cache, for the next reads to
+    /// check it with the object store
+    fn forget(&self, location: &Path) {
+        self.versions.remove(location.as_ref());
+    }
+
+    /// The hits and misses of the cache, its size and the files it holds ranges of
+    pub fn usage(&self) -> DataCacheUsage {
+        let memory = self.memory.lock();
+        let disk = self.disk.lock();
+        let mut seen = HashSet::new();
+        let cached_files = memory
+            .keys()
+            .chain(disk.keys())
+            .filter(|key| seen.insert(key.location.as_str()))
+            .take(MAX_REPORTED_CACHED_FILES)
+            .map(|key| key.location.clone())
+            .collect();
+        DataCacheUsage {
+            memory_hits: self.memory_hits.load(Ordering::Relaxed),
+            disk_hits: self.disk_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            memory_bytes: memory.size,
+            disk_bytes: disk.size,
+            cached_files,
+        }
+    }
+
+    /// The usage of the cache, reported with the heartbeats
+    pub fn metric(&self) -> ExecutorMetric {
+        ExecutorMetric {
+            metric: Some(executor_metric::Metric::DataCacheUsage(self.usage())),
+        }
+    }
+}
+
+/// Object store reading the byte ranges of files through a [DataCache]
+#[derive(Debug)]
+pub struct CachingObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    cache: Arc<DataCache>,
+}
+
+impl CachingObjectStore {
+    pub fn new(inner: Arc<dyn ObjectStore>, cache: Arc<DataCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    fn key(&self, location: &Path, version: &str, range: Range<usize>) -> CacheKey {
+        CacheKey {
+            location: location.as_ref().to_owned(),
+            version: version.to_owned(),
+            range,
+        }
+    }
+}
+
+impl Display for CachingObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CachingObjectStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for CachingObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        self.cache.forget(location);
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.cache.forget(location);
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn get_range(
+        &self,
+        location: &Path,
+        range: Range<usize>,
+    ) -> object_store::Result<Bytes> {
+        let version = self.cache.version(self.inner.as_ref(), location).await?;
+        let key = self.key(location, &version, range.clone());
+        if let Some(data) = self.cache.get(&key).await {
+            return Ok(data);
+        }
+        let data = self.inner.get_range(location, range).await?;
+        self.cache.insert(key, data.clone()).await;
+        Ok(data)
+    }
+
+    async fn get_ranges(
+        &self,
+        location: &Path,
+        ranges: &[Range<usize>],
+    ) -> object_store::Result<Vec<Bytes>> {
+        let version = self.cache.version(self.inner.as_ref(), location).await?;
+        let mut data = Vec::with_capacity(ranges.len());
+        let mut missing = vec![];
+        for (idx, range) in ranges.iter().enumerate() {
+            let key = self.key(location, &version, range.clone());
+            let cached = self.cache.get(&key).await;
+            if cached.is_none() {
+                missing.push(idx);
+            }
+            data.push(cached);
+        }
+        if !missing.is_empty() {
+            let missing_ranges: Vec<_> =
+                missing.iter().map(|&idx| ranges[idx].clone()).collect();
+            let fetched = self.inner.get_ranges(location, &missing_ranges).await?;
+            for (idx, fetched) in missing.into_iter().zip(fetched) {
+                let key = self.key(location, &version, ranges[idx].clone());
+                self.cache.insert(key, fetched.clone()).await;
+                data[idx] = Some(fetched);
+            }
+        }
+        Ok(data.into_iter().flatten().collect())
+    }
+
+    async fn head(&self, location: &Path) -> object_store::Result<ObjectMeta> {
+        let meta = self.inner.head(location).await?;
+        self.cache.record_meta(&meta);
+        Ok(meta)
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.cache.forget(location);
+        self.inner.delete(location).await
+    }
+
+    fn list(
+        &self,
+        prefix: Option<&Path>,
+    ) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(
+        &self,
+        prefix: Option<&Path>,
+    ) -> object_store::Result<ListResult> {
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.cache.forget(to);
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(
+        &self,
+        from: &Path,
+        to: &Path,
+    ) -> object_store::Result<()> {
+        self.cache.forget(to);
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+/// Object store registry whose stores read through a [DataCache], other than the local
+/// file system, which the operating system already caches
+#[derive(Debug)]
+pub struct CachingObjectStoreRegistry {
+    inner: Arc<dyn ObjectStoreRegistry>,
+    cache: Arc<DataCache>,
+}
+
+impl CachingObjectStoreRegistry {
+    pub fn new(inner: Arc<dyn ObjectStoreRegistry>, cache: Arc<DataCache>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+impl ObjectStoreRegistry for CachingObjectStoreRegistry {
+    fn register_store(
+        &self,
+        url: &Url,
+        store: Arc<dyn ObjectStore>,
+    ) -> Option<Arc<dyn ObjectStore>> {
+        self.inner.register_store(url, store)
+    }
+
+    fn get_store(&self, url: &Url) -> DataFusionResult<Arc<dyn ObjectStore>> {
+        let store = self.inner.get_store(url)?;
+        if url.scheme() == "file" {
+            return Ok(store);
+        }
+        Ok(Arc::new(CachingObjectStore::new(store, self.cache.clone())))
+    }
+}
+
+/// Wraps the object store registry of the runtimes `runtime_producer` creates, so that
+/// the tasks read the files of object stores through `cache`
+pub fn caching_runtime_producer(
+    runtime_producer: RuntimeProducer,
+    cache: Arc<DataCache>,
+) -> RuntimeProducer {
+    Arc::new(move |config| {
+        let runtime = runtime_producer(config)?;
+        Ok(Arc::new(RuntimeEnv {
+            memory_pool: runtime.memory_pool.clone(),
+            disk_manager: runtime.disk_manager.clone(),
+            cache_manager: runtime.cache_manager.clone(),
+            object_store_registry: Arc::new(CachingObjectStoreRegistry::new(
+                runtime.object_store_registry.clone(),
+                cache.clone(),
+            )),
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Counts the ranges read from the inner store
+    #[derive(Debug)]
+    struct CountingStore {
+        inner: InMemory,
+        reads: AtomicUsize,
+    }
+
+    impl Display for CountingStore {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "CountingStore")
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for CountingStore {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            payload: PutPayload,
+            opts: PutOptions,
+        ) -> object_store::Result<PutResult> {
+            self.inner.put_opts(location, payload, opts).await
+        }
+
+        async fn put_multipart_opts(
+            &self,
+            location: &Path,
+            opts: PutMultipartOpts,
+        ) -> object_store::Result<Box<dyn MultipartUpload>> {
+            self.inner.put_multipart_opts(location, opts).await
+        }
+
+        async fn get_opts(
+            &self,
+            location: &Path,
+            options: GetOptions,
+        ) -> object_store::Result<GetResult> {
+            if options.range.is_some() {
+                self.reads.fetch_add(1, Ordering::Relaxed);
+            }
+            self.inner.get_opts(location, options).await
+        }
+
+        async fn delete(&self, location: &Path) -> object_store::Result<()> {
+            self.inner.delete(location).await
+        }
+
+        fn list(
+            &self,
+            prefix: Option<&Path>,
+        ) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+            self.inner.list(prefix)
+        }
+
+        async fn list_with_delimiter(
+            &self,
+            prefix: Option<&Path>,
+        ) -> object_store::Result<ListResult> {
+            self.inner.list_with_delimiter(prefix).await
+        }
+
+        async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+            self.inner.copy(from, to).await
+        }
+
+        async fn copy_if_not_exists(
+            &self,
+            from: &Path,
+            to: &Path,
+        ) -> object_store::Result<()> {
+            self.inner.copy_if_not_exists(from, to).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_data_cache() -> object_store::Result<()> {
+        let disk_dir = tempfile::tempdir().unwrap();
+        // room for a single range in memory, and for two on disk
+        let cache = Arc::new(
+            DataCache::try_new(
+                10,
+                20,
+                disk_dir.path().join("cache"),
+                Duration::from_secs(60),
+            )
+            .unwrap(),
+        );
+        let inner = Arc::new(CountingStore {
+            inner: InMemory::new(),
+            reads: AtomicUsize::new(0),
+        });
+        let store = CachingObjectStore::new(inner.clone(), cache.clone());
+        let file = Path::from("data/file.parquet");
+        store
+            .put(&file, Bytes::from("0123456789abcdefghij").into())
+            .await?;
+
+        assert_eq!("0123456789", store.get_range(&file, 0..10).await?);
+        assert_eq!("0123456789", store.get_range(&file, 0..10).await?);
+        assert_eq!(1, inner.reads.load(Ordering::Relaxed));
+
+        // the first range moves to the disk, then back to memory once read again
+        assert_eq!(
+            vec![Bytes::from("abcdefghij"), Bytes::from("0123456789")],
+            store.get_ranges(&file, &[10..20, 0..10]).await?
+        );
+        assert_eq!("abcdefghij", store.get_range(&file, 10..20).await?);
+        assert_eq!("0123456789", store.get_range(&file, 0..10).await?);
+        assert_eq!(2, inner.reads.load(Ordering::Relaxed));
+
+        let usage = cache.usage();
+        assert_eq!(3, usage.memory_hits);
+        assert_eq!(1, usage.disk_hits);
+        assert_eq!(2, usage.misses);
+        assert_eq!(10, usage.memory_bytes);
+        assert_eq!(20, usage.disk_bytes);
+        assert_eq!(vec!["data/file.parquet".to_owned()], usage.cached_files);
+
+        // ranges of a rewritten file are read again
+        store.put(&file, Bytes::from("9876543210").into()).await?;
+        assert_eq!("9876543210", store.get_range(&file, 0..10).await?);
+        assert_eq!(3, inner.reads.load(Ordering::Relaxed));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lru() {
+        let key = |start| CacheKey {
+            location: "file".to_owned(),
+            version: "1".to_owned(),
+            range: start..start + 1,
+        };
+        let mut lru = Lru::new(3);
+        assert!(lru.insert(key(0), 0, 1).is_empty());
+        assert!(lru.insert(key(1), 1, 1).is_empty());
+        assert!(lru.insert(key(2), 2, 1).is_empty());
+        assert_eq!(Some(0), lru.get(&key(0)));
+
+        assert_eq!(vec![(key(1), 1), (key(2), 2)], lru.insert(key(3), 3, 2));
+        assert_eq!(vec![&key(3), &key(0)], lru.keys().collect::<Vec<_>>());
+        assert_eq!(vec![(key(4), 4)], lru.insert(key(4), 4, 4));
+        assert_eq!(3, lru.size);
+    }
+}
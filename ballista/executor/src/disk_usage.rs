@@ -0,0 +1,246 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Disk usage of the shuffle data which the jobs leave in the work dir of the executor.
+//!
+//! The usage is measured by the janitor of the executor, which evicts the data of the
+//! least recently written jobs once it exceeds the maximum, and reported to the scheduler
+//! with the heartbeats, so that it stops binding tasks to full executors.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use ballista_core::serde::protobuf::{executor_metric, ExecutorMetric, ShuffleDiskUsage};
+use log::{error, info, warn};
+use tokio::fs;
+
+/// Bytes of shuffle data in the work dir of the executor, and their maximum
+#[derive(Debug, Default)]
+pub struct DiskUsage {
+    /// 0 if the disk usage isn't bounded
+    max_bytes: u64,
+    used_bytes: AtomicU64,
+}
+
+impl DiskUsage {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            used_bytes: AtomicU64::new(0),
+        }
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Bytes used as of the last measure
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Whether the executor used up the disk it is allowed, and shouldn't run tasks
+    /// writing more shuffle data
+    pub fn is_full(&self) -> bool {
+        self.max_bytes > 0 && self.used_bytes() >= self.max_bytes
+    }
+
+    /// Measure the bytes used by the job dirs in `work_dir`
+    pub async fn refresh(&self, work_dir: &str) -> io::Result<u64> {
+        let used_bytes = job_dirs(work_dir).await?.iter().map(|dir| dir.bytes).sum();
+        self.used_bytes.store(used_bytes, Ordering::Relaxed);
+        Ok(used_bytes)
+    }
+
+    /// Remove the job dirs written the least recently until the usage fits in the
+    /// maximum, then measure it. The dirs of `active_jobs` and hidden dirs, holding the
+    /// spill files of running tasks, are never removed
+    pub async fn evict(
+        &self,
+        work_dir: &str,
+        active_jobs: &HashSet<String>,
+    ) -> io::Result<u64> {
+        let mut dirs = job_dirs(work_dir).await?;
+        let mut used_bytes: u64 = dirs.iter().map(|dir| dir.bytes).sum();
+        if self.max_bytes > 0 && used_bytes > self.max_bytes {
+            dirs.sort_by_key(|dir| dir.modified);
+            for dir in dirs {
+                if used_bytes <= self.max_bytes {
+                    break;
+                }
+                if dir.is_hidden() || active_jobs.contains(&dir.name) {
+                    continue;
+                }
+                info!(
+                    "Removing the {} bytes of job dir {:?} as the work dir exceeds {} bytes",
+                    dir.bytes, dir.path, self.max_bytes
+                );
+                match fs::remove_dir_all(&dir.path).await {
+                    Ok(()) => used_bytes -= dir.bytes,
+                    Err(e) => {
+                        error!("Fail to remove the directory {:?} due to {}", dir.path, e)
+                    }
+                }
+            }
+            if used_bytes > self.max_bytes {
+                warn!(
+                    "The work dir still uses {} bytes, more than {} bytes, the executor won't run tasks until jobs complete",
+                    used_bytes, self.max_bytes
+                );
+            }
+        }
+        self.used_bytes.store(used_bytes, Ordering::Relaxed);
+        Ok(used_bytes)
+    }
+
+    /// The usage reported to the scheduler with the heartbeats
+    pub fn metric(&self) -> ExecutorMetric {
+        ExecutorMetric {
+            metric: Some(executor_metric::Metric::ShuffleDiskUsage(
+                ShuffleDiskUsage {
+                    used_bytes: self.used_bytes(),
+                    max_bytes: self.max_bytes,
+                },
+            )),
+        }
+    }
+}
+
+/// A dir directly in the work dir, holding the data of a job
+struct JobDir {
+    name: String,
+    path: PathBuf,
+    bytes: u64,
+    /// Latest modification of the dir or of its content
+    modified: SystemTime,
+}
+
+impl JobDir {
+    fn is_hidden(&self) -> bool {
+        self.name.starts_with('.')
+    }
+}
+
+/// The dirs in `work_dir`, with the bytes of their files
+async fn job_dirs(work_dir: &str) -> io::Result<Vec<JobDir>> {
+    let mut dirs = vec![];
+    let mut entries = fs::read_dir(work_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if !metadata.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        // dirs may be removed concurrently, e.g. once their job completes
+        let Ok((bytes, modified)) = dir_size(path.clone(), metadata.modified()?).await
+        else {
+            continue;
+        };
+        dirs.push(JobDir {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            path,
+            bytes,
+            modified,
+        });
+    }
+    Ok(dirs)
+}
+
+/// The bytes of the files in `dir` and its latest modification
+async fn dir_size(dir: PathBuf, modified: SystemTime) -> io::Result<(u64, SystemTime)> {
+    let mut bytes = 0;
+    let mut latest = modified;
+    let mut to_visit = vec![dir];
+    while let Some(dir) = to_visit.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            latest = latest.max(metadata.modified()?);
+            if metadata.is_dir() {
+                to_visit.push(entry.path());
+            } else {
+                bytes += metadata.len();
+            }
+        }
+    }
+    Ok((bytes, latest))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn write_job(work_dir: &TempDir, job_id: &str, bytes: usize) {
+        let job_dir = work_dir.path().join(job_id).join("1");
+        fs::create_dir_all(&job_dir).unwrap();
+        fs::write(job_dir.join("data.arrow"), vec![0u8; bytes]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn evict_least_recently_written_jobs() -> io::Result<()> {
+        let work_dir = TempDir::new()?;
+        let path = work_dir.path().to_str().unwrap();
+        write_job(&work_dir, "old", 100);
+        std::thread::sleep(Duration::from_millis(20));
+        write_job(&work_dir, "running", 100);
+        std::thread::sleep(Duration::from_millis(20));
+        write_job(&work_dir, "recent", 100);
+        write_job(&work_dir, ".tmpSpill", 100);
+
+        let usage = DiskUsage::new(350);
+        assert_eq!(usage.refresh(path).await?, 400);
+        assert!(usage.is_full());
+
+        let active_jobs = HashSet::from(["running".to_owned()]);
+        assert_eq!(usage.evict(path, &active_jobs).await?, 300);
+        assert!(!usage.is_full());
+        assert!(!work_dir.path().join("old").exists());
+        assert!(work_dir.path().join("running").exists());
+        assert!(work_dir.path().join("recent").exists());
+
+        // the dirs of running jobs and spill files are kept, even if the executor
+        // stays full
+        let usage = DiskUsage::new(50);
+        assert_eq!(usage.evict(path, &active_jobs).await?, 200);
+        assert!(usage.is_full());
+        assert!(!work_dir.path().join("recent").exists());
+        assert!(work_dir.path().join("running").exists());
+        assert!(work_dir.path().join(".tmpSpill").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unbounded_usage() -> io::Result<()> {
+        let work_dir = TempDir::new()?;
+        let path = work_dir.path().to_str().unwrap();
+        write_job(&work_dir, "job", 100);
+
+        let usage = DiskUsage::default();
+        assert_eq!(usage.evict(path, &HashSet::new()).await?, 100);
+        assert!(!usage.is_full());
+        assert!(work_dir.path().join("job").exists());
+        Ok(())
+    }
+}
@@ -75,6 +75,14 @@ impl ExecutionEngine for DefaultExecutionEngine {
                 work_dir.to_string(),
                 shuffle_writer.shuffle_output_partitioning().cloned(),
             )
+            .map(|exec| {
+                exec.with_range_partitioning(shuffle_writer.range_partitioning().cloned())
+                    .with_compression(shuffle_writer.compression())
+                    .with_object_store_url(
+                        shuffle_writer.object_store_url().map(str::to_owned),
+                    )
+                    .with_final_stage(shuffle_writer.is_final_stage())
+            })
         } else {
             Err(DataFusionError::Internal(
                 "Plan passed to new_query_stage_exec is not a ShuffleWriterExec"
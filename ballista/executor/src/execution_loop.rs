@@ -17,17 +17,20 @@
 
 use crate::cpu_bound_executor::DedicatedExecutor;
 use crate::executor::Executor;
+use crate::scan_leases::SchedulerScanFileLeaser;
 use crate::{as_task_status, TaskExecutionTimes};
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::ScanFileLeases;
 use ballista_core::extension::SessionConfigHelperExt;
 use ballista_core::serde::protobuf::{
     scheduler_grpc_client::SchedulerGrpcClient, PollWorkParams, PollWorkResult,
     TaskDefinition, TaskStatus,
 };
+use ballista_core::serde::scheduler::from_proto::decode_task_plan;
 use ballista_core::serde::scheduler::{ExecutorSpecification, PartitionId};
 use ballista_core::serde::BallistaCodec;
+use ballista_core::utils::GrpcChannel;
 use datafusion::execution::context::TaskContext;
-use datafusion::physical_plan::ExecutionPlan;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 use futures::FutureExt;
@@ -35,15 +38,13 @@ use log::{debug, error, info, warn};
 use std::any::Any;
 use std::convert::TryInto;
 use std::error::Error;
-use std::ops::Deref;
 use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::{sync::Arc, time::Duration};
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
-use tonic::transport::Channel;
 
 pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
-    mut scheduler: SchedulerGrpcClient<Channel>,
+    mut scheduler: SchedulerGrpcClient<GrpcChannel>,
     executor: Arc<Executor>,
     codec: BallistaCodec<T, U>,
 ) -> Result<(), BallistaError> {
@@ -63,6 +64,8 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
 
     let dedicated_executor =
         DedicatedExecutor::new("task_runner", executor_specification.task_slots as usize);
+    let scan_leases =
+        SchedulerScanFileLeaser::new(scheduler.clone(), &executor.metadata).into_leases();
 
     loop {
         // Wait for task slots to be available before asking for new work
@@ -77,25 +80,50 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         let task_status: Vec<TaskStatus> =
             sample_tasks_status(&mut task_status_receiver).await;
 
+        // An executor whose job data fills its disk takes no new tasks, until enough
+        // job data is removed
+        let num_free_slots = if executor.disk_usage.is_full() {
+            0
+        } else {
+            available_task_slots.available_permits() as u32
+        };
         let poll_work_result: anyhow::Result<
             tonic::Response<PollWorkResult>,
             tonic::Status,
         > = scheduler
             .poll_work(PollWorkParams {
                 metadata: Some(executor.metadata.clone()),
-                num_free_slots: available_task_slots.available_permits() as u32,
+                num_free_slots,
                 task_status,
             })
             .await;
 
         match poll_work_result {
             Ok(result) => {
-                let tasks = result.into_inner().tasks;
+                let PollWorkResult { tasks, settings } = result.into_inner();
+                if let Some(settings) = &settings {
+                    executor.apply_settings(settings);
+                }
                 active_job = !tasks.is_empty();
 
-                for task in tasks {
+                for mut task in tasks {
                     let task_status_sender = task_status_sender.clone();
 
+                    if let Err(e) = executor
+                        .task_plan_cache
+                        .resolve_plan(
+                            &mut scheduler,
+                            &executor.metadata,
+                            &task.job_id,
+                            &task.plan_hash,
+                            &mut task.plan,
+                        )
+                        .await
+                    {
+                        warn!("Failed to fetch the plan of task: {:?}", e);
+                        continue;
+                    }
+
                     // Acquire a permit/slot for the task
                     let permit =
                         available_task_slots.clone().acquire_owned().await.unwrap();
@@ -105,6 +133,7 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                         permit,
                         task_status_sender,
                         task,
+                        scan_leases.clone(),
                         &codec,
                         &dedicated_executor,
                     )
@@ -146,11 +175,13 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     permit: OwnedSemaphorePermit,
     task_status_sender: Sender<TaskStatus>,
     task: TaskDefinition,
+    scan_leases: Arc<ScanFileLeases>,
     codec: &BallistaCodec<T, U>,
     dedicated_executor: &DedicatedExecutor,
 ) -> Result<(), BallistaError> {
     let task_id = task.task_id;
     let task_attempt_num = task.task_attempt_num;
+    let plan_format = task.plan_format();
     let job_id = task.job_id;
     let stage_id = task.stage_id;
     let stage_attempt_num = task.stage_attempt_num;
@@ -170,14 +201,27 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
         task_identity,
         task.props
     );
-    let session_config = executor.produce_config();
-    let session_config = session_config.update_from_key_value_pair(&task.props);
+    let session_config = executor.produce_config().with_extension(scan_leases);
+    let session_config = session_config
+        .update_from_key_value_pair(&task.props)
+        .with_ballista_fetch_token(task.fetch_token.clone().into());
+    session_config.check_task_key_value_pairs(&task.props)?;
 
-    let task_scalar_functions = executor.function_registry.scalar_functions.clone();
-    let task_aggregate_functions = executor.function_registry.aggregate_functions.clone();
-    let task_window_functions = executor.function_registry.window_functions.clone();
+    let function_registry = executor
+        .function_registry
+        .with_random_seed(task.random_seed);
+    let task_scalar_functions = function_registry.scalar_functions.clone();
+    let task_aggregate_functions = function_registry.aggregate_functions.clone();
+    let task_window_functions = function_registry.window_functions.clone();
 
-    let runtime = executor.produce_runtime(&session_config)?;
+    let part = PartitionId {
+        job_id: job_id.clone(),
+        stage_id: stage_id as usize,
+        partition_id: partition_id as usize,
+    };
+    let (runtime, spill_dir) =
+        executor.produce_task_runtime(&session_config, &part, task_id as usize)?;
+    let (runtime, object_store_metrics) = executor.produce_task_object_stores(runtime);
     let session_id = task.session_id.clone();
     let task_context = Arc::new(TaskContext::new(
         Some(task_identity.clone()),
@@ -189,14 +233,21 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
         runtime.clone(),
     ));
 
-    let plan: Arc<dyn ExecutionPlan> =
-        U::try_decode(task.plan.as_slice()).and_then(|proto| {
-            proto.try_into_physical_plan(
-                task_context.deref(),
-                runtime.deref(),
-                codec.physical_extension_codec(),
-            )
-        })?;
+    let plan = decode_task_plan(
+        plan_format,
+        &task.plan,
+        &task.plan_compression,
+        &task.inputs,
+        task.plan_fingerprint,
+        &job_id,
+        stage_id as usize,
+        task_context.session_config(),
+        &function_registry,
+        runtime,
+        codec,
+    )
+    .await?;
+    let plan = executor.produce_task_plan(plan, task_context.runtime_env().as_ref())?;
 
     let query_stage_exec = executor.execution_engine.create_query_stage_exec(
         job_id.clone(),
@@ -206,11 +257,6 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     )?;
     dedicated_executor.spawn(async move {
         use std::panic::AssertUnwindSafe;
-        let part = PartitionId {
-            job_id: job_id.clone(),
-            stage_id: stage_id as usize,
-            partition_id: partition_id as usize,
-        };
 
         let execution_result = match AssertUnwindSafe(executor.execute_query_stage(
             task_id as usize,
@@ -228,11 +274,15 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
                 Err(BallistaError::Internal(format!("{:#?}", any_to_string(&r))))
             }
         };
+        drop(spill_dir);
 
         info!("Done with task {}", task_identity);
         debug!("Statistics: {:?}", execution_result);
 
-        let plan_metrics = query_stage_exec.collect_plan_metrics();
+        let mut plan_metrics = query_stage_exec.collect_plan_metrics();
+        if let Some(object_store_metrics) = &object_store_metrics {
+            object_store_metrics.report(&mut plan_metrics, partition_id as usize);
+        }
         let operator_metrics = plan_metrics
             .into_iter()
             .map(|m| m.try_into())
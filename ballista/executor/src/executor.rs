@@ -17,43 +17,85 @@
 
 //! Ballista executor logic
 
+use crate::data_cache::{caching_runtime_producer, DataCache};
+use crate::disk_usage::DiskUsage;
 use crate::execution_engine::DefaultExecutionEngine;
 use crate::execution_engine::ExecutionEngine;
 use crate::execution_engine::QueryStageExecutor;
+use crate::flight_service::{FetchTokens, FlightStreams};
+use crate::metadata_cache::{with_metadata_cache, MetadataCache};
 use crate::metrics::ExecutorMetricsCollector;
 use crate::metrics::LoggingMetricsCollector;
+use crate::object_store_retry::{
+    retrying_task_runtime, ObjectStoreRetry, ObjectStoreTaskMetrics,
+};
+use crate::plan_cache::TaskPlanCache;
+use crate::spill_dir::{SpillEncryption, TaskSpillDir};
+use crate::task_logs::{TaskLogStore, TASK_SPAN_NAME};
+use ballista_core::config::{
+    redact_value, EXECUTOR_LOG_LEVEL, EXECUTOR_TASK_LOG_MAX_LINES,
+    EXECUTOR_TASK_PLAN_CACHE_SIZE,
+};
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::{BroadcastCache, ShuffleFetchLimiter};
+use ballista_core::extension::{SessionConfigExt, SessionConfigHelperExt};
+use ballista_core::planner::StagePlanCache;
 use ballista_core::registry::BallistaFunctionRegistry;
+use ballista_core::security::GrpcSecurityConfig;
 use ballista_core::serde::protobuf;
 use ballista_core::serde::protobuf::ExecutorRegistration;
 use ballista_core::serde::scheduler::PartitionId;
 use ballista_core::ConfigProducer;
 use ballista_core::RuntimeProducer;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use datafusion::execution::context::TaskContext;
 use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::execution::FunctionRegistry;
+use datafusion::physical_plan::ExecutionPlan;
 use datafusion::prelude::SessionConfig;
 use futures::future::AbortHandle;
-use std::future::Future;
-use std::pin::Pin;
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
-pub struct TasksDrainedFuture(pub Arc<Executor>);
+/// Interval at which a draining executor checks whether its tasks completed
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-impl Future for TasksDrainedFuture {
-    type Output = ();
+/// Time a cancelled task is given to stop at its next batch and remove its partial
+/// output, before it is aborted
+const CANCELLED_TASK_GRACE_PERIOD: Duration = Duration::from_secs(5);
 
-    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.0.abort_handles.len() > 0 {
-            Poll::Pending
-        } else {
-            Poll::Ready(())
-        }
+type AbortHandles = Arc<DashMap<(usize, PartitionId), (AbortHandle, CancellationToken)>>;
+
+type CancelledTasks = Arc<DashSet<(usize, PartitionId)>>;
+
+type JobTaskPermits = Arc<DashMap<String, Arc<Semaphore>>>;
+
+/// Counts a task as queued until dropped
+struct QueuedTask<'a>(&'a AtomicUsize);
+
+impl<'a> QueuedTask<'a> {
+    fn new(queued_tasks: &'a AtomicUsize) -> Self {
+        queued_tasks.fetch_add(1, Ordering::Relaxed);
+        Self(queued_tasks)
+    }
+}
+
+impl Drop for QueuedTask<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
     }
 }
 
-type AbortHandles = Arc<DashMap<(usize, PartitionId), AbortHandle>>;
+/// Replaces the log filter of the executor with the given directives, e.g.
+/// `info,ballista=debug`
+pub type LogFilterReloader = Arc<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
 
 /// Ballista executor
 #[derive(Clone)]
@@ -79,12 +121,73 @@ pub struct Executor {
     /// Concurrent tasks can run in executor
     pub concurrent_tasks: usize,
 
-    /// Handles to abort executing tasks
+    /// Handles to abort executing tasks, and the tokens interrupting them
     abort_handles: AbortHandles,
 
+    /// Tasks cancelled before they started executing, which must not execute
+    cancelled_tasks: CancelledTasks,
+
+    /// Permits of the tasks of the jobs capping their tasks running at the same time
+    /// with `ballista.job.max_tasks_per_executor`
+    job_task_permits: JobTaskPermits,
+
+    /// Number of tasks waiting for a permit of their job
+    queued_tasks: Arc<AtomicUsize>,
+
     /// Execution engine that the executor will delegate to
     /// for executing query stages
     pub(crate) execution_engine: Arc<dyn ExecutionEngine>,
+
+    /// Store of the logs captured for each task, if log capture is enabled
+    pub task_log_store: Option<Arc<TaskLogStore>>,
+
+    /// Plans dispatched with tasks by reference, fetched from the scheduler
+    pub task_plan_cache: Arc<TaskPlanCache>,
+
+    /// Stages planned from the logical plans of jobs, shared by the tasks of the stages
+    pub stage_plan_cache: Arc<StagePlanCache>,
+
+    /// Build sides of broadcast joins, shared by the tasks of the join stages
+    pub broadcast_cache: Arc<BroadcastCache>,
+
+    /// Partitions being streamed by the Flight service, aborted with their job
+    pub flight_streams: Arc<FlightStreams>,
+
+    /// Tokens of the jobs whose tasks ran on the executor, required by the Flight
+    /// service to serve their partitions
+    pub fetch_tokens: Arc<FetchTokens>,
+
+    /// TLS and auth token of the connections to the scheduler and the other executors
+    pub grpc_security: Arc<GrpcSecurityConfig>,
+
+    /// Replaces the log filter of the executor, if its logging allows it
+    pub log_filter_reloader: Option<LogFilterReloader>,
+
+    /// Disk used by the shuffle data of the jobs in the work dir
+    pub disk_usage: Arc<DiskUsage>,
+
+    /// Cache of the byte ranges the tasks read from object stores, if enabled
+    pub data_cache: Option<Arc<DataCache>>,
+
+    /// Cache of the metadata of the Parquet files the tasks scan, if enabled
+    pub metadata_cache: Option<Arc<MetadataCache>>,
+
+    /// Retries of the transient errors of the object store reads of the tasks, if
+    /// enabled
+    pub object_store_retry: Option<Arc<ObjectStoreRetry>>,
+
+    /// Limit of the shuffle partitions the tasks fetch at the same time, if enabled
+    pub shuffle_fetch_limiter: Option<Arc<ShuffleFetchLimiter>>,
+
+    /// Whether the tasks spill to private dirs of their own in the work dir, removed
+    /// once they end
+    pub task_spill_dirs: bool,
+
+    /// Encryption of the spill dirs of the tasks with a key per job, if enabled
+    pub spill_encryption: Option<Arc<SpillEncryption>>,
+
+    /// Version of the last operational settings pushed by the scheduler and applied
+    settings_version: Arc<AtomicU64>,
 }
 
 impl Executor {
@@ -132,10 +235,104 @@ impl Executor {
             metrics_collector,
             concurrent_tasks,
             abort_handles: Default::default(),
+            cancelled_tasks: Default::default(),
+            job_task_permits: Default::default(),
+            queued_tasks: Default::default(),
             execution_engine: execution_engine
                 .unwrap_or_else(|| Arc::new(DefaultExecutionEngine {})),
+            task_log_store: None,
+            task_plan_cache: Arc::new(TaskPlanCache::default()),
+            stage_plan_cache: Arc::new(StagePlanCache::default()),
+            broadcast_cache: Arc::new(BroadcastCache::default()),
+            flight_streams: Arc::new(FlightStreams::default()),
+            fetch_tokens: Arc::new(FetchTokens::new(work_dir)),
+            grpc_security: Arc::new(GrpcSecurityConfig::default()),
+            log_filter_reloader: None,
+            disk_usage: Arc::new(DiskUsage::default()),
+            data_cache: None,
+            metadata_cache: None,
+            object_store_retry: None,
+            shuffle_fetch_limiter: None,
+            task_spill_dirs: false,
+            spill_encryption: None,
+            settings_version: Default::default(),
         }
     }
+
+    /// Secure the connections to the scheduler and the other executors with the TLS
+    /// and auth token of `grpc_security`
+    pub fn with_grpc_security(mut self, grpc_security: GrpcSecurityConfig) -> Self {
+        self.grpc_security = Arc::new(grpc_security);
+        self
+    }
+
+    /// Serve the task logs captured in the given [TaskLogStore]
+    pub fn with_task_log_store(mut self, task_log_store: Arc<TaskLogStore>) -> Self {
+        self.task_log_store = Some(task_log_store);
+        self
+    }
+
+    /// Let the scheduler change the log filter of the executor with the given
+    /// [LogFilterReloader]
+    pub fn with_log_filter_reloader(mut self, reloader: LogFilterReloader) -> Self {
+        self.log_filter_reloader = Some(reloader);
+        self
+    }
+
+    /// Bound the disk used by the shuffle data in the work dir to `max_bytes`,
+    /// 0 meaning unbounded
+    pub fn with_max_disk_bytes(mut self, max_bytes: u64) -> Self {
+        self.disk_usage = Arc::new(DiskUsage::new(max_bytes));
+        self
+    }
+
+    /// Read the files of object stores through the given [DataCache], so that repeated
+    /// scans of the same files are served by the executor
+    pub fn with_data_cache(mut self, data_cache: Arc<DataCache>) -> Self {
+        self.runtime_producer =
+            caching_runtime_producer(self.runtime_producer, data_cache.clone());
+        self.data_cache = Some(data_cache);
+        self
+    }
+
+    /// Fetch the metadata of the Parquet files the tasks scan through the given
+    /// [MetadataCache], so that the tasks scanning the same files don't decode it again
+    pub fn with_metadata_cache(mut self, metadata_cache: Arc<MetadataCache>) -> Self {
+        self.metadata_cache = Some(metadata_cache);
+        self
+    }
+
+    /// Retry the transient errors of the object store reads of the tasks with the given
+    /// [ObjectStoreRetry], e.g. the requests throttled by S3
+    pub fn with_object_store_retry(mut self, retry: Arc<ObjectStoreRetry>) -> Self {
+        self.object_store_retry = Some(retry);
+        self
+    }
+
+    /// Limit the shuffle partitions the tasks fetch at the same time from the other
+    /// executors and from object stores with the given [ShuffleFetchLimiter]
+    pub fn with_shuffle_fetch_limiter(
+        mut self,
+        limiter: Arc<ShuffleFetchLimiter>,
+    ) -> Self {
+        self.shuffle_fetch_limiter = Some(limiter);
+        self
+    }
+
+    /// Spill the tasks to private dirs of their own in the work dir, only accessible
+    /// to the user of the executor and removed with their spill files once the tasks
+    /// end, instead of the dirs of the runtimes of the `runtime_producer`
+    pub fn with_task_spill_dirs(mut self, task_spill_dirs: bool) -> Self {
+        self.task_spill_dirs = task_spill_dirs;
+        self
+    }
+
+    /// Encrypt the spill dirs of the tasks with a key per job, see [SpillEncryption].
+    /// Only applies when the tasks spill to dirs of their own
+    pub fn with_spill_encryption(mut self, encryption: Arc<SpillEncryption>) -> Self {
+        self.spill_encryption = Some(encryption);
+        self
+    }
 }
 
 impl Executor {
@@ -146,8 +343,74 @@ impl Executor {
         (self.runtime_producer)(config)
     }
 
+    /// The [RuntimeEnv] of the task `task_id` of the partition `partition`, and its
+    /// spill dir if the tasks spill to dirs of their own. The dir is removed when the
+    /// returned [TaskSpillDir] is dropped, which must outlive the task
+    pub fn produce_task_runtime(
+        &self,
+        config: &SessionConfig,
+        partition: &PartitionId,
+        task_id: usize,
+    ) -> datafusion::error::Result<(Arc<RuntimeEnv>, Option<TaskSpillDir>)> {
+        let runtime = self.produce_runtime(config)?;
+        if !self.task_spill_dirs {
+            return Ok((runtime, None));
+        }
+        let name = format!("{}-{}", partition.job_id, task_id);
+        let spill_dir = match &self.spill_encryption {
+            Some(encryption) => TaskSpillDir::create_encrypted(
+                encryption.clone(),
+                &name,
+                &partition.job_id,
+            )?,
+            None => TaskSpillDir::create(&self.work_dir, &name)?,
+        };
+        Ok((spill_dir.runtime(&runtime)?, Some(spill_dir)))
+    }
+
+    /// The [RuntimeEnv] of a task whose object stores retry the transient errors of
+    /// their reads with the [ObjectStoreRetry] of the executor, if any, and the retries
+    /// and throttles of the task, reported with its metrics
+    pub fn produce_task_object_stores(
+        &self,
+        runtime: Arc<RuntimeEnv>,
+    ) -> (Arc<RuntimeEnv>, Option<Arc<ObjectStoreTaskMetrics>>) {
+        match &self.object_store_retry {
+            Some(retry) => {
+                let metrics = Arc::new(ObjectStoreTaskMetrics::default());
+                let runtime =
+                    retrying_task_runtime(&runtime, retry.clone(), metrics.clone());
+                (runtime, Some(metrics))
+            }
+            None => (runtime, None),
+        }
+    }
+
+    /// The plan of a task, whose Parquet scans fetch the metadata of their files through
+    /// the [MetadataCache] of the executor, if any, from the object stores of `runtime`
+    pub fn produce_task_plan(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        runtime: &RuntimeEnv,
+    ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+        match &self.metadata_cache {
+            Some(metadata_cache) => with_metadata_cache(plan, metadata_cache, runtime),
+            None => Ok(plan),
+        }
+    }
+
+    /// The [SessionConfig] of a task, sharing the build sides of broadcast joins and
+    /// the limit of the shuffle fetches with the other tasks of the executor, and
+    /// fetching shuffle partitions from the other executors with its TLS and auth token
     pub fn produce_config(&self) -> SessionConfig {
-        (self.config_producer)()
+        let config = (self.config_producer)()
+            .with_extension(self.broadcast_cache.clone())
+            .with_extension(self.stage_plan_cache.clone())
+            .with_extension(self.grpc_security.clone());
+        match &self.shuffle_fetch_limiter {
+            Some(limiter) => config.with_extension(limiter.clone()),
+            None => config,
+        }
     }
 
     /// Execute one partition of a query stage and persist the result to disk in IPC format. On
@@ -160,27 +423,150 @@ impl Executor {
         query_stage_exec: Arc<dyn QueryStageExecutor>,
         task_ctx: Arc<TaskContext>,
     ) -> Result<Vec<protobuf::ShuffleWritePartition>, BallistaError> {
+        // the tasks of a job beyond its limit wait for other tasks of the job to complete
+        let max_tasks = task_ctx
+            .session_config()
+            .ballista_job_max_tasks_per_executor();
+        let timeout_seconds = task_ctx.session_config().ballista_stage_timeout_seconds();
+        let _permit = if max_tasks > 0 {
+            let permits = self
+                .job_task_permits
+                .entry(partition.job_id.clone())
+                .or_insert_with(|| Arc::new(Semaphore::new(max_tasks)))
+                .clone();
+            let queued = QueuedTask::new(&self.queued_tasks);
+            let permit = permits.acquire_owned().await.map_err(|e| {
+                BallistaError::Internal(format!("Failed to acquire task permit: {e}"))
+            })?;
+            drop(queued);
+            Some(permit)
+        } else {
+            None
+        };
+
+        // the partitions written by the task are only served to the fetches presenting
+        // the token of its job
+        self.fetch_tokens.register(
+            &partition.job_id,
+            &task_ctx.session_config().ballista_fetch_token(),
+        );
+
+        // the shuffle writer of the task stops at its next batch once cancelled. The
+        // scalar functions of the task may be seeded for it, its aggregate and window
+        // functions are those of the executor
+        let cancellation = CancellationToken::new();
+        let scalar_functions = task_ctx
+            .udfs()
+            .into_iter()
+            .map(|name| Ok((name.clone(), task_ctx.udf(&name)?)))
+            .collect::<datafusion::error::Result<HashMap<_, _>>>()?;
+        let task_ctx = Arc::new(TaskContext::new(
+            task_ctx.task_id(),
+            task_ctx.session_id(),
+            task_ctx
+                .session_config()
+                .clone()
+                .with_extension(Arc::new(cancellation.clone())),
+            scalar_functions,
+            self.function_registry.aggregate_functions.clone(),
+            self.function_registry.window_functions.clone(),
+            task_ctx.runtime_env(),
+        ));
+
+        let span = tracing::info_span!(
+            TASK_SPAN_NAME,
+            job_id = %partition.job_id,
+            stage_id = partition.stage_id,
+            partition_id = partition.partition_id,
+            task_id = task_id as u64,
+        );
         let (task, abort_handle) = futures::future::abortable(
-            query_stage_exec.execute_query_stage(partition.partition_id, task_ctx),
+            query_stage_exec
+                .execute_query_stage(partition.partition_id, task_ctx)
+                .instrument(span),
         );
 
-        self.abort_handles
-            .insert((task_id, partition.clone()), abort_handle);
+        self.abort_handles.insert(
+            (task_id, partition.clone()),
+            (abort_handle, cancellation.clone()),
+        );
 
-        let partitions = task.await??;
+        // The task may have been cancelled while waiting for a free task slot
+        if self
+            .cancelled_tasks
+            .remove(&(task_id, partition.clone()))
+            .is_some()
+        {
+            self.abort_handles.remove(&(task_id, partition.clone()));
+            return Err(killed_task(task_id, &partition));
+        }
 
+        self.metrics_collector.record_task_started(
+            &partition.job_id,
+            partition.stage_id,
+            partition.partition_id,
+        );
+        let mut task = pin!(task);
+        let result = if timeout_seconds > 0 {
+            let timeout = Duration::from_secs(timeout_seconds as u64);
+            tokio::time::timeout(timeout, task.as_mut()).await
+        } else {
+            Ok(task.as_mut().await)
+        };
+        let result = match result {
+            Ok(result) => result
+                .map_err(BallistaError::from)
+                .and_then(|r| r.map_err(BallistaError::from))
+                .map_err(|e| {
+                    if cancellation.is_cancelled() {
+                        killed_task(task_id, &partition)
+                    } else {
+                        e
+                    }
+                }),
+            Err(_) => {
+                // interrupt the task, giving it the time to remove its partial output
+                cancellation.cancel();
+                let _ = tokio::time::timeout(CANCELLED_TASK_GRACE_PERIOD, task).await;
+                Err(BallistaError::TimedOut(format!(
+                    "Task {task_id} of stage {}/{} ran for longer than the {timeout_seconds}s stage timeout",
+                    partition.job_id, partition.stage_id
+                )))
+            }
+        };
         self.abort_handles.remove(&(task_id, partition.clone()));
+        if result.is_err() {
+            self.metrics_collector.record_task_failed(
+                &partition.job_id,
+                partition.stage_id,
+                partition.partition_id,
+            );
+        }
+        let partitions = result?;
 
         self.metrics_collector.record_stage(
             &partition.job_id,
             partition.stage_id,
             partition.partition_id,
-            query_stage_exec,
+            query_stage_exec.clone(),
         );
 
         Ok(partitions)
     }
 
+    /// Whether the given task is currently executing on this executor
+    pub fn is_task_running(&self, job_id: &str, task_id: usize) -> bool {
+        self.abort_handles.iter().any(|entry| {
+            let (running_task_id, partition) = entry.key();
+            *running_task_id == task_id && partition.job_id == job_id
+        })
+    }
+
+    /// Cancel a task, returning whether it was executing. An executing task stops at its
+    /// next batch and removes the shuffle files it partially wrote, or is aborted if it
+    /// didn't stop within [CANCELLED_TASK_GRACE_PERIOD]. A task which did not start
+    /// executing yet is cancelled as soon as it starts, so that the tasks of a failed job
+    /// never execute once cancelled.
     pub async fn cancel_task(
         &self,
         task_id: usize,
@@ -188,21 +574,41 @@ impl Executor {
         stage_id: usize,
         partition_id: usize,
     ) -> Result<bool, BallistaError> {
-        if let Some((_, handle)) = self.abort_handles.remove(&(
+        let key = (
             task_id,
             PartitionId {
                 job_id,
                 stage_id,
                 partition_id,
             },
-        )) {
-            handle.abort();
+        );
+        // Recorded before looking up the abort handle, so that a task starting
+        // concurrently either finds it or has its handle found
+        self.cancelled_tasks.insert(key.clone());
+        if let Some((_, (abort_handle, cancellation))) = self.abort_handles.remove(&key) {
+            self.cancelled_tasks.remove(&key);
+            cancellation.cancel();
+            tokio::spawn(async move {
+                tokio::time::sleep(CANCELLED_TASK_GRACE_PERIOD).await;
+                abort_handle.abort();
+            });
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Forget the cancellations of the tasks of a job which never started
+    pub fn forget_cancelled_tasks(&self, job_id: &str) {
+        self.cancelled_tasks
+            .retain(|(_, partition)| partition.job_id != job_id);
+    }
+
+    /// Forget the permits of the tasks of a completed job
+    pub fn forget_task_permits(&self, job_id: &str) {
+        self.job_task_permits.remove(job_id);
+    }
+
     pub fn work_dir(&self) -> &str {
         &self.work_dir
     }
@@ -210,6 +616,95 @@ impl Executor {
     pub fn active_task_count(&self) -> usize {
         self.abort_handles.len()
     }
+
+    /// Number of tasks waiting for another task of their job to complete, as their job
+    /// caps its tasks running at the same time on the executor
+    pub fn queued_task_count(&self) -> usize {
+        self.queued_tasks.load(Ordering::Relaxed)
+    }
+
+    /// Wait for the running tasks to complete, for at most `timeout` if set. Returns
+    /// whether they all completed
+    pub async fn drain_tasks(&self, timeout: Option<Duration>) -> bool {
+        let started = Instant::now();
+        while !self.abort_handles.is_empty() {
+            if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+                return false;
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        true
+    }
+
+    /// The jobs with tasks currently executing on this executor
+    pub fn active_jobs(&self) -> HashSet<String> {
+        self.abort_handles
+            .iter()
+            .map(|entry| entry.key().1.job_id.clone())
+            .collect()
+    }
+
+    /// Version of the last operational settings pushed by the scheduler and applied,
+    /// 0 if none was
+    pub fn settings_version(&self) -> u64 {
+        self.settings_version.load(Ordering::Acquire)
+    }
+
+    /// Apply the operational settings pushed by the scheduler, unless their version was
+    /// already applied. Invalid and unknown settings are logged and skipped
+    pub fn apply_settings(&self, settings: &protobuf::ExecutorSettings) {
+        let previous_version = self
+            .settings_version
+            .fetch_max(settings.version, Ordering::AcqRel);
+        if settings.version <= previous_version {
+            return;
+        }
+
+        info!(
+            "Applying version {} of the settings pushed by the scheduler",
+            settings.version
+        );
+        for protobuf::KeyValuePair { key, value } in &settings.settings {
+            let result = match key.as_str() {
+                EXECUTOR_LOG_LEVEL => match &self.log_filter_reloader {
+                    Some(reloader) => reloader(value),
+                    None => {
+                        Err("the log filter of the executor can't be changed".to_owned())
+                    }
+                },
+                EXECUTOR_TASK_PLAN_CACHE_SIZE => value
+                    .parse()
+                    .map(|max_plans| {
+                        self.task_plan_cache.set_max_plans(max_plans);
+                        self.stage_plan_cache.set_max_stages(max_plans);
+                    })
+                    .map_err(|e| format!("{e}")),
+                EXECUTOR_TASK_LOG_MAX_LINES => match &self.task_log_store {
+                    Some(task_log_store) => value
+                        .parse()
+                        .map(|max_lines| task_log_store.set_max_lines_per_task(max_lines))
+                        .map_err(|e| format!("{e}")),
+                    None => Err("task log capture is disabled".to_owned()),
+                },
+                _ => Err("unknown setting".to_owned()),
+            };
+            let value = redact_value(key, value);
+            match result {
+                Ok(()) => info!("Set executor setting `{key}` to `{value}`"),
+                Err(e) => {
+                    warn!("Could not set executor setting `{key}` to `{value}`: {e}")
+                }
+            }
+        }
+    }
+}
+
+/// The error of a task cancelled by the scheduler
+fn killed_task(task_id: usize, partition: &PartitionId) -> BallistaError {
+    BallistaError::TaskKilled(format!(
+        "Task {task_id} of stage {}/{} was cancelled by the scheduler",
+        partition.job_id, partition.stage_id
+    ))
 }
 
 #[cfg(test)]
@@ -218,11 +713,16 @@ mod test {
     use crate::executor::Executor;
     use arrow::datatypes::{Schema, SchemaRef};
     use arrow::record_batch::RecordBatch;
+    use ballista_core::config::{EXECUTOR_LOG_LEVEL, EXECUTOR_TASK_PLAN_CACHE_SIZE};
+    use ballista_core::error::BallistaError;
     use ballista_core::execution_plans::ShuffleWriterExec;
+    use ballista_core::extension::SessionConfigExt;
+    use ballista_core::serde::protobuf;
     use ballista_core::serde::protobuf::ExecutorRegistration;
     use ballista_core::serde::scheduler::PartitionId;
     use ballista_core::utils::default_config_producer;
-    use ballista_core::RuntimeProducer;
+    use ballista_core::{build_version, RuntimeProducer};
+    use bytes::Bytes;
     use datafusion::error::{DataFusionError, Result};
     use datafusion::execution::context::TaskContext;
 
@@ -230,11 +730,11 @@ mod test {
         DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PlanProperties,
         RecordBatchStream, SendableRecordBatchStream, Statistics,
     };
-    use datafusion::prelude::SessionContext;
+    use datafusion::prelude::{SessionConfig, SessionContext};
     use futures::Stream;
     use std::any::Any;
     use std::pin::Pin;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
     use std::task::{Context, Poll};
     use std::time::Duration;
     use tempfile::TempDir;
@@ -334,63 +834,87 @@ mod test {
         }
     }
 
-    #[tokio::test]
-    async fn test_task_cancellation() {
-        let work_dir = TempDir::new()
-            .unwrap()
-            .into_path()
-            .into_os_string()
-            .into_string()
-            .unwrap();
-
-        let shuffle_write = ShuffleWriterExec::try_new(
-            "job-id".to_owned(),
-            1,
-            Arc::new(NeverendingOperator::new()),
-            work_dir.clone(),
-            None,
-        )
-        .expect("creating shuffle writer");
-
-        let query_stage_exec = DefaultQueryStageExec::new(shuffle_write);
-
+    /// An executor with two task slots, running its tasks on the runtime of `ctx`
+    fn test_executor(work_dir: &str, ctx: &SessionContext) -> Executor {
         let executor_registration = ExecutorRegistration {
             id: "executor".to_string(),
             port: 0,
             grpc_port: 0,
             specification: None,
             host: None,
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+            registration_token: String::new(),
         };
-        let config_producer = Arc::new(default_config_producer);
-        let ctx = SessionContext::new();
         let runtime_env = ctx.runtime_env().clone();
         let runtime_producer: RuntimeProducer =
             Arc::new(move |_| Ok(runtime_env.clone()));
-
-        let executor = Executor::new_basic(
+        Executor::new_basic(
             executor_registration,
-            &work_dir,
+            work_dir,
             runtime_producer,
-            config_producer,
+            Arc::new(default_config_producer),
             2,
-        );
+        )
+    }
+
+    /// The stage 1 of `job-id`, writing the output of a never ending operator to
+    /// `work_dir`
+    fn never_ending_stage(work_dir: &str) -> Arc<DefaultQueryStageExec> {
+        let shuffle_write = ShuffleWriterExec::try_new(
+            "job-id".to_owned(),
+            1,
+            Arc::new(NeverendingOperator::new()),
+            work_dir.to_owned(),
+            None,
+        )
+        .expect("creating shuffle writer");
+        Arc::new(DefaultQueryStageExec::new(shuffle_write))
+    }
+
+    fn first_partition() -> PartitionId {
+        PartitionId {
+            job_id: "job-id".to_owned(),
+            stage_id: 1,
+            partition_id: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_task_cancellation() {
+        let work_dir = TempDir::new().unwrap();
+        let work_dir = work_dir.path().to_str().unwrap();
+        let query_stage_exec = never_ending_stage(work_dir);
+        let ctx = SessionContext::new();
+        let executor = test_executor(work_dir, &ctx);
 
         let (sender, receiver) = tokio::sync::oneshot::channel();
 
         // Spawn our non-terminating task on a separate fiber.
         let executor_clone = executor.clone();
         tokio::task::spawn(async move {
-            let part = PartitionId {
-                job_id: "job-id".to_owned(),
-                stage_id: 1,
-                partition_id: 0,
-            };
             let task_result = executor_clone
-                .execute_query_stage(1, part, Arc::new(query_stage_exec), ctx.task_ctx())
+                .execute_query_stage(
+                    1,
+                    first_partition(),
+                    query_stage_exec,
+                    ctx.task_ctx(),
+                )
                 .await;
             sender.send(task_result).expect("sending result");
         });
 
+        // A running task holds off draining until the timeout
+        for _ in 0..20 {
+            if executor.active_task_count() > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(!executor.drain_tasks(Some(Duration::from_millis(200))).await);
+
         // Now cancel the task. We can only cancel once the task has been executed and has an `AbortHandle` registered, so
         // poll until that happens.
         for _ in 0..20 {
@@ -411,8 +935,112 @@ mod test {
         // Make sure the task didn't timeout
         assert!(result.is_ok());
 
-        // Make sure the actual task failed
+        // Make sure the actual task was cancelled, without waiting to be aborted
         let inner_result = result.unwrap().unwrap();
-        assert!(inner_result.is_err());
+        assert!(matches!(inner_result, Err(BallistaError::TaskKilled(_))));
+
+        // and removed its partial output
+        assert!(!std::path::Path::new(work_dir).join("job-id/1/0").exists());
+        assert_eq!(executor.active_task_count(), 0);
+        assert!(executor.drain_tasks(None).await);
+    }
+
+    #[tokio::test]
+    async fn test_stage_timeout() {
+        let work_dir = TempDir::new().unwrap();
+        let work_dir = work_dir.path().to_str().unwrap();
+        let ctx = SessionContext::new_with_config(
+            SessionConfig::new_with_ballista().with_ballista_stage_timeout_seconds(1),
+        );
+        let executor = test_executor(work_dir, &ctx);
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            executor.execute_query_stage(
+                1,
+                first_partition(),
+                never_ending_stage(work_dir),
+                ctx.task_ctx(),
+            ),
+        )
+        .await
+        .expect("the task should time out");
+        let Err(e) = result else {
+            panic!("the task should fail");
+        };
+        assert!(matches!(e, BallistaError::TimedOut(_)));
+        assert!(e.is_retryable());
+        assert!(!std::path::Path::new(work_dir).join("job-id/1/0").exists());
+        assert_eq!(executor.active_task_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_task_before_start() {
+        let work_dir = TempDir::new().unwrap();
+        let work_dir = work_dir.path().to_str().unwrap();
+        let ctx = SessionContext::new();
+        let executor = test_executor(work_dir, &ctx);
+
+        // The task is cancelled while waiting for a task slot
+        assert!(!executor
+            .cancel_task(1, "job-id".to_owned(), 1, 0)
+            .await
+            .expect("cancelling task"));
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            executor.execute_query_stage(
+                1,
+                first_partition(),
+                never_ending_stage(work_dir),
+                ctx.task_ctx(),
+            ),
+        )
+        .await
+        .expect("cancelled task should not execute");
+        assert!(matches!(result, Err(BallistaError::TaskKilled(_))));
+        assert_eq!(executor.active_task_count(), 0);
+    }
+
+    #[test]
+    fn test_apply_settings() {
+        let filters = Arc::new(Mutex::new(vec![]));
+        let reloaded_filters = filters.clone();
+        let executor = test_executor("/tmp", &SessionContext::new())
+            .with_log_filter_reloader(Arc::new(move |filter| {
+                reloaded_filters.lock().unwrap().push(filter.to_owned());
+                Ok(())
+            }));
+        executor
+            .task_plan_cache
+            .insert("job-id", "plan", Bytes::from(vec![1]));
+
+        let settings = |version, entries: &[(&str, &str)]| protobuf::ExecutorSettings {
+            version,
+            settings: entries
+                .iter()
+                .map(|(key, value)| protobuf::KeyValuePair {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+        };
+        executor.apply_settings(&settings(
+            2,
+            &[
+                (EXECUTOR_LOG_LEVEL, "debug"),
+                (EXECUTOR_TASK_PLAN_CACHE_SIZE, "0"),
+                ("executor.unknown", "1"),
+            ],
+        ));
+        assert_eq!(2, executor.settings_version());
+        assert_eq!(vec!["debug"], *filters.lock().unwrap());
+        assert_eq!(None, executor.task_plan_cache.get("job-id", "plan"));
+
+        // Versions which were already applied are skipped
+        executor.apply_settings(&settings(1, &[(EXECUTOR_LOG_LEVEL, "info")]));
+        executor.apply_settings(&settings(2, &[(EXECUTOR_LOG_LEVEL, "info")]));
+        assert_eq!(2, executor.settings_version());
+        assert_eq!(vec!["debug"], *filters.lock().unwrap());
     }
 }
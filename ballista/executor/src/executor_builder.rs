@@ -0,0 +1,315 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Typed API to embed an executor in an existing service, configuring it in code
+//! rather than with the command line flags of the `ballista-executor` binary, and
+//! controlling its lifecycle with an [ExecutorHandle].
+//!
+//! # Stability
+//!
+//! The methods of [ExecutorBuilder] and [ExecutorHandle] are stable: they are only
+//! deprecated, never changed or removed, within a major version. The settings without
+//! a method of their own are reached with [ExecutorBuilder::with_config], which exposes
+//! the fields of [ExecutorProcessConfig]. Those follow the `ballista-executor` flags,
+//! and may be added, renamed or removed in any release.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use ballista_core::config::TaskSchedulingPolicy;
+use ballista_core::registry::BallistaFunctionRegistry;
+use ballista_core::security::GrpcSecurityConfig;
+use ballista_core::{ConfigProducer, RuntimeProducer};
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion_proto::logical_plan::LogicalExtensionCodec;
+use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::execution_engine::ExecutionEngine;
+use crate::executor_process::{run_executor_process, ExecutorProcessConfig};
+
+/// Builds and starts an executor embedded in an application.
+///
+/// ```no_run
+/// # async fn run() -> anyhow::Result<()> {
+/// use ballista_executor::ExecutorBuilder;
+///
+/// let executor = ExecutorBuilder::new()
+///     .with_scheduler("scheduler", 50050)
+///     .with_port(50051)
+///     .with_work_dir("/var/lib/ballista")
+///     .start()
+///     .await?;
+/// // ...
+/// executor.drain().await
+/// # }
+/// ```
+///
+/// Unlike the `ballista-executor` binary, an embedded executor doesn't stop on ctrl-c
+/// or terminate signals, which are left to the application.
+///
+/// See the [module documentation](self) for the stability of the API.
+#[derive(Default)]
+pub struct ExecutorBuilder {
+    config: ExecutorProcessConfig,
+}
+
+impl ExecutorBuilder {
+    /// An executor with the default settings of the `ballista-executor` binary
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the settings given so far with `config`, e.g. parsed from the flags
+    /// of the `ballista-executor` binary, before refining them with the other methods
+    /// of the builder.
+    ///
+    /// # Stability
+    ///
+    /// The fields of [ExecutorProcessConfig] may change in any release.
+    pub fn with_config(mut self, config: ExecutorProcessConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// The settings of the executor.
+    ///
+    /// # Stability
+    ///
+    /// The fields of [ExecutorProcessConfig] may change in any release.
+    pub fn config(&self) -> &ExecutorProcessConfig {
+        &self.config
+    }
+
+    /// The host and port of the scheduler the executor registers with
+    pub fn with_scheduler(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.config.scheduler_host = host.into();
+        self.config.scheduler_port = port;
+        self
+    }
+
+    /// The address the executor listens on, all the interfaces by default
+    pub fn with_bind_host(mut self, bind_host: impl Into<String>) -> Self {
+        self.config.bind_host = bind_host.into();
+        self
+    }
+
+    /// The host name the scheduler and the other executors reach the executor at,
+    /// which the scheduler otherwise sees the executor connecting from
+    pub fn with_external_host(mut self, external_host: impl Into<String>) -> Self {
+        self.config.external_host = Some(external_host.into());
+        self
+    }
+
+    /// The port of the Flight service serving the shuffle partitions of the executor
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// The port of the gRPC service the scheduler pushes the tasks to, with the
+    /// push-based task scheduling
+    pub fn with_grpc_port(mut self, grpc_port: u16) -> Self {
+        self.config.grpc_port = grpc_port;
+        self
+    }
+
+    /// The directory of the shuffle partitions and the spill files of the tasks, a
+    /// temporary directory by default
+    pub fn with_work_dir(mut self, work_dir: impl Into<String>) -> Self {
+        self.config.work_dir = Some(work_dir.into());
+        self
+    }
+
+    /// The number of tasks the executor runs at the same time, 0 meaning as many as
+    /// the cores of the host
+    pub fn with_concurrent_tasks(mut self, concurrent_tasks: usize) -> Self {
+        self.config.concurrent_tasks = concurrent_tasks;
+        self
+    }
+
+    /// Whether the scheduler pushes the tasks to the executor, or the executor polls
+    /// the scheduler for tasks. It must match the policy of the scheduler
+    pub fn with_task_scheduling_policy(mut self, policy: TaskSchedulingPolicy) -> Self {
+        self.config.task_scheduling_policy = policy;
+        self
+    }
+
+    /// Run the tasks with the given [RuntimeEnv], e.g. with the object stores and the
+    /// memory pool of the application
+    pub fn with_runtime_env(self, runtime: Arc<RuntimeEnv>) -> Self {
+        self.with_runtime_producer(Arc::new(move |_| Ok(runtime.clone())))
+    }
+
+    /// Produce the [RuntimeEnv] of the tasks with `runtime_producer`, from their
+    /// session config
+    pub fn with_runtime_producer(mut self, runtime_producer: RuntimeProducer) -> Self {
+        self.config.override_runtime_producer = Some(runtime_producer);
+        self
+    }
+
+    /// Produce the session config of the tasks with `config_producer`
+    pub fn with_config_producer(mut self, config_producer: ConfigProducer) -> Self {
+        self.config.override_config_producer = Some(config_producer);
+        self
+    }
+
+    /// The functions the tasks may call, with the user defined functions of the
+    /// application registered
+    pub fn with_function_registry(
+        mut self,
+        function_registry: Arc<BallistaFunctionRegistry>,
+    ) -> Self {
+        self.config.override_function_registry = Some(function_registry);
+        self
+    }
+
+    /// The codec of the extensions of the logical plans, which the scheduler must share
+    pub fn with_logical_codec(mut self, codec: Arc<dyn LogicalExtensionCodec>) -> Self {
+        self.config.override_logical_codec = Some(codec);
+        self
+    }
+
+    /// The codec of the extensions of the physical plans, which the scheduler must
+    /// share
+    pub fn with_physical_codec(mut self, codec: Arc<dyn PhysicalExtensionCodec>) -> Self {
+        self.config.override_physical_codec = Some(codec);
+        self
+    }
+
+    /// Execute the stages of the jobs with `execution_engine` rather than DataFusion
+    pub fn with_execution_engine(
+        mut self,
+        execution_engine: Arc<dyn ExecutionEngine>,
+    ) -> Self {
+        self.config.override_execution_engine = Some(execution_engine);
+        self
+    }
+
+    /// The pool of executors the executor joins, which only runs the jobs of the
+    /// tenants the scheduler assigns to the pool
+    pub fn with_pool(mut self, pool: impl Into<String>) -> Self {
+        self.config.executor_pool = Some(pool.into());
+        self
+    }
+
+    /// Secure the connections to the executor, the scheduler and the other executors
+    /// with the TLS and auth token of `grpc_security`
+    pub fn with_grpc_security(mut self, grpc_security: GrpcSecurityConfig) -> Self {
+        self.config.grpc_security = grpc_security;
+        self
+    }
+
+    /// Start the executor, returning once it registered with the scheduler and serves
+    /// its shuffle partitions, or with the error that prevented it from starting
+    pub async fn start(self) -> Result<ExecutorHandle> {
+        let (stop_send, stop_recv) = mpsc::channel::<bool>(10);
+        let (ready_send, ready_recv) = oneshot::channel();
+        let process = tokio::spawn(run_executor_process(
+            Arc::new(self.config),
+            stop_send.clone(),
+            stop_recv,
+            false,
+            Some(ready_send),
+        ));
+
+        match ready_recv.await {
+            Ok(()) => Ok(ExecutorHandle {
+                stop: stop_send,
+                process,
+            }),
+            // the executor failed to start
+            Err(_) => Err(process
+                .await?
+                .err()
+                .unwrap_or_else(|| anyhow!("Executor stopped while starting"))),
+        }
+    }
+}
+
+/// Controls the lifecycle of an executor started with [ExecutorBuilder::start]
+#[derive(Debug)]
+pub struct ExecutorHandle {
+    stop: mpsc::Sender<bool>,
+    process: JoinHandle<Result<()>>,
+}
+
+impl ExecutorHandle {
+    /// Decommission the executor: stop accepting tasks, wait for the running tasks to
+    /// complete, up to the drain timeout, then deregister from the scheduler and stop
+    pub async fn drain(self) -> Result<()> {
+        self.request_stop(false).await
+    }
+
+    /// Stop the executor right away, without waiting for the running tasks, which the
+    /// scheduler reschedules once it notices the executor is gone
+    pub async fn stop(self) -> Result<()> {
+        self.request_stop(true).await
+    }
+
+    /// Wait for the executor to stop, e.g. once decommissioned by the scheduler
+    pub async fn wait(self) -> Result<()> {
+        self.process.await?
+    }
+
+    async fn request_stop(self, force: bool) -> Result<()> {
+        // the executor may have stopped already, in which case it reports why below
+        let _ = self.stop.send(force).await;
+        self.wait().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ballista_core::config::TaskSchedulingPolicy;
+
+    use crate::ExecutorBuilder;
+
+    #[test]
+    fn test_executor_builder() {
+        let builder = ExecutorBuilder::new()
+            .with_scheduler("scheduler", 50060)
+            .with_bind_host("127.0.0.1")
+            .with_port(50061)
+            .with_work_dir("/tmp/ballista")
+            .with_concurrent_tasks(4)
+            .with_pool("analytics")
+            .with_task_scheduling_policy(TaskSchedulingPolicy::PushStaged);
+
+        let config = builder.config();
+        assert_eq!(
+            ("scheduler", 50060),
+            (config.scheduler_host.as_str(), config.scheduler_port)
+        );
+        assert_eq!(
+            ("127.0.0.1", 50061),
+            (config.bind_host.as_str(), config.port)
+        );
+        assert_eq!(Some("/tmp/ballista"), config.work_dir.as_deref());
+        assert_eq!(4, config.concurrent_tasks);
+        assert_eq!(Some("analytics"), config.executor_pool.as_deref());
+        assert!(matches!(
+            config.task_scheduling_policy,
+            TaskSchedulingPolicy::PushStaged
+        ));
+        // the defaults of the ballista-executor binary
+        assert_eq!(50052, config.grpc_port);
+        assert!(config.task_spill_dirs);
+        assert!(config.override_runtime_producer.is_none());
+    }
+}
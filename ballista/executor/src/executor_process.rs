@@ -18,6 +18,7 @@
 //! Ballista Executor Process
 
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::{Duration, Instant, UNIX_EPOCH};
@@ -25,6 +26,7 @@ use std::time::{Duration, Instant, UNIX_EPOCH};
 use anyhow::{Context, Result};
 use arrow_flight::flight_service_server::FlightServiceServer;
 use ballista_core::registry::BallistaFunctionRegistry;
+use ballista_core::security::GrpcSecurityConfig;
 use datafusion_proto::logical_plan::LogicalExtensionCodec;
 use datafusion_proto::physical_plan::PhysicalExtensionCodec;
 use futures::stream::FuturesUnordered;
@@ -33,21 +35,28 @@ use log::{error, info, warn};
 use tempfile::TempDir;
 use tokio::fs::DirEntry;
 use tokio::signal;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
 use tokio::{fs, time};
+use tonic::service::interceptor::InterceptedService;
 use uuid::Uuid;
 
+use datafusion::execution::memory_pool::{FairSpillPool, MemoryPool};
 use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
 
+use ballista_core::compression::CompressionCodecRegistry;
 use ballista_core::config::{LogRotationPolicy, TaskSchedulingPolicy};
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::{shuffle_io_uring_enabled, ShuffleFetchLimiter};
+use ballista_core::extension::SessionConfigExt;
 use ballista_core::serde::protobuf::executor_resource::Resource;
 use ballista_core::serde::protobuf::executor_status::Status;
 use ballista_core::serde::protobuf::{
-    scheduler_grpc_client::SchedulerGrpcClient, ExecutorRegistration, ExecutorResource,
-    ExecutorSpecification, ExecutorStatus, ExecutorStoppedParams, HeartBeatParams,
+    scheduler_grpc_client::SchedulerGrpcClient, ExecutorFunctions, ExecutorRegistration,
+    ExecutorResource, ExecutorSpecification, ExecutorStatus, ExecutorStoppedParams,
+    HeartBeatParams,
 };
+use ballista_core::serde::scheduler::ShuffleFormats;
 use ballista_core::serde::{
     BallistaCodec, BallistaLogicalExtensionCodec, BallistaPhysicalExtensionCodec,
 };
@@ -55,15 +64,20 @@ use ballista_core::utils::{
     create_grpc_client_connection, create_grpc_server, default_config_producer,
     get_time_before,
 };
-use ballista_core::{ConfigProducer, RuntimeProducer, BALLISTA_VERSION};
+use ballista_core::{build_version, ConfigProducer, RuntimeProducer, BALLISTA_VERSION};
 
+use crate::data_cache::DataCache;
 use crate::execution_engine::ExecutionEngine;
-use crate::executor::{Executor, TasksDrainedFuture};
+use crate::executor::{Executor, LogFilterReloader};
 use crate::executor_server::TERMINATING;
-use crate::flight_service::BallistaFlightService;
-use crate::metrics::LoggingMetricsCollector;
+use crate::flight_service::{BallistaFlightService, FetchTokens, FlightStreams};
+use crate::metadata_cache::MetadataCache;
+use crate::metrics::default_metrics_collector;
+use crate::object_store_retry::{ObjectStoreRetry, ObjectStoreRetryConfig};
 use crate::shutdown::Shutdown;
 use crate::shutdown::ShutdownNotifier;
+use crate::spill_dir::SpillEncryption;
+use crate::task_logs::TaskLogStore;
 use crate::terminate;
 use crate::{execution_loop, executor_server};
 
@@ -72,10 +86,19 @@ pub struct ExecutorProcessConfig {
     pub external_host: Option<String>,
     pub port: u16,
     pub grpc_port: u16,
+    /// Port of the HTTP server exporting the metrics of the executor on `/metrics`.
+    /// 0 disables it
+    pub metrics_port: u16,
     pub scheduler_host: String,
     pub scheduler_port: u16,
     pub scheduler_connect_timeout_seconds: u16,
     pub concurrent_tasks: usize,
+    /// Size in bytes of the memory pool shared by the tasks of the executor, 0 means
+    /// unbounded
+    pub memory_pool_size: u64,
+    /// The memory in bytes a task needs from the memory pool, bounding the task slots
+    /// of the executor, 0 means no limit
+    pub task_memory_limit: u64,
     pub task_scheduling_policy: TaskSchedulingPolicy,
     pub log_dir: Option<String>,
     pub work_dir: Option<String>,
@@ -83,13 +106,58 @@ pub struct ExecutorProcessConfig {
     pub print_thread_info: bool,
     pub log_file_name_prefix: String,
     pub log_rotation_policy: LogRotationPolicy,
+    /// Maximum number of log lines captured per task. 0 disables task log capture.
+    pub task_log_max_lines: usize,
+    /// Store of captured task logs served through the `GetTaskLogs` rpc. It is only
+    /// populated if a [crate::task_logs::TaskLogLayer] writing to it is installed in the tracing subscriber.
+    pub task_log_store: Option<Arc<TaskLogStore>>,
+    /// Replaces the log filter of the tracing subscriber, so that the scheduler can
+    /// change the log level of the executor at runtime
+    pub log_filter_reloader: Option<LogFilterReloader>,
     pub job_data_ttl_seconds: u64,
     pub job_data_clean_up_interval_seconds: u64,
+    /// Maximum bytes of job data in the work dir, enforced by the janitor cleaning
+    /// up job data. 0 means no limit
+    pub job_data_max_disk_bytes: u64,
+    /// Whether each task spills to a private dir of its own in the work dir, removed
+    /// once the task ends
+    pub task_spill_dirs: bool,
+    /// Whether the spill dirs of the tasks are encrypted with a key per job
+    pub task_spill_encryption: bool,
+    /// Maximum bytes of the ranges of the scanned files cached in memory, 0 disables
+    /// the memory tier of the data cache
+    pub data_cache_memory_bytes: u64,
+    /// Maximum bytes of the ranges of the scanned files cached on disk, 0 disables the
+    /// disk tier of the data cache
+    pub data_cache_disk_bytes: u64,
+    /// Directory of the disk tier of the data cache, a temporary directory if None
+    pub data_cache_dir: Option<String>,
+    /// Time in seconds the versions of the cached files are trusted before being
+    /// checked again with the object store
+    pub data_cache_metadata_ttl_seconds: u64,
+    /// Maximum bytes of the metadata of the scanned Parquet files cached in memory, 0
+    /// disables the metadata cache
+    pub metadata_cache_memory_bytes: u64,
+    /// Maximum number of shuffle partitions the tasks fetch at the same time from the
+    /// other executors and from object stores, 0 leaves them unlimited
+    pub max_concurrent_shuffle_fetches: usize,
+    /// Retries of the transient errors of the object store reads of the tasks, disabled
+    /// with 0 retries
+    pub object_store_retry: ObjectStoreRetryConfig,
     /// The maximum size of a decoded message
     pub grpc_max_decoding_message_size: u32,
     /// The maximum size of an encoded message
     pub grpc_max_encoding_message_size: u32,
     pub executor_heartbeat_interval_seconds: u64,
+    /// Time in seconds a decommissioned executor waits for its running tasks to
+    /// complete before it deregisters from the scheduler, 0 means no deadline
+    pub drain_timeout_seconds: u64,
+    /// TLS and auth token settings of the gRPC servers and clients of the executor
+    pub grpc_security: GrpcSecurityConfig,
+    /// Token presented to the scheduler when registering, if it requires one
+    pub registration_token: Option<String>,
+    /// Pool of executors the executor joins, the shared pool if none
+    pub executor_pool: Option<String>,
     /// Optional execution engine to use to execute physical plans, will default to
     /// DataFusion if none is provided.
     pub override_execution_engine: Option<Arc<dyn ExecutionEngine>>,
@@ -105,7 +173,112 @@ pub struct ExecutorProcessConfig {
     pub override_physical_codec: Option<Arc<dyn PhysicalExtensionCodec>>,
 }
 
+impl Default for ExecutorProcessConfig {
+    /// The default settings of the `ballista-executor` binary
+    fn default() -> Self {
+        Self {
+            bind_host: "0.0.0.0".to_owned(),
+            external_host: None,
+            port: 50051,
+            grpc_port: 50052,
+            metrics_port: 0,
+            scheduler_host: "localhost".to_owned(),
+            scheduler_port: 50050,
+            scheduler_connect_timeout_seconds: 0,
+            concurrent_tasks: 0,
+            memory_pool_size: 0,
+            task_memory_limit: 0,
+            task_scheduling_policy: TaskSchedulingPolicy::PullStaged,
+            log_dir: None,
+            work_dir: None,
+            special_mod_log_level: "INFO,datafusion=INFO".to_owned(),
+            print_thread_info: true,
+            log_file_name_prefix: "executor_localhost_50051".to_owned(),
+            log_rotation_policy: LogRotationPolicy::Daily,
+            task_log_max_lines: 1000,
+            task_log_store: None,
+            log_filter_reloader: None,
+            job_data_ttl_seconds: 604800,
+            job_data_clean_up_interval_seconds: 0,
+            job_data_max_disk_bytes: 0,
+            task_spill_dirs: true,
+            task_spill_encryption: false,
+            data_cache_memory_bytes: 0,
+            data_cache_disk_bytes: 0,
+            data_cache_dir: None,
+            data_cache_metadata_ttl_seconds: 60,
+            metadata_cache_memory_bytes: 0,
+            max_concurrent_shuffle_fetches: 0,
+            object_store_retry: ObjectStoreRetryConfig::default(),
+            grpc_max_decoding_message_size: 16777216,
+            grpc_max_encoding_message_size: 16777216,
+            executor_heartbeat_interval_seconds: 60,
+            drain_timeout_seconds: 300,
+            grpc_security: GrpcSecurityConfig::default(),
+            registration_token: None,
+            executor_pool: None,
+            override_execution_engine: None,
+            override_function_registry: None,
+            override_runtime_producer: None,
+            override_config_producer: None,
+            override_logical_codec: None,
+            override_physical_codec: None,
+        }
+    }
+}
+
+#[cfg(feature = "prometheus")]
+fn start_metrics_server(
+    opt: &ExecutorProcessConfig,
+    metrics_collector: Arc<dyn crate::metrics::ExecutorMetricsCollector>,
+    shuffle_fetch_limiter: Option<Arc<ShuffleFetchLimiter>>,
+) -> Result<()> {
+    let addr = format!("{}:{}", opt.bind_host, opt.metrics_port);
+    let addr = addr
+        .parse()
+        .with_context(|| format!("Could not parse address: {addr}"))?;
+    tokio::spawn(async move {
+        if let Err(e) =
+            crate::metrics::serve_metrics(addr, metrics_collector, shuffle_fetch_limiter)
+                .await
+        {
+            error!("Error serving executor metrics: {:?}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "prometheus"))]
+fn start_metrics_server(
+    _opt: &ExecutorProcessConfig,
+    _metrics_collector: Arc<dyn crate::metrics::ExecutorMetricsCollector>,
+    _shuffle_fetch_limiter: Option<Arc<ShuffleFetchLimiter>>,
+) -> Result<()> {
+    warn!(
+        "metrics_port is set but the executor is built without the prometheus-metrics feature"
+    );
+    Ok(())
+}
+
 pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<()> {
+    let (stop_send, stop_recv) = mpsc::channel::<bool>(10);
+    run_executor_process(opt, stop_send, stop_recv, true, None).await
+}
+
+/// Run the executor until it's stopped, by a stop request received from `stop_recv`,
+/// which `stop_send` sends the stop requests of the executor gRPC service to, or, if
+/// `handle_signals`, by a ctrl-c or terminate signal. `ready` is notified once the
+/// executor registered with the scheduler and serves its Flight service
+pub(crate) async fn run_executor_process(
+    opt: Arc<ExecutorProcessConfig>,
+    stop_send: mpsc::Sender<bool>,
+    mut stop_recv: mpsc::Receiver<bool>,
+    handle_signals: bool,
+    ready: Option<oneshot::Sender<()>>,
+) -> Result<()> {
+    // an embedded executor may be started again once stopped
+    TERMINATING.store(false, Ordering::Release);
+
     let addr = format!("{}:{}", opt.bind_host, opt.port);
     let addr = addr
         .parse()
@@ -129,13 +302,38 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     } else {
         opt.concurrent_tasks
     };
+    let concurrent_tasks = memory_bound_concurrent_tasks(
+        concurrent_tasks,
+        opt.memory_pool_size,
+        opt.task_memory_limit,
+    );
 
     info!("Running with config:");
     info!("work_dir: {}", work_dir);
     info!("concurrent_tasks: {}", concurrent_tasks);
+    info!("memory_pool_size: {}", opt.memory_pool_size);
+    info!("job_data_max_disk_bytes: {}", opt.job_data_max_disk_bytes);
+    info!("task_spill_dirs: {}", opt.task_spill_dirs);
+    info!("task_spill_encryption: {}", opt.task_spill_encryption);
+    info!("data_cache_memory_bytes: {}", opt.data_cache_memory_bytes);
+    info!("data_cache_disk_bytes: {}", opt.data_cache_disk_bytes);
+    info!(
+        "metadata_cache_memory_bytes: {}",
+        opt.metadata_cache_memory_bytes
+    );
+    info!(
+        "max_concurrent_shuffle_fetches: {}",
+        opt.max_concurrent_shuffle_fetches
+    );
+    info!("object_store_retry: {:?}", opt.object_store_retry);
+    info!("shuffle_io_uring: {}", shuffle_io_uring_enabled());
 
     // assign this executor an unique ID
     let executor_id = Uuid::new_v4().to_string();
+    let function_registry = opt.override_function_registry.clone().unwrap_or_default();
+    let functions = Some(ExecutorFunctions {
+        names: function_registry.user_defined_function_names(),
+    });
     let executor_meta = ExecutorRegistration {
         id: executor_id.clone(),
         host: opt.external_host.clone(),
@@ -146,20 +344,31 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                 resource: Some(Resource::TaskSlots(concurrent_tasks as u32)),
             }],
         }),
+        version: build_version(),
+        functions: functions.clone(),
+        shuffle_formats: Some(ShuffleFormats::supported().into()),
+        registration_token: opt.registration_token.clone().unwrap_or_default(),
+        pool: opt.executor_pool.clone().unwrap_or_default(),
     };
 
     // put them to session config
-    let metrics_collector = Arc::new(LoggingMetricsCollector::default());
+    let metrics_collector = default_metrics_collector()?;
     let config_producer = opt
         .override_config_producer
         .clone()
         .unwrap_or_else(|| Arc::new(default_config_producer));
 
     let wd = work_dir.clone();
+    // the tasks share the memory pool, each of them getting a fair share of it
+    let memory_pool: Option<Arc<dyn MemoryPool>> = (opt.memory_pool_size > 0)
+        .then(|| Arc::new(FairSpillPool::new(opt.memory_pool_size as usize)) as _);
     let runtime_producer: RuntimeProducer =
         opt.override_runtime_producer.clone().unwrap_or_else(|| {
             Arc::new(move |_| {
-                let config = RuntimeConfig::new().with_temp_file_path(wd.clone());
+                let mut config = RuntimeConfig::new().with_temp_file_path(wd.clone());
+                if let Some(memory_pool) = &memory_pool {
+                    config = config.with_memory_pool(memory_pool.clone());
+                }
                 Ok(Arc::new(RuntimeEnv::new(config)?))
             })
         });
@@ -179,20 +388,81 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
         datafusion_proto::protobuf::PhysicalPlanNode,
     > = BallistaCodec::new(logical, physical);
 
-    let executor = Arc::new(Executor::new(
+    let mut executor = Executor::new(
         executor_meta,
         &work_dir,
         runtime_producer,
         config_producer,
-        opt.override_function_registry.clone().unwrap_or_default(),
+        function_registry,
         metrics_collector,
         concurrent_tasks,
         opt.override_execution_engine.clone(),
-    ));
+    );
+    if let Some(task_log_store) = &opt.task_log_store {
+        executor = executor.with_task_log_store(task_log_store.clone());
+    }
+    if let Some(log_filter_reloader) = &opt.log_filter_reloader {
+        executor = executor.with_log_filter_reloader(log_filter_reloader.clone());
+    }
+    if opt.data_cache_memory_bytes > 0 || opt.data_cache_disk_bytes > 0 {
+        let data_cache_dir = match &opt.data_cache_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => TempDir::new()?.into_path(),
+        };
+        info!("data_cache_dir: {:?}", data_cache_dir);
+        let data_cache = DataCache::try_new(
+            opt.data_cache_memory_bytes,
+            opt.data_cache_disk_bytes,
+            data_cache_dir,
+            Duration::from_secs(opt.data_cache_metadata_ttl_seconds),
+        )
+        .context("Could not create the data cache")?;
+        executor = executor.with_data_cache(Arc::new(data_cache));
+    }
+    if opt.metadata_cache_memory_bytes > 0 {
+        executor = executor.with_metadata_cache(Arc::new(MetadataCache::new(
+            opt.metadata_cache_memory_bytes,
+        )));
+    }
+    if opt.task_spill_encryption {
+        if !opt.task_spill_dirs {
+            return Err(BallistaError::General(
+                "task_spill_encryption requires task_spill_dirs".to_owned(),
+            )
+            .into());
+        }
+        let encryption = SpillEncryption::try_new(&work_dir)
+            .context("Could not enable the encryption of the spill dirs")?;
+        executor = executor.with_spill_encryption(Arc::new(encryption));
+    }
+    if opt.max_concurrent_shuffle_fetches > 0 {
+        executor = executor.with_shuffle_fetch_limiter(Arc::new(
+            ShuffleFetchLimiter::new(opt.max_concurrent_shuffle_fetches),
+        ));
+    }
+    if opt.object_store_retry.max_retries > 0 {
+        executor = executor.with_object_store_retry(Arc::new(ObjectStoreRetry::new(
+            opt.object_store_retry.clone(),
+        )));
+    }
+    let executor = Arc::new(
+        executor
+            .with_grpc_security(opt.grpc_security.clone())
+            .with_max_disk_bytes(opt.job_data_max_disk_bytes)
+            .with_task_spill_dirs(opt.task_spill_dirs),
+    );
+
+    if opt.metrics_port > 0 {
+        start_metrics_server(
+            &opt,
+            executor.metrics_collector.clone(),
+            executor.shuffle_fetch_limiter.clone(),
+        )?;
+    }
 
     let connect_timeout = opt.scheduler_connect_timeout_seconds as u64;
     let connection = if connect_timeout == 0 {
-        create_grpc_client_connection(scheduler_url)
+        create_grpc_client_connection(scheduler_url, &opt.grpc_security)
             .await
             .context("Could not connect to scheduler")
     } else {
@@ -204,7 +474,7 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
         while x.is_none()
             && Instant::now().elapsed().as_secs() - start_time < connect_timeout
         {
-            match create_grpc_client_connection(scheduler_url.clone())
+            match create_grpc_client_connection(scheduler_url.clone(), &opt.grpc_security)
                 .await
                 .context("Could not connect to scheduler")
             {
@@ -245,6 +515,7 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
             time::interval(Duration::from_secs(opt.job_data_clean_up_interval_seconds));
         let mut shuffle_cleaner_shutdown = shutdown_noti.subscribe_for_shutdown();
         let shuffle_cleaner_complete = shutdown_noti.shutdown_complete_tx.clone();
+        let executor = executor.clone();
         tokio::spawn(async move {
             // As long as the shutdown notification has not been received
             while !shuffle_cleaner_shutdown.is_shutdown() {
@@ -254,6 +525,10 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                         {
                             error!("Ballista executor fail to clean_shuffle_data {:?}", e)
                         }
+                        if let Err(e) = executor.disk_usage.evict(&work_dir, &executor.active_jobs()).await
+                        {
+                            error!("Ballista executor fail to bound the job data disk usage {:?}", e)
+                        }
                         },
                     _ = shuffle_cleaner_shutdown.recv() => {
                         if let Err(e) = clean_all_shuffle_data(&work_dir).await
@@ -273,9 +548,6 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     let mut service_handlers: FuturesUnordered<JoinHandle<Result<(), BallistaError>>> =
         FuturesUnordered::new();
 
-    // Channels used to receive stop requests from Executor grpc service.
-    let (stop_send, mut stop_recv) = mpsc::channel::<bool>(10);
-
     match scheduler_policy {
         TaskSchedulingPolicy::PushStaged => {
             service_handlers.push(
@@ -299,12 +571,22 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
             )));
         }
     };
+    let flight_server_config = FlightServerConfig {
+        fetch_tokens: executor.fetch_tokens.clone(),
+        compression_codecs: executor.produce_config().ballista_compression_codecs(),
+        security: opt.grpc_security.clone(),
+        max_decoding_message_size: opt.grpc_max_decoding_message_size as usize,
+        max_encoding_message_size: opt.grpc_max_encoding_message_size as usize,
+    };
     service_handlers.push(tokio::spawn(flight_server_run(
         addr,
+        executor.flight_streams.clone(),
+        flight_server_config,
         shutdown_noti.subscribe_for_shutdown(),
     )));
-
-    let tasks_drained = TasksDrainedFuture(executor);
+    if let Some(ready) = ready {
+        let _ = ready.send(());
+    }
 
     // Concurrently run the service checking and listen for the `shutdown` signal and wait for the stop request coming.
     // The check_services runs until an error is encountered, so under normal circumstances, this `select!` statement runs
@@ -315,18 +597,25 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
             info!("{:?}", msg);
             (true, msg)
         },
-        _ = signal::ctrl_c() => {
+        _ = signal::ctrl_c(), if handle_signals => {
             let msg = "executor received ctrl-c event.".to_string();
              info!("{:?}", msg);
             (true, msg)
         },
-        _ = terminate::sig_term() => {
+        _ = terminate::sig_term(), if handle_signals => {
             let msg = "executor received terminate signal.".to_string();
              info!("{:?}", msg);
             (true, msg)
         },
-        _ = stop_recv.recv() => {
-            (false, "".to_string())
+        force = stop_recv.recv() => {
+            // a forced stop exits right away, without draining the running tasks
+            if force.unwrap_or(true) {
+                (false, "".to_string())
+            } else {
+                let msg = "executor received decommission request.".to_string();
+                info!("{:?}", msg);
+                (true, msg)
+            }
         },
     };
 
@@ -354,6 +643,14 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                             resource: Some(Resource::TaskSlots(concurrent_tasks as u32)),
                         }],
                     }),
+                    version: build_version(),
+                    functions,
+                    shuffle_formats: Some(ShuffleFormats::supported().into()),
+                    registration_token: opt
+                        .registration_token
+                        .clone()
+                        .unwrap_or_default(),
+                    pool: opt.executor_pool.clone().unwrap_or_default(),
                 }),
             })
             .await
@@ -361,19 +658,29 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
             error!("error sending heartbeat with fenced status: {:?}", error);
         }
 
+        // Wait for tasks to drain before deregistering, so that the scheduler doesn't
+        // reschedule them as lost
+        let drain_timeout = (opt.drain_timeout_seconds > 0)
+            .then(|| Duration::from_secs(opt.drain_timeout_seconds));
+        if !executor.drain_tasks(drain_timeout).await {
+            warn!(
+                "{} tasks still running after the drain timeout of {}s",
+                executor.active_task_count(),
+                opt.drain_timeout_seconds
+            );
+        }
+
         // TODO we probably don't need a separate rpc call for this....
         if let Err(error) = scheduler
             .executor_stopped(ExecutorStoppedParams {
                 executor_id,
                 reason: stop_reason,
+                registration_token: opt.registration_token.clone().unwrap_or_default(),
             })
             .await
         {
             error!("ExecutorStopped grpc failed: {:?}", error);
         }
-
-        // Wait for tasks to drain
-        tasks_drained.await;
     }
 
     // Extract the `shutdown_complete` receiver and transmitter
@@ -398,20 +705,48 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     Ok(())
 }
 
+/// Settings of the Arrow Flight server of an executor
+struct FlightServerConfig {
+    /// Tokens of the jobs whose partitions are served
+    fetch_tokens: Arc<FetchTokens>,
+    /// Codecs the partitions are compressed with for their transfer
+    compression_codecs: Arc<CompressionCodecRegistry>,
+    security: GrpcSecurityConfig,
+    max_decoding_message_size: usize,
+    max_encoding_message_size: usize,
+}
+
 // Arrow flight service
 async fn flight_server_run(
     addr: SocketAddr,
+    flight_streams: Arc<FlightStreams>,
+    config: FlightServerConfig,
     mut grpc_shutdown: Shutdown,
 ) -> Result<(), BallistaError> {
-    let service = BallistaFlightService::new();
-    let server = FlightServiceServer::new(service);
+    let FlightServerConfig {
+        fetch_tokens,
+        compression_codecs,
+        security,
+        max_decoding_message_size,
+        max_encoding_message_size,
+    } = config;
+    let service = BallistaFlightService::new()
+        .with_streams(flight_streams)
+        .with_fetch_tokens(fetch_tokens)
+        .with_compression_codecs(compression_codecs);
+    let server = InterceptedService::new(
+        FlightServiceServer::new(service)
+            .max_decoding_message_size(max_decoding_message_size)
+            .max_encoding_message_size(max_encoding_message_size),
+        security.auth_token_validator(),
+    );
     info!(
         "Ballista v{} Rust Executor Flight Server listening on {:?}",
         BALLISTA_VERSION, addr
     );
 
     let shutdown_signal = grpc_shutdown.recv();
-    let server_future = create_grpc_server()
+    let server_future = create_grpc_server(&security)?
         .add_service(server)
         .serve_with_shutdown(addr, shutdown_signal);
 
@@ -547,9 +882,29 @@ pub async fn satisfy_dir_ttl(dir: DirEntry, ttl_seconds: u64) -> Result<bool> {
     Ok(false)
 }
 
+/// The task slots of an executor, bounded by the number of tasks which fit in its
+/// memory pool, so that the scheduler doesn't bind more tasks to the executor than its
+/// memory allows. An executor always offers at least one task slot
+fn memory_bound_concurrent_tasks(
+    concurrent_tasks: usize,
+    memory_pool_size: u64,
+    task_memory_limit: u64,
+) -> usize {
+    if memory_pool_size == 0 || task_memory_limit == 0 {
+        return concurrent_tasks;
+    }
+    let tasks = (memory_pool_size / task_memory_limit).max(1) as usize;
+    if tasks < concurrent_tasks {
+        warn!(
+            "Offering {tasks} task slots rather than {concurrent_tasks}, as tasks need {task_memory_limit} bytes of the memory pool of {memory_pool_size} bytes"
+        );
+    }
+    tasks.min(concurrent_tasks)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::clean_shuffle_data_loop;
+    use super::{clean_shuffle_data_loop, memory_bound_concurrent_tasks};
     use std::fs;
     use std::fs::File;
     use std::io::Write;
@@ -584,4 +939,16 @@ mod tests {
         let count2 = fs::read_dir(work_dir.clone()).unwrap().count();
         assert_eq!(count2, 0);
     }
+
+    #[test]
+    fn test_memory_bound_concurrent_tasks() {
+        // No memory pool or no limit per task
+        assert_eq!(memory_bound_concurrent_tasks(8, 0, 1024), 8);
+        assert_eq!(memory_bound_concurrent_tasks(8, 4096, 0), 8);
+        // 4 tasks fit in the pool
+        assert_eq!(memory_bound_concurrent_tasks(8, 4096, 1024), 4);
+        assert_eq!(memory_bound_concurrent_tasks(2, 4096, 1024), 2);
+        // An executor always runs a task, even if it needs more than the pool
+        assert_eq!(memory_bound_concurrent_tasks(8, 1024, 4096), 1);
+    }
 }
@@ -25,18 +25,22 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
 
 use log::{debug, error, info, warn};
-use tonic::transport::Channel;
+use tonic::service::interceptor::InterceptedService;
 use tonic::{Request, Response, Status};
 
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::shuffle_index_path;
 use ballista_core::serde::protobuf::{
     executor_grpc_server::{ExecutorGrpc, ExecutorGrpcServer},
     executor_metric, executor_status,
     scheduler_grpc_client::SchedulerGrpcClient,
     CancelTasksParams, CancelTasksResult, ExecutorMetric, ExecutorStatus,
+    GetProfileParams, GetProfileResult, GetTaskLogsParams, GetTaskLogsResult,
     HeartBeatParams, LaunchMultiTaskParams, LaunchMultiTaskResult, LaunchTaskParams,
-    LaunchTaskResult, RegisterExecutorParams, RemoveJobDataParams, RemoveJobDataResult,
-    StopExecutorParams, StopExecutorResult, TaskStatus, UpdateTaskStatusParams,
+    LaunchTaskResult, ProfileType, RegisterExecutorParams, RemoveJobDataParams,
+    RemoveJobDataResult, RemoveTaskOutputsParams, RemoveTaskOutputsResult,
+    StopExecutorParams, StopExecutorResult, TaskCounts, TaskStatus,
+    UpdateTaskStatusParams,
 };
 use ballista_core::serde::scheduler::from_proto::{
     get_task_definition, get_task_definition_vec,
@@ -44,9 +48,13 @@ use ballista_core::serde::scheduler::from_proto::{
 use ballista_core::serde::scheduler::PartitionId;
 use ballista_core::serde::scheduler::TaskDefinition;
 use ballista_core::serde::BallistaCodec;
-use ballista_core::utils::{create_grpc_client_connection, create_grpc_server};
+use ballista_core::utils::{
+    create_grpc_client_connection, create_grpc_server, GrpcChannel,
+};
+use bytes::Bytes;
 use dashmap::DashMap;
 use datafusion::execution::TaskContext;
+use datafusion::prelude::SessionConfig;
 use datafusion_proto::{logical_plan::AsLogicalPlan, physical_plan::AsExecutionPlan};
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::task::JoinHandle;
@@ -54,11 +62,13 @@ use tokio::task::JoinHandle;
 use crate::cpu_bound_executor::DedicatedExecutor;
 use crate::executor::Executor;
 use crate::executor_process::ExecutorProcessConfig;
+use crate::resource_usage::ResourceSampler;
+use crate::scan_leases::SchedulerScanFileLeaser;
 use crate::shutdown::ShutdownNotifier;
 use crate::{as_task_status, TaskExecutionTimes};
 
 type ServerHandle = JoinHandle<Result<(), BallistaError>>;
-type SchedulerClients = Arc<DashMap<String, SchedulerGrpcClient<Channel>>>;
+type SchedulerClients = Arc<DashMap<String, SchedulerGrpcClient<GrpcChannel>>>;
 
 /// Wrap TaskDefinition with its curator scheduler id for task update to its specific curator scheduler later
 #[derive(Debug)]
@@ -75,7 +85,7 @@ struct CuratorTaskStatus {
 }
 
 pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
-    mut scheduler: SchedulerGrpcClient<Channel>,
+    mut scheduler: SchedulerGrpcClient<GrpcChannel>,
     config: Arc<ExecutorProcessConfig>,
     executor: Arc<Executor>,
     codec: BallistaCodec<T, U>,
@@ -110,13 +120,19 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
             "Ballista v{} Rust Executor Grpc Server listening on {:?}",
             BALLISTA_VERSION, addr
         );
-        let server = ExecutorGrpcServer::new(executor_server.clone())
-            .max_encoding_message_size(config.grpc_max_encoding_message_size as usize)
-            .max_decoding_message_size(config.grpc_max_decoding_message_size as usize);
+        let server = InterceptedService::new(
+            ExecutorGrpcServer::new(executor_server.clone())
+                .max_encoding_message_size(config.grpc_max_encoding_message_size as usize)
+                .max_decoding_message_size(
+                    config.grpc_max_decoding_message_size as usize,
+                ),
+            config.grpc_security.auth_token_validator(),
+        );
+        let mut grpc_server = create_grpc_server(&config.grpc_security)?;
         let mut grpc_shutdown = shutdown_noti.subscribe_for_shutdown();
         tokio::spawn(async move {
             let shutdown_signal = grpc_shutdown.recv();
-            let grpc_server_future = create_grpc_server()
+            let grpc_server_future = grpc_server
                 .add_service(server)
                 .serve_with_shutdown(addr, shutdown_signal);
             grpc_server_future.await.map_err(|e| {
@@ -158,7 +174,7 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
 
 #[allow(clippy::clone_on_copy)]
 async fn register_executor(
-    scheduler: &mut SchedulerGrpcClient<Channel>,
+    scheduler: &mut SchedulerGrpcClient<GrpcChannel>,
     executor: Arc<Executor>,
 ) -> Result<(), BallistaError> {
     let result = scheduler
@@ -181,10 +197,12 @@ pub struct ExecutorServer<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPl
     executor: Arc<Executor>,
     executor_env: ExecutorEnv,
     codec: BallistaCodec<T, U>,
-    scheduler_to_register: SchedulerGrpcClient<Channel>,
+    scheduler_to_register: SchedulerGrpcClient<GrpcChannel>,
     schedulers: SchedulerClients,
     grpc_max_encoding_message_size: usize,
     grpc_max_decoding_message_size: usize,
+    /// Utilization of the host, sampled with each heartbeat
+    resource_sampler: Arc<ResourceSampler>,
 }
 
 #[derive(Clone)]
@@ -205,7 +223,7 @@ pub static TERMINATING: AtomicBool = AtomicBool::new(false);
 
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T, U> {
     fn new(
-        scheduler_to_register: SchedulerGrpcClient<Channel>,
+        scheduler_to_register: SchedulerGrpcClient<GrpcChannel>,
         executor: Arc<Executor>,
         executor_env: ExecutorEnv,
         codec: BallistaCodec<T, U>,
@@ -224,20 +242,25 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             schedulers: Default::default(),
             grpc_max_encoding_message_size,
             grpc_max_decoding_message_size,
+            resource_sampler: Arc::new(ResourceSampler::default()),
         }
     }
 
     async fn get_scheduler_client(
         &self,
         scheduler_id: &str,
-    ) -> Result<SchedulerGrpcClient<Channel>, BallistaError> {
+    ) -> Result<SchedulerGrpcClient<GrpcChannel>, BallistaError> {
         let scheduler = self.schedulers.get(scheduler_id).map(|value| value.clone());
         // If channel does not exist, create a new one
         if let Some(scheduler) = scheduler {
             Ok(scheduler)
         } else {
             let scheduler_url = format!("http://{scheduler_id}");
-            let connection = create_grpc_client_connection(scheduler_url).await?;
+            let connection = create_grpc_client_connection(
+                scheduler_url,
+                &self.executor.grpc_security,
+            )
+            .await?;
             let scheduler = SchedulerGrpcClient::new(connection)
                 .max_encoding_message_size(self.grpc_max_encoding_message_size)
                 .max_decoding_message_size(self.grpc_max_decoding_message_size);
@@ -251,6 +274,54 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         }
     }
 
+    /// The config of the tasks launched by a scheduler, leasing the files of their
+    /// splittable scans from it
+    async fn produce_task_config(
+        &self,
+        scheduler_id: &str,
+    ) -> Result<SessionConfig, Status> {
+        let scheduler = self
+            .get_scheduler_client(scheduler_id)
+            .await
+            .map_err(|e| Status::unavailable(format!("{e}")))?;
+        Ok(self.executor.produce_config().with_extension(
+            SchedulerScanFileLeaser::new(scheduler, &self.executor.metadata)
+                .into_leases(),
+        ))
+    }
+
+    /// Fill in the plan of tasks dispatched by reference by the scheduler
+    async fn resolve_task_plan(
+        &self,
+        scheduler_id: &str,
+        job_id: &str,
+        plan_hash: &str,
+        plan: &mut Bytes,
+    ) -> Result<(), Status> {
+        if !plan.is_empty() || plan_hash.is_empty() {
+            return Ok(());
+        }
+        let mut scheduler = self
+            .get_scheduler_client(scheduler_id)
+            .await
+            .map_err(|e| Status::unavailable(format!("{e}")))?;
+        self.executor
+            .task_plan_cache
+            .resolve_plan(
+                &mut scheduler,
+                &self.executor.metadata,
+                job_id,
+                plan_hash,
+                plan,
+            )
+            .await
+            .map_err(|e| {
+                Status::unavailable(format!(
+                    "Fail to fetch plan {plan_hash} of job {job_id}: {e}"
+                ))
+            })
+    }
+
     /// 1. First Heartbeat to its registration scheduler, if successful then return; else go next.
     /// 2. Heartbeat to schedulers which has launching tasks to this executor until one succeeds
     async fn heartbeat(&self) {
@@ -273,7 +344,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             .heart_beat_from_executor(heartbeat_params.clone())
             .await
         {
-            Ok(_) => {
+            Ok(result) => {
+                if let Some(settings) = &result.get_ref().settings {
+                    self.executor.apply_settings(settings);
+                }
                 return;
             }
             Err(e) => {
@@ -292,7 +366,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
                 .heart_beat_from_executor(heartbeat_params.clone())
                 .await
             {
-                Ok(_) => {
+                Ok(result) => {
+                    if let Some(settings) = &result.get_ref().settings {
+                        self.executor.apply_settings(settings);
+                    }
                     break;
                 }
                 Err(e) => {
@@ -328,54 +405,74 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             partition_id,
         };
 
-        let query_stage_exec = self
+        // a task whose runtime or plan can't be set up is reported as failed
+        let prepared = self
             .executor
-            .execution_engine
-            .create_query_stage_exec(
-                job_id.clone(),
-                stage_id,
-                plan,
-                &self.executor.work_dir,
-            )
-            .unwrap();
-
-        let task_context = {
-            let function_registry = task.function_registry;
-            let runtime = self.executor.produce_runtime(&task.session_config).unwrap();
-
-            Arc::new(TaskContext::new(
-                Some(task_identity.clone()),
-                task.session_id,
-                task.session_config,
-                function_registry.scalar_functions.clone(),
-                function_registry.aggregate_functions.clone(),
-                function_registry.window_functions.clone(),
-                runtime,
-            ))
-        };
+            .produce_task_runtime(&task.session_config, &part, task_id)
+            .map_err(BallistaError::from)
+            .and_then(|(runtime, spill_dir)| {
+                let (runtime, object_store_metrics) =
+                    self.executor.produce_task_object_stores(runtime);
+                let plan = self.executor.produce_task_plan(plan, &runtime)?;
+                let query_stage_exec =
+                    self.executor.execution_engine.create_query_stage_exec(
+                        job_id.clone(),
+                        stage_id,
+                        plan,
+                        &self.executor.work_dir,
+                    )?;
+                Ok((runtime, spill_dir, object_store_metrics, query_stage_exec))
+            });
+
+        let (execution_result, operator_metrics) = match prepared {
+            Ok((runtime, spill_dir, object_store_metrics, query_stage_exec)) => {
+                let task_context = {
+                    let function_registry = task.function_registry;
+
+                    Arc::new(TaskContext::new(
+                        Some(task_identity.clone()),
+                        task.session_id,
+                        task.session_config,
+                        function_registry.scalar_functions.clone(),
+                        function_registry.aggregate_functions.clone(),
+                        function_registry.window_functions.clone(),
+                        runtime,
+                    ))
+                };
 
-        info!("Start to execute shuffle write for task {}", task_identity);
+                info!("Start to execute shuffle write for task {}", task_identity);
 
-        let execution_result = self
-            .executor
-            .execute_query_stage(
-                task_id,
-                part.clone(),
-                query_stage_exec.clone(),
-                task_context,
-            )
-            .await;
-        info!("Done with task {}", task_identity);
-        debug!("Statistics: {:?}", execution_result);
-
-        let plan_metrics = query_stage_exec.collect_plan_metrics();
-        let operator_metrics = match plan_metrics
-            .into_iter()
-            .map(|m| m.try_into())
-            .collect::<Result<Vec<_>, BallistaError>>()
-        {
-            Ok(metrics) => Some(metrics),
-            Err(_) => None,
+                let execution_result = self
+                    .executor
+                    .execute_query_stage(
+                        task_id,
+                        part.clone(),
+                        query_stage_exec.clone(),
+                        task_context,
+                    )
+                    .await;
+                drop(spill_dir);
+                info!("Done with task {}", task_identity);
+                debug!("Statistics: {:?}", execution_result);
+
+                let mut plan_metrics = query_stage_exec.collect_plan_metrics();
+                if let Some(object_store_metrics) = &object_store_metrics {
+                    object_store_metrics.report(&mut plan_metrics, partition_id);
+                }
+                let operator_metrics = match plan_metrics
+                    .into_iter()
+                    .map(|m| m.try_into())
+                    .collect::<Result<Vec<_>, BallistaError>>()
+                {
+                    Ok(metrics) => Some(metrics),
+                    Err(_) => None,
+                };
+                (execution_result, operator_metrics)
+            }
+            Err(e) => {
+                error!("Failed to prepare task {}: {:?}", task_identity, e);
+                (Err(e), None)
+            }
         };
         let executor_id = &self.executor.metadata.id;
 
@@ -410,12 +507,31 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             .unwrap();
     }
 
-    // TODO populate with real metrics
     fn get_executor_metrics(&self) -> Vec<ExecutorMetric> {
-        let available_memory = ExecutorMetric {
-            metric: Some(executor_metric::Metric::AvailableMemory(u64::MAX)),
-        };
-        let executor_metrics = vec![available_memory];
+        let resource_usage = self.resource_sampler.sample(&self.executor.work_dir);
+        let available_memory = resource_usage
+            .map(|usage| usage.total_memory_bytes - usage.used_memory_bytes)
+            .unwrap_or(u64::MAX);
+        let mut executor_metrics = vec![
+            ExecutorMetric {
+                metric: Some(executor_metric::Metric::AvailableMemory(available_memory)),
+            },
+            self.executor.disk_usage.metric(),
+        ];
+        if let Some(usage) = resource_usage {
+            executor_metrics.push(ExecutorMetric {
+                metric: Some(executor_metric::Metric::ResourceUsage(usage)),
+            });
+        }
+        if let Some(data_cache) = &self.executor.data_cache {
+            executor_metrics.push(data_cache.metric());
+        }
+        executor_metrics.push(ExecutorMetric {
+            metric: Some(executor_metric::Metric::TaskCounts(TaskCounts {
+                running_tasks: self.executor.active_task_count() as u32,
+                queued_tasks: self.executor.queued_task_count() as u32,
+            })),
+        });
         executor_metrics
     }
 }
@@ -541,6 +657,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
                                         .id
                                         .clone(),
                                     task_status: tasks_status.clone(),
+                                    registration_token: executor_server
+                                        .executor
+                                        .metadata
+                                        .registration_token
+                                        .clone(),
                                 })
                                 .await
                             {
@@ -624,19 +745,27 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
             scheduler_id,
         } = request.into_inner();
         let task_sender = self.executor_env.tx_task.clone();
-        for task in tasks {
+        for mut task in tasks {
+            self.resolve_task_plan(
+                &scheduler_id,
+                &task.job_id,
+                &task.plan_hash,
+                &mut task.plan,
+            )
+            .await?;
             task_sender
                 .send(CuratorTaskDefinition {
                     scheduler_id: scheduler_id.clone(),
                     task: get_task_definition(
                         task,
                         self.executor.runtime_producer.clone(),
-                        self.executor.produce_config(),
+                        self.produce_task_config(&scheduler_id).await?,
                         self.executor.function_registry.scalar_functions.clone(),
                         self.executor.function_registry.aggregate_functions.clone(),
                         self.executor.function_registry.window_functions.clone(),
                         self.codec.clone(),
                     )
+                    .await
                     .map_err(|e| Status::invalid_argument(format!("{e}")))?,
                 })
                 .await
@@ -656,16 +785,24 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
             scheduler_id,
         } = request.into_inner();
         let task_sender = self.executor_env.tx_task.clone();
-        for multi_task in multi_tasks {
+        for mut multi_task in multi_tasks {
+            self.resolve_task_plan(
+                &scheduler_id,
+                &multi_task.job_id,
+                &multi_task.plan_hash,
+                &mut multi_task.plan,
+            )
+            .await?;
             let multi_task: Vec<TaskDefinition> = get_task_definition_vec(
                 multi_task,
                 self.executor.runtime_producer.clone(),
-                self.executor.produce_config(),
+                self.produce_task_config(&scheduler_id).await?,
                 self.executor.function_registry.scalar_functions.clone(),
                 self.executor.function_registry.aggregate_functions.clone(),
                 self.executor.function_registry.window_functions.clone(),
                 self.codec.clone(),
             )
+            .await
             .map_err(|e| Status::invalid_argument(format!("{e}")))?;
             for task in multi_task {
                 task_sender
@@ -737,6 +874,23 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
     ) -> Result<Response<RemoveJobDataResult>, Status> {
         let job_id = request.into_inner().job_id;
 
+        self.executor.forget_cancelled_tasks(&job_id);
+        self.executor.forget_task_permits(&job_id);
+        self.executor.task_plan_cache.remove_job(&job_id);
+        self.executor.stage_plan_cache.remove_job(&job_id);
+        self.executor.broadcast_cache.remove_job(&job_id);
+        self.executor.fetch_tokens.remove_job(&job_id);
+        // the job is done, cancelled or failed, and the partitions still being
+        // streamed are of no use anymore
+        let aborted_streams = self.executor.flight_streams.cancel_job(&job_id);
+        if aborted_streams > 0 {
+            info!("Aborted {aborted_streams} streams of partitions of job {job_id}");
+        }
+
+        if let Some(task_log_store) = &self.executor.task_log_store {
+            task_log_store.remove_job(&job_id);
+        }
+
         let work_dir = PathBuf::from(&self.executor.work_dir);
         let mut path = work_dir.clone();
         path.push(&job_id);
@@ -762,8 +916,131 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
 
         std::fs::remove_dir_all(&path)?;
 
+        // a full executor runs tasks again as soon as enough data is removed
+        let disk_usage = self.executor.disk_usage.clone();
+        if disk_usage.max_bytes() > 0 {
+            let work_dir = self.executor.work_dir.clone();
+            tokio::spawn(async move {
+                if let Err(e) = disk_usage.refresh(&work_dir).await {
+                    warn!("Failed to measure the job data disk usage: {e:?}");
+                }
+            });
+        }
+
         Ok(Response::new(RemoveJobDataResult {}))
     }
+
+    async fn remove_task_outputs(
+        &self,
+        request: Request<RemoveTaskOutputsParams>,
+    ) -> Result<Response<RemoveTaskOutputsResult>, Status> {
+        let RemoveTaskOutputsParams { job_id, paths } = request.into_inner();
+
+        let work_dir = PathBuf::from(&self.executor.work_dir);
+        let mut job_dir = work_dir.clone();
+        job_dir.push(&job_id);
+        if !is_subdirectory(job_dir.as_path(), work_dir.as_path()) {
+            return Err(Status::invalid_argument(format!(
+                "Path {job_dir:?} is not a subdirectory of {work_dir:?}!!!"
+            )));
+        }
+
+        // The partitions of a sort-based shuffle file share its path, which is only
+        // removed once
+        for path in paths {
+            let file = Path::new(&path);
+            if !file.exists() {
+                continue;
+            }
+            if !is_subdirectory(file, job_dir.as_path()) {
+                return Err(Status::invalid_argument(format!(
+                    "Path {file:?} is not in the directory {job_dir:?} of the job!!!"
+                )));
+            }
+
+            info!("Remove task output {:?} of job {}", file, job_id);
+            std::fs::remove_file(file)?;
+            let index_path = shuffle_index_path(&path);
+            if Path::new(&index_path).exists() {
+                std::fs::remove_file(index_path)?;
+            }
+        }
+
+        Ok(Response::new(RemoveTaskOutputsResult {}))
+    }
+
+    async fn get_task_logs(
+        &self,
+        request: Request<GetTaskLogsParams>,
+    ) -> Result<Response<GetTaskLogsResult>, Status> {
+        let GetTaskLogsParams { job_id, task_id } = request.into_inner();
+
+        let Some(task_log_store) = &self.executor.task_log_store else {
+            return Err(Status::unimplemented(
+                "Task log capture is not enabled on this executor",
+            ));
+        };
+
+        let result = match task_log_store.get(&job_id, task_id as usize) {
+            Some(lines) => GetTaskLogsResult { found: true, lines },
+            None => GetTaskLogsResult {
+                found: false,
+                lines: vec![],
+            },
+        };
+
+        Ok(Response::new(result))
+    }
+
+    async fn get_profile(
+        &self,
+        request: Request<GetProfileParams>,
+    ) -> Result<Response<GetProfileResult>, Status> {
+        let params = request.into_inner();
+        let executor = self.executor.clone();
+        let profile = tokio::task::spawn_blocking(move || {
+            match ProfileType::try_from(params.profile_type).unwrap_or(ProfileType::Cpu) {
+                ProfileType::Cpu => cpu_profile(&executor, &params),
+                ProfileType::Heap => heap_profile(&params),
+            }
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))??;
+
+        Ok(Response::new(GetProfileResult { profile }))
+    }
+}
+
+#[cfg(feature = "pprof")]
+fn cpu_profile(
+    executor: &Executor,
+    params: &GetProfileParams,
+) -> Result<Vec<u8>, Status> {
+    crate::profiling::profile(executor, params)
+        .map_err(|e| Status::failed_precondition(e.to_string()))
+}
+
+#[cfg(not(feature = "pprof"))]
+fn cpu_profile(
+    _executor: &Executor,
+    _params: &GetProfileParams,
+) -> Result<Vec<u8>, Status> {
+    Err(Status::unimplemented(
+        "Executor was built without the pprof feature",
+    ))
+}
+
+#[cfg(feature = "jemalloc")]
+fn heap_profile(params: &GetProfileParams) -> Result<Vec<u8>, Status> {
+    crate::heap_profiling::profile(params)
+        .map_err(|e| Status::failed_precondition(e.to_string()))
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn heap_profile(_params: &GetProfileParams) -> Result<Vec<u8>, Status> {
+    Err(Status::unimplemented(
+        "Executor was built without the jemalloc feature",
+    ))
 }
 
 // Check whether the path is the subdirectory of the base directory
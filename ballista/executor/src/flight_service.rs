@@ -18,14 +18,20 @@
 //! Implementation of the Apache Arrow Flight protocol that wraps an executor.
 
 use arrow::ipc::reader::StreamReader;
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::fs::File;
+use std::path::{Component, Path, PathBuf};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
-use arrow::ipc::CompressionType;
-use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::encode::{DictionaryHandling, FlightDataEncoderBuilder};
 use arrow_flight::error::FlightError;
-use ballista_core::error::BallistaError;
+use ballista_core::client::compress_flight_data;
+use ballista_core::compression::CompressionCodecRegistry;
+use ballista_core::config::ShuffleCompression;
+use ballista_core::execution_plans::open_shuffle_file;
+use ballista_core::security::FetchToken;
 use ballista_core::serde::decode_protobuf;
 use ballista_core::serde::scheduler::Action as BallistaAction;
 
@@ -35,10 +41,12 @@ use arrow_flight::{
     FlightData, FlightDescriptor, FlightInfo, HandshakeRequest, HandshakeResponse,
     PollInfo, PutResult, SchemaResult, Ticket,
 };
+use dashmap::DashMap;
 use datafusion::arrow::{error::ArrowError, record_batch::RecordBatch};
+use futures::stream::AbortHandle;
 use futures::{Stream, StreamExt, TryStreamExt};
 use log::{debug, info};
-use std::io::{BufReader, Read, Seek};
+use std::io::{BufReader, Read};
 use tokio::sync::mpsc::channel;
 use tokio::sync::mpsc::error::SendError;
 use tokio::{sync::mpsc::Sender, task};
@@ -49,11 +57,47 @@ use tracing::warn;
 
 /// Service implementing the Apache Arrow Flight Protocol
 #[derive(Clone)]
-pub struct BallistaFlightService {}
+pub struct BallistaFlightService {
+    streams: Arc<FlightStreams>,
+    fetch_tokens: Arc<FetchTokens>,
+    compression_codecs: Arc<CompressionCodecRegistry>,
+}
 
 impl BallistaFlightService {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            streams: Arc::new(FlightStreams::default()),
+            fetch_tokens: Arc::new(FetchTokens::default()),
+            compression_codecs: Arc::new(CompressionCodecRegistry::default()),
+        }
+    }
+
+    /// Register the partitions being streamed with `streams`, e.g. those of an
+    /// [Executor], so that they can be aborted
+    ///
+    /// [Executor]: crate::executor::Executor
+    pub fn with_streams(mut self, streams: Arc<FlightStreams>) -> Self {
+        self.streams = streams;
+        self
+    }
+
+    /// Serve the partitions of the jobs whose tokens are registered with
+    /// `fetch_tokens`, e.g. those of an [Executor], to the fetches presenting them
+    ///
+    /// [Executor]: crate::executor::Executor
+    pub fn with_fetch_tokens(mut self, fetch_tokens: Arc<FetchTokens>) -> Self {
+        self.fetch_tokens = fetch_tokens;
+        self
+    }
+
+    /// Compress the partitions fetched with the codecs of `compression_codecs`, which
+    /// must include the codecs the sessions of the fetches refer to
+    pub fn with_compression_codecs(
+        mut self,
+        compression_codecs: Arc<CompressionCodecRegistry>,
+    ) -> Self {
+        self.compression_codecs = compression_codecs;
+        self
     }
 }
 
@@ -86,18 +130,29 @@ impl FlightService for BallistaFlightService {
             decode_protobuf(&ticket.ticket).map_err(|e| from_ballista_err(&e))?;
 
         match &action {
-            BallistaAction::FetchPartition { path, .. } => {
+            BallistaAction::FetchPartition {
+                job_id,
+                path,
+                partition_id,
+                compression,
+                fetch_token,
+                transfer_compression,
+                ipc_metadata_version,
+                ..
+            } => {
+                self.fetch_tokens.check(job_id, path, fetch_token)?;
+                let transfer_codec = if transfer_compression.is_empty() {
+                    None
+                } else {
+                    Some(
+                        self.compression_codecs
+                            .codec(transfer_compression)
+                            .map_err(|e| from_ballista_err(&e))?,
+                    )
+                };
                 debug!("FetchPartition reading {}", path);
-                let file = File::open(path)
-                    .map_err(|e| {
-                        BallistaError::General(format!(
-                            "Failed to open partition file at {path}: {e:?}"
-                        ))
-                    })
+                let reader = open_shuffle_file(path, *partition_id)
                     .map_err(|e| from_ballista_err(&e))?;
-                let file = BufReader::new(file);
-                let reader =
-                    StreamReader::try_new(file, None).map_err(|e| from_arrow_err(&e))?;
 
                 let (tx, rx) = channel(2);
                 let schema = reader.schema();
@@ -107,14 +162,36 @@ impl FlightService for BallistaFlightService {
                     }
                 });
 
-                let write_options: IpcWriteOptions = IpcWriteOptions::default()
-                    .try_with_compression(Some(CompressionType::LZ4_FRAME))
+                // Codecs Arrow IPC supports compress the batches, others the encoded
+                // messages
+                let (ipc_compression, body_codec) = match transfer_codec {
+                    Some(codec) => match codec.ipc_compression() {
+                        Some(ipc_compression) => (ipc_compression, None),
+                        None => (ShuffleCompression::Uncompressed, Some(codec)),
+                    },
+                    None => (*compression, None),
+                };
+                // in the IPC format version of the reader, which may predate this
+                // executor
+                let write_options: IpcWriteOptions = ipc_compression
+                    .ipc_write_options(*ipc_metadata_version)
                     .map_err(|e| from_arrow_err(&e))?;
+                // Keep dictionaries as is, so that the batches match the schema the
+                // shuffle reader expects
                 let flight_data_stream = FlightDataEncoderBuilder::new()
                     .with_schema(schema)
                     .with_options(write_options)
+                    .with_dictionary_handling(DictionaryHandling::Resend)
                     .build(ReceiverStream::new(rx))
                     .map_err(|err| Status::from_error(Box::new(err)));
+                let flight_data_stream: BoxedFlightStream<FlightData> = match body_codec {
+                    Some(codec) => {
+                        Box::pin(compress_flight_data(flight_data_stream, codec))
+                    }
+                    None => Box::pin(flight_data_stream),
+                };
+                let flight_data_stream =
+                    self.streams.register(job_id, flight_data_stream);
 
                 Ok(Response::new(
                     Box::pin(flight_data_stream) as Self::DoGetStream
@@ -213,12 +290,178 @@ impl FlightService for BallistaFlightService {
     }
 }
 
+/// Handles to abort the partitions being streamed, by job, so that the streams of a
+/// cancelled job stop reading its shuffle files and sending them over the network
+#[derive(Debug, Default)]
+pub struct FlightStreams {
+    next_id: AtomicU64,
+    streams: DashMap<String, HashMap<u64, AbortHandle>>,
+}
+
+impl FlightStreams {
+    /// Make the stream of a partition of a job abortable, ending it with a cancelled
+    /// status once aborted rather than letting the client take it for complete
+    fn register<S>(
+        self: &Arc<Self>,
+        job_id: &str,
+        stream: S,
+    ) -> impl Stream<Item = Result<FlightData, Status>> + Send + 'static
+    where
+        S: Stream<Item = Result<FlightData, Status>> + Send + 'static,
+    {
+        let (stream, abort_handle) = futures::stream::abortable(stream);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.streams
+            .entry(job_id.to_owned())
+            .or_default()
+            .insert(id, abort_handle.clone());
+        let registration = StreamRegistration {
+            streams: self.clone(),
+            job_id: job_id.to_owned(),
+            id,
+        };
+
+        let job_id = job_id.to_owned();
+        stream.chain(
+            futures::stream::once(async move {
+                // dropped along with the stream, whether or not it is streamed to
+                // the end
+                drop(registration);
+                abort_handle.is_aborted().then(|| {
+                    Err(Status::cancelled(format!(
+                        "Streaming of a partition of job {job_id} was aborted"
+                    )))
+                })
+            })
+            .filter_map(futures::future::ready),
+        )
+    }
+
+    /// Abort the streams of the partitions of a job, returning their number
+    pub fn cancel_job(&self, job_id: &str) -> usize {
+        match self.streams.remove(job_id) {
+            Some((_, streams)) => {
+                for abort_handle in streams.values() {
+                    abort_handle.abort();
+                }
+                streams.len()
+            }
+            None => 0,
+        }
+    }
+
+    /// Number of the partitions of a job being streamed
+    pub fn job_streams(&self, job_id: &str) -> usize {
+        self.streams
+            .get(job_id)
+            .map(|streams| streams.len())
+            .unwrap_or(0)
+    }
+}
+
+/// Tokens of the jobs whose tasks ran on the executor, which the fetches of the
+/// shuffle partitions of the jobs must present
+#[derive(Debug, Default)]
+pub struct FetchTokens {
+    // Directory holding the directories of the data of the jobs. Nothing is served
+    // unless it's set
+    work_dir: Option<PathBuf>,
+    tokens: DashMap<String, FetchToken>,
+}
+
+impl FetchTokens {
+    /// Tokens of the jobs whose data is in the directories of the jobs in `work_dir`
+    pub fn new(work_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            work_dir: Some(work_dir.into()),
+            tokens: DashMap::new(),
+        }
+    }
+
+    /// Record the token of a job, dispatched by the scheduler with its tasks. Empty
+    /// tokens, which any fetch without a token would present, are not recorded
+    pub fn register(&self, job_id: &str, token: &FetchToken) {
+        if job_id.is_empty() || token.as_str().is_empty() {
+            return;
+        }
+        if !self.tokens.contains_key(job_id) {
+            self.tokens.insert(job_id.to_owned(), token.clone());
+        }
+    }
+
+    /// Forget the token of a job whose data was removed
+    pub fn remove_job(&self, job_id: &str) {
+        self.tokens.remove(job_id);
+    }
+
+    /// Check that a fetch of the partition at `path` presents the token of its job,
+    /// and that the partition is one of the job
+    fn check(&self, job_id: &str, path: &str, token: &str) -> Result<(), Status> {
+        let authorized = self
+            .tokens
+            .get(job_id)
+            .is_some_and(|expected| expected.matches(token));
+        if !authorized {
+            return Err(Status::permission_denied(format!(
+                "Invalid fetch token for the partitions of job {job_id}"
+            )));
+        }
+        let in_job_dir = self
+            .work_dir
+            .as_ref()
+            .is_some_and(|work_dir| is_job_path(work_dir, path, job_id));
+        if !in_job_dir {
+            return Err(Status::permission_denied(format!(
+                "Path {path} is not a partition of job {job_id}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Whether `path` is a file under `{work_dir}/{job_id}/`, once symbolic links and `..`
+/// are resolved
+fn is_job_path(work_dir: &Path, path: &str, job_id: &str) -> bool {
+    let mut job_id_components = Path::new(job_id).components();
+    if !matches!(
+        (job_id_components.next(), job_id_components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return false;
+    }
+    match (
+        work_dir.join(job_id).canonicalize(),
+        Path::new(path).canonicalize(),
+    ) {
+        (Ok(job_dir), Ok(path)) => path != job_dir && path.starts_with(job_dir),
+        _ => false,
+    }
+}
+
+/// Registration of a stream in [FlightStreams], removed when the stream is dropped
+struct StreamRegistration {
+    streams: Arc<FlightStreams>,
+    job_id: String,
+    id: u64,
+}
+
+impl Drop for StreamRegistration {
+    fn drop(&mut self) {
+        if let Some(mut streams) = self.streams.streams.get_mut(&self.job_id) {
+            streams.remove(&self.id);
+        }
+        self.streams
+            .streams
+            .remove_if(&self.job_id, |_, streams| streams.is_empty());
+    }
+}
+
 fn read_partition<T>(
-    reader: StreamReader<std::io::BufReader<T>>,
+    reader: StreamReader<BufReader<T>>,
     tx: Sender<Result<RecordBatch, FlightError>>,
 ) -> Result<(), FlightError>
 where
-    T: Read + Seek,
+    T: Read,
 {
     if tx.is_closed() {
         return Err(FlightError::Tonic(Status::internal(
@@ -246,5 +489,103 @@ fn from_arrow_err(e: &ArrowError) -> Status {
 }
 
 fn from_ballista_err(e: &ballista_core::error::BallistaError) -> Status {
-    Status::internal(format!("Ballista Error: {e:?}"))
+    e.to_grpc_status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn abort_streams_of_cancelled_job() {
+        let streams = Arc::new(FlightStreams::default());
+
+        let (tx, rx) = channel(2);
+        let mut stream = Box::pin(streams.register("job", ReceiverStream::new(rx)));
+        let (other_tx, other_rx) = channel(2);
+        let other_stream = streams.register("other job", ReceiverStream::new(other_rx));
+        assert_eq!(1, streams.job_streams("job"));
+
+        tx.send(Ok(FlightData::default())).await.unwrap();
+        assert!(stream.next().await.unwrap().is_ok());
+
+        // the stream ends with an error as soon as its job is cancelled, even though
+        // its partition is still being read
+        assert_eq!(1, streams.cancel_job("job"));
+        tx.send(Ok(FlightData::default())).await.unwrap();
+        let status = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(tonic::Code::Cancelled, status.code());
+        assert!(stream.next().await.is_none());
+        assert_eq!(0, streams.job_streams("job"));
+
+        // streams of other jobs go on, and are forgotten once streamed
+        other_tx.send(Ok(FlightData::default())).await.unwrap();
+        drop(other_tx);
+        let streamed: Vec<_> = other_stream.collect().await;
+        assert_eq!(1, streamed.len());
+        assert!(streamed[0].is_ok());
+        assert_eq!(0, streams.job_streams("other job"));
+        assert_eq!(0, streams.cancel_job("other job"));
+    }
+
+    #[test]
+    fn check_fetch_tokens() -> Result<(), Box<dyn std::error::Error>> {
+        let work_dir = tempfile::TempDir::new()?;
+        let outside_dir = tempfile::TempDir::new()?;
+        let create = |dir: &Path| -> std::io::Result<String> {
+            std::fs::create_dir_all(dir)?;
+            let file = dir.join("data.arrow");
+            std::fs::write(&file, b"")?;
+            Ok(file.to_str().unwrap().to_owned())
+        };
+        let path = create(&work_dir.path().join("job/1/0"))?;
+        let other_job_path = create(&work_dir.path().join("other/1/0"))?;
+        // a directory named after the job, out of the work dir
+        let outside_path = create(&outside_dir.path().join("job"))?;
+
+        let tokens = FetchTokens::new(work_dir.path());
+        let token = FetchToken::generate();
+        tokens.register("job", &token);
+        // the token of a job is the one given with its first task
+        tokens.register("job", &FetchToken::generate());
+        // empty job ids and tokens are never recorded
+        tokens.register("", &token);
+        tokens.register("empty", &FetchToken::from(String::new()));
+
+        assert!(tokens.check("job", &path, token.as_str()).is_ok());
+
+        let denied = |job_id: &str, path: &str, token: &str| {
+            tokens.check(job_id, path, token).unwrap_err().code()
+                == tonic::Code::PermissionDenied
+        };
+        let path = path.as_str();
+        assert!(denied("job", path, ""));
+        let other_token = FetchToken::generate();
+        assert!(denied("job", path, other_token.as_str()));
+        // the partitions of jobs which never ran on the executor are not served
+        assert!(denied("other job", path, token.as_str()));
+        assert!(denied("empty", path, ""));
+        assert!(denied("", path, token.as_str()));
+        // nor the files out of the directory of the job
+        assert!(denied("job", &other_job_path, token.as_str()));
+        assert!(denied("job", &outside_path, token.as_str()));
+        assert!(denied(
+            "job",
+            work_dir
+                .path()
+                .join("job/../other/1/0/data.arrow")
+                .to_str()
+                .unwrap(),
+            token.as_str()
+        ));
+        assert!(denied("job", "/etc/job/passwd", token.as_str()));
+        // nor without a work dir
+        let no_work_dir = FetchTokens::default();
+        no_work_dir.register("job", &token);
+        assert!(no_work_dir.check("job", path, token.as_str()).is_err());
+
+        tokens.remove_job("job");
+        assert!(denied("job", path, token.as_str()));
+        Ok(())
+    }
 }
@@ -0,0 +1,50 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Heap profiling of a running executor through the sampling profiler of jemalloc
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::{GetProfileParams, ProfileFormat};
+
+/// Dump the live sampled allocations of the executor in the requested format.
+///
+/// Requires the executor binary to use jemalloc as its global allocator with
+/// profiling enabled, as it does with the `jemalloc` feature. The dump is written
+/// to a temporary file and symbolized, which blocks the calling thread.
+pub fn profile(params: &GetProfileParams) -> Result<Vec<u8>> {
+    let ctl = jemalloc_pprof::PROF_CTL.as_ref().ok_or_else(|| {
+        BallistaError::General(
+            "Heap profiling is not enabled in the allocator of the executor".to_string(),
+        )
+    })?;
+    let mut ctl = ctl.blocking_lock();
+    if !ctl.activated() {
+        return Err(BallistaError::General(
+            "Heap profiling is not active in the allocator of the executor".to_string(),
+        ));
+    }
+
+    let profile = match ProfileFormat::try_from(params.format)
+        .unwrap_or(ProfileFormat::Flamegraph)
+    {
+        ProfileFormat::Flamegraph => ctl.dump_flamegraph(),
+        ProfileFormat::Pprof => ctl.dump_pprof(),
+    };
+    profile.map_err(|e| {
+        BallistaError::General(format!("Failed to profile the executor heap: {e}"))
+    })
+}
@@ -19,22 +19,38 @@
 
 pub mod collect;
 pub mod config;
+pub mod data_cache;
+pub mod disk_usage;
 pub mod execution_engine;
 pub mod execution_loop;
 pub mod executor;
+pub mod executor_builder;
 pub mod executor_process;
 pub mod executor_server;
 pub mod flight_service;
+#[cfg(feature = "jemalloc")]
+pub mod heap_profiling;
+pub mod metadata_cache;
 pub mod metrics;
+pub mod object_store_retry;
+pub mod plan_cache;
+#[cfg(feature = "pprof")]
+pub mod profiling;
+pub mod resource_usage;
+pub mod scan_leases;
 pub mod shutdown;
+pub mod spill_dir;
+pub mod task_logs;
 pub mod terminate;
 
 mod cpu_bound_executor;
 mod standalone;
 
+pub use executor_builder::{ExecutorBuilder, ExecutorHandle};
 pub use standalone::new_standalone_executor;
 pub use standalone::new_standalone_executor_from_builder;
 pub use standalone::new_standalone_executor_from_state;
+pub use standalone::new_standalone_executor_with_work_dir;
 
 use log::info;
 
@@ -86,7 +102,7 @@ pub fn as_task_status(
         }
         Err(e) => {
             let error_msg = e.to_string();
-            info!("Task {:?} failed: {}", task_id, error_msg);
+            info!("Task {:?} failed: [{}] {}", task_id, e.code(), error_msg);
 
             TaskStatus {
                 task_id: task_id as u32,
@@ -0,0 +1,281 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cache of the metadata of the Parquet files the tasks of the executor scan, so that
+//! the tasks and jobs scanning the same hot tables don't fetch and decode their footers
+//! every time, which dominates the setup of the tasks scanning wide tables.
+//!
+//! The metadata of a file is cached along with its page index, which the scans would
+//! otherwise fetch separately, and is keyed by the path and the version of the file,
+//! its ETag or else its modification time and size, so that a file rewritten in place
+//! is read again. The least recently scanned files are evicted first once the cache
+//! exceeds its memory budget.
+//!
+//! Only the decoded footers are cached. The Parquet scans of DataFusion build the
+//! [ArrowReaderMetadata] of each file themselves from the footer, converting its Arrow
+//! schema, and decode the dictionary pages of the column chunks inside the Parquet
+//! reader, without a hook through which they could be supplied, so both are still done
+//! by every task. Caching them needs a Parquet scan of Ballista's own, or such a hook
+//! upstream, and is not done by this cache.
+//!
+//! [ArrowReaderMetadata]: datafusion::parquet::arrow::arrow_reader::ArrowReaderMetadata
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::datasource::physical_plan::{
+    FileMeta, ParquetExec, ParquetFileMetrics, ParquetFileReaderFactory,
+};
+use datafusion::error::Result;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::parquet::arrow::async_reader::{AsyncFileReader, ParquetObjectReader};
+use datafusion::parquet::file::metadata::ParquetMetaData;
+use datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
+use datafusion::physical_plan::ExecutionPlan;
+use futures::future::BoxFuture;
+use object_store::ObjectStore;
+use parking_lot::Mutex;
+
+use crate::data_cache::{file_version, Lru};
+
+/// The metadata of Parquet files, keyed by their path, along with their version
+#[derive(Debug)]
+pub struct MetadataCache {
+    entries: Mutex<Lru<String, (String, Arc<ParquetMetaData>)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl MetadataCache {
+    /// Create a cache of the metadata of files up to `memory_bytes`
+    pub fn new(memory_bytes: u64) -> Self {
+        Self {
+            entries: Mutex::new(Lru::new(memory_bytes)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The metadata of a version of a file, forgetting that of its other versions
+    fn get(&self, location: &str, version: &str) -> Option<Arc<ParquetMetaData>> {
+        let mut entries = self.entries.lock();
+        match entries.get(&location.to_owned()) {
+            Some((cached_version, metadata)) if cached_version == version => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Some(metadata);
+            }
+            Some(_) => {
+                entries.remove(&location.to_owned());
+            }
+            None => {}
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    fn insert(&self, location: String, version: String, metadata: Arc<ParquetMetaData>) {
+        let size = metadata.memory_size() as u64;
+        self.entries
+            .lock()
+            .insert(location, (version, metadata), size);
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// The memory used by the metadata cached
+    pub fn memory_bytes(&self) -> u64 {
+        self.entries.lock().size()
+    }
+}
+
+/// Reader of a Parquet file fetching its metadata through a [MetadataCache]
+struct CachingParquetFileReader {
+    inner: ParquetObjectReader,
+    file_metrics: ParquetFileMetrics,
+    location: String,
+    version: String,
+    cache: Arc<MetadataCache>,
+}
+
+impl AsyncFileReader for CachingParquetFileReader {
+    fn get_bytes(
+        &mut self,
+        range: Range<usize>,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Bytes>> {
+        self.file_metrics.bytes_scanned.add(range.end - range.start);
+        self.inner.get_bytes(range)
+    }
+
+    fn get_byte_ranges(
+        &mut self,
+        ranges: Vec<Range<usize>>,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Vec<Bytes>>>
+    where
+        Self: Send,
+    {
+        let total = ranges.iter().map(|r| r.end - r.start).sum();
+        self.file_metrics.bytes_scanned.add(total);
+        self.inner.get_byte_ranges(ranges)
+    }
+
+    fn get_metadata(
+        &mut self,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Arc<ParquetMetaData>>> {
+        Box::pin(async move {
+            if let Some(metadata) = self.cache.get(&self.location, &self.version) {
+                return Ok(metadata);
+            }
+            let metadata = self.inner.get_metadata().await?;
+            self.cache.insert(
+                self.location.clone(),
+                self.version.clone(),
+                metadata.clone(),
+            );
+            Ok(metadata)
+        })
+    }
+}
+
+/// Creates the readers of the Parquet files of an object store, which fetch the
+/// metadata of the files, including their page index, through a [MetadataCache]
+#[derive(Debug)]
+pub struct CachingParquetFileReaderFactory {
+    store: Arc<dyn ObjectStore>,
+    cache: Arc<MetadataCache>,
+}
+
+impl CachingParquetFileReaderFactory {
+    pub fn new(store: Arc<dyn ObjectStore>, cache: Arc<MetadataCache>) -> Self {
+        Self { store, cache }
+    }
+}
+
+impl ParquetFileReaderFactory for CachingParquetFileReaderFactory {
+    fn create_reader(
+        &self,
+        partition_index: usize,
+        file_meta: FileMeta,
+        metadata_size_hint: Option<usize>,
+        metrics: &ExecutionPlanMetricsSet,
+    ) -> Result<Box<dyn AsyncFileReader + Send>> {
+        let location = file_meta.location().as_ref().to_owned();
+        let file_metrics = ParquetFileMetrics::new(partition_index, &location, metrics);
+        let version = file_version(&file_meta.object_meta);
+        let mut inner =
+            ParquetObjectReader::new(self.store.clone(), file_meta.object_meta)
+                .with_preload_column_index(true)
+                .with_preload_offset_index(true);
+        if let Some(hint) = metadata_size_hint {
+            inner = inner.with_footer_size_hint(hint);
+        }
+
+        Ok(Box::new(CachingParquetFileReader {
+            inner,
+            file_metrics,
+            location,
+            version,
+            cache: self.cache.clone(),
+        }))
+    }
+}
+
+/// Make the Parquet scans of a plan fetch the metadata of their files through `cache`,
+/// from the object stores of `runtime`
+pub fn with_metadata_cache(
+    plan: Arc<dyn ExecutionPlan>,
+    cache: &Arc<MetadataCache>,
+    runtime: &RuntimeEnv,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    plan.transform_up(|plan| {
+        let Some(exec) = plan.as_any().downcast_ref::<ParquetExec>() else {
+            return Ok(Transformed::no(plan));
+        };
+        let store = runtime.object_store(&exec.base_config().object_store_url)?;
+        let factory = CachingParquetFileReaderFactory::new(store, cache.clone());
+        Ok(Transformed::yes(Arc::new(
+            exec.clone()
+                .with_parquet_file_reader_factory(Arc::new(factory)),
+        )))
+    })
+    .map(|transformed| transformed.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::parquet::arrow::ArrowWriter;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+
+    #[tokio::test]
+    async fn test_metadata_cache() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )?;
+        let mut data = vec![];
+        let mut writer = ArrowWriter::try_new(&mut data, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+
+        let store = Arc::new(InMemory::new());
+        let location = Path::from("table/file.parquet");
+        store.put(&location, data.into()).await?;
+        let head = store.head(&location).await?;
+
+        let cache = Arc::new(MetadataCache::new(1024 * 1024));
+        let factory = CachingParquetFileReaderFactory::new(store, cache.clone());
+        let metrics = ExecutionPlanMetricsSet::new();
+        let metadata = |e_tag: &str| {
+            let mut meta = head.clone();
+            meta.e_tag = Some(e_tag.to_owned());
+            let mut reader = factory
+                .create_reader(0, FileMeta::from(meta), None, &metrics)
+                .unwrap();
+            async move { reader.get_metadata().await.unwrap() }
+        };
+
+        let first = metadata("1").await;
+        assert_eq!(3, first.file_metadata().num_rows());
+        assert!(first.offset_index().is_some());
+        assert_eq!((0, 1), (cache.hits(), cache.misses()));
+
+        let second = metadata("1").await;
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!((1, 1), (cache.hits(), cache.misses()));
+        assert!(cache.memory_bytes() > 0);
+
+        // a new version of the file is read again
+        let third = metadata("2").await;
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert_eq!((1, 2), (cache.hits(), cache.misses()));
+        Ok(())
+    }
+}
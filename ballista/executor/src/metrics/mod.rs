@@ -15,7 +15,16 @@
 // specific language governing permissions and limitations
 // under the License.
 
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
 use crate::execution_engine::QueryStageExecutor;
+#[cfg(feature = "prometheus")]
+use crate::metrics::prometheus::PrometheusMetricsCollector;
+use ballista_core::error::Result;
+#[cfg(feature = "prometheus")]
+use ballista_core::execution_plans::ShuffleFetchLimiter;
+use ballista_core::execution_plans::ShuffleFetchUsage;
 use log::info;
 use std::sync::Arc;
 
@@ -33,6 +42,22 @@ pub trait ExecutorMetricsCollector: Send + Sync {
         partition: usize,
         plan: Arc<dyn QueryStageExecutor>,
     );
+
+    /// Record that a task started executing
+    fn record_task_started(&self, _job_id: &str, _stage_id: usize, _partition: usize) {}
+
+    /// Record that a task failed or was cancelled while executing
+    fn record_task_failed(&self, _job_id: &str, _stage_id: usize, _partition: usize) {}
+
+    /// Record the shuffle fetches of the tasks under the limit of the executor
+    fn record_shuffle_fetches(&self, _usage: ShuffleFetchUsage) {}
+
+    /// Gather all metrics into a byte array and its content type, to be served on the
+    /// `/metrics` endpoint of the executor. Returns `None` if the collector doesn't
+    /// export metrics
+    fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
+        Ok(None)
+    }
 }
 
 /// Implementation of `ExecutorMetricsCollector` which logs the completed
@@ -54,3 +79,50 @@ impl ExecutorMetricsCollector for LoggingMetricsCollector {
         );
     }
 }
+
+/// Return a reference to the systems default metrics collector.
+#[cfg(feature = "prometheus")]
+pub fn default_metrics_collector() -> Result<Arc<dyn ExecutorMetricsCollector>> {
+    PrometheusMetricsCollector::current()
+}
+
+#[cfg(not(feature = "prometheus"))]
+pub fn default_metrics_collector() -> Result<Arc<dyn ExecutorMetricsCollector>> {
+    Ok(Arc::new(LoggingMetricsCollector::default()))
+}
+
+/// Serve the metrics gathered by `collector` on the `/metrics` endpoint of an HTTP
+/// server listening on `addr`, for Prometheus to scrape, along with the usage of the
+/// shuffle fetch limiter of the executor, if any, sampled on every scrape
+#[cfg(feature = "prometheus")]
+pub async fn serve_metrics(
+    addr: std::net::SocketAddr,
+    collector: Arc<dyn ExecutorMetricsCollector>,
+    shuffle_fetch_limiter: Option<Arc<ShuffleFetchLimiter>>,
+) -> Result<()> {
+    use axum::http::{header::CONTENT_TYPE, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use ballista_core::error::BallistaError;
+
+    let routes = axum::Router::new().route(
+        "/metrics",
+        get(move || async move {
+            if let Some(limiter) = &shuffle_fetch_limiter {
+                collector.record_shuffle_fetches(limiter.usage());
+            }
+            match collector.gather_metrics() {
+                Ok(Some((data, content_type))) => {
+                    ([(CONTENT_TYPE, content_type)], data).into_response()
+                }
+                Ok(None) => StatusCode::NO_CONTENT.into_response(),
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Serving executor metrics on http://{addr}/metrics");
+    axum::serve(listener, routes)
+        .await
+        .map_err(|e| BallistaError::General(format!("Metrics server failed: {e}")))
+}
@@ -0,0 +1,222 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::execution_engine::QueryStageExecutor;
+use crate::metrics::ExecutorMetricsCollector;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::ShuffleFetchUsage;
+
+use datafusion::physical_plan::metrics::MetricsSet;
+use once_cell::sync::OnceCell;
+use prometheus::{
+    register_counter_with_registry, register_gauge_with_registry, Counter, Gauge,
+    Registry,
+};
+use prometheus::{Encoder, TextEncoder};
+use std::sync::Arc;
+
+static COLLECTOR: OnceCell<Arc<dyn ExecutorMetricsCollector>> = OnceCell::new();
+
+/// ExecutorMetricsCollector implementation based on Prometheus. By default this will
+/// track 9 metrics:
+/// *executor_task_completed_total* - Counter of successfully executed tasks
+/// *executor_task_failed_total* - Counter of failed tasks
+/// *executor_running_tasks* - Number of tasks currently executing
+/// *executor_shuffle_write_rows_total* - Counter of rows written to shuffle files
+/// *executor_shuffle_write_bytes_total* - Counter of bytes written to shuffle files
+/// *executor_spill_count_total* - Counter of spills of operators to disk
+/// *executor_spilled_bytes_total* - Counter of bytes spilled by operators to disk
+/// *executor_shuffle_fetches_in_flight* - Number of shuffle fetches holding a permit of
+/// the limit of the executor
+/// *executor_shuffle_fetches_waiting* - Number of shuffle fetches waiting for a permit
+pub struct PrometheusMetricsCollector {
+    completed: Counter,
+    failed: Counter,
+    running: Gauge,
+    shuffle_write_rows: Counter,
+    shuffle_write_bytes: Counter,
+    spill_count: Counter,
+    spilled_bytes: Counter,
+    shuffle_fetches_in_flight: Gauge,
+    shuffle_fetches_waiting: Gauge,
+}
+
+impl PrometheusMetricsCollector {
+    pub fn new(registry: &Registry) -> Result<Self> {
+        let completed = register_counter_with_registry!(
+            "executor_task_completed_total",
+            "Counter of successfully executed tasks",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let failed = register_counter_with_registry!(
+            "executor_task_failed_total",
+            "Counter of failed tasks",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let running = register_gauge_with_registry!(
+            "executor_running_tasks",
+            "Number of tasks currently executing",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let shuffle_write_rows = register_counter_with_registry!(
+            "executor_shuffle_write_rows_total",
+            "Counter of rows written to shuffle files",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let shuffle_write_bytes = register_counter_with_registry!(
+            "executor_shuffle_write_bytes_total",
+            "Counter of bytes written to shuffle files",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let spill_count = register_counter_with_registry!(
+            "executor_spill_count_total",
+            "Counter of spills of operators to disk",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let spilled_bytes = register_counter_with_registry!(
+            "executor_spilled_bytes_total",
+            "Counter of bytes spilled by operators to disk",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let shuffle_fetches_in_flight = register_gauge_with_registry!(
+            "executor_shuffle_fetches_in_flight",
+            "Number of shuffle fetches holding a permit of the limit of the executor",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let shuffle_fetches_waiting = register_gauge_with_registry!(
+            "executor_shuffle_fetches_waiting",
+            "Number of shuffle fetches waiting for a permit of the limit of the executor",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        Ok(Self {
+            completed,
+            failed,
+            running,
+            shuffle_write_rows,
+            shuffle_write_bytes,
+            spill_count,
+            spilled_bytes,
+            shuffle_fetches_in_flight,
+            shuffle_fetches_waiting,
+        })
+    }
+
+    pub fn current() -> Result<Arc<dyn ExecutorMetricsCollector>> {
+        COLLECTOR
+            .get_or_try_init(|| {
+                let collector = Self::new(::prometheus::default_registry())?;
+
+                Ok(Arc::new(collector) as Arc<dyn ExecutorMetricsCollector>)
+            })
+            .cloned()
+    }
+}
+
+impl ExecutorMetricsCollector for PrometheusMetricsCollector {
+    fn record_stage(
+        &self,
+        _job_id: &str,
+        _stage_id: usize,
+        _partition: usize,
+        plan: Arc<dyn QueryStageExecutor>,
+    ) {
+        self.running.dec();
+        self.completed.inc();
+
+        let plan_metrics = plan.collect_plan_metrics();
+        let count = |metrics: &MetricsSet, name: &str| {
+            metrics
+                .sum_by_name(name)
+                .map(|value| value.as_usize())
+                .unwrap_or_default() as f64
+        };
+        // The first metrics set is the one of the shuffle writer at the root of the plan
+        if let Some(metrics) = plan_metrics.first() {
+            self.shuffle_write_rows
+                .inc_by(count(metrics, "output_rows"));
+            self.shuffle_write_bytes
+                .inc_by(count(metrics, "output_bytes"));
+        }
+        // Any operator of the plan may spill
+        for metrics in &plan_metrics {
+            self.spill_count.inc_by(count(metrics, "spill_count"));
+            self.spilled_bytes.inc_by(count(metrics, "spilled_bytes"));
+        }
+    }
+
+    fn record_task_started(&self, _job_id: &str, _stage_id: usize, _partition: usize) {
+        self.running.inc();
+    }
+
+    fn record_task_failed(&self, _job_id: &str, _stage_id: usize, _partition: usize) {
+        self.running.dec();
+        self.failed.inc();
+    }
+
+    fn record_shuffle_fetches(&self, usage: ShuffleFetchUsage) {
+        self.shuffle_fetches_in_flight.set(usage.in_flight as f64);
+        self.shuffle_fetches_waiting.set(usage.waiting as f64);
+    }
+
+    fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
+        let encoder = TextEncoder::new();
+
+        let metric_families = prometheus::gather();
+        let mut buffer = vec![];
+        encoder.encode(&metric_families, &mut buffer).map_err(|e| {
+            BallistaError::Internal(format!("Error encoding prometheus metrics: {e:?}"))
+        })?;
+
+        Ok(Some((buffer, encoder.format_type().to_owned())))
+    }
+}
@@ -0,0 +1,645 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Retries of the transient errors of the object stores the tasks of the executor read,
+//! e.g. the 503 responses S3 throttles requests with, which would otherwise fail the
+//! tasks.
+//!
+//! The reads failing with a transient error are retried with an exponential backoff.
+//! Once the requests to an endpoint, e.g. a bucket, are throttled repeatedly, its circuit
+//! breaker opens and, until it cools down, limits the requests in flight to the endpoint
+//! to a few, the tasks backing off together. The retries and the throttled requests of
+//! each task are reported with its metrics, as `object_store_retries` and
+//! `object_store_throttles`.
+
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use datafusion::error::Result as DataFusionResult;
+use datafusion::execution::object_store::ObjectStoreRegistry;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::physical_plan::metrics::{Count, Metric, MetricValue, MetricsSet};
+use futures::stream::BoxStream;
+use log::warn;
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOpts, PutOptions, PutPayload, PutResult,
+};
+use parking_lot::Mutex;
+use tokio::sync::Semaphore;
+use url::Url;
+
+/// Longest backoff between two attempts of a request
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Settings of the retries of the object store reads of the tasks
+#[derive(Debug, Clone)]
+pub struct ObjectStoreRetryConfig {
+    /// Attempts of a failed read beyond the first one
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled on each retry
+    pub backoff: Duration,
+    /// Consecutive throttled requests to an endpoint opening its circuit breaker, 0
+    /// disabling the circuit breakers
+    pub circuit_breaker_threshold: u32,
+    /// Time an open circuit breaker limits the requests to its endpoint for
+    pub circuit_breaker_cooldown: Duration,
+    /// Requests in flight to an endpoint while its circuit breaker is open
+    pub degraded_concurrency: usize,
+}
+
+impl Default for ObjectStoreRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: Duration::from_millis(100),
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown: Duration::from_secs(30),
+            degraded_concurrency: 2,
+        }
+    }
+}
+
+/// Whether an error is the object store throttling requests
+fn is_throttled(e: &object_store::Error) -> bool {
+    let object_store::Error::Generic { source, .. } = e else {
+        return false;
+    };
+    let message = source.to_string().to_lowercase();
+    [
+        "503",
+        "429",
+        "slow down",
+        "slowdown",
+        "too many requests",
+        "throttl",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Whether a request failing with an error may succeed when attempted again
+fn is_transient(e: &object_store::Error) -> bool {
+    let object_store::Error::Generic { source, .. } = e else {
+        return false;
+    };
+    let message = source.to_string().to_lowercase();
+    is_throttled(e)
+        || [
+            "500 internal",
+            "502",
+            "504",
+            "timed out",
+            "connection reset",
+            "connection closed",
+            "broken pipe",
+        ]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Limits the requests to an endpoint once they are throttled repeatedly
+#[derive(Debug)]
+struct CircuitBreaker {
+    endpoint: String,
+    threshold: u32,
+    cooldown: Duration,
+    /// Consecutive throttled requests
+    throttled: AtomicU32,
+    open_until: Mutex<Option<Instant>>,
+    permits: Semaphore,
+}
+
+impl CircuitBreaker {
+    fn new(endpoint: String, config: &ObjectStoreRetryConfig) -> Self {
+        Self {
+            endpoint,
+            threshold: config.circuit_breaker_threshold,
+            cooldown: config.circuit_breaker_cooldown,
+            throttled: AtomicU32::new(0),
+            open_until: Mutex::new(None),
+            permits: Semaphore::new(config.degraded_concurrency.max(1)),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        let mut open_until = self.open_until.lock();
+        match *open_until {
+            Some(until) if until > Instant::now() => true,
+            Some(_) => {
+                *open_until = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn record_throttled(&self) {
+        let throttled = self.throttled.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.threshold == 0 || throttled < self.threshold {
+            return;
+        }
+        let mut open_until = self.open_until.lock();
+        if open_until.is_none() {
+            warn!(
+                "Object store {} throttled {throttled} requests in a row, limiting the requests to it for {:?}",
+                self.endpoint, self.cooldown
+            );
+        }
+        *open_until = Some(Instant::now() + self.cooldown);
+    }
+
+    fn record_success(&self) {
+        self.throttled.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Retries of the object store reads of the tasks of the executor, and the circuit
+/// breakers of the endpoints they read from
+#[derive(Debug)]
+pub struct ObjectStoreRetry {
+    config: ObjectStoreRetryConfig,
+    breakers: DashMap<String, Arc<CircuitBreaker>>,
+}
+
+impl ObjectStoreRetry {
+    pub fn new(config: ObjectStoreRetryConfig) -> Self {
+        Self {
+            config,
+            breakers: DashMap::new(),
+        }
+    }
+
+    fn breaker(&self, url: &Url) -> Arc<CircuitBreaker> {
+        let endpoint = format!("{}://{}", url.scheme(), url.authority());
+        self.breakers
+            .entry(endpoint.clone())
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(endpoint, &self.config)))
+            .clone()
+    }
+
+    /// Whether the circuit breaker of the endpoint of `url` is open
+    pub fn is_degraded(&self, url: &Url) -> bool {
+        self.breaker(url).is_open()
+    }
+}
+
+/// The retries and throttled requests of the object store reads of a task
+#[derive(Debug, Default)]
+pub struct ObjectStoreTaskMetrics {
+    retries: AtomicU64,
+    throttles: AtomicU64,
+}
+
+impl ObjectStoreTaskMetrics {
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub fn throttles(&self) -> u64 {
+        self.throttles.load(Ordering::Relaxed)
+    }
+
+    /// Report the retries and throttles of the task of the partition `partition` with
+    /// the metrics of the root of its plan, the first of `plan_metrics`
+    pub fn report(&self, plan_metrics: &mut [MetricsSet], partition: usize) {
+        let Some(root) = plan_metrics.first_mut() else {
+            return;
+        };
+        for (name, value) in [
+            ("object_store_retries", self.retries()),
+            ("object_store_throttles", self.throttles()),
+        ] {
+            let count = Count::new();
+            count.add(value as usize);
+            root.push(Arc::new(Metric::new(
+                MetricValue::Count {
+                    name: name.into(),
+                    count,
+                },
+                Some(partition),
+            )));
+        }
+    }
+}
+
+/// Object store retrying the transient errors of the reads of a task
+#[derive(Debug)]
+pub struct RetryingObjectStore {
+    inner: Arc<dyn ObjectStore>,
+    config: ObjectStoreRetryConfig,
+    breaker: Arc<CircuitBreaker>,
+    metrics: Arc<ObjectStoreTaskMetrics>,
+}
+
+impl RetryingObjectStore {
+    async fn retry<T, F, Fut>(&self, request: F) -> object_store::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = object_store::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = if self.breaker.is_open() {
+                let _permit = self.breaker.permits.acquire().await;
+                request().await
+            } else {
+                request().await
+            };
+            let e = match result {
+                Ok(value) => {
+                    self.breaker.record_success();
+                    return Ok(value);
+                }
+                Err(e) => e,
+            };
+            if is_throttled(&e) {
+                self.metrics.throttles.fetch_add(1, Ordering::Relaxed);
+                self.breaker.record_throttled();
+            }
+            if attempt >= self.config.max_retries || !is_transient(&e) {
+                return Err(e);
+            }
+
+            let backoff = self
+                .config
+                .backoff
+                .saturating_mul(1 << attempt.min(16))
+                .min(MAX_BACKOFF);
+            warn!(
+                "Retrying object store read from {} in {backoff:?} after: {e}",
+                self.breaker.endpoint
+            );
+            self.metrics.retries.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl Display for RetryingObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RetryingObjectStore({})", self.inner)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for RetryingObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        self.retry(move || self.inner.get_opts(location, options.clone()))
+            .await
+    }
+
+    async fn get_range(
+        &self,
+        location: &Path,
+        range: Range<usize>,
+    ) -> object_store::Result<Bytes> {
+        self.retry(move || self.inner.get_range(location, range.clone()))
+            .await
+    }
+
+    async fn get_ranges(
+        &self,
+        location: &Path,
+        ranges: &[Range<usize>],
+    ) -> object_store::Result<Vec<Bytes>> {
+        self.retry(move || self.inner.get_ranges(location, ranges))
+            .await
+    }
+
+    async fn head(&self, location: &Path) -> object_store::Result<ObjectMeta> {
+        self.retry(move || self.inner.head(location)).await
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(
+        &self,
+        prefix: Option<&Path>,
+    ) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    async fn list_with_delimiter(
+        &self,
+        prefix: Option<&Path>,
+    ) -> object_store::Result<ListResult> {
+        self.retry(move || self.inner.list_with_delimiter(prefix))
+            .await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(
+        &self,
+        from: &Path,
+        to: &Path,
+    ) -> object_store::Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+/// Object store registry of a task whose stores retry the transient errors of their
+/// reads, other than the local file system
+#[derive(Debug)]
+pub struct RetryingObjectStoreRegistry {
+    inner: Arc<dyn ObjectStoreRegistry>,
+    retry: Arc<ObjectStoreRetry>,
+    metrics: Arc<ObjectStoreTaskMetrics>,
+}
+
+impl RetryingObjectStoreRegistry {
+    pub fn new(
+        inner: Arc<dyn ObjectStoreRegistry>,
+        retry: Arc<ObjectStoreRetry>,
+        metrics: Arc<ObjectStoreTaskMetrics>,
+    ) -> Self {
+        Self {
+            inner,
+            retry,
+            metrics,
+        }
+    }
+}
+
+impl ObjectStoreRegistry for RetryingObjectStoreRegistry {
+    fn register_store(
+        &self,
+        url: &Url,
+        store: Arc<dyn ObjectStore>,
+    ) -> Option<Arc<dyn ObjectStore>> {
+        self.inner.register_store(url, store)
+    }
+
+    fn get_store(&self, url: &Url) -> DataFusionResult<Arc<dyn ObjectStore>> {
+        let store = self.inner.get_store(url)?;
+        if url.scheme() == "file" {
+            return Ok(store);
+        }
+        Ok(Arc::new(RetryingObjectStore {
+            inner: store,
+            config: self.retry.config.clone(),
+            breaker: self.retry.breaker(url),
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+/// The runtime of a task whose object stores retry the transient errors of their reads
+/// with `retry`, recording the retries and throttles of the task in `metrics`
+pub fn retrying_task_runtime(
+    runtime: &RuntimeEnv,
+    retry: Arc<ObjectStoreRetry>,
+    metrics: Arc<ObjectStoreTaskMetrics>,
+) -> Arc<RuntimeEnv> {
+    Arc::new(RuntimeEnv {
+        memory_pool: runtime.memory_pool.clone(),
+        disk_manager: runtime.disk_manager.clone(),
+        cache_manager: runtime.cache_manager.clone(),
+        object_store_registry: Arc::new(RetryingObjectStoreRegistry::new(
+            runtime.object_store_registry.clone(),
+            retry,
+            metrics,
+        )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Fails the first reads with the given error
+    #[derive(Debug)]
+    struct FlakyStore {
+        inner: InMemory,
+        failures: AtomicUsize,
+        error: &'static str,
+    }
+
+    impl FlakyStore {
+        fn error(&self) -> object_store::Result<()> {
+            let failed = self
+                .failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |failures| {
+                    failures.checked_sub(1)
+                })
+                .is_ok();
+            if failed {
+                return Err(object_store::Error::Generic {
+                    store: "S3",
+                    source: self.error.into(),
+                });
+            }
+            Ok(())
+        }
+    }
+
+    impl Display for FlakyStore {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "FlakyStore")
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for FlakyStore {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            payload: PutPayload,
+            opts: PutOptions,
+        ) -> object_store::Result<PutResult> {
+            self.inner.put_opts(location, payload, opts).await
+        }
+
+        async fn put_multipart_opts(
+            &self,
+            location: &Path,
+            opts: PutMultipartOpts,
+        ) -> object_store::Result<Box<dyn MultipartUpload>> {
+            self.inner.put_multipart_opts(location, opts).await
+        }
+
+        async fn get_opts(
+            &self,
+            location: &Path,
+            options: GetOptions,
+        ) -> object_store::Result<GetResult> {
+            self.error()?;
+            self.inner.get_opts(location, options).await
+        }
+
+        async fn delete(&self, location: &Path) -> object_store::Result<()> {
+            self.inner.delete(location).await
+        }
+
+        fn list(
+            &self,
+            prefix: Option<&Path>,
+        ) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+            self.inner.list(prefix)
+        }
+
+        async fn list_with_delimiter(
+            &self,
+            prefix: Option<&Path>,
+        ) -> object_store::Result<ListResult> {
+            self.inner.list_with_delimiter(prefix).await
+        }
+
+        async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+            self.inner.copy(from, to).await
+        }
+
+        async fn copy_if_not_exists(
+            &self,
+            from: &Path,
+            to: &Path,
+        ) -> object_store::Result<()> {
+            self.inner.copy_if_not_exists(from, to).await
+        }
+    }
+
+    async fn flaky_store(
+        failures: usize,
+        error: &'static str,
+        retry: &Arc<ObjectStoreRetry>,
+        metrics: &Arc<ObjectStoreTaskMetrics>,
+    ) -> object_store::Result<Arc<dyn ObjectStore>> {
+        let flaky = FlakyStore {
+            inner: InMemory::new(),
+            failures: AtomicUsize::new(failures),
+            error,
+        };
+        flaky
+            .inner
+            .put(
+                &Path::from("file"),
+                Bytes::from_static(b"0123456789").into(),
+            )
+            .await?;
+        let registry = RetryingObjectStoreRegistry::new(
+            Arc::new(
+                datafusion::execution::object_store::DefaultObjectStoreRegistry::new(),
+            ),
+            retry.clone(),
+            metrics.clone(),
+        );
+        let url = Url::parse("s3://bucket").unwrap();
+        registry.register_store(&url, Arc::new(flaky));
+        Ok(registry.get_store(&url).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() -> object_store::Result<()> {
+        let retry = Arc::new(ObjectStoreRetry::new(ObjectStoreRetryConfig {
+            backoff: Duration::from_millis(1),
+            ..Default::default()
+        }));
+        let metrics = Arc::new(ObjectStoreTaskMetrics::default());
+
+        let throttled = "Server returned non-2xx status code: 503 Service Unavailable";
+        let store = flaky_store(2, throttled, &retry, &metrics).await?;
+        let data = store.get_range(&Path::from("file"), 2..4).await?;
+        assert_eq!(data, Bytes::from_static(b"23"));
+        assert_eq!(2, metrics.retries());
+        assert_eq!(2, metrics.throttles());
+
+        // the retries are bounded
+        let store = flaky_store(4, throttled, &retry, &metrics).await?;
+        assert!(store.get_range(&Path::from("file"), 2..4).await.is_err());
+        assert_eq!(5, metrics.retries());
+
+        // and only transient errors are retried
+        let store = flaky_store(1, "Access Denied", &retry, &metrics).await?;
+        assert!(store.head(&Path::from("file")).await.is_err());
+        assert_eq!(5, metrics.retries());
+
+        let mut plan_metrics = vec![MetricsSet::new()];
+        metrics.report(&mut plan_metrics, 0);
+        assert_eq!(
+            Some(5),
+            plan_metrics[0]
+                .sum_by_name("object_store_retries")
+                .map(|m| m.as_usize())
+        );
+        assert_eq!(
+            Some(6),
+            plan_metrics[0]
+                .sum_by_name("object_store_throttles")
+                .map(|m| m.as_usize())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() -> object_store::Result<()> {
+        let retry = Arc::new(ObjectStoreRetry::new(ObjectStoreRetryConfig {
+            backoff: Duration::from_millis(1),
+            circuit_breaker_threshold: 3,
+            ..Default::default()
+        }));
+        let metrics = Arc::new(ObjectStoreTaskMetrics::default());
+        let url = Url::parse("s3://bucket").unwrap();
+
+        let store = flaky_store(2, "SlowDown", &retry, &metrics).await?;
+        store.get_range(&Path::from("file"), 0..1).await?;
+        // the success of the read resets the consecutive throttled requests
+        assert!(!retry.is_degraded(&url));
+
+        let store = flaky_store(3, "SlowDown", &retry, &metrics).await?;
+        store.get_range(&Path::from("file"), 0..1).await?;
+        assert!(retry.is_degraded(&url));
+        assert!(!retry.is_degraded(&Url::parse("s3://other-bucket").unwrap()));
+        Ok(())
+    }
+}
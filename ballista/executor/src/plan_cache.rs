@@ -0,0 +1,194 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cache of the plans the scheduler dispatches with tasks by reference, i.e. by
+//! their hash, so that each plan is fetched once rather than sent with every task.
+
+use ballista_core::error::BallistaError;
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::protobuf::{ExecutorRegistration, GetTaskPlanParams};
+use ballista_core::utils::GrpcChannel;
+use bytes::Bytes;
+use log::debug;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default maximum number of plans kept by a [TaskPlanCache]
+pub const DEFAULT_MAX_CACHED_PLANS: usize = 32;
+
+/// Size of the chunks in which plans are fetched from the scheduler, well below the
+/// default gRPC message size limits
+const TASK_PLAN_CHUNK_BYTES: u64 = 1024 * 1024;
+
+/// Bounded cache of encoded task plans, keyed by job ID and plan hash. The least
+/// recently fetched plans are dropped first.
+#[derive(Debug)]
+pub struct TaskPlanCache {
+    max_plans: AtomicUsize,
+    plans: Mutex<VecDeque<CachedPlan>>,
+}
+
+#[derive(Debug)]
+struct CachedPlan {
+    job_id: String,
+    plan_hash: String,
+    plan: Bytes,
+}
+
+impl CachedPlan {
+    fn is(&self, job_id: &str, plan_hash: &str) -> bool {
+        self.job_id == job_id && self.plan_hash == plan_hash
+    }
+}
+
+impl Default for TaskPlanCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CACHED_PLANS)
+    }
+}
+
+impl TaskPlanCache {
+    pub fn new(max_plans: usize) -> Self {
+        Self {
+            max_plans: AtomicUsize::new(max_plans),
+            plans: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Change the maximum number of cached plans, dropping the least recently fetched
+    /// plans beyond it
+    pub fn set_max_plans(&self, max_plans: usize) {
+        self.max_plans.store(max_plans, Ordering::Relaxed);
+        let mut plans = self.plans.lock();
+        while plans.len() > max_plans {
+            plans.pop_front();
+        }
+    }
+
+    /// Get the cached plan of a job with the given hash
+    pub fn get(&self, job_id: &str, plan_hash: &str) -> Option<Bytes> {
+        self.plans
+            .lock()
+            .iter()
+            .find(|cached| cached.is(job_id, plan_hash))
+            .map(|cached| cached.plan.clone())
+    }
+
+    /// Cache the plan of a job with the given hash
+    pub fn insert(&self, job_id: &str, plan_hash: &str, plan: Bytes) {
+        let max_plans = self.max_plans.load(Ordering::Relaxed);
+        if max_plans == 0 {
+            return;
+        }
+
+        let mut plans = self.plans.lock();
+        if plans.iter().any(|cached| cached.is(job_id, plan_hash)) {
+            return;
+        }
+        if plans.len() >= max_plans {
+            plans.pop_front();
+        }
+        plans.push_back(CachedPlan {
+            job_id: job_id.to_owned(),
+            plan_hash: plan_hash.to_owned(),
+            plan,
+        });
+    }
+
+    /// Drop the cached plans of a job
+    pub fn remove_job(&self, job_id: &str) {
+        self.plans.lock().retain(|cached| cached.job_id != job_id);
+    }
+
+    /// Fill in the plan of a task dispatched by reference, i.e. with an empty `plan`
+    /// and its `plan_hash`, from the cache or else from the scheduler, in chunks of
+    /// [TASK_PLAN_CHUNK_BYTES]. Plans dispatched inline are left as they are.
+    pub async fn resolve_plan(
+        &self,
+        scheduler: &mut SchedulerGrpcClient<GrpcChannel>,
+        executor: &ExecutorRegistration,
+        job_id: &str,
+        plan_hash: &str,
+        plan: &mut Bytes,
+    ) -> Result<(), BallistaError> {
+        if !plan.is_empty() || plan_hash.is_empty() {
+            return Ok(());
+        }
+
+        let cached_plan = match self.get(job_id, plan_hash) {
+            Some(cached_plan) => cached_plan,
+            None => {
+                debug!("Fetching plan {plan_hash} of job {job_id} from the scheduler");
+                let mut fetched_plan = Vec::new();
+                loop {
+                    let chunk = scheduler
+                        .get_task_plan(GetTaskPlanParams {
+                            job_id: job_id.to_owned(),
+                            plan_hash: plan_hash.to_owned(),
+                            offset: fetched_plan.len() as u64,
+                            max_bytes: TASK_PLAN_CHUNK_BYTES,
+                            executor_id: executor.id.clone(),
+                            registration_token: executor.registration_token.clone(),
+                        })
+                        .await?
+                        .into_inner();
+                    let done = chunk.plan.is_empty()
+                        || fetched_plan.len() as u64 + chunk.plan.len() as u64
+                            >= chunk.total_bytes;
+                    fetched_plan.extend_from_slice(&chunk.plan);
+                    if done {
+                        break;
+                    }
+                }
+                let fetched_plan = Bytes::from(fetched_plan);
+                self.insert(job_id, plan_hash, fetched_plan.clone());
+                fetched_plan
+            }
+        };
+        *plan = cached_plan;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_plan_cache() {
+        let cache = TaskPlanCache::new(2);
+        cache.insert("job1", "a", Bytes::from(vec![1]));
+        cache.insert("job1", "b", Bytes::from(vec![2]));
+        cache.insert("job2", "a", Bytes::from(vec![3]));
+
+        // The first plan was dropped to make room for the third one
+        assert_eq!(None, cache.get("job1", "a"));
+        assert_eq!(Some(Bytes::from(vec![2])), cache.get("job1", "b"));
+        assert_eq!(Some(Bytes::from(vec![3])), cache.get("job2", "a"));
+
+        cache.remove_job("job2");
+        assert_eq!(None, cache.get("job2", "a"));
+        assert_eq!(Some(Bytes::from(vec![2])), cache.get("job1", "b"));
+
+        cache.insert("job2", "c", Bytes::from(vec![4]));
+        cache.set_max_plans(1);
+        assert_eq!(None, cache.get("job1", "b"));
+        assert_eq!(Some(Bytes::from(vec![4])), cache.get("job2", "c"));
+    }
+}
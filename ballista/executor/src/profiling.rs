@@ -0,0 +1,105 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! CPU profiling of a running executor, producing flamegraphs or pprof profiles
+
+use crate::executor::Executor;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::{GetProfileParams, ProfileFormat};
+use pprof::protos::Message;
+use std::time::{Duration, Instant};
+
+/// Maximum duration of a profile. Profiles are served through a unary rpc,
+/// so they must complete within the grpc server timeout.
+pub const MAX_PROFILE_SECONDS: u32 = 15;
+
+const DEFAULT_PROFILE_SECONDS: u32 = 10;
+
+const DEFAULT_PROFILE_FREQUENCY: u32 = 99;
+
+/// How often to check whether the task a profile is scoped to is running
+const TASK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Sample the executor process and encode the result in the requested format.
+///
+/// If the parameters name a task, sampling only runs while that task is executing,
+/// waiting at most the requested duration for it to start and complete. This blocks
+/// the calling thread for the duration of the profile.
+pub fn profile(executor: &Executor, params: &GetProfileParams) -> Result<Vec<u8>> {
+    let seconds = match params.seconds {
+        0 => DEFAULT_PROFILE_SECONDS,
+        seconds => seconds.min(MAX_PROFILE_SECONDS),
+    };
+    let frequency = match params.frequency {
+        0 => DEFAULT_PROFILE_FREQUENCY,
+        frequency => frequency,
+    };
+    let deadline = Instant::now() + Duration::from_secs(seconds as u64);
+
+    let task_running = || {
+        params
+            .task_id
+            .map(|task_id| executor.is_task_running(&params.job_id, task_id as usize))
+    };
+
+    while task_running() == Some(false) {
+        if Instant::now() >= deadline {
+            return Err(BallistaError::General(format!(
+                "Task {}/{} did not run within {seconds}s",
+                params.job_id,
+                params.task_id.unwrap_or_default()
+            )));
+        }
+        std::thread::sleep(TASK_POLL_INTERVAL);
+    }
+
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(frequency as i32)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(to_ballista_error)?;
+
+    match task_running() {
+        Some(_) => {
+            while task_running() == Some(true) && Instant::now() < deadline {
+                std::thread::sleep(TASK_POLL_INTERVAL);
+            }
+        }
+        None => std::thread::sleep(deadline.saturating_duration_since(Instant::now())),
+    }
+
+    let report = guard.report().build().map_err(to_ballista_error)?;
+
+    let mut buf = vec![];
+    match ProfileFormat::try_from(params.format).unwrap_or(ProfileFormat::Flamegraph) {
+        ProfileFormat::Flamegraph => {
+            report.flamegraph(&mut buf).map_err(to_ballista_error)?;
+        }
+        ProfileFormat::Pprof => {
+            let profile = report.pprof().map_err(to_ballista_error)?;
+            profile
+                .encode(&mut buf)
+                .map_err(|e| BallistaError::General(e.to_string()))?;
+        }
+    }
+
+    Ok(buf)
+}
+
+fn to_ballista_error(e: pprof::Error) -> BallistaError {
+    BallistaError::General(format!("Failed to profile executor: {e}"))
+}
@@ -0,0 +1,228 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Utilization of the host of the executor, i.e. its CPU, memory, disk and network,
+//! reported to the scheduler with the heartbeats.
+//!
+//! The CPU and network usage are averaged between two heartbeats, from the counters of
+//! `/proc`. Hosts without `/proc` report no utilization.
+
+use std::time::Instant;
+
+use ballista_core::serde::protobuf::ResourceUsage;
+use parking_lot::Mutex;
+
+/// Samples the utilization of the host, averaging the counters since the previous sample
+#[derive(Debug, Default)]
+pub struct ResourceSampler {
+    previous: Mutex<Option<Counters>>,
+}
+
+/// Cumulative counters of the host at some point in time
+#[derive(Debug, Clone, Copy)]
+struct Counters {
+    at: Instant,
+    cpu: CpuTimes,
+    network: NetworkBytes,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct NetworkBytes {
+    received: u64,
+    sent: u64,
+}
+
+impl ResourceSampler {
+    /// The utilization since the previous sample, and of the file system holding
+    /// `work_dir`. The CPU and network usage of the first sample are 0
+    pub fn sample(&self, work_dir: &str) -> Option<ResourceUsage> {
+        let cpu = parse_cpu_times(&std::fs::read_to_string("/proc/stat").ok()?)?;
+        let network = std::fs::read_to_string("/proc/net/dev")
+            .map(|dev| parse_network_bytes(&dev))
+            .unwrap_or_default();
+        let (used_memory_bytes, total_memory_bytes) =
+            std::fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|meminfo| parse_memory(&meminfo))
+                .unwrap_or_default();
+        let (used_disk_bytes, total_disk_bytes) =
+            disk_usage(work_dir).unwrap_or_default();
+
+        let now = Counters {
+            at: Instant::now(),
+            cpu,
+            network,
+        };
+        let mut usage = ResourceUsage {
+            used_memory_bytes,
+            total_memory_bytes,
+            used_disk_bytes,
+            total_disk_bytes,
+            ..Default::default()
+        };
+        if let Some(previous) = self.previous.lock().replace(now) {
+            let total = now.cpu.total.saturating_sub(previous.cpu.total);
+            if total > 0 {
+                usage.cpu_usage =
+                    now.cpu.busy.saturating_sub(previous.cpu.busy) as f64 / total as f64;
+            }
+            let seconds = now.at.duration_since(previous.at).as_secs_f64();
+            if seconds > 0.0 {
+                usage.network_received_bytes_per_second =
+                    (now.network
+                        .received
+                        .saturating_sub(previous.network.received)
+                        as f64
+                        / seconds) as u64;
+                usage.network_sent_bytes_per_second =
+                    (now.network.sent.saturating_sub(previous.network.sent) as f64
+                        / seconds) as u64;
+            }
+        }
+        Some(usage)
+    }
+}
+
+/// The busy and total CPU time of the host, from the first line of `/proc/stat`
+fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let times: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .map(|time| time.parse().ok())
+        .collect::<Option<_>>()?;
+    if times.len() < 4 {
+        return None;
+    }
+    let total: u64 = times.iter().sum();
+    // idle and iowait
+    let idle = times[3] + times.get(4).copied().unwrap_or_default();
+    Some(CpuTimes {
+        busy: total - idle,
+        total,
+    })
+}
+
+/// The used and total memory of the host in bytes, from `/proc/meminfo`
+fn parse_memory(meminfo: &str) -> Option<(u64, u64)> {
+    let kilobytes = |key: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|value| value.split_whitespace().next())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+    let total = kilobytes("MemTotal:")? * 1024;
+    let available = kilobytes("MemAvailable:")? * 1024;
+    Some((total.saturating_sub(available), total))
+}
+
+/// The bytes received and sent by the interfaces of the host but the loopback, from
+/// `/proc/net/dev`
+fn parse_network_bytes(dev: &str) -> NetworkBytes {
+    dev.lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(interface, _)| interface.trim() != "lo")
+        .filter_map(|(_, counters)| {
+            let counters: Vec<&str> = counters.split_whitespace().collect();
+            Some(NetworkBytes {
+                received: counters.first()?.parse().ok()?,
+                sent: counters.get(8)?.parse().ok()?,
+            })
+        })
+        .fold(NetworkBytes::default(), |sum, bytes| NetworkBytes {
+            received: sum.received + bytes.received,
+            sent: sum.sent + bytes.sent,
+        })
+}
+
+/// The used and total bytes of the file system holding `path`
+#[cfg(unix)]
+fn disk_usage(path: &str) -> Option<(u64, u64)> {
+    let path = std::ffi::CString::new(path).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block_size = stat.f_frsize as u64;
+    let total = stat.f_blocks as u64 * block_size;
+    let free = stat.f_bfree as u64 * block_size;
+    Some((total.saturating_sub(free), total))
+}
+
+#[cfg(not(unix))]
+fn disk_usage(_path: &str) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc() {
+        let stat = "cpu  100 10 50 800 40 0 0 0 0 0\ncpu0 50 5 25 400 20 0 0 0 0 0\n";
+        assert_eq!(
+            Some(CpuTimes {
+                busy: 160,
+                total: 1000
+            }),
+            parse_cpu_times(stat)
+        );
+        assert_eq!(None, parse_cpu_times("intr 1 2 3\n"));
+
+        let meminfo =
+            "MemTotal:       16000 kB\nMemFree:         2000 kB\nMemAvailable:    6000 kB\n";
+        assert_eq!(Some((10000 * 1024, 16000 * 1024)), parse_memory(meminfo));
+
+        let dev = "Inter-|   Receive                            |  Transmit\n \
+                   face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n    \
+                   lo: 500 5 0 0 0 0 0 0 500 5 0 0 0 0 0 0\n  \
+                   eth0: 1000 10 0 0 0 0 0 0 2000 20 0 0 0 0 0 0\n  \
+                   eth1: 300 3 0 0 0 0 0 0 400 4 0 0 0 0 0 0\n";
+        assert_eq!(
+            NetworkBytes {
+                received: 1300,
+                sent: 2400
+            },
+            parse_network_bytes(dev)
+        );
+    }
+
+    #[test]
+    fn test_resource_sampler() {
+        let sampler = ResourceSampler::default();
+        let work_dir = std::env::temp_dir();
+        let Some(first) = sampler.sample(work_dir.to_str().unwrap()) else {
+            // no /proc on this host
+            return;
+        };
+        assert_eq!(0.0, first.cpu_usage);
+        assert!(first.total_memory_bytes > 0);
+        assert!(first.used_memory_bytes <= first.total_memory_bytes);
+        assert!(first.used_disk_bytes <= first.total_disk_bytes);
+
+        let second = sampler.sample(work_dir.to_str().unwrap()).unwrap();
+        assert!((0.0..=1.0).contains(&second.cpu_usage));
+    }
+}
@@ -0,0 +1,89 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Leases of the files of splittable scans, asked to the scheduler of the tasks.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ballista_core::execution_plans::{ScanFileLeaser, ScanFileLeases};
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::protobuf::{ExecutorRegistration, LeaseScanFileParams};
+use ballista_core::utils::GrpcChannel;
+use datafusion::error::{DataFusionError, Result};
+
+/// Leases the files of the splittable scans of the tasks launched by a scheduler
+#[derive(Clone)]
+pub struct SchedulerScanFileLeaser {
+    scheduler: SchedulerGrpcClient<GrpcChannel>,
+    executor_id: String,
+    registration_token: String,
+}
+
+impl SchedulerScanFileLeaser {
+    /// Leases files for the tasks of `executor`, which presents its registration
+    /// token to the scheduler
+    pub fn new(
+        scheduler: SchedulerGrpcClient<GrpcChannel>,
+        executor: &ExecutorRegistration,
+    ) -> Self {
+        Self {
+            scheduler,
+            executor_id: executor.id.clone(),
+            registration_token: executor.registration_token.clone(),
+        }
+    }
+
+    /// The leases to put in the config of the tasks of the scheduler
+    pub fn into_leases(self) -> Arc<ScanFileLeases> {
+        Arc::new(ScanFileLeases(Arc::new(self)))
+    }
+}
+
+impl Debug for SchedulerScanFileLeaser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SchedulerScanFileLeaser")
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl ScanFileLeaser for SchedulerScanFileLeaser {
+    async fn lease_file(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        partition_id: usize,
+        position: usize,
+    ) -> Result<Option<usize>> {
+        let result = self
+            .scheduler
+            .clone()
+            .lease_scan_file(LeaseScanFileParams {
+                job_id: job_id.to_owned(),
+                stage_id: stage_id as u32,
+                partition_id: partition_id as u32,
+                position: position as u64,
+                executor_id: self.executor_id.clone(),
+                registration_token: self.registration_token.clone(),
+            })
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        Ok(result.into_inner().file.map(|file| file as usize))
+    }
+}
@@ -0,0 +1,470 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Private directories holding the spill files of the tasks, optionally encrypted
+//! with a key per job.
+
+use std::collections::HashMap;
+use std::fs::DirBuilder;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use datafusion::error::Result;
+use datafusion::execution::disk_manager::{DiskManager, DiskManagerConfig};
+use datafusion::execution::runtime_env::RuntimeEnv;
+use log::warn;
+use parking_lot::Mutex;
+
+/// Directory of the work dir holding the spill dirs of the running tasks. It is
+/// hidden, so that the eviction of the job data never removes it
+pub const SPILL_DIR: &str = ".spill";
+
+/// Directory the sorts, joins and aggregations of a task spill to, only accessible to
+/// the user of the executor and removed with its spill files once the task ends
+#[derive(Debug)]
+pub struct TaskSpillDir {
+    path: PathBuf,
+    /// Encryption of the dir, with the key of the job of the task, if enabled
+    encryption: Option<(Arc<SpillEncryption>, String)>,
+}
+
+impl TaskSpillDir {
+    /// Create the spill dir `name` of a task in `work_dir`, or take it over if it
+    /// already exists, e.g. left by a previous attempt of the task, making it private
+    pub fn create(work_dir: &str, name: &str) -> io::Result<Self> {
+        let path = Path::new(work_dir).join(SPILL_DIR).join(name);
+        create_private_dir(&path)?;
+        Ok(Self {
+            path,
+            encryption: None,
+        })
+    }
+
+    /// Create the spill dir `name` of a task of the job `job_id` in the work dir of
+    /// `encryption`, encrypted with the key of the job. An existing dir, e.g. left by a
+    /// previous attempt of the task, is removed first, as only empty dirs can be
+    /// encrypted
+    pub fn create_encrypted(
+        encryption: Arc<SpillEncryption>,
+        name: &str,
+        job_id: &str,
+    ) -> io::Result<Self> {
+        let path = encryption.spill_dir.join(name);
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        create_private_dir(&path)?;
+        // the dir is removed if it can't be encrypted
+        let mut spill_dir = Self {
+            path,
+            encryption: None,
+        };
+        encryption.encrypt(job_id, &spill_dir.path)?;
+        spill_dir.encryption = Some((encryption, job_id.to_owned()));
+        Ok(spill_dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The runtime of the task, spilling to this dir instead of the dirs of `runtime`.
+    /// Runtimes which can't spill are left as they are
+    pub fn runtime(&self, runtime: &RuntimeEnv) -> Result<Arc<RuntimeEnv>> {
+        let disk_manager = if runtime.disk_manager.tmp_files_enabled() {
+            DiskManager::try_new(DiskManagerConfig::NewSpecified(vec![self
+                .path
+                .clone()]))?
+        } else {
+            runtime.disk_manager.clone()
+        };
+        Ok(Arc::new(RuntimeEnv {
+            memory_pool: runtime.memory_pool.clone(),
+            disk_manager,
+            cache_manager: runtime.cache_manager.clone(),
+            object_store_registry: runtime.object_store_registry.clone(),
+        }))
+    }
+}
+
+impl Drop for TaskSpillDir {
+    fn drop(&mut self) {
+        match std::fs::remove_dir_all(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not remove the spill dir {:?}: {e}", self.path),
+        }
+        if let Some((encryption, job_id)) = &self.encryption {
+            encryption.release(job_id);
+        }
+    }
+}
+
+/// Create `path` and its missing parents with mode `0700`, or make it private if it
+/// already exists
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(path)?;
+    // the mode is only applied to the dirs created above
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// Encryption of the spill dirs of the tasks with a key per job, through the
+/// filesystem-level encryption of Linux, fscrypt, which DataFusion then writes and
+/// reads the spill files through. The key of a job is random, and only held by the
+/// kernel while the job has tasks running on the executor: it is removed along with
+/// the last spill dir of the job, leaving the spill files of the job unreadable, e.g.
+/// on a disk reused or stolen before they were overwritten.
+///
+/// The work dir must be on a filesystem with encryption enabled, e.g. ext4 formatted
+/// with the `encrypt` feature, on Linux 5.4 or later.
+#[derive(Debug)]
+pub struct SpillEncryption {
+    /// Dir holding the spill dirs, through which the keys are added to the filesystem
+    spill_dir: PathBuf,
+    /// Identifiers of the keys of the jobs with spill dirs, with their number of dirs
+    keys: Mutex<HashMap<String, (fscrypt::KeyIdentifier, usize)>>,
+}
+
+impl SpillEncryption {
+    /// Encryption of the spill dirs in `work_dir`, failing unless its filesystem
+    /// supports encryption, which is checked by adding and removing a key
+    pub fn try_new(work_dir: &str) -> io::Result<Self> {
+        let spill_dir = Path::new(work_dir).join(SPILL_DIR);
+        create_private_dir(&spill_dir)?;
+        let key = fscrypt::add_key(&spill_dir)?;
+        fscrypt::remove_key(&spill_dir, &key)?;
+        Ok(Self {
+            spill_dir,
+            keys: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Encrypt the empty dir `dir` with the key of the job `job_id`, added to the
+    /// filesystem unless the job has other spill dirs
+    fn encrypt(&self, job_id: &str, dir: &Path) -> io::Result<()> {
+        let mut keys = self.keys.lock();
+        let key = match keys.get(job_id) {
+            Some((key, _)) => *key,
+            None => fscrypt::add_key(&self.spill_dir)?,
+        };
+        if let Err(e) = fscrypt::set_policy(dir, &key) {
+            if !keys.contains_key(job_id) {
+                self.remove_key(job_id, &key);
+            }
+            return Err(e);
+        }
+        keys.entry(job_id.to_owned()).or_insert((key, 0)).1 += 1;
+        Ok(())
+    }
+
+    /// Release the key of the job `job_id` for a spill dir removed, removing it from
+    /// the filesystem once the job has no other spill dirs
+    fn release(&self, job_id: &str) {
+        let mut keys = self.keys.lock();
+        let Some((key, dirs)) = keys.get_mut(job_id) else {
+            return;
+        };
+        *dirs -= 1;
+        if *dirs == 0 {
+            let key = *key;
+            keys.remove(job_id);
+            self.remove_key(job_id, &key);
+        }
+    }
+
+    fn remove_key(&self, job_id: &str, key: &fscrypt::KeyIdentifier) {
+        if let Err(e) = fscrypt::remove_key(&self.spill_dir, key) {
+            warn!("Could not remove the spill key of job {job_id}: {e}");
+        }
+    }
+}
+
+/// The ioctls of fscrypt, see <https://docs.kernel.org/filesystems/fscrypt.html>
+#[cfg(target_os = "linux")]
+mod fscrypt {
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::fd::AsRawFd;
+    use std::path::Path;
+
+    /// Identifier of a key added to a filesystem, derived from the key by the kernel
+    pub type KeyIdentifier = [u8; 16];
+
+    const FS_IOC_SET_ENCRYPTION_POLICY: u32 = 0x800c6613;
+    const FS_IOC_ADD_ENCRYPTION_KEY: u32 = 0xc0506617;
+    const FS_IOC_REMOVE_ENCRYPTION_KEY: u32 = 0xc0406618;
+
+    const FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER: u32 = 2;
+    const FSCRYPT_POLICY_V2: u8 = 2;
+    const FSCRYPT_MODE_AES_256_XTS: u8 = 1;
+    const FSCRYPT_MODE_AES_256_CTS: u8 = 4;
+    const FSCRYPT_POLICY_FLAGS_PAD_32: u8 = 3;
+    /// Size of the keys of AES-256-XTS
+    const KEY_SIZE: usize = 64;
+
+    #[repr(C)]
+    struct KeySpecifier {
+        kind: u32,
+        reserved: u32,
+        /// Identifier of the key, in the first 16 bytes
+        identifier: [u8; 32],
+    }
+
+    #[repr(C)]
+    struct AddKeyArg {
+        key_spec: KeySpecifier,
+        raw_size: u32,
+        key_id: u32,
+        reserved: [u32; 8],
+        raw: [u8; KEY_SIZE],
+    }
+
+    #[repr(C)]
+    struct RemoveKeyArg {
+        key_spec: KeySpecifier,
+        removal_status_flags: u32,
+        reserved: [u32; 5],
+    }
+
+    #[repr(C)]
+    struct PolicyV2 {
+        version: u8,
+        contents_encryption_mode: u8,
+        filenames_encryption_mode: u8,
+        flags: u8,
+        log2_data_unit_size: u8,
+        reserved: [u8; 3],
+        master_key_identifier: KeyIdentifier,
+    }
+
+    /// Add a new random key to the filesystem of `dir`
+    pub fn add_key(dir: &Path) -> io::Result<KeyIdentifier> {
+        let mut arg = AddKeyArg {
+            key_spec: KeySpecifier {
+                kind: FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER,
+                reserved: 0,
+                identifier: [0; 32],
+            },
+            raw_size: KEY_SIZE as u32,
+            key_id: 0,
+            reserved: [0; 8],
+            raw: [0; KEY_SIZE],
+        };
+        File::open("/dev/urandom")?.read_exact(&mut arg.raw)?;
+        let result = ioctl(dir, FS_IOC_ADD_ENCRYPTION_KEY, &mut arg);
+        // the key is only kept by the kernel
+        arg.raw.fill(0);
+        result?;
+        let mut identifier = [0; 16];
+        identifier.copy_from_slice(&arg.key_spec.identifier[..16]);
+        Ok(identifier)
+    }
+
+    /// Remove a key from the filesystem of `dir`, making the files it encrypted
+    /// unreadable
+    pub fn remove_key(dir: &Path, key: &KeyIdentifier) -> io::Result<()> {
+        let mut arg = RemoveKeyArg {
+            key_spec: key_specifier(key),
+            removal_status_flags: 0,
+            reserved: [0; 5],
+        };
+        ioctl(dir, FS_IOC_REMOVE_ENCRYPTION_KEY, &mut arg)
+    }
+
+    /// Encrypt the files created in the empty dir `dir` with a key of its filesystem
+    pub fn set_policy(dir: &Path, key: &KeyIdentifier) -> io::Result<()> {
+        let mut policy = PolicyV2 {
+            version: FSCRYPT_POLICY_V2,
+            contents_encryption_mode: FSCRYPT_MODE_AES_256_XTS,
+            filenames_encryption_mode: FSCRYPT_MODE_AES_256_CTS,
+            flags: FSCRYPT_POLICY_FLAGS_PAD_32,
+            log2_data_unit_size: 0,
+            reserved: [0; 3],
+            master_key_identifier: *key,
+        };
+        ioctl(dir, FS_IOC_SET_ENCRYPTION_POLICY, &mut policy)
+    }
+
+    fn key_specifier(key: &KeyIdentifier) -> KeySpecifier {
+        let mut identifier = [0; 32];
+        identifier[..16].copy_from_slice(key);
+        KeySpecifier {
+            kind: FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER,
+            reserved: 0,
+            identifier,
+        }
+    }
+
+    fn ioctl<T>(dir: &Path, request: u32, arg: &mut T) -> io::Result<()> {
+        let dir = File::open(dir)?;
+        // SAFETY: `arg` is the struct the ioctl `request` reads and writes
+        let result = unsafe { libc::ioctl(dir.as_raw_fd(), request as _, arg as *mut T) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::mem::size_of;
+
+        /// `_IOC` of the generic ioctl encoding of Linux
+        fn ioc(dir: u32, nr: u32, size: usize) -> u32 {
+            (dir << 30) | ((size as u32) << 16) | ((b'f' as u32) << 8) | nr
+        }
+
+        #[test]
+        fn structs_match_kernel_abi() {
+            assert_eq!(40, size_of::<KeySpecifier>());
+            assert_eq!(80 + KEY_SIZE, size_of::<AddKeyArg>());
+            assert_eq!(64, size_of::<RemoveKeyArg>());
+            assert_eq!(24, size_of::<PolicyV2>());
+            // the policy ioctl is numbered after the size of the v1 policy
+            assert_eq!(FS_IOC_SET_ENCRYPTION_POLICY, ioc(2, 19, 12));
+            assert_eq!(FS_IOC_ADD_ENCRYPTION_KEY, ioc(3, 23, 80));
+            assert_eq!(
+                FS_IOC_REMOVE_ENCRYPTION_KEY,
+                ioc(3, 24, size_of::<RemoveKeyArg>())
+            );
+        }
+    }
+}
+
+/// fscrypt is only available on Linux
+#[cfg(not(target_os = "linux"))]
+mod fscrypt {
+    use std::io;
+    use std::path::Path;
+
+    pub type KeyIdentifier = [u8; 16];
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Spill encryption is only supported on Linux",
+        )
+    }
+
+    pub fn add_key(_dir: &Path) -> io::Result<KeyIdentifier> {
+        Err(unsupported())
+    }
+
+    pub fn remove_key(_dir: &Path, _key: &KeyIdentifier) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn set_policy(_dir: &Path, _key: &KeyIdentifier) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn private_dir_removed_on_drop() -> Result<()> {
+        let work_dir = TempDir::new()?;
+        let work_dir = work_dir.path().to_str().unwrap();
+        let spill_dir = TaskSpillDir::create(work_dir, "job-1")?;
+        let path = spill_dir.path().to_owned();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path)?.permissions().mode();
+            assert_eq!(0o700, mode & 0o777);
+        }
+
+        let runtime = spill_dir.runtime(&RuntimeEnv::default())?;
+        let spill_file = runtime.disk_manager.create_tmp_file("test")?;
+        assert!(spill_file.path().starts_with(&path));
+
+        drop(spill_dir);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn encrypted_dirs_with_job_keys() -> Result<()> {
+        let work_dir = TempDir::new()?;
+        let work_dir = work_dir.path().to_str().unwrap();
+        let encryption = match SpillEncryption::try_new(work_dir) {
+            Ok(encryption) => Arc::new(encryption),
+            // the filesystem of the tests doesn't support encryption
+            Err(e) => {
+                warn!("Skipping the test of the spill encryption: {e}");
+                return Ok(());
+            }
+        };
+        let first =
+            TaskSpillDir::create_encrypted(encryption.clone(), "job-1-1", "job-1")?;
+        let second =
+            TaskSpillDir::create_encrypted(encryption.clone(), "job-1-2", "job-1")?;
+        let other =
+            TaskSpillDir::create_encrypted(encryption.clone(), "job-2-3", "job-2")?;
+        assert_eq!(2, encryption.keys.lock().len());
+        assert_eq!(2, encryption.keys.lock()["job-1"].1);
+
+        let runtime = first.runtime(&RuntimeEnv::default())?;
+        let spill_file = runtime.disk_manager.create_tmp_file("test")?;
+        std::fs::write(spill_file.path(), b"spilled")?;
+        assert_eq!(b"spilled".to_vec(), std::fs::read(spill_file.path())?);
+        drop(spill_file);
+
+        // the key of a job is removed with its last spill dir
+        drop(first);
+        assert_eq!(1, encryption.keys.lock()["job-1"].1);
+        drop(second);
+        assert!(!encryption.keys.lock().contains_key("job-1"));
+        drop(other);
+        assert!(encryption.keys.lock().is_empty());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn existing_dir_made_private() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let work_dir = TempDir::new()?;
+        let path = work_dir.path().join(SPILL_DIR).join("job-1");
+        std::fs::create_dir_all(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+
+        let spill_dir = TaskSpillDir::create(work_dir.path().to_str().unwrap(), "job-1")?;
+        let mode = std::fs::metadata(spill_dir.path())?.permissions().mode();
+        assert_eq!(0o700, mode & 0o777);
+        Ok(())
+    }
+}
@@ -23,11 +23,15 @@ use ballista_core::extension::SessionConfigExt;
 use ballista_core::registry::BallistaFunctionRegistry;
 use ballista_core::utils::default_config_producer;
 use ballista_core::{
+    build_version,
     error::Result,
-    serde::protobuf::{scheduler_grpc_client::SchedulerGrpcClient, ExecutorRegistration},
-    serde::scheduler::ExecutorSpecification,
+    serde::protobuf::{
+        scheduler_grpc_client::SchedulerGrpcClient, ExecutorFunctions,
+        ExecutorRegistration,
+    },
+    serde::scheduler::{ExecutorSpecification, ShuffleFormats},
     serde::BallistaCodec,
-    utils::create_grpc_server,
+    utils::{create_grpc_server, GrpcChannel},
     BALLISTA_VERSION,
 };
 use ballista_core::{ConfigProducer, RuntimeProducer};
@@ -40,7 +44,6 @@ use log::info;
 use std::sync::Arc;
 use tempfile::TempDir;
 use tokio::net::TcpListener;
-use tonic::transport::Channel;
 use uuid::Uuid;
 
 /// Creates new standalone executor based on
@@ -49,7 +52,7 @@ use uuid::Uuid;
 /// This provides flexible way of configuring underlying
 /// components.
 pub async fn new_standalone_executor_from_state(
-    scheduler: SchedulerGrpcClient<Channel>,
+    scheduler: SchedulerGrpcClient<GrpcChannel>,
     concurrent_tasks: usize,
     session_state: &SessionState,
 ) -> Result<()> {
@@ -81,12 +84,41 @@ pub async fn new_standalone_executor_from_state(
 }
 
 pub async fn new_standalone_executor_from_builder(
-    scheduler: SchedulerGrpcClient<Channel>,
+    scheduler: SchedulerGrpcClient<GrpcChannel>,
     concurrent_tasks: usize,
     config_producer: ConfigProducer,
     runtime_producer: RuntimeProducer,
     codec: BallistaCodec,
     function_registry: BallistaFunctionRegistry,
+) -> Result<()> {
+    let work_dir = TempDir::new()?
+        .into_path()
+        .into_os_string()
+        .into_string()
+        .unwrap();
+
+    new_standalone_executor_with_work_dir(
+        scheduler,
+        concurrent_tasks,
+        config_producer,
+        runtime_producer,
+        codec,
+        function_registry,
+        &work_dir,
+    )
+    .await
+}
+
+/// Creates standalone executor which writes its shuffle files to
+/// `work_dir`, which is left for the caller to clean up.
+pub async fn new_standalone_executor_with_work_dir(
+    scheduler: SchedulerGrpcClient<GrpcChannel>,
+    concurrent_tasks: usize,
+    config_producer: ConfigProducer,
+    runtime_producer: RuntimeProducer,
+    codec: BallistaCodec,
+    function_registry: BallistaFunctionRegistry,
+    work_dir: &str,
 ) -> Result<()> {
     // Let the OS assign a random, free port
     let listener = TcpListener::bind("localhost:0").await?;
@@ -108,19 +140,20 @@ pub async fn new_standalone_executor_from_builder(
             }
             .into(),
         ),
+        version: build_version(),
+        functions: Some(ExecutorFunctions {
+            names: function_registry.user_defined_function_names(),
+        }),
+        shuffle_formats: Some(ShuffleFormats::supported().into()),
+        pool: String::new(),
+        registration_token: String::new(),
     };
 
-    let work_dir = TempDir::new()?
-        .into_path()
-        .into_os_string()
-        .into_string()
-        .unwrap();
-
     info!("work_dir: {}", work_dir);
 
     let executor = Arc::new(Executor::new(
         executor_meta,
-        &work_dir,
+        work_dir,
         runtime_producer,
         config_producer,
         Arc::new(function_registry),
@@ -129,10 +162,13 @@ pub async fn new_standalone_executor_from_builder(
         None,
     ));
 
-    let service = BallistaFlightService::new();
+    let service = BallistaFlightService::new()
+        .with_streams(executor.flight_streams.clone())
+        .with_fetch_tokens(executor.fetch_tokens.clone())
+        .with_compression_codecs(executor.produce_config().ballista_compression_codecs());
     let server = FlightServiceServer::new(service);
     tokio::spawn(
-        create_grpc_server()
+        create_grpc_server(&Default::default())?
             .add_service(server)
             .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(
                 listener,
@@ -149,7 +185,7 @@ pub async fn new_standalone_executor<
     T: 'static + AsLogicalPlan,
     U: 'static + AsExecutionPlan,
 >(
-    scheduler: SchedulerGrpcClient<Channel>,
+    scheduler: SchedulerGrpcClient<GrpcChannel>,
     concurrent_tasks: usize,
     codec: BallistaCodec<T, U>,
 ) -> Result<()> {
@@ -173,6 +209,11 @@ pub async fn new_standalone_executor<
             }
             .into(),
         ),
+        version: build_version(),
+        functions: Some(ExecutorFunctions::default()),
+        shuffle_formats: Some(ShuffleFormats::supported().into()),
+        pool: String::new(),
+        registration_token: String::new(),
     };
     let work_dir = TempDir::new()?
         .into_path()
@@ -196,10 +237,13 @@ pub async fn new_standalone_executor<
         concurrent_tasks,
     ));
 
-    let service = BallistaFlightService::new();
+    let service = BallistaFlightService::new()
+        .with_streams(executor.flight_streams.clone())
+        .with_fetch_tokens(executor.fetch_tokens.clone())
+        .with_compression_codecs(executor.produce_config().ballista_compression_codecs());
     let server = FlightServiceServer::new(service);
     tokio::spawn(
-        create_grpc_server()
+        create_grpc_server(&Default::default())?
             .add_service(server)
             .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(
                 listener,
@@ -0,0 +1,257 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Capture of per-task log segments, so the logs of a single task can be
+//! retrieved through the executor's `GetTaskLogs` rpc.
+
+use dashmap::DashMap;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Name of the span a task is executed in. Events recorded within this span
+/// (or any of its children) are attributed to the task.
+pub const TASK_SPAN_NAME: &str = "ballista_task";
+
+/// Bounded in-memory store of the log lines emitted by each task, keyed by job ID and task ID.
+#[derive(Debug)]
+pub struct TaskLogStore {
+    /// Maximum number of lines kept per task. Older lines are dropped first.
+    max_lines_per_task: AtomicUsize,
+    logs: DashMap<String, HashMap<usize, VecDeque<String>>>,
+}
+
+impl TaskLogStore {
+    pub fn new(max_lines_per_task: usize) -> Self {
+        Self {
+            max_lines_per_task: AtomicUsize::new(max_lines_per_task),
+            logs: DashMap::new(),
+        }
+    }
+
+    /// Change the maximum number of lines kept per task, which applies to the lines
+    /// appended from then on
+    pub fn set_max_lines_per_task(&self, max_lines_per_task: usize) {
+        self.max_lines_per_task
+            .store(max_lines_per_task, Ordering::Relaxed);
+    }
+
+    /// Append a line to the log of a task
+    pub fn append(&self, job_id: &str, task_id: usize, line: String) {
+        let max_lines_per_task = self.max_lines_per_task.load(Ordering::Relaxed);
+        if max_lines_per_task == 0 {
+            return;
+        }
+
+        let mut job_logs = self.logs.entry(job_id.to_owned()).or_default();
+        let task_log = job_logs.entry(task_id).or_default();
+        while task_log.len() >= max_lines_per_task {
+            task_log.pop_front();
+        }
+        task_log.push_back(line);
+    }
+
+    /// Get the captured log lines of a task, or None if nothing has been captured for it
+    pub fn get(&self, job_id: &str, task_id: usize) -> Option<Vec<String>> {
+        self.logs.get(job_id).and_then(|job_logs| {
+            job_logs
+                .get(&task_id)
+                .map(|task_log| task_log.iter().cloned().collect())
+        })
+    }
+
+    /// Drop the logs of all tasks of a job
+    pub fn remove_job(&self, job_id: &str) {
+        self.logs.remove(job_id);
+    }
+}
+
+/// Identity of the task a span belongs to, stored in the span's extensions
+struct TaskIdentity {
+    job_id: String,
+    task_id: usize,
+}
+
+#[derive(Default)]
+struct TaskIdentityVisitor {
+    job_id: Option<String>,
+    task_id: Option<usize>,
+}
+
+impl Visit for TaskIdentityVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "task_id" {
+            self.task_id = Some(value as usize);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "job_id" {
+            self.job_id = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "job_id" => self.job_id = Some(format!("{value:?}")),
+            "task_id" => self.task_id = format!("{value:?}").parse().ok(),
+            _ => {}
+        }
+    }
+}
+
+/// Formats the fields of an event as `message key=value ...`
+#[derive(Default)]
+struct EventVisitor {
+    target: Option<String>,
+    message: String,
+    fields: String,
+}
+
+impl Visit for EventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            // Records bridged from the `log` crate carry their real target as a field
+            "log.target" => self.target = Some(value.to_owned()),
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {name}={value}");
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{value:?}");
+            }
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {name}={value:?}");
+            }
+        }
+    }
+}
+
+/// A [Layer] which captures the events recorded while executing a task into a [TaskLogStore]
+pub struct TaskLogLayer {
+    store: Arc<TaskLogStore>,
+}
+
+impl TaskLogLayer {
+    pub fn new(store: Arc<TaskLogStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl<S> Layer<S> for TaskLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: Context<'_, S>,
+    ) {
+        if attrs.metadata().name() != TASK_SPAN_NAME {
+            return;
+        }
+
+        let mut visitor = TaskIdentityVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(job_id), Some(task_id), Some(span)) =
+            (visitor.job_id, visitor.task_id, ctx.span(id))
+        {
+            span.extensions_mut()
+                .insert(TaskIdentity { job_id, task_id });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+
+        for span in scope {
+            let extensions = span.extensions();
+            let Some(identity) = extensions.get::<TaskIdentity>() else {
+                continue;
+            };
+
+            let mut visitor = EventVisitor::default();
+            event.record(&mut visitor);
+
+            let metadata = event.metadata();
+            let mut line = String::new();
+            let _ = SystemTime.format_time(&mut Writer::new(&mut line));
+            let _ = write!(
+                line,
+                " {:>5} {}: {}{}",
+                metadata.level(),
+                visitor.target.as_deref().unwrap_or(metadata.target()),
+                visitor.message,
+                visitor.fields
+            );
+
+            self.store.append(&identity.job_id, identity.task_id, line);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::{info, info_span};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_capture_task_logs() {
+        let store = Arc::new(TaskLogStore::new(2));
+        let subscriber =
+            tracing_subscriber::registry().with(TaskLogLayer::new(store.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("not part of any task");
+
+            let span = info_span!(TASK_SPAN_NAME, job_id = "job-1", task_id = 7_u64);
+            let _guard = span.enter();
+            info!("first");
+            info_span!("nested").in_scope(|| info!(rows = 10, "second"));
+            info!("third");
+        });
+
+        let lines = store.get("job-1", 7).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("second rows=10"), "{}", lines[0]);
+        assert!(lines[1].ends_with("third"), "{}", lines[1]);
+        assert!(store.get("job-1", 8).is_none());
+
+        store.remove_job("job-1");
+        assert!(store.get("job-1", 7).is_none());
+    }
+}
@@ -0,0 +1,47 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#![cfg(feature = "jemalloc")]
+
+use ballista_core::serde::protobuf::{GetProfileParams, ProfileFormat, ProfileType};
+use ballista_executor::heap_profiling;
+
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[allow(non_upper_case_globals)]
+#[export_name = "malloc_conf"]
+pub static malloc_conf: &[u8] = b"prof:true,prof_active:true,lg_prof_sample:19\0";
+
+#[test]
+fn dump_heap_profiles() {
+    let retained = vec![1u8; 16 * 1024 * 1024];
+
+    let params = |format: ProfileFormat| GetProfileParams {
+        format: format as i32,
+        profile_type: ProfileType::Heap as i32,
+        ..Default::default()
+    };
+    let pprof = heap_profiling::profile(&params(ProfileFormat::Pprof)).unwrap();
+    // gzipped protobuf
+    assert_eq!(&pprof[..2], &[0x1f, 0x8b]);
+
+    let flamegraph = heap_profiling::profile(&params(ProfileFormat::Flamegraph)).unwrap();
+    assert!(String::from_utf8(flamegraph).unwrap().contains("<svg"));
+
+    drop(retained);
+}
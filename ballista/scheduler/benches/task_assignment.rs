@@ -0,0 +1,241 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Benchmarks of task slot accounting in the in-memory cluster state, at the scale of
+//! thousands of executors.
+//!
+//! Run with `cargo bench -p ballista-scheduler --bench task_assignment`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ballista_core::build_version;
+use ballista_core::extension::SessionConfigExt;
+use ballista_core::serde::scheduler::{
+    ExecutorData, ExecutorMetadata, ExecutorSpecification,
+};
+use ballista_scheduler::cluster::job_scheduling::PriorityJobScheduling;
+use ballista_scheduler::cluster::memory::InMemoryClusterState;
+use ballista_scheduler::cluster::ClusterState;
+use ballista_scheduler::config::TaskDistributionPolicy;
+use ballista_scheduler::state::execution_graph::ExecutionGraph;
+use ballista_scheduler::state::task_manager::JobInfoCache;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::functions_aggregate::sum::sum;
+use datafusion::prelude::{col, SessionConfig, SessionContext};
+use datafusion::test_util::scan_empty_with_partitions;
+use tokio::runtime::Runtime;
+
+const SLOTS_PER_EXECUTOR: u32 = 4;
+const EXECUTOR_COUNTS: [usize; 3] = [1_000, 5_000, 10_000];
+const CONCURRENT_RELEASERS: usize = 16;
+
+fn executor_id(idx: usize) -> String {
+    format!("executor-{idx}")
+}
+
+async fn cluster_state(num_executors: usize) -> Arc<InMemoryClusterState> {
+    let state = Arc::new(InMemoryClusterState::default());
+    for idx in 0..num_executors {
+        let metadata = ExecutorMetadata {
+            id: executor_id(idx),
+            host: format!("host-{idx}"),
+            port: 50051,
+            grpc_port: 50052,
+            specification: ExecutorSpecification {
+                task_slots: SLOTS_PER_EXECUTOR,
+            },
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+        };
+        let spec = ExecutorData {
+            executor_id: executor_id(idx),
+            total_task_slots: SLOTS_PER_EXECUTOR,
+            available_task_slots: SLOTS_PER_EXECUTOR,
+        };
+        state.register_executor(metadata, spec).await.unwrap();
+    }
+    state
+}
+
+/// A running job whose first stage has exactly `num_tasks` tasks
+async fn running_job(num_tasks: usize) -> ExecutionGraph {
+    let config = SessionConfig::new().with_target_partitions(num_tasks);
+    let session_state = SessionContext::new_with_config(config).state();
+
+    let schema = Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("gmv", DataType::UInt64, false),
+    ]);
+    let logical_plan = scan_empty_with_partitions(None, &schema, None, num_tasks)
+        .unwrap()
+        .aggregate(vec![col("id")], vec![sum(col("gmv"))])
+        .unwrap()
+        .build()
+        .unwrap();
+    let optimized_plan = session_state.optimize(&logical_plan).unwrap();
+    let plan = session_state
+        .create_physical_plan(&optimized_plan)
+        .await
+        .unwrap();
+
+    let mut graph = ExecutionGraph::new(
+        "localhost:50050",
+        "job",
+        "",
+        "session",
+        plan,
+        0,
+        Arc::new(SessionConfig::new_with_ballista()),
+    )
+    .unwrap();
+    graph.revive();
+    graph
+}
+
+/// Bind every slot of the cluster to tasks of a single job in one scheduling round
+fn bench_bind_tasks(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("bind_schedulable_tasks");
+    group.sample_size(10);
+
+    for num_executors in EXECUTOR_COUNTS {
+        let num_tasks = num_executors * SLOTS_PER_EXECUTOR as usize;
+        let state = rt.block_on(cluster_state(num_executors));
+        let graph = rt.block_on(running_job(num_tasks));
+
+        group.throughput(Throughput::Elements(num_tasks as u64));
+        for (name, policy) in [
+            ("bias", TaskDistributionPolicy::Bias),
+            ("round_robin", TaskDistributionPolicy::RoundRobin),
+        ] {
+            group.bench_with_input(
+                BenchmarkId::new(name, num_executors),
+                &num_executors,
+                |b, _| {
+                    b.to_async(&rt).iter_custom(|iters| {
+                        let state = state.clone();
+                        let graph = graph.clone();
+                        async move {
+                            let mut elapsed = Duration::ZERO;
+                            for _ in 0..iters {
+                                let active_jobs = Arc::new(HashMap::from([(
+                                    "job".to_string(),
+                                    JobInfoCache::new(graph.clone()),
+                                )]));
+
+                                let start = Instant::now();
+                                let bound = state
+                                    .bind_schedulable_tasks(
+                                        policy,
+                                        &PriorityJobScheduling,
+                                        0.0,
+                                        active_jobs,
+                                        None,
+                                    )
+                                    .await
+                                    .unwrap();
+                                elapsed += start.elapsed();
+
+                                assert_eq!(bound.len(), num_tasks);
+                                let released = bound
+                                    .into_iter()
+                                    .map(|(executor_id, _)| (executor_id, 1))
+                                    .collect();
+                                state.unbind_tasks(released).await.unwrap();
+                            }
+                            elapsed
+                        }
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Release slots of every executor from many concurrent tasks, as happens when task
+/// status updates arrive from thousands of executors at once, while the scheduler keeps
+/// running binding rounds
+fn bench_release_slots(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(CONCURRENT_RELEASERS)
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("unbind_tasks_concurrently");
+    group.sample_size(10);
+
+    for num_executors in EXECUTOR_COUNTS {
+        let state = rt.block_on(cluster_state(num_executors));
+        let no_jobs = Arc::new(HashMap::new());
+
+        group.throughput(Throughput::Elements(num_executors as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_executors),
+            &num_executors,
+            |b, &num_executors| {
+                b.to_async(&rt).iter(|| {
+                    let state = state.clone();
+                    let no_jobs = no_jobs.clone();
+                    async move {
+                        let binder = {
+                            let state = state.clone();
+                            tokio::spawn(async move {
+                                state
+                                    .bind_schedulable_tasks(
+                                        TaskDistributionPolicy::Bias,
+                                        &PriorityJobScheduling,
+                                        0.0,
+                                        no_jobs,
+                                        None,
+                                    )
+                                    .await
+                                    .unwrap();
+                            })
+                        };
+                        let releasers = (0..CONCURRENT_RELEASERS).map(|releaser| {
+                            let state = state.clone();
+                            tokio::spawn(async move {
+                                for idx in (releaser..num_executors)
+                                    .step_by(CONCURRENT_RELEASERS)
+                                {
+                                    state
+                                        .unbind_tasks(vec![(executor_id(idx), 1)])
+                                        .await
+                                        .unwrap();
+                                }
+                            })
+                        });
+                        for releaser in releasers.collect::<Vec<_>>() {
+                            releaser.await.unwrap();
+                        }
+                        binder.await.unwrap();
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_bind_tasks, bench_release_slots);
+criterion_main!(benches);
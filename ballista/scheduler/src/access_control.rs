@@ -0,0 +1,370 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Role based access control of the operations exposed by the scheduler.
+//!
+//! Callers are authenticated by the configured [AuthProvider](crate::auth::AuthProvider).
+//! Without one, the principal of a request is read from the [PRINCIPAL_METADATA_KEY]
+//! gRPC metadata, or HTTP header, which is expected to be set by a trusted
+//! authenticating proxy in front of the scheduler.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use ballista_core::error::BallistaError;
+use clap::ValueEnum;
+
+use crate::auth::Identity;
+
+/// Key of the gRPC metadata, or HTTP header, carrying the principal of a request
+pub const PRINCIPAL_METADATA_KEY: &str = "x-ballista-principal";
+
+/// Role of a principal, defining which operations it is allowed to perform. Roles are
+/// ordered from the most to the least privileged.
+///
+/// It needs to be visible to code generated by configure_me
+#[derive(
+    Clone,
+    ValueEnum,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    serde::Deserialize,
+)]
+pub enum Role {
+    /// Allowed to perform any operation
+    Admin,
+    /// Allowed to view, cancel and purge the jobs of any principal, to drain executors
+    /// and to reserve executor slots, but not to change the runtime configuration
+    Operator,
+    /// Only allowed to submit jobs, and to view and cancel its own jobs
+    User,
+}
+
+impl Role {
+    /// Whether the role allows performing `operation`, on a job owned by the caller if
+    /// `is_owner`
+    pub fn permits(&self, operation: Operation, is_owner: bool) -> bool {
+        match (self, operation) {
+            (Role::Admin, _) => true,
+            (Role::Operator, Operation::UpdateRuntimeConfig) => false,
+            (Role::Operator, _) => true,
+            (Role::User, Operation::SubmitJob) => true,
+            (Role::User, Operation::ViewJob | Operation::CancelJob) => is_owner,
+            (Role::User, _) => false,
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ValueEnum::from_str(s, true)
+    }
+}
+
+impl parse_arg::ParseArgFromStr for Role {
+    fn describe_type<W: fmt::Write>(mut writer: W) -> fmt::Result {
+        write!(writer, "The role of a principal")
+    }
+}
+
+/// Operations subject to access control
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// Submit a new job
+    SubmitJob,
+    /// View the status of a job
+    ViewJob,
+    /// Cancel a job, or clean up its data
+    CancelJob,
+    /// Purge completed jobs from the scheduler state
+    PurgeJobs,
+    /// Drain executors of their tasks
+    DrainExecutors,
+    /// Reserve executor slots ahead of jobs, or cancel reservations
+    ReserveSlots,
+    /// View the demand for and utilization of the resources of the cluster
+    ViewCluster,
+    /// Change the runtime configuration of the cluster
+    UpdateRuntimeConfig,
+}
+
+/// Reason an operation is rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessError {
+    /// The request does not carry a principal or credentials
+    Unauthenticated,
+    /// The credentials of the request are rejected by the auth provider
+    AuthenticationFailed(String),
+    /// The role of the principal does not allow the operation
+    PermissionDenied {
+        principal: String,
+        role: Role,
+        operation: Operation,
+    },
+    /// The query authorizer rejects the query of the caller
+    QueryDenied(String),
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessError::Unauthenticated => {
+                write!(f, "Request does not carry a principal or credentials")
+            }
+            AccessError::AuthenticationFailed(reason) => {
+                write!(f, "Authentication failed: {reason}")
+            }
+            AccessError::PermissionDenied {
+                principal,
+                role,
+                operation,
+            } => write!(
+                f,
+                "Principal {principal} with role {role:?} is not allowed to perform {operation:?}"
+            ),
+            AccessError::QueryDenied(reason) => write!(f, "Query denied: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+impl From<AccessError> for tonic::Status {
+    fn from(e: AccessError) -> Self {
+        match e {
+            AccessError::Unauthenticated | AccessError::AuthenticationFailed(_) => {
+                tonic::Status::unauthenticated(e.to_string())
+            }
+            AccessError::PermissionDenied { .. } | AccessError::QueryDenied(_) => {
+                tonic::Status::permission_denied(e.to_string())
+            }
+        }
+    }
+}
+
+/// Roles bound to principals and to the groups they are members of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessControl {
+    principal_roles: HashMap<String, Role>,
+    group_roles: HashMap<String, Role>,
+    default_role: Role,
+}
+
+impl AccessControl {
+    /// Create an access control where all principals have `default_role`
+    pub fn new(default_role: Role) -> Self {
+        Self {
+            principal_roles: HashMap::new(),
+            group_roles: HashMap::new(),
+            default_role,
+        }
+    }
+
+    /// Parse an access control from role bindings of the form
+    /// `principal=role[,principal=role]*`, other principals having `default_role`
+    pub fn try_new(bindings: &str, default_role: Role) -> Result<Self, BallistaError> {
+        Ok(Self {
+            principal_roles: parse_role_bindings(bindings)?,
+            ..Self::new(default_role)
+        })
+    }
+
+    /// Parse role bindings of groups of the form `group=role[,group=role]*`
+    pub fn try_with_group_roles(mut self, bindings: &str) -> Result<Self, BallistaError> {
+        self.group_roles.extend(parse_role_bindings(bindings)?);
+        Ok(self)
+    }
+
+    /// Bind `role` to `principal`
+    pub fn with_role(mut self, principal: impl Into<String>, role: Role) -> Self {
+        self.principal_roles.insert(principal.into(), role);
+        self
+    }
+
+    /// Bind `role` to the members of `group`
+    pub fn with_group_role(mut self, group: impl Into<String>, role: Role) -> Self {
+        self.group_roles.insert(group.into(), role);
+        self
+    }
+
+    /// Role of an identity: the role bound to its principal if any, otherwise the most
+    /// privileged role bound to its groups, otherwise the default role
+    pub fn role(&self, identity: &Identity) -> Role {
+        self.principal_roles
+            .get(&identity.principal)
+            .copied()
+            .or_else(|| {
+                identity
+                    .groups
+                    .iter()
+                    .filter_map(|group| self.group_roles.get(group).copied())
+                    .min()
+            })
+            .unwrap_or(self.default_role)
+    }
+
+    /// Check whether `identity` is allowed to perform `operation`, on a job owned by
+    /// `owner` if the operation targets a job
+    pub fn authorize(
+        &self,
+        identity: Option<&Identity>,
+        operation: Operation,
+        owner: Option<&str>,
+    ) -> Result<(), AccessError> {
+        let identity = identity.ok_or(AccessError::Unauthenticated)?;
+        let role = self.role(identity);
+        if role.permits(operation, owner == Some(identity.principal.as_str())) {
+            Ok(())
+        } else {
+            Err(AccessError::PermissionDenied {
+                principal: identity.principal.clone(),
+                role,
+                operation,
+            })
+        }
+    }
+}
+
+fn parse_role_bindings(bindings: &str) -> Result<HashMap<String, Role>, BallistaError> {
+    bindings
+        .split(',')
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(|binding| {
+            let (name, role) = binding.split_once('=').ok_or_else(|| {
+                BallistaError::General(format!(
+                    "Invalid role binding {binding}, expected name=role"
+                ))
+            })?;
+            let role = role.trim().parse().map_err(|e| {
+                BallistaError::General(format!("Invalid role in binding {binding}: {e}"))
+            })?;
+            Ok((name.trim().to_owned(), role))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::access_control::{AccessControl, AccessError, Operation, Role};
+    use crate::auth::Identity;
+
+    fn identity(principal: &str) -> Identity {
+        Identity::new(principal)
+    }
+
+    #[test]
+    fn test_parse_role_bindings() {
+        let access_control =
+            AccessControl::try_new(" alice=admin, bob=Operator,", Role::User).unwrap();
+        assert_eq!(access_control.role(&identity("alice")), Role::Admin);
+        assert_eq!(access_control.role(&identity("bob")), Role::Operator);
+        assert_eq!(access_control.role(&identity("carol")), Role::User);
+
+        assert!(AccessControl::try_new("alice", Role::User).is_err());
+        assert!(AccessControl::try_new("alice=root", Role::User).is_err());
+    }
+
+    #[test]
+    fn test_authorize() {
+        let access_control = AccessControl::new(Role::User)
+            .with_role("alice", Role::Admin)
+            .with_role("bob", Role::Operator);
+
+        assert_eq!(
+            access_control.authorize(None, Operation::SubmitJob, None),
+            Err(AccessError::Unauthenticated)
+        );
+
+        // Users only access their own jobs
+        for operation in [Operation::ViewJob, Operation::CancelJob] {
+            assert!(access_control
+                .authorize(Some(&identity("carol")), operation, Some("carol"))
+                .is_ok());
+            assert!(access_control
+                .authorize(Some(&identity("carol")), operation, Some("dave"))
+                .is_err());
+            assert!(access_control
+                .authorize(Some(&identity("carol")), operation, None)
+                .is_err());
+        }
+        assert!(access_control
+            .authorize(Some(&identity("carol")), Operation::SubmitJob, None)
+            .is_ok());
+        assert!(access_control
+            .authorize(Some(&identity("carol")), Operation::DrainExecutors, None)
+            .is_err());
+
+        // Operators access the jobs of all principals but do not change configuration
+        assert!(access_control
+            .authorize(Some(&identity("bob")), Operation::CancelJob, Some("carol"))
+            .is_ok());
+        assert!(access_control
+            .authorize(Some(&identity("bob")), Operation::DrainExecutors, None)
+            .is_ok());
+        assert!(access_control
+            .authorize(Some(&identity("bob")), Operation::ReserveSlots, None)
+            .is_ok());
+        assert!(access_control
+            .authorize(Some(&identity("bob")), Operation::UpdateRuntimeConfig, None)
+            .is_err());
+
+        assert!(access_control
+            .authorize(
+                Some(&identity("alice")),
+                Operation::UpdateRuntimeConfig,
+                None
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_group_roles() {
+        let access_control = AccessControl::new(Role::User)
+            .with_role("alice", Role::User)
+            .try_with_group_roles("analysts=operator, admins=admin")
+            .unwrap();
+
+        let carol = identity("carol")
+            .with_groups(vec!["analysts".to_owned(), "admins".to_owned()]);
+        assert_eq!(access_control.role(&carol), Role::Admin);
+
+        let dave = identity("dave").with_groups(vec!["analysts".to_owned()]);
+        assert_eq!(access_control.role(&dave), Role::Operator);
+        assert!(access_control
+            .authorize(Some(&dave), Operation::CancelJob, Some("carol"))
+            .is_ok());
+
+        // Roles bound to principals take precedence over the roles of their groups
+        let alice = identity("alice").with_groups(vec!["admins".to_owned()]);
+        assert_eq!(access_control.role(&alice), Role::User);
+
+        let eve = identity("eve").with_groups(vec!["guests".to_owned()]);
+        assert_eq!(access_control.role(&eve), Role::User);
+
+        assert!(AccessControl::new(Role::User)
+            .try_with_group_roles("admins")
+            .is_err());
+    }
+}
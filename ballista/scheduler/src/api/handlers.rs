@@ -10,27 +10,91 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::access_control::{AccessError, Operation, PRINCIPAL_METADATA_KEY};
+use crate::auth::{RequestAuth, AUTHORIZATION_METADATA_KEY};
+use crate::history::EventLogReader;
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::SchedulerServer;
-use crate::state::execution_graph::ExecutionStage;
+use crate::state::execution_graph::{ExecutionStage, TaskInfo};
 use crate::state::execution_graph_dot::ExecutionGraphDot;
+use crate::state::executor_settings::ExecutorSettingsRollout;
+use crate::state::executor_telemetry::ResourceSample;
+use crate::state::job_metrics::{job_metrics, summarize_metrics};
 use axum::{
-    extract::{Path, State},
-    response::{IntoResponse, Response},
+    extract::{Path, Query, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
     Json,
 };
+use ballista_core::config::redact_settings;
+use ballista_core::error::LEADER_METADATA_KEY;
 use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::protobuf::{
+    task_status, GetProfileParams, JobStatus, ProfileFormat, ProfileType,
+};
 use ballista_core::BALLISTA_VERSION;
 use datafusion::physical_plan::metrics::{MetricValue, MetricsSet, Time};
+use datafusion::physical_plan::{displayable, ExecutionPlan};
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 use graphviz_rust::cmd::{CommandArg, Format};
 use graphviz_rust::exec;
 use graphviz_rust::printer::PrinterContext;
-use http::{header::CONTENT_TYPE, StatusCode};
+use http::{header::CONTENT_TYPE, HeaderMap, Method, StatusCode};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::Duration;
 
+/// The dashboard of the cluster, a page reading the REST API
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// Authentication material of a request: the principal set by an authenticating proxy
+/// in front of the scheduler, and the credentials of the `authorization` header
+fn request_auth(headers: &HeaderMap) -> RequestAuth {
+    let header = |key| {
+        headers
+            .get(key)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+
+    RequestAuth {
+        principal: header(PRINCIPAL_METADATA_KEY),
+        authorization: header(AUTHORIZATION_METADATA_KEY),
+    }
+}
+
+fn access_error_status(e: AccessError) -> StatusCode {
+    match e {
+        AccessError::Unauthenticated | AccessError::AuthenticationFailed(_) => {
+            StatusCode::UNAUTHORIZED
+        }
+        AccessError::PermissionDenied { .. } | AccessError::QueryDenied(_) => {
+            StatusCode::FORBIDDEN
+        }
+    }
+}
+
+/// Check whether the caller of a request is allowed to perform `operation`, on a job
+/// or on the cluster
+async fn authorize<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    data_server: &SchedulerServer<T, U>,
+    headers: &HeaderMap,
+    operation: Operation,
+    job_id: Option<&str>,
+) -> Result<(), StatusCode> {
+    let identity = data_server
+        .identify(request_auth(headers))
+        .await
+        .map_err(access_error_status)?;
+    data_server
+        .authorize(identity.as_ref(), operation, job_id)
+        .map_err(access_error_status)
+}
+
 #[derive(Debug, serde::Serialize)]
 struct SchedulerStateResponse {
     started: u128,
@@ -41,7 +105,50 @@ pub struct ExecutorMetaResponse {
     pub id: String,
     pub host: String,
     pub port: u16,
+    pub version: String,
+    /// Pool of executors the executor joined, empty for the shared pool
+    pub pool: String,
     pub last_seen: u128,
+    /// `active`, `draining` while the executor is decommissioned, or `dead`
+    pub status: &'static str,
+    /// Resource usage reported with the latest heartbeat of the executor, if any
+    pub resource_usage: Option<ResourceSample>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ExecutorProfileQuery {
+    /// Profile duration in seconds
+    pub seconds: Option<u32>,
+    /// Sampling frequency in Hz
+    pub frequency: Option<u32>,
+    /// Either `flamegraph` (default) or `pprof`
+    pub format: Option<String>,
+    /// Either `cpu` (default) or `heap`
+    #[serde(rename = "type")]
+    pub profile_type: Option<String>,
+    /// Only profile while this task is running. Requires `task_id`
+    pub job_id: Option<String>,
+    pub task_id: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ExecutorSettingsRequest {
+    pub settings: BTreeMap<String, String>,
+    /// Percentage of the executors the settings are pushed to, all of them by default
+    pub rollout_percentage: Option<u8>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RegistrationTokensRequest {
+    /// Tokens executors present to register, replacing the accepted ones
+    pub tokens: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RegistrationTokensResponse {
+    /// Executors which registered with a token which is no longer accepted, and
+    /// which are removed from the cluster
+    pub revoked_executors: Vec<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -52,6 +159,77 @@ pub struct JobResponse {
     pub num_stages: usize,
     pub completed_stages: usize,
     pub percent_complete: u8,
+    /// Unix epoch-based timestamps in milliseconds, 0 until the job starts or ends
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct JobDetailResponse {
+    pub job_id: String,
+    pub job_name: String,
+    pub job_status: String,
+    /// Unix epoch-based timestamps in milliseconds, 0 until the job starts or ends
+    pub queued_at: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    /// The stages of the job, ordered by ID, linked to their input and output stages
+    pub stages: Vec<StageDetailResponse>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct StageDetailResponse {
+    pub stage_id: usize,
+    pub stage_status: String,
+    pub stage_attempt_num: usize,
+    /// The number of tasks of the stage, unknown until the stage is resolved
+    pub partitions: Option<usize>,
+    pub completed_tasks: usize,
+    pub input_stages: Vec<usize>,
+    pub output_stages: Vec<usize>,
+    /// The number of tasks of the stage in each state, including the tasks which
+    /// weren't launched yet as `pending`
+    pub task_states: BTreeMap<String, usize>,
+    pub tasks: Vec<TaskDetailResponse>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TaskDetailResponse {
+    pub partition: usize,
+    pub task_id: usize,
+    /// `running`, `successful` or `failed`
+    pub state: &'static str,
+    pub executor_id: Option<String>,
+    pub error: Option<String>,
+    /// Unix epoch-based timestamps in milliseconds, 0 until the task reaches them
+    pub scheduled_time: u128,
+    pub launch_time: u128,
+    pub start_exec_time: u128,
+    pub end_exec_time: u128,
+    pub finish_time: u128,
+    /// Execution time of the task on its executor, once it finished
+    pub duration_ms: Option<u128>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct JobPlanResponse {
+    pub stages: Vec<StagePlanResponse>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct StagePlanResponse {
+    pub stage_id: usize,
+    pub plan: PlanNodeResponse,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct PlanNodeResponse {
+    pub name: String,
+    /// One line description of the operator, as in `EXPLAIN`
+    pub description: String,
+    /// Metrics of the operator summed by name across the completed tasks of the stage
+    pub metrics: BTreeMap<String, u64>,
+    pub children: Vec<PlanNodeResponse>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -59,6 +237,11 @@ struct CancelJobResponse {
     pub cancelled: bool,
 }
 
+#[derive(Debug, serde::Serialize)]
+struct DecommissionExecutorResponse {
+    pub decommissioning: bool,
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct QueryStageSummary {
     pub stage_id: String,
@@ -68,6 +251,10 @@ pub struct QueryStageSummary {
     pub elapsed_compute: String,
 }
 
+pub async fn get_dashboard() -> impl IntoResponse {
+    ([(CONTENT_TYPE, "text/html; charset=utf-8")], DASHBOARD)
+}
+
 pub async fn get_scheduler_state<
     T: AsLogicalPlan + Clone + Send + Sync + 'static,
     U: AsExecutionPlan + Send + Sync + 'static,
@@ -94,26 +281,276 @@ pub async fn get_executors<
         .await
         .unwrap_or_default()
         .into_iter()
-        .map(|(metadata, duration)| ExecutorMetaResponse {
-            id: metadata.id,
-            host: metadata.host,
-            port: metadata.port,
-            last_seen: duration.as_millis(),
+        .map(|(metadata, duration)| {
+            let executor_manager = &state.executor_manager;
+            let status = if executor_manager.is_dead_executor(&metadata.id) {
+                "dead"
+            } else if executor_manager.is_draining_executor(&metadata.id) {
+                "draining"
+            } else {
+                "active"
+            };
+            let resource_usage = executor_manager.telemetry().latest(&metadata.id);
+            ExecutorMetaResponse {
+                id: metadata.id,
+                host: metadata.host,
+                port: metadata.port,
+                version: metadata.version,
+                pool: metadata.pool,
+                last_seen: duration.as_millis(),
+                status,
+                resource_usage,
+            }
         })
         .collect();
 
     Json(executors)
 }
 
+pub async fn get_executor_resources<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    Path(executor_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let executor_manager = &data_server.state.executor_manager;
+    // 404 if executor doesn't exist
+    executor_manager
+        .get_executor_metadata(&executor_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    Ok(Json(executor_manager.telemetry().history(&executor_id)))
+}
+
+pub async fn get_executor_settings<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+) -> impl IntoResponse {
+    let rollout = data_server
+        .state
+        .executor_manager
+        .executor_settings_rollout()
+        .map(|rollout| ExecutorSettingsRollout {
+            settings: redact_settings(&rollout.settings),
+            ..rollout
+        });
+    Json(rollout)
+}
+
+/// Redirect the writes a read replica receives to the leader scheduler, with a 307
+/// status for the clients to repeat them as they are
+pub async fn redirect_writes<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let Err(status) = data_server.ensure_writable().await else {
+        return next.run(request).await;
+    };
+
+    let leader = status
+        .metadata()
+        .get(LEADER_METADATA_KEY)
+        .and_then(|leader| leader.to_str().ok());
+    match leader {
+        Some(leader) => {
+            let scheme = if data_server.state.config.grpc_security.is_server_tls() {
+                "https"
+            } else {
+                "http"
+            };
+            let path = request
+                .uri()
+                .path_and_query()
+                .map(|path| path.as_str())
+                .unwrap_or("/");
+            Redirect::temporary(&format!("{scheme}://{leader}{path}")).into_response()
+        }
+        None => {
+            (StatusCode::SERVICE_UNAVAILABLE, status.message().to_owned()).into_response()
+        }
+    }
+}
+
+pub async fn roll_out_executor_settings<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+    Json(request): Json<ExecutorSettingsRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    authorize(&data_server, &headers, Operation::UpdateRuntimeConfig, None)
+        .await
+        .map_err(|status| (status, String::new()))?;
+
+    let rollout = data_server
+        .state
+        .executor_manager
+        .roll_out_executor_settings(
+            request.settings,
+            request.rollout_percentage.unwrap_or(100),
+        )
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    Ok(Json(rollout))
+}
+
+pub async fn rotate_executor_registration_tokens<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+    Json(request): Json<RegistrationTokensRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    authorize(&data_server, &headers, Operation::UpdateRuntimeConfig, None)
+        .await
+        .map_err(|status| (status, String::new()))?;
+
+    let executor_manager = &data_server.state.executor_manager;
+    let revoked_executors = executor_manager
+        .rotate_registration_tokens(request.tokens)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    if !revoked_executors.is_empty() {
+        let event_sender = data_server
+            .query_stage_event_loop
+            .get_sender()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        for executor_id in &revoked_executors {
+            SchedulerServer::<T, U>::remove_executor(
+                executor_manager.clone(),
+                event_sender.clone(),
+                executor_id,
+                Some("registration token revoked".to_owned()),
+                0,
+            );
+        }
+    }
+
+    Ok(Json(RegistrationTokensResponse { revoked_executors }))
+}
+
+/// The demand for and utilization of the resources of the cluster, e.g. to drive an
+/// autoscaler
+pub async fn get_cluster_utilization<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewCluster, None).await?;
+
+    Ok(Json(data_server.state.cluster_utilization().await))
+}
+
+/// Decommission the executor: it stops accepting tasks, drains its running tasks and
+/// then deregisters
+pub async fn decommission_executor<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+    Path(executor_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::DrainExecutors, None).await?;
+
+    let executor_manager = &data_server.state.executor_manager;
+    // 404 if executor doesn't exist
+    executor_manager
+        .get_executor_metadata(&executor_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    executor_manager
+        .stop_executor(
+            &executor_id,
+            "decommissioned through the REST API".to_owned(),
+            false,
+        )
+        .await;
+
+    Ok(Json(DecommissionExecutorResponse {
+        decommissioning: true,
+    }))
+}
+
+pub async fn get_executor_profile<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    Path(executor_id): Path<String>,
+    Query(query): Query<ExecutorProfileQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let format = match query.format.as_deref() {
+        None | Some("flamegraph") => ProfileFormat::Flamegraph,
+        Some("pprof") => ProfileFormat::Pprof,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+    let profile_type = match query.profile_type.as_deref() {
+        None | Some("cpu") => ProfileType::Cpu,
+        Some("heap") => ProfileType::Heap,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+    if query.job_id.is_some() != query.task_id.is_some() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let params = GetProfileParams {
+        seconds: query.seconds.unwrap_or_default(),
+        frequency: query.frequency.unwrap_or_default(),
+        format: format as i32,
+        job_id: query.job_id.unwrap_or_default(),
+        task_id: query.task_id,
+        profile_type: profile_type as i32,
+    };
+    let profile = data_server
+        .state
+        .executor_manager
+        .get_executor_profile(&executor_id, params)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let content_type = match format {
+        ProfileFormat::Flamegraph => "image/svg+xml",
+        ProfileFormat::Pprof => "application/octet-stream",
+    };
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .body(axum::body::Body::from(profile))
+        .unwrap())
+}
+
 pub async fn get_jobs<
     T: AsLogicalPlan + Clone + Send + Sync + 'static,
     U: AsExecutionPlan + Send + Sync + 'static,
 >(
     State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, StatusCode> {
     // TODO: Display last seen information in UI
     let state = &data_server.state;
 
+    let identity = data_server
+        .identify(request_auth(&headers))
+        .await
+        .map_err(access_error_status)?;
+    if state.config.access_control.is_some() && identity.is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
     let jobs = state
         .task_manager
         .get_jobs()
@@ -122,35 +559,14 @@ pub async fn get_jobs<
 
     let jobs: Vec<JobResponse> = jobs
         .iter()
+        .filter(|job| {
+            data_server
+                .authorize(identity.as_ref(), Operation::ViewJob, Some(&job.job_id))
+                .is_ok()
+        })
         .map(|job| {
-            let status = &job.status;
-            let job_status = match &status.status {
-                Some(Status::Queued(_)) => "Queued".to_string(),
-                Some(Status::Running(_)) => "Running".to_string(),
-                Some(Status::Failed(error)) => format!("Failed: {}", error.error),
-                Some(Status::Successful(completed)) => {
-                    let num_rows = completed
-                        .partition_location
-                        .iter()
-                        .map(|p| {
-                            p.partition_stats.as_ref().map(|s| s.num_rows).unwrap_or(0)
-                        })
-                        .sum::<i64>();
-                    let num_rows_term = if num_rows == 1 { "row" } else { "rows" };
-                    let num_partitions = completed.partition_location.len();
-                    let num_partitions_term = if num_partitions == 1 {
-                        "partition"
-                    } else {
-                        "partitions"
-                    };
-                    format!(
-                        "Completed. Produced {} {} containing {} {}. Elapsed time: {} ms.",
-                        num_partitions, num_partitions_term, num_rows, num_rows_term,
-                        job.end_time - job.start_time
-                    )
-                }
-                _ => "Invalid State".to_string(),
-            };
+            let job_status =
+                describe_job_status(&job.status, job.end_time - job.start_time);
 
             // calculate progress based on completed stages for now, but we could use completed
             // tasks in the future to make this more accurate
@@ -163,6 +579,8 @@ pub async fn get_jobs<
                 num_stages: job.num_stages,
                 completed_stages: job.completed_stages,
                 percent_complete,
+                start_time: job.start_time,
+                end_time: job.end_time,
             }
         })
         .collect();
@@ -170,13 +588,201 @@ pub async fn get_jobs<
     Ok(Json(jobs))
 }
 
+fn describe_job_status(status: &JobStatus, elapsed_ms: u64) -> String {
+    match &status.status {
+        Some(Status::Queued(_)) => "Queued".to_string(),
+        Some(Status::Running(_)) => "Running".to_string(),
+        Some(Status::Failed(error)) => format!("Failed: {}", error.error),
+        Some(Status::Successful(completed)) => {
+            let num_rows = completed
+                .partition_location
+                .iter()
+                .map(|p| p.partition_stats.as_ref().map(|s| s.num_rows).unwrap_or(0))
+                .sum::<i64>();
+            let num_rows_term = if num_rows == 1 { "row" } else { "rows" };
+            let num_partitions = completed.partition_location.len();
+            let num_partitions_term = if num_partitions == 1 {
+                "partition"
+            } else {
+                "partitions"
+            };
+            format!(
+                "Completed. Produced {} {} containing {} {}. Elapsed time: {} ms.",
+                num_partitions, num_partitions_term, num_rows, num_rows_term, elapsed_ms
+            )
+        }
+        _ => "Invalid State".to_string(),
+    }
+}
+
+pub async fn get_job<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewJob, Some(&job_id)).await?;
+
+    let graph = data_server
+        .state
+        .task_manager
+        .get_job_execution_graph(&job_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let stages = graph.stages();
+    let mut stage_ids = stages.keys().copied().collect::<Vec<_>>();
+    stage_ids.sort_unstable();
+    let stages = stage_ids
+        .iter()
+        .map(|stage_id| {
+            let stage = &stages[stage_id];
+            let tasks: Vec<TaskDetailResponse> = stage
+                .task_infos()
+                .into_iter()
+                .map(|(partition, info)| task_detail(partition, info))
+                .collect();
+            let mut task_states = BTreeMap::new();
+            for task in &tasks {
+                *task_states.entry(task.state.to_owned()).or_default() += 1;
+            }
+            let pending = stage
+                .partitions()
+                .unwrap_or_default()
+                .saturating_sub(tasks.len());
+            if pending > 0 {
+                task_states.insert("pending".to_owned(), pending);
+            }
+            StageDetailResponse {
+                stage_id: *stage_id,
+                stage_status: stage.variant_name().to_owned(),
+                stage_attempt_num: stage.stage_attempt_num(),
+                partitions: stage.partitions(),
+                completed_tasks: stage.completed_tasks(),
+                input_stages: stage_ids
+                    .iter()
+                    .filter(|id| stages[*id].output_links().contains(stage_id))
+                    .copied()
+                    .collect(),
+                output_stages: stage.output_links().to_vec(),
+                task_states,
+                tasks,
+            }
+        })
+        .collect();
+
+    Ok(Json(JobDetailResponse {
+        job_id: graph.job_id().to_owned(),
+        job_name: graph.job_name().to_owned(),
+        job_status: describe_job_status(
+            graph.status(),
+            graph.end_time().saturating_sub(graph.start_time()),
+        ),
+        queued_at: graph.queued_at(),
+        start_time: graph.start_time(),
+        end_time: graph.end_time(),
+        stages,
+    }))
+}
+
+fn task_detail(partition: usize, info: &TaskInfo) -> TaskDetailResponse {
+    let (state, executor_id, error) = match &info.task_status {
+        task_status::Status::Running(task) => {
+            ("running", Some(task.executor_id.clone()), None)
+        }
+        task_status::Status::Successful(task) => {
+            ("successful", Some(task.executor_id.clone()), None)
+        }
+        task_status::Status::Failed(task) => ("failed", None, Some(task.error.clone())),
+    };
+    TaskDetailResponse {
+        partition,
+        task_id: info.task_id,
+        state,
+        executor_id,
+        error,
+        scheduled_time: info.scheduled_time,
+        launch_time: info.launch_time,
+        start_exec_time: info.start_exec_time,
+        end_exec_time: info.end_exec_time,
+        finish_time: info.finish_time,
+        duration_ms: (info.end_exec_time > 0)
+            .then(|| info.end_exec_time.saturating_sub(info.start_exec_time)),
+    }
+}
+
+pub async fn get_job_plan<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewJob, Some(&job_id)).await?;
+
+    let graph = data_server
+        .state
+        .task_manager
+        .get_job_execution_graph(&job_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut stages: Vec<StagePlanResponse> = graph
+        .stages()
+        .iter()
+        .map(|(stage_id, stage)| {
+            let metrics = stage.stage_metrics().unwrap_or_default();
+            StagePlanResponse {
+                stage_id: *stage_id,
+                plan: plan_node(stage.plan(), metrics, &mut 0),
+            }
+        })
+        .collect();
+    stages.sort_by_key(|stage| stage.stage_id);
+
+    Ok(Json(JobPlanResponse { stages }))
+}
+
+/// The operator and its inputs, with the metrics of the operators of the stage, which
+/// are in pre-order of the plan. `index` is the position of the operator in pre-order
+fn plan_node(
+    plan: &dyn ExecutionPlan,
+    metrics: &[MetricsSet],
+    index: &mut usize,
+) -> PlanNodeResponse {
+    let description = displayable(plan).one_line().to_string();
+    let operator_metrics = metrics
+        .get(*index)
+        .map(|metrics| summarize_metrics(std::slice::from_ref(metrics)))
+        .unwrap_or_default();
+    *index += 1;
+    PlanNodeResponse {
+        name: plan.name().to_owned(),
+        description,
+        metrics: operator_metrics.into_iter().collect(),
+        children: plan
+            .children()
+            .into_iter()
+            .map(|child| plan_node(child.as_ref(), metrics, index))
+            .collect(),
+    }
+}
+
 pub async fn cancel_job<
     T: AsLogicalPlan + Clone + Send + Sync + 'static,
     U: AsExecutionPlan + Send + Sync + 'static,
 >(
     State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
     Path(job_id): Path<String>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::CancelJob, Some(&job_id)).await?;
+
     // 404 if job doesn't exist
     data_server
         .state
@@ -197,6 +803,51 @@ pub async fn cancel_job<
     Ok(Json(CancelJobResponse { cancelled: true }))
 }
 
+#[derive(Debug, serde::Serialize)]
+pub struct TaskLogsResponse {
+    pub job_id: String,
+    pub task_id: usize,
+    pub executor_id: String,
+    pub lines: Vec<String>,
+}
+
+pub async fn get_task_logs<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+    Path((job_id, task_id)): Path<(String, usize)>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewJob, Some(&job_id)).await?;
+
+    let graph = data_server
+        .state
+        .task_manager
+        .get_job_execution_graph(&job_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let executor_id = graph
+        .task_executor(task_id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .to_owned();
+    let lines = data_server
+        .state
+        .executor_manager
+        .get_task_logs(&executor_id, &job_id, task_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(TaskLogsResponse {
+        job_id,
+        task_id,
+        executor_id,
+        lines,
+    }))
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct QueryStagesResponse {
     pub stages: Vec<QueryStageSummary>,
@@ -207,8 +858,11 @@ pub async fn get_query_stages<
     U: AsExecutionPlan + Send + Sync + 'static,
 >(
     State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
     Path(job_id): Path<String>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewJob, Some(&job_id)).await?;
+
     if let Some(graph) = data_server
         .state
         .task_manager
@@ -270,6 +924,57 @@ pub async fn get_query_stages<
     }
 }
 
+#[derive(Debug, serde::Serialize)]
+pub struct StageMetricsResponse {
+    pub stage_id: u32,
+    pub stage_status: String,
+    pub partitions: u32,
+    pub completed_tasks: u32,
+    pub metrics: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct JobMetricsResponse {
+    pub stages: Vec<StageMetricsResponse>,
+    pub metrics: BTreeMap<String, u64>,
+}
+
+pub async fn get_job_metrics<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewJob, Some(&job_id)).await?;
+
+    let graph = data_server
+        .state
+        .task_manager
+        .get_job_execution_graph(&job_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let job_metrics = job_metrics(&graph);
+    let stages = job_metrics
+        .stages
+        .into_iter()
+        .map(|stage| StageMetricsResponse {
+            stage_id: stage.stage_id,
+            stage_status: stage.status,
+            partitions: stage.partitions,
+            completed_tasks: stage.completed_tasks,
+            metrics: stage.metrics.into_iter().collect(),
+        })
+        .collect();
+
+    Ok(Json(JobMetricsResponse {
+        stages,
+        metrics: job_metrics.metrics.into_iter().collect(),
+    }))
+}
+
 fn get_elapsed_compute_nanos(metrics: &[MetricsSet]) -> String {
     let nanos: usize = metrics
         .iter()
@@ -306,8 +1011,11 @@ pub async fn get_job_dot_graph<
     U: AsExecutionPlan + Send + Sync + 'static,
 >(
     State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
     Path(job_id): Path<String>,
 ) -> Result<String, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewJob, Some(&job_id)).await?;
+
     if let Some(graph) = data_server
         .state
         .task_manager
@@ -327,8 +1035,11 @@ pub async fn get_query_stage_dot_graph<
     U: AsExecutionPlan + Send + Sync + 'static,
 >(
     State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
     Path((job_id, stage_id)): Path<(String, usize)>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewJob, Some(&job_id)).await?;
+
     if let Some(graph) = data_server
         .state
         .task_manager
@@ -348,9 +1059,11 @@ pub async fn get_job_svg_graph<
     U: AsExecutionPlan + Send + Sync + 'static,
 >(
     State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
     Path(job_id): Path<String>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let dot = get_job_dot_graph(State(data_server.clone()), Path(job_id)).await?;
+    let dot =
+        get_job_dot_graph(State(data_server.clone()), headers, Path(job_id)).await?;
     match graphviz_rust::parse(&dot) {
         Ok(graph) => {
             let result = exec(
@@ -373,6 +1086,61 @@ pub async fn get_job_svg_graph<
     }
 }
 
+/// IDs of the jobs in the event log which the caller is allowed to view
+pub async fn get_history_jobs<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    let identity = data_server
+        .identify(request_auth(&headers))
+        .await
+        .map_err(access_error_status)?;
+    if data_server.state.config.access_control.is_some() && identity.is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let reader = EventLogReader::from_config(&data_server.state.config)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let job_ids: Vec<String> = reader
+        .job_ids()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .filter(|job_id| {
+            data_server
+                .authorize(identity.as_ref(), Operation::ViewJob, Some(job_id))
+                .is_ok()
+        })
+        .collect();
+
+    Ok(Json(job_ids))
+}
+
+/// History of a completed job replayed from the event log
+pub async fn get_history_job<
+    T: AsLogicalPlan + Clone + Send + Sync + 'static,
+    U: AsExecutionPlan + Send + Sync + 'static,
+>(
+    State(data_server): State<Arc<SchedulerServer<T, U>>>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    authorize(&data_server, &headers, Operation::ViewJob, Some(&job_id)).await?;
+    let reader = EventLogReader::from_config(&data_server.state.config)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let job = reader
+        .job(&job_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(job))
+}
+
 pub async fn get_scheduler_metrics<
     T: AsLogicalPlan + Clone + Send + Sync + 'static,
     U: AsExecutionPlan + Send + Sync + 'static,
@@ -13,7 +13,8 @@
 mod handlers;
 
 use crate::scheduler_server::SchedulerServer;
-use axum::routing::patch;
+use axum::middleware;
+use axum::routing::{post, put};
 use axum::{routing::get, Router};
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
@@ -26,14 +27,48 @@ pub fn get_routes<
     scheduler_server: Arc<SchedulerServer<T, U>>,
 ) -> Router {
     Router::new()
+        .route("/", get(handlers::get_dashboard))
         .route("/api/state", get(handlers::get_scheduler_state::<T, U>))
         .route("/api/executors", get(handlers::get_executors::<T, U>))
+        .route(
+            "/api/executors/settings",
+            get(handlers::get_executor_settings::<T, U>)
+                .put(handlers::roll_out_executor_settings::<T, U>),
+        )
+        .route(
+            "/api/executors/registration_tokens",
+            put(handlers::rotate_executor_registration_tokens::<T, U>),
+        )
+        .route(
+            "/api/executor/:executor_id/decommission",
+            post(handlers::decommission_executor::<T, U>),
+        )
+        .route(
+            "/api/executor/:executor_id/resources",
+            get(handlers::get_executor_resources::<T, U>),
+        )
+        .route(
+            "/api/executor/:executor_id/profile",
+            get(handlers::get_executor_profile::<T, U>),
+        )
+        .route(
+            "/api/cluster/utilization",
+            get(handlers::get_cluster_utilization::<T, U>),
+        )
         .route("/api/jobs", get(handlers::get_jobs::<T, U>))
-        .route("/api/job/:job_id", patch(handlers::cancel_job::<T, U>))
+        .route(
+            "/api/job/:job_id",
+            get(handlers::get_job::<T, U>).patch(handlers::cancel_job::<T, U>),
+        )
+        .route("/api/job/:job_id/plan", get(handlers::get_job_plan::<T, U>))
         .route(
             "/api/job/:job_id/stages",
             get(handlers::get_query_stages::<T, U>),
         )
+        .route(
+            "/api/job/:job_id/metrics",
+            get(handlers::get_job_metrics::<T, U>),
+        )
         .route(
             "/api/job/:job_id/dot",
             get(handlers::get_job_dot_graph::<T, U>),
@@ -42,10 +77,24 @@ pub fn get_routes<
             "/api/job/:job_id/stage/:stage_id/dot",
             get(handlers::get_query_stage_dot_graph::<T, U>),
         )
+        .route(
+            "/api/job/:job_id/task/:task_id/logs",
+            get(handlers::get_task_logs::<T, U>),
+        )
         .route(
             "/api/job/:job_id/dot_svg",
             get(handlers::get_job_svg_graph::<T, U>),
         )
+        .route("/api/history/jobs", get(handlers::get_history_jobs::<T, U>))
+        .route(
+            "/api/history/job/:job_id",
+            get(handlers::get_history_job::<T, U>),
+        )
         .route("/api/metrics", get(handlers::get_scheduler_metrics::<T, U>))
+        .route("/metrics", get(handlers::get_scheduler_metrics::<T, U>))
+        .layer(middleware::from_fn_with_state(
+            scheduler_server.clone(),
+            handlers::redirect_writes::<T, U>,
+        ))
         .with_state(scheduler_server)
 }
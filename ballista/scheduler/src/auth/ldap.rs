@@ -0,0 +1,167 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! [AuthProvider] binding to an LDAP directory with the credentials of the caller
+
+use ballista_core::error::{BallistaError, Result};
+use ldap3::{dn_escape, ldap_escape, LdapConnAsync, Scope, SearchEntry};
+use log::warn;
+
+use crate::auth::{AuthProvider, Credentials, Identity};
+
+/// Authenticates basic credentials by binding to an LDAP directory as the user, and
+/// resolves the groups of the user with a search under the group search base
+#[derive(Debug, Clone)]
+pub struct LdapAuthProvider {
+    url: String,
+    user_dn_template: String,
+    group_search_base: Option<String>,
+    group_filter: String,
+    group_attribute: String,
+}
+
+impl LdapAuthProvider {
+    /// Create a provider binding to the directory at `url`. The DN of a user is
+    /// `user_dn_template` with `{username}` replaced by the escaped username, e.g.
+    /// `uid={username},ou=people,dc=example,dc=org`.
+    pub fn new(url: impl Into<String>, user_dn_template: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            user_dn_template: user_dn_template.into(),
+            group_search_base: None,
+            group_filter: "(member={dn})".to_owned(),
+            group_attribute: "cn".to_owned(),
+        }
+    }
+
+    /// Search the groups of users under `base`. Groups are not resolved if not set.
+    pub fn with_group_search_base(mut self, base: impl Into<String>) -> Self {
+        self.group_search_base = Some(base.into());
+        self
+    }
+
+    /// Filter matching the groups of a user, where `{dn}` is replaced by the DN of the
+    /// user and `{username}` by its username, `(member={dn})` by default
+    pub fn with_group_filter(mut self, filter: impl Into<String>) -> Self {
+        self.group_filter = filter.into();
+        self
+    }
+
+    /// Attribute holding the name of a group, `cn` by default
+    pub fn with_group_attribute(mut self, attribute: impl Into<String>) -> Self {
+        self.group_attribute = attribute.into();
+        self
+    }
+
+    fn user_dn(&self, username: &str) -> String {
+        self.user_dn_template
+            .replace("{username}", &dn_escape(username))
+    }
+
+    fn group_filter(&self, username: &str, user_dn: &str) -> String {
+        self.group_filter
+            .replace("{dn}", &ldap_escape(user_dn))
+            .replace("{username}", &ldap_escape(username))
+    }
+}
+
+fn ldap_error(e: ldap3::LdapError) -> BallistaError {
+    BallistaError::General(format!("LDAP authentication failed: {e}"))
+}
+
+#[tonic::async_trait]
+impl AuthProvider for LdapAuthProvider {
+    async fn authenticate(&self, credentials: &Credentials) -> Result<Identity> {
+        let Credentials::Basic { username, password } = credentials else {
+            return Err(BallistaError::General(
+                "LDAP authentication requires basic credentials".to_owned(),
+            ));
+        };
+        // An empty password would be an unauthenticated bind, which succeeds on most
+        // directories
+        if username.is_empty() || password.is_empty() {
+            return Err(BallistaError::General(
+                "LDAP authentication requires a username and a password".to_owned(),
+            ));
+        }
+
+        let (conn, mut ldap) = LdapConnAsync::new(&self.url).await.map_err(ldap_error)?;
+        ldap3::drive!(conn);
+
+        let user_dn = self.user_dn(username);
+        ldap.simple_bind(&user_dn, password)
+            .await
+            .and_then(|result| result.success())
+            .map_err(ldap_error)?;
+
+        let mut groups = vec![];
+        if let Some(base) = &self.group_search_base {
+            let (entries, _) = ldap
+                .search(
+                    base,
+                    Scope::Subtree,
+                    &self.group_filter(username, &user_dn),
+                    vec![self.group_attribute.as_str()],
+                )
+                .await
+                .and_then(|result| result.success())
+                .map_err(ldap_error)?;
+            for entry in entries {
+                let mut entry = SearchEntry::construct(entry);
+                groups.extend(
+                    entry
+                        .attrs
+                        .remove(&self.group_attribute)
+                        .unwrap_or_default(),
+                );
+            }
+        }
+
+        if let Err(e) = ldap.unbind().await {
+            warn!("Failed to unbind from {}: {e}", self.url);
+        }
+
+        Ok(Identity::new(username.clone()).with_groups(groups))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auth::ldap::LdapAuthProvider;
+
+    #[test]
+    fn test_escape_user_input() {
+        let provider = LdapAuthProvider::new(
+            "ldap://localhost:389",
+            "uid={username},ou=people,dc=example,dc=org",
+        )
+        .with_group_filter("(&(objectClass=groupOfNames)(member={dn}))");
+
+        assert_eq!(
+            provider.user_dn("alice"),
+            "uid=alice,ou=people,dc=example,dc=org"
+        );
+        assert_eq!(
+            provider.user_dn("alice,ou=admins"),
+            "uid=alice\\2cou\\3dadmins,ou=people,dc=example,dc=org"
+        );
+        assert_eq!(
+            provider.group_filter("*", "uid=*)(uid=*"),
+            "(&(objectClass=groupOfNames)(member=uid=\\2a\\29\\28uid=\\2a))"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Authentication of the callers of the scheduler. An [AuthProvider] validates the
+//! credentials of the `authorization` header of a request against an identity system,
+//! and resolves the [Identity] whose roles are then checked by access control.
+
+#[cfg(feature = "ldap")]
+pub mod ldap;
+#[cfg(feature = "oidc")]
+pub mod oidc;
+
+use std::fmt;
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use base64::Engine;
+
+use crate::config::Config;
+
+/// Key of the gRPC metadata, or HTTP header, carrying the credentials of a request
+pub const AUTHORIZATION_METADATA_KEY: &str = "authorization";
+
+/// Authenticated caller of the scheduler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Name of the caller
+    pub principal: String,
+    /// Groups the caller is a member of in the identity system
+    pub groups: Vec<String>,
+}
+
+impl Identity {
+    pub fn new(principal: impl Into<String>) -> Self {
+        Self {
+            principal: principal.into(),
+            groups: vec![],
+        }
+    }
+
+    pub fn with_groups(mut self, groups: Vec<String>) -> Self {
+        self.groups = groups;
+        self
+    }
+}
+
+/// Credentials carried by the `authorization` header of a request
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// `Bearer <token>`
+    Bearer(String),
+    /// `Basic <base64 of username:password>`
+    Basic { username: String, password: String },
+}
+
+impl Credentials {
+    /// Parse the value of an `authorization` header, returning None if it is malformed
+    /// or uses an unsupported scheme
+    pub fn from_authorization_header(value: &str) -> Option<Self> {
+        let (scheme, credentials) = value.trim().split_once(' ')?;
+        let credentials = credentials.trim();
+        if scheme.eq_ignore_ascii_case("bearer") {
+            Some(Credentials::Bearer(credentials.to_owned()))
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(credentials)
+                .ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (username, password) = decoded.split_once(':')?;
+            Some(Credentials::Basic {
+                username: username.to_owned(),
+                password: password.to_owned(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    // Keep secrets out of logs
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Bearer(_) => write!(f, "Bearer(..)"),
+            Credentials::Basic { username, .. } => {
+                write!(f, "Basic {{ username: {username:?}, .. }}")
+            }
+        }
+    }
+}
+
+/// Authentication material carried by a request
+#[derive(Debug, Clone, Default)]
+pub struct RequestAuth {
+    /// Principal set by a trusted authenticating proxy
+    pub principal: Option<String>,
+    /// Value of the `authorization` header
+    pub authorization: Option<String>,
+}
+
+/// Validates the credentials of requests against an identity system
+#[tonic::async_trait]
+pub trait AuthProvider: Send + Sync + fmt::Debug {
+    /// Authenticate credentials, returning the identity of the caller
+    async fn authenticate(&self, credentials: &Credentials) -> Result<Identity>;
+}
+
+/// Create the [AuthProvider] configured on the command line, if any
+pub(crate) fn auth_provider_from_config(
+    opt: &Config,
+) -> Result<Option<Arc<dyn AuthProvider>>> {
+    match (&opt.oidc_issuer, &opt.ldap_url) {
+        (Some(_), Some(_)) => Err(BallistaError::General(
+            "Only one of oidc_issuer and ldap_url can be set".to_owned(),
+        )),
+        (Some(issuer), None) => oidc_auth_provider(opt, issuer).map(Some),
+        (None, Some(url)) => ldap_auth_provider(opt, url).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+#[cfg(feature = "oidc")]
+fn oidc_auth_provider(opt: &Config, issuer: &str) -> Result<Arc<dyn AuthProvider>> {
+    let mut provider = oidc::OidcAuthProvider::new(issuer)
+        .with_principal_claim(&opt.oidc_principal_claim)
+        .with_groups_claim(&opt.oidc_groups_claim);
+    if let Some(audience) = &opt.oidc_audience {
+        provider = provider.with_audience(audience);
+    }
+
+    Ok(Arc::new(provider))
+}
+
+#[cfg(not(feature = "oidc"))]
+fn oidc_auth_provider(_opt: &Config, _issuer: &str) -> Result<Arc<dyn AuthProvider>> {
+    Err(BallistaError::General(
+        "OIDC authentication requires the scheduler to be built with the oidc feature"
+            .to_owned(),
+    ))
+}
+
+#[cfg(feature = "ldap")]
+fn ldap_auth_provider(opt: &Config, url: &str) -> Result<Arc<dyn AuthProvider>> {
+    let mut provider = ldap::LdapAuthProvider::new(url, &opt.ldap_user_dn_template)
+        .with_group_filter(&opt.ldap_group_filter)
+        .with_group_attribute(&opt.ldap_group_attribute);
+    if let Some(base) = &opt.ldap_group_search_base {
+        provider = provider.with_group_search_base(base);
+    }
+
+    Ok(Arc::new(provider))
+}
+
+#[cfg(not(feature = "ldap"))]
+fn ldap_auth_provider(_opt: &Config, _url: &str) -> Result<Arc<dyn AuthProvider>> {
+    Err(BallistaError::General(
+        "LDAP authentication requires the scheduler to be built with the ldap feature"
+            .to_owned(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auth::Credentials;
+
+    #[test]
+    fn test_parse_authorization_header() {
+        assert_eq!(
+            Credentials::from_authorization_header("Bearer abc.def.ghi"),
+            Some(Credentials::Bearer("abc.def.ghi".to_owned()))
+        );
+        // "alice:s3cr:et"
+        assert_eq!(
+            Credentials::from_authorization_header("basic YWxpY2U6czNjcjpldA=="),
+            Some(Credentials::Basic {
+                username: "alice".to_owned(),
+                password: "s3cr:et".to_owned(),
+            })
+        );
+        assert_eq!(Credentials::from_authorization_header("Bearer"), None);
+        assert_eq!(Credentials::from_authorization_header("Basic !!!"), None);
+        assert_eq!(Credentials::from_authorization_header("Digest abc"), None);
+    }
+}
@@ -0,0 +1,301 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Policies deciding which running jobs the queued tasks bound to free executor slots
+//! are taken from, and in which order.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use ballista_core::serde::protobuf::job_status;
+
+use crate::state::task_manager::JobInfoCache;
+
+/// What a [JobSchedulingPolicy] knows of a running job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobSchedulingInfo {
+    pub job_id: String,
+    /// Priority of the job, higher values being more important
+    pub priority: u32,
+    /// Timestamp of when the job was submitted
+    pub queued_at: u64,
+    /// Number of tasks of the job running on executors
+    pub running_tasks: usize,
+    /// Number of queued tasks of the job, ready to be bound
+    pub pending_tasks: usize,
+}
+
+/// The maximum number of queued tasks of a job bound in a scheduling round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobShare {
+    pub job_id: String,
+    pub max_tasks: usize,
+}
+
+/// Policy sharing the free executor slots among the queued tasks of the running jobs.
+///
+/// Tasks are bound job by job in the order returned by the policy, so that the queued
+/// tasks of the jobs coming first take the slots before those of the jobs coming later.
+/// Running tasks are never preempted.
+pub trait JobSchedulingPolicy: Debug + Send + Sync {
+    /// Returns the share of the `available_slots` free slots of each job of `jobs`, in
+    /// the order their tasks are bound
+    fn schedule(
+        &self,
+        jobs: Vec<JobSchedulingInfo>,
+        available_slots: usize,
+    ) -> Vec<JobShare>;
+}
+
+/// Binds the queued tasks of the jobs submitted first first
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FifoJobScheduling;
+
+impl JobSchedulingPolicy for FifoJobScheduling {
+    fn schedule(
+        &self,
+        mut jobs: Vec<JobSchedulingInfo>,
+        _available_slots: usize,
+    ) -> Vec<JobShare> {
+        jobs.sort_by(|a, b| a.queued_at.cmp(&b.queued_at).then(a.job_id.cmp(&b.job_id)));
+        unbounded_shares(jobs)
+    }
+}
+
+/// Binds the queued tasks of the jobs with the highest priority first, and those of
+/// jobs of the same priority in the order they were submitted. Lower priority jobs only
+/// get the slots left over by higher priority jobs
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PriorityJobScheduling;
+
+impl JobSchedulingPolicy for PriorityJobScheduling {
+    fn schedule(
+        &self,
+        mut jobs: Vec<JobSchedulingInfo>,
+        _available_slots: usize,
+    ) -> Vec<JobShare> {
+        jobs.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then(a.queued_at.cmp(&b.queued_at))
+                .then(a.job_id.cmp(&b.job_id))
+        });
+        unbounded_shares(jobs)
+    }
+}
+
+/// Shares the slots among the jobs in proportion to their priority plus one, counting
+/// the tasks they already run, so that a long job with many tasks doesn't hold back
+/// jobs submitted after it. A job with less queued tasks than its share leaves the rest
+/// of its share to the other jobs
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FairShareJobScheduling;
+
+impl JobSchedulingPolicy for FairShareJobScheduling {
+    fn schedule(
+        &self,
+        jobs: Vec<JobSchedulingInfo>,
+        available_slots: usize,
+    ) -> Vec<JobShare> {
+        // Give the slots one by one to the job with the least slots per unit of weight
+        let mut shares = vec![0usize; jobs.len()];
+        for _ in 0..available_slots {
+            let next = (0..jobs.len())
+                .filter(|&i| shares[i] < jobs[i].pending_tasks)
+                .min_by(|&i, &j| compare_load(&jobs[i], shares[i], &jobs[j], shares[j]));
+            match next {
+                Some(i) => shares[i] += 1,
+                None => break,
+            }
+        }
+
+        let mut order: Vec<usize> = (0..jobs.len()).collect();
+        order.sort_by(|&i, &j| compare_load(&jobs[i], 0, &jobs[j], 0));
+        order
+            .into_iter()
+            .map(|i| JobShare {
+                job_id: jobs[i].job_id.clone(),
+                max_tasks: shares[i],
+            })
+            .collect()
+    }
+}
+
+/// Compares the slots per unit of weight of two jobs given extra slots, the weight of a
+/// job being its priority plus one
+fn compare_load(
+    a: &JobSchedulingInfo,
+    a_extra: usize,
+    b: &JobSchedulingInfo,
+    b_extra: usize,
+) -> Ordering {
+    let a_load = (a.running_tasks + a_extra) as u128 * (b.priority as u128 + 1);
+    let b_load = (b.running_tasks + b_extra) as u128 * (a.priority as u128 + 1);
+    a_load
+        .cmp(&b_load)
+        .then(a.queued_at.cmp(&b.queued_at))
+        .then(a.job_id.cmp(&b.job_id))
+}
+
+fn unbounded_shares(jobs: Vec<JobSchedulingInfo>) -> Vec<JobShare> {
+    jobs.into_iter()
+        .map(|job| JobShare {
+            job_id: job.job_id,
+            max_tasks: usize::MAX,
+        })
+        .collect()
+}
+
+/// A running job whose queued tasks are bound in a scheduling round
+#[derive(Clone)]
+pub struct ScheduledJob {
+    pub job_id: String,
+    pub job_info: JobInfoCache,
+    /// Maximum number of queued tasks of the job left to bind in this round
+    pub max_tasks: usize,
+}
+
+impl ScheduledJob {
+    /// The running jobs of `active_jobs`, in no particular order and without limit on
+    /// the number of their tasks bound
+    pub fn unbounded(active_jobs: &HashMap<String, JobInfoCache>) -> Vec<Self> {
+        active_jobs
+            .iter()
+            .map(|(job_id, job_info)| ScheduledJob {
+                job_id: job_id.clone(),
+                job_info: job_info.clone(),
+                max_tasks: usize::MAX,
+            })
+            .collect()
+    }
+}
+
+/// Orders the running jobs of `active_jobs` and caps the number of their queued tasks
+/// bound to the `available_slots` free slots, according to the `policy`
+pub(crate) async fn schedule_jobs(
+    policy: &dyn JobSchedulingPolicy,
+    active_jobs: &HashMap<String, JobInfoCache>,
+    available_slots: usize,
+) -> Vec<ScheduledJob> {
+    let mut jobs = Vec::with_capacity(active_jobs.len());
+    for (job_id, job_info) in active_jobs.iter() {
+        if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
+            continue;
+        }
+        let graph = job_info.execution_graph.read().await;
+        jobs.push(JobSchedulingInfo {
+            job_id: job_id.clone(),
+            priority: graph.priority(),
+            queued_at: graph.queued_at(),
+            running_tasks: graph.running_tasks().len(),
+            pending_tasks: graph.available_tasks(),
+        });
+    }
+
+    policy
+        .schedule(jobs, available_slots)
+        .into_iter()
+        .filter_map(|share| {
+            let job_info = active_jobs.get(&share.job_id)?.clone();
+            Some(ScheduledJob {
+                job_id: share.job_id,
+                job_info,
+                max_tasks: share.max_tasks,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(
+        job_id: &str,
+        priority: u32,
+        queued_at: u64,
+        running_tasks: usize,
+        pending_tasks: usize,
+    ) -> JobSchedulingInfo {
+        JobSchedulingInfo {
+            job_id: job_id.to_owned(),
+            priority,
+            queued_at,
+            running_tasks,
+            pending_tasks,
+        }
+    }
+
+    fn shares(shares: Vec<JobShare>) -> Vec<(String, usize)> {
+        shares
+            .into_iter()
+            .map(|share| (share.job_id, share.max_tasks))
+            .collect()
+    }
+
+    #[test]
+    fn fifo_and_priority_order() {
+        let jobs = vec![
+            job("a", 0, 3, 0, 4),
+            job("b", 5, 2, 0, 4),
+            job("c", 0, 1, 0, 4),
+            job("d", 5, 4, 0, 4),
+        ];
+
+        let fifo = shares(FifoJobScheduling.schedule(jobs.clone(), 8));
+        let order: Vec<_> = fifo.iter().map(|(job_id, _)| job_id.as_str()).collect();
+        assert_eq!(vec!["c", "b", "a", "d"], order);
+        assert!(fifo.iter().all(|(_, max_tasks)| *max_tasks == usize::MAX));
+
+        let priority = shares(PriorityJobScheduling.schedule(jobs, 8));
+        let order: Vec<_> = priority.iter().map(|(job_id, _)| job_id.as_str()).collect();
+        assert_eq!(vec!["b", "d", "c", "a"], order);
+    }
+
+    #[test]
+    fn fair_share() {
+        // The running tasks of a count against its share
+        let jobs = vec![job("a", 0, 1, 6, 10), job("b", 0, 2, 0, 10)];
+        assert_eq!(
+            vec![("b".to_owned(), 7), ("a".to_owned(), 1)],
+            shares(FairShareJobScheduling.schedule(jobs, 8))
+        );
+
+        // Shares are proportional to the priority plus one
+        let jobs = vec![job("a", 0, 1, 0, 10), job("b", 2, 2, 0, 10)];
+        assert_eq!(
+            vec![("a".to_owned(), 2), ("b".to_owned(), 6)],
+            shares(FairShareJobScheduling.schedule(jobs, 8))
+        );
+
+        // Jobs with less queued tasks than their share leave the rest to the others
+        let jobs = vec![
+            job("a", 0, 1, 0, 1),
+            job("b", 0, 2, 0, 10),
+            job("c", 0, 3, 0, 0),
+        ];
+        assert_eq!(
+            vec![
+                ("a".to_owned(), 1),
+                ("b".to_owned(), 7),
+                ("c".to_owned(), 0)
+            ],
+            shares(FairShareJobScheduling.schedule(jobs, 8))
+        );
+    }
+}
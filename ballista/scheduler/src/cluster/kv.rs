@@ -0,0 +1,607 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ballista_core::error::{BallistaError, ErrorCode, Result};
+use ballista_core::extension::SessionConfigHelperExt;
+use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::protobuf::{
+    self, ExchangeCalibrationState, FailedJob, JobSessionConfig, QueuedJob,
+    SessionSettings, SlotReservation, SuccessfulJob, TableStatistics,
+};
+use ballista_core::serde::BallistaCodec;
+use ballista_core::ConfigProducer;
+use datafusion::prelude::{SessionConfig, SessionContext};
+use log::{error, info, warn};
+use prost::Message;
+
+use crate::cluster::memory::InMemoryJobState;
+use crate::cluster::storage::{KeyValueStore, Keyspace};
+use crate::cluster::{JobState, JobStateEventStream, JobStatus};
+use crate::scheduler_server::{timestamp_millis, SessionBuilder};
+use crate::state::execution_graph::ExecutionGraph;
+use crate::state::session_manager::create_datafusion_context;
+
+/// Key of the calibration of the exchanges, of which the cluster has one
+const EXCHANGE_CALIBRATION_KEY: &str = "calibration";
+
+/// Implementation of `JobState` which persists the jobs and sessions of the scheduler to
+/// a [KeyValueStore], for a scheduler taking over from it to recover them. The jobs and
+/// sessions are served from memory, the store being written through
+pub struct KeyValueJobState {
+    store: Arc<dyn KeyValueStore>,
+    local: InMemoryJobState,
+    /// `SessionBuilder` for building the sessions of the recovered jobs
+    session_builder: SessionBuilder,
+    config_producer: ConfigProducer,
+    /// Codec of the plans of the stages of the jobs
+    codec: BallistaCodec,
+}
+
+impl KeyValueJobState {
+    pub fn new(
+        store: Arc<dyn KeyValueStore>,
+        scheduler: impl Into<String>,
+        session_builder: SessionBuilder,
+        config_producer: ConfigProducer,
+        codec: BallistaCodec,
+    ) -> Self {
+        Self {
+            store,
+            local: InMemoryJobState::new(
+                scheduler,
+                session_builder.clone(),
+                config_producer.clone(),
+            ),
+            session_builder,
+            config_producer,
+            codec,
+        }
+    }
+
+    async fn get_message<M: Message + Default>(
+        &self,
+        keyspace: Keyspace,
+        key: &str,
+    ) -> Result<Option<M>> {
+        self.store
+            .get(keyspace, key)
+            .await?
+            .map(|value| decode(&value))
+            .transpose()
+    }
+
+    async fn put_message(
+        &self,
+        keyspace: Keyspace,
+        key: &str,
+        message: &impl Message,
+    ) -> Result<()> {
+        self.store.put(keyspace, key, message.encode_to_vec()).await
+    }
+
+    /// Persist the final status of a job, replacing its active state
+    async fn complete_job(&self, job_id: &str, status: &JobStatus) -> Result<()> {
+        self.put_message(Keyspace::CompletedJobs, job_id, status)
+            .await?;
+        self.store.delete(Keyspace::QueuedJobs, job_id).await?;
+        self.store.delete(Keyspace::ActiveJobs, job_id).await?;
+        self.store.delete(Keyspace::JobSessions, job_id).await
+    }
+
+    /// Decode the graph of an active job with the settings of the session it was
+    /// submitted with
+    async fn decode_graph(
+        &self,
+        job_id: &str,
+        graph: protobuf::ExecutionGraph,
+    ) -> Result<ExecutionGraph> {
+        let settings: JobSessionConfig = self
+            .get_message(Keyspace::JobSessions, job_id)
+            .await?
+            .unwrap_or_default();
+        let config =
+            (self.config_producer)().update_from_key_value_pair(&settings.configs);
+        let session_ctx =
+            create_datafusion_context(&config, self.session_builder.clone())?;
+        ExecutionGraph::decode(graph, &session_ctx, &self.codec)
+    }
+}
+
+#[async_trait]
+impl JobState for KeyValueJobState {
+    fn accept_job(&self, job_id: &str, job_name: &str, queued_at: u64) -> Result<()> {
+        self.local.accept_job(job_id, job_name, queued_at)?;
+
+        let store = self.store.clone();
+        let job_id = job_id.to_owned();
+        let status = JobStatus {
+            job_id: job_id.clone(),
+            job_name: job_name.to_owned(),
+            status: Some(Status::Queued(QueuedJob { queued_at })),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = store
+                .put(Keyspace::QueuedJobs, &job_id, status.encode_to_vec())
+                .await
+            {
+                warn!("Failed to persist queued job {job_id}: {e}");
+            }
+        });
+        Ok(())
+    }
+
+    fn pending_job_number(&self) -> usize {
+        self.local.pending_job_number()
+    }
+
+    async fn submit_job(&self, job_id: String, graph: &ExecutionGraph) -> Result<()> {
+        self.local.submit_job(job_id.clone(), graph).await?;
+
+        let settings = JobSessionConfig {
+            session_id: graph.session_id().to_owned(),
+            configs: graph.session_config().to_key_value_pairs(),
+        };
+        self.put_message(Keyspace::JobSessions, &job_id, &settings)
+            .await?;
+        self.put_message(Keyspace::ActiveJobs, &job_id, &graph.encode(&self.codec)?)
+            .await?;
+        self.store.delete(Keyspace::QueuedJobs, &job_id).await
+    }
+
+    async fn get_jobs(&self) -> Result<HashSet<String>> {
+        let mut jobs = self.local.get_jobs().await?;
+        // including the jobs of the other schedulers sharing the store, e.g. of the
+        // leader for its read replicas
+        for keyspace in [Keyspace::ActiveJobs, Keyspace::CompletedJobs] {
+            let values = self.store.scan(keyspace).await?;
+            jobs.extend(values.into_iter().map(|(job_id, _)| job_id));
+        }
+        Ok(jobs)
+    }
+
+    async fn get_job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+        if let Some(status) = self.local.get_job_status(job_id).await? {
+            return Ok(Some(status));
+        }
+        if let Some(status) = self.get_message(Keyspace::CompletedJobs, job_id).await? {
+            return Ok(Some(status));
+        }
+        let graph: Option<protobuf::ExecutionGraph> =
+            self.get_message(Keyspace::ActiveJobs, job_id).await?;
+        Ok(graph.and_then(|graph| graph.status))
+    }
+
+    async fn get_completed_jobs(&self) -> Result<Vec<JobStatus>> {
+        let mut jobs = self.local.get_completed_jobs().await?;
+        let local_jobs = jobs
+            .iter()
+            .map(|status| status.job_id.clone())
+            .collect::<HashSet<_>>();
+        for (job_id, value) in self.store.scan(Keyspace::CompletedJobs).await? {
+            if !local_jobs.contains(&job_id) {
+                jobs.push(decode(&value)?);
+            }
+        }
+        Ok(jobs)
+    }
+
+    async fn get_execution_graph(&self, job_id: &str) -> Result<Option<ExecutionGraph>> {
+        if let Some(graph) = self.local.get_execution_graph(job_id).await? {
+            return Ok(Some(graph));
+        }
+        match self.get_message(Keyspace::ActiveJobs, job_id).await? {
+            Some(graph) => self.decode_graph(job_id, graph).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn save_job(&self, job_id: &str, graph: &ExecutionGraph) -> Result<()> {
+        self.local.save_job(job_id, graph).await?;
+
+        let status = graph.status();
+        if matches!(
+            status.status,
+            Some(Status::Successful(_)) | Some(Status::Failed(_))
+        ) {
+            self.complete_job(job_id, status).await
+        } else {
+            self.put_message(Keyspace::ActiveJobs, job_id, &graph.encode(&self.codec)?)
+                .await
+        }
+    }
+
+    async fn fail_unscheduled_job(&self, job_id: &str, reason: String) -> Result<()> {
+        self.local.fail_unscheduled_job(job_id, reason).await?;
+        if let Some(status) = self.local.get_job_status(job_id).await? {
+            self.complete_job(job_id, &status).await?;
+        }
+        Ok(())
+    }
+
+    async fn succeed_unscheduled_job(
+        &self,
+        job_id: &str,
+        job: SuccessfulJob,
+    ) -> Result<()> {
+        self.local.succeed_unscheduled_job(job_id, job).await?;
+        if let Some(status) = self.local.get_job_status(job_id).await? {
+            self.complete_job(job_id, &status).await?;
+        }
+        Ok(())
+    }
+
+    async fn remove_job(&self, job_id: &str) -> Result<()> {
+        self.local.remove_job(job_id).await?;
+        self.store.delete(Keyspace::CompletedJobs, job_id).await
+    }
+
+    async fn try_acquire_job(&self, job_id: &str) -> Result<Option<ExecutionGraph>> {
+        let Some(graph) = self
+            .get_message::<protobuf::ExecutionGraph>(Keyspace::ActiveJobs, job_id)
+            .await?
+        else {
+            return Ok(None);
+        };
+        if !matches!(
+            graph
+                .status
+                .as_ref()
+                .and_then(|status| status.status.as_ref()),
+            Some(Status::Running(_))
+        ) {
+            return Ok(None);
+        }
+        let graph = self.decode_graph(job_id, graph).await?;
+
+        self.local
+            .accept_job(job_id, graph.job_name(), graph.queued_at())?;
+        self.local.submit_job(job_id.to_owned(), &graph).await?;
+        Ok(Some(graph))
+    }
+
+    async fn recover_jobs(&self) -> Result<Vec<ExecutionGraph>> {
+        // the plans of the jobs which weren't planned yet are lost
+        for (job_id, value) in self.store.scan(Keyspace::QueuedJobs).await? {
+            let status: JobStatus = decode(&value)?;
+            let queued_at = match status.status {
+                Some(Status::Queued(QueuedJob { queued_at })) => queued_at,
+                _ => 0,
+            };
+            if self
+                .store
+                .get(Keyspace::ActiveJobs, &job_id)
+                .await?
+                .is_some()
+            {
+                self.store.delete(Keyspace::QueuedJobs, &job_id).await?;
+                continue;
+            }
+            warn!("Failing job {job_id}, which was queued by a previous scheduler");
+            let status = JobStatus {
+                status: Some(Status::Failed(FailedJob {
+                    error: "The scheduler was restarted before planning the job"
+                        .to_owned(),
+                    queued_at,
+                    started_at: 0,
+                    ended_at: timestamp_millis(),
+                    error_code: ErrorCode::PlanningFailed.to_string(),
+                })),
+                ..status
+            };
+            self.complete_job(&job_id, &status).await?;
+        }
+
+        let mut graphs = vec![];
+        for (job_id, _) in self.store.scan(Keyspace::ActiveJobs).await? {
+            match self.try_acquire_job(&job_id).await {
+                Ok(Some(graph)) => {
+                    info!("Recovered job {job_id}");
+                    graphs.push(graph);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Failed to recover job {job_id}: {e}");
+                    let status = self.get_job_status(&job_id).await?;
+                    let (job_name, queued_at) = match status {
+                        Some(JobStatus {
+                            job_name,
+                            status: Some(Status::Running(running)),
+                            ..
+                        }) => (job_name, running.queued_at),
+                        _ => (String::new(), 0),
+                    };
+                    let status = JobStatus {
+                        job_id: job_id.clone(),
+                        job_name,
+                        status: Some(Status::Failed(FailedJob {
+                            error: format!("Failed to recover the job: {e}"),
+                            queued_at,
+                            started_at: 0,
+                            ended_at: timestamp_millis(),
+                            error_code: ErrorCode::Internal.to_string(),
+                        })),
+                    };
+                    self.complete_job(&job_id, &status).await?;
+                }
+            }
+        }
+        Ok(graphs)
+    }
+
+    async fn job_state_events(&self) -> Result<JobStateEventStream> {
+        self.local.job_state_events().await
+    }
+
+    async fn get_session(&self, session_id: &str) -> Result<Arc<SessionContext>> {
+        if let Ok(session) = self.local.get_session(session_id).await {
+            return Ok(session);
+        }
+        // a session created with a previous scheduler
+        let Some(settings) = self
+            .get_message::<SessionSettings>(Keyspace::Sessions, session_id)
+            .await?
+        else {
+            return Err(BallistaError::General(format!(
+                "No session for {session_id} found"
+            )));
+        };
+        let config = self
+            .produce_config()
+            .update_from_key_value_pair(&settings.configs);
+        self.local.update_session(session_id, &config).await
+    }
+
+    async fn create_session(
+        &self,
+        config: &SessionConfig,
+    ) -> Result<Arc<SessionContext>> {
+        let session = self.local.create_session(config).await?;
+        let settings = SessionSettings {
+            configs: config.to_key_value_pairs(),
+        };
+        self.put_message(Keyspace::Sessions, &session.session_id(), &settings)
+            .await?;
+        Ok(session)
+    }
+
+    async fn update_session(
+        &self,
+        session_id: &str,
+        config: &SessionConfig,
+    ) -> Result<Arc<SessionContext>> {
+        let session = self.local.update_session(session_id, config).await?;
+        let settings = SessionSettings {
+            configs: config.to_key_value_pairs(),
+        };
+        self.put_message(Keyspace::Sessions, session_id, &settings)
+            .await?;
+        Ok(session)
+    }
+
+    async fn remove_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<Arc<SessionContext>>> {
+        let session = self.local.remove_session(session_id).await?;
+        self.store.delete(Keyspace::Sessions, session_id).await?;
+        Ok(session)
+    }
+
+    async fn save_slot_reservation(&self, reservation: &SlotReservation) -> Result<()> {
+        self.put_message(
+            Keyspace::SlotReservations,
+            &reservation.reservation_id,
+            reservation,
+        )
+        .await
+    }
+
+    async fn remove_slot_reservation(&self, reservation_id: &str) -> Result<bool> {
+        let existed = self
+            .store
+            .get(Keyspace::SlotReservations, reservation_id)
+            .await?
+            .is_some();
+        self.store
+            .delete(Keyspace::SlotReservations, reservation_id)
+            .await?;
+        Ok(existed)
+    }
+
+    async fn get_slot_reservations(&self) -> Result<Vec<SlotReservation>> {
+        self.store
+            .scan(Keyspace::SlotReservations)
+            .await?
+            .iter()
+            .map(|(_, value)| decode(value))
+            .collect()
+    }
+
+    async fn save_table_statistics(&self, statistics: &TableStatistics) -> Result<()> {
+        self.put_message(Keyspace::TableStatistics, &statistics.location, statistics)
+            .await
+    }
+
+    async fn get_table_statistics(&self) -> Result<Vec<TableStatistics>> {
+        self.store
+            .scan(Keyspace::TableStatistics)
+            .await?
+            .iter()
+            .map(|(_, value)| decode(value))
+            .collect()
+    }
+
+    async fn save_exchange_calibration(
+        &self,
+        calibration: &ExchangeCalibrationState,
+    ) -> Result<()> {
+        self.put_message(
+            Keyspace::ExchangeCalibration,
+            EXCHANGE_CALIBRATION_KEY,
+            calibration,
+        )
+        .await
+    }
+
+    async fn get_exchange_calibration(&self) -> Result<Option<ExchangeCalibrationState>> {
+        self.get_message(Keyspace::ExchangeCalibration, EXCHANGE_CALIBRATION_KEY)
+            .await
+    }
+
+    fn produce_config(&self) -> SessionConfig {
+        self.local.produce_config()
+    }
+}
+
+fn decode<M: Message + Default>(value: &[u8]) -> Result<M> {
+    M::decode(value).map_err(|e| {
+        BallistaError::Internal(format!("Failed to decode the state of the cluster: {e}"))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::job_status::Status;
+    use ballista_core::serde::BallistaCodec;
+    use ballista_core::utils::{default_config_producer, default_session_builder};
+
+    use crate::cluster::kv::KeyValueJobState;
+    use crate::cluster::storage::KeyValueStore;
+    use crate::cluster::test_util::{
+        test_job_lifecycle, test_job_planning_failure, InMemoryKeyValueStore,
+    };
+    use crate::cluster::JobState;
+    use crate::state::execution_graph::{ExecutionGraph, ExecutionStage};
+    use crate::test_utils::{test_aggregation_plan, test_join_plan};
+
+    fn running_stages(graph: &ExecutionGraph) -> Vec<usize> {
+        let mut stages = graph
+            .stages()
+            .iter()
+            .filter(|(_, stage)| matches!(stage, ExecutionStage::Running(_)))
+            .map(|(stage_id, _)| *stage_id)
+            .collect::<Vec<_>>();
+        stages.sort();
+        stages
+    }
+
+    fn job_state(store: Arc<dyn KeyValueStore>) -> KeyValueJobState {
+        KeyValueJobState::new(
+            store,
+            "",
+            Arc::new(default_session_builder),
+            Arc::new(default_config_producer),
+            BallistaCodec::default(),
+        )
+    }
+
+    // encoding the plans of the join recurses deeper than the stacks of the test threads
+    // allow in debug builds, so the test runs on threads with the stack size of the
+    // scheduler
+    #[test]
+    fn test_key_value_job_lifecycle() -> Result<()> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_stack_size(32 * 1024 * 1024)
+            .build()?;
+        runtime.block_on(runtime.spawn(async {
+            let store = Arc::new(InMemoryKeyValueStore::default());
+            test_job_lifecycle(job_state(store.clone()), test_aggregation_plan(4).await)
+                .await?;
+            test_job_lifecycle(job_state(store), test_join_plan(4).await).await
+        }))?
+    }
+
+    #[tokio::test]
+    async fn test_key_value_job_planning_failure() -> Result<()> {
+        let store = Arc::new(InMemoryKeyValueStore::default());
+        test_job_planning_failure(job_state(store), test_aggregation_plan(4).await)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recover_jobs() -> Result<()> {
+        let store: Arc<dyn KeyValueStore> = Arc::new(InMemoryKeyValueStore::default());
+        let state = job_state(store.clone());
+        let session = state
+            .create_session(&state.produce_config().with_target_partitions(3))
+            .await?;
+
+        let mut graph = test_aggregation_plan(4).await;
+        state.accept_job(graph.job_id(), "", 0)?;
+        state.submit_job(graph.job_id().to_owned(), &graph).await?;
+        // the first stage is running when the scheduler fails
+        graph.revive();
+        state.save_job(graph.job_id(), &graph).await?;
+        state.accept_job("queued", "", 0)?;
+        tokio::task::yield_now().await;
+
+        // another scheduler takes over
+        let state = job_state(store);
+        let recovered = state.recover_jobs().await?;
+        assert_eq!(recovered.len(), 1);
+        let recovered = &recovered[0];
+        assert_eq!(recovered.job_id(), graph.job_id());
+        assert_eq!(recovered.stage_count(), graph.stage_count());
+        // the running stage is run again
+        assert_eq!(recovered.available_tasks(), 0);
+        let mut recovered = recovered.clone();
+        recovered.revive();
+        assert!(recovered.available_tasks() > 0);
+        assert_eq!(running_stages(&recovered), running_stages(&graph));
+
+        let status = state.get_job_status("queued").await?.unwrap();
+        assert!(matches!(status.status, Some(Status::Failed(_))));
+        let status = state.get_job_status(graph.job_id()).await?.unwrap();
+        assert!(matches!(status.status, Some(Status::Running(_))));
+
+        let restored = state.get_session(&session.session_id()).await?;
+        assert_eq!(restored.copied_config().target_partitions(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_jobs_of_other_schedulers() -> Result<()> {
+        let store: Arc<dyn KeyValueStore> = Arc::new(InMemoryKeyValueStore::default());
+        let leader = job_state(store.clone());
+        let mut graph = test_aggregation_plan(4).await;
+        leader.accept_job(graph.job_id(), "", 0)?;
+        leader.submit_job(graph.job_id().to_owned(), &graph).await?;
+        graph.revive();
+        leader.save_job(graph.job_id(), &graph).await?;
+
+        // a read replica sees the active jobs of the leader without acquiring them
+        let replica = job_state(store);
+        assert!(replica.get_jobs().await?.contains(graph.job_id()));
+        let mut read = replica.get_execution_graph(graph.job_id()).await?.unwrap();
+        assert_eq!(read.stage_count(), graph.stage_count());
+        // the running stages are read as resolved ones, which are run again once revived
+        assert!(running_stages(&read).is_empty());
+        read.revive();
+        assert_eq!(running_stages(&read), running_stages(&graph));
+        assert!(replica.get_execution_graph("unknown").await?.is_none());
+        Ok(())
+    }
+}
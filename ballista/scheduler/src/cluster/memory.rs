@@ -15,17 +15,22 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::cluster::job_scheduling::{schedule_jobs, JobSchedulingPolicy};
 use crate::cluster::{
-    bind_task_bias, bind_task_consistent_hash, bind_task_round_robin, get_scan_files,
-    is_skip_consistent_hash, BoundTask, ClusterState, ExecutorSlot, JobState,
-    JobStateEvent, JobStateEventStream, JobStatus, TaskDistributionPolicy, TopologyNode,
+    bind_speculative_tasks, bind_task_bias, bind_task_consistent_hash,
+    bind_task_load_aware, bind_task_locality, bind_task_round_robin, get_scan_files,
+    is_skip_consistent_hash, BoundTask, ClusterState, ClusterStateEvent,
+    ClusterStateEventStream, ExecutorSlot, JobState, JobStateEvent, JobStateEventStream,
+    JobStatus, TaskDistributionPolicy, TopologyNode,
 };
 use crate::state::execution_graph::ExecutionGraph;
+use crate::state::executor_telemetry::{data_cache_usage, headroom, resource_usage};
 use async_trait::async_trait;
-use ballista_core::error::{BallistaError, Result};
+use ballista_core::error::{BallistaError, ErrorCode, Result};
 use ballista_core::serde::protobuf::{
-    executor_status, AvailableTaskSlots, ExecutorHeartbeat, ExecutorStatus, FailedJob,
-    QueuedJob,
+    executor_status, AvailableTaskSlots, ExchangeCalibrationState, ExecutorHeartbeat,
+    ExecutorStatus, FailedJob, QueuedJob, SlotReservation, SuccessfulJob,
+    TableStatistics,
 };
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
 use ballista_core::ConfigProducer;
@@ -44,33 +49,32 @@ use std::ops::DerefMut;
 use ballista_core::consistent_hash::node::Node;
 use datafusion::physical_plan::ExecutionPlan;
 use std::sync::Arc;
-use tokio::sync::{Mutex, MutexGuard};
+use tokio::sync::Mutex;
 use tracing::debug;
 
 #[derive(Default)]
 pub struct InMemoryClusterState {
-    /// Current available task slots for each executor
-    task_slots: Mutex<HashMap<String, AvailableTaskSlots>>,
+    /// Current available task slots for each executor. The map is sharded so that
+    /// releasing slots of one executor doesn't contend with other executors
+    task_slots: DashMap<String, AvailableTaskSlots>,
+    /// Serializes binding rounds, which work on a snapshot of `task_slots`
+    bind_lock: Mutex<()>,
     /// Current executors
     executors: DashMap<String, ExecutorMetadata>,
     /// Last heartbeat received for each executor
     heartbeats: DashMap<String, ExecutorHeartbeat>,
+    /// Sender of cluster topology events
+    cluster_event_sender: ClusterEventSender<ClusterStateEvent>,
 }
 
 impl InMemoryClusterState {
     /// Get the topology nodes of the cluster for consistent hashing
     fn get_topology_nodes(
         &self,
-        guard: &MutexGuard<HashMap<String, AvailableTaskSlots>>,
-        executors: Option<HashSet<String>>,
+        slots: &[AvailableTaskSlots],
     ) -> HashMap<String, TopologyNode> {
         let mut nodes: HashMap<String, TopologyNode> = HashMap::new();
-        for (executor_id, slots) in guard.iter() {
-            if let Some(executors) = executors.as_ref() {
-                if !executors.contains(executor_id) {
-                    continue;
-                }
-            }
+        for slots in slots.iter() {
             if let Some(executor) = self.executors.get(&slots.executor_id) {
                 let node = TopologyNode::new(
                     &executor.host,
@@ -93,6 +97,83 @@ impl InMemoryClusterState {
         }
         nodes
     }
+
+    /// The share of the resources left of the executors with the given slots, which
+    /// reported their resource usage with their last heartbeat
+    fn executor_headroom(
+        &self,
+        slots: &[&mut AvailableTaskSlots],
+    ) -> HashMap<String, f64> {
+        let heartbeats: Vec<_> = slots
+            .iter()
+            .filter_map(|slots| self.heartbeats.get(&slots.executor_id))
+            .collect();
+        headroom(heartbeats.iter().filter_map(|heartbeat| {
+            resource_usage(&heartbeat.metrics)
+                .map(|usage| (heartbeat.executor_id.as_str(), usage))
+        }))
+    }
+
+    /// The files in the data caches of the executors with the given slots, as they
+    /// reported with their last heartbeat, by executor ID
+    fn executor_cached_files(
+        &self,
+        slots: &[&mut AvailableTaskSlots],
+    ) -> HashMap<String, HashSet<String>> {
+        slots
+            .iter()
+            .filter_map(|slots| {
+                let heartbeat = self.heartbeats.get(&slots.executor_id)?;
+                let usage = data_cache_usage(&heartbeat.metrics)
+                    .filter(|usage| !usage.cached_files.is_empty())?;
+                Some((
+                    slots.executor_id.clone(),
+                    usage.cached_files.iter().cloned().collect(),
+                ))
+            })
+            .collect()
+    }
+
+    /// The host of each executor, by executor ID
+    fn executor_hosts(&self) -> HashMap<String, String> {
+        self.executors
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().host.clone()))
+            .collect()
+    }
+
+    /// Take a copy of the available slots of the given executors, or of all executors
+    /// if `executors` is `None`
+    fn snapshot_slots(
+        &self,
+        executors: Option<&HashSet<String>>,
+    ) -> Vec<AvailableTaskSlots> {
+        self.task_slots
+            .iter()
+            .filter(|entry| {
+                executors
+                    .map(|executors| executors.contains(entry.key()))
+                    .unwrap_or(true)
+            })
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Notify subscribers of the current available slots of the given executors
+    fn notify_slots_changed<'a>(
+        &self,
+        executor_ids: impl IntoIterator<Item = &'a String>,
+    ) {
+        for executor_id in executor_ids {
+            if let Some(data) = self.task_slots.get(executor_id) {
+                self.cluster_event_sender
+                    .send(&ClusterStateEvent::SlotsChanged {
+                        executor_id: executor_id.clone(),
+                        available_slots: data.slots,
+                    });
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -100,29 +181,40 @@ impl ClusterState for InMemoryClusterState {
     async fn bind_schedulable_tasks(
         &self,
         distribution: TaskDistributionPolicy,
+        job_policy: &dyn JobSchedulingPolicy,
+        speculation_multiplier: f64,
         active_jobs: Arc<HashMap<String, JobInfoCache>>,
         executors: Option<HashSet<String>>,
     ) -> Result<Vec<BoundTask>> {
-        let mut guard = self.task_slots.lock().await;
-
-        let available_slots: Vec<&mut AvailableTaskSlots> = guard
-            .values_mut()
-            .filter_map(|data| {
-                (data.slots > 0
-                    && executors
-                        .as_ref()
-                        .map(|executors| executors.contains(&data.executor_id))
-                        .unwrap_or(true))
-                .then_some(data)
-            })
+        // Only binding takes slots away, so binding against a snapshot and subtracting
+        // what was consumed afterwards is safe while slots are released concurrently
+        let _bind_guard = self.bind_lock.lock().await;
+
+        let mut snapshot = self.snapshot_slots(executors.as_ref());
+        let initial_slots: HashMap<String, u32> = snapshot
+            .iter()
+            .map(|data| (data.executor_id.clone(), data.slots))
             .collect();
 
-        let bound_tasks = match distribution {
+        let available_slots: Vec<&mut AvailableTaskSlots> =
+            snapshot.iter_mut().filter(|data| data.slots > 0).collect();
+        let total_slots = available_slots.iter().map(|data| data.slots as usize).sum();
+        let mut jobs = schedule_jobs(job_policy, &active_jobs, total_slots).await;
+
+        let mut bound_tasks = match distribution {
             TaskDistributionPolicy::Bias => {
-                bind_task_bias(available_slots, active_jobs, |_| false).await
+                bind_task_bias(available_slots, &mut jobs, |_| false).await
             }
             TaskDistributionPolicy::RoundRobin => {
-                bind_task_round_robin(available_slots, active_jobs, |_| false).await
+                bind_task_round_robin(available_slots, &mut jobs, |_| false).await
+            }
+            TaskDistributionPolicy::Locality => {
+                let cached_files = self.executor_cached_files(&available_slots);
+                bind_task_locality(available_slots, &mut jobs, &cached_files).await
+            }
+            TaskDistributionPolicy::LoadAware => {
+                let headroom = self.executor_headroom(&available_slots);
+                bind_task_load_aware(available_slots, &mut jobs, &headroom).await
             }
             TaskDistributionPolicy::ConsistentHash {
                 num_replicas,
@@ -130,7 +222,7 @@ impl ClusterState for InMemoryClusterState {
             } => {
                 let mut bound_tasks = bind_task_round_robin(
                     available_slots,
-                    active_jobs.clone(),
+                    &mut jobs,
                     |stage_plan: Arc<dyn ExecutionPlan>| {
                         if let Ok(scan_files) = get_scan_files(stage_plan) {
                             // Should be opposite to consistent hash ones.
@@ -144,10 +236,10 @@ impl ClusterState for InMemoryClusterState {
                 info!("{} tasks bound by round robin policy", bound_tasks.len());
                 let (bound_tasks_consistent_hash, ch_topology) =
                     bind_task_consistent_hash(
-                        self.get_topology_nodes(&guard, executors),
+                        self.get_topology_nodes(&snapshot),
                         num_replicas,
                         tolerance,
-                        active_jobs,
+                        &mut jobs,
                         |_, plan| get_scan_files(plan),
                     )
                     .await?;
@@ -159,18 +251,56 @@ impl ClusterState for InMemoryClusterState {
                     bound_tasks.extend(bound_tasks_consistent_hash);
                     // Update the available slots
                     let ch_topology = ch_topology.unwrap();
-                    for node in ch_topology.nodes() {
-                        if let Some(data) = guard.get_mut(&node.id) {
-                            data.slots = node.available_slots;
-                        } else {
-                            error!("Fail to find executor data for {}", &node.id);
+                    let mut node_slots: HashMap<&str, u32> = ch_topology
+                        .nodes()
+                        .into_iter()
+                        .map(|node| (node.id.as_str(), node.available_slots))
+                        .collect();
+                    for data in snapshot.iter_mut() {
+                        if let Some(slots) = node_slots.remove(data.executor_id.as_str())
+                        {
+                            data.slots = slots;
                         }
                     }
+                    for executor_id in node_slots.keys() {
+                        error!("Fail to find executor data for {}", executor_id);
+                    }
                 }
                 bound_tasks
             }
         };
 
+        let available_slots: Vec<&mut AvailableTaskSlots> =
+            snapshot.iter_mut().filter(|data| data.slots > 0).collect();
+        bound_tasks.extend(
+            bind_speculative_tasks(
+                available_slots,
+                &jobs,
+                speculation_multiplier,
+                &self.executor_hosts(),
+            )
+            .await,
+        );
+
+        // Apply the consumed slots to the live state
+        for data in snapshot.iter() {
+            let consumed = initial_slots
+                .get(&data.executor_id)
+                .map(|initial| initial.saturating_sub(data.slots))
+                .unwrap_or(0);
+            if consumed > 0 {
+                if let Some(mut live) = self.task_slots.get_mut(&data.executor_id) {
+                    live.slots = live.slots.saturating_sub(consumed);
+                }
+            }
+        }
+
+        let bound_executors: HashSet<&String> = bound_tasks
+            .iter()
+            .map(|(executor_id, _)| executor_id)
+            .collect();
+        self.notify_slots_changed(bound_executors);
+
         Ok(bound_tasks)
     }
 
@@ -181,13 +311,12 @@ impl ClusterState for InMemoryClusterState {
             *v += num_slots;
         }
 
-        let mut guard = self.task_slots.lock().await;
-
-        for (executor_id, num_slots) in increments {
-            if let Some(data) = guard.get_mut(&executor_id) {
+        for (executor_id, num_slots) in increments.iter() {
+            if let Some(mut data) = self.task_slots.get_mut(executor_id) {
                 data.slots += num_slots;
             }
         }
+        self.notify_slots_changed(increments.keys());
 
         Ok(())
     }
@@ -199,7 +328,7 @@ impl ClusterState for InMemoryClusterState {
     ) -> Result<()> {
         let executor_id = metadata.id.clone();
 
-        self.save_executor_metadata(metadata).await?;
+        self.save_executor_metadata(metadata.clone()).await?;
         self.save_executor_heartbeat(ExecutorHeartbeat {
             executor_id: executor_id.clone(),
             timestamp: timestamp_secs(),
@@ -210,9 +339,7 @@ impl ClusterState for InMemoryClusterState {
         })
         .await?;
 
-        let mut guard = self.task_slots.lock().await;
-
-        guard.insert(
+        self.task_slots.insert(
             executor_id.clone(),
             AvailableTaskSlots {
                 executor_id,
@@ -220,6 +347,12 @@ impl ClusterState for InMemoryClusterState {
             },
         );
 
+        self.cluster_event_sender
+            .send(&ClusterStateEvent::ExecutorUp {
+                metadata,
+                available_slots: spec.available_task_slots,
+            });
+
         Ok(())
     }
 
@@ -233,8 +366,8 @@ impl ClusterState for InMemoryClusterState {
             .get(executor_id)
             .map(|pair| pair.value().clone())
             .ok_or_else(|| {
-                BallistaError::Internal(format!(
-                    "Not executor with ID {executor_id} found"
+                BallistaError::ExecutorLost(format!(
+                    "No executor with ID {executor_id} is registered"
                 ))
             })
     }
@@ -251,14 +384,17 @@ impl ClusterState for InMemoryClusterState {
     }
 
     async fn remove_executor(&self, executor_id: &str) -> Result<()> {
-        {
-            let mut guard = self.task_slots.lock().await;
-
-            guard.remove(executor_id);
-        }
+        let removed = self.task_slots.remove(executor_id).is_some();
 
         self.heartbeats.remove(executor_id);
 
+        if removed {
+            self.cluster_event_sender
+                .send(&ClusterStateEvent::ExecutorDown {
+                    executor_id: executor_id.to_owned(),
+                });
+        }
+
         Ok(())
     }
 
@@ -272,6 +408,10 @@ impl ClusterState for InMemoryClusterState {
     fn get_executor_heartbeat(&self, executor_id: &str) -> Option<ExecutorHeartbeat> {
         self.heartbeats.get(executor_id).map(|r| r.value().clone())
     }
+
+    async fn cluster_state_events(&self) -> Result<ClusterStateEventStream> {
+        Ok(Box::pin(self.cluster_event_sender.subscribe()))
+    }
 }
 
 /// Implementation of `JobState` which keeps all state in memory. If using `InMemoryJobState`
@@ -286,6 +426,12 @@ pub struct InMemoryJobState {
     running_jobs: DashMap<String, JobStatus>,
     /// Active ballista sessions
     sessions: DashMap<String, Arc<SessionContext>>,
+    /// Reservations of executor slots, by reservation ID
+    slot_reservations: DashMap<String, SlotReservation>,
+    /// Sizes of the tables scanned by the successful jobs, by location
+    table_statistics: DashMap<String, TableStatistics>,
+    /// Calibration of the cost model of the exchanges
+    exchange_calibration: parking_lot::Mutex<Option<ExchangeCalibrationState>>,
     /// `SessionBuilder` for building DataFusion `SessionContext` from `BallistaConfig`
     session_builder: SessionBuilder,
     /// Sender of job events
@@ -306,6 +452,9 @@ impl InMemoryJobState {
             queued_jobs: Default::default(),
             running_jobs: Default::default(),
             sessions: Default::default(),
+            slot_reservations: Default::default(),
+            table_statistics: Default::default(),
+            exchange_calibration: Default::default(),
             session_builder,
             job_event_sender: ClusterEventSender::new(100),
             config_producer,
@@ -356,6 +505,14 @@ impl JobState for InMemoryJobState {
         Ok(None)
     }
 
+    async fn get_completed_jobs(&self) -> Result<Vec<JobStatus>> {
+        Ok(self
+            .completed_jobs
+            .iter()
+            .map(|pair| pair.value().0.clone())
+            .collect())
+    }
+
     async fn get_execution_graph(&self, job_id: &str) -> Result<Option<ExecutionGraph>> {
         Ok(self
             .completed_jobs
@@ -433,6 +590,50 @@ impl JobState for InMemoryJobState {
         Ok(self.sessions.remove(session_id).map(|(_key, value)| value))
     }
 
+    async fn save_slot_reservation(&self, reservation: &SlotReservation) -> Result<()> {
+        self.slot_reservations
+            .insert(reservation.reservation_id.clone(), reservation.clone());
+        Ok(())
+    }
+
+    async fn remove_slot_reservation(&self, reservation_id: &str) -> Result<bool> {
+        Ok(self.slot_reservations.remove(reservation_id).is_some())
+    }
+
+    async fn get_slot_reservations(&self) -> Result<Vec<SlotReservation>> {
+        Ok(self
+            .slot_reservations
+            .iter()
+            .map(|pair| pair.value().clone())
+            .collect())
+    }
+
+    async fn save_table_statistics(&self, statistics: &TableStatistics) -> Result<()> {
+        self.table_statistics
+            .insert(statistics.location.clone(), statistics.clone());
+        Ok(())
+    }
+
+    async fn get_table_statistics(&self) -> Result<Vec<TableStatistics>> {
+        Ok(self
+            .table_statistics
+            .iter()
+            .map(|pair| pair.value().clone())
+            .collect())
+    }
+
+    async fn save_exchange_calibration(
+        &self,
+        calibration: &ExchangeCalibrationState,
+    ) -> Result<()> {
+        *self.exchange_calibration.lock() = Some(*calibration);
+        Ok(())
+    }
+
+    async fn get_exchange_calibration(&self) -> Result<Option<ExchangeCalibrationState>> {
+        Ok(*self.exchange_calibration.lock())
+    }
+
     async fn job_state_events(&self) -> Result<JobStateEventStream> {
         Ok(Box::pin(self.job_event_sender.subscribe()))
     }
@@ -476,6 +677,7 @@ impl JobState for InMemoryJobState {
                             queued_at,
                             started_at: 0,
                             ended_at: timestamp_millis(),
+                            error_code: ErrorCode::PlanningFailed.to_string(),
                         })),
                     },
                     None,
@@ -490,6 +692,38 @@ impl JobState for InMemoryJobState {
         }
     }
 
+    async fn succeed_unscheduled_job(
+        &self,
+        job_id: &str,
+        job: SuccessfulJob,
+    ) -> Result<()> {
+        if let Some((job_id, (job_name, queued_at))) = self.queued_jobs.remove(job_id) {
+            let now = timestamp_millis();
+            self.completed_jobs.insert(
+                job_id.clone(),
+                (
+                    JobStatus {
+                        job_id,
+                        job_name,
+                        status: Some(Status::Successful(SuccessfulJob {
+                            queued_at,
+                            started_at: now,
+                            ended_at: now,
+                            ..job
+                        })),
+                    },
+                    None,
+                ),
+            );
+
+            Ok(())
+        } else {
+            Err(BallistaError::Internal(format!(
+                "Could not succeed unscheduled job {job_id}, job not found in queued jobs"
+            )))
+        }
+    }
+
     fn produce_config(&self) -> SessionConfig {
         (self.config_producer)()
     }
@@ -497,15 +731,22 @@ impl JobState for InMemoryJobState {
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashMap;
     use std::sync::Arc;
+    use std::time::Duration;
 
-    use crate::cluster::memory::InMemoryJobState;
+    use crate::cluster::job_scheduling::FifoJobScheduling;
+    use crate::cluster::memory::{InMemoryClusterState, InMemoryJobState};
     use crate::cluster::test_util::{test_job_lifecycle, test_job_planning_failure};
+    use crate::cluster::{ClusterState, ClusterStateEvent, TaskDistributionPolicy};
+    use crate::state::task_manager::JobInfoCache;
     use crate::test_utils::{
-        test_aggregation_plan, test_join_plan, test_two_aggregations_plan,
+        mock_executor, test_aggregation_plan, test_join_plan, test_two_aggregations_plan,
     };
     use ballista_core::error::Result;
+    use ballista_core::serde::scheduler::ExecutorData;
     use ballista_core::utils::{default_config_producer, default_session_builder};
+    use futures::StreamExt;
 
     #[tokio::test]
     async fn test_in_memory_job_lifecycle() -> Result<()> {
@@ -572,4 +813,131 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_in_memory_cluster_state_events() -> Result<()> {
+        let cluster_state = InMemoryClusterState::default();
+        let mut events = cluster_state.cluster_state_events().await?;
+
+        let executor = mock_executor("executor-1".to_string());
+        cluster_state
+            .register_executor(
+                executor.clone(),
+                ExecutorData {
+                    executor_id: executor.id.clone(),
+                    total_task_slots: 4,
+                    available_task_slots: 4,
+                },
+            )
+            .await?;
+        cluster_state
+            .unbind_tasks(vec![(executor.id.clone(), 2)])
+            .await?;
+        cluster_state.remove_executor(&executor.id).await?;
+        // Removing an unknown executor should not emit an event
+        cluster_state.remove_executor("executor-2").await?;
+
+        assert_eq!(
+            events.next().await,
+            Some(ClusterStateEvent::ExecutorUp {
+                metadata: executor.clone(),
+                available_slots: 4,
+            })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(ClusterStateEvent::SlotsChanged {
+                executor_id: executor.id.clone(),
+                available_slots: 6,
+            })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(ClusterStateEvent::ExecutorDown {
+                executor_id: executor.id.clone(),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_release_slots_during_binding() -> Result<()> {
+        let cluster_state = Arc::new(InMemoryClusterState::default());
+        let mut events = cluster_state.cluster_state_events().await?;
+
+        let executor = mock_executor("executor-1".to_string());
+        cluster_state
+            .register_executor(
+                executor.clone(),
+                ExecutorData {
+                    executor_id: executor.id.clone(),
+                    total_task_slots: 4,
+                    available_task_slots: 4,
+                },
+            )
+            .await?;
+
+        // The first stage has two tasks
+        let mut graph = test_aggregation_plan(4).await;
+        graph.revive();
+        let job_info = JobInfoCache::new(graph);
+        let graph = job_info.execution_graph.clone();
+        let active_jobs = Arc::new(HashMap::from([("job".to_string(), job_info)]));
+
+        // Hold the graph so that the binding round is paused after taking its snapshot
+        let graph_guard = graph.read().await;
+        let binding = {
+            let cluster_state = cluster_state.clone();
+            tokio::spawn(async move {
+                cluster_state
+                    .bind_schedulable_tasks(
+                        TaskDistributionPolicy::Bias,
+                        &FifoJobScheduling,
+                        0.0,
+                        active_jobs,
+                        None,
+                    )
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Releasing slots must not wait for the binding round
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            cluster_state.unbind_tasks(vec![(executor.id.clone(), 1)]),
+        )
+        .await
+        .expect("releasing slots was blocked by binding")?;
+
+        drop(graph_guard);
+        let bound_tasks = binding.await.unwrap()?;
+        assert_eq!(bound_tasks.len(), 2);
+
+        assert_eq!(
+            events.next().await,
+            Some(ClusterStateEvent::ExecutorUp {
+                metadata: executor.clone(),
+                available_slots: 4,
+            })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(ClusterStateEvent::SlotsChanged {
+                executor_id: executor.id.clone(),
+                available_slots: 5,
+            })
+        );
+        // The released slot is kept and the two bound slots are taken away
+        assert_eq!(
+            events.next().await,
+            Some(ClusterStateEvent::SlotsChanged {
+                executor_id: executor.id.clone(),
+                available_slots: 3,
+            })
+        );
+
+        Ok(())
+    }
 }
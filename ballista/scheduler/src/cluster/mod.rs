@@ -15,10 +15,12 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use clap::ValueEnum;
 use datafusion::common::tree_node::TreeNode;
@@ -34,22 +36,37 @@ use log::{debug, info, warn};
 use ballista_core::config::BallistaConfig;
 use ballista_core::consistent_hash::ConsistentHash;
 use ballista_core::error::Result;
+use ballista_core::execution_plans::ShuffleReaderExec;
 use ballista_core::serde::protobuf::{
-    job_status, AvailableTaskSlots, ExecutorHeartbeat, JobStatus,
+    job_status, AvailableTaskSlots, ExchangeCalibrationState, ExecutorHeartbeat,
+    JobStatus, SlotReservation, SuccessfulJob, TableStatistics,
 };
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata, PartitionId};
+use ballista_core::serde::BallistaCodec;
+#[cfg(feature = "etcd")]
+use ballista_core::serde::{
+    BallistaLogicalExtensionCodec, BallistaPhysicalExtensionCodec,
+};
 use ballista_core::utils::{default_config_producer, default_session_builder};
 use ballista_core::{consistent_hash, ConfigProducer};
 
+use crate::cluster::job_scheduling::{JobSchedulingPolicy, ScheduledJob};
+use crate::cluster::kv::KeyValueJobState;
 use crate::cluster::memory::{InMemoryClusterState, InMemoryJobState};
+use crate::cluster::storage::{KeyValueStore, LeaderElection};
 
 use crate::config::{ClusterStorageConfig, SchedulerConfig, TaskDistributionPolicy};
-use crate::scheduler_server::SessionBuilder;
+use crate::scheduler_server::{timestamp_millis, SessionBuilder};
 use crate::state::execution_graph::{create_task_info, ExecutionGraph, TaskDescription};
 use crate::state::task_manager::JobInfoCache;
 
 pub mod event;
+pub mod job_scheduling;
+pub mod kv;
 pub mod memory;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod storage;
 
 #[cfg(test)]
 #[allow(clippy::uninlined_format_args)]
@@ -60,6 +77,8 @@ pub mod test_util;
 #[derive(Debug, Clone, ValueEnum, serde::Deserialize, PartialEq, Eq)]
 pub enum ClusterStorage {
     Memory,
+    #[cfg(feature = "etcd")]
+    Etcd,
 }
 
 impl std::str::FromStr for ClusterStorage {
@@ -80,6 +99,9 @@ impl parse_arg::ParseArgFromStr for ClusterStorage {
 pub struct BallistaCluster {
     cluster_state: Arc<dyn ClusterState>,
     job_state: Arc<dyn JobState>,
+    /// Election of the scheduler running the jobs, among the schedulers sharing a
+    /// persistent job state
+    leader_election: Option<Arc<LeaderElection>>,
 }
 
 impl BallistaCluster {
@@ -90,6 +112,7 @@ impl BallistaCluster {
         Self {
             cluster_state,
             job_state,
+            leader_election: None,
         }
     }
 
@@ -105,6 +128,36 @@ impl BallistaCluster {
                 session_builder,
                 config_producer,
             )),
+            leader_election: None,
+        }
+    }
+
+    /// A cluster persisting its jobs and sessions to `store`, run by the scheduler
+    /// elected among the schedulers sharing it. The executors are tracked in memory,
+    /// re-registering with a new leader through their heartbeats
+    pub fn new_key_value(
+        store: Arc<dyn KeyValueStore>,
+        scheduler: impl Into<String>,
+        session_builder: SessionBuilder,
+        config_producer: ConfigProducer,
+        codec: BallistaCodec,
+        leader_lease_ttl: Duration,
+    ) -> Self {
+        let scheduler = scheduler.into();
+        Self {
+            cluster_state: Arc::new(InMemoryClusterState::default()),
+            job_state: Arc::new(KeyValueJobState::new(
+                store.clone(),
+                scheduler.clone(),
+                session_builder,
+                config_producer,
+                codec,
+            )),
+            leader_election: Some(Arc::new(LeaderElection::new(
+                store,
+                scheduler,
+                leader_lease_ttl,
+            ))),
         }
     }
 
@@ -127,6 +180,28 @@ impl BallistaCluster {
                 session_builder,
                 config_producer,
             )),
+            #[cfg(feature = "etcd")]
+            ClusterStorageConfig::Etcd(urls) => {
+                let codec = BallistaCodec::new(
+                    config.override_logical_codec.clone().unwrap_or_else(|| {
+                        Arc::new(BallistaLogicalExtensionCodec::default())
+                    }),
+                    config.override_physical_codec.clone().unwrap_or_else(|| {
+                        Arc::new(BallistaPhysicalExtensionCodec::default())
+                    }),
+                );
+                Ok(BallistaCluster::new_key_value(
+                    Arc::new(storage::etcd::EtcdStore::new(
+                        urls.clone(),
+                        &config.namespace,
+                    )),
+                    scheduler,
+                    session_builder,
+                    config_producer,
+                    codec,
+                    Duration::from_secs(config.leader_lease_ttl_seconds),
+                ))
+            }
         }
     }
 
@@ -137,6 +212,10 @@ impl BallistaCluster {
     pub fn job_state(&self) -> Arc<dyn JobState> {
         self.job_state.clone()
     }
+
+    pub fn leader_election(&self) -> Option<Arc<LeaderElection>> {
+        self.leader_election.clone()
+    }
 }
 
 /// Stream of `ExecutorHeartbeat`. This stream should contain all `ExecutorHeartbeats` received
@@ -160,10 +239,15 @@ pub trait ClusterState: Send + Sync + 'static {
 
     /// Bind the ready to running tasks from [`active_jobs`] with available executors.
     ///
-    /// If `executors` is provided, only bind slots from the specified executor IDs
+    /// If `executors` is provided, only bind slots from the specified executor IDs.
+    /// The `job_policy` decides which jobs the tasks are taken from, and in which order.
+    /// Slots left over are bound to speculative attempts of straggler tasks, unless
+    /// `speculation_multiplier` is 0, see [`bind_speculative_tasks`].
     async fn bind_schedulable_tasks(
         &self,
         distribution: TaskDistributionPolicy,
+        job_policy: &dyn JobSchedulingPolicy,
+        speculation_multiplier: f64,
         active_jobs: Arc<HashMap<String, JobInfoCache>>,
         executors: Option<HashSet<String>>,
     ) -> Result<Vec<BoundTask>>;
@@ -198,6 +282,47 @@ pub trait ClusterState: Send + Sync + 'static {
 
     /// Get executor heartbeat for the provided executor ID. Return None if the executor does not exist
     fn get_executor_heartbeat(&self, executor_id: &str) -> Option<ExecutorHeartbeat>;
+
+    /// Get a stream of all `ClusterStateEvent`s. Events should be emitted whenever the
+    /// cluster topology or the available task slots change
+    async fn cluster_state_events(&self) -> Result<ClusterStateEventStream>;
+}
+
+/// Events related to the topology of the cluster. Implementations may or may not support all event types.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClusterStateEvent {
+    /// Event when a new executor has been registered
+    ExecutorUp {
+        /// Metadata of the registered executor
+        metadata: ExecutorMetadata,
+        /// Number of task slots available on the executor
+        available_slots: u32,
+    },
+    /// Event when an executor has been removed from the cluster
+    ExecutorDown {
+        /// ID of the removed executor
+        executor_id: String,
+    },
+    /// Event when the available task slots of an executor changed, either
+    /// because tasks have been bound to it or because tasks have completed
+    SlotsChanged {
+        /// ID of the executor
+        executor_id: String,
+        /// Number of task slots now available on the executor
+        available_slots: u32,
+    },
+}
+
+/// Stream of `ClusterStateEvent`. This stream should contain all `ClusterStateEvent`s received
+/// by any schedulers with a shared `ClusterState`
+pub type ClusterStateEventStream = Pin<Box<dyn Stream<Item = ClusterStateEvent> + Send>>;
+
+/// A trait for pushing `ClusterStateEvent`s to external consumers, such as autoscalers,
+/// dashboards or a message broker, so that they don't need to poll the scheduler
+#[tonic::async_trait]
+pub trait ClusterEventPublisher: Send + Sync {
+    /// Publish a single event. Errors are logged and do not stop the publisher
+    async fn publish(&self, event: &ClusterStateEvent) -> Result<()>;
 }
 
 /// Events related to the state of jobs. Implementations may or may not support all event types.
@@ -264,6 +389,9 @@ pub trait JobState: Send + Sync {
     /// Fetch the job status
     async fn get_job_status(&self, job_id: &str) -> Result<Option<JobStatus>>;
 
+    /// Return the statuses of all completed (successful or failed) jobs in the `JobState`
+    async fn get_completed_jobs(&self) -> Result<Vec<JobStatus>>;
+
     /// Get the `ExecutionGraph` for job. The job may or may not belong to the caller
     /// and should return the `ExecutionGraph` for the given job (if it exists) at the
     /// time this method is called with no guarantees that the graph has not been
@@ -278,6 +406,15 @@ pub trait JobState: Send + Sync {
     /// during planning (and does not yet have an `ExecutionGraph`)
     async fn fail_unscheduled_job(&self, job_id: &str, reason: String) -> Result<()>;
 
+    /// Mark a job which has not been submitted as successful, with the results of `job`.
+    /// This should be called if the results of a job are served without running it, e.g.
+    /// from the query cache. The times of `job` are set to the times of the job
+    async fn succeed_unscheduled_job(
+        &self,
+        job_id: &str,
+        job: SuccessfulJob,
+    ) -> Result<()>;
+
     /// Delete a job from the global state
     async fn remove_job(&self, job_id: &str) -> Result<()>;
 
@@ -286,6 +423,12 @@ pub trait JobState: Send + Sync {
     /// otherwise return `None`
     async fn try_acquire_job(&self, job_id: &str) -> Result<Option<ExecutionGraph>>;
 
+    /// Acquire the jobs left running by a previous scheduler sharing the state, returning
+    /// their `ExecutionGraph`s. The jobs it queued but didn't plan yet are failed
+    async fn recover_jobs(&self) -> Result<Vec<ExecutionGraph>> {
+        Ok(vec![])
+    }
+
     /// Get a stream of all `JobState` events. An event should be published any time that status
     /// of a job changes in state
     async fn job_state_events(&self) -> Result<JobStateEventStream>;
@@ -310,13 +453,108 @@ pub trait JobState: Send + Sync {
         session_id: &str,
     ) -> Result<Option<Arc<SessionContext>>>;
 
+    /// Save a reservation of executor slots, replacing the one with the same ID
+    async fn save_slot_reservation(&self, reservation: &SlotReservation) -> Result<()>;
+
+    /// Remove a reservation of executor slots, returning whether it existed
+    async fn remove_slot_reservation(&self, reservation_id: &str) -> Result<bool>;
+
+    /// Get the saved reservations of executor slots, expired ones included
+    async fn get_slot_reservations(&self) -> Result<Vec<SlotReservation>>;
+
+    /// Save the statistics of a table, replacing those with the same location
+    async fn save_table_statistics(&self, statistics: &TableStatistics) -> Result<()>;
+
+    /// Get the saved statistics of the tables
+    async fn get_table_statistics(&self) -> Result<Vec<TableStatistics>>;
+
+    /// Save the calibration of the cost model of the exchanges, replacing the previous
+    async fn save_exchange_calibration(
+        &self,
+        calibration: &ExchangeCalibrationState,
+    ) -> Result<()>;
+
+    /// Get the saved calibration of the cost model of the exchanges, if any
+    async fn get_exchange_calibration(&self) -> Result<Option<ExchangeCalibrationState>>;
+
     // TODO MM not sure this is the best place to put config producer
     fn produce_config(&self) -> SessionConfig;
 }
 
+/// The tasks of a job running on each executor, which the session of the job may cap
+/// with `ballista.job.max_tasks_per_executor`
+struct ExecutorTaskCap {
+    /// 0 means no limit
+    max_tasks: usize,
+    running_tasks: HashMap<String, usize>,
+}
+
+impl ExecutorTaskCap {
+    fn of_job(graph: &ExecutionGraph) -> Self {
+        let max_tasks = graph.max_tasks_per_executor();
+        let mut running_tasks: HashMap<String, usize> = HashMap::new();
+        if max_tasks > 0 {
+            for task in graph.running_tasks() {
+                *running_tasks.entry(task.executor_id).or_default() += 1;
+            }
+        }
+        Self {
+            max_tasks,
+            running_tasks,
+        }
+    }
+
+    /// Whether another task of the job can be bound to the executor
+    fn allows(&self, executor_id: &str) -> bool {
+        self.max_tasks == 0
+            || self.running_tasks.get(executor_id).copied().unwrap_or(0) < self.max_tasks
+    }
+
+    /// Count a task of the job bound to the executor
+    fn bind(&mut self, executor_id: &str) {
+        if self.max_tasks > 0 {
+            *self
+                .running_tasks
+                .entry(executor_id.to_owned())
+                .or_default() += 1;
+        }
+    }
+}
+
+/// Executors and hosts which an attempt of a task must not be placed on, as they run
+/// another attempt of the same task, so that a slow executor or host doesn't slow down
+/// all the attempts of the task
+struct AntiAffinity {
+    executors: HashSet<String>,
+    hosts: HashSet<String>,
+}
+
+impl AntiAffinity {
+    /// Keep away from `executors` and from their hosts, as found in `hosts`
+    fn of_executors(
+        executors: impl IntoIterator<Item = String>,
+        hosts: &HashMap<String, String>,
+    ) -> Self {
+        let executors: HashSet<String> = executors.into_iter().collect();
+        let hosts = executors
+            .iter()
+            .filter_map(|executor_id| hosts.get(executor_id).cloned())
+            .collect();
+        Self { executors, hosts }
+    }
+
+    /// Whether an attempt of the task can be placed on the executor
+    fn allows(&self, executor_id: &str, hosts: &HashMap<String, String>) -> bool {
+        !self.executors.contains(executor_id)
+            && !hosts
+                .get(executor_id)
+                .is_some_and(|host| self.hosts.contains(host))
+    }
+}
+
 pub(crate) async fn bind_task_bias(
     mut slots: Vec<&mut AvailableTaskSlots>,
-    active_jobs: Arc<HashMap<String, JobInfoCache>>,
+    jobs: &mut [ScheduledJob],
     if_skip: fn(Arc<dyn ExecutionPlan>) -> bool,
 ) -> Vec<BoundTask> {
     let mut schedulable_tasks: Vec<BoundTask> = vec![];
@@ -331,8 +569,12 @@ pub(crate) async fn bind_task_bias(
     slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
 
     let mut idx_slot = 0usize;
-    let mut slot = &mut slots[idx_slot];
-    for (job_id, job_info) in active_jobs.iter() {
+    for ScheduledJob {
+        job_id,
+        job_info,
+        max_tasks,
+    } in jobs.iter_mut()
+    {
         if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
             debug!(
                 "Job {} is not in running status and will be skipped",
@@ -342,10 +584,14 @@ pub(crate) async fn bind_task_bias(
         }
         let mut graph = job_info.execution_graph.write().await;
         let session_id = graph.session_id().to_string();
+        let mut cap = ExecutorTaskCap::of_job(&graph);
         let mut black_list = vec![];
-        while let Some((running_stage, task_id_gen)) =
-            graph.fetch_running_stage(&black_list)
-        {
+        'job: while *max_tasks > 0 {
+            let Some((running_stage, task_id_gen)) =
+                graph.fetch_running_stage(&black_list)
+            else {
+                break;
+            };
             if if_skip(running_stage.plan.clone()) {
                 info!(
                     "Will skip stage {}/{} for bias task binding",
@@ -361,17 +607,23 @@ pub(crate) async fn bind_task_bias(
                 .iter_mut()
                 .enumerate()
                 .filter(|(_partition, info)| info.is_none())
-                .take(total_slots as usize)
+                .take((total_slots as usize).min(*max_tasks))
                 .collect::<Vec<_>>();
             for (partition_id, task_info) in runnable_tasks {
-                // Assign [`slot`] with a slot available slot number larger than 0
-                while slot.slots == 0 {
+                // Move [`idx_slot`] to a slot with an available slot number larger than 0
+                while slots[idx_slot].slots == 0 {
                     idx_slot += 1;
                     if idx_slot >= slots.len() {
                         return schedulable_tasks;
                     }
-                    slot = &mut slots[idx_slot];
                 }
+                // Skip the executors already running as many tasks of the job as it allows
+                let Some(idx) = (idx_slot..slots.len()).find(|&idx| {
+                    slots[idx].slots > 0 && cap.allows(&slots[idx].executor_id)
+                }) else {
+                    break 'job;
+                };
+                let slot = &mut slots[idx];
                 let executor_id = slot.executor_id.clone();
                 let task_id = *task_id_gen;
                 *task_id_gen += 1;
@@ -391,9 +643,11 @@ pub(crate) async fn bind_task_bias(
                     plan: running_stage.plan.clone(),
                     session_config: running_stage.session_config.clone(),
                 };
+                cap.bind(&executor_id);
                 schedulable_tasks.push((executor_id, task_desc));
 
                 slot.slots -= 1;
+                *max_tasks -= 1;
             }
         }
     }
@@ -403,7 +657,7 @@ pub(crate) async fn bind_task_bias(
 
 pub(crate) async fn bind_task_round_robin(
     mut slots: Vec<&mut AvailableTaskSlots>,
-    active_jobs: Arc<HashMap<String, JobInfoCache>>,
+    jobs: &mut [ScheduledJob],
     if_skip: fn(Arc<dyn ExecutionPlan>) -> bool,
 ) -> Vec<BoundTask> {
     let mut schedulable_tasks: Vec<BoundTask> = vec![];
@@ -419,7 +673,12 @@ pub(crate) async fn bind_task_round_robin(
     slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
 
     let mut idx_slot = 0usize;
-    for (job_id, job_info) in active_jobs.iter() {
+    for ScheduledJob {
+        job_id,
+        job_info,
+        max_tasks,
+    } in jobs.iter_mut()
+    {
         if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
             debug!(
                 "Job {} is not in running status and will be skipped",
@@ -429,10 +688,14 @@ pub(crate) async fn bind_task_round_robin(
         }
         let mut graph = job_info.execution_graph.write().await;
         let session_id = graph.session_id().to_string();
+        let mut cap = ExecutorTaskCap::of_job(&graph);
         let mut black_list = vec![];
-        while let Some((running_stage, task_id_gen)) =
-            graph.fetch_running_stage(&black_list)
-        {
+        'job: while *max_tasks > 0 {
+            let Some((running_stage, task_id_gen)) =
+                graph.fetch_running_stage(&black_list)
+            else {
+                break;
+            };
             if if_skip(running_stage.plan.clone()) {
                 info!(
                     "Will skip stage {}/{} for round robin task binding",
@@ -448,7 +711,7 @@ pub(crate) async fn bind_task_round_robin(
                 .iter_mut()
                 .enumerate()
                 .filter(|(_partition, info)| info.is_none())
-                .take(total_slots as usize)
+                .take((total_slots as usize).min(*max_tasks))
                 .collect::<Vec<_>>();
             for (partition_id, task_info) in runnable_tasks {
                 // Move to the index which has available slots
@@ -458,6 +721,17 @@ pub(crate) async fn bind_task_round_robin(
                 if slots[idx_slot].slots == 0 {
                     idx_slot = 0;
                 }
+                // Skip the executors already running as many tasks of the job as it allows
+                if !cap.allows(&slots[idx_slot].executor_id) {
+                    let Some(idx) =
+                        (idx_slot..slots.len()).chain(0..idx_slot).find(|&idx| {
+                            slots[idx].slots > 0 && cap.allows(&slots[idx].executor_id)
+                        })
+                    else {
+                        break 'job;
+                    };
+                    idx_slot = idx;
+                }
                 // Since the slots is a vector with descending order, and the total available slots is larger than 0,
                 // we are sure the available slot number at idx_slot is larger than 1
                 let slot = &mut slots[idx_slot];
@@ -480,11 +754,226 @@ pub(crate) async fn bind_task_round_robin(
                     plan: running_stage.plan.clone(),
                     session_config: running_stage.session_config.clone(),
                 };
+                cap.bind(&executor_id);
                 schedulable_tasks.push((executor_id, task_desc));
 
                 idx_slot += 1;
                 slot.slots -= 1;
                 total_slots -= 1;
+                *max_tasks -= 1;
+                if total_slots == 0 {
+                    return schedulable_tasks;
+                }
+            }
+        }
+    }
+
+    schedulable_tasks
+}
+
+/// Bind each task to the executor holding the most bytes of the shuffle partitions it
+/// reads, plus of the files it scans which are in the data cache of the executor as
+/// listed in `cached_files` by executor ID, so that fewer bytes are fetched from other
+/// executors and object stores. Tasks reading no such data, or whose executors have no
+/// free slot left, are bound to the executors with the most free slots first, as with
+/// [bind_task_bias]
+pub(crate) async fn bind_task_locality(
+    mut slots: Vec<&mut AvailableTaskSlots>,
+    jobs: &mut [ScheduledJob],
+    cached_files: &HashMap<String, HashSet<String>>,
+) -> Vec<BoundTask> {
+    let mut schedulable_tasks: Vec<BoundTask> = vec![];
+
+    let mut total_slots = slots.iter().fold(0, |acc, s| acc + s.slots);
+    if total_slots == 0 {
+        warn!("Not enough available executor slots for task running!!!");
+        return schedulable_tasks;
+    }
+
+    // Sort the slots by descending order
+    slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
+
+    for ScheduledJob {
+        job_id,
+        job_info,
+        max_tasks,
+    } in jobs.iter_mut()
+    {
+        if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
+            debug!(
+                "Job {} is not in running status and will be skipped",
+                job_id
+            );
+            continue;
+        }
+        let mut graph = job_info.execution_graph.write().await;
+        let session_id = graph.session_id().to_string();
+        let mut cap = ExecutorTaskCap::of_job(&graph);
+        'job: while *max_tasks > 0 {
+            let Some((running_stage, task_id_gen)) = graph.fetch_running_stage(&[])
+            else {
+                break;
+            };
+            let input_bytes = shuffle_input_bytes(running_stage.plan.as_ref());
+            let cached_bytes = if cached_files.is_empty() {
+                vec![]
+            } else {
+                let scan_files =
+                    get_scan_files(running_stage.plan.clone()).unwrap_or_default();
+                cached_scan_bytes(&scan_files, cached_files)
+            };
+            let runnable_tasks = running_stage
+                .task_infos
+                .iter_mut()
+                .enumerate()
+                .filter(|(_partition, info)| info.is_none())
+                .take((total_slots as usize).min(*max_tasks))
+                .collect::<Vec<_>>();
+            for (partition_id, task_info) in runnable_tasks {
+                let local_bytes = |executor_id: &str| {
+                    [&input_bytes, &cached_bytes]
+                        .into_iter()
+                        .filter_map(|bytes| bytes.get(partition_id)?.get(executor_id))
+                        .sum::<u64>()
+                };
+                let Some(idx) = (0..slots.len())
+                    .filter(|&idx| {
+                        slots[idx].slots > 0 && cap.allows(&slots[idx].executor_id)
+                    })
+                    .max_by_key(|&idx| {
+                        (local_bytes(&slots[idx].executor_id), Reverse(idx))
+                    })
+                else {
+                    break 'job;
+                };
+                let slot = &mut slots[idx];
+                let executor_id = slot.executor_id.clone();
+                let task_id = *task_id_gen;
+                *task_id_gen += 1;
+                *task_info = Some(create_task_info(executor_id.clone(), task_id));
+
+                let partition = PartitionId {
+                    job_id: job_id.clone(),
+                    stage_id: running_stage.stage_id,
+                    partition_id,
+                };
+                let task_desc = TaskDescription {
+                    session_id: session_id.clone(),
+                    partition,
+                    stage_attempt_num: running_stage.stage_attempt_num,
+                    task_id,
+                    task_attempt: running_stage.task_failure_numbers[partition_id],
+                    plan: running_stage.plan.clone(),
+                    session_config: running_stage.session_config.clone(),
+                };
+                cap.bind(&executor_id);
+                schedulable_tasks.push((executor_id, task_desc));
+
+                slot.slots -= 1;
+                total_slots -= 1;
+                *max_tasks -= 1;
+                if total_slots == 0 {
+                    return schedulable_tasks;
+                }
+            }
+        }
+    }
+
+    schedulable_tasks
+}
+
+/// Bind each task to the executor with the most free slots weighted by its `headroom`,
+/// the share of its resources left as reported with its heartbeats, see
+/// [headroom](crate::state::executor_telemetry::headroom). Executors which reported no
+/// resource usage are weighted as idle, so that the tasks are bound as with
+/// [bind_task_bias] when no executor reports its usage
+pub(crate) async fn bind_task_load_aware(
+    mut slots: Vec<&mut AvailableTaskSlots>,
+    jobs: &mut [ScheduledJob],
+    headroom: &HashMap<String, f64>,
+) -> Vec<BoundTask> {
+    let mut schedulable_tasks: Vec<BoundTask> = vec![];
+
+    let mut total_slots = slots.iter().fold(0, |acc, s| acc + s.slots);
+    if total_slots == 0 {
+        warn!("Not enough available executor slots for task running!!!");
+        return schedulable_tasks;
+    }
+
+    // Sort the slots by descending order
+    slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
+
+    for ScheduledJob {
+        job_id,
+        job_info,
+        max_tasks,
+    } in jobs.iter_mut()
+    {
+        if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
+            debug!(
+                "Job {} is not in running status and will be skipped",
+                job_id
+            );
+            continue;
+        }
+        let mut graph = job_info.execution_graph.write().await;
+        let session_id = graph.session_id().to_string();
+        let mut cap = ExecutorTaskCap::of_job(&graph);
+        'job: while *max_tasks > 0 {
+            let Some((running_stage, task_id_gen)) = graph.fetch_running_stage(&[])
+            else {
+                break;
+            };
+            let runnable_tasks = running_stage
+                .task_infos
+                .iter_mut()
+                .enumerate()
+                .filter(|(_partition, info)| info.is_none())
+                .take((total_slots as usize).min(*max_tasks))
+                .collect::<Vec<_>>();
+            for (partition_id, task_info) in runnable_tasks {
+                let weight = |slot: &AvailableTaskSlots| {
+                    slot.slots as f64
+                        * headroom.get(&slot.executor_id).copied().unwrap_or(1.0)
+                };
+                let Some(idx) = (0..slots.len())
+                    .filter(|&idx| {
+                        slots[idx].slots > 0 && cap.allows(&slots[idx].executor_id)
+                    })
+                    .max_by(|&a, &b| {
+                        weight(slots[a])
+                            .total_cmp(&weight(slots[b]))
+                            .then(b.cmp(&a))
+                    })
+                else {
+                    break 'job;
+                };
+                let slot = &mut slots[idx];
+                let executor_id = slot.executor_id.clone();
+                let task_id = *task_id_gen;
+                *task_id_gen += 1;
+                *task_info = Some(create_task_info(executor_id.clone(), task_id));
+
+                let partition = PartitionId {
+                    job_id: job_id.clone(),
+                    stage_id: running_stage.stage_id,
+                    partition_id,
+                };
+                let task_desc = TaskDescription {
+                    session_id: session_id.clone(),
+                    partition,
+                    stage_attempt_num: running_stage.stage_attempt_num,
+                    task_id,
+                    task_attempt: running_stage.task_failure_numbers[partition_id],
+                    plan: running_stage.plan.clone(),
+                    session_config: running_stage.session_config.clone(),
+                };
+                cap.bind(&executor_id);
+                schedulable_tasks.push((executor_id, task_desc));
+
+                slot.slots -= 1;
+                total_slots -= 1;
+                *max_tasks -= 1;
                 if total_slots == 0 {
                     return schedulable_tasks;
                 }
@@ -495,6 +984,118 @@ pub(crate) async fn bind_task_round_robin(
     schedulable_tasks
 }
 
+/// The bytes of the shuffle partitions read by each partition of a stage, by the
+/// executor holding them. Partitions inlined in the task statuses are not held by any
+pub(crate) fn shuffle_input_bytes(plan: &dyn ExecutionPlan) -> Vec<HashMap<String, u64>> {
+    let mut input_bytes: Vec<HashMap<String, u64>> = vec![];
+    let mut collect = |plan: &dyn ExecutionPlan| {
+        let Some(reader) = plan.as_any().downcast_ref::<ShuffleReaderExec>() else {
+            return;
+        };
+        if input_bytes.len() < reader.partition.len() {
+            input_bytes.resize_with(reader.partition.len(), HashMap::new);
+        }
+        for (partition_id, locations) in reader.partition.iter().enumerate() {
+            for location in locations.iter().filter(|l| l.inline_data.is_none()) {
+                *input_bytes[partition_id]
+                    .entry(location.executor_meta.id.clone())
+                    .or_default() += location.partition_stats.num_bytes().unwrap_or(0);
+            }
+        }
+    };
+    let mut to_visit = vec![plan];
+    while let Some(plan) = to_visit.pop() {
+        collect(plan);
+        to_visit.extend(plan.children().into_iter().map(|child| child.as_ref()));
+    }
+    input_bytes
+}
+
+/// The bytes of the files scanned by each partition of a stage, as returned by
+/// [get_scan_files], which are in the data cache of each executor, by executor ID
+pub(crate) fn cached_scan_bytes(
+    scan_files: &[Vec<Vec<PartitionedFile>>],
+    cached_files: &HashMap<String, HashSet<String>>,
+) -> Vec<HashMap<String, u64>> {
+    let mut cached_bytes: Vec<HashMap<String, u64>> = vec![];
+    for partitions in scan_files {
+        if cached_bytes.len() < partitions.len() {
+            cached_bytes.resize_with(partitions.len(), HashMap::new);
+        }
+        for (partition_id, files) in partitions.iter().enumerate() {
+            for file in files {
+                let location = file.object_meta.location.as_ref();
+                for (executor_id, files) in cached_files {
+                    if files.contains(location) {
+                        *cached_bytes[partition_id]
+                            .entry(executor_id.clone())
+                            .or_default() += file.object_meta.size as u64;
+                    }
+                }
+            }
+        }
+    }
+    cached_bytes
+}
+
+/// Bind speculative attempts of the tasks running `multiplier` times longer than the
+/// median successful task of their stage to the given slots, neither on the executor nor
+/// on the host of the original attempt, as found in `hosts` by executor ID
+pub(crate) async fn bind_speculative_tasks(
+    mut slots: Vec<&mut AvailableTaskSlots>,
+    jobs: &[ScheduledJob],
+    multiplier: f64,
+    hosts: &HashMap<String, String>,
+) -> Vec<BoundTask> {
+    let mut speculative_tasks: Vec<BoundTask> = vec![];
+    if multiplier <= 0.0 || slots.iter().all(|slot| slot.slots == 0) {
+        return speculative_tasks;
+    }
+
+    let now = timestamp_millis() as u128;
+    for ScheduledJob {
+        job_id, job_info, ..
+    } in jobs.iter()
+    {
+        if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
+            continue;
+        }
+        let mut graph = job_info.execution_graph.write().await;
+        let mut cap = ExecutorTaskCap::of_job(&graph);
+        for (stage_id, partition_id, executor_id) in
+            graph.straggler_tasks(multiplier, now)
+        {
+            let anti_affinity = AntiAffinity::of_executors([executor_id.clone()], hosts);
+            let Some(slot) = slots
+                .iter_mut()
+                .filter(|slot| {
+                    slot.slots > 0
+                        && anti_affinity.allows(&slot.executor_id, hosts)
+                        && cap.allows(&slot.executor_id)
+                })
+                .max_by_key(|slot| slot.slots)
+            else {
+                continue;
+            };
+            if let Some(task_desc) = graph.launch_speculative_task(
+                stage_id,
+                partition_id,
+                slot.executor_id.clone(),
+            ) {
+                info!(
+                    "Launch a speculative attempt of task {}/{}/{} on executor {}, running on executor {}",
+                    job_id, stage_id, partition_id, slot.executor_id, executor_id
+                );
+                cap.bind(&slot.executor_id);
+                speculative_tasks.push((slot.executor_id.clone(), task_desc));
+                slot.slots -= 1;
+            }
+        }
+    }
+
+    speculative_tasks
+}
+
 type GetScanFilesFunc = fn(
     &str,
     Arc<dyn ExecutionPlan>,
@@ -504,7 +1105,7 @@ pub(crate) async fn bind_task_consistent_hash(
     topology_nodes: HashMap<String, TopologyNode>,
     num_replicas: usize,
     tolerance: usize,
-    active_jobs: Arc<HashMap<String, JobInfoCache>>,
+    jobs: &mut [ScheduledJob],
     get_scan_files: GetScanFilesFunc,
 ) -> Result<(Vec<BoundTask>, Option<ConsistentHash<TopologyNode>>)> {
     let mut total_slots = 0usize;
@@ -525,7 +1126,12 @@ pub(crate) async fn bind_task_consistent_hash(
         ConsistentHash::new(node_replicas);
 
     let mut schedulable_tasks: Vec<BoundTask> = vec![];
-    for (job_id, job_info) in active_jobs.iter() {
+    for ScheduledJob {
+        job_id,
+        job_info,
+        max_tasks,
+    } in jobs.iter_mut()
+    {
         if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
             debug!(
                 "Job {} is not in running status and will be skipped",
@@ -535,10 +1141,14 @@ pub(crate) async fn bind_task_consistent_hash(
         }
         let mut graph = job_info.execution_graph.write().await;
         let session_id = graph.session_id().to_string();
+        let mut cap = ExecutorTaskCap::of_job(&graph);
         let mut black_list = vec![];
-        while let Some((running_stage, task_id_gen)) =
-            graph.fetch_running_stage(&black_list)
-        {
+        while *max_tasks > 0 {
+            let Some((running_stage, task_id_gen)) =
+                graph.fetch_running_stage(&black_list)
+            else {
+                break;
+            };
             let scan_files = get_scan_files(job_id, running_stage.plan.clone())?;
             if is_skip_consistent_hash(&scan_files) {
                 info!(
@@ -559,7 +1169,7 @@ pub(crate) async fn bind_task_consistent_hash(
                     .iter_mut()
                     .enumerate()
                     .filter(|(_partition, info)| info.is_none())
-                    .take(total_slots)
+                    .take(total_slots.min(*max_tasks))
                     .collect::<Vec<_>>();
                 for (partition_id, task_info) in runnable_tasks {
                     let partition_files = &scan_files[partition_id];
@@ -572,6 +1182,11 @@ pub(crate) async fn bind_task_consistent_hash(
                         file_for_hash.object_meta.location.as_ref().as_bytes(),
                         tolerance,
                     ) {
+                        // the task is bound in a later round, once tasks of the job
+                        // running on the executor complete
+                        if !cap.allows(&node.id) {
+                            continue;
+                        }
                         let executor_id = node.id.clone();
                         let task_id = *task_id_gen;
                         *task_id_gen += 1;
@@ -592,10 +1207,12 @@ pub(crate) async fn bind_task_consistent_hash(
                             plan: running_stage.plan.clone(),
                             session_config: running_stage.session_config.clone(),
                         };
+                        cap.bind(&executor_id);
                         schedulable_tasks.push((executor_id, task_desc));
 
                         node.available_slots -= 1;
                         total_slots -= 1;
+                        *max_tasks -= 1;
                         if total_slots == 0 {
                             return Ok((schedulable_tasks, Some(ch_topology)));
                         }
@@ -682,37 +1299,43 @@ impl consistent_hash::node::Node for TopologyNode {
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
-    use std::sync::Arc;
+    use std::collections::{HashMap, HashSet};
 
     use datafusion::datasource::listing::PartitionedFile;
+    use datafusion::prelude::SessionConfig;
     use object_store::path::Path;
     use object_store::ObjectMeta;
 
+    use ballista_core::build_version;
     use ballista_core::error::Result;
+    use ballista_core::extension::SessionConfigExt;
     use ballista_core::serde::protobuf::AvailableTaskSlots;
     use ballista_core::serde::scheduler::{ExecutorMetadata, ExecutorSpecification};
 
+    use crate::cluster::job_scheduling::ScheduledJob;
     use crate::cluster::{
-        bind_task_bias, bind_task_consistent_hash, bind_task_round_robin, BoundTask,
-        TopologyNode,
+        bind_speculative_tasks, bind_task_bias, bind_task_consistent_hash,
+        bind_task_load_aware, bind_task_locality, bind_task_round_robin,
+        cached_scan_bytes, BoundTask, TopologyNode,
     };
     use crate::state::execution_graph::ExecutionGraph;
     use crate::state::task_manager::JobInfoCache;
     use crate::test_utils::{
-        mock_completed_task, revive_graph_and_complete_next_stage,
-        test_aggregation_plan_with_job_id,
+        mock_completed_task, mock_executor, revive_graph_and_complete_next_stage,
+        revive_graph_and_complete_next_stage_with_executor,
+        test_aggregation_plan_with_job_id, test_aggregation_plan_with_session_config,
     };
 
     #[tokio::test]
     async fn test_bind_task_bias() -> Result<()> {
         let num_partition = 8usize;
-        let active_jobs = mock_active_jobs(num_partition).await?;
+        let mut active_jobs =
+            ScheduledJob::unbounded(&mock_active_jobs(num_partition).await?);
         let mut available_slots = mock_available_slots();
         let available_slots_ref: Vec<&mut AvailableTaskSlots> =
             available_slots.iter_mut().collect();
         let bound_tasks =
-            bind_task_bias(available_slots_ref, Arc::new(active_jobs), |_| false).await;
+            bind_task_bias(available_slots_ref, &mut active_jobs, |_| false).await;
         assert_eq!(9, bound_tasks.len());
 
         let result = get_result(bound_tasks);
@@ -759,13 +1382,13 @@ mod test {
     #[tokio::test]
     async fn test_bind_task_round_robin() -> Result<()> {
         let num_partition = 8usize;
-        let active_jobs = mock_active_jobs(num_partition).await?;
+        let mut active_jobs =
+            ScheduledJob::unbounded(&mock_active_jobs(num_partition).await?);
         let mut available_slots = mock_available_slots();
         let available_slots_ref: Vec<&mut AvailableTaskSlots> =
             available_slots.iter_mut().collect();
         let bound_tasks =
-            bind_task_round_robin(available_slots_ref, Arc::new(active_jobs), |_| false)
-                .await;
+            bind_task_round_robin(available_slots_ref, &mut active_jobs, |_| false).await;
         assert_eq!(9, bound_tasks.len());
 
         let result = get_result(bound_tasks);
@@ -814,11 +1437,260 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_bind_task_in_job_order() -> Result<()> {
+        let active_jobs = mock_active_jobs(8).await?;
+        let mut jobs = vec![
+            ScheduledJob {
+                job_id: "job_b".to_string(),
+                job_info: active_jobs["job_b"].clone(),
+                max_tasks: 3,
+            },
+            ScheduledJob {
+                job_id: "job_a".to_string(),
+                job_info: active_jobs["job_a"].clone(),
+                max_tasks: usize::MAX,
+            },
+        ];
+        let mut available_slots = [AvailableTaskSlots {
+            executor_id: "executor_1".to_string(),
+            slots: 4,
+        }];
+        let bound_tasks =
+            bind_task_bias(available_slots.iter_mut().collect(), &mut jobs, |_| false)
+                .await;
+
+        // job_b is capped to 3 tasks, and job_a gets the slot left over
+        let job_ids: Vec<_> = bound_tasks
+            .iter()
+            .map(|(_, task)| task.partition.job_id.as_str())
+            .collect();
+        assert_eq!(vec!["job_b", "job_b", "job_b", "job_a"], job_ids);
+        assert_eq!(0, jobs[0].max_tasks);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_task_locality() -> Result<()> {
+        let mut graph = test_aggregation_plan_with_job_id(4, "job").await;
+        // the shuffle partitions read by the second stage are all on executor_2
+        revive_graph_and_complete_next_stage_with_executor(
+            &mut graph,
+            &mock_executor("executor_2".to_string()),
+        )?;
+        let active_jobs = HashMap::from([("job".to_string(), JobInfoCache::new(graph))]);
+
+        let mut available_slots = [
+            AvailableTaskSlots {
+                executor_id: "executor_1".to_string(),
+                slots: 8,
+            },
+            AvailableTaskSlots {
+                executor_id: "executor_2".to_string(),
+                slots: 3,
+            },
+        ];
+        let bound_tasks = bind_task_locality(
+            available_slots.iter_mut().collect(),
+            &mut ScheduledJob::unbounded(&active_jobs),
+            &HashMap::new(),
+        )
+        .await;
+        let result = get_result(bound_tasks);
+        // the tasks left once executor_2 is busy are bound as by the bias policy
+        assert_eq!(
+            HashMap::from([(
+                "job".to_string(),
+                HashMap::from([
+                    ("executor_1".to_string(), 1),
+                    ("executor_2".to_string(), 3)
+                ])
+            )]),
+            result
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_scan_bytes() {
+        let scan_files = mock_scan_files(3);
+        let cached_files = HashMap::from([
+            (
+                "executor_1".to_string(),
+                HashSet::from(["file--0".to_string()]),
+            ),
+            (
+                "executor_2".to_string(),
+                HashSet::from(["file--0".to_string(), "file--2".to_string()]),
+            ),
+        ]);
+
+        assert_eq!(
+            vec![
+                HashMap::from([
+                    ("executor_1".to_string(), 1),
+                    ("executor_2".to_string(), 1)
+                ]),
+                HashMap::new(),
+                HashMap::from([("executor_2".to_string(), 1)]),
+            ],
+            cached_scan_bytes(&scan_files, &cached_files)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bind_task_load_aware() -> Result<()> {
+        let active_jobs = mock_active_jobs(8).await?;
+        let mut available_slots = mock_available_slots();
+        // executor_3 has the most free slots but its host is busy, executor_2 reported
+        // no resource usage
+        let headroom = HashMap::from([
+            ("executor_1".to_string(), 1.0),
+            ("executor_3".to_string(), 0.1),
+        ]);
+        let bound_tasks = bind_task_load_aware(
+            available_slots.iter_mut().collect(),
+            &mut ScheduledJob::unbounded(&active_jobs),
+            &headroom,
+        )
+        .await;
+        let mut result: HashMap<String, usize> = HashMap::new();
+        for (executor_id, _) in bound_tasks {
+            *result.entry(executor_id).or_default() += 1;
+        }
+
+        // the free slots of executor_1 and executor_2 are taken before the ones of
+        // executor_3, weighted 0.7, get any of the 9 tasks
+        assert_eq!(
+            HashMap::from([
+                ("executor_1".to_string(), 3),
+                ("executor_2".to_string(), 5),
+                ("executor_3".to_string(), 1),
+            ]),
+            result
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_speculative_tasks_anti_affinity() -> Result<()> {
+        let executor_1 = mock_executor("executor_1".to_string());
+        let mut graph = test_aggregation_plan_with_job_id(4, "job").await;
+        revive_graph_and_complete_next_stage(&mut graph)?;
+        let mut tasks = vec![];
+        while let Some(task) = graph.pop_next_task(&executor_1.id)? {
+            tasks.push(task);
+        }
+        // the last task of the stage straggles on executor_1
+        tasks.pop().unwrap();
+        let task_statuses = tasks
+            .into_iter()
+            .map(|task| mock_completed_task(task, &executor_1.id))
+            .collect();
+        graph.update_task_status(&executor_1, task_statuses, 4, 4)?;
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let active_jobs = HashMap::from([("job".to_string(), JobInfoCache::new(graph))]);
+        let jobs = ScheduledJob::unbounded(&active_jobs);
+
+        let hosts = HashMap::from([
+            ("executor_1".to_string(), "host_a".to_string()),
+            ("executor_2".to_string(), "host_a".to_string()),
+            ("executor_3".to_string(), "host_b".to_string()),
+        ]);
+        let executor = |executor_id: &str, slots: u32| AvailableTaskSlots {
+            executor_id: executor_id.to_string(),
+            slots,
+        };
+
+        // executor_2 shares the host of the original attempt
+        let mut available_slots = [executor("executor_1", 8), executor("executor_2", 8)];
+        let bound_tasks = bind_speculative_tasks(
+            available_slots.iter_mut().collect(),
+            &jobs,
+            2.0,
+            &hosts,
+        )
+        .await;
+        assert!(bound_tasks.is_empty());
+
+        let mut available_slots = [executor("executor_2", 8), executor("executor_3", 1)];
+        let bound_tasks = bind_speculative_tasks(
+            available_slots.iter_mut().collect(),
+            &jobs,
+            2.0,
+            &hosts,
+        )
+        .await;
+        let executor_ids: Vec<_> = bound_tasks
+            .iter()
+            .map(|(executor_id, _)| executor_id.as_str())
+            .collect();
+        assert_eq!(vec!["executor_3"], executor_ids);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_task_max_tasks_per_executor() -> Result<()> {
+        let executor = |executor_id: &str, slots: u32| AvailableTaskSlots {
+            executor_id: executor_id.to_string(),
+            slots,
+        };
+        let session_config = SessionConfig::new_with_ballista()
+            .with_ballista_job_max_tasks_per_executor(2);
+        let mut graph =
+            test_aggregation_plan_with_session_config(8, "job", session_config).await;
+        revive_graph_and_complete_next_stage(&mut graph)?;
+        // a task of the job already runs on executor_1
+        graph.pop_next_task("executor_1")?.unwrap();
+        let active_jobs = HashMap::from([("job".to_string(), JobInfoCache::new(graph))]);
+
+        let mut available_slots = [executor("executor_1", 8), executor("executor_2", 2)];
+        let bound_tasks = bind_task_bias(
+            available_slots.iter_mut().collect(),
+            &mut ScheduledJob::unbounded(&active_jobs),
+            |_| false,
+        )
+        .await;
+        let result = get_result(bound_tasks);
+        assert_eq!(
+            HashMap::from([(
+                "job".to_string(),
+                HashMap::from([
+                    ("executor_1".to_string(), 1),
+                    ("executor_2".to_string(), 2)
+                ])
+            )]),
+            result
+        );
+
+        // the executors are at the limit of the job, whatever their free slots
+        let mut available_slots = [executor("executor_1", 7), executor("executor_3", 8)];
+        let bound_tasks = bind_task_round_robin(
+            available_slots.iter_mut().collect(),
+            &mut ScheduledJob::unbounded(&active_jobs),
+            |_| false,
+        )
+        .await;
+        let result = get_result(bound_tasks);
+        assert_eq!(
+            HashMap::from([(
+                "job".to_string(),
+                HashMap::from([("executor_3".to_string(), 2)])
+            )]),
+            result
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_bind_task_consistent_hash() -> Result<()> {
         let num_partition = 8usize;
         let active_jobs = mock_active_jobs(num_partition).await?;
-        let active_jobs = Arc::new(active_jobs);
         let topology_nodes = mock_topology_nodes();
         let num_replicas = 31;
         let tolerance = 0;
@@ -829,7 +1701,7 @@ mod test {
                 topology_nodes.clone(),
                 num_replicas,
                 tolerance,
-                active_jobs.clone(),
+                &mut ScheduledJob::unbounded(&active_jobs),
                 |_, _| Ok(vec![]),
             )
             .await?;
@@ -842,7 +1714,7 @@ mod test {
                 topology_nodes,
                 num_replicas,
                 tolerance,
-                active_jobs,
+                &mut ScheduledJob::unbounded(&active_jobs),
                 |job_id, _| mock_get_scan_files("job_b", job_id, 8),
             )
             .await?;
@@ -874,7 +1746,6 @@ mod test {
     async fn test_bind_task_consistent_hash_with_tolerance() -> Result<()> {
         let num_partition = 8usize;
         let active_jobs = mock_active_jobs(num_partition).await?;
-        let active_jobs = Arc::new(active_jobs);
         let topology_nodes = mock_topology_nodes();
         let num_replicas = 31;
         let tolerance = 1;
@@ -884,7 +1755,7 @@ mod test {
                 topology_nodes,
                 num_replicas,
                 tolerance,
-                active_jobs,
+                &mut ScheduledJob::unbounded(&active_jobs),
                 |job_id, _| mock_get_scan_files("job_b", job_id, 8),
             )
             .await?;
@@ -955,6 +1826,10 @@ mod test {
             port: 50051,
             grpc_port: 50052,
             specification: ExecutorSpecification { task_slots: 32 },
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
         };
 
         // complete first stage
@@ -0,0 +1,421 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! [KeyValueStore] backed by etcd, through the JSON gateway of its v3 API.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use ballista_core::error::{BallistaError, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::cluster::storage::{KeyValueStore, Keyspace};
+
+/// A [KeyValueStore] storing the keys of a namespace in etcd under `/ballista/{namespace}`
+pub struct EtcdStore {
+    client: reqwest::Client,
+    /// URLs of the etcd cluster members, tried in turn
+    endpoints: Vec<String>,
+    prefix: String,
+    /// Locks held, by key
+    locks: Mutex<HashMap<String, HeldLock>>,
+    /// Key of the lock fencing the writes, if any
+    fence: Mutex<Option<String>>,
+}
+
+/// A lock held by the store
+#[derive(Debug, Clone, Copy)]
+struct HeldLock {
+    lease: i64,
+    /// Revision the key of the lock was created at, which changes once another owner
+    /// takes the lock over
+    create_revision: i64,
+}
+
+impl EtcdStore {
+    pub fn new(endpoints: Vec<String>, namespace: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoints: endpoints
+                .into_iter()
+                .map(|endpoint| endpoint.trim_end_matches('/').to_owned())
+                .collect(),
+            prefix: format!("/ballista/{namespace}"),
+            locks: Mutex::new(HashMap::new()),
+            fence: Mutex::new(None),
+        }
+    }
+
+    fn key(&self, keyspace: Keyspace, key: &str) -> String {
+        format!("{}/{keyspace}/{key}", self.prefix)
+    }
+
+    /// Call an endpoint of the v3 API on the first member of the cluster which answers
+    async fn call<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<T> {
+        let mut last_error = None;
+        for endpoint in &self.endpoints {
+            let response = self
+                .client
+                .post(format!("{endpoint}/v3/{path}"))
+                .json(&body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match response {
+                Ok(response) => {
+                    return response.json().await.map_err(|e| {
+                        BallistaError::General(format!(
+                            "Invalid response of etcd to {path}: {e}"
+                        ))
+                    })
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(BallistaError::General(format!(
+            "Failed to call etcd {path}: {}",
+            last_error
+                .map(|e| e.to_string())
+                .unwrap_or_else(|| "no endpoint".to_owned())
+        )))
+    }
+
+    /// Apply a write in a transaction which, once the writes are fenced, only applies
+    /// it while the lock fencing them is held
+    async fn write(&self, request: Value) -> Result<()> {
+        let fence_key = self.fence.lock().await.clone();
+        let fence = match &fence_key {
+            Some(key) => {
+                let lock =
+                    self.locks.lock().await.get(key).copied().ok_or_else(|| {
+                        BallistaError::General(format!(
+                            "Lost the lock {key} fencing the writes to etcd"
+                        ))
+                    })?;
+                Some((key.as_str(), lock.create_revision))
+            }
+            None => None,
+        };
+        let response: TxnResponse =
+            self.call("kv/txn", fenced_txn(fence, request)).await?;
+        if !response.succeeded {
+            return Err(BallistaError::General(format!(
+                "Lost the lock {} fencing the writes to etcd",
+                fence_key.unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn grant_lease(&self, ttl: Duration) -> Result<i64> {
+        // the lease outlasts the TTL rather than expiring before it
+        let response: LeaseResponse = self
+            .call(
+                "lease/grant",
+                json!({ "TTL": ttl.as_secs_f64().ceil().max(1.0).to_string() }),
+            )
+            .await?;
+        parse_int(&response.id)
+    }
+
+    /// Whether a lease is still alive after renewing it
+    async fn keep_alive(&self, lease: i64) -> Result<bool> {
+        let response: KeepAliveResponse = self
+            .call("lease/keepalive", json!({ "ID": lease.to_string() }))
+            .await?;
+        Ok(parse_int(&response.result.ttl)? > 0)
+    }
+}
+
+#[tonic::async_trait]
+impl KeyValueStore for EtcdStore {
+    async fn get(&self, keyspace: Keyspace, key: &str) -> Result<Option<Vec<u8>>> {
+        let response: RangeResponse = self
+            .call(
+                "kv/range",
+                json!({ "key": encode(self.key(keyspace, key).as_bytes()) }),
+            )
+            .await?;
+        response
+            .kvs
+            .into_iter()
+            .next()
+            .map(|kv| decode(&kv.value))
+            .transpose()
+    }
+
+    async fn scan(&self, keyspace: Keyspace) -> Result<Vec<(String, Vec<u8>)>> {
+        let prefix = self.key(keyspace, "");
+        let response: RangeResponse = self
+            .call(
+                "kv/range",
+                json!({
+                    "key": encode(prefix.as_bytes()),
+                    "range_end": encode(&prefix_end(prefix.as_bytes())),
+                }),
+            )
+            .await?;
+        response
+            .kvs
+            .into_iter()
+            .map(|kv| {
+                let key = String::from_utf8(decode(&kv.key)?).map_err(|e| {
+                    BallistaError::General(format!("Invalid key in etcd: {e}"))
+                })?;
+                let key = key.strip_prefix(&prefix).unwrap_or(&key).to_owned();
+                Ok((key, decode(&kv.value)?))
+            })
+            .collect()
+    }
+
+    async fn put(&self, keyspace: Keyspace, key: &str, value: Vec<u8>) -> Result<()> {
+        self.write(json!({
+            "request_put": {
+                "key": encode(self.key(keyspace, key).as_bytes()),
+                "value": encode(&value),
+            }
+        }))
+        .await
+    }
+
+    async fn delete(&self, keyspace: Keyspace, key: &str) -> Result<()> {
+        self.write(json!({
+            "request_delete_range": {
+                "key": encode(self.key(keyspace, key).as_bytes()),
+            }
+        }))
+        .await
+    }
+
+    async fn lock(
+        &self,
+        keyspace: Keyspace,
+        key: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<bool> {
+        let key = self.key(keyspace, key);
+        let mut locks = self.locks.lock().await;
+        if let Some(lock) = locks.get(&key) {
+            if self.keep_alive(lock.lease).await? {
+                return Ok(true);
+            }
+            locks.remove(&key);
+        }
+
+        // the key is created with a new lease, unless another owner holds it
+        let lease = self.grant_lease(ttl).await?;
+        let put = json!({
+            "key": encode(key.as_bytes()),
+            "value": encode(owner.as_bytes()),
+            "lease": lease.to_string(),
+        });
+        let response: TxnResponse = self
+            .call(
+                "kv/txn",
+                json!({
+                    "compare": [{
+                        "key": encode(key.as_bytes()),
+                        "result": "EQUAL",
+                        "target": "CREATE",
+                        "create_revision": "0",
+                    }],
+                    "success": [{ "request_put": put }],
+                }),
+            )
+            .await?;
+        if response.succeeded {
+            // the key was created by the transaction, at its revision
+            let create_revision = parse_int(&response.header.revision)?;
+            locks.insert(
+                key,
+                HeldLock {
+                    lease,
+                    create_revision,
+                },
+            );
+        }
+        Ok(response.succeeded)
+    }
+
+    async fn fence(&self, keyspace: Keyspace, key: &str) -> Result<()> {
+        let key = self.key(keyspace, key);
+        if !self.locks.lock().await.contains_key(&key) {
+            return Err(BallistaError::General(format!(
+                "Can't fence the writes to etcd with the lock {key}, which isn't held"
+            )));
+        }
+        *self.fence.lock().await = Some(key);
+        Ok(())
+    }
+}
+
+/// A transaction applying a write `request`, only if the key of a fence, if any, still
+/// has the create revision of the lock held
+fn fenced_txn(fence: Option<(&str, i64)>, request: Value) -> Value {
+    let compare: Vec<Value> = fence
+        .map(|(key, create_revision)| {
+            json!({
+                "key": encode(key.as_bytes()),
+                "result": "EQUAL",
+                "target": "CREATE",
+                "create_revision": create_revision.to_string(),
+            })
+        })
+        .into_iter()
+        .collect();
+    json!({ "compare": compare, "success": [request] })
+}
+
+#[derive(Deserialize)]
+struct RangeResponse {
+    #[serde(default)]
+    kvs: Vec<KeyValue>,
+}
+
+#[derive(Deserialize)]
+struct KeyValue {
+    key: String,
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct LeaseResponse {
+    #[serde(rename = "ID", default)]
+    id: String,
+    #[serde(rename = "TTL", default)]
+    ttl: String,
+}
+
+#[derive(Deserialize)]
+struct KeepAliveResponse {
+    result: LeaseResponse,
+}
+
+#[derive(Deserialize)]
+struct TxnResponse {
+    #[serde(default)]
+    header: ResponseHeader,
+    #[serde(default)]
+    succeeded: bool,
+}
+
+#[derive(Deserialize, Default)]
+struct ResponseHeader {
+    #[serde(default)]
+    revision: String,
+}
+
+fn encode(bytes: &[u8]) -> String {
+    STANDARD.encode(bytes)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| BallistaError::General(format!("Invalid value in etcd: {e}")))
+}
+
+/// The JSON gateway encodes 64-bit integers as strings, and omits them when zero
+fn parse_int(value: &str) -> Result<i64> {
+    if value.is_empty() {
+        return Ok(0);
+    }
+    value
+        .parse()
+        .map_err(|e| BallistaError::General(format!("Invalid integer {value}: {e}")))
+}
+
+/// The end of the range of the keys starting with `prefix`
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    // all the keys
+    vec![0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_end_of_prefix() {
+        assert_eq!(prefix_end(b"/ballista/a/"), b"/ballista/a0".to_vec());
+        assert_eq!(prefix_end(&[b'a', u8::MAX]), b"b".to_vec());
+    }
+
+    #[test]
+    fn parse_gateway_responses() -> Result<()> {
+        let response: KeepAliveResponse =
+            serde_json::from_str(r#"{"result":{"ID":"7587","TTL":"10"}}"#).unwrap();
+        assert_eq!(parse_int(&response.result.id)?, 7587);
+        assert_eq!(parse_int(&response.result.ttl)?, 10);
+        // the TTL of an expired lease is omitted
+        let response: KeepAliveResponse =
+            serde_json::from_str(r#"{"result":{"ID":"7587"}}"#).unwrap();
+        assert_eq!(parse_int(&response.result.ttl)?, 0);
+
+        let response: RangeResponse = serde_json::from_str(
+            r#"{"header":{},"kvs":[{"key":"YQ==","value":"Yg=="}]}"#,
+        )
+        .unwrap();
+        assert_eq!(decode(&response.kvs[0].key)?, b"a");
+        assert_eq!(decode(&response.kvs[0].value)?, b"b");
+        let response: RangeResponse = serde_json::from_str(r#"{"header":{}}"#).unwrap();
+        assert!(response.kvs.is_empty());
+
+        let response: TxnResponse =
+            serde_json::from_str(r#"{"header":{"revision":"42"},"succeeded":true}"#)
+                .unwrap();
+        assert!(response.succeeded);
+        assert_eq!(parse_int(&response.header.revision)?, 42);
+        // a failed comparison omits the outcome
+        let response: TxnResponse = serde_json::from_str(r#"{"header":{}}"#).unwrap();
+        assert!(!response.succeeded);
+        Ok(())
+    }
+
+    #[test]
+    fn writes_compare_the_fence() {
+        let request = json!({ "request_delete_range": { "key": "YQ==" } });
+        let txn = fenced_txn(None, request.clone());
+        assert_eq!(txn, json!({ "compare": [], "success": [request] }));
+
+        let txn = fenced_txn(Some(("/ballista/a/leader/scheduler", 7)), request.clone());
+        assert_eq!(
+            txn["compare"],
+            json!([{
+                "key": encode(b"/ballista/a/leader/scheduler"),
+                "result": "EQUAL",
+                "target": "CREATE",
+                "create_revision": "7",
+            }])
+        );
+        assert_eq!(txn["success"], json!([request]));
+    }
+}
@@ -0,0 +1,325 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Persistent storage of the state of the cluster, shared by the schedulers of a
+//! namespace, and the election of the scheduler running its jobs.
+
+#[cfg(feature = "etcd")]
+pub mod etcd;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ballista_core::error::{BallistaError, Result};
+use log::{info, warn};
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+/// Key of the leader lock in [Keyspace::Leader]
+const LEADER_KEY: &str = "scheduler";
+
+/// The keyspaces of a [KeyValueStore]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Keyspace {
+    /// Jobs accepted but not planned yet, as their `JobStatus`
+    QueuedJobs,
+    /// Jobs being run, as their `ExecutionGraph`
+    ActiveJobs,
+    /// Settings of the sessions the active jobs were submitted with, as `JobSessionConfig`
+    JobSessions,
+    /// Jobs which completed or failed, as their `JobStatus`
+    CompletedJobs,
+    /// Settings of the sessions, as `SessionSettings`
+    Sessions,
+    /// Reservations of executor slots, as `SlotReservation`
+    SlotReservations,
+    /// Sizes of the tables scanned by the successful jobs, as `TableStatistics`
+    TableStatistics,
+    /// Calibration of the cost model of the exchanges, as `ExchangeCalibrationState`
+    ExchangeCalibration,
+    /// Lock held by the scheduler running the jobs
+    Leader,
+}
+
+impl fmt::Display for Keyspace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Keyspace::QueuedJobs => "queued_jobs",
+            Keyspace::ActiveJobs => "active_jobs",
+            Keyspace::JobSessions => "job_sessions",
+            Keyspace::CompletedJobs => "completed_jobs",
+            Keyspace::Sessions => "sessions",
+            Keyspace::SlotReservations => "slot_reservations",
+            Keyspace::TableStatistics => "table_statistics",
+            Keyspace::ExchangeCalibration => "exchange_calibration",
+            Keyspace::Leader => "leader",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A key-value store which the state of the cluster is persisted to, so that it
+/// outlives the scheduler
+#[tonic::async_trait]
+pub trait KeyValueStore: Send + Sync + 'static {
+    /// Get the value of a key, if it exists
+    async fn get(&self, keyspace: Keyspace, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Get all the keys of a keyspace with their values
+    async fn scan(&self, keyspace: Keyspace) -> Result<Vec<(String, Vec<u8>)>>;
+
+    /// Set the value of a key
+    async fn put(&self, keyspace: Keyspace, key: &str, value: Vec<u8>) -> Result<()>;
+
+    /// Delete a key, if it exists
+    async fn delete(&self, keyspace: Keyspace, key: &str) -> Result<()>;
+
+    /// Acquire the lock on a key for `owner`, or renew it if `owner` holds it already.
+    /// The lock is released once `ttl` elapses without it being renewed. Returns
+    /// whether `owner` holds the lock
+    async fn lock(
+        &self,
+        keyspace: Keyspace,
+        key: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<bool>;
+
+    /// Fence the writes through this store with the lock on a key it acquired: from
+    /// then on, [Self::put] and [Self::delete] only apply while the lock it acquired is
+    /// still held, and fail otherwise, so that a scheduler which lost its lock can't
+    /// overwrite the state of the next holder
+    async fn fence(&self, keyspace: Keyspace, key: &str) -> Result<()>;
+}
+
+/// Election of the scheduler running the jobs among the schedulers sharing a store.
+/// The others stand by until the leader fails to renew its lock
+pub struct LeaderElection {
+    store: Arc<dyn KeyValueStore>,
+    scheduler: String,
+    ttl: Duration,
+    /// Until when the lock is surely held by the scheduler
+    held_until: Mutex<Instant>,
+}
+
+impl LeaderElection {
+    pub fn new(store: Arc<dyn KeyValueStore>, scheduler: String, ttl: Duration) -> Self {
+        Self {
+            store,
+            scheduler,
+            ttl,
+            held_until: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until the scheduler is elected, fencing the writes of the store with its
+    /// lock from then on
+    pub async fn acquire(&self) {
+        loop {
+            let requested_at = Instant::now();
+            match self.try_lock().await {
+                Ok(true) => match self.store.fence(Keyspace::Leader, LEADER_KEY).await {
+                    Ok(()) => {
+                        *self.held_until.lock() = requested_at + self.lock_validity();
+                        info!("Scheduler {} elected as leader", self.scheduler);
+                        return;
+                    }
+                    Err(e) => {
+                        warn!("Failed to fence the writes with the leader lock: {e}")
+                    }
+                },
+                Ok(false) => {}
+                Err(e) => warn!("Failed to acquire the leader lock: {e}"),
+            }
+            tokio::time::sleep(self.renew_interval()).await;
+        }
+    }
+
+    /// Renew the lock of the elected scheduler until another scheduler takes it over,
+    /// or the lock can't be renewed before it may have expired, returning an error then.
+    /// The lock is only known to be held for its TTL, less a margin, from the time its
+    /// last successful renewal was requested, as the store may have received the
+    /// request any time after.
+    pub async fn keep(&self) -> Result<()> {
+        loop {
+            let held_until = *self.held_until.lock();
+            tokio::time::sleep_until(
+                (Instant::now() + self.renew_interval()).min(held_until),
+            )
+            .await;
+            let requested_at = Instant::now();
+            if requested_at >= held_until {
+                return Err(BallistaError::General(format!(
+                    "Scheduler {} lost its leadership, its lock may have expired before \
+                    it could be renewed",
+                    self.scheduler
+                )));
+            }
+            match tokio::time::timeout_at(held_until, self.try_lock()).await {
+                Ok(Ok(true)) => {
+                    *self.held_until.lock() = requested_at + self.lock_validity();
+                }
+                Ok(Ok(false)) => {
+                    return Err(BallistaError::General(format!(
+                        "Scheduler {} lost its leadership",
+                        self.scheduler
+                    )))
+                }
+                Ok(Err(e)) => warn!("Failed to renew the leader lock: {e}"),
+                Err(_) => warn!("Renewal of the leader lock timed out"),
+            }
+        }
+    }
+
+    /// The name of the elected scheduler, its address, if any
+    pub async fn leader(&self) -> Result<Option<String>> {
+        let Some(leader) = self.store.get(Keyspace::Leader, LEADER_KEY).await? else {
+            return Ok(None);
+        };
+        String::from_utf8(leader).map(Some).map_err(|e| {
+            BallistaError::General(format!("Invalid leader lock owner: {e}"))
+        })
+    }
+
+    async fn try_lock(&self) -> Result<bool> {
+        self.store
+            .lock(Keyspace::Leader, LEADER_KEY, &self.scheduler, self.ttl)
+            .await
+    }
+
+    /// The lock is renewed three times per TTL, to outlast a failed renewal
+    fn renew_interval(&self) -> Duration {
+        self.ttl / 3
+    }
+
+    /// How long the lock is known to be held after a renewal was requested: its TTL,
+    /// less a tenth of it for the drift between the clocks of the scheduler and the
+    /// store
+    fn lock_validity(&self) -> Duration {
+        self.ttl - self.ttl / 10
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::test_util::InMemoryKeyValueStore;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// A store whose lock renewals hang once stalled, as a partitioned etcd would
+    #[derive(Default)]
+    struct StalledStore {
+        store: InMemoryKeyValueStore,
+        stalled: AtomicBool,
+    }
+
+    #[tonic::async_trait]
+    impl KeyValueStore for StalledStore {
+        async fn get(&self, keyspace: Keyspace, key: &str) -> Result<Option<Vec<u8>>> {
+            self.store.get(keyspace, key).await
+        }
+
+        async fn scan(&self, keyspace: Keyspace) -> Result<Vec<(String, Vec<u8>)>> {
+            self.store.scan(keyspace).await
+        }
+
+        async fn put(&self, keyspace: Keyspace, key: &str, value: Vec<u8>) -> Result<()> {
+            self.store.put(keyspace, key, value).await
+        }
+
+        async fn delete(&self, keyspace: Keyspace, key: &str) -> Result<()> {
+            self.store.delete(keyspace, key).await
+        }
+
+        async fn lock(
+            &self,
+            keyspace: Keyspace,
+            key: &str,
+            owner: &str,
+            ttl: Duration,
+        ) -> Result<bool> {
+            if self.stalled.load(Ordering::Relaxed) {
+                futures::future::pending::<()>().await;
+            }
+            self.store.lock(keyspace, key, owner, ttl).await
+        }
+
+        async fn fence(&self, keyspace: Keyspace, key: &str) -> Result<()> {
+            self.store.fence(keyspace, key).await
+        }
+    }
+
+    #[tokio::test]
+    async fn standby_scheduler_takes_over_expired_lock() -> Result<()> {
+        let store: Arc<dyn KeyValueStore> = Arc::new(InMemoryKeyValueStore::default());
+        let ttl = Duration::from_millis(300);
+        let leader = LeaderElection::new(store.clone(), "leader".to_owned(), ttl);
+        let standby = LeaderElection::new(store.clone(), "standby".to_owned(), ttl);
+
+        leader.acquire().await;
+        assert_eq!(Some("leader".to_owned()), standby.leader().await?);
+        assert!(!standby.try_lock().await?);
+        // the lock is renewed by its holder only
+        assert!(leader.try_lock().await?);
+
+        let standby = tokio::spawn(async move {
+            standby.acquire().await;
+            standby
+        });
+        tokio::time::sleep(ttl * 2).await;
+        let standby = standby.await.unwrap();
+        assert!(standby.try_lock().await?);
+        assert_eq!(Some("standby".to_owned()), leader.leader().await?);
+        assert!(leader.keep().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writes_fenced_by_leader_lock() -> Result<()> {
+        let store: Arc<dyn KeyValueStore> = Arc::new(InMemoryKeyValueStore::default());
+        let ttl = Duration::from_millis(300);
+        let leader = LeaderElection::new(store.clone(), "leader".to_owned(), ttl);
+
+        // writes aren't fenced before the election
+        store.put(Keyspace::Sessions, "a", vec![1]).await?;
+        leader.acquire().await;
+        store.put(Keyspace::Sessions, "b", vec![2]).await?;
+
+        // nor applied once the lock expired without being renewed
+        tokio::time::sleep(ttl * 2).await;
+        assert!(store.put(Keyspace::Sessions, "c", vec![3]).await.is_err());
+        assert!(store.delete(Keyspace::Sessions, "a").await.is_err());
+        assert_eq!(2, store.scan(Keyspace::Sessions).await?.len());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn leader_steps_down_before_lock_expires() -> Result<()> {
+        let store = Arc::new(StalledStore::default());
+        let ttl = Duration::from_millis(600);
+        let leader = LeaderElection::new(store.clone(), "leader".to_owned(), ttl);
+        leader.acquire().await;
+        let elected_at = Instant::now();
+
+        // the renewals hang, so the leader steps down on its own
+        store.stalled.store(true, Ordering::Relaxed);
+        assert!(leader.keep().await.is_err());
+        assert!(elected_at.elapsed() < ttl);
+        Ok(())
+    }
+}
@@ -15,16 +15,18 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::cluster::storage::{KeyValueStore, Keyspace};
 use crate::cluster::{JobState, JobStateEvent};
 use crate::scheduler_server::timestamp_millis;
 use crate::state::execution_graph::ExecutionGraph;
 use crate::test_utils::{await_condition, mock_completed_task, mock_executor};
-use ballista_core::error::Result;
+use ballista_core::error::{BallistaError, Result};
 use ballista_core::serde::protobuf::job_status::Status;
 use ballista_core::serde::protobuf::JobStatus;
 use futures::StreamExt;
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
 pub struct JobStateTest<S: JobState> {
@@ -212,3 +214,108 @@ fn drain_tasks(graph: &mut ExecutionGraph) -> Result<()> {
 
     Ok(())
 }
+
+/// A [KeyValueStore] keeping its keys in memory, standing for a store shared by
+/// several schedulers
+#[derive(Default)]
+pub struct InMemoryKeyValueStore {
+    values: parking_lot::Mutex<BTreeMap<(String, String), Vec<u8>>>,
+    /// Owner and expiry of the locks, by key
+    locks: parking_lot::Mutex<BTreeMap<(String, String), (String, Instant)>>,
+    /// Lock fencing the writes, if any, and its owner when the writes were fenced. The
+    /// schedulers sharing the store can't be told apart, so the writes are fenced by
+    /// the last scheduler which fenced them
+    fence: parking_lot::Mutex<Option<((String, String), String)>>,
+}
+
+impl InMemoryKeyValueStore {
+    fn check_fence(&self) -> Result<()> {
+        let Some((key, owner)) = self.fence.lock().clone() else {
+            return Ok(());
+        };
+        match self.locks.lock().get(&key) {
+            Some((holder, expiry)) if *holder == owner && *expiry > Instant::now() => {
+                Ok(())
+            }
+            _ => Err(BallistaError::General(format!(
+                "Lost the lock {key:?} fencing the writes"
+            ))),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl KeyValueStore for InMemoryKeyValueStore {
+    async fn get(&self, keyspace: Keyspace, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .values
+            .lock()
+            .get(&(keyspace.to_string(), key.to_owned()))
+            .cloned())
+    }
+
+    async fn scan(&self, keyspace: Keyspace) -> Result<Vec<(String, Vec<u8>)>> {
+        let keyspace = keyspace.to_string();
+        Ok(self
+            .values
+            .lock()
+            .iter()
+            .filter(|((space, _), _)| *space == keyspace)
+            .map(|((_, key), value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    async fn put(&self, keyspace: Keyspace, key: &str, value: Vec<u8>) -> Result<()> {
+        self.check_fence()?;
+        self.values
+            .lock()
+            .insert((keyspace.to_string(), key.to_owned()), value);
+        Ok(())
+    }
+
+    async fn delete(&self, keyspace: Keyspace, key: &str) -> Result<()> {
+        self.check_fence()?;
+        self.values
+            .lock()
+            .remove(&(keyspace.to_string(), key.to_owned()));
+        Ok(())
+    }
+
+    async fn lock(
+        &self,
+        keyspace: Keyspace,
+        key: &str,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<bool> {
+        let mut locks = self.locks.lock();
+        let now = Instant::now();
+        let lock = locks
+            .entry((keyspace.to_string(), key.to_owned()))
+            .or_insert_with(|| (owner.to_owned(), now));
+        if lock.0 != owner && lock.1 > now {
+            return Ok(false);
+        }
+        *lock = (owner.to_owned(), now + ttl);
+        // the owner of a lock is its value, as in etcd
+        self.values.lock().insert(
+            (keyspace.to_string(), key.to_owned()),
+            owner.as_bytes().to_vec(),
+        );
+        Ok(true)
+    }
+
+    async fn fence(&self, keyspace: Keyspace, key: &str) -> Result<()> {
+        let key = (keyspace.to_string(), key.to_owned());
+        let owner = self
+            .locks
+            .lock()
+            .get(&key)
+            .map(|(owner, _)| owner.clone())
+            .ok_or_else(|| {
+                BallistaError::General(format!("Can't fence the writes with {key:?}"))
+            })?;
+        *self.fence.lock() = Some((key, owner));
+        Ok(())
+    }
+}
@@ -18,12 +18,32 @@
 
 //! Ballista scheduler specific configuration
 
+use crate::access_control::AccessControl;
+use crate::auth::{self, AuthProvider};
+use crate::cluster::job_scheduling::{
+    FairShareJobScheduling, FifoJobScheduling, JobSchedulingPolicy, PriorityJobScheduling,
+};
+#[cfg(feature = "nats")]
+use crate::cluster::nats::NatsEventPublisher;
+use crate::cluster::{ClusterEventPublisher, ClusterStorage};
+use crate::data_masking::DataMasking;
+use crate::executor_pools::ExecutorPools;
+use crate::query_authorization::{AllowAll, QueryAuthorizer, QueryRules};
+use crate::resource_profiles::ResourceProfiles;
+use crate::state::job_retention::JobRetention;
+use crate::state::task_manager::{STAGE_MAX_FAILURES, TASK_MAX_FAILURES};
+use crate::state::webhooks::WebhookConfig;
 use crate::SessionBuilder;
+use ballista_core::compression::{CompressionCodec, CompressionCodecRegistry};
+use ballista_core::config::redact_key_value_pairs;
+use ballista_core::security::GrpcSecurityConfig;
+use ballista_core::serde::protobuf::KeyValuePair;
 use ballista_core::{config::TaskSchedulingPolicy, error::BallistaError, ConfigProducer};
 use clap::ValueEnum;
 use datafusion_proto::logical_plan::LogicalExtensionCodec;
 use datafusion_proto::physical_plan::PhysicalExtensionCodec;
-use std::{fmt, sync::Arc};
+use object_store::ObjectStore;
+use std::{fmt, sync::Arc, time::Duration};
 
 include!(concat!(
     env!("OUT_DIR"),
@@ -46,6 +66,28 @@ pub struct SchedulerConfig {
     pub event_loop_buffer_size: u32,
     /// Policy of distributing tasks to available executor slots. For a cluster with single scheduler, round-robin is recommended
     pub task_distribution: TaskDistributionPolicy,
+    /// Policy of binding tasks of a job to executors built with different Ballista/DataFusion versions
+    pub executor_version_policy: ExecutorVersionPolicy,
+    /// Policy of sharing free executor slots among the queued tasks of the running jobs
+    pub job_scheduling_policy: Arc<dyn JobSchedulingPolicy>,
+    /// Format of the plans dispatched to executors with tasks
+    pub task_plan_format: TaskPlanFormat,
+    /// The size in bytes from which plans are dispatched with tasks by reference, for executors
+    /// to fetch and cache them once rather than receiving them with every task. 0 means plans
+    /// are always dispatched inline
+    pub plan_reference_threshold: usize,
+    /// The codec compressing the plans dispatched with tasks, if any. Executors must
+    /// register it too
+    pub task_plan_compression: Option<Arc<dyn CompressionCodec>>,
+    /// The maximum number of times a task is attempted before its stage, and the job, fail
+    pub task_max_failures: usize,
+    /// The maximum number of times a stage is rerun, after its input partitions were lost
+    /// with the executors holding them, before the job fails
+    pub stage_max_failures: usize,
+    /// How many times slower than the median successful task of its stage a running task
+    /// must be for a speculative attempt of it to be launched, 0 means speculative
+    /// execution is disabled
+    pub speculation_multiplier: f64,
     /// The delayed interval for cleaning up finished job data, mainly the shuffle data, 0 means the cleaning up is disabled
     pub finished_job_data_clean_up_interval_seconds: u64,
     /// The delayed interval for cleaning up finished job state stored in the backend, 0 means the cleaning up is disabled.
@@ -57,6 +99,13 @@ pub struct SchedulerConfig {
     pub job_resubmit_interval_ms: Option<u64>,
     /// Configuration for ballista cluster storage
     pub cluster_storage: ClusterStorageConfig,
+    /// The time in seconds after which a standby scheduler takes over from the leader
+    /// scheduler when it stops renewing its lease, with a persistent cluster storage
+    pub leader_lease_ttl_seconds: u64,
+    /// Whether the scheduler is a read replica, serving the jobs persisted by the
+    /// leader scheduler without running them, and rejecting the requests changing the
+    /// state
+    pub read_replica: bool,
     /// Time in seconds to allow executor for graceful shutdown. Once an executor signals it has entered Terminating status
     /// the scheduler should only consider the executor dead after this time interval has elapsed
     pub executor_termination_grace_period: u64,
@@ -66,10 +115,97 @@ pub struct SchedulerConfig {
     pub grpc_server_max_decoding_message_size: u32,
     /// The maximum size of an encoded message at the grpc server side.
     pub grpc_server_max_encoding_message_size: u32,
+    /// The maximum size of a decoded message at the side of the grpc clients of the
+    /// scheduler, i.e. of its connections to executors.
+    pub grpc_client_max_decoding_message_size: u32,
+    /// The maximum size of an encoded message at the side of the grpc clients of the
+    /// scheduler, e.g. of the tasks launched on executors.
+    pub grpc_client_max_encoding_message_size: u32,
+    /// TLS and auth token settings of the gRPC server of the scheduler and of its
+    /// connections to executors
+    pub grpc_security: GrpcSecurityConfig,
+    /// Tokens executors must present to register with the scheduler, empty means any
+    /// executor may register. Several tokens are accepted at once so that they can be
+    /// rotated
+    pub executor_registration_tokens: Vec<String>,
     /// The executor timeout in seconds. It should be longer than executor's heartbeat intervals.
     pub executor_timeout_seconds: u64,
     /// The interval to check expired or dead executors
     pub expire_dead_executor_interval_seconds: u64,
+    /// The number of the latest samples of the resource usage reported by each executor
+    /// with its heartbeats which are kept
+    pub executor_resource_history_size: usize,
+    /// The interval in seconds at which cluster-wide metrics are sampled, 0 means the sampling is disabled
+    pub cluster_metrics_interval_seconds: u64,
+    /// The number of the latest samples of cluster-wide metrics kept for the `ballista.cluster_metrics` table
+    pub cluster_metrics_retention_count: usize,
+    /// The maximum number of optimized SQL query plans cached for reuse by identical
+    /// submissions, 0 means the caching is disabled
+    pub plan_cache_size: usize,
+    /// The maximum number of queries whose distributed plans are cached for reuse by
+    /// identical queries, 0 means the caching is disabled
+    pub query_cache_size: usize,
+    /// The time in seconds the results of a query are served from the query cache
+    pub query_cache_result_ttl_seconds: u64,
+    /// The maximum size in bytes of the results served from the query cache, 0 means
+    /// results are not cached
+    pub query_cache_max_result_bytes: u64,
+    /// The fraction of the submitted queries which are also executed in shadow with
+    /// `shadow_execution_settings`, 0 means shadow execution is disabled
+    pub shadow_execution_fraction: f64,
+    /// Session settings of the shadow jobs, overriding the settings of the queries
+    /// they shadow
+    pub shadow_execution_settings: Vec<KeyValuePair>,
+    /// The maximum number of result rows returned to clients, beyond which results are
+    /// truncated, 0 means unlimited
+    pub max_result_rows: usize,
+    /// The maximum number of iterations of the recursive term of a recursive query,
+    /// beyond which the job fails
+    pub max_recursive_query_iterations: usize,
+    /// Whether jobs referencing functions which aren't registered on all the alive
+    /// executors are rejected when submitted
+    pub validate_job_functions: bool,
+    /// The maximum number of finished jobs kept in the state backend, 0 means unlimited
+    pub finished_job_retention_count: usize,
+    /// The maximum time in seconds finished jobs are kept in the state backend after they ended,
+    /// 0 means unlimited
+    pub finished_job_retention_seconds: u64,
+    /// The interval for purging finished jobs falling outside of the retention from the state backend
+    pub finished_job_compaction_interval_seconds: u64,
+    /// The directory finished jobs are archived to when they are purged from the state backend.
+    /// It's a directory of the local file system unless `override_job_archive_store` is set.
+    /// If neither is set, purged jobs are discarded
+    pub job_archive_dir: Option<String>,
+    /// The directory the event log of the jobs and executors is written to, so that the
+    /// history of the jobs can be replayed once they are gone from the scheduler.
+    /// It's a directory of the local file system unless `override_event_log_store` is
+    /// set. If neither is set, no event log is written
+    pub event_log_dir: Option<String>,
+    /// Share the tables and views created by DDL statements between all the sessions,
+    /// as the default schema of their default catalog
+    pub cluster_catalog: bool,
+    /// The local directory the definitions of the tables of the cluster catalog are
+    /// persisted to, so that they outlive the scheduler. If not set, they are only kept
+    /// in memory
+    pub cluster_catalog_dir: Option<String>,
+    /// Roles of the principals calling the scheduler, none means access control is disabled
+    pub access_control: Option<AccessControl>,
+    /// Authenticates the credentials of requests when access control is enabled. If not
+    /// set, the principal of requests is read from the header set by a trusted proxy
+    pub auth_provider: Option<Arc<dyn AuthProvider>>,
+    /// Rules masking sensitive columns in the queries submitted to the scheduler
+    pub data_masking: Option<DataMasking>,
+    /// Decides which queries submitted to the scheduler are allowed, all of them by
+    /// default
+    pub query_authorizer: Arc<dyn QueryAuthorizer>,
+    /// Named bundles of settings the sessions submit their jobs with, picked with
+    /// `ballista.job.resource_profile`
+    pub resource_profiles: ResourceProfiles,
+    /// Pools of executors dedicated to tenants, none by default, in which case all the
+    /// executors run the jobs of all the tenants
+    pub executor_pools: ExecutorPools,
+    /// Webhooks notified of the jobs which finished, failed or were cancelled
+    pub webhooks: WebhookConfig,
 
     /// [ConfigProducer] override option
     pub override_config_producer: Option<ConfigProducer>,
@@ -79,6 +215,12 @@ pub struct SchedulerConfig {
     pub override_logical_codec: Option<Arc<dyn LogicalExtensionCodec>>,
     /// [PhysicalExtensionCodec] override option
     pub override_physical_codec: Option<Arc<dyn PhysicalExtensionCodec>>,
+    /// [ClusterEventPublisher] which cluster topology events are pushed to, if any
+    pub override_cluster_event_publisher: Option<Arc<dyn ClusterEventPublisher>>,
+    /// [ObjectStore] which finished jobs are archived to, under `job_archive_dir`
+    pub override_job_archive_store: Option<Arc<dyn ObjectStore>>,
+    /// [ObjectStore] which the event log is written to, under `event_log_dir`
+    pub override_event_log_store: Option<Arc<dyn ObjectStore>>,
 }
 
 impl std::fmt::Debug for SchedulerConfig {
@@ -90,6 +232,20 @@ impl std::fmt::Debug for SchedulerConfig {
             .field("scheduling_policy", &self.scheduling_policy)
             .field("event_loop_buffer_size", &self.event_loop_buffer_size)
             .field("task_distribution", &self.task_distribution)
+            .field("executor_version_policy", &self.executor_version_policy)
+            .field("job_scheduling_policy", &self.job_scheduling_policy)
+            .field("task_plan_format", &self.task_plan_format)
+            .field("plan_reference_threshold", &self.plan_reference_threshold)
+            .field(
+                "task_plan_compression",
+                &self
+                    .task_plan_compression
+                    .as_ref()
+                    .map(|codec| codec.name()),
+            )
+            .field("task_max_failures", &self.task_max_failures)
+            .field("stage_max_failures", &self.stage_max_failures)
+            .field("speculation_multiplier", &self.speculation_multiplier)
             .field(
                 "finished_job_data_clean_up_interval_seconds",
                 &self.finished_job_data_clean_up_interval_seconds,
@@ -104,6 +260,8 @@ impl std::fmt::Debug for SchedulerConfig {
             )
             .field("job_resubmit_interval_ms", &self.job_resubmit_interval_ms)
             .field("cluster_storage", &self.cluster_storage)
+            .field("leader_lease_ttl_seconds", &self.leader_lease_ttl_seconds)
+            .field("read_replica", &self.read_replica)
             .field(
                 "executor_termination_grace_period",
                 &self.executor_termination_grace_period,
@@ -120,13 +278,91 @@ impl std::fmt::Debug for SchedulerConfig {
                 "grpc_server_max_encoding_message_size",
                 &self.grpc_server_max_encoding_message_size,
             )
+            .field(
+                "grpc_client_max_decoding_message_size",
+                &self.grpc_client_max_decoding_message_size,
+            )
+            .field(
+                "grpc_client_max_encoding_message_size",
+                &self.grpc_client_max_encoding_message_size,
+            )
+            .field("grpc_security", &self.grpc_security)
+            .field(
+                "executor_registration_tokens",
+                &format!("{} tokens", self.executor_registration_tokens.len()),
+            )
             .field("executor_timeout_seconds", &self.executor_timeout_seconds)
             .field(
                 "expire_dead_executor_interval_seconds",
                 &self.expire_dead_executor_interval_seconds,
             )
+            .field(
+                "executor_resource_history_size",
+                &self.executor_resource_history_size,
+            )
+            .field(
+                "cluster_metrics_interval_seconds",
+                &self.cluster_metrics_interval_seconds,
+            )
+            .field(
+                "cluster_metrics_retention_count",
+                &self.cluster_metrics_retention_count,
+            )
+            .field("plan_cache_size", &self.plan_cache_size)
+            .field("query_cache_size", &self.query_cache_size)
+            .field(
+                "query_cache_result_ttl_seconds",
+                &self.query_cache_result_ttl_seconds,
+            )
+            .field(
+                "query_cache_max_result_bytes",
+                &self.query_cache_max_result_bytes,
+            )
+            .field("shadow_execution_fraction", &self.shadow_execution_fraction)
+            .field(
+                "shadow_execution_settings",
+                &redact_key_value_pairs(&self.shadow_execution_settings),
+            )
+            .field("max_result_rows", &self.max_result_rows)
+            .field(
+                "max_recursive_query_iterations",
+                &self.max_recursive_query_iterations,
+            )
+            .field("validate_job_functions", &self.validate_job_functions)
+            .field(
+                "finished_job_retention_count",
+                &self.finished_job_retention_count,
+            )
+            .field(
+                "finished_job_retention_seconds",
+                &self.finished_job_retention_seconds,
+            )
+            .field(
+                "finished_job_compaction_interval_seconds",
+                &self.finished_job_compaction_interval_seconds,
+            )
+            .field("job_archive_dir", &self.job_archive_dir)
+            .field("event_log_dir", &self.event_log_dir)
+            .field("cluster_catalog", &self.cluster_catalog)
+            .field("cluster_catalog_dir", &self.cluster_catalog_dir)
+            .field("access_control", &self.access_control)
+            .field("auth_provider", &self.auth_provider)
+            .field("data_masking", &self.data_masking)
+            .field("query_authorizer", &self.query_authorizer)
+            .field("resource_profiles", &self.resource_profiles)
+            .field("executor_pools", &self.executor_pools)
+            .field("webhooks", &self.webhooks)
             .field("override_logical_codec", &self.override_logical_codec)
             .field("override_physical_codec", &self.override_physical_codec)
+            .field(
+                "override_cluster_event_publisher",
+                &self.override_cluster_event_publisher.is_some(),
+            )
+            .field(
+                "override_job_archive_store",
+                &self.override_job_archive_store,
+            )
+            .field("override_event_log_store", &self.override_event_log_store)
             .finish()
     }
 }
@@ -140,21 +376,64 @@ impl Default for SchedulerConfig {
             scheduling_policy: TaskSchedulingPolicy::PullStaged,
             event_loop_buffer_size: 10000,
             task_distribution: TaskDistributionPolicy::Bias,
+            executor_version_policy: ExecutorVersionPolicy::Lenient,
+            job_scheduling_policy: Arc::new(PriorityJobScheduling),
+            task_plan_format: TaskPlanFormat::Physical,
+            plan_reference_threshold: 0,
+            task_plan_compression: None,
+            task_max_failures: TASK_MAX_FAILURES,
+            stage_max_failures: STAGE_MAX_FAILURES,
+            speculation_multiplier: 0.0,
             finished_job_data_clean_up_interval_seconds: 300,
             finished_job_state_clean_up_interval_seconds: 3600,
             advertise_flight_sql_endpoint: None,
             cluster_storage: ClusterStorageConfig::Memory,
+            leader_lease_ttl_seconds: 10,
+            read_replica: false,
             job_resubmit_interval_ms: None,
             executor_termination_grace_period: 0,
             scheduler_event_expected_processing_duration: 0,
             grpc_server_max_decoding_message_size: 16777216,
             grpc_server_max_encoding_message_size: 16777216,
+            grpc_client_max_decoding_message_size: 16777216,
+            grpc_client_max_encoding_message_size: 16777216,
+            grpc_security: GrpcSecurityConfig::default(),
+            executor_registration_tokens: vec![],
             executor_timeout_seconds: 180,
             expire_dead_executor_interval_seconds: 15,
+            executor_resource_history_size: 60,
+            cluster_metrics_interval_seconds: 15,
+            cluster_metrics_retention_count: 240,
+            plan_cache_size: 0,
+            query_cache_size: 0,
+            query_cache_result_ttl_seconds: 300,
+            query_cache_max_result_bytes: 0,
+            shadow_execution_fraction: 0.0,
+            shadow_execution_settings: vec![],
+            max_result_rows: 0,
+            max_recursive_query_iterations: 100,
+            validate_job_functions: true,
+            finished_job_retention_count: 0,
+            finished_job_retention_seconds: 0,
+            finished_job_compaction_interval_seconds: 60,
+            job_archive_dir: None,
+            event_log_dir: None,
+            cluster_catalog: false,
+            cluster_catalog_dir: None,
+            access_control: None,
+            auth_provider: None,
+            data_masking: None,
+            query_authorizer: Arc::new(AllowAll),
+            resource_profiles: ResourceProfiles::default(),
+            executor_pools: ExecutorPools::default(),
+            webhooks: WebhookConfig::default(),
             override_config_producer: None,
             override_session_builder: None,
             override_logical_codec: None,
             override_physical_codec: None,
+            override_cluster_event_publisher: None,
+            override_job_archive_store: None,
+            override_event_log_store: None,
         }
     }
 }
@@ -222,11 +501,72 @@ impl SchedulerConfig {
         self
     }
 
+    pub fn with_executor_version_policy(mut self, policy: ExecutorVersionPolicy) -> Self {
+        self.executor_version_policy = policy;
+        self
+    }
+
+    pub fn with_job_scheduling_policy(
+        mut self,
+        policy: Arc<dyn JobSchedulingPolicy>,
+    ) -> Self {
+        self.job_scheduling_policy = policy;
+        self
+    }
+
+    pub fn with_task_plan_format(mut self, format: TaskPlanFormat) -> Self {
+        self.task_plan_format = format;
+        self
+    }
+
+    pub fn with_plan_reference_threshold(mut self, threshold: usize) -> Self {
+        self.plan_reference_threshold = threshold;
+        self
+    }
+
+    pub fn with_task_plan_compression(
+        mut self,
+        codec: Option<Arc<dyn CompressionCodec>>,
+    ) -> Self {
+        self.task_plan_compression = codec;
+        self
+    }
+
+    pub fn with_executor_resource_history_size(mut self, size: usize) -> Self {
+        self.executor_resource_history_size = size;
+        self
+    }
+
+    pub fn with_task_max_failures(mut self, max_failures: usize) -> Self {
+        self.task_max_failures = max_failures;
+        self
+    }
+
+    pub fn with_stage_max_failures(mut self, max_failures: usize) -> Self {
+        self.stage_max_failures = max_failures;
+        self
+    }
+
+    pub fn with_speculation_multiplier(mut self, multiplier: f64) -> Self {
+        self.speculation_multiplier = multiplier;
+        self
+    }
+
     pub fn with_cluster_storage(mut self, config: ClusterStorageConfig) -> Self {
         self.cluster_storage = config;
         self
     }
 
+    pub fn with_leader_lease_ttl_seconds(mut self, ttl: u64) -> Self {
+        self.leader_lease_ttl_seconds = ttl;
+        self
+    }
+
+    pub fn with_read_replica(mut self, read_replica: bool) -> Self {
+        self.read_replica = read_replica;
+        self
+    }
+
     pub fn with_job_resubmit_interval_ms(mut self, interval_ms: u64) -> Self {
         self.job_resubmit_interval_ms = Some(interval_ms);
         self
@@ -246,11 +586,200 @@ impl SchedulerConfig {
         self.grpc_server_max_encoding_message_size = value;
         self
     }
+
+    pub fn with_grpc_client_max_decoding_message_size(mut self, value: u32) -> Self {
+        self.grpc_client_max_decoding_message_size = value;
+        self
+    }
+
+    pub fn with_grpc_client_max_encoding_message_size(mut self, value: u32) -> Self {
+        self.grpc_client_max_encoding_message_size = value;
+        self
+    }
+
+    pub fn with_grpc_security(mut self, grpc_security: GrpcSecurityConfig) -> Self {
+        self.grpc_security = grpc_security;
+        self
+    }
+
+    pub fn with_executor_registration_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.executor_registration_tokens = tokens;
+        self
+    }
+
+    pub fn with_cluster_metrics_interval_seconds(
+        mut self,
+        interval_seconds: u64,
+    ) -> Self {
+        self.cluster_metrics_interval_seconds = interval_seconds;
+        self
+    }
+
+    pub fn with_cluster_metrics_retention_count(mut self, count: usize) -> Self {
+        self.cluster_metrics_retention_count = count;
+        self
+    }
+
+    pub fn with_plan_cache_size(mut self, size: usize) -> Self {
+        self.plan_cache_size = size;
+        self
+    }
+
+    /// Cache the distributed plans of `size` queries, and serve their results of at
+    /// most `max_result_bytes` for `result_ttl_seconds`
+    pub fn with_query_cache(
+        mut self,
+        size: usize,
+        result_ttl_seconds: u64,
+        max_result_bytes: u64,
+    ) -> Self {
+        self.query_cache_size = size;
+        self.query_cache_result_ttl_seconds = result_ttl_seconds;
+        self.query_cache_max_result_bytes = max_result_bytes;
+        self
+    }
+
+    /// The time the results of a query are served from the query cache, which ends
+    /// before the output partitions of the job which produced them are cleaned up
+    pub fn query_cache_result_ttl(&self) -> Duration {
+        let ttl = match self.finished_job_data_clean_up_interval_seconds {
+            0 => self.query_cache_result_ttl_seconds,
+            interval => self.query_cache_result_ttl_seconds.min(interval),
+        };
+        Duration::from_secs(ttl)
+    }
+
+    /// Also execute a `fraction` of the submitted queries in shadow, with their session
+    /// settings overridden by `settings`
+    pub fn with_shadow_execution(
+        mut self,
+        fraction: f64,
+        settings: Vec<KeyValuePair>,
+    ) -> Self {
+        self.shadow_execution_fraction = fraction;
+        self.shadow_execution_settings = settings;
+        self
+    }
+
+    /// Truncate the results returned to clients at `max_rows` rows
+    pub fn with_max_result_rows(mut self, max_rows: usize) -> Self {
+        self.max_result_rows = max_rows;
+        self
+    }
+
+    /// Fail recursive queries whose recursive term doesn't reach a fixpoint within
+    /// `max_iterations` iterations
+    pub fn with_max_recursive_query_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_recursive_query_iterations = max_iterations;
+        self
+    }
+
+    pub fn with_validate_job_functions(mut self, enabled: bool) -> Self {
+        self.validate_job_functions = enabled;
+        self
+    }
+
+    pub fn with_finished_job_retention_count(mut self, count: usize) -> Self {
+        self.finished_job_retention_count = count;
+        self
+    }
+
+    pub fn with_finished_job_retention_seconds(mut self, seconds: u64) -> Self {
+        self.finished_job_retention_seconds = seconds;
+        self
+    }
+
+    pub fn with_finished_job_compaction_interval_seconds(
+        mut self,
+        interval_seconds: u64,
+    ) -> Self {
+        self.finished_job_compaction_interval_seconds = interval_seconds;
+        self
+    }
+
+    pub fn with_job_archive_dir(mut self, dir: impl Into<String>) -> Self {
+        self.job_archive_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_job_archive_store(mut self, store: Arc<dyn ObjectStore>) -> Self {
+        self.override_job_archive_store = Some(store);
+        self
+    }
+
+    pub fn with_event_log_dir(mut self, dir: impl Into<String>) -> Self {
+        self.event_log_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_event_log_store(mut self, store: Arc<dyn ObjectStore>) -> Self {
+        self.override_event_log_store = Some(store);
+        self
+    }
+
+    pub fn with_cluster_catalog(mut self, enabled: bool) -> Self {
+        self.cluster_catalog = enabled;
+        self
+    }
+
+    pub fn with_cluster_catalog_dir(mut self, dir: impl Into<String>) -> Self {
+        self.cluster_catalog_dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_access_control(mut self, access_control: AccessControl) -> Self {
+        self.access_control = Some(access_control);
+        self
+    }
+
+    pub fn with_auth_provider(mut self, auth_provider: Arc<dyn AuthProvider>) -> Self {
+        self.auth_provider = Some(auth_provider);
+        self
+    }
+
+    pub fn with_data_masking(mut self, data_masking: DataMasking) -> Self {
+        self.data_masking = Some(data_masking);
+        self
+    }
+
+    pub fn with_query_authorizer(
+        mut self,
+        query_authorizer: Arc<dyn QueryAuthorizer>,
+    ) -> Self {
+        self.query_authorizer = query_authorizer;
+        self
+    }
+
+    pub fn with_resource_profiles(mut self, resource_profiles: ResourceProfiles) -> Self {
+        self.resource_profiles = resource_profiles;
+        self
+    }
+
+    pub fn with_executor_pools(mut self, executor_pools: ExecutorPools) -> Self {
+        self.executor_pools = executor_pools;
+        self
+    }
+
+    pub fn with_webhooks(mut self, webhooks: WebhookConfig) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    /// The retention policy of finished jobs in the state backend
+    pub fn finished_job_retention(&self) -> JobRetention {
+        JobRetention {
+            max_count: self.finished_job_retention_count,
+            max_age_seconds: self.finished_job_retention_seconds,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum ClusterStorageConfig {
     Memory,
+    /// The URLs of the members of an etcd cluster
+    #[cfg(feature = "etcd")]
+    Etcd(Vec<String>),
 }
 
 /// Policy of distributing tasks to available executor slots
@@ -269,6 +798,15 @@ pub enum TaskDistribution {
     ///    And then bind it with an execute according to consistent hashing policy.
     /// 3. If needed, work stealing can be enabled based on the tolerance of the consistent hashing.
     ConsistentHash,
+    /// Bind each task to the executor holding the most bytes of the shuffle partitions
+    /// it reads, to reduce the bytes fetched from other executors, falling back to the
+    /// [`Bias`] policy for the tasks without shuffle input or whose executors are busy.
+    Locality,
+    /// Bind tasks to the executors with the most free slots weighted by the share of
+    /// their CPU, memory and network left, as reported with their heartbeats, so that
+    /// fewer tasks land on busy hosts, e.g. hosts shared with other services. With
+    /// pull-based scheduling, tasks are bound as with the [`Bias`] policy.
+    LoadAware,
 }
 
 impl std::str::FromStr for TaskDistribution {
@@ -285,6 +823,101 @@ impl parse_arg::ParseArgFromStr for TaskDistribution {
     }
 }
 
+/// Policy of binding tasks of a job to executors of different versions, e.g. during a
+/// rolling upgrade of the executor fleet.
+///
+/// A job is pinned to the version of the first executor its tasks are bound to.
+///
+/// It needs to be visible to code generated by configure_me
+#[derive(Clone, ValueEnum, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum ExecutorVersionPolicy {
+    /// Only bind tasks of a job to executors with the job's pinned version. Tasks wait
+    /// until such an executor has free slots
+    Strict,
+    /// Prefer executors with the job's pinned version, but fall back to executors
+    /// of other versions when there are free slots left
+    Lenient,
+}
+
+impl std::str::FromStr for ExecutorVersionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ValueEnum::from_str(s, true)
+    }
+}
+
+impl parse_arg::ParseArgFromStr for ExecutorVersionPolicy {
+    fn describe_type<W: fmt::Write>(mut writer: W) -> fmt::Result {
+        write!(writer, "The executor version policy for the scheduler")
+    }
+}
+
+/// Policy of sharing free executor slots among the queued tasks of the running jobs
+///
+/// It needs to be visible to code generated by configure_me
+#[derive(Clone, ValueEnum, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum JobScheduling {
+    /// Bind the queued tasks of the jobs in the order they were submitted
+    Fifo,
+    /// Bind the queued tasks of the jobs with the highest priority first
+    Priority,
+    /// Share the slots among the jobs in proportion to their priority plus one
+    FairShare,
+}
+
+impl JobScheduling {
+    pub fn policy(self) -> Arc<dyn JobSchedulingPolicy> {
+        match self {
+            JobScheduling::Fifo => Arc::new(FifoJobScheduling),
+            JobScheduling::Priority => Arc::new(PriorityJobScheduling),
+            JobScheduling::FairShare => Arc::new(FairShareJobScheduling),
+        }
+    }
+}
+
+impl std::str::FromStr for JobScheduling {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ValueEnum::from_str(s, true)
+    }
+}
+
+impl parse_arg::ParseArgFromStr for JobScheduling {
+    fn describe_type<W: fmt::Write>(mut writer: W) -> fmt::Result {
+        write!(writer, "The job scheduling policy for the scheduler")
+    }
+}
+
+/// Format of the plans dispatched to executors with tasks
+///
+/// It needs to be visible to code generated by configure_me
+#[derive(Clone, ValueEnum, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum TaskPlanFormat {
+    /// Dispatch the physical plans of stages, as planned by the scheduler
+    Physical,
+    /// Dispatch the optimized logical plans of jobs, which executors plan physically,
+    /// e.g. to take advantage of files cached on the executors. Executors must plan jobs
+    /// like the scheduler, with the same functions and table formats. Jobs whose
+    /// logical plan can't be serialized are dispatched as physical plans
+    Logical,
+}
+
+impl std::str::FromStr for TaskPlanFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ValueEnum::from_str(s, true)
+    }
+}
+
+impl parse_arg::ParseArgFromStr for TaskPlanFormat {
+    fn describe_type<W: fmt::Write>(mut writer: W) -> fmt::Result {
+        write!(writer, "The format of the plans dispatched to executors")
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum TaskDistributionPolicy {
     /// Eagerly assign tasks to executor slots. This will assign as many task slots per executor
@@ -301,6 +934,15 @@ pub enum TaskDistributionPolicy {
         num_replicas: usize,
         tolerance: usize,
     },
+    /// Bind each task to the executor holding the most bytes of the shuffle partitions
+    /// it reads, to reduce the bytes fetched from other executors, falling back to the
+    /// [`Bias`] policy for the tasks without shuffle input or whose executors are busy.
+    Locality,
+    /// Bind tasks to the executors with the most free slots weighted by the share of
+    /// their CPU, memory and network left, as reported with their heartbeats, so that
+    /// fewer tasks land on busy hosts, e.g. hosts shared with other services. With
+    /// pull-based scheduling, tasks are bound as with the [`Bias`] policy.
+    LoadAware,
 }
 
 impl TryFrom<Config> for SchedulerConfig {
@@ -310,6 +952,8 @@ impl TryFrom<Config> for SchedulerConfig {
         let task_distribution = match opt.task_distribution {
             TaskDistribution::Bias => TaskDistributionPolicy::Bias,
             TaskDistribution::RoundRobin => TaskDistributionPolicy::RoundRobin,
+            TaskDistribution::Locality => TaskDistributionPolicy::Locality,
+            TaskDistribution::LoadAware => TaskDistributionPolicy::LoadAware,
             TaskDistribution::ConsistentHash => {
                 let num_replicas = opt.consistent_hash_num_replicas as usize;
                 let tolerance = opt.consistent_hash_tolerance as usize;
@@ -320,6 +964,121 @@ impl TryFrom<Config> for SchedulerConfig {
             }
         };
 
+        let access_control = if opt.access_control_enabled {
+            Some(
+                AccessControl::try_new(
+                    opt.principal_roles.as_deref().unwrap_or_default(),
+                    opt.default_role,
+                )?
+                .try_with_group_roles(opt.group_roles.as_deref().unwrap_or_default())?,
+            )
+        } else {
+            None
+        };
+        let auth_provider = auth::auth_provider_from_config(&opt)?;
+        let webhooks = WebhookConfig {
+            urls: parse_list(opt.webhook_urls.as_deref()),
+            allowed_job_url_prefixes: parse_list(opt.webhook_job_url_prefixes.as_deref()),
+            secret: opt.webhook_secret.clone(),
+        };
+        webhooks.validate()?;
+        if cfg!(not(feature = "webhooks")) && webhooks.is_enabled() {
+            return Err(BallistaError::General(
+                "Webhooks require the scheduler to be built with the webhooks feature"
+                    .to_string(),
+            ));
+        }
+        let cluster_event_publisher: Option<Arc<dyn ClusterEventPublisher>> =
+            match &opt.cluster_events_nats_url {
+                #[cfg(feature = "nats")]
+                Some(url) => Some(Arc::new(NatsEventPublisher::try_new(
+                    url,
+                    &opt.cluster_events_nats_subject,
+                )?)),
+                #[cfg(not(feature = "nats"))]
+                Some(_) => {
+                    return Err(BallistaError::General(
+                        "Publishing the cluster events to NATS requires the scheduler to be built with the nats feature"
+                            .to_string(),
+                    ))
+                }
+                None => None,
+            };
+        let data_masking = opt
+            .masking_rules_file
+            .as_deref()
+            .map(DataMasking::try_from_file)
+            .transpose()?;
+        let query_authorizer: Arc<dyn QueryAuthorizer> = match &opt.query_rules_file {
+            Some(path) => Arc::new(QueryRules::try_from_file(path)?),
+            None => Arc::new(AllowAll),
+        };
+        let resource_profiles = opt
+            .resource_profiles_file
+            .as_deref()
+            .map(ResourceProfiles::try_from_file)
+            .transpose()?
+            .unwrap_or_default();
+        let executor_pools = opt
+            .executor_pools_file
+            .as_deref()
+            .map(ExecutorPools::try_from_file)
+            .transpose()?
+            .unwrap_or_default();
+        if !(0.0..=1.0).contains(&opt.shadow_execution_fraction) {
+            return Err(BallistaError::General(format!(
+                "Invalid shadow_execution_fraction {}, expected a value between 0 and 1",
+                opt.shadow_execution_fraction
+            )));
+        }
+        if opt.task_max_failures == 0 || opt.stage_max_failures == 0 {
+            return Err(BallistaError::General(
+                "Invalid task_max_failures or stage_max_failures, expected a value of at least 1"
+                    .to_string(),
+            ));
+        }
+        if opt.speculation_multiplier < 0.0 {
+            return Err(BallistaError::General(format!(
+                "Invalid speculation_multiplier {}, expected a non-negative value",
+                opt.speculation_multiplier
+            )));
+        }
+        let cluster_storage = match opt.cluster_backend {
+            ClusterStorage::Memory => ClusterStorageConfig::Memory,
+            #[cfg(feature = "etcd")]
+            ClusterStorage::Etcd => {
+                ClusterStorageConfig::Etcd(parse_list(Some(&opt.etcd_urls)))
+            }
+        };
+        if opt.leader_lease_ttl_seconds == 0 {
+            return Err(BallistaError::General(
+                "Invalid leader_lease_ttl_seconds, expected a value of at least 1"
+                    .to_string(),
+            ));
+        }
+        if opt.read_replica && matches!(cluster_storage, ClusterStorageConfig::Memory) {
+            return Err(BallistaError::General(
+                "A read replica requires a persistent cluster backend shared with the leader scheduler"
+                    .to_string(),
+            ));
+        }
+        let task_plan_compression = match opt.task_plan_compression.as_str() {
+            "" | "none" => None,
+            name => Some(CompressionCodecRegistry::default().codec(name)?),
+        };
+        let shadow_execution_settings =
+            parse_settings(opt.shadow_execution_settings.as_deref().unwrap_or_default())?;
+        let mut grpc_security = GrpcSecurityConfig::default()
+            .with_tls_files(
+                opt.grpc_tls_cert_file.as_deref(),
+                opt.grpc_tls_key_file.as_deref(),
+                opt.grpc_tls_ca_file.as_deref(),
+            )?
+            .with_client_auth(opt.grpc_tls_client_auth);
+        if let Some(auth_token) = &opt.grpc_auth_token {
+            grpc_security = grpc_security.with_auth_token(auth_token)?;
+        }
+
         let config = SchedulerConfig {
             namespace: opt.namespace,
             external_host: opt.external_host,
@@ -327,12 +1086,22 @@ impl TryFrom<Config> for SchedulerConfig {
             scheduling_policy: opt.scheduler_policy,
             event_loop_buffer_size: opt.event_loop_buffer_size,
             task_distribution,
+            executor_version_policy: opt.executor_version_policy,
+            job_scheduling_policy: opt.job_scheduling_policy.policy(),
+            task_plan_format: opt.task_plan_format,
+            plan_reference_threshold: opt.plan_reference_threshold as usize,
+            task_plan_compression,
+            task_max_failures: opt.task_max_failures as usize,
+            stage_max_failures: opt.stage_max_failures as usize,
+            speculation_multiplier: opt.speculation_multiplier,
             finished_job_data_clean_up_interval_seconds: opt
                 .finished_job_data_clean_up_interval_seconds,
             finished_job_state_clean_up_interval_seconds: opt
                 .finished_job_state_clean_up_interval_seconds,
             advertise_flight_sql_endpoint: opt.advertise_flight_sql_endpoint,
-            cluster_storage: ClusterStorageConfig::Memory,
+            cluster_storage,
+            leader_lease_ttl_seconds: opt.leader_lease_ttl_seconds,
+            read_replica: opt.read_replica,
             job_resubmit_interval_ms: (opt.job_resubmit_interval_ms > 0)
                 .then_some(opt.job_resubmit_interval_ms),
             executor_termination_grace_period: opt.executor_termination_grace_period,
@@ -342,15 +1111,89 @@ impl TryFrom<Config> for SchedulerConfig {
                 .grpc_server_max_decoding_message_size,
             grpc_server_max_encoding_message_size: opt
                 .grpc_server_max_encoding_message_size,
+            grpc_client_max_decoding_message_size: opt
+                .grpc_client_max_decoding_message_size,
+            grpc_client_max_encoding_message_size: opt
+                .grpc_client_max_encoding_message_size,
+            grpc_security,
+            executor_registration_tokens: opt
+                .executor_registration_tokens
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(str::to_owned)
+                .collect(),
             executor_timeout_seconds: opt.executor_timeout_seconds,
             expire_dead_executor_interval_seconds: opt
                 .expire_dead_executor_interval_seconds,
+            executor_resource_history_size: opt.executor_resource_history_size as usize,
+            cluster_metrics_interval_seconds: opt.cluster_metrics_interval_seconds,
+            cluster_metrics_retention_count: opt.cluster_metrics_retention_count as usize,
+            plan_cache_size: opt.plan_cache_size as usize,
+            query_cache_size: opt.query_cache_size as usize,
+            query_cache_result_ttl_seconds: opt.query_cache_result_ttl_seconds,
+            query_cache_max_result_bytes: opt.query_cache_max_result_bytes,
+            shadow_execution_fraction: opt.shadow_execution_fraction,
+            shadow_execution_settings,
+            max_result_rows: opt.max_result_rows as usize,
+            max_recursive_query_iterations: opt.max_recursive_query_iterations as usize,
+            validate_job_functions: opt.validate_job_functions,
+            finished_job_retention_count: opt.finished_job_retention_count as usize,
+            finished_job_retention_seconds: opt.finished_job_retention_seconds,
+            finished_job_compaction_interval_seconds: opt
+                .finished_job_compaction_interval_seconds,
+            job_archive_dir: opt.job_archive_dir,
+            event_log_dir: opt.event_log_dir,
+            cluster_catalog: opt.cluster_catalog,
+            cluster_catalog_dir: opt.cluster_catalog_dir,
+            access_control,
+            auth_provider,
+            data_masking,
+            query_authorizer,
+            resource_profiles,
+            executor_pools,
+            webhooks,
             override_config_producer: None,
             override_logical_codec: None,
             override_physical_codec: None,
             override_session_builder: None,
+            override_cluster_event_publisher: cluster_event_publisher,
+            override_job_archive_store: None,
+            override_event_log_store: None,
         };
 
         Ok(config)
     }
 }
+
+/// Parse session settings of the form `key=value[,key=value]*`
+fn parse_settings(settings: &str) -> Result<Vec<KeyValuePair>, BallistaError> {
+    settings
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|setting| {
+            let (key, value) = setting.split_once('=').ok_or_else(|| {
+                BallistaError::General(format!(
+                    "Invalid setting {setting}, expected key=value"
+                ))
+            })?;
+            Ok(KeyValuePair {
+                key: key.trim().to_owned(),
+                value: value.trim().to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Parse a comma-separated list, ignoring empty items
+fn parse_list(list: Option<&str>) -> Vec<String> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
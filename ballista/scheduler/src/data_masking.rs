@@ -0,0 +1,403 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Column level data masking of the queries submitted to the scheduler.
+//!
+//! Masking rules replace the values of sensitive columns by masking expressions, such
+//! as `'***'` or `concat('***-**-', right(ssn, 4))`, in the plans of the queries of the
+//! callers they apply to. Columns are masked right above the scans of their tables, so
+//! every operator of a query, including filters, joins and aggregations, only sees the
+//! masked values, without changing queries or data.
+
+use std::collections::HashMap;
+
+use ballista_core::error::{BallistaError, Result};
+use datafusion::common::tree_node::Transformed;
+use datafusion::common::{Column, DFSchema, TableReference};
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::utils::conjunction;
+use datafusion::logical_expr::{cast, Expr, LogicalPlan, LogicalPlanBuilder, TableScan};
+
+use crate::access_control::Role;
+
+/// Replaces the values of a column of a table by a masking expression, for the
+/// principals and roles it applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaskingRule {
+    table: TableReference,
+    column: String,
+    expression: String,
+    principals: Vec<String>,
+    roles: Vec<Role>,
+}
+
+impl MaskingRule {
+    /// Mask `column` of `table`, optionally qualified by its schema and catalog, with
+    /// the SQL `expression`, which can refer to any column of the table. The rule
+    /// applies to all callers unless restricted to principals or roles.
+    pub fn new(
+        table: impl Into<TableReference>,
+        column: impl Into<String>,
+        expression: impl Into<String>,
+    ) -> Self {
+        Self {
+            table: table.into(),
+            column: column.into(),
+            expression: expression.into(),
+            principals: vec![],
+            roles: vec![],
+        }
+    }
+
+    /// Only apply the rule to `principals`, and to the roles of [Self::with_roles]
+    pub fn with_principals(mut self, principals: Vec<String>) -> Self {
+        self.principals = principals;
+        self
+    }
+
+    /// Only apply the rule to the principals with one of `roles`, and to the principals
+    /// of [Self::with_principals]
+    pub fn with_roles(mut self, roles: Vec<Role>) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    /// Whether the rule applies to a caller, given as its principal and role. Rules
+    /// apply to anonymous callers, so that columns are masked when the caller is unknown
+    fn applies_to(&self, caller: Option<(&str, Role)>) -> bool {
+        let Some((principal, role)) = caller else {
+            return true;
+        };
+
+        (self.principals.is_empty() && self.roles.is_empty())
+            || self.principals.iter().any(|p| p == principal)
+            || self.roles.contains(&role)
+    }
+}
+
+/// A masking rule, as written in a masking rules file
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaskingRuleSpec {
+    table: String,
+    column: String,
+    expression: String,
+    #[serde(default)]
+    principals: Vec<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaskingRulesSpec {
+    #[serde(default)]
+    rule: Vec<MaskingRuleSpec>,
+}
+
+/// Masking rules of the queries submitted to the scheduler
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataMasking {
+    rules: Vec<MaskingRule>,
+}
+
+impl DataMasking {
+    pub fn new(rules: Vec<MaskingRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Parse masking rules from TOML, of the form
+    ///
+    /// ```toml
+    /// [[rule]]
+    /// table = "customers"
+    /// column = "ssn"
+    /// expression = "concat('***-**-', right(ssn, 4))"
+    /// # Optional, the rule applies to all callers if neither is set
+    /// principals = ["alice"]
+    /// roles = ["user"]
+    /// ```
+    pub fn try_from_toml(rules: &str) -> Result<Self> {
+        let spec: MaskingRulesSpec = toml::from_str(rules)
+            .map_err(|e| BallistaError::General(format!("Invalid masking rules: {e}")))?;
+
+        spec.rule
+            .into_iter()
+            .map(|rule| {
+                let roles = rule
+                    .roles
+                    .iter()
+                    .map(|role| {
+                        role.parse().map_err(|e| {
+                            BallistaError::General(format!(
+                                "Invalid role in masking rule of {}.{}: {e}",
+                                rule.table, rule.column
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<Role>>>()?;
+
+                Ok(
+                    MaskingRule::new(rule.table.as_str(), rule.column, rule.expression)
+                        .with_principals(rule.principals)
+                        .with_roles(roles),
+                )
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Self::new)
+    }
+
+    /// Read masking rules from a TOML file, see [Self::try_from_toml]
+    pub fn try_from_file(path: &str) -> Result<Self> {
+        let rules = std::fs::read_to_string(path).map_err(|e| {
+            BallistaError::General(format!("Failed to read masking rules {path}: {e}"))
+        })?;
+
+        Self::try_from_toml(&rules)
+    }
+
+    pub fn rules(&self) -> &[MaskingRule] {
+        &self.rules
+    }
+
+    /// Mask the columns read by `plan` according to the rules applying to a caller,
+    /// given as its principal and role, or None for anonymous callers
+    pub fn mask_plan(
+        &self,
+        state: &SessionState,
+        plan: LogicalPlan,
+        caller: Option<(&str, Role)>,
+    ) -> Result<LogicalPlan> {
+        let rules: Vec<&MaskingRule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(caller))
+            .collect();
+        if rules.is_empty() {
+            return Ok(plan);
+        }
+
+        let masked_plan = plan.transform_up_with_subqueries(|plan| match plan {
+            LogicalPlan::TableScan(scan) => {
+                let table_rules: Vec<&MaskingRule> = rules
+                    .iter()
+                    .copied()
+                    .filter(|rule| rule.table.resolved_eq(&scan.table_name))
+                    .collect();
+                if table_rules.is_empty() {
+                    Ok(Transformed::no(LogicalPlan::TableScan(scan)))
+                } else {
+                    mask_scan(state, scan, &table_rules).map(Transformed::yes)
+                }
+            }
+            plan => Ok(Transformed::no(plan)),
+        })?;
+
+        Ok(masked_plan.data)
+    }
+}
+
+/// Replace a table scan by a scan of the whole table, followed by the projection of the
+/// masked columns and the projection of the originally scanned columns. Filters and
+/// limits pushed down to the scan which refer to masked columns are applied to the
+/// masked values instead, so that they can't be used to probe the original values.
+fn mask_scan(
+    state: &SessionState,
+    scan: TableScan,
+    rules: &[&MaskingRule],
+) -> datafusion::error::Result<LogicalPlan> {
+    let TableScan {
+        table_name,
+        source,
+        projection,
+        filters,
+        fetch,
+        ..
+    } = scan;
+
+    let source_schema = source.schema();
+    let table_schema =
+        DFSchema::try_from_qualified_schema(table_name.clone(), &source_schema)?;
+    let mut masks = HashMap::new();
+    for rule in rules {
+        let Ok(field) = source_schema.field_with_name(&rule.column) else {
+            continue;
+        };
+        let data_type = field.data_type().clone();
+        let mask = state.create_logical_expr(&rule.expression, &table_schema)?;
+        // The first rule of a column wins
+        masks
+            .entry(rule.column.as_str())
+            .or_insert_with(|| cast(mask, data_type));
+    }
+
+    let (masked_filters, filters): (Vec<Expr>, Vec<Expr>) =
+        filters.into_iter().partition(|filter| {
+            filter
+                .column_refs()
+                .iter()
+                .any(|column| masks.contains_key(column.name.as_str()))
+        });
+
+    let masked_columns = table_schema.columns().into_iter().map(|column| {
+        match masks.get(column.name.as_str()) {
+            Some(mask) => mask
+                .clone()
+                .alias_qualified(column.relation.clone(), &column.name),
+            None => Expr::Column(column),
+        }
+    });
+    let scan = TableScan::try_new(table_name, source, None, filters, None)?;
+    let mut builder =
+        LogicalPlanBuilder::from(LogicalPlan::TableScan(scan)).project(masked_columns)?;
+    if let Some(predicate) = conjunction(masked_filters) {
+        builder = builder.filter(predicate)?;
+    }
+    if let Some(fetch) = fetch {
+        builder = builder.limit(0, Some(fetch))?;
+    }
+    if let Some(projection) = projection {
+        builder = builder.project(
+            projection
+                .into_iter()
+                .map(|i| Expr::Column(Column::from(table_schema.qualified_field(i)))),
+        )?;
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use datafusion::prelude::SessionContext;
+
+    use crate::access_control::Role;
+    use crate::data_masking::{DataMasking, MaskingRule};
+
+    fn context() -> SessionContext {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("ssn", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["alice", "bob"])),
+                Arc::new(StringArray::from(vec!["123-45-6789", "987-65-4321"])),
+            ],
+        )
+        .unwrap();
+
+        let ctx = SessionContext::new();
+        ctx.register_batch("customers", batch).unwrap();
+        ctx
+    }
+
+    async fn query(
+        masking: &DataMasking,
+        sql: &str,
+        caller: Option<(&str, Role)>,
+    ) -> String {
+        let ctx = context();
+        let plan = ctx.sql(sql).await.unwrap().into_optimized_plan().unwrap();
+        let plan = masking.mask_plan(&ctx.state(), plan, caller).unwrap();
+        let batches = ctx
+            .execute_logical_plan(plan)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        pretty_format_batches(&batches.unwrap())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_parse_masking_rules() {
+        let masking = DataMasking::try_from_toml(
+            r#"
+            [[rule]]
+            table = "public.customers"
+            column = "ssn"
+            expression = "'***'"
+            roles = ["User", "operator"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            masking.rules(),
+            &[MaskingRule::new("public.customers", "ssn", "'***'")
+                .with_roles(vec![Role::User, Role::Operator])]
+        );
+
+        assert!(DataMasking::try_from_toml(
+            "[[rule]]\ntable = \"t\"\ncolumn = \"c\"\nexpression = \"1\"\nroles = [\"root\"]"
+        )
+        .is_err());
+        assert!(DataMasking::try_from_toml("[[rule]]\ntable = \"t\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mask_columns() {
+        let masking = DataMasking::new(vec![MaskingRule::new(
+            "customers",
+            "ssn",
+            "concat('***-**-', right(ssn, 4))",
+        )
+        .with_roles(vec![Role::User])]);
+
+        let masked = query(
+            &masking,
+            "SELECT name, ssn FROM customers ORDER BY id",
+            Some(("carol", Role::User)),
+        )
+        .await;
+        assert!(masked.contains("***-**-6789"), "{masked}");
+        assert!(!masked.contains("123-45-6789"), "{masked}");
+
+        // Filters see the masked values, so they can't probe the original ones
+        let probed = query(
+            &masking,
+            "SELECT name FROM customers WHERE ssn = '123-45-6789'",
+            Some(("carol", Role::User)),
+        )
+        .await;
+        assert!(!probed.contains("alice"), "{probed}");
+
+        let unmasked = query(
+            &masking,
+            "SELECT ssn FROM customers ORDER BY id",
+            Some(("alice", Role::Admin)),
+        )
+        .await;
+        assert!(unmasked.contains("123-45-6789"), "{unmasked}");
+
+        // Columns are masked for anonymous callers
+        let anonymous =
+            query(&masking, "SELECT ssn FROM customers ORDER BY id", None).await;
+        assert!(!anonymous.contains("123-45-6789"), "{anonymous}");
+    }
+}
@@ -0,0 +1,170 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Rewrites making the results of a query deterministic, for sessions with
+//! `ballista.job.deterministic` set.
+//!
+//! Rows which compare equal on the keys of a sort can come out in any order, and the
+//! rows of a query without `ORDER BY` come out in the order their tasks complete. Sorts
+//! therefore break ties on all the columns of their input, limits without an order only
+//! keep rows after sorting them on all columns, and queries without an order are sorted
+//! on all their output columns. Together with the ordered reads of shuffle partitions,
+//! two runs of the same query over the same data produce byte-identical results.
+
+use std::sync::Arc;
+
+use ballista_core::error::Result;
+use datafusion::arrow::row::{RowConverter, SortField};
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{Column, DFSchema};
+use datafusion::logical_expr::{Expr, Limit, LogicalPlan, Sort, SortExpr};
+
+/// Rewrite a plan so that its results are the same, in the same order, on every run
+pub fn deterministic_plan(plan: LogicalPlan) -> Result<LogicalPlan> {
+    let plan = plan
+        .transform_up_with_subqueries(|plan| match plan {
+            LogicalPlan::Sort(sort) => {
+                let tie_breakers = sort_exprs(sort.input.schema(), &sort.expr);
+                if tie_breakers.is_empty() {
+                    Ok(Transformed::no(LogicalPlan::Sort(sort)))
+                } else {
+                    let mut expr = sort.expr;
+                    expr.extend(tie_breakers);
+                    Ok(Transformed::yes(LogicalPlan::Sort(Sort { expr, ..sort })))
+                }
+            }
+            LogicalPlan::Limit(limit) if !is_ordered(&limit.input) => {
+                let input = Arc::new(sorted(limit.input.as_ref().clone()));
+                Ok(Transformed::yes(LogicalPlan::Limit(Limit {
+                    input,
+                    ..limit
+                })))
+            }
+            plan => Ok(Transformed::no(plan)),
+        })?
+        .data;
+
+    Ok(match plan {
+        // Statements without results are left as they are, but the rows written by DML
+        // statements are sorted, so that they are written in the same order
+        LogicalPlan::Dml(_) | LogicalPlan::Copy(_) => {
+            plan.map_children(|input| Ok(Transformed::yes(sorted(input))))?
+                .data
+        }
+        LogicalPlan::Ddl(_)
+        | LogicalPlan::Statement(_)
+        | LogicalPlan::Explain(_)
+        | LogicalPlan::Analyze(_)
+        | LogicalPlan::DescribeTable(_) => plan,
+        plan => sorted(plan),
+    })
+}
+
+/// Whether the rows of a plan come out in a defined order
+fn is_ordered(plan: &LogicalPlan) -> bool {
+    match plan {
+        LogicalPlan::Sort(_) => true,
+        LogicalPlan::Projection(projection) => is_ordered(&projection.input),
+        LogicalPlan::Filter(filter) => is_ordered(&filter.input),
+        LogicalPlan::Limit(limit) => is_ordered(&limit.input),
+        LogicalPlan::SubqueryAlias(alias) => is_ordered(&alias.input),
+        _ => false,
+    }
+}
+
+/// Sort the rows of a plan on all its columns, unless they already come out in a
+/// defined order
+fn sorted(plan: LogicalPlan) -> LogicalPlan {
+    if is_ordered(&plan) {
+        return plan;
+    }
+    let expr = sort_exprs(plan.schema(), &[]);
+    if expr.is_empty() {
+        return plan;
+    }
+
+    LogicalPlan::Sort(Sort {
+        expr,
+        input: Arc::new(plan),
+        fetch: None,
+    })
+}
+
+/// Ascending sort expressions on the sortable columns of a schema which are not
+/// already sorted on
+fn sort_exprs(schema: &DFSchema, sorted_on: &[SortExpr]) -> Vec<SortExpr> {
+    schema
+        .iter()
+        .filter(|(_, field)| {
+            RowConverter::supports_fields(&[SortField::new(field.data_type().clone())])
+        })
+        .map(|(qualifier, field)| {
+            Expr::Column(Column::new(qualifier.cloned(), field.name()))
+        })
+        .filter(|column| !sorted_on.iter().any(|sort| &sort.expr == column))
+        .map(|column| SortExpr::new(column, true, false))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::logical_expr::LogicalPlan;
+    use datafusion::prelude::SessionContext;
+
+    use crate::deterministic::deterministic_plan;
+
+    async fn deterministic(sql: &str) -> LogicalPlan {
+        let ctx = SessionContext::new();
+        ctx.sql("CREATE TABLE t (a INT, b VARCHAR)").await.unwrap();
+        let plan = ctx.sql(sql).await.unwrap().into_unoptimized_plan();
+        deterministic_plan(plan).unwrap()
+    }
+
+    fn sort_keys(plan: &LogicalPlan) -> Vec<String> {
+        match plan {
+            LogicalPlan::Sort(sort) => {
+                sort.expr.iter().map(|sort| sort.expr.to_string()).collect()
+            }
+            plan => plan
+                .inputs()
+                .first()
+                .map(|input| sort_keys(input))
+                .unwrap_or_default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_break_sort_ties() {
+        let plan = deterministic("SELECT a, b FROM t ORDER BY a LIMIT 1").await;
+        assert_eq!(sort_keys(&plan), vec!["t.a", "t.b"]);
+        assert!(matches!(plan, LogicalPlan::Limit(_)));
+    }
+
+    #[tokio::test]
+    async fn test_sort_unordered_results() {
+        let plan = deterministic("SELECT b, count(*) AS n FROM t GROUP BY b").await;
+        assert!(matches!(plan, LogicalPlan::Sort(_)));
+        assert_eq!(sort_keys(&plan), vec!["t.b", "n"]);
+
+        // Rows are sorted before the limit keeps some of them
+        let plan = deterministic("SELECT a FROM t LIMIT 1").await;
+        let LogicalPlan::Limit(limit) = &plan else {
+            panic!("Expected a limit, got {plan}");
+        };
+        assert!(matches!(limit.input.as_ref(), LogicalPlan::Sort(_)));
+    }
+}
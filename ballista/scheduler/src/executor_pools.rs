@@ -0,0 +1,258 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Dedicated pools of executors of the tenants of the cluster.
+//!
+//! Executors join a named pool when they register, with their `executor_pool` setting,
+//! and the tasks of the jobs of a tenant, the principal which submitted them, only run
+//! on the executors of the pools listing the tenant. The executors without a pool form
+//! the shared pool, which runs the jobs of the tenants without a pool of their own and
+//! those submitted anonymously.
+//!
+//! A pool may lend its executors while it's idle, i.e. while no job of its tenants has
+//! tasks waiting for a slot, in which case the jobs of the other tenants borrow its free
+//! slots. The tasks they borrow slots for run to completion, the jobs of the tenants of
+//! the pool waiting for them to finish once they are submitted.
+
+use ballista_core::error::{BallistaError, Result};
+
+/// Name of the pool of the executors which join none
+pub const SHARED_POOL: &str = "";
+
+/// A pool of executors and the tenants whose jobs run on them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutorPool {
+    name: String,
+    tenants: Vec<String>,
+    lend_when_idle: bool,
+}
+
+impl ExecutorPool {
+    /// A pool named `name`, without tenants
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            tenants: vec![],
+            lend_when_idle: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A principal whose jobs run on the executors of the pool
+    pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenants.push(tenant.into());
+        self
+    }
+
+    pub fn tenants(&self) -> &[String] {
+        &self.tenants
+    }
+
+    /// Whether the jobs of the other tenants borrow the free slots of the pool while
+    /// no job of its tenants waits for a slot
+    pub fn with_lend_when_idle(mut self, lend_when_idle: bool) -> Self {
+        self.lend_when_idle = lend_when_idle;
+        self
+    }
+
+    pub fn lends_when_idle(&self) -> bool {
+        self.lend_when_idle
+    }
+}
+
+/// A pool, as written in an executor pools file
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExecutorPoolSpec {
+    name: String,
+    #[serde(default)]
+    tenants: Vec<String>,
+    #[serde(default)]
+    lend_when_idle: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExecutorPoolsSpec {
+    #[serde(default)]
+    pool: Vec<ExecutorPoolSpec>,
+}
+
+/// The executor pools of the scheduler, none by default, in which case all the
+/// executors are shared by all the jobs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutorPools {
+    pools: Vec<ExecutorPool>,
+}
+
+impl ExecutorPools {
+    pub fn try_new(pools: Vec<ExecutorPool>) -> Result<Self> {
+        for (i, pool) in pools.iter().enumerate() {
+            if pool.name == SHARED_POOL {
+                return Err(BallistaError::General(
+                    "Executor pools must have a name".to_owned(),
+                ));
+            }
+            if pools[..i].iter().any(|p| p.name == pool.name) {
+                return Err(BallistaError::General(format!(
+                    "Duplicate executor pool {}",
+                    pool.name
+                )));
+            }
+        }
+        Ok(Self { pools })
+    }
+
+    /// Parse executor pools from TOML, of the form
+    ///
+    /// ```toml
+    /// [[pool]]
+    /// name = "analytics"
+    /// # Principals whose jobs run on the executors of the pool
+    /// tenants = ["alice", "bob"]
+    /// # Whether the jobs of the other tenants borrow the pool while it's idle
+    /// lend_when_idle = true
+    /// ```
+    pub fn try_from_toml(pools: &str) -> Result<Self> {
+        let spec: ExecutorPoolsSpec = toml::from_str(pools).map_err(|e| {
+            BallistaError::General(format!("Invalid executor pools: {e}"))
+        })?;
+
+        let pools = spec
+            .pool
+            .into_iter()
+            .map(|spec| ExecutorPool {
+                name: spec.name,
+                tenants: spec.tenants,
+                lend_when_idle: spec.lend_when_idle,
+            })
+            .collect();
+        Self::try_new(pools)
+    }
+
+    /// Read executor pools from a TOML file, see [Self::try_from_toml]
+    pub fn try_from_file(path: &str) -> Result<Self> {
+        let pools = std::fs::read_to_string(path).map_err(|e| {
+            BallistaError::General(format!("Failed to read executor pools {path}: {e}"))
+        })?;
+
+        Self::try_from_toml(&pools)
+    }
+
+    pub fn pools(&self) -> &[ExecutorPool] {
+        &self.pools
+    }
+
+    pub fn pool(&self, name: &str) -> Option<&ExecutorPool> {
+        self.pools.iter().find(|pool| pool.name == name)
+    }
+
+    /// Whether executors may join the pool `name`, the shared pool or a configured one
+    pub fn is_known(&self, name: &str) -> bool {
+        name == SHARED_POOL || self.pool(name).is_some()
+    }
+
+    /// Whether the jobs of `tenant`, none for the jobs submitted anonymously, run on
+    /// the executors of the pool `name`
+    pub fn admits(&self, name: &str, tenant: Option<&str>) -> bool {
+        let of_tenant = |pool: &ExecutorPool| {
+            tenant.is_some_and(|t| pool.tenants.iter().any(|p| p == t))
+        };
+        match self.pool(name) {
+            Some(pool) => of_tenant(pool),
+            // the jobs of the tenants without a pool of their own
+            None => name == SHARED_POOL && !self.pools.iter().any(of_tenant),
+        }
+    }
+
+    /// Whether the pool `name` lends its free slots while it's idle
+    pub fn lends_when_idle(&self, name: &str) -> bool {
+        self.pool(name).is_some_and(|pool| pool.lend_when_idle)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor_pools::{ExecutorPool, ExecutorPools, SHARED_POOL};
+
+    #[test]
+    fn test_parse_executor_pools() {
+        let pools = ExecutorPools::try_from_toml(
+            r#"
+            [[pool]]
+            name = "analytics"
+            tenants = ["alice", "bob"]
+            lend_when_idle = true
+
+            [[pool]]
+            name = "etl"
+            tenants = ["carol"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            pools.pools(),
+            &[
+                ExecutorPool::new("analytics")
+                    .with_tenant("alice")
+                    .with_tenant("bob")
+                    .with_lend_when_idle(true),
+                ExecutorPool::new("etl").with_tenant("carol"),
+            ]
+        );
+
+        assert!(ExecutorPools::try_from_toml("[[pool]]\nsize = 1").is_err());
+        assert!(ExecutorPools::try_from_toml("[[pool]]\nname = \"\"").is_err());
+        assert!(ExecutorPools::try_from_toml(
+            "[[pool]]\nname = \"a\"\n[[pool]]\nname = \"a\""
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_admits() {
+        let pools = ExecutorPools::try_new(vec![
+            ExecutorPool::new("analytics").with_tenant("alice"),
+            ExecutorPool::new("etl")
+                .with_tenant("alice")
+                .with_tenant("carol")
+                .with_lend_when_idle(true),
+        ])
+        .unwrap();
+
+        assert!(pools.admits("analytics", Some("alice")));
+        assert!(pools.admits("etl", Some("alice")));
+        assert!(!pools.admits(SHARED_POOL, Some("alice")));
+        assert!(!pools.admits("analytics", Some("carol")));
+        // tenants without a pool and anonymous jobs share the executors without one
+        assert!(pools.admits(SHARED_POOL, Some("dave")));
+        assert!(pools.admits(SHARED_POOL, None));
+        assert!(!pools.admits("etl", None));
+        assert!(!pools.admits("unknown", Some("alice")));
+
+        assert!(pools.is_known("etl") && pools.is_known(SHARED_POOL));
+        assert!(!pools.is_known("unknown"));
+        assert!(pools.lends_when_idle("etl"));
+        assert!(!pools.lends_when_idle("analytics"));
+
+        // without pools, all the jobs share all the executors
+        assert!(ExecutorPools::default().admits(SHARED_POOL, Some("alice")));
+    }
+}
@@ -15,8 +15,11 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::error::FlightError;
 use arrow_flight::flight_descriptor::DescriptorType;
 use arrow_flight::flight_service_server::FlightService;
+use arrow_flight::sql::metadata::{SqlInfoData, SqlInfoDataBuilder};
 use arrow_flight::sql::server::{FlightSqlService, PeekableFlightDataStream};
 use arrow_flight::sql::{
     ActionBeginSavepointRequest, ActionBeginSavepointResult,
@@ -30,15 +33,15 @@ use arrow_flight::sql::{
     CommandGetTableTypes, CommandGetTables, CommandGetXdbcTypeInfo,
     CommandPreparedStatementQuery, CommandPreparedStatementUpdate, CommandStatementQuery,
     CommandStatementSubstraitPlan, CommandStatementUpdate, DoPutPreparedStatementResult,
-    SqlInfo, TicketStatementQuery,
+    SqlInfo, SqlSupportedTransaction, SubstraitPlan, TicketStatementQuery,
 };
 use arrow_flight::{
-    Action, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest,
+    Action, ActionType, CancelFlightInfoRequest, CancelFlightInfoResult, CancelStatus,
+    FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest,
     HandshakeResponse, Ticket,
 };
-use base64::Engine;
-use futures::Stream;
-use log::{debug, error, warn};
+use futures::{Stream, TryStreamExt};
+use log::{debug, error, info, warn};
 use std::convert::TryFrom;
 use std::pin::Pin;
 use std::str::FromStr;
@@ -47,28 +50,35 @@ use std::sync::Arc;
 use std::time::Duration;
 use tonic::{Request, Response, Status, Streaming};
 
+use crate::access_control::{Operation, PRINCIPAL_METADATA_KEY};
+use crate::auth::{Credentials, Identity, RequestAuth, AUTHORIZATION_METADATA_KEY};
+use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::SchedulerServer;
+use crate::substrait::substrait_to_logical_plan;
 use arrow_flight::flight_service_client::FlightServiceClient;
 use arrow_flight::sql::ProstMessageExt;
 use arrow_flight::utils::batches_to_flight_data;
 use arrow_flight::SchemaAsIpc;
+use ballista_core::event_loop::EventSender;
+use ballista_core::security::FetchToken;
 use ballista_core::serde::protobuf;
 use ballista_core::serde::protobuf::action::ActionType::FetchPartition;
 use ballista_core::serde::protobuf::job_status;
 use ballista_core::serde::protobuf::JobStatus;
 use ballista_core::serde::protobuf::SuccessfulJob;
 use ballista_core::utils::create_grpc_client_connection;
+use ballista_core::verify_table::verify_table_statement;
+use ballista_core::BALLISTA_VERSION;
 use dashmap::DashMap;
 use datafusion::arrow;
-use datafusion::arrow::array::{ArrayRef, StringArray};
+use datafusion::arrow::array::{Int32Builder, StringBuilder};
 use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
-use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::ipc::writer::{
     DictionaryTracker, IpcDataGenerator, IpcWriteOptions,
 };
 use datafusion::arrow::record_batch::RecordBatch;
-use datafusion::common::DFSchemaRef;
-use datafusion::logical_expr::LogicalPlan;
+use datafusion::common::{Constraint, DFSchemaRef, ScalarValue};
+use datafusion::logical_expr::{LogicalPlan, TableType};
 use datafusion::prelude::SessionContext;
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
 use prost::bytes::Bytes;
@@ -76,16 +86,206 @@ use prost::Message;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::time::sleep;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::metadata::MetadataValue;
+use tonic::metadata::{MetadataMap, MetadataValue};
 use uuid::Uuid;
 
 pub struct FlightSqlServiceImpl {
     server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode>,
-    statements: Arc<DashMap<Uuid, LogicalPlan>>,
-    contexts: Arc<DashMap<Uuid, Arc<SessionContext>>>,
+    statements: Arc<DashMap<Uuid, PreparedStatement>>,
+    contexts: Arc<DashMap<Uuid, FlightSqlSession>>,
+    sql_info: SqlInfoData,
 }
 
-const TABLE_TYPES: [&str; 2] = ["TABLE", "VIEW"];
+/// Session opened by the handshake of a Flight SQL client, found by its bearer token
+#[derive(Clone)]
+struct FlightSqlSession {
+    ctx: Arc<SessionContext>,
+    // Identity the client authenticated as in its handshake, none if anonymous
+    identity: Option<Identity>,
+}
+
+const TABLE_TYPES: [&str; 3] = ["TABLE", "VIEW", "LOCAL TEMPORARY"];
+
+/// Type of the standard Flight action cancelling the query of a flight info
+const CANCEL_FLIGHT_INFO: &str = "CancelFlightInfo";
+
+/// Type of the action returning the Ballista [JobStatus] of the query of a flight info.
+/// Flight SQL services can't serve `PollFlightInfo`
+const GET_QUERY_INFO: &str = "GetQueryInfo";
+
+/// First element of the path of the descriptors of the flight infos of jobs, followed
+/// by the job ID and the token of the job, so that jobs are found even when their
+/// results are empty
+const JOB_DESCRIPTOR_PATH: &str = "job";
+
+/// Cancels a job when dropped before being disarmed, i.e. when the Flight SQL request
+/// waiting for the job is dropped because the client cancelled it
+struct CancelJobOnDrop {
+    job_id: String,
+    sender: Option<EventSender<QueryStageSchedulerEvent>>,
+}
+
+impl CancelJobOnDrop {
+    fn disarm(&mut self) {
+        self.sender = None;
+    }
+}
+
+impl Drop for CancelJobOnDrop {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender.take() {
+            let job_id = std::mem::take(&mut self.job_id);
+            info!("Cancelling job {job_id} as its Flight SQL request was dropped");
+            tokio::spawn(async move {
+                if let Err(e) = sender
+                    .post_event(QueryStageSchedulerEvent::JobCancel(job_id))
+                    .await
+                {
+                    warn!("Failed to cancel job of dropped Flight SQL request: {e:?}");
+                }
+            });
+        }
+    }
+}
+
+/// ID of the job whose results a flight info describes, from the path of its
+/// descriptor or from the tickets of its endpoints
+fn flight_info_job_id(info: &FlightInfo) -> Option<String> {
+    if let Some(descriptor) = &info.flight_descriptor {
+        if let [prefix, job_id, ..] = descriptor.path.as_slice() {
+            if prefix == JOB_DESCRIPTOR_PATH {
+                return Some(job_id.clone());
+            }
+        }
+    }
+    flight_info_fetches(info).next().map(|fetch| fetch.job_id)
+}
+
+/// Tokens of the job `job_id` presented by a flight info, in the path of its
+/// descriptor and in the tickets of its endpoints
+fn flight_info_fetch_tokens(info: &FlightInfo, job_id: &str) -> Vec<String> {
+    let descriptor_token = info.flight_descriptor.as_ref().and_then(|descriptor| {
+        match descriptor.path.as_slice() {
+            [prefix, id, token] if prefix == JOB_DESCRIPTOR_PATH && id == job_id => {
+                Some(token.clone())
+            }
+            _ => None,
+        }
+    });
+    descriptor_token
+        .into_iter()
+        .chain(
+            flight_info_fetches(info)
+                .filter(|fetch| fetch.job_id == job_id)
+                .map(|fetch| fetch.fetch_token),
+        )
+        .collect()
+}
+
+/// Partition fetches in the tickets of the endpoints of a flight info
+fn flight_info_fetches(
+    info: &FlightInfo,
+) -> impl Iterator<Item = protobuf::FetchPartition> + '_ {
+    info.endpoint.iter().filter_map(|endpoint| {
+        let ticket = endpoint.ticket.as_ref()?;
+        let message = arrow_flight::sql::Any::decode(ticket.ticket.clone()).ok()?;
+        let action: protobuf::Action = message.unpack().ok()??;
+        match action.action_type {
+            Some(FetchPartition(fetch)) => Some(fetch),
+            _ => None,
+        }
+    })
+}
+
+/// Plan of a prepared statement, cached on the scheduler until the statement is
+/// closed, with the values last bound to its parameters
+#[derive(Debug, Clone)]
+struct PreparedStatement {
+    plan: LogicalPlan,
+    parameters: Vec<ScalarValue>,
+}
+
+impl PreparedStatement {
+    fn new(plan: LogicalPlan) -> Self {
+        Self {
+            plan,
+            parameters: vec![],
+        }
+    }
+
+    /// Schema of the parameters of the statement, i.e. one field per placeholder
+    /// in the order of their positions, `$1`, `$2` etc. Parameters of unknown type
+    /// are of type `Null`
+    fn parameter_schema(&self) -> Result<Schema, Status> {
+        let mut parameters: Vec<(String, Option<DataType>)> = self
+            .plan
+            .get_parameter_types()
+            .map_err(|e| Status::internal(format!("Error getting parameters: {e}")))?
+            .into_iter()
+            .collect();
+        parameters.sort_by_key(|(id, _)| {
+            id.trim_start_matches('$')
+                .parse::<usize>()
+                .unwrap_or(usize::MAX)
+        });
+        let fields: Vec<Field> = parameters
+            .into_iter()
+            .map(|(id, data_type)| {
+                Field::new(id, data_type.unwrap_or(DataType::Null), true)
+            })
+            .collect();
+        Ok(Schema::new(fields))
+    }
+
+    /// Bind the values of the parameters to the first row of `parameters`, whose
+    /// columns are cast to the types of the parameters where they are known
+    fn bind(&mut self, parameters: &RecordBatch) -> Result<(), Status> {
+        if parameters.num_rows() != 1 {
+            Err(Status::invalid_argument(format!(
+                "Expected one row of parameters but got {}",
+                parameters.num_rows()
+            )))?
+        }
+        let parameter_schema = self.parameter_schema()?;
+        if parameters.num_columns() != parameter_schema.fields().len() {
+            Err(Status::invalid_argument(format!(
+                "Expected {} parameters but got {}",
+                parameter_schema.fields().len(),
+                parameters.num_columns()
+            )))?
+        }
+        let values = parameters
+            .columns()
+            .iter()
+            .zip(parameter_schema.fields())
+            .map(|(column, field)| {
+                let value = ScalarValue::try_from_array(column, 0)?;
+                match field.data_type() {
+                    DataType::Null => Ok(value),
+                    data_type => value.cast_to(data_type),
+                }
+            })
+            .collect::<datafusion::common::Result<Vec<_>>>()
+            .map_err(|e| Status::invalid_argument(format!("Invalid parameter: {e}")))?;
+        self.plan
+            .clone()
+            .with_param_values(values.clone())
+            .map_err(|e| Status::invalid_argument(format!("Invalid parameter: {e}")))?;
+        self.parameters = values;
+        Ok(())
+    }
+
+    /// Plan of the statement, with the bound values of its parameters
+    fn bound_plan(&self) -> Result<LogicalPlan, Status> {
+        if self.parameters.is_empty() {
+            return Ok(self.plan.clone());
+        }
+        self.plan
+            .clone()
+            .with_param_values(self.parameters.clone())
+            .map_err(|e| Status::internal(format!("Error binding parameters: {e}")))
+    }
+}
 
 impl FlightSqlServiceImpl {
     pub fn new(server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode>) -> Self {
@@ -93,59 +293,228 @@ impl FlightSqlServiceImpl {
             server,
             statements: Default::default(),
             contexts: Default::default(),
+            sql_info: Self::sql_info_data().expect("SQL info should be valid"),
         }
     }
 
-    fn tables(&self, ctx: Arc<SessionContext>) -> Result<RecordBatch, ArrowError> {
-        let schema = Arc::new(Schema::new(vec![
+    /// Catalogs of the session, for `CommandGetCatalogs`
+    fn catalogs(
+        ctx: &SessionContext,
+        query: CommandGetCatalogs,
+    ) -> Result<RecordBatch, FlightError> {
+        let mut builder = query.into_builder();
+        for catalog_name in ctx.catalog_names() {
+            builder.append(catalog_name);
+        }
+        builder.build()
+    }
+
+    /// Schemas of the catalogs of the session, for `CommandGetDbSchemas`
+    fn schemas(
+        ctx: &SessionContext,
+        query: CommandGetDbSchemas,
+    ) -> Result<RecordBatch, FlightError> {
+        let mut builder = query.into_builder();
+        for catalog_name in ctx.catalog_names() {
+            let catalog = ctx
+                .catalog(&catalog_name)
+                .expect("catalog should have been found");
+            for schema_name in catalog.schema_names() {
+                builder.append(&catalog_name, schema_name);
+            }
+        }
+        builder.build()
+    }
+
+    /// Tables of the session, for `CommandGetTables`
+    async fn tables(
+        ctx: &SessionContext,
+        query: CommandGetTables,
+    ) -> Result<RecordBatch, FlightError> {
+        let mut builder = query.into_builder();
+        for catalog_name in ctx.catalog_names() {
+            let catalog = ctx
+                .catalog(&catalog_name)
+                .expect("catalog should have been found");
+            for schema_name in catalog.schema_names() {
+                let schema = catalog
+                    .schema(&schema_name)
+                    .expect("schema should have been found");
+                for table_name in schema.table_names() {
+                    let Some(table) = schema
+                        .table(&table_name)
+                        .await
+                        .map_err(|e| FlightError::ExternalError(Box::new(e)))?
+                    else {
+                        continue;
+                    };
+                    let table_type = match table.table_type() {
+                        TableType::Base => TABLE_TYPES[0],
+                        TableType::View => TABLE_TYPES[1],
+                        TableType::Temporary => TABLE_TYPES[2],
+                    };
+                    builder.append(
+                        &catalog_name,
+                        &schema_name,
+                        table_name,
+                        table_type,
+                        &table.schema(),
+                    )?;
+                }
+            }
+        }
+        builder.build()
+    }
+
+    /// Schema of the results of `CommandGetPrimaryKeys`, as defined by Flight SQL
+    fn primary_keys_schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
             Field::new("catalog_name", DataType::Utf8, true),
             Field::new("db_schema_name", DataType::Utf8, true),
             Field::new("table_name", DataType::Utf8, false),
-            Field::new("table_type", DataType::Utf8, false),
-        ]));
-        let mut names: Vec<Option<String>> = vec![];
+            Field::new("column_name", DataType::Utf8, false),
+            Field::new("key_name", DataType::Utf8, true),
+            Field::new("key_sequence", DataType::Int32, false),
+        ]))
+    }
+
+    /// Primary key columns of the tables of the session, for `CommandGetPrimaryKeys`.
+    /// The catalog and schema of the table narrow the search only if they are set.
+    async fn primary_keys(
+        ctx: &SessionContext,
+        query: CommandGetPrimaryKeys,
+    ) -> Result<RecordBatch, FlightError> {
+        let mut catalog_names = StringBuilder::new();
+        let mut schema_names = StringBuilder::new();
+        let mut table_names = StringBuilder::new();
+        let mut column_names = StringBuilder::new();
+        let mut key_names = StringBuilder::new();
+        let mut key_sequences = Int32Builder::new();
         for catalog_name in ctx.catalog_names() {
+            if query.catalog.as_ref().is_some_and(|c| *c != catalog_name) {
+                continue;
+            }
             let catalog = ctx
                 .catalog(&catalog_name)
                 .expect("catalog should have been found");
             for schema_name in catalog.schema_names() {
+                if query.db_schema.as_ref().is_some_and(|s| *s != schema_name) {
+                    continue;
+                }
                 let schema = catalog
                     .schema(&schema_name)
                     .expect("schema should have been found");
-                for table_name in schema.table_names() {
-                    names.push(Some(table_name));
+                let Some(table) = schema
+                    .table(&query.table)
+                    .await
+                    .map_err(|e| FlightError::ExternalError(Box::new(e)))?
+                else {
+                    continue;
+                };
+                let primary_key = table.constraints().and_then(|constraints| {
+                    constraints.iter().find_map(|constraint| match constraint {
+                        Constraint::PrimaryKey(indices) => Some(indices.clone()),
+                        Constraint::Unique(_) => None,
+                    })
+                });
+                let table_schema = table.schema();
+                for (sequence, index) in primary_key.into_iter().flatten().enumerate() {
+                    catalog_names.append_value(&catalog_name);
+                    schema_names.append_value(&schema_name);
+                    table_names.append_value(&query.table);
+                    column_names.append_value(table_schema.field(index).name());
+                    key_names.append_null();
+                    key_sequences.append_value(sequence as i32 + 1);
                 }
             }
         }
-        let types: Vec<_> = names.iter().map(|_| Some("TABLE".to_string())).collect();
-        let cats: Vec<_> = names.iter().map(|_| None).collect();
-        let schemas: Vec<_> = names.iter().map(|_| None).collect();
-        let rb = RecordBatch::try_new(
-            schema,
-            [cats, schemas, names, types]
-                .iter()
-                .map(|i| Arc::new(StringArray::from(i.clone())) as ArrayRef)
-                .collect::<Vec<_>>(),
-        )?;
-        Ok(rb)
-    }
-
-    fn table_types() -> Result<RecordBatch, ArrowError> {
-        let schema = Arc::new(Schema::new(vec![Field::new(
-            "table_type",
-            DataType::Utf8,
-            false,
-        )]));
         RecordBatch::try_new(
-            schema,
-            [TABLE_TYPES]
-                .iter()
-                .map(|i| Arc::new(StringArray::from(i.to_vec())) as ArrayRef)
-                .collect::<Vec<_>>(),
+            Self::primary_keys_schema(),
+            vec![
+                Arc::new(catalog_names.finish()),
+                Arc::new(schema_names.finish()),
+                Arc::new(table_names.finish()),
+                Arc::new(column_names.finish()),
+                Arc::new(key_names.finish()),
+                Arc::new(key_sequences.finish()),
+            ],
         )
+        .map_err(FlightError::Arrow)
     }
 
-    async fn create_ctx(&self) -> Result<Uuid, Status> {
+    fn table_types(query: CommandGetTableTypes) -> Result<RecordBatch, FlightError> {
+        let mut builder = query.into_builder();
+        for table_type in TABLE_TYPES {
+            builder.append(table_type);
+        }
+        builder.build()
+    }
+
+    /// Information about the server returned for `CommandGetSqlInfo`, which drivers
+    /// query on connect
+    fn sql_info_data() -> Result<SqlInfoData, FlightError> {
+        let mut builder = SqlInfoDataBuilder::new();
+        builder.append(SqlInfo::FlightSqlServerName, "Apache DataFusion Ballista");
+        builder.append(SqlInfo::FlightSqlServerVersion, BALLISTA_VERSION);
+        builder.append(SqlInfo::FlightSqlServerReadOnly, false);
+        builder.append(SqlInfo::FlightSqlServerSql, true);
+        builder.append(
+            SqlInfo::FlightSqlServerSubstrait,
+            cfg!(feature = "substrait"),
+        );
+        builder.append(
+            SqlInfo::FlightSqlServerTransaction,
+            SqlSupportedTransaction::None as i32,
+        );
+        builder.append(SqlInfo::FlightSqlServerCancel, false);
+        builder.append(SqlInfo::SqlDdlCatalog, false);
+        builder.append(SqlInfo::SqlDdlSchema, true);
+        builder.append(SqlInfo::SqlDdlTable, true);
+        builder.append(SqlInfo::SqlIdentifierQuoteChar, "\"");
+        builder.append(SqlInfo::SqlAllTablesAreSelectable, true);
+        builder.append(SqlInfo::SqlSearchStringEscape, "\\");
+        builder.append(SqlInfo::SqlSchemaTerm, "schema");
+        builder.append(SqlInfo::SqlCatalogTerm, "catalog");
+        builder.build()
+    }
+
+    /// Identify the client of a handshake. With an auth provider, the credentials of
+    /// the handshake are authenticated by it, otherwise clients are identified like the
+    /// callers of the scheduler gRPC service
+    async fn authenticate(
+        &self,
+        metadata: &MetadataMap,
+    ) -> Result<Option<Identity>, Status> {
+        let metadata = |key| {
+            metadata
+                .get(key)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let auth = RequestAuth {
+            principal: metadata(PRINCIPAL_METADATA_KEY),
+            authorization: metadata(AUTHORIZATION_METADATA_KEY),
+        };
+        let Some(auth_provider) = &self.server.state.config.auth_provider else {
+            return Ok(self.server.identify(auth).await?);
+        };
+
+        let authorization = auth
+            .authorization
+            .ok_or_else(|| Status::unauthenticated("authorization field not present"))?;
+        let credentials = Credentials::from_authorization_header(&authorization)
+            .ok_or_else(|| Status::invalid_argument("authorization not parsable"))?;
+        let identity = auth_provider
+            .authenticate(&credentials)
+            .await
+            .map_err(|e| {
+                warn!("Failed to authenticate Flight SQL client {credentials:?}: {e}");
+                Status::unauthenticated("Invalid credentials!")
+            })?;
+        Ok(Some(identity))
+    }
+
+    async fn create_ctx(&self, identity: Option<Identity>) -> Result<Uuid, Status> {
         let config = self.server.state.session_manager.produce_config();
         let ctx = self
             .server
@@ -157,11 +526,16 @@ impl FlightSqlServiceImpl {
                 Status::internal(format!("Failed to create SessionContext: {e:?}"))
             })?;
         let handle = Uuid::new_v4();
-        self.contexts.insert(handle, ctx);
+        self.contexts
+            .insert(handle, FlightSqlSession { ctx, identity });
         Ok(handle)
     }
 
     fn get_ctx<T>(&self, req: &Request<T>) -> Result<Arc<SessionContext>, Status> {
+        Ok(self.get_session(req)?.ctx)
+    }
+
+    fn get_session<T>(&self, req: &Request<T>) -> Result<FlightSqlSession, Status> {
         let auth = req
             .metadata()
             .get("authorization")
@@ -178,8 +552,8 @@ impl FlightSqlServiceImpl {
 
         let handle = Uuid::from_str(auth.as_str())
             .map_err(|e| Status::internal(format!("Error locking contexts: {e}")))?;
-        if let Some(context) = self.contexts.get(&handle) {
-            Ok(context.clone())
+        if let Some(session) = self.contexts.get(&handle) {
+            Ok(session.clone())
         } else {
             Err(Status::internal(format!(
                 "Context handle not found: {handle}"
@@ -187,12 +561,63 @@ impl FlightSqlServiceImpl {
         }
     }
 
+    /// Plan a SQL query of a session, once its caller is allowed to submit jobs and the
+    /// query authorizer of the scheduler allows the query
     async fn prepare_statement(
+        &self,
+        query: &str,
+        session: &FlightSqlSession,
+    ) -> Result<LogicalPlan, Status> {
+        let identity = session.identity.as_ref();
+        self.server
+            .authorize(identity, Operation::SubmitJob, None)?;
+        Self::plan_statement(query, &session.ctx, |plan| {
+            Ok(self.server.authorize_query(identity, &session.ctx, plan)?)
+        })
+        .await
+    }
+
+    /// Plan a SQL query, checking its plan with `authorize` before the DDL and
+    /// statements it contains are executed
+    async fn plan_statement(
         query: &str,
         ctx: &Arc<SessionContext>,
+        authorize: impl Fn(&LogicalPlan) -> Result<(), Status>,
     ) -> Result<LogicalPlan, Status> {
+        let verify_query = verify_table_statement(query);
+        let plan = ctx
+            .state()
+            .create_logical_plan(verify_query.as_deref().unwrap_or(query))
+            .await
+            .map_err(|e| Status::internal(format!("Error building plan: {e}")))?;
+        authorize(&plan)?;
         let plan = ctx
-            .sql(query)
+            .execute_logical_plan(plan)
+            .await
+            .and_then(|df| df.into_optimized_plan())
+            .map_err(|e| Status::internal(format!("Error building plan: {e}")))?;
+        Ok(plan)
+    }
+
+    /// Convert a Substrait plan into a logical plan, checked like the SQL statements
+    async fn prepare_substrait_plan(
+        &self,
+        plan: Option<SubstraitPlan>,
+        session: &FlightSqlSession,
+    ) -> Result<LogicalPlan, Status> {
+        let FlightSqlSession { ctx, identity } = session;
+        self.server
+            .authorize(identity.as_ref(), Operation::SubmitJob, None)?;
+        let plan =
+            plan.ok_or_else(|| Status::invalid_argument("Missing Substrait plan"))?;
+        let plan = substrait_to_logical_plan(ctx, &plan.plan)
+            .await
+            .map_err(|e| {
+                Status::invalid_argument(format!("Error converting Substrait plan: {e}"))
+            })?;
+        self.server.authorize_query(identity.as_ref(), ctx, &plan)?;
+        let plan = ctx
+            .execute_logical_plan(plan)
             .await
             .and_then(|df| df.into_optimized_plan())
             .map_err(|e| Status::internal(format!("Error building plan: {e}")))?;
@@ -241,6 +666,105 @@ impl FlightSqlServiceImpl {
         }
     }
 
+    /// ID of the job whose results the flight info describes, once the caller of the
+    /// session is allowed to perform `operation` on the job. Flight infos are sent by
+    /// clients, so they must also present the token of the job
+    async fn authorize_flight_info(
+        &self,
+        session: &FlightSqlSession,
+        info: &FlightInfo,
+        operation: Operation,
+    ) -> Result<String, Status> {
+        let job_id = flight_info_job_id(info).ok_or_else(|| {
+            Status::invalid_argument("The flight info does not describe a Ballista job")
+        })?;
+        self.server
+            .authorize(session.identity.as_ref(), operation, Some(&job_id))?;
+
+        let token = self
+            .server
+            .state
+            .task_manager
+            .job_fetch_token(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting token of job {job_id}: {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?
+            .map(FetchToken::from)
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+        let presented = flight_info_fetch_tokens(info, &job_id);
+        if !presented.iter().any(|presented| token.matches(presented)) {
+            warn!("Rejected Flight SQL action on job {job_id} without its token");
+            Err(Status::permission_denied(format!(
+                "The flight info does not present the token of job {job_id}"
+            )))?
+        }
+        Ok(job_id)
+    }
+
+    /// Status of the job whose results the flight info describes
+    async fn flight_info_job_status(
+        &self,
+        session: &FlightSqlSession,
+        info: &FlightInfo,
+    ) -> Result<JobStatus, Status> {
+        let job_id = self
+            .authorize_flight_info(session, info, Operation::ViewJob)
+            .await?;
+        self.server
+            .state
+            .task_manager
+            .get_job_status(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting status for job {job_id}: {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))
+    }
+
+    /// Cancel the job whose results the flight info describes, unless it ended
+    async fn cancel_flight_info(
+        &self,
+        session: &FlightSqlSession,
+        info: &FlightInfo,
+    ) -> Result<CancelStatus, Status> {
+        self.server.ensure_writable().await?;
+        let job_id = self
+            .authorize_flight_info(session, info, Operation::CancelJob)
+            .await?;
+        let status = self
+            .server
+            .state
+            .task_manager
+            .get_job_status(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting status for job {job_id}: {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+        match status.status {
+            Some(job_status::Status::Queued(_))
+            | Some(job_status::Status::Running(_)) => {
+                info!("Cancelling job {} through Flight SQL", status.job_id);
+                self.server
+                    .query_stage_event_loop
+                    .get_sender()
+                    .map_err(|e| Status::internal(format!("{e:?}")))?
+                    .post_event(QueryStageSchedulerEvent::JobCancel(status.job_id))
+                    .await
+                    .map_err(|e| Status::internal(format!("{e:?}")))?;
+                Ok(CancelStatus::Cancelled)
+            }
+            _ => Ok(CancelStatus::NotCancellable),
+        }
+    }
+
     async fn job_to_fetch_part(
         &self,
         completed: SuccessfulJob,
@@ -286,6 +810,12 @@ impl FlightSqlServiceImpl {
                     // Use executor ip:port for routing to flight result
                     host: exec_host.clone(),
                     port: exec_port,
+                    compression: loc.compression,
+                    fetch_token: loc.fetch_token.clone(),
+                    // Flight SQL clients only decode batches compressed by Arrow IPC
+                    transfer_compression: String::new(),
+                    // in the Arrow IPC format version of the executor
+                    ipc_metadata_version: 0,
                 };
                 protobuf::Action {
                     action_type: Some(FetchPartition(fetch)),
@@ -311,39 +841,15 @@ impl FlightSqlServiceImpl {
         Ok(fieps)
     }
 
-    fn make_local_fieps(&self, job_id: &str) -> Result<Vec<FlightEndpoint>, Status> {
-        let (host, port) = ("127.0.0.1".to_string(), 50050); // TODO: use advertise host
-        let fetch = protobuf::FetchPartition {
-            job_id: job_id.to_string(),
-            stage_id: 0,
-            partition_id: 0,
-            path: job_id.to_string(),
-            host: host.clone(),
-            port,
-        };
-        let fetch = protobuf::Action {
-            action_type: Some(FetchPartition(fetch)),
-            settings: vec![],
-        };
-        let authority = format!("{}:{}", &host, &port); // TODO: use advertise host
-        let buf = fetch.as_any().encode_to_vec();
-        let ticket = Ticket { ticket: buf.into() };
-        let fiep = FlightEndpoint::new()
-            .with_ticket(ticket)
-            .with_location(format!("grpc+tcp://{authority}"));
-        let fieps = vec![fiep];
-        Ok(fieps)
-    }
-
     fn cache_plan(&self, plan: LogicalPlan) -> Result<Uuid, Status> {
         let handle = Uuid::new_v4();
-        self.statements.insert(handle, plan);
+        self.statements.insert(handle, PreparedStatement::new(plan));
         Ok(handle)
     }
 
     fn get_plan(&self, handle: &Uuid) -> Result<LogicalPlan, Status> {
-        if let Some(plan) = self.statements.get(handle) {
-            Ok(plan.clone())
+        if let Some(statement) = self.statements.get(handle) {
+            statement.bound_plan()
         } else {
             Err(Status::internal(format!(
                 "Statement handle not found: {handle}"
@@ -383,13 +889,27 @@ impl FlightSqlServiceImpl {
 
     async fn enqueue_job(
         &self,
-        ctx: Arc<SessionContext>,
+        session: FlightSqlSession,
         plan: &LogicalPlan,
     ) -> Result<String, Status> {
+        self.server.ensure_writable().await?;
+        let FlightSqlSession { ctx, identity } = session;
+        self.server
+            .authorize(identity.as_ref(), Operation::SubmitJob, None)?;
+        let plan = self
+            .server
+            .mask_plan(identity.as_ref(), &ctx, plan.clone())
+            .map_err(|e| Status::internal(format!("Failed to mask plan: {e}")))?;
         let job_id = self.server.state.task_manager.generate_job_id();
+        if let Some(identity) = &identity {
+            self.server
+                .state
+                .task_manager
+                .set_job_owner(&job_id, &identity.principal);
+        }
         let job_name = format!("Flight SQL job {job_id}");
         self.server
-            .submit_job(&job_id, &job_name, ctx, plan)
+            .submit_job(&job_id, &job_name, ctx, &plan)
             .await
             .map_err(|e| {
                 let msg = format!("Failed to send JobQueued event for {job_id}: {e:?}");
@@ -424,30 +944,67 @@ impl FlightSqlServiceImpl {
 
     async fn execute_plan(
         &self,
-        ctx: Arc<SessionContext>,
+        session: FlightSqlSession,
         plan: &LogicalPlan,
     ) -> Result<Response<FlightInfo>, Status> {
-        let job_id = self.enqueue_job(ctx, plan).await?;
+        let job_id = self.enqueue_job(session, plan).await?;
+        let mut cancel_on_drop = CancelJobOnDrop {
+            job_id: job_id.clone(),
+            sender: self.server.query_stage_event_loop.get_sender().ok(),
+        };
 
         // poll for job completion
-        let mut num_rows = 0;
-        let mut num_bytes = 0;
-        let fieps = loop {
+        let completed = loop {
             sleep(Duration::from_millis(100)).await;
-            let completed = if let Some(comp) = self.check_job(&job_id).await? {
-                comp
-            } else {
-                continue;
-            };
-            let fieps = self
-                .job_to_fetch_part(completed, &mut num_rows, &mut num_bytes)
-                .await?;
-            break fieps;
+            match self.check_job(&job_id).await {
+                Ok(None) => continue,
+                Ok(Some(completed)) => break Ok(completed),
+                Err(e) => break Err(e),
+            }
         };
+        // the job ended, there is nothing left to cancel
+        cancel_on_drop.disarm();
+        let completed = completed?;
+
+        let mut num_rows = 0;
+        let mut num_bytes = 0;
+        let truncated_at = completed.truncated_at;
+        let skipped_partitions = completed.skipped_partitions.len();
+        let fieps = self
+            .job_to_fetch_part(completed, &mut num_rows, &mut num_bytes)
+            .await?;
 
         // Generate response
         let schema_bytes = self.df_schema_to_arrow(plan.schema())?;
-        let resp = Self::create_resp(schema_bytes, fieps, num_rows, num_bytes);
+        let mut resp = Self::create_resp(schema_bytes, fieps, num_rows, num_bytes);
+        let fetch_token = self
+            .server
+            .state
+            .task_manager
+            .job_fetch_token(&job_id)
+            .await
+            .map_err(|e| Status::internal(format!("Error getting job token: {e:?}")))?
+            .unwrap_or_default();
+        resp.get_mut().flight_descriptor = Some(FlightDescriptor::new_path(vec![
+            JOB_DESCRIPTOR_PATH.to_owned(),
+            job_id,
+            fetch_token,
+        ]));
+        let mut notices = vec![];
+        if let Some(max_rows) = truncated_at {
+            // The results hold one row more than the limit, which clients must not read
+            resp.get_mut().total_records = max_rows as i64;
+            notices.push(format!("result truncated at {max_rows} rows"));
+        }
+        if skipped_partitions > 0 {
+            // Best-effort jobs complete without the partitions whose tasks failed
+            notices.push(format!(
+                "result missing {skipped_partitions} failed partitions"
+            ));
+        }
+        if !notices.is_empty() {
+            resp.get_mut().app_metadata = notices.join("; ").into();
+        }
         Ok(resp)
     }
 
@@ -472,18 +1029,19 @@ impl FlightSqlServiceImpl {
         Ok(resp)
     }
 
-    fn batch_to_schema_resp(
+    /// Flight info of a metadata command, whose single endpoint is served by
+    /// the scheduler itself with the command as ticket
+    fn metadata_to_resp(
         &self,
-        data: &RecordBatch,
-        name: &str,
+        command: impl ProstMessageExt,
+        schema: SchemaRef,
     ) -> Result<Response<FlightInfo>, Status> {
-        let num_bytes = data.get_array_memory_size() as i64;
-        let schema = data.schema();
-        let num_rows = data.num_rows() as i64;
-
-        let fieps = self.make_local_fieps(name)?;
+        let ticket = Ticket {
+            ticket: command.as_any().encode_to_vec().into(),
+        };
+        let fieps = vec![FlightEndpoint::new().with_ticket(ticket)];
         let schema_bytes = self.schema_to_arrow(schema)?;
-        let resp = Self::create_resp(schema_bytes, fieps, num_rows, num_bytes);
+        let resp = Self::create_resp(schema_bytes, fieps, -1, -1);
         Ok(resp)
     }
 }
@@ -504,34 +1062,11 @@ impl FlightSqlService for FlightSqlServiceImpl {
             debug!("{:?}", md);
         }
 
-        let basic = "Basic ";
-        let authorization = request
-            .metadata()
-            .get("authorization")
-            .ok_or_else(|| Status::invalid_argument("authorization field not present"))?
-            .to_str()
-            .map_err(|_| Status::invalid_argument("authorization not parsable"))?;
-        if !authorization.starts_with(basic) {
-            Err(Status::invalid_argument(format!(
-                "Auth type not implemented: {authorization}"
-            )))?;
-        }
-        let bytes = base64::engine::general_purpose::STANDARD
-            .decode(&authorization[basic.len()..])
-            .map_err(|_| Status::invalid_argument("authorization not parsable"))?;
-        let str = String::from_utf8(bytes)
-            .map_err(|_| Status::invalid_argument("authorization not parsable"))?;
-        let parts: Vec<_> = str.split(':').collect();
-        if parts.len() != 2 {
-            Err(Status::invalid_argument("Invalid authorization header"))?;
-        }
-        let user = parts[0];
-        let pass = parts[1];
-        if user != "admin" || pass != "password" {
-            Err(Status::unauthenticated("Invalid credentials!"))?
+        let identity = self.authenticate(request.metadata()).await?;
+        if let Some(identity) = &identity {
+            info!("Flight SQL client authenticated as {}", identity.principal);
         }
-
-        let token = self.create_ctx().await?;
+        let token = self.create_ctx(identity).await?;
 
         let result = HandshakeResponse {
             protocol_version: 0,
@@ -554,7 +1089,7 @@ impl FlightSqlService for FlightSqlServiceImpl {
         message: arrow_flight::sql::Any,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_fallback type_url: {}", message.type_url);
-        let ctx = self.get_ctx(&request)?;
+        self.get_ctx(&request)?;
         if !message.is::<protobuf::Action>() {
             Err(Status::unimplemented(format!(
                 "do_get: The defined request is invalid: {}",
@@ -571,39 +1106,27 @@ impl FlightSqlService for FlightSqlServiceImpl {
             None => Err(Status::internal("Expected an ActionType but got None!"))?,
         };
 
-        // Well-known job ID: respond with the data
-        match fp.job_id.as_str() {
-            "get_flight_info_table_types" => {
-                debug!("Responding with table types");
-                let rb = FlightSqlServiceImpl::table_types().map_err(|_| {
-                    Status::internal("Error getting table types".to_string())
-                })?;
-                let resp = Self::record_batch_to_resp(rb).await?;
-                return Ok(resp);
-            }
-            "get_flight_info_tables" => {
-                debug!("Responding with tables");
-                let rb = self
-                    .tables(ctx)
-                    .map_err(|_| Status::internal("Error getting tables".to_string()))?;
-                let resp = Self::record_batch_to_resp(rb).await?;
-                return Ok(resp);
-            }
-            _ => {}
-        }
-
         // Proxy the flight
         let addr = format!("http://{}:{}", fp.host, fp.port);
         debug!("Scheduler proxying flight for to {}", addr);
-        let connection =
-            create_grpc_client_connection(addr.clone())
-                .await
-                .map_err(|e| {
-                    Status::internal(format!(
-                    "Error connecting to Ballista scheduler or executor at {addr}: {e:?}"
-                ))
-                })?;
-        let mut flight_client = FlightServiceClient::new(connection);
+        let connection = create_grpc_client_connection(
+            addr.clone(),
+            &self.server.state.config.grpc_security,
+        )
+        .await
+        .map_err(|e| {
+            Status::internal(format!(
+                "Error connecting to Ballista scheduler or executor at {addr}: {e:?}"
+            ))
+        })?;
+        let config = &self.server.state.config;
+        let mut flight_client = FlightServiceClient::new(connection)
+            .max_encoding_message_size(
+                config.grpc_client_max_encoding_message_size as usize,
+            )
+            .max_decoding_message_size(
+                config.grpc_client_max_decoding_message_size as usize,
+            );
         let buf = action.encode_to_vec();
         let request = Request::new(Ticket { ticket: buf.into() });
 
@@ -632,9 +1155,9 @@ impl FlightSqlService for FlightSqlServiceImpl {
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_statement query:\n{}", query.query);
 
-        let ctx = self.get_ctx(&request)?;
-        let plan = Self::prepare_statement(&query.query, &ctx).await?;
-        let resp = self.execute_plan(ctx, &plan).await?;
+        let session = self.get_session(&request)?;
+        let plan = self.prepare_statement(&query.query, &session).await?;
+        let resp = self.execute_plan(session, &plan).await?;
 
         debug!("Returning flight info...");
         Ok(resp)
@@ -646,11 +1169,14 @@ impl FlightSqlService for FlightSqlServiceImpl {
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_prepared_statement");
-        let ctx = self.get_ctx(&request)?;
+        let session = self.get_session(&request)?;
         let handle = Uuid::from_slice(handle.prepared_statement_handle.as_ref())
             .map_err(|e| Status::internal(format!("Error decoding handle: {e}")))?;
         let plan = self.get_plan(&handle)?;
-        let resp = self.execute_plan(ctx, &plan).await?;
+        // statements may be executed by other sessions than the one preparing them
+        self.server
+            .authorize_query(session.identity.as_ref(), &session.ctx, &plan)?;
+        let resp = self.execute_plan(session, &plan).await?;
 
         debug!("Responding to query {}...", handle);
         Ok(resp)
@@ -658,65 +1184,63 @@ impl FlightSqlService for FlightSqlServiceImpl {
 
     async fn get_flight_info_catalogs(
         &self,
-        _query: CommandGetCatalogs,
-        _request: Request<FlightDescriptor>,
+        query: CommandGetCatalogs,
+        request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_catalogs");
-        Err(Status::unimplemented("Implement get_flight_info_catalogs"))
+        self.get_ctx(&request)?;
+        let schema = query.into_builder().schema();
+        self.metadata_to_resp(query, schema)
     }
     async fn get_flight_info_schemas(
         &self,
-        _query: CommandGetDbSchemas,
-        _request: Request<FlightDescriptor>,
+        query: CommandGetDbSchemas,
+        request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_schemas");
-        Err(Status::unimplemented("Implement get_flight_info_schemas"))
+        self.get_ctx(&request)?;
+        let schema = query.clone().into_builder().schema();
+        self.metadata_to_resp(query, schema)
     }
 
     async fn get_flight_info_tables(
         &self,
-        _query: CommandGetTables,
+        query: CommandGetTables,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_tables");
-        let ctx = self.get_ctx(&request)?;
-        let data = self
-            .tables(ctx)
-            .map_err(|e| Status::internal(format!("Error getting tables: {e}")))?;
-        let resp = self.batch_to_schema_resp(&data, "get_flight_info_tables")?;
-        Ok(resp)
+        self.get_ctx(&request)?;
+        let schema = query.clone().into_builder().schema();
+        self.metadata_to_resp(query, schema)
     }
 
     async fn get_flight_info_table_types(
         &self,
-        _query: CommandGetTableTypes,
+        query: CommandGetTableTypes,
         _request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_table_types");
-        let data = FlightSqlServiceImpl::table_types()
-            .map_err(|e| Status::internal(format!("Error getting table types: {e}")))?;
-        let resp = self.batch_to_schema_resp(&data, "get_flight_info_table_types")?;
-        Ok(resp)
+        let schema = query.into_builder().schema();
+        self.metadata_to_resp(query, schema)
     }
 
     async fn get_flight_info_sql_info(
         &self,
-        _query: CommandGetSqlInfo,
+        query: CommandGetSqlInfo,
         _request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_sql_info");
-        // TODO: implement for FlightSQL JDBC to work
-        Err(Status::unimplemented("Implement CommandGetSqlInfo"))
+        let schema = query.clone().into_builder(&self.sql_info).schema();
+        self.metadata_to_resp(query, schema)
     }
     async fn get_flight_info_primary_keys(
         &self,
-        _query: CommandGetPrimaryKeys,
-        _request: Request<FlightDescriptor>,
+        query: CommandGetPrimaryKeys,
+        request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_primary_keys");
-        Err(Status::unimplemented(
-            "Implement get_flight_info_primary_keys",
-        ))
+        self.get_ctx(&request)?;
+        self.metadata_to_resp(query, Self::primary_keys_schema())
     }
     async fn get_flight_info_exported_keys(
         &self,
@@ -785,51 +1309,71 @@ impl FlightSqlService for FlightSqlServiceImpl {
     }
     async fn do_get_catalogs(
         &self,
-        _query: CommandGetCatalogs,
-        _request: Request<Ticket>,
+        query: CommandGetCatalogs,
+        request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_catalogs");
-        Err(Status::unimplemented("Implement do_get_catalogs"))
+        let ctx = self.get_ctx(&request)?;
+        let rb = Self::catalogs(&ctx, query)
+            .map_err(|e| Status::internal(format!("Error getting catalogs: {e}")))?;
+        Self::record_batch_to_resp(rb).await
     }
     async fn do_get_schemas(
         &self,
-        _query: CommandGetDbSchemas,
-        _request: Request<Ticket>,
+        query: CommandGetDbSchemas,
+        request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_schemas");
-        Err(Status::unimplemented("Implement do_get_schemas"))
+        let ctx = self.get_ctx(&request)?;
+        let rb = Self::schemas(&ctx, query)
+            .map_err(|e| Status::internal(format!("Error getting schemas: {e}")))?;
+        Self::record_batch_to_resp(rb).await
     }
     async fn do_get_tables(
         &self,
-        _query: CommandGetTables,
-        _request: Request<Ticket>,
+        query: CommandGetTables,
+        request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_tables");
-        Err(Status::unimplemented("Implement do_get_tables"))
+        let ctx = self.get_ctx(&request)?;
+        let rb = Self::tables(&ctx, query)
+            .await
+            .map_err(|e| Status::internal(format!("Error getting tables: {e}")))?;
+        Self::record_batch_to_resp(rb).await
     }
     async fn do_get_table_types(
         &self,
-        _query: CommandGetTableTypes,
+        query: CommandGetTableTypes,
         _request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_table_types");
-        Err(Status::unimplemented("Implement do_get_table_types"))
+        let rb = Self::table_types(query)
+            .map_err(|e| Status::internal(format!("Error getting table types: {e}")))?;
+        Self::record_batch_to_resp(rb).await
     }
     async fn do_get_sql_info(
         &self,
-        _query: CommandGetSqlInfo,
+        query: CommandGetSqlInfo,
         _request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_sql_info");
-        Err(Status::unimplemented("Implement do_get_sql_info"))
+        let rb = query
+            .into_builder(&self.sql_info)
+            .build()
+            .map_err(|e| Status::internal(format!("Error getting SQL info: {e}")))?;
+        Self::record_batch_to_resp(rb).await
     }
     async fn do_get_primary_keys(
         &self,
-        _query: CommandGetPrimaryKeys,
-        _request: Request<Ticket>,
+        query: CommandGetPrimaryKeys,
+        request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_primary_keys");
-        Err(Status::unimplemented("Implement do_get_primary_keys"))
+        let ctx = self.get_ctx(&request)?;
+        let rb = Self::primary_keys(&ctx, query)
+            .await
+            .map_err(|e| Status::internal(format!("Error getting primary keys: {e}")))?;
+        Self::record_batch_to_resp(rb).await
     }
     async fn do_get_exported_keys(
         &self,
@@ -866,13 +1410,42 @@ impl FlightSqlService for FlightSqlServiceImpl {
     }
     async fn do_put_prepared_statement_query(
         &self,
-        _query: CommandPreparedStatementQuery,
-        _request: Request<PeekableFlightDataStream>,
+        query: CommandPreparedStatementQuery,
+        request: Request<PeekableFlightDataStream>,
     ) -> Result<DoPutPreparedStatementResult, Status> {
         debug!("do_put_prepared_statement_query");
-        Err(Status::unimplemented(
-            "Implement do_put_prepared_statement_query",
-        ))
+        self.get_ctx(&request)?;
+        let handle = Uuid::from_slice(query.prepared_statement_handle.as_ref())
+            .map_err(|e| Status::internal(format!("Error decoding handle: {e}")))?;
+        let batches: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(
+            request.into_inner().map_err(FlightError::from),
+        )
+        .try_collect()
+        .await
+        .map_err(|e| {
+            Status::invalid_argument(format!("Error decoding parameters: {e}"))
+        })?;
+        let parameters = arrow::compute::concat_batches(
+            &batches
+                .first()
+                .map(|batch| batch.schema())
+                .unwrap_or_else(|| Arc::new(Schema::empty())),
+            &batches,
+        )
+        .map_err(|e| {
+            Status::invalid_argument(format!("Error decoding parameters: {e}"))
+        })?;
+
+        let mut statement = self.statements.get_mut(&handle).ok_or_else(|| {
+            Status::internal(format!("Statement handle not found: {handle}"))
+        })?;
+        statement.bind(&parameters)?;
+        debug!("Bound parameters of {}: {:?}", handle, statement.parameters);
+
+        // The handle is unchanged, as the parameters are kept with the cached plan
+        Ok(DoPutPreparedStatementResult {
+            prepared_statement_handle: None,
+        })
     }
     async fn do_put_prepared_statement_update(
         &self,
@@ -880,11 +1453,13 @@ impl FlightSqlService for FlightSqlServiceImpl {
         request: Request<PeekableFlightDataStream>,
     ) -> Result<i64, Status> {
         debug!("do_put_prepared_statement_update");
-        let ctx = self.get_ctx(&request)?;
+        let session = self.get_session(&request)?;
         let handle = Uuid::from_slice(handle.prepared_statement_handle.as_ref())
             .map_err(|e| Status::internal(format!("Error decoding handle: {e}")))?;
         let plan = self.get_plan(&handle)?;
-        let _ = self.execute_plan(ctx, &plan).await?;
+        self.server
+            .authorize_query(session.identity.as_ref(), &session.ctx, &plan)?;
+        let _ = self.execute_plan(session, &plan).await?;
         debug!("Sending -1 rows affected");
         Ok(-1)
     }
@@ -895,15 +1470,21 @@ impl FlightSqlService for FlightSqlServiceImpl {
         request: Request<Action>,
     ) -> Result<ActionCreatePreparedStatementResult, Status> {
         debug!("do_action_create_prepared_statement");
-        let ctx = self.get_ctx(&request)?;
-        let plan = Self::prepare_statement(&query.query, &ctx).await?;
+        let session = self.get_session(&request)?;
+        let plan = self.prepare_statement(&query.query, &session).await?;
         let schema_bytes = self.df_schema_to_arrow(plan.schema())?;
+        let parameter_schema = PreparedStatement::new(plan.clone()).parameter_schema()?;
+        let parameter_schema_bytes = if parameter_schema.fields().is_empty() {
+            vec![]
+        } else {
+            self.schema_to_arrow(Arc::new(parameter_schema))?
+        };
         let handle = self.cache_plan(plan)?;
         debug!("Prepared statement {}:\n{}", handle, query.query);
         let res = ActionCreatePreparedStatementResult {
             prepared_statement_handle: handle.as_bytes().to_vec().into(),
             dataset_schema: schema_bytes.into(),
-            parameter_schema: Vec::new().into(), // TODO: parameters
+            parameter_schema: parameter_schema_bytes.into(),
         };
         Ok(res)
     }
@@ -926,13 +1507,16 @@ impl FlightSqlService for FlightSqlServiceImpl {
     /// Get a FlightInfo for executing a substrait plan.
     async fn get_flight_info_substrait_plan(
         &self,
-        _query: CommandStatementSubstraitPlan,
-        _request: Request<FlightDescriptor>,
+        query: CommandStatementSubstraitPlan,
+        request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_substrait_plan");
-        Err(Status::unimplemented(
-            "Implement get_flight_info_substrait_plan",
-        ))
+        let session = self.get_session(&request)?;
+        let plan = self.prepare_substrait_plan(query.plan, &session).await?;
+        let resp = self.execute_plan(session, &plan).await?;
+
+        debug!("Returning flight info...");
+        Ok(resp)
     }
 
     /// Execute a substrait plan
@@ -948,13 +1532,20 @@ impl FlightSqlService for FlightSqlServiceImpl {
     /// Create a prepared substrait plan.
     async fn do_action_create_prepared_substrait_plan(
         &self,
-        _query: ActionCreatePreparedSubstraitPlanRequest,
-        _request: Request<Action>,
+        query: ActionCreatePreparedSubstraitPlanRequest,
+        request: Request<Action>,
     ) -> Result<ActionCreatePreparedStatementResult, Status> {
         debug!("do_action_create_prepared_substrait_plan");
-        Err(Status::unimplemented(
-            "Implement do_action_create_prepared_substrait_plan",
-        ))
+        let session = self.get_session(&request)?;
+        let plan = self.prepare_substrait_plan(query.plan, &session).await?;
+        let schema_bytes = self.df_schema_to_arrow(plan.schema())?;
+        let handle = self.cache_plan(plan)?;
+        debug!("Prepared Substrait plan {}", handle);
+        Ok(ActionCreatePreparedStatementResult {
+            prepared_statement_handle: handle.as_bytes().to_vec().into(),
+            dataset_schema: schema_bytes.into(),
+            parameter_schema: vec![].into(),
+        })
     }
 
     /// Begin a transaction
@@ -1002,13 +1593,511 @@ impl FlightSqlService for FlightSqlServiceImpl {
     /// Cancel a query
     async fn do_action_cancel_query(
         &self,
-        _query: ActionCancelQueryRequest,
-        _request: Request<Action>,
+        query: ActionCancelQueryRequest,
+        request: Request<Action>,
     ) -> Result<ActionCancelQueryResult, Status> {
         debug!("do_action_cancel_query");
-        Err(Status::unimplemented("Implement do_action_cancel_query"))
+        let session = self.get_session(&request)?;
+        let info = FlightInfo::decode(query.info)
+            .map_err(|e| Status::invalid_argument(format!("Invalid flight info: {e}")))?;
+        // arrow-flight does not export the `CancelResult` enum, so the result
+        // is set from its wire value
+        let result = match self.cancel_flight_info(&session, &info).await? {
+            CancelStatus::Unspecified => 0,
+            CancelStatus::Cancelled => 1,
+            CancelStatus::Cancelling => 2,
+            CancelStatus::NotCancellable => 3,
+        };
+        Ok(ActionCancelQueryResult { result })
+    }
+
+    async fn do_action_fallback(
+        &self,
+        request: Request<Action>,
+    ) -> Result<Response<<Self as FlightService>::DoActionStream>, Status> {
+        let action_type = request.get_ref().r#type.clone();
+        debug!("do_action_fallback type: {action_type}");
+        let body = match action_type.as_str() {
+            CANCEL_FLIGHT_INFO => {
+                let session = self.get_session(&request)?;
+                let info =
+                    CancelFlightInfoRequest::decode(request.get_ref().body.clone())
+                        .map_err(|e| {
+                            Status::invalid_argument(format!(
+                                "Invalid {action_type}: {e}"
+                            ))
+                        })?
+                        .info
+                        .ok_or_else(|| Status::invalid_argument("Missing flight info"))?;
+                let status = self.cancel_flight_info(&session, &info).await?;
+                CancelFlightInfoResult::new(status).encode_to_vec()
+            }
+            GET_QUERY_INFO => {
+                let session = self.get_session(&request)?;
+                let info =
+                    FlightInfo::decode(request.get_ref().body.clone()).map_err(|e| {
+                        Status::invalid_argument(format!("Invalid flight info: {e}"))
+                    })?;
+                self.flight_info_job_status(&session, &info)
+                    .await?
+                    .encode_to_vec()
+            }
+            _ => Err(Status::invalid_argument(format!(
+                "do_action: The defined request is invalid: {action_type:?}"
+            )))?,
+        };
+        let result = arrow_flight::Result { body: body.into() };
+        let stream = futures::stream::iter(vec![Ok::<_, Status>(result)]);
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn list_custom_actions(&self) -> Option<Vec<Result<ActionType, Status>>> {
+        Some(vec![
+            Ok(ActionType {
+                r#type: CANCEL_FLIGHT_INFO.to_owned(),
+                description: "Cancels the query of a flight info\n
+                Request Message: CancelFlightInfoRequest\n
+                Response Message: CancelFlightInfoResult"
+                    .to_owned(),
+            }),
+            Ok(ActionType {
+                r#type: GET_QUERY_INFO.to_owned(),
+                description: "Returns the status of the Ballista job of a flight info\n
+                Request Message: FlightInfo\n
+                Response Message: JobStatus"
+                    .to_owned(),
+            }),
+        ])
     }
 
     /// Register a new SqlInfo result, making it available when calling GetSqlInfo.
     async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{AsArray, Int32Array, Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{Int32Type, Int64Type};
+    use datafusion::common::Constraints;
+    use datafusion::datasource::MemTable;
+    use datafusion::physical_plan::empty::EmptyExec;
+    use datafusion::prelude::SessionConfig;
+
+    use crate::access_control::{AccessControl, Role};
+    use crate::auth::AuthProvider;
+    use crate::config::SchedulerConfig;
+    use crate::metrics::default_metrics_collector;
+    use crate::query_authorization::{QueryRule, QueryRules};
+    use crate::test_utils::test_cluster_context;
+    use ballista_core::extension::SessionConfigExt;
+    use ballista_core::serde::BallistaCodec;
+
+    async fn test_ctx() -> SessionContext {
+        let ctx = SessionContext::new();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            ],
+        )
+        .unwrap();
+        let table = MemTable::try_new(schema, vec![vec![batch]])
+            .unwrap()
+            .with_constraints(Constraints::new_unverified(vec![Constraint::PrimaryKey(
+                vec![0],
+            )]));
+        ctx.register_table("people", Arc::new(table)).unwrap();
+        ctx.sql("CREATE VIEW names AS SELECT name FROM people")
+            .await
+            .unwrap();
+        ctx
+    }
+
+    #[tokio::test]
+    async fn test_prepared_statement_parameters() {
+        let ctx = Arc::new(test_ctx().await);
+        let plan = FlightSqlServiceImpl::plan_statement(
+            "SELECT id FROM people WHERE id > $1 AND name <> $2",
+            &ctx,
+            |_| Ok(()),
+        )
+        .await
+        .unwrap();
+        let mut statement = PreparedStatement::new(plan);
+
+        let parameter_schema = statement.parameter_schema().unwrap();
+        assert_eq!(
+            Schema::new(vec![
+                Field::new("$1", DataType::Int64, true),
+                Field::new("$2", DataType::Utf8, true),
+            ]),
+            parameter_schema
+        );
+
+        // Values are cast to the types of the parameters
+        let parameters = RecordBatch::try_from_iter(vec![
+            ("a", Arc::new(Int32Array::from(vec![1])) as _),
+            ("b", Arc::new(StringArray::from(vec!["c"])) as _),
+        ])
+        .unwrap();
+        statement.bind(&parameters).unwrap();
+        assert_eq!(
+            vec![ScalarValue::Int64(Some(1)), ScalarValue::from("c")],
+            statement.parameters
+        );
+
+        let batches = ctx
+            .execute_logical_plan(statement.bound_plan().unwrap())
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let ids: Vec<i64> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(vec![2], ids);
+
+        // One row of values is expected, one per parameter
+        let too_few = RecordBatch::try_from_iter(vec![(
+            "a",
+            Arc::new(Int32Array::from(vec![1])) as _,
+        )])
+        .unwrap();
+        assert!(statement.bind(&too_few).is_err());
+        let too_many = RecordBatch::try_from_iter(vec![
+            ("a", Arc::new(Int32Array::from(vec![1, 2])) as _),
+            ("b", Arc::new(StringArray::from(vec!["c", "d"])) as _),
+        ])
+        .unwrap();
+        assert!(statement.bind(&too_many).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_metadata() {
+        let ctx = test_ctx().await;
+
+        let catalogs =
+            FlightSqlServiceImpl::catalogs(&ctx, CommandGetCatalogs {}).unwrap();
+        assert_eq!(1, catalogs.num_rows());
+
+        let schemas = FlightSqlServiceImpl::schemas(
+            &ctx,
+            CommandGetDbSchemas {
+                catalog: Some("datafusion".to_owned()),
+                db_schema_filter_pattern: Some("pub%".to_owned()),
+            },
+        )
+        .unwrap();
+        assert_eq!(1, schemas.num_rows());
+        assert_eq!("public", schemas.column(1).as_string::<i32>().value(0));
+
+        let tables = FlightSqlServiceImpl::tables(
+            &ctx,
+            CommandGetTables {
+                catalog: None,
+                db_schema_filter_pattern: Some("public".to_owned()),
+                table_name_filter_pattern: None,
+                table_types: vec![],
+                include_schema: true,
+            },
+        )
+        .await
+        .unwrap();
+        let mut tables: Vec<(String, String, String)> = (0..tables.num_rows())
+            .map(|i| {
+                (
+                    tables.column(1).as_string::<i32>().value(i).to_owned(),
+                    tables.column(2).as_string::<i32>().value(i).to_owned(),
+                    tables.column(3).as_string::<i32>().value(i).to_owned(),
+                )
+            })
+            .collect();
+        tables.sort();
+        assert_eq!(
+            vec![
+                ("public".to_owned(), "names".to_owned(), "VIEW".to_owned()),
+                ("public".to_owned(), "people".to_owned(), "TABLE".to_owned()),
+            ],
+            tables
+        );
+
+        let views = FlightSqlServiceImpl::tables(
+            &ctx,
+            CommandGetTables {
+                catalog: None,
+                db_schema_filter_pattern: None,
+                table_name_filter_pattern: None,
+                table_types: vec!["VIEW".to_owned()],
+                include_schema: false,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, views.num_rows());
+
+        let primary_keys = FlightSqlServiceImpl::primary_keys(
+            &ctx,
+            CommandGetPrimaryKeys {
+                catalog: None,
+                db_schema: Some("public".to_owned()),
+                table: "people".to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, primary_keys.num_rows());
+        assert_eq!("id", primary_keys.column(3).as_string::<i32>().value(0));
+        assert_eq!(
+            1,
+            primary_keys.column(5).as_primitive::<Int32Type>().value(0)
+        );
+
+        // views have no primary key
+        let primary_keys = FlightSqlServiceImpl::primary_keys(
+            &ctx,
+            CommandGetPrimaryKeys {
+                catalog: None,
+                db_schema: None,
+                table: "names".to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(0, primary_keys.num_rows());
+
+        let sql_info = FlightSqlServiceImpl::sql_info_data().unwrap();
+        let server_name = CommandGetSqlInfo {
+            info: vec![SqlInfo::FlightSqlServerName as u32],
+        }
+        .into_builder(&sql_info)
+        .build()
+        .unwrap();
+        assert_eq!(1, server_name.num_rows());
+    }
+
+    #[test]
+    fn test_flight_info_job_id() {
+        let fetch = protobuf::Action {
+            action_type: Some(FetchPartition(protobuf::FetchPartition {
+                job_id: "job-1".to_owned(),
+                fetch_token: "token-1".to_owned(),
+                ..Default::default()
+            })),
+            settings: vec![],
+        };
+        let ticket = Ticket {
+            ticket: fetch.as_any().encode_to_vec().into(),
+        };
+        let info =
+            FlightInfo::new().with_endpoint(FlightEndpoint::new().with_ticket(ticket));
+        assert_eq!(Some("job-1".to_owned()), flight_info_job_id(&info));
+        assert_eq!(
+            vec!["token-1".to_owned()],
+            flight_info_fetch_tokens(&info, "job-1")
+        );
+        assert!(flight_info_fetch_tokens(&info, "job-2").is_empty());
+
+        // jobs without results are found by the descriptor of their flight info
+        let info = FlightInfo::new().with_descriptor(FlightDescriptor::new_path(vec![
+            JOB_DESCRIPTOR_PATH.to_owned(),
+            "job-2".to_owned(),
+            "token-2".to_owned(),
+        ]));
+        assert_eq!(Some("job-2".to_owned()), flight_info_job_id(&info));
+        assert_eq!(
+            vec!["token-2".to_owned()],
+            flight_info_fetch_tokens(&info, "job-2")
+        );
+
+        assert_eq!(None, flight_info_job_id(&FlightInfo::new()));
+    }
+
+    async fn test_service(config: SchedulerConfig) -> FlightSqlServiceImpl {
+        let mut server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                test_cluster_context(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        server.init().await.unwrap();
+        FlightSqlServiceImpl::new(server)
+    }
+
+    fn with_authorization<T>(message: T, authorization: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request
+            .metadata_mut()
+            .insert(AUTHORIZATION_METADATA_KEY, authorization.parse().unwrap());
+        request
+    }
+
+    /// Accepts the Basic credentials whose password is the name of the user
+    #[derive(Debug)]
+    struct TestAuthProvider;
+
+    #[tonic::async_trait]
+    impl AuthProvider for TestAuthProvider {
+        async fn authenticate(
+            &self,
+            credentials: &Credentials,
+        ) -> ballista_core::error::Result<Identity> {
+            match credentials {
+                Credentials::Basic { username, password } if username == password => {
+                    Ok(Identity::new(username))
+                }
+                _ => Err(ballista_core::error::BallistaError::General(
+                    "Invalid credentials".to_owned(),
+                )),
+            }
+        }
+    }
+
+    fn basic(username: &str, password: &str) -> String {
+        use base64::Engine;
+        let credentials = base64::engine::general_purpose::STANDARD
+            .encode(format!("{username}:{password}"));
+        format!("Basic {credentials}")
+    }
+
+    /// Session of a client whose handshake presented the credentials of `username`
+    async fn session(service: &FlightSqlServiceImpl, username: &str) -> FlightSqlSession {
+        let handshake = with_authorization((), &basic(username, username));
+        let identity = service.authenticate(handshake.metadata()).await.unwrap();
+        let handle = service.create_ctx(identity).await.unwrap();
+        service
+            .get_session(&with_authorization((), &format!("Bearer {handle}")))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handshake_authentication() {
+        let service = test_service(
+            SchedulerConfig::default().with_auth_provider(Arc::new(TestAuthProvider)),
+        )
+        .await;
+
+        let identity = service
+            .authenticate(with_authorization((), &basic("alice", "alice")).metadata())
+            .await
+            .unwrap();
+        assert_eq!(Some(Identity::new("alice")), identity);
+
+        let status = service
+            .authenticate(with_authorization((), &basic("alice", "password")).metadata())
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::Unauthenticated, status.code());
+        let status = service.authenticate(&MetadataMap::new()).await.unwrap_err();
+        assert_eq!(tonic::Code::Unauthenticated, status.code());
+    }
+
+    #[tokio::test]
+    async fn test_session_identity() {
+        let rules = QueryRules::new(vec![QueryRule::new()
+            .with_principals(vec!["etl".to_owned()])
+            .with_submit(false)]);
+        let service = test_service(
+            SchedulerConfig::default()
+                .with_access_control(AccessControl::new(Role::User))
+                .with_auth_provider(Arc::new(TestAuthProvider))
+                .with_query_authorizer(Arc::new(rules)),
+        )
+        .await;
+
+        let alice = session(&service, "alice").await;
+        assert_eq!(Some(Identity::new("alice")), alice.identity);
+        service.prepare_statement("SELECT 1", &alice).await.unwrap();
+
+        // the identity of the session is passed to the query authorizer
+        let etl = session(&service, "etl").await;
+        let status = service
+            .prepare_statement("SELECT 1", &etl)
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::PermissionDenied, status.code());
+
+        // anonymous sessions can't submit jobs under access control
+        let handle = service.create_ctx(None).await.unwrap();
+        let anonymous = service
+            .get_session(&with_authorization((), &format!("Bearer {handle}")))
+            .unwrap();
+        let status = service
+            .prepare_statement("SELECT 1", &anonymous)
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::Unauthenticated, status.code());
+    }
+
+    #[tokio::test]
+    async fn test_forged_flight_info() {
+        let service = test_service(
+            SchedulerConfig::default()
+                .with_access_control(AccessControl::new(Role::User))
+                .with_auth_provider(Arc::new(TestAuthProvider)),
+        )
+        .await;
+        let task_manager = &service.server.state.task_manager;
+        task_manager.set_job_owner("job", "bob");
+        task_manager.queue_job("job", "", 0).unwrap();
+        task_manager
+            .submit_job(
+                "job",
+                "",
+                "session",
+                Arc::new(EmptyExec::new(Arc::new(Schema::empty()))),
+                None,
+                0,
+                Arc::new(SessionConfig::new_with_ballista()),
+            )
+            .await
+            .unwrap();
+        let token = task_manager.job_fetch_token("job").await.unwrap().unwrap();
+        let info = |path: Vec<&str>| {
+            FlightInfo::new().with_descriptor(FlightDescriptor::new_path(
+                path.into_iter().map(str::to_owned).collect(),
+            ))
+        };
+
+        // only the owner of the job can cancel it
+        let alice = session(&service, "alice").await;
+        let status = service
+            .cancel_flight_info(&alice, &info(vec!["job", "job", &token]))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::PermissionDenied, status.code());
+
+        // flight infos not presenting the token of the job are rejected
+        let bob = session(&service, "bob").await;
+        for forged in [info(vec!["job", "job"]), info(vec!["job", "job", "forged"])] {
+            let status = service.cancel_flight_info(&bob, &forged).await.unwrap_err();
+            assert_eq!(tonic::Code::PermissionDenied, status.code());
+            let status = service
+                .flight_info_job_status(&bob, &forged)
+                .await
+                .unwrap_err();
+            assert_eq!(tonic::Code::PermissionDenied, status.code());
+        }
+
+        let info = info(vec!["job", "job", &token]);
+        let status = service.flight_info_job_status(&bob, &info).await.unwrap();
+        assert_eq!("job", status.job_id);
+        let cancelled = service.cancel_flight_info(&bob, &info).await.unwrap();
+        assert_eq!(CancelStatus::Cancelled, cancelled);
+    }
+}
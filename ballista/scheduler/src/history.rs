@@ -0,0 +1,388 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Reader of the event log written by the scheduler, see
+//! [crate::state::event_log], which replays the events of the completed jobs into
+//! their history, long after they are gone from the scheduler state.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use datafusion::error::DataFusionError;
+use futures::TryStreamExt;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use serde::Serialize;
+
+use crate::config::SchedulerConfig;
+use crate::state::event_log::{HistoryEvent, EXECUTORS_DIR, JOBS_DIR};
+
+/// History of a completed job, replayed from its events
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct JobHistory {
+    pub job_id: String,
+    pub job_name: String,
+    pub successful: bool,
+    pub error: Option<String>,
+    pub queued_at: u64,
+    pub submitted_at: u64,
+    pub started_at: u64,
+    pub ended_at: u64,
+    /// The stages of the job ordered by ID, empty if it failed before being planned
+    pub stages: Vec<StageHistory>,
+}
+
+/// History of a stage of a job
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StageHistory {
+    pub stage_id: usize,
+    pub partitions: Option<usize>,
+    pub output_links: Vec<usize>,
+    pub plan: String,
+    /// The attempts of the tasks of the stage, in the order they started
+    pub tasks: Vec<TaskHistory>,
+}
+
+/// History of an attempt of a task
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaskHistory {
+    pub task_id: usize,
+    pub partition_id: usize,
+    pub task_attempt: usize,
+    pub executor_id: String,
+    /// Unset while the task didn't finish
+    pub successful: Option<bool>,
+    pub error: Option<String>,
+    pub launch_time: u64,
+    pub start_exec_time: u64,
+    pub end_exec_time: u64,
+    pub metrics: BTreeMap<String, u64>,
+}
+
+impl JobHistory {
+    /// Replay the events of a job, none if it has no completion event
+    pub fn replay(events: &[HistoryEvent]) -> Option<Self> {
+        let mut job = JobHistory::default();
+        let mut stages: BTreeMap<usize, StageHistory> = BTreeMap::new();
+        // index of each task in the tasks of its stage
+        let mut tasks: HashMap<usize, (usize, usize)> = HashMap::new();
+        let mut completed = false;
+        for event in events {
+            match event.clone() {
+                HistoryEvent::JobSubmitted {
+                    job_id,
+                    job_name,
+                    queued_at,
+                    submitted_at,
+                } => {
+                    job.job_id = job_id;
+                    job.job_name = job_name;
+                    job.queued_at = queued_at;
+                    job.submitted_at = submitted_at;
+                }
+                HistoryEvent::StagePlanned {
+                    stage_id,
+                    partitions,
+                    output_links,
+                    plan,
+                    ..
+                } => {
+                    stages.insert(
+                        stage_id,
+                        StageHistory {
+                            stage_id,
+                            partitions,
+                            output_links,
+                            plan,
+                            tasks: vec![],
+                        },
+                    );
+                }
+                HistoryEvent::TaskStarted {
+                    stage_id,
+                    partition_id,
+                    task_id,
+                    task_attempt,
+                    executor_id,
+                    ..
+                } => {
+                    let stage = stages.entry(stage_id).or_insert_with(|| StageHistory {
+                        stage_id,
+                        ..Default::default()
+                    });
+                    tasks.insert(task_id, (stage_id, stage.tasks.len()));
+                    stage.tasks.push(TaskHistory {
+                        task_id,
+                        partition_id,
+                        task_attempt,
+                        executor_id,
+                        ..Default::default()
+                    });
+                }
+                HistoryEvent::TaskFinished {
+                    stage_id,
+                    partition_id,
+                    task_id,
+                    executor_id,
+                    successful,
+                    error,
+                    launch_time,
+                    start_exec_time,
+                    end_exec_time,
+                    metrics,
+                    ..
+                } => {
+                    let stage = stages.entry(stage_id).or_insert_with(|| StageHistory {
+                        stage_id,
+                        ..Default::default()
+                    });
+                    // tasks whose start wasn't logged, e.g. as they were launched by
+                    // another scheduler, are added once they finish
+                    let index = match tasks.get(&task_id) {
+                        Some((_, index)) => *index,
+                        None => {
+                            stage.tasks.push(TaskHistory {
+                                task_id,
+                                partition_id,
+                                executor_id,
+                                ..Default::default()
+                            });
+                            stage.tasks.len() - 1
+                        }
+                    };
+                    let task = &mut stage.tasks[index];
+                    task.successful = Some(successful);
+                    task.error = error;
+                    task.launch_time = launch_time;
+                    task.start_exec_time = start_exec_time;
+                    task.end_exec_time = end_exec_time;
+                    task.metrics = metrics;
+                }
+                HistoryEvent::JobCompleted {
+                    job_id,
+                    job_name,
+                    successful,
+                    error,
+                    queued_at,
+                    started_at,
+                    ended_at,
+                } => {
+                    completed = true;
+                    job.job_id = job_id;
+                    job.job_name = job_name;
+                    job.successful = successful;
+                    job.error = error;
+                    job.queued_at = queued_at;
+                    job.started_at = started_at;
+                    job.ended_at = ended_at;
+                }
+                HistoryEvent::ExecutorRegistered { .. }
+                | HistoryEvent::ExecutorLost { .. } => {}
+            }
+        }
+        job.stages = stages.into_values().collect();
+        completed.then_some(job)
+    }
+}
+
+/// Decode JSON lines of events
+pub fn decode_events(bytes: &[u8]) -> Result<Vec<HistoryEvent>> {
+    bytes
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            serde_json::from_slice(line)
+                .map_err(|e| BallistaError::General(format!("Invalid event: {e}")))
+        })
+        .collect()
+}
+
+/// Reads the event log written by the scheduler
+#[derive(Clone)]
+pub struct EventLogReader {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl EventLogReader {
+    /// Create a reader of the log written under `prefix` of `store`
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: Path) -> Self {
+        Self { store, prefix }
+    }
+
+    /// Create a reader of the event log configured for the scheduler, if any
+    pub fn from_config(config: &SchedulerConfig) -> Option<Self> {
+        let store = match (&config.override_event_log_store, &config.event_log_dir) {
+            (Some(store), _) => store.clone(),
+            (None, Some(_)) => Arc::new(object_store::local::LocalFileSystem::new()),
+            (None, None) => return None,
+        };
+        let prefix = config
+            .event_log_dir
+            .as_deref()
+            .map(Path::from)
+            .unwrap_or_default();
+        Some(Self::new(store, prefix))
+    }
+
+    /// IDs of the jobs in the log
+    pub async fn job_ids(&self) -> Result<Vec<String>> {
+        let objects: Vec<_> = self
+            .store
+            .list(Some(&self.prefix.child(JOBS_DIR)))
+            .try_collect()
+            .await
+            .map_err(DataFusionError::ObjectStore)?;
+        let mut job_ids: Vec<String> = objects
+            .iter()
+            .filter_map(|object| {
+                object
+                    .location
+                    .filename()?
+                    .strip_suffix(".json")
+                    .map(str::to_owned)
+            })
+            .collect();
+        job_ids.sort();
+        Ok(job_ids)
+    }
+
+    /// The events of a job, none if it isn't in the log
+    pub async fn job_events(&self, job_id: &str) -> Result<Option<Vec<HistoryEvent>>> {
+        let path = self.prefix.child(JOBS_DIR).child(format!("{job_id}.json"));
+        match self.store.get(&path).await {
+            Ok(result) => {
+                let bytes = result.bytes().await.map_err(DataFusionError::ObjectStore)?;
+                Ok(Some(decode_events(&bytes)?))
+            }
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(DataFusionError::ObjectStore(e).into()),
+        }
+    }
+
+    /// The history of a job, none if it isn't in the log
+    pub async fn job(&self, job_id: &str) -> Result<Option<JobHistory>> {
+        Ok(self
+            .job_events(job_id)
+            .await?
+            .and_then(|events| JobHistory::replay(&events)))
+    }
+
+    /// The events of the executors, in the order they happened
+    pub async fn executor_events(&self) -> Result<Vec<HistoryEvent>> {
+        let mut objects: Vec<_> = self
+            .store
+            .list(Some(&self.prefix.child(EXECUTORS_DIR)))
+            .try_collect()
+            .await
+            .map_err(DataFusionError::ObjectStore)?;
+        objects.sort_by(|a, b| a.location.cmp(&b.location));
+        let mut events = vec![];
+        for object in objects {
+            let bytes = self
+                .store
+                .get(&object.location)
+                .await
+                .map_err(DataFusionError::ObjectStore)?
+                .bytes()
+                .await
+                .map_err(DataFusionError::ObjectStore)?;
+            events.extend(decode_events(&bytes)?);
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::event_log::EventLog;
+    use ballista_core::serde::protobuf::{
+        job_status, task_status, JobStatus, SuccessfulJob, SuccessfulTask, TaskStatus,
+    };
+    use ballista_core::serde::scheduler::{ExecutorMetadata, ExecutorSpecification};
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn replay_job_history() -> Result<()> {
+        let store = Arc::new(InMemory::new());
+        let log = EventLog::new(store.clone(), Path::from("history"));
+        let reader = EventLogReader::new(store, Path::from("history"));
+
+        log.task_statuses(
+            "executor-1",
+            &[TaskStatus {
+                task_id: 3,
+                job_id: "job".to_owned(),
+                stage_id: 1,
+                partition_id: 2,
+                launch_time: 10,
+                end_exec_time: 20,
+                status: Some(task_status::Status::Successful(SuccessfulTask::default())),
+                ..Default::default()
+            }],
+        );
+        log.job_completed(&JobStatus {
+            job_id: "job".to_owned(),
+            job_name: "name".to_owned(),
+            status: Some(job_status::Status::Successful(SuccessfulJob {
+                queued_at: 1,
+                started_at: 2,
+                ended_at: 30,
+                ..Default::default()
+            })),
+        })
+        .await?;
+        log.executor_registered(&ExecutorMetadata {
+            id: "executor-1".to_owned(),
+            host: "localhost".to_owned(),
+            port: 50051,
+            grpc_port: 50052,
+            specification: ExecutorSpecification { task_slots: 4 },
+            version: String::new(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+        })
+        .await?;
+        log.executor_lost("executor-1", None).await?;
+
+        assert_eq!(vec!["job".to_owned()], reader.job_ids().await?);
+        let job = reader.job("job").await?.expect("job history");
+        assert!(job.successful);
+        assert_eq!("name", job.job_name);
+        assert_eq!(30, job.ended_at);
+        assert_eq!(1, job.stages.len());
+        let task = &job.stages[0].tasks[0];
+        assert_eq!(
+            (3, 2, Some(true)),
+            (task.task_id, task.partition_id, task.successful)
+        );
+        assert_eq!("executor-1", task.executor_id);
+        assert!(reader.job("other").await?.is_none());
+
+        let executor_events = reader.executor_events().await?;
+        assert_eq!(2, executor_events.len());
+        assert!(matches!(
+            executor_events[1],
+            HistoryEvent::ExecutorLost { .. }
+        ));
+
+        Ok(())
+    }
+}
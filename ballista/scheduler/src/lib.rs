@@ -16,21 +16,32 @@
 // under the License.
 
 #![doc = include_str ! ("../README.md")]
+pub mod access_control;
 #[cfg(feature = "rest-api")]
 pub mod api;
+pub mod auth;
 pub mod cluster;
 pub mod config;
+pub mod data_masking;
+pub mod deterministic;
 pub mod display;
+pub mod executor_pools;
+pub mod history;
 pub mod metrics;
 pub mod planner;
+pub mod query_authorization;
+pub mod resource_profiles;
+pub mod scheduler_builder;
 pub mod scheduler_process;
 pub mod scheduler_server;
 pub mod standalone;
 pub mod state;
+pub mod substrait;
 
 #[cfg(feature = "flight-sql")]
 pub mod flight_sql;
 #[cfg(test)]
 pub mod test_utils;
 
+pub use scheduler_builder::SchedulerBuilder;
 pub use scheduler_server::SessionBuilder;
@@ -0,0 +1,420 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cluster-wide metrics, sampled periodically by the scheduler. The latest sample is
+//! exported through the [SchedulerMetricsCollector](crate::metrics::SchedulerMetricsCollector),
+//! and the latest samples are kept as a time series which sessions of the scheduler
+//! can query as the `ballista.cluster_metrics` table.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ballista_core::serde::protobuf::{operator_metric, task_status, TaskStatus};
+use datafusion::arrow::array::{Float64Array, TimestampMillisecondArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::Session;
+use datafusion::catalog_common::MemorySchemaProvider;
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::Result;
+use datafusion::logical_expr::Expr;
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionContext;
+use parking_lot::Mutex;
+
+use crate::scheduler_server::timestamp_millis;
+
+/// Name of the schema the `cluster_metrics` table is registered in
+pub const CLUSTER_METRICS_SCHEMA: &str = "ballista";
+
+/// Name of the table of the samples of cluster-wide metrics
+pub const CLUSTER_METRICS_TABLE: &str = "cluster_metrics";
+
+/// Name of the metric of the bytes read by the scans of a task
+const BYTES_SCANNED_METRIC: &str = "bytes_scanned";
+
+/// A sample of the cluster-wide metrics. Rates are averaged over the interval since
+/// the previous sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterMetricsSample {
+    /// Timestamp of the sample in milliseconds
+    pub timestamp: u64,
+    /// Number of jobs running
+    pub active_jobs: u64,
+    /// Number of jobs queued, waiting to be planned
+    pub queued_jobs: u64,
+    /// Number of alive executors
+    pub executors: u64,
+    /// Total number of task slots of the alive executors
+    pub task_slots: u64,
+    /// Number of tasks completed successfully per second
+    pub completed_tasks_per_second: f64,
+    /// Number of tasks failed per second
+    pub failed_tasks_per_second: f64,
+    /// Number of bytes read by the scans of successful tasks per second
+    pub bytes_scanned_per_second: f64,
+}
+
+/// Counters of the task statuses received since the previous sample, and the
+/// retained samples of the cluster-wide metrics. Clones share the same state.
+#[derive(Clone)]
+pub struct ClusterMetrics {
+    inner: Arc<ClusterMetricsInner>,
+}
+
+struct ClusterMetricsInner {
+    retention_count: usize,
+    completed_tasks: AtomicU64,
+    failed_tasks: AtomicU64,
+    bytes_scanned: AtomicU64,
+    last_sampled_at: Mutex<u64>,
+    samples: Mutex<VecDeque<ClusterMetricsSample>>,
+}
+
+impl ClusterMetrics {
+    /// Create cluster metrics keeping the latest `retention_count` samples
+    pub fn new(retention_count: usize) -> Self {
+        Self {
+            inner: Arc::new(ClusterMetricsInner {
+                retention_count,
+                completed_tasks: AtomicU64::new(0),
+                failed_tasks: AtomicU64::new(0),
+                bytes_scanned: AtomicU64::new(0),
+                last_sampled_at: Mutex::new(timestamp_millis()),
+                samples: Mutex::new(VecDeque::new()),
+            }),
+        }
+    }
+
+    /// Count the finished tasks among the statuses reported by an executor, and the
+    /// bytes scanned by the successful ones
+    pub fn record_task_statuses(&self, statuses: &[TaskStatus]) {
+        for status in statuses {
+            match &status.status {
+                Some(task_status::Status::Successful(_)) => {
+                    self.inner.completed_tasks.fetch_add(1, Ordering::Relaxed);
+                    self.inner
+                        .bytes_scanned
+                        .fetch_add(bytes_scanned(status), Ordering::Relaxed);
+                }
+                Some(task_status::Status::Failed(_)) => {
+                    self.inner.failed_tasks.fetch_add(1, Ordering::Relaxed);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Take a sample of the cluster-wide metrics at `timestamp`, from the given load of
+    /// the cluster and the task statuses recorded since the previous sample
+    pub fn sample(
+        &self,
+        timestamp: u64,
+        active_jobs: usize,
+        queued_jobs: usize,
+        executors: usize,
+        task_slots: usize,
+    ) -> ClusterMetricsSample {
+        let elapsed_seconds = {
+            let mut last_sampled_at = self.inner.last_sampled_at.lock();
+            let elapsed = timestamp.saturating_sub(*last_sampled_at);
+            *last_sampled_at = timestamp;
+            elapsed.max(1) as f64 / 1000.0
+        };
+        let rate = |counter: &AtomicU64| {
+            counter.swap(0, Ordering::Relaxed) as f64 / elapsed_seconds
+        };
+
+        let sample = ClusterMetricsSample {
+            timestamp,
+            active_jobs: active_jobs as u64,
+            queued_jobs: queued_jobs as u64,
+            executors: executors as u64,
+            task_slots: task_slots as u64,
+            completed_tasks_per_second: rate(&self.inner.completed_tasks),
+            failed_tasks_per_second: rate(&self.inner.failed_tasks),
+            bytes_scanned_per_second: rate(&self.inner.bytes_scanned),
+        };
+
+        if self.inner.retention_count > 0 {
+            let mut samples = self.inner.samples.lock();
+            if samples.len() >= self.inner.retention_count {
+                samples.pop_front();
+            }
+            samples.push_back(sample.clone());
+        }
+
+        sample
+    }
+
+    /// The retained samples, oldest first
+    pub fn samples(&self) -> Vec<ClusterMetricsSample> {
+        self.inner.samples.lock().iter().cloned().collect()
+    }
+
+    /// Register the `ballista.cluster_metrics` table in the default catalog of a
+    /// session, creating the `ballista` schema if needed
+    pub fn register_table(&self, ctx: &SessionContext) -> Result<()> {
+        let catalog_name = ctx
+            .state()
+            .config()
+            .options()
+            .catalog
+            .default_catalog
+            .clone();
+        let Some(catalog) = ctx.catalog(&catalog_name) else {
+            return Ok(());
+        };
+        let schema = match catalog.schema(CLUSTER_METRICS_SCHEMA) {
+            Some(schema) => schema,
+            None => {
+                let schema = Arc::new(MemorySchemaProvider::new());
+                catalog.register_schema(CLUSTER_METRICS_SCHEMA, schema.clone())?;
+                schema
+            }
+        };
+        schema.register_table(
+            CLUSTER_METRICS_TABLE.to_owned(),
+            Arc::new(ClusterMetricsTable::new(self.clone())),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Sum of the bytes scanned by the operators of a task
+fn bytes_scanned(status: &TaskStatus) -> u64 {
+    status
+        .metrics
+        .iter()
+        .flat_map(|metrics| metrics.metrics.iter())
+        .filter_map(|metric| match &metric.metric {
+            Some(operator_metric::Metric::Count(count))
+                if count.name == BYTES_SCANNED_METRIC =>
+            {
+                Some(count.value)
+            }
+            _ => None,
+        })
+        .sum()
+}
+
+/// The `ballista.cluster_metrics` table, which reads the samples retained when it is
+/// scanned, so that cached plans of queries over the table see the latest samples
+pub struct ClusterMetricsTable {
+    metrics: ClusterMetrics,
+    schema: SchemaRef,
+}
+
+impl ClusterMetricsTable {
+    pub fn new(metrics: ClusterMetrics) -> Self {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("active_jobs", DataType::UInt64, false),
+            Field::new("queued_jobs", DataType::UInt64, false),
+            Field::new("executors", DataType::UInt64, false),
+            Field::new("task_slots", DataType::UInt64, false),
+            Field::new("completed_tasks_per_second", DataType::Float64, false),
+            Field::new("failed_tasks_per_second", DataType::Float64, false),
+            Field::new("bytes_scanned_per_second", DataType::Float64, false),
+        ]));
+        Self { metrics, schema }
+    }
+
+    fn to_record_batch(&self) -> Result<RecordBatch> {
+        let samples = self.metrics.samples();
+        let u64_column = |value: fn(&ClusterMetricsSample) -> u64| {
+            Arc::new(samples.iter().map(value).collect::<UInt64Array>())
+        };
+        let f64_column = |value: fn(&ClusterMetricsSample) -> f64| {
+            Arc::new(samples.iter().map(value).collect::<Float64Array>())
+        };
+
+        let timestamps = samples
+            .iter()
+            .map(|sample| sample.timestamp as i64)
+            .collect::<Vec<_>>();
+        Ok(RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(
+                    TimestampMillisecondArray::from(timestamps).with_timezone("UTC"),
+                ),
+                u64_column(|sample| sample.active_jobs),
+                u64_column(|sample| sample.queued_jobs),
+                u64_column(|sample| sample.executors),
+                u64_column(|sample| sample.task_slots),
+                f64_column(|sample| sample.completed_tasks_per_second),
+                f64_column(|sample| sample.failed_tasks_per_second),
+                f64_column(|sample| sample.bytes_scanned_per_second),
+            ],
+        )?)
+    }
+}
+
+#[async_trait]
+impl TableProvider for ClusterMetricsTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let batch = self.to_record_batch()?;
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema.clone(),
+            projection.cloned(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::serde::protobuf::{
+        FailedTask, NamedCount, OperatorMetric, OperatorMetricsSet, SuccessfulTask,
+    };
+    use datafusion::assert_batches_eq;
+
+    fn successful_task(bytes_scanned: u64) -> TaskStatus {
+        TaskStatus {
+            status: Some(task_status::Status::Successful(SuccessfulTask::default())),
+            metrics: vec![OperatorMetricsSet {
+                metrics: vec![
+                    OperatorMetric {
+                        metric: Some(operator_metric::Metric::OutputRows(10)),
+                    },
+                    OperatorMetric {
+                        metric: Some(operator_metric::Metric::Count(NamedCount {
+                            name: BYTES_SCANNED_METRIC.to_owned(),
+                            value: bytes_scanned,
+                        })),
+                    },
+                ],
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sample_cluster_metrics() {
+        let metrics = ClusterMetrics::new(2);
+        let start = *metrics.inner.last_sampled_at.lock();
+        metrics.record_task_statuses(&[
+            successful_task(1000),
+            successful_task(3000),
+            TaskStatus {
+                status: Some(task_status::Status::Failed(FailedTask::default())),
+                ..Default::default()
+            },
+        ]);
+
+        let sample = metrics.sample(start + 2000, 3, 1, 2, 8);
+        assert_eq!(
+            ClusterMetricsSample {
+                timestamp: start + 2000,
+                active_jobs: 3,
+                queued_jobs: 1,
+                executors: 2,
+                task_slots: 8,
+                completed_tasks_per_second: 1.0,
+                failed_tasks_per_second: 0.5,
+                bytes_scanned_per_second: 2000.0,
+            },
+            sample
+        );
+
+        // The counters restart from the previous sample
+        let sample = metrics.sample(start + 4000, 0, 0, 2, 8);
+        assert_eq!(0.0, sample.completed_tasks_per_second);
+        assert_eq!(0.0, sample.bytes_scanned_per_second);
+
+        // Only the latest samples are retained
+        metrics.sample(start + 6000, 0, 0, 2, 8);
+        let timestamps = metrics
+            .samples()
+            .iter()
+            .map(|sample| sample.timestamp - start)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![4000, 6000], timestamps);
+    }
+
+    #[tokio::test]
+    async fn query_cluster_metrics_table() -> Result<()> {
+        let metrics = ClusterMetrics::new(10);
+        metrics.record_task_statuses(&[successful_task(4096)]);
+        metrics.sample(1_700_000_000_000, 1, 0, 1, 4);
+
+        let ctx = SessionContext::new();
+        metrics.register_table(&ctx)?;
+        let batches = ctx
+            .sql(
+                "SELECT timestamp, active_jobs, task_slots FROM ballista.cluster_metrics",
+            )
+            .await?
+            .collect()
+            .await?;
+        let expected = [
+            "+----------------------+-------------+------------+",
+            "| timestamp            | active_jobs | task_slots |",
+            "+----------------------+-------------+------------+",
+            "| 2023-11-14T22:13:20Z | 1           | 4          |",
+            "+----------------------+-------------+------------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+
+        // The table reads the samples taken after it was registered
+        metrics.sample(1_700_000_015_000, 2, 0, 1, 4);
+        let batches = ctx
+            .sql("SELECT count(*) FROM ballista.cluster_metrics")
+            .await?
+            .collect()
+            .await?;
+        let expected = [
+            "+----------+",
+            "| count(*) |",
+            "+----------+",
+            "| 2        |",
+            "+----------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+
+        Ok(())
+    }
+}
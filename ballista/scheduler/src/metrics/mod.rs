@@ -15,11 +15,14 @@
 // specific language governing permissions and limitations
 // under the License.
 
+pub mod cluster;
 #[cfg(feature = "prometheus")]
 pub mod prometheus;
 
+use crate::metrics::cluster::ClusterMetricsSample;
 #[cfg(feature = "prometheus")]
 use crate::metrics::prometheus::PrometheusMetricsCollector;
+use crate::state::shadow_execution::ShadowComparison;
 use ballista_core::error::Result;
 use std::sync::Arc;
 
@@ -48,10 +51,26 @@ pub trait SchedulerMetricsCollector: Send + Sync {
     /// Record that job with `job_id` was cancelled.
     fn record_cancelled(&self, job_id: &str);
 
+    /// Record that the plan of a submitted SQL query was found in the plan cache
+    fn record_plan_cache_hit(&self);
+
+    /// Record that the plan of a submitted SQL query was not found in the plan cache
+    fn record_plan_cache_miss(&self);
+
+    /// Record the comparison of a job with its shadow job, once both of them finished
+    fn record_shadow_execution(&self, comparison: &ShadowComparison);
+
     /// Set the current number of pending tasks in scheduler. A pending task is a task that is available
     /// to schedule on an executor but cannot be scheduled because no resources are available.
     fn set_pending_tasks_queue_size(&self, value: u64);
 
+    /// Set the current size in bytes of the results of successful jobs the executors
+    /// retain until the clients release them or they expire
+    fn set_retained_result_bytes(&self, value: u64);
+
+    /// Record the latest sample of the cluster-wide metrics, taken periodically by the scheduler
+    fn record_cluster_metrics(&self, sample: &ClusterMetricsSample);
+
     /// Gather current metric set that should be returned when calling the scheduler's metrics API
     /// Should return a tuple containing the content of the metric set and the content type (e.g. `application/json`, `text/plain`, etc)
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>>;
@@ -67,7 +86,12 @@ impl SchedulerMetricsCollector for NoopMetricsCollector {
     fn record_completed(&self, _job_id: &str, _queued_at: u64, _completed_att: u64) {}
     fn record_failed(&self, _job_id: &str, _queued_at: u64, _failed_at: u64) {}
     fn record_cancelled(&self, _job_id: &str) {}
+    fn record_plan_cache_hit(&self) {}
+    fn record_plan_cache_miss(&self) {}
+    fn record_shadow_execution(&self, _comparison: &ShadowComparison) {}
     fn set_pending_tasks_queue_size(&self, _value: u64) {}
+    fn set_retained_result_bytes(&self, _value: u64) {}
+    fn record_cluster_metrics(&self, _sample: &ClusterMetricsSample) {}
 
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         Ok(None)
@@ -15,7 +15,9 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::metrics::cluster::ClusterMetricsSample;
 use crate::metrics::SchedulerMetricsCollector;
+use crate::state::shadow_execution::ShadowComparison;
 use ballista_core::error::{BallistaError, Result};
 
 use once_cell::sync::OnceCell;
@@ -29,7 +31,7 @@ use std::sync::Arc;
 static COLLECTOR: OnceCell<Arc<dyn SchedulerMetricsCollector>> = OnceCell::new();
 
 /// SchedulerMetricsCollector implementation based on Prometheus. By default this will track
-/// 7 metrics:
+/// 20 metrics:
 /// *job_exec_time_seconds* - Histogram of successful job execution time in seconds
 /// *planning_time_ms* - Histogram of job planning time in milliseconds
 /// *failed* - Counter of failed jobs
@@ -38,6 +40,19 @@ static COLLECTOR: OnceCell<Arc<dyn SchedulerMetricsCollector>> = OnceCell::new()
 /// *job_completed_total* - Counter of completed jobs
 /// *job_submitted_total* - Counter of submitted jobs
 /// *pending_task_queue_size* - Number of pending tasks
+/// *retained_result_bytes* - Size of the results of successful jobs retained by the executors until released or expired
+/// *plan_cache_hit_total* - Counter of SQL queries whose plan was found in the plan cache
+/// *plan_cache_miss_total* - Counter of SQL queries whose plan was not found in the plan cache
+/// *shadow_execution_match_total* - Counter of shadow jobs whose result matches the result of the job they shadow
+/// *shadow_execution_mismatch_total* - Counter of shadow jobs whose result, or failure, differs from the job they shadow
+/// *shadow_execution_runtime_ratio* - Histogram of the runtime of successful shadow jobs relative to the job they shadow
+/// *cluster_active_jobs* - Number of running jobs
+/// *cluster_queued_jobs* - Number of queued jobs
+/// *cluster_executors* - Number of alive executors
+/// *cluster_task_slots* - Number of task slots of the alive executors
+/// *cluster_completed_tasks_per_second* - Number of tasks completed per second
+/// *cluster_failed_tasks_per_second* - Number of tasks failed per second
+/// *cluster_bytes_scanned_per_second* - Number of bytes scanned by tasks per second
 pub struct PrometheusMetricsCollector {
     execution_time: Histogram,
     planning_time: Histogram,
@@ -46,6 +61,19 @@ pub struct PrometheusMetricsCollector {
     completed: Counter,
     submitted: Counter,
     pending_queue_size: Gauge,
+    retained_result_bytes: Gauge,
+    plan_cache_hit: Counter,
+    plan_cache_miss: Counter,
+    shadow_execution_match: Counter,
+    shadow_execution_mismatch: Counter,
+    shadow_execution_runtime_ratio: Histogram,
+    cluster_active_jobs: Gauge,
+    cluster_queued_jobs: Gauge,
+    cluster_executors: Gauge,
+    cluster_task_slots: Gauge,
+    cluster_completed_tasks_per_second: Gauge,
+    cluster_failed_tasks_per_second: Gauge,
+    cluster_bytes_scanned_per_second: Gauge,
 }
 
 impl PrometheusMetricsCollector {
@@ -115,6 +143,124 @@ impl PrometheusMetricsCollector {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
+        let retained_result_bytes = register_gauge_with_registry!(
+            "retained_result_bytes",
+            "Size of the results of successful jobs retained by the executors",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let plan_cache_hit = register_counter_with_registry!(
+            "plan_cache_hit_total",
+            "Counter of SQL queries whose plan was found in the plan cache",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let plan_cache_miss = register_counter_with_registry!(
+            "plan_cache_miss_total",
+            "Counter of SQL queries whose plan was not found in the plan cache",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let shadow_execution_match = register_counter_with_registry!(
+            "shadow_execution_match_total",
+            "Counter of shadow jobs whose result matches the result of the job they shadow",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let shadow_execution_mismatch = register_counter_with_registry!(
+            "shadow_execution_mismatch_total",
+            "Counter of shadow jobs whose result, or failure, differs from the job they shadow",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let shadow_execution_runtime_ratio = register_histogram_with_registry!(
+            "shadow_execution_runtime_ratio",
+            "Histogram of the runtime of successful shadow jobs relative to the job they shadow",
+            vec![0.25_f64, 0.5_f64, 0.8_f64, 1.0_f64, 1.25_f64, 2.0_f64, 4.0_f64],
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let cluster_active_jobs = register_gauge_with_registry!(
+            "cluster_active_jobs",
+            "Number of running jobs",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let cluster_queued_jobs = register_gauge_with_registry!(
+            "cluster_queued_jobs",
+            "Number of queued jobs",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let cluster_executors = register_gauge_with_registry!(
+            "cluster_executors",
+            "Number of alive executors",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let cluster_task_slots = register_gauge_with_registry!(
+            "cluster_task_slots",
+            "Number of task slots of the alive executors",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let cluster_completed_tasks_per_second = register_gauge_with_registry!(
+            "cluster_completed_tasks_per_second",
+            "Number of tasks completed per second",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let cluster_failed_tasks_per_second = register_gauge_with_registry!(
+            "cluster_failed_tasks_per_second",
+            "Number of tasks failed per second",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let cluster_bytes_scanned_per_second = register_gauge_with_registry!(
+            "cluster_bytes_scanned_per_second",
+            "Number of bytes scanned by tasks per second",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
         Ok(Self {
             execution_time,
             planning_time,
@@ -123,6 +269,19 @@ impl PrometheusMetricsCollector {
             completed,
             submitted,
             pending_queue_size,
+            retained_result_bytes,
+            plan_cache_hit,
+            plan_cache_miss,
+            shadow_execution_match,
+            shadow_execution_mismatch,
+            shadow_execution_runtime_ratio,
+            cluster_active_jobs,
+            cluster_queued_jobs,
+            cluster_executors,
+            cluster_task_slots,
+            cluster_completed_tasks_per_second,
+            cluster_failed_tasks_per_second,
+            cluster_bytes_scanned_per_second,
         })
     }
 
@@ -158,10 +317,47 @@ impl SchedulerMetricsCollector for PrometheusMetricsCollector {
         self.cancelled.inc();
     }
 
+    fn record_plan_cache_hit(&self) {
+        self.plan_cache_hit.inc();
+    }
+
+    fn record_plan_cache_miss(&self) {
+        self.plan_cache_miss.inc();
+    }
+
+    fn record_shadow_execution(&self, comparison: &ShadowComparison) {
+        if comparison.results_match() {
+            self.shadow_execution_match.inc();
+            self.shadow_execution_runtime_ratio.observe(
+                comparison.shadow.runtime_ms as f64
+                    / comparison.primary.runtime_ms.max(1) as f64,
+            );
+        } else {
+            self.shadow_execution_mismatch.inc();
+        }
+    }
+
     fn set_pending_tasks_queue_size(&self, value: u64) {
         self.pending_queue_size.set(value as f64);
     }
 
+    fn set_retained_result_bytes(&self, value: u64) {
+        self.retained_result_bytes.set(value as f64);
+    }
+
+    fn record_cluster_metrics(&self, sample: &ClusterMetricsSample) {
+        self.cluster_active_jobs.set(sample.active_jobs as f64);
+        self.cluster_queued_jobs.set(sample.queued_jobs as f64);
+        self.cluster_executors.set(sample.executors as f64);
+        self.cluster_task_slots.set(sample.task_slots as f64);
+        self.cluster_completed_tasks_per_second
+            .set(sample.completed_tasks_per_second);
+        self.cluster_failed_tasks_per_second
+            .set(sample.failed_tasks_per_second);
+        self.cluster_bytes_scanned_per_second
+            .set(sample.bytes_scanned_per_second);
+    }
+
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         let encoder = TextEncoder::new();
 
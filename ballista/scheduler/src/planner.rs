@@ -17,305 +17,66 @@
 
 //! Distributed query execution
 
-use std::collections::HashMap;
-use std::sync::Arc;
-
-use ballista_core::error::{BallistaError, Result};
-use ballista_core::{
-    execution_plans::{ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec},
-    serde::scheduler::PartitionLocation,
-};
-use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
-use datafusion::physical_plan::repartition::RepartitionExec;
-use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
-use datafusion::physical_plan::{
-    with_new_children_if_necessary, ExecutionPlan, Partitioning,
+pub use ballista_core::planner::{
+    attach_runtime_predicates, coalesce_shuffle_partitions, find_unresolved_shuffles,
+    remove_unresolved_shuffles, rollback_resolved_shuffles, DistributedPlanner,
 };
 
-use log::{debug, info};
-
-type PartialQueryStageResult = (Arc<dyn ExecutionPlan>, Vec<Arc<ShuffleWriterExec>>);
-
-pub struct DistributedPlanner {
-    next_stage_id: usize,
-}
-
-impl DistributedPlanner {
-    pub fn new() -> Self {
-        Self { next_stage_id: 0 }
-    }
-}
-
-impl Default for DistributedPlanner {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl DistributedPlanner {
-    /// Returns a vector of ExecutionPlans, where the root node is a [ShuffleWriterExec].
-    /// Plans that depend on the input of other plans will have leaf nodes of type [UnresolvedShuffleExec].
-    /// A [ShuffleWriterExec] is created whenever the partitioning changes.
-    pub fn plan_query_stages<'a>(
-        &'a mut self,
-        job_id: &'a str,
-        execution_plan: Arc<dyn ExecutionPlan>,
-    ) -> Result<Vec<Arc<ShuffleWriterExec>>> {
-        info!("planning query stages for job {}", job_id);
-        let (new_plan, mut stages) =
-            self.plan_query_stages_internal(job_id, execution_plan)?;
-        stages.push(create_shuffle_writer(
-            job_id,
-            self.next_stage_id(),
-            new_plan,
-            None,
-        )?);
-        Ok(stages)
-    }
-
-    /// Returns a potentially modified version of the input execution_plan along with the resulting query stages.
-    /// This function is needed because the input execution_plan might need to be modified, but it might not hold a
-    /// complete query stage (its parent might also belong to the same stage)
-    fn plan_query_stages_internal<'a>(
-        &'a mut self,
-        job_id: &'a str,
-        execution_plan: Arc<dyn ExecutionPlan>,
-    ) -> Result<PartialQueryStageResult> {
-        // recurse down and replace children
-        if execution_plan.children().is_empty() {
-            return Ok((execution_plan, vec![]));
-        }
-
-        let mut stages = vec![];
-        let mut children = vec![];
-        for child in execution_plan.children() {
-            let (new_child, mut child_stages) =
-                self.plan_query_stages_internal(job_id, child.clone())?;
-            children.push(new_child);
-            stages.append(&mut child_stages);
-        }
-
-        if let Some(_coalesce) = execution_plan
-            .as_any()
-            .downcast_ref::<CoalescePartitionsExec>()
-        {
-            let shuffle_writer = create_shuffle_writer(
-                job_id,
-                self.next_stage_id(),
-                children[0].clone(),
-                None,
-            )?;
-            let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
-            stages.push(shuffle_writer);
-            Ok((
-                with_new_children_if_necessary(execution_plan, vec![unresolved_shuffle])?,
-                stages,
-            ))
-        } else if let Some(_sort_preserving_merge) = execution_plan
-            .as_any()
-            .downcast_ref::<SortPreservingMergeExec>(
-        ) {
-            let shuffle_writer = create_shuffle_writer(
-                job_id,
-                self.next_stage_id(),
-                children[0].clone(),
-                None,
-            )?;
-            let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
-            stages.push(shuffle_writer);
-            Ok((
-                with_new_children_if_necessary(execution_plan, vec![unresolved_shuffle])?,
-                stages,
-            ))
-        } else if let Some(repart) =
-            execution_plan.as_any().downcast_ref::<RepartitionExec>()
-        {
-            match repart.properties().output_partitioning() {
-                Partitioning::Hash(_, _) => {
-                    let shuffle_writer = create_shuffle_writer(
-                        job_id,
-                        self.next_stage_id(),
-                        children[0].clone(),
-                        Some(repart.partitioning().to_owned()),
-                    )?;
-                    let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
-                    stages.push(shuffle_writer);
-                    Ok((unresolved_shuffle, stages))
-                }
-                _ => {
-                    // remove any non-hash repartition from the distributed plan
-                    Ok((children[0].clone(), stages))
-                }
-            }
-        } else {
-            Ok((
-                with_new_children_if_necessary(execution_plan, children)?,
-                stages,
-            ))
-        }
-    }
-
-    /// Generate a new stage ID
-    fn next_stage_id(&mut self) -> usize {
-        self.next_stage_id += 1;
-        self.next_stage_id
-    }
-}
-
-fn create_unresolved_shuffle(
-    shuffle_writer: &ShuffleWriterExec,
-) -> Arc<UnresolvedShuffleExec> {
-    Arc::new(UnresolvedShuffleExec::new(
-        shuffle_writer.stage_id(),
-        shuffle_writer.schema(),
-        shuffle_writer
-            .properties()
-            .output_partitioning()
-            .partition_count(),
-    ))
-}
-
-/// Returns the unresolved shuffles in the execution plan
-pub fn find_unresolved_shuffles(
-    plan: &Arc<dyn ExecutionPlan>,
-) -> Result<Vec<UnresolvedShuffleExec>> {
-    if let Some(unresolved_shuffle) =
-        plan.as_any().downcast_ref::<UnresolvedShuffleExec>()
-    {
-        Ok(vec![unresolved_shuffle.clone()])
-    } else {
-        Ok(plan
-            .children()
-            .into_iter()
-            .map(find_unresolved_shuffles)
-            .collect::<Result<Vec<_>>>()?
-            .into_iter()
-            .flatten()
-            .collect())
-    }
-}
-
-pub fn remove_unresolved_shuffles(
-    stage: Arc<dyn ExecutionPlan>,
-    partition_locations: &HashMap<usize, HashMap<usize, Vec<PartitionLocation>>>,
-) -> Result<Arc<dyn ExecutionPlan>> {
-    let mut new_children: Vec<Arc<dyn ExecutionPlan>> = vec![];
-    for child in stage.children() {
-        if let Some(unresolved_shuffle) =
-            child.as_any().downcast_ref::<UnresolvedShuffleExec>()
-        {
-            let mut relevant_locations = vec![];
-            let p = partition_locations
-                .get(&unresolved_shuffle.stage_id)
-                .ok_or_else(|| {
-                    BallistaError::General(
-                        "Missing partition location. Could not remove unresolved shuffles"
-                            .to_owned(),
-                    )
-                })?
-                .clone();
-
-            for i in 0..unresolved_shuffle.output_partition_count {
-                if let Some(x) = p.get(&i) {
-                    relevant_locations.push(x.to_owned());
-                } else {
-                    relevant_locations.push(vec![]);
-                }
-            }
-            debug!(
-                "Creating shuffle reader: {}",
-                relevant_locations
-                    .iter()
-                    .map(|c| c
-                        .iter()
-                        .filter(|l| !l.path.is_empty())
-                        .map(|l| l.path.clone())
-                        .collect::<Vec<_>>()
-                        .join(", "))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            );
-            new_children.push(Arc::new(ShuffleReaderExec::try_new(
-                unresolved_shuffle.stage_id,
-                relevant_locations,
-                unresolved_shuffle.schema().clone(),
-            )?))
-        } else {
-            new_children.push(remove_unresolved_shuffles(
-                child.clone(),
-                partition_locations,
-            )?);
-        }
-    }
-    Ok(with_new_children_if_necessary(stage, new_children)?)
-}
-
-/// Rollback the ShuffleReaderExec to UnresolvedShuffleExec.
-/// Used when the input stages are finished but some partitions are missing due to executor lost.
-/// The entire stage need to be rolled back and rescheduled.
-pub fn rollback_resolved_shuffles(
-    stage: Arc<dyn ExecutionPlan>,
-) -> Result<Arc<dyn ExecutionPlan>> {
-    let mut new_children: Vec<Arc<dyn ExecutionPlan>> = vec![];
-    for child in stage.children() {
-        if let Some(shuffle_reader) = child.as_any().downcast_ref::<ShuffleReaderExec>() {
-            let output_partition_count = shuffle_reader
-                .properties()
-                .output_partitioning()
-                .partition_count();
-            let stage_id = shuffle_reader.stage_id;
-
-            let unresolved_shuffle = Arc::new(UnresolvedShuffleExec::new(
-                stage_id,
-                shuffle_reader.schema(),
-                output_partition_count,
-            ));
-            new_children.push(unresolved_shuffle);
-        } else {
-            new_children.push(rollback_resolved_shuffles(child.clone())?);
-        }
-    }
-    Ok(with_new_children_if_necessary(stage, new_children)?)
-}
-
-fn create_shuffle_writer(
-    job_id: &str,
-    stage_id: usize,
-    plan: Arc<dyn ExecutionPlan>,
-    partitioning: Option<Partitioning>,
-) -> Result<Arc<ShuffleWriterExec>> {
-    Ok(Arc::new(ShuffleWriterExec::try_new(
-        job_id.to_owned(),
-        stage_id,
-        plan,
-        "".to_owned(), // executor will decide on the work_dir path
-        partitioning,
-    )?))
-}
-
 #[cfg(test)]
 mod test {
-    use crate::planner::DistributedPlanner;
-    use crate::test_utils::datafusion_test_context;
+    use crate::planner::{
+        coalesce_shuffle_partitions, find_unresolved_shuffles,
+        remove_unresolved_shuffles, rollback_resolved_shuffles, DistributedPlanner,
+    };
+    use crate::test_utils::{datafusion_test_context, mock_executor};
     use ballista_core::error::BallistaError;
-    use ballista_core::execution_plans::{ShuffleWriterExec, UnresolvedShuffleExec};
+    use ballista_core::execution_plans::{
+        BroadcastExchangeExec, ShuffleReaderExec, ShuffleWriterExec, SplittableScanExec,
+        UnresolvedShuffleExec,
+    };
+    use ballista_core::extension::SessionConfigExt;
+    use ballista_core::planner::{
+        create_job_physical_plan, plan_job_stage, stage_plan_fingerprint, StagePlanCache,
+    };
+    use ballista_core::registry::BallistaFunctionRegistry;
+    use ballista_core::serde::protobuf::{
+        ShuffleReaderPartition, StageInput, TaskPlanFormat,
+    };
+    use ballista_core::serde::scheduler::from_proto::decode_task_plan;
+    use ballista_core::serde::scheduler::{
+        PartitionId, PartitionLocation, PartitionStats,
+    };
     use ballista_core::serde::BallistaCodec;
+    use datafusion::arrow::array::{Int32Array, StringArray};
     use datafusion::arrow::compute::SortOptions;
-    use datafusion::physical_expr::expressions::Column;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+    use datafusion::datasource::MemTable;
+    use datafusion::physical_expr::expressions::{lit, Column};
     use datafusion::physical_plan::aggregates::{AggregateExec, AggregateMode};
     use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
     use datafusion::physical_plan::filter::FilterExec;
-    use datafusion::physical_plan::joins::HashJoinExec;
+    use datafusion::physical_plan::joins::{
+        HashJoinExec, PartitionMode, SortMergeJoinExec,
+    };
     use datafusion::physical_plan::projection::ProjectionExec;
     use datafusion::physical_plan::sorts::sort::SortExec;
     use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
     use datafusion::physical_plan::windows::BoundedWindowAggExec;
-    use datafusion::physical_plan::{displayable, ExecutionPlan};
+    use datafusion::physical_plan::{
+        displayable, ExecutionPlan, ExecutionPlanProperties, PhysicalExpr,
+    };
     use datafusion::physical_plan::{InputOrderMode, Partitioning};
-    use datafusion::prelude::SessionContext;
-    use datafusion_proto::physical_plan::AsExecutionPlan;
+    use datafusion::prelude::{SessionConfig, SessionContext};
+    use datafusion_proto::logical_plan::AsLogicalPlan;
+    use datafusion_proto::physical_plan::to_proto::serialize_physical_expr;
+    use datafusion_proto::physical_plan::{
+        AsExecutionPlan, DefaultPhysicalExtensionCodec,
+    };
     use datafusion_proto::protobuf::LogicalPlanNode;
     use datafusion_proto::protobuf::PhysicalPlanNode;
+    use std::collections::HashMap;
     use std::ops::Deref;
     use std::sync::Arc;
     use uuid::Uuid;
@@ -406,6 +167,11 @@ mod test {
         assert_eq!(unresolved_shuffle.stage_id, 2);
         assert_eq!(unresolved_shuffle.output_partition_count, 2);
 
+        // only the output of the final stage is inlined in the task statuses
+        assert!(!stages[0].is_final_stage());
+        assert!(!stages[1].is_final_stage());
+        assert!(stages[2].is_final_stage());
+
         Ok(())
     }
 
@@ -705,6 +471,313 @@ order by
         Ok(())
     }
 
+    #[tokio::test]
+    async fn distributed_sort_merge_join_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let mut session_state = ctx.state();
+        session_state
+            .config_mut()
+            .options_mut()
+            .optimizer
+            .prefer_hash_join = false;
+
+        let df = ctx
+            .sql(
+                "select l_shipmode, o_orderpriority
+                 from lineitem join orders on l_orderkey = o_orderkey",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new().with_sorted_shuffles(true);
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), plan)?;
+        for (i, stage) in stages.iter().enumerate() {
+            println!("Stage {i}:\n{}", displayable(stage.as_ref()).indent(false));
+        }
+        /*
+            expected result:
+            Stage 0:
+            ShuffleWriterExec: Some(Hash([Column { name: "l_orderkey", index: 0 }], 2))
+              SortExec: expr=[l_orderkey@0 ASC], preserve_partitioning=[true]
+                CsvExec: ...
+
+            Stage 1:
+            ShuffleWriterExec: Some(Hash([Column { name: "o_orderkey", index: 0 }], 2))
+              SortExec: expr=[o_orderkey@0 ASC], preserve_partitioning=[true]
+                CsvExec: ...
+
+            Stage 2:
+            ShuffleWriterExec: None
+              SortMergeJoin: join_type=Inner, on=[(l_orderkey@0, o_orderkey@0)]
+                UnresolvedShuffleExec: sort_order=[l_orderkey@0 ASC]
+                UnresolvedShuffleExec: sort_order=[o_orderkey@0 ASC]
+        */
+
+        assert_eq!(3, stages.len());
+
+        // the map tasks sort their output
+        for stage in &stages[0..2] {
+            assert!(stage.shuffle_output_partitioning().is_some());
+            downcast_exec!(stage.children()[0], SortExec);
+        }
+
+        // the join merges the sorted shuffles rather than sorting them again
+        let mut join = stages[2].children()[0].clone();
+        while let Some(projection) = join.as_any().downcast_ref::<ProjectionExec>() {
+            join = projection.input().clone();
+        }
+        let join = downcast_exec!(join, SortMergeJoinExec);
+        for (input, stage) in join.children().into_iter().zip(&stages) {
+            let shuffle = downcast_exec!(input, UnresolvedShuffleExec);
+            assert_eq!(stage.stage_id(), shuffle.stage_id);
+            assert_eq!(1, shuffle.sort_order.len());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_broadcast_join_plan() -> Result<(), BallistaError> {
+        let ctx = SessionContext::new_with_config(
+            SessionConfig::new_with_ballista()
+                .with_target_partitions(2)
+                .with_collect_statistics(true),
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = |ids: Vec<i32>| {
+            let names: Vec<String> = ids.iter().map(|id| format!("name {id}")).collect();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(ids)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .unwrap()
+        };
+        let small = MemTable::try_new(
+            schema.clone(),
+            vec![vec![batch(vec![1, 2])], vec![batch(vec![3])]],
+        )?;
+        let large = MemTable::try_new(
+            schema.clone(),
+            vec![
+                vec![batch((0..1000).collect())],
+                vec![batch((1000..2000).collect())],
+            ],
+        )?;
+        ctx.register_table("small", Arc::new(small))?;
+        ctx.register_table("large", Arc::new(large))?;
+
+        let df = ctx
+            .sql("select large.name, small.name from large join small on large.id = small.id")
+            .await?;
+        let plan = ctx.state().optimize(df.logical_plan())?;
+        let plan = create_job_physical_plan(ctx.state(), &plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let stages = planner.plan_query_stages("job", plan)?;
+        for (i, stage) in stages.iter().enumerate() {
+            println!("Stage {i}:\n{}", displayable(stage.as_ref()).indent(false));
+        }
+        /*
+            expected result:
+            Stage 0:
+            ShuffleWriterExec: None
+              MemoryExec: partitions=2, partition_sizes=[1, 1]
+
+            Stage 1:
+            ShuffleWriterExec: None
+              ProjectionExec: expr=[name@1 as name, name@0 as name]
+                CoalesceBatchesExec: target_batch_size=8192
+                  HashJoinExec: mode=CollectLeft, join_type=Inner, on=[(id@0, id@0)], projection=[name@1, name@3]
+                    BroadcastExchangeExec: stage_id=1
+                      UnresolvedShuffleExec
+                    MemoryExec: partitions=2, partition_sizes=[1, 1]
+        */
+
+        assert_eq!(2, stages.len());
+
+        // the small side is written once, unpartitioned, and broadcast to both tasks
+        // joining a partition of the large side
+        assert!(stages[0].shuffle_output_partitioning().is_none());
+        let mut join = None;
+        let join_stage: Arc<dyn ExecutionPlan> = stages[1].clone();
+        join_stage.apply(|plan| {
+            if let Some(hash_join) = plan.as_any().downcast_ref::<HashJoinExec>() {
+                join = Some(hash_join);
+                return Ok(TreeNodeRecursion::Stop);
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+        let join = join.expect("the join stage holds a hash join");
+        assert_eq!(PartitionMode::CollectLeft, *join.partition_mode());
+        let broadcast = downcast_exec!(join.left(), BroadcastExchangeExec);
+        assert_eq!(stages[0].stage_id(), broadcast.stage_id());
+        downcast_exec!(broadcast.input(), UnresolvedShuffleExec);
+        assert_eq!(2, join.right().output_partitioning().partition_count());
+
+        // broadcast joins are disabled with a threshold of 0
+        ctx.sql("set ballista.join.broadcast_threshold_bytes = 0")
+            .await?
+            .collect()
+            .await?;
+        let plan = ctx.state().optimize(df.logical_plan())?;
+        let plan = create_job_physical_plan(ctx.state(), &plan).await?;
+        let stages = planner.plan_query_stages("job", plan)?;
+        for stage in stages {
+            let stage: Arc<dyn ExecutionPlan> = stage;
+            stage.apply(|plan| {
+                assert!(!plan.as_any().is::<BroadcastExchangeExec>());
+                Ok(TreeNodeRecursion::Continue)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_splittable_scan_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag",
+            )
+            .await?;
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new().with_splittable_scans(true);
+        let stages = planner.plan_query_stages("job", plan)?;
+        for (i, stage) in stages.iter().enumerate() {
+            println!("Stage {i}:\n{}", displayable(stage.as_ref()).indent(false));
+        }
+        assert_eq!(2, stages.len());
+
+        // only the scan of the first stage is split, the second stage reading a shuffle
+        let mut scans = vec![];
+        for stage in &stages {
+            let stage_plan: Arc<dyn ExecutionPlan> = stage.clone();
+            stage_plan.apply(|plan| {
+                if let Some(scan) = plan.as_any().downcast_ref::<SplittableScanExec>() {
+                    scans.push((
+                        stage.stage_id(),
+                        scan.stage_id(),
+                        scan.partition_files(),
+                    ));
+                }
+                Ok(TreeNodeRecursion::Continue)
+            })?;
+        }
+        assert_eq!(vec![(1, 1, vec![1, 1])], scans);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_pre_aggregated_grouping_sets_plan() -> Result<(), BallistaError>
+    {
+        let ctx = SessionContext::new_with_config(
+            SessionConfig::new_with_ballista()
+                .with_target_partitions(2)
+                .with_ballista_pre_aggregate_grouping_sets(true),
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["a", "b", "a"])),
+            ],
+        )?;
+        let table = MemTable::try_new(schema, vec![vec![batch.clone()], vec![batch]])?;
+        ctx.register_table("t", Arc::new(table))?;
+
+        let df = ctx
+            .sql("select name, id, sum(id) from t group by rollup(name, id)")
+            .await?;
+        let plan = ctx.state().optimize(df.logical_plan())?;
+        let plan = create_job_physical_plan(ctx.state(), &plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let stages = planner.plan_query_stages("job", plan)?;
+        for (i, stage) in stages.iter().enumerate() {
+            println!("Stage {i}:\n{}", displayable(stage.as_ref()).indent(false));
+        }
+        /*
+            expected result:
+            Stage 0:
+            ShuffleWriterExec: Some(Hash([Column { name: "name", index: 0 }, Column { name: "id", index: 1 }], 2))
+              AggregateExec: mode=Partial, gby=[name@1 as name, id@0 as id], aggr=[__grouping_sets_pre_aggregate_0]
+                MemoryExec: partitions=2, partition_sizes=[1, 1]
+
+            Stage 1:
+            ShuffleWriterExec: Some(Hash([Column { name: "name", index: 0 }, Column { name: "id", index: 1 }], 2))
+              AggregateExec: mode=Partial, gby=[(NULL as name, NULL as id), (name@0 as name, NULL as id), (name@0 as name, id@1 as id)], aggr=[sum(t.id)]
+                AggregateExec: mode=FinalPartitioned, gby=[name@0 as name, id@1 as id], aggr=[__grouping_sets_pre_aggregate_0]
+                  CoalesceBatchesExec: target_batch_size=8192
+                    UnresolvedShuffleExec
+
+            Stage 2:
+            ShuffleWriterExec: None
+              AggregateExec: mode=FinalPartitioned, gby=[name@0 as name, id@1 as id], aggr=[sum(t.id)]
+                UnresolvedShuffleExec
+        */
+
+        assert_eq!(3, stages.len());
+
+        // the first stage shuffles a single partial aggregate per group, and the
+        // grouping sets are only expanded by the second one
+        let aggregates = |stage: Arc<dyn ExecutionPlan>| {
+            let mut aggregates = vec![];
+            stage
+                .apply(|plan| {
+                    if let Some(aggregate) = plan.as_any().downcast_ref::<AggregateExec>()
+                    {
+                        aggregates.push((
+                            *aggregate.mode(),
+                            aggregate.group_expr().is_single(),
+                        ));
+                    }
+                    Ok(TreeNodeRecursion::Continue)
+                })
+                .unwrap();
+            aggregates
+        };
+        assert_eq!(
+            aggregates(stages[0].clone()),
+            vec![(AggregateMode::Partial, true)]
+        );
+        assert_eq!(
+            aggregates(stages[1].clone()),
+            vec![
+                (AggregateMode::Partial, false),
+                (AggregateMode::FinalPartitioned, true)
+            ]
+        );
+        assert_eq!(
+            aggregates(stages[2].clone()),
+            vec![(AggregateMode::FinalPartitioned, true)]
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn roundtrip_serde_aggregate() -> Result<(), BallistaError> {
         let ctx = datafusion_test_context("testdata").await?;
@@ -742,6 +815,211 @@ order by
         Ok(())
     }
 
+    #[tokio::test]
+    async fn plan_stage_from_logical_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let state = ctx.state();
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag
+            order by l_returnflag",
+            )
+            .await?;
+        let plan = state.optimize(df.logical_plan())?;
+
+        // the final stage, as planned by the scheduler
+        let physical_plan = create_job_physical_plan(state.clone(), &plan).await?;
+        let stages = DistributedPlanner::for_session(state.config())?
+            .plan_query_stages("job", physical_plan)?;
+        let input_locations =
+            HashMap::from([(2, HashMap::from([(0, vec![]), (1, vec![])]))]);
+        let expected = remove_unresolved_shuffles(stages[2].clone(), &input_locations)?;
+
+        // the final stage, as planned by an executor from the logical plan of the job
+        let codec: BallistaCodec<LogicalPlanNode, PhysicalPlanNode> =
+            BallistaCodec::default();
+        let mut encoded_plan = vec![];
+        LogicalPlanNode::try_from_logical_plan(&plan, codec.logical_extension_codec())?
+            .try_encode(&mut encoded_plan)?;
+        let decode_stage =
+            |inputs: Vec<StageInput>, plan_fingerprint: u64, config: SessionConfig| {
+                let encoded_plan = &encoded_plan;
+                let state = &state;
+                let codec = &codec;
+                async move {
+                    decode_task_plan(
+                        TaskPlanFormat::LogicalPlan,
+                        encoded_plan,
+                        "",
+                        &inputs,
+                        plan_fingerprint,
+                        "job",
+                        3,
+                        &config,
+                        &BallistaFunctionRegistry::default().with_random_seed(0),
+                        state.runtime_env().clone(),
+                        codec,
+                    )
+                    .await
+                }
+            };
+        let input = StageInput {
+            stage_id: 2,
+            partition: vec![ShuffleReaderPartition::default(); 2],
+            runtime_predicates: vec![],
+        };
+        let fingerprint = stage_plan_fingerprint(&expected)?;
+        let stage_plan_cache = Arc::new(StagePlanCache::default());
+        let config = state
+            .config()
+            .clone()
+            .with_extension(stage_plan_cache.clone());
+        let stage =
+            decode_stage(vec![input.clone()], fingerprint, config.clone()).await?;
+        assert_eq!(
+            displayable(expected.as_ref()).indent(false).to_string(),
+            displayable(stage.as_ref()).indent(false).to_string()
+        );
+        assert!(stage_plan_cache.get("job", 3).is_some());
+
+        // the other tasks of the stage resolve the cached stage, with the runtime
+        // predicates of the stage
+        let predicate: Arc<dyn PhysicalExpr> = lit(true);
+        let with_predicates = StageInput {
+            runtime_predicates: vec![serialize_physical_expr(
+                &predicate,
+                &DefaultPhysicalExtensionCodec {},
+            )?],
+            ..input.clone()
+        };
+        let stage = decode_stage(vec![with_predicates], fingerprint, config).await?;
+        let stage = displayable(stage.as_ref()).indent(false).to_string();
+        assert!(stage.contains("runtime_predicates=[true]"), "{stage}");
+
+        // the executor planned the stage differently than the scheduler
+        let result = decode_stage(
+            vec![StageInput {
+                stage_id: 1,
+                partition: vec![],
+                runtime_predicates: vec![],
+            }],
+            0,
+            state.config().clone(),
+        )
+        .await;
+        assert!(result.is_err());
+        let result =
+            decode_stage(vec![input], fingerprint + 1, state.config().clone()).await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn coalesce_small_shuffle_partitions() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        ctx.sql("set datafusion.execution.target_partitions = 4")
+            .await?
+            .collect()
+            .await?;
+        let state = ctx.state();
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag",
+            )
+            .await?;
+        let plan = state.optimize(df.logical_plan())?;
+        let physical_plan = create_job_physical_plan(state.clone(), &plan).await?;
+        let stages = DistributedPlanner::for_session(state.config())?
+            .plan_query_stages("job", physical_plan.clone())?;
+        let final_stage = stages[1].clone();
+
+        // each map task wrote the output partitions of 10, 20, 100 and 5 bytes
+        let executor = mock_executor("executor".to_owned());
+        let location = |map_partition_id, partition_id, num_bytes| PartitionLocation {
+            map_partition_id,
+            partition_id: PartitionId::new("job", 1, partition_id),
+            executor_meta: executor.clone(),
+            partition_stats: PartitionStats::new(Some(1), Some(1), Some(num_bytes)),
+            path: String::new(),
+            inline_data: None,
+            compression: Default::default(),
+            ipc_metadata_version: 0,
+        };
+        let partition_locations = [10, 20, 100, 5]
+            .into_iter()
+            .enumerate()
+            .map(|(partition_id, num_bytes)| {
+                let locations = (0..2)
+                    .map(|map_partition_id| {
+                        location(map_partition_id, partition_id, num_bytes / 2)
+                    })
+                    .collect();
+                (partition_id, locations)
+            })
+            .collect();
+        let input_locations = HashMap::from([(1, partition_locations)]);
+        let resolved = remove_unresolved_shuffles(final_stage.clone(), &input_locations)?;
+
+        let config = state
+            .config()
+            .clone()
+            .with_ballista_coalesce_partitions(true)
+            .with_ballista_target_partition_bytes(32);
+        let coalesced = coalesce_shuffle_partitions(resolved.clone(), &config)?;
+        let mut readers = vec![];
+        coalesced.apply(|plan| {
+            if let Some(reader) = plan.as_any().downcast_ref::<ShuffleReaderExec>() {
+                readers.push(reader.clone());
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+        assert_eq!(1, readers.len());
+        // the first two partitions are coalesced, the larger ones read on their own
+        let coalesced_partitions: Vec<Vec<usize>> = readers[0]
+            .partition
+            .iter()
+            .map(|locations| {
+                locations
+                    .iter()
+                    .map(|location| location.partition_id.partition_id)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            vec![vec![0, 0, 1, 1], vec![2, 2], vec![3, 3]],
+            coalesced_partitions
+        );
+        assert_eq!(4, readers[0].shuffle_partition_count);
+
+        // an executor planning the stage itself coalesces it the same way
+        let planned = plan_job_stage("job", 2, physical_plan, &config, &input_locations)?;
+        assert_eq!(
+            displayable(coalesced.as_ref()).indent(false).to_string(),
+            displayable(planned.as_ref()).indent(false).to_string()
+        );
+
+        // rolling the stage back restores all the partitions of the shuffle
+        let unresolved = rollback_resolved_shuffles(coalesced)?;
+        let unresolved_shuffles = find_unresolved_shuffles(&unresolved)?;
+        assert_eq!(4, unresolved_shuffles[0].output_partition_count);
+
+        // nothing is coalesced unless enabled
+        let not_coalesced = coalesce_shuffle_partitions(resolved, state.config())?;
+        assert_eq!(
+            4,
+            not_coalesced.children()[0]
+                .output_partitioning()
+                .partition_count()
+        );
+
+        Ok(())
+    }
+
     fn roundtrip_operator(
         ctx: &SessionContext,
         plan: Arc<dyn ExecutionPlan>,
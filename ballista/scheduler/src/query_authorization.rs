@@ -0,0 +1,560 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Authorization of the queries submitted to the scheduler.
+//!
+//! Before a query is planned into a job, and before the DDL statements of SQL queries
+//! are executed, the [QueryAuthorizer] of the scheduler is given the caller and the
+//! logical plan of the query, so that it can restrict which callers may submit jobs,
+//! which tables they may read or write, and the resources their jobs may ask for.
+//! [AllowAll] allows all queries, [QueryRules] enforces rules read from a TOML file.
+
+use std::fmt;
+
+use ballista_core::config::BallistaConfig;
+use ballista_core::error::{BallistaError, Result};
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::common::{ResolvedTableReference, TableReference};
+use datafusion::logical_expr::{DdlStatement, LogicalPlan};
+use datafusion::prelude::SessionConfig;
+
+use crate::access_control::Role;
+use crate::auth::Identity;
+
+/// A query to authorize, along with its caller
+#[derive(Debug, Clone, Copy)]
+pub struct QueryRequest<'a> {
+    /// The caller, None for anonymous callers
+    pub identity: Option<&'a Identity>,
+    /// The role of the caller, None if access control is disabled or the caller is
+    /// anonymous
+    pub role: Option<Role>,
+    /// The logical plan of the query, before it is optimized
+    pub plan: &'a LogicalPlan,
+    /// The configuration of the session the query is submitted in
+    pub config: &'a SessionConfig,
+}
+
+impl QueryRequest<'_> {
+    /// The tables read, written, created or dropped by the query, resolved with the
+    /// default catalog and schema of the session
+    pub fn tables(&self) -> Vec<ResolvedTableReference> {
+        let catalog = &self.config.options().catalog;
+        let mut tables: Vec<ResolvedTableReference> = vec![];
+        let mut add_table = |table: &TableReference| {
+            let table = table
+                .clone()
+                .resolve(&catalog.default_catalog, &catalog.default_schema);
+            let seen = tables.iter().any(|t| {
+                t.catalog == table.catalog
+                    && t.schema == table.schema
+                    && t.table == table.table
+            });
+            if !seen {
+                tables.push(table);
+            }
+        };
+
+        // the closure never fails
+        let _ = self.plan.apply_with_subqueries(|plan| {
+            match plan {
+                LogicalPlan::TableScan(scan) => add_table(&scan.table_name),
+                LogicalPlan::Dml(dml) => add_table(&dml.table_name),
+                LogicalPlan::Ddl(ddl) => match ddl {
+                    DdlStatement::CreateExternalTable(create) => add_table(&create.name),
+                    DdlStatement::CreateMemoryTable(create) => add_table(&create.name),
+                    DdlStatement::CreateView(create) => add_table(&create.name),
+                    DdlStatement::DropTable(drop) => add_table(&drop.name),
+                    DdlStatement::DropView(drop) => add_table(&drop.name),
+                    _ => {}
+                },
+                _ => {}
+            }
+            Ok(TreeNodeRecursion::Continue)
+        });
+
+        tables
+    }
+
+    /// Whether the query only reads data, i.e. contains no DDL, DML or COPY statements
+    pub fn is_read_only(&self) -> bool {
+        !self
+            .plan
+            .exists(|plan| {
+                Ok(matches!(
+                    plan,
+                    LogicalPlan::Ddl(_) | LogicalPlan::Dml(_) | LogicalPlan::Copy(_)
+                ))
+            })
+            .unwrap_or(true)
+    }
+}
+
+/// Decides whether the queries submitted to the scheduler are allowed
+pub trait QueryAuthorizer: Send + Sync + fmt::Debug {
+    /// Ok if the query is allowed, otherwise the reason it is rejected
+    fn authorize(&self, query: &QueryRequest<'_>) -> std::result::Result<(), String>;
+}
+
+/// Allows all queries, the default query authorizer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllowAll;
+
+impl QueryAuthorizer for AllowAll {
+    fn authorize(&self, _query: &QueryRequest<'_>) -> std::result::Result<(), String> {
+        Ok(())
+    }
+}
+
+/// A pattern of table names, `[[catalog.]schema.]table` where `*` matches any name.
+/// Patterns with fewer parts match the tables of any schema and catalog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TablePattern {
+    /// Parts of the pattern, the table name last
+    parts: Vec<String>,
+}
+
+impl TablePattern {
+    pub fn try_new(pattern: &str) -> Result<Self> {
+        let parts: Vec<String> = pattern.split('.').map(str::to_owned).collect();
+        if parts.len() > 3 || parts.iter().any(String::is_empty) {
+            return Err(BallistaError::General(format!(
+                "Invalid table pattern {pattern}, expected [[catalog.]schema.]table"
+            )));
+        }
+
+        Ok(Self { parts })
+    }
+
+    pub fn matches(&self, table: &ResolvedTableReference) -> bool {
+        let names = [&*table.catalog, &*table.schema, &*table.table];
+        self.parts
+            .iter()
+            .rev()
+            .zip(names.iter().rev())
+            .all(|(part, name)| part == "*" || part == name)
+    }
+}
+
+impl fmt::Display for TablePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.parts.join("."))
+    }
+}
+
+/// Restrictions of the queries of the principals and roles a rule applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryRule {
+    principals: Vec<String>,
+    roles: Vec<Role>,
+    submit: bool,
+    tables: Option<Vec<TablePattern>>,
+    denied_tables: Vec<TablePattern>,
+    read_only: bool,
+    max_priority: Option<u32>,
+    max_tasks_per_executor: Option<usize>,
+}
+
+impl Default for QueryRule {
+    fn default() -> Self {
+        Self {
+            principals: vec![],
+            roles: vec![],
+            submit: true,
+            tables: None,
+            denied_tables: vec![],
+            read_only: false,
+            max_priority: None,
+            max_tasks_per_executor: None,
+        }
+    }
+}
+
+impl QueryRule {
+    /// A rule applying to all callers, which allows all queries until restricted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only apply the rule to `principals`, and to the roles of [Self::with_roles]
+    pub fn with_principals(mut self, principals: Vec<String>) -> Self {
+        self.principals = principals;
+        self
+    }
+
+    /// Only apply the rule to the principals with one of `roles`, and to the principals
+    /// of [Self::with_principals]
+    pub fn with_roles(mut self, roles: Vec<Role>) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    /// Whether the callers may submit queries at all
+    pub fn with_submit(mut self, submit: bool) -> Self {
+        self.submit = submit;
+        self
+    }
+
+    /// Only allow the queries whose tables all match one of `tables`
+    pub fn with_tables(mut self, tables: Vec<TablePattern>) -> Self {
+        self.tables = Some(tables);
+        self
+    }
+
+    /// Reject the queries of which a table matches one of `tables`
+    pub fn with_denied_tables(mut self, tables: Vec<TablePattern>) -> Self {
+        self.denied_tables = tables;
+        self
+    }
+
+    /// Reject the queries containing DDL, DML or COPY statements
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Reject the jobs whose `ballista.job.priority` is above `max_priority`
+    pub fn with_max_priority(mut self, max_priority: u32) -> Self {
+        self.max_priority = Some(max_priority);
+        self
+    }
+
+    /// Reject the jobs whose `ballista.job.max_tasks_per_executor` is not set, or is
+    /// above `max_tasks`
+    pub fn with_max_tasks_per_executor(mut self, max_tasks: usize) -> Self {
+        self.max_tasks_per_executor = Some(max_tasks);
+        self
+    }
+
+    /// Whether the rule applies to a caller, given as its principal and role. Only the
+    /// rules restricted to no principal or role apply to anonymous callers
+    fn applies_to(&self, caller: Option<(&str, Option<Role>)>) -> bool {
+        if self.principals.is_empty() && self.roles.is_empty() {
+            return true;
+        }
+        let Some((principal, role)) = caller else {
+            return false;
+        };
+
+        self.principals.iter().any(|p| p == principal)
+            || role.is_some_and(|role| self.roles.contains(&role))
+    }
+
+    fn authorize(&self, query: &QueryRequest<'_>) -> std::result::Result<(), String> {
+        if !self.submit {
+            return Err("not allowed to submit queries".to_owned());
+        }
+        if self.read_only && !query.is_read_only() {
+            return Err("only allowed to submit read-only queries".to_owned());
+        }
+        for table in query.tables() {
+            if let Some(pattern) = self.denied_tables.iter().find(|p| p.matches(&table)) {
+                return Err(format!("access to {table} denied by {pattern}"));
+            }
+            if let Some(tables) = &self.tables {
+                if !tables.iter().any(|p| p.matches(&table)) {
+                    return Err(format!("access to {table} not allowed"));
+                }
+            }
+        }
+
+        let config = query
+            .config
+            .options()
+            .extensions
+            .get::<BallistaConfig>()
+            .cloned()
+            .unwrap_or_default();
+        if let Some(max_priority) = self.max_priority {
+            if config.job_priority() > max_priority {
+                return Err(format!(
+                    "job priority {} above the maximum of {max_priority}",
+                    config.job_priority()
+                ));
+            }
+        }
+        if let Some(max_tasks) = self.max_tasks_per_executor {
+            let tasks = config.job_max_tasks_per_executor();
+            if tasks == 0 || tasks > max_tasks {
+                return Err(format!(
+                    "ballista.job.max_tasks_per_executor must be set to at most {max_tasks}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A query rule, as written in a query rules file
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueryRuleSpec {
+    #[serde(default)]
+    principals: Vec<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+    submit: Option<bool>,
+    tables: Option<Vec<String>>,
+    #[serde(default)]
+    denied_tables: Vec<String>,
+    #[serde(default)]
+    read_only: bool,
+    max_priority: Option<u32>,
+    max_tasks_per_executor: Option<usize>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueryRulesSpec {
+    #[serde(default)]
+    rule: Vec<QueryRuleSpec>,
+}
+
+fn table_patterns(patterns: &[String]) -> Result<Vec<TablePattern>> {
+    patterns.iter().map(|p| TablePattern::try_new(p)).collect()
+}
+
+/// Authorizes queries with the first rule applying to their caller, queries to which
+/// no rule applies are allowed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryRules {
+    rules: Vec<QueryRule>,
+}
+
+impl QueryRules {
+    pub fn new(rules: Vec<QueryRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Parse query rules from TOML, of the form
+    ///
+    /// ```toml
+    /// [[rule]]
+    /// # Optional, the rule applies to all callers if neither is set
+    /// principals = ["alice"]
+    /// roles = ["user"]
+    /// # Optional restrictions of the queries of the callers
+    /// submit = true
+    /// tables = ["datafusion.sales.*"]
+    /// denied_tables = ["*.salaries"]
+    /// read_only = true
+    /// max_priority = 5
+    /// max_tasks_per_executor = 8
+    /// ```
+    pub fn try_from_toml(rules: &str) -> Result<Self> {
+        let spec: QueryRulesSpec = toml::from_str(rules)
+            .map_err(|e| BallistaError::General(format!("Invalid query rules: {e}")))?;
+
+        spec.rule
+            .into_iter()
+            .map(|rule| {
+                let roles = rule
+                    .roles
+                    .iter()
+                    .map(|role| {
+                        role.parse().map_err(|e| {
+                            BallistaError::General(format!(
+                                "Invalid role in query rule: {e}"
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<Role>>>()?;
+
+                let mut query_rule = QueryRule::new()
+                    .with_principals(rule.principals)
+                    .with_roles(roles)
+                    .with_submit(rule.submit.unwrap_or(true))
+                    .with_denied_tables(table_patterns(&rule.denied_tables)?)
+                    .with_read_only(rule.read_only);
+                if let Some(tables) = &rule.tables {
+                    query_rule = query_rule.with_tables(table_patterns(tables)?);
+                }
+                if let Some(max_priority) = rule.max_priority {
+                    query_rule = query_rule.with_max_priority(max_priority);
+                }
+                if let Some(max_tasks) = rule.max_tasks_per_executor {
+                    query_rule = query_rule.with_max_tasks_per_executor(max_tasks);
+                }
+                Ok(query_rule)
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Self::new)
+    }
+
+    /// Read query rules from a TOML file, see [Self::try_from_toml]
+    pub fn try_from_file(path: &str) -> Result<Self> {
+        let rules = std::fs::read_to_string(path).map_err(|e| {
+            BallistaError::General(format!("Failed to read query rules {path}: {e}"))
+        })?;
+
+        Self::try_from_toml(&rules)
+    }
+
+    pub fn rules(&self) -> &[QueryRule] {
+        &self.rules
+    }
+}
+
+impl QueryAuthorizer for QueryRules {
+    fn authorize(&self, query: &QueryRequest<'_>) -> std::result::Result<(), String> {
+        let caller = query
+            .identity
+            .map(|identity| (identity.principal.as_str(), query.role));
+
+        match self.rules.iter().find(|rule| rule.applies_to(caller)) {
+            Some(rule) => rule.authorize(query),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::empty::EmptyTable;
+    use datafusion::prelude::{SessionConfig, SessionContext};
+
+    use ballista_core::extension::SessionConfigExt;
+
+    use crate::access_control::Role;
+    use crate::auth::Identity;
+    use crate::query_authorization::{
+        QueryAuthorizer, QueryRequest, QueryRule, QueryRules, TablePattern,
+    };
+
+    async fn authorize(
+        rules: &QueryRules,
+        sql: &str,
+        caller: Option<(&str, Role)>,
+        priority: u32,
+    ) -> std::result::Result<(), String> {
+        let config =
+            SessionConfig::new_with_ballista().with_ballista_job_priority(priority);
+        let ctx = SessionContext::new_with_config(config);
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        ctx.register_table("customers", Arc::new(EmptyTable::new(schema.clone())))
+            .unwrap();
+        ctx.register_table("salaries", Arc::new(EmptyTable::new(schema)))
+            .unwrap();
+
+        let state = ctx.state();
+        let plan = state.create_logical_plan(sql).await.unwrap();
+        let identity = caller.map(|(principal, _)| Identity::new(principal));
+        rules.authorize(&QueryRequest {
+            identity: identity.as_ref(),
+            role: caller.map(|(_, role)| role),
+            plan: &plan,
+            config: state.config(),
+        })
+    }
+
+    #[test]
+    fn test_parse_query_rules() {
+        let rules = QueryRules::try_from_toml(
+            r#"
+            [[rule]]
+            roles = ["User"]
+            denied_tables = ["public.salaries"]
+            max_priority = 2
+
+            [[rule]]
+            submit = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules.rules(),
+            &[
+                QueryRule::new()
+                    .with_roles(vec![Role::User])
+                    .with_denied_tables(vec![
+                        TablePattern::try_new("public.salaries").unwrap()
+                    ])
+                    .with_max_priority(2),
+                QueryRule::new().with_submit(false),
+            ]
+        );
+
+        assert!(QueryRules::try_from_toml("[[rule]]\nroles = [\"root\"]").is_err());
+        assert!(QueryRules::try_from_toml("[[rule]]\ntables = [\"a.b.c.d\"]").is_err());
+        assert!(QueryRules::try_from_toml("[[rule]]\ntable = \"t\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_authorize_queries() {
+        let rules = QueryRules::new(vec![
+            QueryRule::new().with_principals(vec!["alice".to_owned()]),
+            QueryRule::new()
+                .with_roles(vec![Role::User])
+                .with_tables(vec![TablePattern::try_new("*.customers").unwrap()])
+                .with_read_only(true)
+                .with_max_priority(1),
+            QueryRule::new().with_submit(false),
+        ]);
+        let bob = Some(("bob", Role::User));
+
+        assert!(authorize(
+            &rules,
+            "SELECT a FROM salaries",
+            Some(("alice", Role::User)),
+            5
+        )
+        .await
+        .is_ok());
+        assert!(authorize(&rules, "SELECT a FROM customers", bob, 0)
+            .await
+            .is_ok());
+        // tables are found in subqueries
+        let denied = authorize(
+            &rules,
+            "SELECT a FROM customers WHERE a IN (SELECT a FROM salaries)",
+            bob,
+            0,
+        )
+        .await
+        .unwrap_err();
+        assert!(denied.contains("datafusion.public.salaries"), "{denied}");
+        assert!(authorize(
+            &rules,
+            "CREATE VIEW customers_view AS SELECT a FROM customers",
+            bob,
+            0
+        )
+        .await
+        .is_err());
+        assert!(authorize(&rules, "SELECT a FROM customers", bob, 2)
+            .await
+            .is_err());
+
+        // anonymous callers and principals without a rule of their own get the
+        // catch-all rule
+        assert!(authorize(&rules, "SELECT a FROM customers", None, 0)
+            .await
+            .is_err());
+        assert!(authorize(
+            &rules,
+            "SELECT a FROM customers",
+            Some(("carol", Role::Admin)),
+            0
+        )
+        .await
+        .is_err());
+    }
+}
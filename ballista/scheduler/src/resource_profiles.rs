@@ -0,0 +1,335 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Named resource profiles of the scheduler.
+//!
+//! Administrators configure profiles, e.g. `small`, `medium` and `large`, each bundling
+//! the settings the jobs are submitted with: their target partitions, their priority,
+//! which the job scheduling policy shares the slots of the cluster by, the maximum
+//! number of their tasks per executor, and any other session setting such as memory
+//! hints. Sessions pick a profile with `ballista.job.resource_profile` rather than
+//! tuning the settings one by one.
+
+use std::collections::BTreeMap;
+
+use ballista_core::config::{
+    redact_settings, BALLISTA_JOB_MAX_TASKS_PER_EXECUTOR, BALLISTA_JOB_PRIORITY,
+    BALLISTA_JOB_RESOURCE_PROFILE,
+};
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::extension::SessionConfigExt;
+use ballista_core::serde::protobuf::KeyValuePair;
+use datafusion::prelude::SessionConfig;
+
+const TARGET_PARTITIONS: &str = "datafusion.execution.target_partitions";
+
+/// The settings of the jobs submitted with a resource profile
+#[derive(Clone, PartialEq, Eq)]
+pub struct ResourceProfile {
+    name: String,
+    settings: BTreeMap<String, String>,
+}
+
+impl std::fmt::Debug for ResourceProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let settings: BTreeMap<String, String> = redact_settings(&self.settings);
+        f.debug_struct("ResourceProfile")
+            .field("name", &self.name)
+            .field("settings", &settings)
+            .finish()
+    }
+}
+
+impl ResourceProfile {
+    /// A profile named `name`, without settings
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            settings: BTreeMap::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The number of partitions the jobs are planned with, and so of the partitions
+    /// of their shuffles
+    pub fn with_target_partitions(self, target_partitions: usize) -> Self {
+        self.with_setting(TARGET_PARTITIONS, target_partitions.to_string())
+    }
+
+    /// The `ballista.job.priority` of the jobs
+    pub fn with_priority(self, priority: u32) -> Self {
+        self.with_setting(BALLISTA_JOB_PRIORITY, priority.to_string())
+    }
+
+    /// The `ballista.job.max_tasks_per_executor` of the jobs
+    pub fn with_max_tasks_per_executor(self, max_tasks: usize) -> Self {
+        self.with_setting(BALLISTA_JOB_MAX_TASKS_PER_EXECUTOR, max_tasks.to_string())
+    }
+
+    /// Any other session setting of the jobs
+    pub fn with_setting(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.settings.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn settings(&self) -> &BTreeMap<String, String> {
+        &self.settings
+    }
+
+    /// Check that the settings of the profile are valid session settings
+    fn validate(&self) -> Result<()> {
+        let mut config = SessionConfig::new_with_ballista();
+        for (key, value) in &self.settings {
+            if key == BALLISTA_JOB_RESOURCE_PROFILE {
+                return Err(BallistaError::General(format!(
+                    "Resource profile {} can't set {key}",
+                    self.name
+                )));
+            }
+            config.options_mut().set(key, value).map_err(|e| {
+                BallistaError::General(format!(
+                    "Invalid setting {key} of resource profile {}: {e}",
+                    self.name
+                ))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// A resource profile, as written in a resource profiles file
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ResourceProfileSpec {
+    name: String,
+    target_partitions: Option<usize>,
+    priority: Option<u32>,
+    max_tasks_per_executor: Option<usize>,
+    #[serde(default)]
+    settings: BTreeMap<String, String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ResourceProfilesSpec {
+    #[serde(default)]
+    profile: Vec<ResourceProfileSpec>,
+}
+
+/// The resource profiles of the scheduler, none by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceProfiles {
+    profiles: Vec<ResourceProfile>,
+}
+
+impl ResourceProfiles {
+    pub fn try_new(profiles: Vec<ResourceProfile>) -> Result<Self> {
+        for (i, profile) in profiles.iter().enumerate() {
+            if profiles[..i].iter().any(|p| p.name == profile.name) {
+                return Err(BallistaError::General(format!(
+                    "Duplicate resource profile {}",
+                    profile.name
+                )));
+            }
+            profile.validate()?;
+        }
+        Ok(Self { profiles })
+    }
+
+    /// Parse resource profiles from TOML, of the form
+    ///
+    /// ```toml
+    /// [[profile]]
+    /// name = "small"
+    /// # Optional settings of the jobs submitted with the profile
+    /// target_partitions = 4
+    /// priority = 0
+    /// max_tasks_per_executor = 2
+    /// # Any other session settings
+    /// settings = { "datafusion.execution.batch_size" = "4096" }
+    /// ```
+    pub fn try_from_toml(profiles: &str) -> Result<Self> {
+        let spec: ResourceProfilesSpec = toml::from_str(profiles).map_err(|e| {
+            BallistaError::General(format!("Invalid resource profiles: {e}"))
+        })?;
+
+        let profiles = spec
+            .profile
+            .into_iter()
+            .map(|spec| {
+                let mut profile = ResourceProfile::new(spec.name);
+                profile.settings = spec.settings;
+                if let Some(target_partitions) = spec.target_partitions {
+                    profile = profile.with_target_partitions(target_partitions);
+                }
+                if let Some(priority) = spec.priority {
+                    profile = profile.with_priority(priority);
+                }
+                if let Some(max_tasks) = spec.max_tasks_per_executor {
+                    profile = profile.with_max_tasks_per_executor(max_tasks);
+                }
+                profile
+            })
+            .collect();
+        Self::try_new(profiles)
+    }
+
+    /// Read resource profiles from a TOML file, see [Self::try_from_toml]
+    pub fn try_from_file(path: &str) -> Result<Self> {
+        let profiles = std::fs::read_to_string(path).map_err(|e| {
+            BallistaError::General(format!(
+                "Failed to read resource profiles {path}: {e}"
+            ))
+        })?;
+
+        Self::try_from_toml(&profiles)
+    }
+
+    pub fn profiles(&self) -> &[ResourceProfile] {
+        &self.profiles
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&ResourceProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// The session `settings` expanded with those of the resource profile they name
+    /// with `ballista.job.resource_profile`, if any. The settings of the profile come
+    /// last, so that they take precedence over those of the session, which clients send
+    /// along with the default values of the settings they didn't change
+    pub fn expand_settings(
+        &self,
+        settings: &[KeyValuePair],
+    ) -> Result<Vec<KeyValuePair>> {
+        let name = settings
+            .iter()
+            .rev()
+            .find(|setting| setting.key == BALLISTA_JOB_RESOURCE_PROFILE)
+            .map(|setting| setting.value.as_str())
+            .unwrap_or_default();
+        if name.is_empty() {
+            return Ok(settings.to_vec());
+        }
+        let profile = self.profile(name).ok_or_else(|| {
+            let names: Vec<&str> = self.profiles.iter().map(|p| p.name()).collect();
+            BallistaError::General(format!(
+                "Unknown resource profile {name}, expected one of [{}]",
+                names.join(", ")
+            ))
+        })?;
+
+        Ok(settings
+            .iter()
+            .cloned()
+            .chain(profile.settings.iter().map(|(key, value)| KeyValuePair {
+                key: key.clone(),
+                value: value.clone(),
+            }))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ballista_core::config::BALLISTA_JOB_RESOURCE_PROFILE;
+    use ballista_core::extension::{SessionConfigExt, SessionConfigHelperExt};
+    use ballista_core::serde::protobuf::KeyValuePair;
+    use datafusion::prelude::SessionConfig;
+
+    use crate::resource_profiles::{ResourceProfile, ResourceProfiles};
+
+    fn setting(key: &str, value: &str) -> KeyValuePair {
+        KeyValuePair {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse_resource_profiles() {
+        let profiles = ResourceProfiles::try_from_toml(
+            r#"
+            [[profile]]
+            name = "small"
+            target_partitions = 4
+            max_tasks_per_executor = 2
+
+            [[profile]]
+            name = "large"
+            priority = 5
+            settings = { "datafusion.execution.batch_size" = "16384" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            profiles.profiles(),
+            &[
+                ResourceProfile::new("small")
+                    .with_target_partitions(4)
+                    .with_max_tasks_per_executor(2),
+                ResourceProfile::new("large")
+                    .with_priority(5)
+                    .with_setting("datafusion.execution.batch_size", "16384"),
+            ]
+        );
+
+        assert!(ResourceProfiles::try_from_toml("[[profile]]\nsize = 1").is_err());
+        assert!(ResourceProfiles::try_from_toml(
+            "[[profile]]\nname = \"a\"\n[[profile]]\nname = \"a\""
+        )
+        .is_err());
+        assert!(ResourceProfiles::try_from_toml(
+            "[[profile]]\nname = \"a\"\nsettings = { \"ballista.job.unknown\" = \"1\" }"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_expand_settings() {
+        let profiles = ResourceProfiles::try_new(vec![ResourceProfile::new("small")
+            .with_target_partitions(4)
+            .with_priority(1)])
+        .unwrap();
+
+        let settings = vec![setting("ballista.job.priority", "3")];
+        assert_eq!(settings, profiles.expand_settings(&settings).unwrap());
+
+        let settings = vec![
+            setting(BALLISTA_JOB_RESOURCE_PROFILE, "small"),
+            setting("ballista.job.priority", "3"),
+        ];
+        let config = SessionConfig::new_with_ballista()
+            .update_from_key_value_pair(&profiles.expand_settings(&settings).unwrap());
+        assert_eq!(4, config.target_partitions());
+        // the settings of the profile take precedence over those of the session
+        assert_eq!(1, config.ballista_job_priority());
+        assert_eq!(
+            Some("small".to_owned()),
+            config.ballista_job_resource_profile()
+        );
+
+        let settings = vec![setting(BALLISTA_JOB_RESOURCE_PROFILE, "huge")];
+        assert!(profiles.expand_settings(&settings).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Typed API to embed the scheduler in an application, configuring it in code rather
+//! than with the command line flags of the `ballista-scheduler` binary.
+//!
+//! # Stability
+//!
+//! The methods of [SchedulerBuilder] are stable: they are only deprecated, never
+//! changed or removed, within a major version. The settings without a method of their
+//! own are reached with [SchedulerBuilder::with_config], which exposes the fields of
+//! [SchedulerConfig]. Those follow the `ballista-scheduler` flags, and may be added,
+//! renamed or removed in any release.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::Result;
+use ballista_core::config::TaskSchedulingPolicy;
+use ballista_core::security::GrpcSecurityConfig;
+use ballista_core::ConfigProducer;
+use datafusion_proto::logical_plan::LogicalExtensionCodec;
+use datafusion_proto::physical_plan::PhysicalExtensionCodec;
+
+use crate::auth::AuthProvider;
+use crate::cluster::job_scheduling::JobSchedulingPolicy;
+use crate::cluster::{BallistaCluster, ClusterEventPublisher};
+use crate::config::{SchedulerConfig, TaskDistributionPolicy};
+use crate::query_authorization::QueryAuthorizer;
+use crate::scheduler_process::start_server;
+use crate::SessionBuilder;
+
+/// Builds and serves a scheduler embedded in an application.
+///
+/// ```no_run
+/// # async fn serve() -> anyhow::Result<()> {
+/// use ballista_core::config::TaskSchedulingPolicy;
+/// use ballista_scheduler::SchedulerBuilder;
+///
+/// SchedulerBuilder::new()
+///     .with_port(50050)
+///     .with_task_scheduling_policy(TaskSchedulingPolicy::PushStaged)
+///     .serve()
+///     .await
+/// # }
+/// ```
+///
+/// See the [module documentation](self) for the stability of the API.
+pub struct SchedulerBuilder {
+    config: SchedulerConfig,
+    bind_host: IpAddr,
+    cluster: Option<BallistaCluster>,
+}
+
+impl Default for SchedulerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulerBuilder {
+    /// A scheduler with the default settings of the `ballista-scheduler` binary,
+    /// listening on all the interfaces
+    pub fn new() -> Self {
+        Self {
+            config: SchedulerConfig::default(),
+            bind_host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            cluster: None,
+        }
+    }
+
+    /// Replace the settings given so far with `config`, e.g. parsed from the flags
+    /// of the `ballista-scheduler` binary, before refining them with the other
+    /// methods of the builder.
+    ///
+    /// # Stability
+    ///
+    /// The fields of [SchedulerConfig] may change in any release.
+    pub fn with_config(mut self, config: SchedulerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// The settings of the scheduler.
+    ///
+    /// # Stability
+    ///
+    /// The fields of [SchedulerConfig] may change in any release.
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// The namespace of the scheduler, which the schedulers sharing a state backend
+    /// must have in common
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.config = self.config.with_namespace(namespace);
+        self
+    }
+
+    /// The address the scheduler listens on, all the interfaces by default
+    pub fn with_bind_host(mut self, bind_host: IpAddr) -> Self {
+        self.bind_host = bind_host;
+        self
+    }
+
+    /// The host name the executors and the other schedulers reach the scheduler at
+    pub fn with_external_host(mut self, external_host: impl Into<String>) -> Self {
+        self.config = self.config.with_hostname(external_host);
+        self
+    }
+
+    /// The port the scheduler listens on
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.config = self.config.with_port(port);
+        self
+    }
+
+    /// The state backend of the scheduler, rather than the one created from the
+    /// cluster storage of its settings. The cluster carries its own session builder
+    /// and config producer, and ignores those given to the builder
+    pub fn with_cluster(mut self, cluster: BallistaCluster) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    /// Whether the scheduler pushes the tasks to the executors, or the executors poll
+    /// the scheduler for tasks
+    pub fn with_task_scheduling_policy(mut self, policy: TaskSchedulingPolicy) -> Self {
+        self.config = self.config.with_scheduler_policy(policy);
+        self
+    }
+
+    /// How the task slots of the cluster are shared between the running jobs
+    pub fn with_job_scheduling_policy(
+        mut self,
+        policy: Arc<dyn JobSchedulingPolicy>,
+    ) -> Self {
+        self.config = self.config.with_job_scheduling_policy(policy);
+        self
+    }
+
+    /// How the tasks are distributed to the task slots of the executors
+    pub fn with_task_distribution(mut self, policy: TaskDistributionPolicy) -> Self {
+        self.config = self.config.with_task_distribution(policy);
+        self
+    }
+
+    /// Publish the events of the cluster, e.g. executors registering, to `publisher`
+    pub fn with_cluster_event_publisher(
+        mut self,
+        publisher: Arc<dyn ClusterEventPublisher>,
+    ) -> Self {
+        self.config.override_cluster_event_publisher = Some(publisher);
+        self
+    }
+
+    /// Authenticate the users of the clients with `auth_provider`
+    pub fn with_auth_provider(mut self, auth_provider: Arc<dyn AuthProvider>) -> Self {
+        self.config = self.config.with_auth_provider(auth_provider);
+        self
+    }
+
+    /// Authorize the queries of the users with `query_authorizer`
+    pub fn with_query_authorizer(
+        mut self,
+        query_authorizer: Arc<dyn QueryAuthorizer>,
+    ) -> Self {
+        self.config = self.config.with_query_authorizer(query_authorizer);
+        self
+    }
+
+    /// Secure the connections to the scheduler with the TLS and auth token of
+    /// `grpc_security`
+    pub fn with_grpc_security(mut self, grpc_security: GrpcSecurityConfig) -> Self {
+        self.config = self.config.with_grpc_security(grpc_security);
+        self
+    }
+
+    /// The codec of the extensions of the logical plans, which the clients must share
+    pub fn with_logical_codec(mut self, codec: Arc<dyn LogicalExtensionCodec>) -> Self {
+        self.config.override_logical_codec = Some(codec);
+        self
+    }
+
+    /// The codec of the extensions of the physical plans, which the executors must
+    /// share
+    pub fn with_physical_codec(mut self, codec: Arc<dyn PhysicalExtensionCodec>) -> Self {
+        self.config.override_physical_codec = Some(codec);
+        self
+    }
+
+    /// Build the session state of the sessions with `session_builder`
+    pub fn with_session_builder(mut self, session_builder: SessionBuilder) -> Self {
+        self.config.override_session_builder = Some(session_builder);
+        self
+    }
+
+    /// Produce the session config of the sessions with `config_producer`
+    pub fn with_config_producer(mut self, config_producer: ConfigProducer) -> Self {
+        self.config.override_config_producer = Some(config_producer);
+        self
+    }
+
+    /// The address the scheduler listens on
+    pub fn bind_address(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_host, self.config.bind_port)
+    }
+
+    /// Serve the scheduler until it fails or, with a leader election, loses its
+    /// leadership
+    pub async fn serve(self) -> Result<()> {
+        let addr = self.bind_address();
+        let cluster = match self.cluster {
+            Some(cluster) => cluster,
+            None => BallistaCluster::new_from_config(&self.config).await?,
+        };
+        start_server(cluster, addr, Arc::new(self.config)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::sync::Arc;
+
+    use ballista_core::config::TaskSchedulingPolicy;
+    use ballista_core::serde::BallistaLogicalExtensionCodec;
+
+    use crate::cluster::job_scheduling::PriorityJobScheduling;
+    use crate::config::{SchedulerConfig, TaskDistributionPolicy};
+    use crate::SchedulerBuilder;
+
+    #[test]
+    fn test_scheduler_builder() {
+        let builder = SchedulerBuilder::new()
+            .with_config(SchedulerConfig::default().with_namespace("ignored"))
+            .with_namespace("embedded")
+            .with_bind_host(IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .with_external_host("scheduler")
+            .with_port(50051)
+            .with_task_scheduling_policy(TaskSchedulingPolicy::PushStaged)
+            .with_job_scheduling_policy(Arc::new(PriorityJobScheduling))
+            .with_task_distribution(TaskDistributionPolicy::RoundRobin)
+            .with_logical_codec(Arc::new(BallistaLogicalExtensionCodec::default()));
+
+        let config = builder.config();
+        assert_eq!("embedded", config.namespace);
+        assert_eq!("scheduler:50051", config.scheduler_name());
+        assert!(config.is_push_staged_scheduling());
+        assert_eq!(
+            "PriorityJobScheduling",
+            format!("{:?}", config.job_scheduling_policy)
+        );
+        assert!(matches!(
+            config.task_distribution,
+            TaskDistributionPolicy::RoundRobin
+        ));
+        assert!(config.override_logical_codec.is_some());
+        assert!(config.override_physical_codec.is_none());
+        assert_eq!(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 50051),
+            builder.bind_address()
+        );
+    }
+}
@@ -15,9 +15,12 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use anyhow::{Error, Result};
+use anyhow::{anyhow, Error, Result};
 #[cfg(feature = "flight-sql")]
 use arrow_flight::flight_service_server::FlightServiceServer;
+use axum::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use axum::Router;
+use ballista_core::security::GrpcSecurityConfig;
 use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpcServer;
 use ballista_core::serde::{
     BallistaCodec, BallistaLogicalExtensionCodec, BallistaPhysicalExtensionCodec,
@@ -25,8 +28,17 @@ use ballista_core::serde::{
 use ballista_core::utils::create_grpc_server;
 use ballista_core::BALLISTA_VERSION;
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
-use log::info;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use log::{debug, info, warn};
 use std::{net::SocketAddr, sync::Arc};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tonic::service::interceptor::InterceptedService;
+use tower_service::Service;
 
 #[cfg(feature = "rest-api")]
 use crate::api::get_routes;
@@ -56,6 +68,14 @@ pub async fn start_server(
 
     let metrics_collector = default_metrics_collector()?;
 
+    // a standby scheduler serves once elected, recovering the jobs of the previous leader,
+    // while a read replica serves the state of the jobs of the leader right away
+    let leader_election = cluster.leader_election().filter(|_| !config.read_replica);
+    if let Some(election) = &leader_election {
+        info!("Waiting to be elected as the leader of the schedulers of the namespace");
+        election.acquire().await;
+    }
+
     let codec_logical = config
         .override_logical_codec
         .clone()
@@ -79,20 +99,39 @@ pub async fn start_server(
 
     scheduler_server.init().await?;
 
-    let config = &scheduler_server.state.config;
+    let config = scheduler_server.state.config.clone();
+    let security = &config.grpc_security;
     let scheduler_grpc_server = SchedulerGrpcServer::new(scheduler_server.clone())
         .max_encoding_message_size(config.grpc_server_max_encoding_message_size as usize)
         .max_decoding_message_size(config.grpc_server_max_decoding_message_size as usize);
 
-    let tonic_builder = create_grpc_server().add_service(scheduler_grpc_server);
+    // TLS is terminated in front of the router below, as it also serves the REST API
+    let tonic_builder = create_grpc_server(&GrpcSecurityConfig::default())?.add_service(
+        InterceptedService::new(scheduler_grpc_server, security.auth_token_validator()),
+    );
 
     #[cfg(feature = "keda-scaler")]
-    let tonic_builder =
-        tonic_builder.add_service(ExternalScalerServer::new(scheduler_server.clone()));
+    let tonic_builder = tonic_builder.add_service(InterceptedService::new(
+        ExternalScalerServer::new(scheduler_server.clone())
+            .max_encoding_message_size(
+                config.grpc_server_max_encoding_message_size as usize,
+            )
+            .max_decoding_message_size(
+                config.grpc_server_max_decoding_message_size as usize,
+            ),
+        security.auth_token_validator(),
+    ));
 
     #[cfg(feature = "flight-sql")]
-    let tonic_builder = tonic_builder.add_service(FlightServiceServer::new(
-        FlightSqlServiceImpl::new(scheduler_server.clone()),
+    let tonic_builder = tonic_builder.add_service(InterceptedService::new(
+        FlightServiceServer::new(FlightSqlServiceImpl::new(scheduler_server.clone()))
+            .max_encoding_message_size(
+                config.grpc_server_max_encoding_message_size as usize,
+            )
+            .max_decoding_message_size(
+                config.grpc_server_max_decoding_message_size as usize,
+            ),
+        security.auth_token_validator(),
     ));
 
     let tonic = tonic_builder.into_service().into_axum_router();
@@ -107,11 +146,94 @@ pub async fn start_server(
     #[cfg(not(feature = "rest-api"))]
     let final_route = tonic.into_make_service_with_connect_info::<SocketAddr>();
 
-    let listener = tokio::net::TcpListener::bind(&addr)
-        .await
-        .map_err(Error::from)?;
+    let listener = TcpListener::bind(&addr).await.map_err(Error::from)?;
+
+    let serve = async {
+        if security.is_server_tls() {
+            serve_tls(listener, final_route, security).await
+        } else {
+            axum::serve(listener, final_route)
+                .await
+                .map_err(Error::from)
+        }
+    };
+    match leader_election {
+        // the scheduler stops once another one may have taken over its jobs
+        Some(election) => tokio::select! {
+            result = serve => result,
+            result = election.keep() => result.map_err(Error::from),
+        },
+        None => serve.await,
+    }
+}
+
+/// Serve the connections accepted by `listener` over TLS, with the certificate of
+/// `security` and, if client authentication is enabled, requiring client certificates
+/// signed by its CA
+async fn serve_tls(
+    listener: TcpListener,
+    make_service: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    security: &GrpcSecurityConfig,
+) -> Result<()> {
+    let acceptor = TlsAcceptor::from(Arc::new(rustls_server_config(security)?));
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept connection: {e}");
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let mut make_service = make_service.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("TLS handshake with {remote_addr} failed: {e}");
+                    return;
+                }
+            };
+            let service = match make_service.call(remote_addr).await {
+                Ok(service) => TowerToHyperService::new(service),
+                Err(e) => match e {},
+            };
+            if let Err(e) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Failed to serve connection from {remote_addr}: {e}");
+            }
+        });
+    }
+}
 
-    axum::serve(listener, final_route)
-        .await
-        .map_err(Error::from)
+fn rustls_server_config(security: &GrpcSecurityConfig) -> Result<ServerConfig> {
+    let (Some(cert), Some(key)) = (security.tls_cert(), security.tls_key()) else {
+        return Err(anyhow!("A TLS certificate and key are required"));
+    };
+    let certs = rustls_pemfile::certs(&mut &cert[..]).collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut &key[..])?
+        .ok_or_else(|| anyhow!("No private key found in the TLS key file"))?;
+
+    let builder = ServerConfig::builder();
+    let builder = match (security.client_auth(), security.tls_ca()) {
+        (true, Some(ca)) => {
+            let mut roots = RootCertStore::empty();
+            for ca_cert in rustls_pemfile::certs(&mut &ca[..]) {
+                roots.add(ca_cert?)?;
+            }
+            builder.with_client_cert_verifier(
+                WebPkiClientVerifier::builder(Arc::new(roots)).build()?,
+            )
+        }
+        (true, None) => {
+            return Err(anyhow!("Client authentication requires a TLS CA"));
+        }
+        (false, _) => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(config)
 }
@@ -50,6 +50,13 @@ pub enum QueryStageSchedulerEvent {
         queued_at: u64,
         completed_at: u64,
     },
+    // For a job served the cached results of the earlier job `cached_job_id`
+    JobServedFromCache {
+        job_id: String,
+        cached_job_id: String,
+        queued_at: u64,
+        completed_at: u64,
+    },
     // For a job fails with its execution graph setting failed
     JobRunningFailed {
         job_id: String,
@@ -64,6 +71,12 @@ pub enum QueryStageSchedulerEvent {
     ReviveOffers,
     ExecutorLost(String, Option<String>),
     CancelTasks(Vec<RunningTaskInfo>),
+    // For the shuffle output of task attempts which lost against another attempt
+    RemoveTaskOutputs {
+        job_id: String,
+        executor_id: String,
+        paths: Vec<String>,
+    },
 }
 
 impl Debug for QueryStageSchedulerEvent {
@@ -98,6 +111,17 @@ impl Debug for QueryStageSchedulerEvent {
                     "JobFinished : job_id={job_id}, queued_at={queued_at}, completed_at={completed_at}.",
                 )
             }
+            QueryStageSchedulerEvent::JobServedFromCache {
+                job_id,
+                cached_job_id,
+                queued_at,
+                completed_at,
+            } => {
+                write!(
+                    f,
+                    "JobServedFromCache : job_id={job_id}, cached_job_id={cached_job_id}, queued_at={queued_at}, completed_at={completed_at}.",
+                )
+            }
             QueryStageSchedulerEvent::JobRunningFailed {
                 job_id,
                 fail_message,
@@ -133,6 +157,16 @@ impl Debug for QueryStageSchedulerEvent {
             QueryStageSchedulerEvent::CancelTasks(status) => {
                 write!(f, "CancelTasks : status:[{status:?}].")
             }
+            QueryStageSchedulerEvent::RemoveTaskOutputs {
+                job_id,
+                executor_id,
+                paths,
+            } => {
+                write!(
+                    f,
+                    "RemoveTaskOutputs : job_id={job_id}, executor_id={executor_id}, paths:[{paths:?}]."
+                )
+            }
         }
     }
 }
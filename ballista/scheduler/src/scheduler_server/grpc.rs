@@ -16,45 +16,80 @@
 // under the License.
 
 use axum::extract::ConnectInfo;
-use ballista_core::config::BALLISTA_JOB_NAME;
+use ballista_core::config::{BALLISTA_JOB_NAME, BALLISTA_JOB_PRIORITY};
+use ballista_core::error::BallistaError;
 use ballista_core::extension::SessionConfigHelperExt;
-use ballista_core::serde::protobuf::execute_query_params::{OptionalSessionId, Query};
+use ballista_core::serde::protobuf::execute_query_params::{
+    OptionalPriority, OptionalSessionId, Query,
+};
 use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpc;
+use ballista_core::serde::protobuf::JobEvent;
 use ballista_core::serde::protobuf::{
-    execute_query_failure_result, execute_query_result, AvailableTaskSlots,
-    CancelJobParams, CancelJobResult, CleanJobDataParams, CleanJobDataResult,
+    execute_query_failure_result, execute_query_result, job_status, AvailableTaskSlots,
+    CancelJobParams, CancelJobResult, CancelSlotReservationParams,
+    CancelSlotReservationResult, CleanJobDataParams, CleanJobDataResult,
     CreateSessionParams, CreateSessionResult, ExecuteQueryFailureResult,
     ExecuteQueryParams, ExecuteQueryResult, ExecuteQuerySuccessResult, ExecutorHeartbeat,
-    ExecutorStoppedParams, ExecutorStoppedResult, GetJobStatusParams, GetJobStatusResult,
-    HeartBeatParams, HeartBeatResult, PollWorkParams, PollWorkResult,
-    RegisterExecutorParams, RegisterExecutorResult, RemoveSessionParams,
-    RemoveSessionResult, UpdateSessionParams, UpdateSessionResult,
-    UpdateTaskStatusParams, UpdateTaskStatusResult,
+    ExecutorStoppedParams, ExecutorStoppedResult, GetClusterUtilizationParams,
+    GetClusterUtilizationResult, GetJobMetricsParams, GetJobMetricsResult,
+    GetJobPlanParams, GetJobPlanResult, GetJobStatusParams, GetJobStatusResult,
+    GetResultsParams, GetResultsResult, GetTaskPlanParams, GetTaskPlanResult,
+    HeartBeatParams, HeartBeatResult, KeyValuePair, LeaseScanFileParams,
+    LeaseScanFileResult, ListClusterTablesParams, ListClusterTablesResult,
+    ListSlotReservationsParams, ListSlotReservationsResult, PollWorkParams,
+    PollWorkResult, PurgeJobsParams, PurgeJobsResult, RegisterExecutorParams,
+    RegisterExecutorResult, ReleaseJobResultsParams, ReleaseJobResultsResult,
+    RemoveSessionParams, RemoveSessionResult, ReserveSlotsParams, ReserveSlotsResult,
+    SampleStageParams, SampleStageResult, SubscribeJobEventsParams, UpdateSessionParams,
+    UpdateSessionResult, UpdateTaskStatusParams, UpdateTaskStatusResult,
 };
 use ballista_core::serde::scheduler::ExecutorMetadata;
+use datafusion::error::DataFusionError;
+use datafusion::logical_expr::LogicalPlan;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
+use futures::{Stream, StreamExt};
 use log::{debug, error, info, trace, warn};
+use std::collections::HashMap;
 use std::net::SocketAddr;
 
 use std::ops::Deref;
-
-use crate::cluster::{bind_task_bias, bind_task_round_robin};
-use crate::config::TaskDistributionPolicy;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::access_control::{AccessError, Operation, PRINCIPAL_METADATA_KEY};
+use crate::auth::{RequestAuth, AUTHORIZATION_METADATA_KEY};
+use crate::cluster::job_scheduling::schedule_jobs;
+use crate::cluster::{
+    bind_speculative_tasks, bind_task_bias, bind_task_locality, bind_task_round_robin,
+};
+use crate::config::{ExecutorVersionPolicy, TaskDistributionPolicy};
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tonic::{Request, Response, Status};
 
 use crate::scheduler_server::SchedulerServer;
+use crate::state::executor_manager::ExecutorManager;
+use crate::state::job_events::{is_final, job_event_stream, status_event};
+use crate::state::job_metrics::job_metrics;
+use crate::state::job_results::ResultPage;
+use crate::state::job_retention::ended_at;
+use crate::state::slot_reservations::SlotReservations;
+use crate::state::stage_sample::StageSample;
+use crate::substrait::substrait_to_logical_plan;
 
 #[tonic::async_trait]
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
     for SchedulerServer<T, U>
 {
+    type SubscribeJobEventsStream =
+        Pin<Box<dyn Stream<Item = Result<JobEvent, Status>> + Send>>;
+
     async fn poll_work(
         &self,
         request: Request<PollWorkParams>,
     ) -> Result<Response<PollWorkResult>, Status> {
+        self.ensure_writable().await?;
         if self.state.config.is_push_staged_scheduling() {
             error!("Poll work interface is not supported for push-based task scheduling");
             return Err(tonic::Status::failed_precondition(
@@ -63,13 +98,23 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         }
         let remote_addr = extract_connect_info(&request);
         if let PollWorkParams {
-            metadata: Some(metadata),
+            metadata: Some(mut metadata),
             num_free_slots,
             task_status,
         } = request.into_inner()
         {
+            let registration_token = std::mem::take(&mut metadata.registration_token);
             trace!("Received poll_work request for {:?}", metadata);
+            // Executors are handed tasks with every poll, so they present their token
+            // with every poll
+            authenticate_executor(
+                &self.state.executor_manager,
+                &metadata.id,
+                &registration_token,
+            )?;
             let executor_id = metadata.id.clone();
+            let executor_version = metadata.version.clone();
+            let executor_pool = metadata.pool.clone();
 
             // It's not necessary.
             // It's only for the scheduler to have a picture of the whole executor cluster.
@@ -82,6 +127,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     port: metadata.port as u16,
                     grpc_port: metadata.grpc_port as u16,
                     specification: metadata.specification.unwrap().into(),
+                    version: metadata.version,
+                    functions: metadata.functions.map(|functions| functions.names),
+                    shuffle_formats: metadata.shuffle_formats.map(Into::into),
+                    pool: metadata.pool,
                 };
                 if let Err(e) = self
                     .state
@@ -101,30 +150,78 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                         &executor_id, e
                     );
                     error!("{}", msg);
-                    Status::internal(msg)
+                    e.to_grpc_status_with_message(&msg)
                 })?;
 
+            let executor_manager = &self.state.executor_manager;
+            let settings = executor_manager.executor_settings(&executor_id);
             let mut available_slots = [AvailableTaskSlots {
                 executor_id,
                 slots: num_free_slots,
             }];
-            let available_slots = available_slots.iter_mut().collect();
             let active_jobs = self.state.task_manager.get_running_job_cache();
-            let schedulable_tasks = match self.state.config.task_distribution {
-                TaskDistributionPolicy::Bias => {
-                    bind_task_bias(available_slots, active_jobs, |_| false).await
+            executor_manager.release_job_versions(&active_jobs);
+            let job_policy = executor_manager.job_scheduling_policy(&active_jobs).await;
+            let active_jobs = executor_manager
+                .jobs_for_executor_pool(&active_jobs, &executor_pool)
+                .await;
+            let active_jobs = match self.state.config.executor_version_policy {
+                ExecutorVersionPolicy::Strict => executor_manager
+                    .jobs_for_executor_version(&active_jobs, &executor_version),
+                ExecutorVersionPolicy::Lenient => active_jobs,
+            };
+            let mut jobs =
+                schedule_jobs(&job_policy, &active_jobs, num_free_slots as usize).await;
+            let mut schedulable_tasks = match self.state.config.task_distribution {
+                // an executor polling for tasks is the only one bound to
+                TaskDistributionPolicy::Bias | TaskDistributionPolicy::LoadAware => {
+                    bind_task_bias(
+                        available_slots.iter_mut().collect(),
+                        &mut jobs,
+                        |_| false,
+                    )
+                    .await
                 }
                 TaskDistributionPolicy::RoundRobin => {
-                    bind_task_round_robin(available_slots, active_jobs, |_| false).await
+                    bind_task_round_robin(
+                        available_slots.iter_mut().collect(),
+                        &mut jobs,
+                        |_| false,
+                    )
+                    .await
+                }
+                TaskDistributionPolicy::Locality => {
+                    bind_task_locality(
+                        available_slots.iter_mut().collect(),
+                        &mut jobs,
+                        &HashMap::new(),
+                    )
+                    .await
                 }
                 TaskDistributionPolicy::ConsistentHash{..} => {
                     return Err(Status::unimplemented(
                         "ConsistentHash TaskDistribution is not feasible for pull-based task scheduling"))
                 }
             };
+            let speculation_multiplier = self.state.config.speculation_multiplier;
+            if speculation_multiplier > 0.0 {
+                schedulable_tasks.extend(
+                    bind_speculative_tasks(
+                        available_slots.iter_mut().collect(),
+                        &jobs,
+                        speculation_multiplier,
+                        &executor_manager.executor_hosts().await,
+                    )
+                    .await,
+                );
+            }
+            executor_manager.pin_job_versions(&schedulable_tasks, &executor_version);
 
             let mut tasks = vec![];
-            for (_, task) in schedulable_tasks {
+            for (executor_id, task) in schedulable_tasks {
+                if let Some(event_log) = &self.state.event_log {
+                    event_log.task_started(&executor_id, &task);
+                }
                 match self.state.task_manager.prepare_task_definition(task) {
                     Ok(task_definition) => tasks.push(task_definition),
                     Err(e) => {
@@ -132,7 +229,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     }
                 }
             }
-            Ok(Response::new(PollWorkResult { tasks }))
+            Ok(Response::new(PollWorkResult { tasks, settings }))
         } else {
             warn!("Received invalid executor poll_work request");
             Err(Status::invalid_argument("Missing metadata in request"))
@@ -143,12 +240,19 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<RegisterExecutorParams>,
     ) -> Result<Response<RegisterExecutorResult>, Status> {
+        self.ensure_writable().await?;
         let remote_addr = extract_connect_info(&request);
         if let RegisterExecutorParams {
-            metadata: Some(metadata),
+            metadata: Some(mut metadata),
         } = request.into_inner()
         {
+            let registration_token = std::mem::take(&mut metadata.registration_token);
             info!("Received register executor request for {:?}", metadata);
+            validate_registration_token(
+                &self.state.executor_manager,
+                &metadata.id,
+                &registration_token,
+            )?;
             let metadata = ExecutorMetadata {
                 id: metadata.id,
                 host: metadata
@@ -157,12 +261,16 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 port: metadata.port as u16,
                 grpc_port: metadata.grpc_port as u16,
                 specification: metadata.specification.unwrap().into(),
+                version: metadata.version,
+                functions: metadata.functions.map(|functions| functions.names),
+                shuffle_formats: metadata.shuffle_formats.map(Into::into),
+                pool: metadata.pool,
             };
 
             self.do_register_executor(metadata).await.map_err(|e| {
                 let msg = format!("Fail to do executor registration due to: {e}");
                 error!("{}", msg);
-                Status::internal(msg)
+                e.to_grpc_status_with_message(&msg)
             })?;
 
             Ok(Response::new(RegisterExecutorResult { success: true }))
@@ -176,6 +284,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<HeartBeatParams>,
     ) -> Result<Response<HeartBeatResult>, Status> {
+        self.ensure_writable().await?;
         let remote_addr = extract_connect_info(&request);
         let HeartBeatParams {
             executor_id,
@@ -186,14 +295,30 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         debug!("Received heart beat request for {:?}", executor_id);
 
         // If not registered, do registration first before saving heart beat
-        if let Err(e) = self
+        if self
             .state
             .executor_manager
             .get_executor_metadata(&executor_id)
             .await
+            .is_ok()
         {
-            warn!("Fail to get executor metadata: {}", e);
+            let registration_token = metadata
+                .as_ref()
+                .map(|metadata| metadata.registration_token.as_str())
+                .unwrap_or_default();
+            authenticate_executor(
+                &self.state.executor_manager,
+                &executor_id,
+                registration_token,
+            )?;
+        } else {
+            warn!("Executor {executor_id} is not registered, registering it");
             if let Some(metadata) = metadata {
+                validate_registration_token(
+                    &self.state.executor_manager,
+                    &metadata.id,
+                    &metadata.registration_token,
+                )?;
                 let metadata = ExecutorMetadata {
                     id: metadata.id,
                     host: metadata
@@ -202,12 +327,16 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     port: metadata.port as u16,
                     grpc_port: metadata.grpc_port as u16,
                     specification: metadata.specification.unwrap().into(),
+                    version: metadata.version,
+                    functions: metadata.functions.map(|functions| functions.names),
+                    shuffle_formats: metadata.shuffle_formats.map(Into::into),
+                    pool: metadata.pool,
                 };
 
                 self.do_register_executor(metadata).await.map_err(|e| {
                     let msg = format!("Fail to do executor registration due to: {e}");
                     error!("{}", msg);
-                    Status::internal(msg)
+                    e.to_grpc_status_with_message(&msg)
                 })?;
             } else {
                 return Err(Status::invalid_argument(format!(
@@ -216,6 +345,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             }
         }
 
+        let settings = self.state.executor_manager.executor_settings(&executor_id);
         let executor_heartbeat = ExecutorHeartbeat {
             executor_id,
             timestamp: SystemTime::now()
@@ -233,19 +363,29 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             .map_err(|e| {
                 let msg = format!("Could not save executor heartbeat: {e}");
                 error!("{}", msg);
-                Status::internal(msg)
+                e.to_grpc_status_with_message(&msg)
             })?;
-        Ok(Response::new(HeartBeatResult { reregister: false }))
+        Ok(Response::new(HeartBeatResult {
+            reregister: false,
+            settings,
+        }))
     }
 
     async fn update_task_status(
         &self,
         request: Request<UpdateTaskStatusParams>,
     ) -> Result<Response<UpdateTaskStatusResult>, Status> {
+        self.ensure_writable().await?;
         let UpdateTaskStatusParams {
             executor_id,
             task_status,
+            registration_token,
         } = request.into_inner();
+        authenticate_executor(
+            &self.state.executor_manager,
+            &executor_id,
+            &registration_token,
+        )?;
 
         debug!(
             "Received task status update request for executor {:?}",
@@ -260,7 +400,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     &executor_id, e
                 );
                 error!("{}", msg);
-                Status::internal(msg)
+                e.to_grpc_status_with_message(&msg)
             })?;
 
         Ok(Response::new(UpdateTaskStatusResult { success: true }))
@@ -270,11 +410,16 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<CreateSessionParams>,
     ) -> Result<Response<CreateSessionResult>, Status> {
+        self.ensure_writable().await?;
         let session_params = request.into_inner();
+        let settings = self
+            .state
+            .session_manager
+            .session_settings(&session_params.settings)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         let session_config = self.state.session_manager.produce_config();
-        let session_config =
-            session_config.update_from_key_value_pair(&session_params.settings);
+        let session_config = session_config.update_from_key_value_pair(&settings);
 
         let ctx = self
             .state
@@ -282,7 +427,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             .create_session(&session_config)
             .await
             .map_err(|e| {
-                Status::internal(format!("Failed to create SessionContext: {e:?}"))
+                e.to_grpc_status_with_message(&format!(
+                    "Failed to create SessionContext: {e:?}"
+                ))
             })?;
 
         Ok(Response::new(CreateSessionResult {
@@ -294,19 +441,29 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<UpdateSessionParams>,
     ) -> Result<Response<UpdateSessionResult>, Status> {
+        self.ensure_writable().await?;
         let session_params = request.into_inner();
+        let settings = self
+            .state
+            .session_manager
+            .session_settings(&session_params.settings)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         let session_config = self.state.session_manager.produce_config();
-        let session_config =
-            session_config.update_from_key_value_pair(&session_params.settings);
+        let session_config = session_config.update_from_key_value_pair(&settings);
 
         self.state
             .session_manager
             .update_session(&session_params.session_id, &session_config)
             .await
             .map_err(|e| {
-                Status::internal(format!("Failed to create SessionContext: {e:?}"))
+                e.to_grpc_status_with_message(&format!(
+                    "Failed to create SessionContext: {e:?}"
+                ))
             })?;
+        self.state
+            .plan_cache
+            .bump_session(&session_params.session_id);
 
         Ok(Response::new(UpdateSessionResult { success: true }))
     }
@@ -315,17 +472,21 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<RemoveSessionParams>,
     ) -> Result<Response<RemoveSessionResult>, Status> {
+        self.ensure_writable().await?;
         let session_params = request.into_inner();
         self.state
             .session_manager
             .remove_session(&session_params.session_id)
             .await
             .map_err(|e| {
-                Status::internal(format!(
+                e.to_grpc_status_with_message(&format!(
                     "Failed to remove SessionContext: {e:?} for session {}",
                     session_params.session_id
                 ))
             })?;
+        self.state
+            .plan_cache
+            .remove_session(&session_params.session_id);
 
         Ok(Response::new(RemoveSessionResult { success: true }))
     }
@@ -334,13 +495,29 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<ExecuteQueryParams>,
     ) -> Result<Response<ExecuteQueryResult>, Status> {
+        self.ensure_writable().await?;
+        let identity = self.identify(extract_auth(&request)).await?;
+        self.authorize(identity.as_ref(), Operation::SubmitJob, None)?;
+
         let query_params = request.into_inner();
         if let ExecuteQueryParams {
             query: Some(query),
             optional_session_id,
-            settings,
+            mut settings,
+            optional_priority,
         } = query_params
         {
+            if let Some(OptionalPriority::Priority(priority)) = optional_priority {
+                settings.push(KeyValuePair {
+                    key: BALLISTA_JOB_PRIORITY.to_owned(),
+                    value: priority.to_string(),
+                });
+            }
+            let settings = self
+                .state
+                .session_manager
+                .session_settings(&settings)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
             let job_name = settings
                 .iter()
                 .find(|s| s.key == BALLISTA_JOB_NAME)
@@ -389,7 +566,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                         .create_session(&session_config)
                         .await
                         .map_err(|e| {
-                            Status::internal(format!(
+                            e.to_grpc_status_with_message(&format!(
                                 "Failed to create SessionContext: {e:?}"
                             ))
                         })?;
@@ -400,13 +577,24 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
 
             let plan = match query {
                 Query::LogicalPlan(message) => {
-                    match T::try_decode(message.as_slice()).and_then(|m| {
-                        m.try_into_logical_plan(
-                            session_ctx.deref(),
-                            self.state.codec.logical_extension_codec(),
-                        )
-                    }) {
-                        Ok(plan) => plan,
+                    match T::try_decode(message.as_slice())
+                        .and_then(|m| {
+                            m.try_into_logical_plan(
+                                session_ctx.deref(),
+                                self.state.codec.logical_extension_codec(),
+                            )
+                        })
+                        .map_err(BallistaError::from)
+                        .and_then(|plan| {
+                            match self.state.session_manager.cluster_catalog() {
+                                Some(cluster_catalog) => cluster_catalog.resolve(plan),
+                                None => Ok(plan),
+                            }
+                        }) {
+                        Ok(plan) => {
+                            self.authorize_query(identity.as_ref(), &session_ctx, &plan)?;
+                            plan
+                        }
                         Err(e) => {
                             let msg =
                                 format!("Could not parse logical plan protobuf: {e}");
@@ -421,13 +609,54 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                         }
                     }
                 }
+                Query::SubstraitPlan(plan) => {
+                    match substrait_to_logical_plan(session_ctx.deref(), &plan)
+                        .await
+                        .map_err(BallistaError::from)
+                        .and_then(|plan| {
+                            match self.state.session_manager.cluster_catalog() {
+                                Some(cluster_catalog) => cluster_catalog.resolve(plan),
+                                None => Ok(plan),
+                            }
+                        }) {
+                        Ok(plan) => {
+                            self.authorize_query(identity.as_ref(), &session_ctx, &plan)?;
+                            plan
+                        }
+                        Err(e) => {
+                            let msg = format!("Could not convert Substrait plan: {e}");
+                            error!("{}", msg);
+                            return Ok(Response::new(ExecuteQueryResult {
+                                result: Some(execute_query_result::Result::Failure(
+                                    ExecuteQueryFailureResult {
+                                        failure: Some(execute_query_failure_result::Failure::PlanParsingFailure(msg)),
+                                    },
+                                )),
+                            }));
+                        }
+                    }
+                }
                 Query::Sql(sql) => {
-                    match session_ctx
-                        .sql(&sql)
+                    // authorize the statements before DDL is executed while planning
+                    let authorize = |plan: &LogicalPlan| {
+                        self.authorize_query(identity.as_ref(), &session_ctx, plan)
+                            .map_err(|e| DataFusionError::External(Box::new(e)))
+                    };
+                    match self
+                        .state
+                        .plan_cache
+                        .plan_sql(
+                            session_ctx.deref(),
+                            &sql,
+                            self.metrics_collector(),
+                            &authorize,
+                        )
                         .await
-                        .and_then(|df| df.into_optimized_plan())
                     {
                         Ok(plan) => plan,
+                        Err(DataFusionError::External(e)) if e.is::<AccessError>() => {
+                            return Err(Status::permission_denied(e.to_string()));
+                        }
                         Err(e) => {
                             let msg = format!("Error parsing SQL: {e}");
                             error!("{}", msg);
@@ -443,12 +672,32 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 }
             };
 
+            let plan = match self.mask_plan(identity.as_ref(), &session_ctx, plan) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    let msg = format!("Failed to mask logical plan: {e}");
+                    error!("{}", msg);
+                    return Ok(Response::new(ExecuteQueryResult {
+                        result: Some(execute_query_result::Result::Failure(
+                            ExecuteQueryFailureResult {
+                                failure: Some(execute_query_failure_result::Failure::PlanParsingFailure(msg)),
+                            },
+                        )),
+                    }));
+                }
+            };
+
             debug!(
                 "Decoded logical plan for execution:\n{}",
                 plan.display_indent()
             );
 
             let job_id = self.state.task_manager.generate_job_id();
+            if let Some(identity) = &identity {
+                self.state
+                    .task_manager
+                    .set_job_owner(&job_id, &identity.principal);
+            }
 
             log::trace!("setting job name: {}", job_name);
             self.submit_job(&job_id, &job_name, session_ctx, &plan)
@@ -458,7 +707,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                         format!("Failed to send JobQueued event for {job_id}: {e:?}");
                     error!("{}", msg);
 
-                    Status::internal(msg)
+                    e.to_grpc_status_with_message(&msg)
                 })?;
 
             Ok(Response::new(ExecuteQueryResult {
@@ -475,26 +724,77 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<GetJobStatusParams>,
     ) -> Result<Response<GetJobStatusResult>, Status> {
+        let identity = self.identify(extract_auth(&request)).await?;
         let job_id = request.into_inner().job_id;
         trace!("Received get_job_status request for job {}", job_id);
+        self.authorize(identity.as_ref(), Operation::ViewJob, Some(&job_id))?;
         match self.state.task_manager.get_job_status(&job_id).await {
             Ok(status) => Ok(Response::new(GetJobStatusResult { status })),
             Err(e) => {
                 let msg = format!("Error getting status for job {job_id}: {e:?}");
                 error!("{}", msg);
-                Err(Status::internal(msg))
+                Err(e.to_grpc_status_with_message(&msg))
             }
         }
     }
 
+    async fn subscribe_job_events(
+        &self,
+        request: Request<SubscribeJobEventsParams>,
+    ) -> Result<Response<Self::SubscribeJobEventsStream>, Status> {
+        self.ensure_writable().await?;
+        let identity = self.identify(extract_auth(&request)).await?;
+        let job_id = request.into_inner().job_id;
+        self.authorize(identity.as_ref(), Operation::ViewJob, Some(&job_id))?;
+
+        // subscribe before getting the status of the job, not to miss the events
+        // published in between
+        let task_manager = self.state.task_manager.clone();
+        let receiver = task_manager.job_events().subscribe();
+        let status = match task_manager.get_job_status(&job_id).await {
+            Ok(Some(status)) => status,
+            Ok(None) => {
+                return Err(Status::not_found(format!("Job {job_id} not found")));
+            }
+            Err(e) => {
+                let msg = format!("Error getting status for job {job_id}: {e:?}");
+                error!("{}", msg);
+                return Err(e.to_grpc_status_with_message(&msg));
+            }
+        };
+        debug!("Subscribed to the events of job {job_id}");
+
+        let current = status_event(status);
+        let events = if is_final(&current) {
+            futures::stream::empty().boxed()
+        } else {
+            job_event_stream(job_id, receiver, move |job_id| {
+                let task_manager = task_manager.clone();
+                async move { task_manager.get_job_status(&job_id).await.ok().flatten() }
+            })
+            .boxed()
+        };
+        let stream = futures::stream::once(async { current })
+            .chain(events)
+            .map(Ok);
+        Ok(Response::new(Box::pin(stream)))
+    }
+
     async fn executor_stopped(
         &self,
         request: Request<ExecutorStoppedParams>,
     ) -> Result<Response<ExecutorStoppedResult>, Status> {
+        self.ensure_writable().await?;
         let ExecutorStoppedParams {
             executor_id,
             reason,
+            registration_token,
         } = request.into_inner();
+        authenticate_executor(
+            &self.state.executor_manager,
+            &executor_id,
+            &registration_token,
+        )?;
         info!(
             "Received executor stopped request from Executor {} with reason '{}'",
             executor_id, reason
@@ -504,7 +804,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         let event_sender = self.query_stage_event_loop.get_sender().map_err(|e| {
             let msg = format!("Get query stage event loop error due to {e:?}");
             error!("{}", msg);
-            Status::internal(msg)
+            e.to_grpc_status_with_message(&msg)
         })?;
 
         Self::remove_executor(
@@ -522,22 +822,25 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<CancelJobParams>,
     ) -> Result<Response<CancelJobResult>, Status> {
+        self.ensure_writable().await?;
+        let identity = self.identify(extract_auth(&request)).await?;
         let job_id = request.into_inner().job_id;
         info!("Received cancellation request for job {}", job_id);
+        self.authorize(identity.as_ref(), Operation::CancelJob, Some(&job_id))?;
 
         self.query_stage_event_loop
             .get_sender()
             .map_err(|e| {
                 let msg = format!("Get query stage event loop error due to {e:?}");
                 error!("{}", msg);
-                Status::internal(msg)
+                e.to_grpc_status_with_message(&msg)
             })?
             .post_event(QueryStageSchedulerEvent::JobCancel(job_id))
             .await
             .map_err(|e| {
                 let msg = format!("Post to query stage event loop error due to {e:?}");
                 error!("{}", msg);
-                Status::internal(msg)
+                e.to_grpc_status_with_message(&msg)
             })?;
         Ok(Response::new(CancelJobResult { cancelled: true }))
     }
@@ -546,25 +849,572 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<CleanJobDataParams>,
     ) -> Result<Response<CleanJobDataResult>, Status> {
+        self.ensure_writable().await?;
+        let identity = self.identify(extract_auth(&request)).await?;
         let job_id = request.into_inner().job_id;
         info!("Received clean data request for job {}", job_id);
+        self.authorize(identity.as_ref(), Operation::CancelJob, Some(&job_id))?;
 
         self.query_stage_event_loop
             .get_sender()
             .map_err(|e| {
                 let msg = format!("Get query stage event loop error due to {e:?}");
                 error!("{}", msg);
-                Status::internal(msg)
+                e.to_grpc_status_with_message(&msg)
             })?
             .post_event(QueryStageSchedulerEvent::JobDataClean(job_id))
             .await
             .map_err(|e| {
                 let msg = format!("Post to query stage event loop error due to {e:?}");
                 error!("{}", msg);
-                Status::internal(msg)
+                e.to_grpc_status_with_message(&msg)
             })?;
         Ok(Response::new(CleanJobDataResult {}))
     }
+
+    async fn release_job_results(
+        &self,
+        request: Request<ReleaseJobResultsParams>,
+    ) -> Result<Response<ReleaseJobResultsResult>, Status> {
+        self.ensure_writable().await?;
+        let identity = self.identify(extract_auth(&request)).await?;
+        let job_id = request.into_inner().job_id;
+        info!("Received release_job_results request for job {}", job_id);
+        self.authorize(identity.as_ref(), Operation::CancelJob, Some(&job_id))?;
+
+        let status = self
+            .state
+            .task_manager
+            .get_job_status(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting status for job {job_id}: {e:?}");
+                error!("{}", msg);
+                e.to_grpc_status_with_message(&msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+        if !matches!(status.status, Some(job_status::Status::Successful(_))) {
+            return Err(Status::failed_precondition(format!(
+                "Job {job_id} has not succeeded"
+            )));
+        }
+
+        let released = self.state.release_job_results(&job_id);
+        Ok(Response::new(ReleaseJobResultsResult {
+            released: released.is_some(),
+            released_bytes: released.map(|released| released.bytes).unwrap_or_default(),
+        }))
+    }
+
+    async fn purge_jobs(
+        &self,
+        request: Request<PurgeJobsParams>,
+    ) -> Result<Response<PurgeJobsResult>, Status> {
+        self.ensure_writable().await?;
+        let identity = self.identify(extract_auth(&request)).await?;
+        self.authorize(identity.as_ref(), Operation::PurgeJobs, None)?;
+
+        let PurgeJobsParams {
+            mut job_id,
+            ended_before,
+        } = request.into_inner();
+        info!(
+            "Received purge request for jobs {:?} and jobs ended before {:?}",
+            job_id, ended_before
+        );
+
+        if let Some(ended_before) = ended_before {
+            let completed =
+                self.state
+                    .task_manager
+                    .get_completed_jobs()
+                    .await
+                    .map_err(|e| {
+                        let msg = format!("Failed to get completed jobs due to {e:?}");
+                        error!("{}", msg);
+                        e.to_grpc_status_with_message(&msg)
+                    })?;
+            job_id.extend(
+                completed
+                    .into_iter()
+                    .filter(|job| ended_at(job).is_some_and(|t| t < ended_before))
+                    .map(|job| job.job_id),
+            );
+        }
+        job_id.sort_unstable();
+        job_id.dedup();
+
+        let purged = self.state.purge_jobs(job_id).await.map_err(|e| {
+            let msg = format!("Failed to purge jobs due to {e:?}");
+            error!("{}", msg);
+            e.to_grpc_status_with_message(&msg)
+        })?;
+
+        Ok(Response::new(PurgeJobsResult { job_id: purged }))
+    }
+
+    async fn get_task_plan(
+        &self,
+        request: Request<GetTaskPlanParams>,
+    ) -> Result<Response<GetTaskPlanResult>, Status> {
+        self.ensure_writable().await?;
+        let GetTaskPlanParams {
+            job_id,
+            plan_hash,
+            offset,
+            max_bytes,
+            executor_id,
+            registration_token,
+        } = request.into_inner();
+        authenticate_executor(
+            &self.state.executor_manager,
+            &executor_id,
+            &registration_token,
+        )?;
+        trace!("Received get_task_plan request for plan {plan_hash} of job {job_id}");
+        match self.state.task_manager.get_task_plan(&job_id, &plan_hash) {
+            Some(plan) => {
+                let start = (offset as usize).min(plan.len());
+                let end = if max_bytes == 0 {
+                    plan.len()
+                } else {
+                    start.saturating_add(max_bytes as usize).min(plan.len())
+                };
+                Ok(Response::new(GetTaskPlanResult {
+                    plan: plan[start..end].to_vec(),
+                    total_bytes: plan.len() as u64,
+                }))
+            }
+            None => Err(Status::not_found(format!(
+                "Plan {plan_hash} of job {job_id} not found, the job may have ended"
+            ))),
+        }
+    }
+
+    async fn get_job_plan(
+        &self,
+        request: Request<GetJobPlanParams>,
+    ) -> Result<Response<GetJobPlanResult>, Status> {
+        let identity = self.identify(extract_auth(&request)).await?;
+        let job_id = request.into_inner().job_id;
+        trace!("Received get_job_plan request for job {}", job_id);
+        self.authorize(identity.as_ref(), Operation::ViewJob, Some(&job_id))?;
+
+        let task_manager = &self.state.task_manager;
+        let graph = task_manager
+            .get_job_execution_graph(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting plan of job {job_id}: {e:?}");
+                error!("{}", msg);
+                e.to_grpc_status_with_message(&msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+        // The plans of the stages may still change while the job runs
+        if !matches!(
+            graph.status().status,
+            Some(job_status::Status::Successful(_) | job_status::Status::Failed(_))
+        ) {
+            return Err(Status::failed_precondition(format!(
+                "Job {job_id} is not completed yet"
+            )));
+        }
+        let stages = task_manager.encode_job_plan(&graph).map_err(|e| {
+            let msg = format!("Error encoding plan of job {job_id}: {e:?}");
+            error!("{}", msg);
+            e.to_grpc_status_with_message(&msg)
+        })?;
+
+        Ok(Response::new(GetJobPlanResult { stages }))
+    }
+
+    async fn sample_stage(
+        &self,
+        request: Request<SampleStageParams>,
+    ) -> Result<Response<SampleStageResult>, Status> {
+        let identity = self.identify(extract_auth(&request)).await?;
+        let SampleStageParams {
+            job_id,
+            stage_id,
+            partition_id,
+            limit,
+        } = request.into_inner();
+        trace!(
+            "Received sample_stage request for {limit} rows of stage {stage_id} of job {job_id}"
+        );
+        self.authorize(identity.as_ref(), Operation::ViewJob, Some(&job_id))?;
+
+        let graph = self
+            .state
+            .task_manager
+            .get_job_execution_graph(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting plan of job {job_id}: {e:?}");
+                error!("{}", msg);
+                e.to_grpc_status_with_message(&msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+        let Some(stage) = graph.stages().get(&(stage_id as usize)) else {
+            return Err(Status::not_found(format!(
+                "Stage {stage_id} of job {job_id} not found"
+            )));
+        };
+        match (stage.partitions(), partition_id) {
+            (None, _) => {
+                return Err(Status::failed_precondition(format!(
+                    "Inputs of stage {stage_id} of job {job_id} are not resolved yet"
+                )));
+            }
+            (Some(partitions), Some(partition_id))
+                if partition_id as usize >= partitions =>
+            {
+                return Err(Status::invalid_argument(format!(
+                    "Stage {stage_id} of job {job_id} has {partitions} partitions"
+                )));
+            }
+            _ => {}
+        }
+        if !StageSample::can_sample(stage) {
+            return Err(Status::failed_precondition(format!(
+                "Stage {stage_id} of job {job_id} buffers its rows and can't be sampled"
+            )));
+        }
+        let session_ctx = self
+            .state
+            .session_manager
+            .get_session(graph.session_id())
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting session of job {job_id}: {e:?}");
+                error!("{}", msg);
+                e.to_grpc_status_with_message(&msg)
+            })?;
+
+        let sample = StageSample::of_stage(
+            &graph,
+            stage_id as usize,
+            partition_id.map(|partition_id| partition_id as usize),
+            limit,
+            &session_ctx,
+            self.state.config.grpc_security.clone(),
+        )
+        .await
+        .map_err(|e| {
+            let msg = format!("Error sampling stage {stage_id} of job {job_id}: {e:?}");
+            error!("{}", msg);
+            e.to_grpc_status_with_message(&msg)
+        })?;
+        let data = sample.encode().map_err(|e| {
+            let msg = format!("Error encoding sample of stage {stage_id}: {e:?}");
+            error!("{}", msg);
+            e.to_grpc_status_with_message(&msg)
+        })?;
+
+        Ok(Response::new(SampleStageResult {
+            data,
+            num_rows: sample.num_rows() as u64,
+            partitions: sample.partitions,
+        }))
+    }
+
+    async fn get_job_metrics(
+        &self,
+        request: Request<GetJobMetricsParams>,
+    ) -> Result<Response<GetJobMetricsResult>, Status> {
+        let identity = self.identify(extract_auth(&request)).await?;
+        let job_id = request.into_inner().job_id;
+        trace!("Received get_job_metrics request for job {}", job_id);
+        self.authorize(identity.as_ref(), Operation::ViewJob, Some(&job_id))?;
+
+        let graph = self
+            .state
+            .task_manager
+            .get_job_execution_graph(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting metrics of job {job_id}: {e:?}");
+                error!("{}", msg);
+                e.to_grpc_status_with_message(&msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+
+        Ok(Response::new(job_metrics(&graph)))
+    }
+
+    async fn get_results(
+        &self,
+        request: Request<GetResultsParams>,
+    ) -> Result<Response<GetResultsResult>, Status> {
+        let identity = self.identify(extract_auth(&request)).await?;
+        let GetResultsParams {
+            job_id,
+            offset,
+            max_rows,
+        } = request.into_inner();
+        trace!(
+            "Received get_results request for {max_rows} rows from row {offset} of job {job_id}"
+        );
+        self.authorize(identity.as_ref(), Operation::ViewJob, Some(&job_id))?;
+
+        let status = self
+            .state
+            .task_manager
+            .get_job_status(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting status for job {job_id}: {e:?}");
+                error!("{}", msg);
+                e.to_grpc_status_with_message(&msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+        if !matches!(status.status, Some(job_status::Status::Successful(_))) {
+            return Err(Status::failed_precondition(format!(
+                "Job {job_id} has not succeeded"
+            )));
+        }
+
+        let page = ResultPage::of_job(
+            &status,
+            offset,
+            max_rows,
+            Arc::new(self.state.config.grpc_security.clone()),
+        )
+        .await
+        .map_err(|e| {
+            let msg = format!("Error reading results of job {job_id}: {e:?}");
+            error!("{}", msg);
+            e.to_grpc_status_with_message(&msg)
+        })?;
+        let data = page.encode().map_err(|e| {
+            let msg = format!("Error encoding results of job {job_id}: {e:?}");
+            error!("{}", msg);
+            e.to_grpc_status_with_message(&msg)
+        })?;
+
+        Ok(Response::new(GetResultsResult {
+            data,
+            num_rows: page.num_rows() as u64,
+            end_of_results: page.end_of_results,
+        }))
+    }
+
+    async fn list_cluster_tables(
+        &self,
+        request: Request<ListClusterTablesParams>,
+    ) -> Result<Response<ListClusterTablesResult>, Status> {
+        let identity = self.identify(extract_auth(&request)).await?;
+        trace!("Received list_cluster_tables request");
+        self.authorize(identity.as_ref(), Operation::SubmitJob, None)?;
+
+        let Some(cluster_catalog) = self.state.session_manager.cluster_catalog() else {
+            return Err(Status::failed_precondition(
+                "The cluster catalog is not enabled",
+            ));
+        };
+        let tables = cluster_catalog
+            .tables()
+            .iter()
+            .map(|table| table.try_to_proto())
+            .collect::<datafusion::common::Result<Vec<_>>>()
+            .map_err(|e| {
+                let msg = format!("Error encoding tables of the cluster catalog: {e:?}");
+                error!("{}", msg);
+                BallistaError::from(e).to_grpc_status_with_message(&msg)
+            })?;
+
+        Ok(Response::new(ListClusterTablesResult { tables }))
+    }
+
+    async fn get_cluster_utilization(
+        &self,
+        request: Request<GetClusterUtilizationParams>,
+    ) -> Result<Response<GetClusterUtilizationResult>, Status> {
+        let identity = self.identify(extract_auth(&request)).await?;
+        trace!("Received get_cluster_utilization request");
+        self.authorize(identity.as_ref(), Operation::ViewCluster, None)?;
+
+        let utilization = self.state.cluster_utilization().await;
+        Ok(Response::new(GetClusterUtilizationResult {
+            utilization: Some(utilization.into()),
+        }))
+    }
+
+    async fn reserve_slots(
+        &self,
+        request: Request<ReserveSlotsParams>,
+    ) -> Result<Response<ReserveSlotsResult>, Status> {
+        self.ensure_writable().await?;
+        let identity = self.identify(extract_auth(&request)).await?;
+        self.authorize(identity.as_ref(), Operation::ReserveSlots, None)?;
+
+        let ReserveSlotsParams {
+            reservation_id,
+            slots,
+            ttl_seconds,
+        } = request.into_inner();
+        info!(
+            "Received reserve_slots request for {slots} slots for {ttl_seconds}s with \
+             {reservation_id}"
+        );
+        let reservation = self
+            .state
+            .slot_reservations
+            .reserve(
+                &reservation_id,
+                slots,
+                Duration::from_secs(ttl_seconds),
+                identity
+                    .as_ref()
+                    .map(|identity| identity.principal.as_str()),
+            )
+            .await
+            .map_err(|e| {
+                let msg = format!("Failed to reserve slots with {reservation_id}: {e}");
+                error!("{}", msg);
+                match e {
+                    BallistaError::General(_) => Status::invalid_argument(msg),
+                    _ => e.to_grpc_status_with_message(&msg),
+                }
+            })?;
+
+        Ok(Response::new(ReserveSlotsResult {
+            reservation: Some(reservation),
+        }))
+    }
+
+    async fn cancel_slot_reservation(
+        &self,
+        request: Request<CancelSlotReservationParams>,
+    ) -> Result<Response<CancelSlotReservationResult>, Status> {
+        self.ensure_writable().await?;
+        let identity = self.identify(extract_auth(&request)).await?;
+        self.authorize(identity.as_ref(), Operation::ReserveSlots, None)?;
+
+        let reservation_id = request.into_inner().reservation_id;
+        info!("Received cancel_slot_reservation request for {reservation_id}");
+        let cancelled = self
+            .state
+            .slot_reservations
+            .cancel(&reservation_id)
+            .await
+            .map_err(|e| {
+                let msg =
+                    format!("Failed to cancel slot reservation {reservation_id}: {e}");
+                error!("{}", msg);
+                e.to_grpc_status_with_message(&msg)
+            })?;
+
+        Ok(Response::new(CancelSlotReservationResult { cancelled }))
+    }
+
+    async fn list_slot_reservations(
+        &self,
+        request: Request<ListSlotReservationsParams>,
+    ) -> Result<Response<ListSlotReservationsResult>, Status> {
+        let identity = self.identify(extract_auth(&request)).await?;
+        trace!("Received list_slot_reservations request");
+        self.authorize(identity.as_ref(), Operation::ViewCluster, None)?;
+
+        let reservations = self.state.slot_reservations.list().await.map_err(|e| {
+            let msg = format!("Failed to list slot reservations: {e}");
+            error!("{}", msg);
+            e.to_grpc_status_with_message(&msg)
+        })?;
+        let active_jobs = self.state.task_manager.get_running_job_cache();
+        let reservations = SlotReservations::usage(reservations, &active_jobs).await;
+
+        Ok(Response::new(ListSlotReservationsResult { reservations }))
+    }
+
+    async fn lease_scan_file(
+        &self,
+        request: Request<LeaseScanFileParams>,
+    ) -> Result<Response<LeaseScanFileResult>, Status> {
+        self.ensure_writable().await?;
+        let LeaseScanFileParams {
+            job_id,
+            stage_id,
+            partition_id,
+            position,
+            executor_id,
+            registration_token,
+        } = request.into_inner();
+        authenticate_executor(
+            &self.state.executor_manager,
+            &executor_id,
+            &registration_token,
+        )?;
+        trace!(
+            "Received lease_scan_file request for file {position} of partition \
+             {partition_id} of stage {stage_id} of job {job_id}"
+        );
+        let file = self
+            .state
+            .task_manager
+            .lease_scan_file(
+                &job_id,
+                stage_id as usize,
+                partition_id as usize,
+                position as usize,
+            )
+            .await
+            .map_err(|e| {
+                let msg = format!(
+                    "Error leasing file {position} of partition {partition_id} of stage \
+                     {stage_id} of job {job_id}: {e}"
+                );
+                error!("{}", msg);
+                e.to_grpc_status_with_message(&msg)
+            })?;
+        Ok(Response::new(LeaseScanFileResult {
+            file: file.map(|file| file as u64),
+        }))
+    }
+}
+
+/// Principal of a request, set by an authenticating proxy in front of the scheduler
+fn extract_auth<T>(request: &Request<T>) -> RequestAuth {
+    let metadata = |key| {
+        request
+            .metadata()
+            .get(key)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+
+    RequestAuth {
+        principal: metadata(PRINCIPAL_METADATA_KEY),
+        authorization: metadata(AUTHORIZATION_METADATA_KEY),
+    }
+}
+
+/// Reject executors which don't present an accepted registration token
+fn validate_registration_token(
+    executor_manager: &ExecutorManager,
+    executor_id: &str,
+    token: &str,
+) -> Result<(), Status> {
+    executor_manager
+        .validate_registration_token(executor_id, token)
+        .map_err(|e| {
+            warn!("Rejecting executor: {e}");
+            Status::unauthenticated(e.to_string())
+        })
+}
+
+/// Reject the requests of executors which don't present the token they registered with
+fn authenticate_executor(
+    executor_manager: &ExecutorManager,
+    executor_id: &str,
+    token: &str,
+) -> Result<(), Status> {
+    executor_manager
+        .authenticate_executor(executor_id, token)
+        .map_err(|e| {
+            warn!("Rejecting request of executor: {e}");
+            Status::unauthenticated(e.to_string())
+        })
 }
 
 fn extract_connect_info<T>(request: &Request<T>) -> Option<ConnectInfo<SocketAddr>> {
@@ -581,14 +1431,20 @@ mod test {
 
     use datafusion_proto::protobuf::LogicalPlanNode;
     use datafusion_proto::protobuf::PhysicalPlanNode;
-    use tonic::Request;
+    use tonic::{Code, Request};
 
+    use crate::access_control::{AccessControl, Role, PRINCIPAL_METADATA_KEY};
+    use crate::auth::{AuthProvider, Credentials, Identity, AUTHORIZATION_METADATA_KEY};
     use crate::config::SchedulerConfig;
     use crate::metrics::default_metrics_collector;
+    use ballista_core::build_version;
     use ballista_core::error::BallistaError;
     use ballista_core::serde::protobuf::{
-        executor_status, ExecutorRegistration, ExecutorStatus, ExecutorStoppedParams,
-        HeartBeatParams, PollWorkParams, RegisterExecutorParams,
+        executor_metric, executor_status, CancelJobParams, ExecutorMetric,
+        ExecutorRegistration, ExecutorStatus, ExecutorStoppedParams, GetJobStatusParams,
+        GetTaskPlanParams, HeartBeatParams, LeaseScanFileParams, PollWorkParams,
+        PurgeJobsParams, RegisterExecutorParams, ShuffleDiskUsage,
+        UpdateTaskStatusParams,
     };
     use ballista_core::serde::scheduler::ExecutorSpecification;
     use ballista_core::serde::BallistaCodec;
@@ -619,6 +1475,11 @@ mod test {
             port: 0,
             grpc_port: 0,
             specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+            registration_token: String::new(),
         };
         let request: Request<PollWorkParams> = Request::new(PollWorkParams {
             metadata: Some(exec_meta.clone()),
@@ -686,6 +1547,215 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_poll_work_registration_token() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default()
+            .with_executor_registration_tokens(vec!["s3cr3t".to_owned()]);
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+        let poll_work = |registration_token: &str| {
+            Request::new(PollWorkParams {
+                metadata: Some(ExecutorRegistration {
+                    id: "abc".to_owned(),
+                    host: Some("http://localhost:8080".to_owned()),
+                    port: 0,
+                    grpc_port: 0,
+                    specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+                    version: build_version(),
+                    functions: None,
+                    shuffle_formats: None,
+                    pool: String::new(),
+                    registration_token: registration_token.to_owned(),
+                }),
+                num_free_slots: 1,
+                task_status: vec![],
+            })
+        };
+
+        for token in ["", "other"] {
+            let status = scheduler.poll_work(poll_work(token)).await.unwrap_err();
+            assert_eq!(tonic::Code::Unauthenticated, status.code());
+        }
+        assert!(scheduler
+            .state
+            .executor_manager
+            .get_executor_metadata("abc")
+            .await
+            .is_err());
+
+        scheduler.poll_work(poll_work("s3cr3t")).await?;
+        scheduler
+            .state
+            .executor_manager
+            .get_executor_metadata("abc")
+            .await?;
+
+        // The executor is revoked along with its token
+        let revoked = scheduler
+            .state
+            .executor_manager
+            .rotate_registration_tokens(vec!["n3w".to_owned()])?;
+        assert_eq!(vec!["abc".to_owned()], revoked);
+        assert!(scheduler.poll_work(poll_work("s3cr3t")).await.is_err());
+        scheduler.poll_work(poll_work("n3w")).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_requests_registration_token() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default()
+            .with_executor_registration_tokens(vec!["s3cr3t".to_owned()]);
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+        let registration = |registration_token: &str| ExecutorRegistration {
+            id: "abc".to_owned(),
+            host: Some("http://localhost:8080".to_owned()),
+            port: 0,
+            grpc_port: 0,
+            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+            registration_token: registration_token.to_owned(),
+        };
+        scheduler
+            .poll_work(Request::new(PollWorkParams {
+                metadata: Some(registration("s3cr3t")),
+                num_free_slots: 1,
+                task_status: vec![],
+            }))
+            .await?;
+
+        let update_task_status = |registration_token: &str| {
+            Request::new(UpdateTaskStatusParams {
+                executor_id: "abc".to_owned(),
+                task_status: vec![],
+                registration_token: registration_token.to_owned(),
+            })
+        };
+        let heart_beat = |registration_token: &str| {
+            Request::new(HeartBeatParams {
+                executor_id: "abc".to_owned(),
+                metrics: vec![],
+                status: None,
+                metadata: Some(registration(registration_token)),
+            })
+        };
+        let get_task_plan = |registration_token: &str| {
+            Request::new(GetTaskPlanParams {
+                job_id: "job".to_owned(),
+                plan_hash: "hash".to_owned(),
+                executor_id: "abc".to_owned(),
+                registration_token: registration_token.to_owned(),
+                ..Default::default()
+            })
+        };
+        let lease_scan_file = |registration_token: &str| {
+            Request::new(LeaseScanFileParams {
+                job_id: "job".to_owned(),
+                executor_id: "abc".to_owned(),
+                registration_token: registration_token.to_owned(),
+                ..Default::default()
+            })
+        };
+        let executor_stopped = |registration_token: &str| {
+            Request::new(ExecutorStoppedParams {
+                executor_id: "abc".to_owned(),
+                reason: "test".to_owned(),
+                registration_token: registration_token.to_owned(),
+            })
+        };
+
+        // Registered executors present their token with each of their requests
+        for token in ["", "other"] {
+            let status =
+                SchedulerGrpc::update_task_status(&scheduler, update_task_status(token))
+                    .await
+                    .unwrap_err();
+            assert_eq!(Code::Unauthenticated, status.code());
+            let status = scheduler
+                .heart_beat_from_executor(heart_beat(token))
+                .await
+                .unwrap_err();
+            assert_eq!(Code::Unauthenticated, status.code());
+            let status = scheduler
+                .get_task_plan(get_task_plan(token))
+                .await
+                .unwrap_err();
+            assert_eq!(Code::Unauthenticated, status.code());
+            let status = scheduler
+                .lease_scan_file(lease_scan_file(token))
+                .await
+                .unwrap_err();
+            assert_eq!(Code::Unauthenticated, status.code());
+            let status = scheduler
+                .executor_stopped(executor_stopped(token))
+                .await
+                .unwrap_err();
+            assert_eq!(Code::Unauthenticated, status.code());
+        }
+
+        SchedulerGrpc::update_task_status(&scheduler, update_task_status("s3cr3t"))
+            .await?;
+        scheduler
+            .heart_beat_from_executor(heart_beat("s3cr3t"))
+            .await?;
+        let status = scheduler
+            .get_task_plan(get_task_plan("s3cr3t"))
+            .await
+            .unwrap_err();
+        assert_eq!(Code::NotFound, status.code());
+
+        // An accepted token doesn't let another process act as the executor
+        scheduler
+            .state
+            .executor_manager
+            .rotate_registration_tokens(vec!["s3cr3t".to_owned(), "n3w".to_owned()])?;
+        let status =
+            SchedulerGrpc::update_task_status(&scheduler, update_task_status("n3w"))
+                .await
+                .unwrap_err();
+        assert_eq!(Code::Unauthenticated, status.code());
+
+        // The requests of the executor are rejected once its token is rotated out
+        scheduler
+            .state
+            .executor_manager
+            .rotate_registration_tokens(vec!["n3w".to_owned()])?;
+        let status =
+            SchedulerGrpc::update_task_status(&scheduler, update_task_status("s3cr3t"))
+                .await
+                .unwrap_err();
+        assert_eq!(Code::Unauthenticated, status.code());
+        let status = scheduler
+            .heart_beat_from_executor(heart_beat("s3cr3t"))
+            .await
+            .unwrap_err();
+        assert_eq!(Code::Unauthenticated, status.code());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_stop_executor() -> Result<(), BallistaError> {
         let cluster = test_cluster_context();
@@ -707,6 +1777,11 @@ mod test {
             port: 0,
             grpc_port: 0,
             specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+            registration_token: String::new(),
         };
 
         let request: Request<RegisterExecutorParams> =
@@ -739,6 +1814,7 @@ mod test {
             Request::new(ExecutorStoppedParams {
                 executor_id: "abc".to_owned(),
                 reason: "test_stop".to_owned(),
+                registration_token: String::new(),
             });
 
         let _response = scheduler
@@ -792,6 +1868,11 @@ mod test {
             port: 0,
             grpc_port: 0,
             specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+            registration_token: String::new(),
         };
 
         let request: Request<HeartBeatParams> = Request::new(HeartBeatParams {
@@ -823,6 +1904,74 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_disk_full_executor() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster,
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let exec_meta = ExecutorRegistration {
+            id: "abc".to_owned(),
+            host: Some("http://localhost:8080".to_owned()),
+            port: 0,
+            grpc_port: 0,
+            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+            registration_token: String::new(),
+        };
+        let heartbeat = |used_bytes: u64, max_bytes: u64| {
+            Request::new(HeartBeatParams {
+                executor_id: exec_meta.id.clone(),
+                metrics: vec![ExecutorMetric {
+                    metric: Some(executor_metric::Metric::ShuffleDiskUsage(
+                        ShuffleDiskUsage {
+                            used_bytes,
+                            max_bytes,
+                        },
+                    )),
+                }],
+                status: Some(ExecutorStatus {
+                    status: Some(executor_status::Status::Active("".to_string())),
+                }),
+                metadata: Some(exec_meta.clone()),
+            })
+        };
+        let executor_manager = &scheduler.state.executor_manager;
+
+        scheduler
+            .heart_beat_from_executor(heartbeat(100, 0))
+            .await
+            .expect("Received error response");
+        assert!(!executor_manager.is_disk_full("abc"));
+
+        scheduler
+            .heart_beat_from_executor(heartbeat(100, 100))
+            .await
+            .expect("Received error response");
+        assert!(executor_manager.is_disk_full("abc"));
+
+        // once job data is removed, the executor is bound tasks again
+        scheduler
+            .heart_beat_from_executor(heartbeat(10, 100))
+            .await
+            .expect("Received error response");
+        assert!(!executor_manager.is_disk_full("abc"));
+
+        Ok(())
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_expired_executor() -> Result<(), BallistaError> {
@@ -845,6 +1994,11 @@ mod test {
             port: 0,
             grpc_port: 0,
             specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            version: build_version(),
+            functions: None,
+            shuffle_formats: None,
+            pool: String::new(),
+            registration_token: String::new(),
         };
 
         let request: Request<RegisterExecutorParams> =
@@ -909,4 +2063,162 @@ mod test {
         assert!(active_executors.is_empty());
         Ok(())
     }
+
+    fn with_principal<T>(message: T, principal: Option<&str>) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(principal) = principal {
+            request
+                .metadata_mut()
+                .insert(PRINCIPAL_METADATA_KEY, principal.parse().unwrap());
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn test_job_access_control() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default().with_access_control(
+            AccessControl::new(Role::User).with_role("bob", Role::Operator),
+        );
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+        scheduler.state.task_manager.set_job_owner("job", "alice");
+
+        let get_job_status = |principal| {
+            with_principal(
+                GetJobStatusParams {
+                    job_id: "job".to_owned(),
+                },
+                principal,
+            )
+        };
+
+        let status = scheduler.get_job_status(get_job_status(None)).await;
+        assert_eq!(status.unwrap_err().code(), Code::Unauthenticated);
+
+        let status = scheduler
+            .get_job_status(get_job_status(Some("carol")))
+            .await;
+        assert_eq!(status.unwrap_err().code(), Code::PermissionDenied);
+
+        // The owner and operators can view the job
+        scheduler
+            .get_job_status(get_job_status(Some("alice")))
+            .await
+            .expect("owner views job");
+        scheduler
+            .get_job_status(get_job_status(Some("bob")))
+            .await
+            .expect("operator views job");
+
+        let cancelled = scheduler
+            .cancel_job(with_principal(
+                CancelJobParams {
+                    job_id: "job".to_owned(),
+                },
+                Some("carol"),
+            ))
+            .await;
+        assert_eq!(cancelled.unwrap_err().code(), Code::PermissionDenied);
+
+        let purged = scheduler
+            .purge_jobs(with_principal(PurgeJobsParams::default(), Some("alice")))
+            .await;
+        assert_eq!(purged.unwrap_err().code(), Code::PermissionDenied);
+
+        Ok(())
+    }
+
+    /// Accepts the bearer tokens `<principal>:<group>`
+    #[derive(Debug)]
+    struct TestAuthProvider;
+
+    #[tonic::async_trait]
+    impl AuthProvider for TestAuthProvider {
+        async fn authenticate(
+            &self,
+            credentials: &Credentials,
+        ) -> ballista_core::error::Result<Identity> {
+            match credentials {
+                Credentials::Bearer(token) => {
+                    let (principal, group) = token.split_once(':').ok_or_else(|| {
+                        BallistaError::General("Invalid token".to_owned())
+                    })?;
+                    Ok(Identity::new(principal).with_groups(vec![group.to_owned()]))
+                }
+                _ => Err(BallistaError::General("Unsupported credentials".to_owned())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticated_access_control() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default()
+            .with_access_control(
+                AccessControl::new(Role::User).with_group_role("ops", Role::Operator),
+            )
+            .with_auth_provider(Arc::new(TestAuthProvider));
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+        scheduler.state.task_manager.set_job_owner("job", "alice");
+
+        let get_job_status = |authorization: &str| {
+            let mut request = Request::new(GetJobStatusParams {
+                job_id: "job".to_owned(),
+            });
+            request
+                .metadata_mut()
+                .insert(AUTHORIZATION_METADATA_KEY, authorization.parse().unwrap());
+            request
+        };
+
+        // The principal header is not trusted when credentials are authenticated
+        let status = scheduler
+            .get_job_status(with_principal(
+                GetJobStatusParams {
+                    job_id: "job".to_owned(),
+                },
+                Some("alice"),
+            ))
+            .await;
+        assert_eq!(status.unwrap_err().code(), Code::Unauthenticated);
+
+        let status = scheduler
+            .get_job_status(get_job_status("Bearer invalid"))
+            .await;
+        assert_eq!(status.unwrap_err().code(), Code::Unauthenticated);
+
+        let status = scheduler
+            .get_job_status(get_job_status("Bearer carol:analysts"))
+            .await;
+        assert_eq!(status.unwrap_err().code(), Code::PermissionDenied);
+
+        scheduler
+            .get_job_status(get_job_status("Bearer alice:analysts"))
+            .await
+            .expect("owner views job");
+        scheduler
+            .get_job_status(get_job_status("Bearer bob:ops"))
+            .await
+            .expect("member of operator group views job");
+
+        Ok(())
+    }
 }
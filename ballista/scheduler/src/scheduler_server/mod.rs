@@ -18,8 +18,9 @@
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use ballista_core::error::Result;
+use ballista_core::error::{Result, LEADER_METADATA_KEY};
 use ballista_core::event_loop::{EventLoop, EventSender};
+use ballista_core::extension::SessionConfigHelperExt;
 use ballista_core::serde::protobuf::TaskStatus;
 use ballista_core::serde::BallistaCodec;
 
@@ -28,17 +29,24 @@ use datafusion::logical_expr::LogicalPlan;
 use datafusion::prelude::{SessionConfig, SessionContext};
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
+use futures::StreamExt;
 
+use crate::access_control::{AccessError, Operation};
+use crate::auth::{Credentials, Identity, RequestAuth};
+use crate::cluster::storage::LeaderElection;
 use crate::cluster::BallistaCluster;
 use crate::config::SchedulerConfig;
 use crate::metrics::SchedulerMetricsCollector;
+use crate::query_authorization::QueryRequest;
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
-use log::{error, warn};
+use log::{error, info, warn};
+use tokio::sync::mpsc::{self, error::TrySendError};
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::query_stage_scheduler::QueryStageScheduler;
 
 use crate::state::executor_manager::ExecutorManager;
+use crate::state::plan_cache::is_cacheable;
 
 use crate::state::task_manager::TaskLauncher;
 use crate::state::SchedulerState;
@@ -56,6 +64,14 @@ mod external_scaler;
 mod grpc;
 pub(crate) mod query_stage_scheduler;
 
+/// Interval at which the scheduler looks for tasks exceeding the stage timeout of their
+/// job, see [SchedulerServer::time_out_tasks]
+const STAGE_TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of cluster events buffered for the [crate::cluster::ClusterEventPublisher],
+/// beyond which the events are dropped rather than holding up the scheduler
+const CLUSTER_EVENT_BUFFER: usize = 1024;
+
 pub type SessionBuilder =
     Arc<dyn Fn(SessionConfig) -> datafusion::common::Result<SessionState> + Send + Sync>;
 
@@ -65,8 +81,8 @@ pub struct SchedulerServer<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     pub start_time: u128,
     pub state: Arc<SchedulerState<T, U>>,
     pub(crate) query_stage_event_loop: EventLoop<QueryStageSchedulerEvent>,
-    #[cfg(feature = "rest-api")]
     query_stage_scheduler: Arc<QueryStageScheduler<T, U>>,
+    leader_election: Option<Arc<LeaderElection>>,
     config: Arc<SchedulerConfig>,
 }
 
@@ -78,6 +94,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
         config: Arc<SchedulerConfig>,
         metrics_collector: Arc<dyn SchedulerMetricsCollector>,
     ) -> Self {
+        let leader_election = cluster.leader_election();
         let state = Arc::new(SchedulerState::new(
             cluster,
             codec,
@@ -100,8 +117,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             start_time: timestamp_millis() as u128,
             state,
             query_stage_event_loop,
-            #[cfg(feature = "rest-api")]
             query_stage_scheduler,
+            leader_election,
             config,
         }
     }
@@ -115,6 +132,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
         metrics_collector: Arc<dyn SchedulerMetricsCollector>,
         task_launcher: Arc<dyn TaskLauncher>,
     ) -> Self {
+        let leader_election = cluster.leader_election();
         let state = Arc::new(SchedulerState::new_with_task_launcher(
             cluster,
             codec,
@@ -138,16 +156,40 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             start_time: timestamp_millis() as u128,
             state,
             query_stage_event_loop,
-            #[cfg(feature = "rest-api")]
             query_stage_scheduler,
+            leader_election,
             config,
         }
     }
 
     pub async fn init(&mut self) -> Result<()> {
         self.state.init().await?;
+        // the jobs of a read replica are run by the leader
+        if self.config.read_replica {
+            info!("Scheduler {} serves as a read replica", self.scheduler_name);
+            return Ok(());
+        }
         self.query_stage_event_loop.start()?;
+        let recovered = self.state.task_manager.recover_jobs().await?;
+        for job_id in &recovered {
+            if let Some(graph) =
+                self.state.task_manager.get_active_execution_graph(job_id)
+            {
+                let graph = graph.read().await;
+                if let Err(e) = self.state.webhooks.watch(job_id, graph.session_config())
+                {
+                    warn!("Not notifying the webhooks of recovered job {job_id}: {e}");
+                }
+            }
+        }
+        if !recovered.is_empty() && self.state.config.is_push_staged_scheduling() {
+            self.revive_offers().await?;
+        }
         self.expire_dead_executors()?;
+        self.compact_finished_jobs();
+        self.sample_cluster_metrics();
+        self.time_out_tasks()?;
+        self.publish_cluster_events().await?;
 
         Ok(())
     }
@@ -159,17 +201,176 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
     pub fn running_job_number(&self) -> usize {
         self.state.task_manager.running_job_number()
     }
-    #[cfg(feature = "rest-api")]
     pub(crate) fn metrics_collector(&self) -> &dyn SchedulerMetricsCollector {
         self.query_stage_scheduler.metrics_collector()
     }
 
+    /// Reject the writes a read replica receives, with the address of the leader
+    /// scheduler, if elected, in the [LEADER_METADATA_KEY] metadata of the status for
+    /// the clients to direct them to
+    pub(crate) async fn ensure_writable(&self) -> std::result::Result<(), tonic::Status> {
+        if !self.config.read_replica {
+            return Ok(());
+        }
+        let leader = match &self.leader_election {
+            Some(election) => election.leader().await.unwrap_or_else(|e| {
+                warn!("Failed to look up the leader scheduler: {e:?}");
+                None
+            }),
+            None => None,
+        };
+
+        let mut status = tonic::Status::failed_precondition(format!(
+            "Scheduler {} is a read replica, writes are served by the leader scheduler {}",
+            self.scheduler_name,
+            leader.as_deref().unwrap_or("once elected")
+        ));
+        if let Some(leader) = leader.and_then(|leader| leader.parse().ok()) {
+            status.metadata_mut().insert(LEADER_METADATA_KEY, leader);
+        }
+        Err(status)
+    }
+
+    /// Resolve the identity of the caller of a request. With an auth provider, the
+    /// credentials of the request are authenticated and the principal header is ignored.
+    /// Requests are anonymous when access control is disabled
+    pub(crate) async fn identify(
+        &self,
+        auth: RequestAuth,
+    ) -> std::result::Result<Option<Identity>, AccessError> {
+        if self.state.config.access_control.is_none() {
+            return Ok(None);
+        }
+        let Some(auth_provider) = &self.state.config.auth_provider else {
+            return Ok(auth.principal.map(Identity::new));
+        };
+        let Some(authorization) = auth.authorization else {
+            return Ok(None);
+        };
+
+        let credentials = Credentials::from_authorization_header(&authorization)
+            .ok_or_else(|| {
+                AccessError::AuthenticationFailed(
+                    "Malformed authorization header".to_owned(),
+                )
+            })?;
+        auth_provider
+            .authenticate(&credentials)
+            .await
+            .map(Some)
+            .map_err(|e| {
+                warn!("Failed to authenticate {credentials:?}: {e}");
+                AccessError::AuthenticationFailed(e.to_string())
+            })
+    }
+
+    /// Check whether `identity` is allowed to perform `operation`, on the job `job_id`
+    /// if the operation targets a job. Everything is allowed when access control is
+    /// disabled
+    pub(crate) fn authorize(
+        &self,
+        identity: Option<&Identity>,
+        operation: Operation,
+        job_id: Option<&str>,
+    ) -> std::result::Result<(), AccessError> {
+        let Some(access_control) = &self.state.config.access_control else {
+            return Ok(());
+        };
+        let owner = job_id.and_then(|job_id| self.state.task_manager.job_owner(job_id));
+
+        access_control.authorize(identity, operation, owner.as_deref())
+    }
+
+    /// Mask the sensitive columns read by `plan` according to the masking rules which
+    /// apply to `identity`, all of them for anonymous callers
+    pub(crate) fn mask_plan(
+        &self,
+        identity: Option<&Identity>,
+        ctx: &SessionContext,
+        plan: LogicalPlan,
+    ) -> Result<LogicalPlan> {
+        let Some(data_masking) = &self.state.config.data_masking else {
+            return Ok(plan);
+        };
+        let caller = identity.zip(self.state.config.access_control.as_ref()).map(
+            |(identity, access_control)| {
+                (identity.principal.as_str(), access_control.role(identity))
+            },
+        );
+
+        data_masking.mask_plan(&ctx.state(), plan, caller)
+    }
+
+    /// Check that the query authorizer allows `identity` to submit `plan`, planned in
+    /// the session of `ctx`
+    pub(crate) fn authorize_query(
+        &self,
+        identity: Option<&Identity>,
+        ctx: &SessionContext,
+        plan: &LogicalPlan,
+    ) -> std::result::Result<(), AccessError> {
+        let state = ctx.state();
+        let role = identity
+            .zip(self.state.config.access_control.as_ref())
+            .map(|(identity, access_control)| access_control.role(identity));
+        let query = QueryRequest {
+            identity,
+            role,
+            plan,
+            config: state.config(),
+        };
+
+        self.state
+            .config
+            .query_authorizer
+            .authorize(&query)
+            .map_err(|reason| {
+                let principal = identity.map_or("anonymous", |i| i.principal.as_str());
+                warn!("Rejected query of {principal}: {reason}");
+                AccessError::QueryDenied(reason)
+            })
+    }
+
+    /// Submit a job of a client, whose webhooks are notified once it completes. A
+    /// sample of the pure queries are also submitted in shadow, see
+    /// [crate::state::shadow_execution::ShadowExecution]
     pub(crate) async fn submit_job(
         &self,
         job_id: &str,
         job_name: &str,
         ctx: Arc<SessionContext>,
         plan: &LogicalPlan,
+    ) -> Result<()> {
+        let webhooks = &self.state.webhooks;
+        webhooks.watch(job_id, ctx.state().config())?;
+        // the pair is tracked before the job is queued, so that the job is planned
+        // with its results checksummed
+        let shadow_job_id = self.sample_shadow_job(job_id, plan)?;
+        let shadow_execution = &self.state.shadow_execution;
+        self.queue_job(job_id, job_name, ctx.clone(), plan)
+            .await
+            .inspect_err(|_| {
+                webhooks.forget(job_id);
+                shadow_execution.forget(job_id);
+            })?;
+        if let Some(shadow_job_id) = shadow_job_id {
+            if let Err(e) = self
+                .submit_shadow_job(job_id, &shadow_job_id, job_name, &ctx, plan)
+                .await
+            {
+                warn!("Failed to submit shadow job of {job_id}: {e:?}");
+                shadow_execution.forget(job_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn queue_job(
+        &self,
+        job_id: &str,
+        job_name: &str,
+        ctx: Arc<SessionContext>,
+        plan: &LogicalPlan,
     ) -> Result<()> {
         self.query_stage_event_loop
             .get_sender()?
@@ -183,6 +384,52 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             .await
     }
 
+    /// Whether to shadow the job `job_id`, returning the id of its shadow job if so,
+    /// whose outcome is then tracked along with the outcome of the job
+    fn sample_shadow_job(
+        &self,
+        job_id: &str,
+        plan: &LogicalPlan,
+    ) -> Result<Option<String>> {
+        let shadow_execution = &self.state.shadow_execution;
+        if !shadow_execution.sample() || !is_cacheable(plan)? {
+            return Ok(None);
+        }
+
+        let shadow_job_id = self.state.task_manager.generate_job_id();
+        shadow_execution.register(job_id, &shadow_job_id);
+        Ok(Some(shadow_job_id))
+    }
+
+    /// Submit the shadow job of a sampled pure query, with the experimental session
+    /// settings of shadow execution, to compare its result and runtime with the job
+    /// `job_id`
+    async fn submit_shadow_job(
+        &self,
+        job_id: &str,
+        shadow_job_id: &str,
+        job_name: &str,
+        ctx: &SessionContext,
+        plan: &LogicalPlan,
+    ) -> Result<()> {
+        let session_config = ctx
+            .copied_config()
+            .update_from_key_value_pair(self.state.shadow_execution.settings());
+        let shadow_ctx = self
+            .state
+            .session_manager
+            .create_session(&session_config)
+            .await?;
+        if let Some(owner) = self.state.task_manager.job_owner(job_id) {
+            self.state.task_manager.set_job_owner(shadow_job_id, &owner);
+        }
+
+        info!("Shadowing job {job_id} with job {shadow_job_id}");
+        let job_name = format!("{job_name} (shadow of {job_id})");
+        self.queue_job(shadow_job_id, &job_name, shadow_ctx, plan)
+            .await
+    }
+
     /// It just send task status update event to the channel,
     /// and will not guarantee the event processing completed after return
     pub(crate) async fn update_task_status(
@@ -216,6 +463,130 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             .await
     }
 
+    /// Forward cluster topology events to the configured
+    /// [crate::cluster::ClusterEventPublisher], if any. The events are buffered for the
+    /// publisher, and dropped and counted once it falls [CLUSTER_EVENT_BUFFER] events
+    /// behind
+    async fn publish_cluster_events(&self) -> Result<()> {
+        let Some(publisher) = self.config.override_cluster_event_publisher.clone() else {
+            return Ok(());
+        };
+
+        let mut events = self.state.executor_manager.cluster_state_events().await?;
+        let (sender, mut receiver) = mpsc::channel(CLUSTER_EVENT_BUFFER);
+        tokio::task::spawn(async move {
+            let mut dropped = 0_u64;
+            while let Some(event) = events.next().await {
+                if let Err(TrySendError::Full(event)) = sender.try_send(event) {
+                    dropped += 1;
+                    warn!(
+                        "Dropped cluster event {event:?} as the publisher lags behind, {dropped} events dropped so far"
+                    );
+                }
+            }
+        });
+        tokio::task::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                if let Err(e) = publisher.publish(&event).await {
+                    warn!("Failed to publish cluster event {event:?}: {e}");
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Spawn an async task which periodically purges the finished jobs falling outside
+    /// of the retention policy from the state
+    fn compact_finished_jobs(&self) {
+        if self.state.config.finished_job_retention().is_unlimited() {
+            return;
+        }
+
+        let state = self.state.clone();
+        let interval = Duration::from_secs(
+            state.config.finished_job_compaction_interval_seconds.max(1),
+        );
+        tokio::task::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match state.compact_finished_jobs().await {
+                    Ok(purged) if !purged.is_empty() => {
+                        info!("Purged {} finished jobs from state", purged.len())
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!("Failed to compact finished jobs: {e:?}"),
+                }
+            }
+        });
+    }
+
+    /// Spawn an async task which periodically fails the tasks exceeding the stage timeout
+    /// of their job which their executors didn't report, e.g. as they are unresponsive
+    fn time_out_tasks(&self) -> Result<()> {
+        let state = self.state.clone();
+        let event_sender = self.query_stage_event_loop.get_sender()?;
+        tokio::task::spawn(async move {
+            loop {
+                tokio::time::sleep(STAGE_TIMEOUT_CHECK_INTERVAL).await;
+                let events = match state
+                    .task_manager
+                    .time_out_tasks(&state.executor_manager)
+                    .await
+                {
+                    Ok(events) => events,
+                    Err(e) => {
+                        error!("Failed to time out tasks: {e:?}");
+                        continue;
+                    }
+                };
+                if events.is_empty() {
+                    continue;
+                }
+                for event in events {
+                    if let Err(e) = event_sender.post_event(event).await {
+                        error!("Failed to post the events of timed out tasks: {e:?}");
+                    }
+                }
+                if state.config.is_push_staged_scheduling() {
+                    if let Err(e) = event_sender
+                        .post_event(QueryStageSchedulerEvent::ReviveOffers)
+                        .await
+                    {
+                        error!("Failed to revive offers: {e:?}");
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Spawn an async task which periodically samples the cluster-wide metrics, and
+    /// records them through the metrics collector
+    fn sample_cluster_metrics(&self) {
+        if self.state.config.cluster_metrics_interval_seconds == 0 {
+            return;
+        }
+
+        let state = self.state.clone();
+        let query_stage_scheduler = self.query_stage_scheduler.clone();
+        let interval = Duration::from_secs(state.config.cluster_metrics_interval_seconds);
+        tokio::task::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let sample = state.sample_cluster_metrics().await;
+                let metrics_collector = query_stage_scheduler.metrics_collector();
+                metrics_collector.record_cluster_metrics(&sample);
+                metrics_collector.set_pending_tasks_queue_size(
+                    state.task_manager.pending_task_number().await as u64,
+                );
+                metrics_collector.set_retained_result_bytes(
+                    state.result_retention.retained_bytes(timestamp_millis()),
+                );
+            }
+        });
+    }
+
     /// Spawn an async task which periodically check the active executors' status and
     /// expire the dead executors
     fn expire_dead_executors(&self) -> Result<()> {
@@ -264,7 +635,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
                     if !terminating {
                         state
                             .executor_manager
-                            .stop_executor(&executor_id, stop_reason)
+                            .stop_executor(&executor_id, stop_reason, true)
                             .await;
                     }
                 }
@@ -313,6 +684,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             available_task_slots: metadata.specification.task_slots,
         };
 
+        if let Some(event_log) = &self.state.event_log {
+            if let Err(e) = event_log.executor_registered(&metadata).await {
+                warn!(
+                    "Fail to log the registration of executor {}: {e}",
+                    metadata.id
+                );
+            }
+        }
+
         // Save the executor to state
         self.state
             .executor_manager
@@ -346,25 +726,41 @@ pub fn timestamp_millis() -> u64 {
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
+    use std::time::Duration;
+
+    use object_store::memory::InMemory;
 
     use ballista_core::extension::SessionConfigExt;
     use datafusion::arrow::datatypes::{DataType, Field, Schema};
     use datafusion::functions_aggregate::sum::sum;
-    use datafusion::logical_expr::{col, LogicalPlan};
+    use datafusion::logical_expr::{col, create_udf, LogicalPlan, Volatility};
+    use datafusion::physical_plan::displayable;
 
     use datafusion::prelude::SessionConfig;
     use datafusion::test_util::scan_empty_with_partitions;
+    use datafusion_proto::physical_plan::AsExecutionPlan;
     use datafusion_proto::protobuf::LogicalPlanNode;
     use datafusion_proto::protobuf::PhysicalPlanNode;
+    use prost::Message;
 
+    use ballista_core::compression::{CompressionCodecRegistry, Lz4Codec};
     use ballista_core::config::TaskSchedulingPolicy;
-    use ballista_core::error::Result;
-
-    use crate::config::SchedulerConfig;
-
+    use ballista_core::error::{leader_url_from_status, ErrorCode, Result};
+    use ballista_core::utils::{default_config_producer, default_session_builder};
+    use tonic::Request;
+
+    use crate::cluster::storage::KeyValueStore;
+    use crate::cluster::test_util::InMemoryKeyValueStore;
+    use crate::cluster::BallistaCluster;
+    use crate::config::{ExecutorVersionPolicy, SchedulerConfig};
+    use crate::executor_pools::{ExecutorPool, ExecutorPools};
+
+    use ballista_core::build_version;
+    use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpc;
     use ballista_core::serde::protobuf::{
-        failed_task, job_status, task_status, ExecutionError, FailedTask, JobStatus,
-        MultiTaskDefinition, ShuffleWritePartition, SuccessfulJob, SuccessfulTask,
+        failed_task, job_status, task_status, ExecuteQueryParams, ExecutionError,
+        FailedTask, GetJobStatusParams, JobStatus, MultiTaskDefinition,
+        RegisterExecutorParams, ShuffleWritePartition, SuccessfulJob, SuccessfulTask,
         TaskId, TaskStatus,
     };
     use ballista_core::serde::scheduler::{
@@ -376,9 +772,11 @@ mod test {
 
     use crate::test_utils::{
         assert_completed_event, assert_failed_event, assert_no_submitted_event,
-        assert_submitted_event, test_cluster_context, ExplodingTableProvider,
-        SchedulerTest, TaskRunnerFn, TestMetricsCollector,
+        assert_submitted_event, default_task_runner, test_cluster_context,
+        ExplodingTableProvider, MetricEvent, SchedulerTest, TaskRunner, TaskRunnerFn,
+        TestMetricsCollector,
     };
+    use parking_lot::Mutex;
 
     #[tokio::test]
     async fn test_pull_scheduling() -> Result<()> {
@@ -414,9 +812,10 @@ mod test {
             .queue_job(job_id, "", timestamp_millis())?;
 
         // Submit job
+        let event_sender = scheduler.query_stage_event_loop.get_sender()?;
         scheduler
             .state
-            .submit_job(job_id, "", ctx, &plan, 0)
+            .submit_job(job_id, "", ctx, &plan, 0, &event_sender)
             .await
             .expect("submitting plan");
 
@@ -442,6 +841,10 @@ mod test {
                         num_batches: 1,
                         num_rows: 1,
                         num_bytes: 1,
+                        inline_data: vec![],
+                        checksum: None,
+                        compression: Default::default(),
+                        ipc_metadata_version: 0,
                     })
                 }
 
@@ -525,6 +928,299 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_dispatch_plans_by_reference() -> Result<()> {
+        let plan = test_plan();
+
+        let dispatched_tasks = Arc::new(Mutex::new(vec![]));
+        let runner = {
+            let dispatched_tasks = dispatched_tasks.clone();
+            let default_runner = default_task_runner();
+            Arc::new(TaskRunnerFn::new(
+                move |executor_id: String, task: MultiTaskDefinition| {
+                    dispatched_tasks.lock().push(task.clone());
+                    default_runner.run(executor_id, task)
+                },
+            ))
+        };
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged)
+                .with_plan_reference_threshold(1),
+            Arc::new(TestMetricsCollector::default()),
+            4,
+            1,
+            Some(runner),
+        )
+        .await?;
+
+        test.submit("job", "", &plan).await?;
+        test.tick().await?;
+
+        let task = dispatched_tasks.lock()[0].clone();
+        assert!(task.plan.is_empty());
+        assert!(!task.plan_hash.is_empty());
+        let fetched_plan = test
+            .get_task_plan("job", &task.plan_hash)
+            .expect("plan of a running job");
+        assert!(!fetched_plan.is_empty());
+        assert!(test.get_task_plan("job", "unknown").is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_compressed_plans() -> Result<()> {
+        let plan = test_plan();
+
+        let dispatched_tasks = Arc::new(Mutex::new(vec![]));
+        let runner = {
+            let dispatched_tasks = dispatched_tasks.clone();
+            let default_runner = default_task_runner();
+            Arc::new(TaskRunnerFn::new(
+                move |executor_id: String, task: MultiTaskDefinition| {
+                    dispatched_tasks.lock().push(task.clone());
+                    default_runner.run(executor_id, task)
+                },
+            ))
+        };
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged)
+                .with_task_plan_compression(Some(Arc::new(Lz4Codec))),
+            Arc::new(TestMetricsCollector::default()),
+            4,
+            1,
+            Some(runner),
+        )
+        .await?;
+
+        test.submit("job", "", &plan).await?;
+        test.tick().await?;
+
+        let task = dispatched_tasks.lock()[0].clone();
+        assert_eq!("lz4", task.plan_compression);
+        let plan = CompressionCodecRegistry::default()
+            .decompress(&task.plan_compression, &task.plan)?;
+        assert!(PhysicalPlanNode::decode(plan.as_ref()).is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_plan() -> Result<()> {
+        let plan = test_plan();
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged),
+            Arc::new(TestMetricsCollector::default()),
+            4,
+            1,
+            None,
+        )
+        .await?;
+
+        let status = test.job_plan("job").await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        test.run("job", "", &plan).await.expect("running plan");
+        let stages = test.job_plan("job").await.expect("plan of job").stages;
+        assert_eq!(
+            stages
+                .iter()
+                .map(|stage| (
+                    stage.stage_id,
+                    stage.input_stages.clone(),
+                    stage.output_links.clone()
+                ))
+                .collect::<Vec<_>>(),
+            vec![(1, vec![], vec![2]), (2, vec![1], vec![])]
+        );
+
+        // The plans are decoded with the codec of the client
+        let ctx = test.ctx().await?;
+        let codec: BallistaCodec = BallistaCodec::default();
+        let final_stage = PhysicalPlanNode::decode(stages[1].plan.as_slice())
+            .unwrap()
+            .try_into_physical_plan(
+                ctx.as_ref(),
+                ctx.runtime_env().as_ref(),
+                codec.physical_extension_codec(),
+            )?;
+        assert_eq!(stages[1].partitions, 4);
+        assert!(displayable(final_stage.as_ref())
+            .indent(false)
+            .to_string()
+            .contains("ShuffleReaderExec"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recursive_query_without_fixpoint() -> Result<()> {
+        let metrics_collector = Arc::new(TestMetricsCollector::default());
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged)
+                .with_max_recursive_query_iterations(2),
+            metrics_collector.clone(),
+            4,
+            1,
+            None,
+        )
+        .await?;
+
+        let plan = test
+            .ctx()
+            .await?
+            .sql(
+                "with recursive t as (select 1 as n union all select n + 1 from t where n < 10) \
+                select * from t",
+            )
+            .await?
+            .into_unoptimized_plan();
+
+        // The virtual executors report a row in the output of every iteration
+        let status = test.run("job", "", &plan).await.expect("running plan");
+        match status.status {
+            Some(job_status::Status::Failed(failed)) => assert!(
+                failed
+                    .error
+                    .contains("did not reach a fixpoint within 2 iterations"),
+                "Unexpected error {}",
+                failed.error
+            ),
+            other => panic!("Expected failed status but found {other:?}"),
+        }
+
+        // The static term and the 2 iterations of the recursive term ran as jobs
+        let internal_jobs = metrics_collector
+            .events
+            .lock()
+            .iter()
+            .filter(|event| matches!(event, MetricEvent::Completed(..)))
+            .count();
+        assert_eq!(internal_jobs, 3);
+        assert_failed_event("job", &metrics_collector);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_metrics() -> Result<()> {
+        let plan = test_plan();
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged),
+            Arc::new(TestMetricsCollector::default()),
+            4,
+            1,
+            None,
+        )
+        .await?;
+
+        let status = test.job_metrics("job").await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        test.run("job", "", &plan).await.expect("running plan");
+        let stages = test
+            .job_metrics("job")
+            .await
+            .expect("metrics of job")
+            .stages;
+        assert_eq!(
+            stages
+                .iter()
+                .map(|stage| (
+                    stage.stage_id,
+                    stage.status.as_str(),
+                    stage.partitions,
+                    stage.completed_tasks
+                ))
+                .collect::<Vec<_>>(),
+            vec![(1, "Successful", 2, 2), (2, "Successful", 4, 4)]
+        );
+
+        Ok(())
+    }
+
+    // The tasks of a job are dispatched stage after stage, each task once
+    #[tokio::test]
+    async fn test_first_task_dispatch() -> Result<()> {
+        let plan = test_plan();
+
+        let dispatched = Arc::new(Mutex::new(vec![]));
+        let runner = {
+            let dispatched = dispatched.clone();
+            let runner = default_task_runner();
+            Arc::new(TaskRunnerFn::new(
+                move |executor_id: String, task: MultiTaskDefinition| {
+                    dispatched
+                        .lock()
+                        .extend(task.task_ids.iter().map(|_| task.stage_id));
+                    runner.run(executor_id, task)
+                },
+            ))
+        };
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged),
+            Arc::new(TestMetricsCollector::default()),
+            4,
+            1,
+            Some(runner),
+        )
+        .await?;
+
+        let status = test.run("job", "", &plan).await?;
+        assert!(matches!(
+            status.status,
+            Some(job_status::Status::Successful(_))
+        ));
+        assert_eq!(vec![1, 1, 2, 2, 2, 2], *dispatched.lock());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_purge_finished_job() -> Result<()> {
+        let plan = test_plan();
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged)
+                .with_job_archive_store(Arc::new(InMemory::new()))
+                .with_job_archive_dir("archive"),
+            Arc::new(TestMetricsCollector::default()),
+            4,
+            1,
+            None,
+        )
+        .await?;
+
+        let status = test.run("job", "", &plan).await?;
+        assert!(matches!(
+            status.status,
+            Some(job_status::Status::Successful(_))
+        ));
+
+        let purged = test
+            .purge_jobs(vec!["job".to_owned(), "unknown".to_owned()])
+            .await?;
+        assert_eq!(purged, vec!["job".to_owned()]);
+
+        // The job is gone from the state but still available in the archive
+        assert!(test.purge_jobs(vec!["job".to_owned()]).await?.is_empty());
+        assert_eq!(test.await_completion("job").await?, status);
+
+        Ok(())
+    }
+
     // Simulate a task failure and ensure the job status is updated correctly
     #[tokio::test]
     async fn test_job_failure() -> Result<()> {
@@ -555,6 +1251,7 @@ mod test {
                             error: "ERROR".to_string(),
                             retryable: false,
                             count_to_failures: false,
+                            error_code: ErrorCode::ExecutionFailed.to_string(),
                             failed_reason: Some(
                                 failed_task::FailedReason::ExecutionError(
                                     ExecutionError {},
@@ -645,6 +1342,318 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_strict_executor_version_binding() -> Result<()> {
+        let plan = test_plan();
+        let task_slots = 4;
+
+        let config = SchedulerConfig::default()
+            .with_scheduler_policy(TaskSchedulingPolicy::PullStaged)
+            .with_executor_version_policy(ExecutorVersionPolicy::Strict);
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                test_cluster_context(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                Arc::new(TestMetricsCollector::default()),
+            );
+        scheduler.init().await?;
+
+        // Each executor runs a different version
+        for (i, (mut executor_metadata, executor_data)) in
+            test_executors(task_slots).into_iter().enumerate()
+        {
+            executor_metadata.version = format!("version-{i}");
+            scheduler
+                .state
+                .executor_manager
+                .register_executor(executor_metadata, executor_data)
+                .await?;
+        }
+
+        let config =
+            SessionConfig::new_with_ballista().with_target_partitions(task_slots);
+        let ctx = scheduler
+            .state
+            .session_manager
+            .create_session(&config)
+            .await?;
+
+        let job_id = "job";
+        scheduler
+            .state
+            .task_manager
+            .queue_job(job_id, "", timestamp_millis())?;
+        let event_sender = scheduler.query_stage_event_loop.get_sender()?;
+        scheduler
+            .state
+            .submit_job(job_id, "", ctx, &plan, 0, &event_sender)
+            .await
+            .expect("submitting plan");
+
+        let bound_tasks = scheduler
+            .state
+            .executor_manager
+            .bind_schedulable_tasks(scheduler.state.task_manager.get_running_job_cache())
+            .await?;
+
+        assert!(!bound_tasks.is_empty());
+        assert!(
+            bound_tasks
+                .iter()
+                .all(|(executor_id, _)| executor_id == "executor-1"),
+            "Expected all tasks to be bound to executors of a single version"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksum_shadow_execution_results() -> Result<()> {
+        let plan = test_plan();
+        let config = SchedulerConfig::default()
+            .with_scheduler_policy(TaskSchedulingPolicy::PullStaged);
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                test_cluster_context(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                Arc::new(TestMetricsCollector::default()),
+            );
+        scheduler.init().await?;
+
+        let ctx = scheduler
+            .state
+            .session_manager
+            .create_session(&SessionConfig::new_with_ballista())
+            .await?;
+        scheduler.state.shadow_execution.register("job", "shadow");
+
+        let event_sender = scheduler.query_stage_event_loop.get_sender()?;
+        for job_id in ["job", "shadow", "other"] {
+            scheduler
+                .state
+                .task_manager
+                .queue_job(job_id, "", timestamp_millis())?;
+            scheduler
+                .state
+                .submit_job(job_id, "", ctx.clone(), &plan, 0, &event_sender)
+                .await?;
+        }
+
+        // the executors checksum the results of both jobs of the shadow execution
+        for (job_id, checksummed) in [("job", true), ("shadow", true), ("other", false)] {
+            let graph = scheduler
+                .state
+                .task_manager
+                .get_active_execution_graph(job_id)
+                .unwrap();
+            assert_eq!(
+                checksummed,
+                graph
+                    .read()
+                    .await
+                    .session_config()
+                    .ballista_checksum_results(),
+                "job {job_id}"
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn pool_scheduler(
+        lend_when_idle: bool,
+    ) -> Result<SchedulerServer<LogicalPlanNode, PhysicalPlanNode>> {
+        let pools = ExecutorPools::try_new(vec![ExecutorPool::new("analytics")
+            .with_tenant("alice")
+            .with_lend_when_idle(lend_when_idle)])?;
+        let config = SchedulerConfig::default()
+            .with_scheduler_policy(TaskSchedulingPolicy::PullStaged)
+            .with_executor_pools(pools);
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                test_cluster_context(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                Arc::new(TestMetricsCollector::default()),
+            );
+        scheduler.init().await?;
+        Ok(scheduler)
+    }
+
+    async fn submit_tenant_job(
+        scheduler: &SchedulerServer<LogicalPlanNode, PhysicalPlanNode>,
+        job_id: &str,
+        tenant: &str,
+    ) -> Result<()> {
+        let config = SessionConfig::new_with_ballista().with_target_partitions(4);
+        let ctx = scheduler
+            .state
+            .session_manager
+            .create_session(&config)
+            .await?;
+        scheduler.state.task_manager.set_job_owner(job_id, tenant);
+        scheduler
+            .state
+            .task_manager
+            .queue_job(job_id, "", timestamp_millis())?;
+        let event_sender = scheduler.query_stage_event_loop.get_sender()?;
+        scheduler
+            .state
+            .submit_job(job_id, "", ctx, &test_plan(), 0, &event_sender)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_pool_binding() -> Result<()> {
+        let scheduler = pool_scheduler(true).await?;
+        // executor-1 is dedicated to the analytics pool, executor-2 is shared
+        for (mut executor_metadata, executor_data) in test_executors(4) {
+            if executor_metadata.id == "executor-1" {
+                executor_metadata.pool = "analytics".to_owned();
+            }
+            scheduler
+                .state
+                .executor_manager
+                .register_executor(executor_metadata, executor_data)
+                .await?;
+        }
+        submit_tenant_job(&scheduler, "alice-job", "alice").await?;
+        submit_tenant_job(&scheduler, "bob-job", "bob").await?;
+
+        let bound_tasks = scheduler
+            .state
+            .executor_manager
+            .bind_schedulable_tasks(scheduler.state.task_manager.get_running_job_cache())
+            .await?;
+
+        assert_eq!(4, bound_tasks.len());
+        for (executor_id, task) in bound_tasks {
+            let expected = match task.partition.job_id.as_str() {
+                "alice-job" => "executor-1",
+                _ => "executor-2",
+            };
+            assert_eq!(expected, executor_id, "{}", task.partition.job_id);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_pool_lending() -> Result<()> {
+        for lend_when_idle in [false, true] {
+            let scheduler = pool_scheduler(lend_when_idle).await?;
+            let mut executors = test_executors(4).into_iter();
+            let (mut executor_metadata, executor_data) = executors.next().unwrap();
+            executor_metadata.pool = "analytics".to_owned();
+            scheduler
+                .state
+                .executor_manager
+                .register_executor(executor_metadata, executor_data)
+                .await?;
+            // executors can't join the pools the scheduler doesn't know
+            let (mut executor_metadata, executor_data) = executors.next().unwrap();
+            executor_metadata.pool = "unknown".to_owned();
+            assert!(scheduler
+                .state
+                .executor_manager
+                .register_executor(executor_metadata, executor_data)
+                .await
+                .is_err());
+
+            // the jobs of bob only borrow the analytics pool while alice has no job
+            submit_tenant_job(&scheduler, "bob-job", "bob").await?;
+            let bound_tasks = scheduler
+                .state
+                .executor_manager
+                .bind_schedulable_tasks(
+                    scheduler.state.task_manager.get_running_job_cache(),
+                )
+                .await?;
+            assert_eq!(lend_when_idle, !bound_tasks.is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_function_validation() -> Result<()> {
+        let my_udf = create_udf(
+            "my_udf",
+            vec![DataType::Utf8],
+            Arc::new(DataType::Utf8),
+            Volatility::Immutable,
+            Arc::new(|args| Ok(args[0].clone())),
+        );
+        let schema = Schema::new(vec![Field::new("id", DataType::Utf8, false)]);
+        let plan = scan_empty_with_partitions(None, &schema, None, 2)?
+            .project(vec![my_udf.call(vec![col("id")])])?
+            .build()?;
+
+        for validate_job_functions in [true, false] {
+            let config = SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PullStaged)
+                .with_validate_job_functions(validate_job_functions);
+            let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+                SchedulerServer::new(
+                    "localhost:50050".to_owned(),
+                    test_cluster_context(),
+                    BallistaCodec::default(),
+                    Arc::new(config),
+                    Arc::new(TestMetricsCollector::default()),
+                );
+            scheduler.init().await?;
+
+            // Only the first executor has the user defined function of the plan
+            for (i, (mut executor_metadata, executor_data)) in
+                test_executors(4).into_iter().enumerate()
+            {
+                executor_metadata.functions = if i == 0 {
+                    Some(vec!["my_udf".to_owned()])
+                } else {
+                    Some(vec![])
+                };
+                scheduler
+                    .state
+                    .executor_manager
+                    .register_executor(executor_metadata, executor_data)
+                    .await?;
+            }
+
+            let ctx = scheduler
+                .state
+                .session_manager
+                .create_session(&SessionConfig::new_with_ballista())
+                .await?;
+            scheduler
+                .state
+                .task_manager
+                .queue_job("job", "", timestamp_millis())?;
+            let event_sender = scheduler.query_stage_event_loop.get_sender()?;
+            let result = scheduler
+                .state
+                .submit_job("job", "", ctx, &plan, 0, &event_sender)
+                .await;
+
+            if validate_job_functions {
+                let error = result.expect_err("submitting plan").to_string();
+                assert!(
+                    error.contains("my_udf (missing on executor-2)"),
+                    "Unexpected error {error}"
+                );
+            } else {
+                result.expect("submitting plan");
+            }
+        }
+
+        Ok(())
+    }
+
     async fn test_scheduler(
         scheduling_policy: TaskSchedulingPolicy,
     ) -> Result<SchedulerServer<LogicalPlanNode, PhysicalPlanNode>> {
@@ -675,6 +1684,10 @@ mod test {
                     port: 8080,
                     grpc_port: 9090,
                     specification: ExecutorSpecification { task_slots },
+                    version: build_version(),
+                    functions: None,
+                    shuffle_formats: None,
+                    pool: String::new(),
                 },
                 ExecutorData {
                     executor_id: "executor-1".to_owned(),
@@ -691,6 +1704,10 @@ mod test {
                     specification: ExecutorSpecification {
                         task_slots: num_partitions as u32 - task_slots,
                     },
+                    version: build_version(),
+                    functions: None,
+                    shuffle_formats: None,
+                    pool: String::new(),
                 },
                 ExecutorData {
                     executor_id: "executor-2".to_owned(),
@@ -701,6 +1718,114 @@ mod test {
         ]
     }
 
+    #[tokio::test]
+    async fn test_read_replica() -> Result<()> {
+        let store: Arc<dyn KeyValueStore> = Arc::new(InMemoryKeyValueStore::default());
+        let scheduler = |name: &str, config: SchedulerConfig| {
+            let cluster = BallistaCluster::new_key_value(
+                store.clone(),
+                name,
+                Arc::new(default_session_builder),
+                Arc::new(default_config_producer),
+                BallistaCodec::default(),
+                Duration::from_secs(60),
+            );
+            SchedulerServer::<LogicalPlanNode, PhysicalPlanNode>::new(
+                name.to_owned(),
+                cluster,
+                BallistaCodec::default(),
+                Arc::new(config.with_scheduler_policy(TaskSchedulingPolicy::PullStaged)),
+                Arc::new(TestMetricsCollector::default()),
+            )
+        };
+        let mut leader = scheduler("leader:50050", SchedulerConfig::default());
+        leader.leader_election.as_ref().unwrap().acquire().await;
+        leader.init().await?;
+        let mut replica = scheduler(
+            "replica:50050",
+            SchedulerConfig::default().with_read_replica(true),
+        );
+        replica.init().await?;
+        submit_tenant_job(&leader, "job", "alice").await?;
+
+        // the replica serves the state of the jobs of the leader
+        let status = replica
+            .get_job_status(Request::new(GetJobStatusParams {
+                job_id: "job".to_owned(),
+            }))
+            .await
+            .expect("getting the status of the job")
+            .into_inner()
+            .status
+            .expect("job status");
+        assert_eq!("job", status.job_id);
+        let jobs = replica.state.task_manager.get_jobs().await?;
+        assert!(jobs.iter().any(|job| job.job_id == "job"));
+
+        // and directs the writes to the leader
+        let status = replica
+            .execute_query(Request::new(ExecuteQueryParams::default()))
+            .await
+            .expect_err("a read replica doesn't run jobs");
+        assert_eq!(tonic::Code::FailedPrecondition, status.code());
+        assert_eq!(
+            Some("http://leader:50050".to_owned()),
+            leader_url_from_status(&status)
+        );
+        let status = replica
+            .register_executor(Request::new(RegisterExecutorParams::default()))
+            .await
+            .expect_err("executors register with the leader");
+        assert_eq!(
+            Some("http://leader:50050".to_owned()),
+            leader_url_from_status(&status)
+        );
+        assert!(leader.ensure_writable().await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recover_job_owner() -> Result<()> {
+        let store: Arc<dyn KeyValueStore> = Arc::new(InMemoryKeyValueStore::default());
+        let scheduler = || {
+            let cluster = BallistaCluster::new_key_value(
+                store.clone(),
+                "scheduler:50050",
+                Arc::new(default_session_builder),
+                Arc::new(default_config_producer),
+                BallistaCodec::default(),
+                Duration::from_secs(60),
+            );
+            SchedulerServer::<LogicalPlanNode, PhysicalPlanNode>::new(
+                "scheduler:50050".to_owned(),
+                cluster,
+                BallistaCodec::default(),
+                Arc::new(
+                    SchedulerConfig::default()
+                        .with_scheduler_policy(TaskSchedulingPolicy::PullStaged),
+                ),
+                Arc::new(TestMetricsCollector::default()),
+            )
+        };
+        let mut failed = scheduler();
+        failed.init().await?;
+        submit_tenant_job(&failed, "job", "alice").await?;
+
+        // the restarted scheduler takes the job over along with its owner
+        let mut restarted = scheduler();
+        restarted.init().await?;
+        let task_manager = &restarted.state.task_manager;
+        assert_eq!(Some("alice".to_owned()), task_manager.job_owner("job"));
+        let tenants = task_manager
+            .get_running_job_cache()
+            .iter()
+            .map(|(job_id, job_info)| (job_id.clone(), job_info.tenant.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("job".to_owned(), Some("alice".to_owned()))], tenants);
+        Ok(())
+    }
+
     fn test_plan() -> LogicalPlan {
         let schema = Schema::new(vec![
             Field::new("id", DataType::Utf8, false),
@@ -21,7 +21,7 @@ use std::time::Duration;
 use async_trait::async_trait;
 use log::{debug, error, info, warn};
 
-use ballista_core::error::{BallistaError, Result};
+use ballista_core::error::{BallistaError, ErrorCode, Result};
 use ballista_core::event_loop::{EventAction, EventSender};
 
 use crate::config::SchedulerConfig;
@@ -34,6 +34,7 @@ use tokio::time::Instant;
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 
+use crate::state::stage_verification::verified_job_id;
 use crate::state::SchedulerState;
 
 pub(crate) struct QueryStageScheduler<
@@ -57,10 +58,34 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> QueryStageSchedul
             config,
         }
     }
-    #[cfg(feature = "rest-api")]
     pub(crate) fn metrics_collector(&self) -> &dyn SchedulerMetricsCollector {
         self.metrics_collector.as_ref()
     }
+
+    /// Record the outcome of a finished job if it takes part in a shadow execution.
+    /// The result of successful jobs is read from the executors in the background.
+    fn record_shadow_outcome(
+        &self,
+        job_id: &str,
+        runtime_ms: u64,
+        failure: Option<String>,
+    ) {
+        if !self.state.shadow_execution.is_tracked(job_id) {
+            return;
+        }
+
+        let state = self.state.clone();
+        let metrics_collector = self.metrics_collector.clone();
+        let job_id = job_id.to_owned();
+        tokio::spawn(async move {
+            if let Some(comparison) = state
+                .record_shadow_outcome(&job_id, runtime_ms, failure)
+                .await
+            {
+                metrics_collector.record_shadow_execution(&comparison);
+            }
+        });
+    }
 }
 
 #[async_trait]
@@ -104,27 +129,45 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     error!("Fail to queue job {} due to {:?}", job_id, e);
                     return Ok(());
                 }
+                self.state.task_manager.publish_job_status(&job_id).await;
 
                 let state = self.state.clone();
                 tokio::spawn(async move {
-                    let event = if let Err(e) = state
-                        .submit_job(&job_id, &job_name, session_ctx, &plan, queued_at)
+                    let event = match state
+                        .submit_job(
+                            &job_id,
+                            &job_name,
+                            session_ctx,
+                            &plan,
+                            queued_at,
+                            &event_sender,
+                        )
                         .await
                     {
-                        let fail_message = format!("Error planning job {job_id}: {e:?}");
-                        error!("{}", &fail_message);
-                        QueryStageSchedulerEvent::JobPlanningFailed {
-                            job_id,
-                            fail_message,
-                            queued_at,
-                            failed_at: timestamp_millis(),
+                        Err(e) => {
+                            let fail_message =
+                                format!("Error planning job {job_id}: {e:?}");
+                            error!("{}", &fail_message);
+                            QueryStageSchedulerEvent::JobPlanningFailed {
+                                job_id,
+                                fail_message,
+                                queued_at,
+                                failed_at: timestamp_millis(),
+                            }
                         }
-                    } else {
-                        QueryStageSchedulerEvent::JobSubmitted {
+                        Ok(Some(cached_job_id)) => {
+                            QueryStageSchedulerEvent::JobServedFromCache {
+                                job_id,
+                                cached_job_id,
+                                queued_at,
+                                completed_at: timestamp_millis(),
+                            }
+                        }
+                        Ok(None) => QueryStageSchedulerEvent::JobSubmitted {
                             job_id,
                             queued_at,
                             submitted_at: timestamp_millis(),
-                        }
+                        },
                     };
                     if let Err(e) = event_sender.post_event(event).await {
                         error!("Fail to send event due to {}", e);
@@ -140,6 +183,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     .record_submitted(&job_id, queued_at, submitted_at);
 
                 info!("Job {} submitted", job_id);
+                self.state.log_job_submitted(&job_id, submitted_at).await;
+                self.state.task_manager.publish_job_status(&job_id).await;
 
                 if self.state.config.is_push_staged_scheduling() {
                     event_sender
@@ -157,6 +202,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     .record_failed(&job_id, queued_at, failed_at);
 
                 error!("Job {} failed: {}", job_id, fail_message);
+                self.record_shadow_outcome(
+                    &job_id,
+                    failed_at.saturating_sub(queued_at),
+                    Some(fail_message.clone()),
+                );
                 if let Err(e) = self
                     .state
                     .task_manager
@@ -168,6 +218,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                         job_id, e
                     );
                 }
+                self.state.task_manager.publish_job_status(&job_id).await;
+                self.state.notify_webhooks(&job_id).await;
+                self.state.log_job_completed(&job_id).await;
+                self.state.query_cache.remove_job(&job_id);
+                self.state.clean_up_internal_jobs(&job_id);
             }
             QueryStageSchedulerEvent::JobFinished {
                 job_id,
@@ -178,12 +233,43 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     .record_completed(&job_id, queued_at, completed_at);
 
                 info!("Job {} success", job_id);
+                // before the graph of the job leaves the active jobs
+                self.state.calibrate_exchanges(&job_id).await;
+                self.state.record_table_statistics(&job_id).await;
                 if let Err(e) = self.state.task_manager.succeed_job(&job_id).await {
                     error!(
                         "Fail to invoke succeed_job for job {} due to {:?}",
                         job_id, e
                     );
                 }
+                self.state.task_manager.publish_job_status(&job_id).await;
+                self.state.notify_webhooks(&job_id).await;
+                self.state.log_job_completed(&job_id).await;
+                self.state.retain_job_results(&job_id).await;
+                self.state.cache_job_results(&job_id).await;
+                self.record_shadow_outcome(
+                    &job_id,
+                    completed_at.saturating_sub(queued_at),
+                    None,
+                );
+                self.state.clean_up_successful_job(job_id);
+            }
+            QueryStageSchedulerEvent::JobServedFromCache {
+                job_id,
+                cached_job_id,
+                queued_at,
+                completed_at,
+            } => {
+                self.metrics_collector
+                    .record_completed(&job_id, queued_at, completed_at);
+
+                info!(
+                    "Job {} served from the results of job {}",
+                    job_id, cached_job_id
+                );
+                self.state.task_manager.publish_job_status(&job_id).await;
+                self.state.notify_webhooks(&job_id).await;
+                self.state.log_job_completed(&job_id).await;
                 self.state.clean_up_successful_job(job_id);
             }
             QueryStageSchedulerEvent::JobRunningFailed {
@@ -196,12 +282,21 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     .record_failed(&job_id, queued_at, failed_at);
 
                 error!("Job {} running failed", job_id);
-                match self
+                self.record_shadow_outcome(
+                    &job_id,
+                    failed_at.saturating_sub(queued_at),
+                    Some(fail_message.clone()),
+                );
+                let aborted = self
                     .state
                     .task_manager
-                    .abort_job(&job_id, fail_message)
-                    .await
-                {
+                    .abort_job(&job_id, ErrorCode::ExecutionFailed, fail_message)
+                    .await;
+                self.state.task_manager.publish_job_status(&job_id).await;
+                self.state.notify_webhooks(&job_id).await;
+                self.state.log_job_completed(&job_id).await;
+                self.state.query_cache.remove_job(&job_id);
+                match aborted {
                     Ok((running_tasks, _pending_tasks)) => {
                         if !running_tasks.is_empty() {
                             event_sender
@@ -222,18 +317,25 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             }
             QueryStageSchedulerEvent::JobUpdated(job_id) => {
                 info!("Job {} Updated", job_id);
-                if let Err(e) = self.state.task_manager.update_job(&job_id).await {
-                    error!(
-                        "Fail to invoke update_job for job {} due to {:?}",
-                        job_id, e
-                    );
-                }
+                self.state.task_manager.queue_job_update(&job_id);
             }
             QueryStageSchedulerEvent::JobCancel(job_id) => {
                 self.metrics_collector.record_cancelled(&job_id);
 
                 info!("Job {} Cancelled", job_id);
-                match self.state.task_manager.cancel_job(&job_id).await {
+                // The comparison of a cancelled job is meaningless, and its shadow job
+                // would only waste resources
+                if let Some(shadow_job_id) = self.state.shadow_execution.forget(&job_id) {
+                    event_sender
+                        .post_event(QueryStageSchedulerEvent::JobCancel(shadow_job_id))
+                        .await?;
+                }
+                let cancelled = self.state.task_manager.cancel_job(&job_id).await;
+                self.state.task_manager.publish_job_status(&job_id).await;
+                self.state.notify_webhooks(&job_id).await;
+                self.state.log_job_completed(&job_id).await;
+                self.state.query_cache.remove_job(&job_id);
+                match cancelled {
                     Ok((running_tasks, _pending_tasks)) => {
                         event_sender
                             .post_event(QueryStageSchedulerEvent::CancelTasks(
@@ -256,28 +358,45 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     tasks_status
                 );
 
-                let num_status = tasks_status.len();
+                // verification tasks are launched without binding a slot
+                let num_status = tasks_status
+                    .iter()
+                    .filter(|status| verified_job_id(&status.job_id).is_none())
+                    .count();
                 if self.state.config.is_push_staged_scheduling() {
                     self.state
                         .executor_manager
                         .unbind_tasks(vec![(executor_id.clone(), num_status as u32)])
                         .await?;
                 }
+                // the statuses are queued to the drivers of their jobs in the order
+                // they are received, and applied off the event loop
                 match self
                     .state
-                    .update_task_statuses(&executor_id, tasks_status)
+                    .dispatch_task_statuses(&executor_id, tasks_status)
                     .await
                 {
-                    Ok(stage_events) => {
-                        if self.state.config.is_push_staged_scheduling() {
-                            event_sender
-                                .post_event(QueryStageSchedulerEvent::ReviveOffers)
-                                .await?;
+                    Ok((verification_events, updates)) => {
+                        for event in verification_events {
+                            event_sender.post_event(event).await?;
                         }
 
-                        for stage_event in stage_events {
-                            event_sender.post_event(stage_event).await?;
-                        }
+                        let state = self.state.clone();
+                        let event_sender = event_sender.clone();
+                        tokio::spawn(async move {
+                            let mut stage_events =
+                                state.await_task_statuses(updates).await;
+                            if state.config.is_push_staged_scheduling() {
+                                stage_events
+                                    .insert(0, QueryStageSchedulerEvent::ReviveOffers);
+                            }
+                            for stage_event in stage_events {
+                                if let Err(e) = event_sender.post_event(stage_event).await
+                                {
+                                    error!("Fail to send event due to {}", e);
+                                }
+                            }
+                        });
                     }
                     Err(e) => {
                         error!(
@@ -292,6 +411,14 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 self.state.revive_offers(event_sender).await?;
             }
             QueryStageSchedulerEvent::ExecutorLost(executor_id, _) => {
+                for event in self
+                    .state
+                    .task_manager
+                    .stage_verification()
+                    .executor_lost(&executor_id)
+                {
+                    event_sender.post_event(event).await?;
+                }
                 match self.state.task_manager.executor_lost(&executor_id).await {
                     Ok(tasks) => {
                         if !tasks.is_empty() {
@@ -324,8 +451,22 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 }
             }
             QueryStageSchedulerEvent::JobDataClean(job_id) => {
+                self.state
+                    .result_retention
+                    .release(&job_id, timestamp_millis());
                 self.state.executor_manager.clean_up_job_data(job_id);
             }
+            QueryStageSchedulerEvent::RemoveTaskOutputs {
+                job_id,
+                executor_id,
+                paths,
+            } => {
+                self.state.executor_manager.remove_task_outputs(
+                    executor_id,
+                    job_id,
+                    paths,
+                );
+            }
         }
         if let Some((start, ec)) = time_recorder {
             let duration = start.elapsed();
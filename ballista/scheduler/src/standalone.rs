@@ -78,17 +78,20 @@ pub async fn new_standalone_scheduler_with_builder(
         BallistaCluster::new_memory("localhost:50050", session_builder, config_producer);
     let metrics_collector = default_metrics_collector()?;
 
+    let config = Arc::new(SchedulerConfig::default());
     let mut scheduler_server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
         SchedulerServer::new(
             "localhost:50050".to_owned(),
             cluster,
             codec,
-            Arc::new(SchedulerConfig::default()),
+            config.clone(),
             metrics_collector,
         );
 
     scheduler_server.init().await?;
-    let server = SchedulerGrpcServer::new(scheduler_server.clone());
+    let server = SchedulerGrpcServer::new(scheduler_server.clone())
+        .max_encoding_message_size(config.grpc_server_max_encoding_message_size as usize)
+        .max_decoding_message_size(config.grpc_server_max_decoding_message_size as usize);
     // Let the OS assign a random, free port
     let listener = TcpListener::bind("localhost:0").await?;
     let addr = listener.local_addr()?;
@@ -97,7 +100,7 @@ pub async fn new_standalone_scheduler_with_builder(
         BALLISTA_VERSION, addr
     );
     tokio::spawn(
-        create_grpc_server()
+        create_grpc_server(&Default::default())?
             .add_service(server)
             .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(
                 listener,
@@ -0,0 +1,348 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Catalog of the tables and views shared by all the sessions of the scheduler.
+//!
+//! The catalog is the default schema of the default catalog of the sessions, so that
+//! the DDL statements they run, e.g. through Flight SQL, register their tables in it.
+//! Clients reference its tables by name with [ClusterTable]s, which the scheduler
+//! resolves to the tables of the catalog before planning the jobs.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ballista_core::cluster_catalog::ClusterTable;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::BallistaLogicalExtensionCodec;
+use datafusion::catalog::{SchemaProvider, TableProvider};
+use datafusion::common::exec_err;
+use datafusion::common::tree_node::{Transformed, TreeNodeRecursion};
+use datafusion::datasource::{provider_as_source, source_as_provider};
+use datafusion::logical_expr::{LogicalPlan, LogicalPlanBuilder};
+use datafusion::prelude::SessionContext;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::protobuf::LogicalPlanNode;
+use log::{info, warn};
+use parking_lot::RwLock;
+use prost::Message;
+
+/// Extension of the files holding the definitions of the tables of the catalog
+const TABLE_FILE_EXTENSION: &str = "pb";
+
+/// Tables of the cluster, kept in memory and persisted to a local directory, if any
+pub struct ClusterCatalog {
+    tables: RwLock<HashMap<String, Arc<dyn TableProvider>>>,
+    /// Bumped whenever a table is registered or deregistered
+    version: AtomicU64,
+    dir: Option<PathBuf>,
+    codec: BallistaLogicalExtensionCodec,
+}
+
+impl ClusterCatalog {
+    pub fn new(dir: Option<String>) -> Self {
+        Self {
+            tables: RwLock::new(HashMap::new()),
+            version: AtomicU64::new(0),
+            dir: dir.map(PathBuf::from),
+            codec: BallistaLogicalExtensionCodec::default(),
+        }
+    }
+
+    /// Load the tables persisted by a previous scheduler
+    pub fn load(&self) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(dir)?;
+
+        let ctx = SessionContext::new();
+        let mut tables = self.tables.write();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(TABLE_FILE_EXTENSION) {
+                continue;
+            }
+            let bytes = std::fs::read(&path)?;
+            let plan = LogicalPlanNode::decode(bytes.as_slice())
+                .map_err(|e| {
+                    BallistaError::Internal(format!(
+                        "Could not deserialize cluster table {}: {e}",
+                        path.display()
+                    ))
+                })?
+                .try_into_logical_plan(&ctx, &self.codec)?;
+            let LogicalPlan::TableScan(scan) = plan else {
+                return Err(BallistaError::Internal(format!(
+                    "Cluster table {} is not a table scan",
+                    path.display()
+                )));
+            };
+            tables.insert(
+                scan.table_name.table().to_owned(),
+                source_as_provider(&scan.source)?,
+            );
+        }
+        self.version.fetch_add(1, Ordering::SeqCst);
+        info!("Loaded {} tables of the cluster catalog", tables.len());
+        Ok(())
+    }
+
+    /// Version of the tables of the catalog, which changes whenever a table is
+    /// registered or deregistered
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// References to the tables of the catalog, ordered by name
+    pub fn tables(&self) -> Vec<ClusterTable> {
+        let tables = self.tables.read();
+        let mut tables: Vec<_> = tables
+            .iter()
+            .map(|(name, table)| {
+                ClusterTable::new(name.clone(), table.schema(), table.table_type())
+            })
+            .collect();
+        tables.sort_by(|a, b| a.name().cmp(b.name()));
+        tables
+    }
+
+    /// Replace the references to the tables of the catalog in the plan and its
+    /// subqueries by the tables themselves
+    pub fn resolve(&self, plan: LogicalPlan) -> Result<LogicalPlan> {
+        let mut referenced = false;
+        plan.apply_with_subqueries(|node| {
+            referenced = cluster_table(node).is_some();
+            Ok(if referenced {
+                TreeNodeRecursion::Stop
+            } else {
+                TreeNodeRecursion::Continue
+            })
+        })?;
+        if !referenced {
+            return Ok(plan);
+        }
+
+        let tables = self.tables.read();
+        let plan = plan
+            .transform_up_with_subqueries(|node| {
+                let Some(reference) = cluster_table(&node) else {
+                    return Ok(Transformed::no(node));
+                };
+                let Some(table) = tables.get(reference.name()) else {
+                    return exec_err!(
+                        "Table {} is not in the cluster catalog",
+                        reference.name()
+                    );
+                };
+                if table.schema().fields() != reference.schema().fields() {
+                    return exec_err!(
+                        "Table {} of the cluster catalog changed since it was listed",
+                        reference.name()
+                    );
+                }
+                let LogicalPlan::TableScan(mut scan) = node else {
+                    unreachable!("cluster tables are table scans");
+                };
+                scan.source = provider_as_source(table.clone());
+                Ok(Transformed::yes(LogicalPlan::TableScan(scan)))
+            })?
+            .data;
+        Ok(plan)
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{name}.{TABLE_FILE_EXTENSION}")))
+    }
+
+    fn persist(&self, name: &str, table: Arc<dyn TableProvider>) -> Result<()> {
+        let Some(path) = self.path(name) else {
+            return Ok(());
+        };
+        let plan =
+            LogicalPlanBuilder::scan(name, provider_as_source(table), None)?.build()?;
+        let bytes =
+            LogicalPlanNode::try_from_logical_plan(&plan, &self.codec)?.encode_to_vec();
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+/// The reference to a table of the catalog scanned by the node, if any
+fn cluster_table(node: &LogicalPlan) -> Option<ClusterTable> {
+    let LogicalPlan::TableScan(scan) = node else {
+        return None;
+    };
+    source_as_provider(&scan.source)
+        .ok()?
+        .as_any()
+        .downcast_ref::<ClusterTable>()
+        .cloned()
+}
+
+#[async_trait]
+impl SchemaProvider for ClusterCatalog {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.tables.read().keys().cloned().collect()
+    }
+
+    async fn table(
+        &self,
+        name: &str,
+    ) -> datafusion::common::Result<Option<Arc<dyn TableProvider>>> {
+        Ok(self.tables.read().get(name).cloned())
+    }
+
+    fn register_table(
+        &self,
+        name: String,
+        table: Arc<dyn TableProvider>,
+    ) -> datafusion::common::Result<Option<Arc<dyn TableProvider>>> {
+        if self.table_exist(&name) {
+            return exec_err!("The table {name} already exists");
+        }
+        // tables which can't be encoded, e.g. the results of CREATE TABLE AS, are
+        // still shared by the sessions until the scheduler restarts
+        if let Err(e) = self.persist(&name, table.clone()) {
+            warn!("Could not persist table {name} of the cluster catalog: {e}");
+        }
+        let previous = self.tables.write().insert(name, table);
+        self.version.fetch_add(1, Ordering::SeqCst);
+        Ok(previous)
+    }
+
+    fn deregister_table(
+        &self,
+        name: &str,
+    ) -> datafusion::common::Result<Option<Arc<dyn TableProvider>>> {
+        let table = self.tables.write().remove(name);
+        if table.is_some() {
+            self.version.fetch_add(1, Ordering::SeqCst);
+            if let Some(path) = self.path(name) {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    warn!("Could not remove table {name} of the cluster catalog: {e}");
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    fn table_exist(&self, name: &str) -> bool {
+        self.tables.read().contains_key(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ballista_core::error::Result;
+    use datafusion::catalog::{SchemaProvider, TableProvider};
+    use datafusion::logical_expr::TableType;
+    use datafusion::prelude::SessionContext;
+    use tempfile::TempDir;
+
+    use super::ClusterCatalog;
+
+    fn session(catalog: &Arc<ClusterCatalog>) -> Result<SessionContext> {
+        let ctx = SessionContext::new();
+        ctx.catalog("datafusion")
+            .unwrap()
+            .register_schema("public", catalog.clone())?;
+        Ok(ctx)
+    }
+
+    fn table_names(catalog: &ClusterCatalog) -> Vec<String> {
+        catalog
+            .tables()
+            .iter()
+            .map(|table| table.name().to_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cluster_catalog() -> Result<()> {
+        let dir = TempDir::new()?;
+        let data = dir.path().join("people.csv");
+        std::fs::write(&data, "name,age\nalice,30\nbob,40\n")?;
+        let catalog_dir = dir.path().join("catalog").to_string_lossy().into_owned();
+        let catalog = Arc::new(ClusterCatalog::new(Some(catalog_dir.clone())));
+        catalog.load()?;
+
+        // tables created by a session are visible to the others
+        session(&catalog)?
+            .sql(&format!(
+                "CREATE EXTERNAL TABLE people STORED AS CSV LOCATION '{}' \
+                 OPTIONS ('format.has_header' 'true')",
+                data.display()
+            ))
+            .await?;
+        let other = session(&catalog)?;
+        other
+            .sql("CREATE VIEW names AS SELECT name FROM people")
+            .await?;
+        let tables = catalog.tables();
+        assert_eq!(vec!["names", "people"], table_names(&catalog));
+        assert_eq!(TableType::View, tables[0].table_type());
+
+        // clients reference the tables, which the scheduler resolves
+        let client = SessionContext::new();
+        for table in tables {
+            client.register_table(table.name().to_owned(), Arc::new(table))?;
+        }
+        assert!(client
+            .sql("SELECT * FROM people")
+            .await?
+            .collect()
+            .await
+            .is_err());
+        let plan = client
+            .sql("SELECT name FROM people WHERE age > 35")
+            .await?
+            .into_unoptimized_plan();
+        let resolved = catalog.resolve(plan)?;
+        let batches = other
+            .execute_logical_plan(resolved)
+            .await?
+            .collect()
+            .await?;
+        assert_eq!(
+            1,
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>()
+        );
+
+        // the definitions of the tables outlive the scheduler
+        let reloaded = ClusterCatalog::new(Some(catalog_dir.clone()));
+        reloaded.load()?;
+        assert_eq!(vec!["names", "people"], table_names(&reloaded));
+
+        catalog.deregister_table("names")?;
+        let reloaded = ClusterCatalog::new(Some(catalog_dir));
+        reloaded.load()?;
+        assert_eq!(vec!["people"], table_names(&reloaded));
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Log of the events of the jobs and executors of the scheduler, written as JSON
+//! lines to a directory or object store, so that the history of the jobs outlives the
+//! scheduler state. See [crate::history] for reading it back.
+//!
+//! The events of a job are kept in memory until it completes, and then written to
+//! `jobs/<job_id>.json` at once, as object stores can't append to objects. Executor
+//! events are written as soon as they happen, each to an object of `executors/`.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::{job_status, task_status, JobStatus, TaskStatus};
+use ballista_core::serde::scheduler::ExecutorMetadata;
+use dashmap::DashMap;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::displayable;
+use datafusion::physical_plan::metrics::MetricsSet;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SchedulerConfig;
+use crate::scheduler_server::timestamp_millis;
+use crate::state::execution_graph::{ExecutionGraph, TaskDescription};
+use crate::state::job_metrics::summarize_metrics;
+
+/// Directory of the event log holding the events of the completed jobs
+pub const JOBS_DIR: &str = "jobs";
+/// Directory of the event log holding the events of the executors
+pub const EXECUTORS_DIR: &str = "executors";
+
+/// An event of the event log. Timestamps are in milliseconds since the Unix epoch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    JobSubmitted {
+        job_id: String,
+        job_name: String,
+        queued_at: u64,
+        submitted_at: u64,
+    },
+    /// A stage of a submitted job, whose number of partitions is unknown until the
+    /// stages it reads from complete
+    StagePlanned {
+        job_id: String,
+        stage_id: usize,
+        partitions: Option<usize>,
+        output_links: Vec<usize>,
+        plan: String,
+    },
+    TaskStarted {
+        job_id: String,
+        stage_id: usize,
+        partition_id: usize,
+        task_id: usize,
+        task_attempt: usize,
+        executor_id: String,
+        timestamp: u64,
+    },
+    /// A task which succeeded or failed, with its metrics summed by name
+    TaskFinished {
+        job_id: String,
+        stage_id: usize,
+        partition_id: usize,
+        task_id: usize,
+        executor_id: String,
+        successful: bool,
+        error: Option<String>,
+        launch_time: u64,
+        start_exec_time: u64,
+        end_exec_time: u64,
+        metrics: BTreeMap<String, u64>,
+    },
+    /// A job which succeeded, failed or was cancelled, its last event
+    JobCompleted {
+        job_id: String,
+        job_name: String,
+        successful: bool,
+        error: Option<String>,
+        queued_at: u64,
+        started_at: u64,
+        ended_at: u64,
+    },
+    ExecutorRegistered {
+        executor_id: String,
+        host: String,
+        port: u16,
+        task_slots: u32,
+        timestamp: u64,
+    },
+    ExecutorLost {
+        executor_id: String,
+        reason: Option<String>,
+        timestamp: u64,
+    },
+}
+
+impl HistoryEvent {
+    /// The event of a task status reported by an executor, unless the task is running
+    pub fn task_finished(executor_id: &str, status: &TaskStatus) -> Option<Self> {
+        let error = match status.status.as_ref()? {
+            task_status::Status::Running(_) => return None,
+            task_status::Status::Successful(_) => None,
+            task_status::Status::Failed(failed) => Some(failed.error.clone()),
+        };
+        let metrics: Vec<MetricsSet> = status
+            .metrics
+            .iter()
+            .filter_map(|metrics| metrics.clone().try_into().ok())
+            .collect();
+        Some(HistoryEvent::TaskFinished {
+            job_id: status.job_id.clone(),
+            stage_id: status.stage_id as usize,
+            partition_id: status.partition_id as usize,
+            task_id: status.task_id as usize,
+            executor_id: executor_id.to_owned(),
+            successful: error.is_none(),
+            error,
+            launch_time: status.launch_time,
+            start_exec_time: status.start_exec_time,
+            end_exec_time: status.end_exec_time,
+            metrics: summarize_metrics(&metrics).into_iter().collect(),
+        })
+    }
+
+    /// The event of the final status of a job, none if it isn't completed
+    pub fn job_completed(status: &JobStatus) -> Option<Self> {
+        let (successful, error, queued_at, started_at, ended_at) =
+            match status.status.as_ref()? {
+                job_status::Status::Successful(job) => {
+                    (true, None, job.queued_at, job.started_at, job.ended_at)
+                }
+                job_status::Status::Failed(job) => (
+                    false,
+                    Some(job.error.clone()),
+                    job.queued_at,
+                    job.started_at,
+                    job.ended_at,
+                ),
+                _ => return None,
+            };
+        Some(HistoryEvent::JobCompleted {
+            job_id: status.job_id.clone(),
+            job_name: status.job_name.clone(),
+            successful,
+            error,
+            queued_at,
+            started_at,
+            ended_at,
+        })
+    }
+}
+
+/// Encode events as JSON lines
+pub fn encode_events(events: &[HistoryEvent]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for event in events {
+        serde_json::to_writer(&mut bytes, event)
+            .map_err(|e| BallistaError::General(format!("Invalid event: {e}")))?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+/// Writes the events of the jobs and executors to the event log
+#[derive(Clone)]
+pub struct EventLog {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    /// Events of the jobs which didn't complete yet, by job ID
+    jobs: Arc<DashMap<String, Vec<HistoryEvent>>>,
+    /// Number of the executor events written, ordering those of the same millisecond
+    executor_events: Arc<AtomicU64>,
+}
+
+impl EventLog {
+    /// Create a log writing its events under `prefix` of `store`
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: Path) -> Self {
+        Self {
+            store,
+            prefix,
+            jobs: Arc::new(DashMap::new()),
+            executor_events: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Create the event log configured for the scheduler, if any
+    pub fn from_config(config: &SchedulerConfig) -> Option<Self> {
+        if config.event_log_dir.is_none() && config.override_event_log_store.is_none() {
+            return None;
+        }
+
+        let store = config
+            .override_event_log_store
+            .clone()
+            .unwrap_or_else(|| Arc::new(LocalFileSystem::new()));
+        let prefix = config
+            .event_log_dir
+            .as_deref()
+            .map(Path::from)
+            .unwrap_or_default();
+
+        Some(Self::new(store, prefix))
+    }
+
+    fn record(&self, job_id: &str, event: HistoryEvent) {
+        self.jobs.entry(job_id.to_owned()).or_default().push(event);
+    }
+
+    /// Record the submission of a job along with the stages it was planned into
+    pub fn job_submitted(&self, graph: &ExecutionGraph, submitted_at: u64) {
+        let job_id = graph.job_id();
+        let mut events = vec![HistoryEvent::JobSubmitted {
+            job_id: job_id.to_owned(),
+            job_name: graph.job_name().to_owned(),
+            queued_at: graph.queued_at(),
+            submitted_at,
+        }];
+        let mut stage_ids: Vec<_> = graph.stages().keys().copied().collect();
+        stage_ids.sort_unstable();
+        for stage_id in stage_ids {
+            let stage = &graph.stages()[&stage_id];
+            events.push(HistoryEvent::StagePlanned {
+                job_id: job_id.to_owned(),
+                stage_id,
+                partitions: stage.partitions(),
+                output_links: stage.output_links().to_vec(),
+                plan: displayable(stage.plan()).indent(false).to_string(),
+            });
+        }
+        self.jobs
+            .entry(job_id.to_owned())
+            .or_default()
+            .extend(events);
+    }
+
+    /// Record the launch of a task on an executor
+    pub fn task_started(&self, executor_id: &str, task: &TaskDescription) {
+        self.record(
+            &task.partition.job_id,
+            HistoryEvent::TaskStarted {
+                job_id: task.partition.job_id.clone(),
+                stage_id: task.partition.stage_id,
+                partition_id: task.partition.partition_id,
+                task_id: task.task_id,
+                task_attempt: task.task_attempt,
+                executor_id: executor_id.to_owned(),
+                timestamp: timestamp_millis(),
+            },
+        );
+    }
+
+    /// Record the statuses of tasks reported by an executor
+    pub fn task_statuses(&self, executor_id: &str, statuses: &[TaskStatus]) {
+        for status in statuses {
+            if let Some(event) = HistoryEvent::task_finished(executor_id, status) {
+                self.record(&status.job_id, event);
+            }
+        }
+    }
+
+    /// Record the completion of a job, and write its events to the log
+    pub async fn job_completed(&self, status: &JobStatus) -> Result<()> {
+        let Some(event) = HistoryEvent::job_completed(status) else {
+            return Ok(());
+        };
+        let mut events = self
+            .jobs
+            .remove(&status.job_id)
+            .map(|(_, events)| events)
+            .unwrap_or_default();
+        events.push(event);
+        let path = self
+            .prefix
+            .child(JOBS_DIR)
+            .child(format!("{}.json", status.job_id));
+        self.put(&path, &events).await
+    }
+
+    /// Write the registration of an executor to the log
+    pub async fn executor_registered(&self, metadata: &ExecutorMetadata) -> Result<()> {
+        let timestamp = timestamp_millis();
+        let event = HistoryEvent::ExecutorRegistered {
+            executor_id: metadata.id.clone(),
+            host: metadata.host.clone(),
+            port: metadata.port,
+            task_slots: metadata.specification.task_slots,
+            timestamp,
+        };
+        self.put(
+            &self.executor_path(&metadata.id, timestamp, "registered"),
+            &[event],
+        )
+        .await
+    }
+
+    /// Write the loss of an executor to the log
+    pub async fn executor_lost(
+        &self,
+        executor_id: &str,
+        reason: Option<String>,
+    ) -> Result<()> {
+        let timestamp = timestamp_millis();
+        let event = HistoryEvent::ExecutorLost {
+            executor_id: executor_id.to_owned(),
+            reason,
+            timestamp,
+        };
+        self.put(
+            &self.executor_path(executor_id, timestamp, "lost"),
+            &[event],
+        )
+        .await
+    }
+
+    /// Path of an executor event, ordered by time, and by the order they were written
+    /// in for the events of the same millisecond
+    fn executor_path(&self, executor_id: &str, timestamp: u64, kind: &str) -> Path {
+        let sequence = self.executor_events.fetch_add(1, Ordering::Relaxed);
+        self.prefix.child(EXECUTORS_DIR).child(format!(
+            "{timestamp:013}-{sequence:010}-{executor_id}-{kind}.json"
+        ))
+    }
+
+    async fn put(&self, path: &Path, events: &[HistoryEvent]) -> Result<()> {
+        self.store
+            .put(path, encode_events(events)?.into())
+            .await
+            .map_err(DataFusionError::ObjectStore)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Calibration of the cost model of the exchanges of the jobs, see
+//! [ballista_core::cost_model].
+//!
+//! The compression ratios the shuffles of the completed jobs achieved are compared to
+//! those the cost model expected of their columns, and the network bandwidth is the
+//! peak network throughput the executors reported with their heartbeats. Jobs are
+//! planned with the calibrated settings, unless their session sets them.
+//!
+//! The calibration is saved to the [JobState] as it changes, so that it outlives the
+//! scheduler, and loaded by the schedulers taking over.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use ballista_core::config::{
+    BALLISTA_OPTIMIZER_COMPRESSION_RATIO_SCALE,
+    BALLISTA_OPTIMIZER_NETWORK_BYTES_PER_SECOND,
+};
+use ballista_core::cost_model::{
+    ExchangeCostModel, MAX_COMPRESSION_RATIO_SCALE, MIN_COMPRESSION_RATIO_SCALE,
+};
+use ballista_core::error::Result;
+use ballista_core::execution_plans::ShuffleWriterExec;
+use ballista_core::extension::SessionConfigExt;
+use ballista_core::serde::protobuf::{ExchangeCalibrationState, KeyValuePair};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionConfig;
+use parking_lot::Mutex;
+
+use crate::cluster::JobState;
+use crate::state::execution_graph::{ExecutionGraph, ExecutionStage};
+
+/// Bytes a shuffle must have written for its compression ratio to be calibrated
+/// from, smaller shuffles being dominated by the headers of their files
+const MIN_CALIBRATION_BYTES: usize = 1024 * 1024;
+
+/// Weight of each shuffle in the calibrated compression ratio scale
+const SMOOTHING: f64 = 0.2;
+
+pub struct ExchangeCalibration {
+    state: Arc<dyn JobState>,
+    /// Ratio of the compression ratios observed to those expected, smoothed over the
+    /// shuffles of the completed jobs
+    compression_ratio_scale: Mutex<Option<f64>>,
+    /// Peak network bandwidth of the executors measured, 0 until measured
+    network_bytes_per_second: AtomicU64,
+}
+
+impl ExchangeCalibration {
+    pub fn new(state: Arc<dyn JobState>) -> Self {
+        Self {
+            state,
+            compression_ratio_scale: Mutex::new(None),
+            network_bytes_per_second: AtomicU64::new(0),
+        }
+    }
+
+    /// Load the calibration saved by previous schedulers
+    pub async fn init(&self) -> Result<()> {
+        if let Some(saved) = self.state.get_exchange_calibration().await? {
+            *self.compression_ratio_scale.lock() = saved.compression_ratio_scale;
+            self.network_bytes_per_second
+                .fetch_max(saved.network_bytes_per_second, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Calibrate the compression ratios from the shuffles of a completed job, saving
+    /// the calibration if any of them is large enough to calibrate from
+    pub async fn record_job(&self, graph: &ExecutionGraph) -> Result<()> {
+        let shuffles: Vec<_> = graph
+            .stages()
+            .values()
+            .filter_map(shuffle_compression_ratios)
+            .collect();
+        if shuffles.is_empty() {
+            return Ok(());
+        }
+        for (observed, expected) in shuffles {
+            self.record_shuffle(observed, expected);
+        }
+        self.save().await
+    }
+
+    /// Calibrate the compression ratios from a shuffle compressed by `observed_ratio`
+    /// where the cost model expected `expected_ratio`
+    pub fn record_shuffle(&self, observed_ratio: f64, expected_ratio: f64) {
+        let sample = (observed_ratio / expected_ratio)
+            .clamp(MIN_COMPRESSION_RATIO_SCALE, MAX_COMPRESSION_RATIO_SCALE);
+        let mut scale = self.compression_ratio_scale.lock();
+        *scale = Some(match *scale {
+            Some(scale) => scale + SMOOTHING * (sample - scale),
+            None => sample,
+        });
+    }
+
+    /// Record the network bandwidth measured, keeping the highest, which is saved
+    pub async fn record_network_bytes_per_second(&self, bandwidth: u64) -> Result<()> {
+        let previous = self
+            .network_bytes_per_second
+            .fetch_max(bandwidth, Ordering::Relaxed);
+        if bandwidth <= previous {
+            return Ok(());
+        }
+        self.save().await
+    }
+
+    async fn save(&self) -> Result<()> {
+        let calibration = ExchangeCalibrationState {
+            compression_ratio_scale: self.compression_ratio_scale(),
+            network_bytes_per_second: self
+                .network_bytes_per_second
+                .load(Ordering::Relaxed),
+        };
+        self.state.save_exchange_calibration(&calibration).await
+    }
+
+    pub fn compression_ratio_scale(&self) -> Option<f64> {
+        *self.compression_ratio_scale.lock()
+    }
+
+    pub fn network_bytes_per_second(&self) -> Option<u64> {
+        match self.network_bytes_per_second.load(Ordering::Relaxed) {
+            0 => None,
+            bandwidth => Some(bandwidth),
+        }
+    }
+
+    /// The calibrated settings of the cost model which the session `config` leaves to
+    /// the scheduler, which a job is planned with on the scheduler and the executors
+    pub fn settings(&self, config: &SessionConfig) -> Vec<KeyValuePair> {
+        let mut settings = vec![];
+        if let (Ok(None), Some(scale)) = (
+            config.ballista_compression_ratio_scale(),
+            self.compression_ratio_scale(),
+        ) {
+            settings.push(KeyValuePair {
+                key: BALLISTA_OPTIMIZER_COMPRESSION_RATIO_SCALE.to_owned(),
+                value: scale.to_string(),
+            });
+        }
+        if let (0, Some(bandwidth)) = (
+            config.ballista_network_bytes_per_second(),
+            self.network_bytes_per_second(),
+        ) {
+            settings.push(KeyValuePair {
+                key: BALLISTA_OPTIMIZER_NETWORK_BYTES_PER_SECOND.to_owned(),
+                value: bandwidth.to_string(),
+            });
+        }
+        settings
+    }
+}
+
+/// The compression ratio a successful shuffle achieved, and the one the cost model
+/// expected of its columns
+fn shuffle_compression_ratios(stage: &ExecutionStage) -> Option<(f64, f64)> {
+    if !matches!(stage, ExecutionStage::Successful(_)) {
+        return None;
+    }
+    let writer = stage.plan().as_any().downcast_ref::<ShuffleWriterExec>()?;
+    // the unpartitioned outputs of stages report their size in memory
+    writer.shuffle_output_partitioning()?;
+    let metrics = stage.stage_metrics()?.first()?;
+    let input_bytes = metrics.sum_by_name("input_bytes")?.as_usize();
+    let output_bytes = metrics.sum_by_name("output_bytes")?.as_usize();
+    if input_bytes < MIN_CALIBRATION_BYTES || output_bytes == 0 {
+        return None;
+    }
+
+    let expected = ExchangeCostModel::new(writer.compression())
+        .expected_compression_ratio(&writer.schema());
+    Some((output_bytes as f64 / input_bytes as f64, expected))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ballista_core::error::Result;
+    use ballista_core::extension::{SessionConfigExt, SessionConfigHelperExt};
+    use ballista_core::utils::{default_config_producer, default_session_builder};
+    use datafusion::prelude::SessionConfig;
+
+    use crate::cluster::memory::InMemoryJobState;
+    use crate::cluster::JobState;
+    use crate::state::exchange_calibration::ExchangeCalibration;
+
+    fn job_state() -> Arc<dyn JobState> {
+        Arc::new(InMemoryJobState::new(
+            "",
+            Arc::new(default_session_builder),
+            Arc::new(default_config_producer),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_exchange_calibration() -> Result<()> {
+        let calibration = ExchangeCalibration::new(job_state());
+        let calibrate = |config: SessionConfig| {
+            let settings = calibration.settings(&config);
+            config.update_from_key_value_pair(&settings)
+        };
+        assert!(calibration
+            .settings(&SessionConfig::new_with_ballista())
+            .is_empty());
+
+        // shuffles compressing half as well as expected, then as well as expected
+        calibration.record_shuffle(0.8, 0.4);
+        assert_eq!(Some(2.0), calibration.compression_ratio_scale());
+        calibration.record_shuffle(0.4, 0.4);
+        assert_eq!(Some(1.8), calibration.compression_ratio_scale());
+        // wildly off shuffles are bounded
+        calibration.record_shuffle(1.0, 0.01);
+        assert_eq!(Some(2.24), calibration.compression_ratio_scale());
+
+        calibration.record_network_bytes_per_second(1000).await?;
+        calibration.record_network_bytes_per_second(500).await?;
+        assert_eq!(Some(1000), calibration.network_bytes_per_second());
+
+        let config = calibrate(SessionConfig::new_with_ballista());
+        assert_eq!(Ok(Some(2.24)), config.ballista_compression_ratio_scale());
+        assert_eq!(1000, config.ballista_network_bytes_per_second());
+
+        // the settings of the session take precedence
+        let config = calibrate(
+            SessionConfig::new_with_ballista()
+                .with_ballista_compression_ratio_scale(0.5)
+                .with_ballista_network_bytes_per_second(10),
+        );
+        assert_eq!(Ok(Some(0.5)), config.ballista_compression_ratio_scale());
+        assert_eq!(10, config.ballista_network_bytes_per_second());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_exchange_calibration_recovered() -> Result<()> {
+        let state = job_state();
+        let calibration = ExchangeCalibration::new(state.clone());
+        calibration.record_shuffle(0.8, 0.4);
+        calibration.record_network_bytes_per_second(1000).await?;
+
+        // a scheduler taking over loads the calibration from the state
+        let recovered = ExchangeCalibration::new(state.clone());
+        recovered.init().await?;
+        assert_eq!(Some(2.0), recovered.compression_ratio_scale());
+        assert_eq!(Some(1000), recovered.network_bytes_per_second());
+
+        // nothing is saved while nothing was measured
+        let state = job_state();
+        ExchangeCalibration::new(state.clone())
+            .record_network_bytes_per_second(0)
+            .await?;
+        assert_eq!(None, state.get_exchange_calibration().await?);
+        Ok(())
+    }
+}
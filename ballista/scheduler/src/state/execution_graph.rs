@@ -23,15 +23,23 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use datafusion::physical_plan::display::DisplayableExecutionPlan;
-use datafusion::physical_plan::{accept, ExecutionPlan, ExecutionPlanVisitor};
+use datafusion::physical_plan::{
+    accept, ExecutionPlan, ExecutionPlanVisitor, PhysicalExpr,
+};
 use datafusion::prelude::SessionConfig;
 use log::{error, info, warn};
 
-use ballista_core::error::{BallistaError, Result};
-use ballista_core::execution_plans::{ShuffleWriterExec, UnresolvedShuffleExec};
+use ballista_core::error::{BallistaError, ErrorCode, Result};
+use ballista_core::execution_plans::{
+    is_object_store_path, ShuffleWriterExec, SplittableScanExec, UnresolvedShuffleExec,
+};
+use ballista_core::extension::{SessionConfigExt, SessionConfigHelperExt};
+use ballista_core::security::FetchToken;
 use ballista_core::serde::protobuf::failed_task::FailedReason;
 use ballista_core::serde::protobuf::job_status::Status;
-use ballista_core::serde::protobuf::{job_status, FailedJob, ShuffleWritePartition};
+use ballista_core::serde::protobuf::{
+    self, job_status, FailedJob, ShuffleWritePartition, SkippedPartition,
+};
 use ballista_core::serde::protobuf::{task_status, RunningTask};
 use ballista_core::serde::protobuf::{
     FailedTask, JobStatus, ResultLost, RunningJob, SuccessfulJob, TaskStatus,
@@ -41,16 +49,23 @@ use ballista_core::serde::scheduler::{
 };
 
 use crate::display::print_stage_metrics;
-use crate::planner::DistributedPlanner;
+use crate::planner::{attach_runtime_predicates, DistributedPlanner};
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::timestamp_millis;
 use crate::state::execution_graph::execution_stage::RunningStage;
 pub(crate) use crate::state::execution_graph::execution_stage::{
     ExecutionStage, ResolvedStage, StageOutput, TaskInfo, UnresolvedStage,
 };
+use crate::state::execution_graph::scan_leases::StageScanLeases;
 use crate::state::task_manager::UpdatedStages;
 
 mod execution_stage;
+mod persistence;
+mod scan_leases;
+
+/// Time left to executors to report the tasks exceeding the stage timeout of their job,
+/// which they fail themselves, before the scheduler fails the tasks in their stead
+pub(crate) const STAGE_TIMEOUT_GRACE_PERIOD_MS: u128 = 30_000;
 
 /// Represents the DAG for a distributed query plan.
 ///
@@ -126,8 +141,23 @@ pub struct ExecutionGraph {
     /// Failed stage attempts, record the failed stage attempts to limit the retry times.
     /// Map from Stage ID -> Set<Stage_ATTPMPT_NUM>
     failed_stage_attempts: HashMap<usize, HashSet<usize>>,
+    /// IDs of the task attempts cancelled after another attempt of the same task finished
+    /// first, see [ExecutionGraph::launch_speculative_task]
+    cancelled_attempts: HashSet<usize>,
+    /// Partitions completed without output after their tasks permanently failed, when
+    /// the job runs with best effort
+    skipped_partitions: Vec<SkippedPartition>,
+    /// Leases of the files of the splittable scans of the stages, by stage ID, created
+    /// once a task of the stage asks for its first file
+    scan_leases: HashMap<usize, StageScanLeases>,
     /// Session config for this job
     session_config: Arc<SessionConfig>,
+    /// Token the tasks and the clients of the job present to fetch its partitions
+    fetch_token: FetchToken,
+    /// Whether the plan of the job was reused from the query cache
+    planned_from_cache: bool,
+    /// Identity which submitted the job, none if submitted anonymously
+    owner: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -149,7 +179,7 @@ impl ExecutionGraph {
         queued_at: u64,
         session_config: Arc<SessionConfig>,
     ) -> Result<Self> {
-        let mut planner = DistributedPlanner::new();
+        let mut planner = DistributedPlanner::for_session(&session_config)?;
 
         let output_partitions = plan.properties().output_partitioning().partition_count();
 
@@ -159,6 +189,14 @@ impl ExecutionGraph {
         let stages = builder.build(shuffle_stages)?;
 
         let started_at = timestamp_millis();
+        // jobs reading the output partitions of each other, such as the iterations of
+        // recursive queries, are given the same token with their session config
+        let fetch_token = session_config.ballista_fetch_token();
+        let fetch_token = if fetch_token.as_str().is_empty() {
+            FetchToken::generate()
+        } else {
+            fetch_token.as_ref().clone()
+        };
 
         Ok(Self {
             scheduler_id: Some(scheduler_id.to_string()),
@@ -173,6 +211,7 @@ impl ExecutionGraph {
                     queued_at,
                     started_at,
                     scheduler: scheduler_id.to_string(),
+                    partition_location: vec![],
                 })),
             },
             queued_at,
@@ -183,7 +222,13 @@ impl ExecutionGraph {
             output_locations: vec![],
             task_id_gen: 0,
             failed_stage_attempts: HashMap::new(),
+            cancelled_attempts: HashSet::new(),
+            skipped_partitions: vec![],
+            scan_leases: HashMap::new(),
             session_config,
+            fetch_token,
+            planned_from_cache: false,
+            owner: None,
         })
     }
 
@@ -203,6 +248,48 @@ impl ExecutionGraph {
         &self.status
     }
 
+    pub fn queued_at(&self) -> u64 {
+        self.queued_at
+    }
+
+    /// Config of the session the job was submitted with
+    pub fn session_config(&self) -> &Arc<SessionConfig> {
+        &self.session_config
+    }
+
+    /// Token of the job, dispatched with its tasks and set on the output partitions
+    /// returned to clients
+    pub fn fetch_token(&self) -> &FetchToken {
+        &self.fetch_token
+    }
+
+    /// Record that the plan of the job was reused from the query cache
+    pub fn set_planned_from_cache(&mut self) {
+        self.planned_from_cache = true;
+    }
+
+    /// Identity which submitted the job, persisted with the graph so that the access
+    /// rules of its owner still apply after a failover
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+
+    /// Record the identity which submitted the job
+    pub fn set_owner(&mut self, owner: Option<String>) {
+        self.owner = owner;
+    }
+
+    /// Priority of the job, set by its session
+    pub fn priority(&self) -> u32 {
+        self.session_config.ballista_job_priority()
+    }
+
+    /// Maximum number of tasks of the job running at the same time on an executor, set
+    /// by its session. 0 means no limit
+    pub fn max_tasks_per_executor(&self) -> usize {
+        self.session_config.ballista_job_max_tasks_per_executor()
+    }
+
     pub fn start_time(&self) -> u64 {
         self.start_time
     }
@@ -225,6 +312,27 @@ impl ExecutionGraph {
         &self.stages
     }
 
+    /// Executor the task with the given ID was scheduled on, if it's the latest attempt
+    /// of its partition or a running speculative attempt. The executors of earlier
+    /// attempts are not kept
+    pub fn task_executor(&self, task_id: usize) -> Option<&str> {
+        self.stages.values().find_map(|stage| {
+            let speculative = match stage {
+                ExecutionStage::Running(stage) => {
+                    stage.speculative_task_infos.values().collect()
+                }
+                _ => vec![],
+            };
+            stage
+                .task_infos()
+                .into_iter()
+                .map(|(_, info)| info)
+                .chain(speculative)
+                .find(|info| info.task_id == task_id)
+                .map(|info| info.executor_id.as_str())
+        })
+    }
+
     /// An ExecutionGraph is successful if all its stages are successful
     pub fn is_successful(&self) -> bool {
         self.stages
@@ -276,6 +384,38 @@ impl ExecutionGraph {
         max_stage_failures: usize,
     ) -> Result<Vec<QueryStageSchedulerEvent>> {
         let job_id = self.job_id().to_owned();
+        // Attempts which lost against another attempt of the same task are not tracked
+        // anymore, only the output of those which finished as well is removed
+        let mut losing_attempt_events = vec![];
+        let task_statuses = task_statuses.into_iter().filter(|task_status| {
+            if !self
+                .cancelled_attempts
+                .remove(&(task_status.task_id as usize))
+            {
+                return true;
+            }
+            if let Some(task_status::Status::Successful(successful_task)) =
+                &task_status.status
+            {
+                let paths = successful_task
+                    .partitions
+                    .iter()
+                    .map(|p| p.path.clone())
+                    .filter(|path| !is_object_store_path(path))
+                    .collect::<Vec<_>>();
+                if !paths.is_empty() {
+                    losing_attempt_events.push(
+                        QueryStageSchedulerEvent::RemoveTaskOutputs {
+                            job_id: job_id.clone(),
+                            executor_id: successful_task.executor_id.clone(),
+                            paths,
+                        },
+                    );
+                }
+            }
+            false
+        });
+
         // First of all, classify the statuses by stages
         let mut job_task_statuses: HashMap<usize, Vec<TaskStatus>> = HashMap::new();
         for task_status in task_statuses {
@@ -305,6 +445,9 @@ impl ExecutionGraph {
         let mut resubmit_successful_stages: HashMap<usize, HashSet<usize>> =
             HashMap::new();
         let mut reset_running_stages: HashMap<usize, HashSet<usize>> = HashMap::new();
+        // Partitions whose tasks permanently failed are skipped rather than failing the
+        // job, fetch failures being still recovered by resubmitting the map stages
+        let best_effort = self.session_config.ballista_best_effort();
 
         for (stage_id, stage_task_statuses) in job_task_statuses {
             if let Some(stage) = self.stages.get_mut(&stage_id) {
@@ -400,6 +543,18 @@ impl ExecutionGraph {
                                         failed_stages.insert(stage_id, error_msg);
                                     }
                                 }
+                                Some(FailedReason::ExecutionError(_)) if best_effort => {
+                                    warn!(
+                                        "Skip the partition of task {} which failed: {}",
+                                        task_identity, failed_task.error
+                                    );
+                                    running_stage.skip_task(partition_id);
+                                    self.skipped_partitions.push(SkippedPartition {
+                                        stage_id: stage_id as u32,
+                                        partition_id: partition_id as u32,
+                                        error: failed_task.error,
+                                    });
+                                }
                                 Some(FailedReason::ExecutionError(_)) => {
                                     failed_stages.insert(stage_id, failed_task.error);
                                 }
@@ -413,6 +568,16 @@ impl ExecutionGraph {
                                             // TODO add new struct to track all the failed task infos
                                             // The failure TaskInfo is ignored and set to None here
                                             running_stage.reset_task_info(partition_id);
+                                        } else if best_effort {
+                                            warn!("Skip the partition of task {} which failed {} times: {}", task_identity, max_task_failures, failed_task.error);
+                                            running_stage.skip_task(partition_id);
+                                            self.skipped_partitions.push(
+                                                SkippedPartition {
+                                                    stage_id: stage_id as u32,
+                                                    partition_id: partition_id as u32,
+                                                    error: failed_task.error,
+                                                },
+                                            );
                                         } else {
                                             let error_msg = format!(
                                                 "Task {} in Stage {} failed {} times, fail the stage, most recent failure reason: {:?}",
@@ -603,6 +768,7 @@ impl ExecutionGraph {
                             error: "FetchPartitionError in parent stage".to_owned(),
                             retryable: true,
                             count_to_failures: false,
+                            error_code: ErrorCode::ResultLost.to_string(),
                             failed_reason: Some(FailedReason::ResultLost(ResultLost {})),
                         });
                     }
@@ -644,7 +810,8 @@ impl ExecutionGraph {
             }
         }
 
-        self.processing_stages_update(UpdatedStages {
+        losing_attempt_events.extend(self.cancel_losing_attempts());
+        let mut events = self.processing_stages_update(UpdatedStages {
             resolved_stages,
             successful_stages,
             failed_stages,
@@ -653,7 +820,32 @@ impl ExecutionGraph {
                 .keys()
                 .cloned()
                 .collect(),
-        })
+        })?;
+        events.extend(losing_attempt_events);
+        Ok(events)
+    }
+
+    /// Event to cancel the running task attempts which lost against another attempt of
+    /// the same task. Output in object stores is never removed, as all attempts of a task
+    /// upload it to the same objects.
+    fn cancel_losing_attempts(&mut self) -> Option<QueryStageSchedulerEvent> {
+        let mut tasks_to_cancel = vec![];
+        for stage in self.stages.values_mut() {
+            if let ExecutionStage::Running(stage) = stage {
+                for attempt in stage.take_losing_attempts() {
+                    self.cancelled_attempts.insert(attempt.task_id);
+                    tasks_to_cancel.push(RunningTaskInfo {
+                        task_id: attempt.task_id,
+                        job_id: self.job_id.clone(),
+                        stage_id: stage.stage_id,
+                        partition_id: attempt.partition_id,
+                        executor_id: attempt.executor_id,
+                    });
+                }
+            }
+        }
+        (!tasks_to_cancel.is_empty())
+            .then_some(QueryStageSchedulerEvent::CancelTasks(tasks_to_cancel))
     }
 
     /// Processing stage status update after task status changing
@@ -701,8 +893,8 @@ impl ExecutionGraph {
         }
 
         if !updated_stages.failed_stages.is_empty() {
-            info!("Job {} is failed", job_id);
-            self.fail_job(job_err_msg.clone());
+            info!("Job {} is failed [{}]", job_id, ErrorCode::ExecutionFailed);
+            self.fail_job(ErrorCode::ExecutionFailed, job_err_msg.clone());
             events.push(QueryStageSchedulerEvent::JobRunningFailed {
                 job_id,
                 fail_message: job_err_msg,
@@ -736,8 +928,18 @@ impl ExecutionGraph {
         let job_id = &self.job_id;
         if output_links.is_empty() {
             // If `output_links` is empty, then this is a final stage
+            self.publish_partial_results(&locations)?;
             self.output_locations.extend(locations);
         } else {
+            // Inlined results are only delivered to the client, consuming stages
+            // fetch their input from the executors
+            let locations: Vec<PartitionLocation> = locations
+                .into_iter()
+                .map(|location| PartitionLocation {
+                    inline_data: None,
+                    ..location
+                })
+                .collect();
             for link in output_links.iter() {
                 // If this is an intermediate stage, we need to push its `PartitionLocation`s to the parent stage
                 if let Some(linked_stage) = self.stages.get_mut(link) {
@@ -811,6 +1013,159 @@ impl ExecutionGraph {
             .collect::<Vec<RunningTaskInfo>>()
     }
 
+    /// Return the running tasks of the running stages which run `multiplier` times
+    /// longer than the median successful task of their stage, as (stage ID, partition ID,
+    /// executor ID) tuples
+    pub(crate) fn straggler_tasks(
+        &self,
+        multiplier: f64,
+        now: u128,
+    ) -> Vec<(usize, usize, String)> {
+        self.stages
+            .values()
+            .flat_map(|stage| {
+                if let ExecutionStage::Running(stage) = stage {
+                    stage
+                        .straggler_tasks(multiplier, now)
+                        .into_iter()
+                        .map(|(partition_id, executor_id)| {
+                            (stage.stage_id, partition_id, executor_id)
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                }
+            })
+            .collect()
+    }
+
+    /// Return failed statuses, along with the executor they run on, for the running
+    /// tasks which exceeded the stage timeout of the job by
+    /// [STAGE_TIMEOUT_GRACE_PERIOD_MS]. Executors fail the tasks exceeding the timeout
+    /// themselves, so these are the tasks of unresponsive executors.
+    pub(crate) fn timed_out_tasks(&self, now: u128) -> Vec<(String, TaskStatus)> {
+        let timeout_seconds = self.session_config.ballista_stage_timeout_seconds();
+        if timeout_seconds == 0 {
+            return vec![];
+        }
+        let running_since = now.saturating_sub(
+            timeout_seconds as u128 * 1000 + STAGE_TIMEOUT_GRACE_PERIOD_MS,
+        );
+        self.stages
+            .values()
+            .flat_map(|stage| {
+                let ExecutionStage::Running(stage) = stage else {
+                    return vec![];
+                };
+                stage
+                    .tasks_running_since(running_since)
+                    .into_iter()
+                    .map(|(task_id, partition_id, executor_id)| {
+                        let error = BallistaError::TimedOut(format!(
+                            "Task {task_id} of stage {}/{} on executor {executor_id} wasn't reported within the {timeout_seconds}s stage timeout",
+                            self.job_id, stage.stage_id
+                        ));
+                        let status = TaskStatus {
+                            task_id: task_id as u32,
+                            job_id: self.job_id.clone(),
+                            stage_id: stage.stage_id as u32,
+                            stage_attempt_num: stage.stage_attempt_num as u32,
+                            partition_id: partition_id as u32,
+                            launch_time: 0,
+                            start_exec_time: 0,
+                            end_exec_time: 0,
+                            metrics: vec![],
+                            status: Some(task_status::Status::Failed(error.into())),
+                        };
+                        (executor_id, status)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Drop the status of a task attempt once its executor reports it, e.g. as the
+    /// scheduler already failed the attempt
+    pub(crate) fn ignore_task_status(&mut self, task_id: usize) {
+        self.cancelled_attempts.insert(task_id);
+    }
+
+    /// Launch a speculative attempt of a running task on the given executor. Whichever
+    /// attempt of the task finishes first is kept, and the other one cancelled.
+    pub(crate) fn launch_speculative_task(
+        &mut self,
+        stage_id: usize,
+        partition_id: usize,
+        executor_id: String,
+    ) -> Option<TaskDescription> {
+        let task_id = self.task_id_gen;
+        let Some(ExecutionStage::Running(stage)) = self.stages.get_mut(&stage_id) else {
+            warn!(
+                "Fail to find running stage {stage_id} to speculate task {partition_id}"
+            );
+            return None;
+        };
+        self.task_id_gen += 1;
+        stage.add_speculative_task(partition_id, executor_id, task_id);
+
+        Some(TaskDescription {
+            session_id: self.session_id.clone(),
+            partition: PartitionId {
+                job_id: self.job_id.clone(),
+                stage_id,
+                partition_id,
+            },
+            stage_attempt_num: stage.stage_attempt_num,
+            task_id,
+            task_attempt: stage.task_failure_numbers[partition_id],
+            plan: stage.plan.clone(),
+            session_config: stage.session_config.clone(),
+        })
+    }
+
+    /// Lease the file a task of a stage with a splittable scan scans after the
+    /// `position` files it already scanned, see [StageScanLeases]. Returns the index of
+    /// the file among the files of all the partitions of the scan, None once there are
+    /// no files left.
+    pub(crate) fn lease_scan_file(
+        &mut self,
+        stage_id: usize,
+        partition_id: usize,
+        position: usize,
+    ) -> Result<Option<usize>> {
+        if !self.scan_leases.contains_key(&stage_id) {
+            let stage = self.stages.get(&stage_id).ok_or_else(|| {
+                BallistaError::General(format!(
+                    "Stage {stage_id} of job {} does not exist",
+                    self.job_id
+                ))
+            })?;
+            let partition_files =
+                splittable_scan_files(stage.plan()).ok_or_else(|| {
+                    BallistaError::General(format!(
+                        "Stage {stage_id} of job {} has no splittable scan",
+                        self.job_id
+                    ))
+                })?;
+            self.scan_leases
+                .insert(stage_id, StageScanLeases::new(&partition_files));
+        }
+
+        let lease = self
+            .scan_leases
+            .get_mut(&stage_id)
+            .expect("scan leases of the stage")
+            .lease(partition_id, position)?;
+        if let Some(lease) = lease.filter(|lease| lease.owner != partition_id) {
+            info!(
+                "Task of partition {partition_id} of stage {stage_id} of job {} takes \
+                 over file {} of partition {}",
+                self.job_id, lease.file, lease.owner
+            );
+        }
+        Ok(lease.map(|lease| lease.file))
+    }
+
     /// Total number of tasks in this plan that are ready for scheduling
     pub fn available_tasks(&self) -> usize {
         self.stages
@@ -889,6 +1244,7 @@ impl ExecutionGraph {
                 let task_attempt = stage.task_failure_numbers[partition_id];
                 let task_info = TaskInfo {
                     task_id,
+                    executor_id: executor_id.to_owned(),
                     scheduled_time: SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .unwrap()
@@ -901,6 +1257,7 @@ impl ExecutionGraph {
                     task_status: task_status::Status::Running(RunningTask {
                         executor_id: executor_id.to_owned()
                     }),
+                    metrics: HashMap::new(),
                 };
 
                 // Set the task info to Running for new task
@@ -1069,7 +1426,11 @@ impl ExecutionGraph {
                     stage_output.partition_locations.iter_mut().for_each(
                         |(_partition, locs)| {
                             let before_len = locs.len();
-                            locs.retain(|loc| loc.executor_meta.id != executor_id);
+                            // partitions uploaded to an object store outlive the executor
+                            locs.retain(|loc| {
+                                loc.executor_meta.id != executor_id
+                                    || is_object_store_path(&loc.path)
+                            });
                             if locs.len() < before_len {
                                 match_found = true;
                             }
@@ -1267,8 +1628,32 @@ impl ExecutionGraph {
         }
     }
 
-    /// fail job with error message
-    pub fn fail_job(&mut self, error: String) {
+    /// Attach runtime predicates to the shuffle readers of a resolved or running stage
+    /// which read the output of `input_stage_id`. Tasks already launched are not affected.
+    pub fn add_runtime_predicates(
+        &mut self,
+        stage_id: usize,
+        input_stage_id: usize,
+        predicates: &[Arc<dyn PhysicalExpr>],
+    ) -> Result<bool> {
+        let plan = match self.stages.get_mut(&stage_id) {
+            Some(ExecutionStage::Resolved(stage)) => &mut stage.plan,
+            Some(ExecutionStage::Running(stage)) => &mut stage.plan,
+            _ => {
+                warn!(
+                    "Fail to find a resolved or running stage {}/{} to add runtime predicates",
+                    self.job_id(),
+                    stage_id
+                );
+                return Ok(false);
+            }
+        };
+        *plan = attach_runtime_predicates(plan.clone(), input_stage_id, predicates)?;
+        Ok(true)
+    }
+
+    /// fail job with error code and message
+    pub fn fail_job(&mut self, error_code: ErrorCode, error: String) {
         self.status = JobStatus {
             job_id: self.job_id.clone(),
             job_name: self.job_name.clone(),
@@ -1277,10 +1662,39 @@ impl ExecutionGraph {
                 queued_at: self.queued_at,
                 started_at: self.start_time,
                 ended_at: self.end_time,
+                error_code: error_code.to_string(),
             })),
         };
     }
 
+    /// Whether clients are returned the output partitions of the final stage as they
+    /// complete. Deterministic jobs return them in a fixed order, and jobs with a result
+    /// row limit or verified stages only once they complete
+    fn streams_results(&self) -> bool {
+        !self.session_config.ballista_deterministic()
+            && self.session_config.ballista_max_result_rows() == 0
+            && !self
+                .stages
+                .keys()
+                .any(|stage_id| self.session_config.ballista_verify_stage(*stage_id))
+    }
+
+    /// Add completed output partitions of the final stage to the status of the running
+    /// job, so that clients can stream them before the job completes
+    fn publish_partial_results(&mut self, locations: &[PartitionLocation]) -> Result<()> {
+        if !self.streams_results() {
+            return Ok(());
+        }
+        if let Some(Status::Running(running)) = &mut self.status.status {
+            for location in locations {
+                running
+                    .partition_location
+                    .push(output_location(location.clone(), &self.fetch_token)?);
+            }
+        }
+        Ok(())
+    }
+
     /// Mark the job success
     pub fn succeed_job(&mut self) -> Result<()> {
         if !self.is_successful() {
@@ -1290,11 +1704,28 @@ impl ExecutionGraph {
             )));
         }
 
-        let partition_location = self
-            .output_locations()
+        let mut output_locations = self.output_locations();
+        if self.session_config.ballista_deterministic() {
+            // Output partitions are collected as their tasks complete, return them in a
+            // fixed order instead
+            output_locations
+                .sort_by_key(|l| (l.partition_id.partition_id, l.map_partition_id));
+        }
+        let partition_location = output_locations
             .into_iter()
-            .map(|l| l.try_into())
-            .collect::<Result<Vec<_>>>()?;
+            .map(|l| output_location(l, &self.fetch_token))
+            .collect::<Result<Vec<protobuf::PartitionLocation>>>()?;
+
+        // Jobs with a result row limit produce one row more than the limit, so that
+        // truncated results can be told apart
+        let max_result_rows = self.session_config.ballista_max_result_rows() as u64;
+        let num_rows: u64 = partition_location
+            .iter()
+            .filter_map(|l| l.partition_stats.as_ref())
+            .map(|stats| stats.num_rows.max(0) as u64)
+            .sum();
+        let truncated_at = (max_result_rows > 0 && num_rows > max_result_rows)
+            .then_some(max_result_rows);
 
         self.status = JobStatus {
             job_id: self.job_id.clone(),
@@ -1305,6 +1736,10 @@ impl ExecutionGraph {
                 queued_at: self.queued_at,
                 started_at: self.start_time,
                 ended_at: self.end_time,
+                truncated_at,
+                skipped_partitions: self.skipped_partitions.clone(),
+                planned_from_cache: self.planned_from_cache,
+                cached_results_job_id: String::new(),
             })),
         };
         self.end_time = SystemTime::now()
@@ -1334,9 +1769,21 @@ impl Debug for ExecutionGraph {
     }
 }
 
+/// Number of files of each partition of the splittable scan of the plan of a stage
+fn splittable_scan_files(plan: &dyn ExecutionPlan) -> Option<Vec<usize>> {
+    match plan.as_any().downcast_ref::<SplittableScanExec>() {
+        Some(scan) => Some(scan.partition_files()),
+        None => plan
+            .children()
+            .into_iter()
+            .find_map(|child| splittable_scan_files(child.as_ref())),
+    }
+}
+
 pub(crate) fn create_task_info(executor_id: String, task_id: usize) -> TaskInfo {
     TaskInfo {
         task_id,
+        executor_id: executor_id.clone(),
         scheduled_time: SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -1347,6 +1794,7 @@ pub(crate) fn create_task_info(executor_id: String, task_id: usize) -> TaskInfo
         end_exec_time: 0,
         finish_time: 0,
         task_status: task_status::Status::Running(RunningTask { executor_id }),
+        metrics: HashMap::new(),
     }
 }
 
@@ -1502,6 +1950,18 @@ impl TaskDescription {
     }
 }
 
+/// Output partition of a job returned to clients, with the token of the job to fetch it
+fn output_location(
+    location: PartitionLocation,
+    fetch_token: &FetchToken,
+) -> Result<protobuf::PartitionLocation> {
+    let location: protobuf::PartitionLocation = location.try_into()?;
+    Ok(protobuf::PartitionLocation {
+        fetch_token: fetch_token.as_str().to_owned(),
+        ..location
+    })
+}
+
 fn partition_to_location(
     job_id: &str,
     map_partition_id: usize,
@@ -1523,8 +1983,12 @@ fn partition_to_location(
                 Some(shuffle.num_rows),
                 Some(shuffle.num_batches),
                 Some(shuffle.num_bytes),
-            ),
+            )
+            .with_checksum(shuffle.checksum),
+            compression: shuffle.compression().into(),
+            ipc_metadata_version: shuffle.ipc_metadata_version,
             path: shuffle.path,
+            inline_data: (!shuffle.inline_data.is_empty()).then_some(shuffle.inline_data),
         })
         .collect()
 }
@@ -1532,21 +1996,33 @@ fn partition_to_location(
 #[cfg(test)]
 mod test {
     use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use datafusion::common::ScalarValue;
+    use datafusion::physical_expr::expressions::Literal;
+    use datafusion::physical_plan::display::DisplayableExecutionPlan;
+    use datafusion::physical_plan::PhysicalExpr;
+    use datafusion::prelude::SessionConfig;
 
     use crate::scheduler_server::event::QueryStageSchedulerEvent;
-    use ballista_core::error::Result;
+    use crate::scheduler_server::timestamp_millis;
+    use ballista_core::error::{BallistaError, ErrorCode, Result};
+    use ballista_core::extension::SessionConfigExt;
     use ballista_core::serde::protobuf::{
         self, failed_task, job_status, ExecutionError, FailedTask, FetchPartitionError,
         IoError, JobStatus, TaskKilled,
     };
+    use ballista_core::serde::scheduler::ExecutorMetadata;
 
-    use crate::state::execution_graph::ExecutionGraph;
+    use crate::state::execution_graph::{
+        ExecutionGraph, ExecutionStage, TaskDescription, STAGE_TIMEOUT_GRACE_PERIOD_MS,
+    };
     use crate::test_utils::{
         mock_completed_task, mock_executor, mock_failed_task,
         revive_graph_and_complete_next_stage,
         revive_graph_and_complete_next_stage_with_executor, test_aggregation_plan,
-        test_coalesce_plan, test_join_plan, test_two_aggregations_plan,
-        test_union_all_plan, test_union_plan,
+        test_aggregation_plan_with_session_config, test_coalesce_plan, test_join_plan,
+        test_two_aggregations_plan, test_union_all_plan, test_union_plan,
     };
 
     #[tokio::test]
@@ -1628,6 +2104,100 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_truncate_results() -> Result<()> {
+        let truncated_at = |max_rows: usize| async move {
+            let mut agg_graph = test_aggregation_plan(4).await;
+            agg_graph.session_config = Arc::new(
+                SessionConfig::new_with_ballista()
+                    .with_ballista_max_result_rows(max_rows),
+            );
+            drain_tasks(&mut agg_graph)?;
+
+            match agg_graph.status().status.clone() {
+                Some(job_status::Status::Successful(successful)) => {
+                    Ok::<_, BallistaError>(successful.truncated_at)
+                }
+                other => panic!("Expected success status but found {other:?}"),
+            }
+        };
+
+        // every mocked output partition holds a single row
+        let num_rows = test_aggregation_plan(4).await.output_partitions;
+        assert_eq!(truncated_at(0).await?, None);
+        assert_eq!(truncated_at(num_rows).await?, None);
+        assert_eq!(truncated_at(num_rows - 1).await?, Some(num_rows as u64 - 1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_partial_results() -> Result<()> {
+        let running_partitions = |graph: &ExecutionGraph| match &graph.status().status {
+            Some(job_status::Status::Running(running)) => {
+                running.partition_location.len()
+            }
+            other => panic!("Expected running status but found {other:?}"),
+        };
+        let complete_final_task = |graph: &mut ExecutionGraph| {
+            let executor = mock_executor("executor-id1".to_string());
+            let task = graph.pop_next_task(&executor.id)?.unwrap();
+            let task_status = mock_completed_task(task, &executor.id);
+            graph.update_task_status(&executor, vec![task_status], 1, 1)
+        };
+
+        let mut agg_graph = test_aggregation_plan(4).await;
+        revive_graph_and_complete_next_stage(&mut agg_graph)?;
+        assert_eq!(running_partitions(&agg_graph), 0);
+        complete_final_task(&mut agg_graph)?;
+        assert_eq!(running_partitions(&agg_graph), 1);
+        complete_final_task(&mut agg_graph)?;
+        assert_eq!(running_partitions(&agg_graph), 2);
+
+        // Deterministic jobs only return their results once complete
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.session_config = Arc::new(
+            SessionConfig::new_with_ballista().with_ballista_deterministic(true),
+        );
+        revive_graph_and_complete_next_stage(&mut agg_graph)?;
+        complete_final_task(&mut agg_graph)?;
+        assert_eq!(running_partitions(&agg_graph), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_runtime_predicates() -> Result<()> {
+        let mut agg_graph = test_aggregation_plan(4).await;
+        let predicate: Arc<dyn PhysicalExpr> =
+            Arc::new(Literal::new(ScalarValue::Boolean(Some(true))));
+
+        // The final aggregation stage is not resolved yet
+        assert!(!agg_graph.add_runtime_predicates(
+            2,
+            1,
+            std::slice::from_ref(&predicate)
+        )?);
+
+        revive_graph_and_complete_next_stage(&mut agg_graph)?;
+        assert!(agg_graph.add_runtime_predicates(2, 1, &[predicate])?);
+
+        let plan = match agg_graph.stages().get(&2) {
+            Some(ExecutionStage::Resolved(stage)) => stage.plan.clone(),
+            Some(ExecutionStage::Running(stage)) => stage.plan.clone(),
+            other => panic!("Expected stage 2 to be resolved, but was {other:?}"),
+        };
+        let plan = DisplayableExecutionPlan::new(plan.as_ref())
+            .indent(false)
+            .to_string();
+        assert!(
+            plan.contains("runtime_predicates=[true]"),
+            "Expected runtime predicates in plan: {plan}"
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_reset_completed_stage_executor_lost() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -1796,6 +2366,7 @@ mod test {
                 error: "Killed".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::TaskKilled.to_string(),
                 failed_reason: Some(failed_task::FailedReason::TaskKilled(TaskKilled {})),
             },
         );
@@ -1840,6 +2411,7 @@ mod test {
                 error: "IOError".to_string(),
                 retryable: true,
                 count_to_failures: true,
+                error_code: ErrorCode::IoFailure.to_string(),
                 failed_reason: Some(failed_task::FailedReason::IoError(IoError {})),
             },
         );
@@ -1869,6 +2441,7 @@ mod test {
                         error: "IOError".to_string(),
                         retryable: true,
                         count_to_failures: true,
+                        error_code: ErrorCode::IoFailure.to_string(),
                         failed_reason: Some(failed_task::FailedReason::IoError(
                             IoError {},
                         )),
@@ -1899,6 +2472,223 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_best_effort_skips_failed_partitions() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.session_config =
+            Arc::new(SessionConfig::new_with_ballista().with_ballista_best_effort(true));
+        agg_graph.revive();
+
+        // Complete the first stage
+        revive_graph_and_complete_next_stage(&mut agg_graph)?;
+
+        // The first task of the second stage fails, the other ones succeed
+        let mut task_statuses = vec![];
+        let mut skipped_partition = None;
+        while let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            if skipped_partition.is_none() {
+                skipped_partition = Some(task.partition.partition_id);
+                task_statuses.push(mock_failed_task(
+                    task,
+                    FailedTask {
+                        error: "ExecutionError".to_string(),
+                        retryable: false,
+                        count_to_failures: false,
+                        error_code: ErrorCode::ExecutionFailed.to_string(),
+                        failed_reason: Some(failed_task::FailedReason::ExecutionError(
+                            ExecutionError {},
+                        )),
+                    },
+                ));
+            } else {
+                task_statuses.push(mock_completed_task(task, &executor.id));
+            }
+        }
+        agg_graph.update_task_status(&executor, task_statuses, 4, 4)?;
+        assert_eq!(agg_graph.available_tasks(), 0);
+        assert!(agg_graph.is_successful());
+
+        match agg_graph.status().status.clone() {
+            Some(job_status::Status::Successful(successful)) => {
+                assert_eq!(
+                    successful.skipped_partitions,
+                    vec![protobuf::SkippedPartition {
+                        stage_id: 2,
+                        partition_id: skipped_partition.unwrap() as u32,
+                        error: "ExecutionError".to_string(),
+                    }]
+                );
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_speculative_attempt_finishes_first() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        let straggler = run_stage_with_straggler(&mut agg_graph, &executor1)?;
+        let stage_id = straggler.partition.stage_id;
+        let partition_id = straggler.partition.partition_id;
+
+        let now = timestamp_millis() as u128 + 10_000;
+        assert!(agg_graph.straggler_tasks(0.0, now).is_empty());
+        assert_eq!(
+            agg_graph.straggler_tasks(2.0, now),
+            vec![(stage_id, partition_id, executor1.id.clone())]
+        );
+
+        let speculative = agg_graph
+            .launch_speculative_task(stage_id, partition_id, executor2.id.clone())
+            .unwrap();
+        assert_ne!(speculative.task_id, straggler.task_id);
+        let speculative_task_id = speculative.task_id;
+        assert!(agg_graph.straggler_tasks(2.0, now).is_empty());
+        assert_eq!(agg_graph.running_tasks().len(), 2);
+        assert_eq!(
+            Some(executor1.id.as_str()),
+            agg_graph.task_executor(straggler.task_id)
+        );
+        assert_eq!(
+            Some(executor2.id.as_str()),
+            agg_graph.task_executor(speculative.task_id)
+        );
+
+        // The speculative attempt finishes first and the original attempt is cancelled
+        let task_status = mock_completed_task(speculative, &executor2.id);
+        let events = agg_graph.update_task_status(&executor2, vec![task_status], 4, 4)?;
+        let cancelled_tasks = events
+            .iter()
+            .find_map(|event| match event {
+                QueryStageSchedulerEvent::CancelTasks(tasks) => Some(tasks),
+                _ => None,
+            })
+            .expect("Expected the original attempt to be cancelled");
+        assert_eq!(cancelled_tasks.len(), 1);
+        assert_eq!(cancelled_tasks[0].task_id, straggler.task_id);
+        assert_eq!(cancelled_tasks[0].executor_id, executor1.id);
+        assert!(agg_graph.running_tasks().is_empty());
+        // the losing attempt is no longer tracked
+        assert_eq!(
+            Some(executor2.id.as_str()),
+            agg_graph.task_executor(speculative_task_id)
+        );
+        assert_eq!(None, agg_graph.task_executor(straggler.task_id));
+
+        // The output of the original attempt is removed if it finishes nonetheless
+        let task_status = mock_completed_task(straggler, &executor1.id);
+        let events = agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
+        assert!(events.iter().any(|event| matches!(
+            event,
+            QueryStageSchedulerEvent::RemoveTaskOutputs { executor_id, .. } if *executor_id == executor1.id
+        )));
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_original_attempt_finishes_first() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        let straggler = run_stage_with_straggler(&mut agg_graph, &executor1)?;
+        let stage_id = straggler.partition.stage_id;
+        let partition_id = straggler.partition.partition_id;
+
+        // A failed speculative attempt is dropped, leaving the original attempt running
+        let speculative = agg_graph
+            .launch_speculative_task(stage_id, partition_id, executor2.id.clone())
+            .unwrap();
+        let task_status = mock_failed_task(
+            speculative,
+            FailedTask {
+                error: "IOError".to_string(),
+                retryable: true,
+                count_to_failures: true,
+                error_code: ErrorCode::IoFailure.to_string(),
+                failed_reason: Some(failed_task::FailedReason::IoError(IoError {})),
+            },
+        );
+        let events = agg_graph.update_task_status(&executor2, vec![task_status], 4, 4)?;
+        assert!(events.is_empty());
+        assert_eq!(agg_graph.available_tasks(), 0);
+        assert_eq!(agg_graph.running_tasks().len(), 1);
+
+        // The original attempt finishes first and the speculative attempt is cancelled
+        let speculative = agg_graph
+            .launch_speculative_task(stage_id, partition_id, executor2.id.clone())
+            .unwrap();
+        let task_status = mock_completed_task(straggler, &executor1.id);
+        let events = agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
+        assert!(events.iter().any(|event| matches!(
+            event,
+            QueryStageSchedulerEvent::CancelTasks(tasks) if tasks[0].task_id == speculative.task_id
+        )));
+
+        let task_status = mock_completed_task(speculative, &executor2.id);
+        let events = agg_graph.update_task_status(&executor2, vec![task_status], 4, 4)?;
+        assert!(events.iter().any(|event| matches!(
+            event,
+            QueryStageSchedulerEvent::RemoveTaskOutputs { executor_id, .. } if *executor_id == executor2.id
+        )));
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_tasks() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let session_config =
+            SessionConfig::new_with_ballista().with_ballista_stage_timeout_seconds(1);
+        let mut agg_graph =
+            test_aggregation_plan_with_session_config(4, "job", session_config).await;
+        let straggler = run_stage_with_straggler(&mut agg_graph, &executor1)?;
+
+        // Executors are left some time to report the tasks they timed out themselves
+        let now = timestamp_millis() as u128;
+        assert!(agg_graph.timed_out_tasks(now + 1_000).is_empty());
+        let mut timed_out =
+            agg_graph.timed_out_tasks(now + 1_001 + STAGE_TIMEOUT_GRACE_PERIOD_MS);
+        assert_eq!(timed_out.len(), 1);
+        let (executor_id, task_status) = timed_out.pop().unwrap();
+        assert_eq!(executor_id, executor1.id);
+        assert_eq!(task_status.task_id as usize, straggler.task_id);
+        assert!(matches!(
+            &task_status.status,
+            Some(protobuf::task_status::Status::Failed(failed_task)) if failed_task.retryable
+        ));
+
+        // The timed out task is retried
+        agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
+        agg_graph.ignore_task_status(straggler.task_id);
+        assert_eq!(agg_graph.available_tasks(), 1);
+        assert!(agg_graph.running_tasks().is_empty());
+
+        // The output of the timed out attempt is removed if it finishes nonetheless
+        let task_status = mock_completed_task(straggler, &executor1.id);
+        let events = agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
+        assert!(events.iter().any(|event| matches!(
+            event,
+            QueryStageSchedulerEvent::RemoveTaskOutputs { executor_id, .. } if *executor_id == executor1.id
+        )));
+        assert_eq!(agg_graph.available_tasks(), 1);
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_long_delayed_failed_task_after_executor_lost() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -1949,6 +2739,7 @@ mod test {
                 error: "ExecutionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ExecutionFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::ExecutionError(
                     ExecutionError {},
                 )),
@@ -1989,6 +2780,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor1.id.clone(),
@@ -2067,6 +2859,7 @@ mod test {
                         error: "FetchPartitionError".to_string(),
                         retryable: false,
                         count_to_failures: false,
+                        error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                         failed_reason: Some(
                             failed_task::FailedReason::FetchPartitionError(
                                 FetchPartitionError {
@@ -2114,6 +2907,7 @@ mod test {
                         error: "FetchPartitionError".to_string(),
                         retryable: false,
                         count_to_failures: false,
+                        error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                         failed_reason: Some(
                             failed_task::FailedReason::FetchPartitionError(
                                 FetchPartitionError {
@@ -2216,6 +3010,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor2.id.clone(),
@@ -2240,6 +3035,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor2.id.clone(),
@@ -2263,6 +3059,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor1.id.clone(),
@@ -2297,6 +3094,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor1.id.clone(),
@@ -2331,6 +3129,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor3.id.clone(),
@@ -2406,6 +3205,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor2.id.clone(),
@@ -2438,6 +3238,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor1.id.clone(),
@@ -2501,6 +3302,7 @@ mod test {
                     error: "FetchPartitionError".to_string(),
                     retryable: false,
                     count_to_failures: false,
+                    error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                     failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                         FetchPartitionError {
                             executor_id: executor1.id.clone(),
@@ -2528,6 +3330,7 @@ mod test {
                     error: "FetchPartitionError".to_string(),
                     retryable: false,
                     count_to_failures: false,
+                    error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                     failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                         FetchPartitionError {
                             executor_id: executor1.id.clone(),
@@ -2584,6 +3387,7 @@ mod test {
                 error: "FetchPartitionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ShuffleFetchFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
                     FetchPartitionError {
                         executor_id: executor1.id.clone(),
@@ -2602,6 +3406,7 @@ mod test {
                 error: "ExecutionError".to_string(),
                 retryable: false,
                 count_to_failures: false,
+                error_code: ErrorCode::ExecutionFailed.to_string(),
                 failed_reason: Some(failed_task::FailedReason::ExecutionError(
                     ExecutionError {},
                 )),
@@ -2636,6 +3441,29 @@ mod test {
     //     todo!()
     // }
 
+    /// Complete the first stage and all the tasks of the second stage but the last one,
+    /// which is returned
+    fn run_stage_with_straggler(
+        graph: &mut ExecutionGraph,
+        executor: &ExecutorMetadata,
+    ) -> Result<TaskDescription> {
+        graph.revive();
+        revive_graph_and_complete_next_stage(graph)?;
+
+        let mut tasks = vec![];
+        while let Some(task) = graph.pop_next_task(&executor.id)? {
+            tasks.push(task);
+        }
+        let straggler = tasks.pop().unwrap();
+        let task_statuses = tasks
+            .into_iter()
+            .map(|task| mock_completed_task(task, &executor.id))
+            .collect();
+        graph.update_task_status(executor, task_statuses, 4, 4)?;
+
+        Ok(straggler)
+    }
+
     fn drain_tasks(graph: &mut ExecutionGraph) -> Result<()> {
         let executor = mock_executor("executor-id1".to_string());
         while let Some(task) = graph.pop_next_task(&executor.id)? {
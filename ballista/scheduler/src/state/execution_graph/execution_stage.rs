@@ -29,16 +29,23 @@ use datafusion::physical_plan::{ExecutionPlan, Metric};
 use datafusion::prelude::SessionConfig;
 use log::{debug, warn};
 
-use ballista_core::error::{BallistaError, Result};
-use ballista_core::execution_plans::ShuffleWriterExec;
+use ballista_core::error::{BallistaError, ErrorCode, Result};
+use ballista_core::execution_plans::{is_object_store_path, ShuffleWriterExec};
 use ballista_core::serde::protobuf::failed_task::FailedReason;
 use ballista_core::serde::protobuf::{task_status, RunningTask};
 use ballista_core::serde::protobuf::{
-    FailedTask, OperatorMetricsSet, ResultLost, SuccessfulTask, TaskStatus,
+    FailedTask, OperatorMetricsSet, ResultLost, ShuffleWritePartition, SuccessfulTask,
+    TaskStatus,
 };
 use ballista_core::serde::scheduler::PartitionLocation;
 
 use crate::display::DisplayableBallistaExecutionPlan;
+use crate::state::execution_graph::create_task_info;
+use crate::state::job_metrics::summarize_metrics;
+
+/// The minimum time in milliseconds a task must have been running for before it is
+/// considered for speculative execution, so that short tasks are never duplicated
+const MIN_SPECULATION_RUNTIME_MS: u128 = 100;
 
 /// A stage in the ExecutionGraph,
 /// represents a set of tasks (one per each `partition`) which can be executed concurrently.
@@ -82,6 +89,17 @@ impl ExecutionStage {
         }
     }
 
+    /// Get the ID of this stage
+    pub(crate) fn stage_id(&self) -> usize {
+        match self {
+            ExecutionStage::UnResolved(stage) => stage.stage_id,
+            ExecutionStage::Resolved(stage) => stage.stage_id,
+            ExecutionStage::Running(stage) => stage.stage_id,
+            ExecutionStage::Successful(stage) => stage.stage_id,
+            ExecutionStage::Failed(stage) => stage.stage_id,
+        }
+    }
+
     /// Get the query plan for this query stage
     pub(crate) fn plan(&self) -> &dyn ExecutionPlan {
         match self {
@@ -92,6 +110,98 @@ impl ExecutionStage {
             ExecutionStage::Failed(stage) => stage.plan.as_ref(),
         }
     }
+
+    /// Get the attempt number of this stage
+    pub(crate) fn stage_attempt_num(&self) -> usize {
+        match self {
+            ExecutionStage::UnResolved(stage) => stage.stage_attempt_num,
+            ExecutionStage::Resolved(stage) => stage.stage_attempt_num,
+            ExecutionStage::Running(stage) => stage.stage_attempt_num,
+            ExecutionStage::Successful(stage) => stage.stage_attempt_num,
+            ExecutionStage::Failed(stage) => stage.stage_attempt_num,
+        }
+    }
+
+    /// Get the number of tasks of this stage, None if it is not resolved yet
+    pub(crate) fn partitions(&self) -> Option<usize> {
+        match self {
+            ExecutionStage::UnResolved(_) => None,
+            ExecutionStage::Resolved(stage) => Some(stage.partitions),
+            ExecutionStage::Running(stage) => Some(stage.partitions),
+            ExecutionStage::Successful(stage) => Some(stage.partitions),
+            ExecutionStage::Failed(stage) => Some(stage.partitions),
+        }
+    }
+
+    /// Get the IDs of the stages taking the outputs of this stage as inputs
+    pub(crate) fn output_links(&self) -> &[usize] {
+        match self {
+            ExecutionStage::UnResolved(stage) => &stage.output_links,
+            ExecutionStage::Resolved(stage) => &stage.output_links,
+            ExecutionStage::Running(stage) => &stage.output_links,
+            ExecutionStage::Successful(stage) => &stage.output_links,
+            ExecutionStage::Failed(stage) => &stage.output_links,
+        }
+    }
+
+    /// Get the number of tasks of this stage which completed successfully
+    pub(crate) fn completed_tasks(&self) -> usize {
+        match self {
+            ExecutionStage::UnResolved(_) | ExecutionStage::Resolved(_) => 0,
+            ExecutionStage::Running(stage) => stage.successful_tasks(),
+            ExecutionStage::Successful(stage) => stage.partitions,
+            ExecutionStage::Failed(stage) => stage.successful_tasks(),
+        }
+    }
+
+    /// Get the metrics of each operator of this stage, combined across its completed
+    /// tasks, None if no task completed yet
+    pub(crate) fn stage_metrics(&self) -> Option<&[MetricsSet]> {
+        match self {
+            ExecutionStage::UnResolved(_) | ExecutionStage::Resolved(_) => None,
+            ExecutionStage::Running(stage) => stage.stage_metrics.as_deref(),
+            ExecutionStage::Successful(stage) => Some(&stage.stage_metrics),
+            ExecutionStage::Failed(stage) => stage.stage_metrics.as_deref(),
+        }
+    }
+
+    /// Get the TaskInfo of the latest attempts of the tasks of this stage, along with
+    /// their partition. Tasks which weren't launched yet have none
+    pub(crate) fn task_infos(&self) -> Vec<(usize, &TaskInfo)> {
+        match self {
+            ExecutionStage::UnResolved(_) | ExecutionStage::Resolved(_) => vec![],
+            ExecutionStage::Running(stage) => stage
+                .task_infos
+                .iter()
+                .enumerate()
+                .filter_map(|(partition, info)| {
+                    info.as_ref().map(|info| (partition, info))
+                })
+                .collect(),
+            ExecutionStage::Successful(stage) => {
+                stage.task_infos.iter().enumerate().collect()
+            }
+            ExecutionStage::Failed(stage) => stage
+                .task_infos
+                .iter()
+                .enumerate()
+                .filter_map(|(partition, info)| {
+                    info.as_ref().map(|info| (partition, info))
+                })
+                .collect(),
+        }
+    }
+
+    /// Get the TaskInfo of the tasks of this stage which completed successfully, along
+    /// with their partition
+    pub(crate) fn completed_task_infos(&self) -> Vec<(usize, &TaskInfo)> {
+        self.task_infos()
+            .into_iter()
+            .filter(|(_, info)| {
+                matches!(info.task_status, task_status::Status::Successful(_))
+            })
+            .collect()
+    }
 }
 
 /// For a stage whose input stages are not all completed, we say it's a unresolved stage
@@ -168,10 +278,24 @@ pub(crate) struct RunningStage {
     pub(crate) task_failure_numbers: Vec<usize>,
     /// Combined metrics of the already finished tasks in the stage, If it is None, no task is finished yet.
     pub(crate) stage_metrics: Option<Vec<MetricsSet>>,
+    /// TaskInfo of the speculative attempts of tasks running much longer than the other
+    /// tasks of the stage. The key is the task's partition id
+    pub(crate) speculative_task_infos: HashMap<usize, TaskInfo>,
+    /// Running attempts which lost against another attempt of the same task, still to
+    /// be cancelled
+    pub(crate) losing_attempts: Vec<LosingAttempt>,
 
     pub(crate) session_config: Arc<SessionConfig>,
 }
 
+/// A running task attempt which lost against another attempt of the same task
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct LosingAttempt {
+    pub(crate) task_id: usize,
+    pub(crate) partition_id: usize,
+    pub(crate) executor_id: String,
+}
+
 /// If a stage finishes successfully, its task statuses and metrics will be finalized
 #[derive(Clone)]
 pub(crate) struct SuccessfulStage {
@@ -210,7 +334,6 @@ pub(crate) struct FailedStage {
     pub(crate) partitions: usize,
     /// Stage ID of the stage that will take this stages outputs as inputs.
     /// If `output_links` is empty then this the final stage in the `ExecutionGraph`
-    #[allow(dead_code)] // not used at the moment, will be used later
     pub(crate) output_links: Vec<usize>,
     /// `ExecutionPlan` for this stage
     pub(crate) plan: Arc<dyn ExecutionPlan>,
@@ -218,7 +341,6 @@ pub(crate) struct FailedStage {
     /// The index of the Vec is the task's partition id
     pub(crate) task_infos: Vec<Option<TaskInfo>>,
     /// Combined metrics of the already finished tasks in the stage, If it is None, no task is finished yet.
-    #[allow(dead_code)] // not used at the moment, will be used later
     pub(crate) stage_metrics: Option<Vec<MetricsSet>>,
     /// Error message
     pub(crate) error_message: String,
@@ -228,19 +350,23 @@ pub(crate) struct FailedStage {
 #[allow(dead_code)] // we may use the fields later
 pub(crate) struct TaskInfo {
     /// Task ID
-    pub(super) task_id: usize,
+    pub(crate) task_id: usize,
+    /// Executor the task was scheduled on
+    pub(crate) executor_id: String,
     /// Task scheduled time
-    pub(super) scheduled_time: u128,
+    pub(crate) scheduled_time: u128,
     /// Task launch time
-    pub(super) launch_time: u128,
+    pub(crate) launch_time: u128,
     /// Start execution time
-    pub(super) start_exec_time: u128,
+    pub(crate) start_exec_time: u128,
     /// Finish execution time
-    pub(super) end_exec_time: u128,
+    pub(crate) end_exec_time: u128,
     /// Task finish time
-    pub(super) finish_time: u128,
+    pub(crate) finish_time: u128,
     /// Task Status
-    pub(super) task_status: task_status::Status,
+    pub(crate) task_status: task_status::Status,
+    /// Metrics of the operators of the task summed by name, once it finished
+    pub(crate) metrics: HashMap<String, u64>,
     //pub(crate) session_config: Arc<SessionConfig>,
 }
 
@@ -358,6 +484,8 @@ impl UnresolvedStage {
             self.plan.clone(),
             &input_locations,
         )?;
+        let plan =
+            crate::planner::coalesce_shuffle_partitions(plan, &self.session_config)?;
 
         // TODO reinstate this logic once https://github.com/apache/datafusion/issues/10978
         // is fixed
@@ -484,6 +612,8 @@ impl RunningStage {
             task_infos: vec![None; partitions],
             task_failure_numbers: vec![0; partitions],
             stage_metrics: None,
+            speculative_task_infos: HashMap::new(),
+            losing_attempts: vec![],
             session_config,
         }
     }
@@ -585,14 +715,21 @@ impl RunningStage {
         self.task_infos.iter().filter(|s| s.is_some()).count()
     }
 
-    /// Returns a vector of currently running tasks in this stage
+    /// Returns a vector of currently running tasks in this stage, including their
+    /// speculative attempts
     pub(super) fn running_tasks(&self) -> Vec<(usize, usize, usize, String)> {
+        let speculative_tasks = self
+            .speculative_task_infos
+            .iter()
+            .map(|(partition, info)| (*partition, info));
         self.task_infos
             .iter()
             .enumerate()
+            .filter_map(|(partition, info)| info.as_ref().map(|info| (partition, info)))
+            .chain(speculative_tasks)
             .filter_map(|(partition, info)| match info {
-                Some(TaskInfo {task_id,
-                         task_status: task_status::Status::Running(RunningTask { executor_id }), ..}) => {
+                TaskInfo {task_id,
+                         task_status: task_status::Status::Running(RunningTask { executor_id }), ..} => {
                     Some((*task_id, self.stage_id, partition, executor_id.clone()))
                 }
                 _ => None,
@@ -600,6 +737,115 @@ impl RunningStage {
             .collect()
     }
 
+    /// Returns the running tasks in this stage, including their speculative attempts,
+    /// which were scheduled before `time`, as (task ID, partition, executor ID) tuples
+    pub(super) fn tasks_running_since(&self, time: u128) -> Vec<(usize, usize, String)> {
+        let speculative_tasks = self
+            .speculative_task_infos
+            .iter()
+            .map(|(partition, info)| (*partition, info));
+        self.task_infos
+            .iter()
+            .enumerate()
+            .filter_map(|(partition, info)| info.as_ref().map(|info| (partition, info)))
+            .chain(speculative_tasks)
+            .filter_map(|(partition, info)| match info {
+                TaskInfo {
+                    task_id,
+                    scheduled_time,
+                    task_status: task_status::Status::Running(RunningTask { executor_id }),
+                    ..
+                } if *scheduled_time < time => {
+                    Some((*task_id, partition, executor_id.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the running tasks without a speculative attempt yet, along with the
+    /// executor they run on, which have been running `multiplier` times longer than the
+    /// median runtime of the successful tasks of the stage. Stragglers are only looked
+    /// for once at least half of the tasks of the stage succeeded.
+    pub(super) fn straggler_tasks(
+        &self,
+        multiplier: f64,
+        now: u128,
+    ) -> Vec<(usize, String)> {
+        let mut runtimes = self
+            .task_infos
+            .iter()
+            .filter_map(|info| match info {
+                Some(TaskInfo {
+                    scheduled_time,
+                    finish_time,
+                    task_status: task_status::Status::Successful(_),
+                    ..
+                }) => Some(finish_time.saturating_sub(*scheduled_time)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if multiplier <= 0.0
+            || runtimes.is_empty()
+            || runtimes.len() * 2 < self.partitions
+        {
+            return vec![];
+        }
+        runtimes.sort_unstable();
+        let median = runtimes[runtimes.len() / 2];
+        let threshold =
+            ((median as f64 * multiplier) as u128).max(MIN_SPECULATION_RUNTIME_MS);
+
+        self.task_infos
+            .iter()
+            .enumerate()
+            .filter_map(|(partition, info)| match info {
+                Some(TaskInfo {
+                    scheduled_time,
+                    task_status: task_status::Status::Running(RunningTask { executor_id }),
+                    ..
+                }) if !self.speculative_task_infos.contains_key(&partition)
+                    && now.saturating_sub(*scheduled_time) > threshold =>
+                {
+                    Some((partition, executor_id.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Record a speculative attempt of the task of the given partition, launched on
+    /// another executor than the original attempt
+    pub(super) fn add_speculative_task(
+        &mut self,
+        partition_id: usize,
+        executor_id: String,
+        task_id: usize,
+    ) {
+        self.speculative_task_infos
+            .insert(partition_id, create_task_info(executor_id, task_id));
+    }
+
+    /// Take the attempts which lost against another attempt of the same task since
+    /// the last call
+    pub(super) fn take_losing_attempts(&mut self) -> Vec<LosingAttempt> {
+        std::mem::take(&mut self.losing_attempts)
+    }
+
+    /// Cancel the attempt of a task after another attempt of the same task finished
+    fn cancel_attempt(
+        &mut self,
+        task_id: usize,
+        partition_id: usize,
+        executor_id: String,
+    ) {
+        self.losing_attempts.push(LosingAttempt {
+            task_id,
+            partition_id,
+            executor_id,
+        });
+    }
+
     /// Returns the number of tasks in this stage which are available for scheduling.
     /// If the stage is not yet resolved, then this will return `0`, otherwise it will
     /// return the number of tasks where the task info is not yet set.
@@ -614,28 +860,50 @@ impl RunningStage {
         status: TaskStatus,
     ) -> bool {
         debug!("Updating TaskInfo for partition {}", partition_id);
+        let status_task_id = status.task_id as usize;
+        if self
+            .speculative_task_infos
+            .get(&partition_id)
+            .is_some_and(|info| info.task_id == status_task_id)
+        {
+            return self.update_speculative_task_info(partition_id, status);
+        }
+
         let task_info = self.task_infos[partition_id].as_ref().unwrap();
         let task_id = task_info.task_id;
-        if (status.task_id as usize) < task_id {
+        if status_task_id < task_id {
             warn!("Ignore TaskStatus update with TID {} because there is more recent task attempt with TID {} running for partition {}",
                 status.task_id, task_id, partition_id);
             return false;
         }
         let scheduled_time = task_info.scheduled_time;
-        let task_status = status.status.unwrap();
-        let updated_task_info = TaskInfo {
+        let executor_id = task_info.executor_id.clone();
+        let task_status = status.status.clone().unwrap();
+
+        let finished = !matches!(task_status, task_status::Status::Running(_));
+        if let Some(speculative) = finished
+            .then(|| self.speculative_task_infos.remove(&partition_id))
+            .flatten()
+        {
+            if matches!(&task_status, task_status::Status::Failed(failed_task) if failed_task.retryable)
+            {
+                // The speculative attempt takes over the task
+                self.task_infos[partition_id] = Some(speculative);
+                return false;
+            }
+            if let task_status::Status::Running(RunningTask { executor_id }) =
+                speculative.task_status
+            {
+                self.cancel_attempt(speculative.task_id, partition_id, executor_id);
+            }
+        }
+
+        self.task_infos[partition_id] = Some(finished_task_info(
             task_id,
             scheduled_time,
-            launch_time: status.launch_time as u128,
-            start_exec_time: status.start_exec_time as u128,
-            end_exec_time: status.end_exec_time as u128,
-            finish_time: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_millis(),
-            task_status: task_status.clone(),
-        };
-        self.task_infos[partition_id] = Some(updated_task_info);
+            executor_id,
+            &status,
+        ));
 
         if let task_status::Status::Failed(failed_task) = task_status {
             // if the failed task is retryable, increase the task failure count for this partition
@@ -648,6 +916,45 @@ impl RunningStage {
         true
     }
 
+    /// Update the TaskInfo of the speculative attempt of the task of a partition. If the
+    /// speculative attempt succeeds, it replaces the original attempt, which is
+    /// cancelled. Failed speculative attempts are dropped, leaving the original attempt
+    /// running.
+    fn update_speculative_task_info(
+        &mut self,
+        partition_id: usize,
+        status: TaskStatus,
+    ) -> bool {
+        if matches!(status.status, Some(task_status::Status::Running(_))) {
+            return false;
+        }
+        let speculative = self.speculative_task_infos.remove(&partition_id).unwrap();
+        if !matches!(status.status, Some(task_status::Status::Successful(_))) {
+            debug!(
+                "Drop the speculative attempt with TID {} for partition {}",
+                status.task_id, partition_id
+            );
+            return false;
+        }
+
+        if let Some(TaskInfo {
+            task_id,
+            task_status: task_status::Status::Running(RunningTask { executor_id }),
+            ..
+        }) = self.task_infos[partition_id].clone()
+        {
+            self.cancel_attempt(task_id, partition_id, executor_id);
+        }
+        self.task_infos[partition_id] = Some(finished_task_info(
+            speculative.task_id,
+            speculative.scheduled_time,
+            speculative.executor_id,
+            &status,
+        ));
+        self.task_failure_numbers[partition_id] = 0;
+        true
+    }
+
     /// update and combine the task metrics to the stage metrics
     pub(super) fn update_task_metrics(
         &mut self,
@@ -709,33 +1016,59 @@ impl RunningStage {
         self.task_failure_numbers[partition_id]
     }
 
+    /// Mark the task of a partition which permanently failed as finished without any
+    /// output, so that the stage completes without the partition. The task is not
+    /// attributed to any executor, as there is no output to lose with it.
+    pub(super) fn skip_task(&mut self, partition_id: usize) {
+        if let Some(info) = self.task_infos[partition_id].as_mut() {
+            info.task_status = task_status::Status::Successful(SuccessfulTask::default());
+        }
+        self.task_failure_numbers[partition_id] = 0;
+    }
+
     /// Reset the task info for the given task partition. This should be called when a task failed and need to be
     /// re-scheduled.
     pub fn reset_task_info(&mut self, partition_id: usize) {
         self.task_infos[partition_id] = None;
+        if let Some(TaskInfo {
+            task_id,
+            task_status: task_status::Status::Running(RunningTask { executor_id }),
+            ..
+        }) = self.speculative_task_infos.remove(&partition_id)
+        {
+            self.cancel_attempt(task_id, partition_id, executor_id);
+        }
     }
 
     /// Reset the running and completed tasks on a given executor
     /// Returns the number of running tasks that were reset
+    /// Running tasks with a speculative attempt on another executor are taken over by it
     pub fn reset_tasks(&mut self, executor: &str) -> usize {
+        self.speculative_task_infos.retain(|_, info| {
+            !matches!(&info.task_status,
+                task_status::Status::Running(RunningTask { executor_id }) if *executor == *executor_id)
+        });
+
         let mut reset = 0;
-        for task in self.task_infos.iter_mut() {
+        for (partition_id, task) in self.task_infos.iter_mut().enumerate() {
             match task {
                 Some(TaskInfo {
                     task_status: task_status::Status::Running(RunningTask { executor_id }),
                     ..
                 }) if *executor == *executor_id => {
-                    *task = None;
-                    reset += 1;
+                    *task = self.speculative_task_infos.remove(&partition_id);
+                    if task.is_none() {
+                        reset += 1;
+                    }
                 }
                 Some(TaskInfo {
                     task_status:
                         task_status::Status::Successful(SuccessfulTask {
                             executor_id,
-                            partitions: _,
+                            partitions,
                         }),
                     ..
-                }) if *executor == *executor_id => {
+                }) if *executor == *executor_id && !outlives_executor(partitions) => {
                     *task = None;
                     reset += 1;
                 }
@@ -822,6 +1155,8 @@ impl SuccessfulStage {
             // It is Ok to forget the previous task failure attempts
             task_failure_numbers: vec![0; self.partitions],
             stage_metrics,
+            speculative_task_infos: HashMap::new(),
+            losing_attempts: vec![],
             session_config: self.session_config.clone(),
         }
     }
@@ -838,21 +1173,25 @@ impl SuccessfulStage {
                     scheduled_time,
                     task_status:
                         task_status::Status::Successful(SuccessfulTask {
-                            executor_id, ..
+                            executor_id,
+                            partitions,
                         }),
                     ..
-                } if *executor == *executor_id => {
+                } if *executor == *executor_id && !outlives_executor(partitions) => {
                     *task = TaskInfo {
                         task_id: *task_id,
+                        executor_id: executor.to_owned(),
                         scheduled_time: *scheduled_time,
                         launch_time: 0,
                         start_exec_time: 0,
                         end_exec_time: 0,
                         finish_time: 0,
+                        metrics: HashMap::new(),
                         task_status: task_status::Status::Failed(FailedTask {
                             error: failure_reason.clone(),
                             retryable: true,
                             count_to_failures: false,
+                            error_code: ErrorCode::ResultLost.to_string(),
                             failed_reason: Some(FailedReason::ResultLost(ResultLost {})),
                         }),
                     };
@@ -932,6 +1271,40 @@ impl Debug for FailedStage {
 /// Get the total number of partitions for a stage with plan.
 /// Only for [`ShuffleWriterExec`], the input partition count and the output partition count
 /// will be different. Here, we should use the input partition count.
+/// TaskInfo of a task attempt which reported the given status
+fn finished_task_info(
+    task_id: usize,
+    scheduled_time: u128,
+    executor_id: String,
+    status: &TaskStatus,
+) -> TaskInfo {
+    TaskInfo {
+        task_id,
+        executor_id,
+        scheduled_time,
+        launch_time: status.launch_time as u128,
+        start_exec_time: status.start_exec_time as u128,
+        end_exec_time: status.end_exec_time as u128,
+        finish_time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis(),
+        task_status: status.status.clone().unwrap(),
+        metrics: task_metrics(&status.metrics),
+    }
+}
+
+/// Metrics of the operators of a finished task summed by name, or none if the task
+/// reported metrics which can't be decoded
+fn task_metrics(metrics: &[OperatorMetricsSet]) -> HashMap<String, u64> {
+    metrics
+        .iter()
+        .map(|metrics| metrics.clone().try_into())
+        .collect::<Result<Vec<MetricsSet>>>()
+        .map(|metrics| summarize_metrics(&metrics))
+        .unwrap_or_default()
+}
+
 fn get_stage_partitions(plan: Arc<dyn ExecutionPlan>) -> usize {
     plan.as_any()
         .downcast_ref::<ShuffleWriterExec>()
@@ -939,6 +1312,12 @@ fn get_stage_partitions(plan: Arc<dyn ExecutionPlan>) -> usize {
         .unwrap_or_else(|| plan.properties().output_partitioning().partition_count())
 }
 
+/// Whether the output of a successful task outlives its executor, having been uploaded
+/// to an object store
+fn outlives_executor(partitions: &[ShuffleWritePartition]) -> bool {
+    !partitions.is_empty() && partitions.iter().all(|p| is_object_store_path(&p.path))
+}
+
 /// This data structure collects the partition locations for an `ExecutionStage`.
 /// Each `ExecutionStage` will hold a `StageOutput`s for each of its child stages.
 /// When all tasks for the child stage are complete, it will mark the `StageOutput`
@@ -0,0 +1,512 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Encoding of execution graphs, for the state of the jobs of a scheduler to outlive it.
+//!
+//! Running stages are encoded as resolved stages of their next attempt: once decoded,
+//! their tasks are run again, and the statuses of the tasks of the previous attempt,
+//! which may still be reported by executors, are ignored. The output of stages is only
+//! kept once they are successful.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::with_scan_options;
+use ballista_core::serde::protobuf::{
+    self, execution_graph_stage::StageType, task_info, task_status,
+};
+use ballista_core::serde::scheduler::PartitionLocation;
+use ballista_core::serde::BallistaCodec;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionContext;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+
+use super::execution_stage::{FailedStage, SuccessfulStage};
+use super::{
+    ExecutionGraph, ExecutionStage, ResolvedStage, StageOutput, TaskInfo, UnresolvedStage,
+};
+
+impl ExecutionGraph {
+    /// Encode the graph, with the plans of its stages encoded by `codec`
+    pub(crate) fn encode<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
+        &self,
+        codec: &BallistaCodec<T, U>,
+    ) -> Result<protobuf::ExecutionGraph> {
+        let stages = self
+            .stages
+            .values()
+            .map(|stage| self.encode_stage(stage, codec))
+            .collect::<Result<Vec<_>>>()?;
+        let output_locations = self
+            .output_locations
+            .iter()
+            .map(|location| location.clone().try_into())
+            .collect::<Result<Vec<_>>>()?;
+        let failed_attempts = self
+            .failed_stage_attempts
+            .iter()
+            .map(|(stage_id, attempts)| protobuf::StageAttempts {
+                stage_id: *stage_id as u32,
+                stage_attempt_num: attempts.iter().map(|num| *num as u32).collect(),
+            })
+            .collect();
+
+        Ok(protobuf::ExecutionGraph {
+            job_id: self.job_id.clone(),
+            session_id: self.session_id.clone(),
+            status: Some(self.status.clone()),
+            stages,
+            output_partitions: self.output_partitions as u64,
+            output_locations,
+            scheduler_id: self.scheduler_id.clone().unwrap_or_default(),
+            task_id_gen: self.task_id_gen as u32,
+            failed_attempts,
+            job_name: self.job_name.clone(),
+            start_time: self.start_time,
+            end_time: self.end_time,
+            queued_at: self.queued_at,
+            fetch_token: self.fetch_token.as_str().to_owned(),
+            planned_from_cache: self.planned_from_cache,
+            owner: self.owner.clone(),
+        })
+    }
+
+    /// Decode a graph encoded by [ExecutionGraph::encode], whose plans are decoded with
+    /// the functions of `session_ctx`, the session of the job
+    pub(crate) fn decode<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
+        proto: protobuf::ExecutionGraph,
+        session_ctx: &SessionContext,
+        codec: &BallistaCodec<T, U>,
+    ) -> Result<Self> {
+        let session_config = Arc::new(session_ctx.copied_config());
+        let mut stages = HashMap::new();
+        for stage in proto.stages {
+            let stage = match stage.stage_type {
+                Some(StageType::UnresolvedStage(stage)) => {
+                    ExecutionStage::UnResolved(UnresolvedStage::new_with_inputs(
+                        stage.stage_id as usize,
+                        stage.stage_attempt_num as usize,
+                        decode_plan(&stage.plan, session_ctx, codec)?,
+                        to_usize(&stage.output_links),
+                        decode_inputs(stage.inputs)?,
+                        stage.last_attempt_failure_reasons.into_iter().collect(),
+                        session_config.clone(),
+                    ))
+                }
+                Some(StageType::ResolvedStage(stage)) => {
+                    ExecutionStage::Resolved(ResolvedStage::new(
+                        stage.stage_id as usize,
+                        stage.stage_attempt_num as usize,
+                        decode_plan(&stage.plan, session_ctx, codec)?,
+                        to_usize(&stage.output_links),
+                        decode_inputs(stage.inputs)?,
+                        stage.last_attempt_failure_reasons.into_iter().collect(),
+                        session_config.clone(),
+                    ))
+                }
+                Some(StageType::SuccessfulStage(stage)) => {
+                    ExecutionStage::Successful(SuccessfulStage {
+                        stage_id: stage.stage_id as usize,
+                        stage_attempt_num: stage.stage_attempt_num as usize,
+                        partitions: stage.partitions as usize,
+                        output_links: to_usize(&stage.output_links),
+                        inputs: decode_inputs(stage.inputs)?,
+                        plan: decode_plan(&stage.plan, session_ctx, codec)?,
+                        task_infos: decode_task_infos(
+                            stage.task_infos,
+                            stage.partitions as usize,
+                        )?
+                        .into_iter()
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| {
+                            BallistaError::Internal(format!(
+                                "Missing tasks of successful stage {}",
+                                stage.stage_id
+                            ))
+                        })?,
+                        stage_metrics: decode_metrics(stage.stage_metrics)?,
+                        session_config: session_config.clone(),
+                    })
+                }
+                Some(StageType::FailedStage(stage)) => {
+                    ExecutionStage::Failed(FailedStage {
+                        stage_id: stage.stage_id as usize,
+                        stage_attempt_num: stage.stage_attempt_num as usize,
+                        partitions: stage.partitions as usize,
+                        output_links: to_usize(&stage.output_links),
+                        plan: decode_plan(&stage.plan, session_ctx, codec)?,
+                        task_infos: decode_task_infos(
+                            stage.task_infos,
+                            stage.partitions as usize,
+                        )?,
+                        stage_metrics: (!stage.stage_metrics.is_empty())
+                            .then(|| decode_metrics(stage.stage_metrics))
+                            .transpose()?,
+                        error_message: stage.error_message,
+                    })
+                }
+                None => {
+                    return Err(BallistaError::Internal(format!(
+                        "Missing stage type in the execution graph of job {}",
+                        proto.job_id
+                    )))
+                }
+            };
+            stages.insert(stage.stage_id(), stage);
+        }
+
+        let output_locations = proto
+            .output_locations
+            .into_iter()
+            .map(|location| location.try_into())
+            .collect::<Result<Vec<PartitionLocation>>>()?;
+        let failed_stage_attempts = proto
+            .failed_attempts
+            .into_iter()
+            .map(|attempts| {
+                (
+                    attempts.stage_id as usize,
+                    attempts
+                        .stage_attempt_num
+                        .into_iter()
+                        .map(|num| num as usize)
+                        .collect(),
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            scheduler_id: (!proto.scheduler_id.is_empty()).then_some(proto.scheduler_id),
+            status: proto.status.ok_or_else(|| {
+                BallistaError::Internal(format!(
+                    "Missing status in the execution graph of job {}",
+                    proto.job_id
+                ))
+            })?,
+            job_id: proto.job_id,
+            job_name: proto.job_name,
+            session_id: proto.session_id,
+            queued_at: proto.queued_at,
+            start_time: proto.start_time,
+            end_time: proto.end_time,
+            stages,
+            output_partitions: proto.output_partitions as usize,
+            output_locations,
+            task_id_gen: proto.task_id_gen as usize,
+            failed_stage_attempts,
+            cancelled_attempts: HashSet::new(),
+            skipped_partitions: vec![],
+            scan_leases: HashMap::new(),
+            session_config,
+            fetch_token: proto.fetch_token.into(),
+            planned_from_cache: proto.planned_from_cache,
+            owner: proto.owner,
+        })
+    }
+
+    fn encode_stage<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
+        &self,
+        stage: &ExecutionStage,
+        codec: &BallistaCodec<T, U>,
+    ) -> Result<protobuf::ExecutionGraphStage> {
+        let stage_type = match stage {
+            ExecutionStage::UnResolved(stage) => {
+                StageType::UnresolvedStage(protobuf::UnResolvedStage {
+                    stage_id: stage.stage_id as u32,
+                    output_links: to_u32(&stage.output_links),
+                    inputs: self.encode_inputs(&stage.inputs)?,
+                    plan: encode_plan(&stage.plan, codec)?,
+                    stage_attempt_num: stage.stage_attempt_num as u32,
+                    last_attempt_failure_reasons: stage
+                        .last_attempt_failure_reasons
+                        .iter()
+                        .cloned()
+                        .collect(),
+                })
+            }
+            ExecutionStage::Resolved(stage) => {
+                StageType::ResolvedStage(protobuf::ResolvedStage {
+                    stage_id: stage.stage_id as u32,
+                    partitions: stage.partitions as u32,
+                    output_links: to_u32(&stage.output_links),
+                    inputs: self.encode_inputs(&stage.inputs)?,
+                    plan: encode_plan(&stage.plan, codec)?,
+                    stage_attempt_num: stage.stage_attempt_num as u32,
+                    last_attempt_failure_reasons: stage
+                        .last_attempt_failure_reasons
+                        .iter()
+                        .cloned()
+                        .collect(),
+                })
+            }
+            ExecutionStage::Running(stage) => {
+                StageType::ResolvedStage(protobuf::ResolvedStage {
+                    stage_id: stage.stage_id as u32,
+                    partitions: stage.partitions as u32,
+                    output_links: to_u32(&stage.output_links),
+                    inputs: self.encode_inputs(&stage.inputs)?,
+                    plan: encode_plan(&stage.plan, codec)?,
+                    stage_attempt_num: stage.stage_attempt_num as u32 + 1,
+                    last_attempt_failure_reasons: vec![],
+                })
+            }
+            ExecutionStage::Successful(stage) => {
+                StageType::SuccessfulStage(protobuf::SuccessfulStage {
+                    stage_id: stage.stage_id as u32,
+                    partitions: stage.partitions as u32,
+                    output_links: to_u32(&stage.output_links),
+                    inputs: self.encode_inputs(&stage.inputs)?,
+                    plan: encode_plan(&stage.plan, codec)?,
+                    task_infos: stage
+                        .task_infos
+                        .iter()
+                        .enumerate()
+                        .map(|(partition_id, info)| encode_task_info(partition_id, info))
+                        .collect(),
+                    stage_metrics: encode_metrics(&stage.stage_metrics)?,
+                    stage_attempt_num: stage.stage_attempt_num as u32,
+                })
+            }
+            ExecutionStage::Failed(stage) => {
+                StageType::FailedStage(protobuf::FailedStage {
+                    stage_id: stage.stage_id as u32,
+                    partitions: stage.partitions as u32,
+                    output_links: to_u32(&stage.output_links),
+                    plan: encode_plan(&stage.plan, codec)?,
+                    task_infos: stage
+                        .task_infos
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(partition_id, info)| {
+                            info.as_ref()
+                                .map(|info| encode_task_info(partition_id, info))
+                        })
+                        .collect(),
+                    stage_metrics: encode_metrics(
+                        stage.stage_metrics.as_deref().unwrap_or_default(),
+                    )?,
+                    error_message: stage.error_message.clone(),
+                    stage_attempt_num: stage.stage_attempt_num as u32,
+                })
+            }
+        };
+        Ok(protobuf::ExecutionGraphStage {
+            stage_type: Some(stage_type),
+        })
+    }
+
+    /// Take over a job recovered from the state of another scheduler
+    pub(crate) fn take_over(&mut self, scheduler_id: &str) {
+        self.scheduler_id = Some(scheduler_id.to_owned());
+        if let Some(protobuf::job_status::Status::Running(running)) =
+            &mut self.status.status
+        {
+            running.scheduler = scheduler_id.to_owned();
+        }
+    }
+
+    /// Encode the inputs of a stage. The partitions of input stages which aren't
+    /// successful are left out, as these stages are run again once decoded
+    fn encode_inputs(
+        &self,
+        inputs: &HashMap<usize, StageOutput>,
+    ) -> Result<Vec<protobuf::GraphStageInput>> {
+        inputs
+            .iter()
+            .map(|(stage_id, output)| {
+                let successful = matches!(
+                    self.stages.get(stage_id),
+                    Some(ExecutionStage::Successful(_))
+                );
+                let partition_locations = if successful {
+                    output
+                        .partition_locations
+                        .iter()
+                        .map(|(partition, locations)| {
+                            Ok(protobuf::TaskInputPartitions {
+                                partition: *partition as u32,
+                                partition_location: locations
+                                    .iter()
+                                    .map(|location| location.clone().try_into())
+                                    .collect::<Result<Vec<_>>>()?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?
+                } else {
+                    vec![]
+                };
+                Ok(protobuf::GraphStageInput {
+                    stage_id: *stage_id as u32,
+                    partition_locations,
+                    complete: successful && output.complete,
+                })
+            })
+            .collect()
+    }
+}
+
+fn decode_inputs(
+    inputs: Vec<protobuf::GraphStageInput>,
+) -> Result<HashMap<usize, StageOutput>> {
+    inputs
+        .into_iter()
+        .map(|input| {
+            let partition_locations = input
+                .partition_locations
+                .into_iter()
+                .map(|partitions| {
+                    let locations = partitions
+                        .partition_location
+                        .into_iter()
+                        .map(|location| location.try_into())
+                        .collect::<Result<Vec<PartitionLocation>>>()?;
+                    Ok((partitions.partition as usize, locations))
+                })
+                .collect::<Result<HashMap<_, _>>>()?;
+            Ok((
+                input.stage_id as usize,
+                StageOutput {
+                    partition_locations,
+                    complete: input.complete,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn encode_plan<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
+    plan: &Arc<dyn ExecutionPlan>,
+    codec: &BallistaCodec<T, U>,
+) -> Result<Vec<u8>> {
+    let proto = U::try_from_physical_plan(
+        with_scan_options(plan.clone())?,
+        codec.physical_extension_codec(),
+    )?;
+    let mut buf = vec![];
+    proto.try_encode(&mut buf)?;
+    Ok(buf)
+}
+
+fn decode_plan<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
+    plan: &[u8],
+    session_ctx: &SessionContext,
+    codec: &BallistaCodec<T, U>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    Ok(U::try_decode(plan)?.try_into_physical_plan(
+        session_ctx,
+        session_ctx.runtime_env().as_ref(),
+        codec.physical_extension_codec(),
+    )?)
+}
+
+/// Encode the info of a task. The metrics of the task are left out, the metrics of its
+/// stage summing them up
+fn encode_task_info(partition_id: usize, info: &TaskInfo) -> protobuf::TaskInfo {
+    let status = match &info.task_status {
+        task_status::Status::Running(running) => {
+            task_info::Status::Running(running.clone())
+        }
+        task_status::Status::Failed(failed) => task_info::Status::Failed(failed.clone()),
+        task_status::Status::Successful(successful) => {
+            task_info::Status::Successful(successful.clone())
+        }
+    };
+    protobuf::TaskInfo {
+        task_id: info.task_id as u32,
+        partition_id: partition_id as u32,
+        scheduled_time: info.scheduled_time as u64,
+        launch_time: info.launch_time as u64,
+        start_exec_time: info.start_exec_time as u64,
+        end_exec_time: info.end_exec_time as u64,
+        finish_time: info.finish_time as u64,
+        status: Some(status),
+        executor_id: info.executor_id.clone(),
+    }
+}
+
+/// Decode the infos of the tasks of a stage of `partitions` tasks, by partition
+fn decode_task_infos(
+    infos: Vec<protobuf::TaskInfo>,
+    partitions: usize,
+) -> Result<Vec<Option<TaskInfo>>> {
+    let mut task_infos = vec![None; partitions];
+    for info in infos {
+        let task_status = match info.status {
+            Some(task_info::Status::Running(running)) => {
+                task_status::Status::Running(running)
+            }
+            Some(task_info::Status::Failed(failed)) => {
+                task_status::Status::Failed(failed)
+            }
+            Some(task_info::Status::Successful(successful)) => {
+                task_status::Status::Successful(successful)
+            }
+            None => {
+                return Err(BallistaError::Internal(format!(
+                    "Missing status of task {}",
+                    info.task_id
+                )))
+            }
+        };
+        let slot = task_infos
+            .get_mut(info.partition_id as usize)
+            .ok_or_else(|| {
+                BallistaError::Internal(format!(
+                    "Invalid partition {} of task {}",
+                    info.partition_id, info.task_id
+                ))
+            })?;
+        *slot = Some(TaskInfo {
+            task_id: info.task_id as usize,
+            executor_id: info.executor_id,
+            scheduled_time: info.scheduled_time as u128,
+            launch_time: info.launch_time as u128,
+            start_exec_time: info.start_exec_time as u128,
+            end_exec_time: info.end_exec_time as u128,
+            finish_time: info.finish_time as u128,
+            task_status,
+            metrics: HashMap::new(),
+        });
+    }
+    Ok(task_infos)
+}
+
+fn encode_metrics(metrics: &[MetricsSet]) -> Result<Vec<protobuf::OperatorMetricsSet>> {
+    metrics
+        .iter()
+        .map(|metrics| metrics.clone().try_into())
+        .collect()
+}
+
+fn decode_metrics(metrics: Vec<protobuf::OperatorMetricsSet>) -> Result<Vec<MetricsSet>> {
+    metrics
+        .into_iter()
+        .map(|metrics| metrics.try_into())
+        .collect()
+}
+
+fn to_u32(values: &[usize]) -> Vec<u32> {
+    values.iter().map(|value| *value as u32).collect()
+}
+
+fn to_usize(values: &[u32]) -> Vec<usize> {
+    values.iter().map(|value| *value as usize).collect()
+}
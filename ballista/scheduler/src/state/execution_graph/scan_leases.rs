@@ -0,0 +1,166 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Leases of the files of the splittable scan of a stage to its tasks.
+
+use std::collections::VecDeque;
+
+use ballista_core::error::{BallistaError, Result};
+
+/// A file leased to a partition of a stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ScanFileLease {
+    /// Index of the file among the files of all the partitions of the scan
+    pub file: usize,
+    /// Partition the file belongs to, another partition than the leasing one when the
+    /// file was taken over from a slower partition
+    pub owner: usize,
+}
+
+/// Files of the splittable scan of a stage, leased one at a time to the tasks of the
+/// stage. Partitions are leased their own files first, then the files other partitions
+/// have not started yet, taken from the partition with the most files left.
+///
+/// The files leased to a partition are recorded, so that retried or speculative
+/// attempts of its task are leased the same files, in the same order, and once a
+/// partition is told there are no files left it is never leased any other.
+#[derive(Debug, Clone)]
+pub(crate) struct StageScanLeases {
+    /// Files of each partition not leased yet
+    unleased: Vec<VecDeque<usize>>,
+    /// Files leased to each partition, in order
+    leased: Vec<Vec<ScanFileLease>>,
+    /// Whether each partition was told there are no files left
+    done: Vec<bool>,
+}
+
+impl StageScanLeases {
+    /// Leases of a scan whose partitions have the given numbers of files
+    pub(crate) fn new(partition_files: &[usize]) -> Self {
+        let mut next_file = 0;
+        let unleased = partition_files
+            .iter()
+            .map(|files| {
+                let partition = (next_file..next_file + files).collect();
+                next_file += files;
+                partition
+            })
+            .collect();
+        Self {
+            unleased,
+            leased: vec![vec![]; partition_files.len()],
+            done: vec![false; partition_files.len()],
+        }
+    }
+
+    /// The file a task of `partition` scans after the `position` files it already
+    /// scanned, None once there are no files left
+    pub(crate) fn lease(
+        &mut self,
+        partition: usize,
+        position: usize,
+    ) -> Result<Option<ScanFileLease>> {
+        let Some(leased) = self.leased.get(partition) else {
+            return Err(BallistaError::General(format!(
+                "Partition {partition} is out of the {} partitions of the scan",
+                self.leased.len()
+            )));
+        };
+        if let Some(lease) = leased.get(position) {
+            return Ok(Some(*lease));
+        }
+        if position > leased.len() {
+            return Err(BallistaError::General(format!(
+                "Partition {partition} asked for file {position} of the scan, but was \
+                 only leased {} files",
+                leased.len()
+            )));
+        }
+        if self.done[partition] {
+            return Ok(None);
+        }
+
+        let owner = if self.unleased[partition].is_empty() {
+            self.unleased
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, files)| files.len())
+                .filter(|(_, files)| !files.is_empty())
+                .map(|(owner, _)| owner)
+        } else {
+            Some(partition)
+        };
+        // partitions scan their own files from the front, and give up the files at the
+        // back to the partitions taking them over
+        let file = owner.and_then(|owner| {
+            let files = &mut self.unleased[owner];
+            if owner == partition {
+                files.pop_front()
+            } else {
+                files.pop_back()
+            }
+            .map(|file| ScanFileLease { file, owner })
+        });
+        match file {
+            Some(lease) => self.leased[partition].push(lease),
+            None => self.done[partition] = true,
+        }
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(leases: &mut StageScanLeases, partition: usize) -> Vec<usize> {
+        (0..)
+            .map_while(|position| leases.lease(partition, position).unwrap())
+            .map(|lease| lease.file)
+            .collect()
+    }
+
+    #[test]
+    fn lease_own_files_then_steal() {
+        let mut leases = StageScanLeases::new(&[2, 4, 1]);
+
+        // partition 1 is slow, and only starts its first file
+        assert_eq!(
+            Some(ScanFileLease { file: 2, owner: 1 }),
+            leases.lease(1, 0).unwrap()
+        );
+        assert_eq!(
+            Some(ScanFileLease { file: 6, owner: 2 }),
+            leases.lease(2, 0).unwrap()
+        );
+
+        // partition 0 scans its own files, then takes over the files partition 1 has not
+        // started, from the last one
+        assert_eq!(vec![0, 1, 5, 4, 3], files(&mut leases, 0));
+        assert_eq!(vec![2], files(&mut leases, 1));
+        assert_eq!(None, leases.lease(2, 1).unwrap());
+
+        // attempts of a task are leased the same files
+        assert_eq!(vec![0, 1, 5, 4, 3], files(&mut leases, 0));
+        assert_eq!(
+            Some(ScanFileLease { file: 5, owner: 1 }),
+            leases.lease(0, 2).unwrap()
+        );
+        assert!(leases.lease(0, 6).is_err());
+        assert!(leases.lease(3, 0).is_err());
+    }
+}
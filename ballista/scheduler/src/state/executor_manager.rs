@@ -17,49 +17,89 @@
 
 use std::time::Duration;
 
+use ballista_core::config::ShuffleCompression;
 use ballista_core::error::BallistaError;
 use ballista_core::error::Result;
 use ballista_core::serde::protobuf;
 
-use crate::cluster::{BoundTask, ClusterState, ExecutorSlot};
-use crate::config::SchedulerConfig;
+use crate::cluster::job_scheduling::JobSchedulingPolicy;
+use crate::cluster::{BoundTask, ClusterState, ClusterStateEventStream, ExecutorSlot};
+use crate::config::{ExecutorVersionPolicy, SchedulerConfig};
 
 use crate::state::execution_graph::RunningTaskInfo;
+use crate::state::executor_registration::ExecutorRegistrationTokens;
+use crate::state::executor_settings::ExecutorSettingsRollout;
+use crate::state::executor_telemetry::{ExecutorTelemetry, ExecutorUtilization};
+use crate::state::job_staging::JobStaging;
+use crate::state::slot_reservations::{ReservedSlotsScheduling, SlotReservations};
+use crate::state::stage_verification::verification_job_id;
 use crate::state::task_manager::JobInfoCache;
+use ballista_core::security::GrpcSecurityConfig;
 use ballista_core::serde::protobuf::executor_grpc_client::ExecutorGrpcClient;
 use ballista_core::serde::protobuf::{
-    executor_status, CancelTasksParams, ExecutorHeartbeat, MultiTaskDefinition,
-    RemoveJobDataParams, StopExecutorParams,
+    executor_metric, executor_status, CancelTasksParams, ExecutorHeartbeat,
+    GetProfileParams, GetTaskLogsParams, MultiTaskDefinition, RemoveJobDataParams,
+    RemoveTaskOutputsParams, StopExecutorParams,
 };
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
-use ballista_core::utils::{create_grpc_client_connection, get_time_before};
+use ballista_core::utils::{create_grpc_client_connection, get_time_before, GrpcChannel};
 use dashmap::DashMap;
 use log::{debug, error, info, warn};
-use std::collections::{HashMap, HashSet};
+use parking_lot::RwLock;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
-use tonic::transport::Channel;
 
-type ExecutorClients = Arc<DashMap<String, ExecutorGrpcClient<Channel>>>;
+type ExecutorClients = Arc<DashMap<String, ExecutorGrpcClient<GrpcChannel>>>;
 
 #[derive(Clone)]
 pub struct ExecutorManager {
     cluster_state: Arc<dyn ClusterState>,
     config: Arc<SchedulerConfig>,
     clients: ExecutorClients,
+    /// Executor version each active job is pinned to, keyed by job ID
+    job_versions: Arc<DashMap<String, String>>,
+    /// Operational settings pushed to the executors
+    settings_rollout: Arc<RwLock<Option<ExecutorSettingsRollout>>>,
+    /// Tokens executors present to register
+    registration_tokens: Arc<ExecutorRegistrationTokens>,
+    /// Resource usage the executors report with their heartbeats
+    telemetry: Arc<ExecutorTelemetry>,
+    /// Staging prefixes of the jobs in object stores, deleted with the job data
+    job_staging: JobStaging,
+    /// Reservations of executor slots held back for the jobs running with them
+    slot_reservations: Arc<SlotReservations>,
 }
 
 impl ExecutorManager {
     pub(crate) fn new(
         cluster_state: Arc<dyn ClusterState>,
         config: Arc<SchedulerConfig>,
+        slot_reservations: Arc<SlotReservations>,
     ) -> Self {
+        let registration_tokens = Arc::new(ExecutorRegistrationTokens::new(
+            config.executor_registration_tokens.clone(),
+        ));
+        let telemetry = Arc::new(ExecutorTelemetry::new(
+            config.executor_resource_history_size,
+        ));
         Self {
             cluster_state,
             config,
             clients: Default::default(),
+            job_versions: Default::default(),
+            settings_rollout: Default::default(),
+            registration_tokens,
+            telemetry,
+            job_staging: JobStaging::default(),
+            slot_reservations,
         }
     }
 
+    /// Staging prefixes of the jobs in object stores
+    pub(crate) fn job_staging(&self) -> &JobStaging {
+        &self.job_staging
+    }
+
     pub async fn init(&self) -> Result<()> {
         self.cluster_state.init().await?;
 
@@ -68,7 +108,12 @@ impl ExecutorManager {
 
     /// Bind the ready to running tasks from [`active_jobs`] with available executors.
     ///
-    /// If `executors` is provided, only bind slots from the specified executor IDs
+    /// Tasks of a job are only bound to the executors of the pools of its tenant, see
+    /// [`ExecutorPools`](crate::executor_pools::ExecutorPools). Within a pool, tasks of a
+    /// job are bound to executors with the version the job is pinned to first. Depending
+    /// on the [`ExecutorVersionPolicy`], remaining tasks may then be bound to executors of
+    /// other versions. The jobs not running with a reservation of slots leave the
+    /// reserved slots free, see [`SlotReservations`].
     pub async fn bind_schedulable_tasks(
         &self,
         active_jobs: Arc<HashMap<String, JobInfoCache>>,
@@ -77,26 +122,236 @@ impl ExecutorManager {
             warn!("There's no active jobs for binding tasks");
             return Ok(vec![]);
         }
-        let alive_executors = self.get_alive_executors();
+        let mut alive_executors = self.get_alive_executors();
         if alive_executors.is_empty() {
             warn!("There's no alive executors for binding tasks");
             return Ok(vec![]);
         }
-        self.cluster_state
-            .bind_schedulable_tasks(
-                self.config.task_distribution,
-                active_jobs,
-                Some(alive_executors),
-            )
+        alive_executors.retain(|executor_id| !self.is_disk_full(executor_id));
+        if alive_executors.is_empty() {
+            warn!("The job data of all the alive executors fills their disk, not binding tasks");
+            return Ok(vec![]);
+        }
+
+        let mut executors_by_pool: HashMap<String, HashMap<String, HashSet<String>>> =
+            HashMap::new();
+        for executor_id in alive_executors.iter() {
+            let (pool, version) = self
+                .get_executor_metadata(executor_id)
+                .await
+                .map(|metadata| (metadata.pool, metadata.version))
+                .unwrap_or_default();
+            executors_by_pool
+                .entry(pool)
+                .or_default()
+                .entry(version)
+                .or_default()
+                .insert(executor_id.clone());
+        }
+        self.release_job_versions(&active_jobs);
+        self.job_versions.retain(|job_id, version| {
+            let alive = executors_by_pool
+                .values()
+                .any(|executors_by_version| executors_by_version.contains_key(version));
+            if !alive {
+                // Don't block the job forever, its shuffle data on executors of the
+                // pinned version is lost anyway
+                info!(
+                    "No alive executor with version {} left for job {}, releasing its version pin",
+                    version, job_id
+                );
+            }
+            alive
+        });
+
+        let job_policy = self.job_scheduling_policy(&active_jobs).await;
+        let mut bound_tasks = vec![];
+        for (pool, executors_by_version) in executors_by_pool {
+            let jobs = self.jobs_for_executor_pool(&active_jobs, &pool).await;
+            if jobs.is_empty() {
+                continue;
+            }
+            bound_tasks.extend(
+                self.bind_pool_tasks(&job_policy, jobs, executors_by_version)
+                    .await?,
+            );
+        }
+
+        Ok(bound_tasks)
+    }
+
+    /// Bind the ready to running tasks from [`active_jobs`] with the available executors
+    /// of a pool, grouped by their version
+    async fn bind_pool_tasks(
+        &self,
+        job_policy: &dyn JobSchedulingPolicy,
+        active_jobs: Arc<HashMap<String, JobInfoCache>>,
+        executors_by_version: HashMap<String, HashSet<String>>,
+    ) -> Result<Vec<BoundTask>> {
+        let pool_executors: HashSet<String> =
+            executors_by_version.values().flatten().cloned().collect();
+
+        // Larger groups first, so that new jobs are pinned to the majority version
+        let mut groups: Vec<_> = executors_by_version.into_iter().collect();
+        groups.sort_by(|(v1, e1), (v2, e2)| e2.len().cmp(&e1.len()).then(v1.cmp(v2)));
+        let num_groups = groups.len();
+
+        let mut bound_tasks = vec![];
+        for (version, executors) in groups {
+            let jobs = self.jobs_for_executor_version(&active_jobs, &version);
+            if jobs.is_empty() {
+                continue;
+            }
+            let tasks = self
+                .cluster_state
+                .bind_schedulable_tasks(
+                    self.config.task_distribution,
+                    job_policy,
+                    self.config.speculation_multiplier,
+                    jobs,
+                    Some(executors),
+                )
+                .await?;
+            self.pin_job_versions(&tasks, &version);
+            bound_tasks.extend(tasks);
+        }
+
+        if num_groups > 1
+            && self.config.executor_version_policy == ExecutorVersionPolicy::Lenient
+        {
+            let tasks = self
+                .cluster_state
+                .bind_schedulable_tasks(
+                    self.config.task_distribution,
+                    job_policy,
+                    self.config.speculation_multiplier,
+                    active_jobs,
+                    Some(pool_executors),
+                )
+                .await?;
+            if !tasks.is_empty() {
+                debug!(
+                    "Bound {} tasks to executors with a different version than their job",
+                    tasks.len()
+                );
+            }
+            bound_tasks.extend(tasks);
+        }
+
+        Ok(bound_tasks)
+    }
+
+    /// The policy of the scheduler deciding which jobs the tasks bound to free slots are
+    /// taken from, holding back the slots reserved for the jobs running with a
+    /// reservation from the other jobs
+    pub(crate) async fn job_scheduling_policy(
+        &self,
+        active_jobs: &HashMap<String, JobInfoCache>,
+    ) -> ReservedSlotsScheduling<'_> {
+        let policy = self.config.job_scheduling_policy.as_ref();
+        if self.slot_reservations.is_empty() {
+            return ReservedSlotsScheduling::unreserved(policy);
+        }
+        let mut task_slots = 0;
+        for executor_id in self.get_alive_executors() {
+            if let Ok(metadata) = self.get_executor_metadata(&executor_id).await {
+                task_slots += metadata.specification.task_slots as usize;
+            }
+        }
+        self.slot_reservations
+            .scheduling(policy, active_jobs, task_slots)
             .await
     }
 
+    /// Returns the jobs from [`active_jobs`] which are allowed to run on executors of the
+    /// given pool, i.e. the jobs of its tenants and, if the pool lends its slots while
+    /// none of those has tasks waiting for a slot, the jobs of the other tenants.
+    pub(crate) async fn jobs_for_executor_pool(
+        &self,
+        active_jobs: &Arc<HashMap<String, JobInfoCache>>,
+        pool: &str,
+    ) -> Arc<HashMap<String, JobInfoCache>> {
+        let pools = &self.config.executor_pools;
+        if pools.pools().is_empty() {
+            return active_jobs.clone();
+        }
+
+        let pool_jobs: HashMap<String, JobInfoCache> = active_jobs
+            .iter()
+            .filter(|(_, job_info)| pools.admits(pool, job_info.tenant.as_deref()))
+            .map(|(job_id, job_info)| (job_id.clone(), job_info.clone()))
+            .collect();
+        if pools.lends_when_idle(pool) {
+            let mut idle = true;
+            for job_info in pool_jobs.values() {
+                if job_info.execution_graph.read().await.available_tasks() > 0 {
+                    idle = false;
+                    break;
+                }
+            }
+            if idle {
+                return active_jobs.clone();
+            }
+        }
+        Arc::new(pool_jobs)
+    }
+
+    /// Returns the jobs from [`active_jobs`] which are allowed to run on executors of the
+    /// given version, i.e. jobs pinned to this version and jobs not pinned yet.
+    pub(crate) fn jobs_for_executor_version(
+        &self,
+        active_jobs: &HashMap<String, JobInfoCache>,
+        version: &str,
+    ) -> Arc<HashMap<String, JobInfoCache>> {
+        Arc::new(
+            active_jobs
+                .iter()
+                .filter(|(job_id, _)| {
+                    self.job_versions
+                        .get(*job_id)
+                        .map(|pinned| pinned.value() == version)
+                        .unwrap_or(true)
+                })
+                .map(|(job_id, job_info)| (job_id.clone(), job_info.clone()))
+                .collect(),
+        )
+    }
+
+    /// Pins the jobs of the bound tasks to the given executor version, if not pinned yet.
+    pub(crate) fn pin_job_versions(&self, bound_tasks: &[BoundTask], version: &str) {
+        for (_, task) in bound_tasks {
+            self.job_versions
+                .entry(task.partition.job_id.clone())
+                .or_insert_with(|| {
+                    debug!(
+                        "Pin job {} to executor version {}",
+                        task.partition.job_id, version
+                    );
+                    version.to_owned()
+                });
+        }
+    }
+
+    /// Releases the version pins of jobs which are no longer active.
+    pub(crate) fn release_job_versions(
+        &self,
+        active_jobs: &HashMap<String, JobInfoCache>,
+    ) {
+        self.job_versions
+            .retain(|job_id, _| active_jobs.contains_key(job_id));
+    }
+
     /// Returned reserved task slots to the pool of available slots. This operation is atomic
     /// so either the entire pool of reserved task slots it returned or none are.
     pub async fn unbind_tasks(&self, executor_slots: Vec<ExecutorSlot>) -> Result<()> {
         self.cluster_state.unbind_tasks(executor_slots).await
     }
 
+    /// Subscribe to changes of the cluster topology and available task slots
+    pub async fn cluster_state_events(&self) -> Result<ClusterStateEventStream> {
+        self.cluster_state.cluster_state_events().await
+    }
+
     /// Send rpc to Executors to cancel the running tasks
     pub async fn cancel_running_tasks(&self, tasks: Vec<RunningTaskInfo>) -> Result<()> {
         let mut tasks_to_cancel: HashMap<String, Vec<protobuf::RunningTaskInfo>> =
@@ -166,23 +421,24 @@ impl ExecutorManager {
         });
     }
 
-    /// Send rpc to Executors to clean up the job data
+    /// Send rpc to Executors to clean up the job data, along with the output of its
+    /// verification tasks, and delete its staging prefixes in object stores
     async fn clean_up_job_data_inner(&self, job_id: String) {
+        self.job_staging.remove_job(&job_id).await;
         let alive_executors = self.get_alive_executors();
         for executor in alive_executors {
-            let job_id_clone = job_id.to_owned();
+            let job_ids = [job_id.to_owned(), verification_job_id(&job_id)];
             if let Ok(mut client) = self.get_client(&executor).await {
                 tokio::spawn(async move {
-                    if let Err(err) = client
-                        .remove_job_data(RemoveJobDataParams {
-                            job_id: job_id_clone,
-                        })
-                        .await
-                    {
-                        warn!(
-                            "Failed to call remove_job_data on Executor {} due to {:?}",
-                            executor, err
-                        )
+                    for job_id in job_ids {
+                        if let Err(err) =
+                            client.remove_job_data(RemoveJobDataParams { job_id }).await
+                        {
+                            warn!(
+                                "Failed to call remove_job_data on Executor {} due to {:?}",
+                                executor, err
+                            )
+                        }
                     }
                 });
             } else {
@@ -191,6 +447,75 @@ impl ExecutorManager {
         }
     }
 
+    /// Send rpc to an Executor to remove the shuffle output of task attempts which lost
+    /// against another attempt of the same task
+    pub fn remove_task_outputs(
+        &self,
+        executor_id: String,
+        job_id: String,
+        paths: Vec<String>,
+    ) {
+        let executor_manager = self.clone();
+        tokio::spawn(async move {
+            if let Ok(mut client) = executor_manager.get_client(&executor_id).await {
+                if let Err(err) = client
+                    .remove_task_outputs(RemoveTaskOutputsParams { job_id, paths })
+                    .await
+                {
+                    warn!(
+                        "Failed to call remove_task_outputs on Executor {} due to {:?}",
+                        executor_id, err
+                    )
+                }
+            } else {
+                warn!("Failed to get client for Executor {}", executor_id)
+            }
+        });
+    }
+
+    /// Fetch the captured logs of a task from the executor it was scheduled on. Returns
+    /// None if the executor holds no logs for the task
+    pub async fn get_task_logs(
+        &self,
+        executor_id: &str,
+        job_id: &str,
+        task_id: usize,
+    ) -> Result<Option<Vec<String>>> {
+        let mut client = self.get_client(executor_id).await?;
+        let result = client
+            .get_task_logs(GetTaskLogsParams {
+                job_id: job_id.to_owned(),
+                task_id: task_id as u32,
+            })
+            .await
+            .map_err(|e| {
+                BallistaError::Internal(format!(
+                    "Failed to get the logs of task {task_id} from Executor {executor_id}: {}",
+                    e.message()
+                ))
+            })?
+            .into_inner();
+
+        Ok(result.found.then_some(result.lines))
+    }
+
+    /// Profile an executor through its `GetProfile` rpc and return the encoded profile
+    pub async fn get_executor_profile(
+        &self,
+        executor_id: &str,
+        params: GetProfileParams,
+    ) -> Result<Vec<u8>> {
+        let mut client = self.get_client(executor_id).await?;
+        let response = client.get_profile(params).await.map_err(|e| {
+            BallistaError::Internal(format!(
+                "Failed to profile Executor {executor_id}: {}",
+                e.message()
+            ))
+        })?;
+
+        Ok(response.into_inner().profile)
+    }
+
     /// Get a list of all executors along with the timestamp of their last recorded heartbeat
     pub async fn get_executor_state(&self) -> Result<Vec<(ExecutorMetadata, Duration)>> {
         let heartbeat_timestamps: Vec<(String, u64)> = self
@@ -212,6 +537,39 @@ impl ExecutorManager {
         Ok(state)
     }
 
+    /// The utilization of the alive executors as of their latest heartbeat, ordered by
+    /// executor ID
+    pub async fn executor_utilization(&self) -> Vec<ExecutorUtilization> {
+        let mut executors = vec![];
+        for executor_id in self.get_alive_executors() {
+            let Some(heartbeat) = self.cluster_state.get_executor_heartbeat(&executor_id)
+            else {
+                continue;
+            };
+            match self.get_executor_metadata(&executor_id).await {
+                Ok(metadata) => executors.push(ExecutorUtilization::new(
+                    &executor_id,
+                    metadata.specification.task_slots,
+                    &heartbeat.metrics,
+                )),
+                Err(e) => warn!("Failed to get metadata of executor {executor_id}: {e}"),
+            }
+        }
+        executors.sort_by(|a, b| a.executor_id.cmp(&b.executor_id));
+        executors
+    }
+
+    /// The host of each executor with a heartbeat, by executor ID
+    pub(crate) async fn executor_hosts(&self) -> HashMap<String, String> {
+        let mut hosts = HashMap::new();
+        for executor_id in self.cluster_state.executor_heartbeats().into_keys() {
+            if let Ok(metadata) = self.get_executor_metadata(&executor_id).await {
+                hosts.insert(executor_id, metadata.host);
+            }
+        }
+        hosts
+    }
+
     /// Get executor metadata for the provided executor ID. Returns an error if the executor does not exist
     pub async fn get_executor_metadata(
         &self,
@@ -220,6 +578,34 @@ impl ExecutorManager {
         self.cluster_state.get_executor_metadata(executor_id).await
     }
 
+    /// Check the token an executor presents to register, or to poll for work with
+    /// pull-based task scheduling
+    pub fn validate_registration_token(
+        &self,
+        executor_id: &str,
+        token: &str,
+    ) -> Result<()> {
+        self.registration_tokens.validate(executor_id, token)
+    }
+
+    /// Check the token a registered executor presents with its other requests to the
+    /// scheduler
+    pub fn authenticate_executor(&self, executor_id: &str, token: &str) -> Result<()> {
+        self.registration_tokens.authenticate(executor_id, token)
+    }
+
+    /// Replace the tokens executors present to register, returning the IDs of the
+    /// executors which registered with a token which is no longer accepted. They are
+    /// to be removed from the cluster
+    pub fn rotate_registration_tokens(&self, tokens: Vec<String>) -> Result<Vec<String>> {
+        let revoked = self.registration_tokens.rotate(tokens)?;
+        info!(
+            "Rotated executor registration tokens, revoking executors {:?}",
+            revoked
+        );
+        Ok(revoked)
+    }
+
     /// It's only used for pull-based task scheduling.
     ///
     /// For push-based one, we should use [`register_executor`], instead.
@@ -242,7 +628,13 @@ impl ExecutorManager {
             metadata.id, specification.total_task_slots
         );
 
-        ExecutorManager::test_connectivity(&metadata).await?;
+        if !self.config.executor_pools.is_known(&metadata.pool) {
+            return Err(BallistaError::General(format!(
+                "Executor {} joins unknown executor pool {}",
+                metadata.id, metadata.pool
+            )));
+        }
+        ExecutorManager::test_connectivity(&metadata, &self.config.grpc_security).await?;
 
         self.cluster_state
             .register_executor(metadata, specification)
@@ -258,10 +650,19 @@ impl ExecutorManager {
         reason: Option<String>,
     ) -> Result<()> {
         info!("Removing executor {}: {:?}", executor_id, reason);
+        self.registration_tokens.remove_executor(executor_id);
+        self.telemetry.remove_executor(executor_id);
         self.cluster_state.remove_executor(executor_id).await
     }
 
-    pub async fn stop_executor(&self, executor_id: &str, stop_reason: String) {
+    /// Stop the executor, right away if `force` is set, otherwise once it has drained
+    /// its running tasks
+    pub async fn stop_executor(
+        &self,
+        executor_id: &str,
+        stop_reason: String,
+        force: bool,
+    ) {
         let executor_id = executor_id.to_string();
         match self.get_client(&executor_id).await {
             Ok(mut client) => {
@@ -270,7 +671,7 @@ impl ExecutorManager {
                         .stop_executor(StopExecutorParams {
                             executor_id: executor_id.to_string(),
                             reason: stop_reason,
-                            force: true,
+                            force,
                         })
                         .await
                     {
@@ -304,7 +705,7 @@ impl ExecutorManager {
             })
             .await
             .map_err(|e| {
-                BallistaError::Internal(format!(
+                BallistaError::ExecutorUnreachable(format!(
                     "Failed to connect to executor {}: {:?}",
                     executor_id, e
                 ))
@@ -317,6 +718,11 @@ impl ExecutorManager {
         &self,
         heartbeat: ExecutorHeartbeat,
     ) -> Result<()> {
+        self.telemetry.record(
+            &heartbeat.executor_id,
+            heartbeat.timestamp,
+            &heartbeat.metrics,
+        );
         self.cluster_state
             .save_executor_heartbeat(heartbeat.clone())
             .await?;
@@ -337,6 +743,39 @@ impl ExecutorManager {
             })
     }
 
+    /// Whether the executor is being decommissioned, draining its running tasks while
+    /// no new tasks are bound to it
+    #[cfg(feature = "rest-api")]
+    pub(crate) fn is_draining_executor(&self, executor_id: &str) -> bool {
+        self.cluster_state
+            .get_executor_heartbeat(executor_id)
+            .is_some_and(|heartbeat| {
+                matches!(
+                    heartbeat
+                        .status
+                        .as_ref()
+                        .and_then(|status| status.status.as_ref()),
+                    Some(executor_status::Status::Terminating(_))
+                )
+            })
+    }
+
+    /// Whether the job data of an executor fills the disk it is allowed, as of its last
+    /// heartbeat. Tasks aren't bound to such executors until job data is removed
+    pub(crate) fn is_disk_full(&self, executor_id: &str) -> bool {
+        self.cluster_state
+            .get_executor_heartbeat(executor_id)
+            .is_some_and(|heartbeat| {
+                heartbeat.metrics.iter().any(|metric| {
+                    matches!(
+                        metric.metric,
+                        Some(executor_metric::Metric::ShuffleDiskUsage(usage))
+                            if usage.max_bytes > 0 && usage.used_bytes >= usage.max_bytes
+                    )
+                })
+            })
+    }
+
     /// Retrieve the set of all executor IDs where the executor has been observed in the last
     /// `last_seen_ts_threshold` seconds.
     pub(crate) fn get_alive_executors(&self) -> HashSet<String> {
@@ -360,6 +799,108 @@ impl ExecutorManager {
             .collect()
     }
 
+    /// The user defined functions of `functions` which aren't registered on all the
+    /// alive executors, with the executors missing them. Executors which don't advertise
+    /// their functions are assumed to have them all
+    pub(crate) async fn missing_functions(
+        &self,
+        functions: &BTreeSet<String>,
+    ) -> BTreeMap<String, Vec<String>> {
+        let mut missing: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for executor_id in self.get_alive_executors() {
+            let Ok(metadata) = self.get_executor_metadata(&executor_id).await else {
+                continue;
+            };
+            let Some(executor_functions) = metadata.functions else {
+                continue;
+            };
+            for function in functions {
+                if !executor_functions.contains(function) {
+                    missing
+                        .entry(function.clone())
+                        .or_default()
+                        .push(executor_id.clone());
+                }
+            }
+        }
+        for executors in missing.values_mut() {
+            executors.sort();
+        }
+        missing
+    }
+
+    /// The codec, `preferred` if possible, which the shuffle partitions are compressed
+    /// with for all the alive executors to read them. Executors which don't advertise
+    /// their shuffle formats are assumed to read them all
+    pub(crate) async fn readable_shuffle_compression(
+        &self,
+        preferred: ShuffleCompression,
+    ) -> ShuffleCompression {
+        let mut formats = vec![];
+        for executor_id in self.get_alive_executors() {
+            let Ok(metadata) = self.get_executor_metadata(&executor_id).await else {
+                continue;
+            };
+            formats.extend(metadata.shuffle_formats);
+        }
+        [
+            preferred,
+            ShuffleCompression::Lz4,
+            ShuffleCompression::Zstd,
+            ShuffleCompression::Uncompressed,
+        ]
+        .into_iter()
+        .find(|compression| {
+            formats
+                .iter()
+                .all(|formats| formats.compressions.contains(compression))
+        })
+        .unwrap_or(preferred)
+    }
+
+    /// Roll out operational settings to a percentage of the executors, which apply them
+    /// when they next heartbeat or poll for tasks
+    pub fn roll_out_executor_settings(
+        &self,
+        settings: BTreeMap<String, String>,
+        percentage: u8,
+    ) -> Result<ExecutorSettingsRollout> {
+        let mut settings_rollout = self.settings_rollout.write();
+        let rollout = ExecutorSettingsRollout::new(
+            settings_rollout.as_ref(),
+            settings,
+            percentage,
+        )?;
+        info!(
+            "Rolling out version {} of the executor settings to {}% of the executors: {:?}",
+            rollout.version, rollout.percentage, rollout.settings
+        );
+        *settings_rollout = Some(rollout.clone());
+        Ok(rollout)
+    }
+
+    /// The operational settings being rolled out to the executors, if any
+    /// Resource usage the executors report with their heartbeats
+    pub fn telemetry(&self) -> &ExecutorTelemetry {
+        &self.telemetry
+    }
+
+    pub fn executor_settings_rollout(&self) -> Option<ExecutorSettingsRollout> {
+        self.settings_rollout.read().clone()
+    }
+
+    /// The operational settings to push to an executor, if it is in their rollout
+    pub(crate) fn executor_settings(
+        &self,
+        executor_id: &str,
+    ) -> Option<protobuf::ExecutorSettings> {
+        self.settings_rollout
+            .read()
+            .as_ref()
+            .filter(|rollout| rollout.includes(executor_id))
+            .map(ExecutorSettingsRollout::to_proto)
+    }
+
     /// Return a list of expired executors
     pub(crate) fn get_expired_executors(&self) -> Vec<ExecutorHeartbeat> {
         // Threshold for last heartbeat from Active executor before marking dead
@@ -392,7 +933,10 @@ impl ExecutorManager {
             .collect::<Vec<_>>()
     }
 
-    async fn get_client(&self, executor_id: &str) -> Result<ExecutorGrpcClient<Channel>> {
+    async fn get_client(
+        &self,
+        executor_id: &str,
+    ) -> Result<ExecutorGrpcClient<GrpcChannel>> {
         let client = self.clients.get(executor_id).map(|value| value.clone());
 
         if let Some(client) = client {
@@ -403,8 +947,21 @@ impl ExecutorManager {
                 "http://{}:{}",
                 executor_metadata.host, executor_metadata.grpc_port
             );
-            let connection = create_grpc_client_connection(executor_url).await?;
-            let client = ExecutorGrpcClient::new(connection);
+            let connection =
+                create_grpc_client_connection(executor_url, &self.config.grpc_security)
+                    .await
+                    .map_err(|e| {
+                        BallistaError::ExecutorUnreachable(format!(
+                            "Failed to connect to executor {executor_id}: {e}"
+                        ))
+                    })?;
+            let client = ExecutorGrpcClient::new(connection)
+                .max_encoding_message_size(
+                    self.config.grpc_client_max_encoding_message_size as usize,
+                )
+                .max_decoding_message_size(
+                    self.config.grpc_client_max_decoding_message_size as usize,
+                );
 
             {
                 self.clients.insert(executor_id.to_owned(), client.clone());
@@ -414,13 +971,16 @@ impl ExecutorManager {
     }
 
     #[cfg(not(test))]
-    async fn test_connectivity(metadata: &ExecutorMetadata) -> Result<()> {
+    async fn test_connectivity(
+        metadata: &ExecutorMetadata,
+        security: &GrpcSecurityConfig,
+    ) -> Result<()> {
         let executor_url = format!("http://{}:{}", metadata.host, metadata.grpc_port);
         debug!("Connecting to executor {:?}", executor_url);
-        let _ = protobuf::executor_grpc_client::ExecutorGrpcClient::connect(executor_url)
+        let _ = create_grpc_client_connection(executor_url, security)
             .await
             .map_err(|e| {
-                BallistaError::Internal(format!(
+                BallistaError::ExecutorUnreachable(format!(
                     "Failed to register executor at {}:{}, could not connect: {:?}",
                     metadata.host, metadata.grpc_port, e
                 ))
@@ -429,7 +989,10 @@ impl ExecutorManager {
     }
 
     #[cfg(test)]
-    async fn test_connectivity(_metadata: &ExecutorMetadata) -> Result<()> {
+    async fn test_connectivity(
+        _metadata: &ExecutorMetadata,
+        _security: &GrpcSecurityConfig,
+    ) -> Result<()> {
         Ok(())
     }
 }
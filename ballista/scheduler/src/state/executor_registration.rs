@@ -0,0 +1,197 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Tokens executors present to register with the scheduler, so that processes which
+//! merely speak the protocol can't join the cluster and receive tasks.
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::security::constant_time_eq;
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use std::fmt;
+
+/// Registration tokens accepted by the scheduler. A token may be shared by all the
+/// executors or given to a single one. Several tokens are accepted at once, so that
+/// they can be rotated: the new token is accepted along with the old one until all
+/// the executors present it, and executors which registered with a token which is
+/// no longer accepted are revoked.
+#[derive(Default)]
+pub struct ExecutorRegistrationTokens {
+    /// Accepted tokens, any executor may register when there are none
+    tokens: RwLock<Vec<String>>,
+    /// Token each registered executor presented, keyed by executor ID
+    executor_tokens: DashMap<String, String>,
+}
+
+impl ExecutorRegistrationTokens {
+    pub fn new(tokens: Vec<String>) -> Self {
+        Self {
+            tokens: RwLock::new(tokens),
+            executor_tokens: Default::default(),
+        }
+    }
+
+    /// Whether executors must present a token to register
+    pub fn is_required(&self) -> bool {
+        !self.tokens.read().is_empty()
+    }
+
+    /// Check the token an executor presents to register or to poll for work, and
+    /// remember it so that the executor is revoked along with the token
+    pub fn validate(&self, executor_id: &str, token: &str) -> Result<()> {
+        let tokens = self.tokens.read();
+        if tokens.is_empty() {
+            return Ok(());
+        }
+        if token.is_empty() {
+            return Err(BallistaError::General(format!(
+                "Executor {executor_id} presented no registration token"
+            )));
+        }
+        if !tokens
+            .iter()
+            .any(|accepted| constant_time_eq(accepted.as_bytes(), token.as_bytes()))
+        {
+            return Err(BallistaError::General(format!(
+                "Executor {executor_id} presented an invalid registration token"
+            )));
+        }
+        self.executor_tokens
+            .insert(executor_id.to_owned(), token.to_owned());
+        Ok(())
+    }
+
+    /// Check the token a registered executor presents with its other requests, which
+    /// must be the token it registered with and still be accepted. Executors registered
+    /// with a previous scheduler are accepted with any accepted token
+    pub fn authenticate(&self, executor_id: &str, token: &str) -> Result<()> {
+        if !self.is_required() {
+            return Ok(());
+        }
+        let registered = self
+            .executor_tokens
+            .get(executor_id)
+            .map(|registered| constant_time_eq(registered.as_bytes(), token.as_bytes()));
+        if registered == Some(false) {
+            return Err(BallistaError::General(format!(
+                "Executor {executor_id} presented another token than its registration token"
+            )));
+        }
+        self.validate(executor_id, token)
+    }
+
+    /// Replace the accepted tokens, returning the IDs of the executors which
+    /// registered with a token which is no longer accepted
+    pub fn rotate(&self, tokens: Vec<String>) -> Result<Vec<String>> {
+        if tokens.iter().any(|token| token.trim().is_empty()) {
+            return Err(BallistaError::General(
+                "Registration tokens must not be empty".to_owned(),
+            ));
+        }
+
+        let mut accepted = self.tokens.write();
+        *accepted = tokens;
+        if accepted.is_empty() {
+            self.executor_tokens.clear();
+            return Ok(vec![]);
+        }
+        let mut revoked = vec![];
+        self.executor_tokens.retain(|executor_id, token| {
+            let valid = accepted
+                .iter()
+                .any(|accepted| constant_time_eq(accepted.as_bytes(), token.as_bytes()));
+            if !valid {
+                revoked.push(executor_id.clone());
+            }
+            valid
+        });
+        revoked.sort();
+        Ok(revoked)
+    }
+
+    /// Forget the token of an executor removed from the cluster
+    pub fn remove_executor(&self, executor_id: &str) {
+        self.executor_tokens.remove(executor_id);
+    }
+}
+
+impl fmt::Debug for ExecutorRegistrationTokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecutorRegistrationTokens")
+            .field("tokens", &self.tokens.read().len())
+            .field("executors", &self.executor_tokens.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registration_tokens() -> Result<()> {
+        let tokens = ExecutorRegistrationTokens::default();
+        assert!(!tokens.is_required());
+        tokens.validate("executor-1", "")?;
+
+        let tokens = ExecutorRegistrationTokens::new(vec!["old".to_owned()]);
+        assert!(tokens.is_required());
+        tokens.validate("executor-1", "old")?;
+        tokens.validate("executor-2", "old")?;
+        assert!(tokens.validate("executor-3", "").is_err());
+        assert!(tokens.validate("executor-3", "ol").is_err());
+
+        // Both tokens are accepted while executors switch to the new one
+        let revoked = tokens.rotate(vec!["old".to_owned(), "new".to_owned()])?;
+        assert!(revoked.is_empty());
+        tokens.validate("executor-1", "new")?;
+
+        // Executors still on the old token are revoked with it
+        let revoked = tokens.rotate(vec!["new".to_owned()])?;
+        assert_eq!(vec!["executor-2".to_owned()], revoked);
+        assert!(tokens.validate("executor-2", "old").is_err());
+        tokens.validate("executor-1", "new")?;
+
+        tokens.remove_executor("executor-1");
+        assert!(tokens.rotate(vec!["newer".to_owned()])?.is_empty());
+        assert!(tokens.rotate(vec![" ".to_owned()]).is_err());
+        assert!(!format!("{tokens:?}").contains("newer"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_authenticate_registered_executors() -> Result<()> {
+        let tokens = ExecutorRegistrationTokens::default();
+        tokens.authenticate("executor-1", "")?;
+
+        let tokens = ExecutorRegistrationTokens::new(vec![
+            "token-1".to_owned(),
+            "token-2".to_owned(),
+        ]);
+        tokens.validate("executor-1", "token-1")?;
+        tokens.authenticate("executor-1", "token-1")?;
+        assert!(tokens.authenticate("executor-1", "").is_err());
+        assert!(tokens.authenticate("executor-1", "forged").is_err());
+        // an accepted token is not enough to act as another executor
+        assert!(tokens.authenticate("executor-1", "token-2").is_err());
+
+        // executors are re-checked once the tokens are rotated
+        tokens.rotate(vec!["token-2".to_owned()])?;
+        assert!(tokens.authenticate("executor-1", "token-1").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Staged rollout of the operational settings of the executors, e.g. their log level,
+//! which the scheduler pushes to them with the responses to their heartbeats and polls.
+
+use ballista_core::config::{
+    EXECUTOR_SETTINGS, EXECUTOR_TASK_LOG_MAX_LINES, EXECUTOR_TASK_PLAN_CACHE_SIZE,
+};
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::{self, KeyValuePair};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Settings pushed to a percentage of the executors, which is widened in stages by
+/// rolling out the same settings again
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ExecutorSettingsRollout {
+    /// Version of the settings, which executors apply once. It increases across
+    /// restarts of the scheduler
+    pub version: u64,
+    pub settings: BTreeMap<String, String>,
+    /// Percentage of the executors the settings are pushed to
+    pub percentage: u8,
+}
+
+impl ExecutorSettingsRollout {
+    /// Roll out `settings` to `percentage` of the executors. Rolling out the settings
+    /// of the `previous` rollout again keeps their version, and only changes the
+    /// executors they are pushed to
+    pub fn new(
+        previous: Option<&Self>,
+        settings: BTreeMap<String, String>,
+        percentage: u8,
+    ) -> Result<Self> {
+        if percentage > 100 {
+            return Err(BallistaError::General(format!(
+                "Invalid rollout percentage {percentage}, expected at most 100"
+            )));
+        }
+        for (key, value) in &settings {
+            if !EXECUTOR_SETTINGS.contains(&key.as_str()) {
+                return Err(BallistaError::General(format!(
+                    "Unknown executor setting `{key}`, expected one of {EXECUTOR_SETTINGS:?}"
+                )));
+            }
+            if (key == EXECUTOR_TASK_PLAN_CACHE_SIZE
+                || key == EXECUTOR_TASK_LOG_MAX_LINES)
+                && value.parse::<usize>().is_err()
+            {
+                return Err(BallistaError::General(format!(
+                    "Invalid value `{value}` of executor setting `{key}`, expected a number"
+                )));
+            }
+        }
+
+        let version = match previous {
+            Some(previous) if previous.settings == settings => previous.version,
+            _ => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_millis() as u64;
+                now.max(previous.map(|p| p.version + 1).unwrap_or_default())
+            }
+        };
+
+        Ok(Self {
+            version,
+            settings,
+            percentage,
+        })
+    }
+
+    /// Whether the settings are pushed to an executor. Executors are always in the same
+    /// bucket, so that they stay included as the rollout is widened
+    pub fn includes(&self, executor_id: &str) -> bool {
+        rollout_bucket(executor_id) < self.percentage
+    }
+
+    pub fn to_proto(&self) -> protobuf::ExecutorSettings {
+        protobuf::ExecutorSettings {
+            version: self.version,
+            settings: self
+                .settings
+                .iter()
+                .map(|(key, value)| KeyValuePair {
+                    key: key.clone(),
+                    value: value.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Bucket of an executor in 0..100, from FNV-1a, which unlike the hasher of the
+/// standard library is stable across builds
+fn rollout_bucket(executor_id: &str) -> u8 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in executor_id.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    (hash % 100) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ballista_core::config::EXECUTOR_LOG_LEVEL;
+
+    fn settings(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_staged_rollout() -> Result<()> {
+        let debug = settings(&[(EXECUTOR_LOG_LEVEL, "debug")]);
+        let executors: Vec<String> = (0..1000).map(|i| format!("executor-{i}")).collect();
+
+        let canary = ExecutorSettingsRollout::new(None, debug.clone(), 10)?;
+        let included = executors.iter().filter(|id| canary.includes(id)).count();
+        assert!(
+            (50..150).contains(&included),
+            "{included} executors included"
+        );
+
+        // Widening the rollout keeps the version and the executors already included
+        let widened = ExecutorSettingsRollout::new(Some(&canary), debug.clone(), 50)?;
+        assert_eq!(canary.version, widened.version);
+        assert!(executors
+            .iter()
+            .all(|id| !canary.includes(id) || widened.includes(id)));
+
+        let all = ExecutorSettingsRollout::new(Some(&widened), debug, 100)?;
+        assert!(executors.iter().all(|id| all.includes(id)));
+
+        // New settings get a new version
+        let info = settings(&[(EXECUTOR_LOG_LEVEL, "info")]);
+        let reverted = ExecutorSettingsRollout::new(Some(&all), info, 100)?;
+        assert!(reverted.version > all.version);
+        assert_eq!(
+            vec![KeyValuePair {
+                key: EXECUTOR_LOG_LEVEL.to_owned(),
+                value: "info".to_owned(),
+            }],
+            reverted.to_proto().settings
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_rollout() {
+        let debug = settings(&[(EXECUTOR_LOG_LEVEL, "debug")]);
+        assert!(ExecutorSettingsRollout::new(None, debug, 101).is_err());
+        let unknown = settings(&[("executor.unknown", "1")]);
+        assert!(ExecutorSettingsRollout::new(None, unknown, 100).is_err());
+        let invalid = settings(&[(EXECUTOR_TASK_PLAN_CACHE_SIZE, "many")]);
+        assert!(ExecutorSettingsRollout::new(None, invalid, 100).is_err());
+    }
+}
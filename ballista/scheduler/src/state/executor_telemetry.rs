@@ -0,0 +1,467 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Utilization of the hosts of the executors, i.e. their CPU, memory, disk and network,
+//! which the executors report with their heartbeats. The scheduler keeps the latest
+//! samples of each executor, so that the recent load of the executors can be
+//! considered rather than their free task slots only, and aggregates the latest
+//! heartbeats into the utilization of the cluster, e.g. to drive an autoscaler.
+
+use std::collections::{HashMap, VecDeque};
+
+use ballista_core::serde::protobuf::{
+    self, executor_metric, DataCacheUsage, ExecutorMetric, ResourceUsage,
+    ShuffleDiskUsage, TaskCounts,
+};
+use dashmap::DashMap;
+
+/// Lowest headroom of an executor, so that executors reporting full utilization still
+/// get tasks when no other executor has free slots
+pub const MIN_HEADROOM: f64 = 0.05;
+
+/// The resource usage among the metrics of a heartbeat, if any
+pub fn resource_usage(metrics: &[ExecutorMetric]) -> Option<&ResourceUsage> {
+    metrics.iter().find_map(|metric| match &metric.metric {
+        Some(executor_metric::Metric::ResourceUsage(usage)) => Some(usage),
+        _ => None,
+    })
+}
+
+/// The usage of the data cache among the metrics of a heartbeat, if any
+pub fn data_cache_usage(metrics: &[ExecutorMetric]) -> Option<&DataCacheUsage> {
+    metrics.iter().find_map(|metric| match &metric.metric {
+        Some(executor_metric::Metric::DataCacheUsage(usage)) => Some(usage),
+        _ => None,
+    })
+}
+
+/// The shuffle disk usage among the metrics of a heartbeat, if any
+pub fn shuffle_disk_usage(metrics: &[ExecutorMetric]) -> Option<&ShuffleDiskUsage> {
+    metrics.iter().find_map(|metric| match &metric.metric {
+        Some(executor_metric::Metric::ShuffleDiskUsage(usage)) => Some(usage),
+        _ => None,
+    })
+}
+
+/// The task counts among the metrics of a heartbeat, if any
+pub fn task_counts(metrics: &[ExecutorMetric]) -> Option<&TaskCounts> {
+    metrics.iter().find_map(|metric| match &metric.metric {
+        Some(executor_metric::Metric::TaskCounts(counts)) => Some(counts),
+        _ => None,
+    })
+}
+
+/// Share of the resources of each executor left for more tasks, between [MIN_HEADROOM]
+/// and 1, from the latest resource usage of the executors: the idle CPU time, times
+/// the free memory, times the network throughput relative to the busiest executor,
+/// which weighs half as much
+pub fn headroom<'a>(
+    usages: impl IntoIterator<Item = (&'a str, &'a ResourceUsage)>,
+) -> HashMap<String, f64> {
+    let usages: Vec<_> = usages.into_iter().collect();
+    let network = |usage: &ResourceUsage| {
+        usage.network_received_bytes_per_second + usage.network_sent_bytes_per_second
+    };
+    let max_network = usages
+        .iter()
+        .map(|(_, usage)| network(usage))
+        .max()
+        .unwrap_or(0);
+
+    usages
+        .into_iter()
+        .map(|(executor_id, usage)| {
+            let cpu = 1.0 - usage.cpu_usage.clamp(0.0, 1.0);
+            let memory = if usage.total_memory_bytes > 0 {
+                1.0 - usage.used_memory_bytes as f64 / usage.total_memory_bytes as f64
+            } else {
+                1.0
+            };
+            let io = if max_network > 0 {
+                1.0 - 0.5 * network(usage) as f64 / max_network as f64
+            } else {
+                1.0
+            };
+            (
+                executor_id.to_owned(),
+                (cpu * memory * io).clamp(MIN_HEADROOM, 1.0),
+            )
+        })
+        .collect()
+}
+
+/// The resource usage an executor reported with a heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct ResourceSample {
+    /// Unix epoch-based timestamp in seconds of the heartbeat
+    pub timestamp: u64,
+    /// Fraction of the CPU time of the host which was busy, between 0 and 1
+    pub cpu_usage: f64,
+    pub used_memory_bytes: u64,
+    pub total_memory_bytes: u64,
+    pub used_disk_bytes: u64,
+    pub total_disk_bytes: u64,
+    pub network_received_bytes_per_second: u64,
+    pub network_sent_bytes_per_second: u64,
+}
+
+impl ResourceSample {
+    pub fn new(timestamp: u64, usage: &ResourceUsage) -> Self {
+        Self {
+            timestamp,
+            cpu_usage: usage.cpu_usage,
+            used_memory_bytes: usage.used_memory_bytes,
+            total_memory_bytes: usage.total_memory_bytes,
+            used_disk_bytes: usage.used_disk_bytes,
+            total_disk_bytes: usage.total_disk_bytes,
+            network_received_bytes_per_second: usage.network_received_bytes_per_second,
+            network_sent_bytes_per_second: usage.network_sent_bytes_per_second,
+        }
+    }
+}
+
+/// Utilization of an executor, as of its latest heartbeat
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct ExecutorUtilization {
+    pub executor_id: String,
+    pub task_slots: u32,
+    pub running_tasks: u32,
+    /// Tasks launched on the executor which wait for a task permit of their job
+    pub queued_tasks: u32,
+    /// Fraction of the CPU time of the host which was busy, between 0 and 1, None if
+    /// the executor doesn't report its resource usage
+    pub cpu_usage: Option<f64>,
+    pub used_memory_bytes: u64,
+    pub total_memory_bytes: u64,
+    pub shuffle_disk_used_bytes: u64,
+    /// 0 if the disk usage of the executor isn't bounded
+    pub shuffle_disk_max_bytes: u64,
+}
+
+impl ExecutorUtilization {
+    /// The utilization of an executor from the metrics of its latest heartbeat
+    pub fn new(executor_id: &str, task_slots: u32, metrics: &[ExecutorMetric]) -> Self {
+        let counts = task_counts(metrics).copied().unwrap_or_default();
+        let usage = resource_usage(metrics);
+        let disk_usage = shuffle_disk_usage(metrics).copied().unwrap_or_default();
+        Self {
+            executor_id: executor_id.to_owned(),
+            task_slots,
+            running_tasks: counts.running_tasks,
+            queued_tasks: counts.queued_tasks,
+            cpu_usage: usage.map(|usage| usage.cpu_usage),
+            used_memory_bytes: usage.map_or(0, |usage| usage.used_memory_bytes),
+            total_memory_bytes: usage.map_or(0, |usage| usage.total_memory_bytes),
+            shuffle_disk_used_bytes: disk_usage.used_bytes,
+            shuffle_disk_max_bytes: disk_usage.max_bytes,
+        }
+    }
+}
+
+/// Demand for and utilization of the resources of the cluster, as of the latest
+/// heartbeats of the alive executors
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct ClusterUtilization {
+    pub running_jobs: u32,
+    pub queued_jobs: u32,
+    /// Tasks of the running jobs which are ready to run, but not bound to an executor
+    /// yet
+    pub pending_tasks: u64,
+    pub task_slots: u32,
+    pub running_tasks: u32,
+    pub queued_tasks: u32,
+    /// Average CPU usage of the executors reporting it, between 0 and 1
+    pub cpu_usage: f64,
+    pub used_memory_bytes: u64,
+    pub total_memory_bytes: u64,
+    pub shuffle_disk_used_bytes: u64,
+    pub executors: Vec<ExecutorUtilization>,
+}
+
+impl ClusterUtilization {
+    pub fn new(
+        running_jobs: usize,
+        queued_jobs: usize,
+        pending_tasks: usize,
+        executors: Vec<ExecutorUtilization>,
+    ) -> Self {
+        let cpu_usages: Vec<f64> = executors.iter().filter_map(|e| e.cpu_usage).collect();
+        let cpu_usage = if cpu_usages.is_empty() {
+            0.0
+        } else {
+            cpu_usages.iter().sum::<f64>() / cpu_usages.len() as f64
+        };
+        Self {
+            running_jobs: running_jobs as u32,
+            queued_jobs: queued_jobs as u32,
+            pending_tasks: pending_tasks as u64,
+            task_slots: executors.iter().map(|e| e.task_slots).sum(),
+            running_tasks: executors.iter().map(|e| e.running_tasks).sum(),
+            queued_tasks: executors.iter().map(|e| e.queued_tasks).sum(),
+            cpu_usage,
+            used_memory_bytes: executors.iter().map(|e| e.used_memory_bytes).sum(),
+            total_memory_bytes: executors.iter().map(|e| e.total_memory_bytes).sum(),
+            shuffle_disk_used_bytes: executors
+                .iter()
+                .map(|e| e.shuffle_disk_used_bytes)
+                .sum(),
+            executors,
+        }
+    }
+}
+
+impl From<ClusterUtilization> for protobuf::ClusterUtilization {
+    fn from(utilization: ClusterUtilization) -> Self {
+        Self {
+            running_jobs: utilization.running_jobs,
+            queued_jobs: utilization.queued_jobs,
+            pending_tasks: utilization.pending_tasks,
+            task_slots: utilization.task_slots,
+            running_tasks: utilization.running_tasks,
+            queued_tasks: utilization.queued_tasks,
+            cpu_usage: utilization.cpu_usage,
+            used_memory_bytes: utilization.used_memory_bytes,
+            total_memory_bytes: utilization.total_memory_bytes,
+            shuffle_disk_used_bytes: utilization.shuffle_disk_used_bytes,
+            executors: utilization
+                .executors
+                .into_iter()
+                .map(|executor| protobuf::ExecutorUtilization {
+                    executor_id: executor.executor_id,
+                    task_slots: executor.task_slots,
+                    running_tasks: executor.running_tasks,
+                    queued_tasks: executor.queued_tasks,
+                    cpu_usage: executor.cpu_usage.unwrap_or_default(),
+                    used_memory_bytes: executor.used_memory_bytes,
+                    total_memory_bytes: executor.total_memory_bytes,
+                    shuffle_disk_used_bytes: executor.shuffle_disk_used_bytes,
+                    shuffle_disk_max_bytes: executor.shuffle_disk_max_bytes,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The latest resource usage samples of each executor
+#[derive(Debug, Default)]
+pub struct ExecutorTelemetry {
+    /// Maximum number of samples kept for each executor
+    history_size: usize,
+    /// Samples of each executor, oldest first, keyed by executor ID
+    samples: DashMap<String, VecDeque<ResourceSample>>,
+}
+
+impl ExecutorTelemetry {
+    pub fn new(history_size: usize) -> Self {
+        Self {
+            history_size,
+            samples: Default::default(),
+        }
+    }
+
+    /// Record the resource usage among the metrics of a heartbeat, if any
+    pub fn record(&self, executor_id: &str, timestamp: u64, metrics: &[ExecutorMetric]) {
+        if self.history_size == 0 {
+            return;
+        }
+        let Some(usage) = resource_usage(metrics) else {
+            return;
+        };
+
+        let mut samples = self.samples.entry(executor_id.to_owned()).or_default();
+        if samples.len() >= self.history_size {
+            samples.pop_front();
+        }
+        samples.push_back(ResourceSample::new(timestamp, usage));
+    }
+
+    /// The latest resource usage of the executor
+    pub fn latest(&self, executor_id: &str) -> Option<ResourceSample> {
+        self.samples
+            .get(executor_id)
+            .and_then(|samples| samples.back().copied())
+    }
+
+    /// The resource usage samples of the executor, oldest first
+    pub fn history(&self, executor_id: &str) -> Vec<ResourceSample> {
+        self.samples
+            .get(executor_id)
+            .map(|samples| samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn remove_executor(&self, executor_id: &str) {
+        self.samples.remove(executor_id);
+    }
+
+    /// Network bandwidth of the executors, as the median across the executors of the
+    /// peak throughput they sent or received in their samples. None until an executor
+    /// reported network traffic
+    pub fn network_bytes_per_second(&self) -> Option<u64> {
+        let mut peaks: Vec<u64> = self
+            .samples
+            .iter()
+            .filter_map(|samples| {
+                samples
+                    .iter()
+                    .map(|sample| {
+                        sample
+                            .network_received_bytes_per_second
+                            .max(sample.network_sent_bytes_per_second)
+                    })
+                    .max()
+            })
+            .filter(|peak| *peak > 0)
+            .collect();
+        if peaks.is_empty() {
+            return None;
+        }
+        peaks.sort_unstable();
+        Some(peaks[peaks.len() / 2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ballista_core::serde::protobuf::{
+        executor_metric, ExecutorMetric, ResourceUsage, ShuffleDiskUsage, TaskCounts,
+    };
+
+    use super::{
+        headroom, ClusterUtilization, ExecutorTelemetry, ExecutorUtilization,
+        MIN_HEADROOM,
+    };
+
+    fn metrics(cpu_usage: f64) -> Vec<ExecutorMetric> {
+        vec![
+            ExecutorMetric {
+                metric: Some(executor_metric::Metric::ShuffleDiskUsage(
+                    ShuffleDiskUsage::default(),
+                )),
+            },
+            ExecutorMetric {
+                metric: Some(executor_metric::Metric::ResourceUsage(ResourceUsage {
+                    cpu_usage,
+                    ..Default::default()
+                })),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_executor_telemetry() {
+        let telemetry = ExecutorTelemetry::new(2);
+        telemetry.record("executor-1", 1, &metrics(0.1));
+        telemetry.record("executor-1", 2, &metrics(0.2));
+        telemetry.record("executor-1", 3, &metrics(0.3));
+        // heartbeats of older executors carry no resource usage
+        telemetry.record("executor-2", 3, &[]);
+
+        let history = telemetry.history("executor-1");
+        assert_eq!(
+            vec![(2, 0.2), (3, 0.3)],
+            history
+                .iter()
+                .map(|sample| (sample.timestamp, sample.cpu_usage))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(3), telemetry.latest("executor-1").map(|s| s.timestamp));
+        assert_eq!(None, telemetry.latest("executor-2"));
+
+        telemetry.remove_executor("executor-1");
+        assert!(telemetry.history("executor-1").is_empty());
+    }
+
+    #[test]
+    fn test_network_bytes_per_second() {
+        let telemetry = ExecutorTelemetry::new(3);
+        assert_eq!(None, telemetry.network_bytes_per_second());
+
+        let network = |received, sent| {
+            vec![ExecutorMetric {
+                metric: Some(executor_metric::Metric::ResourceUsage(ResourceUsage {
+                    network_received_bytes_per_second: received,
+                    network_sent_bytes_per_second: sent,
+                    ..Default::default()
+                })),
+            }]
+        };
+        telemetry.record("executor-1", 1, &network(100, 0));
+        telemetry.record("executor-1", 2, &network(0, 300));
+        telemetry.record("executor-2", 1, &network(200, 50));
+        telemetry.record("executor-3", 1, &network(1000, 0));
+        telemetry.record("executor-4", 1, &network(0, 0));
+
+        // the median of the peaks of the executors which reported network traffic
+        assert_eq!(Some(300), telemetry.network_bytes_per_second());
+    }
+
+    #[test]
+    fn test_headroom() {
+        let usage =
+            |cpu_usage, used_memory_bytes, network_bytes_per_second| ResourceUsage {
+                cpu_usage,
+                used_memory_bytes,
+                total_memory_bytes: 100,
+                network_received_bytes_per_second: network_bytes_per_second,
+                ..Default::default()
+            };
+        let idle = usage(0.0, 0, 0);
+        let busy_cpu = usage(0.5, 0, 0);
+        let busy_memory_and_network = usage(0.0, 50, 1000);
+        let saturated = usage(1.0, 100, 0);
+        let headroom = headroom([
+            ("idle", &idle),
+            ("busy_cpu", &busy_cpu),
+            ("busy_memory_and_network", &busy_memory_and_network),
+            ("saturated", &saturated),
+        ]);
+
+        assert_eq!(1.0, headroom["idle"]);
+        assert_eq!(0.5, headroom["busy_cpu"]);
+        assert_eq!(0.25, headroom["busy_memory_and_network"]);
+        assert_eq!(MIN_HEADROOM, headroom["saturated"]);
+    }
+
+    #[test]
+    fn test_cluster_utilization() {
+        let mut busy_metrics = metrics(0.8);
+        busy_metrics.push(ExecutorMetric {
+            metric: Some(executor_metric::Metric::TaskCounts(TaskCounts {
+                running_tasks: 4,
+                queued_tasks: 2,
+            })),
+        });
+        let busy = ExecutorUtilization::new("busy", 4, &busy_metrics);
+        assert_eq!(Some(0.8), busy.cpu_usage);
+        assert_eq!((4, 2), (busy.running_tasks, busy.queued_tasks));
+
+        let idle = ExecutorUtilization::new("idle", 4, &metrics(0.2));
+        // executors which don't report their resource usage are left out of the
+        // average CPU usage
+        let unknown = ExecutorUtilization::new("unknown", 2, &[]);
+        assert_eq!(None, unknown.cpu_usage);
+
+        let utilization = ClusterUtilization::new(2, 1, 10, vec![busy, idle, unknown]);
+        assert_eq!(10, utilization.task_slots);
+        assert_eq!(4, utilization.running_tasks);
+        assert_eq!(2, utilization.queued_tasks);
+        assert_eq!(10, utilization.pending_tasks);
+        assert!((utilization.cpu_usage - 0.5).abs() < 1e-9);
+        assert_eq!(3, utilization.executors.len());
+    }
+}
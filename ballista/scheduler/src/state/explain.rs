@@ -0,0 +1,292 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! `EXPLAIN` of the distributed plan of a query.
+//!
+//! The physical plan shown by `EXPLAIN` is the plan of each stage the query would be
+//! run as, rather than the plan DataFusion would run in a single process. It is shown
+//! as indented text, or as a JSON document of the stages, their operators, partitioning
+//! and estimated statistics, meant for query analysis tools.
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use ballista_core::config::ExplainFormat;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::{ShuffleWriterExec, UnresolvedShuffleExec};
+use ballista_core::planner::DistributedPlanner;
+use datafusion::common::display::{PlanType, StringifiedPlan};
+use datafusion::common::stats::Precision;
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::logical_expr::{Explain, ToStringifiedPlan};
+use datafusion::physical_plan::explain::ExplainExec;
+use datafusion::physical_plan::{
+    displayable, ExecutionPlan, ExecutionPlanProperties, Partitioning,
+};
+use serde::Serialize;
+
+/// The distributed plan of a query
+#[derive(Debug, Serialize)]
+pub struct DistributedPlan {
+    pub stages: Vec<StagePlan>,
+}
+
+/// The plan of a stage, whose output is shuffled to the stages reading it, or fetched
+/// by the client for the final stage
+#[derive(Debug, Serialize)]
+pub struct StagePlan {
+    pub stage_id: usize,
+    /// Stages whose output the stage reads
+    pub input_stages: Vec<usize>,
+    /// Number of tasks of the stage
+    pub partitions: usize,
+    /// How the output of the stage is partitioned, none if it isn't
+    pub output_partitioning: Option<PartitioningPlan>,
+    pub plan: OperatorPlan,
+}
+
+/// An operator of the plan of a stage
+#[derive(Debug, Serialize)]
+pub struct OperatorPlan {
+    pub operator: String,
+    pub description: String,
+    pub output_partitioning: PartitioningPlan,
+    pub statistics: StatisticsPlan,
+    /// Stage whose output the operator reads, for the shuffle reads of a stage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_stage: Option<usize>,
+    pub children: Vec<OperatorPlan>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartitioningPlan {
+    /// `hash`, `round_robin` or `unknown`
+    pub scheme: String,
+    /// The expressions of a hash partitioning
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expressions: Vec<String>,
+    pub partitions: usize,
+}
+
+/// Statistics estimated by the planner, absent when unknown
+#[derive(Debug, Serialize)]
+pub struct StatisticsPlan {
+    pub num_rows: Option<Estimate>,
+    pub total_byte_size: Option<Estimate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Estimate {
+    pub value: usize,
+    /// Whether the value is exact rather than estimated
+    pub exact: bool,
+}
+
+/// The `EXPLAIN` of a query, whose physical plan is the distributed plan of the query
+pub(crate) fn explain_distributed_plan(
+    job_id: &str,
+    explain: &Explain,
+    plan: Arc<dyn ExecutionPlan>,
+    format: ExplainFormat,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let stages = DistributedPlanner::new().plan_query_stages(job_id, plan)?;
+    let distributed_plan = match format {
+        ExplainFormat::Text => format_stages(&stages),
+        ExplainFormat::Json => serde_json::to_string_pretty(&DistributedPlan::try_new(
+            &stages,
+        )?)
+        .map_err(|e| BallistaError::General(format!("Failed to serialize plan: {e}")))?,
+    };
+
+    // as DataFusion's physical planner does, the optimized logical plan is shown too
+    let mut stringified_plans = explain.stringified_plans.clone();
+    if explain.logical_optimization_succeeded {
+        stringified_plans.push(explain.plan.to_stringified(PlanType::FinalLogicalPlan));
+    }
+    stringified_plans.push(StringifiedPlan::new(
+        PlanType::FinalPhysicalPlan,
+        distributed_plan,
+    ));
+    Ok(Arc::new(ExplainExec::new(
+        explain.schema.as_ref().clone().into(),
+        stringified_plans,
+        explain.verbose,
+    )))
+}
+
+/// The plan of each stage, as indented text
+fn format_stages(stages: &[Arc<ShuffleWriterExec>]) -> String {
+    let mut text = String::new();
+    for stage in stages {
+        let plan: Arc<dyn ExecutionPlan> = stage.clone();
+        let input_stages = input_stages(&plan)
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = writeln!(
+            text,
+            "Stage {}: input_stages=[{input_stages}], partitions={}",
+            stage.stage_id(),
+            plan.output_partitioning().partition_count()
+        );
+        let _ = write!(text, "{}", displayable(stage.as_ref()).indent(false));
+    }
+    text
+}
+
+impl DistributedPlan {
+    pub fn try_new(stages: &[Arc<ShuffleWriterExec>]) -> Result<Self> {
+        let stages = stages
+            .iter()
+            .map(|stage| {
+                let plan: Arc<dyn ExecutionPlan> = stage.clone();
+                Ok(StagePlan {
+                    stage_id: stage.stage_id(),
+                    input_stages: input_stages(&plan),
+                    partitions: plan.output_partitioning().partition_count(),
+                    output_partitioning: stage
+                        .shuffle_output_partitioning()
+                        .map(PartitioningPlan::new),
+                    plan: OperatorPlan::try_new(stage.children()[0])?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { stages })
+    }
+}
+
+impl OperatorPlan {
+    fn try_new(plan: &Arc<dyn ExecutionPlan>) -> Result<Self> {
+        let statistics = plan.statistics()?;
+        Ok(Self {
+            operator: plan.name().to_owned(),
+            description: displayable(plan.as_ref())
+                .one_line()
+                .to_string()
+                .trim_end()
+                .to_owned(),
+            output_partitioning: PartitioningPlan::new(plan.output_partitioning()),
+            statistics: StatisticsPlan {
+                num_rows: Estimate::new(statistics.num_rows),
+                total_byte_size: Estimate::new(statistics.total_byte_size),
+            },
+            input_stage: plan
+                .as_any()
+                .downcast_ref::<UnresolvedShuffleExec>()
+                .map(|shuffle| shuffle.stage_id),
+            children: plan
+                .children()
+                .into_iter()
+                .map(Self::try_new)
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl PartitioningPlan {
+    fn new(partitioning: &Partitioning) -> Self {
+        let (scheme, expressions) = match partitioning {
+            Partitioning::Hash(exprs, _) => {
+                ("hash", exprs.iter().map(|expr| expr.to_string()).collect())
+            }
+            Partitioning::RoundRobinBatch(_) => ("round_robin", vec![]),
+            Partitioning::UnknownPartitioning(_) => ("unknown", vec![]),
+        };
+        Self {
+            scheme: scheme.to_owned(),
+            expressions,
+            partitions: partitioning.partition_count(),
+        }
+    }
+}
+
+impl Estimate {
+    fn new(precision: Precision<usize>) -> Option<Self> {
+        match precision {
+            Precision::Exact(value) => Some(Self { value, exact: true }),
+            Precision::Inexact(value) => Some(Self {
+                value,
+                exact: false,
+            }),
+            Precision::Absent => None,
+        }
+    }
+}
+
+/// Stages whose output a stage reads
+fn input_stages(plan: &Arc<dyn ExecutionPlan>) -> Vec<usize> {
+    let mut stage_ids = vec![];
+    let _ = plan.apply(|plan| {
+        if let Some(shuffle) = plan.as_any().downcast_ref::<UnresolvedShuffleExec>() {
+            stage_ids.push(shuffle.stage_id);
+        }
+        Ok(TreeNodeRecursion::Continue)
+    });
+    stage_ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::datafusion_test_context;
+
+    #[tokio::test]
+    async fn distributed_plan_of_aggregation() -> Result<()> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let df = ctx
+            .sql("select l_returnflag, count(*) from lineitem group by l_returnflag")
+            .await?;
+        let plan = df.create_physical_plan().await?;
+        let stages = DistributedPlanner::new().plan_query_stages("job", plan)?;
+
+        let plan = DistributedPlan::try_new(&stages)?;
+        assert_eq!(plan.stages.len(), 2);
+
+        // the partial aggregation is shuffled by the grouping column
+        let partial = &plan.stages[0];
+        assert_eq!(partial.stage_id, 1);
+        assert!(partial.input_stages.is_empty());
+        let partitioning = partial.output_partitioning.as_ref().unwrap();
+        assert_eq!(partitioning.scheme, "hash");
+        assert_eq!(partitioning.expressions, vec!["l_returnflag@0"]);
+        assert_eq!(partitioning.partitions, 2);
+        assert_eq!(partial.plan.operator, "AggregateExec");
+        assert!(partial
+            .plan
+            .description
+            .starts_with("AggregateExec: mode=Partial"));
+
+        // the final aggregation reads the shuffle of the first stage
+        let final_stage = &plan.stages[1];
+        assert_eq!(final_stage.input_stages, vec![1]);
+        assert!(final_stage.output_partitioning.is_none());
+        let mut operator = &final_stage.plan;
+        while operator.input_stage.is_none() {
+            operator = &operator.children[0];
+        }
+        assert_eq!(operator.operator, "UnresolvedShuffleExec");
+        assert_eq!(operator.input_stage, Some(1));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&plan).unwrap()).unwrap();
+        assert_eq!(json["stages"][0]["output_partitioning"]["scheme"], "hash");
+        assert_eq!(json["stages"][1]["input_stages"][0], 1);
+
+        Ok(())
+    }
+}
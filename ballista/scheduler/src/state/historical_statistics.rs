@@ -0,0 +1,270 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sizes of the tables scanned by the successful jobs, which the later jobs scanning
+//! the same tables are planned with, see [ballista_core::historical_statistics].
+//!
+//! The rows output by the scans of whole tables are recorded from the metrics of the
+//! stages of the successful jobs, and saved to the [JobState] so that they outlive the
+//! scheduler. Jobs get the sizes of the tables they scan with
+//! `ballista.optimizer.table_statistics`, unless their session sets it or disables
+//! `ballista.optimizer.historical_statistics`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use ballista_core::config::BALLISTA_OPTIMIZER_TABLE_STATISTICS;
+use ballista_core::error::Result;
+use ballista_core::extension::SessionConfigExt;
+use ballista_core::historical_statistics::{
+    scanned_table_size, table_location, TableSize,
+};
+use ballista_core::serde::protobuf::{KeyValuePair, TableStatistics};
+use dashmap::DashMap;
+use datafusion::common::tree_node::TreeNodeRecursion;
+use datafusion::datasource::listing::ListingTable;
+use datafusion::datasource::source_as_provider;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionConfig;
+
+use crate::cluster::JobState;
+use crate::state::execution_graph::{ExecutionGraph, ExecutionStage};
+
+/// The sizes of the tables scanned by the successful jobs
+pub struct HistoricalStatistics {
+    state: Arc<dyn JobState>,
+    /// Statistics of the tables, by location
+    tables: DashMap<String, TableStatistics>,
+}
+
+impl HistoricalStatistics {
+    pub fn new(state: Arc<dyn JobState>) -> Self {
+        Self {
+            state,
+            tables: DashMap::new(),
+        }
+    }
+
+    /// Load the statistics saved by previous schedulers
+    pub async fn init(&self) -> Result<()> {
+        for statistics in self.state.get_table_statistics().await? {
+            self.tables.insert(statistics.location.clone(), statistics);
+        }
+        Ok(())
+    }
+
+    /// Record the sizes of the tables scanned whole by job `job_id`, which succeeded at
+    /// `now` (in milliseconds), replacing those recorded from earlier jobs
+    pub async fn record(
+        &self,
+        job_id: &str,
+        tables: HashMap<String, TableSize>,
+        now: u64,
+    ) -> Result<()> {
+        for (location, size) in tables {
+            let statistics = TableStatistics {
+                location: location.clone(),
+                rows: size.rows,
+                bytes: size.bytes,
+                job_id: job_id.to_owned(),
+                recorded_at: now,
+            };
+            self.state.save_table_statistics(&statistics).await?;
+            self.tables.insert(location, statistics);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, location: &str) -> Option<TableStatistics> {
+        self.tables
+            .get(location)
+            .map(|statistics| statistics.clone())
+    }
+
+    /// The recorded sizes of the tables scanned by `plan`, which the job is planned
+    /// with on the scheduler and the executors unless the session `config` disables
+    /// them or sets its own
+    pub fn settings(
+        &self,
+        config: &SessionConfig,
+        plan: &LogicalPlan,
+    ) -> Result<Vec<KeyValuePair>> {
+        let session_unset = config
+            .ballista_table_statistics()
+            .is_ok_and(|tables| tables.is_empty());
+        if !config.ballista_historical_statistics()
+            || !session_unset
+            || self.tables.is_empty()
+        {
+            return Ok(vec![]);
+        }
+
+        let mut locations = BTreeSet::new();
+        plan.apply_with_subqueries(|plan| {
+            if let LogicalPlan::TableScan(scan) = plan {
+                let provider = source_as_provider(&scan.source)?;
+                if let Some(table) = provider.as_any().downcast_ref::<ListingTable>() {
+                    locations.extend(table.table_paths().iter().map(table_location));
+                }
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+        let statistics: Vec<String> = locations
+            .into_iter()
+            .filter_map(|location| self.get(&location))
+            .map(|table| format!("{}={}:{}", table.location, table.rows, table.bytes))
+            .collect();
+        if statistics.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![KeyValuePair {
+            key: BALLISTA_OPTIMIZER_TABLE_STATISTICS.to_owned(),
+            value: statistics.join(","),
+        }])
+    }
+}
+
+/// Sizes of the tables scanned whole by the successful stages of a job, by location
+pub fn scanned_tables(graph: &ExecutionGraph) -> HashMap<String, TableSize> {
+    graph
+        .stages()
+        .values()
+        .flat_map(stage_scanned_tables)
+        .collect()
+}
+
+/// Sizes of the tables scanned whole by a successful stage, from the rows its scans
+/// output
+fn stage_scanned_tables(stage: &ExecutionStage) -> Vec<(String, TableSize)> {
+    if !matches!(stage, ExecutionStage::Successful(_)) {
+        return vec![];
+    }
+    let Some(metrics) = stage.stage_metrics() else {
+        return vec![];
+    };
+    // the metrics of a stage are those of the nodes of its plan having any, in the
+    // order collect_plan_metrics visits them
+    let mut nodes = vec![];
+    metered_nodes(stage.plan(), &mut nodes);
+    if nodes.len() != metrics.len() {
+        return vec![];
+    }
+    nodes
+        .into_iter()
+        .zip(metrics)
+        .filter_map(|(node, metrics)| {
+            scanned_table_size(node, metrics.output_rows()? as u64)
+        })
+        .collect()
+}
+
+fn metered_nodes<'a>(
+    plan: &'a dyn ExecutionPlan,
+    nodes: &mut Vec<&'a dyn ExecutionPlan>,
+) {
+    if plan.metrics().is_some() {
+        nodes.push(plan);
+    }
+    for child in plan.children() {
+        metered_nodes(child.as_ref(), nodes);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use ballista_core::config::BALLISTA_OPTIMIZER_TABLE_STATISTICS;
+    use ballista_core::error::Result;
+    use ballista_core::extension::{SessionConfigExt, SessionConfigHelperExt};
+    use ballista_core::historical_statistics::TableSize;
+    use ballista_core::utils::{default_config_producer, default_session_builder};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::file_format::parquet::ParquetFormat;
+    use datafusion::datasource::listing::{
+        ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+    };
+    use datafusion::datasource::provider_as_source;
+    use datafusion::logical_expr::{LogicalPlan, LogicalPlanBuilder};
+    use datafusion::prelude::SessionConfig;
+
+    use crate::cluster::memory::InMemoryJobState;
+    use crate::cluster::JobState;
+    use crate::state::historical_statistics::HistoricalStatistics;
+
+    fn scan(url: &str) -> Result<LogicalPlan> {
+        let schema =
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let config = ListingTableConfig::new(ListingTableUrl::parse(url)?)
+            .with_listing_options(ListingOptions::new(Arc::new(ParquetFormat::default())))
+            .with_schema(schema);
+        let table = Arc::new(ListingTable::try_new(config)?);
+        Ok(LogicalPlanBuilder::scan("t", provider_as_source(table), None)?.build()?)
+    }
+
+    #[tokio::test]
+    async fn test_historical_statistics() -> Result<()> {
+        let state: Arc<dyn JobState> = Arc::new(InMemoryJobState::new(
+            "",
+            Arc::new(default_session_builder),
+            Arc::new(default_config_producer),
+        ));
+        let statistics = HistoricalStatistics::new(state.clone());
+        let plan = scan("s3://bucket/orders/")?;
+        let config = SessionConfig::new_with_ballista();
+        assert!(statistics.settings(&config, &plan)?.is_empty());
+
+        let tables = HashMap::from([
+            ("s3://bucket/orders".to_owned(), TableSize::new(1000, 8000)),
+            ("s3://bucket/items".to_owned(), TableSize::new(10, 80)),
+        ]);
+        statistics.record("job1", tables, 1_000).await?;
+
+        // a scheduler taking over loads the statistics from the state
+        let recovered = HistoricalStatistics::new(state);
+        recovered.init().await?;
+        assert_eq!(
+            Some("job1".to_owned()),
+            recovered
+                .get("s3://bucket/orders")
+                .map(|table| table.job_id)
+        );
+
+        // jobs only get the sizes of the tables they scan
+        let settings = recovered.settings(&config, &plan)?;
+        assert_eq!(1, settings.len());
+        assert_eq!(BALLISTA_OPTIMIZER_TABLE_STATISTICS, settings[0].key);
+        assert_eq!("s3://bucket/orders=1000:8000", settings[0].value);
+        let config = config.update_from_key_value_pair(&settings);
+        assert_eq!(
+            Ok(vec![(
+                "s3://bucket/orders".to_owned(),
+                TableSize::new(1000, 8000)
+            )]),
+            config.ballista_table_statistics()
+        );
+
+        // the settings of the session take precedence
+        assert!(recovered.settings(&config, &plan)?.is_empty());
+        let config =
+            SessionConfig::new_with_ballista().with_ballista_historical_statistics(false);
+        assert!(recovered.settings(&config, &plan)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Drivers of the jobs curated by the scheduler.
+//!
+//! Scheduling is split in two levels. The event loop of the
+//! [QueryStageScheduler](crate::scheduler_server::query_stage_scheduler) allocates the
+//! resources of the cluster: it tracks the executors and binds their slots to tasks.
+//! Each active job has a driver, a task of its own consuming a queue of updates of the
+//! job, which advances the state machines of its stages and retries its failed tasks
+//! and stages. The updates of a job are applied in the order they are queued, while
+//! the updates of different jobs are applied concurrently, off the event loop.
+//!
+//! A driver failing to apply an update only fails its job, and the logs of the updates
+//! are recorded within a `job` span carrying the ID of the job.
+
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use log::error;
+use tokio::sync::{mpsc, oneshot};
+use tracing::Instrument;
+
+type JobUpdate = BoxFuture<'static, ()>;
+
+/// The drivers of the active jobs, by job ID
+#[derive(Clone, Default)]
+pub(crate) struct JobDrivers {
+    drivers: Arc<DashMap<String, mpsc::UnboundedSender<JobUpdate>>>,
+}
+
+impl JobDrivers {
+    /// Queue an update to the driver of a job, started if the job has none yet. The
+    /// returned receiver resolves to the outcome of the update once it was applied,
+    /// an error if it panicked
+    pub(crate) fn run<R, F>(
+        &self,
+        job_id: &str,
+        update: F,
+    ) -> oneshot::Receiver<Result<R>>
+    where
+        R: Send + 'static,
+        F: Future<Output = Result<R>> + Send + 'static,
+    {
+        let (reply, receiver) = oneshot::channel();
+        let message = format!("Driver of job {job_id} panicked");
+        let update = async move {
+            let result = AssertUnwindSafe(update)
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| Err(BallistaError::Internal(message)));
+            // the caller may not wait for the outcome
+            let _ = reply.send(result);
+        };
+
+        let driver = self
+            .drivers
+            .entry(job_id.to_owned())
+            .or_insert_with(|| spawn_driver(job_id))
+            .clone();
+        if driver.send(update.boxed()).is_err() {
+            error!("Driver of job {job_id} stopped");
+        }
+        receiver
+    }
+
+    /// Stop the driver of a job once the updates queued to it are applied
+    pub(crate) fn remove(&self, job_id: &str) {
+        self.drivers.remove(job_id);
+    }
+}
+
+fn spawn_driver(job_id: &str) -> mpsc::UnboundedSender<JobUpdate> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<JobUpdate>();
+    let span = tracing::info_span!("job", job_id = %job_id);
+    tokio::spawn(
+        async move {
+            while let Some(update) = receiver.recv().await {
+                update.await;
+            }
+        }
+        .instrument(span),
+    );
+    sender
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn apply_updates_of_a_job_in_order() -> Result<()> {
+        let drivers = JobDrivers::default();
+        let order = Arc::new(parking_lot::Mutex::new(vec![]));
+
+        let first = {
+            let order = order.clone();
+            drivers.run("job", async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                order.lock().push(1);
+                Ok(())
+            })
+        };
+        let second = {
+            let order = order.clone();
+            drivers.run("job", async move {
+                order.lock().push(2);
+                Ok(())
+            })
+        };
+        first.await.unwrap()?;
+        second.await.unwrap()?;
+
+        assert_eq!(vec![1, 2], *order.lock());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn isolate_panicking_updates() -> Result<()> {
+        let drivers = JobDrivers::default();
+
+        let panicked = drivers.run("job", async { fail() });
+        let other = drivers.run("other", async { Ok(1) });
+        let next = drivers.run("job", async { Ok(2) });
+
+        assert!(matches!(
+            panicked.await.unwrap(),
+            Err(BallistaError::Internal(_))
+        ));
+        assert_eq!(1, other.await.unwrap()?);
+        assert_eq!(2, next.await.unwrap()?);
+        Ok(())
+    }
+
+    fn fail() -> Result<()> {
+        panic!("boom")
+    }
+}
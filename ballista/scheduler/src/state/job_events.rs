@@ -0,0 +1,217 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Events of the jobs, pushed to the clients subscribed to them with
+//! `SubscribeJobEvents`, so that they don't have to poll the status of the jobs.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::protobuf::{
+    job_event, JobEvent, JobStatus, StageCompletedEvent,
+};
+use futures::Stream;
+use log::warn;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::scheduler_server::timestamp_millis;
+
+/// Events buffered for the subscribers, beyond which slow subscribers lag behind
+const EVENT_BUFFER_SIZE: usize = 1024;
+
+/// Publishes the events of the jobs of the scheduler to their subscribers
+#[derive(Clone)]
+pub struct JobEvents {
+    sender: broadcast::Sender<JobEvent>,
+}
+
+impl Default for JobEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        Self { sender }
+    }
+}
+
+impl JobEvents {
+    /// Whether any client is subscribed to the events of a job
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// Publish the new status of a job
+    pub fn publish_status(&self, status: JobStatus) {
+        let job_id = status.job_id.clone();
+        self.publish(job_id, job_event::Event::Status(status));
+    }
+
+    /// Publish the completion of the stage `stage_id` of a job, the
+    /// `completed_stages`-th of its `total_stages` stages
+    pub fn publish_stage_completed(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        completed_stages: usize,
+        total_stages: usize,
+    ) {
+        self.publish(
+            job_id.to_owned(),
+            job_event::Event::StageCompleted(StageCompletedEvent {
+                stage_id: stage_id as u32,
+                completed_stages: completed_stages as u32,
+                total_stages: total_stages as u32,
+            }),
+        );
+    }
+
+    fn publish(&self, job_id: String, event: job_event::Event) {
+        if self.has_subscribers() {
+            // fails only when the last subscriber just went away
+            let _ = self.sender.send(JobEvent {
+                job_id,
+                timestamp: timestamp_millis(),
+                event: Some(event),
+            });
+        }
+    }
+
+    /// Receive the events published from now on, of all the jobs
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// The event of the current status of a job
+pub fn status_event(status: JobStatus) -> JobEvent {
+    JobEvent {
+        job_id: status.job_id.clone(),
+        timestamp: timestamp_millis(),
+        event: Some(job_event::Event::Status(status)),
+    }
+}
+
+/// Whether the event is the final status of its job, after which it has no events
+pub fn is_final(event: &JobEvent) -> bool {
+    matches!(
+        &event.event,
+        Some(job_event::Event::Status(JobStatus {
+            status: Some(Status::Successful(_) | Status::Failed(_)),
+            ..
+        }))
+    )
+}
+
+/// The events of the job `job_id` received by `receiver`, up to its final status.
+/// Subscribers lagging behind are sent the current status of the job, from
+/// `current_status`, in place of the events they missed
+pub fn job_event_stream<F, Fut>(
+    job_id: String,
+    receiver: broadcast::Receiver<JobEvent>,
+    current_status: F,
+) -> impl Stream<Item = JobEvent> + Send
+where
+    F: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Option<JobStatus>> + Send,
+{
+    let current_status = Arc::new(current_status);
+    futures::stream::unfold(Some(receiver), move |receiver| {
+        let job_id = job_id.clone();
+        let current_status = current_status.clone();
+        async move {
+            let mut receiver = receiver?;
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) if event.job_id == job_id => event,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Subscriber of job {job_id} missed {missed} job events");
+                        match current_status(job_id.clone()).await {
+                            Some(status) => status_event(status),
+                            None => continue,
+                        }
+                    }
+                    Err(RecvError::Closed) => return None,
+                };
+                let receiver = (!is_final(&event)).then_some(receiver);
+                return Some((event, receiver));
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::serde::protobuf::{RunningJob, SuccessfulJob};
+    use futures::StreamExt;
+
+    fn status(job_id: &str, status: Status) -> JobStatus {
+        JobStatus {
+            job_id: job_id.to_owned(),
+            job_name: String::new(),
+            status: Some(status),
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_job_events_until_final_status() {
+        let events = JobEvents::default();
+        assert!(!events.has_subscribers());
+        let stream =
+            job_event_stream("job".to_owned(), events.subscribe(), |_| async { None });
+
+        events.publish_status(status("job", Status::Running(RunningJob::default())));
+        events.publish_stage_completed("other", 1, 1, 2);
+        events.publish_stage_completed("job", 1, 1, 2);
+        events
+            .publish_status(status("job", Status::Successful(SuccessfulJob::default())));
+        events.publish_stage_completed("job", 2, 2, 2);
+
+        let received: Vec<_> = stream.collect().await;
+        assert_eq!(3, received.len());
+        assert!(received.iter().all(|event| event.job_id == "job"));
+        assert!(matches!(
+            received[1].event,
+            Some(job_event::Event::StageCompleted(StageCompletedEvent {
+                stage_id: 1,
+                completed_stages: 1,
+                total_stages: 2,
+            }))
+        ));
+        assert!(is_final(&received[2]));
+    }
+
+    #[tokio::test]
+    async fn replace_missed_events_by_current_status() {
+        let events = JobEvents::default();
+        let stream = job_event_stream("job".to_owned(), events.subscribe(), |job_id| {
+            futures::future::ready(Some(status(
+                &job_id,
+                Status::Successful(SuccessfulJob::default()),
+            )))
+        });
+
+        for stage_id in 0..EVENT_BUFFER_SIZE + 1 {
+            events.publish_stage_completed("job", stage_id, stage_id, EVENT_BUFFER_SIZE);
+        }
+
+        let received: Vec<_> = stream.collect().await;
+        assert_eq!(1, received.len());
+        assert!(is_final(&received[0]));
+    }
+}
@@ -0,0 +1,174 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Metrics of the stages of a job and of the job, aggregated from the DataFusion
+//! metrics which the completed tasks report to the scheduler.
+
+use std::collections::HashMap;
+
+use ballista_core::serde::protobuf::{
+    task_status, GetJobMetricsResult, StageMetrics, TaskMetrics,
+};
+use datafusion::physical_plan::metrics::{MetricValue, MetricsSet};
+
+use crate::display::DisplayableBallistaExecutionPlan;
+use crate::state::execution_graph::ExecutionGraph;
+
+/// Aggregate the metrics of the stages of a job, kept in its execution graph as the
+/// metrics of each operator combined across the completed tasks of the stage
+pub(crate) fn job_metrics(graph: &ExecutionGraph) -> GetJobMetricsResult {
+    let stages = graph.stages();
+    let mut stage_ids = stages.keys().copied().collect::<Vec<_>>();
+    stage_ids.sort_unstable();
+
+    let mut metrics = HashMap::new();
+    let stages = stage_ids
+        .iter()
+        .map(|&stage_id| {
+            let stage = &stages[&stage_id];
+            let stage_metrics = stage
+                .stage_metrics()
+                .map(summarize_metrics)
+                .unwrap_or_default();
+            for (name, value) in &stage_metrics {
+                *metrics.entry(name.clone()).or_default() += value;
+            }
+            let operator_metrics = stage
+                .stage_metrics()
+                .map(|metrics| metrics.to_vec())
+                .unwrap_or_default();
+            let plan =
+                DisplayableBallistaExecutionPlan::new(stage.plan(), &operator_metrics)
+                    .indent()
+                    .to_string();
+            let tasks = stage
+                .completed_task_infos()
+                .into_iter()
+                .map(|(partition, info)| TaskMetrics {
+                    partition_id: partition as u32,
+                    task_id: info.task_id as u32,
+                    executor_id: match &info.task_status {
+                        task_status::Status::Successful(task) => task.executor_id.clone(),
+                        _ => String::new(),
+                    },
+                    start_exec_time: info.start_exec_time as u64,
+                    end_exec_time: info.end_exec_time as u64,
+                    metrics: info.metrics.clone(),
+                })
+                .collect();
+            let input_stages = stage_ids
+                .iter()
+                .filter(|id| stages[*id].output_links().contains(&stage_id))
+                .map(|id| *id as u32)
+                .collect();
+            StageMetrics {
+                stage_id: stage_id as u32,
+                status: stage.variant_name().to_owned(),
+                partitions: stage.partitions().unwrap_or_default() as u32,
+                completed_tasks: stage.completed_tasks() as u32,
+                metrics: stage_metrics,
+                plan,
+                tasks,
+                input_stages,
+            }
+        })
+        .collect();
+
+    GetJobMetricsResult { stages, metrics }
+}
+
+/// Sum the metrics of the operators of a stage, or of a task of the stage, by name. The
+/// operators are in pre-order of the plan of the stage, so that the output rows of the
+/// stage are those of its first operator, rather than the sum of the output rows of all
+/// of its operators. Gauges and timestamps, which can't be summed, are left out
+pub(crate) fn summarize_metrics(operators: &[MetricsSet]) -> HashMap<String, u64> {
+    let mut summary = HashMap::new();
+    for (index, operator) in operators.iter().enumerate() {
+        for metric in operator.iter() {
+            let value = metric.value();
+            match value {
+                MetricValue::OutputRows(_) if index > 0 => continue,
+                MetricValue::Gauge { .. }
+                | MetricValue::CurrentMemoryUsage(_)
+                | MetricValue::StartTimestamp(_)
+                | MetricValue::EndTimestamp(_) => continue,
+                _ => {}
+            }
+            *summary.entry(value.name().to_owned()).or_default() +=
+                value.as_usize() as u64;
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use datafusion::physical_plan::metrics::{Count, Metric, Time};
+    use std::borrow::Cow;
+    use std::sync::Arc;
+
+    fn metrics_set(values: Vec<MetricValue>) -> MetricsSet {
+        let mut set = MetricsSet::new();
+        for value in values {
+            set.push(Arc::new(Metric::new(value, None)));
+        }
+        set
+    }
+
+    fn count(value: usize) -> Count {
+        let count = Count::new();
+        count.add(value);
+        count
+    }
+
+    #[test]
+    fn test_summarize_metrics() {
+        let elapsed_compute = Time::new();
+        elapsed_compute.add_duration(std::time::Duration::from_nanos(5));
+        let operators = vec![
+            metrics_set(vec![
+                MetricValue::OutputRows(count(10)),
+                MetricValue::Count {
+                    name: Cow::Borrowed("output_bytes"),
+                    count: count(1024),
+                },
+            ]),
+            metrics_set(vec![
+                MetricValue::OutputRows(count(100)),
+                MetricValue::ElapsedCompute(elapsed_compute.clone()),
+                MetricValue::SpillCount(count(1)),
+                MetricValue::CurrentMemoryUsage(Default::default()),
+            ]),
+            metrics_set(vec![
+                MetricValue::ElapsedCompute(elapsed_compute),
+                MetricValue::SpillCount(count(2)),
+            ]),
+        ];
+
+        let summary = summarize_metrics(&operators);
+        assert_eq!(
+            summary,
+            HashMap::from([
+                ("output_rows".to_owned(), 10),
+                ("output_bytes".to_owned(), 1024),
+                ("elapsed_compute".to_owned(), 10),
+                ("spill_count".to_owned(), 3),
+            ])
+        );
+    }
+}
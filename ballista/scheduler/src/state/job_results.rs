@@ -0,0 +1,241 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Pages of the results of successful jobs, read by the scheduler from the executors
+//! holding the output partitions, for clients which only speak plain gRPC and can't
+//! fetch the partitions over Flight themselves.
+
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::fetch_partition;
+use ballista_core::security::GrpcSecurityConfig;
+use ballista_core::serde::protobuf::{job_status, JobStatus};
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use futures::TryStreamExt;
+
+/// Maximum number of rows of a page, so that pages fit in a gRPC message
+pub const MAX_RESULT_PAGE_ROWS: u64 = 10_000;
+
+/// Rows of the results of a job, starting at some offset
+#[derive(Debug, Default)]
+pub struct ResultPage {
+    pub batches: Vec<RecordBatch>,
+    /// Whether the page holds the last rows of the results
+    pub end_of_results: bool,
+}
+
+impl ResultPage {
+    /// Read the page of at most `max_rows` rows starting at row `offset` of the results
+    /// of a successful job. The output partitions ending before the offset are skipped
+    /// without being fetched, when their number of rows is known.
+    pub async fn of_job(
+        status: &JobStatus,
+        offset: u64,
+        max_rows: u64,
+        security: Arc<GrpcSecurityConfig>,
+    ) -> Result<Self> {
+        let Some(job_status::Status::Successful(successful)) = &status.status else {
+            return Err(BallistaError::General(format!(
+                "Job {} did not succeed",
+                status.job_id
+            )));
+        };
+
+        // clients only read the rows of truncated results up to the truncation
+        let limit = successful.truncated_at.unwrap_or(u64::MAX);
+        if offset >= limit {
+            return Ok(Self {
+                batches: vec![],
+                end_of_results: true,
+            });
+        }
+        let max_rows = match max_rows {
+            0 => MAX_RESULT_PAGE_ROWS,
+            max_rows => max_rows.min(MAX_RESULT_PAGE_ROWS),
+        };
+        let end = offset.saturating_add(max_rows).min(limit);
+
+        let mut batches = vec![];
+        // index of the first row of the next batch
+        let mut start = 0;
+        let mut more_rows = false;
+        'locations: for location in &successful.partition_location {
+            let num_rows = location
+                .partition_stats
+                .as_ref()
+                .and_then(|stats| u64::try_from(stats.num_rows).ok());
+            if let Some(num_rows) = num_rows {
+                if start >= end && num_rows > 0 {
+                    more_rows = true;
+                    break;
+                }
+                if start + num_rows <= offset {
+                    start += num_rows;
+                    continue;
+                }
+            }
+
+            let mut stream = fetch_partition(location.clone(), security.clone()).await?;
+            while let Some(batch) = stream.try_next().await? {
+                let num_rows = batch.num_rows() as u64;
+                let batch_start = start;
+                start += num_rows;
+
+                let from = offset.saturating_sub(batch_start).min(num_rows);
+                let to = end.saturating_sub(batch_start).min(num_rows);
+                if from < to {
+                    batches.push(batch.slice(from as usize, (to - from) as usize));
+                }
+                if start > end {
+                    more_rows = true;
+                    break 'locations;
+                }
+            }
+        }
+
+        Ok(Self {
+            batches,
+            end_of_results: !more_rows || end >= limit,
+        })
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.batches.iter().map(|batch| batch.num_rows()).sum()
+    }
+
+    /// Encode the rows of the page in Arrow IPC stream format, empty if there are none
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let Some(first) = self.batches.first() else {
+            return Ok(vec![]);
+        };
+        let mut writer = StreamWriter::try_new(vec![], first.schema().as_ref())?;
+        for batch in &self.batches {
+            writer.write(batch)?;
+        }
+        Ok(writer.into_inner()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use ballista_core::error::Result;
+    use ballista_core::security::GrpcSecurityConfig;
+    use ballista_core::serde::protobuf::{
+        job_status, JobStatus, PartitionLocation, PartitionStats, SuccessfulJob,
+    };
+    use datafusion::arrow::array::{AsArray, Int32Array};
+    use datafusion::arrow::datatypes::{DataType, Field, Int32Type, Schema};
+    use datafusion::arrow::ipc::reader::StreamReader;
+    use datafusion::arrow::ipc::writer::StreamWriter;
+    use datafusion::arrow::record_batch::RecordBatch;
+
+    use super::ResultPage;
+
+    /// Output partition delivered inline, holding the given batches of values
+    fn inline_location(
+        batches: &[Vec<i32>],
+        with_stats: bool,
+    ) -> Result<PartitionLocation> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let mut writer = StreamWriter::try_new(vec![], &schema)?;
+        for values in batches {
+            writer.write(&RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from(values.clone()))],
+            )?)?;
+        }
+        let num_rows = batches.iter().map(Vec::len).sum::<usize>() as i64;
+        Ok(PartitionLocation {
+            inline_data: writer.into_inner()?,
+            partition_stats: with_stats.then(|| PartitionStats {
+                num_rows,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn successful_job(
+        partition_location: Vec<PartitionLocation>,
+        truncated_at: Option<u64>,
+    ) -> JobStatus {
+        JobStatus {
+            job_id: "job".to_owned(),
+            status: Some(job_status::Status::Successful(SuccessfulJob {
+                partition_location,
+                truncated_at,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    async fn page_values(
+        status: &JobStatus,
+        offset: u64,
+        max_rows: u64,
+    ) -> Result<(Vec<i32>, bool)> {
+        let page = ResultPage::of_job(
+            status,
+            offset,
+            max_rows,
+            Arc::new(GrpcSecurityConfig::default()),
+        )
+        .await?;
+        let data = page.encode()?;
+        let mut values = vec![];
+        if !data.is_empty() {
+            for batch in StreamReader::try_new(Cursor::new(data), None)? {
+                values
+                    .extend(batch?.column(0).as_primitive::<Int32Type>().values().iter());
+            }
+        }
+        assert_eq!(page.num_rows(), values.len());
+        Ok((values, page.end_of_results))
+    }
+
+    #[tokio::test]
+    async fn test_result_pages() -> Result<()> {
+        for with_stats in [true, false] {
+            let status = successful_job(
+                vec![
+                    inline_location(&[vec![0, 1, 2], vec![3]], with_stats)?,
+                    inline_location(&[], with_stats)?,
+                    inline_location(&[vec![4, 5], vec![6, 7, 8]], with_stats)?,
+                ],
+                None,
+            );
+            assert_eq!((vec![0, 1], false), page_values(&status, 0, 2).await?);
+            assert_eq!((vec![2, 3, 4, 5], false), page_values(&status, 2, 4).await?);
+            assert_eq!((vec![6, 7, 8], true), page_values(&status, 6, 3).await?);
+            assert_eq!((vec![7, 8], true), page_values(&status, 7, 0).await?);
+            assert_eq!((vec![], true), page_values(&status, 9, 5).await?);
+        }
+
+        let truncated =
+            successful_job(vec![inline_location(&[vec![0, 1, 2, 3]], true)?], Some(3));
+        assert_eq!((vec![1, 2], true), page_values(&truncated, 1, 5).await?);
+        assert_eq!((vec![], true), page_values(&truncated, 3, 5).await?);
+
+        Ok(())
+    }
+}
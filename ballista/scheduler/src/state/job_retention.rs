@@ -0,0 +1,242 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Retention of completed jobs in the scheduler state. Completed jobs falling outside
+//! of the retention policy are purged from the state, after being written to the
+//! [JobArchive] if one is configured, so that the live state stays small.
+
+use std::sync::Arc;
+
+use ballista_core::error::Result;
+use ballista_core::serde::protobuf::{job_status, JobStatus};
+use datafusion::error::DataFusionError;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use prost::Message;
+
+use crate::config::SchedulerConfig;
+use crate::state::decode_protobuf;
+
+/// Archive which completed jobs are written to before being purged from the state
+#[derive(Clone)]
+pub struct JobArchive {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl JobArchive {
+    /// Create an archive storing jobs under `prefix` of `store`
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: Path) -> Self {
+        Self { store, prefix }
+    }
+
+    /// Create the archive configured for the scheduler, if any
+    pub fn from_config(config: &SchedulerConfig) -> Option<Self> {
+        if config.job_archive_dir.is_none() && config.override_job_archive_store.is_none()
+        {
+            return None;
+        }
+
+        let store = config
+            .override_job_archive_store
+            .clone()
+            .unwrap_or_else(|| Arc::new(LocalFileSystem::new()));
+        let prefix = config
+            .job_archive_dir
+            .as_deref()
+            .map(Path::from)
+            .unwrap_or_default();
+
+        Some(Self::new(store, prefix))
+    }
+
+    fn job_path(&self, job_id: &str) -> Path {
+        self.prefix.child(format!("{job_id}.pb"))
+    }
+
+    /// Write the status of a completed job to the archive
+    pub async fn archive(&self, status: &JobStatus) -> Result<()> {
+        self.store
+            .put(
+                &self.job_path(&status.job_id),
+                status.encode_to_vec().into(),
+            )
+            .await
+            .map_err(DataFusionError::ObjectStore)?;
+
+        Ok(())
+    }
+
+    /// Get the status of an archived job, if it has been archived
+    pub async fn get(&self, job_id: &str) -> Result<Option<JobStatus>> {
+        match self.store.get(&self.job_path(job_id)).await {
+            Ok(result) => {
+                let bytes = result.bytes().await.map_err(DataFusionError::ObjectStore)?;
+                Ok(Some(decode_protobuf(&bytes)?))
+            }
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(DataFusionError::ObjectStore(e).into()),
+        }
+    }
+}
+
+/// Retention policy of completed jobs in the scheduler state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JobRetention {
+    /// Maximum number of completed jobs kept, 0 means unlimited
+    pub max_count: usize,
+    /// Maximum time in seconds completed jobs are kept after they ended, 0 means unlimited
+    pub max_age_seconds: u64,
+}
+
+impl JobRetention {
+    /// Whether the policy retains all completed jobs
+    pub fn is_unlimited(&self) -> bool {
+        self.max_count == 0 && self.max_age_seconds == 0
+    }
+
+    /// IDs of the completed jobs among `jobs` which fall outside of the policy at
+    /// `now` (in milliseconds), the earliest ended first. Jobs which are not
+    /// completed are always retained.
+    pub fn jobs_to_purge(&self, jobs: &[JobStatus], now: u64) -> Vec<String> {
+        let mut completed: Vec<(u64, &str)> = jobs
+            .iter()
+            .filter_map(|job| Some((ended_at(job)?, job.job_id.as_str())))
+            .collect();
+        completed.sort_unstable();
+
+        let excess = if self.max_count > 0 {
+            completed.len().saturating_sub(self.max_count)
+        } else {
+            0
+        };
+        let max_age_millis = self.max_age_seconds.saturating_mul(1000);
+
+        completed
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, (ended_at, _))| {
+                *idx < excess
+                    || (max_age_millis > 0
+                        && now.saturating_sub(*ended_at) > max_age_millis)
+            })
+            .map(|(_, (_, job_id))| job_id.to_owned())
+            .collect()
+    }
+}
+
+/// Time in milliseconds at which a job ended, if it is completed
+pub(crate) fn ended_at(job: &JobStatus) -> Option<u64> {
+    match &job.status {
+        Some(job_status::Status::Successful(successful)) => Some(successful.ended_at),
+        Some(job_status::Status::Failed(failed)) => Some(failed.ended_at),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::{
+        job_status, FailedJob, JobStatus, RunningJob, SuccessfulJob,
+    };
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+
+    use crate::state::job_retention::{JobArchive, JobRetention};
+
+    fn successful(job_id: &str, ended_at: u64) -> JobStatus {
+        JobStatus {
+            job_id: job_id.to_owned(),
+            job_name: String::default(),
+            status: Some(job_status::Status::Successful(SuccessfulJob {
+                ended_at,
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn failed(job_id: &str, ended_at: u64) -> JobStatus {
+        JobStatus {
+            job_id: job_id.to_owned(),
+            job_name: String::default(),
+            status: Some(job_status::Status::Failed(FailedJob {
+                ended_at,
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn running(job_id: &str) -> JobStatus {
+        JobStatus {
+            job_id: job_id.to_owned(),
+            job_name: String::default(),
+            status: Some(job_status::Status::Running(RunningJob::default())),
+        }
+    }
+
+    #[test]
+    fn test_jobs_to_purge() {
+        let jobs = vec![
+            successful("job-3", 3_000),
+            running("job-running"),
+            failed("job-1", 1_000),
+            successful("job-2", 2_000),
+        ];
+
+        assert!(JobRetention::default()
+            .jobs_to_purge(&jobs, 10_000)
+            .is_empty());
+
+        let by_count = JobRetention {
+            max_count: 1,
+            max_age_seconds: 0,
+        };
+        assert_eq!(
+            by_count.jobs_to_purge(&jobs, 10_000),
+            vec!["job-1", "job-2"]
+        );
+
+        let by_age = JobRetention {
+            max_count: 0,
+            max_age_seconds: 7,
+        };
+        assert_eq!(by_age.jobs_to_purge(&jobs, 10_000), vec!["job-1", "job-2"]);
+
+        let by_both = JobRetention {
+            max_count: 2,
+            max_age_seconds: 8,
+        };
+        assert_eq!(by_both.jobs_to_purge(&jobs, 10_000), vec!["job-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_job_archive() -> Result<()> {
+        let archive = JobArchive::new(Arc::new(InMemory::new()), Path::from("archive"));
+
+        assert_eq!(archive.get("job-1").await?, None);
+
+        let status = failed("job-1", 1_000);
+        archive.archive(&status).await?;
+        assert_eq!(archive.get("job-1").await?, Some(status));
+
+        Ok(())
+    }
+}
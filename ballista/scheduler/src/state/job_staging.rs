@@ -0,0 +1,167 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Staging prefixes of the jobs in object stores, see [ballista_core::staging], which
+//! the scheduler tracks from the submission of the jobs and recursively deletes along
+//! with the job data of the executors, once the jobs complete or expire.
+//!
+//! The prefixes are tracked in memory, so the prefixes of the jobs running while the
+//! scheduler restarts are left to the lifecycle rules of the buckets.
+
+use std::sync::Arc;
+
+use ballista_core::staging::{
+    job_staging_prefix, job_staging_urls, remove_staging_prefix,
+};
+use dashmap::DashMap;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::prelude::SessionConfig;
+use log::{info, warn};
+
+use crate::state::stage_verification::verification_job_id;
+
+/// The staging URLs of a job, and the runtime of its session whose object stores hold
+/// them
+#[derive(Clone)]
+struct StagedJob {
+    urls: Vec<String>,
+    runtime: Arc<RuntimeEnv>,
+}
+
+/// The staging prefixes of the jobs which are not cleaned up yet
+#[derive(Clone, Default)]
+pub struct JobStaging {
+    /// Keyed by job ID
+    jobs: Arc<DashMap<String, StagedJob>>,
+}
+
+impl JobStaging {
+    /// Track the staging prefixes of a job, from the settings of its session
+    pub fn register(
+        &self,
+        job_id: &str,
+        config: &SessionConfig,
+        runtime: Arc<RuntimeEnv>,
+    ) {
+        let urls = job_staging_urls(config);
+        if !urls.is_empty() {
+            self.jobs
+                .insert(job_id.to_owned(), StagedJob { urls, runtime });
+        }
+    }
+
+    /// Track the staging prefixes of an internal job run for `job_id`, sharing its
+    /// session
+    pub fn register_internal(&self, job_id: &str, internal_job_id: &str) {
+        let staged = self.jobs.get(job_id).map(|staged| staged.clone());
+        if let Some(staged) = staged {
+            self.jobs.insert(internal_job_id.to_owned(), staged);
+        }
+    }
+
+    /// The staging prefixes of a job, empty if it has none or was cleaned up
+    pub fn prefixes(&self, job_id: &str) -> Vec<String> {
+        self.jobs
+            .get(job_id)
+            .map(|staged| {
+                staged
+                    .urls
+                    .iter()
+                    .map(|url| job_staging_prefix(url, job_id))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Recursively delete the staging prefixes of a job, along with those of its
+    /// verification tasks, and stop tracking them
+    pub async fn remove_job(&self, job_id: &str) {
+        let Some((_, staged)) = self.jobs.remove(job_id) else {
+            return;
+        };
+        for url in &staged.urls {
+            for job_id in [job_id.to_owned(), verification_job_id(job_id)] {
+                let prefix = job_staging_prefix(url, &job_id);
+                match remove_staging_prefix(&staged.runtime, &prefix).await {
+                    Ok(0) => {}
+                    Ok(deleted) => {
+                        info!("Deleted {deleted} staged objects of job {job_id} under {prefix}")
+                    }
+                    Err(e) => {
+                        warn!("Failed to delete the staged objects of job {job_id} under {prefix}: {e}")
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::extension::SessionConfigExt;
+    use datafusion::execution::object_store::ObjectStoreUrl;
+    use futures::TryStreamExt;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    #[tokio::test]
+    async fn test_job_staging() -> datafusion::error::Result<()> {
+        let runtime = Arc::new(RuntimeEnv::default());
+        let store = Arc::new(InMemory::new());
+        runtime.register_object_store(
+            ObjectStoreUrl::parse("memory://bucket")?.as_ref(),
+            store.clone(),
+        );
+        let config = SessionConfig::new_with_ballista()
+            .with_ballista_job_staging_url("memory://bucket/staging")
+            .with_ballista_shuffle_object_store_url("memory://bucket/shuffle");
+        for path in [
+            "staging/job/literals.arrow",
+            "shuffle/job/1/0/data.arrow",
+            "shuffle/job-verify/1/0/data.arrow",
+            "shuffle/job-iteration/1/0/data.arrow",
+            "shuffle/other-job/1/0/data.arrow",
+        ] {
+            store.put(&Path::from(path), "data".into()).await?;
+        }
+
+        let staging = JobStaging::default();
+        staging.register("job", &config, runtime.clone());
+        staging.register_internal("job", "job-iteration");
+        // jobs without staging URL have no prefix
+        staging.register("other-job", &SessionConfig::new_with_ballista(), runtime);
+        assert_eq!(
+            vec!["memory://bucket/staging/job", "memory://bucket/shuffle/job"],
+            staging.prefixes("job")
+        );
+        assert!(staging.prefixes("other-job").is_empty());
+
+        staging.remove_job("job").await;
+        staging.remove_job("job-iteration").await;
+        assert!(staging.prefixes("job").is_empty());
+        let left: Vec<String> = store
+            .list(None)
+            .map_ok(|meta| meta.location.to_string())
+            .try_collect()
+            .await?;
+        assert_eq!(vec!["shuffle/other-job/1/0/data.arrow"], left);
+
+        Ok(())
+    }
+}
@@ -15,43 +15,88 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
 use datafusion::datasource::listing::{ListingTable, ListingTableUrl};
 use datafusion::datasource::source_as_provider;
 use datafusion::error::DataFusionError;
-use datafusion::physical_plan::{ExecutionPlan, ExecutionPlanProperties};
 use std::any::type_name;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::timestamp_millis;
 
+use crate::state::cluster_catalog::ClusterCatalog;
+use crate::state::event_log::EventLog;
+use crate::state::exchange_calibration::ExchangeCalibration;
 use crate::state::executor_manager::ExecutorManager;
+use crate::state::executor_telemetry::ClusterUtilization;
+use crate::state::explain::explain_distributed_plan;
+use crate::state::historical_statistics::{scanned_tables, HistoricalStatistics};
+use crate::state::job_retention::JobArchive;
+use crate::state::plan_cache::PlanCache;
+use crate::state::query_cache::{query_fingerprint, QueryCache, QueryCacheHit};
+use crate::state::recursive_query::{RecursiveQueries, RecursiveQueryContext};
+use crate::state::result_retention::{ResultRetention, RetainedResults};
 use crate::state::session_manager::SessionManager;
-use crate::state::task_manager::{TaskLauncher, TaskManager};
+use crate::state::shadow_execution::{
+    ResultDigest, ShadowComparison, ShadowExecution, ShadowOutcome,
+};
+use crate::state::slot_reservations::SlotReservations;
+use crate::state::stage_verification::verified_job_id;
+use crate::state::task_manager::{JobUpdates, TaskLauncher, TaskManager};
+use crate::state::webhooks::JobWebhooks;
 
 use crate::cluster::{BallistaCluster, BoundTask, ExecutorSlot};
-use crate::config::SchedulerConfig;
+use crate::config::{SchedulerConfig, TaskPlanFormat};
+use crate::deterministic::deterministic_plan;
+use crate::metrics::cluster::{ClusterMetrics, ClusterMetricsSample};
 use crate::state::execution_graph::TaskDescription;
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::event_loop::EventSender;
-use ballista_core::serde::protobuf::TaskStatus;
+use ballista_core::extension::{SessionConfigExt, SessionConfigHelperExt};
+use ballista_core::planner::create_job_physical_plan;
+use ballista_core::recursive_query::decode_recursive_queries;
+use ballista_core::registry::BallistaFunctionRegistry;
+use ballista_core::security::FetchToken;
+use ballista_core::serde::protobuf::{job_status, JobStatus, TaskStatus};
 use ballista_core::serde::BallistaCodec;
-use datafusion::logical_expr::LogicalPlan;
-use datafusion::physical_plan::display::DisplayableExecutionPlan;
-use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::logical_expr::{Expr, Limit, LogicalPlan, WindowFunctionDefinition};
 use datafusion::prelude::SessionContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 use log::{debug, error, info, warn};
 use prost::Message;
 
+pub mod cluster_catalog;
+pub mod event_log;
+pub mod exchange_calibration;
 pub mod execution_graph;
 pub mod execution_graph_dot;
 pub mod executor_manager;
+pub mod executor_registration;
+pub mod executor_settings;
+pub mod executor_telemetry;
+pub mod explain;
+pub mod historical_statistics;
+pub mod job_driver;
+pub mod job_events;
+pub mod job_metrics;
+pub mod job_results;
+pub mod job_retention;
+pub mod job_staging;
+pub mod plan_cache;
+pub mod query_cache;
+pub mod recursive_query;
+pub mod result_retention;
 pub mod session_manager;
+pub mod shadow_execution;
+pub mod slot_reservations;
+pub mod stage_sample;
+pub mod stage_verification;
 pub mod task_manager;
+pub mod webhooks;
 
 pub fn decode_protobuf<T: Message + Default>(bytes: &[u8]) -> Result<T> {
     T::decode(bytes).map_err(|e| {
@@ -75,6 +120,71 @@ pub fn decode_into<T: Message + Default + Into<U>, U>(bytes: &[u8]) -> Result<U>
         .map(|t| t.into())
 }
 
+/// Limit the rows returned by a query to one more than `max_rows`, so that results
+/// exceeding the limit can be flagged as truncated once the job completes
+fn limit_result_rows(plan: &LogicalPlan, max_rows: usize) -> LogicalPlan {
+    match plan {
+        LogicalPlan::Dml(_)
+        | LogicalPlan::Copy(_)
+        | LogicalPlan::Ddl(_)
+        | LogicalPlan::Statement(_)
+        | LogicalPlan::Explain(_) => plan.clone(),
+        plan => LogicalPlan::Limit(Limit {
+            skip: 0,
+            fetch: Some(max_rows + 1),
+            input: Arc::new(plan.clone()),
+        }),
+    }
+}
+
+/// The catalog shared by the sessions, if enabled
+fn cluster_catalog(config: &SchedulerConfig) -> Option<Arc<ClusterCatalog>> {
+    config
+        .cluster_catalog
+        .then(|| Arc::new(ClusterCatalog::new(config.cluster_catalog_dir.clone())))
+}
+
+/// Names of the scalar, aggregate and window functions referenced by a plan and its
+/// subqueries, which executors look up in their function registry
+fn referenced_functions(plan: &LogicalPlan) -> Result<BTreeSet<String>> {
+    let mut functions = BTreeSet::new();
+    plan.apply_with_subqueries(|plan| {
+        plan.apply_expressions(|expr| {
+            expr.apply(|expr| {
+                let name = match expr {
+                    Expr::ScalarFunction(function) => Some(function.name()),
+                    Expr::AggregateFunction(function) => Some(function.func.name()),
+                    Expr::WindowFunction(function) => match &function.fun {
+                        WindowFunctionDefinition::AggregateUDF(udaf) => Some(udaf.name()),
+                        WindowFunctionDefinition::WindowUDF(udwf) => Some(udwf.name()),
+                        WindowFunctionDefinition::BuiltInWindowFunction(_) => None,
+                    },
+                    _ => None,
+                };
+                if let Some(name) = name {
+                    functions.insert(name.to_owned());
+                }
+                Ok(TreeNodeRecursion::Continue)
+            })
+        })
+    })?;
+    Ok(functions)
+}
+
+/// Whether a plan has recursive queries, which run as iterations of internal jobs
+fn has_recursive_queries(plan: &LogicalPlan) -> Result<bool> {
+    let mut recursive = false;
+    plan.apply_with_subqueries(|plan| {
+        recursive = matches!(plan, LogicalPlan::RecursiveQuery(_));
+        Ok(if recursive {
+            TreeNodeRecursion::Stop
+        } else {
+            TreeNodeRecursion::Continue
+        })
+    })?;
+    Ok(recursive)
+}
+
 pub fn encode_protobuf<T: Message + Default>(msg: &T) -> Result<Vec<u8>> {
     let mut value: Vec<u8> = Vec::with_capacity(msg.encoded_len());
     msg.encode(&mut value).map_err(|e| {
@@ -92,6 +202,17 @@ pub struct SchedulerState<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPl
     pub executor_manager: ExecutorManager,
     pub task_manager: TaskManager<T, U>,
     pub session_manager: SessionManager,
+    pub plan_cache: PlanCache,
+    pub query_cache: QueryCache,
+    pub shadow_execution: ShadowExecution,
+    pub recursive_queries: RecursiveQueries,
+    pub webhooks: JobWebhooks,
+    pub event_log: Option<EventLog>,
+    pub cluster_metrics: ClusterMetrics,
+    pub exchange_calibration: Arc<ExchangeCalibration>,
+    pub historical_statistics: Arc<HistoricalStatistics>,
+    pub slot_reservations: Arc<SlotReservations>,
+    pub result_retention: Arc<ResultRetention>,
     pub codec: BallistaCodec<T, U>,
     pub config: Arc<SchedulerConfig>,
 }
@@ -103,17 +224,49 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
         scheduler_name: String,
         config: Arc<SchedulerConfig>,
     ) -> Self {
+        let cluster_metrics = ClusterMetrics::new(config.cluster_metrics_retention_count);
+        let slot_reservations = Arc::new(SlotReservations::new(cluster.job_state()));
+        let cluster_catalog = cluster_catalog(&config);
         Self {
             executor_manager: ExecutorManager::new(
                 cluster.cluster_state(),
                 config.clone(),
+                slot_reservations.clone(),
             ),
             task_manager: TaskManager::new(
                 cluster.job_state(),
                 codec.clone(),
                 scheduler_name,
+            )
+            .with_job_archive(JobArchive::from_config(&config))
+            .with_max_failures(config.task_max_failures, config.stage_max_failures)
+            .with_plan_reference_threshold(config.plan_reference_threshold)
+            .with_plan_compression(config.task_plan_compression.clone()),
+            session_manager: SessionManager::new(cluster.job_state())
+                .with_cluster_metrics(cluster_metrics.clone())
+                .with_cluster_catalog(cluster_catalog.clone())
+                .with_resource_profiles(config.resource_profiles.clone()),
+            plan_cache: PlanCache::new(config.plan_cache_size)
+                .with_cluster_catalog(cluster_catalog),
+            query_cache: QueryCache::new(
+                config.query_cache_size,
+                config.query_cache_result_ttl(),
+                config.query_cache_max_result_bytes,
+            ),
+            shadow_execution: ShadowExecution::new(
+                config.shadow_execution_fraction,
+                config.shadow_execution_settings.clone(),
             ),
-            session_manager: SessionManager::new(cluster.job_state()),
+            recursive_queries: RecursiveQueries::default(),
+            webhooks: JobWebhooks::new(config.webhooks.clone()),
+            event_log: EventLog::from_config(&config),
+            cluster_metrics,
+            exchange_calibration: Arc::new(ExchangeCalibration::new(cluster.job_state())),
+            historical_statistics: Arc::new(HistoricalStatistics::new(
+                cluster.job_state(),
+            )),
+            slot_reservations,
+            result_retention: Arc::new(ResultRetention::default()),
             codec,
             config,
         }
@@ -136,25 +289,93 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
         config: Arc<SchedulerConfig>,
         dispatcher: Arc<dyn TaskLauncher>,
     ) -> Self {
+        let cluster_metrics = ClusterMetrics::new(config.cluster_metrics_retention_count);
+        let slot_reservations = Arc::new(SlotReservations::new(cluster.job_state()));
+        let cluster_catalog = cluster_catalog(&config);
         Self {
             executor_manager: ExecutorManager::new(
                 cluster.cluster_state(),
                 config.clone(),
+                slot_reservations.clone(),
             ),
             task_manager: TaskManager::with_launcher(
                 cluster.job_state(),
                 codec.clone(),
                 scheduler_name,
                 dispatcher,
+            )
+            .with_job_archive(JobArchive::from_config(&config))
+            .with_max_failures(config.task_max_failures, config.stage_max_failures)
+            .with_plan_reference_threshold(config.plan_reference_threshold)
+            .with_plan_compression(config.task_plan_compression.clone()),
+            session_manager: SessionManager::new(cluster.job_state())
+                .with_cluster_metrics(cluster_metrics.clone())
+                .with_cluster_catalog(cluster_catalog.clone())
+                .with_resource_profiles(config.resource_profiles.clone()),
+            plan_cache: PlanCache::new(config.plan_cache_size)
+                .with_cluster_catalog(cluster_catalog),
+            query_cache: QueryCache::new(
+                config.query_cache_size,
+                config.query_cache_result_ttl(),
+                config.query_cache_max_result_bytes,
+            ),
+            shadow_execution: ShadowExecution::new(
+                config.shadow_execution_fraction,
+                config.shadow_execution_settings.clone(),
             ),
-            session_manager: SessionManager::new(cluster.job_state()),
+            recursive_queries: RecursiveQueries::default(),
+            webhooks: JobWebhooks::new(config.webhooks.clone()),
+            event_log: EventLog::from_config(&config),
+            cluster_metrics,
+            exchange_calibration: Arc::new(ExchangeCalibration::new(cluster.job_state())),
+            historical_statistics: Arc::new(HistoricalStatistics::new(
+                cluster.job_state(),
+            )),
+            slot_reservations,
+            result_retention: Arc::new(ResultRetention::default()),
             codec,
             config,
         }
     }
 
     pub async fn init(&self) -> Result<()> {
-        self.executor_manager.init().await
+        if let Some(cluster_catalog) = self.session_manager.cluster_catalog() {
+            cluster_catalog.load()?;
+        }
+        self.executor_manager.init().await?;
+        self.slot_reservations.init().await?;
+        self.historical_statistics.init().await?;
+        self.exchange_calibration.init().await?;
+        Ok(())
+    }
+
+    /// Calibrate the cost model of the exchanges from a job which succeeded
+    pub(crate) async fn calibrate_exchanges(&self, job_id: &str) {
+        let Some(graph) = self.task_manager.get_active_execution_graph(job_id) else {
+            return;
+        };
+        let graph = graph.read().await;
+        if let Err(e) = self.exchange_calibration.record_job(&graph).await {
+            warn!("Failed to calibrate the exchanges from job {job_id}: {e:?}");
+        }
+    }
+
+    /// Record the sizes of the tables scanned whole by a job which succeeded
+    pub(crate) async fn record_table_statistics(&self, job_id: &str) {
+        let Some(graph) = self.task_manager.get_active_execution_graph(job_id) else {
+            return;
+        };
+        let tables = scanned_tables(&*graph.read().await);
+        if tables.is_empty() {
+            return;
+        }
+        if let Err(e) = self
+            .historical_statistics
+            .record(job_id, tables, timestamp_millis())
+            .await
+        {
+            warn!("Failed to record the statistics of the tables of job {job_id}: {e:?}");
+        }
     }
 
     pub(crate) async fn revive_offers(
@@ -215,11 +436,17 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
     ) {
         if let Err(e) = self
             .executor_manager
-            .remove_executor(executor_id, reason)
+            .remove_executor(executor_id, reason.clone())
             .await
         {
             warn!("Fail to remove executor {}: {}", executor_id, e);
         }
+        self.query_cache.remove_executor(executor_id);
+        if let Some(event_log) = &self.event_log {
+            if let Err(e) = event_log.executor_lost(executor_id, reason).await {
+                warn!("Fail to log the loss of executor {executor_id}: {e}");
+            }
+        }
 
         match self.task_manager.executor_lost(executor_id).await {
             Ok(tasks) => {
@@ -256,6 +483,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             HashMap<(String, usize), Vec<TaskDescription>>,
         > = HashMap::new();
         for (executor_id, task) in bound_tasks.into_iter() {
+            if let Some(event_log) = &self.event_log {
+                event_log.task_started(&executor_id, &task);
+            }
             let stage_key = (task.partition.job_id.clone(), task.partition.stage_id);
             if let Some(tasks) = executor_stage_assignments.get_mut(&executor_id) {
                 if let Some(executor_stage_tasks) = tasks.get_mut(&stage_key) {
@@ -333,21 +563,143 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             .collect::<Vec<ExecutorSlot>>())
     }
 
+    #[cfg(test)]
     pub(crate) async fn update_task_statuses(
         &self,
         executor_id: &str,
         tasks_status: Vec<TaskStatus>,
     ) -> Result<Vec<QueryStageSchedulerEvent>> {
+        let (mut events, updates) = self
+            .dispatch_task_statuses(executor_id, tasks_status)
+            .await?;
+        events.extend(self.await_task_statuses(updates).await);
+
+        Ok(events)
+    }
+
+    /// Queue the task statuses reported by an executor to the drivers of their jobs,
+    /// returning the events of the verified stages they completed right away
+    pub(crate) async fn dispatch_task_statuses(
+        &self,
+        executor_id: &str,
+        tasks_status: Vec<TaskStatus>,
+    ) -> Result<(Vec<QueryStageSchedulerEvent>, JobUpdates)> {
         let executor = self
             .executor_manager
             .get_executor_metadata(executor_id)
             .await?;
+        self.cluster_metrics.record_task_statuses(&tasks_status);
 
+        let (verification_status, tasks_status): (Vec<_>, Vec<_>) = tasks_status
+            .into_iter()
+            .partition(|status| verified_job_id(&status.job_id).is_some());
+        if let Some(event_log) = &self.event_log {
+            event_log.task_statuses(executor_id, &tasks_status);
+        }
+        let mut events = vec![];
+        for status in &verification_status {
+            events.extend(
+                self.task_manager
+                    .stage_verification()
+                    .complete(executor_id, status),
+            );
+        }
+        if self.config.is_push_staged_scheduling() {
+            events.extend(
+                self.launch_verification_tasks(executor_id, &tasks_status)
+                    .await,
+            );
+        }
+
+        let updates = self
+            .task_manager
+            .dispatch_task_statuses(&executor, tasks_status);
+        Ok((events, updates))
+    }
+
+    /// Wait for the drivers of the jobs to apply queued task statuses, returning the
+    /// events they resulted in
+    pub(crate) async fn await_task_statuses(
+        &self,
+        updates: JobUpdates,
+    ) -> Vec<QueryStageSchedulerEvent> {
         self.task_manager
-            .update_task_statuses(&executor, tasks_status)
+            .await_job_updates(updates)
             .await
+            .into_iter()
+            .filter_map(|event| {
+                self.task_manager
+                    .stage_verification()
+                    .defer_completion(event)
+            })
+            .collect()
+    }
+
+    /// Execute the successful tasks of verified stages a second time, on another
+    /// executor than the one which executed them, returning the completion events of
+    /// jobs whose verifications could not be launched
+    async fn launch_verification_tasks(
+        &self,
+        executor_id: &str,
+        tasks_status: &[TaskStatus],
+    ) -> Vec<QueryStageSchedulerEvent> {
+        let verification_tasks = self
+            .task_manager
+            .prepare_verification_tasks(tasks_status)
+            .await;
+        if verification_tasks.is_empty() {
+            return vec![];
+        }
+
+        let Some(verifying_executor_id) = self
+            .executor_manager
+            .get_alive_executors()
+            .into_iter()
+            .find(|id| id != executor_id)
+        else {
+            warn!("No executor other than {executor_id} to verify stages on");
+            return vec![];
+        };
+        let verifying_executor = match self
+            .executor_manager
+            .get_executor_metadata(&verifying_executor_id)
+            .await
+        {
+            Ok(executor) => executor,
+            Err(e) => {
+                warn!("Fail to verify stages on executor {verifying_executor_id}: {e}");
+                return vec![];
+            }
+        };
+
+        let (statuses, tasks): (Vec<_>, Vec<_>) = verification_tasks.into_iter().unzip();
+        for status in &statuses {
+            self.task_manager.stage_verification().expect(
+                executor_id,
+                &verifying_executor_id,
+                status,
+            );
+        }
+        match self
+            .task_manager
+            .launch_verification_tasks(&verifying_executor, tasks, &self.executor_manager)
+            .await
+        {
+            Ok(()) => vec![],
+            Err(e) => {
+                warn!("Fail to launch verification tasks on executor {verifying_executor_id}: {e}");
+                statuses
+                    .iter()
+                    .filter_map(|status| {
+                        self.task_manager.stage_verification().abandon(status)
+                    })
+                    .collect()
+            }
+        }
     }
 
+    /// Plan and submit a queued job. Returns the ID of the earlier job whose cached
+    /// results the job succeeded with right away, if it was served from the query cache
     pub(crate) async fn submit_job(
         &self,
         job_id: &str,
@@ -355,16 +707,84 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
         session_ctx: Arc<SessionContext>,
         plan: &LogicalPlan,
         queued_at: u64,
-    ) -> Result<()> {
+        event_sender: &EventSender<QueryStageSchedulerEvent>,
+    ) -> Result<Option<String>> {
         let start = Instant::now();
-        let session_config = Arc::new(session_ctx.copied_config());
-        if log::max_level() >= log::Level::Debug {
-            // optimizing the plan here is redundant because the physical planner will do this again
-            // but it is helpful to see what the optimized plan will be
-            let optimized_plan = session_ctx.state().optimize(plan)?;
-            debug!("Optimized plan: {}", optimized_plan.display_indent());
+        let decoded = decode_recursive_queries(plan.clone())?;
+        let plan = &decoded;
+        let mut session_config = session_ctx.copied_config();
+        // Sessions can lower the result row limit of the scheduler, but not raise it
+        let max_result_rows = match (
+            self.config.max_result_rows,
+            session_config.ballista_max_result_rows(),
+        ) {
+            (0, session_max) => session_max,
+            (max, 0) => max,
+            (max, session_max) => max.min(session_max),
+        };
+        if max_result_rows > 0 {
+            session_config =
+                session_config.with_ballista_max_result_rows(max_result_rows);
+        }
+        // Executors of other versions of Ballista may not read every codec, in which
+        // case the shuffle partitions are compressed with one they all read
+        if let Ok(compression) = session_config.ballista_shuffle_compression() {
+            let readable = self
+                .executor_manager
+                .readable_shuffle_compression(compression)
+                .await;
+            if readable != compression {
+                warn!(
+                    "Job {job_id} compresses its shuffle partitions with {readable:?} rather than {compression:?}, which not all the executors read"
+                );
+                session_config =
+                    session_config.with_ballista_shuffle_compression(readable);
+            }
+        }
+        // the exchanges of the job are costed with the calibrated settings the session
+        // leaves to the scheduler, and its scans without statistics get the sizes of
+        // their tables, which the executors planning its stages get too
+        if let Some(bandwidth) =
+            self.executor_manager.telemetry().network_bytes_per_second()
+        {
+            if let Err(e) = self
+                .exchange_calibration
+                .record_network_bytes_per_second(bandwidth)
+                .await
+            {
+                warn!("Failed to save the network bandwidth of the executors: {e:?}");
+            }
+        }
+        let mut calibrated_settings = self.exchange_calibration.settings(&session_config);
+        calibrated_settings
+            .extend(self.historical_statistics.settings(&session_config, plan)?);
+        session_config.update_from_key_value_pair_mut(&calibrated_settings);
+        // the executors checksum the results of the jobs of shadow executions, which
+        // are compared without being read
+        if self.shadow_execution.is_tracked(job_id) {
+            session_config = session_config.with_ballista_checksum_results(true);
         }
+        let session_config = Arc::new(session_config);
+        self.executor_manager.job_staging().register(
+            job_id,
+            &session_config,
+            session_ctx.runtime_env(),
+        );
 
+        let deterministic;
+        let plan = if session_config.ballista_deterministic() {
+            deterministic = deterministic_plan(plan.clone())?;
+            &deterministic
+        } else {
+            plan
+        };
+        let limited;
+        let plan = if max_result_rows > 0 {
+            limited = limit_result_rows(plan, max_result_rows);
+            &limited
+        } else {
+            plan
+        };
         plan. apply(&mut |plan: &LogicalPlan| {
             if let LogicalPlan::TableScan(scan) = plan {
                 let provider = source_as_provider(&scan.source)?;
@@ -404,62 +824,449 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             Ok(TreeNodeRecursion::Continue)
         })?;
 
-        let plan = session_ctx.state().create_physical_plan(plan).await?;
-        debug!(
-            "Physical plan: {}",
-            DisplayableExecutionPlan::new(plan.as_ref()).indent(false)
-        );
-
-        let plan = plan.transform_down(&|node: Arc<dyn ExecutionPlan>| {
-            if node.output_partitioning().partition_count() == 0 {
-                let empty: Arc<dyn ExecutionPlan> =
-                    Arc::new(EmptyExec::new(node.schema()));
-                Ok(Transformed::yes(empty))
-            } else {
-                Ok(Transformed::no(node))
+        let mut state = session_ctx.state();
+        state
+            .config_mut()
+            .update_from_key_value_pair_mut(&calibrated_settings);
+        let plan = state.optimize(plan)?;
+        debug!("Optimized plan: {}", plan.display_indent());
+        if self.config.validate_job_functions {
+            self.validate_functions(&plan).await?;
+        }
+        if let LogicalPlan::Explain(explain) = &plan {
+            // the plan of the query is explained rather than run
+            let format = session_config
+                .ballista_explain_format()
+                .map_err(BallistaError::General)?;
+            let query_plan = create_job_physical_plan(state, &explain.plan).await?;
+            let plan = explain_distributed_plan(job_id, explain, query_plan, format)?;
+            self.task_manager
+                .submit_job(
+                    job_id,
+                    job_name,
+                    &session_ctx.session_id(),
+                    plan,
+                    None,
+                    queued_at,
+                    session_config,
+                )
+                .await?;
+            info!("Planned job {} in {:?}", job_id, start.elapsed());
+            return Ok(None);
+        }
+        let recursive = has_recursive_queries(&plan)?;
+        // shadow jobs are compared with the jobs they shadow, so both of them run
+        let fingerprint = if self.query_cache.is_enabled()
+            && !recursive
+            && !self.recursive_queries.is_internal(job_id)
+            && !self.shadow_execution.is_tracked(job_id)
+        {
+            query_fingerprint(&state, &plan).await?
+        } else {
+            None
+        };
+        let cached =
+            fingerprint.and_then(|fingerprint| self.query_cache.get(fingerprint));
+        if let Some(QueryCacheHit::Results(results)) = &cached {
+            self.task_manager
+                .succeed_unscheduled_job(job_id, results.to_successful_job())
+                .await?;
+            info!(
+                "Served job {} from the cached results of job {}",
+                job_id, results.job_id
+            );
+            return Ok(Some(results.job_id.clone()));
+        }
+        // the recursive queries of the plan are replaced by the results of their
+        // iterations, so that the plans of the stages have to be dispatched
+        let logical_plan = match self.config.task_plan_format {
+            TaskPlanFormat::Logical if !recursive => {
+                self.encode_logical_plan(job_id, &plan)
             }
-        })?;
-        debug!(
-            "Transformed physical plan: {}",
-            DisplayableExecutionPlan::new(plan.data.as_ref()).indent(false)
-        );
+            _ => None,
+        };
+        let (mut plan, planned_from_cache) = match cached {
+            Some(QueryCacheHit::Plan(plan)) => (plan, true),
+            _ => (create_job_physical_plan(state, &plan).await?, false),
+        };
+        if let Some(fingerprint) = fingerprint {
+            self.query_cache.insert_job(
+                job_id,
+                fingerprint,
+                (!planned_from_cache).then(|| plan.clone()),
+            );
+        }
+        let session_config = if recursive {
+            // the job and the iterations of its recursive queries read the output
+            // partitions of each other, so they share the same fetch token
+            let fetch_token = FetchToken::generate();
+            let ctx = RecursiveQueryContext {
+                job_id,
+                job_name,
+                session_id: &session_ctx.session_id(),
+                // the results of the iterations are never truncated
+                session_config: Arc::new(
+                    session_ctx
+                        .copied_config()
+                        .with_ballista_max_result_rows(0)
+                        .with_ballista_fetch_token(fetch_token.clone()),
+                ),
+                event_sender,
+            };
+            plan = self.materialize_recursive_queries(&ctx, plan).await?;
+            Arc::new(
+                session_config
+                    .as_ref()
+                    .clone()
+                    .with_ballista_fetch_token(fetch_token),
+            )
+        } else {
+            session_config
+        };
 
         self.task_manager
             .submit_job(
                 job_id,
                 job_name,
                 &session_ctx.session_id(),
-                plan.data,
+                plan,
+                logical_plan,
                 queued_at,
                 session_config,
             )
             .await?;
+        if planned_from_cache {
+            self.task_manager.set_planned_from_cache(job_id).await;
+        }
 
         let elapsed = start.elapsed();
 
         info!("Planned job {} in {:?}", job_id, elapsed);
 
-        Ok(())
+        Ok(None)
     }
 
-    /// Spawn a delayed future to clean up job data on both Scheduler and Executors
+    /// Check that the functions referenced by a plan are registered on all the alive
+    /// executors, so that the job is rejected rather than failing its tasks
+    async fn validate_functions(&self, plan: &LogicalPlan) -> Result<()> {
+        let builtins = BallistaFunctionRegistry::default();
+        let mut functions = referenced_functions(plan)?;
+        functions.retain(|function| !builtins.contains(function));
+        if functions.is_empty() {
+            return Ok(());
+        }
+        let missing = self.executor_manager.missing_functions(&functions).await;
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let missing = missing
+            .iter()
+            .map(|(function, executors)| {
+                format!("{function} (missing on {})", executors.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(BallistaError::General(format!(
+            "The query references functions which are not registered on all executors: {missing}"
+        )))
+    }
+
+    /// Encode the optimized logical plan of a job to dispatch to executors, or none if it
+    /// can't be serialized, e.g. because it scans in-memory tables, in which case the
+    /// physical plans of the stages of the job are dispatched instead
+    fn encode_logical_plan(&self, job_id: &str, plan: &LogicalPlan) -> Option<Vec<u8>> {
+        let mut buf = vec![];
+        match T::try_from_logical_plan(plan, self.codec.logical_extension_codec())
+            .and_then(|proto| proto.try_encode(&mut buf))
+        {
+            Ok(()) => Some(buf),
+            Err(e) => {
+                warn!(
+                    "Dispatching physical plans for job {job_id}, whose logical plan can't be serialized: {e}"
+                );
+                None
+            }
+        }
+    }
+
+    /// Take a sample of the cluster-wide metrics from the current load of the cluster
+    pub(crate) async fn sample_cluster_metrics(&self) -> ClusterMetricsSample {
+        let executors = self.executor_manager.get_alive_executors();
+        let mut task_slots = 0;
+        for executor_id in &executors {
+            match self
+                .executor_manager
+                .get_executor_metadata(executor_id)
+                .await
+            {
+                Ok(metadata) => task_slots += metadata.specification.task_slots as usize,
+                Err(e) => warn!("Failed to get metadata of executor {executor_id}: {e}"),
+            }
+        }
+
+        self.cluster_metrics.sample(
+            timestamp_millis(),
+            self.task_manager.running_job_number(),
+            self.task_manager.pending_job_number(),
+            executors.len(),
+            task_slots,
+        )
+    }
+
+    /// The demand for and utilization of the resources of the cluster
+    pub(crate) async fn cluster_utilization(&self) -> ClusterUtilization {
+        ClusterUtilization::new(
+            self.task_manager.running_job_number(),
+            self.task_manager.pending_job_number(),
+            self.task_manager.pending_task_number().await,
+            self.executor_manager.executor_utilization().await,
+        )
+    }
+
+    /// Spawn a delayed future to clean up job data on both Scheduler and Executors. The
+    /// results of the job are deleted once their time to live expires, unless the
+    /// client released them earlier
     pub(crate) fn clean_up_successful_job(&self, job_id: String) {
-        self.executor_manager.clean_up_job_data_delayed(
-            job_id.clone(),
-            self.config.finished_job_data_clean_up_interval_seconds,
-        );
+        // the data of internal jobs is read by the job they were run for
+        if !self.recursive_queries.is_internal(&job_id) {
+            for internal_job_id in self.recursive_queries.take(&job_id) {
+                self.executor_manager.clean_up_job_data_delayed(
+                    internal_job_id,
+                    self.config.finished_job_data_clean_up_interval_seconds,
+                );
+            }
+            let result_ttl_seconds = self
+                .result_retention
+                .get(&job_id, timestamp_millis())
+                .map(|retained| retained.ttl_seconds)
+                .unwrap_or(self.config.finished_job_data_clean_up_interval_seconds);
+            self.executor_manager
+                .clean_up_job_data_delayed(job_id.clone(), result_ttl_seconds);
+        }
         self.task_manager.clean_up_job_delayed(
             job_id,
             self.config.finished_job_state_clean_up_interval_seconds,
         );
     }
 
+    /// Cache the results of a job which succeeded, if its query is in the query cache
+    pub(crate) async fn cache_job_results(&self, job_id: &str) {
+        if !self.query_cache.is_enabled() {
+            return;
+        }
+        // results deleted before the cached results expire are not cached
+        let result_ttl = self
+            .result_retention
+            .get(job_id, timestamp_millis())
+            .map(|retained| Duration::from_secs(retained.ttl_seconds));
+        if result_ttl.is_some_and(|ttl| {
+            !ttl.is_zero() && ttl < self.config.query_cache_result_ttl()
+        }) {
+            self.query_cache.remove_job(job_id);
+            return;
+        }
+        match self.task_manager.get_job_status(job_id).await {
+            Ok(Some(JobStatus {
+                status: Some(job_status::Status::Successful(job)),
+                ..
+            })) => self.query_cache.job_succeeded(job_id, &job),
+            Ok(_) => self.query_cache.remove_job(job_id),
+            Err(e) => {
+                warn!("Failed to cache the results of job {job_id}: {e:?}");
+                self.query_cache.remove_job(job_id)
+            }
+        }
+    }
+
+    /// Retain the results of a job which succeeded until the client releases them, or
+    /// for the result TTL of the job, `ballista.job.result_ttl_seconds`, which defaults
+    /// to the finished job data clean up interval of the scheduler
+    pub(crate) async fn retain_job_results(&self, job_id: &str) {
+        // the data of internal jobs is read by the job they were run for
+        if self.recursive_queries.is_internal(job_id) {
+            return;
+        }
+        let graph = match self.task_manager.get_job_execution_graph(job_id).await {
+            Ok(Some(graph)) => graph,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to retain the results of job {job_id}: {e:?}");
+                return;
+            }
+        };
+        let Some(job_status::Status::Successful(job)) = &graph.status().status else {
+            return;
+        };
+        let ttl_seconds = match graph.session_config().ballista_job_result_ttl_seconds() {
+            0 => self.config.finished_job_data_clean_up_interval_seconds,
+            ttl_seconds => ttl_seconds as u64,
+        };
+        let retained = self.result_retention.retain(
+            job_id,
+            &job.partition_location,
+            ttl_seconds,
+            timestamp_millis(),
+        );
+        debug!(
+            "Retaining {} bytes of results of job {job_id} for {ttl_seconds}s",
+            retained.bytes
+        );
+    }
+
+    /// Delete the results of a job the client fetched, along with the rest of its
+    /// data on the executors. Returns the released results, unless they were not
+    /// retained anymore
+    pub(crate) fn release_job_results(&self, job_id: &str) -> Option<RetainedResults> {
+        let released = self.result_retention.release(job_id, timestamp_millis())?;
+        self.query_cache.remove_job(job_id);
+        self.executor_manager.clean_up_job_data(job_id.to_owned());
+        info!(
+            "Released {} bytes of results of job {job_id}",
+            released.bytes
+        );
+
+        Some(released)
+    }
+
+    /// Record the submission of a job and the stages it was planned into to the
+    /// event log, if any
+    pub(crate) async fn log_job_submitted(&self, job_id: &str, submitted_at: u64) {
+        let Some(event_log) = &self.event_log else {
+            return;
+        };
+        if let Some(graph) = self.task_manager.get_active_execution_graph(job_id) {
+            event_log.job_submitted(&*graph.read().await, submitted_at);
+        }
+    }
+
+    /// Write the events of a completed job to the event log, if any
+    pub(crate) async fn log_job_completed(&self, job_id: &str) {
+        let Some(event_log) = &self.event_log else {
+            return;
+        };
+        let status = match self.task_manager.get_job_status(job_id).await {
+            Ok(Some(status)) => status,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to log the completion of job {job_id}: {e:?}");
+                return;
+            }
+        };
+        // writing to the object store shouldn't hold up the event loop
+        let event_log = event_log.clone();
+        tokio::spawn(async move {
+            if let Err(e) = event_log.job_completed(&status).await {
+                warn!(
+                    "Failed to log the completion of job {}: {e:?}",
+                    status.job_id
+                );
+            }
+        });
+    }
+
+    /// Record the outcome of a job taking part in a shadow execution, `failure` being
+    /// the reason the job failed if it did. Returns the comparison of the job with its
+    /// pair once both of them finished
+    pub(crate) async fn record_shadow_outcome(
+        &self,
+        job_id: &str,
+        runtime_ms: u64,
+        failure: Option<String>,
+    ) -> Option<ShadowComparison> {
+        let result = match failure {
+            Some(failure) => Err(failure),
+            None => match self.task_manager.get_job_status(job_id).await {
+                Ok(Some(status)) => ResultDigest::of_job(&status)
+                    .map_err(|e| format!("Failed to digest result: {e}")),
+                Ok(None) => Err("Unknown job".to_owned()),
+                Err(e) => Err(format!("Failed to get job status: {e}")),
+            },
+        };
+
+        let comparison = self
+            .shadow_execution
+            .record_outcome(job_id, ShadowOutcome { runtime_ms, result })?;
+        if comparison.results_match() {
+            info!(
+                "Shadow job {} matches job {}, runtime {}ms vs {}ms",
+                comparison.shadow_job_id,
+                comparison.job_id,
+                comparison.shadow.runtime_ms,
+                comparison.primary.runtime_ms
+            );
+        } else {
+            warn!(
+                "Shadow job {} does not match job {}: {:?} vs {:?}",
+                comparison.shadow_job_id,
+                comparison.job_id,
+                comparison.shadow,
+                comparison.primary
+            );
+        }
+
+        Some(comparison)
+    }
+
+    /// Purge the given completed jobs from the state and clean up their data on
+    /// Executors. Jobs which are unknown or not completed are skipped. Returns the
+    /// IDs of the purged jobs
+    pub(crate) async fn purge_jobs(&self, job_ids: Vec<String>) -> Result<Vec<String>> {
+        let mut purged = vec![];
+        for job_id in job_ids {
+            if self.task_manager.purge_job(&job_id).await? {
+                self.query_cache.remove_job(&job_id);
+                self.result_retention.release(&job_id, timestamp_millis());
+                self.executor_manager.clean_up_job_data(job_id.clone());
+                purged.push(job_id);
+            } else {
+                debug!("Skip purging job {job_id} which is unknown or not completed");
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Purge the completed jobs falling outside of the retention policy from the state
+    pub(crate) async fn compact_finished_jobs(&self) -> Result<Vec<String>> {
+        let completed = self.task_manager.get_completed_jobs().await?;
+        let to_purge = self
+            .config
+            .finished_job_retention()
+            .jobs_to_purge(&completed, timestamp_millis());
+
+        self.purge_jobs(to_purge).await
+    }
+
     /// Spawn a delayed future to clean up job data on both Scheduler and Executors
     pub(crate) fn clean_up_failed_job(&self, job_id: String) {
+        self.clean_up_internal_jobs(&job_id);
         self.executor_manager.clean_up_job_data(job_id.clone());
         self.task_manager.clean_up_job_delayed(
             job_id,
             self.config.finished_job_state_clean_up_interval_seconds,
         );
     }
+
+    /// Notify the webhooks of a job submitted by a client, once it completed
+    pub(crate) async fn notify_webhooks(&self, job_id: &str) {
+        if !self.webhooks.is_watched(job_id) {
+            return;
+        }
+        match self.task_manager.get_job_status(job_id).await {
+            Ok(Some(status)) => self.webhooks.notify(&status),
+            Ok(None) => warn!("Not notifying the webhooks of unknown job {job_id}"),
+            Err(e) => {
+                warn!("Failed to notify the webhooks of job {job_id}: {e:?}")
+            }
+        }
+    }
+
+    /// Clean up the data of the internal jobs run for the recursive queries of a job
+    /// which failed
+    pub(crate) fn clean_up_internal_jobs(&self, job_id: &str) {
+        for internal_job_id in self.recursive_queries.take(job_id) {
+            self.executor_manager.clean_up_job_data(internal_job_id);
+        }
+    }
 }
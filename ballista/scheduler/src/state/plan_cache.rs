@@ -0,0 +1,367 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cache of the optimized logical plans of SQL queries, so that repeat submissions
+//! of the same SQL skip parsing, view expansion, analysis and optimization.
+
+use crate::metrics::SchedulerMetricsCollector;
+use crate::state::cluster_catalog::ClusterCatalog;
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::error::Result;
+use datafusion::logical_expr::{Expr, LogicalPlan, Volatility};
+use datafusion::prelude::SessionContext;
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PlanCacheKey {
+    session_id: String,
+    sql: String,
+    /// Fingerprint of the session settings the plan was created with
+    settings_version: u64,
+    /// Version of the session's tables and functions the plan was created with
+    session_version: u64,
+    /// Version of the cluster catalog the plan was created with
+    cluster_catalog_version: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CachedPlan {
+    /// The plan created from the SQL, which is authorized again on every submission
+    logical: LogicalPlan,
+    optimized: LogicalPlan,
+}
+
+#[derive(Default)]
+struct PlanCacheEntries {
+    /// Cached plans, along with the tick they were last used at
+    plans: HashMap<PlanCacheKey, (CachedPlan, u64)>,
+    tick: u64,
+    /// Version of the tables and functions of the sessions, bumped whenever they
+    /// may have been registered or deregistered
+    session_versions: HashMap<String, u64>,
+}
+
+/// Least recently used cache of the optimized logical plans of SQL queries, keyed by
+/// session, SQL text, a fingerprint of the session's settings and the versions of
+/// the session's tables and of the cluster catalog.
+///
+/// Only plans which are pure queries are cached, i.e. no DDL, DML or statements, and
+/// no expressions whose result may change between executions, such as `now()`.
+#[derive(Clone)]
+pub struct PlanCache {
+    capacity: usize,
+    entries: Arc<Mutex<PlanCacheEntries>>,
+    cluster_catalog: Option<Arc<ClusterCatalog>>,
+}
+
+impl PlanCache {
+    /// Create a cache holding at most `capacity` plans. A capacity of 0 disables caching.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Arc::new(Mutex::new(PlanCacheEntries::default())),
+            cluster_catalog: None,
+        }
+    }
+
+    /// Invalidate the cached plans whenever a table of `cluster_catalog` is
+    /// registered or deregistered
+    pub fn with_cluster_catalog(
+        mut self,
+        cluster_catalog: Option<Arc<ClusterCatalog>>,
+    ) -> Self {
+        self.cluster_catalog = cluster_catalog;
+        self
+    }
+
+    /// Create the optimized logical plan of a SQL query, reusing the cached plan
+    /// of an identical earlier submission if possible. `authorize` is given the plan
+    /// created from the SQL, before its DDL and statements are executed.
+    pub async fn plan_sql(
+        &self,
+        ctx: &SessionContext,
+        sql: &str,
+        metrics_collector: &dyn SchedulerMetricsCollector,
+        authorize: &(dyn Fn(&LogicalPlan) -> Result<()> + Sync),
+    ) -> Result<LogicalPlan> {
+        if self.capacity == 0 {
+            let plan = ctx.state().create_logical_plan(sql).await?;
+            authorize(&plan)?;
+            return ctx.execute_logical_plan(plan).await?.into_optimized_plan();
+        }
+
+        let key = self.key(ctx, sql);
+
+        if let Some(cached) = self.get(&key) {
+            metrics_collector.record_plan_cache_hit();
+            authorize(&cached.logical)?;
+            return Ok(cached.optimized);
+        }
+        metrics_collector.record_plan_cache_miss();
+
+        // check the plan before executing it, as DDL and statements are
+        // executed while creating the DataFrame
+        let logical = ctx.state().create_logical_plan(sql).await?;
+        authorize(&logical)?;
+        let cacheable = is_cacheable(&logical)?;
+        let changes_session = changes_session(&logical);
+        let optimized = ctx.execute_logical_plan(logical.clone()).await;
+        if changes_session {
+            self.bump_session(&key.session_id);
+        }
+        let optimized = optimized?.into_optimized_plan()?;
+        if cacheable {
+            self.insert(
+                key,
+                CachedPlan {
+                    logical,
+                    optimized: optimized.clone(),
+                },
+            );
+        }
+
+        Ok(optimized)
+    }
+
+    /// Invalidate the cached plans of a session, whose tables or functions may
+    /// have changed
+    pub fn bump_session(&self, session_id: &str) {
+        *self
+            .entries
+            .lock()
+            .session_versions
+            .entry(session_id.to_owned())
+            .or_default() += 1;
+    }
+
+    /// Drop the cached plans of a session
+    pub fn remove_session(&self, session_id: &str) {
+        let mut entries = self.entries.lock();
+        entries.plans.retain(|key, _| key.session_id != session_id);
+        entries.session_versions.remove(session_id);
+    }
+
+    fn key(&self, ctx: &SessionContext, sql: &str) -> PlanCacheKey {
+        let session_id = ctx.session_id();
+        let session_version = self
+            .entries
+            .lock()
+            .session_versions
+            .get(&session_id)
+            .copied()
+            .unwrap_or_default();
+        PlanCacheKey {
+            settings_version: settings_version(ctx),
+            session_version,
+            cluster_catalog_version: self
+                .cluster_catalog
+                .as_ref()
+                .map(|catalog| catalog.version())
+                .unwrap_or_default(),
+            session_id,
+            sql: sql.to_owned(),
+        }
+    }
+
+    fn get(&self, key: &PlanCacheKey) -> Option<CachedPlan> {
+        let mut entries = self.entries.lock();
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.plans.get_mut(key).map(|(plan, last_used)| {
+            *last_used = tick;
+            plan.clone()
+        })
+    }
+
+    fn insert(&self, key: PlanCacheKey, plan: CachedPlan) {
+        let mut entries = self.entries.lock();
+        if entries.plans.len() >= self.capacity && !entries.plans.contains_key(&key) {
+            let least_recently_used = entries
+                .plans
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(least_recently_used) = least_recently_used {
+                entries.plans.remove(&least_recently_used);
+            }
+        }
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.plans.insert(key, (plan, tick));
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().plans.len()
+    }
+}
+
+/// Fingerprint of the settings of a session
+fn settings_version(ctx: &SessionContext) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for entry in ctx.state().config_options().entries() {
+        entry.key.hash(&mut hasher);
+        entry.value.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Whether executing the plan may change the tables, functions or settings of its
+/// session
+fn changes_session(plan: &LogicalPlan) -> bool {
+    matches!(plan, LogicalPlan::Ddl(_) | LogicalPlan::Statement(_))
+}
+
+/// Whether the plan is a pure query whose result only depends on the tables it reads
+pub(crate) fn is_cacheable(plan: &LogicalPlan) -> Result<bool> {
+    let mut cacheable = true;
+    plan.apply_with_subqueries(|node| {
+        if matches!(
+            node,
+            LogicalPlan::Ddl(_)
+                | LogicalPlan::Dml(_)
+                | LogicalPlan::Copy(_)
+                | LogicalPlan::Statement(_)
+                | LogicalPlan::DescribeTable(_)
+        ) {
+            cacheable = false;
+            return Ok(TreeNodeRecursion::Stop);
+        }
+
+        node.apply_expressions(|expr| {
+            expr.apply(|expr| match expr {
+                Expr::ScalarFunction(f)
+                    if f.func.signature().volatility != Volatility::Immutable =>
+                {
+                    cacheable = false;
+                    Ok(TreeNodeRecursion::Stop)
+                }
+                _ => Ok(TreeNodeRecursion::Continue),
+            })
+        })
+    })?;
+    Ok(cacheable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::NoopMetricsCollector;
+    use datafusion::arrow::array::Int32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::catalog::{SchemaProvider, TableProvider};
+    use datafusion::datasource::MemTable;
+    use datafusion::error::DataFusionError;
+
+    fn table(value: i32) -> Arc<dyn TableProvider> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![value]))],
+        )
+        .unwrap();
+        Arc::new(MemTable::try_new(schema, vec![vec![batch]]).unwrap())
+    }
+
+    fn allow_all(_plan: &LogicalPlan) -> Result<()> {
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_plan_cache() -> Result<()> {
+        let cluster_catalog = Arc::new(ClusterCatalog::new(None));
+        let cache = PlanCache::new(2).with_cluster_catalog(Some(cluster_catalog.clone()));
+        let metrics = NoopMetricsCollector::default();
+        let ctx = SessionContext::new();
+        ctx.register_table("t", table(1))?;
+
+        let sql = "SELECT a + 1 FROM t";
+        let plan = cache.plan_sql(&ctx, sql, &metrics, &allow_all).await?;
+        assert_eq!(1, cache.len());
+        let key = cache.key(&ctx, sql);
+        assert_eq!(Some(plan), cache.get(&key).map(|cached| cached.optimized));
+
+        // re-creating a table invalidates the cached plans
+        cache
+            .plan_sql(&ctx, "DROP TABLE t", &metrics, &allow_all)
+            .await?;
+        ctx.register_table("t", table(2))?;
+        assert_ne!(key, cache.key(&ctx, sql));
+        cache.plan_sql(&ctx, sql, &metrics, &allow_all).await?;
+        assert_eq!(2, cache.len());
+
+        // so do updating the session and changing the cluster catalog
+        let before = cache.key(&ctx, sql);
+        cache.bump_session(&ctx.session_id());
+        assert_ne!(before, cache.key(&ctx, sql));
+        let before = cache.key(&ctx, sql);
+        cluster_catalog.register_table("u".to_owned(), table(3))?;
+        assert_ne!(before, cache.key(&ctx, sql));
+        let before = cache.key(&ctx, sql);
+        cluster_catalog.deregister_table("u")?;
+        assert_ne!(before, cache.key(&ctx, sql));
+
+        // cached plans are authorized again on every submission
+        let deny = |_: &LogicalPlan| Err(DataFusionError::Plan("denied".to_owned()));
+        assert!(cache.plan_sql(&ctx, sql, &metrics, &deny).await.is_err());
+
+        // and changing a setting
+        let before = cache.key(&ctx, sql);
+        ctx.sql("SET datafusion.execution.batch_size = 1024")
+            .await?;
+        assert_ne!(before, cache.key(&ctx, sql));
+
+        // plans which are not pure queries are not cached
+        cache
+            .plan_sql(
+                &ctx,
+                "SELECT a FROM t WHERE random() > 0.5",
+                &metrics,
+                &allow_all,
+            )
+            .await?;
+        cache
+            .plan_sql(&ctx, "SELECT now()", &metrics, &allow_all)
+            .await?;
+        cache
+            .plan_sql(
+                &ctx,
+                "CREATE VIEW v AS SELECT a FROM t",
+                &metrics,
+                &allow_all,
+            )
+            .await?;
+        assert_eq!(2, cache.len());
+
+        // the least recently used plan is evicted
+        cache
+            .plan_sql(&ctx, "SELECT a FROM t", &metrics, &allow_all)
+            .await?;
+        assert_eq!(2, cache.len());
+        assert!(cache.get(&key).is_none());
+
+        cache.remove_session(&ctx.session_id());
+        assert_eq!(0, cache.len());
+
+        Ok(())
+    }
+}
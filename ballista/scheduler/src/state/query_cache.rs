@@ -0,0 +1,471 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cache of the queries run by the jobs, so that repeat submissions of an identical
+//! query over unchanged tables skip physical planning, and are served the results of
+//! the earlier job while they are small and fresh enough.
+
+use crate::state::plan_cache::is_cacheable;
+use ballista_core::serde::protobuf::{PartitionLocation, SuccessfulJob};
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::datasource::listing::ListingTable;
+use datafusion::datasource::source_as_provider;
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{Expr, LogicalPlan, WindowFunctionDefinition};
+use datafusion::physical_plan::ExecutionPlan;
+use futures::TryStreamExt;
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Results of a job, served to the identical jobs submitted until they expire
+#[derive(Debug, Clone)]
+pub struct CachedResults {
+    /// The job which produced the results, whose output partitions they are
+    pub job_id: String,
+    pub partition_location: Vec<PartitionLocation>,
+    pub truncated_at: Option<u64>,
+    expires_at: Instant,
+}
+
+impl CachedResults {
+    /// The results of a job served from the cache, whose times are set once served
+    pub fn to_successful_job(&self) -> SuccessfulJob {
+        SuccessfulJob {
+            partition_location: self.partition_location.clone(),
+            truncated_at: self.truncated_at,
+            cached_results_job_id: self.job_id.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// What a submitted query was found with in the cache
+#[derive(Debug, Clone)]
+pub enum QueryCacheHit {
+    /// The results of an earlier job, served without running the job
+    Results(CachedResults),
+    /// The distributed plan of an earlier job, run again
+    Plan(Arc<dyn ExecutionPlan>),
+}
+
+struct CachedQuery {
+    plan: Arc<dyn ExecutionPlan>,
+    results: Option<CachedResults>,
+}
+
+#[derive(Default)]
+struct QueryCacheEntries {
+    /// Cached queries by fingerprint, along with the tick they were last used at
+    queries: HashMap<u64, (CachedQuery, u64)>,
+    /// Fingerprints of the running jobs, whose results are cached once they succeed
+    running_jobs: HashMap<String, u64>,
+    tick: u64,
+}
+
+/// Least recently used cache of the physical plans of the jobs, and of their results
+/// when small enough, keyed by the fingerprint of their optimized logical plan, see
+/// [query_fingerprint].
+///
+/// The cached results are the output partitions of the job which produced them, so
+/// they are dropped once their executor is lost or the job is purged, and expire
+/// before the job data is cleaned up from the executors.
+#[derive(Clone)]
+pub struct QueryCache {
+    capacity: usize,
+    result_ttl: Duration,
+    max_result_bytes: u64,
+    entries: Arc<Mutex<QueryCacheEntries>>,
+}
+
+impl QueryCache {
+    /// Create a cache holding at most `capacity` queries, along with the results of
+    /// at most `max_result_bytes` for `result_ttl`. A capacity of 0 disables caching,
+    /// and a `max_result_bytes` of 0 the caching of results.
+    pub fn new(capacity: usize, result_ttl: Duration, max_result_bytes: u64) -> Self {
+        Self {
+            capacity,
+            result_ttl,
+            max_result_bytes,
+            entries: Arc::new(Mutex::new(QueryCacheEntries::default())),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Look up a query by fingerprint, preferring its results to its plan. Expired
+    /// results are dropped
+    pub fn get(&self, fingerprint: u64) -> Option<QueryCacheHit> {
+        let mut entries = self.entries.lock();
+        entries.tick += 1;
+        let tick = entries.tick;
+        let (query, last_used) = entries.queries.get_mut(&fingerprint)?;
+        *last_used = tick;
+        if query
+            .results
+            .as_ref()
+            .is_some_and(|results| results.expires_at <= Instant::now())
+        {
+            query.results = None;
+        }
+        Some(match &query.results {
+            Some(results) => QueryCacheHit::Results(results.clone()),
+            None => QueryCacheHit::Plan(query.plan.clone()),
+        })
+    }
+
+    /// Cache the physical plan of the job `job_id` running a query, whose results are
+    /// cached once it succeeds. Plans reused from the cache are only tracked
+    pub fn insert_job(
+        &self,
+        job_id: &str,
+        fingerprint: u64,
+        plan: Option<Arc<dyn ExecutionPlan>>,
+    ) {
+        let mut entries = self.entries.lock();
+        if let Some(plan) = plan {
+            if entries.queries.len() >= self.capacity
+                && !entries.queries.contains_key(&fingerprint)
+            {
+                let least_recently_used = entries
+                    .queries
+                    .iter()
+                    .min_by_key(|(_, (_, last_used))| *last_used)
+                    .map(|(fingerprint, _)| *fingerprint);
+                if let Some(least_recently_used) = least_recently_used {
+                    entries.queries.remove(&least_recently_used);
+                }
+            }
+            entries.tick += 1;
+            let tick = entries.tick;
+            entries.queries.insert(
+                fingerprint,
+                (
+                    CachedQuery {
+                        plan,
+                        results: None,
+                    },
+                    tick,
+                ),
+            );
+        }
+        if self.max_result_bytes > 0 {
+            entries.running_jobs.insert(job_id.to_owned(), fingerprint);
+        }
+    }
+
+    /// Cache the results of a job which succeeded, unless they are bigger than the
+    /// maximum, incomplete or their query was evicted in the meantime
+    pub fn job_succeeded(&self, job_id: &str, job: &SuccessfulJob) {
+        let mut entries = self.entries.lock();
+        let Some(fingerprint) = entries.running_jobs.remove(job_id) else {
+            return;
+        };
+        let mut result_bytes = 0;
+        for location in &job.partition_location {
+            match &location.partition_stats {
+                Some(stats) if stats.num_bytes >= 0 => {
+                    result_bytes += stats.num_bytes as u64
+                }
+                // results of unknown size are never cached
+                _ => return,
+            }
+        }
+        if result_bytes > self.max_result_bytes || !job.skipped_partitions.is_empty() {
+            return;
+        }
+        if let Some((query, _)) = entries.queries.get_mut(&fingerprint) {
+            query.results = Some(CachedResults {
+                job_id: job_id.to_owned(),
+                partition_location: job.partition_location.clone(),
+                truncated_at: job.truncated_at,
+                expires_at: Instant::now() + self.result_ttl,
+            });
+        }
+    }
+
+    /// Forget a job which failed, or was purged along with its results
+    pub fn remove_job(&self, job_id: &str) {
+        let mut entries = self.entries.lock();
+        entries.running_jobs.remove(job_id);
+        for (query, _) in entries.queries.values_mut() {
+            if query
+                .results
+                .as_ref()
+                .is_some_and(|results| results.job_id == job_id)
+            {
+                query.results = None;
+            }
+        }
+    }
+
+    /// Drop the results with output partitions on a lost executor
+    pub fn remove_executor(&self, executor_id: &str) {
+        for (query, _) in self.entries.lock().queries.values_mut() {
+            if query.results.as_ref().is_some_and(|results| {
+                results.partition_location.iter().any(|location| {
+                    location
+                        .executor_meta
+                        .as_ref()
+                        .is_some_and(|executor| executor.id == executor_id)
+                })
+            }) {
+                query.results = None;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().queries.len()
+    }
+}
+
+/// Fingerprint of an optimized plan, the settings it runs with and the snapshots of
+/// the tables it scans, or `None` if its results may change while these don't.
+///
+/// Only pure queries scanning listing tables are fingerprinted, the snapshot of a
+/// listing table being the size, modification time and ETag of its files. Functions
+/// are told apart by their address, so that the functions of different sessions
+/// registered under the same name never share a fingerprint.
+pub async fn query_fingerprint(
+    state: &SessionState,
+    plan: &LogicalPlan,
+) -> Result<Option<u64>> {
+    if !is_cacheable(plan)? {
+        return Ok(None);
+    }
+
+    let mut hasher = DefaultHasher::new();
+    plan.hash(&mut hasher);
+    for entry in state.config_options().entries() {
+        entry.key.hash(&mut hasher);
+        entry.value.hash(&mut hasher);
+    }
+
+    let mut tables = vec![];
+    let mut cacheable = true;
+    plan.apply_with_subqueries(|plan| {
+        match plan {
+            LogicalPlan::TableScan(scan) => {
+                tables.push(source_as_provider(&scan.source)?)
+            }
+            LogicalPlan::Extension(_) | LogicalPlan::RecursiveQuery(_) => {
+                cacheable = false;
+                return Ok(TreeNodeRecursion::Stop);
+            }
+            _ => {}
+        }
+        plan.apply_expressions(|expr| {
+            expr.apply(|expr| {
+                // cached plans hold on to their functions, so the address of a
+                // function is not reused while a plan referencing it is cached
+                let address = match expr {
+                    Expr::ScalarFunction(function) => {
+                        Arc::as_ptr(&function.func) as usize
+                    }
+                    Expr::AggregateFunction(function) => {
+                        Arc::as_ptr(&function.func) as usize
+                    }
+                    Expr::WindowFunction(function) => match &function.fun {
+                        WindowFunctionDefinition::AggregateUDF(udaf) => {
+                            Arc::as_ptr(udaf) as usize
+                        }
+                        WindowFunctionDefinition::WindowUDF(udwf) => {
+                            Arc::as_ptr(udwf) as usize
+                        }
+                        WindowFunctionDefinition::BuiltInWindowFunction(_) => 0,
+                    },
+                    _ => return Ok(TreeNodeRecursion::Continue),
+                };
+                address.hash(&mut hasher);
+                Ok(TreeNodeRecursion::Continue)
+            })
+        })
+    })?;
+    if !cacheable {
+        return Ok(None);
+    }
+
+    for table in tables {
+        let Some(table) = table.as_any().downcast_ref::<ListingTable>() else {
+            return Ok(None);
+        };
+        for table_path in table.table_paths() {
+            let store = state.runtime_env().object_store(table_path)?;
+            let mut files: Vec<_> = table_path
+                .list_all_files(state, store.as_ref(), &table.options().file_extension)
+                .await?
+                .try_collect()
+                .await?;
+            files.sort_by(|a, b| a.location.cmp(&b.location));
+            table_path.as_str().hash(&mut hasher);
+            for file in files {
+                file.location.as_ref().hash(&mut hasher);
+                file.size.hash(&mut hasher);
+                file.last_modified.timestamp_millis().hash(&mut hasher);
+                file.e_tag.hash(&mut hasher);
+                file.version.hash(&mut hasher);
+            }
+        }
+    }
+
+    Ok(Some(hasher.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::serde::protobuf::{ExecutorMetadata, PartitionStats};
+    use datafusion::arrow::array::Int32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::datasource::MemTable;
+    use datafusion::physical_plan::empty::EmptyExec;
+    use datafusion::prelude::{CsvReadOptions, SessionContext};
+    use std::io::Write;
+
+    fn location(executor_id: &str, num_bytes: i64) -> PartitionLocation {
+        PartitionLocation {
+            executor_meta: Some(ExecutorMetadata {
+                id: executor_id.to_owned(),
+                ..Default::default()
+            }),
+            partition_stats: Some(PartitionStats {
+                num_bytes,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn succeeded(partition_location: Vec<PartitionLocation>) -> SuccessfulJob {
+        SuccessfulJob {
+            partition_location,
+            ..Default::default()
+        }
+    }
+
+    fn plan() -> Arc<dyn ExecutionPlan> {
+        Arc::new(EmptyExec::new(Arc::new(Schema::empty())))
+    }
+
+    #[test]
+    fn test_query_cache() {
+        let cache = QueryCache::new(2, Duration::from_secs(60), 100);
+        assert!(cache.get(1).is_none());
+
+        cache.insert_job("job-1", 1, Some(plan()));
+        assert!(matches!(cache.get(1), Some(QueryCacheHit::Plan(_))));
+        cache.job_succeeded("job-1", &succeeded(vec![location("executor-1", 10)]));
+        let Some(QueryCacheHit::Results(results)) = cache.get(1) else {
+            panic!("Expected cached results");
+        };
+        assert_eq!("job-1", results.job_id);
+        assert_eq!(
+            "job-1",
+            results.to_successful_job().cached_results_job_id.as_str()
+        );
+
+        // results bigger than the maximum are not cached
+        cache.insert_job("job-2", 2, Some(plan()));
+        cache.job_succeeded("job-2", &succeeded(vec![location("executor-1", 101)]));
+        assert!(matches!(cache.get(2), Some(QueryCacheHit::Plan(_))));
+
+        // the results of a lost executor are dropped
+        cache.remove_executor("executor-1");
+        assert!(matches!(cache.get(1), Some(QueryCacheHit::Plan(_))));
+        cache.insert_job("job-3", 1, None);
+        cache.job_succeeded("job-3", &succeeded(vec![location("executor-2", 10)]));
+        assert!(matches!(cache.get(1), Some(QueryCacheHit::Results(_))));
+
+        // and so are those of a purged job
+        cache.remove_job("job-3");
+        assert!(matches!(cache.get(1), Some(QueryCacheHit::Plan(_))));
+
+        // the least recently used query is evicted
+        cache.insert_job("job-4", 3, Some(plan()));
+        assert_eq!(2, cache.len());
+        assert!(cache.get(2).is_none());
+    }
+
+    #[test]
+    fn test_cached_results_expire() {
+        let cache = QueryCache::new(1, Duration::ZERO, 100);
+        cache.insert_job("job-1", 1, Some(plan()));
+        cache.job_succeeded("job-1", &succeeded(vec![location("executor-1", 10)]));
+        assert!(matches!(cache.get(1), Some(QueryCacheHit::Plan(_))));
+    }
+
+    #[tokio::test]
+    async fn test_query_fingerprint() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("t.csv");
+        std::fs::write(&path, "a\n1\n")?;
+        let ctx = SessionContext::new();
+        ctx.register_csv("t", path.to_str().unwrap(), CsvReadOptions::new())
+            .await?;
+
+        let fingerprint = |sql: &'static str| {
+            let ctx = ctx.clone();
+            async move {
+                let plan = ctx.sql(sql).await?.into_optimized_plan()?;
+                query_fingerprint(&ctx.state(), &plan).await
+            }
+        };
+        let sql = "SELECT a + 1 FROM t";
+        let version = fingerprint(sql).await?;
+        assert!(version.is_some());
+        assert_eq!(version, fingerprint(sql).await?);
+        assert_ne!(version, fingerprint("SELECT a + 2 FROM t").await?);
+
+        // rewriting a file of a table changes the fingerprint
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"2\n")?;
+        assert_ne!(version, fingerprint(sql).await?);
+
+        // as does changing a setting
+        let version = fingerprint(sql).await?;
+        ctx.sql("SET datafusion.execution.batch_size = 1024")
+            .await?;
+        assert_ne!(version, fingerprint(sql).await?);
+
+        // queries which are not pure, or scan tables without snapshots, are not
+        // fingerprinted
+        assert_eq!(
+            None,
+            fingerprint("SELECT a FROM t WHERE random() > 0.5").await?
+        );
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )?;
+        ctx.register_table("m", Arc::new(MemTable::try_new(schema, vec![vec![batch]])?))?;
+        assert_eq!(None, fingerprint("SELECT a FROM m").await?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Iterative execution of recursive queries (`WITH RECURSIVE`).
+//!
+//! The static term of a recursive query, then each iteration of its recursive term, run
+//! as internal jobs, each iteration reading the output partitions of the previous one
+//! in place of the work table of the query. The iterations stop at the first one which
+//! returns no rows, and the recursive query is replaced in the plan of the job by a
+//! read of the output partitions of all of them. The data of the internal jobs is kept
+//! on the executors until the job they were run for finishes.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::event_loop::EventSender;
+use ballista_core::execution_plans::ShuffleReaderExec;
+use ballista_core::serde::protobuf::job_status;
+use ballista_core::serde::scheduler::PartitionLocation;
+use dashmap::DashMap;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::physical_plan::recursive_query::RecursiveQueryExec;
+use datafusion::physical_plan::work_table::WorkTableExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionConfig;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use log::info;
+use parking_lot::Mutex;
+
+use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::timestamp_millis;
+use crate::state::SchedulerState;
+
+/// How often the status of an internal job is checked while waiting for it to finish
+const ITERATION_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The internal jobs run for the recursive queries of jobs, whose data is cleaned up
+/// along with that of the jobs they were run for
+#[derive(Clone, Default)]
+pub struct RecursiveQueries {
+    /// Internal jobs by the job they were run for
+    iterations: Arc<DashMap<String, Vec<String>>>,
+    internal_jobs: Arc<Mutex<HashSet<String>>>,
+}
+
+impl RecursiveQueries {
+    /// Record an internal job run for the job `job_id`
+    pub fn register(&self, job_id: &str, internal_job_id: &str) {
+        self.internal_jobs.lock().insert(internal_job_id.to_owned());
+        self.iterations
+            .entry(job_id.to_owned())
+            .or_default()
+            .push(internal_job_id.to_owned());
+    }
+
+    /// Whether the job is an internal job run for a recursive query
+    pub fn is_internal(&self, job_id: &str) -> bool {
+        self.internal_jobs.lock().contains(job_id)
+    }
+
+    /// Forget the internal jobs run for the job `job_id`, returning them
+    pub fn take(&self, job_id: &str) -> Vec<String> {
+        let internal_job_ids = self
+            .iterations
+            .remove(job_id)
+            .map(|(_, internal_job_ids)| internal_job_ids)
+            .unwrap_or_default();
+        let mut internal_jobs = self.internal_jobs.lock();
+        for internal_job_id in &internal_job_ids {
+            internal_jobs.remove(internal_job_id);
+        }
+        internal_job_ids
+    }
+}
+
+/// The output partitions of an iteration of a recursive query
+struct Iteration {
+    num_rows: u64,
+    locations: Vec<PartitionLocation>,
+}
+
+/// Everything needed to run the internal jobs of a job
+pub(crate) struct RecursiveQueryContext<'a> {
+    pub job_id: &'a str,
+    pub job_name: &'a str,
+    pub session_id: &'a str,
+    pub session_config: Arc<SessionConfig>,
+    pub event_sender: &'a EventSender<QueryStageSchedulerEvent>,
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T, U> {
+    /// Run the recursive queries of the physical plan of a job, replacing each of them
+    /// with a read of the output partitions of its iterations
+    pub(crate) fn materialize_recursive_queries<'a>(
+        &'a self,
+        ctx: &'a RecursiveQueryContext<'a>,
+        plan: Arc<dyn ExecutionPlan>,
+    ) -> BoxFuture<'a, Result<Arc<dyn ExecutionPlan>>> {
+        async move {
+            let mut children = vec![];
+            for child in plan.children() {
+                children.push(
+                    self.materialize_recursive_queries(ctx, child.clone())
+                        .await?,
+                );
+            }
+            let plan = if children.is_empty() {
+                plan
+            } else {
+                plan.with_new_children(children)?
+            };
+
+            match plan.as_any().downcast_ref::<RecursiveQueryExec>() {
+                Some(query) => self.run_recursive_query(ctx, query).await,
+                None => Ok(plan),
+            }
+        }
+        .boxed()
+    }
+
+    async fn run_recursive_query(
+        &self,
+        ctx: &RecursiveQueryContext<'_>,
+        query: &RecursiveQueryExec,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let [static_term, recursive_term] = query.children()[..] else {
+            return Err(BallistaError::Internal(
+                "RecursiveQueryExec expects 2 children".to_owned(),
+            ));
+        };
+
+        let mut iterations = vec![self.run_iteration(ctx, 0, static_term.clone()).await?];
+        loop {
+            let previous = &iterations[iterations.len() - 1];
+            if previous.num_rows == 0 {
+                break;
+            }
+            let iteration = iterations.len();
+            if iteration > self.config.max_recursive_query_iterations {
+                return Err(BallistaError::General(format!(
+                    "Recursive query of job {} did not reach a fixpoint within {} iterations",
+                    ctx.job_id, self.config.max_recursive_query_iterations
+                )));
+            }
+            let plan = read_work_table(recursive_term.clone(), &previous.locations)?;
+            iterations.push(self.run_iteration(ctx, iteration, plan).await?);
+        }
+        info!(
+            "Recursive query of job {} reached a fixpoint after {} iterations",
+            ctx.job_id,
+            iterations.len() - 1
+        );
+
+        // the n-th partition reads the n-th output partition of every iteration
+        let mut partitions: Vec<Vec<PartitionLocation>> = vec![];
+        for iteration in iterations {
+            for (index, location) in iteration.locations.into_iter().enumerate() {
+                if partitions.len() <= index {
+                    partitions.push(vec![]);
+                }
+                partitions[index].push(location);
+            }
+        }
+        if partitions.is_empty() {
+            return Ok(Arc::new(EmptyExec::new(static_term.schema())));
+        }
+        Ok(Arc::new(ShuffleReaderExec::try_new(
+            0,
+            partitions,
+            static_term.schema(),
+        )?))
+    }
+
+    /// Run an iteration of a recursive query as an internal job, returning its output
+    /// partitions once it succeeds
+    async fn run_iteration(
+        &self,
+        ctx: &RecursiveQueryContext<'_>,
+        iteration: usize,
+        plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<Iteration> {
+        let internal_job_id = self.task_manager.generate_job_id();
+        let job_name = format!(
+            "{} (iteration {iteration} of recursive query of {})",
+            ctx.job_name, ctx.job_id
+        );
+        self.recursive_queries
+            .register(ctx.job_id, &internal_job_id);
+        self.executor_manager
+            .job_staging()
+            .register_internal(ctx.job_id, &internal_job_id);
+
+        let queued_at = timestamp_millis();
+        self.task_manager
+            .queue_job(&internal_job_id, &job_name, queued_at)?;
+        self.task_manager
+            .submit_job(
+                &internal_job_id,
+                &job_name,
+                ctx.session_id,
+                plan,
+                None,
+                queued_at,
+                ctx.session_config.clone(),
+            )
+            .await?;
+        ctx.event_sender
+            .post_event(QueryStageSchedulerEvent::JobSubmitted {
+                job_id: internal_job_id.clone(),
+                queued_at,
+                submitted_at: timestamp_millis(),
+            })
+            .await?;
+
+        loop {
+            let status = self
+                .task_manager
+                .get_job_status(&internal_job_id)
+                .await?
+                .and_then(|status| status.status);
+            match status {
+                Some(job_status::Status::Successful(successful)) => {
+                    let num_rows = successful
+                        .partition_location
+                        .iter()
+                        .filter_map(|location| location.partition_stats.as_ref())
+                        .map(|stats| stats.num_rows.max(0) as u64)
+                        .sum();
+                    let locations = successful
+                        .partition_location
+                        .into_iter()
+                        .map(|location| location.try_into())
+                        .collect::<Result<_>>()?;
+                    return Ok(Iteration {
+                        num_rows,
+                        locations,
+                    });
+                }
+                Some(job_status::Status::Failed(failed)) => {
+                    return Err(BallistaError::General(format!(
+                        "Iteration {iteration} of recursive query failed in job {internal_job_id}: {}",
+                        failed.error
+                    )));
+                }
+                Some(_) => tokio::time::sleep(ITERATION_POLL_INTERVAL).await,
+                None => {
+                    return Err(BallistaError::Internal(format!(
+                        "Unknown job {internal_job_id} of iteration {iteration} of recursive query"
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// The recursive term of a query, reading the output partitions of the previous
+/// iteration in place of its work table
+fn read_work_table(
+    recursive_term: Arc<dyn ExecutionPlan>,
+    previous: &[PartitionLocation],
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let plan = recursive_term.transform_up(|node| {
+        if node.as_any().is::<WorkTableExec>() {
+            let partitions = previous.iter().map(|location| vec![location.clone()]);
+            let reader =
+                ShuffleReaderExec::try_new(0, partitions.collect(), node.schema())?;
+            Ok(Transformed::yes(Arc::new(reader) as Arc<dyn ExecutionPlan>))
+        } else {
+            Ok(Transformed::no(node))
+        }
+    })?;
+    Ok(plan.data)
+}
@@ -0,0 +1,150 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Retention of the results of successful jobs, the output partitions of their final
+//! stage, which the executors keep after the jobs complete for the clients to fetch
+//! them. The results are deleted once the client releases them, or once their time to
+//! live expires.
+
+use ballista_core::serde::protobuf::PartitionLocation;
+use dashmap::DashMap;
+
+/// Results of a successful job retained by the executors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetainedResults {
+    /// Size of the results in bytes, as reported by the executors
+    pub bytes: u64,
+    /// Time the results were retained at, in milliseconds
+    pub retained_at: u64,
+    /// Seconds the results are kept for, 0 meaning until released
+    pub ttl_seconds: u64,
+}
+
+impl RetainedResults {
+    /// Time the results are deleted at, in milliseconds, unless kept until released
+    pub fn expires_at(&self) -> Option<u64> {
+        (self.ttl_seconds > 0).then(|| {
+            self.retained_at
+                .saturating_add(self.ttl_seconds.saturating_mul(1000))
+        })
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// The results of the successful jobs retained by the executors, by job ID
+#[derive(Debug, Default)]
+pub struct ResultRetention {
+    jobs: DashMap<String, RetainedResults>,
+}
+
+impl ResultRetention {
+    /// Track the results of a job which succeeded at `now` (in milliseconds), kept for
+    /// `ttl_seconds`, or until released if 0
+    pub fn retain(
+        &self,
+        job_id: &str,
+        partition_location: &[PartitionLocation],
+        ttl_seconds: u64,
+        now: u64,
+    ) -> RetainedResults {
+        let retained = RetainedResults {
+            bytes: result_bytes(partition_location),
+            retained_at: now,
+            ttl_seconds,
+        };
+        self.jobs.insert(job_id.to_owned(), retained);
+        retained
+    }
+
+    /// Stop tracking the results of a job, released by the client or deleted along
+    /// with the job. Returns them unless they were not retained or expired at `now`
+    pub fn release(&self, job_id: &str, now: u64) -> Option<RetainedResults> {
+        self.jobs
+            .remove(job_id)
+            .map(|(_, retained)| retained)
+            .filter(|retained| !retained.is_expired(now))
+    }
+
+    /// The results retained for a job, unless expired at `now`
+    pub fn get(&self, job_id: &str, now: u64) -> Option<RetainedResults> {
+        self.jobs
+            .get(job_id)
+            .map(|retained| *retained)
+            .filter(|retained| !retained.is_expired(now))
+    }
+
+    /// Total size in bytes of the results retained at `now`, the expired ones being
+    /// dropped on the way
+    pub fn retained_bytes(&self, now: u64) -> u64 {
+        self.jobs.retain(|_, retained| !retained.is_expired(now));
+        self.jobs.iter().map(|retained| retained.bytes).sum()
+    }
+}
+
+/// Size of the output partitions of a job, those of unknown size counting for nothing
+fn result_bytes(partition_location: &[PartitionLocation]) -> u64 {
+    partition_location
+        .iter()
+        .filter_map(|location| location.partition_stats.as_ref())
+        .map(|stats| stats.num_bytes.max(0) as u64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use ballista_core::serde::protobuf::{PartitionLocation, PartitionStats};
+
+    use super::ResultRetention;
+
+    fn location(num_bytes: i64) -> PartitionLocation {
+        PartitionLocation {
+            partition_stats: Some(PartitionStats {
+                num_bytes,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_result_retention() {
+        let retention = ResultRetention::default();
+        let retained =
+            retention.retain("job1", &[location(100), location(-1)], 60, 1_000);
+        assert_eq!(retained.bytes, 100);
+        assert_eq!(retained.expires_at(), Some(61_000));
+        retention.retain("job2", &[location(50)], 0, 1_000);
+        assert_eq!(retention.retained_bytes(2_000), 150);
+
+        // the results kept until released never expire
+        assert_eq!(retention.get("job1", 61_000), None);
+        assert_eq!(retention.retained_bytes(61_000), 50);
+        assert_eq!(retention.get("job2", u64::MAX).map(|r| r.bytes), Some(50));
+
+        assert_eq!(retention.release("job2", 61_000).map(|r| r.bytes), Some(50));
+        assert_eq!(retention.release("job2", 61_000), None);
+        assert_eq!(retention.retained_bytes(61_000), 0);
+
+        // expired results are not released again
+        retention.retain("job3", &[location(10)], 1, 1_000);
+        assert_eq!(retention.release("job3", 2_000), None);
+    }
+}
@@ -17,19 +17,69 @@
 
 use crate::scheduler_server::SessionBuilder;
 use ballista_core::error::Result;
+use ballista_core::serde::protobuf::KeyValuePair;
+use ballista_core::table_functions::with_ballista_table_functions;
+use datafusion::execution::SessionStateBuilder;
 use datafusion::prelude::{SessionConfig, SessionContext};
 
 use crate::cluster::JobState;
+use crate::metrics::cluster::ClusterMetrics;
+use crate::resource_profiles::ResourceProfiles;
+use crate::state::cluster_catalog::ClusterCatalog;
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct SessionManager {
     state: Arc<dyn JobState>,
+    cluster_metrics: Option<ClusterMetrics>,
+    cluster_catalog: Option<Arc<ClusterCatalog>>,
+    resource_profiles: Arc<ResourceProfiles>,
 }
 
 impl SessionManager {
     pub fn new(state: Arc<dyn JobState>) -> Self {
-        Self { state }
+        Self {
+            state,
+            cluster_metrics: None,
+            cluster_catalog: None,
+            resource_profiles: Arc::default(),
+        }
+    }
+
+    /// Register the `ballista.cluster_metrics` table of `cluster_metrics` in the
+    /// sessions created or updated
+    pub fn with_cluster_metrics(mut self, cluster_metrics: ClusterMetrics) -> Self {
+        self.cluster_metrics = Some(cluster_metrics);
+        self
+    }
+
+    /// Make `cluster_catalog` the default schema of the sessions created or updated
+    pub fn with_cluster_catalog(
+        mut self,
+        cluster_catalog: Option<Arc<ClusterCatalog>>,
+    ) -> Self {
+        self.cluster_catalog = cluster_catalog;
+        self
+    }
+
+    /// Expand the settings of the sessions created or updated with those of the
+    /// resource profiles they pick
+    pub fn with_resource_profiles(mut self, resource_profiles: ResourceProfiles) -> Self {
+        self.resource_profiles = Arc::new(resource_profiles);
+        self
+    }
+
+    /// The settings a session is created or updated with, given those sent by its
+    /// client, see [ResourceProfiles::expand_settings]
+    pub fn session_settings(
+        &self,
+        settings: &[KeyValuePair],
+    ) -> Result<Vec<KeyValuePair>> {
+        self.resource_profiles.expand_settings(settings)
+    }
+
+    pub fn cluster_catalog(&self) -> Option<&Arc<ClusterCatalog>> {
+        self.cluster_catalog.as_ref()
     }
 
     pub async fn remove_session(
@@ -44,14 +94,20 @@ impl SessionManager {
         session_id: &str,
         config: &SessionConfig,
     ) -> Result<Arc<SessionContext>> {
-        self.state.update_session(session_id, config).await
+        let session = self.state.update_session(session_id, config).await?;
+        self.register_cluster_metrics(&session)?;
+        self.register_cluster_catalog(&session)?;
+        Ok(session)
     }
 
     pub async fn create_session(
         &self,
         config: &SessionConfig,
     ) -> Result<Arc<SessionContext>> {
-        self.state.create_session(config).await
+        let session = self.state.create_session(config).await?;
+        self.register_cluster_metrics(&session)?;
+        self.register_cluster_catalog(&session)?;
+        Ok(session)
     }
 
     pub async fn get_session(&self, session_id: &str) -> Result<Arc<SessionContext>> {
@@ -61,6 +117,24 @@ impl SessionManager {
     pub(crate) fn produce_config(&self) -> SessionConfig {
         self.state.produce_config()
     }
+
+    fn register_cluster_metrics(&self, session: &SessionContext) -> Result<()> {
+        if let Some(cluster_metrics) = &self.cluster_metrics {
+            cluster_metrics.register_table(session)?;
+        }
+        Ok(())
+    }
+
+    fn register_cluster_catalog(&self, session: &SessionContext) -> Result<()> {
+        let Some(cluster_catalog) = &self.cluster_catalog else {
+            return Ok(());
+        };
+        let options = session.state().config().options().catalog.clone();
+        if let Some(catalog) = session.catalog(&options.default_catalog) {
+            catalog.register_schema(&options.default_schema, cluster_catalog.clone())?;
+        }
+        Ok(())
+    }
 }
 
 /// Create a DataFusion session context that is compatible with Ballista Configuration
@@ -79,6 +153,12 @@ pub fn create_datafusion_context(
     } else {
         session_builder(session_config.clone())?
     };
+    // the SQL submitted to the scheduler calls the table functions of the clients,
+    // whichever functions the session builder registers
+    let session_state = with_ballista_table_functions(
+        SessionStateBuilder::new_from_existing(session_state),
+    )
+    .build();
 
     Ok(Arc::new(SessionContext::new_with_state(session_state)))
 }
@@ -0,0 +1,342 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Shadow execution of a sample of the submitted queries with an experimental planner
+//! configuration. The results and runtimes of the shadow jobs are compared to those of
+//! the jobs they shadow, to evaluate optimizer changes on production workloads without
+//! affecting the callers, which only ever see the results of the original jobs.
+//!
+//! Both jobs of a pair are run with [BALLISTA_CHECKSUM_RESULTS], so that the executors
+//! checksum their output partitions and the scheduler compares the results of the jobs
+//! without reading them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[cfg(doc)]
+use ballista_core::config::BALLISTA_CHECKSUM_RESULTS;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::{job_status, JobStatus, KeyValuePair};
+use parking_lot::Mutex;
+
+/// Digest of the result of a job, which does not depend on the order of its rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResultDigest {
+    pub num_rows: u64,
+    /// Order-insensitive checksum of the rows
+    pub hash: u64,
+}
+
+impl ResultDigest {
+    /// Add the rows of an output partition to the digest, given their number and
+    /// checksum, see [ballista_core::utils::batch_checksum]
+    pub fn add_partition(&mut self, num_rows: u64, checksum: u64) {
+        self.hash = self.hash.wrapping_add(checksum);
+        self.num_rows += num_rows;
+    }
+
+    /// Digest of the result of a successful job, combining the checksums of its output
+    /// partitions reported by the executors which wrote them
+    pub fn of_job(status: &JobStatus) -> Result<Self> {
+        let Some(job_status::Status::Successful(successful)) = &status.status else {
+            return Err(BallistaError::General(format!(
+                "Job {} did not succeed",
+                status.job_id
+            )));
+        };
+
+        let mut digest = Self::default();
+        for location in &successful.partition_location {
+            let Some((num_rows, checksum)) =
+                location.partition_stats.as_ref().and_then(|stats| {
+                    stats.checksum.map(|checksum| (stats.num_rows, checksum))
+                })
+            else {
+                return Err(BallistaError::General(format!(
+                    "Output partition {} of job {} was not checksummed",
+                    location.map_partition_id, status.job_id
+                )));
+            };
+            digest.add_partition(num_rows.max(0) as u64, checksum);
+        }
+
+        Ok(digest)
+    }
+}
+
+/// Outcome of a job taking part in a shadow execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowOutcome {
+    /// Time between the job being queued and it finishing, in milliseconds
+    pub runtime_ms: u64,
+    /// Digest of the result of the job, or the reason it failed
+    pub result: std::result::Result<ResultDigest, String>,
+}
+
+/// Comparison of a job with its shadow job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowComparison {
+    pub job_id: String,
+    pub shadow_job_id: String,
+    pub primary: ShadowOutcome,
+    pub shadow: ShadowOutcome,
+}
+
+impl ShadowComparison {
+    /// Whether both jobs succeeded with the same result
+    pub fn results_match(&self) -> bool {
+        matches!(
+            (&self.primary.result, &self.shadow.result),
+            (Ok(primary), Ok(shadow)) if primary == shadow
+        )
+    }
+}
+
+struct ShadowPair {
+    shadow_job_id: String,
+    primary: Option<ShadowOutcome>,
+    shadow: Option<ShadowOutcome>,
+}
+
+#[derive(Default)]
+struct ShadowPairs {
+    /// Pairs of jobs, keyed by the id of the shadowed job
+    pairs: HashMap<String, ShadowPair>,
+    /// Id of the shadowed job of each shadow job
+    shadowed_job_ids: HashMap<String, String>,
+}
+
+/// Samples the queries to shadow and pairs the outcomes of the shadowed jobs with the
+/// outcomes of their shadow jobs
+#[derive(Clone)]
+pub struct ShadowExecution {
+    fraction: f64,
+    settings: Vec<KeyValuePair>,
+    pairs: Arc<Mutex<ShadowPairs>>,
+}
+
+impl ShadowExecution {
+    /// Shadow a `fraction` of the queries, with their session settings overridden by
+    /// `settings`. A fraction of 0 disables shadow execution.
+    pub fn new(fraction: f64, settings: Vec<KeyValuePair>) -> Self {
+        Self {
+            fraction,
+            settings,
+            pairs: Arc::new(Mutex::new(ShadowPairs::default())),
+        }
+    }
+
+    /// Settings overriding the session settings of shadow jobs
+    pub fn settings(&self) -> &[KeyValuePair] {
+        &self.settings
+    }
+
+    /// Whether to shadow the next query
+    pub fn sample(&self) -> bool {
+        self.fraction > 0.0 && rand::random::<f64>() < self.fraction
+    }
+
+    /// Track the outcomes of the job `job_id` and of its shadow job
+    pub fn register(&self, job_id: &str, shadow_job_id: &str) {
+        let mut pairs = self.pairs.lock();
+        pairs.pairs.insert(
+            job_id.to_owned(),
+            ShadowPair {
+                shadow_job_id: shadow_job_id.to_owned(),
+                primary: None,
+                shadow: None,
+            },
+        );
+        pairs
+            .shadowed_job_ids
+            .insert(shadow_job_id.to_owned(), job_id.to_owned());
+    }
+
+    /// Whether the job is a shadowed job, or a shadow job
+    pub fn is_tracked(&self, job_id: &str) -> bool {
+        let pairs = self.pairs.lock();
+        pairs.pairs.contains_key(job_id) || pairs.shadowed_job_ids.contains_key(job_id)
+    }
+
+    /// Record the outcome of a tracked job, returning the comparison of the pair of jobs
+    /// once both of them finished
+    pub fn record_outcome(
+        &self,
+        job_id: &str,
+        outcome: ShadowOutcome,
+    ) -> Option<ShadowComparison> {
+        let mut pairs = self.pairs.lock();
+        let (shadowed_job_id, is_shadow) = match pairs.shadowed_job_ids.get(job_id) {
+            Some(shadowed_job_id) => (shadowed_job_id.clone(), true),
+            None => (job_id.to_owned(), false),
+        };
+        let pair = pairs.pairs.get_mut(&shadowed_job_id)?;
+        if is_shadow {
+            pair.shadow = Some(outcome);
+        } else {
+            pair.primary = Some(outcome);
+        }
+        if pair.primary.is_none() || pair.shadow.is_none() {
+            return None;
+        }
+
+        let pair = pairs.pairs.remove(&shadowed_job_id)?;
+        pairs.shadowed_job_ids.remove(&pair.shadow_job_id);
+        Some(ShadowComparison {
+            job_id: shadowed_job_id,
+            shadow_job_id: pair.shadow_job_id,
+            primary: pair.primary?,
+            shadow: pair.shadow?,
+        })
+    }
+
+    /// Stop tracking the pair of a job, returning the id of its shadow job if the job
+    /// is a shadowed job
+    pub fn forget(&self, job_id: &str) -> Option<String> {
+        let mut pairs = self.pairs.lock();
+        match pairs.shadowed_job_ids.remove(job_id) {
+            Some(shadowed_job_id) => {
+                pairs.pairs.remove(&shadowed_job_id);
+                None
+            }
+            None => {
+                let pair = pairs.pairs.remove(job_id)?;
+                pairs.shadowed_job_ids.remove(&pair.shadow_job_id);
+                Some(pair.shadow_job_id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ballista_core::serde::protobuf::{
+        job_status, JobStatus, PartitionLocation, PartitionStats, SuccessfulJob,
+    };
+    use ballista_core::utils::batch_checksum;
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+
+    use crate::state::shadow_execution::{ResultDigest, ShadowExecution, ShadowOutcome};
+
+    fn batch(ids: Vec<i32>, names: Vec<&str>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(ids)),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .unwrap()
+    }
+
+    /// Digest of a job with an output partition per batch, checksummed by executors
+    fn digest(batches: &[RecordBatch]) -> ResultDigest {
+        let partition_location = batches
+            .iter()
+            .map(|batch| PartitionLocation {
+                partition_stats: Some(PartitionStats {
+                    num_rows: batch.num_rows() as i64,
+                    checksum: Some(batch_checksum(batch).unwrap()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect();
+        ResultDigest::of_job(&successful_job(partition_location)).unwrap()
+    }
+
+    fn successful_job(partition_location: Vec<PartitionLocation>) -> JobStatus {
+        JobStatus {
+            job_id: "job".to_owned(),
+            status: Some(job_status::Status::Successful(SuccessfulJob {
+                partition_location,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_result_digest() {
+        let result = digest(&[batch(vec![1, 2, 3], vec!["a", "b", "c"])]);
+        assert_eq!(result.num_rows, 3);
+
+        // The digest does not depend on the order of rows, nor on their batches
+        let reordered =
+            digest(&[batch(vec![3], vec!["c"]), batch(vec![2, 1], vec!["b", "a"])]);
+        assert_eq!(result, reordered);
+
+        let different = digest(&[batch(vec![1, 2, 3], vec!["a", "b", "d"])]);
+        assert_ne!(result, different);
+
+        // results whose partitions were not checksummed have no digest
+        let unchecksummed = successful_job(vec![PartitionLocation {
+            partition_stats: Some(PartitionStats::default()),
+            ..Default::default()
+        }]);
+        assert!(ResultDigest::of_job(&unchecksummed).is_err());
+    }
+
+    #[test]
+    fn test_pair_outcomes() {
+        let shadow_execution = ShadowExecution::new(1.0, vec![]);
+        assert!(shadow_execution.sample());
+        assert!(!ShadowExecution::new(0.0, vec![]).sample());
+
+        shadow_execution.register("job", "shadow");
+        assert!(shadow_execution.is_tracked("job"));
+        assert!(shadow_execution.is_tracked("shadow"));
+        assert!(!shadow_execution.is_tracked("other"));
+
+        let outcome = |runtime_ms, num_rows| ShadowOutcome {
+            runtime_ms,
+            result: Ok(ResultDigest { num_rows, hash: 42 }),
+        };
+        assert_eq!(
+            shadow_execution.record_outcome("shadow", outcome(5, 3)),
+            None
+        );
+        let comparison = shadow_execution
+            .record_outcome("job", outcome(10, 3))
+            .unwrap();
+        assert_eq!(comparison.job_id, "job");
+        assert_eq!(comparison.shadow_job_id, "shadow");
+        assert_eq!(comparison.primary.runtime_ms, 10);
+        assert_eq!(comparison.shadow.runtime_ms, 5);
+        assert!(comparison.results_match());
+        assert!(!shadow_execution.is_tracked("job"));
+
+        shadow_execution.register("job", "shadow");
+        shadow_execution.record_outcome("job", outcome(10, 3));
+        let comparison = shadow_execution
+            .record_outcome("shadow", outcome(5, 4))
+            .unwrap();
+        assert!(!comparison.results_match());
+
+        shadow_execution.register("job", "shadow");
+        assert_eq!(shadow_execution.forget("job"), Some("shadow".to_owned()));
+        assert!(!shadow_execution.is_tracked("shadow"));
+    }
+}
@@ -0,0 +1,433 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Reservations of executor slots made ahead of latency critical jobs, e.g. scheduled
+//! reports, so that they don't queue behind ad-hoc jobs. Jobs run with a reservation
+//! when they set `ballista.job.slot_reservation` to its ID.
+//!
+//! Until a reservation expires, the scheduler holds back from the other jobs as many
+//! free slots as the reservation has slots not taken by the running tasks of its jobs,
+//! and binds the tasks of its jobs first. The reservations are saved to the
+//! [JobState], so that they outlive the scheduler.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::{SlotReservation, SlotReservationUsage};
+use dashmap::DashMap;
+use log::{info, warn};
+use parking_lot::Mutex;
+
+use crate::cluster::job_scheduling::{JobSchedulingInfo, JobSchedulingPolicy, JobShare};
+use crate::cluster::JobState;
+use crate::scheduler_server::timestamp_millis;
+use crate::state::task_manager::JobInfoCache;
+
+/// The reservations of executor slots of the cluster
+pub struct SlotReservations {
+    state: Arc<dyn JobState>,
+    /// Reservations not known to have expired, by reservation ID
+    reservations: DashMap<String, SlotReservation>,
+}
+
+impl SlotReservations {
+    pub fn new(state: Arc<dyn JobState>) -> Self {
+        Self {
+            state,
+            reservations: DashMap::new(),
+        }
+    }
+
+    /// Load the reservations saved by previous schedulers, removing the expired ones
+    pub async fn init(&self) -> Result<()> {
+        let now = timestamp_millis();
+        for reservation in self.state.get_slot_reservations().await? {
+            if reservation.expires_at <= now {
+                self.state
+                    .remove_slot_reservation(&reservation.reservation_id)
+                    .await?;
+            } else {
+                self.reservations
+                    .insert(reservation.reservation_id.clone(), reservation);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reserve `slots` executor slots for `ttl`, replacing the reservation with the same
+    /// ID. The expired reservations are removed from the state on the way
+    pub async fn reserve(
+        &self,
+        reservation_id: &str,
+        slots: u32,
+        ttl: Duration,
+        owner: Option<&str>,
+    ) -> Result<SlotReservation> {
+        if reservation_id.is_empty() {
+            return Err(BallistaError::General(
+                "Slot reservations must have an ID".to_owned(),
+            ));
+        }
+        if slots == 0 || ttl.is_zero() {
+            return Err(BallistaError::General(format!(
+                "Slot reservation {reservation_id} must reserve slots for some time"
+            )));
+        }
+
+        let now = timestamp_millis();
+        for expired in self.remove_expired(now) {
+            self.state.remove_slot_reservation(&expired).await?;
+        }
+        let reservation = SlotReservation {
+            reservation_id: reservation_id.to_owned(),
+            slots,
+            expires_at: now.saturating_add(ttl.as_millis() as u64),
+            owner: owner.unwrap_or_default().to_owned(),
+            created_at: now,
+        };
+        self.state.save_slot_reservation(&reservation).await?;
+        self.reservations
+            .insert(reservation_id.to_owned(), reservation.clone());
+        info!("Reserved {slots} executor slots for {ttl:?} with {reservation_id}");
+
+        Ok(reservation)
+    }
+
+    /// Cancel a reservation, returning whether it existed
+    pub async fn cancel(&self, reservation_id: &str) -> Result<bool> {
+        let removed = self.state.remove_slot_reservation(reservation_id).await?;
+        let cancelled = self.reservations.remove(reservation_id).is_some() || removed;
+        if cancelled {
+            info!("Cancelled slot reservation {reservation_id}");
+        }
+        Ok(cancelled)
+    }
+
+    /// The reservations not expired yet, ordered by ID. They are read from the state,
+    /// for read replicas to list those made on the leader
+    pub async fn list(&self) -> Result<Vec<SlotReservation>> {
+        let now = timestamp_millis();
+        let mut reservations: Vec<_> = self
+            .state
+            .get_slot_reservations()
+            .await?
+            .into_iter()
+            .filter(|reservation| reservation.expires_at > now)
+            .collect();
+        reservations.sort_by(|a, b| a.reservation_id.cmp(&b.reservation_id));
+        Ok(reservations)
+    }
+
+    /// The running jobs of each of `reservations` among `active_jobs`, and the slots
+    /// of the reservation their running tasks take
+    pub async fn usage(
+        reservations: Vec<SlotReservation>,
+        active_jobs: &HashMap<String, JobInfoCache>,
+    ) -> Vec<SlotReservationUsage> {
+        let mut usage: Vec<_> = reservations
+            .into_iter()
+            .map(|reservation| SlotReservationUsage {
+                reservation: Some(reservation),
+                ..Default::default()
+            })
+            .collect();
+        for (job_id, job_info) in active_jobs {
+            let Some(reservation_id) = &job_info.slot_reservation else {
+                continue;
+            };
+            let Some(usage) = usage.iter_mut().find(|usage| {
+                usage
+                    .reservation
+                    .as_ref()
+                    .is_some_and(|r| &r.reservation_id == reservation_id)
+            }) else {
+                continue;
+            };
+            let running_tasks =
+                job_info.execution_graph.read().await.running_tasks().len();
+            usage.job_ids.push(job_id.clone());
+            usage.used_slots = usage.used_slots.saturating_add(running_tasks as u32);
+        }
+        for usage in usage.iter_mut() {
+            usage.job_ids.sort();
+            let slots = usage
+                .reservation
+                .as_ref()
+                .map(|r| r.slots)
+                .unwrap_or_default();
+            usage.used_slots = usage.used_slots.min(slots);
+        }
+        usage
+    }
+
+    /// Whether no reservation is held, the expired ones being held until dropped when
+    /// scheduling or reserving
+    pub fn is_empty(&self) -> bool {
+        self.reservations.is_empty()
+    }
+
+    /// Drop the expired reservations, returning their IDs
+    fn remove_expired(&self, now: u64) -> Vec<String> {
+        let mut expired = vec![];
+        self.reservations.retain(|reservation_id, reservation| {
+            let active = reservation.expires_at > now;
+            if !active {
+                info!("Slot reservation {reservation_id} expired");
+                expired.push(reservation_id.clone());
+            }
+            active
+        });
+        expired
+    }
+
+    /// Wrap `policy` so that the jobs of `active_jobs` which don't run with a
+    /// reservation leave the slots reserved for the others free, `task_slots` being the
+    /// number of slots of the alive executors
+    pub(crate) async fn scheduling<'a>(
+        &self,
+        policy: &'a dyn JobSchedulingPolicy,
+        active_jobs: &HashMap<String, JobInfoCache>,
+        task_slots: usize,
+    ) -> ReservedSlotsScheduling<'a> {
+        self.remove_expired(timestamp_millis());
+        let reserved_slots: HashMap<String, usize> = self
+            .reservations
+            .iter()
+            .map(|pair| (pair.key().clone(), pair.value().slots as usize))
+            .collect();
+        if reserved_slots.is_empty() {
+            return ReservedSlotsScheduling::unreserved(policy);
+        }
+
+        let mut reserved_jobs = HashSet::new();
+        let mut used_slots: HashMap<&str, usize> = HashMap::new();
+        let mut running_tasks = 0;
+        for (job_id, job_info) in active_jobs {
+            let tasks = job_info.execution_graph.read().await.running_tasks().len();
+            running_tasks += tasks;
+            let Some(reservation_id) = job_info.slot_reservation.as_deref() else {
+                continue;
+            };
+            if reserved_slots.contains_key(reservation_id) {
+                reserved_jobs.insert(job_id.clone());
+                *used_slots.entry(reservation_id).or_default() += tasks;
+            } else {
+                warn!("Job {job_id} runs with unknown or expired slot reservation {reservation_id}");
+            }
+        }
+        let withheld_slots: usize = reserved_slots
+            .iter()
+            .map(|(reservation_id, slots)| {
+                let used = used_slots.get(reservation_id.as_str()).copied();
+                slots.saturating_sub(used.unwrap_or_default())
+            })
+            .sum();
+        let free_slots = task_slots.saturating_sub(running_tasks);
+
+        ReservedSlotsScheduling {
+            policy,
+            reserved_jobs,
+            unreserved_slots: Some(Mutex::new(free_slots.saturating_sub(withheld_slots))),
+        }
+    }
+}
+
+/// A [JobSchedulingPolicy] binding the tasks of the jobs running with a reservation
+/// first, and capping the tasks of the other jobs so that they leave the reserved slots
+/// free, across all the scheduling rounds it's used for
+#[derive(Debug)]
+pub(crate) struct ReservedSlotsScheduling<'a> {
+    policy: &'a dyn JobSchedulingPolicy,
+    /// Jobs running with a reservation
+    reserved_jobs: HashSet<String>,
+    /// Free slots the other jobs may still take, unlimited without reservations
+    unreserved_slots: Option<Mutex<usize>>,
+}
+
+impl<'a> ReservedSlotsScheduling<'a> {
+    /// Schedule the jobs with `policy` alone
+    pub(crate) fn unreserved(policy: &'a dyn JobSchedulingPolicy) -> Self {
+        Self {
+            policy,
+            reserved_jobs: HashSet::new(),
+            unreserved_slots: None,
+        }
+    }
+}
+
+impl JobSchedulingPolicy for ReservedSlotsScheduling<'_> {
+    fn schedule(
+        &self,
+        jobs: Vec<JobSchedulingInfo>,
+        available_slots: usize,
+    ) -> Vec<JobShare> {
+        let Some(unreserved_slots) = &self.unreserved_slots else {
+            return self.policy.schedule(jobs, available_slots);
+        };
+        let pending_tasks: HashMap<String, usize> = jobs
+            .iter()
+            .map(|job| (job.job_id.clone(), job.pending_tasks))
+            .collect();
+        let (mut shares, mut other_shares): (Vec<_>, Vec<_>) = self
+            .policy
+            .schedule(jobs, available_slots)
+            .into_iter()
+            .partition(|share| self.reserved_jobs.contains(&share.job_id));
+
+        let mut unreserved_slots = unreserved_slots.lock();
+        let granted = (*unreserved_slots).min(available_slots);
+        let mut left = granted;
+        for share in other_shares.iter_mut() {
+            let pending = pending_tasks
+                .get(&share.job_id)
+                .copied()
+                .unwrap_or_default();
+            share.max_tasks = share.max_tasks.min(pending).min(left);
+            left -= share.max_tasks;
+        }
+        *unreserved_slots -= granted - left;
+
+        shares.extend(other_shares);
+        shares
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::SlotReservation;
+    use ballista_core::utils::{default_config_producer, default_session_builder};
+
+    use crate::cluster::job_scheduling::{
+        FifoJobScheduling, JobSchedulingInfo, JobSchedulingPolicy, JobShare,
+    };
+    use crate::cluster::memory::InMemoryJobState;
+    use crate::cluster::JobState;
+    use parking_lot::Mutex;
+
+    use super::{ReservedSlotsScheduling, SlotReservations};
+
+    fn job(job_id: &str, queued_at: u64, pending_tasks: usize) -> JobSchedulingInfo {
+        JobSchedulingInfo {
+            job_id: job_id.to_owned(),
+            priority: 0,
+            queued_at,
+            running_tasks: 0,
+            pending_tasks,
+        }
+    }
+
+    fn shares(shares: Vec<JobShare>) -> Vec<(String, usize)> {
+        shares
+            .into_iter()
+            .map(|share| (share.job_id, share.max_tasks))
+            .collect()
+    }
+
+    #[test]
+    fn test_reserved_slots_scheduling() {
+        let scheduling = ReservedSlotsScheduling {
+            policy: &FifoJobScheduling,
+            reserved_jobs: ["report".to_owned()].into(),
+            unreserved_slots: Some(Mutex::new(5)),
+        };
+
+        // the job of the reservation comes first, the others share the unreserved slots
+        let jobs = vec![job("a", 1, 3), job("b", 2, 4), job("report", 3, 10)];
+        assert_eq!(
+            vec![
+                ("report".to_owned(), usize::MAX),
+                ("a".to_owned(), 3),
+                ("b".to_owned(), 2)
+            ],
+            shares(scheduling.schedule(jobs, 8))
+        );
+
+        // the unreserved slots are shared across the scheduling rounds
+        let jobs = vec![job("c", 4, 4)];
+        assert_eq!(
+            vec![("c".to_owned(), 0)],
+            shares(scheduling.schedule(jobs, 8))
+        );
+
+        let unreserved = ReservedSlotsScheduling::unreserved(&FifoJobScheduling);
+        let jobs = vec![job("a", 1, 3), job("report", 3, 10)];
+        assert_eq!(
+            vec![
+                ("a".to_owned(), usize::MAX),
+                ("report".to_owned(), usize::MAX)
+            ],
+            shares(unreserved.schedule(jobs, 8))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slot_reservations() -> Result<()> {
+        let state: Arc<dyn JobState> = Arc::new(InMemoryJobState::new(
+            "",
+            Arc::new(default_session_builder),
+            Arc::new(default_config_producer),
+        ));
+        let reservations = SlotReservations::new(state.clone());
+
+        assert!(reservations
+            .reserve("", 4, Duration::from_secs(60), None)
+            .await
+            .is_err());
+        assert!(reservations
+            .reserve("report", 0, Duration::from_secs(60), None)
+            .await
+            .is_err());
+
+        let reservation = reservations
+            .reserve("report", 4, Duration::from_secs(60), Some("alice"))
+            .await?;
+        assert_eq!(4, reservation.slots);
+        assert_eq!("alice", reservation.owner);
+        reservations
+            .reserve("etl", 2, Duration::from_secs(60), None)
+            .await?;
+        let ids = |list: Vec<SlotReservation>| {
+            list.into_iter()
+                .map(|reservation| reservation.reservation_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["etl", "report"], ids(reservations.list().await?));
+
+        // a scheduler taking over loads the reservations from the state
+        let recovered = SlotReservations::new(state.clone());
+        recovered.init().await?;
+        assert!(!recovered.is_empty());
+        assert!(recovered.cancel("etl").await?);
+        assert!(!recovered.cancel("etl").await?);
+        assert_eq!(vec!["report"], ids(reservations.list().await?));
+
+        let usage =
+            SlotReservations::usage(reservations.list().await?, &HashMap::new()).await;
+        assert_eq!(1, usage.len());
+        assert!(usage[0].job_ids.is_empty());
+        assert_eq!(0, usage[0].used_slots);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Samples of the output of single stages of jobs, to find which stage of a long
+//! pipeline produces unexpected values. The scheduler executes the plan of the stage
+//! in isolation, without its shuffle write, reading its inputs from the executors
+//! holding the outputs of the stages it depends on, and stops once it has enough rows.
+//! As the stage runs on the scheduler, only the stages streaming their rows, which
+//! scan, filter and project their inputs, can be sampled, not those buffering them
+//! such as aggregations, joins or sorts.
+
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::ShuffleWriterExec;
+use ballista_core::extension::{SessionConfigExt, SessionConfigHelperExt};
+use ballista_core::security::GrpcSecurityConfig;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::execution::TaskContext;
+use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::limit::{GlobalLimitExec, LocalLimitExec};
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionContext;
+use futures::TryStreamExt;
+
+use crate::state::execution_graph::{ExecutionGraph, ExecutionStage};
+
+/// Number of rows of the samples of stages asking for none in particular
+pub const DEFAULT_SAMPLE_ROWS: u64 = 100;
+
+/// Maximum number of rows of a sample, so that samples fit in a gRPC message
+pub const MAX_SAMPLE_ROWS: u64 = 10_000;
+
+/// Rows of the output of a stage
+#[derive(Debug, Default)]
+pub struct StageSample {
+    pub batches: Vec<RecordBatch>,
+    /// Partitions of the stage the rows were read from
+    pub partitions: Vec<u32>,
+}
+
+impl StageSample {
+    /// Sample at most `limit` rows of the output of the stage `stage_id` of a job,
+    /// executing its `partition`, or all its partitions in order until it has enough
+    /// rows, with the config of the job and the functions and object stores of its
+    /// session. The stages whose inputs are not resolved yet and those which don't
+    /// stream their rows, see [Self::can_sample], can't be sampled.
+    pub async fn of_stage(
+        graph: &ExecutionGraph,
+        stage_id: usize,
+        partition: Option<usize>,
+        limit: u64,
+        session_ctx: &SessionContext,
+        security: GrpcSecurityConfig,
+    ) -> Result<Self> {
+        let job_id = graph.job_id();
+        let stage = graph.stages().get(&stage_id).ok_or_else(|| {
+            BallistaError::General(format!("Stage {stage_id} of job {job_id} not found"))
+        })?;
+        let Some(num_partitions) = stage.partitions() else {
+            return Err(BallistaError::General(format!(
+                "Inputs of stage {stage_id} of job {job_id} are not resolved yet"
+            )));
+        };
+        let partitions = match partition {
+            Some(partition) if partition >= num_partitions => {
+                return Err(BallistaError::General(format!(
+                    "Stage {stage_id} of job {job_id} has {num_partitions} partitions"
+                )));
+            }
+            Some(partition) => vec![partition],
+            None => (0..num_partitions).collect(),
+        };
+
+        let plan = stage_input_plan(stage);
+        if !is_streaming(&plan) {
+            return Err(BallistaError::General(format!(
+                "Stage {stage_id} of job {job_id} buffers its rows and can't be sampled"
+            )));
+        }
+        let state = session_ctx.state();
+        let session_config = graph
+            .session_config()
+            .as_ref()
+            .clone()
+            .with_ballista_fetch_token(graph.fetch_token().clone())
+            .with_ballista_grpc_security(security);
+        let task_ctx = Arc::new(TaskContext::new(
+            Some(format!("{job_id}/{stage_id}/sample")),
+            graph.session_id().to_owned(),
+            session_config,
+            state.scalar_functions().clone(),
+            state.aggregate_functions().clone(),
+            state.window_functions().clone(),
+            state.runtime_env().clone(),
+        ));
+
+        sample_plan(plan, &partitions, limit, task_ctx).await
+    }
+
+    /// Whether the stage only scans, filters and projects its inputs, so that sampling
+    /// it holds no more than a few batches in the memory of the scheduler
+    pub(crate) fn can_sample(stage: &ExecutionStage) -> bool {
+        is_streaming(&stage_input_plan(stage))
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.batches.iter().map(|batch| batch.num_rows()).sum()
+    }
+
+    /// Encode the rows of the sample in Arrow IPC stream format, empty if there are
+    /// none
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let Some(first) = self.batches.first() else {
+            return Ok(vec![]);
+        };
+        let mut writer = StreamWriter::try_new(vec![], first.schema().as_ref())?;
+        for batch in &self.batches {
+            writer.write(batch)?;
+        }
+        Ok(writer.into_inner()?)
+    }
+}
+
+/// The plan of a stage producing the rows it writes to its output partitions
+fn stage_input_plan(stage: &ExecutionStage) -> Arc<dyn ExecutionPlan> {
+    let plan = match stage {
+        ExecutionStage::UnResolved(stage) => &stage.plan,
+        ExecutionStage::Resolved(stage) => &stage.plan,
+        ExecutionStage::Running(stage) => &stage.plan,
+        ExecutionStage::Successful(stage) => &stage.plan,
+        ExecutionStage::Failed(stage) => &stage.plan,
+    };
+    match plan.as_any().downcast_ref::<ShuffleWriterExec>() {
+        Some(writer) => writer.children()[0].clone(),
+        None => plan.clone(),
+    }
+}
+
+/// Whether the rows of the plan stream from its scans without being buffered
+fn is_streaming(plan: &Arc<dyn ExecutionPlan>) -> bool {
+    let children = plan.children();
+    if children.is_empty() {
+        return true;
+    }
+    let any = plan.as_any();
+    let streams = any.is::<ProjectionExec>()
+        || any.is::<FilterExec>()
+        || any.is::<CoalesceBatchesExec>()
+        || any.is::<LocalLimitExec>()
+        || any.is::<GlobalLimitExec>();
+    streams && children.into_iter().all(is_streaming)
+}
+
+/// Execute the `partitions` of `plan` in order until `limit` rows were read
+async fn sample_plan(
+    plan: Arc<dyn ExecutionPlan>,
+    partitions: &[usize],
+    limit: u64,
+    task_ctx: Arc<TaskContext>,
+) -> Result<StageSample> {
+    let limit = match limit {
+        0 => DEFAULT_SAMPLE_ROWS,
+        limit => limit.min(MAX_SAMPLE_ROWS),
+    } as usize;
+
+    let mut sample = StageSample::default();
+    let mut num_rows = 0;
+    'partitions: for &partition in partitions {
+        sample.partitions.push(partition as u32);
+        let mut stream = plan.execute(partition, task_ctx.clone())?;
+        while let Some(batch) = stream.try_next().await? {
+            let rows = batch.num_rows().min(limit - num_rows);
+            if rows > 0 {
+                sample.batches.push(batch.slice(0, rows));
+                num_rows += rows;
+            }
+            if num_rows >= limit {
+                break 'partitions;
+            }
+        }
+    }
+    Ok(sample)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ballista_core::error::Result;
+    use datafusion::arrow::array::{AsArray, Int32Array};
+    use datafusion::arrow::datatypes::{DataType, Field, Int32Type, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::execution::TaskContext;
+    use datafusion::logical_expr::Operator;
+    use datafusion::physical_expr::expressions::{col, lit, BinaryExpr};
+    use datafusion::physical_expr::PhysicalSortExpr;
+    use datafusion::physical_plan::filter::FilterExec;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use datafusion::physical_plan::projection::ProjectionExec;
+    use datafusion::physical_plan::sorts::sort::SortExec;
+    use datafusion::physical_plan::ExecutionPlan;
+
+    use super::{is_streaming, sample_plan, StageSample};
+
+    fn sample_values(sample: &StageSample) -> Vec<i32> {
+        sample
+            .batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sample_plan() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = |values: Vec<i32>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))])
+                .unwrap()
+        };
+        let plan = Arc::new(MemoryExec::try_new(
+            &[
+                vec![batch(vec![1, 2]), batch(vec![3])],
+                vec![batch(vec![4, 5, 6])],
+                vec![batch(vec![7])],
+            ],
+            schema.clone(),
+            None,
+        )?);
+        let task_ctx = Arc::new(TaskContext::default());
+
+        // partitions are read in order until the limit is reached
+        let sample = sample_plan(plan.clone(), &[0, 1, 2], 4, task_ctx.clone()).await?;
+        assert_eq!(sample_values(&sample), vec![1, 2, 3, 4]);
+        assert_eq!(sample.partitions, vec![0, 1]);
+        assert_eq!(sample.num_rows(), 4);
+
+        let sample = sample_plan(plan.clone(), &[1], 0, task_ctx.clone()).await?;
+        assert_eq!(sample_values(&sample), vec![4, 5, 6]);
+        assert_eq!(sample.partitions, vec![1]);
+
+        let sample = sample_plan(plan, &[2, 0], 2, task_ctx).await?;
+        assert_eq!(sample_values(&sample), vec![7, 1]);
+        assert!(!sample.encode()?.is_empty());
+        assert!(StageSample::default().encode()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_streaming() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let scan: Arc<dyn ExecutionPlan> =
+            Arc::new(MemoryExec::try_new(&[vec![]], schema.clone(), None)?);
+        let a = col("a", &schema)?;
+        let filter: Arc<dyn ExecutionPlan> = Arc::new(FilterExec::try_new(
+            Arc::new(BinaryExpr::new(a.clone(), Operator::Gt, lit(1))),
+            scan.clone(),
+        )?);
+        let projection: Arc<dyn ExecutionPlan> = Arc::new(ProjectionExec::try_new(
+            vec![(a.clone(), "a".to_owned())],
+            filter.clone(),
+        )?);
+        assert!(is_streaming(&scan));
+        assert!(is_streaming(&projection));
+
+        // sorts buffer their inputs, as do the stages above them
+        let sort: Arc<dyn ExecutionPlan> = Arc::new(SortExec::new(
+            vec![PhysicalSortExpr {
+                expr: a.clone(),
+                options: Default::default(),
+            }],
+            filter,
+        ));
+        assert!(!is_streaming(&sort));
+        let projection: Arc<dyn ExecutionPlan> =
+            Arc::new(ProjectionExec::try_new(vec![(a, "a".to_owned())], sort)?);
+        assert!(!is_streaming(&projection));
+        Ok(())
+    }
+}
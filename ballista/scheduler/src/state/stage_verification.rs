@@ -0,0 +1,424 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Verification of the output of selected stages. Every successful task of a verified
+//! stage is executed a second time on another executor, under the verification job id
+//! of its job so that both outputs are kept apart, and the checksums of both outputs are
+//! compared. A mismatch reveals a non-deterministic operator or corruption by faulty
+//! hardware, and fails the job with a diagnostic instead of returning its results.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use ballista_core::serde::protobuf::{
+    job_status, task_status, JobStatus, RunningJob, TaskStatus,
+};
+use log::{error, warn};
+use parking_lot::Mutex;
+
+use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::timestamp_millis;
+
+/// Suffix of the job ids the verification tasks of a job are executed under
+pub const VERIFICATION_JOB_ID_SUFFIX: &str = "-verify";
+
+/// Id of the job the verification tasks of `job_id` are executed under
+pub fn verification_job_id(job_id: &str) -> String {
+    format!("{job_id}{VERIFICATION_JOB_ID_SUFFIX}")
+}
+
+/// Id of the verified job, if `job_id` is the id of verification tasks
+pub fn verified_job_id(job_id: &str) -> Option<&str> {
+    job_id.strip_suffix(VERIFICATION_JOB_ID_SUFFIX)
+}
+
+/// Number of rows and checksum of each output partition of a task
+type TaskOutput = HashMap<u64, (u64, Option<u64>)>;
+
+fn task_output(status: &TaskStatus) -> Option<TaskOutput> {
+    match &status.status {
+        Some(task_status::Status::Successful(successful)) => Some(
+            successful
+                .partitions
+                .iter()
+                .map(|p| (p.partition_id, (p.num_rows, p.checksum)))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Job id, stage id, stage attempt and partition of a task
+type TaskKey = (String, u32, u32, u32);
+
+fn task_key(job_id: &str, status: &TaskStatus) -> TaskKey {
+    (
+        job_id.to_owned(),
+        status.stage_id,
+        status.stage_attempt_num,
+        status.partition_id,
+    )
+}
+
+/// Outputs of the two executions of a task which differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationMismatch {
+    pub job_id: String,
+    pub stage_id: u32,
+    pub partition_id: u32,
+    pub executor_id: String,
+    pub verifying_executor_id: String,
+    /// Output partitions whose number of rows or checksum differ
+    pub output_partitions: Vec<u64>,
+}
+
+impl fmt::Display for VerificationMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Output of task {} of stage {} differs between executors {} and {} in output partitions {:?}, \
+            the stage is non-deterministic or one of the executors is faulty",
+            self.partition_id,
+            self.stage_id,
+            self.executor_id,
+            self.verifying_executor_id,
+            self.output_partitions
+        )
+    }
+}
+
+struct PendingVerification {
+    executor_id: String,
+    verifying_executor_id: String,
+    output: TaskOutput,
+}
+
+#[derive(Default)]
+struct Verifications {
+    /// Outputs of the verified tasks, awaiting the outputs of their verification tasks
+    pending: HashMap<TaskKey, PendingVerification>,
+    /// Completion events of jobs, held until their pending verifications complete
+    deferred: HashMap<String, QueryStageSchedulerEvent>,
+    /// Jobs failed by a mismatch
+    failed: HashSet<String>,
+}
+
+impl Verifications {
+    fn has_pending(&self, job_id: &str) -> bool {
+        self.pending.keys().any(|(id, _, _, _)| id == job_id)
+    }
+
+    /// Release the completion event of a job once it has no pending verification
+    fn release(&mut self, job_id: &str) -> Option<QueryStageSchedulerEvent> {
+        if self.has_pending(job_id) {
+            None
+        } else {
+            self.deferred.remove(job_id)
+        }
+    }
+}
+
+/// Tracks the verification tasks of verified stages, and holds back the completion of
+/// jobs until all their verifications completed
+#[derive(Clone, Default)]
+pub struct StageVerification {
+    verifications: Arc<Mutex<Verifications>>,
+}
+
+impl StageVerification {
+    /// Record the output of a successful task executed by `executor_id`, to be compared
+    /// with the output of its verification task executed by `verifying_executor_id`
+    pub fn expect(
+        &self,
+        executor_id: &str,
+        verifying_executor_id: &str,
+        status: &TaskStatus,
+    ) {
+        if let Some(output) = task_output(status) {
+            self.verifications.lock().pending.insert(
+                task_key(&status.job_id, status),
+                PendingVerification {
+                    executor_id: executor_id.to_owned(),
+                    verifying_executor_id: verifying_executor_id.to_owned(),
+                    output,
+                },
+            );
+        }
+    }
+
+    /// Give up on verifying a task, e.g. because its verification task could not be
+    /// launched, returning the completion event of its job if it was held back
+    pub fn abandon(&self, status: &TaskStatus) -> Option<QueryStageSchedulerEvent> {
+        let mut verifications = self.verifications.lock();
+        verifications
+            .pending
+            .remove(&task_key(&status.job_id, status));
+        verifications.release(&status.job_id)
+    }
+
+    /// Compare the output of a verification task executed by `executor_id` with the
+    /// output of the task it verifies, returning the events to publish: the failure of
+    /// the job on a mismatch, or its completion if it was held back by this verification
+    pub fn complete(
+        &self,
+        executor_id: &str,
+        status: &TaskStatus,
+    ) -> Vec<QueryStageSchedulerEvent> {
+        let Some(job_id) = verified_job_id(&status.job_id) else {
+            return vec![];
+        };
+        let mut verifications = self.verifications.lock();
+        if matches!(&status.status, Some(task_status::Status::Running(_))) {
+            return vec![];
+        }
+        let Some(pending) = verifications.pending.remove(&task_key(job_id, status))
+        else {
+            return vec![];
+        };
+
+        let Some(output) = task_output(status) else {
+            warn!(
+                "Verification of task {} of stage {}/{} failed to execute on executor {executor_id}",
+                status.partition_id,
+                job_id,
+                status.stage_id
+            );
+            return verifications.release(job_id).into_iter().collect();
+        };
+
+        let mut output_partitions: Vec<u64> = pending
+            .output
+            .keys()
+            .chain(output.keys())
+            .filter(|p| pending.output.get(*p) != output.get(*p))
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if output_partitions.is_empty() {
+            return verifications.release(job_id).into_iter().collect();
+        }
+        output_partitions.sort_unstable();
+
+        let mismatch = VerificationMismatch {
+            job_id: job_id.to_owned(),
+            stage_id: status.stage_id,
+            partition_id: status.partition_id,
+            executor_id: pending.executor_id,
+            verifying_executor_id: executor_id.to_owned(),
+            output_partitions,
+        };
+        error!("Verification of job {job_id} failed: {mismatch}");
+
+        // Fail the job rather than returning results which can't be trusted
+        verifications
+            .pending
+            .retain(|(id, _, _, _), _| id != job_id);
+        let queued_at = match verifications.deferred.remove(job_id) {
+            Some(QueryStageSchedulerEvent::JobFinished { queued_at, .. }) => queued_at,
+            _ => 0,
+        };
+        if !verifications.failed.insert(job_id.to_owned()) {
+            return vec![];
+        }
+        vec![QueryStageSchedulerEvent::JobRunningFailed {
+            job_id: job_id.to_owned(),
+            fail_message: format!("Stage verification failed: {mismatch}"),
+            queued_at,
+            failed_at: timestamp_millis(),
+        }]
+    }
+
+    /// Report a successful job as running while some of its verifications are pending,
+    /// so that its results are not returned before being verified
+    pub fn hold_back(&self, status: JobStatus) -> JobStatus {
+        let Some(job_status::Status::Successful(successful)) = &status.status else {
+            return status;
+        };
+        let (queued_at, started_at) = (successful.queued_at, successful.started_at);
+        let verifications = self.verifications.lock();
+        if !verifications.has_pending(&status.job_id)
+            && !verifications.failed.contains(&status.job_id)
+        {
+            return status;
+        }
+
+        JobStatus {
+            status: Some(job_status::Status::Running(RunningJob {
+                queued_at,
+                started_at,
+                scheduler: String::new(),
+                partition_location: vec![],
+            })),
+            ..status
+        }
+    }
+
+    /// Hold back the completion of a job while some of its verifications are pending,
+    /// and drop it if the job was failed by a mismatch
+    pub fn defer_completion(
+        &self,
+        event: QueryStageSchedulerEvent,
+    ) -> Option<QueryStageSchedulerEvent> {
+        let QueryStageSchedulerEvent::JobFinished { job_id, .. } = &event else {
+            return Some(event);
+        };
+        let mut verifications = self.verifications.lock();
+        if verifications.failed.contains(job_id) {
+            None
+        } else if verifications.has_pending(job_id) {
+            verifications.deferred.insert(job_id.clone(), event);
+            None
+        } else {
+            Some(event)
+        }
+    }
+
+    /// Give up on the verifications executed by a lost executor, returning the
+    /// completion events of the jobs they held back
+    pub fn executor_lost(&self, executor_id: &str) -> Vec<QueryStageSchedulerEvent> {
+        let mut verifications = self.verifications.lock();
+        let job_ids: HashSet<String> = verifications
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.verifying_executor_id == executor_id)
+            .map(|((job_id, _, _, _), _)| job_id.clone())
+            .collect();
+        verifications
+            .pending
+            .retain(|_, pending| pending.verifying_executor_id != executor_id);
+
+        job_ids
+            .iter()
+            .filter_map(|job_id| verifications.release(job_id))
+            .collect()
+    }
+
+    /// Stop tracking the verifications of a job
+    pub fn forget_job(&self, job_id: &str) {
+        let mut verifications = self.verifications.lock();
+        verifications
+            .pending
+            .retain(|(id, _, _, _), _| id != job_id);
+        verifications.deferred.remove(job_id);
+        verifications.failed.remove(job_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ballista_core::serde::protobuf::{
+        job_status, task_status, FailedTask, JobStatus, ShuffleWritePartition,
+        SuccessfulJob, SuccessfulTask, TaskStatus,
+    };
+
+    use crate::scheduler_server::event::QueryStageSchedulerEvent;
+    use crate::state::stage_verification::{verification_job_id, StageVerification};
+
+    fn status(job_id: &str, num_rows: u64, checksum: u64) -> TaskStatus {
+        TaskStatus {
+            job_id: job_id.to_owned(),
+            stage_id: 1,
+            partition_id: 0,
+            status: Some(task_status::Status::Successful(SuccessfulTask {
+                executor_id: String::new(),
+                partitions: vec![ShuffleWritePartition {
+                    partition_id: 0,
+                    num_rows,
+                    checksum: Some(checksum),
+                    ..Default::default()
+                }],
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn finished(job_id: &str) -> QueryStageSchedulerEvent {
+        QueryStageSchedulerEvent::JobFinished {
+            job_id: job_id.to_owned(),
+            queued_at: 1,
+            completed_at: 2,
+        }
+    }
+
+    #[test]
+    fn test_matching_outputs() {
+        let verification = StageVerification::default();
+        verification.expect("executor-1", "executor-2", &status("job", 3, 42));
+
+        // The job completes once its verifications completed
+        let successful = JobStatus {
+            job_id: "job".to_owned(),
+            status: Some(job_status::Status::Successful(SuccessfulJob::default())),
+            ..Default::default()
+        };
+        assert!(matches!(
+            verification.hold_back(successful.clone()).status,
+            Some(job_status::Status::Running(_))
+        ));
+        assert!(verification.defer_completion(finished("job")).is_none());
+        let events = verification
+            .complete("executor-2", &status(&verification_job_id("job"), 3, 42));
+        assert!(matches!(
+            events.as_slice(),
+            [QueryStageSchedulerEvent::JobFinished { job_id, .. }] if job_id == "job"
+        ));
+        assert!(verification.defer_completion(finished("job")).is_some());
+        assert_eq!(verification.hold_back(successful.clone()), successful);
+    }
+
+    #[test]
+    fn test_mismatching_outputs() {
+        let verification = StageVerification::default();
+        verification.expect("executor-1", "executor-2", &status("job", 3, 42));
+        assert!(verification.defer_completion(finished("job")).is_none());
+
+        let events = verification
+            .complete("executor-2", &status(&verification_job_id("job"), 3, 43));
+        let [QueryStageSchedulerEvent::JobRunningFailed {
+            job_id,
+            fail_message,
+            queued_at,
+            ..
+        }] = events.as_slice()
+        else {
+            panic!("Expected the job to fail, got {events:?}");
+        };
+        assert_eq!(job_id, "job");
+        assert_eq!(*queued_at, 1);
+        assert!(fail_message.contains("executors executor-1 and executor-2"));
+
+        // The results of the job are never returned
+        assert!(verification.defer_completion(finished("job")).is_none());
+    }
+
+    #[test]
+    fn test_failed_verification() {
+        let verification = StageVerification::default();
+        verification.expect("executor-1", "executor-2", &status("job", 3, 42));
+        assert!(verification.defer_completion(finished("job")).is_none());
+
+        let mut failed = status(&verification_job_id("job"), 3, 42);
+        failed.status = Some(task_status::Status::Failed(FailedTask::default()));
+        let events = verification.complete("executor-2", &failed);
+        assert!(matches!(
+            events.as_slice(),
+            [QueryStageSchedulerEvent::JobFinished { .. }]
+        ));
+    }
+}
@@ -16,43 +16,62 @@
 // under the License.
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::timestamp_millis;
 
 use crate::state::execution_graph::{
     ExecutionGraph, ExecutionStage, RunningTaskInfo, TaskDescription,
 };
 use crate::state::executor_manager::ExecutorManager;
+use crate::state::job_driver::JobDrivers;
+use crate::state::job_events::JobEvents;
+use crate::state::job_retention::{ended_at, JobArchive};
+use crate::state::stage_verification::{verification_job_id, StageVerification};
 
-use ballista_core::error::BallistaError;
+use ballista_core::compression::CompressionCodec;
 use ballista_core::error::Result;
-use ballista_core::extension::SessionConfigHelperExt;
+use ballista_core::error::{BallistaError, ErrorCode};
+use ballista_core::execution_plans::{with_scan_options, ShuffleReaderExec};
+use ballista_core::extension::{SessionConfigExt, SessionConfigHelperExt};
+use ballista_core::planner::stage_plan_fingerprint;
+use ballista_core::registry::task_random_seed;
 use datafusion::prelude::SessionConfig;
 
 use crate::cluster::JobState;
 use ballista_core::serde::protobuf::{
-    job_status, JobStatus, MultiTaskDefinition, TaskDefinition, TaskId, TaskStatus,
+    job_status, task_status, JobPlanStage, JobStatus, MultiTaskDefinition,
+    ShuffleReaderPartition, StageInput, SuccessfulJob, TaskDefinition, TaskId,
+    TaskPlanFormat, TaskStatus,
 };
 use ballista_core::serde::scheduler::ExecutorMetadata;
 use ballista_core::serde::BallistaCodec;
+use bytes::Bytes;
 use dashmap::DashMap;
 
-use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_plan::{ExecutionPlan, PhysicalExpr};
 use datafusion_proto::logical_plan::AsLogicalPlan;
-use datafusion_proto::physical_plan::AsExecutionPlan;
+use datafusion_proto::physical_plan::to_proto::serialize_physical_expr;
+use datafusion_proto::physical_plan::{AsExecutionPlan, DefaultPhysicalExtensionCodec};
 use log::{debug, error, info, warn};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
-use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, RwLock};
 
 use tracing::trace;
 
 type ActiveJobCache = Arc<DashMap<String, JobInfoCache>>;
+/// Task statuses queued to the drivers of their jobs, by job ID
+pub(crate) type JobUpdates = Vec<(
+    String,
+    oneshot::Receiver<Result<Vec<QueryStageSchedulerEvent>>>,
+)>;
 
-// TODO move to configuration file
 /// Default max failure attempts for task level retry
 pub const TASK_MAX_FAILURES: usize = 4;
 /// Default max failure attempts for stage level retry
@@ -118,6 +137,24 @@ pub struct TaskManager<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     // Cache for active jobs curated by this scheduler
     active_job_cache: ActiveJobCache,
     launcher: Arc<dyn TaskLauncher>,
+    // Archive which completed jobs are written to before being purged from the state
+    job_archive: Option<JobArchive>,
+    // Principals which submitted the jobs
+    job_owners: Arc<DashMap<String, String>>,
+    // Verification tasks of the verified stages of the jobs
+    stage_verification: StageVerification,
+    // Max failure attempts for task level retry
+    task_max_failures: usize,
+    // Max failure attempts for stage level retry, after fetch failures of its inputs
+    stage_max_failures: usize,
+    // Size from which plans are dispatched by reference, 0 if they are always inline
+    plan_reference_threshold: usize,
+    // Codec compressing the dispatched plans, if any
+    plan_compression: Option<Arc<dyn CompressionCodec>>,
+    // Events of the jobs, pushed to the clients subscribed to them
+    job_events: JobEvents,
+    // Drivers applying the updates of the active jobs
+    job_drivers: JobDrivers,
 }
 
 #[derive(Clone)]
@@ -126,17 +163,58 @@ pub struct JobInfoCache {
     pub execution_graph: Arc<RwLock<ExecutionGraph>>,
     // Cache for job status
     pub status: Option<job_status::Status>,
-    // Cache for encoded execution stage plan to avoid duplicated encoding for multiple tasks
-    encoded_stage_plans: HashMap<usize, Vec<u8>>,
+    // Cache for encoded execution stage plans, keyed by stage id and stage attempt, to avoid
+    // duplicated encoding for multiple tasks. Plans are shared, as this cache is cloned on
+    // every scheduling round
+    encoded_stage_plans: HashMap<(usize, usize), EncodedPlan>,
+    // Encoded optimized logical plan of the job, if it's dispatched to executors as such
+    // rather than as the physical plans of its stages
+    encoded_logical_plan: Option<EncodedPlan>,
+    // Token of the job, dispatched with its tasks
+    fetch_token: String,
+    // Principal which submitted the job, whose executor pools run its tasks, none if
+    // submitted anonymously
+    pub tenant: Option<String>,
+    // Reservation of executor slots the job runs with, if any
+    pub slot_reservation: Option<String>,
+}
+
+/// A plan encoded for dispatching with tasks
+#[derive(Clone)]
+struct EncodedPlan {
+    plan: Bytes,
+    // Hash of the plan if it's dispatched by reference, for executors to fetch it with
+    hash: Option<String>,
+}
+
+/// The plan dispatched with the tasks of a stage
+struct TaskPlan {
+    format: TaskPlanFormat,
+    // Empty if the plan is dispatched by reference
+    plan: Bytes,
+    // Empty unless the plan is dispatched by reference
+    plan_hash: String,
+    // Name of the codec which compressed the plan, empty if none
+    plan_compression: String,
+    inputs: Vec<StageInput>,
+    // Fingerprint of the stage, 0 unless the plan is the logical plan of the job
+    plan_fingerprint: u64,
 }
 
 impl JobInfoCache {
     pub fn new(graph: ExecutionGraph) -> Self {
         let status = graph.status().status.clone();
+        let fetch_token = graph.fetch_token().as_str().to_owned();
+        let slot_reservation = graph.session_config().ballista_job_slot_reservation();
+        let tenant = graph.owner().map(str::to_owned);
         Self {
             execution_graph: Arc::new(RwLock::new(graph)),
             status,
             encoded_stage_plans: HashMap::new(),
+            encoded_logical_plan: None,
+            fetch_token,
+            tenant,
+            slot_reservation,
         }
     }
 }
@@ -162,6 +240,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             scheduler_id: scheduler_id.clone(),
             active_job_cache: Arc::new(DashMap::new()),
             launcher: Arc::new(DefaultTaskLauncher::new(scheduler_id)),
+            job_archive: None,
+            job_owners: Arc::new(DashMap::new()),
+            stage_verification: StageVerification::default(),
+            task_max_failures: TASK_MAX_FAILURES,
+            stage_max_failures: STAGE_MAX_FAILURES,
+            plan_reference_threshold: 0,
+            plan_compression: None,
+            job_events: JobEvents::default(),
+            job_drivers: JobDrivers::default(),
         }
     }
 
@@ -178,9 +265,54 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             scheduler_id,
             active_job_cache: Arc::new(DashMap::new()),
             launcher,
+            job_archive: None,
+            job_owners: Arc::new(DashMap::new()),
+            stage_verification: StageVerification::default(),
+            task_max_failures: TASK_MAX_FAILURES,
+            stage_max_failures: STAGE_MAX_FAILURES,
+            plan_reference_threshold: 0,
+            plan_compression: None,
+            job_events: JobEvents::default(),
+            job_drivers: JobDrivers::default(),
         }
     }
 
+    /// Archive completed jobs to `job_archive` before purging them from the state
+    pub fn with_job_archive(mut self, job_archive: Option<JobArchive>) -> Self {
+        self.job_archive = job_archive;
+        self
+    }
+
+    /// Dispatch the plans of at least `threshold` bytes by reference, for executors to
+    /// fetch them once with `GetTaskPlan` rather than with every task. 0 means plans
+    /// are always dispatched inline
+    pub fn with_plan_reference_threshold(mut self, threshold: usize) -> Self {
+        self.plan_reference_threshold = threshold;
+        self
+    }
+
+    /// Compress the dispatched plans, whether inline or by reference, with `codec`,
+    /// which the executors must register too
+    pub fn with_plan_compression(
+        mut self,
+        codec: Option<Arc<dyn CompressionCodec>>,
+    ) -> Self {
+        self.plan_compression = codec;
+        self
+    }
+
+    /// Fail stages whose tasks failed `task_max_failures` times, and jobs whose stages
+    /// were rerun `stage_max_failures` times after losing their inputs
+    pub fn with_max_failures(
+        mut self,
+        task_max_failures: usize,
+        stage_max_failures: usize,
+    ) -> Self {
+        self.task_max_failures = task_max_failures;
+        self.stage_max_failures = stage_max_failures;
+        self
+    }
+
     /// Enqueue a job for scheduling
     pub fn queue_job(&self, job_id: &str, job_name: &str, queued_at: u64) -> Result<()> {
         self.state.accept_job(job_id, job_name, queued_at)
@@ -197,15 +329,32 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         self.active_job_cache.len()
     }
 
+    /// Get the number of tasks of the running jobs which are ready to run, but not
+    /// bound to an executor yet. The value returned is strictly a point-in-time snapshot
+    pub async fn pending_task_number(&self) -> usize {
+        let graphs: Vec<_> = self
+            .active_job_cache
+            .iter()
+            .map(|job| job.execution_graph.clone())
+            .collect();
+        let mut pending_tasks = 0;
+        for graph in graphs {
+            pending_tasks += graph.read().await.available_tasks();
+        }
+        pending_tasks
+    }
+
     /// Generate an ExecutionGraph for the job and save it to the persistent state.
     /// By default, this job will be curated by the scheduler which receives it.
     /// Then we will also save it to the active execution graph
+    #[allow(clippy::too_many_arguments)]
     pub async fn submit_job(
         &self,
         job_id: &str,
         job_name: &str,
         session_id: &str,
         plan: Arc<dyn ExecutionPlan>,
+        logical_plan: Option<Vec<u8>>,
         queued_at: u64,
         session_config: Arc<SessionConfig>,
     ) -> Result<()> {
@@ -218,17 +367,48 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             queued_at,
             session_config,
         )?;
+        graph.set_owner(self.job_owner(job_id));
         info!("Submitting execution graph: {:?}", graph);
 
         self.state.submit_job(job_id.to_string(), &graph).await?;
 
         graph.revive();
-        self.active_job_cache
-            .insert(job_id.to_owned(), JobInfoCache::new(graph));
+        let mut encoded_stage_plans = HashMap::new();
+        if logical_plan.is_none() {
+            self.encode_running_stage_plans(&graph, &mut encoded_stage_plans);
+        }
+        let mut job_info = JobInfoCache::new(graph);
+        job_info.encoded_stage_plans = encoded_stage_plans;
+        job_info.encoded_logical_plan = logical_plan
+            .map(|plan| self.dispatched_plan(plan.into()))
+            .transpose()?;
+        self.active_job_cache.insert(job_id.to_owned(), job_info);
 
         Ok(())
     }
 
+    /// Take over the jobs left running by a previous scheduler sharing the job state.
+    /// Returns the ids of the jobs recovered
+    pub async fn recover_jobs(&self) -> Result<Vec<String>> {
+        let graphs = self.state.recover_jobs().await?;
+        let mut recovered = Vec::with_capacity(graphs.len());
+        for mut graph in graphs {
+            graph.take_over(&self.scheduler_id);
+            graph.revive();
+            let mut encoded_stage_plans = HashMap::new();
+            self.encode_running_stage_plans(&graph, &mut encoded_stage_plans);
+            let job_id = graph.job_id().to_owned();
+            if let Some(owner) = graph.owner() {
+                self.set_job_owner(&job_id, owner);
+            }
+            let mut job_info = JobInfoCache::new(graph);
+            job_info.encoded_stage_plans = encoded_stage_plans;
+            self.active_job_cache.insert(job_id.clone(), job_info);
+            recovered.push(job_id);
+        }
+        Ok(recovered)
+    }
+
     pub fn get_running_job_cache(&self) -> Arc<HashMap<String, JobInfoCache>> {
         let ret = self
             .active_job_cache
@@ -254,12 +434,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             if let Some(cached) = self.get_active_execution_graph(job_id) {
                 let graph = cached.read().await;
                 jobs.push(graph.deref().into());
-            } else {
-                let graph = self.state
-                    .get_execution_graph(job_id)
-                    .await?
-                    .ok_or_else(|| BallistaError::Internal(format!("Error getting job overview, no execution graph found for job {job_id}")))?;
+            } else if let Some(graph) = self.state.get_execution_graph(job_id).await? {
                 jobs.push((&graph).into());
+            } else if let Some(status) = self.state.get_job_status(job_id).await? {
+                // the graphs of the completed jobs of other schedulers are not kept
+                jobs.push(status.into());
+            } else {
+                return Err(BallistaError::Internal(format!(
+                    "Error getting job overview, no status found for job {job_id}"
+                )));
             }
         }
         Ok(jobs)
@@ -271,15 +454,101 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         if let Some(graph) = self.get_active_execution_graph(job_id) {
             let guard = graph.read().await;
 
-            Ok(Some(guard.status().clone()))
+            // the results of a job are only returned once its stages are verified
+            Ok(Some(
+                self.stage_verification.hold_back(guard.status().clone()),
+            ))
+        } else if let Some(status) = self.state.get_job_status(job_id).await? {
+            Ok(Some(status))
+        } else if let Some(job_archive) = &self.job_archive {
+            job_archive.get(job_id).await
         } else {
-            self.state.get_job_status(job_id).await
+            Ok(None)
+        }
+    }
+
+    /// Events of the jobs, pushed to the clients subscribed to them
+    pub fn job_events(&self) -> &JobEvents {
+        &self.job_events
+    }
+
+    /// Publish the current status of a job to the clients subscribed to its events
+    pub(crate) async fn publish_job_status(&self, job_id: &str) {
+        if !self.job_events.has_subscribers() {
+            return;
+        }
+        match self.get_job_status(job_id).await {
+            Ok(Some(status)) => self.job_events.publish_status(status),
+            Ok(None) => warn!("Not publishing the status of unknown job {job_id}"),
+            Err(e) => warn!("Failed to publish the status of job {job_id}: {e:?}"),
         }
     }
 
+    /// Verification tasks of the verified stages of the jobs
+    pub(crate) fn stage_verification(&self) -> &StageVerification {
+        &self.stage_verification
+    }
+
+    /// Record the principal which submitted a job
+    pub(crate) fn set_job_owner(&self, job_id: &str, principal: &str) {
+        self.job_owners
+            .insert(job_id.to_owned(), principal.to_owned());
+    }
+
+    /// Get the principal which submitted a job, if known
+    pub(crate) fn job_owner(&self, job_id: &str) -> Option<String> {
+        self.job_owners
+            .get(job_id)
+            .map(|owner| owner.value().clone())
+    }
+
+    /// Get the token of a job, which its clients present to fetch its results
+    #[cfg(feature = "flight-sql")]
+    pub(crate) async fn job_fetch_token(&self, job_id: &str) -> Result<Option<String>> {
+        if let Some(graph) = self.get_active_execution_graph(job_id) {
+            return Ok(Some(graph.read().await.fetch_token().as_str().to_owned()));
+        }
+        if let Some(graph) = self.state.get_execution_graph(job_id).await? {
+            return Ok(Some(graph.fetch_token().as_str().to_owned()));
+        }
+        // archived jobs only keep the token in the locations of their results
+        let status = self.get_job_status(job_id).await?;
+        Ok(status.and_then(|status| match status.status {
+            Some(job_status::Status::Successful(job)) => job
+                .partition_location
+                .first()
+                .map(|location| location.fetch_token.clone()),
+            _ => None,
+        }))
+    }
+
+    /// Get the statuses of the completed jobs kept in the state
+    pub async fn get_completed_jobs(&self) -> Result<Vec<JobStatus>> {
+        self.state.get_completed_jobs().await
+    }
+
+    /// Purge a completed job from the state, after writing it to the job archive if
+    /// one is configured. Returns false if the job is unknown or not completed.
+    pub(crate) async fn purge_job(&self, job_id: &str) -> Result<bool> {
+        let Some(status) = self.state.get_job_status(job_id).await? else {
+            return Ok(false);
+        };
+        if ended_at(&status).is_none() {
+            return Ok(false);
+        }
+
+        if let Some(job_archive) = &self.job_archive {
+            job_archive.archive(&status).await?;
+        }
+        self.state.remove_job(job_id).await?;
+        self.job_owners.remove(job_id);
+        self.stage_verification.forget_job(job_id);
+
+        Ok(true)
+    }
+
     /// Get the execution graph of of a job. First look in the active cache.
     /// If no one found, then in the Active/Completed jobs.
-    #[cfg(feature = "rest-api")]
     pub(crate) async fn get_job_execution_graph(
         &self,
         job_id: &str,
@@ -295,14 +564,13 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
-    /// Update given task statuses in the respective job and return a tuple containing:
-    /// 1. A list of QueryStageSchedulerEvent to publish.
-    /// 2. A list of reservations that can now be offered.
-    pub(crate) async fn update_task_statuses(
+    /// Queue the task statuses reported by an executor to the drivers of their jobs,
+    /// which apply them to the graphs of the jobs. See [Self::await_job_updates]
+    pub(crate) fn dispatch_task_statuses(
         &self,
         executor: &ExecutorMetadata,
         task_status: Vec<TaskStatus>,
-    ) -> Result<Vec<QueryStageSchedulerEvent>> {
+    ) -> JobUpdates {
         let mut job_updates: HashMap<String, Vec<TaskStatus>> = HashMap::new();
         for status in task_status {
             trace!("Task Update\n{:?}", status);
@@ -311,36 +579,112 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             job_task_statuses.push(status);
         }
 
-        let mut events: Vec<QueryStageSchedulerEvent> = vec![];
+        let mut updates = vec![];
         for (job_id, statuses) in job_updates {
-            let num_tasks = statuses.len();
-            debug!("Updating {} tasks in job {}", num_tasks, job_id);
-
-            // let graph = self.get_active_execution_graph(&job_id).await;
-            let job_events = if let Some(cached) =
-                self.get_active_execution_graph(&job_id)
-            {
-                let mut graph = cached.write().await;
-                graph.update_task_status(
-                    executor,
-                    statuses,
-                    TASK_MAX_FAILURES,
-                    STAGE_MAX_FAILURES,
-                )?
-            } else {
+            if !self.active_job_cache.contains_key(&job_id) {
                 // TODO Deal with curator changed case
                 error!("Fail to find job {} in the active cache and it may not be curated by this scheduler", job_id);
-                vec![]
-            };
+                continue;
+            }
+            let task_manager = self.clone();
+            let executor = executor.clone();
+            let update_job_id = job_id.clone();
+            let receiver = self.job_drivers.run(&job_id, async move {
+                task_manager
+                    .apply_task_statuses(&executor, &update_job_id, statuses)
+                    .await
+            });
+            updates.push((job_id, receiver));
+        }
+        updates
+    }
 
-            for event in job_events {
-                events.push(event);
+    /// Wait for the drivers of the jobs to apply the queued task statuses, returning
+    /// the events they resulted in. The jobs whose task statuses failed to be applied
+    /// are failed, without affecting the other jobs
+    pub(crate) async fn await_job_updates(
+        &self,
+        updates: JobUpdates,
+    ) -> Vec<QueryStageSchedulerEvent> {
+        let (job_ids, receivers): (Vec<_>, Vec<_>) = updates.into_iter().unzip();
+        let results = futures::future::join_all(receivers).await;
+
+        let mut events = vec![];
+        for (job_id, result) in job_ids.into_iter().zip(results) {
+            let fail_message = match result {
+                Ok(Ok(job_events)) => {
+                    events.extend(job_events);
+                    continue;
+                }
+                Ok(Err(e)) => format!("Failed to update the tasks of job {job_id}: {e}"),
+                Err(_) => format!("Driver of job {job_id} stopped"),
+            };
+            error!("{fail_message}");
+            if let Some(graph) = self.get_active_execution_graph(&job_id) {
+                let queued_at = graph.read().await.queued_at();
+                events.push(QueryStageSchedulerEvent::JobRunningFailed {
+                    job_id,
+                    fail_message,
+                    queued_at,
+                    failed_at: timestamp_millis(),
+                });
             }
         }
+        events
+    }
+
+    /// Apply the task statuses of a job to its graph, on the driver of the job
+    async fn apply_task_statuses(
+        &self,
+        executor: &ExecutorMetadata,
+        job_id: &str,
+        statuses: Vec<TaskStatus>,
+    ) -> Result<Vec<QueryStageSchedulerEvent>> {
+        debug!("Updating {} tasks in job {}", statuses.len(), job_id);
+
+        let Some(cached) = self.get_active_execution_graph(job_id) else {
+            warn!("Job {job_id} is no longer active, ignoring its task statuses");
+            return Ok(vec![]);
+        };
+        let mut graph = cached.write().await;
+        let completed_stages = self
+            .job_events
+            .has_subscribers()
+            .then(|| successful_stages(&graph));
+        let events = graph.update_task_status(
+            executor,
+            statuses,
+            self.task_max_failures,
+            self.stage_max_failures,
+        )?;
+        if let Some(completed_stages) = completed_stages {
+            self.publish_completed_stages(&graph, &completed_stages);
+        }
 
         Ok(events)
     }
 
+    /// Publish the completions of the stages of a job which were not completed yet
+    fn publish_completed_stages(
+        &self,
+        graph: &ExecutionGraph,
+        completed_stages: &HashSet<usize>,
+    ) {
+        let now_completed = successful_stages(graph);
+        let mut newly_completed: Vec<_> =
+            now_completed.difference(completed_stages).collect();
+        newly_completed.sort();
+        let total_stages = graph.stage_count();
+        for (i, stage_id) in newly_completed.iter().enumerate() {
+            self.job_events.publish_stage_completed(
+                graph.job_id(),
+                **stage_id,
+                completed_stages.len() + i + 1,
+                total_stages,
+            );
+        }
+    }
+
     /// Mark a job to success. This will create a key under the CompletedJobs keyspace
     /// and remove the job from ActiveJobs
     pub(crate) async fn succeed_job(&self, job_id: &str) -> Result<()> {
@@ -366,13 +710,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         &self,
         job_id: &str,
     ) -> Result<(Vec<RunningTaskInfo>, usize)> {
-        self.abort_job(job_id, "Cancelled".to_owned()).await
+        self.abort_job(job_id, ErrorCode::Cancelled, "Cancelled".to_owned())
+            .await
     }
 
     /// Abort the job and return a Vec of running tasks need to cancel
     pub(crate) async fn abort_job(
         &self,
         job_id: &str,
+        error_code: ErrorCode,
         failure_reason: String,
     ) -> Result<(Vec<RunningTaskInfo>, usize)> {
         let (tasks_to_cancel, pending_tasks) = if let Some(graph) =
@@ -389,7 +735,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                 job_id
             );
 
-            guard.fail_job(failure_reason);
+            guard.fail_job(error_code, failure_reason);
 
             self.state.save_job(job_id, &guard).await?;
 
@@ -399,6 +745,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             warn!("Fail to find job {} in the cache, unable to cancel tasks for job, fail the job state only.", job_id);
             (vec![], 0)
         };
+        self.stage_verification.forget_job(job_id);
 
         Ok((tasks_to_cancel, pending_tasks))
     }
@@ -415,6 +762,74 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             .await
     }
 
+    /// Mark a unscheduled job as successful with the results of `job`, served without
+    /// running the job
+    pub async fn succeed_unscheduled_job(
+        &self,
+        job_id: &str,
+        job: SuccessfulJob,
+    ) -> Result<()> {
+        self.state.succeed_unscheduled_job(job_id, job).await
+    }
+
+    /// Record that the plan of an active job was reused from the query cache
+    pub async fn set_planned_from_cache(&self, job_id: &str) {
+        if let Some(graph) = self.get_active_execution_graph(job_id) {
+            graph.write().await.set_planned_from_cache();
+        }
+    }
+
+    /// Attach runtime predicates to the shuffle readers of a stage reading from
+    /// `input_stage_id`, e.g. from dynamic pruning results. Only tasks launched
+    /// afterwards apply them.
+    pub async fn add_runtime_predicates(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        input_stage_id: usize,
+        predicates: &[Arc<dyn PhysicalExpr>],
+    ) -> Result<bool> {
+        if let Some(graph) = self.get_active_execution_graph(job_id) {
+            let mut graph = graph.write().await;
+            if !graph.add_runtime_predicates(stage_id, input_stage_id, predicates)? {
+                return Ok(false);
+            }
+            // The cached encoded plan of the stage is stale now
+            if let Some(mut job_info) = self.active_job_cache.get_mut(job_id) {
+                job_info
+                    .encoded_stage_plans
+                    .retain(|(id, _), _| *id != stage_id);
+            }
+            self.state.save_job(job_id, &graph).await?;
+
+            Ok(true)
+        } else {
+            warn!("Fail to find job {} in the cache", job_id);
+
+            Ok(false)
+        }
+    }
+
+    /// Queue the update of a job to its driver, see [Self::update_job]
+    pub(crate) fn queue_job_update(&self, job_id: &str) {
+        if !self.active_job_cache.contains_key(job_id) {
+            warn!("Fail to find job {} in the cache", job_id);
+            return;
+        }
+        let task_manager = self.clone();
+        let update_job_id = job_id.to_owned();
+        // the outcome is logged rather than awaited
+        drop(self.job_drivers.run(job_id, async move {
+            if let Err(e) = task_manager.update_job(&update_job_id).await {
+                error!(
+                    "Fail to invoke update_job for job {} due to {:?}",
+                    update_job_id, e
+                );
+            }
+            Ok(())
+        }));
+    }
+
     pub async fn update_job(&self, job_id: &str) -> Result<usize> {
         debug!("Update active job {job_id}");
         if let Some(graph) = self.get_active_execution_graph(job_id) {
@@ -430,6 +845,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
 
             let new_tasks = graph.available_tasks() - curr_available_tasks;
 
+            if let Some(mut job_info) = self.active_job_cache.get_mut(job_id) {
+                if job_info.encoded_logical_plan.is_none() {
+                    self.encode_running_stage_plans(
+                        &graph,
+                        &mut job_info.encoded_stage_plans,
+                    );
+                }
+            }
+
             Ok(new_tasks)
         } else {
             warn!("Fail to find job {} in the cache", job_id);
@@ -459,6 +883,83 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         Ok(running_tasks_to_cancel)
     }
 
+    /// Fail the running tasks which exceeded the stage timeout of their job without their
+    /// executor reporting them, so that they are retried, and cancel them on their
+    /// executors. Their status is dropped if their executor reports them later.
+    pub(crate) async fn time_out_tasks(
+        &self,
+        executor_manager: &ExecutorManager,
+    ) -> Result<Vec<QueryStageSchedulerEvent>> {
+        let now = timestamp_millis() as u128;
+        let mut events = vec![];
+        let mut tasks_to_cancel = vec![];
+        for (job_id, job_info) in self.get_running_job_cache().iter() {
+            if job_info
+                .execution_graph
+                .read()
+                .await
+                .timed_out_tasks(now)
+                .is_empty()
+            {
+                continue;
+            }
+            let mut graph = job_info.execution_graph.write().await;
+            for (executor_id, status) in graph.timed_out_tasks(now) {
+                let executor =
+                    match executor_manager.get_executor_metadata(&executor_id).await {
+                        Ok(executor) => executor,
+                        Err(e) => {
+                            // the tasks of lost executors are reset once they are removed
+                            debug!(
+                                "Not timing out task {} of job {job_id}: {e}",
+                                status.task_id
+                            );
+                            continue;
+                        }
+                    };
+                warn!(
+                    "Failing task {} of job {job_id} as executor {executor_id} didn't report it within the stage timeout",
+                    status.task_id
+                );
+                let task_id = status.task_id as usize;
+                tasks_to_cancel.push(RunningTaskInfo {
+                    task_id,
+                    job_id: job_id.clone(),
+                    stage_id: status.stage_id as usize,
+                    partition_id: status.partition_id as usize,
+                    executor_id,
+                });
+                events.extend(graph.update_task_status(
+                    &executor,
+                    vec![status],
+                    self.task_max_failures,
+                    self.stage_max_failures,
+                )?);
+                graph.ignore_task_status(task_id);
+            }
+        }
+        if !tasks_to_cancel.is_empty() {
+            events.push(QueryStageSchedulerEvent::CancelTasks(tasks_to_cancel));
+        }
+        Ok(events)
+    }
+
+    /// Lease the next file to scan to a task of a stage with a splittable scan, see
+    /// [ExecutionGraph::lease_scan_file]
+    pub async fn lease_scan_file(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        partition_id: usize,
+        position: usize,
+    ) -> Result<Option<usize>> {
+        let graph = self.get_active_execution_graph(job_id).ok_or_else(|| {
+            BallistaError::General(format!("Job {job_id} is not running"))
+        })?;
+        let mut graph = graph.write().await;
+        graph.lease_scan_file(stage_id, partition_id, position)
+    }
+
     /// Retrieve the number of available tasks for the given job. The value returned
     /// is strictly a point-in-time snapshot
     pub async fn get_available_task_count(&self, job_id: &str) -> Result<usize> {
@@ -482,22 +983,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         let stage_id = task.partition.stage_id;
 
         if let Some(mut job_info) = self.active_job_cache.get_mut(&job_id) {
-            let plan = if let Some(plan) = job_info.encoded_stage_plans.get(&stage_id) {
-                plan.clone()
-            } else {
-                let mut plan_buf: Vec<u8> = vec![];
-                let plan_proto = U::try_from_physical_plan(
-                    task.plan,
-                    self.codec.physical_extension_codec(),
-                )?;
-                plan_proto.try_encode(&mut plan_buf)?;
-
-                job_info
-                    .encoded_stage_plans
-                    .insert(stage_id, plan_buf.clone());
-
-                plan_buf
-            };
+            let task_plan = self.task_plan(&mut job_info, &task)?;
+            let random_seed =
+                task_random_seed(&job_id, stage_id, task.partition.partition_id);
 
             let task_definition = TaskDefinition {
                 task_id: task.task_id as u32,
@@ -506,13 +994,20 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                 stage_id: stage_id as u32,
                 stage_attempt_num: task.stage_attempt_num as u32,
                 partition_id: task.partition.partition_id as u32,
-                plan,
+                plan: task_plan.plan,
                 session_id: task.session_id,
                 launch_time: SystemTime::now()
                     .duration_since(UNIX_EPOCH)
                     .unwrap()
                     .as_millis() as u64,
                 props: task.session_config.to_key_value_pairs(),
+                plan_format: task_plan.format.into(),
+                inputs: task_plan.inputs,
+                plan_fingerprint: task_plan.plan_fingerprint,
+                plan_hash: task_plan.plan_hash,
+                plan_compression: task_plan.plan_compression,
+                random_seed,
+                fetch_token: job_info.fetch_token.clone(),
             };
             Ok(task_definition)
         } else {
@@ -568,22 +1063,19 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             }
 
             if let Some(mut job_info) = self.active_job_cache.get_mut(&job_id) {
-                let plan = if let Some(plan) = job_info.encoded_stage_plans.get(&stage_id)
+                let task_plan = self.task_plan(&mut job_info, task)?;
+                // executors only learn that a stage is verified, or that the results
+                // of the job are checksummed, from the session settings, which they
+                // also plan the stages of jobs dispatched as logical plans with.
+                // Otherwise only the settings changing the results of functions, such
+                // as the time zone, are sent
+                let props = if task_plan.format == TaskPlanFormat::LogicalPlan
+                    || task.session_config.ballista_verify_stage(stage_id)
+                    || task.session_config.ballista_checksum_results()
                 {
-                    plan.clone()
+                    task.session_config.to_key_value_pairs()
                 } else {
-                    let mut plan_buf: Vec<u8> = vec![];
-                    let plan_proto = U::try_from_physical_plan(
-                        task.plan.clone(),
-                        self.codec.physical_extension_codec(),
-                    )?;
-                    plan_proto.try_encode(&mut plan_buf)?;
-
-                    job_info
-                        .encoded_stage_plans
-                        .insert(stage_id, plan_buf.clone());
-
-                    plan_buf
+                    task.session_config.to_task_key_value_pairs()
                 };
 
                 let launch_time = SystemTime::now()
@@ -599,6 +1091,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                         task_id: task.task_id as u32,
                         task_attempt_num: task.task_attempt as u32,
                         partition_id: task.partition.partition_id as u32,
+                        random_seed: task_random_seed(
+                            &job_id,
+                            stage_id,
+                            task.partition.partition_id,
+                        ),
                     })
                     .collect();
                 multi_tasks.push(MultiTaskDefinition {
@@ -606,10 +1103,16 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                     job_id,
                     stage_id: stage_id as u32,
                     stage_attempt_num: stage_attempt_num as u32,
-                    plan,
+                    plan: task_plan.plan,
                     session_id,
                     launch_time,
-                    props: vec![],
+                    props,
+                    plan_format: task_plan.format.into(),
+                    inputs: task_plan.inputs,
+                    plan_fingerprint: task_plan.plan_fingerprint,
+                    plan_hash: task_plan.plan_hash,
+                    plan_compression: task_plan.plan_compression,
+                    fetch_token: job_info.fetch_token.clone(),
                 });
 
                 Ok(multi_tasks)
@@ -623,6 +1126,271 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
+    /// Prepare the tasks executing a second time the successful tasks of verified stages,
+    /// under the verification job id of their job
+    pub(crate) async fn prepare_verification_tasks(
+        &self,
+        task_status: &[TaskStatus],
+    ) -> Vec<(TaskStatus, MultiTaskDefinition)> {
+        let mut verification_tasks = vec![];
+        for status in task_status {
+            if !matches!(status.status, Some(task_status::Status::Successful(_))) {
+                continue;
+            }
+            let Some(graph) = self.get_active_execution_graph(&status.job_id) else {
+                continue;
+            };
+            let graph = graph.read().await;
+            let Some(ExecutionStage::Running(stage)) =
+                graph.stages().get(&(status.stage_id as usize))
+            else {
+                continue;
+            };
+            if stage.stage_attempt_num != status.stage_attempt_num as usize
+                || !stage.session_config.ballista_verify_stage(stage.stage_id)
+            {
+                continue;
+            }
+
+            let plan = match self.encode_stage_plan(stage.plan.clone()) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    warn!(
+                        "Fail to encode plan of verified stage {}/{}: {:?}",
+                        status.job_id, status.stage_id, e
+                    );
+                    continue;
+                }
+            };
+            verification_tasks.push((
+                status.clone(),
+                MultiTaskDefinition {
+                    task_ids: vec![TaskId {
+                        task_id: status.task_id,
+                        task_attempt_num: 0,
+                        partition_id: status.partition_id,
+                        // the seed of the verified task, for random() to draw the
+                        // same values
+                        random_seed: task_random_seed(
+                            &status.job_id,
+                            status.stage_id as usize,
+                            status.partition_id as usize,
+                        ),
+                    }],
+                    job_id: verification_job_id(&status.job_id),
+                    stage_id: status.stage_id,
+                    stage_attempt_num: status.stage_attempt_num,
+                    plan,
+                    session_id: graph.session_id().to_owned(),
+                    launch_time: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_millis() as u64,
+                    props: stage.session_config.to_key_value_pairs(),
+                    plan_format: TaskPlanFormat::PhysicalPlan.into(),
+                    inputs: vec![],
+                    plan_fingerprint: 0,
+                    plan_hash: String::new(),
+                    plan_compression: String::new(),
+                    fetch_token: graph.fetch_token().as_str().to_owned(),
+                },
+            ));
+        }
+
+        verification_tasks
+    }
+
+    /// Launch verification tasks on the specified executor
+    pub(crate) async fn launch_verification_tasks(
+        &self,
+        executor: &ExecutorMetadata,
+        tasks: Vec<MultiTaskDefinition>,
+        executor_manager: &ExecutorManager,
+    ) -> Result<()> {
+        self.launcher
+            .launch_tasks(executor, tasks, executor_manager)
+            .await
+    }
+
+    /// Get the plan dispatched with the tasks of a stage: the logical plan of the job along
+    /// with the partitions the stage reads, if the job is dispatched as a logical plan, or
+    /// else the physical plan of the stage
+    fn task_plan(
+        &self,
+        job_info: &mut JobInfoCache,
+        task: &TaskDescription,
+    ) -> Result<TaskPlan> {
+        let (format, encoded_plan, inputs, plan_fingerprint) =
+            if let Some(plan) = job_info.encoded_logical_plan.clone() {
+                (
+                    TaskPlanFormat::LogicalPlan,
+                    plan,
+                    stage_inputs(&task.plan)?,
+                    stage_plan_fingerprint(&task.plan)?,
+                )
+            } else {
+                let plan = self.stage_plan(job_info, task)?;
+                (TaskPlanFormat::PhysicalPlan, plan, vec![], 0)
+            };
+
+        let (plan, plan_hash) = match encoded_plan.hash {
+            Some(hash) => (Bytes::new(), hash),
+            None => (encoded_plan.plan, String::new()),
+        };
+        let plan_compression = self
+            .plan_compression
+            .as_ref()
+            .map(|codec| codec.name().to_owned())
+            .unwrap_or_default();
+        Ok(TaskPlan {
+            format,
+            plan,
+            plan_hash,
+            plan_compression,
+            inputs,
+            plan_fingerprint,
+        })
+    }
+
+    /// Get the encoded plan of the stage of a task, encoding it if it is not cached yet
+    fn stage_plan(
+        &self,
+        job_info: &mut JobInfoCache,
+        task: &TaskDescription,
+    ) -> Result<EncodedPlan> {
+        let key = (task.partition.stage_id, task.stage_attempt_num);
+        if let Some(plan) = job_info.encoded_stage_plans.get(&key) {
+            return Ok(plan.clone());
+        }
+
+        let plan = self.dispatched_plan(self.encode_stage_plan(task.plan.clone())?)?;
+        job_info.encoded_stage_plans.insert(key, plan.clone());
+        Ok(plan)
+    }
+
+    /// Compress an encoded plan with the plan compression codec, if any, and hash it if
+    /// it's large enough to be dispatched by reference
+    fn dispatched_plan(&self, plan: Bytes) -> Result<EncodedPlan> {
+        let plan = match &self.plan_compression {
+            Some(codec) => codec.compress(&plan)?.into(),
+            None => plan,
+        };
+        let hash = (self.plan_reference_threshold > 0
+            && plan.len() >= self.plan_reference_threshold)
+            .then(|| {
+                let mut hasher = DefaultHasher::new();
+                plan.hash(&mut hasher);
+                format!("{:016x}", hasher.finish())
+            });
+        Ok(EncodedPlan { plan, hash })
+    }
+
+    /// Get a plan dispatched by reference with the tasks of an active job
+    pub fn get_task_plan(&self, job_id: &str, plan_hash: &str) -> Option<Bytes> {
+        let job_info = self.active_job_cache.get(job_id)?;
+        job_info
+            .encoded_logical_plan
+            .iter()
+            .chain(job_info.encoded_stage_plans.values())
+            .find(|plan| plan.hash.as_deref() == Some(plan_hash))
+            .map(|plan| plan.plan.clone())
+    }
+
+    /// Encode the plans of the running stages of a job which are not encoded yet, and drop
+    /// the plans of stages which are not running anymore. This is done as soon as stages
+    /// start running, so that launching their first tasks does not pay for the encoding.
+    fn encode_running_stage_plans(
+        &self,
+        graph: &ExecutionGraph,
+        encoded_stage_plans: &mut HashMap<(usize, usize), EncodedPlan>,
+    ) {
+        let stages = graph.stages();
+        encoded_stage_plans.retain(|(stage_id, stage_attempt_num), _| {
+            matches!(
+                stages.get(stage_id),
+                Some(ExecutionStage::Running(stage)) if stage.stage_attempt_num == *stage_attempt_num
+            )
+        });
+
+        for stage in stages.values() {
+            if let ExecutionStage::Running(stage) = stage {
+                let key = (stage.stage_id, stage.stage_attempt_num);
+                if encoded_stage_plans.contains_key(&key) {
+                    continue;
+                }
+                match self
+                    .encode_stage_plan(stage.plan.clone())
+                    .and_then(|plan| self.dispatched_plan(plan))
+                {
+                    Ok(plan) => {
+                        encoded_stage_plans.insert(key, plan);
+                    }
+                    // Encoding is retried, and the error reported, when the tasks are launched
+                    Err(e) => warn!(
+                        "Fail to encode plan of stage {}/{}: {:?}",
+                        graph.job_id(),
+                        stage.stage_id,
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Encode the plan of a stage
+    fn encode_stage_plan(&self, plan: Arc<dyn ExecutionPlan>) -> Result<Bytes> {
+        // DataFusion doesn't serialize the compression of CSV and JSON scans
+        let plan = with_scan_options(plan)?;
+        let plan_proto =
+            U::try_from_physical_plan(plan, self.codec.physical_extension_codec())?;
+
+        let mut buf = vec![];
+        plan_proto.try_encode(&mut buf)?;
+        Ok(buf.into())
+    }
+
+    /// Encode the stages of a job with the plans they last ran, so that clients can
+    /// decode them with their codec
+    pub(crate) fn encode_job_plan(
+        &self,
+        graph: &ExecutionGraph,
+    ) -> Result<Vec<JobPlanStage>> {
+        let stages = graph.stages();
+        let mut stage_ids = stages.keys().copied().collect::<Vec<_>>();
+        stage_ids.sort_unstable();
+
+        stage_ids
+            .into_iter()
+            .map(|stage_id| {
+                let stage = &stages[&stage_id];
+                let plan = match stage {
+                    ExecutionStage::UnResolved(stage) => stage.plan.clone(),
+                    ExecutionStage::Resolved(stage) => stage.plan.clone(),
+                    ExecutionStage::Running(stage) => stage.plan.clone(),
+                    ExecutionStage::Successful(stage) => stage.plan.clone(),
+                    ExecutionStage::Failed(stage) => stage.plan.clone(),
+                };
+                let input_stages = stages
+                    .iter()
+                    .filter(|(_, input)| input.output_links().contains(&stage_id))
+                    .map(|(input_stage_id, _)| *input_stage_id as u32)
+                    .collect::<BTreeSet<_>>();
+                Ok(JobPlanStage {
+                    stage_id: stage_id as u32,
+                    stage_attempt_num: stage.stage_attempt_num() as u32,
+                    partitions: stage.partitions().unwrap_or_default() as u32,
+                    input_stages: input_stages.into_iter().collect(),
+                    output_links: stage
+                        .output_links()
+                        .iter()
+                        .map(|stage_id| *stage_id as u32)
+                        .collect(),
+                    plan: self.encode_stage_plan(plan)?.to_vec(),
+                })
+            })
+            .collect()
+    }
+
     /// Get the `ExecutionGraph` for the given job ID from cache
     pub(crate) fn get_active_execution_graph(
         &self,
@@ -639,6 +1407,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         &self,
         job_id: &str,
     ) -> Option<Arc<RwLock<ExecutionGraph>>> {
+        self.job_drivers.remove(job_id);
         self.active_job_cache
             .remove(job_id)
             .map(|value| value.1.execution_graph)
@@ -661,16 +1430,65 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             return;
         }
 
-        let state = self.state.clone();
+        let task_manager = self.clone();
         tokio::spawn(async move {
             tokio::time::sleep(Duration::from_secs(clean_up_interval)).await;
-            if let Err(err) = state.remove_job(&job_id).await {
+            if let Err(err) = task_manager.purge_job(&job_id).await {
                 error!("Failed to delete job {job_id}: {err:?}");
             }
         });
     }
 }
 
+/// The ids of the successful stages of a job
+fn successful_stages(graph: &ExecutionGraph) -> HashSet<usize> {
+    graph
+        .stages()
+        .iter()
+        .filter(|(_, stage)| matches!(stage, ExecutionStage::Successful(_)))
+        .map(|(stage_id, _)| *stage_id)
+        .collect()
+}
+
+/// The partitions read by the shuffle readers of a resolved stage plan, and the runtime
+/// predicates they filter them with, by input stage
+fn stage_inputs(plan: &Arc<dyn ExecutionPlan>) -> Result<Vec<StageInput>> {
+    if let Some(reader) = plan.as_any().downcast_ref::<ShuffleReaderExec>() {
+        let partition = reader
+            .partition
+            .iter()
+            .map(|locations| {
+                Ok(ShuffleReaderPartition {
+                    location: locations
+                        .iter()
+                        .map(|location| location.clone().try_into())
+                        .collect::<Result<_>>()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let runtime_predicates = reader
+            .runtime_predicates
+            .iter()
+            .map(|predicate| {
+                serialize_physical_expr(predicate, &DefaultPhysicalExtensionCodec {})
+            })
+            .collect::<datafusion::error::Result<_>>()?;
+        return Ok(vec![StageInput {
+            stage_id: reader.stage_id as u32,
+            partition,
+            runtime_predicates,
+        }]);
+    }
+    Ok(plan
+        .children()
+        .into_iter()
+        .map(stage_inputs)
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
 pub struct JobOverview {
     pub job_id: String,
     pub job_name: String,
@@ -681,6 +1499,29 @@ pub struct JobOverview {
     pub completed_stages: usize,
 }
 
+/// The overview of a job known by its status only, without the progress of its stages
+impl From<JobStatus> for JobOverview {
+    fn from(status: JobStatus) -> Self {
+        let (start_time, end_time) = match &status.status {
+            Some(job_status::Status::Queued(job)) => (job.queued_at, job.queued_at),
+            Some(job_status::Status::Running(job)) => (job.started_at, job.started_at),
+            Some(job_status::Status::Failed(job)) => (job.started_at, job.ended_at),
+            Some(job_status::Status::Successful(job)) => (job.started_at, job.ended_at),
+            None => (0, 0),
+        };
+
+        Self {
+            job_id: status.job_id.clone(),
+            job_name: status.job_name.clone(),
+            status,
+            start_time,
+            end_time,
+            num_stages: 0,
+            completed_stages: 0,
+        }
+    }
+}
+
 impl From<&ExecutionGraph> for JobOverview {
     fn from(value: &ExecutionGraph) -> Self {
         let mut completed_stages = 0;
@@ -0,0 +1,447 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Webhooks notifying external systems, e.g. orchestrators, of the jobs which finished,
+//! failed or were cancelled, so that they don't have to poll their status.
+//!
+//! The notifications are JSON payloads posted to the webhooks of the scheduler, and to
+//! the webhook of the job if it set `ballista.job.webhook_url`. With a secret, their
+//! body is signed with HMAC-SHA256, over the timestamp of the notification and the body.
+
+use std::fmt;
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, ErrorCode, Result};
+use ballista_core::extension::SessionConfigExt;
+use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::protobuf::JobStatus;
+use dashmap::DashMap;
+use datafusion::prelude::SessionConfig;
+use ring::hmac;
+use serde_json::{json, Value};
+use url::Url;
+
+/// Header of the timestamp of a notification, in milliseconds since the epoch
+pub const TIMESTAMP_HEADER: &str = "x-ballista-timestamp";
+/// Header of the signature of a notification, `sha256=` followed by the hex-encoded
+/// HMAC-SHA256 of `{timestamp}.{body}`
+pub const SIGNATURE_HEADER: &str = "x-ballista-signature";
+
+/// The webhooks of the scheduler
+#[derive(Clone, Default)]
+pub struct WebhookConfig {
+    /// URLs notified of all the jobs
+    pub urls: Vec<String>,
+    /// Prefixes of the webhook URLs which jobs can set, see [is_allowed_job_url]. Jobs
+    /// can't set webhooks without any
+    pub allowed_job_url_prefixes: Vec<String>,
+    /// Secret key signing the notifications
+    pub secret: Option<String>,
+}
+
+impl WebhookConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.urls.is_empty() || !self.allowed_job_url_prefixes.is_empty()
+    }
+
+    /// Check that the prefixes of the webhook URLs which jobs can set are URLs
+    pub fn validate(&self) -> Result<()> {
+        for prefix in &self.allowed_job_url_prefixes {
+            let url = Url::parse(prefix).map_err(|e| {
+                BallistaError::General(format!(
+                    "Invalid webhook job URL prefix {prefix}: {e}"
+                ))
+            })?;
+            if url.host_str().is_none() {
+                return Err(BallistaError::General(format!(
+                    "Webhook job URL prefix {prefix} has no host"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for WebhookConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookConfig")
+            .field("urls", &self.urls)
+            .field("allowed_job_url_prefixes", &self.allowed_job_url_prefixes)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// Notifies the webhooks of the jobs submitted by clients once they complete
+#[derive(Clone)]
+pub struct JobWebhooks {
+    config: Arc<WebhookConfig>,
+    /// The jobs to notify, with the URL of their own webhook if set
+    jobs: Arc<DashMap<String, Option<String>>>,
+    #[cfg(feature = "webhooks")]
+    client: reqwest::Client,
+}
+
+impl JobWebhooks {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            jobs: Arc::new(DashMap::new()),
+            #[cfg(feature = "webhooks")]
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Watch the job `job_id`, submitted with the session config `config`, to notify its
+    /// webhooks once it completes. Fails if the job set a webhook URL which isn't allowed
+    pub fn watch(&self, job_id: &str, config: &SessionConfig) -> Result<()> {
+        let job_url = config.ballista_job_webhook_url();
+        if let Some(url) = &job_url {
+            if !is_allowed_job_url(url, &self.config.allowed_job_url_prefixes) {
+                return Err(BallistaError::General(format!(
+                    "Webhook URL {url} of job {job_id} is not allowed by the scheduler"
+                )));
+            }
+        }
+        if self.config.urls.is_empty() && job_url.is_none() {
+            return Ok(());
+        }
+
+        self.jobs.insert(job_id.to_owned(), job_url);
+        Ok(())
+    }
+
+    /// Stop watching a job, e.g. which failed to be submitted
+    pub fn forget(&self, job_id: &str) {
+        self.jobs.remove(job_id);
+    }
+
+    /// Whether the job is watched
+    pub fn is_watched(&self, job_id: &str) -> bool {
+        self.jobs.contains_key(job_id)
+    }
+
+    /// Notify the webhooks of a watched job of its final status, in the background.
+    /// Jobs which aren't complete yet are ignored
+    pub fn notify(&self, status: &JobStatus) {
+        let Some(payload) = payload(status) else {
+            return;
+        };
+        let Some((_, job_url)) = self.jobs.remove(&status.job_id) else {
+            return;
+        };
+        let urls = self
+            .config
+            .urls
+            .iter()
+            .cloned()
+            .chain(job_url)
+            .collect::<Vec<_>>();
+        self.post(urls, payload.to_string());
+    }
+
+    #[cfg(feature = "webhooks")]
+    fn post(&self, urls: Vec<String>, body: String) {
+        let client = self.client.clone();
+        let secret = self.config.secret.clone();
+        tokio::spawn(async move {
+            for url in urls {
+                if let Err(e) = post(&client, &url, &body, secret.as_deref()).await {
+                    log::warn!("Failed to notify webhook {url}: {e}");
+                }
+            }
+        });
+    }
+
+    #[cfg(not(feature = "webhooks"))]
+    fn post(&self, urls: Vec<String>, _body: String) {
+        log::warn!(
+            "Not notifying webhooks {urls:?}, the scheduler was built without the webhooks feature"
+        );
+    }
+}
+
+/// Post a notification, retrying failed attempts
+#[cfg(feature = "webhooks")]
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    body: &str,
+    secret: Option<&str>,
+) -> Result<()> {
+    use std::time::Duration;
+
+    const ATTEMPTS: u32 = 3;
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    let mut attempt = 1;
+    loop {
+        let timestamp = crate::scheduler_server::timestamp_millis().to_string();
+        let mut request = client
+            .post(url)
+            .timeout(TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, &timestamp)
+            .body(body.to_owned());
+        if let Some(secret) = secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &timestamp, body));
+        }
+        match request.send().await.and_then(|r| r.error_for_status()) {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt >= ATTEMPTS => {
+                return Err(BallistaError::General(e.to_string()));
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Whether jobs can set the webhook `url`, i.e. whether it has the scheme, host and port
+/// of one of the `prefixes`, and a path under the path of the prefix, if any. URLs are
+/// compared once parsed rather than as strings, so that e.g. the prefix
+/// `https://hooks.example.org` doesn't allow `https://hooks.example.org.attacker.com/`
+pub fn is_allowed_job_url(url: &str, prefixes: &[String]) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    prefixes.iter().any(|prefix| {
+        let Ok(prefix) = Url::parse(prefix) else {
+            return false;
+        };
+        let (path, prefix_path) = (url.path(), prefix.path());
+        url.scheme() == prefix.scheme()
+            && url.host().is_some()
+            && url.host() == prefix.host()
+            && url.port_or_known_default() == prefix.port_or_known_default()
+            && (path == prefix_path
+                || prefix_path.ends_with('/') && path.starts_with(prefix_path)
+                || path
+                    .strip_prefix(prefix_path)
+                    .is_some_and(|rest| rest.starts_with('/')))
+    })
+}
+
+/// The notification of a job, if it is complete
+pub(crate) fn payload(status: &JobStatus) -> Option<Value> {
+    let mut payload = json!({
+        "job_id": status.job_id,
+        "job_name": status.job_name,
+    });
+    match status.status.as_ref()? {
+        Status::Successful(job) => {
+            payload["event"] = json!("job_finished");
+            payload["queued_at"] = json!(job.queued_at);
+            payload["started_at"] = json!(job.started_at);
+            payload["ended_at"] = json!(job.ended_at);
+        }
+        Status::Failed(job) => {
+            let event = if job.error_code == ErrorCode::Cancelled.to_string() {
+                "job_cancelled"
+            } else {
+                "job_failed"
+            };
+            payload["event"] = json!(event);
+            payload["queued_at"] = json!(job.queued_at);
+            payload["started_at"] = json!(job.started_at);
+            payload["ended_at"] = json!(job.ended_at);
+            payload["error"] = json!(job.error);
+            payload["error_code"] = json!(job.error_code);
+        }
+        Status::Queued(_) | Status::Running(_) => return None,
+    }
+    Some(payload)
+}
+
+/// The signature of a notification sent at `timestamp` with `body`
+pub fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, format!("{timestamp}.{body}").as_bytes());
+    let hex = tag
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!("sha256={hex}")
+}
+
+#[cfg(test)]
+mod tests {
+    use ballista_core::serde::protobuf::{FailedJob, RunningJob, SuccessfulJob};
+
+    use super::*;
+
+    fn status(status: Status) -> JobStatus {
+        JobStatus {
+            job_id: "job".to_owned(),
+            job_name: "name".to_owned(),
+            status: Some(status),
+        }
+    }
+
+    fn failed(error_code: ErrorCode) -> JobStatus {
+        status(Status::Failed(FailedJob {
+            error: "error".to_owned(),
+            queued_at: 1,
+            started_at: 2,
+            ended_at: 3,
+            error_code: error_code.to_string(),
+        }))
+    }
+
+    #[test]
+    fn payload_of_complete_jobs() {
+        let finished = status(Status::Successful(SuccessfulJob {
+            queued_at: 1,
+            started_at: 2,
+            ended_at: 3,
+            ..Default::default()
+        }));
+        assert_eq!(
+            payload(&finished),
+            Some(json!({
+                "event": "job_finished",
+                "job_id": "job",
+                "job_name": "name",
+                "queued_at": 1,
+                "started_at": 2,
+                "ended_at": 3,
+            }))
+        );
+
+        let payload_of_failed = payload(&failed(ErrorCode::ExecutionFailed)).unwrap();
+        assert_eq!(payload_of_failed["event"], "job_failed");
+        assert_eq!(payload_of_failed["error"], "error");
+        assert_eq!(
+            payload_of_failed["error_code"],
+            ErrorCode::ExecutionFailed.to_string()
+        );
+        assert_eq!(
+            payload(&failed(ErrorCode::Cancelled)).unwrap()["event"],
+            "job_cancelled"
+        );
+
+        let running = status(Status::Running(RunningJob::default()));
+        assert_eq!(payload(&running), None);
+    }
+
+    #[test]
+    fn signature() {
+        // echo -n '1700000000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", "1700000000000", "{}"),
+            "sha256=8399216d111287e3bb28e25c0f4f31dffdf831c68c9ee2b96c2f67c9b81d341b"
+        );
+    }
+
+    #[tokio::test]
+    async fn job_urls_must_be_allowed() -> Result<()> {
+        let webhooks = JobWebhooks::new(WebhookConfig {
+            allowed_job_url_prefixes: vec!["http://127.0.0.1:9/".to_owned()],
+            ..Default::default()
+        });
+
+        // jobs without a webhook aren't watched without webhooks of the scheduler
+        webhooks.watch("job", &SessionConfig::new_with_ballista())?;
+        assert!(!webhooks.is_watched("job"));
+
+        let config = SessionConfig::new_with_ballista()
+            .with_ballista_job_webhook_url("http://127.0.0.1:9/jobs");
+        webhooks.watch("job", &config)?;
+        assert!(webhooks.is_watched("job"));
+
+        let config = SessionConfig::new_with_ballista()
+            .with_ballista_job_webhook_url("http://169.254.169.254/");
+        assert!(webhooks.watch("other", &config).is_err());
+        assert!(!webhooks.is_watched("other"));
+
+        // complete jobs are forgotten once notified
+        webhooks.notify(&failed(ErrorCode::Cancelled));
+        assert!(!webhooks.is_watched("job"));
+        Ok(())
+    }
+
+    #[test]
+    fn allowed_job_urls() {
+        let prefixes = vec![
+            "https://hooks.example.org".to_owned(),
+            "http://127.0.0.1:9/jobs".to_owned(),
+        ];
+        let allowed = |url| is_allowed_job_url(url, &prefixes);
+
+        assert!(allowed("https://hooks.example.org/"));
+        assert!(allowed("https://HOOKS.example.org:443/ballista?job=1"));
+        assert!(allowed("http://127.0.0.1:9/jobs"));
+        assert!(allowed("http://127.0.0.1:9/jobs/1"));
+
+        // lookalike hosts
+        assert!(!allowed("https://hooks.example.org.attacker.com/"));
+        assert!(!allowed("https://hooks.example.org@attacker.com/"));
+        assert!(!allowed("https://attacker.com/https://hooks.example.org/"));
+        // other schemes, ports and paths
+        assert!(!allowed("http://hooks.example.org/"));
+        assert!(!allowed("https://hooks.example.org:8443/"));
+        assert!(!allowed("http://127.0.0.1:90/jobs"));
+        assert!(!allowed("http://127.0.0.1:9/jobsearch"));
+        assert!(!allowed("http://127.0.0.1:9/"));
+        assert!(!allowed("not a url"));
+
+        let config = WebhookConfig {
+            allowed_job_url_prefixes: prefixes.clone(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        let config = WebhookConfig {
+            allowed_job_url_prefixes: vec!["hooks.example.org/".to_owned()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[cfg(feature = "webhooks")]
+    #[tokio::test]
+    async fn post_signed_notification() -> Result<()> {
+        use axum::http::HeaderMap;
+        use axum::routing::post as route_post;
+        use tokio::sync::mpsc;
+
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let app = axum::Router::new().route(
+            "/hook",
+            route_post(move |headers: HeaderMap, body: String| async move {
+                sender.send((headers, body)).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let url = format!("http://{addr}/hook");
+        post(&reqwest::Client::new(), &url, "{}", Some("secret")).await?;
+
+        let (headers, body) = received.recv().await.unwrap();
+        assert_eq!(body, "{}");
+        let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            sign("secret", timestamp, "{}")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Conversion of the Substrait plans submitted by clients into logical plans, so
+//! that frontends other than DataFusion can submit jobs.
+
+use datafusion::error::Result;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::prelude::SessionContext;
+
+/// The logical plan of the encoded Substrait `plan`, whose tables are resolved in
+/// the catalogs of `ctx`
+#[cfg(feature = "substrait")]
+pub async fn substrait_to_logical_plan(
+    ctx: &SessionContext,
+    plan: &[u8],
+) -> Result<LogicalPlan> {
+    use datafusion_substrait::logical_plan::consumer::from_substrait_plan;
+    use datafusion_substrait::serializer::deserialize_bytes;
+
+    let plan = deserialize_bytes(plan.to_vec()).await?;
+    from_substrait_plan(ctx, &plan).await
+}
+
+/// Substrait plans are rejected by schedulers built without the substrait feature
+#[cfg(not(feature = "substrait"))]
+pub async fn substrait_to_logical_plan(
+    _ctx: &SessionContext,
+    _plan: &[u8],
+) -> Result<LogicalPlan> {
+    Err(datafusion::error::DataFusionError::NotImplemented(
+        "Substrait plans require the scheduler to be built with the substrait feature"
+            .to_owned(),
+    ))
+}
+
+#[cfg(all(test, feature = "substrait"))]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::empty::EmptyTable;
+    use datafusion_substrait::serializer::serialize_bytes;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn convert_substrait_plan() -> Result<()> {
+        let ctx = SessionContext::new();
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]);
+        ctx.register_table("t", Arc::new(EmptyTable::new(Arc::new(schema))))?;
+
+        let sql = "SELECT name FROM t WHERE id > 1";
+        let bytes = serialize_bytes(sql, &ctx).await?;
+        let plan = substrait_to_logical_plan(&ctx, &bytes).await?;
+
+        let columns: Vec<_> = plan
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect();
+        assert_eq!(vec!["name"], columns);
+        Ok(())
+    }
+}
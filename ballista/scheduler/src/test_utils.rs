@@ -15,6 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use ballista_core::build_version;
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::extension::SessionConfigExt;
 use datafusion::catalog::Session;
@@ -25,17 +26,22 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
+use bytes::Bytes;
 
 use crate::config::SchedulerConfig;
+use crate::metrics::cluster::ClusterMetricsSample;
 use crate::metrics::SchedulerMetricsCollector;
 use crate::scheduler_server::{timestamp_millis, SchedulerServer};
+use crate::state::shadow_execution::ShadowComparison;
 
 use crate::state::executor_manager::ExecutorManager;
 use crate::state::task_manager::TaskLauncher;
 
 use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpc;
 use ballista_core::serde::protobuf::{
-    task_status, FailedTask, JobStatus, MultiTaskDefinition, ShuffleWritePartition,
+    task_status, FailedTask, GetJobMetricsParams, GetJobMetricsResult, GetJobPlanParams,
+    GetJobPlanResult, JobStatus, MultiTaskDefinition, ShuffleWritePartition,
     SuccessfulTask, TaskId, TaskStatus,
 };
 use ballista_core::serde::scheduler::{
@@ -282,6 +288,10 @@ pub fn default_task_runner() -> impl TaskRunner {
                 num_batches: 1,
                 num_rows: 1,
                 num_bytes: 1,
+                inline_data: vec![],
+                checksum: None,
+                compression: Default::default(),
+                ipc_metadata_version: 0,
             })
             .collect();
 
@@ -440,6 +450,10 @@ impl SchedulerTest {
                 specification: ExecutorSpecification {
                     task_slots: task_slots as u32,
                 },
+                version: build_version(),
+                functions: None,
+                shuffle_formats: None,
+                pool: String::new(),
             };
 
             let executor_data = ExecutorData {
@@ -536,6 +550,37 @@ impl SchedulerTest {
             .await
     }
 
+    pub fn get_task_plan(&self, job_id: &str, plan_hash: &str) -> Option<Bytes> {
+        self.scheduler
+            .state
+            .task_manager
+            .get_task_plan(job_id, plan_hash)
+    }
+
+    pub async fn job_plan(
+        &self,
+        job_id: &str,
+    ) -> std::result::Result<GetJobPlanResult, tonic::Status> {
+        let request = tonic::Request::new(GetJobPlanParams {
+            job_id: job_id.to_owned(),
+        });
+        Ok(self.scheduler.get_job_plan(request).await?.into_inner())
+    }
+
+    pub async fn job_metrics(
+        &self,
+        job_id: &str,
+    ) -> std::result::Result<GetJobMetricsResult, tonic::Status> {
+        let request = tonic::Request::new(GetJobMetricsParams {
+            job_id: job_id.to_owned(),
+        });
+        Ok(self.scheduler.get_job_metrics(request).await?.into_inner())
+    }
+
+    pub async fn purge_jobs(&self, job_ids: Vec<String>) -> Result<Vec<String>> {
+        self.scheduler.state.purge_jobs(job_ids).await
+    }
+
     pub async fn await_completion_timeout(
         &self,
         job_id: &str,
@@ -731,8 +776,18 @@ impl SchedulerMetricsCollector for TestMetricsCollector {
         guard.push(MetricEvent::Cancelled(job_id.to_owned()));
     }
 
+    fn record_plan_cache_hit(&self) {}
+
+    fn record_plan_cache_miss(&self) {}
+
+    fn record_shadow_execution(&self, _comparison: &ShadowComparison) {}
+
     fn set_pending_tasks_queue_size(&self, _value: u64) {}
 
+    fn set_retained_result_bytes(&self, _value: u64) {}
+
+    fn record_cluster_metrics(&self, _sample: &ClusterMetricsSample) {}
+
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         Ok(None)
     }
@@ -831,6 +886,20 @@ pub async fn test_aggregation_plan(partition: usize) -> ExecutionGraph {
 pub async fn test_aggregation_plan_with_job_id(
     partition: usize,
     job_id: &str,
+) -> ExecutionGraph {
+    test_aggregation_plan_with_session_config(
+        partition,
+        job_id,
+        SessionConfig::new_with_ballista(),
+    )
+    .await
+}
+
+/// The graph of an aggregation job submitted with the given session config
+pub async fn test_aggregation_plan_with_session_config(
+    partition: usize,
+    job_id: &str,
+    session_config: SessionConfig,
 ) -> ExecutionGraph {
     let config = SessionConfig::new().with_target_partitions(partition);
     let ctx = Arc::new(SessionContext::new_with_config(config));
@@ -868,7 +937,7 @@ pub async fn test_aggregation_plan_with_job_id(
         "session",
         plan,
         0,
-        Arc::new(SessionConfig::new_with_ballista()),
+        Arc::new(session_config),
     )
     .unwrap()
 }
@@ -1104,6 +1173,10 @@ pub fn mock_executor(executor_id: String) -> ExecutorMetadata {
         port: 8080,
         grpc_port: 9090,
         specification: ExecutorSpecification { task_slots: 1 },
+        version: build_version(),
+        functions: None,
+        shuffle_formats: None,
+        pool: String::new(),
     }
 }
 
@@ -1124,6 +1197,10 @@ pub fn mock_completed_task(task: TaskDescription, executor_id: &str) -> TaskStat
             num_batches: 1,
             num_rows: 1,
             num_bytes: 1,
+            inline_data: vec![],
+            checksum: None,
+            compression: Default::default(),
+            ipc_metadata_version: 0,
         })
     }
 
@@ -1162,6 +1239,10 @@ pub fn mock_failed_task(task: TaskDescription, failed_task: FailedTask) -> TaskS
             num_batches: 1,
             num_rows: 1,
             num_bytes: 1,
+            inline_data: vec![],
+            checksum: None,
+            compression: Default::default(),
+            ipc_metadata_version: 0,
         })
     }
 
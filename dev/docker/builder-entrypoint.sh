@@ -22,4 +22,8 @@ set -x
 
 printenv
 RELEASE_FLAG=${RELEASE_FLAG:=release}
-cargo build --features flight-sql --profile $RELEASE_FLAG "$@"
+# Build the scheduler and executor on their own so that Cargo doesn't unify the features
+# of their dependencies with the rest of the workspace, keeping their images minimal
+cargo build -p ballista-scheduler --features flight-sql --profile $RELEASE_FLAG "$@"
+cargo build -p ballista-executor --profile $RELEASE_FLAG "$@"
+cargo build --workspace --exclude ballista-scheduler --exclude ballista-executor --profile $RELEASE_FLAG "$@"
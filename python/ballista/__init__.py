@@ -27,10 +27,12 @@ import pyarrow as pa
 
 from .ballista_internal import (
     BallistaBuilder,
+    BallistaSessionContext,
 )
 
 __version__ = importlib_metadata.version(__name__)
 
 __all__ = [
     "BallistaBuilder",
+    "BallistaSessionContext",
 ]
\ No newline at end of file
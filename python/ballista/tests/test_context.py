@@ -15,7 +15,7 @@
 # specific language governing permissions and limitations
 # under the License.
 
-from ballista import BallistaBuilder
+from ballista import BallistaBuilder, BallistaSessionContext
 import pytest
 
 def test_create_context():
@@ -76,3 +76,17 @@ def test_execute_plan():
     batches = df.collect()
     assert len(batches) == 1
     assert len(batches[0]) == 1
+
+def test_session_context():
+    ctx = BallistaSessionContext(config={"ballista.job.name": "test"})
+    ctx.register_csv("test", "testdata/test.csv", has_header=True)
+    df = ctx.sql("SELECT a, b FROM test")
+    table = df.to_arrow_table()
+    assert table.num_rows == 1
+    assert table.column_names == ["a", "b"]
+
+def test_session_context_dataframe_api():
+    ctx = BallistaSessionContext()
+    df = ctx.read_parquet("testdata/test.parquet").limit(2)
+    batches = df.collect()
+    assert sum(len(batch) for batch in batches) == 2
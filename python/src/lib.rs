@@ -32,6 +32,8 @@ fn ballista_internal(_py: Python, m: Bound<'_, PyModule>) -> PyResult<()> {
     pyo3_log::init();
     // BallistaBuilder struct
     m.add_class::<PyBallistaBuilder>()?;
+    // BallistaSessionContext struct
+    m.add_class::<PyBallistaSessionContext>()?;
     // DataFusion struct
     m.add_class::<datafusion_python::dataframe::PyDataFrame>()?;
     Ok(())
@@ -101,3 +103,35 @@ impl PyBallistaBuilder {
         Ok(ctx.into())
     }
 }
+
+/// A DataFusion SessionContext whose queries run on a Ballista cluster, connected to
+/// the scheduler at `address`, e.g. `df://localhost:50050`, or on an in-process
+/// standalone cluster when no address is given. The DataFrames it creates are
+/// collected from the executors over Arrow Flight.
+#[pyclass(
+    name = "BallistaSessionContext",
+    module = "ballista",
+    extends = PySessionContext,
+    subclass
+)]
+pub struct PyBallistaSessionContext {}
+
+#[pymethods]
+impl PyBallistaSessionContext {
+    #[new]
+    #[pyo3(signature = (address = None, config = None))]
+    pub fn new(
+        address: Option<&str>,
+        config: Option<HashMap<String, String>>,
+        py: Python,
+    ) -> PyResult<(Self, PySessionContext)> {
+        let builder = PyBallistaBuilder {
+            conf: config.unwrap_or_default(),
+        };
+        let ctx = match address {
+            Some(address) => builder.remote(address, py)?,
+            None => builder.standalone(py)?,
+        };
+        Ok((Self {}, ctx))
+    }
+}